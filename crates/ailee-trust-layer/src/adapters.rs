@@ -2,8 +2,10 @@
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 use super::generation::TaskType;
+use super::pool::ConnectionPool;
 
 /// Locality of a model (local or remote)
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -25,6 +27,9 @@ pub struct ModelOutput {
     pub confidence: f64,
     /// Execution time in milliseconds
     pub execution_time_ms: u64,
+    /// Version or build hash the provider reported for the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_version: Option<String>,
 }
 
 impl ModelOutput {
@@ -40,8 +45,15 @@ impl ModelOutput {
             model_id: model_id.into(),
             confidence: confidence.clamp(0.0, 1.0),
             execution_time_ms,
+            model_version: None,
         }
     }
+
+    /// Attach the model version or build hash the provider reported
+    pub fn with_model_version(mut self, model_version: impl Into<String>) -> Self {
+        self.model_version = Some(model_version.into());
+        self
+    }
 }
 
 /// Trait for model adapters
@@ -113,11 +125,17 @@ impl ModelAdapter for LocalModelAdapter {
     }
 }
 
-/// Remote model adapter (stub implementation with connectivity awareness)
+/// Remote model adapter with connectivity awareness
+///
+/// Without a pool this is a stub that echoes the prompt.  Attached to a
+/// [`ConnectionPool`] with [`Self::with_pool`], generation goes over a
+/// pooled connection to its provider, shared with every other adapter of
+/// that provider.
 #[derive(Debug, Clone)]
 pub struct RemoteModelAdapter {
     model_id: String,
     is_online: bool,
+    pool: Option<(Arc<ConnectionPool>, String)>,
 }
 
 impl RemoteModelAdapter {
@@ -126,9 +144,16 @@ impl RemoteModelAdapter {
         Self {
             model_id: model_id.into(),
             is_online,
+            pool: None,
         }
     }
 
+    /// Generate over connections to `provider` from `pool`
+    pub fn with_pool(mut self, pool: Arc<ConnectionPool>, provider: impl Into<String>) -> Self {
+        self.pool = Some((pool, provider.into()));
+        self
+    }
+
     /// Update online status
     pub fn set_online(&mut self, is_online: bool) {
         self.is_online = is_online;
@@ -142,6 +167,11 @@ impl ModelAdapter for RemoteModelAdapter {
             anyhow::bail!("Remote model unavailable: offline");
         }
 
+        if let Some((pool, provider)) = &self.pool {
+            let connection = pool.checkout(provider).await?;
+            return connection.generate(&self.model_id, prompt, task_type).await;
+        }
+
         // Simulate remote processing (would normally call external API)
         let start = std::time::Instant::now();
 
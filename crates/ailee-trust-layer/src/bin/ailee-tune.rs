@@ -0,0 +1,34 @@
+//! Threshold tuning tool
+//!
+//! Usage: `ailee-tune <corpus.jsonl> [config.json]`
+//!
+//! Replays the corpus through [`ailee_trust_layer::tuning::simulate`] and
+//! prints the recommendation report as JSON. Config fields left out keep
+//! their defaults.
+
+use ailee_trust_layer::tuning::{simulate, TuningCase, TuningConfig};
+use anyhow::Context;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let Some(corpus_path) = args.next() else {
+        anyhow::bail!("usage: ailee-tune <corpus.jsonl> [config.json]");
+    };
+
+    let corpus = std::fs::read_to_string(&corpus_path)
+        .with_context(|| format!("reading corpus {}", corpus_path))?;
+    let cases = TuningCase::parse_corpus(&corpus)?;
+
+    let config = match args.next() {
+        Some(config_path) => {
+            let raw = std::fs::read_to_string(&config_path)
+                .with_context(|| format!("reading config {}", config_path))?;
+            serde_json::from_str(&raw).with_context(|| format!("parsing config {}", config_path))?
+        }
+        None => TuningConfig::default(),
+    };
+
+    let report = simulate(&cases, &config)?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
@@ -1,11 +1,23 @@
 //! Consensus engine for selecting final output from multiple models
 
 use futures::future::join_all;
+use futures::stream::{self, FuturesUnordered, StreamExt};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::task::JoinHandle;
 
 use super::adapters::{ModelAdapter, ModelOutput};
-use super::generation::{ExecutionMetadata, GenerationRequest, GenerationResult};
-use super::trust::{compute_trust_scores, TrustScores};
+use super::generation::{
+    BatchMetrics, BatchResult, ExecutionMetadata, GenerationRequest, GenerationResult,
+};
+use super::ledger::{LedgerObservation, TrustLedger};
+use super::redaction::Redactor;
+use super::speculation::{EarlyReturn, SpeculationConfig, SpeculationStats};
+use super::trust::{compute_trust_scores, ConsistencyScore, TrustScores};
+
+/// Adapter calls still running when a speculative round returned.
+type OutstandingCalls = FuturesUnordered<JoinHandle<anyhow::Result<ModelOutput>>>;
 
 /// Default per-adapter timeout: 30 seconds.
 ///
@@ -14,6 +26,9 @@ use super::trust::{compute_trust_scores, TrustScores};
 /// waiting indefinitely.
 const DEFAULT_ADAPTER_TIMEOUT_MS: u64 = 30_000;
 
+/// Default number of batch requests run at once.
+const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+
 /// Consensus engine for multi-model output selection
 #[derive(Debug, Clone)]
 pub struct ConsensusEngine {
@@ -21,6 +36,16 @@ pub struct ConsensusEngine {
     min_models: usize,
     /// Per-adapter call timeout in milliseconds.
     adapter_timeout_ms: u64,
+    /// Distinct requests of a batch run at once.
+    batch_concurrency: usize,
+    /// Scrubs results before they are returned for storage or logging.
+    redactor: Option<Arc<Redactor>>,
+    /// Return before every adapter answers once outputs agree.
+    speculation: Option<SpeculationConfig>,
+    /// Shared by clones so background completions land in one place.
+    speculation_stats: Arc<Mutex<SpeculationStats>>,
+    /// Per-version trust history; quarantined versions are left out.
+    trust_ledger: Option<Arc<TrustLedger>>,
 }
 
 impl ConsensusEngine {
@@ -29,9 +54,23 @@ impl ConsensusEngine {
         Self {
             min_models: min_models.max(1),
             adapter_timeout_ms: DEFAULT_ADAPTER_TIMEOUT_MS,
+            batch_concurrency: DEFAULT_BATCH_CONCURRENCY,
+            redactor: None,
+            speculation: None,
+            speculation_stats: Arc::new(Mutex::new(SpeculationStats::default())),
+            trust_ledger: None,
         }
     }
 
+    /// Require `min_models` successful models (at least 1).
+    ///
+    /// Clones share speculation stats, so a clone with a different minimum
+    /// can serve a single request.
+    pub fn with_min_models(mut self, min_models: usize) -> Self {
+        self.min_models = min_models.max(1);
+        self
+    }
+
     /// Override the per-adapter call timeout.
     ///
     /// Applies to both availability checks and generation calls.  Timed-out
@@ -42,6 +81,56 @@ impl ConsensusEngine {
         self
     }
 
+    /// Redact every result before it is returned.
+    ///
+    /// The final output and lineage are scrubbed, the output hash is
+    /// recomputed over the redacted text, and a redaction report is attached.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    /// Run up to `n` requests of a batch at once (default 4).
+    ///
+    /// Each running request calls every adapter its execution mode selects,
+    /// so a provider sees at most `n` concurrent calls from one batch.
+    pub fn with_batch_concurrency(mut self, n: usize) -> Self {
+        self.batch_concurrency = n.max(1);
+        self
+    }
+
+    /// Return as soon as the outputs received so far agree (see
+    /// [`crate::speculation`]) instead of waiting for every adapter.
+    ///
+    /// Adapter calls then run as spawned tasks, so this needs a Tokio
+    /// runtime.
+    pub fn with_speculation(mut self, config: SpeculationConfig) -> Self {
+        self.speculation = Some(config);
+        self
+    }
+
+    /// Record every round in `ledger` and leave outputs of quarantined
+    /// model versions out of consensus (see [`crate::ledger`]).
+    ///
+    /// Quarantined outputs are still scored against the others and
+    /// recorded, so a version builds the history needed to revalidate it.
+    pub fn with_trust_ledger(mut self, ledger: Arc<TrustLedger>) -> Self {
+        self.trust_ledger = Some(ledger);
+        self
+    }
+
+    pub fn trust_ledger(&self) -> Option<&Arc<TrustLedger>> {
+        self.trust_ledger.as_ref()
+    }
+
+    /// Speculation counters, including late outputs recorded so far
+    pub fn speculation_stats(&self) -> SpeculationStats {
+        self.speculation_stats
+            .lock()
+            .expect("speculation stats poisoned")
+            .clone()
+    }
+
     /// Execute generation request across multiple adapters
     pub async fn execute(
         &self,
@@ -51,15 +140,116 @@ impl ConsensusEngine {
         let start = std::time::Instant::now();
 
         // Filter adapters based on availability and execution mode
-        let available_adapters = self.filter_adapters(adapters, request).await;
+        let available_adapters = self.available_adapters(adapters).await;
+        let selected = Self::adapters_for_mode(&available_adapters, request.execution_mode);
+
+        self.run(request, &selected, start).await
+    }
+
+    /// Execute many requests against one set of adapters.
+    ///
+    /// Adapters are checked for availability once and then shared by every
+    /// request, so their sessions and connections are reused across the
+    /// batch.  Identical requests (same [`GenerationRequest::hash`]) run once
+    /// and share the result.  At most `batch_concurrency` distinct requests
+    /// run at a time.
+    ///
+    /// `results[i]` is the outcome of `requests[i]`; one request failing does
+    /// not fail the others.
+    pub async fn execute_batch(
+        &self,
+        requests: &[GenerationRequest],
+        adapters: Vec<Box<dyn ModelAdapter>>,
+    ) -> BatchResult {
+        let start = std::time::Instant::now();
+        let available_adapters = self.available_adapters(adapters).await;
+
+        // Map every request to the first identical one.
+        let mut first_by_hash: HashMap<String, usize> = HashMap::new();
+        let mut unique: Vec<&GenerationRequest> = Vec::new();
+        let slots: Vec<usize> = requests
+            .iter()
+            .map(|request| {
+                *first_by_hash.entry(request.hash()).or_insert_with(|| {
+                    unique.push(request);
+                    unique.len() - 1
+                })
+            })
+            .collect();
+
+        let available = &available_adapters;
+        let outcomes: Vec<(usize, Result<GenerationResult, String>)> = stream::iter(&unique)
+            .map(|request| async move {
+                let selected = Self::adapters_for_mode(available, request.execution_mode);
+                let outcome = self
+                    .run(request, &selected, std::time::Instant::now())
+                    .await
+                    .map_err(|e| format!("{e:#}"));
+                (selected.len(), outcome)
+            })
+            .buffered(self.batch_concurrency)
+            .collect()
+            .await;
+
+        let results: Vec<anyhow::Result<GenerationResult>> = slots
+            .iter()
+            .map(|&slot| outcomes[slot].1.clone().map_err(anyhow::Error::msg))
+            .collect();
+
+        let trust_scores: Vec<f64> = results
+            .iter()
+            .filter_map(|result| result.as_ref().ok().map(|r| r.trust_score))
+            .collect();
+        let metrics = BatchMetrics {
+            requests: requests.len(),
+            unique_requests: unique.len(),
+            succeeded: trust_scores.len(),
+            failed: requests.len() - trust_scores.len(),
+            adapters_available: available_adapters.len(),
+            adapter_calls: outcomes.iter().map(|(calls, _)| calls).sum(),
+            concurrency: self.batch_concurrency,
+            mean_trust_score: (!trust_scores.is_empty())
+                .then(|| trust_scores.iter().sum::<f64>() / trust_scores.len() as f64),
+            execution_time_ms: start.elapsed().as_millis() as u64,
+        };
+
+        BatchResult { results, metrics }
+    }
 
+    /// Run consensus for one request over adapters already filtered for it.
+    async fn run(
+        &self,
+        request: &GenerationRequest,
+        available_adapters: &[Arc<dyn ModelAdapter>],
+        start: std::time::Instant,
+    ) -> anyhow::Result<GenerationResult> {
         if available_adapters.is_empty() {
             anyhow::bail!("No adapters available for execution");
         }
 
         // Execute generation across all available adapters
-        let outputs = self.generate_all(&available_adapters, request).await;
-
+        let (outputs, outstanding) = match &self.speculation {
+            Some(config) => {
+                self.generate_speculative(available_adapters, request, config)
+                    .await
+            }
+            None => (
+                self.generate_all(available_adapters, request).await,
+                OutstandingCalls::new(),
+            ),
+        };
+
+        // Outputs of quarantined versions are recorded but do not count.
+        let (outputs, quarantined): (Vec<_>, Vec<_>) = match &self.trust_ledger {
+            Some(ledger) => outputs
+                .into_iter()
+                .partition(|output| !ledger.is_quarantined(output)),
+            None => (outputs, Vec::new()),
+        };
+
+        if outputs.is_empty() && !quarantined.is_empty() {
+            anyhow::bail!("Only quarantined model versions produced output");
+        }
         if outputs.is_empty() {
             anyhow::bail!("All models failed to generate output");
         }
@@ -80,6 +270,10 @@ impl ConsensusEngine {
         let (final_output, trust_score) =
             self.select_output(&scored_outputs, request.trust_threshold)?;
 
+        if let Some(ledger) = &self.trust_ledger {
+            Self::record_round(ledger, &scored_outputs, &quarantined, &final_output);
+        }
+
         // Build result metadata
         let elapsed = start.elapsed().as_millis() as u64;
         let was_offline = outputs.iter().all(|o| {
@@ -92,33 +286,42 @@ impl ConsensusEngine {
 
         let model_lineage = outputs.iter().map(|o| o.model_id.clone()).collect();
 
-        let metadata = ExecutionMetadata::new(
+        let mut metadata = ExecutionMetadata::new(
             available_adapters.len(),
             outputs.len(),
             was_offline,
             elapsed,
         );
+        metadata.models_outstanding = outstanding.len();
+        metadata.models_quarantined = quarantined.len();
+        if let Some(config) = &self.speculation {
+            self.settle_outstanding(outstanding, final_output.text.clone(), config);
+        }
 
-        Ok(GenerationResult::new(
+        let result = GenerationResult::new(
             final_output.text.clone(),
             trust_score,
             model_lineage,
             metadata,
             request.hash(),
-        ))
+        );
+
+        Ok(match &self.redactor {
+            Some(redactor) => redactor.redact_result(result),
+            None => result,
+        })
     }
 
-    /// Filter adapters based on availability and execution mode
+    /// Keep the adapters that report themselves available
     ///
     /// Availability checks are performed concurrently so that remote-adapter
     /// latency does not stack serially when multiple adapters are configured.
     /// Each check is bounded by `adapter_timeout_ms`; timed-out adapters are
     /// treated as unavailable so a single slow adapter cannot stall the round.
-    async fn filter_adapters(
+    async fn available_adapters(
         &self,
         adapters: Vec<Box<dyn ModelAdapter>>,
-        request: &GenerationRequest,
-    ) -> Vec<Box<dyn ModelAdapter>> {
+    ) -> Vec<Arc<dyn ModelAdapter>> {
         let timeout = Duration::from_millis(self.adapter_timeout_ms);
 
         // Check all adapters for availability concurrently.
@@ -132,14 +335,20 @@ impl ConsensusEngine {
         adapters
             .into_iter()
             .zip(availability)
-            .filter_map(|(adapter, available)| {
-                if !available {
-                    return None;
-                }
+            .filter_map(|(adapter, available)| available.then(|| Arc::from(adapter)))
+            .collect()
+    }
 
+    /// Select the adapters a request's execution mode allows
+    fn adapters_for_mode(
+        adapters: &[Arc<dyn ModelAdapter>],
+        mode: super::generation::ExecutionMode,
+    ) -> Vec<Arc<dyn ModelAdapter>> {
+        adapters
+            .iter()
+            .filter(|adapter| {
                 let locality = adapter.locality();
-
-                let should_include = match request.execution_mode {
+                match mode {
                     super::generation::ExecutionMode::Local => {
                         matches!(locality, super::adapters::ModelLocality::Local)
                     }
@@ -147,14 +356,9 @@ impl ConsensusEngine {
                         matches!(locality, super::adapters::ModelLocality::Remote)
                     }
                     super::generation::ExecutionMode::Hybrid => true,
-                };
-
-                if should_include {
-                    Some(adapter)
-                } else {
-                    None
                 }
             })
+            .cloned()
             .collect()
     }
 
@@ -166,7 +370,7 @@ impl ConsensusEngine {
     /// silently dropped, consistent with graceful degradation.
     async fn generate_all(
         &self,
-        adapters: &[Box<dyn ModelAdapter>],
+        adapters: &[Arc<dyn ModelAdapter>],
         request: &GenerationRequest,
     ) -> Vec<ModelOutput> {
         let timeout = Duration::from_millis(self.adapter_timeout_ms);
@@ -184,8 +388,177 @@ impl ConsensusEngine {
         .collect()
     }
 
+    /// Generate outputs until they agree, the time box runs out, or every
+    /// adapter has answered
+    ///
+    /// Calls are spawned so the ones still running when the round returns
+    /// can be finished or cancelled by [`Self::settle_outstanding`].  The
+    /// time box only ends a round once `min_models` outputs are in.
+    async fn generate_speculative(
+        &self,
+        adapters: &[Arc<dyn ModelAdapter>],
+        request: &GenerationRequest,
+        config: &SpeculationConfig,
+    ) -> (Vec<ModelOutput>, OutstandingCalls) {
+        let timeout = Duration::from_millis(self.adapter_timeout_ms);
+        let deadline = config
+            .time_box
+            .map(|time_box| tokio::time::Instant::now() + time_box);
+
+        let mut outstanding: OutstandingCalls = adapters
+            .iter()
+            .map(|adapter| {
+                let adapter = Arc::clone(adapter);
+                let prompt = request.prompt.clone();
+                let task_type = request.task_type;
+                tokio::spawn(async move {
+                    tokio::time::timeout(timeout, adapter.generate(&prompt, task_type))
+                        .await
+                        .unwrap_or_else(|_| Err(anyhow::anyhow!("adapter timed out")))
+                })
+            })
+            .collect();
+
+        let mut outputs = Vec::new();
+        let mut early = None;
+        loop {
+            let next = match deadline {
+                Some(deadline) if outputs.len() >= self.min_models => {
+                    match tokio::time::timeout_at(deadline, outstanding.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            early = Some(EarlyReturn::TimeBox);
+                            break;
+                        }
+                    }
+                }
+                _ => outstanding.next().await,
+            };
+            let Some(joined) = next else {
+                break;
+            };
+            if let Ok(Ok(output)) = joined {
+                outputs.push(output);
+            }
+            if outstanding.is_empty() {
+                break;
+            }
+            let counted = self.counted_outputs(&outputs);
+            if counted.len() >= self.min_models
+                && config.agreement_reached(&counted)
+                && self
+                    .score_outputs(&counted)
+                    .iter()
+                    .any(|(_, scores)| scores.overall_score() >= request.trust_threshold)
+            {
+                early = Some(EarlyReturn::Agreement);
+                break;
+            }
+        }
+
+        self.speculation_stats
+            .lock()
+            .expect("speculation stats poisoned")
+            .record_return(early);
+        (outputs, outstanding)
+    }
+
+    /// Finish or cancel the calls a speculative round did not wait for
+    ///
+    /// Finished outputs are compared with the returned output `chosen`;
+    /// those less than `min_consistency` similar count as late divergences.
+    fn settle_outstanding(
+        &self,
+        outstanding: OutstandingCalls,
+        chosen: String,
+        config: &SpeculationConfig,
+    ) {
+        if outstanding.is_empty() {
+            return;
+        }
+        if !config.finish_in_background {
+            for call in outstanding.iter() {
+                call.abort();
+            }
+            self.speculation_stats
+                .lock()
+                .expect("speculation stats poisoned")
+                .cancelled += outstanding.len() as u64;
+            return;
+        }
+
+        let stats = Arc::clone(&self.speculation_stats);
+        let min_consistency = config.min_consistency;
+        tokio::spawn(async move {
+            let mut outstanding = outstanding;
+            while let Some(joined) = outstanding.next().await {
+                let mut stats = stats.lock().expect("speculation stats poisoned");
+                match joined {
+                    Ok(Ok(output)) => {
+                        let similarity =
+                            ConsistencyScore::compute_similarity(&chosen, &output.text);
+                        let diverged = similarity < min_consistency;
+                        if diverged {
+                            tracing::info!(
+                                model_id = %output.model_id,
+                                similarity,
+                                "Late output diverged from speculative consensus result"
+                            );
+                        }
+                        stats.record_late_output(similarity, diverged);
+                    }
+                    _ => stats.late_failures += 1,
+                }
+            }
+        });
+    }
+
+    /// Outputs that count towards consensus: all but those of quarantined
+    /// model versions
+    fn counted_outputs(&self, outputs: &[ModelOutput]) -> Vec<ModelOutput> {
+        match &self.trust_ledger {
+            Some(ledger) => outputs
+                .iter()
+                .filter(|output| !ledger.is_quarantined(output))
+                .cloned()
+                .collect(),
+            None => outputs.to_vec(),
+        }
+    }
+
+    /// Record a round's outputs, including quarantined ones scored against
+    /// the counted outputs, in the trust ledger
+    fn record_round(
+        ledger: &TrustLedger,
+        scored_outputs: &[(ModelOutput, TrustScores)],
+        quarantined: &[ModelOutput],
+        selected: &ModelOutput,
+    ) {
+        let counted: Vec<ModelOutput> = scored_outputs
+            .iter()
+            .map(|(output, _)| output.clone())
+            .collect();
+        let shadow_scores: Vec<TrustScores> = quarantined
+            .iter()
+            .map(|output| compute_trust_scores(output, &counted))
+            .collect();
+
+        let observations: Vec<LedgerObservation<'_>> = scored_outputs
+            .iter()
+            .map(|(output, scores)| (output, scores))
+            .chain(quarantined.iter().zip(&shadow_scores))
+            .map(|(output, scores)| LedgerObservation {
+                output,
+                agreement: ConsistencyScore::compute_similarity(&output.text, &selected.text),
+                trust: scores.overall_score(),
+                selected: output.model_id == selected.model_id,
+            })
+            .collect();
+        ledger.record_round(&observations);
+    }
+
     /// Compute trust scores for all outputs
-    fn score_outputs(&self, outputs: &[ModelOutput]) -> Vec<(ModelOutput, TrustScores)> {
+    pub(crate) fn score_outputs(&self, outputs: &[ModelOutput]) -> Vec<(ModelOutput, TrustScores)> {
         outputs
             .iter()
             .map(|output| {
@@ -442,4 +815,268 @@ mod tests {
             .model_lineage
             .contains(&"remote-excluded".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_redactor_scrubs_result() {
+        use crate::testkit::MockModelAdapter;
+
+        let engine =
+            ConsensusEngine::new(1).with_redactor(Redactor::new().with_default_detectors());
+        let adapters: Vec<Box<dyn ModelAdapter>> = vec![Box::new(
+            MockModelAdapter::new("mock-1").respond("contact ops@example.com"),
+        )];
+        let request = GenerationRequest::new(
+            "who to contact",
+            TaskType::Chat,
+            0.5,
+            ExecutionMode::Local,
+            true,
+        );
+
+        let result = engine.execute(&request, adapters).await.unwrap();
+        assert_eq!(result.final_output, "contact [REDACTED:email]");
+        assert!(result.verify_hash());
+        assert_eq!(result.redaction.unwrap().entries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_deduplicates_identical_requests() {
+        use crate::testkit::{fixtures, MockModelAdapter};
+
+        let echo = MockModelAdapter::new("echo");
+        let adapters: Vec<Box<dyn ModelAdapter>> = vec![Box::new(echo.clone())];
+        let requests = vec![
+            fixtures::request("first", ExecutionMode::Local),
+            fixtures::request("second", ExecutionMode::Local),
+            fixtures::request("first", ExecutionMode::Local),
+        ];
+
+        let batch = ConsensusEngine::new(1)
+            .execute_batch(&requests, adapters)
+            .await;
+
+        let outputs: Vec<&str> = batch
+            .results
+            .iter()
+            .map(|r| r.as_ref().unwrap().final_output.as_str())
+            .collect();
+        assert_eq!(outputs, ["first", "second", "first"]);
+        assert_eq!(echo.calls(), 2);
+        assert_eq!(batch.metrics.requests, 3);
+        assert_eq!(batch.metrics.unique_requests, 2);
+        assert_eq!(batch.metrics.succeeded, 3);
+        assert_eq!(batch.metrics.adapter_calls, 2);
+    }
+
+    #[tokio::test]
+    async fn test_batch_reports_failures_per_request() {
+        use crate::testkit::{fixtures, MockModelAdapter};
+
+        let adapters: Vec<Box<dyn ModelAdapter>> = vec![
+            Box::new(MockModelAdapter::new("local")),
+            Box::new(MockModelAdapter::remote("remote").unavailable()),
+        ];
+        let requests = vec![
+            fixtures::request("remote only", ExecutionMode::Remote),
+            fixtures::request("local", ExecutionMode::Local),
+            fixtures::request("remote only", ExecutionMode::Remote),
+        ];
+
+        let batch = ConsensusEngine::new(1)
+            .execute_batch(&requests, adapters)
+            .await;
+
+        assert!(batch.results[0].is_err());
+        assert!(batch.results[1].is_ok());
+        assert!(batch.results[2]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("No adapters available"));
+        assert_eq!(batch.metrics.adapters_available, 1);
+        assert_eq!(batch.metrics.succeeded, 1);
+        assert_eq!(batch.metrics.failed, 2);
+        assert_eq!(batch.metrics.adapter_calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_bounds_concurrent_requests() {
+        use crate::testkit::fixtures;
+        use async_trait::async_trait;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Records the most `generate` calls it saw in flight at once.
+        #[derive(Default)]
+        struct Gauge {
+            in_flight: AtomicUsize,
+            peak: AtomicUsize,
+        }
+
+        struct GaugedAdapter(Arc<Gauge>);
+
+        #[async_trait]
+        impl ModelAdapter for GaugedAdapter {
+            async fn generate(&self, prompt: &str, _: TaskType) -> anyhow::Result<ModelOutput> {
+                let now = self.0.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                self.0.peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(ModelOutput::new(prompt, "gauged", 0.9, 20))
+            }
+
+            fn model_id(&self) -> &str {
+                "gauged"
+            }
+
+            fn locality(&self) -> super::super::adapters::ModelLocality {
+                super::super::adapters::ModelLocality::Local
+            }
+
+            async fn is_available(&self) -> bool {
+                true
+            }
+        }
+
+        let gauge = Arc::new(Gauge::default());
+        let adapters: Vec<Box<dyn ModelAdapter>> = vec![Box::new(GaugedAdapter(gauge.clone()))];
+        let requests: Vec<GenerationRequest> = (0..8)
+            .map(|i| fixtures::request(format!("prompt {i}"), ExecutionMode::Local))
+            .collect();
+
+        let batch = ConsensusEngine::new(1)
+            .with_batch_concurrency(3)
+            .execute_batch(&requests, adapters)
+            .await;
+
+        assert_eq!(batch.metrics.succeeded, 8);
+        assert_eq!(batch.metrics.concurrency, 3);
+        assert_eq!(gauge.peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_speculation_returns_on_early_agreement() {
+        use crate::testkit::{assert_lineage, fixtures, MockModelAdapter};
+
+        let engine = ConsensusEngine::new(2).with_speculation(SpeculationConfig::default());
+        let adapters: Vec<Box<dyn ModelAdapter>> = vec![
+            Box::new(MockModelAdapter::new("fast-1").respond("the answer is 4")),
+            Box::new(MockModelAdapter::new("fast-2").respond("the answer is 4")),
+            Box::new(
+                MockModelAdapter::new("slow")
+                    .with_delay(Duration::from_millis(500))
+                    .respond("probably five"),
+            ),
+        ];
+        let request = fixtures::request("2 + 2", ExecutionMode::Local);
+
+        let started = std::time::Instant::now();
+        let result = engine.execute(&request, adapters).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert_lineage(&result, &["fast-1", "fast-2"]);
+        assert_eq!(result.execution_metadata.models_consulted, 3);
+        assert_eq!(result.execution_metadata.models_outstanding, 1);
+
+        // The slow adapter finishes in the background and disagrees.
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        let stats = engine.speculation_stats();
+        assert_eq!(stats.rounds, 1);
+        assert_eq!(stats.early_agreements, 1);
+        assert_eq!(stats.late_outputs, 1);
+        assert_eq!(stats.late_divergences, 1);
+    }
+
+    #[tokio::test]
+    async fn test_speculation_time_box_cancels_outstanding_calls() {
+        use crate::testkit::{fixtures, MockModelAdapter};
+
+        let engine = ConsensusEngine::new(2).with_speculation(
+            SpeculationConfig::default()
+                .with_time_box(Duration::from_millis(50))
+                .with_finish_in_background(false),
+        );
+        let adapters: Vec<Box<dyn ModelAdapter>> = vec![
+            Box::new(MockModelAdapter::new("a").respond("the answer is 4")),
+            Box::new(MockModelAdapter::new("b").respond("no idea")),
+            Box::new(
+                MockModelAdapter::new("slow")
+                    .with_delay(Duration::from_millis(500))
+                    .respond("the answer is 4"),
+            ),
+        ];
+        let request = fixtures::request("2 + 2", ExecutionMode::Local);
+
+        let started = std::time::Instant::now();
+        let result = engine.execute(&request, adapters).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert_eq!(result.model_lineage.len(), 2);
+        assert_eq!(result.execution_metadata.models_outstanding, 1);
+
+        let stats = engine.speculation_stats();
+        assert_eq!(stats.time_boxed, 1);
+        assert_eq!(stats.early_agreements, 0);
+        assert_eq!(stats.cancelled, 1);
+        assert_eq!(stats.late_outputs, 0);
+    }
+
+    #[tokio::test]
+    async fn test_trust_ledger_quarantines_drifted_model_version() {
+        use crate::ledger::{DriftConfig, DriftMetric, ModelIdentity};
+        use crate::testkit::{fixtures, MockModelAdapter};
+
+        let ledger = Arc::new(TrustLedger::new(DriftConfig {
+            min_samples: 2,
+            ..DriftConfig::default()
+        }));
+        let engine = ConsensusEngine::new(2).with_trust_ledger(Arc::clone(&ledger));
+        let request = fixtures::request("2 + 2", ExecutionMode::Local);
+        let adapters = |version: &str, text: &str| -> Vec<Box<dyn ModelAdapter>> {
+            vec![
+                Box::new(MockModelAdapter::new("a").respond("the answer is 4")),
+                Box::new(MockModelAdapter::new("b").respond("the answer is 4")),
+                Box::new(
+                    MockModelAdapter::new("c")
+                        .with_model_version(version)
+                        .respond(text),
+                ),
+            ]
+        };
+
+        for _ in 0..2 {
+            engine
+                .execute(&request, adapters("v1", "the answer is 4"))
+                .await
+                .unwrap();
+        }
+        for _ in 0..2 {
+            let result = engine
+                .execute(&request, adapters("v2", "five, probably"))
+                .await
+                .unwrap();
+            assert_eq!(result.execution_metadata.models_quarantined, 0);
+        }
+
+        let alerts = ledger.alerts();
+        assert!(alerts
+            .iter()
+            .any(|alert| alert.metric == DriftMetric::Agreement
+                && alert.identity == ModelIdentity::new("c", "v2")));
+
+        let result = engine
+            .execute(&request, adapters("v2", "five, probably"))
+            .await
+            .unwrap();
+        assert_eq!(result.execution_metadata.models_quarantined, 1);
+        assert_eq!(result.execution_metadata.models_succeeded, 2);
+        assert!(!result.model_lineage.contains(&"c".to_string()));
+        // Still recorded while quarantined.
+        let record = ledger.record(&ModelIdentity::new("c", "v2")).unwrap();
+        assert_eq!(record.agreement.count, 3);
+
+        assert!(ledger.release(&ModelIdentity::new("c", "v2")));
+        let result = engine
+            .execute(&request, adapters("v2", "five, probably"))
+            .await
+            .unwrap();
+        assert_eq!(result.execution_metadata.models_quarantined, 0);
+    }
 }
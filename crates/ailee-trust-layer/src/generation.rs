@@ -3,6 +3,8 @@
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Sha3_256};
 
+use super::redaction::RedactionReport;
+
 /// Type of generative task
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum TaskType {
@@ -85,6 +87,9 @@ pub struct GenerationResult {
     pub input_hash: String,
     /// Cryptographic hash of output
     pub output_hash: String,
+    /// What was scrubbed from the output and lineage, when a redactor ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redaction: Option<RedactionReport>,
 }
 
 impl GenerationResult {
@@ -104,9 +109,16 @@ impl GenerationResult {
             execution_metadata,
             input_hash,
             output_hash,
+            redaction: None,
         }
     }
 
+    /// Replace the final output and recompute its hash
+    pub(crate) fn set_final_output(&mut self, final_output: String) {
+        self.output_hash = Self::compute_output_hash(&final_output);
+        self.final_output = final_output;
+    }
+
     /// Compute hash of output
     fn compute_output_hash(output: &str) -> String {
         let mut hasher = Sha3_256::new();
@@ -133,6 +145,12 @@ pub struct ExecutionMetadata {
     pub execution_time_ms: u64,
     /// Timestamp of execution (Unix epoch seconds)
     pub timestamp: u64,
+    /// Adapters still running when a speculative round returned early
+    #[serde(default)]
+    pub models_outstanding: usize,
+    /// Outputs left out because their model version is quarantined
+    #[serde(default)]
+    pub models_quarantined: usize,
 }
 
 impl ExecutionMetadata {
@@ -153,10 +171,44 @@ impl ExecutionMetadata {
             was_offline,
             execution_time_ms,
             timestamp,
+            models_outstanding: 0,
+            models_quarantined: 0,
         }
     }
 }
 
+/// Outcome of [`crate::ConsensusEngine::execute_batch`]
+#[derive(Debug)]
+pub struct BatchResult {
+    /// One result per request, in request order
+    pub results: Vec<anyhow::Result<GenerationResult>>,
+    /// Summary of the whole batch
+    pub metrics: BatchMetrics,
+}
+
+/// Metrics summarising a batch execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchMetrics {
+    /// Requests submitted
+    pub requests: usize,
+    /// Distinct requests executed; duplicates reuse their result
+    pub unique_requests: usize,
+    /// Requests that produced a result
+    pub succeeded: usize,
+    /// Requests that failed
+    pub failed: usize,
+    /// Adapters that passed the availability check
+    pub adapters_available: usize,
+    /// Adapter `generate` calls issued
+    pub adapter_calls: usize,
+    /// Distinct requests allowed to run at once
+    pub concurrency: usize,
+    /// Mean trust score of successful requests, if any
+    pub mean_trust_score: Option<f64>,
+    /// Wall-clock time of the batch in milliseconds
+    pub execution_time_ms: u64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,561 @@
+//! Long-term trust ledger keyed by model identity and version
+//!
+//! Providers change model behaviour behind a stable adapter name.  The
+//! [`TrustLedger`] keeps trust metrics per [`ModelIdentity`] — the adapter
+//! plus the version or build hash its outputs report — so a version bump
+//! starts a fresh record instead of blending into the old one.
+//!
+//! Every consensus round records, per output:
+//!
+//! - **agreement**: similarity to the output consensus selected
+//! - **trust**: the output's overall trust score
+//!
+//! When an adapter starts reporting a new version, the version it replaces
+//! becomes the new one's baseline.  Once the new version has
+//! `min_samples` rounds, the mean of each metric is compared with the
+//! baseline's; a shift larger than `max_mean_shift` raises a [`DriftAlert`]
+//! and, with `auto_quarantine`, quarantines the new version.
+//!
+//! Quarantined versions still run and are still recorded — their outputs
+//! are scored against the others — but [`crate::ConsensusEngine`] leaves
+//! them out of consensus.  [`TrustLedger::release`] revalidates a version
+//! and makes its current metrics the reference for later versions.
+//!
+//! The ledger is meant to outlive a process: [`TrustLedger::save`] and
+//! [`TrustLedger::load`] keep it in a JSON file.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::adapters::ModelOutput;
+
+/// Version recorded for outputs that do not report one.
+pub const UNKNOWN_MODEL_VERSION: &str = "unknown";
+
+/// Default rounds a new version needs before it is compared with its
+/// baseline.
+pub const DEFAULT_DRIFT_MIN_SAMPLES: u64 = 30;
+
+/// Default shift in a metric's mean that counts as drift.
+pub const DEFAULT_DRIFT_MAX_MEAN_SHIFT: f64 = 0.15;
+
+/// Drift alerts kept for [`TrustLedger::alerts`].
+const MAX_ALERTS: usize = 256;
+
+/// An adapter at one reported model version.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ModelIdentity {
+    /// Adapter model identifier
+    pub adapter: String,
+    /// Version or build hash the model reported
+    pub version: String,
+}
+
+impl ModelIdentity {
+    pub fn new(adapter: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            adapter: adapter.into(),
+            version: version.into(),
+        }
+    }
+
+    /// Identity of the adapter and version that produced `output`.
+    pub fn of(output: &ModelOutput) -> Self {
+        Self::new(
+            output.model_id.clone(),
+            output
+                .model_version
+                .as_deref()
+                .unwrap_or(UNKNOWN_MODEL_VERSION),
+        )
+    }
+}
+
+impl std::fmt::Display for ModelIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.adapter, self.version)
+    }
+}
+
+/// Running mean and variance of one metric (Welford).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricStats {
+    pub count: u64,
+    pub mean: f64,
+    /// Sum of squared differences from the mean
+    m2: f64,
+}
+
+impl MetricStats {
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Sample standard deviation, once there are two samples
+    pub fn std_dev(&self) -> Option<f64> {
+        (self.count > 1).then(|| (self.m2 / (self.count - 1) as f64).sqrt())
+    }
+}
+
+/// Which metric drifted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftMetric {
+    /// Similarity to the selected output
+    Agreement,
+    /// Overall trust score
+    Trust,
+}
+
+/// Why and since when a version is kept out of consensus.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quarantine {
+    pub reason: String,
+    /// Unix epoch milliseconds
+    pub since_ms: u64,
+}
+
+/// Metrics of one model version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionRecord {
+    pub identity: ModelIdentity,
+    /// Unix epoch milliseconds
+    pub first_seen_ms: u64,
+    pub last_seen_ms: u64,
+    pub agreement: MetricStats,
+    pub trust: MetricStats,
+    /// Rounds in which this version's output was selected
+    pub selected: u64,
+    /// Version this one replaced, compared against for drift
+    pub baseline: Option<String>,
+    /// Whether this version has been compared with its baseline
+    pub drift_checked: bool,
+    pub quarantine: Option<Quarantine>,
+}
+
+impl VersionRecord {
+    fn new(identity: ModelIdentity, baseline: Option<String>, now_ms: u64) -> Self {
+        Self {
+            identity,
+            first_seen_ms: now_ms,
+            last_seen_ms: now_ms,
+            agreement: MetricStats::default(),
+            trust: MetricStats::default(),
+            selected: 0,
+            // Nothing to compare against means nothing to check.
+            drift_checked: baseline.is_none(),
+            baseline,
+            quarantine: None,
+        }
+    }
+
+    fn metric(&self, metric: DriftMetric) -> &MetricStats {
+        match metric {
+            DriftMetric::Agreement => &self.agreement,
+            DriftMetric::Trust => &self.trust,
+        }
+    }
+}
+
+/// A version's metrics moved away from the version it replaced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DriftAlert {
+    pub identity: ModelIdentity,
+    pub baseline_version: String,
+    pub metric: DriftMetric,
+    pub baseline_mean: f64,
+    pub mean: f64,
+    /// Rounds of the new version behind `mean`
+    pub samples: u64,
+    /// Whether the version was quarantined for it
+    pub quarantined: bool,
+    /// Unix epoch milliseconds
+    pub at_ms: u64,
+}
+
+/// When a new version counts as drifted.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DriftConfig {
+    /// Rounds of a new version before it is compared with its baseline
+    pub min_samples: u64,
+    /// Largest change in a metric's mean that is not drift
+    pub max_mean_shift: f64,
+    /// Quarantine versions that drift
+    pub auto_quarantine: bool,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        Self {
+            min_samples: DEFAULT_DRIFT_MIN_SAMPLES,
+            max_mean_shift: DEFAULT_DRIFT_MAX_MEAN_SHIFT,
+            auto_quarantine: true,
+        }
+    }
+}
+
+/// Everything the ledger keeps, as saved to disk.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LedgerSnapshot {
+    /// Every version seen, by adapter then version
+    pub versions: BTreeMap<String, BTreeMap<String, VersionRecord>>,
+    /// Version each adapter reported last
+    pub current: BTreeMap<String, String>,
+    /// Most recent drift alerts, oldest first
+    pub alerts: Vec<DriftAlert>,
+}
+
+/// One output of a consensus round, as recorded in the ledger.
+#[derive(Debug, Clone)]
+pub struct LedgerObservation<'a> {
+    pub output: &'a ModelOutput,
+    /// Similarity to the selected output
+    pub agreement: f64,
+    /// Overall trust score
+    pub trust: f64,
+    /// Whether this output was selected
+    pub selected: bool,
+}
+
+/// Trust metrics per model version, with drift detection and quarantine.
+#[derive(Debug, Default)]
+pub struct TrustLedger {
+    config: DriftConfig,
+    state: Mutex<LedgerSnapshot>,
+}
+
+impl TrustLedger {
+    pub fn new(config: DriftConfig) -> Self {
+        Self::from_snapshot(config, LedgerSnapshot::default())
+    }
+
+    pub fn from_snapshot(config: DriftConfig, snapshot: LedgerSnapshot) -> Self {
+        Self {
+            config,
+            state: Mutex::new(snapshot),
+        }
+    }
+
+    /// Load the ledger saved at `path`, or start an empty one if there is
+    /// no file yet.
+    pub fn load(config: DriftConfig, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let snapshot = match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("invalid trust ledger {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => LedgerSnapshot::default(),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read trust ledger {}", path.display()))
+            }
+        };
+        Ok(Self::from_snapshot(config, snapshot))
+    }
+
+    /// Write the ledger to `path`, replacing it atomically.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let bytes = serde_json::to_vec_pretty(&self.snapshot())?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, bytes)
+            .with_context(|| format!("failed to write trust ledger {}", path.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("failed to replace trust ledger {}", path.display()))
+    }
+
+    pub fn config(&self) -> DriftConfig {
+        self.config
+    }
+
+    pub fn snapshot(&self) -> LedgerSnapshot {
+        self.lock().clone()
+    }
+
+    /// Record of one version, if it has been seen.
+    pub fn record(&self, identity: &ModelIdentity) -> Option<VersionRecord> {
+        self.lock()
+            .versions
+            .get(&identity.adapter)?
+            .get(&identity.version)
+            .cloned()
+    }
+
+    /// Whether `output` comes from a quarantined version.
+    pub fn is_quarantined(&self, output: &ModelOutput) -> bool {
+        let identity = ModelIdentity::of(output);
+        self.lock()
+            .versions
+            .get(&identity.adapter)
+            .and_then(|versions| versions.get(&identity.version))
+            .is_some_and(|record| record.quarantine.is_some())
+    }
+
+    /// Versions currently quarantined.
+    pub fn quarantined(&self) -> Vec<VersionRecord> {
+        self.lock()
+            .versions
+            .values()
+            .flat_map(|versions| versions.values())
+            .filter(|record| record.quarantine.is_some())
+            .cloned()
+            .collect()
+    }
+
+    /// Most recent drift alerts, oldest first.
+    pub fn alerts(&self) -> Vec<DriftAlert> {
+        self.lock().alerts.clone()
+    }
+
+    /// Keep `identity` out of consensus until it is released.
+    pub fn quarantine(&self, identity: &ModelIdentity, reason: impl Into<String>) {
+        let now_ms = now_ms();
+        let mut state = self.lock();
+        let record = state
+            .versions
+            .entry(identity.adapter.clone())
+            .or_default()
+            .entry(identity.version.clone())
+            .or_insert_with(|| VersionRecord::new(identity.clone(), None, now_ms));
+        record.quarantine = Some(Quarantine {
+            reason: reason.into(),
+            since_ms: now_ms,
+        });
+        tracing::warn!(model = %identity, "Model version quarantined from consensus");
+    }
+
+    /// Return a revalidated version to consensus.
+    ///
+    /// Its current metrics become the reference: it is not checked against
+    /// its baseline again.  Returns whether it was quarantined.
+    pub fn release(&self, identity: &ModelIdentity) -> bool {
+        let mut state = self.lock();
+        let Some(record) = state
+            .versions
+            .get_mut(&identity.adapter)
+            .and_then(|versions| versions.get_mut(&identity.version))
+        else {
+            return false;
+        };
+        record.drift_checked = true;
+        let released = record.quarantine.take().is_some();
+        if released {
+            tracing::info!(model = %identity, "Model version released from quarantine");
+        }
+        released
+    }
+
+    /// Record one consensus round and return the drift alerts it raised.
+    pub fn record_round(&self, observations: &[LedgerObservation<'_>]) -> Vec<DriftAlert> {
+        let now_ms = now_ms();
+        let mut state = self.lock();
+        let mut alerts = Vec::new();
+
+        for observation in observations {
+            let identity = ModelIdentity::of(observation.output);
+            let previous = state
+                .current
+                .insert(identity.adapter.clone(), identity.version.clone());
+            let versions = state.versions.entry(identity.adapter.clone()).or_default();
+
+            let record = versions.entry(identity.version.clone()).or_insert_with(|| {
+                let baseline = previous.filter(|version| *version != identity.version);
+                if let Some(baseline) = &baseline {
+                    tracing::info!(
+                        model = %identity,
+                        baseline_version = %baseline,
+                        "New model version reported"
+                    );
+                }
+                VersionRecord::new(identity.clone(), baseline, now_ms)
+            });
+            record.last_seen_ms = now_ms;
+            record.agreement.push(observation.agreement);
+            record.trust.push(observation.trust);
+            if observation.selected {
+                record.selected += 1;
+            }
+
+            if record.drift_checked || record.trust.count < self.config.min_samples {
+                continue;
+            }
+            let record = record.clone();
+            let baseline = record
+                .baseline
+                .as_ref()
+                .and_then(|baseline| versions.get(baseline))
+                .filter(|baseline| baseline.trust.count >= self.config.min_samples)
+                .cloned();
+            let record = versions
+                .get_mut(&identity.version)
+                .expect("record inserted above");
+            // A baseline with too little history cannot show drift.
+            record.drift_checked = true;
+            let Some(baseline) = baseline else {
+                continue;
+            };
+
+            let mut drifted = false;
+            for metric in [DriftMetric::Agreement, DriftMetric::Trust] {
+                let baseline_mean = baseline.metric(metric).mean;
+                let mean = record.metric(metric).mean;
+                if (mean - baseline_mean).abs() <= self.config.max_mean_shift {
+                    continue;
+                }
+                drifted = true;
+                tracing::warn!(
+                    model = %identity,
+                    baseline_version = %baseline.identity.version,
+                    ?metric,
+                    baseline_mean,
+                    mean,
+                    "Model version drifted from its baseline"
+                );
+                alerts.push(DriftAlert {
+                    identity: identity.clone(),
+                    baseline_version: baseline.identity.version.clone(),
+                    metric,
+                    baseline_mean,
+                    mean,
+                    samples: record.trust.count,
+                    quarantined: self.config.auto_quarantine,
+                    at_ms: now_ms,
+                });
+            }
+            if drifted && self.config.auto_quarantine && record.quarantine.is_none() {
+                record.quarantine = Some(Quarantine {
+                    reason: format!("drifted from {}", baseline.identity),
+                    since_ms: now_ms,
+                });
+            }
+        }
+
+        state.alerts.extend(alerts.iter().cloned());
+        let excess = state.alerts.len().saturating_sub(MAX_ALERTS);
+        state.alerts.drain(..excess);
+        alerts
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LedgerSnapshot> {
+        self.state.lock().expect("trust ledger poisoned")
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::fixtures;
+
+    fn observe(ledger: &TrustLedger, output: &ModelOutput, agreement: f64) -> Vec<DriftAlert> {
+        ledger.record_round(&[LedgerObservation {
+            output,
+            agreement,
+            trust: 0.8,
+            selected: false,
+        }])
+    }
+
+    fn ledger(auto_quarantine: bool) -> TrustLedger {
+        TrustLedger::new(DriftConfig {
+            min_samples: 3,
+            auto_quarantine,
+            ..DriftConfig::default()
+        })
+    }
+
+    #[test]
+    fn version_bump_that_shifts_agreement_is_quarantined() {
+        let ledger = ledger(true);
+        let v1 = fixtures::output("gpt", "ok", 0.9).with_model_version("2026-01");
+        let v2 = fixtures::output("gpt", "ok", 0.9).with_model_version("2026-03");
+        for _ in 0..3 {
+            assert!(observe(&ledger, &v1, 0.9).is_empty());
+        }
+        assert!(observe(&ledger, &v2, 0.4).is_empty());
+        assert!(observe(&ledger, &v2, 0.5).is_empty());
+        assert!(!ledger.is_quarantined(&v2));
+
+        let alerts = observe(&ledger, &v2, 0.6);
+        assert_eq!(alerts.len(), 1);
+        let alert = &alerts[0];
+        assert_eq!(alert.identity, ModelIdentity::new("gpt", "2026-03"));
+        assert_eq!(alert.baseline_version, "2026-01");
+        assert_eq!(alert.metric, DriftMetric::Agreement);
+        assert!((alert.mean - 0.5).abs() < 1e-9);
+        assert!(alert.quarantined);
+        assert!(ledger.is_quarantined(&v2));
+        assert!(!ledger.is_quarantined(&v1));
+
+        // Checked once per version.
+        assert!(observe(&ledger, &v2, 0.1).is_empty());
+        assert_eq!(ledger.alerts().len(), 1);
+
+        assert!(ledger.release(&ModelIdentity::of(&v2)));
+        assert!(!ledger.is_quarantined(&v2));
+        assert!(ledger.quarantined().is_empty());
+    }
+
+    #[test]
+    fn stable_version_bump_and_unversioned_outputs_are_not_drift() {
+        let ledger = ledger(true);
+        let v1 = fixtures::output("local", "ok", 0.9).with_model_version("a1");
+        let v2 = fixtures::output("local", "ok", 0.9).with_model_version("b2");
+        let unversioned = fixtures::output("other", "ok", 0.9);
+        for _ in 0..3 {
+            observe(&ledger, &v1, 0.9);
+            observe(&ledger, &unversioned, 0.2);
+        }
+        for _ in 0..3 {
+            assert!(observe(&ledger, &v2, 0.85).is_empty());
+        }
+        assert!(!ledger.is_quarantined(&v2));
+
+        let record = ledger.record(&ModelIdentity::new("other", UNKNOWN_MODEL_VERSION));
+        assert_eq!(record.unwrap().agreement.count, 3);
+        let record = ledger.record(&ModelIdentity::new("local", "b2")).unwrap();
+        assert_eq!(record.baseline.as_deref(), Some("a1"));
+        assert!(record.drift_checked);
+    }
+
+    #[test]
+    fn drift_without_auto_quarantine_only_alerts_and_ledger_round_trips() {
+        let ledger = ledger(false);
+        let v1 = fixtures::output("m", "ok", 0.9).with_model_version("1");
+        let v2 = fixtures::output("m", "ok", 0.9).with_model_version("2");
+        for _ in 0..3 {
+            observe(&ledger, &v1, 0.9);
+        }
+        let alerts: Vec<_> = (0..3).flat_map(|_| observe(&ledger, &v2, 0.1)).collect();
+        assert_eq!(alerts.len(), 1);
+        assert!(!alerts[0].quarantined);
+        assert!(!ledger.is_quarantined(&v2));
+
+        ledger.quarantine(&ModelIdentity::of(&v2), "manual");
+        let path = std::env::temp_dir().join(format!("trust-ledger-{}.json", std::process::id()));
+        ledger.save(&path).unwrap();
+        let loaded = TrustLedger::load(ledger.config(), &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.snapshot(), ledger.snapshot());
+        assert!(loaded.is_quarantined(&v2));
+
+        let missing = std::env::temp_dir().join("trust-ledger-missing.json");
+        assert!(TrustLedger::load(DriftConfig::default(), missing)
+            .unwrap()
+            .snapshot()
+            .versions
+            .is_empty());
+    }
+}
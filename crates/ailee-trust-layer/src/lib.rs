@@ -21,6 +21,18 @@
 //! - **Adapters**: Model abstraction layer (local/remote)
 //! - **Trust**: Trust scoring algorithms (confidence, safety, consistency)
 //! - **Consensus**: Multi-model output selection and scoring
+//! - **Ledger**: Long-term trust metrics per model version, with drift
+//!   alerts and quarantine of versions that drift
+//! - **Speculation**: Early return from a consensus round once fast models
+//!   agree, with late outputs recorded for calibration
+//! - **Pool**: Shared provider connections, keep-warm pings and per-provider
+//!   concurrency limits for remote adapters
+//! - **Redaction**: Regex and plugin detectors that scrub results before
+//!   storage, with an audit report of what was removed
+//! - **Tuning**: Simulated adapter disagreement for choosing trust thresholds
+//!   and consensus strategies
+//! - **Testkit** (feature `testkit`): Mock adapters, fixtures and lineage
+//!   assertions for testing code built on this crate
 //!
 //! ### Usage
 //!
@@ -62,8 +74,15 @@
 pub mod adapters;
 pub mod consensus;
 pub mod generation;
+pub mod ledger;
 pub mod metric;
+pub mod pool;
+pub mod redaction;
+pub mod speculation;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
 pub mod trust;
+pub mod tuning;
 
 // Re-export commonly used types
 pub use adapters::{
@@ -71,7 +90,12 @@ pub use adapters::{
 };
 pub use consensus::ConsensusEngine;
 pub use generation::{
-    ExecutionMetadata, ExecutionMode, GenerationRequest, GenerationResult, TaskType,
+    BatchMetrics, BatchResult, ExecutionMetadata, ExecutionMode, GenerationRequest,
+    GenerationResult, TaskType,
 };
+pub use ledger::{DriftAlert, DriftConfig, ModelIdentity, TrustLedger};
 pub use metric::{AileeMetric, AileeParams, AileeSample};
+pub use pool::{ConnectionPool, PoolStats, ProviderConfig, ProviderConnection, ProviderConnector};
+pub use redaction::{RedactionDetector, RedactionReport, RedactionRule, Redactor};
+pub use speculation::{SpeculationConfig, SpeculationStats};
 pub use trust::{compute_trust_scores, ConsistencyScore, SafetyChecker, TrustScores};
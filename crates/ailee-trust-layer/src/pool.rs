@@ -0,0 +1,489 @@
+//! Shared provider connections for remote model adapters
+//!
+//! A [`ConnectionPool`] keeps open [`ProviderConnection`]s (an HTTP client
+//! with its TLS session, a gRPC channel, ...) per provider so consensus
+//! rounds reuse them instead of handshaking on every call. Every
+//! [`RemoteModelAdapter`](crate::RemoteModelAdapter) attached to the pool
+//! with the same provider name shares its connections and its concurrency
+//! limit:
+//!
+//! - A checkout reuses an idle connection when one is open and younger than
+//!   the provider's idle timeout, and opens one through the
+//!   [`ProviderConnector`] otherwise.
+//! - At most `max_concurrency` calls per provider are in flight; further
+//!   calls wait for a slot.
+//! - Providers with `keep_warm` set get their idle connections pinged by
+//!   [`ConnectionPool::spawn_keep_warm`], and one connection is opened ahead
+//!   of the first call, so the first request of a round does not pay for
+//!   the handshake.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::adapters::ModelOutput;
+use super::generation::TaskType;
+
+/// Default concurrent calls per provider.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 8;
+
+/// Default time an unused connection stays open: 90 seconds, the idle
+/// timeout of common provider load balancers.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// An open connection to a model provider
+#[async_trait]
+pub trait ProviderConnection: Send + Sync {
+    /// Generate output from `model_id` over this connection
+    async fn generate(
+        &self,
+        model_id: &str,
+        prompt: &str,
+        task_type: TaskType,
+    ) -> anyhow::Result<ModelOutput>;
+
+    /// Cheap request that keeps the connection from being closed as idle
+    async fn ping(&self) -> anyhow::Result<()>;
+}
+
+/// Opens connections to providers
+#[async_trait]
+pub trait ProviderConnector: Send + Sync {
+    /// Open a connection to `provider`, including any TLS handshake
+    async fn connect(&self, provider: &str) -> anyhow::Result<Arc<dyn ProviderConnection>>;
+}
+
+/// Pooling settings for one provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    /// Calls allowed in flight at once
+    pub max_concurrency: usize,
+    /// Unused connections older than this are closed
+    pub idle_timeout: Duration,
+    /// Ping idle connections and keep one open; otherwise they lapse
+    pub keep_warm: bool,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            keep_warm: false,
+        }
+    }
+}
+
+impl ProviderConfig {
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    pub fn with_keep_warm(mut self, keep_warm: bool) -> Self {
+        self.keep_warm = keep_warm;
+        self
+    }
+}
+
+/// Connection counters for one provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolStats {
+    /// Connections opened
+    pub opened: u64,
+    /// Checkouts served by an already open connection
+    pub reused: u64,
+    /// Keep-warm pings sent
+    pub pings: u64,
+    /// Connections closed because a ping failed or they sat idle too long
+    pub closed: u64,
+    /// Connections open and unused right now
+    pub idle: usize,
+    /// Calls in flight right now
+    pub in_flight: usize,
+}
+
+struct IdleConnection {
+    connection: Arc<dyn ProviderConnection>,
+    since: Instant,
+}
+
+struct Provider {
+    config: ProviderConfig,
+    slots: Arc<Semaphore>,
+    idle: Vec<IdleConnection>,
+    stats: PoolStats,
+}
+
+impl Provider {
+    fn new(config: ProviderConfig) -> Self {
+        Self {
+            config,
+            slots: Arc::new(Semaphore::new(config.max_concurrency.max(1))),
+            idle: Vec::new(),
+            stats: PoolStats::default(),
+        }
+    }
+
+    /// Close idle connections past the idle timeout.
+    fn evict_stale(&mut self, now: Instant) {
+        let timeout = self.config.idle_timeout;
+        let before = self.idle.len();
+        self.idle
+            .retain(|idle| now.duration_since(idle.since) < timeout);
+        self.stats.closed += (before - self.idle.len()) as u64;
+    }
+}
+
+/// Connections and concurrency limits shared by remote adapters
+pub struct ConnectionPool {
+    connector: Arc<dyn ProviderConnector>,
+    default_config: ProviderConfig,
+    providers: Mutex<HashMap<String, Provider>>,
+}
+
+impl std::fmt::Debug for ConnectionPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectionPool")
+            .field("default_config", &self.default_config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ConnectionPool {
+    /// Pool opening connections through `connector`, with default settings
+    /// for every provider.
+    pub fn new(connector: impl ProviderConnector + 'static) -> Self {
+        Self {
+            connector: Arc::new(connector),
+            default_config: ProviderConfig::default(),
+            providers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Settings for providers without their own.
+    pub fn with_default_config(mut self, config: ProviderConfig) -> Self {
+        self.default_config = config;
+        self
+    }
+
+    /// Settings for one provider.
+    pub fn with_provider(self, provider: impl Into<String>, config: ProviderConfig) -> Self {
+        self.providers
+            .lock()
+            .unwrap()
+            .insert(provider.into(), Provider::new(config));
+        self
+    }
+
+    /// Check out a connection to `provider`, waiting for a free slot first.
+    pub async fn checkout(self: &Arc<Self>, provider: &str) -> anyhow::Result<PooledConnection> {
+        let slots = self.with_provider_entry(provider, |entry| entry.slots.clone());
+        let permit = slots
+            .acquire_owned()
+            .await
+            .map_err(|_| anyhow::anyhow!("connection pool for {provider} is closed"))?;
+
+        let reused = self.with_provider_entry(provider, |entry| {
+            entry.evict_stale(Instant::now());
+            let idle = entry.idle.pop()?;
+            entry.stats.reused += 1;
+            Some(idle.connection)
+        });
+        let connection = match reused {
+            Some(connection) => connection,
+            None => {
+                let connection = self.connector.connect(provider).await?;
+                self.with_provider_entry(provider, |entry| entry.stats.opened += 1);
+                connection
+            }
+        };
+
+        Ok(PooledConnection {
+            connection: Some(connection),
+            provider: provider.to_string(),
+            pool: Arc::clone(self),
+            _permit: permit,
+        })
+    }
+
+    /// Ping idle connections of keep-warm providers, closing those that
+    /// fail or sat idle too long, and open one for a keep-warm provider
+    /// with none.  Returns the number of pings sent.
+    pub async fn keep_warm(&self) -> usize {
+        let providers: Vec<(String, Vec<IdleConnection>)> = {
+            let mut providers = self.providers.lock().unwrap();
+            let now = Instant::now();
+            providers
+                .iter_mut()
+                .filter(|(_, entry)| entry.config.keep_warm)
+                .map(|(name, entry)| {
+                    entry.evict_stale(now);
+                    (name.clone(), std::mem::take(&mut entry.idle))
+                })
+                .collect()
+        };
+
+        let mut pinged = 0;
+        for (provider, idle) in providers {
+            let pings = idle.len() as u64;
+            let mut alive = Vec::with_capacity(idle.len());
+            for idle in idle {
+                match idle.connection.ping().await {
+                    Ok(()) => alive.push(IdleConnection {
+                        connection: idle.connection,
+                        since: Instant::now(),
+                    }),
+                    Err(err) => {
+                        tracing::debug!(%provider, "Closing connection after failed ping: {err:#}");
+                    }
+                }
+            }
+            let closed = pings - alive.len() as u64;
+
+            let mut opened = 0;
+            if alive.is_empty() {
+                match self.connector.connect(&provider).await {
+                    Ok(connection) => {
+                        alive.push(IdleConnection {
+                            connection,
+                            since: Instant::now(),
+                        });
+                        opened = 1;
+                    }
+                    Err(err) => {
+                        tracing::warn!(%provider, "Failed to open warm connection: {err:#}");
+                    }
+                }
+            }
+
+            self.with_provider_entry(&provider, |entry| {
+                entry.idle.extend(alive);
+                entry.stats.pings += pings;
+                entry.stats.closed += closed;
+                entry.stats.opened += opened;
+            });
+            pinged += pings as usize;
+        }
+        pinged
+    }
+
+    /// Run [`Self::keep_warm`] every `interval` until the pool is dropped.
+    pub fn spawn_keep_warm(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let pool = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(pool) = pool.upgrade() else {
+                    return;
+                };
+                pool.keep_warm().await;
+            }
+        })
+    }
+
+    /// Counters for `provider`.
+    pub fn stats(&self, provider: &str) -> PoolStats {
+        self.providers
+            .lock()
+            .unwrap()
+            .get(provider)
+            .map(|entry| PoolStats {
+                idle: entry.idle.len(),
+                in_flight: entry.config.max_concurrency.max(1) - entry.slots.available_permits(),
+                ..entry.stats
+            })
+            .unwrap_or_default()
+    }
+
+    fn with_provider_entry<T>(&self, provider: &str, f: impl FnOnce(&mut Provider) -> T) -> T {
+        let mut providers = self.providers.lock().unwrap();
+        let entry = providers
+            .entry(provider.to_string())
+            .or_insert_with(|| Provider::new(self.default_config));
+        f(entry)
+    }
+}
+
+/// A checked-out connection; returned to the pool when dropped
+pub struct PooledConnection {
+    connection: Option<Arc<dyn ProviderConnection>>,
+    provider: String,
+    pool: Arc<ConnectionPool>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledConnection {
+    /// Drop the connection instead of returning it, e.g. after a transport
+    /// error left it unusable.
+    pub fn discard(mut self) {
+        self.connection = None;
+        self.pool
+            .with_provider_entry(&self.provider, |entry| entry.stats.closed += 1);
+    }
+}
+
+impl std::ops::Deref for PooledConnection {
+    type Target = dyn ProviderConnection;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection
+            .as_deref()
+            .expect("connection is present until dropped")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.with_provider_entry(&self.provider, |entry| {
+                entry.idle.push(IdleConnection {
+                    connection,
+                    since: Instant::now(),
+                })
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::{ModelAdapter, RemoteModelAdapter};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Connector whose connections echo the prompt after `delay` and record
+    /// how many calls were in flight at once.
+    #[derive(Default)]
+    struct EchoConnector {
+        delay: Duration,
+        failing_pings: Arc<AtomicBool>,
+        in_flight: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    struct EchoConnection {
+        delay: Duration,
+        failing_pings: Arc<AtomicBool>,
+        in_flight: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl ProviderConnector for EchoConnector {
+        async fn connect(&self, _provider: &str) -> anyhow::Result<Arc<dyn ProviderConnection>> {
+            Ok(Arc::new(EchoConnection {
+                delay: self.delay,
+                failing_pings: self.failing_pings.clone(),
+                in_flight: self.in_flight.clone(),
+                peak: self.peak.clone(),
+            }))
+        }
+    }
+
+    #[async_trait]
+    impl ProviderConnection for EchoConnection {
+        async fn generate(
+            &self,
+            model_id: &str,
+            prompt: &str,
+            _task_type: TaskType,
+        ) -> anyhow::Result<ModelOutput> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(ModelOutput::new(prompt, model_id, 0.9, 0))
+        }
+
+        async fn ping(&self) -> anyhow::Result<()> {
+            if self.failing_pings.load(Ordering::SeqCst) {
+                anyhow::bail!("connection reset");
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn adapters_of_one_provider_share_connections() {
+        let pool = Arc::new(ConnectionPool::new(EchoConnector::default()));
+        let a = RemoteModelAdapter::new("model-a", true).with_pool(pool.clone(), "acme");
+        let b = RemoteModelAdapter::new("model-b", true).with_pool(pool.clone(), "acme");
+
+        let output = a.generate("hello", TaskType::Chat).await.unwrap();
+        assert_eq!(output.text, "hello");
+        assert_eq!(output.model_id, "model-a");
+        b.generate("again", TaskType::Chat).await.unwrap();
+
+        let stats = pool.stats("acme");
+        assert_eq!((stats.opened, stats.reused, stats.idle), (1, 1, 1));
+        assert_eq!(stats.in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn provider_concurrency_is_bounded() {
+        let connector = EchoConnector {
+            delay: Duration::from_millis(20),
+            ..Default::default()
+        };
+        let peak = connector.peak.clone();
+        let pool = Arc::new(
+            ConnectionPool::new(connector)
+                .with_provider("acme", ProviderConfig::default().with_max_concurrency(2)),
+        );
+        let adapter = RemoteModelAdapter::new("model-a", true).with_pool(pool.clone(), "acme");
+
+        let calls = (0..6).map(|_| adapter.generate("hi", TaskType::Chat));
+        for output in futures::future::join_all(calls).await {
+            output.unwrap();
+        }
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.stats("acme").opened, 2);
+    }
+
+    #[tokio::test]
+    async fn keep_warm_opens_pings_and_replaces_connections() {
+        let connector = EchoConnector::default();
+        let failing_pings = connector.failing_pings.clone();
+        let pool = ConnectionPool::new(connector)
+            .with_provider("acme", ProviderConfig::default().with_keep_warm(true))
+            .with_provider("cold", ProviderConfig::default());
+
+        assert_eq!(pool.keep_warm().await, 0);
+        assert_eq!(pool.stats("acme").opened, 1);
+        assert_eq!(pool.stats("cold").opened, 0);
+
+        assert_eq!(pool.keep_warm().await, 1);
+        let stats = pool.stats("acme");
+        assert_eq!((stats.opened, stats.pings, stats.idle), (1, 1, 1));
+
+        failing_pings.store(true, Ordering::SeqCst);
+        pool.keep_warm().await;
+        let stats = pool.stats("acme");
+        assert_eq!((stats.opened, stats.closed, stats.idle), (2, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn idle_connections_past_timeout_are_closed() {
+        let pool = Arc::new(
+            ConnectionPool::new(EchoConnector::default())
+                .with_default_config(ProviderConfig::default().with_idle_timeout(Duration::ZERO)),
+        );
+
+        drop(pool.checkout("acme").await.unwrap());
+        drop(pool.checkout("acme").await.unwrap());
+
+        let stats = pool.stats("acme");
+        assert_eq!((stats.opened, stats.reused, stats.closed), (2, 0, 1));
+    }
+}
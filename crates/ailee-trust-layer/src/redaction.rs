@@ -0,0 +1,371 @@
+//! Output redaction for generation results
+//!
+//! A [`Redactor`] scrubs `final_output` and the model lineage of a
+//! [`GenerationResult`] before it is stored or logged. Spans are found by
+//! detectors: regex rules configured by the deployment, or custom
+//! [`RedactionDetector`] plugins. Every removal is recorded in a
+//! [`RedactionReport`] attached to the result, with a hash of the removed text
+//! so auditors can confirm what was scrubbed without the text itself being
+//! retained.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::ops::Range;
+
+use super::generation::GenerationResult;
+
+/// A span a detector wants removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedSpan {
+    /// Byte range within the scanned text
+    pub range: Range<usize>,
+    /// Why the span is sensitive
+    pub reason: String,
+}
+
+/// Detector plugin that finds sensitive spans in text
+pub trait RedactionDetector: Send + Sync {
+    /// Name recorded in the redaction report and the replacement marker
+    fn name(&self) -> &str;
+
+    /// Find sensitive spans in `text`. Spans must lie on char boundaries.
+    fn detect(&self, text: &str) -> Vec<DetectedSpan>;
+}
+
+/// Detector that redacts every match of a regular expression
+pub struct RegexDetector {
+    name: String,
+    pattern: Regex,
+    reason: String,
+}
+
+impl RegexDetector {
+    /// Create a regex detector, failing if `pattern` does not compile
+    pub fn new(
+        name: impl Into<String>,
+        pattern: &str,
+        reason: impl Into<String>,
+    ) -> anyhow::Result<Self> {
+        let name = name.into();
+        let pattern = Regex::new(pattern)
+            .map_err(|e| anyhow::anyhow!("invalid redaction pattern for {}: {}", name, e))?;
+        Ok(Self {
+            name,
+            pattern,
+            reason: reason.into(),
+        })
+    }
+}
+
+impl RedactionDetector for RegexDetector {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn detect(&self, text: &str) -> Vec<DetectedSpan> {
+        self.pattern
+            .find_iter(text)
+            .filter(|m| !m.range().is_empty())
+            .map(|m| DetectedSpan {
+                range: m.range(),
+                reason: self.reason.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A regex rule as it appears in deployment configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionRule {
+    /// Detector name
+    pub name: String,
+    /// Regular expression to redact
+    pub pattern: String,
+    /// Why matches are sensitive
+    pub reason: String,
+}
+
+/// One removal recorded for auditors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedactionEntry {
+    /// Result field the span was removed from, e.g. `final_output` or `model_lineage[1]`
+    pub field: String,
+    /// Detector that found the span
+    pub detector: String,
+    /// Why the span was removed
+    pub reason: String,
+    /// Byte offset of the span in the original field
+    pub start: usize,
+    /// Length in bytes of the removed span
+    pub length: usize,
+    /// SHA3-256 of the removed text
+    pub removed_hash: String,
+}
+
+/// Redactions applied to a generation result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedactionReport {
+    /// Hash of `final_output` before redaction
+    pub original_output_hash: String,
+    /// Every span removed, in field order
+    pub entries: Vec<RedactionEntry>,
+}
+
+impl RedactionReport {
+    /// Whether anything was removed
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Applies detectors to generation results
+#[derive(Default)]
+pub struct Redactor {
+    detectors: Vec<Box<dyn RedactionDetector>>,
+}
+
+impl std::fmt::Debug for Redactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Redactor")
+            .field(
+                "detectors",
+                &self.detectors.iter().map(|d| d.name()).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Redactor {
+    /// Create a redactor with no detectors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a redactor from configured regex rules
+    pub fn from_rules(rules: &[RedactionRule]) -> anyhow::Result<Self> {
+        rules.iter().try_fold(Self::new(), |redactor, rule| {
+            redactor.with_regex(&rule.name, &rule.pattern, &rule.reason)
+        })
+    }
+
+    /// Add a regex detector
+    pub fn with_regex(
+        self,
+        name: impl Into<String>,
+        pattern: &str,
+        reason: impl Into<String>,
+    ) -> anyhow::Result<Self> {
+        Ok(self.with_detector(RegexDetector::new(name, pattern, reason)?))
+    }
+
+    /// Add a detector plugin
+    pub fn with_detector(mut self, detector: impl RedactionDetector + 'static) -> Self {
+        self.detectors.push(Box::new(detector));
+        self
+    }
+
+    /// Add detectors for email addresses, bearer tokens and card-like numbers
+    pub fn with_default_detectors(self) -> Self {
+        self.with_regex(
+            "email",
+            r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+            "email address",
+        )
+        .and_then(|r| {
+            r.with_regex(
+                "bearer_token",
+                r"(?i)bearer\s+[A-Za-z0-9\-._~+/]+=*",
+                "authorization credential",
+            )
+        })
+        .and_then(|r| {
+            r.with_regex(
+                "card_number",
+                r"\b(?:\d[ -]?){12,18}\d\b",
+                "payment card number",
+            )
+        })
+        .expect("built-in redaction patterns compile")
+    }
+
+    /// Number of configured detectors
+    pub fn detector_count(&self) -> usize {
+        self.detectors.len()
+    }
+
+    /// Redact `text`, returning the scrubbed text and the entries removed.
+    ///
+    /// Overlapping spans are merged; the detector that found the earliest
+    /// span names the merged removal.
+    pub fn redact_text(&self, field: &str, text: &str) -> (String, Vec<RedactionEntry>) {
+        let mut spans: Vec<(&str, DetectedSpan)> = self
+            .detectors
+            .iter()
+            .flat_map(|detector| {
+                detector
+                    .detect(text)
+                    .into_iter()
+                    .filter(|span| {
+                        span.range.start < span.range.end
+                            && span.range.end <= text.len()
+                            && text.is_char_boundary(span.range.start)
+                            && text.is_char_boundary(span.range.end)
+                    })
+                    .map(move |span| (detector.name(), span))
+            })
+            .collect();
+        spans.sort_by_key(|(_, span)| (span.range.start, std::cmp::Reverse(span.range.end)));
+
+        let mut merged: Vec<(&str, DetectedSpan)> = Vec::new();
+        for (name, span) in spans {
+            match merged.last_mut() {
+                Some((_, last)) if span.range.start < last.range.end => {
+                    last.range.end = last.range.end.max(span.range.end);
+                }
+                _ => merged.push((name, span)),
+            }
+        }
+
+        let mut redacted = String::with_capacity(text.len());
+        let mut entries = Vec::with_capacity(merged.len());
+        let mut cursor = 0;
+        for (name, span) in merged {
+            redacted.push_str(&text[cursor..span.range.start]);
+            redacted.push_str(&format!("[REDACTED:{}]", name));
+            entries.push(RedactionEntry {
+                field: field.to_string(),
+                detector: name.to_string(),
+                reason: span.reason,
+                start: span.range.start,
+                length: span.range.len(),
+                removed_hash: sha3_hex(&text[span.range.clone()]),
+            });
+            cursor = span.range.end;
+        }
+        redacted.push_str(&text[cursor..]);
+        (redacted, entries)
+    }
+
+    /// Scrub `final_output` and `model_lineage`, recompute the output hash
+    /// and attach the report. Results with nothing to remove still get an
+    /// empty report so auditors can tell they were scanned.
+    pub fn redact_result(&self, mut result: GenerationResult) -> GenerationResult {
+        let original_output_hash = result.output_hash.clone();
+        let (final_output, mut entries) = self.redact_text("final_output", &result.final_output);
+
+        for (index, model_id) in result.model_lineage.iter_mut().enumerate() {
+            let (redacted, lineage_entries) =
+                self.redact_text(&format!("model_lineage[{}]", index), model_id);
+            *model_id = redacted;
+            entries.extend(lineage_entries);
+        }
+
+        result.set_final_output(final_output);
+        result.redaction = Some(RedactionReport {
+            original_output_hash,
+            entries,
+        });
+        result
+    }
+}
+
+fn sha3_hex(text: &str) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(text.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::ExecutionMetadata;
+
+    struct KeywordDetector(&'static str);
+
+    impl RedactionDetector for KeywordDetector {
+        fn name(&self) -> &str {
+            "keyword"
+        }
+
+        fn detect(&self, text: &str) -> Vec<DetectedSpan> {
+            text.match_indices(self.0)
+                .map(|(start, word)| DetectedSpan {
+                    range: start..start + word.len(),
+                    reason: format!("contains {}", self.0),
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_redact_text_with_regex_and_plugin_detectors() {
+        let redactor = Redactor::new()
+            .with_default_detectors()
+            .with_detector(KeywordDetector("project-x"));
+
+        let (text, entries) = redactor.redact_text(
+            "final_output",
+            "mail alice@example.com about project-x, card 4111 1111 1111 1111",
+        );
+        assert_eq!(
+            text,
+            "mail [REDACTED:email] about [REDACTED:keyword], card [REDACTED:card_number]"
+        );
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].detector, "email");
+        assert_eq!(entries[0].start, 5);
+        assert_eq!(entries[0].length, "alice@example.com".len());
+        assert_eq!(entries[0].removed_hash, sha3_hex("alice@example.com"));
+        assert_eq!(entries[1].reason, "contains project-x");
+    }
+
+    #[test]
+    fn test_overlapping_spans_are_merged() {
+        let redactor = Redactor::new()
+            .with_regex("word", r"secret\w*", "secret word")
+            .unwrap()
+            .with_regex("pair", r"top secret", "classified phrase")
+            .unwrap();
+
+        let (text, entries) = redactor.redact_text("final_output", "a top secrets list");
+        assert_eq!(text, "a [REDACTED:pair] list");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].length, "top secrets".len());
+    }
+
+    #[test]
+    fn test_redact_result_rehashes_and_reports() {
+        let result = GenerationResult::new(
+            "token: Bearer abc.def".to_string(),
+            0.9,
+            vec!["local-a".to_string(), "user@corp.example".to_string()],
+            ExecutionMetadata::new(2, 2, true, 5),
+            "input".to_string(),
+        );
+        let original_hash = result.output_hash.clone();
+
+        let redacted = Redactor::new()
+            .with_default_detectors()
+            .redact_result(result);
+        assert_eq!(redacted.final_output, "token: [REDACTED:bearer_token]");
+        assert_eq!(redacted.model_lineage[1], "[REDACTED:email]");
+        assert!(redacted.verify_hash());
+
+        let report = redacted.redaction.unwrap();
+        assert_eq!(report.original_output_hash, original_hash);
+        let fields: Vec<_> = report.entries.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["final_output", "model_lineage[1]"]);
+    }
+
+    #[test]
+    fn test_invalid_rule_is_rejected() {
+        let rules = vec![RedactionRule {
+            name: "broken".to_string(),
+            pattern: "(".to_string(),
+            reason: "bad".to_string(),
+        }];
+        let err = Redactor::from_rules(&rules).unwrap_err();
+        assert!(err.to_string().contains("broken"));
+    }
+}
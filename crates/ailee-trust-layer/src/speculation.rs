@@ -0,0 +1,190 @@
+//! Speculative consensus
+//!
+//! With speculation enabled, [`ConsensusEngine`](crate::ConsensusEngine)
+//! returns as soon as the outputs received so far agree strongly, instead of
+//! waiting for the slowest adapter:
+//!
+//! - At least `min_agreeing` outputs (and the engine's `min_models`) must be
+//!   in, with one of them at least `min_consistency` similar to
+//!   `min_agreeing - 1` of the others, and the best output must meet the
+//!   request's trust threshold.
+//! - With a `time_box`, the round also returns once that much time has
+//!   passed and `min_models` outputs are in, agreeing or not.
+//!
+//! Adapters still running when a round returns early either finish in the
+//! background, where their outputs are compared against the returned one
+//! and counted in [`SpeculationStats`] so the thresholds can be calibrated,
+//! or are cancelled.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::adapters::ModelOutput;
+use super::trust::ConsistencyScore;
+
+/// Default outputs that must agree before a round returns early.
+pub const DEFAULT_MIN_AGREEING: usize = 2;
+
+/// Default similarity at which two outputs count as agreeing.
+pub const DEFAULT_MIN_CONSISTENCY: f64 = 0.9;
+
+/// When a consensus round may return before every adapter has answered
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeculationConfig {
+    /// Outputs that must agree with each other
+    pub min_agreeing: usize,
+    /// Similarity (0.0 - 1.0) at which two outputs agree
+    pub min_consistency: f64,
+    /// Return with whatever `min_models` outputs are in after this long
+    pub time_box: Option<Duration>,
+    /// Let outstanding adapters finish and record how their outputs compare;
+    /// otherwise they are cancelled
+    pub finish_in_background: bool,
+}
+
+impl Default for SpeculationConfig {
+    fn default() -> Self {
+        Self {
+            min_agreeing: DEFAULT_MIN_AGREEING,
+            min_consistency: DEFAULT_MIN_CONSISTENCY,
+            time_box: None,
+            finish_in_background: true,
+        }
+    }
+}
+
+impl SpeculationConfig {
+    pub fn with_min_agreeing(mut self, min_agreeing: usize) -> Self {
+        self.min_agreeing = min_agreeing.max(2);
+        self
+    }
+
+    pub fn with_min_consistency(mut self, min_consistency: f64) -> Self {
+        self.min_consistency = min_consistency.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_time_box(mut self, time_box: Duration) -> Self {
+        self.time_box = Some(time_box);
+        self
+    }
+
+    pub fn with_finish_in_background(mut self, finish_in_background: bool) -> Self {
+        self.finish_in_background = finish_in_background;
+        self
+    }
+
+    /// Whether `outputs` agree strongly enough to stop waiting
+    pub fn agreement_reached(&self, outputs: &[ModelOutput]) -> bool {
+        outputs.len() >= self.min_agreeing
+            && largest_agreement(outputs, self.min_consistency) >= self.min_agreeing
+    }
+}
+
+/// Size of the largest group of outputs that all agree with one of them
+fn largest_agreement(outputs: &[ModelOutput], min_consistency: f64) -> usize {
+    outputs
+        .iter()
+        .enumerate()
+        .map(|(i, output)| {
+            1 + outputs
+                .iter()
+                .enumerate()
+                .filter(|&(j, peer)| {
+                    j != i
+                        && ConsistencyScore::compute_similarity(&output.text, &peer.text)
+                            >= min_consistency
+                })
+                .count()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Why a speculative round stopped waiting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EarlyReturn {
+    Agreement,
+    TimeBox,
+}
+
+/// Speculation counters of one engine (and its clones)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpeculationStats {
+    /// Rounds run with speculation enabled
+    pub rounds: u64,
+    /// Rounds that returned early on agreement
+    pub early_agreements: u64,
+    /// Rounds that returned early when the time box ran out
+    pub time_boxed: u64,
+    /// Outstanding adapter calls cancelled after an early return
+    pub cancelled: u64,
+    /// Outputs that arrived after their round returned
+    pub late_outputs: u64,
+    /// Late outputs less than `min_consistency` similar to the returned output
+    pub late_divergences: u64,
+    /// Late adapter calls that failed or timed out
+    pub late_failures: u64,
+    /// Sum of late outputs' similarity to the returned output
+    pub late_similarity_total: f64,
+}
+
+impl SpeculationStats {
+    /// Share of late outputs that diverged from the returned output
+    pub fn late_divergence_rate(&self) -> Option<f64> {
+        (self.late_outputs > 0).then(|| self.late_divergences as f64 / self.late_outputs as f64)
+    }
+
+    /// Mean similarity of late outputs to the returned output
+    pub fn mean_late_similarity(&self) -> Option<f64> {
+        (self.late_outputs > 0).then(|| self.late_similarity_total / self.late_outputs as f64)
+    }
+
+    pub(crate) fn record_return(&mut self, early: Option<EarlyReturn>) {
+        self.rounds += 1;
+        match early {
+            Some(EarlyReturn::Agreement) => self.early_agreements += 1,
+            Some(EarlyReturn::TimeBox) => self.time_boxed += 1,
+            None => {}
+        }
+    }
+
+    pub(crate) fn record_late_output(&mut self, similarity: f64, diverged: bool) {
+        self.late_outputs += 1;
+        self.late_similarity_total += similarity;
+        if diverged {
+            self.late_divergences += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::fixtures;
+
+    #[test]
+    fn agreement_needs_enough_similar_outputs() {
+        let config = SpeculationConfig::default();
+        let same = fixtures::output("a", "the answer is 4", 0.9);
+        let also_same = fixtures::output("b", "the answer is 4", 0.9);
+        let different = fixtures::output("c", "it is five", 0.9);
+
+        assert!(!config.agreement_reached(std::slice::from_ref(&same)));
+        assert!(!config.agreement_reached(&[same.clone(), different.clone()]));
+        assert!(config.agreement_reached(&[same.clone(), different, also_same.clone()]));
+        assert!(!config
+            .with_min_agreeing(3)
+            .agreement_reached(&[same, also_same]));
+    }
+
+    #[test]
+    fn late_outputs_feed_divergence_rate() {
+        let mut stats = SpeculationStats::default();
+        assert_eq!(stats.late_divergence_rate(), None);
+        stats.record_late_output(1.0, false);
+        stats.record_late_output(0.2, true);
+        assert_eq!(stats.late_divergence_rate(), Some(0.5));
+        assert_eq!(stats.mean_late_similarity(), Some(0.6));
+    }
+}
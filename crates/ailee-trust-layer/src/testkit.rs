@@ -0,0 +1,411 @@
+//! Test kit for code built on the trust layer
+//!
+//! Enabled with the `testkit` feature, typically from `[dev-dependencies]`:
+//!
+//! ```toml
+//! ailee-trust-layer = { path = "../ailee-trust-layer", features = ["testkit"] }
+//! ```
+//!
+//! - [`MockModelAdapter`] plays back scripted outputs, delays and failures
+//!   and records the prompts it was given.
+//! - [`fixtures`] builds requests and model outputs whose trust scores are
+//!   fixed, so consensus outcomes can be asserted exactly.
+//! - The `assert_*` helpers check lineage and result integrity with messages
+//!   that show what the result actually held.
+//!
+//! ```rust
+//! use ailee_trust_layer::testkit::{assert_lineage, fixtures, MockModelAdapter};
+//! use ailee_trust_layer::{ConsensusEngine, ExecutionMode, ModelAdapter};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let flaky = MockModelAdapter::new("flaky").fail("rate limited");
+//! let adapters: Vec<Box<dyn ModelAdapter>> = vec![
+//!     Box::new(MockModelAdapter::new("a").respond("the answer is 4")),
+//!     Box::new(MockModelAdapter::new("b").respond("the answer is 4")),
+//!     Box::new(flaky.clone()),
+//! ];
+//!
+//! let request = fixtures::request("2 + 2", ExecutionMode::Local);
+//! let result = ConsensusEngine::new(2).execute(&request, adapters).await?;
+//!
+//! assert_lineage(&result, &["a", "b"]);
+//! assert_eq!(flaky.calls(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::adapters::{ModelAdapter, ModelLocality, ModelOutput};
+use super::generation::{GenerationRequest, GenerationResult, TaskType};
+
+/// Confidence of scripted outputs unless set otherwise.
+pub const DEFAULT_MOCK_CONFIDENCE: f64 = 0.9;
+
+/// One scripted reply of a [`MockModelAdapter`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockStep {
+    /// Return `text` with `confidence`.
+    Output { text: String, confidence: f64 },
+    /// Fail with `message`.
+    Fail(String),
+}
+
+#[derive(Debug, Clone)]
+struct ScriptedStep {
+    step: MockStep,
+    delay: Duration,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    script: Mutex<VecDeque<ScriptedStep>>,
+    prompts: Mutex<Vec<String>>,
+    calls: AtomicUsize,
+}
+
+/// Model adapter that replays a script instead of calling a model.
+///
+/// Each `generate` call takes the next scripted step; the last step repeats
+/// once the script runs out.  An adapter with no script echoes the prompt.
+/// Clones share the script and call log, so keep a clone to inspect an
+/// adapter after boxing it for the engine.
+///
+/// `execution_time_ms` reports the scripted delay rather than measured time,
+/// keeping results identical between runs.
+#[derive(Debug, Clone)]
+pub struct MockModelAdapter {
+    model_id: String,
+    locality: ModelLocality,
+    available: bool,
+    confidence: f64,
+    delay: Duration,
+    model_version: Option<String>,
+    state: Arc<MockState>,
+}
+
+impl MockModelAdapter {
+    /// Local, available adapter with an empty script.
+    pub fn new(model_id: impl Into<String>) -> Self {
+        Self {
+            model_id: model_id.into(),
+            locality: ModelLocality::Local,
+            available: true,
+            confidence: DEFAULT_MOCK_CONFIDENCE,
+            delay: Duration::ZERO,
+            model_version: None,
+            state: Arc::default(),
+        }
+    }
+
+    /// Remote, available adapter with an empty script.
+    pub fn remote(model_id: impl Into<String>) -> Self {
+        Self::new(model_id).with_locality(ModelLocality::Remote)
+    }
+
+    pub fn with_locality(mut self, locality: ModelLocality) -> Self {
+        self.locality = locality;
+        self
+    }
+
+    /// Confidence of outputs scripted after this call.
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// Delay of steps scripted after this call.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Report `model_version` on outputs.  Clones share the script, so a
+    /// clone with another version simulates a provider version bump.
+    pub fn with_model_version(mut self, model_version: impl Into<String>) -> Self {
+        self.model_version = Some(model_version.into());
+        self
+    }
+
+    /// Report the adapter as unavailable, as an offline remote model would.
+    pub fn unavailable(mut self) -> Self {
+        self.available = false;
+        self
+    }
+
+    /// Script an output.
+    pub fn respond(self, text: impl Into<String>) -> Self {
+        let confidence = self.confidence;
+        self.then(MockStep::Output {
+            text: text.into(),
+            confidence,
+        })
+    }
+
+    /// Script a failure.
+    pub fn fail(self, message: impl Into<String>) -> Self {
+        self.then(MockStep::Fail(message.into()))
+    }
+
+    /// Script `step` with the current delay.
+    pub fn then(self, step: MockStep) -> Self {
+        self.state.script.lock().unwrap().push_back(ScriptedStep {
+            step,
+            delay: self.delay,
+        });
+        self
+    }
+
+    /// Number of `generate` calls so far, across clones.
+    pub fn calls(&self) -> usize {
+        self.state.calls.load(Ordering::SeqCst)
+    }
+
+    /// Prompts passed to `generate`, in call order.
+    pub fn prompts(&self) -> Vec<String> {
+        self.state.prompts.lock().unwrap().clone()
+    }
+
+    fn next_step(&self, prompt: &str) -> ScriptedStep {
+        let mut script = self.state.script.lock().unwrap();
+        let step = if script.len() > 1 {
+            script.pop_front()
+        } else {
+            script.front().cloned()
+        };
+        step.unwrap_or_else(|| ScriptedStep {
+            step: MockStep::Output {
+                text: prompt.to_string(),
+                confidence: self.confidence,
+            },
+            delay: self.delay,
+        })
+    }
+}
+
+#[async_trait]
+impl ModelAdapter for MockModelAdapter {
+    async fn generate(&self, prompt: &str, _task_type: TaskType) -> anyhow::Result<ModelOutput> {
+        self.state.calls.fetch_add(1, Ordering::SeqCst);
+        self.state.prompts.lock().unwrap().push(prompt.to_string());
+
+        let ScriptedStep { step, delay } = self.next_step(prompt);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        match step {
+            MockStep::Output { text, confidence } => {
+                let output = ModelOutput::new(
+                    text,
+                    self.model_id.clone(),
+                    confidence,
+                    delay.as_millis() as u64,
+                );
+                Ok(match &self.model_version {
+                    Some(version) => output.with_model_version(version.clone()),
+                    None => output,
+                })
+            }
+            MockStep::Fail(message) => anyhow::bail!("{}: {}", self.model_id, message),
+        }
+    }
+
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn locality(&self) -> ModelLocality {
+        self.locality
+    }
+
+    async fn is_available(&self) -> bool {
+        self.available
+    }
+}
+
+/// Requests and outputs with known trust scores.
+///
+/// Texts avoid the words [`crate::SafetyChecker`] flags, so safety is `1.0`
+/// and each output's overall score is `0.4 * confidence + 0.3 +
+/// 0.3 * consistency`.
+pub mod fixtures {
+    use super::*;
+    use crate::generation::ExecutionMode;
+    use crate::trust::{compute_trust_scores, TrustScores};
+
+    /// Chat request with a 0.5 trust threshold that allows offline execution.
+    pub fn request(prompt: impl Into<String>, mode: ExecutionMode) -> GenerationRequest {
+        GenerationRequest::new(prompt, TaskType::Chat, 0.5, mode, true)
+    }
+
+    /// Output of `model_id` with zero execution time.
+    pub fn output(model_id: &str, text: &str, confidence: f64) -> ModelOutput {
+        ModelOutput::new(text, model_id, confidence, 0)
+    }
+
+    /// `n` outputs from `model-0`.. with the same text, so each has
+    /// consistency `1.0` when `n > 1`.
+    pub fn unanimous(n: usize, text: &str, confidence: f64) -> Vec<ModelOutput> {
+        (0..n)
+            .map(|i| output(&format!("model-{i}"), text, confidence))
+            .collect()
+    }
+
+    /// `majority` outputs of one text followed by `minority` of another,
+    /// sharing no words, so majority outputs outscore minority ones at equal
+    /// confidence.
+    pub fn split(majority: usize, minority: usize, confidence: f64) -> Vec<ModelOutput> {
+        let mut outputs = unanimous(majority, "paris is the capital", confidence);
+        outputs.extend(
+            (0..minority).map(|i| output(&format!("dissent-{i}"), "lyon obviously", confidence)),
+        );
+        outputs
+    }
+
+    /// Trust scores of each output against the others, as consensus computes
+    /// them.
+    pub fn scores(outputs: &[ModelOutput]) -> Vec<TrustScores> {
+        outputs
+            .iter()
+            .map(|output| {
+                let peers: Vec<ModelOutput> = outputs
+                    .iter()
+                    .filter(|peer| peer.model_id != output.model_id)
+                    .cloned()
+                    .collect();
+                compute_trust_scores(output, &peers)
+            })
+            .collect()
+    }
+
+    /// Adapters replaying `outputs`, one per output.
+    pub fn adapters(outputs: &[ModelOutput]) -> Vec<Box<dyn ModelAdapter>> {
+        outputs
+            .iter()
+            .map(|output| {
+                Box::new(
+                    MockModelAdapter::new(output.model_id.clone())
+                        .with_confidence(output.confidence)
+                        .respond(output.text.clone()),
+                ) as Box<dyn ModelAdapter>
+            })
+            .collect()
+    }
+}
+
+/// Assert `result` lists exactly `expected` models, in order.
+#[track_caller]
+pub fn assert_lineage(result: &GenerationResult, expected: &[&str]) {
+    let actual: Vec<&str> = result.model_lineage.iter().map(String::as_str).collect();
+    assert_eq!(actual, expected, "unexpected model lineage");
+}
+
+/// Assert `model_id` contributed to `result`.
+#[track_caller]
+pub fn assert_lineage_contains(result: &GenerationResult, model_id: &str) {
+    assert!(
+        result.model_lineage.iter().any(|id| id == model_id),
+        "{model_id} missing from lineage {:?}",
+        result.model_lineage
+    );
+}
+
+/// Assert `model_id` did not contribute to `result`.
+#[track_caller]
+pub fn assert_lineage_excludes(result: &GenerationResult, model_id: &str) {
+    assert!(
+        !result.model_lineage.iter().any(|id| id == model_id),
+        "{model_id} unexpectedly in lineage {:?}",
+        result.model_lineage
+    );
+}
+
+/// Assert `result` answers `request`, its output hash matches, and its
+/// metadata agrees with its lineage.
+#[track_caller]
+pub fn assert_result_integrity(result: &GenerationResult, request: &GenerationRequest) {
+    assert_eq!(
+        result.input_hash,
+        request.hash(),
+        "result was produced for a different request"
+    );
+    assert!(result.verify_hash(), "output hash does not match output");
+    assert_eq!(
+        result.execution_metadata.models_succeeded,
+        result.model_lineage.len(),
+        "models_succeeded disagrees with lineage {:?}",
+        result.model_lineage
+    );
+    assert!(
+        result.execution_metadata.models_consulted >= result.model_lineage.len(),
+        "fewer models consulted than contributed"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConsensusEngine, ExecutionMode};
+
+    #[tokio::test]
+    async fn test_mock_adapter_replays_script() {
+        let adapter = MockModelAdapter::new("m")
+            .respond("first")
+            .fail("boom")
+            .with_confidence(0.4)
+            .respond("last");
+
+        let first = adapter.generate("p1", TaskType::Chat).await.unwrap();
+        assert_eq!(first.text, "first");
+        assert_eq!(first.confidence, DEFAULT_MOCK_CONFIDENCE);
+        let err = adapter.generate("p2", TaskType::Chat).await.unwrap_err();
+        assert!(err.to_string().contains("boom"));
+        for _ in 0..2 {
+            let last = adapter.generate("p3", TaskType::Chat).await.unwrap();
+            assert_eq!((last.text.as_str(), last.confidence), ("last", 0.4));
+        }
+
+        assert_eq!(adapter.calls(), 4);
+        assert_eq!(adapter.prompts(), vec!["p1", "p2", "p3", "p3"]);
+        let echo = MockModelAdapter::new("echo");
+        assert_eq!(
+            echo.generate("hi", TaskType::Code).await.unwrap().text,
+            "hi"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consensus_with_mocks_and_fixtures() {
+        let outputs = fixtures::split(2, 1, 0.9);
+        let scores = fixtures::scores(&outputs);
+        assert_eq!(scores[0].consistency_score, 0.5);
+        assert_eq!(scores[2].consistency_score, 0.0);
+
+        let slow = MockModelAdapter::remote("slow")
+            .with_delay(Duration::from_millis(200))
+            .respond("paris is the capital");
+        let mut adapters = fixtures::adapters(&outputs);
+        adapters.push(Box::new(slow.clone()));
+        adapters.push(Box::new(MockModelAdapter::new("down").unavailable()));
+
+        let request = fixtures::request("capital of france?", ExecutionMode::Hybrid);
+        let result = ConsensusEngine::new(2)
+            .with_adapter_timeout_ms(50)
+            .execute(&request, adapters)
+            .await
+            .unwrap();
+
+        assert_lineage(&result, &["model-0", "model-1", "dissent-0"]);
+        assert_lineage_excludes(&result, "slow");
+        assert_lineage_excludes(&result, "down");
+        assert_result_integrity(&result, &request);
+        assert_eq!(result.final_output, "paris is the capital");
+        assert_eq!(result.trust_score, scores[0].overall_score());
+        assert_eq!(slow.calls(), 1);
+    }
+}
@@ -0,0 +1,643 @@
+//! Offline threshold tuning by simulated adapter disagreement
+//!
+//! Replays a corpus of stored requests against synthetic model outputs with
+//! configurable disagreement and confidence noise, then reports how each
+//! trust threshold and consensus strategy would have changed acceptance and
+//! error rates compared with what the engine does today (the request's own
+//! threshold, highest-trust selection).
+//!
+//! The simulation is seeded, so a corpus and configuration always produce the
+//! same report. Outputs are scored with the same trust scoring the
+//! [`ConsensusEngine`] uses.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::adapters::ModelOutput;
+use super::consensus::ConsensusEngine;
+use super::generation::GenerationRequest;
+use super::trust::TrustScores;
+
+/// One stored request and the output it should have produced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TuningCase {
+    /// The request as it was submitted
+    pub request: GenerationRequest,
+    /// Output treated as correct; accepted outputs that differ are errors
+    pub reference_output: String,
+}
+
+impl TuningCase {
+    /// Parse a JSON Lines corpus, one case per line. Blank lines are skipped.
+    pub fn parse_corpus(corpus: &str) -> anyhow::Result<Vec<Self>> {
+        corpus
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| anyhow::anyhow!("corpus line {}: {}", index + 1, e))
+            })
+            .collect()
+    }
+}
+
+/// Synthetic noise applied to every simulated model
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NoiseLevel {
+    /// Probability (0.0 - 1.0) that a model disagrees with the reference output
+    pub disagreement: f64,
+    /// Maximum deviation (0.0 - 1.0) applied to each model's confidence
+    pub confidence_jitter: f64,
+}
+
+impl NoiseLevel {
+    /// Create a noise level, clamping both values to 0.0 - 1.0
+    pub fn new(disagreement: f64, confidence_jitter: f64) -> Self {
+        Self {
+            disagreement: disagreement.clamp(0.0, 1.0),
+            confidence_jitter: confidence_jitter.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// How the final output is chosen from the scored model outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsensusStrategy {
+    /// Highest overall trust score, as [`ConsensusEngine`] selects today
+    HighestTrust,
+    /// Highest trust among outputs shared by more than half of the models
+    Majority,
+    /// Only when every model produced the same output
+    Unanimous,
+}
+
+/// Parameters of a tuning run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TuningConfig {
+    /// Number of simulated models per request
+    pub models: usize,
+    /// Candidate trust thresholds
+    pub thresholds: Vec<f64>,
+    /// Candidate consensus strategies
+    pub strategies: Vec<ConsensusStrategy>,
+    /// Noise levels every candidate is evaluated under
+    pub noise_levels: Vec<NoiseLevel>,
+    /// Confidence reported by a model before jitter
+    pub base_confidence: f64,
+    /// Simulated rounds per request and noise level
+    pub trials: usize,
+    /// Seed for the synthetic outputs
+    pub seed: u64,
+    /// Highest error rate a recommended setting may have
+    pub max_error_rate: f64,
+}
+
+impl Default for TuningConfig {
+    fn default() -> Self {
+        Self {
+            models: 3,
+            thresholds: vec![0.5, 0.6, 0.7, 0.8, 0.9],
+            strategies: vec![
+                ConsensusStrategy::HighestTrust,
+                ConsensusStrategy::Majority,
+                ConsensusStrategy::Unanimous,
+            ],
+            noise_levels: vec![
+                NoiseLevel::new(0.0, 0.05),
+                NoiseLevel::new(0.1, 0.1),
+                NoiseLevel::new(0.3, 0.1),
+                NoiseLevel::new(0.5, 0.2),
+            ],
+            base_confidence: 0.85,
+            trials: 5,
+            seed: 0x5eed,
+            max_error_rate: 0.05,
+        }
+    }
+}
+
+impl TuningConfig {
+    /// Reject configurations that cannot produce a meaningful report
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.models == 0 {
+            anyhow::bail!("models must be at least 1");
+        }
+        if self.trials == 0 {
+            anyhow::bail!("trials must be at least 1");
+        }
+        if self.thresholds.is_empty() || self.strategies.is_empty() || self.noise_levels.is_empty()
+        {
+            anyhow::bail!("thresholds, strategies and noise_levels must not be empty");
+        }
+        if let Some(threshold) = self.thresholds.iter().find(|t| !(0.0..=1.0).contains(*t)) {
+            anyhow::bail!("threshold {} is outside 0.0 - 1.0", threshold);
+        }
+        if !(0.0..=1.0).contains(&self.base_confidence) {
+            anyhow::bail!("base_confidence must be within 0.0 - 1.0");
+        }
+        if !(0.0..=1.0).contains(&self.max_error_rate) {
+            anyhow::bail!("max_error_rate must be within 0.0 - 1.0");
+        }
+        Ok(())
+    }
+}
+
+/// Threshold used by a scenario
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind", content = "value")]
+pub enum ThresholdChoice {
+    /// Each request's own `trust_threshold`
+    PerRequest,
+    /// One threshold for every request
+    Fixed(f64),
+}
+
+impl ThresholdChoice {
+    fn for_request(self, request: &GenerationRequest) -> f64 {
+        match self {
+            Self::PerRequest => request.trust_threshold,
+            Self::Fixed(threshold) => threshold,
+        }
+    }
+}
+
+/// Outcome of one threshold and strategy under one noise level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioResult {
+    /// Threshold evaluated
+    pub threshold: ThresholdChoice,
+    /// Strategy evaluated
+    pub strategy: ConsensusStrategy,
+    /// Simulated rounds
+    pub rounds: usize,
+    /// Share of rounds whose selected output met the threshold
+    pub acceptance_rate: f64,
+    /// Share of accepted outputs that differ from the reference output
+    pub error_rate: f64,
+    /// Acceptance rate minus the baseline's
+    pub acceptance_delta: f64,
+    /// Error rate minus the baseline's
+    pub error_delta: f64,
+}
+
+/// All scenarios evaluated under one noise level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoiseLevelReport {
+    /// Noise applied
+    pub noise: NoiseLevel,
+    /// Per-request thresholds with highest-trust selection
+    pub baseline: ScenarioResult,
+    /// One entry per candidate threshold and strategy
+    pub scenarios: Vec<ScenarioResult>,
+    /// Best candidate at this noise level, if any meets the error bound
+    pub recommended: Option<ScenarioResult>,
+}
+
+/// Setting recommended across every noise level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recommendation {
+    /// Recommended threshold
+    pub threshold: f64,
+    /// Recommended strategy
+    pub strategy: ConsensusStrategy,
+    /// Lowest acceptance rate over the noise levels
+    pub worst_acceptance_rate: f64,
+    /// Highest error rate over the noise levels
+    pub worst_error_rate: f64,
+    /// Whether the error bound holds at every noise level
+    pub meets_error_bound: bool,
+}
+
+/// Recommendation report produced by [`simulate`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TuningReport {
+    /// Requests replayed
+    pub cases: usize,
+    /// Configuration the report was produced with
+    pub config: TuningConfig,
+    /// Results per noise level
+    pub noise_levels: Vec<NoiseLevelReport>,
+    /// Setting with the best worst-case acceptance
+    pub recommendation: Recommendation,
+    /// Observations for the operator
+    pub notes: Vec<String>,
+}
+
+/// Replay `cases` under every configured noise level and recommend a threshold
+/// and consensus strategy.
+pub fn simulate(cases: &[TuningCase], config: &TuningConfig) -> anyhow::Result<TuningReport> {
+    config.validate()?;
+    if cases.is_empty() {
+        anyhow::bail!("corpus contains no requests");
+    }
+
+    let engine = ConsensusEngine::default();
+    let mut rng = SplitMix64::new(config.seed);
+    let candidates: Vec<(ThresholdChoice, ConsensusStrategy)> = config
+        .thresholds
+        .iter()
+        .flat_map(|threshold| {
+            config
+                .strategies
+                .iter()
+                .map(move |strategy| (ThresholdChoice::Fixed(*threshold), *strategy))
+        })
+        .collect();
+    let baseline_choice = (ThresholdChoice::PerRequest, ConsensusStrategy::HighestTrust);
+
+    let mut noise_levels = Vec::with_capacity(config.noise_levels.len());
+    for noise in &config.noise_levels {
+        // Every candidate is judged on the same synthetic rounds.
+        let mut tallies: Vec<Tally> = vec![Tally::default(); candidates.len() + 1];
+        for case in cases {
+            for _ in 0..config.trials {
+                let outputs = synthesize_outputs(case, noise, config, &mut rng);
+                let scored = engine.score_outputs(&outputs);
+                let choices = std::iter::once(&baseline_choice).chain(candidates.iter());
+                for (tally, (threshold, strategy)) in tallies.iter_mut().zip(choices) {
+                    let selected = select(&scored, *strategy);
+                    tally.record(
+                        selected,
+                        threshold.for_request(&case.request),
+                        &case.reference_output,
+                    );
+                }
+            }
+        }
+
+        let baseline = tallies[0].result(baseline_choice, None);
+        let scenarios: Vec<ScenarioResult> = candidates
+            .iter()
+            .zip(&tallies[1..])
+            .map(|(choice, tally)| tally.result(*choice, Some(&baseline)))
+            .collect();
+        let recommended = best_scenario(&scenarios, config.max_error_rate).cloned();
+        noise_levels.push(NoiseLevelReport {
+            noise: *noise,
+            baseline,
+            scenarios,
+            recommended,
+        });
+    }
+
+    let recommendation = recommend(&candidates, &noise_levels, config.max_error_rate);
+    let notes = notes_for(&noise_levels, &recommendation, config);
+
+    Ok(TuningReport {
+        cases: cases.len(),
+        config: config.clone(),
+        noise_levels,
+        recommendation,
+        notes,
+    })
+}
+
+/// Build one round of model outputs: each model either repeats the reference
+/// output or, with probability `disagreement`, returns its own variant.
+fn synthesize_outputs(
+    case: &TuningCase,
+    noise: &NoiseLevel,
+    config: &TuningConfig,
+    rng: &mut SplitMix64,
+) -> Vec<ModelOutput> {
+    (0..config.models)
+        .map(|index| {
+            let model_id = format!("sim-model-{}", index + 1);
+            let text = if rng.next_f64() < noise.disagreement {
+                perturb(&case.reference_output, &model_id, rng)
+            } else {
+                case.reference_output.clone()
+            };
+            let jitter = (rng.next_f64() * 2.0 - 1.0) * noise.confidence_jitter;
+            ModelOutput::new(text, model_id, config.base_confidence + jitter, 0)
+        })
+        .collect()
+}
+
+/// Replace roughly half of the words, always changing at least one, so the
+/// variant is distinct from the reference and from other models' variants.
+fn perturb(reference: &str, model_id: &str, rng: &mut SplitMix64) -> String {
+    let mut words: Vec<String> = reference.split_whitespace().map(str::to_string).collect();
+    let mut changed = false;
+    for (position, word) in words.iter_mut().enumerate() {
+        if rng.next_f64() < 0.5 {
+            *word = format!("{}-alt{}", model_id, position);
+            changed = true;
+        }
+    }
+    if !changed {
+        words.push(format!("{}-alt", model_id));
+    }
+    words.join(" ")
+}
+
+fn select(
+    scored: &[(ModelOutput, TrustScores)],
+    strategy: ConsensusStrategy,
+) -> Option<(&ModelOutput, f64)> {
+    match strategy {
+        ConsensusStrategy::HighestTrust => most_trusted(scored.iter()),
+        ConsensusStrategy::Majority => {
+            let mut votes: HashMap<&str, usize> = HashMap::new();
+            for (output, _) in scored {
+                *votes.entry(output.text.as_str()).or_default() += 1;
+            }
+            let (text, count) = votes.into_iter().max_by_key(|(_, count)| *count)?;
+            if count * 2 <= scored.len() {
+                return None;
+            }
+            most_trusted(scored.iter().filter(|(output, _)| output.text == text))
+        }
+        ConsensusStrategy::Unanimous => {
+            let first = &scored.first()?.0.text;
+            if scored.iter().any(|(output, _)| &output.text != first) {
+                return None;
+            }
+            most_trusted(scored.iter())
+        }
+    }
+}
+
+fn most_trusted<'a>(
+    outputs: impl Iterator<Item = &'a (ModelOutput, TrustScores)>,
+) -> Option<(&'a ModelOutput, f64)> {
+    outputs
+        .map(|(output, scores)| (output, scores.overall_score()))
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+#[derive(Debug, Clone, Default)]
+struct Tally {
+    rounds: usize,
+    accepted: usize,
+    errors: usize,
+}
+
+impl Tally {
+    fn record(&mut self, selected: Option<(&ModelOutput, f64)>, threshold: f64, reference: &str) {
+        self.rounds += 1;
+        if let Some((output, score)) = selected {
+            if score >= threshold {
+                self.accepted += 1;
+                if output.text != reference {
+                    self.errors += 1;
+                }
+            }
+        }
+    }
+
+    fn result(
+        &self,
+        (threshold, strategy): (ThresholdChoice, ConsensusStrategy),
+        baseline: Option<&ScenarioResult>,
+    ) -> ScenarioResult {
+        let acceptance_rate = ratio(self.accepted, self.rounds);
+        let error_rate = ratio(self.errors, self.accepted);
+        ScenarioResult {
+            threshold,
+            strategy,
+            rounds: self.rounds,
+            acceptance_rate,
+            error_rate,
+            acceptance_delta: baseline.map_or(0.0, |b| acceptance_rate - b.acceptance_rate),
+            error_delta: baseline.map_or(0.0, |b| error_rate - b.error_rate),
+        }
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Highest acceptance within the error bound; ties go to the stricter threshold.
+fn best_scenario(scenarios: &[ScenarioResult], max_error_rate: f64) -> Option<&ScenarioResult> {
+    scenarios
+        .iter()
+        .filter(|s| s.acceptance_rate > 0.0 && s.error_rate <= max_error_rate)
+        .max_by(|a, b| {
+            a.acceptance_rate
+                .total_cmp(&b.acceptance_rate)
+                .then_with(|| fixed(a.threshold).total_cmp(&fixed(b.threshold)))
+        })
+}
+
+fn fixed(choice: ThresholdChoice) -> f64 {
+    match choice {
+        ThresholdChoice::Fixed(threshold) => threshold,
+        ThresholdChoice::PerRequest => 0.0,
+    }
+}
+
+/// Pick the candidate with the best worst-case acceptance among those meeting
+/// the error bound at every noise level, or the lowest worst-case error rate
+/// when none does.
+fn recommend(
+    candidates: &[(ThresholdChoice, ConsensusStrategy)],
+    noise_levels: &[NoiseLevelReport],
+    max_error_rate: f64,
+) -> Recommendation {
+    let summaries: Vec<Recommendation> = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, (threshold, strategy))| {
+            let results = noise_levels.iter().map(|level| &level.scenarios[index]);
+            let worst_acceptance_rate = results
+                .clone()
+                .map(|s| s.acceptance_rate)
+                .fold(f64::INFINITY, f64::min);
+            let worst_error_rate = results.map(|s| s.error_rate).fold(0.0, f64::max);
+            Recommendation {
+                threshold: fixed(*threshold),
+                strategy: *strategy,
+                worst_acceptance_rate,
+                worst_error_rate,
+                meets_error_bound: worst_error_rate <= max_error_rate,
+            }
+        })
+        .collect();
+
+    let within_bound = summaries
+        .iter()
+        .filter(|r| r.meets_error_bound && r.worst_acceptance_rate > 0.0)
+        .max_by(|a, b| {
+            a.worst_acceptance_rate
+                .total_cmp(&b.worst_acceptance_rate)
+                .then_with(|| a.threshold.total_cmp(&b.threshold))
+        });
+    within_bound
+        .or_else(|| {
+            summaries.iter().min_by(|a, b| {
+                a.worst_error_rate
+                    .total_cmp(&b.worst_error_rate)
+                    .then_with(|| b.worst_acceptance_rate.total_cmp(&a.worst_acceptance_rate))
+            })
+        })
+        .cloned()
+        .expect("config validation guarantees at least one candidate")
+}
+
+fn notes_for(
+    noise_levels: &[NoiseLevelReport],
+    recommendation: &Recommendation,
+    config: &TuningConfig,
+) -> Vec<String> {
+    let mut notes = Vec::new();
+    if !recommendation.meets_error_bound {
+        notes.push(format!(
+            "no candidate keeps the error rate at or below {:.3} under every noise level; \
+             the recommendation minimises the worst-case error rate instead",
+            config.max_error_rate
+        ));
+    }
+    for level in noise_levels {
+        if level.baseline.error_rate > config.max_error_rate {
+            notes.push(format!(
+                "at disagreement {:.2}, current per-request thresholds accept wrong outputs \
+                 at rate {:.3}",
+                level.noise.disagreement, level.baseline.error_rate
+            ));
+        }
+        if level.recommended.is_none() {
+            notes.push(format!(
+                "at disagreement {:.2}, no candidate meets the error bound",
+                level.noise.disagreement
+            ));
+        }
+    }
+    if config.models < 3 && config.strategies.contains(&ConsensusStrategy::Majority) {
+        notes.push(
+            "majority consensus with fewer than 3 models only accepts unanimous outputs"
+                .to_string(),
+        );
+    }
+    notes
+}
+
+/// Small seeded generator so reports are reproducible without extra dependencies
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::{ExecutionMode, TaskType};
+
+    fn corpus() -> Vec<TuningCase> {
+        ["summarise the quarterly report", "list three prime numbers"]
+            .iter()
+            .map(|prompt| TuningCase {
+                request: GenerationRequest::new(
+                    *prompt,
+                    TaskType::Chat,
+                    0.5,
+                    ExecutionMode::Local,
+                    true,
+                ),
+                reference_output: format!("answer to {}", prompt),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_noise_free_corpus_is_fully_accepted_without_errors() {
+        let config = TuningConfig {
+            noise_levels: vec![NoiseLevel::new(0.0, 0.0)],
+            thresholds: vec![0.7],
+            ..TuningConfig::default()
+        };
+        let report = simulate(&corpus(), &config).unwrap();
+
+        let level = &report.noise_levels[0];
+        assert_eq!(level.baseline.rounds, 2 * config.trials);
+        for scenario in &level.scenarios {
+            assert_eq!(scenario.acceptance_rate, 1.0);
+            assert_eq!(scenario.error_rate, 0.0);
+        }
+        assert!(report.recommendation.meets_error_bound);
+        assert_eq!(report.recommendation.threshold, 0.7);
+    }
+
+    #[test]
+    fn test_disagreement_separates_strategies_and_is_reproducible() {
+        let config = TuningConfig {
+            noise_levels: vec![NoiseLevel::new(0.4, 0.1)],
+            trials: 50,
+            ..TuningConfig::default()
+        };
+        let report = simulate(&corpus(), &config).unwrap();
+        let level = &report.noise_levels[0];
+        let at = |threshold: f64, strategy| {
+            level
+                .scenarios
+                .iter()
+                .find(|s| {
+                    s.threshold == ThresholdChoice::Fixed(threshold) && s.strategy == strategy
+                })
+                .unwrap()
+        };
+
+        // Lenient highest-trust selection accepts more but lets wrong outputs through.
+        let lenient = at(0.5, ConsensusStrategy::HighestTrust);
+        let unanimous = at(0.5, ConsensusStrategy::Unanimous);
+        assert!(lenient.acceptance_rate > unanimous.acceptance_rate);
+        assert!(lenient.error_rate > 0.0);
+        assert_eq!(unanimous.error_rate, 0.0);
+        assert!(at(0.5, ConsensusStrategy::Majority).error_rate < lenient.error_rate);
+
+        assert_eq!(
+            lenient.acceptance_delta,
+            lenient.acceptance_rate - level.baseline.acceptance_rate
+        );
+        assert!(report.recommendation.meets_error_bound);
+        assert!(report.recommendation.worst_error_rate <= config.max_error_rate);
+        assert!(report.recommendation.threshold > 0.5);
+
+        let again = simulate(&corpus(), &config).unwrap();
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::to_value(&again).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_corpus_parsing_and_config_validation() {
+        let line = serde_json::to_string(&corpus()[0]).unwrap();
+        let parsed = TuningCase::parse_corpus(&format!("{line}\n\n{line}\n")).unwrap();
+        assert_eq!(parsed.len(), 2);
+
+        let err = TuningCase::parse_corpus(&format!("{line}\nnot json")).unwrap_err();
+        assert!(err.to_string().contains("corpus line 2"));
+
+        let config = TuningConfig {
+            thresholds: vec![1.5],
+            ..TuningConfig::default()
+        };
+        assert!(simulate(&corpus(), &config).is_err());
+        assert!(simulate(&[], &TuningConfig::default()).is_err());
+    }
+}
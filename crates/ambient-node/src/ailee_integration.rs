@@ -17,12 +17,127 @@
 //! - AILEE provides: trust scoring, consensus, lineage, determinism
 //! - No VCP logic leaks into AILEE
 //! - No AILEE logic re-implemented in VCP
+//!
+//! ## Trust policies
+//!
+//! The trust threshold and number of agreeing models each [`TaskType`]
+//! needs come from [`TrustPolicies`], set from the node config
+//! ([`crate::DeploymentSettings::trust`]) and replaced at runtime from a
+//! signed control-plane message
+//! ([`AileeEngineAdapter::apply_signed_trust_policies`]).  Neither source
+//! can go below [`TrustPolicies::FLOORS`]; lower values are raised to the
+//! floor.
 
 use ailee_trust_layer::{
     ConsensusEngine, ExecutionMode, GenerationRequest, GenerationResult, LocalModelAdapter,
-    ModelAdapter, RemoteModelAdapter, TaskType,
+    ModelAdapter, Redactor, RemoteModelAdapter, TaskType, TrustLedger,
 };
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+use crate::control_signature::ControlVerifier;
+
+/// Trust a task type's results must reach.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrustRequirement {
+    /// Minimum trust score (0.0 - 1.0) a consensus output should reach
+    pub trust_threshold: f64,
+    /// Models that must succeed for a result
+    pub min_models: usize,
+}
+
+impl TrustRequirement {
+    /// `self` raised to `floor` wherever it is lower.  A NaN threshold
+    /// takes the floor.
+    fn at_least(self, floor: TrustRequirement) -> Self {
+        let trust_threshold = if self.trust_threshold.is_nan() {
+            floor.trust_threshold
+        } else {
+            self.trust_threshold.clamp(floor.trust_threshold, 1.0)
+        };
+        Self {
+            trust_threshold,
+            min_models: self.min_models.max(floor.min_models),
+        }
+    }
+}
+
+/// Trust requirements per task type.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrustPolicies {
+    pub chat: TrustRequirement,
+    pub code: TrustRequirement,
+    pub analysis: TrustRequirement,
+}
+
+impl TrustPolicies {
+    /// Minimums no configuration can lower.  Code and analysis results
+    /// feed other work, so they need two models to agree.
+    pub const FLOORS: TrustPolicies = TrustPolicies {
+        chat: TrustRequirement {
+            trust_threshold: 0.5,
+            min_models: 1,
+        },
+        code: TrustRequirement {
+            trust_threshold: 0.6,
+            min_models: 2,
+        },
+        analysis: TrustRequirement {
+            trust_threshold: 0.6,
+            min_models: 2,
+        },
+    };
+
+    /// The floors, with every task type needing at least `min_models`.
+    pub fn with_min_models(min_models: usize) -> Self {
+        Self::FLOORS.map(|_, requirement| TrustRequirement {
+            min_models: requirement.min_models.max(min_models),
+            ..requirement
+        })
+    }
+
+    pub fn get(&self, task_type: TaskType) -> TrustRequirement {
+        match task_type {
+            TaskType::Chat => self.chat,
+            TaskType::Code => self.code,
+            TaskType::Analysis => self.analysis,
+        }
+    }
+
+    /// `self` with every requirement raised to [`Self::FLOORS`].
+    pub fn enforce_floors(self) -> Self {
+        self.map(|task_type, requirement| {
+            let floor = Self::FLOORS.get(task_type);
+            let enforced = requirement.at_least(floor);
+            if enforced != requirement {
+                tracing::warn!(
+                    ?task_type,
+                    requested_threshold = requirement.trust_threshold,
+                    requested_min_models = requirement.min_models,
+                    "Trust policy below the floor; raised to {:?}",
+                    enforced
+                );
+            }
+            enforced
+        })
+    }
+
+    fn map(self, f: impl Fn(TaskType, TrustRequirement) -> TrustRequirement) -> Self {
+        Self {
+            chat: f(TaskType::Chat, self.chat),
+            code: f(TaskType::Code, self.code),
+            analysis: f(TaskType::Analysis, self.analysis),
+        }
+    }
+}
+
+impl Default for TrustPolicies {
+    fn default() -> Self {
+        Self::FLOORS
+    }
+}
 
 /// VCP execution context passed to AILEE
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,16 +180,87 @@ impl VcpExecutionContext {
 /// - Maintains clean separation of concerns
 pub struct AileeEngineAdapter {
     consensus_engine: ConsensusEngine,
+    trust_policies: RwLock<TrustPolicies>,
 }
 
 impl AileeEngineAdapter {
-    /// Create new AILEE engine adapter
+    /// Create new AILEE engine adapter requiring at least `min_models`
+    /// models for every task type
     pub fn new(min_models: usize) -> Self {
         Self {
             consensus_engine: ConsensusEngine::new(min_models),
+            trust_policies: RwLock::new(TrustPolicies::with_min_models(min_models)),
         }
     }
 
+    /// Start from `policies`, e.g. the node config's, raised to the floors.
+    pub fn with_trust_policies(self, policies: TrustPolicies) -> Self {
+        self.set_trust_policies(policies);
+        self
+    }
+
+    /// The trust policies in force.
+    pub fn trust_policies(&self) -> TrustPolicies {
+        *self
+            .trust_policies
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Replace the trust policies for every later request and return them
+    /// as applied, raised to the floors.
+    pub fn set_trust_policies(&self, policies: TrustPolicies) -> TrustPolicies {
+        let policies = policies.enforce_floors();
+        *self
+            .trust_policies
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = policies;
+        policies
+    }
+
+    /// Apply trust policies from a control-plane message signed for this
+    /// node.  `now_ms` should be skew-corrected (see
+    /// [`ControlVerifier::verify`]).  Task types the message leaves out
+    /// return to their floors.
+    pub fn apply_signed_trust_policies(
+        &self,
+        verifier: &mut ControlVerifier,
+        message: serde_json::Value,
+        now_ms: i64,
+    ) -> anyhow::Result<TrustPolicies> {
+        let body = verifier
+            .verify(message, now_ms)
+            .context("rejected trust policy message")?;
+        let policies: TrustPolicies =
+            serde_json::from_value(body).context("malformed trust policy message")?;
+        Ok(self.set_trust_policies(policies))
+    }
+
+    /// Scrub every result with `redactor` before it is returned to VCP for
+    /// persistence or logging.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.consensus_engine = self.consensus_engine.with_redactor(redactor);
+        self
+    }
+
+    /// Track trust per model version in `ledger` and keep versions it has
+    /// quarantined out of consensus.
+    pub fn with_trust_ledger(mut self, ledger: Arc<TrustLedger>) -> Self {
+        self.consensus_engine = self.consensus_engine.with_trust_ledger(ledger);
+        self
+    }
+
+    /// Execute a generation request under the task type's trust policy
+    pub async fn execute(
+        &self,
+        prompt: impl Into<String>,
+        task_type: TaskType,
+        vcp_context: &VcpExecutionContext,
+    ) -> anyhow::Result<GenerationResult> {
+        self.execute_with_context(prompt, task_type, 0.0, vcp_context)
+            .await
+    }
+
     /// Execute generation request using AILEE Trust Layer
     ///
     /// This method:
@@ -82,12 +268,29 @@ impl AileeEngineAdapter {
     /// 2. Configures model adapters based on connectivity
     /// 3. Invokes AILEE in-process
     /// 4. Returns result with trust scores and lineage
+    ///
+    /// `trust_threshold` can only raise the task type's policy threshold.
     pub async fn execute_with_context(
         &self,
         prompt: impl Into<String>,
         task_type: TaskType,
         trust_threshold: f64,
         vcp_context: &VcpExecutionContext,
+    ) -> anyhow::Result<GenerationResult> {
+        let adapters = self.create_adapters(vcp_context);
+        self.execute_with_adapters(prompt, task_type, trust_threshold, vcp_context, adapters)
+            .await
+    }
+
+    /// Execute like [`Self::execute_with_context`] with caller-supplied
+    /// model adapters, e.g. `ailee_trust_layer::testkit` mocks in tests.
+    pub async fn execute_with_adapters(
+        &self,
+        prompt: impl Into<String>,
+        task_type: TaskType,
+        trust_threshold: f64,
+        vcp_context: &VcpExecutionContext,
+        adapters: Vec<Box<dyn ModelAdapter>>,
     ) -> anyhow::Result<GenerationResult> {
         // Determine execution mode based on VCP connectivity
         let execution_mode = if vcp_context.is_online {
@@ -96,20 +299,24 @@ impl AileeEngineAdapter {
             ExecutionMode::Local // Fall back to local only when offline
         };
 
+        let requirement = self.trust_policies().get(task_type);
+
         // Create generation request
         let request = GenerationRequest::new(
             prompt,
             task_type,
-            trust_threshold,
+            trust_threshold.max(requirement.trust_threshold),
             execution_mode,
             vcp_context.allow_offline_execution,
         );
 
-        // Configure model adapters based on VCP context
-        let adapters = self.create_adapters(vcp_context);
-
         // Invoke AILEE in-process
-        let result = self.consensus_engine.execute(&request, adapters).await?;
+        let result = self
+            .consensus_engine
+            .clone()
+            .with_min_models(requirement.min_models)
+            .execute(&request, adapters)
+            .await?;
 
         // Validate execution time constraint
         if result.execution_metadata.execution_time_ms > vcp_context.max_execution_time_ms {
@@ -294,4 +501,132 @@ mod tests {
         assert!(!result.final_output.is_empty());
         assert!(result.trust_score >= 0.0 && result.trust_score <= 1.0);
     }
+
+    #[test]
+    fn test_trust_policies_cannot_go_below_floors() {
+        let lowered = TrustPolicies {
+            chat: TrustRequirement {
+                trust_threshold: 0.1,
+                min_models: 0,
+            },
+            code: TrustRequirement {
+                trust_threshold: f64::NAN,
+                min_models: 3,
+            },
+            analysis: TrustRequirement {
+                trust_threshold: 1.5,
+                min_models: 1,
+            },
+        };
+        let enforced = lowered.enforce_floors();
+        assert_eq!(enforced.chat, TrustPolicies::FLOORS.chat);
+        assert_eq!(enforced.code.trust_threshold, 0.6);
+        assert_eq!(enforced.code.min_models, 3);
+        assert_eq!(enforced.analysis.trust_threshold, 1.0);
+        assert_eq!(enforced.analysis.min_models, 2);
+
+        let adapter = AileeEngineAdapter::new(1).with_trust_policies(lowered);
+        assert_eq!(adapter.trust_policies(), enforced);
+        assert_eq!(
+            AileeEngineAdapter::new(3).trust_policies().chat.min_models,
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_live_policy_changes_apply_to_next_request() {
+        use ailee_trust_layer::testkit::MockModelAdapter;
+
+        let adapter = AileeEngineAdapter::new(1);
+        let context = VcpExecutionContext::new(false, "us-west", "compute", 5000, true);
+        let adapters = || -> Vec<Box<dyn ModelAdapter>> {
+            vec![
+                Box::new(MockModelAdapter::new("local-a").respond("42")),
+                Box::new(MockModelAdapter::new("local-b").respond("42")),
+            ]
+        };
+
+        let result = adapter
+            .execute_with_adapters("answer?", TaskType::Chat, 0.0, &context, adapters())
+            .await
+            .unwrap();
+        let floor =
+            GenerationRequest::new("answer?", TaskType::Chat, 0.5, ExecutionMode::Local, true);
+        assert_eq!(result.input_hash, floor.hash());
+
+        // A raised threshold is part of the request from now on, and three
+        // models are more than the node has.
+        let mut policies = adapter.trust_policies();
+        policies.chat = TrustRequirement {
+            trust_threshold: 0.9,
+            min_models: 3,
+        };
+        adapter.set_trust_policies(policies);
+        let err = adapter
+            .execute_with_adapters("answer?", TaskType::Chat, 0.0, &context, adapters())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Insufficient models"), "{err}");
+
+        policies.chat.min_models = 2;
+        adapter.set_trust_policies(policies);
+        let result = adapter
+            .execute_with_adapters("answer?", TaskType::Chat, 0.7, &context, adapters())
+            .await
+            .unwrap();
+        let raised =
+            GenerationRequest::new("answer?", TaskType::Chat, 0.9, ExecutionMode::Local, true);
+        assert_eq!(result.input_hash, raised.hash());
+    }
+
+    #[test]
+    fn test_signed_trust_policies_are_verified() {
+        use crate::control_signature::ControlSigner;
+
+        let signer = ControlSigner::generate().unwrap();
+        let mut verifier = ControlVerifier::new("node-1", &signer.public_key_b64()).unwrap();
+        let adapter = AileeEngineAdapter::new(1);
+        let message = serde_json::json!({
+            "code": {"trust_threshold": 0.85, "min_models": 3},
+            "chat": {"trust_threshold": 0.2, "min_models": 1},
+        });
+
+        let applied = adapter
+            .apply_signed_trust_policies(
+                &mut verifier,
+                signer.sign("node-1", message.clone(), 1_000),
+                1_000,
+            )
+            .unwrap();
+        assert_eq!(applied.code.trust_threshold, 0.85);
+        assert_eq!(applied.code.min_models, 3);
+        assert_eq!(applied.chat, TrustPolicies::FLOORS.chat);
+        assert_eq!(applied.analysis, TrustPolicies::FLOORS.analysis);
+        assert_eq!(adapter.trust_policies(), applied);
+
+        // Forged, misaddressed or malformed messages change nothing.
+        let impostor = ControlSigner::generate().unwrap();
+        assert!(adapter
+            .apply_signed_trust_policies(
+                &mut verifier,
+                impostor.sign("node-1", message.clone(), 1_100),
+                1_100,
+            )
+            .is_err());
+        assert!(adapter
+            .apply_signed_trust_policies(
+                &mut verifier,
+                signer.sign("node-2", message, 1_200),
+                1_200,
+            )
+            .is_err());
+        assert!(adapter
+            .apply_signed_trust_policies(
+                &mut verifier,
+                signer.sign("node-1", serde_json::json!({"code": "strict"}), 1_300),
+                1_300,
+            )
+            .is_err());
+        assert_eq!(adapter.trust_policies(), applied);
+    }
 }
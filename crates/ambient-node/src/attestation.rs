@@ -0,0 +1,89 @@
+//! Node attestation at registration
+//!
+//! When the control plane requires attestation, a newly registered node is
+//! held in `pending_attestation` and handed a single-use nonce.  The node
+//! proves it was enrolled by signing [`attestation_message`] with its
+//! enrollment key, an Ed25519 key provisioned onto the machine out of band
+//! (a [`NodeSigningKey`] distinct from its sandbox-report key), optionally
+//! attaching a TPM or secure-element quote over the same nonce.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::signature;
+use serde::{Deserialize, Serialize};
+
+use crate::sandbox_report::NodeSigningKey;
+
+/// Domain separator so an attestation signature cannot be replayed as any
+/// other signed message.
+pub const ATTESTATION_CONTEXT: &str = "ambient-vcp/node-attestation/v1";
+
+/// Bytes an enrollment key signs to answer `nonce` for `node_id`.
+pub fn attestation_message(node_id: &str, nonce: &str) -> Vec<u8> {
+    format!("{ATTESTATION_CONTEXT}\n{node_id}\n{nonce}").into_bytes()
+}
+
+/// A node's answer to an attestation challenge.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationResponse {
+    /// The nonce from the challenge being answered.
+    pub nonce: String,
+    /// Base64 public half of the enrollment key.
+    pub enrollment_public_key: String,
+    /// Base64 Ed25519 signature over [`attestation_message`].
+    pub signature: String,
+    /// Base64 TPM / secure-element quote over the nonce, when the node has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+}
+
+impl AttestationResponse {
+    pub fn sign(node_id: &str, nonce: &str, enrollment_key: &NodeSigningKey) -> Self {
+        Self {
+            nonce: nonce.to_string(),
+            enrollment_public_key: enrollment_key.public_key_b64(),
+            signature: enrollment_key.sign_b64(&attestation_message(node_id, nonce)),
+            quote: None,
+        }
+    }
+
+    pub fn with_quote(mut self, quote_b64: impl Into<String>) -> Self {
+        self.quote = Some(quote_b64.into());
+        self
+    }
+
+    /// Whether the signature is valid for `node_id` under
+    /// `enrollment_public_key`.  Whether that key is trusted is up to the
+    /// caller.
+    pub fn verify(&self, node_id: &str) -> bool {
+        let (Ok(public_key), Ok(signature)) = (
+            STANDARD.decode(&self.enrollment_public_key),
+            STANDARD.decode(&self.signature),
+        ) else {
+            return false;
+        };
+        signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+            .verify(&attestation_message(node_id, &self.nonce), &signature)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attestation_binds_node_and_nonce() {
+        let key = NodeSigningKey::generate().unwrap();
+        let response = AttestationResponse::sign("node-1", "nonce-a", &key);
+        assert!(response.verify("node-1"));
+        assert!(!response.verify("node-2"));
+
+        let mut replayed = response.clone();
+        replayed.nonce = "nonce-b".to_string();
+        assert!(!replayed.verify("node-1"));
+
+        let mut wrong_key = response;
+        wrong_key.enrollment_public_key = NodeSigningKey::generate().unwrap().public_key_b64();
+        assert!(!wrong_key.verify("node-1"));
+    }
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Number of recent probe exchanges kept for offset estimation.
+const PROBE_WINDOW: usize = 8;
+
+/// One NTP-style request/response exchange with the coordinator.
+///
+/// All timestamps are Unix milliseconds:
+/// - `t0` local clock when the request was sent
+/// - `t1` server clock when the request was received
+/// - `t2` server clock when the response was sent
+/// - `t3` local clock when the response was received
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ClockSample {
+    /// Server clock minus local clock, in milliseconds.
+    pub offset_ms: i64,
+    /// Network round trip excluding server processing time, in milliseconds.
+    pub round_trip_ms: i64,
+}
+
+impl ClockSample {
+    pub fn from_exchange(t0: i64, t1: i64, t2: i64, t3: i64) -> Self {
+        Self {
+            offset_ms: ((t1 - t0) + (t2 - t3)) / 2,
+            round_trip_ms: ((t3 - t0) - (t2 - t1)).max(0),
+        }
+    }
+}
+
+/// Estimates local clock skew against the coordinator from recent exchanges.
+///
+/// The sample with the smallest round trip is trusted most, since queueing
+/// delay is what makes one-way latency asymmetric.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClockSkewProbe {
+    samples: VecDeque<ClockSample>,
+}
+
+impl ClockSkewProbe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an exchange, discarding the oldest sample once the window is full.
+    pub fn record(&mut self, sample: ClockSample) {
+        if self.samples.len() == PROBE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Best current estimate, or `None` before the first exchange.
+    pub fn estimate(&self) -> Option<ClockSample> {
+        self.samples
+            .iter()
+            .min_by_key(|sample| sample.round_trip_ms)
+            .copied()
+    }
+
+    /// Milliseconds to add to the local clock to obtain coordinator time.
+    pub fn offset_ms(&self) -> i64 {
+        self.estimate().map_or(0, |sample| sample.offset_ms)
+    }
+
+    /// Local clock minus coordinator clock, as reported in heartbeats
+    /// (positive when this node's clock runs ahead).
+    pub fn skew_ms(&self) -> Option<i64> {
+        self.estimate().map(|sample| -sample.offset_ms)
+    }
+
+    /// Convert a local Unix timestamp (seconds) to coordinator time.
+    pub fn to_server_secs(&self, local_secs: u64) -> u64 {
+        let adjusted = local_secs as i64 + self.offset_ms() / 1000;
+        adjusted.max(0) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_from_symmetric_exchange() {
+        // Local clock is 5s behind; 100ms each way, 10ms server processing.
+        let sample = ClockSample::from_exchange(1_000, 6_100, 6_110, 1_210);
+        assert_eq!(sample.offset_ms, 5_000);
+        assert_eq!(sample.round_trip_ms, 200);
+    }
+
+    #[test]
+    fn test_probe_prefers_lowest_round_trip() {
+        let mut probe = ClockSkewProbe::new();
+        assert_eq!(probe.skew_ms(), None);
+
+        probe.record(ClockSample {
+            offset_ms: 900,
+            round_trip_ms: 800,
+        });
+        probe.record(ClockSample {
+            offset_ms: -2_000,
+            round_trip_ms: 40,
+        });
+
+        assert_eq!(probe.offset_ms(), -2_000);
+        assert_eq!(probe.skew_ms(), Some(2_000));
+        assert_eq!(probe.to_server_secs(10_000), 9_998);
+    }
+
+    #[test]
+    fn test_probe_window_is_bounded() {
+        let mut probe = ClockSkewProbe::new();
+        probe.record(ClockSample {
+            offset_ms: 1,
+            round_trip_ms: 0,
+        });
+        for _ in 0..PROBE_WINDOW {
+            probe.record(ClockSample {
+                offset_ms: 7,
+                round_trip_ms: 10,
+            });
+        }
+        assert_eq!(probe.offset_ms(), 7);
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::Read;
+
+pub const CONTENT_TYPE_JSON: &str = "application/json";
+pub const CONTENT_TYPE_MSGPACK: &str = "application/msgpack";
+/// Media types some MessagePack libraries send instead of `application/msgpack`.
+const MSGPACK_ALIASES: &[&str] = &["application/x-msgpack", "application/vnd.msgpack"];
+
+pub const ENCODING_ZSTD: &str = "zstd";
+
+/// Compression level for control-plane bodies; favours speed on small nodes.
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+/// Bodies smaller than this are sent uncompressed; zstd framing would
+/// outweigh the savings.
+pub const MIN_COMPRESS_BYTES: usize = 256;
+/// Upper bound on a decompressed body, guarding against zstd bombs.
+pub const MAX_DECODED_BYTES: usize = 16 * 1024 * 1024;
+
+/// Serialization used for control-plane request and response bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyFormat {
+    Json,
+    /// MessagePack with named fields, so it transcodes losslessly to JSON.
+    MsgPack,
+}
+
+impl BodyFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => CONTENT_TYPE_JSON,
+            Self::MsgPack => CONTENT_TYPE_MSGPACK,
+        }
+    }
+
+    /// Parse a `Content-Type` value, ignoring parameters such as `charset`.
+    pub fn from_content_type(value: &str) -> Option<Self> {
+        let media_type = media_type(value);
+        if media_type.eq_ignore_ascii_case(CONTENT_TYPE_JSON) {
+            Some(Self::Json)
+        } else if media_type.eq_ignore_ascii_case(CONTENT_TYPE_MSGPACK)
+            || MSGPACK_ALIASES
+                .iter()
+                .any(|alias| media_type.eq_ignore_ascii_case(alias))
+        {
+            Some(Self::MsgPack)
+        } else {
+            None
+        }
+    }
+
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            Self::Json => serde_json::to_vec(value).context("JSON encoding failed"),
+            Self::MsgPack => rmp_serde::to_vec_named(value).context("MessagePack encoding failed"),
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        match self {
+            Self::Json => serde_json::from_slice(bytes).context("invalid JSON body"),
+            Self::MsgPack => rmp_serde::from_slice(bytes).context("invalid MessagePack body"),
+        }
+    }
+}
+
+/// A serialized body and the headers that describe it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedBody {
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+    pub content_encoding: Option<&'static str>,
+}
+
+/// How a node encodes control-plane bodies and what it asks the coordinator
+/// to send back.
+///
+/// The default is plain JSON.  [`ControlPlaneCodec::compact`] switches to
+/// MessagePack with zstd, which shrinks telemetry-heavy heartbeats by roughly
+/// an order of magnitude on constrained backhauls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlPlaneCodec {
+    pub format: BodyFormat,
+    pub compress: bool,
+}
+
+impl Default for ControlPlaneCodec {
+    fn default() -> Self {
+        Self {
+            format: BodyFormat::Json,
+            compress: false,
+        }
+    }
+}
+
+impl ControlPlaneCodec {
+    /// MessagePack bodies, zstd-compressed in both directions.
+    pub fn compact() -> Self {
+        Self {
+            format: BodyFormat::MsgPack,
+            compress: true,
+        }
+    }
+
+    /// Encode a request body.
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<EncodedBody> {
+        let bytes = self.format.serialize(value)?;
+        if self.compress && bytes.len() >= MIN_COMPRESS_BYTES {
+            return Ok(EncodedBody {
+                bytes: zstd_compress(&bytes, DEFAULT_ZSTD_LEVEL)?,
+                content_type: self.format.content_type(),
+                content_encoding: Some(ENCODING_ZSTD),
+            });
+        }
+        Ok(EncodedBody {
+            bytes,
+            content_type: self.format.content_type(),
+            content_encoding: None,
+        })
+    }
+
+    /// `Accept` and `Accept-Encoding` values to send with each request.
+    pub fn accept_headers(&self) -> Vec<(&'static str, &'static str)> {
+        let mut headers = vec![("accept", self.format.content_type())];
+        if self.compress {
+            headers.push(("accept-encoding", ENCODING_ZSTD));
+        }
+        headers
+    }
+}
+
+/// Decode a response body using its `Content-Type` and `Content-Encoding`.
+/// A missing content type is treated as JSON.
+pub fn decode_body<T: DeserializeOwned>(
+    bytes: &[u8],
+    content_type: Option<&str>,
+    content_encoding: Option<&str>,
+) -> Result<T> {
+    let format = match content_type {
+        None => BodyFormat::Json,
+        Some(value) => BodyFormat::from_content_type(value)
+            .with_context(|| format!("unsupported content type '{value}'"))?,
+    };
+    match content_encoding.map(str::trim) {
+        None | Some("") | Some("identity") => format.deserialize(bytes),
+        Some(encoding) if encoding.eq_ignore_ascii_case(ENCODING_ZSTD) => {
+            format.deserialize(&zstd_decompress(bytes, MAX_DECODED_BYTES)?)
+        }
+        Some(other) => bail!("unsupported content encoding '{other}'"),
+    }
+}
+
+pub fn zstd_compress(bytes: &[u8], level: i32) -> Result<Vec<u8>> {
+    zstd::encode_all(bytes, level).context("zstd compression failed")
+}
+
+/// Decompress `bytes`, failing if the output would exceed `limit`.
+pub fn zstd_decompress(bytes: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let decoder = zstd::stream::read::Decoder::new(bytes).context("invalid zstd stream")?;
+    let mut output = Vec::new();
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut output)
+        .context("invalid zstd stream")?;
+    if output.len() > limit {
+        bail!("decompressed body exceeds {limit} bytes");
+    }
+    Ok(output)
+}
+
+/// Whether an `Accept` or `Accept-Encoding` header lists `token` with a
+/// non-zero quality.
+pub fn header_accepts(header: &str, token: &str) -> bool {
+    header.split(',').any(|entry| {
+        let mut parts = entry.split(';');
+        let value = parts.next().unwrap_or_default().trim();
+        let refused = parts.any(|param| {
+            param
+                .trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .is_some_and(|q| q <= 0.0)
+        });
+        value.eq_ignore_ascii_case(token) && !refused
+    })
+}
+
+fn media_type(value: &str) -> &str {
+    value.split(';').next().unwrap_or_default().trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn telemetry() -> Value {
+        let samples: Vec<Value> = (0..64)
+            .map(|i| json!({"cpu_usage_percent": 12.5, "memory_usage_percent": 40.0, "seq": i}))
+            .collect();
+        json!({"seq": 7, "state": {"samples": samples}})
+    }
+
+    #[test]
+    fn test_compact_codec_round_trips_and_shrinks() {
+        let value = telemetry();
+        let codec = ControlPlaneCodec::compact();
+        let encoded = codec.encode(&value).unwrap();
+        assert_eq!(encoded.content_type, CONTENT_TYPE_MSGPACK);
+        assert_eq!(encoded.content_encoding, Some(ENCODING_ZSTD));
+
+        let json_len = serde_json::to_vec(&value).unwrap().len();
+        assert!(encoded.bytes.len() * 10 < json_len);
+
+        let decoded: Value = decode_body(
+            &encoded.bytes,
+            Some(encoded.content_type),
+            encoded.content_encoding,
+        )
+        .unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn test_small_bodies_skip_compression() {
+        let encoded = ControlPlaneCodec::compact()
+            .encode(&json!({"seq": 1}))
+            .unwrap();
+        assert_eq!(encoded.content_encoding, None);
+        assert_eq!(
+            ControlPlaneCodec::default().accept_headers(),
+            vec![("accept", CONTENT_TYPE_JSON)]
+        );
+    }
+
+    #[test]
+    fn test_decompression_is_bounded() {
+        let bomb = zstd_compress(&vec![0u8; 4096], DEFAULT_ZSTD_LEVEL).unwrap();
+        assert!(zstd_decompress(&bomb, 1024).is_err());
+        assert_eq!(zstd_decompress(&bomb, 4096).unwrap().len(), 4096);
+    }
+
+    #[test]
+    fn test_header_negotiation() {
+        assert!(header_accepts(
+            "application/msgpack, application/json;q=0.5",
+            CONTENT_TYPE_MSGPACK
+        ));
+        assert!(!header_accepts("gzip, zstd;q=0", ENCODING_ZSTD));
+        assert!(!header_accepts("*/*", CONTENT_TYPE_MSGPACK));
+        assert_eq!(
+            BodyFormat::from_content_type("application/x-msgpack"),
+            Some(BodyFormat::MsgPack)
+        );
+        assert_eq!(
+            BodyFormat::from_content_type("application/json; charset=utf-8"),
+            Some(BodyFormat::Json)
+        );
+    }
+}
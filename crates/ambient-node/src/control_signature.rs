@@ -0,0 +1,320 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{self, Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// Field of a signed coordinator response that carries its [`ControlSignature`].
+pub const CONTROL_SIGNATURE_FIELD: &str = "control_signature";
+
+/// Oldest (or furthest in the future) a signed response may be, by default.
+pub const DEFAULT_CONTROL_MAX_AGE_MS: i64 = 30_000;
+
+/// Coordinator signature over a control-plane response (heartbeat acks,
+/// gateway session lists) so a node can reject forged or replayed
+/// assignments.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ControlSignature {
+    /// Node the response was issued to.
+    pub node_id: String,
+    /// Strictly increasing across every response signed with one key.
+    pub seq: u64,
+    /// Coordinator clock, Unix milliseconds.
+    pub issued_at_ms: i64,
+    /// Base64 Ed25519 signature over the body, `node_id`, `seq` and
+    /// `issued_at_ms`.
+    pub signature: String,
+}
+
+fn signing_bytes(body: &Value, node_id: &str, seq: u64, issued_at_ms: i64) -> Vec<u8> {
+    serde_json::to_vec(&json!({
+        "body": body,
+        "issued_at_ms": issued_at_ms,
+        "node_id": node_id,
+        "seq": seq,
+    }))
+    .expect("control message should serialize")
+}
+
+/// The coordinator's control key.
+///
+/// Sequence numbers start from the clock in microseconds, so they keep
+/// increasing across restarts as long as the clock does not step backwards.
+pub struct ControlSigner {
+    key_pair: Ed25519KeyPair,
+    last_seq: AtomicU64,
+}
+
+impl ControlSigner {
+    pub fn generate() -> Result<Self> {
+        let mut seed = Zeroizing::new([0u8; 32]);
+        SystemRandom::new()
+            .fill(seed.as_mut())
+            .map_err(|_| anyhow!("failed to generate control key"))?;
+        Self::from_bytes(*seed)
+    }
+
+    pub fn from_bytes(seed: [u8; 32]) -> Result<Self> {
+        let seed = Zeroizing::new(seed);
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(seed.as_ref())
+            .map_err(|_| anyhow!("invalid Ed25519 seed"))?;
+        Ok(Self {
+            key_pair,
+            last_seq: AtomicU64::new(0),
+        })
+    }
+
+    /// Base64 public key nodes pin to verify responses.
+    pub fn public_key_b64(&self) -> String {
+        STANDARD.encode(self.key_pair.public_key().as_ref())
+    }
+
+    fn next_seq(&self, now_ms: i64) -> u64 {
+        let floor = (now_ms.max(0) as u64).saturating_mul(1000);
+        let previous = self
+            .last_seq
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+                Some(floor.max(last + 1))
+            })
+            .unwrap_or_default();
+        floor.max(previous + 1)
+    }
+
+    /// Sign a JSON object response for `node_id`, adding the
+    /// [`CONTROL_SIGNATURE_FIELD`].  Non-object bodies are returned unsigned.
+    pub fn sign(&self, node_id: &str, mut body: Value, now_ms: i64) -> Value {
+        let Some(object) = body.as_object_mut() else {
+            return body;
+        };
+        object.remove(CONTROL_SIGNATURE_FIELD);
+        let seq = self.next_seq(now_ms);
+        let message = signing_bytes(&Value::Object(object.clone()), node_id, seq, now_ms);
+        let signature = ControlSignature {
+            node_id: node_id.to_string(),
+            seq,
+            issued_at_ms: now_ms,
+            signature: STANDARD.encode(self.key_pair.sign(&message).as_ref()),
+        };
+        object.insert(
+            CONTROL_SIGNATURE_FIELD.to_string(),
+            serde_json::to_value(signature).expect("control signature should serialize"),
+        );
+        body
+    }
+}
+
+impl std::fmt::Debug for ControlSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ControlSigner")
+            .field("public_key", &self.public_key_b64())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Why a coordinator response was rejected.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ControlVerifyError {
+    #[error("response carries no control signature")]
+    Unsigned,
+
+    #[error("malformed control signature")]
+    Malformed,
+
+    #[error("control signature does not verify")]
+    BadSignature,
+
+    #[error("response was issued to node {0}")]
+    WrongNode(String),
+
+    #[error("response is {age_ms} ms away from the local clock")]
+    Stale { age_ms: i64 },
+
+    #[error("response sequence {seq} does not follow {last_seq}")]
+    Replayed { seq: u64, last_seq: u64 },
+}
+
+/// Node-side check of coordinator responses before acting on them.
+///
+/// A response is accepted only when it is signed by the pinned control key,
+/// addressed to this node, issued within `max_age_ms` of the caller's clock
+/// (pass a skew-corrected time, see [`crate::ClockSkewProbe`]) and numbered
+/// after every response accepted before it.
+#[derive(Debug, Clone)]
+pub struct ControlVerifier {
+    node_id: String,
+    public_key: Vec<u8>,
+    max_age_ms: i64,
+    last_seq: u64,
+}
+
+impl ControlVerifier {
+    pub fn new(node_id: impl Into<String>, public_key_b64: &str) -> Result<Self> {
+        let public_key = STANDARD
+            .decode(public_key_b64)
+            .map_err(|_| anyhow!("control public key is not valid base64"))?;
+        if public_key.len() != 32 {
+            return Err(anyhow!("control public key must be 32 bytes"));
+        }
+        Ok(Self {
+            node_id: node_id.into(),
+            public_key,
+            max_age_ms: DEFAULT_CONTROL_MAX_AGE_MS,
+            last_seq: 0,
+        })
+    }
+
+    pub fn with_max_age_ms(mut self, max_age_ms: i64) -> Self {
+        self.max_age_ms = max_age_ms.max(0);
+        self
+    }
+
+    /// Sequence of the newest accepted response.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Check a response and return its body without the signature field.
+    pub fn verify(&mut self, body: Value, now_ms: i64) -> Result<Value, ControlVerifyError> {
+        let (body, control) = open_signed(&self.public_key, body)?;
+
+        if control.node_id != self.node_id {
+            return Err(ControlVerifyError::WrongNode(control.node_id));
+        }
+        let age_ms = now_ms - control.issued_at_ms;
+        if age_ms.abs() > self.max_age_ms {
+            return Err(ControlVerifyError::Stale { age_ms });
+        }
+        if control.seq <= self.last_seq {
+            return Err(ControlVerifyError::Replayed {
+                seq: control.seq,
+                last_seq: self.last_seq,
+            });
+        }
+
+        self.last_seq = control.seq;
+        Ok(body)
+    }
+}
+
+/// Split off and check the signature of a signed body.
+fn open_signed(
+    public_key: &[u8],
+    mut body: Value,
+) -> Result<(Value, ControlSignature), ControlVerifyError> {
+    let raw = body
+        .as_object_mut()
+        .and_then(|object| object.remove(CONTROL_SIGNATURE_FIELD))
+        .ok_or(ControlVerifyError::Unsigned)?;
+    let control: ControlSignature =
+        serde_json::from_value(raw).map_err(|_| ControlVerifyError::Malformed)?;
+    let signature = STANDARD
+        .decode(&control.signature)
+        .map_err(|_| ControlVerifyError::Malformed)?;
+
+    let message = signing_bytes(&body, &control.node_id, control.seq, control.issued_at_ms);
+    signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+        .verify(&message, &signature)
+        .map_err(|_| ControlVerifyError::BadSignature)?;
+    Ok((body, control))
+}
+
+/// Check a published record signed with the control key, such as a
+/// transparency snapshot, against `public_key_b64`.
+///
+/// Only the signature is checked: records are verified long after issue and
+/// out of order, so there is no freshness or sequence check.  The audience
+/// (`ControlSignature::node_id`) is returned for the caller to compare.
+pub fn verify_signed_record(
+    public_key_b64: &str,
+    body: Value,
+) -> Result<(Value, ControlSignature), ControlVerifyError> {
+    let public_key = STANDARD
+        .decode(public_key_b64)
+        .map_err(|_| ControlVerifyError::Malformed)?;
+    open_signed(&public_key, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verifier_rejects_forged_stale_and_replayed_responses() {
+        let signer = ControlSigner::generate().unwrap();
+        let mut verifier = ControlVerifier::new("node-1", &signer.public_key_b64()).unwrap();
+        let body = json!({"node_id": "node-1", "assigned_tasks": [{"task_id": "t-1"}], "health_score": 87.5});
+
+        let first = signer.sign("node-1", body.clone(), 1_000_000);
+        let wire: Value = serde_json::from_str(&first.to_string()).unwrap();
+        assert_eq!(verifier.verify(wire, 1_000_500).unwrap(), body);
+
+        assert!(matches!(
+            verifier.verify(first.clone(), 1_000_500),
+            Err(ControlVerifyError::Replayed { .. })
+        ));
+
+        let late = signer.sign("node-1", body.clone(), 1_000_000);
+        assert!(matches!(
+            verifier.verify(late, 1_000_000 + DEFAULT_CONTROL_MAX_AGE_MS + 1),
+            Err(ControlVerifyError::Stale { .. })
+        ));
+
+        let mut tampered = signer.sign("node-1", body.clone(), 1_000_100);
+        tampered["assigned_tasks"] = json!([]);
+        assert_eq!(
+            verifier.verify(tampered, 1_000_100),
+            Err(ControlVerifyError::BadSignature)
+        );
+
+        let other_node = signer.sign("node-2", body.clone(), 1_000_100);
+        assert_eq!(
+            verifier.verify(other_node, 1_000_100),
+            Err(ControlVerifyError::WrongNode("node-2".into()))
+        );
+
+        assert_eq!(
+            verifier.verify(body.clone(), 1_000_100),
+            Err(ControlVerifyError::Unsigned)
+        );
+
+        let impostor = ControlSigner::generate().unwrap();
+        assert_eq!(
+            verifier.verify(impostor.sign("node-1", body.clone(), 1_000_100), 1_000_100),
+            Err(ControlVerifyError::BadSignature)
+        );
+
+        // A restarted coordinator with the same key keeps numbering upward.
+        let restarted = ControlSigner::from_bytes([7; 32]).unwrap();
+        let mut pinned = ControlVerifier::new("node-1", &restarted.public_key_b64()).unwrap();
+        pinned
+            .verify(restarted.sign("node-1", body.clone(), 2_000_000), 2_000_000)
+            .unwrap();
+        let restarted = ControlSigner::from_bytes([7; 32]).unwrap();
+        assert!(pinned
+            .verify(restarted.sign("node-1", body, 2_000_001), 2_000_001)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_signed_record_verifies_without_freshness() {
+        let signer = ControlSigner::generate().unwrap();
+        let body = json!({"tasks_completed": 12});
+        let signed = signer.sign("transparency", body.clone(), 1_000);
+
+        let (opened, control) =
+            verify_signed_record(&signer.public_key_b64(), signed.clone()).unwrap();
+        assert_eq!(opened, body);
+        assert_eq!(control.node_id, "transparency");
+
+        let mut tampered = signed;
+        tampered["tasks_completed"] = json!(13);
+        assert_eq!(
+            verify_signed_record(&signer.public_key_b64(), tampered).unwrap_err(),
+            ControlVerifyError::BadSignature
+        );
+    }
+}
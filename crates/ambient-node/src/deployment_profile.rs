@@ -0,0 +1,297 @@
+//! Named deployment profiles
+//!
+//! Backhaul probing, WAN keepalive, relay QoS, gateway timeouts and sandbox
+//! limits interact: a battery-powered edge node wants slow probes and small
+//! sandboxes, a datacenter node wants neither keepalives nor QoS shaping.
+//! Control-plane request budgets follow the link the node sits on.  A
+//! profile picks a coherent set of defaults for all of them, and operators
+//! override individual settings on top (see [`DeploymentSettings::resolve`]).
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fmt, str::FromStr};
+use wasm_engine::SandboxLimits;
+
+use crate::ailee_integration::TrustPolicies;
+use crate::connectivity::backhaul::{BackhaulConfig, ProbeConfig};
+use crate::connectivity::{HardwareKeepaliveConfig, RelayQosConfig};
+use crate::gateway::GatewayConfig;
+use crate::gateway_limits::SessionConnectionLimits;
+use crate::request_budget::{RequestBudget, RequestBudgets};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeploymentProfile {
+    /// Battery or solar powered nodes on metered links: probe rarely, keep
+    /// relays modest and sandboxes small.
+    EdgeBattery,
+    /// Always-on home nodes relaying for nearby endpoints over a consumer
+    /// uplink.
+    HomeRelay,
+    /// Rack machines on wired links, sized for compute.
+    DatacenterCompute,
+}
+
+impl DeploymentProfile {
+    pub const ALL: [DeploymentProfile; 3] = [
+        DeploymentProfile::EdgeBattery,
+        DeploymentProfile::HomeRelay,
+        DeploymentProfile::DatacenterCompute,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeploymentProfile::EdgeBattery => "edge-battery",
+            DeploymentProfile::HomeRelay => "home-relay",
+            DeploymentProfile::DatacenterCompute => "datacenter-compute",
+        }
+    }
+
+    /// The profile's node-side defaults.
+    pub fn settings(&self) -> DeploymentSettings {
+        let defaults = DeploymentSettings::default();
+        match self {
+            DeploymentProfile::EdgeBattery => DeploymentSettings {
+                backhaul: BackhaulConfig {
+                    probe_config: ProbeConfig {
+                        interval_secs: 30,
+                        timeout_secs: 5,
+                        ..defaults.backhaul.probe_config
+                    },
+                    // LTE modems still drop idle links, but every probe
+                    // wakes the radio.
+                    hardware_keepalive: HardwareKeepaliveConfig {
+                        enabled: true,
+                        interval_secs: 120,
+                    },
+                    relay_qos_config: RelayQosConfig {
+                        relay_min_bandwidth_kbps: 1_000,
+                        relay_max_bandwidth_kbps: 20_000,
+                        node_min_bandwidth_kbps: 500,
+                        ..defaults.backhaul.relay_qos_config
+                    },
+                    ..defaults.backhaul
+                },
+                gateway: GatewayConfig {
+                    connect_timeout_seconds: 10,
+                    idle_timeout_seconds: 300,
+                    // Small devices run out of sockets first.
+                    session_limits: SessionConnectionLimits {
+                        max_concurrent_tunnels: 32,
+                        max_new_connections_per_minute: 120,
+                        ..defaults.gateway.session_limits
+                    },
+                    ..defaults.gateway
+                },
+                sandbox: SandboxLimits::new(128, 10, 1_000_000_000),
+                // Metered links are slow and flaky; give calls longer and
+                // back off harder rather than waking the radio to retry.
+                requests: RequestBudgets {
+                    heartbeat: RequestBudget {
+                        timeout_ms: 10_000,
+                        ..defaults.requests.heartbeat
+                    },
+                    upload: RequestBudget {
+                        timeout_ms: 900_000,
+                        initial_backoff_ms: 5_000,
+                        max_backoff_ms: 120_000,
+                        ..defaults.requests.upload
+                    },
+                    ..defaults.requests
+                },
+                trust: defaults.trust,
+            },
+            DeploymentProfile::HomeRelay => DeploymentSettings {
+                backhaul: BackhaulConfig {
+                    probe_config: ProbeConfig {
+                        interval_secs: 10,
+                        ..defaults.backhaul.probe_config
+                    },
+                    relay_qos_config: RelayQosConfig {
+                        relay_min_bandwidth_kbps: 5_000,
+                        relay_max_bandwidth_kbps: 100_000,
+                        node_min_bandwidth_kbps: 2_000,
+                        ..defaults.backhaul.relay_qos_config
+                    },
+                    ..defaults.backhaul
+                },
+                gateway: defaults.gateway,
+                sandbox: SandboxLimits::new(256, 30, 5_000_000_000),
+                requests: defaults.requests,
+                trust: defaults.trust,
+            },
+            DeploymentProfile::DatacenterCompute => DeploymentSettings {
+                backhaul: BackhaulConfig {
+                    probe_config: ProbeConfig {
+                        timeout_secs: 2,
+                        ..defaults.backhaul.probe_config
+                    },
+                    // Wired uplinks neither idle out nor need shaping.
+                    hardware_keepalive: HardwareKeepaliveConfig {
+                        enabled: false,
+                        ..defaults.backhaul.hardware_keepalive
+                    },
+                    relay_qos_config: RelayQosConfig {
+                        enabled: false,
+                        ..defaults.backhaul.relay_qos_config
+                    },
+                    ..defaults.backhaul
+                },
+                gateway: GatewayConfig {
+                    connect_timeout_seconds: 3,
+                    idle_timeout_seconds: 1800,
+                    ..defaults.gateway
+                },
+                sandbox: SandboxLimits::new(2048, 300, 100_000_000_000),
+                requests: defaults.requests,
+                trust: defaults.trust,
+            },
+        }
+    }
+}
+
+impl fmt::Display for DeploymentProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DeploymentProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|profile| profile.as_str() == s)
+            .with_context(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|p| p.as_str()).collect();
+                format!(
+                    "unknown deployment profile {s:?}; expected one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Node-side settings a profile covers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeploymentSettings {
+    pub backhaul: BackhaulConfig,
+    pub gateway: GatewayConfig,
+    pub sandbox: SandboxLimits,
+    /// Timeout, retry and breaker budgets of outbound calls
+    #[serde(default)]
+    pub requests: RequestBudgets,
+    /// Trust threshold and model count per generative task type
+    #[serde(default)]
+    pub trust: TrustPolicies,
+}
+
+impl DeploymentSettings {
+    /// The profile's settings (built-in defaults without one), with
+    /// `overrides` merged on top.  `overrides` mirrors the settings' JSON,
+    /// e.g. `{"backhaul": {"probe_config": {"interval_secs": 20}}}`; objects
+    /// merge key by key and anything else replaces the value.  Keys the
+    /// settings do not have are rejected rather than ignored.
+    pub fn resolve(profile: Option<DeploymentProfile>, overrides: &Value) -> Result<Self> {
+        let base = profile.map(|p| p.settings()).unwrap_or_default();
+        if overrides.is_null() {
+            return Ok(base);
+        }
+        let mut merged = serde_json::to_value(base)?;
+        merge_overrides(&mut merged, overrides, "")?;
+        serde_json::from_value(merged).context("invalid deployment setting override")
+    }
+}
+
+fn merge_overrides(target: &mut Value, overrides: &Value, path: &str) -> Result<()> {
+    let (Value::Object(target), Value::Object(overrides)) = (&mut *target, overrides) else {
+        *target = overrides.clone();
+        return Ok(());
+    };
+    for (key, value) in overrides {
+        let key_path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        match target.get_mut(key) {
+            Some(slot) => merge_overrides(slot, value, &key_path)?,
+            None => bail!("unknown deployment setting {key_path}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn profile_names_round_trip() {
+        for profile in DeploymentProfile::ALL {
+            assert_eq!(
+                profile.as_str().parse::<DeploymentProfile>().unwrap(),
+                profile
+            );
+            assert_eq!(
+                serde_json::to_value(profile).unwrap(),
+                json!(profile.as_str())
+            );
+        }
+        assert!("laptop".parse::<DeploymentProfile>().is_err());
+    }
+
+    #[test]
+    fn overrides_apply_on_top_of_the_profile() {
+        let settings = DeploymentSettings::resolve(
+            Some(DeploymentProfile::EdgeBattery),
+            &json!({
+                "backhaul": {"probe_config": {"interval_secs": 60}},
+                "sandbox": {"memory_mb": 64},
+                "requests": {"control": {"max_attempts": 5}},
+            }),
+        )
+        .unwrap();
+
+        assert_eq!(settings.backhaul.probe_config.interval_secs, 60);
+        // Untouched settings keep the profile's values, not the defaults.
+        assert_eq!(settings.backhaul.probe_config.timeout_secs, 5);
+        assert_eq!(settings.backhaul.hardware_keepalive.interval_secs, 120);
+        assert_eq!(settings.sandbox.memory_mb, 64);
+        assert_eq!(settings.sandbox.timeout_seconds, 10);
+        assert_eq!(settings.gateway.idle_timeout_seconds, 300);
+        assert_eq!(settings.requests.control.max_attempts, 5);
+        assert_eq!(settings.requests.upload.timeout_ms, 900_000);
+    }
+
+    #[test]
+    fn no_profile_keeps_built_in_defaults() {
+        let settings = DeploymentSettings::resolve(None, &Value::Null).unwrap();
+        assert_eq!(
+            settings.backhaul.probe_config.interval_secs,
+            ProbeConfig::default().interval_secs
+        );
+        assert_eq!(
+            settings.sandbox.memory_mb,
+            SandboxLimits::default().memory_mb
+        );
+    }
+
+    #[test]
+    fn unknown_or_mistyped_overrides_are_rejected() {
+        let err = DeploymentSettings::resolve(
+            Some(DeploymentProfile::HomeRelay),
+            &json!({"backhaul": {"probe_config": {"interval": 20}}}),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("backhaul.probe_config.interval"));
+
+        assert!(DeploymentSettings::resolve(
+            None,
+            &json!({"gateway": {"idle_timeout_seconds": "soon"}})
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Longest gap between two power samples that is still integrated.
+///
+/// A larger gap usually means the node was suspended or telemetry stalled;
+/// integrating across it would attribute phantom energy to active workloads.
+const MAX_SAMPLE_GAP_SECS: u64 = 300;
+
+/// Energy consumed by a single task or relay session.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+pub struct EnergyUsage {
+    /// Energy attributed to the workload, in watt-hours.
+    pub watt_hours: f64,
+    /// Seconds of integrated power samples that contributed to `watt_hours`.
+    pub metered_secs: u64,
+}
+
+/// Per-workload energy accounting driven by `TelemetrySample::power_watts`.
+///
+/// Each power sample is integrated (trapezoidal rule) over the interval since
+/// the previous sample and the resulting energy is split evenly across every
+/// task and relay session that was active during that interval.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct EnergyMeter {
+    tasks: HashMap<String, EnergyUsage>,
+    sessions: HashMap<String, EnergyUsage>,
+    last_sample: Option<(u64, f64)>,
+    total_watt_hours: f64,
+}
+
+impl EnergyMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Begin attributing energy to a task.
+    pub fn start_task(&mut self, task_id: impl Into<String>) {
+        self.tasks.entry(task_id.into()).or_default();
+    }
+
+    /// Stop metering a task and return what it consumed.
+    pub fn finish_task(&mut self, task_id: &str) -> Option<EnergyUsage> {
+        self.tasks.remove(task_id)
+    }
+
+    /// Begin attributing energy to a relay session.
+    pub fn start_session(&mut self, session_id: impl Into<String>) {
+        self.sessions.entry(session_id.into()).or_default();
+    }
+
+    /// Stop metering a relay session and return what it consumed.
+    pub fn finish_session(&mut self, session_id: &str) -> Option<EnergyUsage> {
+        self.sessions.remove(session_id)
+    }
+
+    /// Energy consumed so far by an active task.
+    pub fn task_usage(&self, task_id: &str) -> Option<EnergyUsage> {
+        self.tasks.get(task_id).copied()
+    }
+
+    /// Energy consumed so far by an active relay session.
+    pub fn session_usage(&self, session_id: &str) -> Option<EnergyUsage> {
+        self.sessions.get(session_id).copied()
+    }
+
+    /// Total energy integrated since the meter was created, including idle time.
+    pub fn total_watt_hours(&self) -> f64 {
+        self.total_watt_hours
+    }
+
+    /// Integrate a power reading taken at `timestamp` (Unix seconds).
+    ///
+    /// Out-of-order samples, non-finite or negative readings, and gaps longer
+    /// than five minutes only reset the integration baseline.
+    pub fn record_power(&mut self, timestamp: u64, power_watts: f64) {
+        if !power_watts.is_finite() || power_watts < 0.0 {
+            return;
+        }
+
+        let previous = self.last_sample.replace((timestamp, power_watts));
+        let Some((prev_ts, prev_watts)) = previous else {
+            return;
+        };
+
+        if timestamp <= prev_ts || timestamp - prev_ts > MAX_SAMPLE_GAP_SECS {
+            return;
+        }
+
+        let elapsed = timestamp - prev_ts;
+        let watt_hours = (prev_watts + power_watts) / 2.0 * elapsed as f64 / 3600.0;
+        self.total_watt_hours += watt_hours;
+
+        let active = self.tasks.len() + self.sessions.len();
+        if active == 0 {
+            return;
+        }
+
+        let share = watt_hours / active as f64;
+        for usage in self.tasks.values_mut().chain(self.sessions.values_mut()) {
+            usage.watt_hours += share;
+            usage.metered_secs += elapsed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integrates_constant_power_for_single_task() {
+        let mut meter = EnergyMeter::new();
+        meter.start_task("task-1");
+        meter.record_power(1_000, 120.0);
+        meter.record_power(1_060, 120.0);
+
+        let usage = meter.finish_task("task-1").unwrap();
+        assert!((usage.watt_hours - 2.0).abs() < 1e-9);
+        assert_eq!(usage.metered_secs, 60);
+        assert!(meter.task_usage("task-1").is_none());
+    }
+
+    #[test]
+    fn test_splits_energy_across_tasks_and_sessions() {
+        let mut meter = EnergyMeter::new();
+        meter.start_task("task-1");
+        meter.start_session("session-1");
+        meter.record_power(0, 100.0);
+        meter.record_power(36, 100.0);
+
+        let task = meter.task_usage("task-1").unwrap();
+        let session = meter.session_usage("session-1").unwrap();
+        assert!((task.watt_hours - 0.5).abs() < 1e-9);
+        assert!((session.watt_hours - 0.5).abs() < 1e-9);
+        assert!((meter.total_watt_hours() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ignores_gaps_and_invalid_samples() {
+        let mut meter = EnergyMeter::new();
+        meter.start_task("task-1");
+        meter.record_power(0, 100.0);
+        meter.record_power(10_000, 100.0);
+        meter.record_power(10_010, f64::NAN);
+        meter.record_power(9_000, 100.0);
+
+        assert_eq!(meter.task_usage("task-1").unwrap().watt_hours, 0.0);
+        assert_eq!(meter.total_watt_hours(), 0.0);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::request_budget::{BudgetedClient, EndpointClass, RequestBudgets};
 use crate::{AileeMetric, AileeSample};
 use anyhow::Result;
 use async_trait::async_trait;
@@ -58,16 +59,17 @@ pub trait FeenEngine: Send + Sync {
 #[derive(Debug, Clone)]
 pub struct FeenClient {
     pub api_url: String,
-    client: reqwest::Client,
+    client: BudgetedClient,
 }
 
 impl FeenClient {
     pub fn new(api_url: String) -> Self {
-        let client = reqwest::Client::builder()
-            .connect_timeout(std::time::Duration::from_secs(5))
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .expect("Failed to build HTTP client");
+        Self::with_budgets(api_url, RequestBudgets::default())
+    }
+
+    /// Client whose calls run under the `engine` budget of `budgets`.
+    pub fn with_budgets(api_url: String, budgets: RequestBudgets) -> Self {
+        let client = BudgetedClient::new(budgets).expect("Failed to build HTTP client");
         Self { api_url, client }
     }
 }
@@ -109,9 +111,11 @@ impl FeenEngine for FeenClient {
         let url = format!("{}/api/v1/simulate", self.api_url);
         let response = self
             .client
-            .post(&url)
-            .json(&request)
-            .send()
+            .send(
+                EndpointClass::Engine,
+                "feen-simulate",
+                self.client.client().post(&url).json(&request),
+            )
             .await
             .map_err(|e| anyhow::anyhow!("Failed to send request: {}", e))?;
 
@@ -131,9 +135,11 @@ impl FeenEngine for FeenClient {
         let url = format!("{}/api/v1/coupling", self.api_url);
         let response = self
             .client
-            .post(&url)
-            .json(config)
-            .send()
+            .send(
+                EndpointClass::Engine,
+                "feen-coupling",
+                self.client.client().post(&url).json(config),
+            )
             .await
             .map_err(|e| anyhow::anyhow!("Failed to send request: {}", e))?;
 
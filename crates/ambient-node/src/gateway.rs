@@ -1,28 +1,62 @@
 use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rustls::pki_types::CertificateDer;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     net::{IpAddr, SocketAddr},
     path::Path,
     str::FromStr,
-    sync::Arc,
-    time::Duration,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
     net::{TcpListener, TcpStream},
-    sync::RwLock,
+    sync::{broadcast, watch, Notify, RwLock},
 };
 use tracing::{debug, info, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::gateway_access_log::{AccessLog, AccessRecord, TerminationReason};
+use crate::gateway_dns::{self, is_internal_address, DnsStats, GatewayResolver};
+use crate::gateway_limits::{
+    ConnectionLimiter, SessionConnectionLimits, SessionLimitExceeded, TunnelPermit,
+};
+use crate::gateway_socks5 as socks5;
+use crate::gateway_tls::{self, GatewayTlsConfig};
+use crate::gateway_transparent::{self, RedirectRules, TransparentConfig};
+use crate::gateway_usage::{CountedStream, GatewayUsage};
+use crate::policy_bundle::PolicyBundleCache;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GatewaySession {
     pub session_id: String,
     pub session_token: String,
     pub egress_profile: String,
     pub destination_policy_id: String,
     pub allowed_destinations: Vec<String>,
+    /// Cap on the session's relayed throughput in each direction, shared by
+    /// all of its relays.  `None` or a non-positive value is unlimited.
+    #[serde(default)]
+    pub bandwidth_limit_mbps: Option<f64>,
+    /// Tunnel protocol of the connect session.  `mtls` sessions are only
+    /// relayed over the TLS listener, by clients presenting the certificate
+    /// derived from the session (see [`crate::gateway_tls`]).
+    #[serde(default)]
+    pub tunnel_protocol: Option<String>,
+    pub expires_at_epoch_seconds: u64,
+}
+
+/// A live session as the local admin socket lists it, without its token.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GatewaySessionSummary {
+    pub session_id: String,
+    pub egress_profile: String,
+    pub destination_policy_id: String,
+    pub tunnel_protocol: Option<String>,
     pub expires_at_epoch_seconds: u64,
+    /// Relays currently open for the session
+    pub active_tunnels: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +64,50 @@ pub struct GatewayConfig {
     pub listen_addr: String,
     pub connect_timeout_seconds: u64,
     pub idle_timeout_seconds: u64,
+    /// Coordinator clock minus local clock (ms), from `ClockSkewProbe`.
+    /// Applied before comparing against server-issued session expiry.
+    #[serde(default)]
+    pub clock_offset_ms: i64,
+    /// Seconds a live relay may outlast its session's expiry before the
+    /// expiry scheduler tears it down.
+    #[serde(default = "default_expiry_grace_seconds")]
+    pub expiry_grace_seconds: u64,
+    /// Node certificate for the TLS listener; `None` keeps the plaintext
+    /// handshake, which `mtls` sessions refuse.
+    #[serde(default)]
+    pub tls: Option<GatewayTlsConfig>,
+    /// Address of the SOCKS5 listener for unmodified applications (see
+    /// [`crate::gateway_socks5`]); `None` serves only the handshake
+    /// protocol.
+    #[serde(default)]
+    pub socks5_listen_addr: Option<String>,
+    /// How long the gateway caches the addresses it resolved for relayed
+    /// destinations (see [`crate::gateway_dns`]); `0` resolves every time.
+    #[serde(default = "default_dns_cache_ttl_seconds")]
+    pub dns_cache_ttl_seconds: u64,
+    /// Caps on each session's tunnels and connections (see
+    /// [`crate::gateway_limits`]).
+    #[serde(default)]
+    pub session_limits: SessionConnectionLimits,
+    /// Relay connections the node's firewall redirects to the gateway,
+    /// without any client configuration (see
+    /// [`crate::gateway_transparent`]); `None` leaves the mode off.
+    #[serde(default)]
+    pub transparent: Option<TransparentConfig>,
+    /// Let relays reach loopback, private and link-local addresses (see
+    /// [`crate::gateway_dns`]).  Off by default, so a session cannot reach
+    /// the node's own services or its cloud metadata endpoint; for
+    /// single-host development only.
+    #[serde(default)]
+    pub allow_internal_upstreams: bool,
+}
+
+fn default_expiry_grace_seconds() -> u64 {
+    5
+}
+
+fn default_dns_cache_ttl_seconds() -> u64 {
+    60
 }
 
 impl Default for GatewayConfig {
@@ -38,14 +116,138 @@ impl Default for GatewayConfig {
             listen_addr: "0.0.0.0:7000".to_string(),
             connect_timeout_seconds: 5,
             idle_timeout_seconds: 600,
+            clock_offset_ms: 0,
+            expiry_grace_seconds: default_expiry_grace_seconds(),
+            tls: None,
+            socks5_listen_addr: None,
+            dns_cache_ttl_seconds: default_dns_cache_ttl_seconds(),
+            session_limits: SessionConnectionLimits::default(),
+            transparent: None,
+            allow_internal_upstreams: false,
+        }
+    }
+}
+
+/// Longest the expiry scheduler sleeps before re-checking deadlines.
+const MAX_EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Read size of throttled relays, and the smallest bucket burst.
+const THROTTLE_CHUNK_BYTES: usize = 16 * 1024;
+
+/// Session lifecycle notifications for the session reconciler and usage
+/// reporter, delivered through [`DataPlaneGateway::subscribe_events`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GatewayEvent {
+    /// A session passed its expiry (plus grace) and was torn down.
+    SessionExpired {
+        session_id: String,
+        expires_at_epoch_seconds: u64,
+        /// Live relays terminated by the expiry
+        terminated_relays: usize,
+    },
+}
+
+/// A provisioned session and the signal its live relays watch for teardown.
+/// Dropping `terminate` (on revoke) also tears the relays down.
+#[derive(Debug)]
+struct LiveSession {
+    session: GatewaySession,
+    terminate: watch::Sender<bool>,
+    throttle: Option<Arc<SessionThrottle>>,
+    limiter: Arc<ConnectionLimiter>,
+}
+
+impl LiveSession {
+    fn new(session: GatewaySession) -> Self {
+        Self {
+            throttle: SessionThrottle::for_session(&session),
+            session,
+            terminate: watch::channel(false).0,
+            limiter: Arc::default(),
+        }
+    }
+}
+
+/// Token bucket for one direction of a session.  A chunk larger than the
+/// balance still goes through; the balance goes negative and the sender
+/// waits until it has refilled to zero.
+#[derive(Debug)]
+struct TokenBucket {
+    bytes_per_second: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket holding one second of traffic.
+    fn new(bytes_per_second: f64, now: Instant) -> Self {
+        let capacity = bytes_per_second.max(THROTTLE_CHUNK_BYTES as f64);
+        Self {
+            bytes_per_second,
+            capacity,
+            tokens: capacity,
+            refilled_at: now,
+        }
+    }
+
+    /// Take `bytes` from the bucket and return how long to wait before
+    /// sending them.
+    fn reserve(&mut self, bytes: usize, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.bytes_per_second).min(self.capacity);
+        self.refilled_at = now;
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.bytes_per_second)
         }
     }
 }
 
+/// Enforces `bandwidth_limit_mbps` across every relay of a session.
+#[derive(Debug)]
+struct SessionThrottle {
+    limit_mbps: f64,
+    /// Client to upstream
+    upload: Mutex<TokenBucket>,
+    /// Upstream to client
+    download: Mutex<TokenBucket>,
+}
+
+impl SessionThrottle {
+    fn for_session(session: &GatewaySession) -> Option<Arc<Self>> {
+        let limit_mbps = session
+            .bandwidth_limit_mbps
+            .filter(|mbps| mbps.is_finite() && *mbps > 0.0)?;
+        let bytes_per_second = limit_mbps * 1_000_000.0 / 8.0;
+        let now = Instant::now();
+        Some(Arc::new(Self {
+            limit_mbps,
+            upload: Mutex::new(TokenBucket::new(bytes_per_second, now)),
+            download: Mutex::new(TokenBucket::new(bytes_per_second, now)),
+        }))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DataPlaneGateway {
     config: GatewayConfig,
-    sessions: Arc<RwLock<HashMap<String, GatewaySession>>>,
+    sessions: Arc<RwLock<HashMap<String, LiveSession>>>,
+    events: broadcast::Sender<GatewayEvent>,
+    /// Wakes the expiry scheduler when a new deadline may be earlier.
+    expiry_wakeup: Arc<Notify>,
+    /// Destination policies for sessions provisioned without an allowlist.
+    policy_cache: Option<Arc<RwLock<PolicyBundleCache>>>,
+    resolver: Arc<GatewayResolver>,
+    /// Coordinator epoch second the node shuts down, once announced; no
+    /// session outlives it.
+    drain_deadline: Arc<RwLock<Option<u64>>>,
+    usage: GatewayUsage,
+    access_log: Option<Arc<AccessLog>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -59,14 +261,50 @@ impl DataPlaneGateway {
     pub fn new(config: GatewayConfig, sessions: Vec<GatewaySession>) -> Self {
         let map = sessions
             .into_iter()
-            .map(|session| (session.session_id.clone(), session))
+            .map(|session| (session.session_id.clone(), LiveSession::new(session)))
             .collect();
+        let resolver = GatewayResolver::new(Duration::from_secs(config.dns_cache_ttl_seconds));
         Self {
             config,
             sessions: Arc::new(RwLock::new(map)),
+            events: broadcast::channel(64).0,
+            expiry_wakeup: Arc::new(Notify::new()),
+            policy_cache: None,
+            resolver: Arc::new(resolver),
+            drain_deadline: Arc::new(RwLock::new(None)),
+            usage: GatewayUsage::default(),
+            access_log: None,
         }
     }
 
+    /// Write an [`AccessRecord`] for every relayed connection to `log`.
+    pub fn with_access_log(mut self, log: AccessLog) -> Self {
+        self.access_log = Some(Arc::new(log));
+        self
+    }
+
+    /// Per-session relay counters; see [`crate::gateway_usage`].
+    pub fn usage(&self) -> GatewayUsage {
+        self.usage.clone()
+    }
+
+    /// Check `allowlist_domains` sessions that arrive without
+    /// `allowed_destinations` against the cached destination-policy bundle.
+    pub fn with_policy_cache(mut self, cache: PolicyBundleCache) -> Self {
+        self.policy_cache = Some(Arc::new(RwLock::new(cache)));
+        self
+    }
+
+    /// Verify and cache a newer signed policy bundle fetched from the
+    /// coordinator.  Returns the cached version.
+    pub async fn install_policy_bundle(&self, signed: serde_json::Value) -> Result<u64> {
+        let cache = self
+            .policy_cache
+            .as_ref()
+            .context("gateway has no policy cache")?;
+        Ok(cache.write().await.install(signed)?)
+    }
+
     pub async fn from_sessions_file(
         config: GatewayConfig,
         sessions_file: impl AsRef<Path>,
@@ -82,23 +320,182 @@ impl DataPlaneGateway {
     /// Provision a new session into the gateway's live session store.
     ///
     /// Call this when a connect session is started so the endpoint can
-    /// immediately begin relaying traffic through this node.
-    pub async fn add_session(&self, session: GatewaySession) {
+    /// immediately begin relaying traffic through this node.  Re-adding an
+    /// existing session updates it in place; its live relays keep running
+    /// against the new expiry and keep counting against its connection
+    /// limits.  A changed bandwidth limit applies to relays opened after the
+    /// update.
+    pub async fn add_session(&self, mut session: GatewaySession) {
+        if let Some(deadline) = *self.drain_deadline.read().await {
+            session.expires_at_epoch_seconds = session.expires_at_epoch_seconds.min(deadline);
+        }
         let mut sessions = self.sessions.write().await;
-        sessions.insert(session.session_id.clone(), session);
+        match sessions.get_mut(&session.session_id) {
+            Some(live) => {
+                if live.session.bandwidth_limit_mbps != session.bandwidth_limit_mbps {
+                    live.throttle = SessionThrottle::for_session(&session);
+                }
+                live.session = session;
+            }
+            None => {
+                sessions.insert(session.session_id.clone(), LiveSession::new(session));
+            }
+        }
+        drop(sessions);
+        self.expiry_wakeup.notify_one();
     }
 
     /// Remove a session from the gateway's live session store.
     ///
-    /// Call this when a connect session is stopped or expires so the
-    /// node stops relaying internet traffic on behalf of the endpoint.
-    /// Returns `true` if the session existed and was removed, `false`
-    /// if it was not present (already removed or never added).
+    /// Call this when a connect session is stopped so the node stops
+    /// relaying internet traffic on behalf of the endpoint; relays already
+    /// running for the session are torn down.  Returns `true` if the session
+    /// existed and was removed, `false` if it was not present (already
+    /// removed, expired, or never added).
     pub async fn revoke_session(&self, session_id: &str) -> bool {
         let mut sessions = self.sessions.write().await;
         sessions.remove(session_id).is_some()
     }
 
+    /// The live sessions, ordered by session ID.
+    pub async fn list_sessions(&self) -> Vec<GatewaySessionSummary> {
+        let sessions = self.sessions.read().await;
+        let mut summaries: Vec<GatewaySessionSummary> = sessions
+            .values()
+            .map(|live| GatewaySessionSummary {
+                session_id: live.session.session_id.clone(),
+                egress_profile: live.session.egress_profile.clone(),
+                destination_policy_id: live.session.destination_policy_id.clone(),
+                tunnel_protocol: live.session.tunnel_protocol.clone(),
+                expires_at_epoch_seconds: live.session.expires_at_epoch_seconds,
+                active_tunnels: live.limiter.active(),
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        summaries
+    }
+
+    /// Current time on the coordinator clock, Unix milliseconds.
+    pub(crate) fn coordinator_now_ms(&self) -> i64 {
+        coordinator_now_ms(self.config.clock_offset_ms)
+    }
+
+    /// Whether the expiry scheduler would already tear `session` down.
+    pub(crate) fn is_expired(&self, session: &GatewaySession) -> bool {
+        coordinator_now_ms(self.config.clock_offset_ms)
+            >= expiry_deadline_ms(session, self.config.expiry_grace_seconds)
+    }
+
+    /// Prepare for the node shutting down in `grace_seconds`: every session,
+    /// including ones added later, expires by then, so live relays are torn
+    /// down by the expiry scheduler (after `expiry_grace_seconds`) rather
+    /// than cut when the process exits.  Announce the drain to the
+    /// coordinator as well so clients move to another relay first.  Returns
+    /// the deadline on the coordinator clock.
+    pub async fn begin_drain(&self, grace_seconds: u64) -> u64 {
+        let now = coordinator_now_ms(self.config.clock_offset_ms).max(0) as u64 / 1000;
+        let deadline = now.saturating_add(grace_seconds);
+        let deadline = {
+            let mut drain_deadline = self.drain_deadline.write().await;
+            let deadline = drain_deadline.map_or(deadline, |current| current.min(deadline));
+            *drain_deadline = Some(deadline);
+            deadline
+        };
+        let mut sessions = self.sessions.write().await;
+        for live in sessions.values_mut() {
+            live.session.expires_at_epoch_seconds =
+                live.session.expires_at_epoch_seconds.min(deadline);
+        }
+        drop(sessions);
+        self.expiry_wakeup.notify_one();
+        info!(grace_seconds, deadline, "data-plane gateway draining");
+        deadline
+    }
+
+    /// Resolver cache counters and destinations blocked per policy.
+    pub fn dns_stats(&self) -> DnsStats {
+        self.resolver.stats()
+    }
+
+    /// Subscribe to session lifecycle events such as expiry teardowns.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<GatewayEvent> {
+        self.events.subscribe()
+    }
+
+    /// Remove every session whose expiry plus grace has passed on the
+    /// coordinator clock, terminate its live relays and publish a
+    /// [`GatewayEvent::SessionExpired`] for each.
+    pub async fn expire_due_sessions(&self) -> Vec<GatewayEvent> {
+        let now_ms = coordinator_now_ms(self.config.clock_offset_ms);
+        let grace_seconds = self.config.expiry_grace_seconds;
+
+        let expired: Vec<LiveSession> = {
+            let mut sessions = self.sessions.write().await;
+            let due: Vec<String> = sessions
+                .iter()
+                .filter(|(_, live)| now_ms >= expiry_deadline_ms(&live.session, grace_seconds))
+                .map(|(session_id, _)| session_id.clone())
+                .collect();
+            due.iter()
+                .filter_map(|session_id| sessions.remove(session_id))
+                .collect()
+        };
+
+        let events: Vec<GatewayEvent> = expired
+            .into_iter()
+            .map(|live| {
+                let terminated_relays = live.terminate.receiver_count();
+                live.terminate.send_replace(true);
+                info!(
+                    session_id = %live.session.session_id,
+                    terminated_relays,
+                    "gateway session expired"
+                );
+                GatewayEvent::SessionExpired {
+                    session_id: live.session.session_id,
+                    expires_at_epoch_seconds: live.session.expires_at_epoch_seconds,
+                    terminated_relays,
+                }
+            })
+            .collect();
+
+        for event in &events {
+            // No subscribers is fine; the teardown itself already happened.
+            let _ = self.events.send(event.clone());
+        }
+        events
+    }
+
+    /// Tear sessions down as their deadlines pass.  Sleeps until the next
+    /// deadline, waking early when a session is added or updated.
+    pub async fn run_expiry_scheduler(self) {
+        loop {
+            self.expire_due_sessions().await;
+
+            let next_deadline_ms = {
+                let sessions = self.sessions.read().await;
+                sessions
+                    .values()
+                    .map(|live| expiry_deadline_ms(&live.session, self.config.expiry_grace_seconds))
+                    .min()
+            };
+            let wait = next_deadline_ms
+                .map(|deadline_ms| {
+                    let remaining_ms =
+                        deadline_ms - coordinator_now_ms(self.config.clock_offset_ms);
+                    Duration::from_millis(remaining_ms.max(0) as u64)
+                })
+                .map_or(MAX_EXPIRY_CHECK_INTERVAL, |wait| {
+                    wait.min(MAX_EXPIRY_CHECK_INTERVAL)
+                });
+
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.expiry_wakeup.notified() => {}
+            }
+        }
+    }
+
     pub async fn run(self) -> Result<()> {
         let listener = TcpListener::bind(&self.config.listen_addr)
             .await
@@ -109,20 +506,214 @@ impl DataPlaneGateway {
                 )
             })?;
 
-        info!(listen_addr = %self.config.listen_addr, "data-plane gateway listening");
+        let acceptor = self
+            .config
+            .tls
+            .as_ref()
+            .map(gateway_tls::acceptor)
+            .transpose()?;
+
+        info!(
+            listen_addr = %self.config.listen_addr,
+            tls = acceptor.is_some(),
+            "data-plane gateway listening"
+        );
+
+        if let Some(socks5_addr) = &self.config.socks5_listen_addr {
+            let socks5_listener = TcpListener::bind(socks5_addr)
+                .await
+                .with_context(|| format!("failed to bind SOCKS5 listener on {socks5_addr}"))?;
+            info!(listen_addr = %socks5_addr, "gateway SOCKS5 listener ready");
+            tokio::spawn(self.clone().run_socks5(socks5_listener));
+        }
+
+        if let Some(transparent) = &self.config.transparent {
+            transparent.validate()?;
+            let transparent_listener = TcpListener::bind(&transparent.listen_addr)
+                .await
+                .with_context(|| {
+                    format!(
+                        "failed to bind transparent listener on {}",
+                        transparent.listen_addr
+                    )
+                })?;
+            if transparent.install_rules {
+                RedirectRules::new(transparent.clone(), true).install()?;
+            }
+            info!(
+                listen_addr = %transparent.listen_addr,
+                interface = %transparent.interface,
+                bindings = transparent.bindings.len(),
+                "gateway transparent intercept listener ready"
+            );
+            tokio::spawn(
+                self.clone()
+                    .run_transparent(transparent_listener, transparent.clone()),
+            );
+        }
+
+        tokio::spawn(self.clone().run_expiry_scheduler());
 
         loop {
             let (stream, peer_addr) = listener.accept().await?;
             let gateway = self.clone();
+            let acceptor = acceptor.clone();
             tokio::spawn(async move {
-                if let Err(err) = gateway.handle_connection(stream, peer_addr).await {
+                let result = match acceptor {
+                    Some(acceptor) => gateway.accept_tls(&acceptor, stream, peer_addr).await,
+                    None => gateway.handle_connection(stream, peer_addr, None).await,
+                };
+                if let Err(err) = result {
                     warn!(%peer_addr, "gateway connection terminated: {err:#}");
                 }
             });
         }
     }
 
-    async fn handle_connection(&self, mut stream: TcpStream, peer_addr: SocketAddr) -> Result<()> {
+    async fn run_socks5(self, listener: TcpListener) {
+        loop {
+            let (stream, peer_addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    warn!("SOCKS5 listener accept failed: {err}");
+                    continue;
+                }
+            };
+            let gateway = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = gateway.handle_socks5_connection(stream, peer_addr).await {
+                    warn!(%peer_addr, "SOCKS5 connection terminated: {err:#}");
+                }
+            });
+        }
+    }
+
+    async fn run_transparent(self, listener: TcpListener, config: TransparentConfig) {
+        let config = Arc::new(config);
+        loop {
+            let (stream, peer_addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    warn!("transparent listener accept failed: {err}");
+                    continue;
+                }
+            };
+            let gateway = self.clone();
+            let config = config.clone();
+            tokio::spawn(async move {
+                if let Err(err) = gateway
+                    .handle_transparent_connection(stream, peer_addr, &config)
+                    .await
+                {
+                    warn!(%peer_addr, "transparent connection terminated: {err:#}");
+                }
+            });
+        }
+    }
+
+    /// Relay a firewall-redirected connection under its source's session
+    /// to the destination it was originally headed for.  Refusals just
+    /// close the connection: the client spoke to what it thinks is the
+    /// destination, so there is no protocol to report them in.
+    async fn handle_transparent_connection(
+        &self,
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+        config: &TransparentConfig,
+    ) -> Result<()> {
+        let session_id = config
+            .session_for(peer_addr.ip())
+            .context("no transparent binding covers the client address")?;
+        let original = gateway_transparent::original_destination(&stream)
+            .context("failed to recover the original destination")?;
+        // Without a redirect the "original" destination is the listener.
+        anyhow::ensure!(
+            original != stream.local_addr()?,
+            "connection reached the transparent listener without being redirected"
+        );
+        let destination = original.to_string();
+
+        let grant = self.session_grant(session_id).await?;
+        ensure_session_live(&grant.session, self.config.clock_offset_ms)?;
+        anyhow::ensure!(
+            grant.session.tunnel_protocol.as_deref() != Some("mtls"),
+            "mtls sessions are only relayed over the TLS listener"
+        );
+        let session = self
+            .authorize_destination(grant.session.clone(), &destination)
+            .await?;
+        let _permit = self.admit_connection(&grant)?;
+        let upstream = self.connect_upstream(&destination).await?;
+
+        self.relay(
+            stream,
+            upstream,
+            session,
+            grant,
+            peer_addr,
+            &destination,
+            "transparent",
+        )
+        .await
+    }
+
+    /// Complete the TLS handshake, then serve the connection with the
+    /// client certificate (if any) it presented.
+    async fn accept_tls(
+        &self,
+        acceptor: &tokio_rustls::TlsAcceptor,
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+    ) -> Result<()> {
+        let stream = tokio::time::timeout(
+            Duration::from_secs(self.config.idle_timeout_seconds),
+            acceptor.accept(stream),
+        )
+        .await
+        .context("TLS handshake timeout")?
+        .context("TLS handshake failed")?;
+        let client_cert = stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(|cert| cert.clone().into_owned());
+        self.handle_connection(stream, peer_addr, client_cert).await
+    }
+
+    /// Fill in an allowlist session's destinations from the policy cache.
+    /// An expired bundle or unknown policy refuses the relay.
+    async fn resolve_destination_policy(
+        &self,
+        mut session: GatewaySession,
+    ) -> Result<GatewaySession> {
+        let Some(cache) = &self.policy_cache else {
+            return Ok(session);
+        };
+        if session.egress_profile != "allowlist_domains" || !session.allowed_destinations.is_empty()
+        {
+            return Ok(session);
+        }
+        session.allowed_destinations = cache
+            .read()
+            .await
+            .allowed_destinations(
+                &session.destination_policy_id,
+                coordinator_now_ms(self.config.clock_offset_ms),
+            )?
+            .to_vec();
+        Ok(session)
+    }
+
+    async fn handle_connection<S>(
+        &self,
+        mut stream: S,
+        peer_addr: SocketAddr,
+        client_cert: Option<CertificateDer<'static>>,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         let idle_timeout = Duration::from_secs(self.config.idle_timeout_seconds);
 
         let mut reader = BufReader::new(&mut stream);
@@ -136,49 +727,467 @@ impl DataPlaneGateway {
         let handshake: HandshakeRequest =
             serde_json::from_str(handshake_line.trim()).context("invalid handshake JSON")?;
 
-        let session = {
-            let sessions = self.sessions.read().await;
-            sessions
-                .get(&handshake.session_id)
-                .cloned()
-                .context("unknown session_id")?
+        let grant = self
+            .authenticate(
+                &handshake.session_id,
+                &handshake.session_token,
+                client_cert.as_ref(),
+            )
+            .await?;
+        let session = self
+            .authorize_destination(grant.session.clone(), &handshake.destination)
+            .await?;
+        let _permit = match self.admit_connection(&grant) {
+            Ok(permit) => permit,
+            Err(exceeded) => {
+                let reply = format!("ERR {}\n", serde_json::to_string(&exceeded)?);
+                let _ = stream.write_all(reply.as_bytes()).await;
+                return Err(exceeded.into());
+            }
         };
+        let upstream = self.connect_upstream(&handshake.destination).await?;
+
+        stream
+            .write_all(b"OK\n")
+            .await
+            .context("failed to send handshake ack")?;
 
-        validate_session(&session, &handshake.session_token)?;
-        validate_destination(&session, &handshake.destination)?;
+        self.relay(
+            stream,
+            upstream,
+            session,
+            grant,
+            peer_addr,
+            &handshake.destination,
+            "tunnel",
+        )
+        .await
+    }
 
-        let destination = handshake.destination.clone();
-        let mut upstream = tokio::time::timeout(
-            Duration::from_secs(self.config.connect_timeout_seconds),
-            TcpStream::connect(&destination),
+    /// Serve one SOCKS5 client; see [`crate::gateway_socks5`].
+    async fn handle_socks5_connection<S>(&self, mut stream: S, peer_addr: SocketAddr) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_seconds);
+
+        let (session_id, session_token) =
+            tokio::time::timeout(idle_timeout, socks5::read_credentials(&mut stream))
+                .await
+                .context("SOCKS5 handshake timeout")??;
+        let grant = match self.authenticate(&session_id, &session_token, None).await {
+            Ok(grant) => grant,
+            Err(err) => {
+                let _ = socks5::send_auth_status(&mut stream, false).await;
+                return Err(err);
+            }
+        };
+        socks5::send_auth_status(&mut stream, true).await?;
+
+        let destination =
+            match tokio::time::timeout(idle_timeout, socks5::read_request(&mut stream))
+                .await
+                .context("SOCKS5 request timeout")??
+            {
+                Ok(destination) => destination,
+                Err(reply) => {
+                    socks5::send_reply(&mut stream, reply, None).await?;
+                    anyhow::bail!("unsupported SOCKS5 request ({reply:?})");
+                }
+            };
+        let session = match self
+            .authorize_destination(grant.session.clone(), &destination)
+            .await
+        {
+            Ok(session) => session,
+            Err(err) => {
+                let _ = socks5::send_reply(&mut stream, socks5::Reply::NotAllowed, None).await;
+                return Err(err);
+            }
+        };
+        let _permit = match self.admit_connection(&grant) {
+            Ok(permit) => permit,
+            Err(exceeded) => {
+                let _ = socks5::send_reply(&mut stream, socks5::Reply::NotAllowed, None).await;
+                return Err(exceeded.into());
+            }
+        };
+        let upstream = match self.connect_upstream(&destination).await {
+            Ok(upstream) => upstream,
+            Err(err) => {
+                let reply = socks5::Reply::for_connect_error(&err);
+                let _ = socks5::send_reply(&mut stream, reply, None).await;
+                return Err(err);
+            }
+        };
+        socks5::send_reply(
+            &mut stream,
+            socks5::Reply::Succeeded,
+            upstream.local_addr().ok(),
         )
         .await
-        .context("upstream connect timeout")?
-        .with_context(|| format!("failed to connect upstream destination {destination}"))?;
+        .context("failed to send SOCKS5 reply")?;
+
+        self.relay(
+            stream,
+            upstream,
+            session,
+            grant,
+            peer_addr,
+            &destination,
+            "socks5",
+        )
+        .await
+    }
 
+    /// Serve one CONNECT request of an [`HttpConnectProxy`] attached to the
+    /// gateway, under the session its `Proxy-Authorization` names.
+    async fn handle_http_connect(
+        &self,
+        mut stream: TcpStream,
+        peer_addr: SocketAddr,
+        destination: &str,
+        proxy_auth: Option<&str>,
+    ) -> Result<()> {
+        let grant = match self.proxy_grant(proxy_auth).await {
+            Ok(grant) => grant,
+            Err(err) => {
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"ambient-vcp\"\r\nProxy-Authenticate: Bearer\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+                return Err(err);
+            }
+        };
+        let session = match self
+            .authorize_destination(grant.session.clone(), destination)
+            .await
+        {
+            Ok(session) => session,
+            Err(err) => {
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+                return Err(err);
+            }
+        };
+        let _permit = match self.admit_connection(&grant) {
+            Ok(permit) => permit,
+            Err(exceeded) => {
+                let body = serde_json::to_string(&exceeded)?;
+                let reply = format!(
+                    "HTTP/1.1 429 Too Many Requests\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(reply.as_bytes()).await;
+                return Err(exceeded.into());
+            }
+        };
+        let upstream = match self.connect_upstream(destination).await {
+            Ok(upstream) => upstream,
+            Err(err) => {
+                let reply: &[u8] = if err.downcast_ref::<tokio::time::error::Elapsed>().is_some() {
+                    b"HTTP/1.1 504 Gateway Timeout\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                } else {
+                    b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                };
+                let _ = stream.write_all(reply).await;
+                return Err(err);
+            }
+        };
         stream
-            .write_all(b"OK\n")
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
             .await
-            .context("failed to send handshake ack")?;
+            .context("failed to send 200 Connection Established")?;
+
+        self.relay(
+            stream,
+            upstream,
+            session,
+            grant,
+            peer_addr,
+            destination,
+            "http_connect",
+        )
+        .await
+    }
 
-        let bytes_relayed = tokio::time::timeout(
-            idle_timeout,
-            tokio::io::copy_bidirectional(&mut stream, &mut upstream),
+    /// The session a `Proxy-Authorization` header names: `Bearer <token>`
+    /// or `Basic base64(<session_id>:<token>)`.
+    async fn proxy_grant(&self, proxy_auth: Option<&str>) -> Result<RelayGrant> {
+        let (scheme, credentials) = proxy_auth
+            .and_then(|value| value.split_once(' '))
+            .context("missing Proxy-Authorization")?;
+        let credentials = credentials.trim();
+        let (session_id, session_token) = if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = STANDARD
+                .decode(credentials)
+                .context("invalid Basic proxy credentials")?;
+            let decoded = String::from_utf8(decoded).context("invalid Basic proxy credentials")?;
+            let (session_id, token) = decoded
+                .split_once(':')
+                .context("Basic proxy credentials must be session_id:token")?;
+            (session_id.to_string(), token.to_string())
+        } else if scheme.eq_ignore_ascii_case("bearer") {
+            let session_id = self
+                .sessions
+                .read()
+                .await
+                .values()
+                .find(|live| live.session.session_token == credentials)
+                .map(|live| live.session.session_id.clone())
+                .context("unknown session token")?;
+            (session_id, credentials.to_string())
+        } else {
+            anyhow::bail!("unsupported Proxy-Authorization scheme {scheme}");
+        };
+        self.authenticate(&session_id, &session_token, None).await
+    }
+
+    /// Check a session's credentials and, for `mtls` sessions, the client
+    /// certificate.
+    async fn authenticate(
+        &self,
+        session_id: &str,
+        session_token: &str,
+        client_cert: Option<&CertificateDer<'static>>,
+    ) -> Result<RelayGrant> {
+        let grant = self.session_grant(session_id).await?;
+
+        validate_session(&grant.session, session_token, self.config.clock_offset_ms)?;
+        if grant.session.tunnel_protocol.as_deref() == Some("mtls") {
+            anyhow::ensure!(
+                self.config.tls.is_some(),
+                "session requires mTLS but the gateway listener has no TLS certificate"
+            );
+            gateway_tls::verify_session_client_cert(&grant.session, client_cert)?;
+        }
+        Ok(grant)
+    }
+
+    /// A live session's current state and relay handles, unchecked.
+    async fn session_grant(&self, session_id: &str) -> Result<RelayGrant> {
+        let sessions = self.sessions.read().await;
+        let live = sessions.get(session_id).context("unknown session_id")?;
+        Ok(RelayGrant {
+            session: live.session.clone(),
+            terminate: live.terminate.subscribe(),
+            throttle: live.throttle.clone(),
+            limiter: live.limiter.clone(),
+        })
+    }
+
+    /// The session with its destination policy applied, if it allows
+    /// `destination`.
+    async fn authorize_destination(
+        &self,
+        session: GatewaySession,
+        destination: &str,
+    ) -> Result<GatewaySession> {
+        let session = self.resolve_destination_policy(session).await?;
+        if let Err(err) = validate_destination(&session, destination) {
+            let blocked = self.resolver.record_blocked(&session.destination_policy_id);
+            warn!(
+                session_id = %session.session_id,
+                policy_id = %session.destination_policy_id,
+                destination = %destination,
+                blocked_for_policy = blocked,
+                "relay destination blocked: {err:#}"
+            );
+            return Err(err);
+        }
+        Ok(session)
+    }
+
+    /// Take one of the session's tunnel slots for an authorized connection.
+    fn admit_connection(&self, grant: &RelayGrant) -> Result<TunnelPermit, SessionLimitExceeded> {
+        grant
+            .limiter
+            .admit(
+                &grant.session.session_id,
+                &self.config.session_limits,
+                Instant::now(),
+            )
+            .inspect_err(|exceeded| {
+                warn!(
+                    session_id = %exceeded.session_id,
+                    limit = ?exceeded.limit,
+                    max = exceeded.max,
+                    active_tunnels = grant.limiter.active(),
+                    "relay connection refused by session limit"
+                );
+            })
+    }
+
+    /// Connect to an authorized destination through the gateway's resolver.
+    async fn connect_upstream(&self, destination: &str) -> Result<TcpStream> {
+        connect_upstream(
+            &self.resolver,
+            destination,
+            Duration::from_secs(self.config.connect_timeout_seconds),
+            self.config.allow_internal_upstreams,
         )
         .await
-        .context("relay idle timeout")?
-        .context("relay I/O failure")?;
+    }
 
-        info!(
-            %peer_addr,
-            session_id = %session.session_id,
-            destination = %destination,
-            from_client_bytes = bytes_relayed.0,
-            from_upstream_bytes = bytes_relayed.1,
-            "relay session completed"
+    /// Relay an accepted connection until either side closes, the relay
+    /// idles out, or the session expires or is revoked, then account for it
+    /// in the usage counters and the access log.
+    #[allow(clippy::too_many_arguments)]
+    async fn relay<S>(
+        &self,
+        stream: S,
+        mut upstream: TcpStream,
+        session: GatewaySession,
+        grant: RelayGrant,
+        peer_addr: SocketAddr,
+        destination: &str,
+        listener: &str,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let RelayGrant {
+            mut terminate,
+            throttle,
+            ..
+        } = grant;
+        let meter = self.usage.open(&session.session_id);
+        let mut stream = CountedStream::new(stream, meter.clone());
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_seconds);
+        let started_at = chrono::Utc::now();
+        let relay_started = Instant::now();
+        let relay = tokio::time::timeout(
+            idle_timeout,
+            relay_streams(&mut stream, &mut upstream, throttle.as_deref()),
         );
+        let (termination, result) = tokio::select! {
+            result = relay => match result {
+                Err(elapsed) => (
+                    TerminationReason::IdleTimeout,
+                    Err(anyhow::Error::from(elapsed).context("relay idle timeout")),
+                ),
+                Ok(Err(err)) => (
+                    TerminationReason::IoError,
+                    Err(anyhow::Error::from(err).context("relay I/O failure")),
+                ),
+                Ok(Ok(_)) => (TerminationReason::Closed, Ok(())),
+            },
+            // Set on expiry; an error means the session was revoked.
+            _ = terminate.wait_for(|expired| *expired) => {
+                info!(
+                    %peer_addr,
+                    session_id = %session.session_id,
+                    destination = %destination,
+                    "relay session terminated: session expired or revoked"
+                );
+                (TerminationReason::SessionEnded, Ok(()))
+            }
+        };
+        let elapsed = relay_started.elapsed();
+        self.usage.close(&meter, termination);
+        if let Some(log) = &self.access_log {
+            log.write(&AccessRecord {
+                session_id: session.session_id.clone(),
+                destination: destination.to_string(),
+                peer_addr: peer_addr.to_string(),
+                listener: listener.to_string(),
+                started_at: started_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                duration_ms: elapsed.as_millis() as u64,
+                bytes_up: stream.bytes_up,
+                bytes_down: stream.bytes_down,
+                termination,
+            });
+        }
+
+        if termination == TerminationReason::Closed {
+            info!(
+                %peer_addr,
+                session_id = %session.session_id,
+                destination = %destination,
+                from_client_bytes = stream.bytes_up,
+                from_upstream_bytes = stream.bytes_down,
+                upload_mbps = observed_mbps(stream.bytes_up, elapsed),
+                download_mbps = observed_mbps(stream.bytes_down, elapsed),
+                bandwidth_limit_mbps = ?throttle.map(|throttle| throttle.limit_mbps),
+                "relay session completed"
+            );
+        }
+        result
+    }
+}
 
-        Ok(())
+/// A session's credentials checked out: its state when the relay was
+/// authorized, and the handles the relay runs under.
+struct RelayGrant {
+    session: GatewaySession,
+    terminate: watch::Receiver<bool>,
+    throttle: Option<Arc<SessionThrottle>>,
+    limiter: Arc<ConnectionLimiter>,
+}
+
+/// Relay both directions, through the session's token buckets when it has
+/// a bandwidth limit.  Returns `(from_client, from_upstream)` byte counts.
+async fn relay_streams<S>(
+    client: &mut S,
+    upstream: &mut TcpStream,
+    throttle: Option<&SessionThrottle>,
+) -> std::io::Result<(u64, u64)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let Some(throttle) = throttle else {
+        return tokio::io::copy_bidirectional(client, upstream).await;
+    };
+    let (mut client_read, mut client_write) = tokio::io::split(client);
+    let (mut upstream_read, mut upstream_write) = upstream.split();
+    tokio::try_join!(
+        copy_throttled(&mut client_read, &mut upstream_write, &throttle.upload),
+        copy_throttled(&mut upstream_read, &mut client_write, &throttle.download),
+    )
+}
+
+/// Copy until EOF, waiting on `bucket` before each write, then shut the
+/// writer down so the peer sees the half-close.
+async fn copy_throttled<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    bucket: &Mutex<TokenBucket>,
+) -> std::io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; THROTTLE_CHUNK_BYTES];
+    let mut copied = 0u64;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            writer.shutdown().await?;
+            return Ok(copied);
+        }
+        let wait = bucket
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .reserve(n, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        writer.write_all(&buf[..n]).await?;
+        copied += n as u64;
+    }
+}
+
+/// Average throughput in megabits per second.
+fn observed_mbps(bytes: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        bytes as f64 * 8.0 / secs / 1_000_000.0
+    } else {
+        0.0
     }
 }
 
@@ -337,7 +1346,8 @@ pub struct HttpConnectProxyConfig {
     pub listen_addr: String,
     /// Session bearer token required in the `Proxy-Authorization: Bearer <token>`
     /// request header. Set to an empty string to disable authentication
-    /// (not recommended outside development).
+    /// (not recommended outside development).  Unused when the proxy is
+    /// attached to a gateway with [`HttpConnectProxy::with_gateway`].
     pub session_token: String,
     /// Whether the HTTP CONNECT proxy is enabled.
     pub enabled: bool,
@@ -345,6 +1355,11 @@ pub struct HttpConnectProxyConfig {
     pub connect_timeout_secs: u64,
     /// Maximum idle seconds before an established tunnel is torn down.
     pub idle_timeout_secs: u64,
+    /// Allow tunnels to loopback, private and link-local addresses.  Off by
+    /// default; unused when the proxy is attached to a gateway, which
+    /// applies [`GatewayConfig::allow_internal_upstreams`] instead.
+    #[serde(default)]
+    pub allow_internal_upstreams: bool,
 }
 
 impl Default for HttpConnectProxyConfig {
@@ -355,6 +1370,7 @@ impl Default for HttpConnectProxyConfig {
             enabled: false,
             connect_timeout_secs: 10,
             idle_timeout_secs: 300,
+            allow_internal_upstreams: false,
         }
     }
 }
@@ -380,13 +1396,45 @@ impl Default for HttpConnectProxyConfig {
 /// Only the `CONNECT` method is supported.  Unauthenticated plain-HTTP
 /// proxying (`GET http://...`) is intentionally not implemented to avoid open
 /// relay abuse.
+///
+/// ## Per-user sessions
+///
+/// On its own the proxy checks one shared `session_token`.  Attached to a
+/// [`DataPlaneGateway`] with [`Self::with_gateway`], every tunnel instead
+/// runs under one of the gateway's connect sessions, named by either
+///
+/// - `Proxy-Authorization: Bearer <session_token>`, or
+/// - `Proxy-Authorization: Basic base64(<session_id>:<session_token>)`,
+///   which browsers send after prompting for proxy credentials.
+///
+/// The session's destination policy and connection limits apply, and its
+/// tunnels count in the gateway's usage counters (reported to the control
+/// plane by [`crate::GatewayUsageReporter`]) and access log.  Failed
+/// credentials get `407`, a destination outside the policy `403` and a
+/// tunnel over the session's limits `429`.
 pub struct HttpConnectProxy {
     config: HttpConnectProxyConfig,
+    gateway: Option<DataPlaneGateway>,
+    /// Resolves destinations of tunnels authorized by `session_token`
+    resolver: Arc<GatewayResolver>,
 }
 
 impl HttpConnectProxy {
     pub fn new(config: HttpConnectProxyConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            gateway: None,
+            resolver: Arc::new(GatewayResolver::new(Duration::from_secs(
+                default_dns_cache_ttl_seconds(),
+            ))),
+        }
+    }
+
+    /// Authenticate tunnels as `gateway`'s sessions and relay them through
+    /// it instead of checking the shared `session_token`.
+    pub fn with_gateway(mut self, gateway: DataPlaneGateway) -> Self {
+        self.gateway = Some(gateway);
+        self
     }
 
     /// Start the HTTP CONNECT proxy.  Returns immediately when
@@ -411,8 +1459,12 @@ impl HttpConnectProxy {
         loop {
             let (stream, peer_addr) = listener.accept().await?;
             let cfg = config.clone();
+            let gateway = self.gateway.clone();
+            let resolver = self.resolver.clone();
             tokio::spawn(async move {
-                if let Err(err) = handle_connect_proxy(stream, cfg).await {
+                if let Err(err) =
+                    handle_connect_proxy(stream, peer_addr, cfg, gateway, &resolver).await
+                {
                     warn!(%peer_addr, "HTTP CONNECT proxy connection error: {err:#}");
                 }
             });
@@ -420,10 +1472,51 @@ impl HttpConnectProxy {
     }
 }
 
+/// Connect to `destination`, resolving names through `resolver` and trying
+/// each address in turn.  Internal addresses are refused unless
+/// `allow_internal` is set.
+async fn connect_upstream(
+    resolver: &GatewayResolver,
+    destination: &str,
+    connect_timeout: Duration,
+    allow_internal: bool,
+) -> Result<TcpStream> {
+    tokio::time::timeout(connect_timeout, async {
+        let (host, port) = split_host_port(destination)?;
+        let addrs = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) => resolver.lookup(&host).await?,
+        };
+        // Checked on every answer, not just the first, so a name that
+        // rebinds to an internal address is refused.
+        if !allow_internal {
+            if let Some(ip) = addrs.iter().find(|ip| is_internal_address(ip)) {
+                anyhow::bail!("{host} resolves to internal address {ip}");
+            }
+        }
+        let mut last_err = None;
+        for ip in addrs {
+            match TcpStream::connect(SocketAddr::new(ip, port)).await {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(anyhow::Error::from(
+            last_err.expect("lookup returns addresses"),
+        ))
+    })
+    .await
+    .context("upstream connect timeout")?
+    .with_context(|| format!("failed to connect upstream destination {destination}"))
+}
+
 /// Handle one browser connection to the HTTP CONNECT proxy.
 async fn handle_connect_proxy(
     stream: TcpStream,
+    peer_addr: SocketAddr,
     config: Arc<HttpConnectProxyConfig>,
+    gateway: Option<DataPlaneGateway>,
+    resolver: &GatewayResolver,
 ) -> Result<()> {
     let idle_timeout = Duration::from_secs(config.idle_timeout_secs);
     let (read_half, mut write_half) = stream.into_split();
@@ -479,20 +1572,32 @@ async fn handle_connect_proxy(
             }
             Ok(Ok(_)) if header == "\r\n" || header == "\n" => break,
             Ok(Ok(_)) => {
-                let lower = header.to_lowercase();
-                if let Some(val) = lower.strip_prefix("proxy-authorization:") {
-                    proxy_auth = Some(val.trim().to_string());
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("proxy-authorization") {
+                        proxy_auth = Some(value.trim().to_string());
+                    }
                 }
             }
         }
     }
 
+    if let Some(gateway) = gateway {
+        let stream = reader
+            .into_inner()
+            .reunite(write_half)
+            .context("failed to reunite TCP stream halves")?;
+        return gateway
+            .handle_http_connect(stream, peer_addr, &target, proxy_auth.as_deref())
+            .await;
+    }
+
     // Validate the bearer token when authentication is configured.
     if !config.session_token.is_empty() {
         let provided = proxy_auth
             .as_deref()
-            .and_then(|v| v.strip_prefix("bearer "))
-            .unwrap_or("");
+            .and_then(|v| v.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map_or("", |(_, token)| token.trim());
         if provided != config.session_token {
             write_half
                 .write_all(
@@ -504,28 +1609,23 @@ async fn handle_connect_proxy(
     }
 
     // Connect to the upstream destination.
-    let mut upstream = match tokio::time::timeout(
+    let mut upstream = match connect_upstream(
+        resolver,
+        &target,
         Duration::from_secs(config.connect_timeout_secs),
-        TcpStream::connect(&target),
+        config.allow_internal_upstreams,
     )
     .await
     {
-        Ok(Ok(s)) => s,
-        Ok(Err(e)) => {
-            warn!(target = %target, "HTTP CONNECT upstream connect failed: {e}");
-            write_half
-                .write_all(
-                    b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
-                )
-                .await?;
-            return Ok(());
-        }
-        Err(_) => {
-            write_half
-                .write_all(
-                    b"HTTP/1.1 504 Gateway Timeout\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
-                )
-                .await?;
+        Ok(s) => s,
+        Err(e) => {
+            warn!(target = %target, "HTTP CONNECT upstream connect failed: {e:#}");
+            let reply: &[u8] = if e.downcast_ref::<tokio::time::error::Elapsed>().is_some() {
+                b"HTTP/1.1 504 Gateway Timeout\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            } else {
+                b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            };
+            write_half.write_all(reply).await?;
             return Ok(());
         }
     };
@@ -560,22 +1660,286 @@ async fn handle_connect_proxy(
     Ok(())
 }
 
-fn validate_session(session: &GatewaySession, provided_token: &str) -> Result<()> {
-    if session.session_token != provided_token {
-        anyhow::bail!("invalid session token");
-    }
-
-    let now = chrono::Utc::now().timestamp() as u64;
-    if now >= session.expires_at_epoch_seconds {
+/// Configuration for the proxy auto-configuration (PAC) server.
+///
+/// Endpoints pointed at `http://<node-ip>:<port>/proxy.pac` (or finding
+/// `/wpad.dat` through WPAD) configure themselves to use the node's
+/// [`HttpConnectProxy`] without manual browser setup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacServerConfig {
+    /// Address and port to serve the PAC file on.
+    pub listen_addr: String,
+    /// Whether the PAC server is enabled.
+    pub enabled: bool,
+    /// Host endpoints reach the proxy at.  Empty uses the address each
+    /// endpoint reached the PAC server on, which suits a proxy listening on
+    /// every interface.
+    pub proxy_host: String,
+    /// Port of the HTTP CONNECT proxy.
+    pub proxy_port: u16,
+    /// Hosts that go direct: exact names, `*.example.com` wildcards (which
+    /// also match `example.com`) or IPv4 CIDR ranges.
+    pub bypass: Vec<String>,
+    /// Also send plain host names, `*.local` and loopback, link-local and
+    /// private IPv4 addresses direct.
+    pub bypass_local: bool,
+}
+
+impl Default for PacServerConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: "0.0.0.0:8082".to_string(),
+            enabled: false,
+            proxy_host: String::new(),
+            proxy_port: 3128,
+            bypass: Vec::new(),
+            bypass_local: true,
+        }
+    }
+}
+
+impl PacServerConfig {
+    /// Defaults pointing at `proxy`'s port, enabled along with it.
+    pub fn for_proxy(proxy: &HttpConnectProxyConfig) -> Self {
+        let defaults = Self::default();
+        Self {
+            enabled: proxy.enabled,
+            proxy_port: proxy
+                .listen_addr
+                .parse::<SocketAddr>()
+                .map(|addr| addr.port())
+                .unwrap_or(defaults.proxy_port),
+            ..defaults
+        }
+    }
+}
+
+/// Private, loopback and link-local IPv4 ranges that `bypass_local` sends
+/// direct.
+const PAC_LOCAL_NETS: [(&str, &str); 5] = [
+    ("10.0.0.0", "255.0.0.0"),
+    ("172.16.0.0", "255.240.0.0"),
+    ("192.168.0.0", "255.255.0.0"),
+    ("127.0.0.0", "255.0.0.0"),
+    ("169.254.0.0", "255.255.0.0"),
+];
+
+/// HTTP server for the PAC file that points endpoints at the node's
+/// [`HttpConnectProxy`].
+///
+/// Serves `GET /proxy.pac` and `GET /wpad.dat`; any other path is a 404.
+/// The proxy only tunnels `CONNECT`, which browsers use for `https:`,
+/// `wss:` and `ws:` URLs, so only those are proxied and everything else goes
+/// direct.  Browsers cannot add a bearer token themselves; a proxy attached
+/// to a gateway answers them with a Basic challenge they prompt for.
+pub struct PacServer {
+    config: PacServerConfig,
+    /// JavaScript conditions, one per bypass rule
+    bypass: Vec<String>,
+}
+
+impl PacServer {
+    /// Fails when a bypass rule is not a host name, `*.` wildcard or IPv4
+    /// CIDR range, or the proxy host is not a host name or IP address.
+    pub fn new(config: PacServerConfig) -> Result<Self> {
+        if !config.proxy_host.is_empty()
+            && config.proxy_host.parse::<IpAddr>().is_err()
+            && !is_pac_host_name(&config.proxy_host)
+        {
+            anyhow::bail!("invalid PAC proxy host {:?}", config.proxy_host);
+        }
+        let bypass = config
+            .bypass
+            .iter()
+            .map(|rule| pac_bypass_condition(rule))
+            .collect::<Result<_>>()?;
+        Ok(Self { config, bypass })
+    }
+
+    /// The PAC file for endpoints that reach the proxy at `proxy_host`.
+    pub fn pac_file(&self, proxy_host: &str) -> String {
+        let proxy_host = match self.config.proxy_host.as_str() {
+            "" => proxy_host,
+            configured => configured,
+        };
+        let proxy = match proxy_host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{ip}]:{}", self.config.proxy_port),
+            _ => format!("{proxy_host}:{}", self.config.proxy_port),
+        };
+
+        let mut direct = self.bypass.clone();
+        if self.config.bypass_local {
+            direct.push("isPlainHostName(host)".to_string());
+            direct.push("dnsDomainIs(host, \".local\")".to_string());
+            direct.extend(
+                PAC_LOCAL_NETS
+                    .iter()
+                    .map(|(net, mask)| format!("(isIpV4 && isInNet(host, \"{net}\", \"{mask}\"))")),
+            );
+        }
+        let mut pac = String::from(
+            "function FindProxyForURL(url, host) {\n  \
+             host = host.toLowerCase();\n  \
+             var isIpV4 = /^\\d+\\.\\d+\\.\\d+\\.\\d+$/.test(host);\n",
+        );
+        for condition in direct {
+            pac.push_str(&format!("  if ({condition}) return \"DIRECT\";\n"));
+        }
+        pac.push_str(&format!(
+            "  if (url.substring(0, 6) == \"https:\" || url.substring(0, 4) == \"wss:\" || \
+             url.substring(0, 3) == \"ws:\") return \"PROXY {proxy}\";\n  \
+             return \"DIRECT\";\n}}\n"
+        ));
+        pac
+    }
+
+    /// Start the PAC server.  Returns immediately when `config.enabled` is
+    /// `false`.
+    pub async fn run(self) -> Result<()> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let listener = TcpListener::bind(&self.config.listen_addr)
+            .await
+            .with_context(|| format!("failed to bind PAC server on {}", self.config.listen_addr))?;
+
+        info!(listen_addr = %self.config.listen_addr, "PAC server listening");
+
+        let server = Arc::new(self);
+        loop {
+            let (stream, peer_addr) = listener.accept().await?;
+            let server = server.clone();
+            tokio::spawn(async move {
+                if let Err(err) = handle_pac_connection(stream, &server).await {
+                    warn!(%peer_addr, "PAC server connection error: {err:#}");
+                }
+            });
+        }
+    }
+}
+
+/// Whether `host` is a plain DNS name, safe to embed in the PAC file.
+fn is_pac_host_name(host: &str) -> bool {
+    !host.is_empty()
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// The PAC condition matching one bypass rule.
+fn pac_bypass_condition(rule: &str) -> Result<String> {
+    let rule = rule.trim().to_lowercase();
+    if let Some((net, len)) = rule.split_once('/') {
+        let net: std::net::Ipv4Addr = net
+            .parse()
+            .with_context(|| format!("invalid PAC bypass range {rule:?}"))?;
+        let len: u32 = len
+            .parse()
+            .ok()
+            .filter(|len| *len <= 32)
+            .with_context(|| format!("invalid PAC bypass range {rule:?}"))?;
+        let mask = std::net::Ipv4Addr::from(u32::MAX.checked_shl(32 - len).unwrap_or(0));
+        return Ok(format!("(isIpV4 && isInNet(host, \"{net}\", \"{mask}\"))"));
+    }
+    if let Some(suffix) = rule.strip_prefix("*.") {
+        if is_pac_host_name(suffix) {
+            return Ok(format!(
+                "(host == \"{suffix}\" || dnsDomainIs(host, \".{suffix}\"))"
+            ));
+        }
+    } else if is_pac_host_name(&rule) {
+        return Ok(format!("host == \"{rule}\""));
+    }
+    anyhow::bail!("invalid PAC bypass rule {rule:?}")
+}
+
+/// Serve the PAC file on one connection.
+async fn handle_pac_connection(stream: TcpStream, server: &PacServer) -> Result<()> {
+    let local_ip = stream.local_addr()?.ip();
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+    let mut request_line = String::new();
+
+    match tokio::time::timeout(Duration::from_secs(5), reader.read_line(&mut request_line)).await {
+        Err(_) | Ok(Err(_)) | Ok(Ok(0)) => return Ok(()),
+        Ok(Ok(_)) => {}
+    }
+    loop {
+        let mut header_line = String::new();
+        match tokio::time::timeout(Duration::from_secs(5), reader.read_line(&mut header_line)).await
+        {
+            Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break,
+            Ok(Ok(_)) if header_line == "\r\n" || header_line == "\n" => break,
+            _ => {}
+        }
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let path = path.split('?').next().unwrap_or(path);
+    let response = match path {
+        "/proxy.pac" | "/wpad.dat" => {
+            let body = server.pac_file(&local_ip.to_canonical().to_string());
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\
+                 Content-Type: application/x-ns-proxy-autoconfig\r\n\
+                 Cache-Control: max-age=300\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+    write_half
+        .write_all(response.as_bytes())
+        .await
+        .context("failed to write PAC response")?;
+    Ok(())
+}
+
+fn validate_session(
+    session: &GatewaySession,
+    provided_token: &str,
+    clock_offset_ms: i64,
+) -> Result<()> {
+    if session.session_token != provided_token {
+        anyhow::bail!("invalid session token");
+    }
+    ensure_session_live(session, clock_offset_ms)
+}
+
+fn ensure_session_live(session: &GatewaySession, clock_offset_ms: i64) -> Result<()> {
+    // Expiry is issued on the coordinator's clock; compare in that frame.
+    let now = coordinator_now_ms(clock_offset_ms) / 1000;
+    if now >= session.expires_at_epoch_seconds as i64 {
         anyhow::bail!("session expired");
     }
 
     Ok(())
 }
 
+/// Current time on the coordinator's clock, in epoch milliseconds.
+fn coordinator_now_ms(clock_offset_ms: i64) -> i64 {
+    chrono::Utc::now().timestamp_millis() + clock_offset_ms
+}
+
+/// When live relays for `session` are torn down, in coordinator epoch ms.
+fn expiry_deadline_ms(session: &GatewaySession, grace_seconds: u64) -> i64 {
+    (session
+        .expires_at_epoch_seconds
+        .saturating_add(grace_seconds) as i64)
+        .saturating_mul(1000)
+}
+
 fn validate_destination(session: &GatewaySession, destination: &str) -> Result<()> {
     let (host, _port) = split_host_port(destination)?;
 
+    if gateway_dns::is_disguised_ip_literal(&host) {
+        anyhow::bail!("destination {host} is a non-canonical IP literal");
+    }
+
     if matches!(session.egress_profile.as_str(), "allowlist_domains") {
         let allowed = session
             .allowed_destinations
@@ -647,10 +2011,20 @@ mod tests {
             egress_profile: "allowlist_domains".to_string(),
             destination_policy_id: "policy_web_basic_v1".to_string(),
             allowed_destinations: vec!["127.0.0.1".to_string(), "*.example.com".to_string()],
+            bandwidth_limit_mbps: None,
+            tunnel_protocol: None,
             expires_at_epoch_seconds: (chrono::Utc::now().timestamp() as u64) + 300,
         }
     }
 
+    #[test]
+    fn session_expiry_uses_coordinator_clock_offset() {
+        let session = sample_session();
+        assert!(validate_session(&session, "cs_token", 0).is_ok());
+        // Local clock 10 minutes behind the coordinator: session already expired.
+        assert!(validate_session(&session, "cs_token", 600_000).is_err());
+    }
+
     #[tokio::test]
     async fn add_session_allows_relay() {
         let gateway = DataPlaneGateway::new(GatewayConfig::default(), vec![]);
@@ -714,10 +2088,138 @@ mod tests {
 
     #[test]
     fn validates_allowlist_destinations() {
-        let session = sample_session();
+        let mut session = sample_session();
         assert!(validate_destination(&session, "127.0.0.1:8080").is_ok());
         assert!(validate_destination(&session, "api.example.com:443").is_ok());
         assert!(validate_destination(&session, "evil.com:443").is_err());
+        // Numeric spellings of an allowed IP are refused, whatever the profile.
+        assert!(validate_destination(&session, "2130706433:8080").is_err());
+        session.egress_profile = "metered_general_egress".to_string();
+        assert!(validate_destination(&session, "127.1:8080").is_err());
+        assert!(validate_destination(&session, "evil.com:443").is_ok());
+    }
+
+    #[tokio::test]
+    async fn allowlist_falls_back_to_cached_policy_bundle() {
+        let signer = crate::ControlSigner::generate().unwrap();
+        let bundle = |version: u64, offline_policy: crate::OfflinePolicy, issued_at_ms: i64| {
+            let bundle = crate::PolicyBundle {
+                version,
+                offline_policy,
+                policies: vec![crate::EgressPolicy {
+                    id: "policy_web_basic_v1".to_string(),
+                    allowed_destinations: vec!["*.example.com".to_string()],
+                }],
+            };
+            signer.sign(
+                crate::POLICY_BUNDLE_AUDIENCE,
+                serde_json::to_value(bundle).unwrap(),
+                issued_at_ms,
+            )
+        };
+        let mut session = sample_session();
+        session.allowed_destinations.clear();
+
+        let gateway = DataPlaneGateway::new(GatewayConfig::default(), vec![])
+            .with_policy_cache(PolicyBundleCache::new(signer.public_key_b64()));
+        assert!(gateway
+            .resolve_destination_policy(session.clone())
+            .await
+            .is_err());
+
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        gateway
+            .install_policy_bundle(bundle(1, crate::OfflinePolicy::default(), now_ms))
+            .await
+            .unwrap();
+        let resolved = gateway
+            .resolve_destination_policy(session.clone())
+            .await
+            .unwrap();
+        assert!(validate_destination(&resolved, "api.example.com:443").is_ok());
+        assert!(validate_destination(&resolved, "evil.com:443").is_err());
+
+        // Past max staleness the cached bundle no longer admits anything.
+        let expired = crate::OfflinePolicy {
+            refresh_after_secs: 1,
+            max_staleness_secs: 60,
+        };
+        gateway
+            .install_policy_bundle(bundle(2, expired, now_ms - 61_000))
+            .await
+            .unwrap();
+        assert!(gateway.resolve_destination_policy(session).await.is_err());
+    }
+
+    #[test]
+    fn token_bucket_allows_one_second_burst_then_paces() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(100_000.0, start);
+        assert_eq!(bucket.reserve(100_000, start), Duration::ZERO);
+        assert_eq!(bucket.reserve(50_000, start), Duration::from_millis(500));
+        assert_eq!(
+            bucket.reserve(0, start + Duration::from_millis(500)),
+            Duration::ZERO
+        );
+
+        // A long idle period refills no more than one second of traffic.
+        let later = start + Duration::from_secs(60);
+        assert_eq!(bucket.reserve(100_000, later), Duration::ZERO);
+        assert!(bucket.reserve(1, later) > Duration::ZERO);
+    }
+
+    #[test]
+    fn session_throttle_requires_positive_limit() {
+        let mut session = sample_session();
+        assert!(SessionThrottle::for_session(&session).is_none());
+        session.bandwidth_limit_mbps = Some(0.0);
+        assert!(SessionThrottle::for_session(&session).is_none());
+        session.bandwidth_limit_mbps = Some(8.0);
+        let throttle = SessionThrottle::for_session(&session).unwrap();
+        assert_eq!(
+            throttle.upload.lock().unwrap().bytes_per_second,
+            1_000_000.0
+        );
+    }
+
+    async fn tcp_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let connect = TcpStream::connect(listener.local_addr().unwrap());
+        let (connected, accepted) = tokio::join!(connect, listener.accept());
+        (connected.unwrap(), accepted.unwrap().0)
+    }
+
+    #[tokio::test]
+    async fn throttled_relay_caps_session_throughput() {
+        let (mut client, mut client_side) = tcp_pair().await;
+        let (mut upstream_side, mut upstream) = tcp_pair().await;
+
+        let mut session = sample_session();
+        // 100 kB/s with a 100 kB burst.
+        session.bandwidth_limit_mbps = Some(0.8);
+        let throttle = SessionThrottle::for_session(&session).unwrap();
+        let relay = tokio::spawn(async move {
+            relay_streams(&mut client_side, &mut upstream_side, Some(&throttle)).await
+        });
+
+        let started = Instant::now();
+        let sender = tokio::spawn(async move {
+            client.write_all(&[7u8; 150_000]).await.unwrap();
+            client.shutdown().await.unwrap();
+            client
+        });
+        let mut received = Vec::new();
+        upstream.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received.len(), 150_000);
+        // The 50 kB past the burst take about half a second.
+        assert!(started.elapsed() >= Duration::from_millis(400));
+
+        drop(upstream);
+        let mut client = sender.await.unwrap();
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(relay.await.unwrap().unwrap(), (150_000, 0));
     }
 
     #[tokio::test]
@@ -741,6 +2243,14 @@ mod tests {
                 listen_addr: "127.0.0.1:0".to_string(),
                 connect_timeout_seconds: 5,
                 idle_timeout_seconds: 30,
+                clock_offset_ms: 0,
+                expiry_grace_seconds: 0,
+                tls: None,
+                socks5_listen_addr: None,
+                dns_cache_ttl_seconds: 60,
+                session_limits: SessionConnectionLimits::default(),
+                transparent: None,
+                allow_internal_upstreams: true,
             },
             vec![session],
         );
@@ -754,9 +2264,28 @@ mod tests {
                 listen_addr: gateway_addr.to_string(),
                 connect_timeout_seconds: 5,
                 idle_timeout_seconds: 30,
+                clock_offset_ms: 0,
+                expiry_grace_seconds: 0,
+                tls: None,
+                socks5_listen_addr: None,
+                dns_cache_ttl_seconds: 60,
+                session_limits: SessionConnectionLimits::default(),
+                transparent: None,
+                allow_internal_upstreams: true,
             },
             sessions: gateway.sessions.clone(),
+            events: gateway.events.clone(),
+            expiry_wakeup: gateway.expiry_wakeup.clone(),
+            policy_cache: None,
+            resolver: gateway.resolver.clone(),
+            drain_deadline: gateway.drain_deadline.clone(),
+            usage: gateway.usage.clone(),
+            access_log: None,
         };
+        let log_path =
+            std::env::temp_dir().join(format!("gateway-access-{}.log", uuid::Uuid::new_v4()));
+        let run_gateway =
+            run_gateway.with_access_log(AccessLog::open(&log_path, 1 << 20, 1).unwrap());
 
         tokio::spawn(async move {
             if let Err(err) = run_gateway.run().await {
@@ -785,6 +2314,593 @@ mod tests {
         let mut echoed = vec![0u8; 11];
         client.read_exact(&mut echoed).await.unwrap();
         assert_eq!(&echoed, b"hello relay");
+        let usage = gateway.usage().snapshot();
+        assert_eq!(usage.sessions["sess_123"].active_connections, 1);
+        assert_eq!(usage.sessions["sess_123"].bytes_up, 11);
+
+        client.shutdown().await.unwrap();
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let usage = gateway.usage().snapshot();
+        assert_eq!(usage.totals.connections, 1);
+        assert_eq!(usage.totals.active_connections, 0);
+        assert_eq!(usage.totals.terminations[&TerminationReason::Closed], 1);
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let record: AccessRecord = serde_json::from_str(log.trim()).unwrap();
+        assert_eq!(record.session_id, "sess_123");
+        assert_eq!(
+            record.destination,
+            format!("127.0.0.1:{}", echo_addr.port())
+        );
+        assert_eq!(record.listener, "tunnel");
+        assert_eq!((record.bytes_up, record.bytes_down), (11, 11));
+        assert_eq!(record.termination, TerminationReason::Closed);
+        std::fs::remove_file(log_path).unwrap();
+    }
+
+    /// Echo server for one connection.
+    async fn spawn_echo() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = socket.read(&mut buf).await.unwrap();
+                socket.write_all(&buf[..n]).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    /// Self-signed `localhost` node certificate written to a temp dir.
+    fn node_certificate() -> (GatewayTlsConfig, rustls::RootCertStore) {
+        let node = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir().join(format!("gateway-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = GatewayTlsConfig {
+            cert_path: dir.join("node.crt"),
+            key_path: dir.join("node.key"),
+        };
+        std::fs::write(&config.cert_path, node.cert.pem()).unwrap();
+        std::fs::write(&config.key_path, node.key_pair.serialize_pem()).unwrap();
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(node.cert.der().clone()).unwrap();
+        (config, roots)
+    }
+
+    async fn spawn_gateway(tls: Option<GatewayTlsConfig>, session: GatewaySession) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let gateway_addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let gateway = DataPlaneGateway::new(
+            GatewayConfig {
+                listen_addr: gateway_addr.to_string(),
+                idle_timeout_seconds: 30,
+                tls,
+                allow_internal_upstreams: true,
+                ..GatewayConfig::default()
+            },
+            vec![session],
+        );
+        tokio::spawn(async move {
+            if let Err(err) = gateway.run().await {
+                tracing::error!("gateway terminated in test: {err:#}");
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        gateway_addr
+    }
+
+    /// Send the handshake and return whether the gateway acknowledged it.
+    async fn handshake<S>(client: &mut S, destination: SocketAddr) -> bool
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        handshake_to(client, &destination.to_string()).await
+    }
+
+    async fn handshake_to<S>(client: &mut S, destination: &str) -> bool
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let handshake = serde_json::json!({
+            "session_id": "sess_123",
+            "session_token": "cs_token",
+            "destination": destination,
+        })
+        .to_string();
+        client.write_all(handshake.as_bytes()).await.unwrap();
+        client.write_all(b"\n").await.unwrap();
+        client.flush().await.unwrap();
+
+        let mut ack = [0u8; 3];
+        client.read_exact(&mut ack).await.is_ok() && &ack == b"OK\n"
+    }
+
+    fn mtls_session() -> GatewaySession {
+        let mut session = sample_session();
+        session.tunnel_protocol = Some("mtls".to_string());
+        session
+    }
+
+    #[tokio::test]
+    async fn mtls_session_relays_with_session_client_certificate() {
+        let echo_addr = spawn_echo().await;
+        let (tls, roots) = node_certificate();
+        let gateway_addr = spawn_gateway(Some(tls), mtls_session()).await;
+
+        let client_config =
+            gateway_tls::session_client_config("sess_123", "cs_token", roots).unwrap();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+        let tcp = TcpStream::connect(gateway_addr).await.unwrap();
+        let mut client = connector
+            .connect("localhost".try_into().unwrap(), tcp)
+            .await
+            .unwrap();
+
+        assert!(handshake(&mut client, echo_addr).await);
+        client.write_all(b"hello relay").await.unwrap();
+        client.flush().await.unwrap();
+        let mut echoed = vec![0u8; 11];
+        client.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"hello relay");
+    }
+
+    #[tokio::test]
+    async fn mtls_session_refuses_other_client_certificates() {
+        let echo_addr = spawn_echo().await;
+        let (tls, roots) = node_certificate();
+        let gateway_addr = spawn_gateway(Some(tls), mtls_session()).await;
+
+        // A certificate derived from a different session token.
+        let client_config =
+            gateway_tls::session_client_config("sess_123", "other_token", roots.clone()).unwrap();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+        let tcp = TcpStream::connect(gateway_addr).await.unwrap();
+        let mut client = connector
+            .connect("localhost".try_into().unwrap(), tcp)
+            .await
+            .unwrap();
+        assert!(!handshake(&mut client, echo_addr).await);
+
+        // TLS without any client certificate.
+        let client_config = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+        let tcp = TcpStream::connect(gateway_addr).await.unwrap();
+        let mut client = connector
+            .connect("localhost".try_into().unwrap(), tcp)
+            .await
+            .unwrap();
+        assert!(!handshake(&mut client, echo_addr).await);
+    }
+
+    #[tokio::test]
+    async fn plaintext_gateway_refuses_mtls_session() {
+        let echo_addr = spawn_echo().await;
+        let gateway_addr = spawn_gateway(None, mtls_session()).await;
+
+        let mut client = TcpStream::connect(gateway_addr).await.unwrap();
+        assert!(!handshake(&mut client, echo_addr).await);
+    }
+
+    #[tokio::test]
+    async fn resolves_allowed_names_and_blocks_ip_literal_bypass() {
+        let mut session = sample_session();
+        session.allowed_destinations = vec!["localhost".to_string()];
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let gateway_addr = listener.local_addr().unwrap();
+        drop(listener);
+        let gateway = DataPlaneGateway::new(
+            GatewayConfig {
+                listen_addr: gateway_addr.to_string(),
+                idle_timeout_seconds: 30,
+                ..GatewayConfig::default()
+            },
+            vec![session],
+        );
+        tokio::spawn(gateway.clone().run());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The name is allowed and resolved (then cached), but it resolves to
+        // loopback, so the relay is refused as a rebinding would be.
+        let echo_addr = spawn_echo().await;
+        for _ in 0..2 {
+            let mut client = TcpStream::connect(gateway_addr).await.unwrap();
+            let destination = format!("localhost:{}", echo_addr.port());
+            assert!(!handshake_to(&mut client, &destination).await);
+        }
+
+        // The address behind an allowed name is not itself allowed, in any
+        // spelling.
+        for host in ["127.0.0.1", "2130706433", "0x7f.1", "127.1"] {
+            let mut client = TcpStream::connect(gateway_addr).await.unwrap();
+            let destination = format!("{host}:{}", echo_addr.port());
+            assert!(!handshake_to(&mut client, &destination).await, "{host}");
+        }
+
+        let stats = gateway.dns_stats();
+        assert_eq!((stats.cache_misses, stats.cache_hits), (1, 1));
+        assert_eq!(stats.cached_names, 1);
+        assert_eq!(stats.blocked_by_policy.get("policy_web_basic_v1"), Some(&4));
+    }
+
+    /// Read the gateway's reply line to a handshake.
+    async fn handshake_reply(client: &mut TcpStream, destination: SocketAddr) -> String {
+        let handshake = serde_json::json!({
+            "session_id": "sess_123",
+            "session_token": "cs_token",
+            "destination": destination.to_string(),
+        })
+        .to_string();
+        client
+            .write_all(format!("{handshake}\n").as_bytes())
+            .await
+            .unwrap();
+        let mut reply = String::new();
+        BufReader::new(client).read_line(&mut reply).await.unwrap();
+        reply
+    }
+
+    #[tokio::test]
+    async fn session_limits_refuse_excess_connections() {
+        // Echoes every connection until the client closes it.
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = upstream.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = upstream.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = socket.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+
+        let mut session = sample_session();
+        session.allowed_destinations = vec!["127.0.0.1".to_string()];
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let gateway_addr = listener.local_addr().unwrap();
+        drop(listener);
+        let gateway = DataPlaneGateway::new(
+            GatewayConfig {
+                listen_addr: gateway_addr.to_string(),
+                idle_timeout_seconds: 30,
+                session_limits: SessionConnectionLimits {
+                    max_concurrent_tunnels: 1,
+                    max_new_connections_per_minute: 0,
+                    max_total_connections: 3,
+                },
+                allow_internal_upstreams: true,
+                ..GatewayConfig::default()
+            },
+            vec![session],
+        );
+        tokio::spawn(gateway.run());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut first = TcpStream::connect(gateway_addr).await.unwrap();
+        assert_eq!(handshake_reply(&mut first, upstream_addr).await, "OK\n");
+
+        let mut second = TcpStream::connect(gateway_addr).await.unwrap();
+        let reply = handshake_reply(&mut second, upstream_addr).await;
+        let error: serde_json::Value =
+            serde_json::from_str(reply.strip_prefix("ERR ").unwrap()).unwrap();
+        assert_eq!(error["error"], "session_limit_exceeded");
+        assert_eq!(error["limit"], "concurrent_tunnels");
+        assert_eq!(error["max"], 1);
+
+        // Closing a tunnel frees its slot; the lifetime cap still counts it.
+        for _ in 0..2 {
+            drop(first);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            first = TcpStream::connect(gateway_addr).await.unwrap();
+            assert_eq!(handshake_reply(&mut first, upstream_addr).await, "OK\n");
+        }
+        drop(first);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut last = TcpStream::connect(gateway_addr).await.unwrap();
+        let reply = handshake_reply(&mut last, upstream_addr).await;
+        assert!(reply.contains(r#""limit":"total_connections""#), "{reply}");
+    }
+
+    async fn spawn_socks5_gateway(session: GatewaySession) -> SocketAddr {
+        let mut addrs = Vec::new();
+        for _ in 0..2 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            addrs.push(listener.local_addr().unwrap());
+        }
+
+        let gateway = DataPlaneGateway::new(
+            GatewayConfig {
+                listen_addr: addrs[0].to_string(),
+                idle_timeout_seconds: 30,
+                socks5_listen_addr: Some(addrs[1].to_string()),
+                allow_internal_upstreams: true,
+                ..GatewayConfig::default()
+            },
+            vec![session],
+        );
+        tokio::spawn(async move {
+            if let Err(err) = gateway.run().await {
+                tracing::error!("gateway terminated in test: {err:#}");
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        addrs[1]
+    }
+
+    /// Log in with `password` and ask for an IPv4 `destination`.  Returns the
+    /// authentication status, then the reply code if authentication passed.
+    async fn socks5_connect(
+        client: &mut TcpStream,
+        password: &str,
+        destination: SocketAddr,
+    ) -> (u8, Option<u8>) {
+        client.write_all(&[0x05, 0x01, 0x02]).await.unwrap();
+        let mut selected = [0u8; 2];
+        client.read_exact(&mut selected).await.unwrap();
+        assert_eq!(selected, [0x05, 0x02]);
+
+        let mut login = vec![0x01, 8];
+        login.extend_from_slice(b"sess_123");
+        login.push(password.len() as u8);
+        login.extend_from_slice(password.as_bytes());
+        client.write_all(&login).await.unwrap();
+        let mut status = [0u8; 2];
+        client.read_exact(&mut status).await.unwrap();
+        if status[1] != 0x00 {
+            return (status[1], None);
+        }
+
+        let SocketAddr::V4(destination) = destination else {
+            panic!("IPv4 destination expected");
+        };
+        let mut request = vec![0x05, 0x01, 0x00, 0x01];
+        request.extend_from_slice(&destination.ip().octets());
+        request.extend_from_slice(&destination.port().to_be_bytes());
+        client.write_all(&request).await.unwrap();
+        let mut reply = [0u8; 10];
+        client.read_exact(&mut reply).await.unwrap();
+        (status[1], Some(reply[1]))
+    }
+
+    #[tokio::test]
+    async fn socks5_listener_relays_for_session_credentials() {
+        let echo_addr = spawn_echo().await;
+        let socks5_addr = spawn_socks5_gateway(sample_session()).await;
+
+        let mut client = TcpStream::connect(socks5_addr).await.unwrap();
+        assert_eq!(
+            socks5_connect(&mut client, "cs_token", echo_addr).await,
+            (0x00, Some(0x00))
+        );
+        client.write_all(b"hello socks").await.unwrap();
+        let mut echoed = vec![0u8; 11];
+        client.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"hello socks");
+    }
+
+    #[tokio::test]
+    async fn socks5_listener_enforces_token_and_destination_policy() {
+        let echo_addr = spawn_echo().await;
+        let socks5_addr = spawn_socks5_gateway(sample_session()).await;
+
+        let mut client = TcpStream::connect(socks5_addr).await.unwrap();
+        assert_eq!(
+            socks5_connect(&mut client, "wrong_token", echo_addr).await,
+            (0x01, None)
+        );
+
+        // 127.0.0.2 is outside the session's allowlist.
+        let blocked = SocketAddr::from(([127, 0, 0, 2], echo_addr.port()));
+        let mut client = TcpStream::connect(socks5_addr).await.unwrap();
+        assert_eq!(
+            socks5_connect(&mut client, "cs_token", blocked).await,
+            (0x00, Some(0x02))
+        );
+    }
+
+    #[tokio::test]
+    async fn socks5_listener_refuses_mtls_sessions() {
+        let echo_addr = spawn_echo().await;
+        let socks5_addr = spawn_socks5_gateway(mtls_session()).await;
+
+        let mut client = TcpStream::connect(socks5_addr).await.unwrap();
+        assert_eq!(
+            socks5_connect(&mut client, "cs_token", echo_addr).await,
+            (0x01, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn transparent_listener_drops_connections_that_were_not_redirected() {
+        let mut addrs = Vec::new();
+        for _ in 0..2 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            addrs.push(listener.local_addr().unwrap());
+        }
+        let gateway = DataPlaneGateway::new(
+            GatewayConfig {
+                listen_addr: addrs[0].to_string(),
+                transparent: Some(TransparentConfig {
+                    listen_addr: addrs[1].to_string(),
+                    interface: "lo".to_string(),
+                    bindings: vec!["127.0.0.0/8=sess_123".parse().unwrap()],
+                    ..TransparentConfig::default()
+                }),
+                ..GatewayConfig::default()
+            },
+            vec![sample_session()],
+        );
+        let usage = gateway.usage();
+        tokio::spawn(async move {
+            if let Err(err) = gateway.run().await {
+                tracing::error!("gateway terminated in test: {err:#}");
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Dialled directly, the connection has no other destination to
+        // relay to and must not loop back into the listener.
+        let mut client = TcpStream::connect(addrs[1]).await.unwrap();
+        let _ = client.write_all(b"GET / HTTP/1.1\r\n\r\n").await;
+        let mut buf = [0u8; 16];
+        let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf))
+            .await
+            .unwrap();
+        assert!(matches!(read, Ok(0) | Err(_)), "{read:?}");
+        assert!(usage.snapshot().sessions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expire_due_sessions_waits_for_grace() {
+        let mut session = sample_session();
+        session.expires_at_epoch_seconds = (chrono::Utc::now().timestamp() as u64) - 2;
+
+        let gateway = DataPlaneGateway::new(GatewayConfig::default(), vec![session.clone()]);
+        assert!(gateway.expire_due_sessions().await.is_empty());
+        assert!(gateway
+            .sessions
+            .read()
+            .await
+            .contains_key(&session.session_id));
+
+        let gateway = DataPlaneGateway::new(
+            GatewayConfig {
+                expiry_grace_seconds: 0,
+                tls: None,
+                ..GatewayConfig::default()
+            },
+            vec![session.clone()],
+        );
+        let mut events = gateway.subscribe_events();
+        let expected = GatewayEvent::SessionExpired {
+            session_id: session.session_id.clone(),
+            expires_at_epoch_seconds: session.expires_at_epoch_seconds,
+            terminated_relays: 0,
+        };
+        assert_eq!(gateway.expire_due_sessions().await, vec![expected.clone()]);
+        assert_eq!(events.recv().await.unwrap(), expected);
+        assert!(gateway.sessions.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn drain_caps_session_expiry() {
+        let gateway = DataPlaneGateway::new(
+            GatewayConfig {
+                expiry_grace_seconds: 0,
+                ..GatewayConfig::default()
+            },
+            vec![sample_session()],
+        );
+        let deadline = gateway.begin_drain(30).await;
+        // A later, longer announcement does not push the deadline out.
+        assert_eq!(gateway.begin_drain(120).await, deadline);
+
+        let mut later = sample_session();
+        later.session_id = "sess_later".to_string();
+        gateway.add_session(later).await;
+        let sessions = gateway.sessions.read().await;
+        assert!(sessions
+            .values()
+            .all(|live| live.session.expires_at_epoch_seconds == deadline));
+        drop(sessions);
+
+        let drained = gateway.begin_drain(0).await;
+        assert!(drained < deadline);
+        let events = gateway.expire_due_sessions().await;
+        assert_eq!(events.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn live_relay_is_torn_down_at_expiry() {
+        let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = upstream_listener.local_addr().unwrap();
+
+        // Upstream that keeps the connection open without sending anything.
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = upstream_listener.accept().await {
+                let mut buf = [0u8; 1024];
+                while matches!(socket.read(&mut buf).await, Ok(n) if n > 0) {}
+            }
+        });
+
+        let mut session = sample_session();
+        session.expires_at_epoch_seconds = (chrono::Utc::now().timestamp() as u64) + 2;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let gateway_addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let gateway = DataPlaneGateway::new(
+            GatewayConfig {
+                listen_addr: gateway_addr.to_string(),
+                connect_timeout_seconds: 5,
+                idle_timeout_seconds: 30,
+                clock_offset_ms: 0,
+                expiry_grace_seconds: 0,
+                tls: None,
+                socks5_listen_addr: None,
+                dns_cache_ttl_seconds: 60,
+                session_limits: SessionConnectionLimits::default(),
+                transparent: None,
+                allow_internal_upstreams: true,
+            },
+            vec![session],
+        );
+        let mut events = gateway.subscribe_events();
+
+        let run_gateway = gateway.clone();
+        tokio::spawn(async move {
+            if let Err(err) = run_gateway.run().await {
+                tracing::error!("gateway terminated in test: {err:#}");
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut client = TcpStream::connect(gateway_addr).await.unwrap();
+        let handshake = serde_json::json!({
+            "session_id": "sess_123",
+            "session_token": "cs_token",
+            "destination": format!("127.0.0.1:{}", upstream_addr.port()),
+        })
+        .to_string();
+        client.write_all(handshake.as_bytes()).await.unwrap();
+        client.write_all(b"\n").await.unwrap();
+
+        let mut ack = [0u8; 3];
+        client.read_exact(&mut ack).await.unwrap();
+        assert_eq!(&ack, b"OK\n");
+
+        // The gateway closes the tunnel once the session expires.
+        let mut buf = [0u8; 16];
+        let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf))
+            .await
+            .expect("relay should be torn down at expiry");
+        assert!(matches!(read, Ok(0) | Err(_)));
+
+        let event = tokio::time::timeout(Duration::from_secs(1), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            event,
+            GatewayEvent::SessionExpired {
+                ref session_id,
+                terminated_relays: 1,
+                ..
+            } if session_id == "sess_123"
+        ));
+        assert!(gateway.sessions.read().await.is_empty());
     }
 
     // -----------------------------------------------------------------------
@@ -940,6 +3056,134 @@ mod tests {
         );
     }
 
+    // -----------------------------------------------------------------------
+    // PAC server tests
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn pac_file_proxies_tunnelled_schemes_and_bypasses_rules() {
+        let server = PacServer::new(PacServerConfig {
+            bypass: vec![
+                "Intranet.example".to_string(),
+                "*.corp.example".to_string(),
+                "100.64.0.0/10".to_string(),
+            ],
+            ..PacServerConfig::default()
+        })
+        .unwrap();
+
+        let pac = server.pac_file("192.168.1.5");
+        assert!(pac.starts_with("function FindProxyForURL(url, host) {"));
+        assert!(pac.contains("return \"PROXY 192.168.1.5:3128\""));
+        assert!(pac.contains("host == \"intranet.example\""));
+        assert!(pac.contains("host == \"corp.example\" || dnsDomainIs(host, \".corp.example\")"));
+        assert!(pac.contains("isInNet(host, \"100.64.0.0\", \"255.192.0.0\")"));
+        assert!(pac.contains("isPlainHostName(host)"));
+        assert!(pac.contains("isInNet(host, \"192.168.0.0\", \"255.255.0.0\")"));
+        assert!(pac.trim_end().ends_with("return \"DIRECT\";\n}"));
+
+        // IPv6 proxy addresses are bracketed.
+        assert!(server
+            .pac_file("fd00::1")
+            .contains("return \"PROXY [fd00::1]:3128\""));
+    }
+
+    #[test]
+    fn pac_file_uses_configured_proxy_host_and_skips_local_bypass() {
+        let server = PacServer::new(PacServerConfig {
+            proxy_host: "proxy.lan".to_string(),
+            proxy_port: 8443,
+            bypass_local: false,
+            ..PacServerConfig::default()
+        })
+        .unwrap();
+
+        let pac = server.pac_file("10.0.0.1");
+        assert!(pac.contains("return \"PROXY proxy.lan:8443\""));
+        assert!(!pac.contains("10.0.0.1"));
+        assert!(!pac.contains("isPlainHostName"));
+    }
+
+    #[test]
+    fn pac_server_rejects_rules_that_are_not_hosts_or_ranges() {
+        for rule in [
+            "evil\"); alert(1); (\"",
+            "*.",
+            "10.0.0.0/33",
+            "fd00::/8",
+            "",
+        ] {
+            let config = PacServerConfig {
+                bypass: vec![rule.to_string()],
+                ..PacServerConfig::default()
+            };
+            assert!(PacServer::new(config).is_err(), "accepted {rule:?}");
+        }
+        let config = PacServerConfig {
+            proxy_host: "proxy\";".to_string(),
+            ..PacServerConfig::default()
+        };
+        assert!(PacServer::new(config).is_err());
+    }
+
+    #[test]
+    fn pac_config_for_proxy_takes_the_proxy_port() {
+        let proxy = HttpConnectProxyConfig {
+            listen_addr: "0.0.0.0:8888".to_string(),
+            enabled: true,
+            ..HttpConnectProxyConfig::default()
+        };
+        let config = PacServerConfig::for_proxy(&proxy);
+        assert_eq!(config.proxy_port, 8888);
+        assert!(config.enabled);
+        assert_eq!(config.listen_addr, PacServerConfig::default().listen_addr);
+    }
+
+    #[tokio::test]
+    async fn pac_server_disabled_exits_immediately() {
+        let server = PacServer::new(PacServerConfig::default()).unwrap();
+        assert!(server.run().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn pac_server_serves_pac_file_for_the_reached_address() {
+        use tokio::io::AsyncReadExt;
+
+        let probe = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = probe.local_addr().unwrap();
+        drop(probe);
+
+        let server = PacServer::new(PacServerConfig {
+            listen_addr: addr.to_string(),
+            enabled: true,
+            ..PacServerConfig::default()
+        })
+        .unwrap();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let fetch = |path: &'static str| async move {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            let request = format!("GET {path} HTTP/1.1\r\nHost: wpad\r\nConnection: close\r\n\r\n");
+            client.write_all(request.as_bytes()).await.unwrap();
+            let mut response = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            String::from_utf8(response).unwrap()
+        };
+
+        for path in ["/proxy.pac", "/wpad.dat"] {
+            let response = fetch(path).await;
+            assert!(response.contains("200 OK"), "got: {response}");
+            assert!(response.contains("Content-Type: application/x-ns-proxy-autoconfig"));
+            assert!(response.contains("PROXY 127.0.0.1:3128"), "got: {response}");
+        }
+        let response = fetch("/index.html").await;
+        assert!(response.contains("404 Not Found"), "got: {response}");
+    }
+
     // -----------------------------------------------------------------------
     // HTTP CONNECT proxy tests
     // -----------------------------------------------------------------------
@@ -952,6 +3196,7 @@ mod tests {
             enabled: false,
             connect_timeout_secs: 5,
             idle_timeout_secs: 30,
+            allow_internal_upstreams: false,
         };
         let proxy = HttpConnectProxy::new(config);
         let result = proxy.run().await;
@@ -972,6 +3217,7 @@ mod tests {
             enabled: true,
             connect_timeout_secs: 5,
             idle_timeout_secs: 30,
+            allow_internal_upstreams: false,
         };
         let proxy = HttpConnectProxy::new(config);
         tokio::spawn(async move {
@@ -1009,6 +3255,7 @@ mod tests {
             enabled: true,
             connect_timeout_secs: 5,
             idle_timeout_secs: 30,
+            allow_internal_upstreams: false,
         };
         let proxy = HttpConnectProxy::new(config);
         tokio::spawn(async move {
@@ -1047,6 +3294,7 @@ mod tests {
             enabled: true,
             connect_timeout_secs: 5,
             idle_timeout_secs: 30,
+            allow_internal_upstreams: false,
         };
         let proxy = HttpConnectProxy::new(config);
         tokio::spawn(async move {
@@ -1096,6 +3344,7 @@ mod tests {
             enabled: true,
             connect_timeout_secs: 5,
             idle_timeout_secs: 30,
+            allow_internal_upstreams: true,
         };
         let proxy = HttpConnectProxy::new(config);
         tokio::spawn(async move {
@@ -1127,6 +3376,58 @@ mod tests {
         assert_eq!(&echoed, b"hello proxy");
     }
 
+    #[tokio::test]
+    async fn http_connect_proxy_refuses_internal_destinations() {
+        use tokio::io::AsyncReadExt;
+
+        // A loopback service the tunnel must not reach.
+        let internal = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let internal_addr = internal.local_addr().unwrap();
+
+        let probe = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = probe.local_addr().unwrap();
+        drop(probe);
+
+        let config = HttpConnectProxyConfig {
+            listen_addr: proxy_addr.to_string(),
+            session_token: "proxy-token".to_string(),
+            enabled: true,
+            connect_timeout_secs: 5,
+            idle_timeout_secs: 30,
+            allow_internal_upstreams: false,
+        };
+        let proxy = HttpConnectProxy::new(config);
+        tokio::spawn(async move {
+            let _ = proxy.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        for target in [
+            format!("127.0.0.1:{}", internal_addr.port()),
+            format!("localhost:{}", internal_addr.port()),
+        ] {
+            let mut client = TcpStream::connect(proxy_addr).await.unwrap();
+            let request = format!(
+                "CONNECT {target} HTTP/1.1\r\nProxy-Authorization: Bearer proxy-token\r\n\r\n"
+            );
+            client.write_all(request.as_bytes()).await.unwrap();
+
+            let mut response = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            let response_str = std::str::from_utf8(&response).unwrap();
+            assert!(
+                response_str.contains("502 Bad Gateway"),
+                "expected 502 for {target}, got: {response_str}"
+            );
+        }
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), internal.accept())
+                .await
+                .is_err(),
+            "the internal service was reached"
+        );
+    }
+
     #[tokio::test]
     async fn http_connect_proxy_no_auth_required_when_token_empty() {
         use tokio::io::AsyncReadExt;
@@ -1153,6 +3454,7 @@ mod tests {
             enabled: true,
             connect_timeout_secs: 5,
             idle_timeout_secs: 30,
+            allow_internal_upstreams: true,
         };
         let proxy = HttpConnectProxy::new(config);
         tokio::spawn(async move {
@@ -1180,4 +3482,91 @@ mod tests {
         client.read_exact(&mut echoed).await.unwrap();
         assert_eq!(&echoed, b"no-auth");
     }
+
+    /// Send a CONNECT with `auth` as `Proxy-Authorization` and return the
+    /// response head.
+    async fn http_connect(client: &mut TcpStream, auth: &str, destination: SocketAddr) -> String {
+        let request =
+            format!("CONNECT {destination} HTTP/1.1\r\nProxy-Authorization: {auth}\r\n\r\n");
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0u8; 1];
+            if client.read(&mut byte).await.unwrap() == 0 {
+                break;
+            }
+            head.push(byte[0]);
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    #[tokio::test]
+    async fn http_connect_proxy_relays_each_token_under_its_own_session() {
+        let echo_addr = spawn_echo().await;
+        let mut web_only = sample_session();
+        web_only.session_id = "sess_web".to_string();
+        web_only.session_token = "web_token".to_string();
+        web_only.allowed_destinations = vec!["*.example.com".to_string()];
+
+        let gateway = DataPlaneGateway::new(
+            GatewayConfig {
+                session_limits: SessionConnectionLimits {
+                    max_concurrent_tunnels: 1,
+                    ..SessionConnectionLimits::default()
+                },
+                allow_internal_upstreams: true,
+                ..GatewayConfig::default()
+            },
+            vec![sample_session(), web_only],
+        );
+        let usage = gateway.usage();
+
+        let probe = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = probe.local_addr().unwrap();
+        drop(probe);
+        let proxy = HttpConnectProxy::new(HttpConnectProxyConfig {
+            listen_addr: proxy_addr.to_string(),
+            enabled: true,
+            ..HttpConnectProxyConfig::default()
+        })
+        .with_gateway(gateway);
+        tokio::spawn(async move {
+            let _ = proxy.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut tunnel = TcpStream::connect(proxy_addr).await.unwrap();
+        let head = http_connect(&mut tunnel, "Bearer cs_token", echo_addr).await;
+        assert!(head.contains("200 Connection Established"), "{head}");
+        tunnel.write_all(b"hello session").await.unwrap();
+        let mut echoed = [0u8; 13];
+        tunnel.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"hello session");
+
+        // The first tunnel holds sess_123's only slot.
+        let mut client = TcpStream::connect(proxy_addr).await.unwrap();
+        let head = http_connect(&mut client, "Bearer cs_token", echo_addr).await;
+        assert!(head.contains("429 Too Many Requests"), "{head}");
+
+        // Basic credentials name the session; its policy refuses 127.0.0.1.
+        let basic = format!("Basic {}", STANDARD.encode("sess_web:web_token"));
+        let mut client = TcpStream::connect(proxy_addr).await.unwrap();
+        let head = http_connect(&mut client, &basic, echo_addr).await;
+        assert!(head.contains("403 Forbidden"), "{head}");
+
+        for auth in ["Bearer unknown", "Basic bm90OmZvdW5k", "Bearer"] {
+            let mut client = TcpStream::connect(proxy_addr).await.unwrap();
+            let head = http_connect(&mut client, auth, echo_addr).await;
+            assert!(
+                head.contains("407 Proxy Authentication Required"),
+                "{auth}: {head}"
+            );
+            assert!(head.contains("Proxy-Authenticate: Basic"), "{head}");
+        }
+
+        let snapshot = usage.snapshot();
+        assert_eq!(snapshot.sessions["sess_123"].connections, 1);
+        assert_eq!(snapshot.sessions["sess_123"].active_connections, 1);
+        assert!(!snapshot.sessions.contains_key("sess_web"));
+    }
 }
@@ -0,0 +1,193 @@
+//! Gateway access log
+//!
+//! Every relayed connection ends in one [`AccessRecord`]: the session, the
+//! destination, bytes each way, how long it ran and why it ended.
+//! [`AccessLog`] appends records as JSON lines to a local file and rotates
+//! it by size: once `path` would grow past `max_bytes` it becomes
+//! `path.1`, the older files shift up, and at most `keep` rotated files are
+//! kept.  A failed write is logged and never fails the relay.
+//!
+//! Running totals per session are kept by [`crate::gateway_usage`].
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::warn;
+
+/// Default size a log file may reach before it is rotated.
+pub const DEFAULT_ACCESS_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Default number of rotated files kept.
+pub const DEFAULT_ACCESS_LOG_KEEP: u32 = 5;
+
+/// Why a relayed connection ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminationReason {
+    /// Both sides closed
+    Closed,
+    /// Nothing moved for the gateway's idle timeout
+    IdleTimeout,
+    /// Reading or writing either side failed
+    IoError,
+    /// The session expired, was revoked or the node drained
+    SessionEnded,
+}
+
+/// One relayed connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessRecord {
+    pub session_id: String,
+    /// `host:port` the client asked for
+    pub destination: String,
+    pub peer_addr: String,
+    /// `tunnel` (handshake listener), `socks5`, `http_connect` or
+    /// `transparent`
+    pub listener: String,
+    /// RFC 3339 time the relay started
+    pub started_at: String,
+    pub duration_ms: u64,
+    /// Bytes from the client towards the destination
+    pub bytes_up: u64,
+    /// Bytes from the destination back to the client
+    pub bytes_down: u64,
+    pub termination: TerminationReason,
+}
+
+/// Size-rotated JSON-lines access log.
+#[derive(Debug)]
+pub struct AccessLog {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: u32,
+    file: Mutex<(File, u64)>,
+}
+
+impl AccessLog {
+    /// Append to `path`, creating it if needed.  `keep` of 0 truncates the
+    /// file instead of keeping rotated copies.
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64, keep: u32) -> Result<Self> {
+        let path = path.into();
+        let file = open_append(&path)?;
+        let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            max_bytes: max_bytes.max(1),
+            keep,
+            file: Mutex::new((file, size)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `record`, rotating first when it would not fit.
+    pub fn write(&self, record: &AccessRecord) {
+        if let Err(err) = self.try_write(record) {
+            warn!(path = %self.path.display(), "failed to write gateway access log: {err:#}");
+        }
+    }
+
+    fn try_write(&self, record: &AccessRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if file.1 > 0 && file.1 + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+            *file = (open_append(&self.path)?, 0);
+        }
+        file.0.write_all(&line)?;
+        file.1 += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&self) -> Result<()> {
+        let rotated = |n: u32| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+        if self.keep == 0 {
+            return std::fs::remove_file(&self.path).context("failed to truncate access log");
+        }
+        for n in (1..self.keep).rev() {
+            let from = rotated(n);
+            if from.exists() {
+                std::fs::rename(&from, rotated(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated(1)).context("failed to rotate access log")
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open access log {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(session_id: &str) -> AccessRecord {
+        AccessRecord {
+            session_id: session_id.to_string(),
+            destination: "example.com:443".to_string(),
+            peer_addr: "127.0.0.1:50000".to_string(),
+            listener: "tunnel".to_string(),
+            started_at: "2026-01-01T00:00:00Z".to_string(),
+            duration_ms: 1_500,
+            bytes_up: 120,
+            bytes_down: 4_096,
+            termination: TerminationReason::Closed,
+        }
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_the_newest_files() {
+        let dir = std::env::temp_dir().join(format!("gateway-access-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("access.log");
+        let line_len = serde_json::to_vec(&record("s-0")).unwrap().len() as u64 + 1;
+
+        // Two records per file, two rotated files.
+        let log = AccessLog::open(&path, line_len * 2, 2).unwrap();
+        for n in 0..7 {
+            log.write(&record(&format!("s-{n}")));
+        }
+        let sessions = |path: &Path| -> Vec<String> {
+            std::fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    serde_json::from_str::<AccessRecord>(line)
+                        .unwrap()
+                        .session_id
+                })
+                .collect()
+        };
+        assert_eq!(sessions(&path), ["s-6"]);
+        assert_eq!(sessions(&dir.join("access.log.1")), ["s-4", "s-5"]);
+        assert_eq!(sessions(&dir.join("access.log.2")), ["s-2", "s-3"]);
+        assert!(!dir.join("access.log.3").exists());
+
+        // Reopening appends and counts the existing size.
+        let log = AccessLog::open(&path, line_len * 2, 2).unwrap();
+        log.write(&record("s-7"));
+        log.write(&record("s-8"));
+        assert_eq!(sessions(&path), ["s-8"]);
+        assert_eq!(sessions(&dir.join("access.log.1")), ["s-6", "s-7"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+//! Local admin socket for runtime gateway session management
+//!
+//! [`GatewayAdminSocket`] listens on a Unix socket, readable and writable
+//! by the gateway's user only, for one JSON request per line and answers
+//! each with one JSON line:
+//!
+//! - `{"op":"add","session":{...}}` — provision or update a session, as
+//!   [`DataPlaneGateway::add_session`]
+//! - `{"op":"revoke","session_id":"..."}` — revoke a session; `revoked`
+//!   says whether it was live
+//! - `{"op":"list"}` — the live sessions, without their tokens
+//! - `{"op":"drain","grace_seconds":N}` — as
+//!   [`DataPlaneGateway::begin_drain`]; `deadline` is the coordinator epoch
+//!   second sessions now expire by
+//!
+//! Replies carry `"ok":true`, or `"ok":false` with an `error`.  Sessions
+//! added here are not written to the sessions file, so a reload
+//! ([`crate::gateway_reload`]) leaves them alone.
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+use tracing::{info, warn};
+
+use crate::gateway::{DataPlaneGateway, GatewaySession, GatewaySessionSummary};
+
+/// Longest request line the socket reads.
+pub const MAX_ADMIN_REQUEST_BYTES: usize = 64 * 1024;
+
+/// One admin socket request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum GatewayAdminRequest {
+    Add { session: GatewaySession },
+    Revoke { session_id: String },
+    List,
+    Drain { grace_seconds: u64 },
+}
+
+/// Reply to one admin socket request.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GatewayAdminResponse {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<Vec<GatewaySessionSummary>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
+}
+
+impl GatewayAdminResponse {
+    fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            revoked: None,
+            sessions: None,
+            deadline: None,
+        }
+    }
+
+    fn error(error: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.into()),
+            ..Self::ok()
+        }
+    }
+}
+
+/// Apply one request to `gateway`.
+pub async fn handle_admin_request(
+    gateway: &DataPlaneGateway,
+    request: GatewayAdminRequest,
+) -> GatewayAdminResponse {
+    match request {
+        GatewayAdminRequest::Add { session } => {
+            if session.session_id.is_empty() {
+                return GatewayAdminResponse::error("session_id must not be empty");
+            }
+            info!(session_id = %session.session_id, "gateway session added over admin socket");
+            gateway.add_session(session).await;
+            GatewayAdminResponse::ok()
+        }
+        GatewayAdminRequest::Revoke { session_id } => {
+            let revoked = gateway.revoke_session(&session_id).await;
+            info!(%session_id, revoked, "gateway session revoked over admin socket");
+            GatewayAdminResponse {
+                revoked: Some(revoked),
+                ..GatewayAdminResponse::ok()
+            }
+        }
+        GatewayAdminRequest::List => GatewayAdminResponse {
+            sessions: Some(gateway.list_sessions().await),
+            ..GatewayAdminResponse::ok()
+        },
+        GatewayAdminRequest::Drain { grace_seconds } => GatewayAdminResponse {
+            deadline: Some(gateway.begin_drain(grace_seconds).await),
+            ..GatewayAdminResponse::ok()
+        },
+    }
+}
+
+/// Unix socket serving admin requests for one gateway.
+#[derive(Debug)]
+pub struct GatewayAdminSocket {
+    gateway: DataPlaneGateway,
+    path: PathBuf,
+    listener: UnixListener,
+}
+
+impl GatewayAdminSocket {
+    /// Bind the socket at `path`, replacing a stale socket left by an
+    /// earlier run, and restrict it to the current user.
+    pub fn bind(gateway: DataPlaneGateway, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err).context("failed to remove stale gateway admin socket"),
+        }
+        let listener = UnixListener::bind(&path).context("failed to bind gateway admin socket")?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .context("failed to restrict gateway admin socket")?;
+        Ok(Self {
+            gateway,
+            path,
+            listener,
+        })
+    }
+
+    /// Accept admin connections until the task is dropped.
+    pub async fn run(self) -> Result<()> {
+        info!(path = %self.path.display(), "gateway admin socket listening");
+        loop {
+            let (stream, _) = self
+                .listener
+                .accept()
+                .await
+                .context("failed to accept on gateway admin socket")?;
+            let gateway = self.gateway.clone();
+            tokio::spawn(async move {
+                if let Err(err) = serve_connection(&gateway, stream).await {
+                    warn!("gateway admin connection failed: {err:#}");
+                }
+            });
+        }
+    }
+}
+
+async fn serve_connection(gateway: &DataPlaneGateway, stream: UnixStream) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = (&mut reader)
+            .take(MAX_ADMIN_REQUEST_BYTES as u64 + 1)
+            .read_line(&mut line)
+            .await?;
+        if read == 0 {
+            return Ok(());
+        }
+        if read > MAX_ADMIN_REQUEST_BYTES {
+            let response = GatewayAdminResponse::error("request too large");
+            writer
+                .write_all(format!("{}\n", serde_json::to_string(&response)?).as_bytes())
+                .await?;
+            return Ok(());
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<GatewayAdminRequest>(line.trim()) {
+            Ok(request) => handle_admin_request(gateway, request).await,
+            Err(err) => GatewayAdminResponse::error(format!("invalid request: {err}")),
+        };
+        writer
+            .write_all(format!("{}\n", serde_json::to_string(&response)?).as_bytes())
+            .await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::GatewayConfig;
+
+    #[tokio::test]
+    async fn admin_socket_manages_sessions() {
+        let path =
+            std::env::temp_dir().join(format!("gateway-admin-{}.sock", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "stale").unwrap();
+        let gateway = DataPlaneGateway::new(GatewayConfig::default(), Vec::new());
+        let socket = GatewayAdminSocket::bind(gateway.clone(), &path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let server = tokio::spawn(socket.run());
+
+        let expires = chrono::Utc::now().timestamp() as u64 + 3600;
+        let requests = [
+            serde_json::json!({"op": "add", "session": {
+                "session_id": "sess_admin",
+                "session_token": "cs_secret",
+                "egress_profile": "allowlist_domains",
+                "destination_policy_id": "policy_web_basic_v1",
+                "allowed_destinations": ["example.com"],
+                "expires_at_epoch_seconds": expires,
+            }})
+            .to_string(),
+            r#"{"op":"list"}"#.to_string(),
+            r#"{"op":"revoke","session_id":"sess_admin"}"#.to_string(),
+            r#"{"op":"revoke","session_id":"sess_admin"}"#.to_string(),
+            r#"{"op":"reboot"}"#.to_string(),
+        ];
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(format!("{}\n", requests.join("\n")).as_bytes())
+            .await
+            .unwrap();
+        stream.shutdown().await.unwrap();
+        let mut replies = String::new();
+        stream.read_to_string(&mut replies).await.unwrap();
+        let replies: Vec<serde_json::Value> = replies
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(replies[0], serde_json::json!({"ok": true}));
+        assert_eq!(replies[1]["sessions"][0]["session_id"], "sess_admin");
+        assert_eq!(replies[1]["sessions"][0]["active_tunnels"], 0);
+        assert!(replies[1]["sessions"][0].get("session_token").is_none());
+        assert_eq!(replies[2]["revoked"], true);
+        assert_eq!(replies[3]["revoked"], false);
+        assert_eq!(replies[4]["ok"], false);
+        assert!(gateway.list_sessions().await.is_empty());
+
+        server.abort();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn drain_request_returns_the_deadline() {
+        let gateway = DataPlaneGateway::new(GatewayConfig::default(), Vec::new());
+        let response =
+            handle_admin_request(&gateway, GatewayAdminRequest::Drain { grace_seconds: 30 }).await;
+        let now = chrono::Utc::now().timestamp() as u64;
+        assert!(response.ok);
+        assert!((now + 29..=now + 31).contains(&response.deadline.unwrap()));
+    }
+}
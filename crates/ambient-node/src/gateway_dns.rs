@@ -0,0 +1,282 @@
+//! DNS resolution and egress filtering for relayed connections
+//!
+//! The gateway resolves destination names itself rather than leaving them
+//! to the OS connect path, and only after the session's destination policy
+//! allowed the name, so a lookup never happens for a blocked domain.
+//!
+//! - IP-literal destinations must be listed in an `allowlist_domains`
+//!   session's allowlist.  Numeric hosts the system resolver would read as
+//!   an address (`2130706433`, `0x7f.1`, `127.1`) are refused for every
+//!   profile instead of slipping past the allowlist as names.
+//! - Every address a destination resolves to is checked with
+//!   [`is_internal_address`], so neither a literal nor a name that rebinds
+//!   to loopback, a private range or link-local (`169.254.169.254`) is
+//!   relayed unless the gateway sets `allow_internal_upstreams`.
+//! - Answers are cached for `dns_cache_ttl_seconds` (`0` disables the
+//!   cache), up to [`DNS_CACHE_MAX_ENTRIES`] names.
+//! - Blocked destinations are logged with their policy and counted per
+//!   policy in [`DnsStats`].
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Names the resolver cache holds before evicting.
+pub const DNS_CACHE_MAX_ENTRIES: usize = 4096;
+
+/// Resolver counters, from [`crate::DataPlaneGateway::dns_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DnsStats {
+    /// Names currently cached, fresh or not
+    pub cached_names: usize,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Destinations refused, by destination policy ID
+    pub blocked_by_policy: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedLookup {
+    addrs: Vec<IpAddr>,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct ResolverState {
+    cache: HashMap<String, CachedLookup>,
+    stats: DnsStats,
+}
+
+/// Caching resolver shared by every listener of a gateway.
+#[derive(Debug)]
+pub(crate) struct GatewayResolver {
+    ttl: Duration,
+    state: Mutex<ResolverState>,
+}
+
+impl GatewayResolver {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            state: Mutex::new(ResolverState::default()),
+        }
+    }
+
+    /// Addresses of `host`, from the cache while the entry is fresh.
+    pub(crate) async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>> {
+        if let Some(addrs) = self.cached(host, Instant::now()) {
+            return Ok(addrs);
+        }
+
+        let mut addrs: Vec<IpAddr> = Vec::new();
+        for addr in tokio::net::lookup_host((host, 0))
+            .await
+            .with_context(|| format!("failed to resolve {host}"))?
+        {
+            if !addrs.contains(&addr.ip()) {
+                addrs.push(addr.ip());
+            }
+        }
+        if addrs.is_empty() {
+            bail!("{host} has no addresses");
+        }
+
+        if !self.ttl.is_zero() {
+            let now = Instant::now();
+            let mut state = self.lock();
+            if state.cache.len() >= DNS_CACHE_MAX_ENTRIES {
+                state.cache.retain(|_, entry| entry.expires_at > now);
+            }
+            if state.cache.len() >= DNS_CACHE_MAX_ENTRIES {
+                state.cache.clear();
+            }
+            state.cache.insert(
+                host.to_string(),
+                CachedLookup {
+                    addrs: addrs.clone(),
+                    expires_at: now + self.ttl,
+                },
+            );
+        }
+        Ok(addrs)
+    }
+
+    /// Count a destination refused under `policy_id`.
+    pub(crate) fn record_blocked(&self, policy_id: &str) -> u64 {
+        let mut state = self.lock();
+        let blocked = state
+            .stats
+            .blocked_by_policy
+            .entry(policy_id.to_string())
+            .or_default();
+        *blocked += 1;
+        *blocked
+    }
+
+    pub(crate) fn stats(&self) -> DnsStats {
+        let state = self.lock();
+        DnsStats {
+            cached_names: state.cache.len(),
+            ..state.stats.clone()
+        }
+    }
+
+    fn cached(&self, host: &str, now: Instant) -> Option<Vec<IpAddr>> {
+        let mut state = self.lock();
+        let addrs = state
+            .cache
+            .get(host)
+            .filter(|entry| entry.expires_at > now)
+            .map(|entry| entry.addrs.clone());
+        if addrs.is_some() {
+            state.stats.cache_hits += 1;
+        } else {
+            state.stats.cache_misses += 1;
+        }
+        addrs
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ResolverState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Whether `host` is not a canonical IP address but would still be read as
+/// one by `inet_aton`: one to four dot-separated decimal, octal or `0x` hex
+/// parts, optionally with a trailing dot.
+pub(crate) fn is_disguised_ip_literal(host: &str) -> bool {
+    if host.parse::<IpAddr>().is_ok() {
+        return false;
+    }
+    let host = host.strip_suffix('.').unwrap_or(host);
+    if host.is_empty() {
+        return false;
+    }
+    let parts: Vec<&str> = host.split('.').collect();
+    parts.len() <= 4
+        && parts.iter().all(|part| {
+            let digits = part.strip_prefix("0x").or_else(|| part.strip_prefix("0X"));
+            match digits {
+                Some(hex) => hex.chars().all(|c| c.is_ascii_hexdigit()),
+                None => !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()),
+            }
+        })
+}
+
+/// Whether `ip` is on the node itself or its local networks: loopback,
+/// unspecified, private (RFC 1918, IPv6 ULA), link-local (including the
+/// cloud metadata address), broadcast, or an IPv4-mapped form of these.
+pub(crate) fn is_internal_address(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_internal_address(&IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_disguised_ip_literals() {
+        for host in [
+            "2130706433",
+            "0x7f000001",
+            "0x7f.1",
+            "127.1",
+            "0177.0.0.1",
+            "10.0.0.1.",
+        ] {
+            assert!(is_disguised_ip_literal(host), "{host}");
+        }
+        for host in [
+            "127.0.0.1",
+            "::1",
+            "example.com",
+            "1.example",
+            "0xdead.beef.io",
+            "1.2.3.4.5",
+        ] {
+            assert!(!is_disguised_ip_literal(host), "{host}");
+        }
+    }
+
+    #[test]
+    fn detects_internal_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(is_internal_address(&ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "93.184.216.34",
+            "8.8.8.8",
+            "2606:4700::1111",
+            "::ffff:1.1.1.1",
+        ] {
+            assert!(!is_internal_address(&ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn caches_lookups_until_ttl() {
+        let resolver = GatewayResolver::new(Duration::from_secs(60));
+        let addrs = resolver.lookup("localhost").await.unwrap();
+        assert!(addrs.iter().all(IpAddr::is_loopback));
+        assert_eq!(resolver.lookup("localhost").await.unwrap(), addrs);
+
+        let stats = resolver.stats();
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 1));
+        assert_eq!(stats.cached_names, 1);
+
+        let uncached = GatewayResolver::new(Duration::ZERO);
+        uncached.lookup("localhost").await.unwrap();
+        uncached.lookup("localhost").await.unwrap();
+        assert_eq!(uncached.stats().cache_misses, 2);
+        assert_eq!(uncached.stats().cached_names, 0);
+    }
+
+    #[test]
+    fn counts_blocked_destinations_per_policy() {
+        let resolver = GatewayResolver::new(Duration::from_secs(60));
+        assert_eq!(resolver.record_blocked("policy_a"), 1);
+        assert_eq!(resolver.record_blocked("policy_a"), 2);
+        assert_eq!(resolver.record_blocked("policy_b"), 1);
+        let blocked = resolver.stats().blocked_by_policy;
+        assert_eq!(blocked.get("policy_a"), Some(&2));
+        assert_eq!(blocked.get("policy_b"), Some(&1));
+    }
+}
@@ -0,0 +1,234 @@
+//! Per-session connection limits for the data-plane gateway
+//!
+//! Every relayed connection is admitted against its session's limits after
+//! the destination was authorized and before the upstream socket is opened,
+//! so one endpoint cannot exhaust the relay node's sockets:
+//!
+//! - `max_concurrent_tunnels` — relays open at once
+//! - `max_new_connections_per_minute` — admissions in any 60 second window
+//! - `max_total_connections` — admissions over the session's lifetime
+//!
+//! `0` leaves a limit off.  A refused connection is answered with a
+//! [`SessionLimitExceeded`]: an `ERR` line carrying it as JSON on the
+//! handshake listener, `connection not allowed` on the SOCKS5 listener.
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Window `max_new_connections_per_minute` is counted over.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Connection caps applied to each session separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConnectionLimits {
+    pub max_concurrent_tunnels: u32,
+    pub max_new_connections_per_minute: u32,
+    pub max_total_connections: u64,
+}
+
+impl Default for SessionConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent_tunnels: 128,
+            max_new_connections_per_minute: 600,
+            max_total_connections: 0,
+        }
+    }
+}
+
+/// Which of a session's limits refused a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionLimit {
+    ConcurrentTunnels,
+    ConnectionRate,
+    TotalConnections,
+}
+
+/// A connection refused by a session limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SessionLimitExceeded {
+    /// Always `session_limit_exceeded`
+    pub error: &'static str,
+    pub session_id: String,
+    pub limit: SessionLimit,
+    /// The configured value of `limit`
+    pub max: u64,
+}
+
+impl fmt::Display for SessionLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.limit {
+            SessionLimit::ConcurrentTunnels => "concurrent tunnels",
+            SessionLimit::ConnectionRate => "new connections per minute",
+            SessionLimit::TotalConnections => "total connections",
+        };
+        write!(
+            f,
+            "session {} is at its limit of {} {what}",
+            self.session_id, self.max
+        )
+    }
+}
+
+impl std::error::Error for SessionLimitExceeded {}
+
+#[derive(Debug, Default)]
+struct Counters {
+    active: u32,
+    total: u64,
+    /// Admission times within the rate window, oldest first
+    recent: VecDeque<Instant>,
+}
+
+/// Connection counters of one session.  Kept when the session is re-added,
+/// so refreshing a session does not reset its limits.
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionLimiter {
+    counters: Mutex<Counters>,
+}
+
+impl ConnectionLimiter {
+    /// Admit a connection for `session_id`, or say which limit refuses it.
+    /// The returned permit holds a tunnel slot until dropped.
+    pub(crate) fn admit(
+        self: &Arc<Self>,
+        session_id: &str,
+        limits: &SessionConnectionLimits,
+        now: Instant,
+    ) -> Result<TunnelPermit, SessionLimitExceeded> {
+        let exceeded = |limit, max| SessionLimitExceeded {
+            error: "session_limit_exceeded",
+            session_id: session_id.to_string(),
+            limit,
+            max,
+        };
+
+        let mut counters = self.lock();
+        while counters
+            .recent
+            .front()
+            .is_some_and(|admitted| now.saturating_duration_since(*admitted) >= RATE_WINDOW)
+        {
+            counters.recent.pop_front();
+        }
+
+        let max_concurrent = limits.max_concurrent_tunnels;
+        if max_concurrent > 0 && counters.active >= max_concurrent {
+            return Err(exceeded(
+                SessionLimit::ConcurrentTunnels,
+                max_concurrent.into(),
+            ));
+        }
+        let max_rate = limits.max_new_connections_per_minute;
+        if max_rate > 0 && counters.recent.len() >= max_rate as usize {
+            return Err(exceeded(SessionLimit::ConnectionRate, max_rate.into()));
+        }
+        let max_total = limits.max_total_connections;
+        if max_total > 0 && counters.total >= max_total {
+            return Err(exceeded(SessionLimit::TotalConnections, max_total));
+        }
+
+        counters.active += 1;
+        counters.total += 1;
+        if max_rate > 0 {
+            counters.recent.push_back(now);
+        }
+        Ok(TunnelPermit {
+            limiter: Arc::clone(self),
+        })
+    }
+
+    /// Tunnels currently open.
+    pub(crate) fn active(&self) -> u32 {
+        self.lock().active
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Counters> {
+        self.counters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A session's tunnel slot, released on drop.
+#[derive(Debug)]
+pub(crate) struct TunnelPermit {
+    limiter: Arc<ConnectionLimiter>,
+}
+
+impl Drop for TunnelPermit {
+    fn drop(&mut self) {
+        let mut counters = self.limiter.lock();
+        counters.active = counters.active.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(concurrent: u32, per_minute: u32, total: u64) -> SessionConnectionLimits {
+        SessionConnectionLimits {
+            max_concurrent_tunnels: concurrent,
+            max_new_connections_per_minute: per_minute,
+            max_total_connections: total,
+        }
+    }
+
+    #[test]
+    fn concurrent_tunnels_are_released_on_drop() {
+        let limiter = Arc::new(ConnectionLimiter::default());
+        let limits = limits(2, 0, 0);
+        let now = Instant::now();
+        let first = limiter.admit("sess", &limits, now).unwrap();
+        let _second = limiter.admit("sess", &limits, now).unwrap();
+        let err = limiter.admit("sess", &limits, now).unwrap_err();
+        assert_eq!(err.limit, SessionLimit::ConcurrentTunnels);
+        assert_eq!(err.max, 2);
+
+        drop(first);
+        assert_eq!(limiter.active(), 1);
+        assert!(limiter.admit("sess", &limits, now).is_ok());
+    }
+
+    #[test]
+    fn connection_rate_recovers_after_window() {
+        let limiter = Arc::new(ConnectionLimiter::default());
+        let limits = limits(0, 2, 0);
+        let start = Instant::now();
+        drop(limiter.admit("sess", &limits, start).unwrap());
+        drop(limiter.admit("sess", &limits, start).unwrap());
+        let err = limiter
+            .admit("sess", &limits, start + Duration::from_secs(30))
+            .unwrap_err();
+        assert_eq!(err.limit, SessionLimit::ConnectionRate);
+        assert!(limiter.admit("sess", &limits, start + RATE_WINDOW).is_ok());
+    }
+
+    #[test]
+    fn total_connections_and_structured_error() {
+        let limiter = Arc::new(ConnectionLimiter::default());
+        let limits = limits(0, 0, 1);
+        drop(limiter.admit("sess", &limits, Instant::now()).unwrap());
+        let err = limiter.admit("sess", &limits, Instant::now()).unwrap_err();
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({
+                "error": "session_limit_exceeded",
+                "session_id": "sess",
+                "limit": "total_connections",
+                "max": 1,
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "session sess is at its limit of 1 total connections"
+        );
+    }
+}
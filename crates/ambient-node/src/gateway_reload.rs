@@ -0,0 +1,233 @@
+//! Hot reload of the gateway sessions file
+//!
+//! [`SessionsFileReloader`] re-reads the `--sessions-file` JSON every
+//! `interval` and applies what changed to the running gateway:
+//!
+//! - sessions new to the file, or changed in it, go through
+//!   [`DataPlaneGateway::add_session`], so a changed session is updated in
+//!   place and its live relays keep running
+//! - sessions dropped from the file go through
+//!   [`DataPlaneGateway::revoke_session`]
+//!
+//! The file owns only the sessions it listed: sessions added another way
+//! (such as the admin socket, [`crate::gateway_admin`], or the control-plane
+//! sync, [`crate::gateway_sync`]) are left alone.
+//! Sessions already past their expiry are not re-added.  A file that fails
+//! to read or parse is logged and skipped, keeping the sessions as they are
+//! until the next good read.
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::gateway::{DataPlaneGateway, GatewaySession};
+
+/// What one reload changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionsReload {
+    /// Sessions added or updated, by ID
+    pub upserted: Vec<String>,
+    /// Sessions revoked because the file no longer lists them
+    pub revoked: Vec<String>,
+}
+
+/// Sessions to add or update and session IDs to revoke to go from the
+/// `previous` file contents to `next`.  A session listed twice keeps its
+/// last entry.
+pub(crate) fn diff_sessions(
+    previous: &HashMap<String, GatewaySession>,
+    next: &HashMap<String, GatewaySession>,
+) -> (Vec<GatewaySession>, Vec<String>) {
+    let mut upserts: Vec<GatewaySession> = next
+        .values()
+        .filter(|session| previous.get(&session.session_id) != Some(*session))
+        .cloned()
+        .collect();
+    upserts.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+    let mut removed: Vec<String> = previous
+        .keys()
+        .filter(|session_id| !next.contains_key(*session_id))
+        .cloned()
+        .collect();
+    removed.sort();
+    (upserts, removed)
+}
+
+/// Bring the sessions a source owns from `loaded` to `next` on `gateway`,
+/// skipping sessions already past their expiry, and remember `next` as
+/// loaded.
+pub(crate) async fn apply_sessions(
+    gateway: &DataPlaneGateway,
+    loaded: &mut HashMap<String, GatewaySession>,
+    next: HashMap<String, GatewaySession>,
+) -> SessionsReload {
+    let (upserts, removed) = diff_sessions(loaded, &next);
+    let mut reload = SessionsReload::default();
+    for session in upserts {
+        if gateway.is_expired(&session) {
+            continue;
+        }
+        reload.upserted.push(session.session_id.clone());
+        gateway.add_session(session).await;
+    }
+    for session_id in removed {
+        if gateway.revoke_session(&session_id).await {
+            reload.revoked.push(session_id);
+        }
+    }
+    *loaded = next;
+    reload
+}
+
+/// Keeps a gateway's sessions in step with its sessions file.
+#[derive(Debug)]
+pub struct SessionsFileReloader {
+    gateway: DataPlaneGateway,
+    path: PathBuf,
+    /// The file's contents at the last successful reload
+    contents: Option<String>,
+    /// Sessions the file listed at the last successful reload
+    loaded: HashMap<String, GatewaySession>,
+}
+
+impl SessionsFileReloader {
+    pub fn new(gateway: DataPlaneGateway, path: impl AsRef<Path>) -> Self {
+        Self {
+            gateway,
+            path: path.as_ref().to_path_buf(),
+            contents: None,
+            loaded: HashMap::new(),
+        }
+    }
+
+    /// Read the file and apply what changed since the last reload.  The
+    /// first call provisions every session in the file.
+    pub async fn reload(&mut self) -> Result<SessionsReload> {
+        let contents = tokio::fs::read_to_string(&self.path)
+            .await
+            .context("failed to read gateway sessions file")?;
+        if self.contents.as_deref() == Some(contents.as_str()) {
+            return Ok(SessionsReload::default());
+        }
+        let sessions: Vec<GatewaySession> =
+            serde_json::from_str(&contents).context("failed to parse gateway sessions JSON")?;
+        let next: HashMap<String, GatewaySession> = sessions
+            .into_iter()
+            .map(|session| (session.session_id.clone(), session))
+            .collect();
+
+        let reload = apply_sessions(&self.gateway, &mut self.loaded, next).await;
+        self.contents = Some(contents);
+        Ok(reload)
+    }
+
+    /// Reload every `interval` until the task is dropped.
+    pub async fn run(mut self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match self.reload().await {
+                Ok(reload) if reload == SessionsReload::default() => {}
+                Ok(reload) => info!(
+                    path = %self.path.display(),
+                    upserted = reload.upserted.len(),
+                    revoked = reload.revoked.len(),
+                    "gateway sessions file reloaded"
+                ),
+                Err(err) => warn!(
+                    path = %self.path.display(),
+                    "gateway sessions file not reloaded, keeping current sessions: {err:#}"
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::GatewayConfig;
+
+    fn session(session_id: &str, expires_at_epoch_seconds: u64) -> GatewaySession {
+        GatewaySession {
+            session_id: session_id.to_string(),
+            session_token: "cs_token".to_string(),
+            egress_profile: "allowlist_domains".to_string(),
+            destination_policy_id: "policy_web_basic_v1".to_string(),
+            allowed_destinations: vec!["example.com".to_string()],
+            bandwidth_limit_mbps: None,
+            tunnel_protocol: None,
+            expires_at_epoch_seconds,
+        }
+    }
+
+    fn far_future() -> u64 {
+        chrono::Utc::now().timestamp() as u64 + 3600
+    }
+
+    #[test]
+    fn diff_finds_new_changed_and_dropped_sessions() {
+        let keyed = |sessions: Vec<GatewaySession>| {
+            sessions
+                .into_iter()
+                .map(|s| (s.session_id.clone(), s))
+                .collect::<HashMap<_, _>>()
+        };
+        let previous = keyed(vec![
+            session("a", 100),
+            session("b", 100),
+            session("c", 100),
+        ]);
+        let next = keyed(vec![
+            session("a", 100),
+            session("b", 200),
+            session("d", 100),
+        ]);
+        let (upserts, removed) = diff_sessions(&previous, &next);
+        let upserted: Vec<&str> = upserts.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(upserted, ["b", "d"]);
+        assert_eq!(removed, ["c"]);
+    }
+
+    #[tokio::test]
+    async fn reload_applies_file_changes_to_the_gateway() {
+        let path =
+            std::env::temp_dir().join(format!("gateway-sessions-{}.json", uuid::Uuid::new_v4()));
+        let write = |sessions: Vec<GatewaySession>| {
+            std::fs::write(&path, serde_json::to_string(&sessions).unwrap()).unwrap()
+        };
+        let gateway = DataPlaneGateway::new(GatewayConfig::default(), Vec::new());
+        gateway
+            .add_session(session("from_admin", far_future()))
+            .await;
+        let mut reloader = SessionsFileReloader::new(gateway.clone(), &path);
+
+        write(vec![session("a", far_future()), session("expired", 1)]);
+        let reload = reloader.reload().await.unwrap();
+        assert_eq!(reload.upserted, ["a"]);
+        assert_eq!(reloader.reload().await.unwrap(), SessionsReload::default());
+
+        write(vec![session("b", far_future())]);
+        let reload = reloader.reload().await.unwrap();
+        assert_eq!(reload.upserted, ["b"]);
+        assert_eq!(reload.revoked, ["a"]);
+        let live: Vec<String> = gateway
+            .list_sessions()
+            .await
+            .into_iter()
+            .map(|summary| summary.session_id)
+            .collect();
+        assert_eq!(live, ["b", "from_admin"]);
+
+        // A broken file keeps the sessions the last good one provisioned.
+        std::fs::write(&path, "[{").unwrap();
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(gateway.list_sessions().await.len(), 2);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
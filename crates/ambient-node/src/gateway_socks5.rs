@@ -0,0 +1,223 @@
+//! SOCKS5 front end for the data-plane gateway
+//!
+//! Unmodified applications (curl, browsers, SSH through `ncat --proxy`)
+//! reach a relay through standard SOCKS5 (RFC 1928) instead of the JSON
+//! handshake.  Clients authenticate with username/password (RFC 1929):
+//! the username is the session ID and the password the session token.  Only
+//! `CONNECT` is supported; destinations pass the same session checks as
+//! handshake relays.
+use anyhow::{bail, Context, Result};
+use std::net::{IpAddr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const VERSION: u8 = 0x05;
+const AUTH_VERSION: u8 = 0x01;
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const METHOD_NONE_ACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// `REP` codes of a SOCKS5 reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reply {
+    Succeeded = 0x00,
+    GeneralFailure = 0x01,
+    NotAllowed = 0x02,
+    HostUnreachable = 0x04,
+    ConnectionRefused = 0x05,
+    TtlExpired = 0x06,
+    CommandNotSupported = 0x07,
+    AddressTypeNotSupported = 0x08,
+}
+
+impl Reply {
+    /// Reply for a failed upstream connect.
+    pub(crate) fn for_connect_error(err: &anyhow::Error) -> Self {
+        if err.downcast_ref::<tokio::time::error::Elapsed>().is_some() {
+            return Reply::TtlExpired;
+        }
+        match err.downcast_ref::<std::io::Error>().map(|err| err.kind()) {
+            Some(std::io::ErrorKind::ConnectionRefused) => Reply::ConnectionRefused,
+            Some(_) => Reply::HostUnreachable,
+            None => Reply::GeneralFailure,
+        }
+    }
+}
+
+/// Read the method greeting and the username/password sub-negotiation.
+/// Returns `(session_id, session_token)`; the caller answers with
+/// [`send_auth_status`] once it has checked them.
+pub(crate) async fn read_credentials<S>(stream: &mut S) -> Result<(String, String)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != VERSION {
+        bail!("unsupported SOCKS version {}", header[0]);
+    }
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&METHOD_USERNAME_PASSWORD) {
+        stream.write_all(&[VERSION, METHOD_NONE_ACCEPTABLE]).await?;
+        bail!("SOCKS5 client does not offer username/password authentication");
+    }
+    stream
+        .write_all(&[VERSION, METHOD_USERNAME_PASSWORD])
+        .await?;
+
+    let mut version = [0u8; 1];
+    stream.read_exact(&mut version).await?;
+    if version[0] != AUTH_VERSION {
+        bail!("unsupported SOCKS5 authentication version {}", version[0]);
+    }
+    let session_id = read_short_string(stream)
+        .await
+        .context("invalid username")?;
+    let session_token = read_short_string(stream)
+        .await
+        .context("invalid password")?;
+    Ok((session_id, session_token))
+}
+
+/// Answer the username/password sub-negotiation.
+pub(crate) async fn send_auth_status<S>(stream: &mut S, accepted: bool) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let status = if accepted { 0x00 } else { 0x01 };
+    stream.write_all(&[AUTH_VERSION, status]).await?;
+    Ok(())
+}
+
+/// Read the client's request.  `Ok(Err(reply))` is a well-formed request
+/// the gateway refuses with `reply`.
+pub(crate) async fn read_request<S>(stream: &mut S) -> Result<std::result::Result<String, Reply>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let [version, command, _reserved, address_type] = header;
+    if version != VERSION {
+        bail!("unsupported SOCKS version {version}");
+    }
+
+    let host = match address_type {
+        ATYP_IPV4 => {
+            let mut octets = [0u8; 4];
+            stream.read_exact(&mut octets).await?;
+            IpAddr::from(octets).to_string()
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0u8; 16];
+            stream.read_exact(&mut octets).await?;
+            format!("[{}]", IpAddr::from(octets))
+        }
+        ATYP_DOMAIN => read_short_string(stream)
+            .await
+            .context("invalid destination domain")?,
+        _ => return Ok(Err(Reply::AddressTypeNotSupported)),
+    };
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port).await?;
+
+    if command != CMD_CONNECT {
+        return Ok(Err(Reply::CommandNotSupported));
+    }
+    Ok(Ok(format!("{host}:{}", u16::from_be_bytes(port))))
+}
+
+/// Send the reply to a request, with the upstream socket's local address
+/// on success.
+pub(crate) async fn send_reply<S>(
+    stream: &mut S,
+    reply: Reply,
+    bound: Option<SocketAddr>,
+) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let bound = bound.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
+    let mut message = vec![VERSION, reply as u8, 0x00];
+    match bound.ip() {
+        IpAddr::V4(ip) => {
+            message.push(ATYP_IPV4);
+            message.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            message.push(ATYP_IPV6);
+            message.extend_from_slice(&ip.octets());
+        }
+    }
+    message.extend_from_slice(&bound.port().to_be_bytes());
+    stream.write_all(&message).await?;
+    Ok(())
+}
+
+/// A length-prefixed string of at most 255 bytes.
+async fn read_short_string<S>(stream: &mut S) -> Result<String>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut len = [0u8; 1];
+    stream.read_exact(&mut len).await?;
+    let mut bytes = vec![0u8; len[0] as usize];
+    stream.read_exact(&mut bytes).await?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_credentials_and_connect_requests() {
+        let (mut client, mut server) = tokio::io::duplex(512);
+        let mut message = vec![VERSION, 2, 0x00, METHOD_USERNAME_PASSWORD, AUTH_VERSION, 6];
+        message.extend_from_slice(b"sess_1");
+        message.push(5);
+        message.extend_from_slice(b"token");
+        message.extend_from_slice(&[VERSION, CMD_CONNECT, 0, ATYP_DOMAIN, 11]);
+        message.extend_from_slice(b"example.com");
+        message.extend_from_slice(&443u16.to_be_bytes());
+        message.extend_from_slice(&[VERSION, CMD_CONNECT, 0, ATYP_IPV6]);
+        message.extend_from_slice(&"::1".parse::<std::net::Ipv6Addr>().unwrap().octets());
+        message.extend_from_slice(&22u16.to_be_bytes());
+        message.extend_from_slice(&[VERSION, 0x03, 0, ATYP_IPV4, 127, 0, 0, 1, 0, 53]);
+        client.write_all(&message).await.unwrap();
+
+        let credentials = read_credentials(&mut server).await.unwrap();
+        assert_eq!(credentials, ("sess_1".to_string(), "token".to_string()));
+        assert_eq!(
+            read_request(&mut server).await.unwrap(),
+            Ok("example.com:443".to_string())
+        );
+        assert_eq!(
+            read_request(&mut server).await.unwrap(),
+            Ok("[::1]:22".to_string())
+        );
+        // UDP ASSOCIATE is refused.
+        assert_eq!(
+            read_request(&mut server).await.unwrap(),
+            Err(Reply::CommandNotSupported)
+        );
+
+        let mut selected = [0u8; 2];
+        client.read_exact(&mut selected).await.unwrap();
+        assert_eq!(selected, [VERSION, METHOD_USERNAME_PASSWORD]);
+    }
+
+    #[tokio::test]
+    async fn refuses_clients_without_password_authentication() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(&[VERSION, 1, 0x00]).await.unwrap();
+
+        assert!(read_credentials(&mut server).await.is_err());
+        let mut selected = [0u8; 2];
+        client.read_exact(&mut selected).await.unwrap();
+        assert_eq!(selected, [VERSION, METHOD_NONE_ACCEPTABLE]);
+    }
+}
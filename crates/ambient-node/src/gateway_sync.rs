@@ -0,0 +1,296 @@
+//! Gateway session sync with the control plane
+//!
+//! [`GatewaySessionSyncer`] polls `GET /api/v1/nodes/{id}/gateway-sessions`
+//! every `interval` with the node owner's credential and reconciles the
+//! running gateway with the answer, as the reload of the sessions file does
+//! ([`crate::gateway_reload`]):
+//!
+//! - sessions new to the list, or changed in it, go through
+//!   [`DataPlaneGateway::add_session`]
+//! - sessions dropped from the list go through
+//!   [`DataPlaneGateway::revoke_session`]
+//!
+//! With a control public key, every response must pass
+//! [`ControlVerifier::verify`] on the gateway's coordinator clock, so a
+//! forged or replayed list is rejected.  The syncer owns only the sessions
+//! the control plane listed; sessions from the file or the admin socket are
+//! left alone.  A failed poll is logged and skipped, keeping the sessions as
+//! they are until the next good answer.  Polls run under the `control`
+//! request budget ([`crate::request_budget`]), so a control plane that keeps
+//! failing trips the breaker instead of being hammered every interval.
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{collections::HashMap, time::Duration};
+use tracing::{info, warn};
+
+use crate::control_signature::{ControlVerifier, CONTROL_SIGNATURE_FIELD};
+use crate::gateway::{DataPlaneGateway, GatewaySession};
+use crate::gateway_reload::{apply_sessions, SessionsReload};
+use crate::request_budget::{BudgetedClient, EndpointClass, RequestBudgets};
+
+/// Default seconds between polls.
+pub const DEFAULT_SESSION_SYNC_SECS: u64 = 10;
+
+/// Where and as whom to reach the control plane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewaySessionSyncConfig {
+    /// API base URL, without `/api/v1`
+    pub api_url: String,
+    pub node_id: String,
+    /// API key (`vcp_...`) or access token of the node's owner.  A
+    /// long-running gateway should use an API key, which does not expire.
+    pub token: String,
+    /// Base64 control public key; when set, unsigned or forged responses
+    /// are rejected
+    pub control_public_key: Option<String>,
+    /// Budgets the polls run under; see [`crate::DeploymentSettings::requests`]
+    pub requests: RequestBudgets,
+}
+
+#[derive(Debug, Deserialize)]
+struct GatewaySessionsBody {
+    sessions: Vec<GatewaySession>,
+}
+
+/// Authenticate with an API key (`vcp_...`) or an access token.
+pub(crate) fn authorize(request: reqwest::RequestBuilder, token: &str) -> reqwest::RequestBuilder {
+    if token.starts_with("vcp_") {
+        request.header("X-API-Key", token)
+    } else {
+        request.bearer_auth(token)
+    }
+}
+
+/// Keeps a gateway's sessions in step with the control plane.
+#[derive(Debug)]
+pub struct GatewaySessionSyncer {
+    gateway: DataPlaneGateway,
+    config: GatewaySessionSyncConfig,
+    client: BudgetedClient,
+    verifier: Option<ControlVerifier>,
+    /// Sessions the control plane listed at the last successful poll
+    loaded: HashMap<String, GatewaySession>,
+}
+
+impl GatewaySessionSyncer {
+    pub fn new(gateway: DataPlaneGateway, config: GatewaySessionSyncConfig) -> Result<Self> {
+        let verifier = config
+            .control_public_key
+            .as_deref()
+            .map(|key| ControlVerifier::new(config.node_id.clone(), key))
+            .transpose()?;
+        let client = BudgetedClient::new(config.requests)
+            .context("failed to build gateway session sync client")?;
+        Ok(Self {
+            gateway,
+            config,
+            client,
+            verifier,
+            loaded: HashMap::new(),
+        })
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "{}/api/v1/nodes/{}/gateway-sessions",
+            self.config.api_url.trim_end_matches('/'),
+            self.config.node_id
+        )
+    }
+
+    /// Fetch the node's sessions and apply what changed since the last
+    /// poll.  The first call provisions every session listed.
+    pub async fn sync(&mut self) -> Result<SessionsReload> {
+        let request = authorize(self.client.client().get(self.url()), &self.config.token);
+        let response = self
+            .client
+            .send(EndpointClass::Control, "gateway-sessions", request)
+            .await
+            .context("failed to reach the control plane")?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!("control plane answered {status}");
+        }
+        let mut body: serde_json::Value = response
+            .json()
+            .await
+            .context("failed to read gateway sessions response")?;
+
+        body = match &mut self.verifier {
+            Some(verifier) => verifier
+                .verify(body, self.gateway.coordinator_now_ms())
+                .context("gateway sessions response rejected")?,
+            None => {
+                if let Some(object) = body.as_object_mut() {
+                    object.remove(CONTROL_SIGNATURE_FIELD);
+                }
+                body
+            }
+        };
+        let body: GatewaySessionsBody =
+            serde_json::from_value(body).context("failed to parse gateway sessions response")?;
+        let next = body
+            .sessions
+            .into_iter()
+            .map(|session| (session.session_id.clone(), session))
+            .collect();
+        Ok(apply_sessions(&self.gateway, &mut self.loaded, next).await)
+    }
+
+    /// Poll every `interval` until the task is dropped.
+    pub async fn run(mut self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match self.sync().await {
+                Ok(reload) if reload == SessionsReload::default() => {}
+                Ok(reload) => info!(
+                    node_id = %self.config.node_id,
+                    upserted = reload.upserted.len(),
+                    revoked = reload.revoked.len(),
+                    "gateway sessions synced from the control plane"
+                ),
+                Err(err) => warn!(
+                    node_id = %self.config.node_id,
+                    "gateway sessions not synced, keeping current sessions: {err:#}"
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_signature::ControlSigner;
+    use crate::gateway::GatewayConfig;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn session(session_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "session_id": session_id,
+            "session_token": "cs_token",
+            "egress_profile": "allowlist_domains",
+            "destination_policy_id": "policy_web_basic_v1",
+            "allowed_destinations": ["example.com"],
+            "expires_at_epoch_seconds": chrono::Utc::now().timestamp() as u64 + 3600,
+        })
+    }
+
+    /// Answer each request with the next body, recording request heads.
+    async fn serve(bodies: Vec<serde_json::Value>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let heads = Arc::new(Mutex::new(Vec::new()));
+        let seen = heads.clone();
+        tokio::spawn(async move {
+            for body in bodies {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut head = Vec::new();
+                let mut byte = [0u8; 1];
+                while !head.ends_with(b"\r\n\r\n") {
+                    stream.read_exact(&mut byte).await.unwrap();
+                    head.push(byte[0]);
+                }
+                seen.lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(&head).to_lowercase());
+                let body = body.to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, heads)
+    }
+
+    async fn live(gateway: &DataPlaneGateway) -> Vec<String> {
+        gateway
+            .list_sessions()
+            .await
+            .into_iter()
+            .map(|summary| summary.session_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn sync_reconciles_signed_session_lists() {
+        let signer = ControlSigner::generate().unwrap();
+        let now = chrono::Utc::now().timestamp_millis();
+        let signed = |sessions: Vec<serde_json::Value>, node_id: &str, at_ms: i64| {
+            signer.sign(node_id, serde_json::json!({ "sessions": sessions }), at_ms)
+        };
+        let first = signed(vec![session("a"), session("b")], "node-1", now);
+        let second = signed(vec![session("b"), session("c")], "node-1", now);
+        let replayed = first.clone();
+        let forged = signed(vec![], "node-2", now);
+        let (url, heads) = serve(vec![first, second, replayed, forged]).await;
+
+        let gateway = DataPlaneGateway::new(GatewayConfig::default(), Vec::new());
+        gateway
+            .add_session(serde_json::from_value(session("from_admin")).unwrap())
+            .await;
+        let mut syncer = GatewaySessionSyncer::new(
+            gateway.clone(),
+            GatewaySessionSyncConfig {
+                api_url: format!("{url}/"),
+                node_id: "node-1".to_string(),
+                token: "vcp_key".to_string(),
+                control_public_key: Some(signer.public_key_b64()),
+                requests: RequestBudgets::default(),
+            },
+        )
+        .unwrap();
+
+        let reload = syncer.sync().await.unwrap();
+        assert_eq!(reload.upserted, ["a", "b"]);
+        let reload = syncer.sync().await.unwrap();
+        assert_eq!(reload.upserted, ["c"]);
+        assert_eq!(reload.revoked, ["a"]);
+        assert_eq!(live(&gateway).await, ["b", "c", "from_admin"]);
+
+        // A replayed or misaddressed list changes nothing.
+        assert!(syncer.sync().await.is_err());
+        assert!(syncer.sync().await.is_err());
+        assert_eq!(live(&gateway).await, ["b", "c", "from_admin"]);
+
+        let heads = heads.lock().unwrap();
+        assert!(heads[0].starts_with("get /api/v1/nodes/node-1/gateway-sessions "));
+        assert!(heads[0].contains("x-api-key: vcp_key"));
+    }
+
+    #[tokio::test]
+    async fn unverified_sync_accepts_unsigned_lists() {
+        let (url, heads) = serve(vec![serde_json::json!({ "sessions": [session("a")] })]).await;
+        let gateway = DataPlaneGateway::new(GatewayConfig::default(), Vec::new());
+        let mut syncer = GatewaySessionSyncer::new(
+            gateway.clone(),
+            GatewaySessionSyncConfig {
+                api_url: url,
+                node_id: "node-1".to_string(),
+                token: "jwt".to_string(),
+                control_public_key: None,
+                requests: RequestBudgets::default(),
+            },
+        )
+        .unwrap();
+        assert_eq!(syncer.sync().await.unwrap().upserted, ["a"]);
+        assert!(heads.lock().unwrap()[0].contains("authorization: bearer jwt"));
+        assert!(GatewaySessionSyncer::new(
+            gateway,
+            GatewaySessionSyncConfig {
+                api_url: String::new(),
+                node_id: "node-1".to_string(),
+                token: String::new(),
+                control_public_key: Some("not base64!".to_string()),
+                requests: RequestBudgets::default(),
+            },
+        )
+        .is_err());
+    }
+}
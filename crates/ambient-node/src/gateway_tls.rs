@@ -0,0 +1,223 @@
+//! TLS for the data-plane gateway listener
+//!
+//! With a node certificate configured the gateway runs TLS 1.3 before the
+//! JSON handshake.  Sessions whose `tunnel_protocol` is `mtls` must also
+//! present a client certificate whose key is derived from the session ID and
+//! token: the endpoint and the gateway both hold those, so each derives the
+//! same Ed25519 key and the certificate needs no CA.
+use anyhow::{anyhow, bail, Context, Result};
+use hkdf::Hkdf;
+use rcgen::{CertificateParams, DnType, KeyPair, PKCS_ED25519};
+use rustls::{
+    client::danger::HandshakeSignatureValid,
+    crypto::{CryptoProvider, WebPkiSupportedAlgorithms},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, UnixTime},
+    server::danger::{ClientCertVerified, ClientCertVerifier},
+    CertificateError, ClientConfig, DigitallySignedStruct, DistinguishedName, RootCertStore,
+    ServerConfig, SignatureScheme,
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{path::PathBuf, sync::Arc};
+use tokio_rustls::TlsAcceptor;
+use zeroize::Zeroizing;
+
+use crate::gateway::GatewaySession;
+
+/// Domain separation for the session client key derivation.
+const CLIENT_KEY_KDF_INFO: &[u8] = b"ambient-vcp gateway client key v1";
+
+/// PKCS#8 v1 encoding of an Ed25519 key, up to the 32-byte seed (RFC 8410).
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// Node certificate the gateway listener presents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayTlsConfig {
+    /// PEM certificate chain, leaf first
+    pub cert_path: PathBuf,
+    /// PEM private key of the leaf certificate
+    pub key_path: PathBuf,
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// The Ed25519 key both ends derive for a session.
+fn session_key_pair(session_id: &str, session_token: &str) -> Result<KeyPair> {
+    let hkdf = Hkdf::<Sha256>::new(Some(session_id.as_bytes()), session_token.as_bytes());
+    let mut seed = Zeroizing::new([0u8; 32]);
+    hkdf.expand(CLIENT_KEY_KDF_INFO, seed.as_mut())
+        .map_err(|_| anyhow!("client key derivation failed"))?;
+    let mut pkcs8 = Zeroizing::new(ED25519_PKCS8_PREFIX.to_vec());
+    pkcs8.extend_from_slice(seed.as_ref());
+    KeyPair::from_pkcs8_der_and_sign_algo(
+        &PrivatePkcs8KeyDer::from(pkcs8.as_slice()),
+        &PKCS_ED25519,
+    )
+    .context("invalid derived client key")
+}
+
+/// Self-signed certificate and key an endpoint presents for an `mtls`
+/// session.
+pub fn session_client_identity(
+    session_id: &str,
+    session_token: &str,
+) -> Result<(CertificateDer<'static>, PrivateKeyDer<'static>)> {
+    let key_pair = session_key_pair(session_id, session_token)?;
+    let mut params = CertificateParams::new(Vec::<String>::new())?;
+    params
+        .distinguished_name
+        .push(DnType::CommonName, session_id);
+    let cert = params.self_signed(&key_pair)?;
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der()));
+    Ok((cert.der().clone(), key))
+}
+
+/// Client configuration for an endpoint connecting to a TLS gateway: checks
+/// the node certificate against `roots` and presents the session's client
+/// certificate.
+pub fn session_client_config(
+    session_id: &str,
+    session_token: &str,
+    roots: RootCertStore,
+) -> Result<ClientConfig> {
+    let (cert, key) = session_client_identity(session_id, session_token)?;
+    Ok(ClientConfig::builder_with_provider(crypto_provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_root_certificates(roots)
+        .with_client_auth_cert(vec![cert], key)?)
+}
+
+/// Build the listener's acceptor from the node certificate.
+pub(crate) fn acceptor(config: &GatewayTlsConfig) -> Result<TlsAcceptor> {
+    let certs = CertificateDer::pem_file_iter(&config.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| {
+            format!(
+                "failed to read gateway certificate {}",
+                config.cert_path.display()
+            )
+        })?;
+    let key = PrivateKeyDer::from_pem_file(&config.key_path).with_context(|| {
+        format!(
+            "failed to read gateway private key {}",
+            config.key_path.display()
+        )
+    })?;
+
+    let provider = crypto_provider();
+    let verifier = Arc::new(SessionCertVerifier {
+        algorithms: provider.signature_verification_algorithms,
+    });
+    let server = ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)
+        .context("gateway certificate and key do not match")?;
+    Ok(TlsAcceptor::from(Arc::new(server)))
+}
+
+/// Check that the client certificate carries the key derived from
+/// `session`.
+pub(crate) fn verify_session_client_cert(
+    session: &GatewaySession,
+    client_cert: Option<&CertificateDer<'_>>,
+) -> Result<()> {
+    let cert = client_cert.context("session requires a client certificate")?;
+    let presented = webpki::EndEntityCert::try_from(cert)
+        .map_err(|err| anyhow!("invalid client certificate: {err}"))?
+        .subject_public_key_info();
+    let expected = session_key_pair(&session.session_id, &session.session_token)?.public_key_der();
+    if presented.as_ref() != expected.as_slice() {
+        bail!("client certificate was not derived from this session");
+    }
+    Ok(())
+}
+
+/// Accepts any well-formed client certificate whose owner proves it holds
+/// the key.  Which session it must belong to is only known once the
+/// handshake line arrives, so the gateway checks the binding afterwards;
+/// clients without a certificate are let through for non-`mtls` sessions.
+#[derive(Debug)]
+struct SessionCertVerifier {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ClientCertVerifier for SessionCertVerifier {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        webpki::EndEntityCert::try_from(end_entity)
+            .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(session_id: &str, session_token: &str) -> GatewaySession {
+        GatewaySession {
+            session_id: session_id.to_string(),
+            session_token: session_token.to_string(),
+            egress_profile: "allowlist_domains".to_string(),
+            destination_policy_id: "policy_web_basic_v1".to_string(),
+            allowed_destinations: vec!["127.0.0.1".to_string()],
+            bandwidth_limit_mbps: None,
+            tunnel_protocol: Some("mtls".to_string()),
+            expires_at_epoch_seconds: u64::MAX,
+        }
+    }
+
+    #[test]
+    fn client_certificate_is_bound_to_its_session() {
+        let (cert, _) = session_client_identity("sess_a", "cs_token_a").unwrap();
+        // Certificates are re-issued per connection; only the key must match.
+        let (reissued, _) = session_client_identity("sess_a", "cs_token_a").unwrap();
+
+        assert!(verify_session_client_cert(&session("sess_a", "cs_token_a"), Some(&cert)).is_ok());
+        assert!(
+            verify_session_client_cert(&session("sess_a", "cs_token_a"), Some(&reissued)).is_ok()
+        );
+        assert!(verify_session_client_cert(&session("sess_a", "cs_token_b"), Some(&cert)).is_err());
+        assert!(verify_session_client_cert(&session("sess_b", "cs_token_a"), Some(&cert)).is_err());
+        assert!(verify_session_client_cert(&session("sess_a", "cs_token_a"), None).is_err());
+    }
+}
@@ -0,0 +1,601 @@
+//! Transparent TCP intercept for relay nodes
+//!
+//! Endpoints behind a relay node (on its hotspot or LAN interface) get
+//! their outbound TCP connections redirected to the gateway by the node's
+//! firewall, so they need no proxy settings at all.  The redirect is an
+//! iptables or nftables `REDIRECT` in the nat prerouting hook; the gateway
+//! reads the destination the endpoint actually dialled back from the
+//! connection with `SO_ORIGINAL_DST`.
+//!
+//! Intercepted connections carry no credentials.  Each [`TransparentBinding`]
+//! maps a source address range to the session its traffic relays under;
+//! the most specific range wins and unbound sources are dropped.  The
+//! recovered destination is an IP and port, and the session's destination
+//! policy is enforced on exactly that: `allowlist_domains` sessions must
+//! list the IPs, and `protocol_limited` sessions, which require host names,
+//! refuse every intercepted connection.  `mtls` sessions are only relayed
+//! over the TLS listener.
+//!
+//! With `install_rules`, the gateway installs the redirect rules (see
+//! [`RedirectRules`]) when it starts; otherwise the operator adds the
+//! equivalent rules from [`TransparentConfig::install_commands`].  Traffic
+//! to the node's own addresses is never redirected.
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::process::Command;
+use std::str::FromStr;
+use tracing::{debug, info};
+
+/// iptables chain (and nftables table) holding the redirect rules.
+pub const TRANSPARENT_RULES_NAME: &str = "AMBIENT_TRANSPARENT";
+
+/// Firewall the redirect rules are written for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallBackend {
+    /// `iptables` for IPv4 sources, `ip6tables` for IPv6 sources
+    #[default]
+    Iptables,
+    /// One `inet` table covering both families
+    Nftables,
+}
+
+impl FromStr for FirewallBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "iptables" => Ok(Self::Iptables),
+            "nftables" => Ok(Self::Nftables),
+            other => bail!("unknown firewall {other:?} (expected iptables or nftables)"),
+        }
+    }
+}
+
+/// Endpoints in `source` relay under `session_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransparentBinding {
+    /// Source address or CIDR range, e.g. `192.168.50.0/24`
+    pub source: String,
+    pub session_id: String,
+}
+
+/// Parses `SOURCE=SESSION_ID`.
+impl FromStr for TransparentBinding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (source, session_id) = s
+            .split_once('=')
+            .context("transparent binding must be SOURCE=SESSION_ID")?;
+        parse_cidr(source)?;
+        Ok(Self {
+            source: source.trim().to_string(),
+            session_id: session_id.trim().to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransparentConfig {
+    /// Address the redirected connections arrive on
+    pub listen_addr: String,
+    /// Interface the endpoints sit behind; only its traffic is redirected
+    pub interface: String,
+    pub bindings: Vec<TransparentBinding>,
+    #[serde(default)]
+    pub firewall: FirewallBackend,
+    /// Install the redirect rules when the gateway starts
+    #[serde(default)]
+    pub install_rules: bool,
+}
+
+impl Default for TransparentConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: "0.0.0.0:7070".to_string(),
+            interface: "wlan0".to_string(),
+            bindings: Vec::new(),
+            firewall: FirewallBackend::default(),
+            install_rules: false,
+        }
+    }
+}
+
+impl TransparentConfig {
+    /// Check the listen address, interface name and binding ranges.
+    pub fn validate(&self) -> Result<()> {
+        self.listen_port()?;
+        let valid_interface = !self.interface.is_empty()
+            && self.interface.len() <= 15
+            && self
+                .interface
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_interface {
+            bail!(
+                "invalid transparent intercept interface {:?}",
+                self.interface
+            );
+        }
+        for binding in &self.bindings {
+            parse_cidr(&binding.source)?;
+            if binding.session_id.is_empty() {
+                bail!(
+                    "transparent binding for {} has no session_id",
+                    binding.source
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn listen_port(&self) -> Result<u16> {
+        self.listen_addr
+            .parse::<SocketAddr>()
+            .map(|addr| addr.port())
+            .with_context(|| format!("invalid transparent listen address {}", self.listen_addr))
+    }
+
+    /// Session of the most specific binding covering `peer`.
+    pub fn session_for(&self, peer: IpAddr) -> Option<&str> {
+        let peer = peer.to_canonical();
+        self.bindings
+            .iter()
+            .filter_map(|binding| {
+                let (net, len) = parse_cidr(&binding.source).ok()?;
+                cidr_contains(net, len, peer).then_some((len, binding.session_id.as_str()))
+            })
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, session_id)| session_id)
+    }
+
+    /// Commands that install the redirect rules, in order.
+    pub fn install_commands(&self) -> Result<Vec<Vec<String>>> {
+        self.validate()?;
+        let port = self.listen_port()?.to_string();
+        let iface = self.interface.as_str();
+        let chain = TRANSPARENT_RULES_NAME;
+        let sources: Vec<(IpAddr, u8)> = self
+            .bindings
+            .iter()
+            .map(|binding| parse_cidr(&binding.source))
+            .collect::<Result<_>>()?;
+
+        let mut commands = Vec::new();
+        match self.firewall {
+            FirewallBackend::Iptables => {
+                for tool in ["iptables", "ip6tables"] {
+                    let family: Vec<_> = sources
+                        .iter()
+                        .filter(|(net, _)| net.is_ipv6() == (tool == "ip6tables"))
+                        .collect();
+                    if family.is_empty() {
+                        continue;
+                    }
+                    commands.push(args(&[tool, "-t", "nat", "-N", chain]));
+                    commands.push(args(&[
+                        tool,
+                        "-t",
+                        "nat",
+                        "-A",
+                        chain,
+                        "-m",
+                        "addrtype",
+                        "--dst-type",
+                        "LOCAL",
+                        "-j",
+                        "RETURN",
+                    ]));
+                    for (net, len) in family {
+                        let source = format!("{net}/{len}");
+                        commands.push(args(&[
+                            tool,
+                            "-t",
+                            "nat",
+                            "-A",
+                            chain,
+                            "-s",
+                            &source,
+                            "-p",
+                            "tcp",
+                            "-j",
+                            "REDIRECT",
+                            "--to-ports",
+                            &port,
+                        ]));
+                    }
+                    commands.push(args(&[
+                        tool,
+                        "-t",
+                        "nat",
+                        "-A",
+                        "PREROUTING",
+                        "-i",
+                        iface,
+                        "-p",
+                        "tcp",
+                        "-j",
+                        chain,
+                    ]));
+                }
+            }
+            FirewallBackend::Nftables => {
+                let table = chain.to_lowercase();
+                commands.push(args(&["nft", "add", "table", "inet", &table]));
+                commands.push(args(&[
+                    "nft",
+                    "add",
+                    "chain",
+                    "inet",
+                    &table,
+                    "prerouting",
+                    "{",
+                    "type",
+                    "nat",
+                    "hook",
+                    "prerouting",
+                    "priority",
+                    "dstnat",
+                    ";",
+                    "}",
+                ]));
+                commands.push(args(&[
+                    "nft",
+                    "add",
+                    "rule",
+                    "inet",
+                    &table,
+                    "prerouting",
+                    "fib",
+                    "daddr",
+                    "type",
+                    "local",
+                    "return",
+                ]));
+                for (net, len) in sources {
+                    let family = if net.is_ipv6() { "ip6" } else { "ip" };
+                    let source = format!("{net}/{len}");
+                    let to_port = format!(":{port}");
+                    commands.push(args(&[
+                        "nft",
+                        "add",
+                        "rule",
+                        "inet",
+                        &table,
+                        "prerouting",
+                        "iifname",
+                        iface,
+                        family,
+                        "saddr",
+                        &source,
+                        "meta",
+                        "l4proto",
+                        "tcp",
+                        "redirect",
+                        "to",
+                        &to_port,
+                    ]));
+                }
+            }
+        }
+        Ok(commands)
+    }
+
+    /// Commands that remove whatever [`Self::install_commands`] installed.
+    /// They fail harmlessly when the rules are not there.
+    pub fn remove_commands(&self) -> Vec<Vec<String>> {
+        let chain = TRANSPARENT_RULES_NAME;
+        let iface = self.interface.as_str();
+        match self.firewall {
+            FirewallBackend::Iptables => ["iptables", "ip6tables"]
+                .into_iter()
+                .flat_map(|tool| {
+                    [
+                        args(&[
+                            tool,
+                            "-t",
+                            "nat",
+                            "-D",
+                            "PREROUTING",
+                            "-i",
+                            iface,
+                            "-p",
+                            "tcp",
+                            "-j",
+                            chain,
+                        ]),
+                        args(&[tool, "-t", "nat", "-F", chain]),
+                        args(&[tool, "-t", "nat", "-X", chain]),
+                    ]
+                })
+                .collect(),
+            FirewallBackend::Nftables => vec![args(&[
+                "nft",
+                "delete",
+                "table",
+                "inet",
+                &chain.to_lowercase(),
+            ])],
+        }
+    }
+}
+
+/// Installs and removes the redirect rules of a [`TransparentConfig`].
+#[derive(Debug, Clone)]
+pub struct RedirectRules {
+    config: TransparentConfig,
+    /// `false` logs the commands instead of running them
+    execute_commands: bool,
+}
+
+impl RedirectRules {
+    pub fn new(config: TransparentConfig, execute_commands: bool) -> Self {
+        Self {
+            config,
+            execute_commands,
+        }
+    }
+
+    /// Replace any rules left from an earlier run with fresh ones.
+    pub fn install(&self) -> Result<()> {
+        let commands = self.config.install_commands()?;
+        self.remove();
+        for command in &commands {
+            self.execute(command)?;
+        }
+        info!(
+            interface = %self.config.interface,
+            listen_addr = %self.config.listen_addr,
+            firewall = ?self.config.firewall,
+            "transparent intercept rules installed"
+        );
+        Ok(())
+    }
+
+    /// Remove the rules; missing rules are not an error.
+    pub fn remove(&self) {
+        for command in self.config.remove_commands() {
+            if let Err(err) = self.execute(&command) {
+                debug!("transparent intercept rule removal skipped: {err:#}");
+            }
+        }
+    }
+
+    fn execute(&self, command: &[String]) -> Result<()> {
+        if !self.execute_commands {
+            debug!(command = ?command, "Skipping command execution (dry run)");
+            return Ok(());
+        }
+        let output = Command::new(&command[0])
+            .args(&command[1..])
+            .output()
+            .with_context(|| format!("failed to run {}", command[0]))?;
+        if !output.status.success() {
+            bail!(
+                "`{}` failed: {}",
+                command.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Where a redirected connection was headed before the firewall sent it
+/// to the gateway.
+#[cfg(target_os = "linux")]
+pub fn original_destination(stream: &tokio::net::TcpStream) -> std::io::Result<SocketAddr> {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+    use std::os::fd::AsRawFd;
+
+    fn getsockopt<T>(fd: libc::c_int, level: libc::c_int, value: &mut T) -> std::io::Result<()> {
+        let mut len = std::mem::size_of::<T>() as libc::socklen_t;
+        // SAFETY: `value` is a valid, writable `T` of `len` bytes for the
+        // duration of the call.
+        let rc = unsafe {
+            libc::getsockopt(
+                fd,
+                level,
+                libc::SO_ORIGINAL_DST,
+                value as *mut T as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if rc == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+
+    let fd = stream.as_raw_fd();
+    let local = stream.local_addr()?;
+    // IPv4 clients of a dual-stack listener are tracked as IPv4.
+    if local.ip().to_canonical().is_ipv4() {
+        // SAFETY: all-zero bytes are a valid `sockaddr_in`.
+        let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        getsockopt(fd, libc::SOL_IP, &mut addr)?;
+        Ok(SocketAddr::V4(SocketAddrV4::new(
+            Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+            u16::from_be(addr.sin_port),
+        )))
+    } else {
+        // SAFETY: all-zero bytes are a valid `sockaddr_in6`.
+        let mut addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+        getsockopt(fd, libc::SOL_IPV6, &mut addr)?;
+        Ok(SocketAddr::V6(SocketAddrV6::new(
+            Ipv6Addr::from(addr.sin6_addr.s6_addr),
+            u16::from_be(addr.sin6_port),
+            0,
+            0,
+        )))
+    }
+}
+
+/// Where a redirected connection was headed; only Linux can tell.
+#[cfg(not(target_os = "linux"))]
+pub fn original_destination(_stream: &tokio::net::TcpStream) -> std::io::Result<SocketAddr> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "transparent intercept needs Linux SO_ORIGINAL_DST",
+    ))
+}
+
+fn args(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|part| part.to_string()).collect()
+}
+
+/// Parse `ip` or `ip/len` into the network address and prefix length.
+fn parse_cidr(source: &str) -> Result<(IpAddr, u8)> {
+    let (ip, len) = match source.split_once('/') {
+        Some((ip, len)) => (ip, Some(len)),
+        None => (source, None),
+    };
+    let ip: IpAddr = ip
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid transparent binding source {source:?}"))?;
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    let len = match len {
+        Some(len) => len
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|len| *len <= max)
+            .with_context(|| format!("invalid transparent binding source {source:?}"))?,
+        None => max,
+    };
+    Ok((mask(ip, len), len))
+}
+
+fn mask(ip: IpAddr, len: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let bits = u32::MAX.checked_shl(32 - u32::from(len)).unwrap_or(0);
+            IpAddr::V4((u32::from(ip) & bits).into())
+        }
+        IpAddr::V6(ip) => {
+            let bits = u128::MAX.checked_shl(128 - u32::from(len)).unwrap_or(0);
+            IpAddr::V6((u128::from(ip) & bits).into())
+        }
+    }
+}
+
+fn cidr_contains(net: IpAddr, len: u8, ip: IpAddr) -> bool {
+    net.is_ipv4() == ip.is_ipv4() && mask(ip, len) == net
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(firewall: FirewallBackend) -> TransparentConfig {
+        TransparentConfig {
+            listen_addr: "0.0.0.0:7070".to_string(),
+            interface: "wlan0".to_string(),
+            bindings: vec![
+                TransparentBinding {
+                    source: "192.168.50.0/24".to_string(),
+                    session_id: "cs_lan".to_string(),
+                },
+                TransparentBinding {
+                    source: "192.168.50.20".to_string(),
+                    session_id: "cs_kiosk".to_string(),
+                },
+                TransparentBinding {
+                    source: "fd00:50::/64".to_string(),
+                    session_id: "cs_v6".to_string(),
+                },
+            ],
+            firewall,
+            install_rules: false,
+        }
+    }
+
+    #[test]
+    fn most_specific_binding_picks_the_session() {
+        let config = config(FirewallBackend::Iptables);
+        let session = |ip: &str| config.session_for(ip.parse().unwrap());
+        assert_eq!(session("192.168.50.7"), Some("cs_lan"));
+        assert_eq!(session("192.168.50.20"), Some("cs_kiosk"));
+        assert_eq!(session("::ffff:192.168.50.7"), Some("cs_lan"));
+        assert_eq!(session("fd00:50::1234"), Some("cs_v6"));
+        assert_eq!(session("192.168.51.7"), None);
+        assert_eq!(session("fd00:51::1"), None);
+    }
+
+    #[test]
+    fn validate_rejects_bad_sources_and_interfaces() {
+        assert!(config(FirewallBackend::Iptables).validate().is_ok());
+        for source in ["192.168.50.0/33", "not-an-ip", "fd00::/129"] {
+            let mut config = config(FirewallBackend::Iptables);
+            config.bindings[0].source = source.to_string();
+            assert!(config.validate().is_err(), "accepted {source}");
+        }
+        let mut config = config(FirewallBackend::Nftables);
+        config.interface = "wlan0; reboot".to_string();
+        assert!(config.validate().is_err());
+        assert!(config.install_commands().is_err());
+    }
+
+    #[test]
+    fn iptables_rules_redirect_each_family_and_skip_local_traffic() {
+        let commands: Vec<String> = config(FirewallBackend::Iptables)
+            .install_commands()
+            .unwrap()
+            .iter()
+            .map(|command| command.join(" "))
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                "iptables -t nat -N AMBIENT_TRANSPARENT",
+                "iptables -t nat -A AMBIENT_TRANSPARENT -m addrtype --dst-type LOCAL -j RETURN",
+                "iptables -t nat -A AMBIENT_TRANSPARENT -s 192.168.50.0/24 -p tcp -j REDIRECT --to-ports 7070",
+                "iptables -t nat -A AMBIENT_TRANSPARENT -s 192.168.50.20/32 -p tcp -j REDIRECT --to-ports 7070",
+                "iptables -t nat -A PREROUTING -i wlan0 -p tcp -j AMBIENT_TRANSPARENT",
+                "ip6tables -t nat -N AMBIENT_TRANSPARENT",
+                "ip6tables -t nat -A AMBIENT_TRANSPARENT -m addrtype --dst-type LOCAL -j RETURN",
+                "ip6tables -t nat -A AMBIENT_TRANSPARENT -s fd00:50::/64 -p tcp -j REDIRECT --to-ports 7070",
+                "ip6tables -t nat -A PREROUTING -i wlan0 -p tcp -j AMBIENT_TRANSPARENT",
+            ]
+        );
+        assert_eq!(
+            config(FirewallBackend::Iptables).remove_commands()[0].join(" "),
+            "iptables -t nat -D PREROUTING -i wlan0 -p tcp -j AMBIENT_TRANSPARENT"
+        );
+    }
+
+    #[test]
+    fn nftables_rules_use_one_inet_table() {
+        let config = config(FirewallBackend::Nftables);
+        let commands: Vec<String> = config
+            .install_commands()
+            .unwrap()
+            .iter()
+            .map(|command| command.join(" "))
+            .collect();
+        assert_eq!(commands[0], "nft add table inet ambient_transparent");
+        assert_eq!(
+            commands[2],
+            "nft add rule inet ambient_transparent prerouting fib daddr type local return"
+        );
+        assert_eq!(
+            commands[5],
+            "nft add rule inet ambient_transparent prerouting iifname wlan0 ip6 saddr fd00:50::/64 meta l4proto tcp redirect to :7070"
+        );
+        assert_eq!(
+            config.remove_commands(),
+            [args(&[
+                "nft",
+                "delete",
+                "table",
+                "inet",
+                "ambient_transparent"
+            ])]
+        );
+        RedirectRules::new(config, false).install().unwrap();
+    }
+}
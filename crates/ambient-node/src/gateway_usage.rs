@@ -0,0 +1,558 @@
+//! Gateway usage accounting
+//!
+//! [`GatewayUsage`] counts, per session, the connections relayed, bytes each
+//! way and why connections ended.  Bytes are counted as they move, so a
+//! long-lived tunnel shows up before it closes and a relay cut short by
+//! expiry or an I/O error is still accounted.  [`GatewayUsage::snapshot`]
+//! is what the local observability server serves at `/gateway/usage`.
+//!
+//! [`GatewayUsageReporter`] feeds the control plane's usage API: every
+//! interval it posts the bytes each session moved since its last report to
+//! `POST /api/v1/connect-sessions/{session_id}/usage`.  Reports carry the
+//! coordinator time in milliseconds as `seq`, so they stay ordered across
+//! gateway restarts.  Bytes of a report that failed are kept for the next
+//! one; a session the control plane does not know as relayed by this node
+//! (`404`) is dropped, as are the counters of sessions no longer live.
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context as TaskContext, Poll},
+    time::{Duration, Instant},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tracing::{debug, info, warn};
+
+use crate::gateway::DataPlaneGateway;
+use crate::gateway_access_log::TerminationReason;
+use crate::gateway_sync::{authorize, GatewaySessionSyncConfig};
+use crate::request_budget::{BudgetedClient, EndpointClass};
+
+/// Default seconds between usage reports.
+pub const DEFAULT_USAGE_REPORT_SECS: u64 = 60;
+
+/// Longest interval one report may claim, as the usage API accepts.
+const MAX_REPORT_INTERVAL_SECS: u64 = 3600;
+
+/// Relay counters of one session, or of the whole gateway.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageCounters {
+    pub connections: u64,
+    pub active_connections: u64,
+    /// Bytes from clients towards destinations
+    pub bytes_up: u64,
+    /// Bytes from destinations back to clients
+    pub bytes_down: u64,
+    /// Connections ended, by reason
+    pub terminations: BTreeMap<TerminationReason, u64>,
+}
+
+impl UsageCounters {
+    fn add(&mut self, other: &UsageCounters) {
+        self.connections += other.connections;
+        self.active_connections += other.active_connections;
+        self.bytes_up += other.bytes_up;
+        self.bytes_down += other.bytes_down;
+        for (reason, count) in &other.terminations {
+            *self.terminations.entry(*reason).or_default() += count;
+        }
+    }
+}
+
+/// What `/gateway/usage` serves.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageSnapshot {
+    /// Every connection since the gateway started, including sessions no
+    /// longer listed
+    pub totals: UsageCounters,
+    pub sessions: BTreeMap<String, UsageCounters>,
+}
+
+/// Bytes a session moved since its last report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionUsageDelta {
+    pub session_id: String,
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+}
+
+/// Live counters of one session, updated by its relays.
+#[derive(Debug, Default)]
+pub(crate) struct SessionMeter {
+    connections: AtomicU64,
+    active: AtomicU64,
+    bytes_up: AtomicU64,
+    bytes_down: AtomicU64,
+    unreported_up: AtomicU64,
+    unreported_down: AtomicU64,
+    terminations: Mutex<BTreeMap<TerminationReason, u64>>,
+}
+
+impl SessionMeter {
+    fn counters(&self) -> UsageCounters {
+        UsageCounters {
+            connections: self.connections.load(Ordering::Relaxed),
+            active_connections: self.active.load(Ordering::Relaxed),
+            bytes_up: self.bytes_up.load(Ordering::Relaxed),
+            bytes_down: self.bytes_down.load(Ordering::Relaxed),
+            terminations: self
+                .terminations
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone(),
+        }
+    }
+
+    fn idle(&self) -> bool {
+        self.active.load(Ordering::Relaxed) == 0
+            && self.unreported_up.load(Ordering::Relaxed) == 0
+            && self.unreported_down.load(Ordering::Relaxed) == 0
+    }
+}
+
+#[derive(Debug, Default)]
+struct UsageState {
+    sessions: BTreeMap<String, Arc<SessionMeter>>,
+    /// Counters of sessions pruned from `sessions`
+    retired: UsageCounters,
+}
+
+/// Per-session relay counters shared by a gateway and its readers.
+#[derive(Debug, Clone, Default)]
+pub struct GatewayUsage {
+    state: Arc<Mutex<UsageState>>,
+}
+
+impl GatewayUsage {
+    fn state(&self) -> std::sync::MutexGuard<'_, UsageState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Count a new relay for `session_id`; pair with [`Self::close`].
+    pub(crate) fn open(&self, session_id: &str) -> Arc<SessionMeter> {
+        let meter = self
+            .state()
+            .sessions
+            .entry(session_id.to_string())
+            .or_default()
+            .clone();
+        meter.connections.fetch_add(1, Ordering::Relaxed);
+        meter.active.fetch_add(1, Ordering::Relaxed);
+        meter
+    }
+
+    pub(crate) fn close(&self, meter: &SessionMeter, reason: TerminationReason) {
+        meter.active.fetch_sub(1, Ordering::Relaxed);
+        *meter
+            .terminations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(reason)
+            .or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> UsageSnapshot {
+        let state = self.state();
+        let mut totals = state.retired.clone();
+        let sessions = state
+            .sessions
+            .iter()
+            .map(|(session_id, meter)| {
+                let counters = meter.counters();
+                totals.add(&counters);
+                (session_id.clone(), counters)
+            })
+            .collect();
+        UsageSnapshot { totals, sessions }
+    }
+
+    /// Bytes each session moved since the last call, resetting them.
+    pub fn take_unreported(&self) -> Vec<SessionUsageDelta> {
+        self.state()
+            .sessions
+            .iter()
+            .map(|(session_id, meter)| SessionUsageDelta {
+                session_id: session_id.clone(),
+                bytes_up: meter.unreported_up.swap(0, Ordering::Relaxed),
+                bytes_down: meter.unreported_down.swap(0, Ordering::Relaxed),
+            })
+            .filter(|delta| delta.bytes_up > 0 || delta.bytes_down > 0)
+            .collect()
+    }
+
+    /// Put back the bytes of a report that did not go through.
+    pub fn restore(&self, delta: &SessionUsageDelta) {
+        let meter = self
+            .state()
+            .sessions
+            .entry(delta.session_id.clone())
+            .or_default()
+            .clone();
+        meter
+            .unreported_up
+            .fetch_add(delta.bytes_up, Ordering::Relaxed);
+        meter
+            .unreported_down
+            .fetch_add(delta.bytes_down, Ordering::Relaxed);
+    }
+
+    /// Fold sessions `keep` rejects into the totals, once they have no
+    /// relay open and nothing left to report.
+    pub fn prune(&self, keep: impl Fn(&str) -> bool) {
+        let mut state = self.state();
+        let UsageState { sessions, retired } = &mut *state;
+        sessions.retain(|session_id, meter| {
+            if keep(session_id) || !meter.idle() {
+                return true;
+            }
+            retired.add(&meter.counters());
+            false
+        });
+    }
+}
+
+/// Client side of a relay, counting bytes into the session's meter:
+/// reads are bytes up, writes bytes down.
+pub(crate) struct CountedStream<S> {
+    inner: S,
+    meter: Arc<SessionMeter>,
+    pub(crate) bytes_up: u64,
+    pub(crate) bytes_down: u64,
+}
+
+impl<S> CountedStream<S> {
+    pub(crate) fn new(inner: S, meter: Arc<SessionMeter>) -> Self {
+        Self {
+            inner,
+            meter,
+            bytes_up: 0,
+            bytes_down: 0,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for CountedStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = (buf.filled().len() - before) as u64;
+        if read > 0 {
+            self.bytes_up += read;
+            self.meter.bytes_up.fetch_add(read, Ordering::Relaxed);
+            self.meter.unreported_up.fetch_add(read, Ordering::Relaxed);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for CountedStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            let written = written as u64;
+            self.bytes_down += written;
+            self.meter.bytes_down.fetch_add(written, Ordering::Relaxed);
+            self.meter
+                .unreported_down
+                .fetch_add(written, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Sessions of one round of reports, by outcome.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageReportRound {
+    pub reported: Vec<String>,
+    /// Not relayed by this node as far as the control plane knows
+    pub rejected: Vec<String>,
+    /// Failed; their bytes go out with the next round
+    pub deferred: Vec<String>,
+}
+
+/// Reports a gateway's per-session byte counts to the control plane.
+#[derive(Debug)]
+pub struct GatewayUsageReporter {
+    gateway: DataPlaneGateway,
+    config: GatewaySessionSyncConfig,
+    client: BudgetedClient,
+    last_round: Instant,
+}
+
+impl GatewayUsageReporter {
+    /// Report as `config` says the control plane is reached; the same
+    /// settings session sync uses.
+    pub fn new(gateway: DataPlaneGateway, config: GatewaySessionSyncConfig) -> Result<Self> {
+        let client = BudgetedClient::new(config.requests)
+            .context("failed to build gateway usage report client")?;
+        Ok(Self {
+            gateway,
+            config,
+            client,
+            last_round: Instant::now(),
+        })
+    }
+
+    /// Post every session's bytes since the last round.
+    pub async fn report(&mut self) -> UsageReportRound {
+        let interval_seconds = self
+            .last_round
+            .elapsed()
+            .as_secs()
+            .clamp(1, MAX_REPORT_INTERVAL_SECS);
+        self.last_round = Instant::now();
+        let usage = self.gateway.usage();
+        let mut round = UsageReportRound::default();
+        for delta in usage.take_unreported() {
+            let session_id = delta.session_id.clone();
+            match self.post(&delta, interval_seconds).await {
+                Ok(true) => round.reported.push(session_id),
+                Ok(false) => {
+                    debug!(%session_id, "usage report rejected; session not relayed by this node");
+                    round.rejected.push(session_id);
+                }
+                Err(err) => {
+                    warn!(%session_id, "usage report failed, keeping it for the next round: {err:#}");
+                    usage.restore(&delta);
+                    round.deferred.push(session_id);
+                }
+            }
+        }
+
+        let live: HashSet<String> = self
+            .gateway
+            .list_sessions()
+            .await
+            .into_iter()
+            .map(|summary| summary.session_id)
+            .collect();
+        usage.prune(|session_id| live.contains(session_id));
+        round
+    }
+
+    /// `Ok(false)` when the control plane answered `404`.
+    async fn post(&self, delta: &SessionUsageDelta, interval_seconds: u64) -> Result<bool> {
+        let url = format!(
+            "{}/api/v1/connect-sessions/{}/usage",
+            self.config.api_url.trim_end_matches('/'),
+            delta.session_id
+        );
+        let request = authorize(self.client.client().post(url), &self.config.token).json(
+            &serde_json::json!({
+                "node_id": self.config.node_id,
+                "bytes_up": delta.bytes_up,
+                "bytes_down": delta.bytes_down,
+                "interval_seconds": interval_seconds,
+                "seq": self.gateway.coordinator_now_ms().max(0) as u64,
+            }),
+        );
+        let response = self
+            .client
+            .send(EndpointClass::Control, "connect-session-usage", request)
+            .await
+            .context("failed to reach the control plane")?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        if !status.is_success() {
+            bail!("control plane answered {status}");
+        }
+        Ok(true)
+    }
+
+    /// Report every `interval` until the task is dropped.
+    pub async fn run(mut self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let round = self.report().await;
+            if !round.reported.is_empty() || !round.deferred.is_empty() {
+                info!(
+                    node_id = %self.config.node_id,
+                    reported = round.reported.len(),
+                    rejected = round.rejected.len(),
+                    deferred = round.deferred.len(),
+                    "gateway usage reported to the control plane"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::{GatewayConfig, GatewaySession};
+    use crate::request_budget::RequestBudgets;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn session(session_id: &str) -> GatewaySession {
+        serde_json::from_value(serde_json::json!({
+            "session_id": session_id,
+            "session_token": "cs_token",
+            "egress_profile": "allowlist_domains",
+            "destination_policy_id": "policy_web_basic_v1",
+            "allowed_destinations": ["example.com"],
+            "expires_at_epoch_seconds": chrono::Utc::now().timestamp() as u64 + 3600,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn counted_stream_feeds_session_counters() {
+        let usage = GatewayUsage::default();
+        let (client, mut peer) = tokio::io::duplex(64);
+        let mut counted = CountedStream::new(client, usage.open("s-1"));
+
+        peer.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        counted.read_exact(&mut buf).await.unwrap();
+        counted.write_all(b"answer!").await.unwrap();
+        assert_eq!((counted.bytes_up, counted.bytes_down), (5, 7));
+
+        let snapshot = usage.snapshot();
+        assert_eq!(snapshot.sessions["s-1"].active_connections, 1);
+        assert_eq!(snapshot.totals.bytes_up, 5);
+        usage.close(&counted.meter, TerminationReason::IdleTimeout);
+
+        let deltas = usage.take_unreported();
+        assert_eq!(
+            deltas,
+            [SessionUsageDelta {
+                session_id: "s-1".to_string(),
+                bytes_up: 5,
+                bytes_down: 7,
+            }]
+        );
+        assert!(usage.take_unreported().is_empty());
+
+        // Pruning keeps sessions with bytes still to report.
+        usage.restore(&deltas[0]);
+        usage.prune(|_| false);
+        assert!(usage.snapshot().sessions.contains_key("s-1"));
+        usage.take_unreported();
+        usage.prune(|_| false);
+        let snapshot = usage.snapshot();
+        assert!(snapshot.sessions.is_empty());
+        assert_eq!(snapshot.totals.connections, 1);
+        assert_eq!(snapshot.totals.bytes_down, 7);
+        assert_eq!(
+            snapshot.totals.terminations[&TerminationReason::IdleTimeout],
+            1
+        );
+    }
+
+    /// Answer each request with the next status, returning request heads
+    /// and bodies.
+    async fn serve(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut head = Vec::new();
+                let mut byte = [0u8; 1];
+                while !head.ends_with(b"\r\n\r\n") {
+                    stream.read_exact(&mut byte).await.unwrap();
+                    head.push(byte[0]);
+                }
+                let head = String::from_utf8_lossy(&head).to_lowercase();
+                let length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |len| len.trim().parse().unwrap());
+                let mut body = vec![0u8; length];
+                stream.read_exact(&mut body).await.unwrap();
+                seen.lock()
+                    .unwrap()
+                    .push(format!("{head}{}", String::from_utf8_lossy(&body)));
+                let response = format!(
+                    "HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn reporter_posts_deltas_and_keeps_failed_ones() {
+        let (url, requests) = serve(vec![200, 404, 500, 200]).await;
+        let gateway = DataPlaneGateway::new(GatewayConfig::default(), vec![session("a")]);
+        let usage = gateway.usage();
+        for (session_id, up) in [("a", 10), ("b", 20)] {
+            usage.restore(&SessionUsageDelta {
+                session_id: session_id.to_string(),
+                bytes_up: up,
+                bytes_down: 1,
+            });
+        }
+        let mut reporter = GatewayUsageReporter::new(
+            gateway.clone(),
+            GatewaySessionSyncConfig {
+                api_url: url,
+                node_id: "node-1".to_string(),
+                token: "vcp_key".to_string(),
+                control_public_key: None,
+                requests: RequestBudgets::default(),
+            },
+        )
+        .unwrap();
+
+        let round = reporter.report().await;
+        assert_eq!(round.reported, ["a"]);
+        assert_eq!(round.rejected, ["b"]);
+        // `b` is not live and has nothing left to report.
+        assert_eq!(usage.snapshot().sessions.keys().collect::<Vec<_>>(), ["a"]);
+
+        usage.restore(&SessionUsageDelta {
+            session_id: "a".to_string(),
+            bytes_up: 5,
+            bytes_down: 0,
+        });
+        assert_eq!(reporter.report().await.deferred, ["a"]);
+        assert_eq!(reporter.report().await.reported, ["a"]);
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with("post /api/v1/connect-sessions/a/usage "));
+        assert!(requests[0].contains("x-api-key: vcp_key"));
+        let body: serde_json::Value =
+            serde_json::from_str(&requests[0][requests[0].find('{').unwrap()..]).unwrap();
+        assert_eq!(body["node_id"], "node-1");
+        assert_eq!(body["bytes_up"], 10);
+        assert!(body["seq"].as_u64().unwrap() > 0);
+        let retried: serde_json::Value =
+            serde_json::from_str(&requests[3][requests[3].find('{').unwrap()..]).unwrap();
+        assert_eq!(retried["bytes_up"], 5);
+    }
+}
@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
 /// Health scoring system for nodes
 pub trait HealthScorer {
     fn health_score(&self) -> f64;
@@ -5,3 +8,199 @@ pub trait HealthScorer {
         self.health_score() > 0.5
     }
 }
+
+/// How far the weights of a [`HealthScoreConfig`] may sum away from 1.
+pub const HEALTH_WEIGHT_TOLERANCE: f64 = 1e-6;
+
+/// Weights of the components of [`crate::AmbientNode::health_score`].
+///
+/// The default, 40% bandwidth, 30% latency, 20% compute and 10% reputation,
+/// suits general-purpose nodes; [`HealthScoreConfig::relay`] and
+/// [`HealthScoreConfig::worker`] favour latency and compute headroom.  Weights
+/// must be finite, non-negative and sum to 1, which is checked both by
+/// [`HealthScoreConfig::new`] and when deserializing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "HealthScoreWeights")]
+pub struct HealthScoreConfig {
+    bandwidth: f64,
+    latency: f64,
+    compute: f64,
+    reputation: f64,
+}
+
+/// Unchecked weights as written in a config file.
+#[derive(Deserialize)]
+struct HealthScoreWeights {
+    bandwidth: f64,
+    latency: f64,
+    compute: f64,
+    reputation: f64,
+}
+
+impl TryFrom<HealthScoreWeights> for HealthScoreConfig {
+    type Error = HealthScoreConfigError;
+
+    fn try_from(weights: HealthScoreWeights) -> Result<Self, Self::Error> {
+        Self::new(
+            weights.bandwidth,
+            weights.latency,
+            weights.compute,
+            weights.reputation,
+        )
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum HealthScoreConfigError {
+    #[error("{0} weight must be a finite, non-negative number")]
+    InvalidWeight(&'static str),
+    #[error("health score weights must sum to 1, not {0}")]
+    WrongSum(f64),
+}
+
+impl HealthScoreConfig {
+    pub fn new(
+        bandwidth: f64,
+        latency: f64,
+        compute: f64,
+        reputation: f64,
+    ) -> Result<Self, HealthScoreConfigError> {
+        for (name, weight) in [
+            ("bandwidth", bandwidth),
+            ("latency", latency),
+            ("compute", compute),
+            ("reputation", reputation),
+        ] {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(HealthScoreConfigError::InvalidWeight(name));
+            }
+        }
+        let sum = bandwidth + latency + compute + reputation;
+        if (sum - 1.0).abs() > HEALTH_WEIGHT_TOLERANCE {
+            return Err(HealthScoreConfigError::WrongSum(sum));
+        }
+        Ok(Self {
+            bandwidth,
+            latency,
+            compute,
+            reputation,
+        })
+    }
+
+    /// Latency-dominant weighting for relay nodes (gateways and
+    /// `open_internet` exits), whose sessions suffer from slow hops more than
+    /// from busy CPUs.
+    pub fn relay() -> Self {
+        Self {
+            bandwidth: 0.3,
+            latency: 0.5,
+            compute: 0.05,
+            reputation: 0.15,
+        }
+    }
+
+    /// Compute-heavy weighting for worker nodes, which are judged mostly on
+    /// free CPU and memory and on their task record.
+    pub fn worker() -> Self {
+        Self {
+            bandwidth: 0.15,
+            latency: 0.1,
+            compute: 0.5,
+            reputation: 0.25,
+        }
+    }
+
+    pub fn bandwidth(&self) -> f64 {
+        self.bandwidth
+    }
+
+    pub fn latency(&self) -> f64 {
+        self.latency
+    }
+
+    pub fn compute(&self) -> f64 {
+        self.compute
+    }
+
+    pub fn reputation(&self) -> f64 {
+        self.reputation
+    }
+
+    /// Weighted sum of component scores, each in `0.0 - 1.0`.
+    #[must_use]
+    pub fn score(&self, bandwidth: f64, latency: f64, compute: f64, reputation: f64) -> f64 {
+        (bandwidth * self.bandwidth)
+            + (latency * self.latency)
+            + (compute * self.compute)
+            + (reputation * self.reputation)
+    }
+}
+
+impl Default for HealthScoreConfig {
+    fn default() -> Self {
+        Self {
+            bandwidth: 0.4,
+            latency: 0.3,
+            compute: 0.2,
+            reputation: 0.1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_valid_weightings() {
+        for config in [
+            HealthScoreConfig::default(),
+            HealthScoreConfig::relay(),
+            HealthScoreConfig::worker(),
+        ] {
+            assert_eq!(
+                HealthScoreConfig::new(
+                    config.bandwidth(),
+                    config.latency(),
+                    config.compute(),
+                    config.reputation()
+                ),
+                Ok(config)
+            );
+        }
+    }
+
+    #[test]
+    fn weights_must_sum_to_one() {
+        assert!(matches!(
+            HealthScoreConfig::new(0.4, 0.4, 0.4, 0.1),
+            Err(HealthScoreConfigError::WrongSum(_))
+        ));
+        assert_eq!(
+            HealthScoreConfig::new(1.2, -0.2, 0.0, 0.0),
+            Err(HealthScoreConfigError::InvalidWeight("latency"))
+        );
+        assert!(HealthScoreConfig::new(f64::NAN, 0.5, 0.5, 0.0).is_err());
+        assert!(HealthScoreConfig::new(0.1, 0.2, 0.3, 0.4).is_ok());
+    }
+
+    #[test]
+    fn deserializing_checks_the_weights() {
+        let config: HealthScoreConfig = serde_json::from_str(
+            r#"{"bandwidth": 0.25, "latency": 0.25, "compute": 0.25, "reputation": 0.25}"#,
+        )
+        .unwrap();
+        assert_eq!(config.score(1.0, 0.0, 1.0, 0.0), 0.5);
+
+        let err = serde_json::from_str::<HealthScoreConfig>(
+            r#"{"bandwidth": 0.5, "latency": 0.5, "compute": 0.5, "reputation": 0.5}"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("must sum to 1"));
+
+        let round_trip: HealthScoreConfig =
+            serde_json::from_str(&serde_json::to_string(&HealthScoreConfig::relay()).unwrap())
+                .unwrap();
+        assert_eq!(round_trip, HealthScoreConfig::relay());
+    }
+}
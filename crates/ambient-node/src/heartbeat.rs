@@ -0,0 +1,235 @@
+use crate::TelemetrySample;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Heartbeats between forced full snapshots, bounding how long a silently
+/// diverged server state can persist.
+pub const DEFAULT_FULL_SNAPSHOT_INTERVAL: u32 = 60;
+
+/// Body for `PUT /api/v1/nodes/{node_id}/heartbeat`.
+///
+/// Without `baseline_seq`, `state` is a full snapshot.  With it, `state` holds
+/// only fields that changed since `baseline_seq`, and `null` removes a field.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HeartbeatPayload {
+    pub seq: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline_seq: Option<u64>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub state: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_skew_ms: Option<i64>,
+}
+
+impl HeartbeatPayload {
+    pub fn is_full(&self) -> bool {
+        self.baseline_seq.is_none()
+    }
+}
+
+/// Fields of the coordinator's heartbeat response used by the encoder.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+pub struct HeartbeatAck {
+    #[serde(default)]
+    pub state_seq: Option<u64>,
+    #[serde(default)]
+    pub resync_required: bool,
+}
+
+/// Heartbeat state for a telemetry sample.  `timestamp` is left out because
+/// it changes every beat and the server records its own receive time.
+pub fn telemetry_state(sample: &TelemetrySample) -> Map<String, Value> {
+    let mut state = match serde_json::to_value(sample) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    state.remove("timestamp");
+    state
+}
+
+/// Encodes successive node states as full snapshots or deltas.
+///
+/// Each payload is diffed against the previous one sent.  A full snapshot is
+/// sent first, every `full_interval` beats, after a failed send, and whenever
+/// the server acknowledges a different sequence or asks for a resync, such as
+/// after a lost heartbeat or a coordinator restore.
+#[derive(Debug, Clone)]
+pub struct HeartbeatEncoder {
+    seq: u64,
+    last_sent: Option<Map<String, Value>>,
+    full_interval: u32,
+    since_full: u32,
+}
+
+impl Default for HeartbeatEncoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_FULL_SNAPSHOT_INTERVAL)
+    }
+}
+
+impl HeartbeatEncoder {
+    pub fn new(full_interval: u32) -> Self {
+        Self {
+            seq: 0,
+            last_sent: None,
+            full_interval: full_interval.max(1),
+            since_full: 0,
+        }
+    }
+
+    /// Sequence of the last encoded payload.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Build the next heartbeat for `state`.
+    pub fn encode(
+        &mut self,
+        state: Map<String, Value>,
+        clock_skew_ms: Option<i64>,
+    ) -> HeartbeatPayload {
+        let baseline_seq = self.seq;
+        self.seq += 1;
+
+        let delta = match self.last_sent {
+            Some(ref previous) if self.since_full < self.full_interval => {
+                Some(diff_state(previous, &state))
+            }
+            _ => None,
+        };
+
+        let payload = match delta {
+            Some(changed) => {
+                self.since_full += 1;
+                HeartbeatPayload {
+                    seq: self.seq,
+                    baseline_seq: Some(baseline_seq),
+                    state: changed,
+                    clock_skew_ms,
+                }
+            }
+            None => {
+                self.since_full = 1;
+                HeartbeatPayload {
+                    seq: self.seq,
+                    baseline_seq: None,
+                    state: state.clone(),
+                    clock_skew_ms,
+                }
+            }
+        };
+
+        self.last_sent = Some(state);
+        payload
+    }
+
+    /// Apply the coordinator's response to the last payload.
+    pub fn handle_ack(&mut self, ack: &HeartbeatAck) {
+        if ack.resync_required || ack.state_seq != Some(self.seq) {
+            self.force_full_snapshot();
+        }
+    }
+
+    /// Send a full snapshot next, e.g. after a heartbeat failed to send.
+    pub fn force_full_snapshot(&mut self) {
+        self.last_sent = None;
+    }
+}
+
+/// Fields of `current` that differ from `previous`; removed fields map to `null`.
+pub fn diff_state(
+    previous: &Map<String, Value>,
+    current: &Map<String, Value>,
+) -> Map<String, Value> {
+    let mut changed: Map<String, Value> = current
+        .iter()
+        .filter(|(key, value)| previous.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    for key in previous.keys() {
+        if !current.contains_key(key) {
+            changed.insert(key.clone(), Value::Null);
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn state(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn test_encoder_sends_deltas_after_first_snapshot() {
+        let mut encoder = HeartbeatEncoder::new(10);
+
+        let first = encoder.encode(state(json!({"cpu": 10, "mem": 40})), None);
+        assert!(first.is_full());
+        assert_eq!(first.seq, 1);
+        encoder.handle_ack(&HeartbeatAck {
+            state_seq: Some(1),
+            resync_required: false,
+        });
+
+        let unchanged = encoder.encode(state(json!({"cpu": 10, "mem": 40})), None);
+        assert_eq!(unchanged.baseline_seq, Some(1));
+        assert!(unchanged.state.is_empty());
+        assert_eq!(
+            serde_json::to_value(&unchanged).unwrap(),
+            json!({"seq": 2, "baseline_seq": 1})
+        );
+        encoder.handle_ack(&HeartbeatAck {
+            state_seq: Some(2),
+            resync_required: false,
+        });
+
+        let changed = encoder.encode(state(json!({"cpu": 75})), Some(12));
+        assert_eq!(changed.state, state(json!({"cpu": 75, "mem": null})));
+        assert_eq!(changed.clock_skew_ms, Some(12));
+    }
+
+    #[test]
+    fn test_encoder_resyncs_on_gap_and_interval() {
+        let mut encoder = HeartbeatEncoder::new(3);
+        let sample = state(json!({"cpu": 10}));
+
+        encoder.encode(sample.clone(), None);
+        encoder.handle_ack(&HeartbeatAck {
+            state_seq: Some(1),
+            resync_required: false,
+        });
+        encoder.encode(sample.clone(), None);
+        encoder.handle_ack(&HeartbeatAck {
+            state_seq: None,
+            resync_required: true,
+        });
+        assert!(encoder.encode(sample.clone(), None).is_full());
+
+        encoder.handle_ack(&HeartbeatAck {
+            state_seq: Some(3),
+            resync_required: false,
+        });
+        assert!(!encoder.encode(sample.clone(), None).is_full());
+        encoder.handle_ack(&HeartbeatAck {
+            state_seq: Some(4),
+            resync_required: false,
+        });
+        assert!(!encoder.encode(sample.clone(), None).is_full());
+        encoder.handle_ack(&HeartbeatAck {
+            state_seq: Some(5),
+            resync_required: false,
+        });
+        assert!(encoder.encode(sample, None).is_full());
+    }
+
+    #[test]
+    fn test_telemetry_state_omits_timestamp() {
+        let state = telemetry_state(&TelemetrySample::default());
+        assert!(state.contains_key("cpu_usage_percent"));
+        assert!(!state.contains_key("timestamp"));
+    }
+}
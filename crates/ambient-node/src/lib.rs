@@ -2,13 +2,41 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 // VCP modules
+pub mod attestation;
+pub mod clock;
+pub mod codec;
 pub mod connectivity;
+pub mod control_signature;
+pub mod deployment_profile;
+pub mod energy;
 pub mod feen;
 pub mod gateway;
+pub mod gateway_access_log;
+#[cfg(unix)]
+pub mod gateway_admin;
+pub mod gateway_dns;
+pub mod gateway_limits;
+pub mod gateway_reload;
+pub mod gateway_socks5;
+pub mod gateway_sync;
+pub mod gateway_tls;
+pub mod gateway_transparent;
+pub mod gateway_usage;
 pub mod health;
+pub mod heartbeat;
+pub mod network_diversity;
+pub mod node_kind;
 pub mod offline;
+pub mod policy_bundle;
+pub mod proof_verdict;
 pub mod reputation;
+pub mod request_budget;
+pub mod sandbox_report;
+pub mod secrets;
 pub mod telemetry;
+pub mod telemetry_collector;
+pub mod trace_context;
+pub mod transport;
 
 // Local observability (operator-only, privacy-preserving)
 #[cfg(feature = "observability")]
@@ -19,21 +47,60 @@ pub mod ailee_integration;
 
 // Re-export AILEE types from external crate (not re-implemented)
 pub use ailee_trust_layer::{
-    AileeMetric, AileeParams, AileeSample, ConsensusEngine, ExecutionMode, GenerationRequest,
-    GenerationResult, LocalModelAdapter, ModelAdapter, ModelLocality, ModelOutput,
-    RemoteModelAdapter, TaskType, TrustScores,
+    AileeMetric, AileeParams, AileeSample, ConsensusEngine, DriftAlert, DriftConfig, ExecutionMode,
+    GenerationRequest, GenerationResult, LocalModelAdapter, ModelAdapter, ModelIdentity,
+    ModelLocality, ModelOutput, RemoteModelAdapter, TaskType, TrustLedger, TrustScores,
 };
 
 // Re-export VCP integration adapter
-pub use ailee_integration::{AileeEngineAdapter, VcpExecutionContext};
+pub use ailee_integration::{
+    AileeEngineAdapter, TrustPolicies, TrustRequirement, VcpExecutionContext,
+};
 
 // Re-export VCP types
+pub use attestation::*;
+pub use clock::*;
+pub use codec::*;
 pub use connectivity::*;
+pub use control_signature::*;
+pub use deployment_profile::*;
+pub use energy::*;
 pub use gateway::*;
+pub use gateway_access_log::{
+    AccessLog, AccessRecord, TerminationReason, DEFAULT_ACCESS_LOG_KEEP,
+    DEFAULT_ACCESS_LOG_MAX_BYTES,
+};
+#[cfg(unix)]
+pub use gateway_admin::{GatewayAdminRequest, GatewayAdminResponse, GatewayAdminSocket};
+pub use gateway_dns::DnsStats;
+pub use gateway_limits::{SessionConnectionLimits, SessionLimit, SessionLimitExceeded};
+pub use gateway_reload::{SessionsFileReloader, SessionsReload};
+pub use gateway_sync::{GatewaySessionSyncConfig, GatewaySessionSyncer, DEFAULT_SESSION_SYNC_SECS};
+pub use gateway_tls::*;
+pub use gateway_transparent::{
+    FirewallBackend, RedirectRules, TransparentBinding, TransparentConfig,
+};
+pub use gateway_usage::{
+    GatewayUsage, GatewayUsageReporter, SessionUsageDelta, UsageCounters, UsageReportRound,
+    UsageSnapshot, DEFAULT_USAGE_REPORT_SECS,
+};
 pub use health::*;
+pub use heartbeat::*;
+pub use network_diversity::*;
+pub use node_kind::*;
 pub use offline::*;
+pub use policy_bundle::*;
+pub use proof_verdict::*;
 pub use reputation::*;
+pub use request_budget::*;
+pub use sandbox_report::*;
+pub use secrets::*;
 pub use telemetry::*;
+pub use telemetry_collector::{
+    PowerEstimate, TelemetryCollector, TelemetryCollectorConfig, DEFAULT_TELEMETRY_INTERVAL_SECS,
+};
+pub use trace_context::*;
+pub use transport::*;
 
 // Re-export observability types when feature is enabled
 #[cfg(feature = "observability")]
@@ -83,6 +150,11 @@ impl NodeId {
             node_type: node_type.into(),
         }
     }
+
+    /// Canonical kind of `node_type`, or `None` for free-form types.
+    pub fn kind(&self) -> Option<NodeKind> {
+        NodeKind::parse(&self.node_type)
+    }
 }
 
 /// Safety policy configuration for circuit breakers
@@ -111,7 +183,13 @@ pub struct AmbientNode {
     pub id: NodeId,
     pub telemetry: TelemetrySample,
     pub reputation: Reputation,
+    /// Per-task and per-relay-session energy accounting fed by telemetry.
+    #[serde(default)]
+    pub energy: EnergyMeter,
     safety_policy: SafetyPolicy,
+    /// Component weights of [`Self::health_score`].
+    #[serde(default)]
+    health_config: HealthScoreConfig,
     error_count: u32,
 }
 
@@ -121,29 +199,37 @@ impl AmbientNode {
             id,
             telemetry: TelemetrySample::default(),
             reputation: Reputation::default(),
+            energy: EnergyMeter::default(),
             safety_policy,
+            health_config: HealthScoreConfig::default(),
             error_count: 0,
         }
     }
 
+    /// Weight the health score with `config` instead of the default.
+    pub fn with_health_config(mut self, config: HealthScoreConfig) -> Self {
+        self.health_config = config;
+        self
+    }
+
     /// Ingest new telemetry data
     pub fn ingest_telemetry(&mut self, sample: TelemetrySample) {
+        self.energy
+            .record_power(sample.timestamp, sample.power_watts);
         self.telemetry = sample;
     }
 
     /// Calculate overall health score (0.0 - 1.0)
-    /// Weights: Bandwidth (upload+download bottleneck) 40%, Latency 30%, Compute 20%, Reputation 10%
+    /// Bandwidth (upload+download bottleneck), latency, compute and reputation
+    /// scores weighted by the node's [`HealthScoreConfig`], 40/30/20/10 by default.
     #[must_use]
     pub fn health_score(&self) -> f64 {
-        let bandwidth_score = self.telemetry.bandwidth_score();
-        let latency_score = self.telemetry.latency_score();
-        let compute_score = self.telemetry.compute_score();
-        let reputation_score = self.reputation.score();
-
-        (bandwidth_score * 0.4)
-            + (latency_score * 0.3)
-            + (compute_score * 0.2)
-            + (reputation_score * 0.1)
+        self.health_config.score(
+            self.telemetry.bandwidth_score(),
+            self.telemetry.latency_score(),
+            self.telemetry.compute_score(),
+            self.reputation.score(),
+        )
     }
 
     /// Check if node is in safe mode (circuit breaker triggered)
@@ -169,10 +255,33 @@ impl AmbientNode {
         &self.safety_policy
     }
 
+    /// Get current health score weights
+    pub fn health_config(&self) -> &HealthScoreConfig {
+        &self.health_config
+    }
+
+    pub fn set_health_config(&mut self, config: HealthScoreConfig) {
+        self.health_config = config;
+    }
+
     /// Reset error count
     pub fn reset_errors(&mut self) {
         self.error_count = 0;
     }
+
+    /// Stop metering a task and add the energy it consumed to the sandbox's
+    /// resource usage, for the result report.  Energy stays `None` when no
+    /// power samples covered the task.
+    pub fn finish_task_usage(
+        &mut self,
+        task_id: &str,
+        usage: wasm_engine::ResourceUsage,
+    ) -> wasm_engine::ResourceUsage {
+        match self.energy.finish_task(task_id) {
+            Some(energy) if energy.metered_secs > 0 => usage.with_energy_wh(energy.watt_hours),
+            _ => usage,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -180,6 +289,23 @@ mod tests {
     use super::*;
     use std::time::{SystemTime, UNIX_EPOCH};
 
+    #[test]
+    fn finished_task_usage_carries_metered_energy() {
+        let node_id = NodeId::new("node-001", "us-west", "compute").unwrap();
+        let mut node = AmbientNode::new(node_id, SafetyPolicy::default());
+        let usage = wasm_engine::ResourceUsage::from_pages(2, None);
+
+        node.energy.start_task("unmetered");
+        assert_eq!(node.finish_task_usage("unmetered", usage).energy_wh, None);
+
+        node.energy.start_task("metered");
+        node.energy.record_power(1_000, 100.0);
+        node.energy.record_power(1_036, 100.0);
+        let reported = node.finish_task_usage("metered", usage);
+        assert_eq!(reported.peak_memory_bytes, 2 * 65_536);
+        assert!((reported.energy_wh.unwrap() - 1.0).abs() < 1e-9);
+    }
+
     #[test]
     fn test_node_creation() {
         let node_id = NodeId::new("node-001", "us-west", "gateway").unwrap();
@@ -216,6 +342,53 @@ mod tests {
         assert!((0.0..=1.0).contains(&score));
     }
 
+    #[test]
+    fn health_score_follows_the_configured_weights() {
+        let node_id = NodeId::new("node-001", "us-west", "gateway").unwrap();
+        let mut node = AmbientNode::new(node_id, SafetyPolicy::default());
+        // Fast links on a saturated host.
+        node.ingest_telemetry(TelemetrySample {
+            bandwidth_mbps: 1000.0,
+            avg_latency_ms: 5.0,
+            cpu_usage_percent: 95.0,
+            memory_usage_percent: 95.0,
+            ..TelemetrySample::default()
+        });
+        let default_score = node.health_score();
+
+        node.set_health_config(HealthScoreConfig::relay());
+        let relay_score = node.health_score();
+        let worker = node.clone().with_health_config(HealthScoreConfig::worker());
+
+        assert!(relay_score > default_score);
+        assert!(worker.health_score() < default_score);
+        assert_eq!(worker.health_config(), &HealthScoreConfig::worker());
+
+        // Nodes serialized before the weights existed get the default.
+        let mut legacy = serde_json::to_value(&node).unwrap();
+        legacy.as_object_mut().unwrap().remove("health_config");
+        let restored: AmbientNode = serde_json::from_value(legacy).unwrap();
+        assert_eq!(restored.health_config(), &HealthScoreConfig::default());
+    }
+
+    #[test]
+    fn test_ingest_telemetry_meters_task_energy() {
+        let node_id = NodeId::new("node-001", "us-west", "compute").unwrap();
+        let mut node = AmbientNode::new(node_id, SafetyPolicy::default());
+        node.energy.start_task("task-1");
+
+        for (timestamp, power_watts) in [(1_000, 200.0), (1_018, 200.0)] {
+            node.ingest_telemetry(TelemetrySample {
+                power_watts,
+                timestamp,
+                ..Default::default()
+            });
+        }
+
+        let usage = node.energy.finish_task("task-1").unwrap();
+        assert!((usage.watt_hours - 1.0).abs() < 1e-9);
+    }
+
     #[test]
     fn test_safe_mode_temperature() {
         let node_id = NodeId::new("node-001", "us-west", "gateway").unwrap();
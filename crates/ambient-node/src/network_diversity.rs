@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+/// Where a node sits on the network, for spreading redundant work so one
+/// ISP or regional outage cannot take out every copy.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NetworkLocation {
+    /// Autonomous system number of the node's uplink, when known.
+    pub asn: Option<u32>,
+    pub region: String,
+}
+
+impl NetworkLocation {
+    pub fn new(asn: Option<u32>, region: impl Into<String>) -> Self {
+        Self {
+            asn,
+            region: region.into(),
+        }
+    }
+
+    /// Whether a failure of `other`'s network is likely to hit this node
+    /// too: same ASN, or same region when either ASN is unknown.
+    pub fn shares_failure_domain(&self, other: &NetworkLocation) -> bool {
+        match (self.asn, other.asn) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.region == other.region,
+        }
+    }
+}
+
+/// Pick up to `want` items from `ranked` (best first), spreading them across
+/// failure domains and away from the locations already in `taken`.
+///
+/// Candidates in a new ASN *and* a new region go first, then ones in a new
+/// failure domain; both passes keep the incoming rank order.  With `strict`
+/// that is all; otherwise the remaining slots are filled in rank order.
+pub fn spread_by_network<T>(
+    ranked: Vec<(T, NetworkLocation)>,
+    taken: &[NetworkLocation],
+    want: usize,
+    strict: bool,
+) -> Vec<T> {
+    let mut used: Vec<NetworkLocation> = taken.to_vec();
+    let mut remaining: Vec<Option<(T, NetworkLocation)>> = ranked.into_iter().map(Some).collect();
+    let mut picked = Vec::new();
+
+    let new_region = |location: &NetworkLocation, used: &[NetworkLocation]| {
+        used.iter().all(|other| other.region != location.region)
+    };
+    let new_domain = |location: &NetworkLocation, used: &[NetworkLocation]| {
+        used.iter()
+            .all(|other| !location.shares_failure_domain(other))
+    };
+
+    for pass in 0..3 {
+        if pass == 2 && strict {
+            break;
+        }
+        for slot in remaining.iter_mut() {
+            if picked.len() >= want {
+                return picked;
+            }
+            let Some((_, location)) = slot.as_ref() else {
+                continue;
+            };
+            let fits = match pass {
+                0 => new_domain(location, &used) && new_region(location, &used),
+                1 => new_domain(location, &used),
+                _ => true,
+            };
+            if fits {
+                let (item, location) = slot.take().expect("slot checked above");
+                used.push(location);
+                picked.push(item);
+            }
+        }
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(id: &'static str, asn: Option<u32>, region: &str) -> (&'static str, NetworkLocation) {
+        (id, NetworkLocation::new(asn, region))
+    }
+
+    #[test]
+    fn test_spread_prefers_new_asns_then_fills_in_rank_order() {
+        let ranked = vec![
+            at("a1", Some(7922), "us-east"),
+            at("a2", Some(7922), "us-east"),
+            at("b1", Some(3320), "us-east"),
+            at("c1", Some(16509), "eu-west"),
+        ];
+
+        assert_eq!(
+            spread_by_network(ranked.clone(), &[], 3, false),
+            vec!["a1", "c1", "b1"]
+        );
+
+        // A node already running the task in AS7922 pushes a1 and a2 back.
+        let taken = [NetworkLocation::new(Some(7922), "us-west")];
+        assert_eq!(
+            spread_by_network(ranked.clone(), &taken, 3, false),
+            vec!["b1", "c1", "a1"]
+        );
+        assert_eq!(spread_by_network(ranked, &taken, 3, true), vec!["b1", "c1"]);
+    }
+
+    #[test]
+    fn test_unknown_asn_falls_back_to_region() {
+        let ranked = vec![
+            at("x", None, "us-east"),
+            at("y", Some(3320), "us-east"),
+            at("z", None, "ap-south"),
+        ];
+        assert_eq!(spread_by_network(ranked, &[], 3, true), vec!["x", "z"]);
+    }
+}
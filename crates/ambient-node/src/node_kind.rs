@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Canonical role a node registers with (`node_type` on the wire).
+///
+/// Scheduling rules for each kind live here so the coordinator, mesh router
+/// and node agent agree on which work a node may take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    /// General-purpose WASM and computation worker.
+    #[serde(alias = "worker")]
+    Compute,
+    /// Publicly reachable edge node; relays mesh traffic for peers.
+    Gateway,
+    /// Persistent storage provider.
+    Storage,
+    /// Verifies proofs and consensus results.
+    Validator,
+    /// Exit node for `connect_only` sessions.
+    #[serde(alias = "open")]
+    OpenInternet,
+    /// Combined relay and compute node: takes both `open_internet` and
+    /// `compute` work.
+    #[serde(alias = "any")]
+    Universal,
+    /// Photonic FEEN hardware; only takes FEEN connectivity work.
+    FeenResonator,
+}
+
+impl NodeKind {
+    pub const ALL: [NodeKind; 7] = [
+        NodeKind::Compute,
+        NodeKind::Gateway,
+        NodeKind::Storage,
+        NodeKind::Validator,
+        NodeKind::OpenInternet,
+        NodeKind::Universal,
+        NodeKind::FeenResonator,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NodeKind::Compute => "compute",
+            NodeKind::Gateway => "gateway",
+            NodeKind::Storage => "storage",
+            NodeKind::Validator => "validator",
+            NodeKind::OpenInternet => "open_internet",
+            NodeKind::Universal => "universal",
+            NodeKind::FeenResonator => "feen_resonator",
+        }
+    }
+
+    /// Every `node_type` string that parses to this kind, canonical first.
+    /// The rest are legacy aliases from before the kinds were unified
+    /// (`any` for universal nodes, mesh-only `worker` and `open`); they are
+    /// accepted on input and normalized on write.
+    pub fn spellings(self) -> &'static [&'static str] {
+        match self {
+            NodeKind::Compute => &["compute", "worker"],
+            NodeKind::Gateway => &["gateway"],
+            NodeKind::Storage => &["storage"],
+            NodeKind::Validator => &["validator"],
+            NodeKind::OpenInternet => &["open_internet", "open"],
+            NodeKind::Universal => &["universal", "any"],
+            NodeKind::FeenResonator => &["feen_resonator"],
+        }
+    }
+
+    /// The kind `node_type` is a legacy alias of, or `None` when it is
+    /// canonical or unknown.
+    pub fn legacy_alias(node_type: &str) -> Option<Self> {
+        Self::parse(node_type).filter(|kind| kind.as_str() != node_type)
+    }
+
+    /// Parse a `node_type`, accepting every spelling in [`Self::spellings`].
+    pub fn parse(node_type: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.spellings().contains(&node_type))
+    }
+
+    /// Whether the node forwards traffic for others (connect sessions or
+    /// mesh relaying).
+    pub fn relays_traffic(self) -> bool {
+        matches!(
+            self,
+            NodeKind::Gateway | NodeKind::OpenInternet | NodeKind::Universal
+        )
+    }
+
+    /// Whether the node runs general WASM and computation workloads.
+    pub fn runs_compute(self) -> bool {
+        matches!(self, NodeKind::Compute | NodeKind::Universal)
+    }
+
+    /// Whether a node of this kind may take a task whose preferred kind is
+    /// `preferred`.  Universal nodes cover the relay and compute kinds but
+    /// not specialised hardware or storage/validator roles.
+    pub fn can_serve(self, preferred: NodeKind) -> bool {
+        self == preferred
+            || (self == NodeKind::Universal
+                && matches!(preferred, NodeKind::Compute | NodeKind::OpenInternet))
+    }
+
+    /// `node_type` strings of every kind that may serve `preferred`, for
+    /// matching stored rows in SQL.
+    pub fn serving_node_types(preferred: NodeKind) -> Vec<&'static str> {
+        Self::ALL
+            .into_iter()
+            .filter(|kind| kind.can_serve(preferred))
+            .flat_map(|kind| kind.spellings().iter().copied())
+            .collect()
+    }
+}
+
+impl fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NodeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or_else(|| format!("unknown node kind: {s}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_universal_serves_relay_and_compute_work_only() {
+        assert_eq!(NodeKind::parse("any"), Some(NodeKind::Universal));
+        assert_eq!(NodeKind::parse("universal"), Some(NodeKind::Universal));
+        assert_eq!(NodeKind::parse("worker"), Some(NodeKind::Compute));
+        assert_eq!(NodeKind::parse("relay"), None);
+        assert_eq!(NodeKind::legacy_alias("any"), Some(NodeKind::Universal));
+        assert_eq!(NodeKind::legacy_alias("universal"), None);
+        assert_eq!(NodeKind::legacy_alias("relay"), None);
+        for kind in NodeKind::ALL {
+            assert_eq!(NodeKind::parse(kind.as_str()), Some(kind));
+            assert!(kind.can_serve(kind));
+        }
+
+        assert!(NodeKind::Universal.relays_traffic() && NodeKind::Universal.runs_compute());
+        assert!(NodeKind::Universal.can_serve(NodeKind::OpenInternet));
+        assert!(!NodeKind::Universal.can_serve(NodeKind::FeenResonator));
+        assert!(!NodeKind::Gateway.can_serve(NodeKind::OpenInternet));
+
+        assert_eq!(
+            NodeKind::serving_node_types(NodeKind::OpenInternet),
+            vec!["open_internet", "open", "universal", "any"]
+        );
+        assert_eq!(
+            serde_json::from_str::<NodeKind>("\"any\"").unwrap(),
+            NodeKind::Universal
+        );
+    }
+}
@@ -33,8 +33,12 @@
 //! 2. Prints a curl command to stdout for operator inspection
 //! 3. Exposes /node/status endpoint with high-level, non-sensitive data
 //!
+//! A data-plane gateway serves `/gateway/usage` instead: its per-session
+//! relay counters ([`crate::gateway_usage::UsageSnapshot`]).
+//!
 //! The feature is **disabled by default** and must be explicitly enabled.
 
+use crate::gateway_usage::GatewayUsage;
 use crate::AmbientNode;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -153,7 +157,8 @@ impl ObservableNodeState {
 /// Local observability server (bound to 127.0.0.1 only)
 pub struct LocalObservabilityServer {
     port: u16,
-    state: ObservableNodeState,
+    state: Option<ObservableNodeState>,
+    gateway_usage: Option<GatewayUsage>,
 }
 
 impl LocalObservabilityServer {
@@ -166,7 +171,25 @@ impl LocalObservabilityServer {
     pub fn new(port: u16, node: Arc<RwLock<AmbientNode>>) -> Self {
         Self {
             port,
-            state: ObservableNodeState::new(node),
+            state: Some(ObservableNodeState::new(node)),
+            gateway_usage: None,
+        }
+    }
+
+    /// Server for a data-plane gateway, serving only `/gateway/usage`
+    pub fn for_gateway(port: u16, usage: GatewayUsage) -> Self {
+        Self {
+            port,
+            state: None,
+            gateway_usage: Some(usage),
+        }
+    }
+
+    fn path(&self) -> &'static str {
+        if self.state.is_some() {
+            "/node/status"
+        } else {
+            "/gateway/usage"
         }
     }
 
@@ -178,8 +201,9 @@ impl LocalObservabilityServer {
         println!();
         println!("  Inspect your node status:");
         println!(
-            "  \x1b[1;36mcurl http://127.0.0.1:{}/node/status | jq\x1b[0m",
-            self.port
+            "  \x1b[1;36mcurl http://127.0.0.1:{}{} | jq\x1b[0m",
+            self.port,
+            self.path()
         );
         println!();
         println!("  (Access limited to localhost only)");
@@ -188,24 +212,35 @@ impl LocalObservabilityServer {
 
     /// Run the observability server
     ///
-    /// This starts an HTTP server bound to 127.0.0.1 that serves the /node/status endpoint.
+    /// This starts an HTTP server bound to 127.0.0.1 that serves the /node/status endpoint
+    /// (or /gateway/usage for a gateway).
     /// The server runs until the process is terminated.
     pub async fn run(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         use axum::{extract::State, routing::get, Json, Router};
         use std::net::SocketAddr;
 
-        let state = self.state.clone();
+        let mut app = Router::new();
 
         // Define the /node/status endpoint
-        let app = Router::new()
-            .route(
-                "/node/status",
-                get(|State(state): State<ObservableNodeState>| async move {
-                    let status = state.status().await;
-                    Json(status)
-                }),
-            )
-            .with_state(state);
+        if let Some(state) = self.state.clone() {
+            app = app.merge(
+                Router::new()
+                    .route(
+                        "/node/status",
+                        get(|State(state): State<ObservableNodeState>| async move {
+                            let status = state.status().await;
+                            Json(status)
+                        }),
+                    )
+                    .with_state(state),
+            );
+        }
+        if let Some(usage) = self.gateway_usage.clone() {
+            app = app.route(
+                "/gateway/usage",
+                get(move || async move { Json(usage.snapshot()) }),
+            );
+        }
 
         // Bind STRICTLY to 127.0.0.1 (local-only access)
         let addr = SocketAddr::from(([127, 0, 0, 1], self.port));
@@ -266,5 +301,9 @@ mod tests {
 
         let server = LocalObservabilityServer::new(8080, node_arc);
         assert_eq!(server.port, 8080);
+        assert_eq!(server.path(), "/node/status");
+
+        let server = LocalObservabilityServer::for_gateway(8081, GatewayUsage::default());
+        assert_eq!(server.path(), "/gateway/usage");
     }
 }
@@ -0,0 +1,329 @@
+//! Destination-policy bundles cached on relay nodes
+//!
+//! The coordinator publishes every destination policy as one versioned
+//! bundle signed with its control key (audience [`POLICY_BUNDLE_AUDIENCE`]).
+//! A relay node keeps the newest bundle it has accepted on disk and checks
+//! `allowlist_domains` sessions against it, so a control-plane outage does
+//! not stop enforcement.  The bundle's [`OfflinePolicy`] bounds how long a
+//! cached copy is trusted: past `max_staleness_secs` every lookup fails and
+//! the gateway refuses destinations that need a policy.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::control_signature::{verify_signed_record, ControlVerifyError};
+use crate::offline::EgressPolicy;
+
+/// Audience of signed destination-policy bundles.
+pub const POLICY_BUNDLE_AUDIENCE: &str = "destination-policies";
+
+/// Default age after which a node should fetch a newer bundle.
+pub const DEFAULT_POLICY_REFRESH_SECS: u64 = 300;
+
+/// Default age after which a cached bundle is no longer enforced.
+pub const DEFAULT_POLICY_MAX_STALENESS_SECS: u64 = 24 * 3600;
+
+/// How long a node may keep enforcing a bundle without reaching the
+/// coordinator.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OfflinePolicy {
+    /// Bundle age after which it is stale: still enforced, but due for refresh.
+    pub refresh_after_secs: u64,
+    /// Bundle age after which it is expired and every lookup is refused.
+    pub max_staleness_secs: u64,
+}
+
+impl Default for OfflinePolicy {
+    fn default() -> Self {
+        Self {
+            refresh_after_secs: DEFAULT_POLICY_REFRESH_SECS,
+            max_staleness_secs: DEFAULT_POLICY_MAX_STALENESS_SECS,
+        }
+    }
+}
+
+impl OfflinePolicy {
+    pub fn freshness(&self, age_ms: i64) -> PolicyFreshness {
+        let age_secs = age_ms.max(0) as u64 / 1000;
+        if age_secs >= self.max_staleness_secs {
+            PolicyFreshness::Expired
+        } else if age_secs >= self.refresh_after_secs {
+            PolicyFreshness::Stale
+        } else {
+            PolicyFreshness::Fresh
+        }
+    }
+}
+
+/// Age class of a cached bundle under its [`OfflinePolicy`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyFreshness {
+    Fresh,
+    Stale,
+    Expired,
+}
+
+/// Body of a signed bundle.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PolicyBundle {
+    /// Increases with every policy change on the coordinator.
+    pub version: u64,
+    pub offline_policy: OfflinePolicy,
+    pub policies: Vec<EgressPolicy>,
+}
+
+/// Why a bundle or a lookup was refused.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PolicyBundleError {
+    #[error("policy bundle signature: {0}")]
+    Signature(#[from] ControlVerifyError),
+
+    #[error("signed record is for audience {0}, not a policy bundle")]
+    WrongAudience(String),
+
+    #[error("malformed policy bundle")]
+    Malformed,
+
+    #[error("policy bundle version {version} is older than cached version {cached}")]
+    Rollback { version: u64, cached: u64 },
+
+    #[error("no policy bundle cached")]
+    Missing,
+
+    #[error("cached policy bundle expired {age_secs} s after issue")]
+    Expired { age_secs: u64 },
+
+    #[error("destination policy {0} is not in the cached bundle")]
+    UnknownPolicy(String),
+
+    #[error("policy bundle cache I/O: {0}")]
+    Io(String),
+}
+
+/// The newest accepted bundle, optionally persisted to a file.
+#[derive(Debug, Clone)]
+pub struct PolicyBundleCache {
+    public_key_b64: String,
+    path: Option<PathBuf>,
+    bundle: Option<PolicyBundle>,
+    issued_at_ms: i64,
+}
+
+impl PolicyBundleCache {
+    /// An empty in-memory cache trusting bundles signed by `public_key_b64`.
+    pub fn new(public_key_b64: impl Into<String>) -> Self {
+        Self {
+            public_key_b64: public_key_b64.into(),
+            path: None,
+            bundle: None,
+            issued_at_ms: 0,
+        }
+    }
+
+    /// A cache persisted at `path`, loading the bundle already stored there.
+    pub fn open(
+        path: impl AsRef<Path>,
+        public_key_b64: impl Into<String>,
+    ) -> Result<Self, PolicyBundleError> {
+        let mut cache = Self::new(public_key_b64);
+        let path = path.as_ref().to_path_buf();
+        match std::fs::read(&path) {
+            Ok(data) => {
+                let signed: Value =
+                    serde_json::from_slice(&data).map_err(|_| PolicyBundleError::Malformed)?;
+                cache.accept(signed)?;
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(PolicyBundleError::Io(err.to_string())),
+        }
+        cache.path = Some(path);
+        Ok(cache)
+    }
+
+    /// Verify a signed bundle and make it current, writing it to the cache
+    /// file.  A newer copy of the cached version only renews its age; older
+    /// versions are refused.  Returns the cached version.
+    pub fn install(&mut self, signed: Value) -> Result<u64, PolicyBundleError> {
+        let to_store = signed.clone();
+        if !self.accept(signed)? {
+            return Ok(self.version().unwrap_or_default());
+        }
+        if let Some(path) = &self.path {
+            write_atomically(path, &to_store)
+                .map_err(|err| PolicyBundleError::Io(err.to_string()))?;
+        }
+        Ok(self.version().unwrap_or_default())
+    }
+
+    /// Returns whether the bundle replaced or renewed the cached one.
+    fn accept(&mut self, signed: Value) -> Result<bool, PolicyBundleError> {
+        let (body, control) = verify_signed_record(&self.public_key_b64, signed)?;
+        if control.node_id != POLICY_BUNDLE_AUDIENCE {
+            return Err(PolicyBundleError::WrongAudience(control.node_id));
+        }
+        let bundle: PolicyBundle =
+            serde_json::from_value(body).map_err(|_| PolicyBundleError::Malformed)?;
+
+        match self.version() {
+            Some(cached) if bundle.version < cached => Err(PolicyBundleError::Rollback {
+                version: bundle.version,
+                cached,
+            }),
+            Some(cached)
+                if bundle.version == cached && control.issued_at_ms <= self.issued_at_ms =>
+            {
+                Ok(false)
+            }
+            _ => {
+                self.bundle = Some(bundle);
+                self.issued_at_ms = control.issued_at_ms;
+                Ok(true)
+            }
+        }
+    }
+
+    pub fn version(&self) -> Option<u64> {
+        self.bundle.as_ref().map(|bundle| bundle.version)
+    }
+
+    pub fn bundle(&self) -> Option<&PolicyBundle> {
+        self.bundle.as_ref()
+    }
+
+    /// Freshness at `now_ms` on the coordinator's clock, if a bundle is cached.
+    pub fn freshness(&self, now_ms: i64) -> Option<PolicyFreshness> {
+        self.bundle
+            .as_ref()
+            .map(|bundle| bundle.offline_policy.freshness(now_ms - self.issued_at_ms))
+    }
+
+    /// Allowed destinations of `policy_id`, unless the bundle has expired.
+    pub fn allowed_destinations(
+        &self,
+        policy_id: &str,
+        now_ms: i64,
+    ) -> Result<&[String], PolicyBundleError> {
+        let bundle = self.bundle.as_ref().ok_or(PolicyBundleError::Missing)?;
+        if self.freshness(now_ms) == Some(PolicyFreshness::Expired) {
+            return Err(PolicyBundleError::Expired {
+                age_secs: (now_ms - self.issued_at_ms).max(0) as u64 / 1000,
+            });
+        }
+        bundle
+            .policies
+            .iter()
+            .find(|policy| policy.id == policy_id)
+            .map(|policy| policy.allowed_destinations.as_slice())
+            .ok_or_else(|| PolicyBundleError::UnknownPolicy(policy_id.to_string()))
+    }
+}
+
+fn write_atomically(path: &Path, signed: &Value) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_vec(signed)?)?;
+    std::fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_signature::ControlSigner;
+
+    fn signed_bundle(signer: &ControlSigner, version: u64, issued_at_ms: i64) -> Value {
+        let bundle = PolicyBundle {
+            version,
+            offline_policy: OfflinePolicy {
+                refresh_after_secs: 60,
+                max_staleness_secs: 3600,
+            },
+            policies: vec![EgressPolicy {
+                id: "policy_web_basic_v1".into(),
+                allowed_destinations: vec!["*.example.com".into()],
+            }],
+        };
+        signer.sign(
+            POLICY_BUNDLE_AUDIENCE,
+            serde_json::to_value(bundle).unwrap(),
+            issued_at_ms,
+        )
+    }
+
+    #[test]
+    fn test_cache_enforces_until_max_staleness() {
+        let signer = ControlSigner::generate().unwrap();
+        let mut cache = PolicyBundleCache::new(signer.public_key_b64());
+        assert_eq!(
+            cache.allowed_destinations("policy_web_basic_v1", 0),
+            Err(PolicyBundleError::Missing)
+        );
+
+        assert_eq!(cache.install(signed_bundle(&signer, 3, 900_000)), Ok(3));
+        assert_eq!(cache.install(signed_bundle(&signer, 3, 1_000_000)), Ok(3));
+        assert_eq!(cache.freshness(1_030_000), Some(PolicyFreshness::Fresh));
+        assert_eq!(cache.freshness(1_120_000), Some(PolicyFreshness::Stale));
+        assert_eq!(
+            cache
+                .allowed_destinations("policy_web_basic_v1", 1_120_000)
+                .unwrap(),
+            ["*.example.com"]
+        );
+        assert!(matches!(
+            cache.allowed_destinations("policy_web_basic_v1", 1_000_000 + 3_600_000),
+            Err(PolicyBundleError::Expired { age_secs: 3600 })
+        ));
+        assert_eq!(
+            cache.allowed_destinations("other", 1_030_000),
+            Err(PolicyBundleError::UnknownPolicy("other".into()))
+        );
+
+        assert_eq!(
+            cache.install(signed_bundle(&signer, 2, 2_000_000)),
+            Err(PolicyBundleError::Rollback {
+                version: 2,
+                cached: 3
+            })
+        );
+        let impostor = ControlSigner::generate().unwrap();
+        assert_eq!(
+            cache.install(signed_bundle(&impostor, 4, 2_000_000)),
+            Err(PolicyBundleError::Signature(
+                ControlVerifyError::BadSignature
+            ))
+        );
+        let other_audience = signer.sign("transparency", serde_json::json!({}), 2_000_000);
+        assert_eq!(
+            cache.install(other_audience),
+            Err(PolicyBundleError::WrongAudience("transparency".into()))
+        );
+    }
+
+    #[test]
+    fn test_cache_file_survives_restart() {
+        let signer = ControlSigner::generate().unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "ambient-policy-bundle-{}.json",
+            uuid::Uuid::new_v4()
+        ));
+
+        let mut cache = PolicyBundleCache::open(&path, signer.public_key_b64()).unwrap();
+        assert_eq!(cache.version(), None);
+        cache.install(signed_bundle(&signer, 7, 1_000_000)).unwrap();
+
+        let reopened = PolicyBundleCache::open(&path, signer.public_key_b64()).unwrap();
+        assert_eq!(reopened.version(), Some(7));
+        assert_eq!(
+            reopened.freshness(1_000_000 + 3_600_000),
+            Some(PolicyFreshness::Expired)
+        );
+
+        let other_key = ControlSigner::generate().unwrap();
+        assert!(PolicyBundleCache::open(&path, other_key.public_key_b64()).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::signature;
+use serde::{Deserialize, Serialize};
+
+use crate::sandbox_report::NodeSigningKey;
+
+/// A verifier node's outcome for one offloaded proof, returned as the result
+/// of a `verify_proof` task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofVerdict {
+    /// The `verify_proof` task the proof was handed out with.
+    pub task_id: String,
+    /// Stored proof named in the task's inputs.
+    pub proof_id: String,
+    pub valid: bool,
+}
+
+impl ProofVerdict {
+    /// Bytes the verdict signature covers.  The task and proof IDs are both
+    /// included so a verdict cannot be replayed for another proof.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        format!(
+            "ambient-vcp/proof-verdict/v1\n{}\n{}\n{}",
+            self.task_id, self.proof_id, self.valid
+        )
+        .into_bytes()
+    }
+}
+
+/// A [`ProofVerdict`] signed by the verifier node.  `signature` (base64
+/// Ed25519) covers [`ProofVerdict::canonical_bytes`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedProofVerdict {
+    pub verdict: ProofVerdict,
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl SignedProofVerdict {
+    pub fn sign(verdict: ProofVerdict, key: &NodeSigningKey) -> Self {
+        Self {
+            public_key: key.public_key_b64(),
+            signature: key.sign_b64(&verdict.canonical_bytes()),
+            verdict,
+        }
+    }
+
+    /// Whether the signature is valid for `public_key`.
+    pub fn verify(&self) -> bool {
+        let (Ok(public_key), Ok(signature)) = (
+            STANDARD.decode(&self.public_key),
+            STANDARD.decode(&self.signature),
+        ) else {
+            return false;
+        };
+        signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+            .verify(&self.verdict.canonical_bytes(), &signature)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_verdict_verifies_and_detects_tampering() {
+        let key = NodeSigningKey::generate().unwrap();
+        let verdict = ProofVerdict {
+            task_id: "task-1".into(),
+            proof_id: "proof-1".into(),
+            valid: true,
+        };
+        let signed = SignedProofVerdict::sign(verdict, &key);
+        let wire: SignedProofVerdict =
+            serde_json::from_str(&serde_json::to_string(&signed).unwrap()).unwrap();
+        assert!(wire.verify());
+
+        let mut flipped = wire.clone();
+        flipped.verdict.valid = false;
+        assert!(!flipped.verify());
+
+        let mut other_proof = wire.clone();
+        other_proof.verdict.proof_id = "proof-2".into();
+        assert!(!other_proof.verify());
+
+        let mut wrong_key = wire;
+        wrong_key.public_key = NodeSigningKey::generate().unwrap().public_key_b64();
+        assert!(!wrong_key.verify());
+    }
+}
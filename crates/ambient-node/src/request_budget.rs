@@ -0,0 +1,508 @@
+//! Per-endpoint timeout, retry and circuit-breaker budgets for HTTP calls
+//!
+//! Outbound calls fall into classes with different needs: a heartbeat
+//! should give up quickly and try again on the next beat, an artifact upload
+//! may take minutes.  [`RequestBudgets`] declares one [`RequestBudget`] per
+//! [`EndpointClass`].  It is part of [`crate::DeploymentSettings`], so
+//! profiles and `profile_overrides` tune it, e.g.
+//! `{"requests": {"upload": {"timeout_ms": 600000}}}`.
+//!
+//! [`BudgetedClient`] sends requests under those budgets:
+//!
+//! - each attempt is bounded by its class's `timeout_ms`
+//! - a failed attempt is retried, up to `max_attempts` in all, after a
+//!   full-jitter exponential backoff.  Requests that never reached the
+//!   server (connect errors) are always retried; others only when the method
+//!   is idempotent and the attempt timed out or got a `429` or `5xx`
+//! - per endpoint, `breaker_failures` failed calls in a row open a circuit
+//!   breaker that fails calls fast for `breaker_cooldown_ms`, after which a
+//!   single trial call closes it again or re-opens it
+//! - [`BudgetedClient::metrics`] counts attempts, retries, outcomes and
+//!   breaker rejections per endpoint
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// Connect timeout shared by every class; each class bounds whole attempts.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Kind of call a budget applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointClass {
+    /// Heartbeats and clock probes: short, superseded by the next one
+    Heartbeat,
+    /// Control-plane reads and writes: session lists, registration, auth
+    Control,
+    /// Artifact and result uploads
+    Upload,
+    /// External engines such as FEEN
+    Engine,
+}
+
+/// Timeout, retry and breaker settings for one endpoint class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestBudget {
+    /// Longest one attempt may take
+    pub timeout_ms: u64,
+    /// Attempts per call, including the first; at least 1
+    pub max_attempts: u32,
+    /// Backoff ceiling before the first retry; doubles per retry
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Failed calls in a row that open an endpoint's breaker; 0 disables it
+    pub breaker_failures: u32,
+    /// How long an open breaker fails calls fast
+    pub breaker_cooldown_ms: u64,
+}
+
+impl RequestBudget {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Full-jitter backoff before retry number `retry` (1-based): a delay
+    /// in `[0, min(max_backoff, initial_backoff * 2^(retry - 1))]` picked by
+    /// `random`.
+    pub fn backoff(&self, retry: u32, random: u64) -> Duration {
+        let ceiling = self
+            .initial_backoff_ms
+            .saturating_mul(1u64 << retry.saturating_sub(1).min(32))
+            .min(self.max_backoff_ms);
+        Duration::from_millis(random % ceiling.saturating_add(1))
+    }
+}
+
+/// One [`RequestBudget`] per [`EndpointClass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestBudgets {
+    pub heartbeat: RequestBudget,
+    pub control: RequestBudget,
+    pub upload: RequestBudget,
+    pub engine: RequestBudget,
+}
+
+impl Default for RequestBudgets {
+    fn default() -> Self {
+        Self {
+            heartbeat: RequestBudget {
+                timeout_ms: 5_000,
+                max_attempts: 2,
+                initial_backoff_ms: 200,
+                max_backoff_ms: 1_000,
+                breaker_failures: 5,
+                breaker_cooldown_ms: 30_000,
+            },
+            control: RequestBudget {
+                timeout_ms: 10_000,
+                max_attempts: 3,
+                initial_backoff_ms: 250,
+                max_backoff_ms: 4_000,
+                breaker_failures: 5,
+                breaker_cooldown_ms: 30_000,
+            },
+            upload: RequestBudget {
+                timeout_ms: 300_000,
+                max_attempts: 3,
+                initial_backoff_ms: 1_000,
+                max_backoff_ms: 30_000,
+                breaker_failures: 3,
+                breaker_cooldown_ms: 60_000,
+            },
+            engine: RequestBudget {
+                timeout_ms: 30_000,
+                max_attempts: 2,
+                initial_backoff_ms: 500,
+                max_backoff_ms: 5_000,
+                breaker_failures: 5,
+                breaker_cooldown_ms: 30_000,
+            },
+        }
+    }
+}
+
+impl RequestBudgets {
+    pub fn get(&self, class: EndpointClass) -> &RequestBudget {
+        match class {
+            EndpointClass::Heartbeat => &self.heartbeat,
+            EndpointClass::Control => &self.control,
+            EndpointClass::Upload => &self.upload,
+            EndpointClass::Engine => &self.engine,
+        }
+    }
+}
+
+/// Why a budgeted call failed without a response.
+#[derive(Debug, Error)]
+pub enum RequestError {
+    #[error("circuit breaker for {endpoint} is open; retry in {retry_in_ms} ms")]
+    BreakerOpen { endpoint: String, retry_in_ms: u64 },
+
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+/// State of an endpoint's circuit breaker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    #[default]
+    Closed,
+    Open,
+    /// Cooldown over; the next call is the trial
+    HalfOpen,
+}
+
+/// Counters for one endpoint, from [`BudgetedClient::metrics`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EndpointMetrics {
+    pub class: EndpointClass,
+    pub attempts: u64,
+    pub retries: u64,
+    pub successes: u64,
+    pub failures: u64,
+    pub breaker_rejections: u64,
+    pub breaker: BreakerState,
+}
+
+#[derive(Debug)]
+struct EndpointState {
+    metrics: EndpointMetrics,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+impl EndpointState {
+    fn new(class: EndpointClass) -> Self {
+        Self {
+            metrics: EndpointMetrics {
+                class,
+                attempts: 0,
+                retries: 0,
+                successes: 0,
+                failures: 0,
+                breaker_rejections: 0,
+                breaker: BreakerState::Closed,
+            },
+            consecutive_failures: 0,
+            open_until: None,
+            trial_in_flight: false,
+        }
+    }
+
+    fn breaker(&self, now: Instant) -> BreakerState {
+        match self.open_until {
+            None => BreakerState::Closed,
+            Some(until) if now < until => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+}
+
+/// HTTP client that applies [`RequestBudgets`] per endpoint.
+#[derive(Debug, Clone)]
+pub struct BudgetedClient {
+    client: reqwest::Client,
+    budgets: RequestBudgets,
+    endpoints: Arc<Mutex<HashMap<String, EndpointState>>>,
+    rng: SystemRandom,
+}
+
+impl BudgetedClient {
+    pub fn new(budgets: RequestBudgets) -> Result<Self, RequestError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(DEFAULT_CONNECT_TIMEOUT)
+            .build()?;
+        Ok(Self {
+            client,
+            budgets,
+            endpoints: Arc::new(Mutex::new(HashMap::new())),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// The underlying client, for building requests to [`Self::send`].
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    pub fn budgets(&self) -> &RequestBudgets {
+        &self.budgets
+    }
+
+    /// Counters of every endpoint called so far.
+    pub fn metrics(&self) -> BTreeMap<String, EndpointMetrics> {
+        let now = Instant::now();
+        let endpoints = self.endpoints.lock().expect("endpoint state lock poisoned");
+        endpoints
+            .iter()
+            .map(|(endpoint, state)| {
+                let mut metrics = state.metrics.clone();
+                metrics.breaker = state.breaker(now);
+                (endpoint.clone(), metrics)
+            })
+            .collect()
+    }
+
+    /// Send `request` to `endpoint` (a stable name such as `heartbeat`)
+    /// under `class`'s budget.  A final `429` or `5xx` response is returned
+    /// as is, for the caller to report, but counts as a failure.
+    pub async fn send(
+        &self,
+        class: EndpointClass,
+        endpoint: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, RequestError> {
+        let budget = *self.budgets.get(class);
+        let request = request.build()?;
+        let idempotent = request.method().is_idempotent();
+        self.admit(class, endpoint)?;
+
+        let max_attempts = budget.max_attempts.max(1);
+        let mut pending = Some(request);
+        let mut attempt = 1;
+        loop {
+            let mut current = pending.take().expect("a request is pending");
+            // Bodies that cannot be replayed (streams) get one attempt.
+            if attempt < max_attempts {
+                pending = current.try_clone();
+            }
+            *current.timeout_mut() = Some(budget.timeout());
+            self.count(endpoint, |metrics| metrics.attempts += 1);
+
+            let outcome = self.client.execute(current).await;
+            let retryable = match &outcome {
+                Ok(response) => {
+                    let status = response.status();
+                    let failed = status.is_server_error()
+                        || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                    if !failed {
+                        self.record(endpoint, &budget, true);
+                        return outcome.map_err(RequestError::from);
+                    }
+                    idempotent
+                }
+                Err(err) => err.is_connect() || (idempotent && err.is_timeout()),
+            };
+            if !retryable || pending.is_none() {
+                self.record(endpoint, &budget, false);
+                return outcome.map_err(RequestError::from);
+            }
+
+            let delay = budget.backoff(attempt, self.random());
+            debug!(
+                endpoint,
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "retrying request"
+            );
+            self.count(endpoint, |metrics| metrics.retries += 1);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    fn random(&self) -> u64 {
+        let mut bytes = [0u8; 8];
+        // A failed draw only loses the jitter.
+        let _ = self.rng.fill(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn count(&self, endpoint: &str, update: impl FnOnce(&mut EndpointMetrics)) {
+        let mut endpoints = self.endpoints.lock().expect("endpoint state lock poisoned");
+        if let Some(state) = endpoints.get_mut(endpoint) {
+            update(&mut state.metrics);
+        }
+    }
+
+    /// Let a call through unless the endpoint's breaker is open, or
+    /// half-open with its trial call already under way.
+    fn admit(&self, class: EndpointClass, endpoint: &str) -> Result<(), RequestError> {
+        let now = Instant::now();
+        let mut endpoints = self.endpoints.lock().expect("endpoint state lock poisoned");
+        let state = endpoints
+            .entry(endpoint.to_string())
+            .or_insert_with(|| EndpointState::new(class));
+        let reject = match state.breaker(now) {
+            BreakerState::Closed => false,
+            BreakerState::Open => true,
+            BreakerState::HalfOpen => std::mem::replace(&mut state.trial_in_flight, true),
+        };
+        if reject {
+            state.metrics.breaker_rejections += 1;
+            let retry_in_ms = state.open_until.map_or(0, |until| {
+                until.saturating_duration_since(now).as_millis() as u64
+            });
+            return Err(RequestError::BreakerOpen {
+                endpoint: endpoint.to_string(),
+                retry_in_ms,
+            });
+        }
+        Ok(())
+    }
+
+    fn record(&self, endpoint: &str, budget: &RequestBudget, success: bool) {
+        let mut endpoints = self.endpoints.lock().expect("endpoint state lock poisoned");
+        let Some(state) = endpoints.get_mut(endpoint) else {
+            return;
+        };
+        let trial = std::mem::take(&mut state.trial_in_flight);
+        if success {
+            state.metrics.successes += 1;
+            state.consecutive_failures = 0;
+            state.open_until = None;
+            return;
+        }
+        state.metrics.failures += 1;
+        state.consecutive_failures += 1;
+        if trial
+            || (budget.breaker_failures > 0
+                && state.consecutive_failures >= budget.breaker_failures)
+        {
+            warn!(
+                endpoint,
+                failures = state.consecutive_failures,
+                cooldown_ms = budget.breaker_cooldown_ms,
+                "circuit breaker opened"
+            );
+            state.open_until =
+                Some(Instant::now() + Duration::from_millis(budget.breaker_cooldown_ms));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answer each connection with the next status, then stop listening.
+    async fn serve(statuses: Vec<u16>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut head = Vec::new();
+                let mut byte = [0u8; 1];
+                while !head.ends_with(b"\r\n\r\n") {
+                    stream.read_exact(&mut byte).await.unwrap();
+                    head.push(byte[0]);
+                }
+                let response = format!(
+                    "HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    fn budgets(breaker_failures: u32, breaker_cooldown_ms: u64) -> RequestBudgets {
+        let budget = RequestBudget {
+            timeout_ms: 2_000,
+            max_attempts: 3,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 5,
+            breaker_failures,
+            breaker_cooldown_ms,
+        };
+        RequestBudgets {
+            heartbeat: budget,
+            control: budget,
+            upload: budget,
+            engine: budget,
+        }
+    }
+
+    #[test]
+    fn backoff_grows_within_its_ceiling() {
+        let budget = RequestBudgets::default().control;
+        for retry in 1..10 {
+            let ceiling = (250u64 << (retry - 1)).min(4_000);
+            assert_eq!(
+                budget.backoff(retry, u64::MAX - (u64::MAX % (ceiling + 1)) - 1),
+                Duration::from_millis(ceiling)
+            );
+            assert!(budget.backoff(retry, 12345).as_millis() as u64 <= ceiling);
+        }
+        assert_eq!(budget.backoff(1, 0), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn idempotent_calls_retry_server_errors() {
+        let url = serve(vec![503, 502, 200]).await;
+        let client = BudgetedClient::new(budgets(0, 0)).unwrap();
+        let response = client
+            .send(
+                EndpointClass::Control,
+                "sessions",
+                client.client().get(&url),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let metrics = &client.metrics()["sessions"];
+        assert_eq!(metrics.attempts, 3);
+        assert_eq!(metrics.retries, 2);
+        assert_eq!(metrics.successes, 1);
+        assert_eq!(metrics.failures, 0);
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_calls_return_the_first_server_error() {
+        let url = serve(vec![503, 200]).await;
+        let client = BudgetedClient::new(budgets(0, 0)).unwrap();
+        let response = client
+            .send(EndpointClass::Upload, "upload", client.client().post(&url))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 503);
+        let metrics = &client.metrics()["upload"];
+        assert_eq!((metrics.attempts, metrics.failures), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn breaker_opens_after_failed_calls_and_recovers_by_trial() {
+        // Three attempts per failed call, then one trial that succeeds.
+        let url = serve(vec![500, 500, 500, 500, 500, 500, 200]).await;
+        let client = BudgetedClient::new(budgets(2, 50)).unwrap();
+        let call = || {
+            client.send(
+                EndpointClass::Heartbeat,
+                "heartbeat",
+                client.client().get(&url),
+            )
+        };
+
+        assert_eq!(call().await.unwrap().status(), 500);
+        assert_eq!(client.metrics()["heartbeat"].breaker, BreakerState::Closed);
+        assert_eq!(call().await.unwrap().status(), 500);
+        assert_eq!(client.metrics()["heartbeat"].breaker, BreakerState::Open);
+        assert!(matches!(
+            call().await,
+            Err(RequestError::BreakerOpen { .. })
+        ));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(
+            client.metrics()["heartbeat"].breaker,
+            BreakerState::HalfOpen
+        );
+        assert_eq!(call().await.unwrap().status(), 200);
+        let metrics = &client.metrics()["heartbeat"];
+        assert_eq!(metrics.breaker, BreakerState::Closed);
+        assert_eq!(metrics.breaker_rejections, 1);
+        assert_eq!((metrics.successes, metrics.failures), (1, 2));
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{self, Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+use wasm_engine::SandboxReport;
+use zeroize::Zeroizing;
+
+/// Long-lived Ed25519 key a node signs its sandbox reports with.  The public
+/// half is published at registration as `signing_public_key`.
+pub struct NodeSigningKey {
+    seed: Zeroizing<[u8; 32]>,
+    key_pair: Ed25519KeyPair,
+}
+
+impl NodeSigningKey {
+    pub fn generate() -> Result<Self> {
+        let mut seed = Zeroizing::new([0u8; 32]);
+        SystemRandom::new()
+            .fill(seed.as_mut())
+            .map_err(|_| anyhow!("failed to generate signing key"))?;
+        Self::from_bytes(*seed)
+    }
+
+    pub fn from_bytes(seed: [u8; 32]) -> Result<Self> {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&seed)
+            .map_err(|_| anyhow!("invalid Ed25519 seed"))?;
+        Ok(Self {
+            seed: Zeroizing::new(seed),
+            key_pair,
+        })
+    }
+
+    /// Raw seed for the node's own key file; never send this anywhere.
+    pub fn to_bytes(&self) -> Zeroizing<[u8; 32]> {
+        self.seed.clone()
+    }
+
+    /// Base64 public key to publish at node registration.
+    pub fn public_key_b64(&self) -> String {
+        STANDARD.encode(self.key_pair.public_key().as_ref())
+    }
+
+    /// Base64 Ed25519 signature over `message`.
+    pub fn sign_b64(&self, message: &[u8]) -> String {
+        STANDARD.encode(self.key_pair.sign(message).as_ref())
+    }
+}
+
+impl std::fmt::Debug for NodeSigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeSigningKey")
+            .field("public_key", &self.public_key_b64())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`SandboxReport`] signed by the node that ran the execution, as
+/// attached to a task result.  `signature` (base64 Ed25519) covers
+/// [`SandboxReport::canonical_bytes`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedSandboxReport {
+    pub report: SandboxReport,
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl SignedSandboxReport {
+    pub fn sign(report: SandboxReport, key: &NodeSigningKey) -> Self {
+        let signature = key.key_pair.sign(&report.canonical_bytes());
+        Self {
+            public_key: key.public_key_b64(),
+            signature: STANDARD.encode(signature.as_ref()),
+            report,
+        }
+    }
+
+    /// Whether the signature is valid for `public_key`.
+    pub fn verify(&self) -> bool {
+        let (Ok(public_key), Ok(signature)) = (
+            STANDARD.decode(&self.public_key),
+            STANDARD.decode(&self.signature),
+        ) else {
+            return false;
+        };
+        signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+            .verify(&self.report.canonical_bytes(), &signature)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_engine::{
+        ExecutionTrace, SandboxAuditor, SandboxCapabilities, SandboxLimits, WasmResult,
+    };
+
+    fn report() -> SandboxReport {
+        let trace = ExecutionTrace::new("m".into(), "run".into(), vec![], vec![], 1, 1);
+        let result = WasmResult {
+            output: vec![],
+            execution_time_ms: 1,
+            gas_used: 1,
+            success: true,
+            error: None,
+            usage: Default::default(),
+        };
+        SandboxAuditor::new().finish(
+            &trace,
+            &result,
+            &SandboxLimits::default(),
+            &SandboxCapabilities::default(),
+        )
+    }
+
+    #[test]
+    fn test_signed_report_verifies_and_detects_tampering() {
+        let key = NodeSigningKey::generate().unwrap();
+        let restored = NodeSigningKey::from_bytes(*key.to_bytes()).unwrap();
+        assert_eq!(restored.public_key_b64(), key.public_key_b64());
+
+        let signed = SignedSandboxReport::sign(report(), &key);
+        let wire: SignedSandboxReport =
+            serde_json::from_str(&serde_json::to_string(&signed).unwrap()).unwrap();
+        assert!(wire.verify());
+
+        let mut tampered = wire.clone();
+        tampered.report.capabilities.network_access = true;
+        assert!(!tampered.verify());
+
+        let mut wrong_key = wire;
+        wrong_key.public_key = NodeSigningKey::generate().unwrap().public_key_b64();
+        assert!(!wrong_key.verify());
+    }
+}
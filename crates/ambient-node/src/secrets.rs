@@ -0,0 +1,299 @@
+use crate::offline::PersistentAuditQueue;
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Sha3_256};
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Domain separation for the sealing key derivation.
+const SEAL_KDF_INFO: &[u8] = b"ambient-vcp sealed task secret v1";
+
+/// Long-lived X25519 key a node publishes so requesters can seal task
+/// secrets to it.  Only the node can open them; the server relays ciphertext.
+pub struct NodeSecretKey {
+    secret: StaticSecret,
+}
+
+impl NodeSecretKey {
+    pub fn generate() -> Self {
+        Self {
+            secret: StaticSecret::random_from_rng(OsRng),
+        }
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self {
+            secret: StaticSecret::from(bytes),
+        }
+    }
+
+    /// Raw key bytes for the node's own key file; never send these anywhere.
+    pub fn to_bytes(&self) -> Zeroizing<[u8; 32]> {
+        Zeroizing::new(self.secret.to_bytes())
+    }
+
+    /// Base64 public key to publish at node registration.
+    pub fn public_key_b64(&self) -> String {
+        STANDARD.encode(PublicKey::from(&self.secret).as_bytes())
+    }
+}
+
+impl std::fmt::Debug for NodeSecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeSecretKey")
+            .field("public_key", &self.public_key_b64())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A secret encrypted to one node's public key (all fields base64).
+///
+/// The secret name is bound as associated data, so a ciphertext cannot be
+/// replayed under a different name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SealedSecret {
+    pub name: String,
+    pub ephemeral_public_key: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// Plaintext secret value, zeroed when dropped and redacted in `Debug`.
+pub struct SecretValue(Zeroizing<Vec<u8>>);
+
+impl SecretValue {
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Take the zeroizing buffer, e.g. to mount it into the sandbox.
+    pub fn into_inner(self) -> Zeroizing<Vec<u8>> {
+        self.0
+    }
+}
+
+impl std::fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretValue(<{} bytes redacted>)", self.0.len())
+    }
+}
+
+fn sealing_cipher(shared_secret: &[u8; 32], ephemeral_public: &[u8; 32]) -> ChaCha20Poly1305 {
+    let hkdf = Hkdf::<Sha256>::new(Some(ephemeral_public), shared_secret);
+    let mut key = Zeroizing::new([0u8; 32]);
+    hkdf.expand(SEAL_KDF_INFO, key.as_mut())
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+}
+
+fn decode_array<const N: usize>(field: &str, value: &str) -> Result<[u8; N]> {
+    let bytes = STANDARD
+        .decode(value)
+        .with_context(|| format!("{field} is not valid base64"))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("{field} must decode to {N} bytes"))
+}
+
+/// Seal `plaintext` to a node's base64 public key (requester side).
+pub fn seal_secret(
+    node_public_key_b64: &str,
+    name: impl Into<String>,
+    plaintext: &[u8],
+) -> Result<SealedSecret> {
+    let name = name.into();
+    let node_public = PublicKey::from(decode_array::<32>("node public key", node_public_key_b64)?);
+
+    let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(&node_public);
+
+    let cipher = sealing_cipher(shared.as_bytes(), ephemeral_public.as_bytes());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("failed to seal secret"))?;
+
+    Ok(SealedSecret {
+        name,
+        ephemeral_public_key: STANDARD.encode(ephemeral_public.as_bytes()),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    })
+}
+
+/// Open a secret sealed to this node (node side).
+pub fn open_secret(key: &NodeSecretKey, sealed: &SealedSecret) -> Result<SecretValue> {
+    let ephemeral_public =
+        decode_array::<32>("ephemeral_public_key", &sealed.ephemeral_public_key)?;
+    let nonce = decode_array::<12>("nonce", &sealed.nonce)?;
+    let ciphertext = STANDARD
+        .decode(&sealed.ciphertext)
+        .context("ciphertext is not valid base64")?;
+
+    let shared = key
+        .secret
+        .diffie_hellman(&PublicKey::from(ephemeral_public));
+    let cipher = sealing_cipher(shared.as_bytes(), &ephemeral_public);
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: sealed.name.as_bytes(),
+            },
+        )
+        .map_err(|_| {
+            anyhow!(
+                "secret {} could not be opened with this node key",
+                sealed.name
+            )
+        })?;
+
+    Ok(SecretValue(Zeroizing::new(plaintext)))
+}
+
+/// Secrets opened for one task; values are zeroed when dropped.
+///
+/// Mount and wipe events are appended to the node's audit journal with the
+/// secret names and a digest of the name set only — never values — so the
+/// journal can attest that secrets were not persisted.
+#[derive(Debug, Default)]
+pub struct TaskSecrets {
+    task_id: String,
+    values: Vec<(String, SecretValue)>,
+}
+
+impl TaskSecrets {
+    /// Open every sealed secret for `task_id`; fails if any cannot be opened.
+    pub fn open_all(
+        key: &NodeSecretKey,
+        task_id: impl Into<String>,
+        sealed: &[SealedSecret],
+    ) -> Result<Self> {
+        let values = sealed
+            .iter()
+            .map(|secret| Ok((secret.name.clone(), open_secret(key, secret)?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            task_id: task_id.into(),
+            values,
+        })
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.values.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SecretValue> {
+        self.values
+            .iter()
+            .find(|(secret_name, _)| secret_name == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Hand the plaintext buffers to the sandbox as `(name, value)` pairs.
+    pub fn into_mounts(mut self) -> Vec<(String, Zeroizing<Vec<u8>>)> {
+        std::mem::take(&mut self.values)
+            .into_iter()
+            .map(|(name, value)| (name, value.into_inner()))
+            .collect()
+    }
+
+    /// Journal that secrets were mounted for execution.
+    pub fn journal_mounted(&self, journal: &PersistentAuditQueue, at: u64) -> std::io::Result<()> {
+        journal
+            .append(
+                "task_secrets_mounted",
+                &self.task_id,
+                self.journal_details(),
+                0,
+                at,
+            )
+            .map(|_| ())
+    }
+
+    /// Zero every value and journal the wipe.
+    pub fn wipe(mut self, journal: &PersistentAuditQueue, at: u64) -> std::io::Result<()> {
+        let details = self.journal_details();
+        for (_, value) in self.values.iter_mut() {
+            value.0.zeroize();
+        }
+        self.values.clear();
+        journal
+            .append("task_secrets_wiped", &self.task_id, details, 0, at)
+            .map(|_| ())
+    }
+
+    fn journal_details(&self) -> String {
+        let mut names: Vec<&str> = self.names();
+        names.sort_unstable();
+        let digest = Sha3_256::digest(names.join("\n").as_bytes());
+        format!(
+            "names={} names_sha3={:x} persisted=false",
+            names.join(","),
+            digest
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open_round_trip() {
+        let key = NodeSecretKey::generate();
+        let sealed = seal_secret(&key.public_key_b64(), "db_token", b"s3cr3t").unwrap();
+        assert_ne!(sealed.ciphertext, STANDARD.encode(b"s3cr3t"));
+
+        let opened = open_secret(&key, &sealed).unwrap();
+        assert_eq!(opened.expose(), b"s3cr3t");
+        assert!(!format!("{opened:?}").contains("s3cr3t"));
+    }
+
+    #[test]
+    fn test_open_rejects_wrong_key_and_renamed_secret() {
+        let key = NodeSecretKey::generate();
+        let other = NodeSecretKey::generate();
+        let mut sealed = seal_secret(&key.public_key_b64(), "db_token", b"s3cr3t").unwrap();
+
+        assert!(open_secret(&other, &sealed).is_err());
+
+        sealed.name = "other_name".to_string();
+        assert!(open_secret(&key, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_journal_records_names_but_not_values() {
+        let dir = std::env::temp_dir().join(format!("secrets-journal-{}", uuid::Uuid::new_v4()));
+        let journal = PersistentAuditQueue::new(dir.join("journal.log"));
+        let key = NodeSecretKey::generate();
+        let sealed = vec![seal_secret(&key.public_key_b64(), "api_key", b"hunter2").unwrap()];
+
+        let secrets = TaskSecrets::open_all(&key, "task-1", &sealed).unwrap();
+        secrets.journal_mounted(&journal, 10).unwrap();
+        secrets.wipe(&journal, 11).unwrap();
+
+        let records = journal.read_all().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].event_type, "task_secrets_wiped");
+        assert!(records.iter().all(|r| !r.details.contains("hunter2")));
+        assert!(journal.verify_chain().unwrap());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
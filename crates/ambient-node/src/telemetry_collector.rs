@@ -0,0 +1,475 @@
+//! Host telemetry collection.
+//!
+//! [`TelemetryCollector`] fills a [`TelemetrySample`] from the host instead
+//! of configured constants, reading Linux procfs and sysfs directly:
+//!
+//! - CPU usage from `/proc/stat`, over the time since the previous sample
+//!   (since boot for the first one)
+//! - memory usage from `/proc/meminfo` (`MemTotal - MemAvailable`)
+//! - temperature as the hottest `/sys/class/thermal` zone, falling back to
+//!   `/sys/class/hwmon` sensors
+//! - power from RAPL package energy counters under `/sys/class/powercap`,
+//!   then a discharging battery's `power_now`, then an optional linear
+//!   estimate from CPU usage
+//! - bandwidth as the fastest link speed of an up, non-loopback interface
+//!   (`/sys/class/net/*/speed`)
+//! - latency as a moving average of TCP connect times to configured targets
+//!
+//! A reading the host does not expose is left at zero.  On other platforms
+//! only latency is measured.  [`TelemetryCollector::spawn`] samples on an
+//! interval, feeds [`AmbientNode::ingest_telemetry`] and publishes each
+//! sample on a watch channel for the heartbeat sender
+//! ([`telemetry_state`](crate::telemetry_state)).
+
+use crate::{AmbientNode, TelemetrySample};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinHandle;
+
+/// Default time between samples.
+pub const DEFAULT_TELEMETRY_INTERVAL_SECS: u64 = 10;
+
+/// Weight of the newest probe in the latency moving average.
+const LATENCY_SMOOTHING: f64 = 0.3;
+
+/// Linear power model for hosts without power sensors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerEstimate {
+    /// Draw at 0% CPU.
+    pub idle_watts: f64,
+    /// Draw at 100% CPU.
+    pub max_watts: f64,
+}
+
+impl PowerEstimate {
+    pub fn watts(&self, cpu_usage_percent: f64) -> f64 {
+        let load = (cpu_usage_percent / 100.0).clamp(0.0, 1.0);
+        self.idle_watts + (self.max_watts - self.idle_watts) * load
+    }
+}
+
+/// What [`TelemetryCollector`] samples and how often.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryCollectorConfig {
+    pub interval: Duration,
+    /// `host:port` targets whose TCP connect time is the latency reading,
+    /// e.g. the control plane.  Empty leaves latency at zero.
+    pub latency_targets: Vec<String>,
+    pub latency_timeout: Duration,
+    /// Used when the host reports no power reading.
+    pub power_estimate: Option<PowerEstimate>,
+}
+
+impl Default for TelemetryCollectorConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(DEFAULT_TELEMETRY_INTERVAL_SECS),
+            latency_targets: Vec::new(),
+            latency_timeout: Duration::from_secs(2),
+            power_estimate: None,
+        }
+    }
+}
+
+/// Cumulative CPU time from the first line of `/proc/stat`, in clock ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+/// Samples host telemetry; see the module docs for the sources.
+#[derive(Debug)]
+pub struct TelemetryCollector {
+    config: TelemetryCollectorConfig,
+    /// Filesystem root procfs and sysfs are read under; `/` except in tests.
+    root: PathBuf,
+    last_cpu: Option<CpuTimes>,
+    last_energy: Option<(Instant, u64)>,
+    latency_ms: Option<f64>,
+}
+
+impl TelemetryCollector {
+    pub fn new(config: TelemetryCollectorConfig) -> Self {
+        Self::with_root(config, "/")
+    }
+
+    /// A collector reading `proc/` and `sys/` under `root`.
+    pub fn with_root(config: TelemetryCollectorConfig, root: impl Into<PathBuf>) -> Self {
+        Self {
+            config,
+            root: root.into(),
+            last_cpu: None,
+            last_energy: None,
+            latency_ms: None,
+        }
+    }
+
+    pub fn config(&self) -> &TelemetryCollectorConfig {
+        &self.config
+    }
+
+    /// Take one sample.
+    pub async fn sample(&mut self) -> TelemetrySample {
+        let cpu_usage_percent = self.cpu_usage_percent();
+        let power_watts = self
+            .rapl_watts()
+            .or_else(|| battery_watts(&self.root))
+            .or_else(|| {
+                self.config
+                    .power_estimate
+                    .map(|estimate| estimate.watts(cpu_usage_percent))
+            })
+            .unwrap_or(0.0);
+
+        TelemetrySample {
+            bandwidth_mbps: link_speed_mbps(&self.root).unwrap_or(0.0),
+            avg_latency_ms: self.probe_latency().await.unwrap_or(0.0),
+            cpu_usage_percent,
+            memory_usage_percent: read(&self.root, "proc/meminfo")
+                .and_then(|meminfo| memory_usage_percent(&meminfo))
+                .unwrap_or(0.0),
+            temperature_c: temperature_c(&self.root).unwrap_or(0.0),
+            power_watts,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            ..TelemetrySample::default()
+        }
+    }
+
+    /// Sample every interval until the task is aborted.  Each sample is
+    /// ingested by `node` and published on the returned channel, which
+    /// starts with the first sample.
+    pub async fn spawn(
+        mut self,
+        node: Arc<RwLock<AmbientNode>>,
+    ) -> (JoinHandle<()>, watch::Receiver<TelemetrySample>) {
+        let first = self.sample().await;
+        node.write().await.ingest_telemetry(first.clone());
+        let (tx, rx) = watch::channel(first);
+
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let sample = self.sample().await;
+                node.write().await.ingest_telemetry(sample.clone());
+                tracing::debug!(
+                    cpu = sample.cpu_usage_percent,
+                    memory = sample.memory_usage_percent,
+                    temperature = sample.temperature_c,
+                    power = sample.power_watts,
+                    latency_ms = sample.avg_latency_ms,
+                    "Collected telemetry"
+                );
+                tx.send_replace(sample);
+            }
+        });
+        (handle, rx)
+    }
+
+    fn cpu_usage_percent(&mut self) -> f64 {
+        let Some(now) = read(&self.root, "proc/stat").and_then(|stat| parse_cpu_times(&stat))
+        else {
+            return 0.0;
+        };
+        let since = self
+            .last_cpu
+            .replace(now)
+            .unwrap_or(CpuTimes { busy: 0, total: 0 });
+        let total = now.total.saturating_sub(since.total);
+        if total == 0 {
+            return 0.0;
+        }
+        (now.busy.saturating_sub(since.busy) as f64 / total as f64 * 100.0).clamp(0.0, 100.0)
+    }
+
+    /// Package power from the RAPL energy counters, averaged since the
+    /// previous sample.  `None` on the first sample.
+    fn rapl_watts(&mut self) -> Option<f64> {
+        let energy_uj = rapl_energy_uj(&self.root)?;
+        let now = Instant::now();
+        let (then, previous_uj) = self.last_energy.replace((now, energy_uj))?;
+        let elapsed = now.duration_since(then).as_secs_f64();
+        // A counter that wrapped reads as a drop; skip that interval.
+        (elapsed > 0.0 && energy_uj >= previous_uj)
+            .then(|| (energy_uj - previous_uj) as f64 / 1_000_000.0 / elapsed)
+    }
+
+    /// Mean connect time to the reachable targets, folded into the moving
+    /// average.  Keeps the previous average when none answer.
+    async fn probe_latency(&mut self) -> Option<f64> {
+        let mut times = Vec::new();
+        for target in &self.config.latency_targets {
+            let started = Instant::now();
+            match tokio::time::timeout(self.config.latency_timeout, TcpStream::connect(target))
+                .await
+            {
+                Ok(Ok(_)) => times.push(started.elapsed().as_secs_f64() * 1000.0),
+                Ok(Err(err)) => tracing::debug!(target, "Latency probe failed: {err}"),
+                Err(_) => tracing::debug!(target, "Latency probe timed out"),
+            }
+        }
+        if !times.is_empty() {
+            let mean = times.iter().sum::<f64>() / times.len() as f64;
+            self.latency_ms = Some(match self.latency_ms {
+                Some(average) => average + LATENCY_SMOOTHING * (mean - average),
+                None => mean,
+            });
+        }
+        self.latency_ms
+    }
+}
+
+fn read(root: &Path, path: &str) -> Option<String> {
+    std::fs::read_to_string(root.join(path)).ok()
+}
+
+/// Entries of a sysfs class directory, sorted by name.
+fn class_entries(root: &Path, class: &str) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(root.join("sys/class").join(class))
+        .map(|dir| dir.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn parse_cpu_times(stat: &str) -> Option<CpuTimes> {
+    let fields: Vec<u64> = stat
+        .lines()
+        .find(|line| line.starts_with("cpu "))?
+        .split_whitespace()
+        .skip(1)
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    // user nice system idle iowait irq softirq steal; guest time is already
+    // counted in user and nice.
+    let total: u64 = fields.iter().take(8).sum();
+    let idle = fields.get(3).copied()? + fields.get(4).copied().unwrap_or(0);
+    Some(CpuTimes {
+        busy: total.saturating_sub(idle),
+        total,
+    })
+}
+
+fn memory_usage_percent(meminfo: &str) -> Option<f64> {
+    let field = |name: &str| -> Option<f64> {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    };
+    let total = field("MemTotal")?;
+    let available = field("MemAvailable")?;
+    (total > 0.0).then(|| ((total - available) / total * 100.0).clamp(0.0, 100.0))
+}
+
+/// Hottest thermal zone, else hottest hwmon sensor, in °C.
+fn temperature_c(root: &Path) -> Option<f64> {
+    let hottest = |readings: Vec<f64>| readings.into_iter().reduce(f64::max);
+    let zones = class_entries(root, "thermal")
+        .into_iter()
+        .filter(|zone| {
+            zone.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("thermal_zone"))
+        })
+        .filter_map(|zone| read_number(&zone.join("temp")))
+        .map(|millidegrees| millidegrees / 1000.0)
+        .collect();
+    hottest(zones).or_else(|| {
+        let sensors = class_entries(root, "hwmon")
+            .into_iter()
+            .flat_map(|hwmon| {
+                std::fs::read_dir(hwmon)
+                    .map(|dir| dir.flatten().map(|entry| entry.path()).collect::<Vec<_>>())
+                    .unwrap_or_default()
+            })
+            .filter(|path| {
+                path.file_name().is_some_and(|name| {
+                    let name = name.to_string_lossy();
+                    name.starts_with("temp") && name.ends_with("_input")
+                })
+            })
+            .filter_map(|path| read_number(&path))
+            .map(|millidegrees| millidegrees / 1000.0)
+            .collect();
+        hottest(sensors)
+    })
+}
+
+/// Summed energy of the top-level RAPL domains (`intel-rapl:N`, one per
+/// package), in microjoules.
+fn rapl_energy_uj(root: &Path) -> Option<u64> {
+    let packages: Vec<u64> = class_entries(root, "powercap")
+        .into_iter()
+        .filter(|domain| {
+            domain.file_name().is_some_and(|name| {
+                let name = name.to_string_lossy();
+                name.starts_with("intel-rapl:") && name.matches(':').count() == 1
+            })
+        })
+        .filter_map(|domain| read_number(&domain.join("energy_uj")))
+        .map(|energy| energy as u64)
+        .collect();
+    (!packages.is_empty()).then(|| packages.iter().sum())
+}
+
+/// Draw of discharging batteries, in watts.
+fn battery_watts(root: &Path) -> Option<f64> {
+    let draws: Vec<f64> = class_entries(root, "power_supply")
+        .into_iter()
+        .filter(|supply| {
+            read(supply, "status").is_some_and(|status| status.trim() == "Discharging")
+        })
+        .filter_map(|supply| read_number(&supply.join("power_now")))
+        .map(|microwatts| microwatts / 1_000_000.0)
+        .collect();
+    (!draws.is_empty()).then(|| draws.iter().sum())
+}
+
+/// Fastest link speed of an up, non-loopback interface, in Mbps.
+fn link_speed_mbps(root: &Path) -> Option<f64> {
+    class_entries(root, "net")
+        .into_iter()
+        .filter(|iface| iface.file_name().is_some_and(|name| name != "lo"))
+        .filter(|iface| read(iface, "operstate").is_some_and(|state| state.trim() == "up"))
+        .filter_map(|iface| read_number(&iface.join("speed")))
+        .filter(|speed| *speed > 0.0)
+        .reduce(f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn fake_host() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("telemetry-{}", uuid::Uuid::new_v4()));
+        write(
+            &root,
+            "proc/stat",
+            "cpu  100 0 100 700 100 0 0 0 0 0\ncpu0 100 0 100 700 100 0 0 0 0 0\n",
+        );
+        write(
+            &root,
+            "proc/meminfo",
+            "MemTotal:       8000000 kB\nMemFree:        1000000 kB\nMemAvailable:   2000000 kB\n",
+        );
+        write(&root, "sys/class/thermal/thermal_zone0/temp", "45000\n");
+        write(&root, "sys/class/thermal/thermal_zone1/temp", "61500\n");
+        write(&root, "sys/class/net/lo/operstate", "unknown\n");
+        write(&root, "sys/class/net/eth0/operstate", "up\n");
+        write(&root, "sys/class/net/eth0/speed", "1000\n");
+        write(&root, "sys/class/net/wlan0/operstate", "down\n");
+        write(&root, "sys/class/net/wlan0/speed", "10000\n");
+        root
+    }
+
+    #[tokio::test]
+    async fn samples_cpu_memory_temperature_and_link_speed_from_procfs() {
+        let root = fake_host();
+        let mut collector = TelemetryCollector::with_root(
+            TelemetryCollectorConfig {
+                power_estimate: Some(PowerEstimate {
+                    idle_watts: 10.0,
+                    max_watts: 110.0,
+                }),
+                ..TelemetryCollectorConfig::default()
+            },
+            &root,
+        );
+
+        let first = collector.sample().await;
+        assert!((first.cpu_usage_percent - 20.0).abs() < 1e-9, "since boot");
+        assert!((first.memory_usage_percent - 75.0).abs() < 1e-9);
+        assert_eq!(first.temperature_c, 61.5);
+        assert_eq!(first.bandwidth_mbps, 1000.0);
+        assert!((first.power_watts - 30.0).abs() < 1e-9, "estimated");
+        assert_eq!(first.avg_latency_ms, 0.0);
+
+        // 300 busy of 400 ticks since the first sample.
+        write(&root, "proc/stat", "cpu  300 0 200 750 150 0 0 0 0 0\n");
+        let second = collector.sample().await;
+        assert!((second.cpu_usage_percent - 75.0).abs() < 1e-9);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn prefers_measured_power_and_leaves_missing_readings_at_zero() {
+        let root = std::env::temp_dir().join(format!("telemetry-{}", uuid::Uuid::new_v4()));
+        write(&root, "sys/class/power_supply/BAT0/status", "Discharging\n");
+        write(&root, "sys/class/power_supply/BAT0/power_now", "12500000\n");
+        write(&root, "sys/class/hwmon/hwmon0/temp1_input", "52000\n");
+        let mut collector = TelemetryCollector::with_root(
+            TelemetryCollectorConfig {
+                power_estimate: Some(PowerEstimate {
+                    idle_watts: 10.0,
+                    max_watts: 110.0,
+                }),
+                ..TelemetryCollectorConfig::default()
+            },
+            &root,
+        );
+
+        let sample = collector.sample().await;
+        assert_eq!(sample.power_watts, 12.5);
+        assert_eq!(sample.temperature_c, 52.0);
+        assert_eq!(sample.cpu_usage_percent, 0.0);
+        assert_eq!(sample.memory_usage_percent, 0.0);
+        assert_eq!(sample.bandwidth_mbps, 0.0);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn measures_latency_and_feeds_the_node() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                drop(stream);
+            }
+        });
+
+        let root = fake_host();
+        let collector = TelemetryCollector::with_root(
+            TelemetryCollectorConfig {
+                latency_targets: vec![target],
+                ..TelemetryCollectorConfig::default()
+            },
+            &root,
+        );
+        let node = Arc::new(RwLock::new(AmbientNode::new(
+            crate::NodeId::new("collector-node", "eu-west", "compute").unwrap(),
+            crate::SafetyPolicy::default(),
+        )));
+
+        let (handle, samples) = collector.spawn(Arc::clone(&node)).await;
+        handle.abort();
+        let published = samples.borrow().clone();
+        assert!(published.avg_latency_ms > 0.0);
+        assert_eq!(node.read().await.telemetry.memory_usage_percent, 75.0);
+        assert!(crate::telemetry_state(&published).contains_key("avg_latency_ms"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// HTTP header carrying a [`TraceParent`] (W3C Trace Context).
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// A W3C `traceparent` value linking node-agent work to the control-plane
+/// trace that produced it.
+///
+/// The coordinator returns one on every response and stores one per task;
+/// a node agent sends `traceparent: <child>` on requests made for that task
+/// so task assignment and execution show up as one trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceParent {
+    pub trace_id: [u8; 16],
+    /// Span the next hop should treat as its parent.
+    pub parent_id: [u8; 8],
+    pub sampled: bool,
+}
+
+impl TraceParent {
+    /// Start a new sampled trace.
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: *uuid::Uuid::new_v4().as_bytes(),
+            parent_id: random_span_id(),
+            sampled: true,
+        }
+    }
+
+    /// Same trace with a fresh span id, for a request made on its behalf.
+    pub fn child(&self) -> Self {
+        Self {
+            parent_id: random_span_id(),
+            ..*self
+        }
+    }
+
+    /// Parse a `traceparent` header.  Versions other than `00` are read by
+    /// their first four fields, as the spec requires; all-zero ids and
+    /// malformed values return `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut fields = value.trim().split('-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let parent_id = fields.next()?;
+        let flags = fields.next()?;
+        if version.len() != 2 || version == "ff" || (version == "00" && fields.next().is_some()) {
+            return None;
+        }
+        u8::from_str_radix(version, 16).ok()?;
+
+        let trace_id: [u8; 16] = decode_lower_hex(trace_id)?.try_into().ok()?;
+        let parent_id: [u8; 8] = decode_lower_hex(parent_id)?.try_into().ok()?;
+        let flags: [u8; 1] = decode_lower_hex(flags)?.try_into().ok()?;
+        if trace_id == [0; 16] || parent_id == [0; 8] {
+            return None;
+        }
+
+        Some(Self {
+            trace_id,
+            parent_id,
+            sampled: flags[0] & 0x01 != 0,
+        })
+    }
+
+    pub fn trace_id_hex(&self) -> String {
+        hex::encode(self.trace_id)
+    }
+
+    pub fn parent_id_hex(&self) -> String {
+        hex::encode(self.parent_id)
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id_hex(),
+            self.parent_id_hex(),
+            u8::from(self.sampled)
+        )
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or_else(|| format!("invalid traceparent: {s}"))
+    }
+}
+
+fn random_span_id() -> [u8; 8] {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    let mut span_id = [0; 8];
+    span_id.copy_from_slice(&bytes[..8]);
+    span_id
+}
+
+fn decode_lower_hex(value: &str) -> Option<Vec<u8>> {
+    if value.bytes().any(|b| b.is_ascii_uppercase()) {
+        return None;
+    }
+    hex::decode(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_round_trips_and_rejects_invalid_values() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let parent = TraceParent::parse(header).unwrap();
+        assert!(parent.sampled);
+        assert_eq!(parent.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parent.to_string(), header);
+
+        let child = parent.child();
+        assert_eq!(child.trace_id, parent.trace_id);
+        assert_ne!(child.parent_id, parent.parent_id);
+
+        // Future versions may append fields.
+        assert!(TraceParent::parse(&format!("01{}-extra", &header[2..])).is_some());
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceParent::parse(invalid), None, "{invalid}");
+        }
+
+        let root = TraceParent::new_root();
+        assert_eq!(TraceParent::parse(&root.to_string()), Some(root));
+    }
+}
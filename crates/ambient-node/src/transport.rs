@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// ALPN identifiers offered when connecting to the coordinator, most
+/// preferred first.
+pub const ALPN_H3: &[u8] = b"h3";
+pub const ALPN_H2: &[u8] = b"h2";
+pub const ALPN_HTTP11: &[u8] = b"http/1.1";
+
+/// First HTTP/3 retry delay after a failure; doubles up to `MAX_H3_BACKOFF`.
+const INITIAL_H3_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_H3_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// Wire protocol used for control-plane requests (heartbeats, results).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlPlaneProtocol {
+    /// HTTP/3 over QUIC; avoids TCP head-of-line blocking on lossy links.
+    Http3,
+    /// HTTP/2 or HTTP/1.1 over TCP, negotiated by TLS ALPN.
+    Tcp,
+}
+
+impl ControlPlaneProtocol {
+    /// ALPN list to offer in the TLS handshake for this transport.
+    pub fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        match self {
+            Self::Http3 => vec![ALPN_H3.to_vec()],
+            Self::Tcp => vec![ALPN_H2.to_vec(), ALPN_HTTP11.to_vec()],
+        }
+    }
+}
+
+/// Extract the HTTP/3 port from an `Alt-Svc` header such as
+/// `h3=":8443"; ma=86400, h2=":443"`.  Alternatives on another host are
+/// ignored, since the node only trusts the coordinator it was configured with.
+pub fn parse_alt_svc_h3_port(header: &str) -> Option<u16> {
+    header.split(',').find_map(|alternative| {
+        let authority = alternative
+            .split(';')
+            .next()?
+            .trim()
+            .strip_prefix("h3=")?
+            .trim_matches('"');
+        authority.strip_prefix(':')?.parse().ok()
+    })
+}
+
+/// Chooses between HTTP/3 and TCP for control-plane requests.
+///
+/// HTTP/3 is used once the coordinator advertises it via `Alt-Svc` (or a port
+/// is configured).  A failed QUIC attempt falls back to TCP and backs off
+/// exponentially before HTTP/3 is tried again, so UDP-blocking networks do not
+/// pay a handshake timeout on every request.
+#[derive(Debug, Clone)]
+pub struct TransportNegotiator {
+    enabled: bool,
+    h3_port: Option<u16>,
+    consecutive_failures: u32,
+    retry_h3_at: Option<Instant>,
+}
+
+impl TransportNegotiator {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            h3_port: None,
+            consecutive_failures: 0,
+            retry_h3_at: None,
+        }
+    }
+
+    /// Use a known HTTP/3 port without waiting for an `Alt-Svc` advertisement.
+    pub fn with_h3_port(mut self, port: u16) -> Self {
+        self.h3_port = Some(port);
+        self
+    }
+
+    pub fn h3_port(&self) -> Option<u16> {
+        self.h3_port
+    }
+
+    /// Record the `Alt-Svc` header from a TCP response.
+    pub fn observe_alt_svc(&mut self, header: &str) {
+        if header.trim() == "clear" {
+            self.h3_port = None;
+        } else if let Some(port) = parse_alt_svc_h3_port(header) {
+            self.h3_port = Some(port);
+        }
+    }
+
+    /// Transport to use for the next request.
+    pub fn select(&self, now: Instant) -> ControlPlaneProtocol {
+        let backing_off = self.retry_h3_at.is_some_and(|retry_at| now < retry_at);
+        if self.enabled && self.h3_port.is_some() && !backing_off {
+            ControlPlaneProtocol::Http3
+        } else {
+            ControlPlaneProtocol::Tcp
+        }
+    }
+
+    pub fn record_h3_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.retry_h3_at = None;
+    }
+
+    /// Record a failed HTTP/3 attempt; returns how long HTTP/3 is skipped.
+    pub fn record_h3_failure(&mut self, now: Instant) -> Duration {
+        let backoff = INITIAL_H3_BACKOFF
+            .saturating_mul(1 << self.consecutive_failures.min(10))
+            .min(MAX_H3_BACKOFF);
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.retry_h3_at = Some(now + backoff);
+        backoff
+    }
+}
+
+/// Minimal HTTP/3 client for control-plane requests.
+#[cfg(feature = "http3")]
+pub mod h3_client {
+    use anyhow::{Context, Result};
+    use bytes::{Buf, Bytes, BytesMut};
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    /// One QUIC connection to the coordinator, negotiated with ALPN `h3`.
+    pub struct Http3Client {
+        send_request: h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>,
+        _endpoint: quinn::Endpoint,
+    }
+
+    impl Http3Client {
+        /// Connect to `addr`, verifying the certificate for `server_name`
+        /// against `ca_pem` if given, otherwise the platform trust store.
+        pub async fn connect(
+            addr: SocketAddr,
+            server_name: &str,
+            ca_pem: Option<&Path>,
+        ) -> Result<Self> {
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let builder = rustls::ClientConfig::builder_with_provider(provider)
+                .with_protocol_versions(&[&rustls::version::TLS13])?;
+
+            let mut tls = match ca_pem {
+                Some(path) => {
+                    use rustls_pki_types::pem::PemObject;
+                    let mut roots = rustls::RootCertStore::empty();
+                    for cert in rustls_pki_types::CertificateDer::pem_file_iter(path)? {
+                        roots.add(cert?)?;
+                    }
+                    builder.with_root_certificates(roots).with_no_client_auth()
+                }
+                None => {
+                    use rustls_platform_verifier::BuilderVerifierExt;
+                    builder.with_platform_verifier()?.with_no_client_auth()
+                }
+            };
+            tls.alpn_protocols = super::ControlPlaneProtocol::Http3.alpn_protocols();
+
+            let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(tls)?;
+            let bind: SocketAddr = if addr.is_ipv6() {
+                "[::]:0".parse()?
+            } else {
+                "0.0.0.0:0".parse()?
+            };
+            let mut endpoint = quinn::Endpoint::client(bind)?;
+            endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+
+            let conn = endpoint
+                .connect(addr, server_name)?
+                .await
+                .context("QUIC handshake failed")?;
+            let (mut driver, send_request) = h3::client::new(h3_quinn::Connection::new(conn))
+                .await
+                .context("HTTP/3 connection setup failed")?;
+            tokio::spawn(async move {
+                let _ = driver.wait_idle().await;
+            });
+
+            Ok(Self {
+                send_request,
+                _endpoint: endpoint,
+            })
+        }
+
+        /// Send a request and buffer the full response body.
+        pub async fn send(
+            &mut self,
+            request: http::Request<Bytes>,
+        ) -> Result<http::Response<Bytes>> {
+            let (parts, body) = request.into_parts();
+            let mut stream = self
+                .send_request
+                .send_request(http::Request::from_parts(parts, ()))
+                .await?;
+            if !body.is_empty() {
+                stream.send_data(body).await?;
+            }
+            stream.finish().await?;
+
+            let response = stream.recv_response().await?;
+            let mut body = BytesMut::new();
+            while let Some(mut chunk) = stream.recv_data().await? {
+                body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+            }
+
+            let (parts, ()) = response.into_parts();
+            Ok(http::Response::from_parts(parts, body.freeze()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_alt_svc() {
+        assert_eq!(
+            parse_alt_svc_h3_port("h3=\":8443\"; ma=86400, h2=\":443\""),
+            Some(8443)
+        );
+        assert_eq!(parse_alt_svc_h3_port("h2=\":443\""), None);
+        assert_eq!(parse_alt_svc_h3_port("h3=\"other.example:443\""), None);
+    }
+
+    #[test]
+    fn test_negotiator_prefers_h3_once_advertised() {
+        let now = Instant::now();
+        let mut negotiator = TransportNegotiator::new(true);
+        assert_eq!(negotiator.select(now), ControlPlaneProtocol::Tcp);
+
+        negotiator.observe_alt_svc("h3=\":3000\"; ma=86400");
+        assert_eq!(negotiator.select(now), ControlPlaneProtocol::Http3);
+        assert_eq!(
+            ControlPlaneProtocol::Http3.alpn_protocols(),
+            vec![ALPN_H3.to_vec()]
+        );
+
+        negotiator.observe_alt_svc("clear");
+        assert_eq!(negotiator.select(now), ControlPlaneProtocol::Tcp);
+
+        let disabled = TransportNegotiator::new(false).with_h3_port(3000);
+        assert_eq!(disabled.select(now), ControlPlaneProtocol::Tcp);
+    }
+
+    #[test]
+    fn test_negotiator_backs_off_after_failures() {
+        let now = Instant::now();
+        let mut negotiator = TransportNegotiator::new(true).with_h3_port(3000);
+
+        assert_eq!(negotiator.record_h3_failure(now), INITIAL_H3_BACKOFF);
+        assert_eq!(negotiator.select(now), ControlPlaneProtocol::Tcp);
+        assert_eq!(
+            negotiator.select(now + INITIAL_H3_BACKOFF),
+            ControlPlaneProtocol::Http3
+        );
+
+        assert_eq!(negotiator.record_h3_failure(now), INITIAL_H3_BACKOFF * 2);
+        for _ in 0..20 {
+            negotiator.record_h3_failure(now);
+        }
+        assert_eq!(negotiator.record_h3_failure(now), MAX_H3_BACKOFF);
+
+        negotiator.record_h3_success();
+        assert_eq!(negotiator.select(now), ControlPlaneProtocol::Http3);
+    }
+}
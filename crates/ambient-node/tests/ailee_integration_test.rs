@@ -523,3 +523,46 @@ async fn test_vcp_adapter_all_node_types() {
         );
     }
 }
+
+#[tokio::test]
+async fn test_vcp_adapter_with_scripted_models() {
+    use ailee_trust_layer::testkit::{
+        assert_lineage, assert_lineage_excludes, assert_result_integrity, MockModelAdapter,
+    };
+
+    let adapter = AileeEngineAdapter::new(2);
+    let remote = MockModelAdapter::remote("remote").respond("forty two");
+    let adapters = || -> Vec<Box<dyn ModelAdapter>> {
+        vec![
+            Box::new(MockModelAdapter::new("local-a").respond("forty two")),
+            Box::new(MockModelAdapter::new("local-b").fail("out of memory")),
+            Box::new(MockModelAdapter::new("local-c").respond("forty two")),
+            Box::new(remote.clone()),
+        ]
+    };
+
+    // Online nodes consult remote models; failed ones drop out of the lineage.
+    let online = VcpExecutionContext::new(true, "us-west-2", "compute", 5000, true);
+    let result = adapter
+        .execute_with_adapters("answer?", TaskType::Chat, 0.5, &online, adapters())
+        .await
+        .unwrap();
+    assert_lineage(&result, &["local-a", "local-c", "remote"]);
+    assert_eq!(result.final_output, "forty two");
+    assert_eq!(result.execution_metadata.models_consulted, 4);
+    assert!(!result.execution_metadata.was_offline);
+
+    // Offline nodes never call the remote model.
+    let offline = VcpExecutionContext::new(false, "us-west-2", "compute", 5000, true);
+    let result = adapter
+        .execute_with_adapters("answer?", TaskType::Chat, 0.5, &offline, adapters())
+        .await
+        .unwrap();
+    assert_lineage_excludes(&result, "remote");
+    assert!(result.execution_metadata.was_offline);
+    assert_eq!(remote.calls(), 1);
+
+    let request =
+        GenerationRequest::new("answer?", TaskType::Chat, 0.5, ExecutionMode::Local, true);
+    assert_result_integrity(&result, &request);
+}
@@ -0,0 +1,14 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "grpc")]
+    {
+        // Use the vendored protoc unless the environment points at one.
+        if std::env::var_os("PROTOC").is_none() {
+            std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+        }
+        println!("cargo:rerun-if-changed=proto/node_agent.proto");
+        tonic_build::configure()
+            .build_client(true)
+            .compile_protos(&["proto/node_agent.proto"], &["proto"])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,12 @@
+-- Energy accounting: nodes report watt-hours metered from power telemetry for
+-- each task assignment (with their result) and for each relay session.
+ALTER TABLE task_assignments
+    ADD COLUMN IF NOT EXISTS energy_wh DOUBLE PRECISION;
+
+ALTER TABLE connect_sessions
+    ADD COLUMN IF NOT EXISTS energy_wh DOUBLE PRECISION;
+
+-- Usage reports aggregate a requester's sessions by region.
+CREATE INDEX IF NOT EXISTS idx_connect_sessions_requester_energy
+    ON connect_sessions(requester_id)
+    WHERE energy_wh IS NOT NULL;
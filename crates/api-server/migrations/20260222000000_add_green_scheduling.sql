@@ -0,0 +1,7 @@
+-- Opt-in green scheduling: tasks record their scheduling mode and nodes may
+-- publish a benchmark-derived energy efficiency (work units per watt-hour).
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS scheduling_mode VARCHAR(16) NOT NULL DEFAULT 'standard';
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS benchmark_ops_per_wh DOUBLE PRECISION;
@@ -0,0 +1,9 @@
+-- Task priority queues: requesters may rank their tasks (0-10, higher first).
+-- Pending tasks are ordered by priority plus an aging bonus so low-priority
+-- work is not starved indefinitely.
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS priority SMALLINT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_pending_priority
+    ON tasks(priority DESC, created_at ASC)
+    WHERE status = 'pending';
@@ -0,0 +1,28 @@
+-- Task retry policy: when an assigned node times out or reports a failed
+-- result, the task is re-queued to other eligible nodes (after an optional
+-- backoff) until max_retries is exhausted, then marked failed.
+--
+-- retry_excluded_nodes lists nodes whose attempt failed so the task is not
+-- handed straight back to them.
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS max_retries INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS retry_backoff_sec BIGINT NOT NULL DEFAULT 0;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS retry_count INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS next_attempt_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS last_error TEXT;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS retry_excluded_nodes VARCHAR(64)[] NOT NULL DEFAULT ARRAY[]::VARCHAR(64)[];
+
+CREATE INDEX IF NOT EXISTS idx_tasks_next_attempt_at
+    ON tasks(next_attempt_at)
+    WHERE status = 'pending' AND next_attempt_at IS NOT NULL;
@@ -0,0 +1,8 @@
+-- Clock skew reported by nodes in heartbeats (node clock minus server clock,
+-- in milliseconds).  Used to correct node-supplied timestamps such as proof
+-- generation times before checking them against the server's tolerance.
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS clock_skew_ms BIGINT;
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS clock_skew_reported_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,9 @@
+-- Uploaded WASM modules.  Binaries live in the artifact store keyed by their
+-- SHA3-256 hash; this table records metadata and who uploaded them.
+CREATE TABLE IF NOT EXISTS wasm_modules (
+    module_hash VARCHAR(64) PRIMARY KEY,
+    size_bytes BIGINT NOT NULL,
+    storage_backend VARCHAR(32) NOT NULL,
+    uploaded_by UUID REFERENCES users(user_id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,16 @@
+-- Sealed task secrets.  Requesters encrypt each secret to an assigned node's
+-- X25519 key; the server only ever stores ciphertext and deletes it once the
+-- node's attempt ends.
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS secrets_public_key VARCHAR(64);
+
+CREATE TABLE IF NOT EXISTS task_sealed_secrets (
+    task_id UUID NOT NULL REFERENCES tasks(task_id) ON DELETE CASCADE,
+    node_id VARCHAR(64) NOT NULL REFERENCES nodes(node_id) ON DELETE CASCADE,
+    name VARCHAR(64) NOT NULL,
+    ephemeral_public_key VARCHAR(64) NOT NULL,
+    nonce VARCHAR(32) NOT NULL,
+    ciphertext TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (task_id, node_id, name)
+);
@@ -0,0 +1,4 @@
+-- Network egress declared by a task (JSON array of {host, ports}).  Empty
+-- means the task's sandbox had no network access.
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS egress JSONB NOT NULL DEFAULT '[]'::jsonb;
@@ -0,0 +1,4 @@
+-- Node state reconstructed from full and delta-encoded heartbeats
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS heartbeat_seq BIGINT,
+    ADD COLUMN IF NOT EXISTS heartbeat_state JSONB;
@@ -0,0 +1,12 @@
+-- Execution log lines reported by assigned nodes, streamed to the task
+-- creator over SSE.  `log_id` orders lines and doubles as the SSE event id.
+CREATE TABLE IF NOT EXISTS task_logs (
+    log_id BIGSERIAL PRIMARY KEY,
+    task_id UUID NOT NULL REFERENCES tasks(task_id) ON DELETE CASCADE,
+    node_id VARCHAR(64) NOT NULL REFERENCES nodes(node_id) ON DELETE CASCADE,
+    level VARCHAR(8) NOT NULL,
+    message TEXT NOT NULL,
+    logged_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_logs_task_id_log_id ON task_logs(task_id, log_id);
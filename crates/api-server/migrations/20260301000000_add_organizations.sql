@@ -0,0 +1,42 @@
+-- Organizations: users belong to orgs with a role, and nodes / tasks may be
+-- owned by an org.  Rows with a NULL org_id stay personal to their owner.
+CREATE TABLE IF NOT EXISTS organizations (
+    org_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(128) NOT NULL,
+    created_by UUID REFERENCES users(user_id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS organization_members (
+    org_id UUID NOT NULL REFERENCES organizations(org_id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    role VARCHAR(16) NOT NULL CHECK (role IN ('owner', 'admin', 'member', 'viewer')),
+    joined_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (org_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_organization_members_user_id ON organization_members(user_id);
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS org_id UUID REFERENCES organizations(org_id) ON DELETE SET NULL;
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS org_id UUID REFERENCES organizations(org_id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_nodes_org_id ON nodes(org_id) WHERE org_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_tasks_org_id ON tasks(org_id) WHERE org_id IS NOT NULL;
+
+-- Whether `p_user_id` holds at least `p_min_role` in `p_org_id`
+-- (viewer < member < admin < owner).  A NULL org grants nothing.
+CREATE OR REPLACE FUNCTION org_role_at_least(p_org_id UUID, p_user_id UUID, p_min_role TEXT)
+RETURNS BOOLEAN
+LANGUAGE sql STABLE
+AS $$
+    SELECT p_org_id IS NOT NULL AND EXISTS (
+        SELECT 1
+        FROM organization_members m
+        WHERE m.org_id = p_org_id
+          AND m.user_id = p_user_id
+          AND array_position(ARRAY['viewer', 'member', 'admin', 'owner'], m.role::TEXT)
+              >= array_position(ARRAY['viewer', 'member', 'admin', 'owner'], p_min_role)
+    )
+$$;
@@ -0,0 +1,30 @@
+-- Starvation handling for tasks that stay pending without matching any node.
+--
+-- queued_at marks when the task last entered the pending queue (submission
+-- or re-queue after a failed attempt); aging thresholds count from it.
+-- constraints_relaxed_at is set once soft constraints (the preferred node
+-- type) are dropped, starving_at once the task is flagged as starving, and
+-- scheduling_diagnostics holds the summary recorded when the task is finally
+-- marked unschedulable.
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS queued_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE tasks SET queued_at = created_at WHERE queued_at IS NULL;
+
+ALTER TABLE tasks
+    ALTER COLUMN queued_at SET DEFAULT NOW(),
+    ALTER COLUMN queued_at SET NOT NULL;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS constraints_relaxed_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS starving_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS scheduling_diagnostics JSONB;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_pending_queued_at
+    ON tasks(queued_at)
+    WHERE status = 'pending';
@@ -0,0 +1,27 @@
+-- Typed slot pools: nodes advertise separate concurrency limits for relay
+-- sessions (connect), sandboxed compute (wasm) and GPU tasks (gpu).  NULL
+-- falls back to MAX_CONCURRENT_TASKS_PER_NODE.  Each task records the pool
+-- it occupies so the assignment queries can count usage per pool.
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS connect_slots INTEGER CHECK (connect_slots >= 0);
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS wasm_slots INTEGER CHECK (wasm_slots >= 0);
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS gpu_slots INTEGER CHECK (gpu_slots >= 0);
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS slot_class VARCHAR(16) NOT NULL DEFAULT 'wasm';
+
+UPDATE tasks
+SET slot_class = CASE
+    WHEN task_type = 'connect_only' THEN 'connect'
+    WHEN require_gpu THEN 'gpu'
+    ELSE 'wasm'
+END;
+
+CREATE INDEX IF NOT EXISTS idx_task_assignments_active_node
+    ON task_assignments(node_id)
+    WHERE disconnected_at IS NULL;
@@ -0,0 +1,21 @@
+-- Checkpoint-based task handoff.
+--
+-- Checkpointable WASM tasks upload state snapshots while they run.  Only the
+-- newest snapshot per task is tracked; its bytes live in the artifact store
+-- under checkpoint_hash.  When a node drains (nodes.status = 'draining') or
+-- goes offline, the task is re-queued and the next node resumes from it.
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS checkpointable BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS task_checkpoints (
+    task_id UUID PRIMARY KEY REFERENCES tasks(task_id) ON DELETE CASCADE,
+    node_id VARCHAR(64) NOT NULL,
+    seq BIGINT NOT NULL CHECK (seq > 0),
+    checkpoint_hash VARCHAR(64) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- task_assignments.execution_status gains 'handed_off': the assignment ended
+-- because its node drained and the task moved to another node.
@@ -0,0 +1,20 @@
+-- Signed sandbox reports.
+--
+-- Nodes publish an Ed25519 signing key at registration and attach a signed
+-- report of the sandbox limits, capabilities, hostcalls, scratch files and
+-- blocked network attempts to each task result.  Reports are verified on
+-- receipt and served as part of the task's provenance bundle.
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS signing_public_key VARCHAR(64);
+
+CREATE TABLE IF NOT EXISTS task_sandbox_reports (
+    task_id UUID NOT NULL REFERENCES tasks(task_id) ON DELETE CASCADE,
+    node_id VARCHAR(64) NOT NULL,
+    report JSONB NOT NULL,
+    report_hash VARCHAR(64) NOT NULL,
+    signer_public_key VARCHAR(64) NOT NULL,
+    signature TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (task_id, node_id)
+);
@@ -0,0 +1,17 @@
+-- Indexes for the retention job.
+--
+-- Each retention batch selects the oldest rows past a window; these partial
+-- indexes keep that scan proportional to the history being purged rather
+-- than to the live tables the scheduler reads.
+
+CREATE INDEX IF NOT EXISTS idx_task_assignments_disconnected_at
+    ON task_assignments(disconnected_at)
+    WHERE disconnected_at IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_connect_sessions_inactive_ended
+    ON connect_sessions(COALESCE(ended_at, updated_at))
+    WHERE status <> 'active';
+
+CREATE INDEX IF NOT EXISTS idx_node_heartbeat_events_recorded_at
+    ON node_heartbeat_history(recorded_at)
+    WHERE status IN ('task_cleared', 'task_connected');
@@ -0,0 +1,25 @@
+-- Indexes for the scheduler's node-eligibility queries.
+--
+-- Candidate selection filters on status, deletion, node_type and the
+-- capability columns, then orders by health; the offline sweep scans live
+-- nodes by heartbeat age; and connect-only placement probes each candidate
+-- for an active connect session.  Without these the queries scan every node
+-- and session ever registered.  `db::REQUIRED_INDEXES` lists them so startup
+-- warns when one is missing or invalid.
+
+CREATE INDEX IF NOT EXISTS idx_nodes_schedulable_capabilities
+    ON nodes(node_type, cpu_cores, memory_gb, bandwidth_mbps)
+    INCLUDE (gpu_available, health_score)
+    WHERE status = 'online' AND deleted_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_nodes_schedulable_health
+    ON nodes(health_score DESC, registered_at ASC)
+    WHERE status = 'online' AND deleted_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_nodes_live_last_heartbeat
+    ON nodes(last_heartbeat)
+    WHERE status IN ('online', 'draining') AND deleted_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_connect_sessions_active_node
+    ON connect_sessions(node_id, expires_at)
+    WHERE status = 'active';
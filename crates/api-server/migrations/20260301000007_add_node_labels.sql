@@ -0,0 +1,12 @@
+-- Placement labels on nodes and the selector a task requires of them.
+-- A node matches when its labels contain every selector entry (`@>`); an
+-- empty selector matches every node.
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS labels JSONB NOT NULL DEFAULT '{}'::jsonb;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS node_selector JSONB NOT NULL DEFAULT '{}'::jsonb;
+
+CREATE INDEX IF NOT EXISTS idx_nodes_labels
+    ON nodes USING GIN (labels jsonb_path_ops)
+    WHERE deleted_at IS NULL;
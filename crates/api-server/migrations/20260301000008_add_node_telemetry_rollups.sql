@@ -0,0 +1,41 @@
+-- Node telemetry history: per-heartbeat samples and time-bucketed rollups
+--
+-- Heartbeats that carry reported state fill the metric columns of
+-- node_heartbeat_history; the rollup job aggregates closed buckets into
+-- node_telemetry_rollups for dashboard queries.
+
+ALTER TABLE node_heartbeat_history
+    ADD COLUMN IF NOT EXISTS bandwidth_mbps DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS temperature_c DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS power_watts DOUBLE PRECISION;
+
+CREATE INDEX IF NOT EXISTS idx_node_heartbeat_samples_node_recorded
+    ON node_heartbeat_history(node_id, recorded_at)
+    WHERE status NOT IN ('task_cleared', 'task_connected');
+
+CREATE TABLE IF NOT EXISTS node_telemetry_rollups (
+    node_id VARCHAR(64) NOT NULL REFERENCES nodes(node_id) ON DELETE CASCADE,
+    resolution VARCHAR(8) NOT NULL,
+    bucket_start TIMESTAMP WITH TIME ZONE NOT NULL,
+    samples INTEGER NOT NULL,
+    health_score_avg DOUBLE PRECISION,
+    health_score_min DOUBLE PRECISION,
+    cpu_usage_avg DOUBLE PRECISION,
+    cpu_usage_max DOUBLE PRECISION,
+    memory_usage_avg DOUBLE PRECISION,
+    memory_usage_max DOUBLE PRECISION,
+    network_latency_ms_avg DOUBLE PRECISION,
+    network_latency_ms_max DOUBLE PRECISION,
+    bandwidth_mbps_avg DOUBLE PRECISION,
+    bandwidth_mbps_min DOUBLE PRECISION,
+    temperature_c_avg DOUBLE PRECISION,
+    temperature_c_max DOUBLE PRECISION,
+    power_watts_avg DOUBLE PRECISION,
+    power_watts_max DOUBLE PRECISION,
+    active_tasks_avg DOUBLE PRECISION,
+    active_tasks_max DOUBLE PRECISION,
+    PRIMARY KEY (node_id, resolution, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_node_telemetry_rollups_resolution_bucket
+    ON node_telemetry_rollups(resolution, bucket_start);
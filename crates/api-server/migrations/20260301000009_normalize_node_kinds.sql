@@ -0,0 +1,18 @@
+-- Normalize legacy node_type aliases to canonical node kinds
+--
+-- `any`, `worker` and `open` are still accepted on registration but stored
+-- as `universal`, `compute` and `open_internet`. legacy_node_type keeps the
+-- spelling the node registered with so operators can find nodes that still
+-- need their configuration updated.
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS legacy_node_type VARCHAR(64);
+
+UPDATE nodes
+SET legacy_node_type = node_type,
+    node_type = CASE node_type
+        WHEN 'any' THEN 'universal'
+        WHEN 'worker' THEN 'compute'
+        WHEN 'open' THEN 'open_internet'
+    END
+WHERE node_type IN ('any', 'worker', 'open');
@@ -0,0 +1,7 @@
+-- W3C trace context of the request that submitted each task
+--
+-- Returned to node agents with the task so their requests for it (results,
+-- logs, checkpoints) join the submitting trace.
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS traceparent VARCHAR(64);
@@ -0,0 +1,12 @@
+-- Network diversity for redundant execution
+--
+-- Each node records the ASN it sits in (GeoIP of its registration address or
+-- self-reported) and each task how strictly its replicas must be spread
+-- across ASNs/regions: 'off', 'prefer' or 'require'.
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS asn BIGINT,
+    ADD COLUMN IF NOT EXISTS asn_source VARCHAR(16);
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS diversity VARCHAR(16) NOT NULL DEFAULT 'prefer';
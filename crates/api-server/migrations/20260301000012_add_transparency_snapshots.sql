@@ -0,0 +1,23 @@
+-- Signed cluster aggregates for the public transparency endpoint
+--
+-- A periodic job stores one snapshot per run, signed with the control key
+-- that was active at the time (kept alongside so old snapshots stay
+-- verifiable after a key rotation).  tasks.proof_verified_at records when a
+-- result's ZK proof verified, for the proofs-verified counters.
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS proof_verified_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_proof_verified_at
+    ON tasks(proof_verified_at)
+    WHERE proof_verified_at IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS transparency_snapshots (
+    snapshot_id BIGSERIAL PRIMARY KEY,
+    generated_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    public_key VARCHAR(64) NOT NULL,
+    document JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_transparency_snapshots_generated_at
+    ON transparency_snapshots(generated_at);
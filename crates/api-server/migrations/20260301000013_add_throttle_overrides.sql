@@ -0,0 +1,20 @@
+-- Per-user and per-API-key rate limit overrides
+--
+-- The rate limiter reloads active rows periodically and after every admin
+-- change, scaling each tier's requests per minute by `multiplier` and
+-- replacing its burst with `burst` when set.  subject_id is a users.user_id
+-- or api_keys.key_id depending on subject_type.
+
+CREATE TABLE IF NOT EXISTS throttle_overrides (
+    override_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subject_type VARCHAR(16) NOT NULL CHECK (subject_type IN ('user', 'api_key')),
+    subject_id UUID NOT NULL,
+    multiplier DOUBLE PRECISION NOT NULL CHECK (multiplier > 0),
+    burst INTEGER CHECK (burst > 0),
+    reason TEXT,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    created_by UUID REFERENCES users(user_id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (subject_type, subject_id)
+);
@@ -0,0 +1,16 @@
+-- Refresh token families
+--
+-- Every login starts a family and each rotation issues the next token in it.
+-- Presenting a token that was already rotated means it was replayed, so the
+-- whole family and the user's other sessions are revoked.  Existing tokens
+-- each start their own family.
+
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS family_id UUID;
+
+UPDATE refresh_tokens SET family_id = token_id WHERE family_id IS NULL;
+
+ALTER TABLE refresh_tokens
+    ALTER COLUMN family_id SET DEFAULT gen_random_uuid(),
+    ALTER COLUMN family_id SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_family_id ON refresh_tokens(family_id);
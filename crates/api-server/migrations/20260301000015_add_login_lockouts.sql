@@ -0,0 +1,17 @@
+-- Failed login counters and temporary locks
+--
+-- One row per account (scope 'user', subject = user_id) or client IP
+-- (scope 'ip').  Login refuses a subject while locked_until is in the future;
+-- lockout_count drives the exponential backoff of successive locks.
+
+CREATE TABLE IF NOT EXISTS login_lockouts (
+    scope VARCHAR(8) NOT NULL CHECK (scope IN ('user', 'ip')),
+    subject VARCHAR(64) NOT NULL,
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    lockout_count INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (scope, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_lockouts_locked_until ON login_lockouts(locked_until);
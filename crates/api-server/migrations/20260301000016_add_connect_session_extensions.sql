@@ -0,0 +1,7 @@
+-- Count how often each connect session has been extended
+--
+-- POST /connect-sessions/{id}/extend moves expires_at forward; the count is
+-- checked against CONNECT_SESSION_MAX_EXTENSIONS.
+
+ALTER TABLE connect_sessions
+    ADD COLUMN IF NOT EXISTS extension_count INTEGER NOT NULL DEFAULT 0;
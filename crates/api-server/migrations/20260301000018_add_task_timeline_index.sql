@@ -0,0 +1,7 @@
+-- Look up a task's audit entries for its timeline
+--
+-- GET /tasks/{id}/timeline reads audit_log rows with resource_type 'task',
+-- such as the notifications queued for the task.
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_resource
+    ON audit_log(resource_type, resource_id, created_at);
@@ -0,0 +1,10 @@
+-- Flap counting for the node circuit breaker
+--
+-- A heartbeat from an offline node brings it back online and counts a flap.
+-- Nodes that flap NODE_FLAP_THRESHOLD times within NODE_FLAP_WINDOW_SECS get
+-- no new assignments until flap_breaker_until.
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS flap_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS first_flap_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN IF NOT EXISTS flap_breaker_until TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,27 @@
+-- Bandwidth usage reported by gateways for connect sessions
+--
+-- POST /connect-sessions/{id}/usage records one row per reporting interval
+-- and adds it to the session's running totals.  A session whose totals reach
+-- data_cap_bytes is ended.  usage_seq is the last accepted report sequence,
+-- so retried reports are not counted twice.
+
+ALTER TABLE connect_sessions
+    ADD COLUMN IF NOT EXISTS bytes_up BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS bytes_down BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS data_cap_bytes BIGINT,
+    ADD COLUMN IF NOT EXISTS usage_seq BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS last_usage_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE IF NOT EXISTS connect_session_usage (
+    usage_id BIGSERIAL PRIMARY KEY,
+    session_id VARCHAR(128) NOT NULL REFERENCES connect_sessions(session_id) ON DELETE CASCADE,
+    node_id VARCHAR(64) NOT NULL,
+    seq BIGINT NOT NULL,
+    bytes_up BIGINT NOT NULL,
+    bytes_down BIGINT NOT NULL,
+    interval_start TIMESTAMP WITH TIME ZONE NOT NULL,
+    interval_end TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_connect_session_usage_session
+    ON connect_session_usage(session_id, interval_end);
@@ -0,0 +1,8 @@
+-- Row version for task status transitions
+--
+-- Every status change bumps version and only applies while the version the
+-- writer read is unchanged, so concurrent sweepers, result submissions and
+-- the synthetic completer cannot overwrite each other's transitions.
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,29 @@
+-- Transactional outbox for task notifications
+--
+-- State changes insert their notification here in the same transaction, so a
+-- notification exists exactly when its change commits.  The dispatcher claims
+-- due rows by pushing next_attempt_at past a lease, delivers them, and stamps
+-- delivered_at; a replica that dies mid-delivery leaves the row to be retried
+-- once the lease lapses.
+
+CREATE TABLE IF NOT EXISTS notification_outbox (
+    outbox_id BIGSERIAL PRIMARY KEY,
+    event VARCHAR(64) NOT NULL,
+    user_id UUID NOT NULL,
+    task_id UUID,
+    notification JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    delivered_at TIMESTAMP WITH TIME ZONE,
+    failed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_outbox_due
+    ON notification_outbox (next_attempt_at)
+    WHERE delivered_at IS NULL AND failed_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_notification_outbox_finished
+    ON notification_outbox (COALESCE(delivered_at, failed_at))
+    WHERE delivered_at IS NOT NULL OR failed_at IS NOT NULL;
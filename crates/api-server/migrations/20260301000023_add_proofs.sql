@@ -0,0 +1,20 @@
+-- Stored ZK proofs
+--
+-- proof_artifacts was created for proof storage but never written.  It
+-- becomes `proofs`: every proof the API verifies, from task results and from
+-- /proofs/verify, with its outcome and timing.  tasks.proof_id names the proof
+-- that accompanied a task's accepted result.
+
+ALTER TABLE proof_artifacts RENAME TO proofs;
+
+ALTER INDEX idx_proof_artifacts_task_id RENAME TO idx_proofs_task_id;
+ALTER INDEX idx_proof_artifacts_run_id RENAME TO idx_proofs_run_id;
+ALTER INDEX idx_proof_artifacts_verified RENAME TO idx_proofs_verified;
+ALTER INDEX idx_proof_artifacts_created_at RENAME TO idx_proofs_created_at;
+
+ALTER TABLE proofs
+    ADD COLUMN IF NOT EXISTS node_id VARCHAR(64),
+    ADD COLUMN IF NOT EXISTS submitted_by UUID REFERENCES users(user_id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS error_message TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_proofs_submitted_by ON proofs(submitted_by);
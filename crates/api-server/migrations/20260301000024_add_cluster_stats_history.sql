@@ -0,0 +1,19 @@
+-- Periodic snapshots of GET /api/v1/cluster/stats
+--
+-- A background job inserts one row per interval; the history endpoint
+-- downsamples them for dashboard graphs.
+
+CREATE TABLE IF NOT EXISTS cluster_stats_history (
+    snapshot_id BIGSERIAL PRIMARY KEY,
+    recorded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    total_nodes BIGINT NOT NULL,
+    healthy_nodes BIGINT NOT NULL,
+    total_tasks BIGINT NOT NULL,
+    completed_tasks BIGINT NOT NULL,
+    failed_tasks BIGINT NOT NULL,
+    avg_health_score DOUBLE PRECISION NOT NULL,
+    total_compute_capacity DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_cluster_stats_history_recorded_at
+    ON cluster_stats_history(recorded_at);
@@ -0,0 +1,27 @@
+-- Resources each task attempt consumed, as reported by its node
+--
+-- Nodes report CPU time and peak sandbox memory (and, already, energy) with
+-- their result or error.  The per-attempt values live on the assignment;
+-- task_type_stats keeps running totals per task type for estimation and
+-- billing.  Totals are NUMERIC so they cannot overflow.
+
+ALTER TABLE task_assignments
+    ADD COLUMN IF NOT EXISTS execution_time_ms BIGINT,
+    ADD COLUMN IF NOT EXISTS cpu_time_ms BIGINT,
+    ADD COLUMN IF NOT EXISTS peak_memory_bytes BIGINT;
+
+CREATE TABLE IF NOT EXISTS task_type_stats (
+    task_type VARCHAR(64) PRIMARY KEY,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    completed_attempts BIGINT NOT NULL DEFAULT 0,
+    execution_time_samples BIGINT NOT NULL DEFAULT 0,
+    execution_time_ms_total NUMERIC NOT NULL DEFAULT 0,
+    cpu_time_samples BIGINT NOT NULL DEFAULT 0,
+    cpu_time_ms_total NUMERIC NOT NULL DEFAULT 0,
+    peak_memory_samples BIGINT NOT NULL DEFAULT 0,
+    peak_memory_bytes_total NUMERIC NOT NULL DEFAULT 0,
+    peak_memory_bytes_max BIGINT NOT NULL DEFAULT 0,
+    energy_samples BIGINT NOT NULL DEFAULT 0,
+    energy_wh_total DOUBLE PRECISION NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
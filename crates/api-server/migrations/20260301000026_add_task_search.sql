@@ -0,0 +1,19 @@
+-- Full-text and substring search over tasks
+--
+-- search_text holds the task type, the start of the inputs JSON and the
+-- start of the result, so GET /api/v1/tasks/search can find tasks by prompt
+-- content.  A GIN tsvector index serves word queries and a trigram index
+-- serves substring matches such as IDs embedded in inputs.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS search_text TEXT GENERATED ALWAYS AS (
+        task_type || ' ' || LEFT(inputs::TEXT, 8192) || ' ' || COALESCE(LEFT(result::TEXT, 2048), '')
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_search_fts
+    ON tasks USING GIN (to_tsvector('simple', search_text));
+
+CREATE INDEX IF NOT EXISTS idx_tasks_search_trgm
+    ON tasks USING GIN (search_text gin_trgm_ops);
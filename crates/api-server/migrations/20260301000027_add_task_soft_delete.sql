@@ -0,0 +1,12 @@
+-- Soft delete for tasks
+--
+-- DELETE /api/v1/tasks/{id} now sets deleted_at instead of removing the row,
+-- so a task's result, proofs and history survive until the retention job
+-- purges it (RETENTION_DELETED_TASKS_DAYS).  POST /api/v1/tasks/{id}/restore
+-- clears deleted_at.  The partial index serves the purge.
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_deleted_at
+    ON tasks (deleted_at)
+    WHERE deleted_at IS NOT NULL;
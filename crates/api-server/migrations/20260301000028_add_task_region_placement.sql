@@ -0,0 +1,12 @@
+-- Per-region task placement
+--
+-- A task may name node regions it prefers (e.g. near the requester of a
+-- connect_only session) and regions it must avoid.  region_strictness is
+-- 'prefer' (preferred regions rank first, others remain eligible) or
+-- 'require' (only preferred regions are eligible).  Excluded regions are
+-- never eligible.
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS preferred_regions TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN IF NOT EXISTS excluded_regions TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN IF NOT EXISTS region_strictness VARCHAR(16) NOT NULL DEFAULT 'prefer';
@@ -0,0 +1,18 @@
+-- Node attestation at registration
+--
+-- With NODE_ENROLLMENT_KEYS configured, new nodes register as
+-- 'pending_attestation' and get a nonce here.  The node signs it with its
+-- enrollment key; once verified the challenge row is deleted and the node
+-- records which key (and optional TPM quote) attested it.
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS attested_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS enrollment_public_key TEXT,
+    ADD COLUMN IF NOT EXISTS attestation_quote TEXT;
+
+CREATE TABLE IF NOT EXISTS node_attestation_challenges (
+    node_id VARCHAR(64) PRIMARY KEY REFERENCES nodes(node_id) ON DELETE CASCADE,
+    nonce TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,24 @@
+-- Destination policies for connect_only relay sessions
+--
+-- Each policy is the allowlist behind a session's destination_policy_id.
+-- Relay nodes cache every policy as one signed bundle; the single row of
+-- destination_policy_bundle holds its version, bumped on every change so
+-- nodes can refuse rolled-back bundles.
+
+CREATE TABLE IF NOT EXISTS destination_policies (
+    policy_id VARCHAR(128) PRIMARY KEY,
+    allowed_destinations TEXT[] NOT NULL DEFAULT '{}',
+    bundle_version BIGINT NOT NULL DEFAULT 0,
+    created_by UUID REFERENCES users(user_id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS destination_policy_bundle (
+    singleton BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (singleton),
+    version BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO destination_policy_bundle (singleton, version)
+VALUES (TRUE, 0)
+ON CONFLICT (singleton) DO NOTHING;
@@ -0,0 +1,6 @@
+-- Task types a node's operator accepts or refuses.
+-- An empty allow list accepts every task type; the block list always wins.
+-- Both are checked by every assignment query.
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS allowed_task_types TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN IF NOT EXISTS blocked_task_types TEXT[] NOT NULL DEFAULT '{}';
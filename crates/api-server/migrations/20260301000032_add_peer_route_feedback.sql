@@ -0,0 +1,19 @@
+-- Node agent feedback behind peer route queries
+--
+-- relay_load_reports holds the latest load each relay reported for itself;
+-- peer_route_invalidations the relays a source node dropped, until they
+-- expire.  Both feed mesh_coordinator::PeerRouter when a route is resolved.
+
+CREATE TABLE IF NOT EXISTS relay_load_reports (
+    node_id VARCHAR(64) PRIMARY KEY REFERENCES nodes(node_id) ON DELETE CASCADE,
+    utilization DOUBLE PRECISION NOT NULL,
+    saturated BOOLEAN NOT NULL DEFAULT FALSE,
+    reported_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS peer_route_invalidations (
+    source_node_id VARCHAR(64) NOT NULL REFERENCES nodes(node_id) ON DELETE CASCADE,
+    relay_node_id VARCHAR(64) NOT NULL REFERENCES nodes(node_id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (source_node_id, relay_node_id)
+);
@@ -0,0 +1,19 @@
+-- Relay each source node is routed through
+--
+-- A peer route query records the relay it chose, so later queries count
+-- that relay's active peers and spread offline nodes across relays.  Rows
+-- older than ROUTE_FEEDBACK_TTL_SECS no longer count; node agents refresh
+-- them by querying their route again.  Recent connect_session_usage per
+-- relay is weighed against its bandwidth_mbps in the same choice.
+
+CREATE TABLE IF NOT EXISTS peer_route_assignments (
+    source_node_id VARCHAR(64) PRIMARY KEY REFERENCES nodes(node_id) ON DELETE CASCADE,
+    relay_node_id VARCHAR(64) NOT NULL REFERENCES nodes(node_id) ON DELETE CASCADE,
+    assigned_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_peer_route_assignments_relay
+    ON peer_route_assignments(relay_node_id, assigned_at);
+
+CREATE INDEX IF NOT EXISTS idx_connect_session_usage_node
+    ON connect_session_usage(node_id, interval_end);
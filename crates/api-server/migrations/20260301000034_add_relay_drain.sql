@@ -0,0 +1,17 @@
+-- Relays announcing an imminent shutdown
+--
+-- POST /nodes/{id}/relay-drain drains the node and sets relay_drain_deadline.
+-- Until then the relay keeps serving its connect sessions, capped at the
+-- deadline; sessions handed to another relay remember where they came from
+-- so the old relay serves them until drain_deadline too.
+
+ALTER TABLE nodes
+    ADD COLUMN IF NOT EXISTS relay_drain_deadline TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE connect_sessions
+    ADD COLUMN IF NOT EXISTS drained_from_node_id VARCHAR(64),
+    ADD COLUMN IF NOT EXISTS drain_deadline TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_connect_sessions_drained_from
+    ON connect_sessions(drained_from_node_id, drain_deadline)
+    WHERE drained_from_node_id IS NOT NULL;
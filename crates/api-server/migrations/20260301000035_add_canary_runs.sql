@@ -0,0 +1,34 @@
+-- Synthetic end-to-end canaries
+--
+-- The canary job submits tiny tasks and short connect sessions through a
+-- chosen node and follows each through the scheduler, the node and back.
+-- pinned_node_id keeps a task off every other node; canary_runs records
+-- how far each run got and how long every stage took.
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS pinned_node_id VARCHAR(64);
+
+CREATE TABLE IF NOT EXISTS canary_runs (
+    run_id UUID PRIMARY KEY,
+    kind VARCHAR(32) NOT NULL,
+    node_id VARCHAR(64) NOT NULL,
+    task_id UUID REFERENCES tasks(task_id) ON DELETE SET NULL,
+    session_id VARCHAR(128),
+    expected JSONB NOT NULL DEFAULT '{}',
+    status VARCHAR(16) NOT NULL DEFAULT 'running',
+    stage VARCHAR(32) NOT NULL,
+    stage_latencies_ms JSONB NOT NULL DEFAULT '{}',
+    error TEXT,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_canary_runs_running
+    ON canary_runs(started_at)
+    WHERE status = 'running';
+
+CREATE INDEX IF NOT EXISTS idx_canary_runs_kind_started
+    ON canary_runs(kind, started_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_canary_runs_task
+    ON canary_runs(task_id);
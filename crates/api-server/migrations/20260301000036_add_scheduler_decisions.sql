@@ -0,0 +1,38 @@
+-- Scheduler decision log
+--
+-- Every scheduling decision that picked something is recorded with the
+-- inputs it was made from, so it can be replayed against the current
+-- ranking rules after the fact:
+--
+-- - `placement` rows choose nodes for task_id from the candidate set the
+--   candidate query returned; chosen and attached hold node IDs.
+-- - `pickup` rows record the fair-share order pending tasks were offered to
+--   the heartbeating node_id in; chosen holds that order and attached the
+--   task IDs the node took.
+--
+-- candidate_set_hash is the SHA-256 of the recorded inputs.  Retention
+-- purges (and archives) rows after RETENTION_SCHEDULER_DECISIONS_DAYS.
+
+CREATE TABLE IF NOT EXISTS scheduler_decisions (
+    decision_id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(16) NOT NULL,
+    task_id UUID,
+    node_id VARCHAR(64),
+    rule_versions JSONB NOT NULL,
+    candidate_set_hash CHAR(64) NOT NULL,
+    inputs JSONB NOT NULL,
+    chosen TEXT[] NOT NULL,
+    attached TEXT[] NOT NULL,
+    decided_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduler_decisions_task
+    ON scheduler_decisions(task_id, decided_at DESC)
+    WHERE task_id IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_scheduler_decisions_pickups
+    ON scheduler_decisions USING GIN (attached)
+    WHERE kind = 'pickup';
+
+CREATE INDEX IF NOT EXISTS idx_scheduler_decisions_decided_at
+    ON scheduler_decisions(decided_at);
@@ -0,0 +1,35 @@
+-- Snapshot-consistent cluster exports
+--
+-- An admin export dumps nodes, telemetry rollups, tasks, assignments and
+-- connect sessions from one read-only snapshot into the artifact store.
+-- files lists what it wrote ([{table, rows, bytes, hash}]); snapshot_at is
+-- the point the snapshot reflects and the base of the next incremental
+-- export.
+
+CREATE TABLE IF NOT EXISTS cluster_exports (
+    export_id UUID PRIMARY KEY,
+    status VARCHAR(16) NOT NULL DEFAULT 'running',
+    format VARCHAR(16) NOT NULL,
+    incremental BOOLEAN NOT NULL DEFAULT FALSE,
+    since TIMESTAMP WITH TIME ZONE,
+    snapshot_at TIMESTAMP WITH TIME ZONE,
+    policy JSONB NOT NULL DEFAULT '{}',
+    stable_identifiers BOOLEAN NOT NULL DEFAULT FALSE,
+    files JSONB NOT NULL DEFAULT '[]',
+    error TEXT,
+    created_by UUID,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_cluster_exports_started
+    ON cluster_exports(started_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_cluster_exports_completed
+    ON cluster_exports(snapshot_at DESC)
+    WHERE status = 'completed';
+
+-- At most one export runs at a time.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_cluster_exports_one_running
+    ON cluster_exports((TRUE))
+    WHERE status = 'running';
@@ -0,0 +1,27 @@
+-- Proof verification batches and quotas
+--
+-- POST /proofs/batches queues proofs for background verification; the
+-- submitter polls the batch for results.  requests holds the queued proofs
+-- until the batch finishes; results gains one entry per verified proof.
+--
+-- Standalone verifications (proofs without a node_id) count toward the
+-- submitter's daily quota, so they are looked up by submitter and time.
+
+CREATE TABLE IF NOT EXISTS proof_batches (
+    batch_id UUID PRIMARY KEY,
+    submitted_by UUID NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    status VARCHAR(16) NOT NULL DEFAULT 'running',
+    total INTEGER NOT NULL,
+    requests JSONB NOT NULL DEFAULT '[]',
+    results JSONB NOT NULL DEFAULT '[]',
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_proof_batches_submitter
+    ON proof_batches(submitted_by, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_proofs_standalone_submitter
+    ON proofs(submitted_by, created_at)
+    WHERE node_id IS NULL;
@@ -0,0 +1,22 @@
+-- Offloaded proof verification
+--
+-- With PROOF_VERIFY_OFFLOAD on, batch proofs are stored unverified
+-- (verified_at NULL) and handed to verifier nodes as a verify_proof task,
+-- recorded in proofs.verification_task_id.  Each verifier returns a signed
+-- verdict; the proof is settled once min_nodes of them agree.
+
+ALTER TABLE proofs
+    ADD COLUMN IF NOT EXISTS verification_task_id UUID REFERENCES tasks(task_id) ON DELETE SET NULL;
+
+CREATE TABLE IF NOT EXISTS proof_verdicts (
+    task_id UUID NOT NULL REFERENCES tasks(task_id) ON DELETE CASCADE,
+    node_id VARCHAR(64) NOT NULL,
+    proof_id UUID NOT NULL REFERENCES proofs(proof_id) ON DELETE CASCADE,
+    valid BOOLEAN NOT NULL,
+    signer_public_key TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (task_id, node_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_proof_verdicts_proof_id ON proof_verdicts(proof_id);
@@ -0,0 +1,45 @@
+-- Independent proof verifiers
+--
+-- A verify_proof task's creator is the proof's submitter.  Its verdicts
+-- only mean something if no verifier answers to the submitter, so a node
+-- may not verify when it has no owner, belongs to the submitter, to an org
+-- the submitter is a member of or to a fellow member of such an org, or
+-- shares its owner with another node attached to the same task.
+
+CREATE OR REPLACE FUNCTION verifier_conflicts_with_task(p_node_id TEXT, p_task_id UUID)
+RETURNS BOOLEAN
+LANGUAGE sql STABLE
+AS $$
+    SELECT EXISTS (
+        SELECT 1
+        FROM nodes n
+        JOIN tasks vt ON vt.task_id = p_task_id
+        WHERE n.node_id = p_node_id
+          AND (
+                n.owner_id IS NULL
+                OR n.owner_id = vt.creator_id
+                OR EXISTS (
+                    SELECT 1
+                    FROM organization_members submitter
+                    WHERE submitter.user_id = vt.creator_id
+                      AND (
+                            submitter.org_id = n.org_id
+                            OR EXISTS (
+                                SELECT 1
+                                FROM organization_members operator
+                                WHERE operator.org_id = submitter.org_id
+                                  AND operator.user_id = n.owner_id
+                            )
+                          )
+                )
+                OR EXISTS (
+                    SELECT 1
+                    FROM task_assignments peer
+                    JOIN nodes pn ON pn.node_id = peer.node_id
+                    WHERE peer.task_id = p_task_id
+                      AND peer.node_id <> p_node_id
+                      AND pn.owner_id = n.owner_id
+                )
+              )
+    )
+$$;
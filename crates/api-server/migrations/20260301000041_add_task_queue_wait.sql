@@ -0,0 +1,7 @@
+-- Time each task spent pending before it started running, summed over its
+-- starts (a re-queued task waits again).  NULL until the task first runs.
+-- Waits are reported per requester on GET /api/v1/usage; the queue-wait
+-- metric only carries the requester kind, to keep its label set bounded.
+
+ALTER TABLE tasks
+    ADD COLUMN IF NOT EXISTS queue_wait_seconds DOUBLE PRECISION;
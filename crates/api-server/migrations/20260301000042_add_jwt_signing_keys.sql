@@ -0,0 +1,13 @@
+-- JWT signing keys
+--
+-- One random Ed25519 seed per rotation period, sealed with AES-256-GCM
+-- under a key derived from JWT_SECRET.  The first replica to need a
+-- period's key inserts it; the others keep whichever row won, so every
+-- replica signs and verifies with the same keys.  Rows older than the
+-- oldest key a ring still verifies with are deleted as the ring rotates.
+
+CREATE TABLE IF NOT EXISTS jwt_signing_keys (
+    period BIGINT PRIMARY KEY,
+    sealed_seed BYTEA NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,13 @@
+-- Account lockouts are keyed by the username a login submits rather than
+-- the user ID, so unknown usernames are counted like existing ones.  Move
+-- the counters of existing accounts over to their usernames.
+
+UPDATE login_lockouts l
+SET subject = u.username
+FROM users u
+WHERE l.scope = 'user'
+  AND l.subject = u.user_id::TEXT
+  AND NOT EXISTS (
+      SELECT 1 FROM login_lockouts taken
+      WHERE taken.scope = 'user' AND taken.subject = u.username
+  );
@@ -0,0 +1,108 @@
+/// Single-use tokens for password reset and email verification
+///
+/// Tokens are mailed to the user through the notifier and only their hash is
+/// stored.  Each token works once and expires after:
+///
+/// - `PASSWORD_RESET_TOKEN_TTL_SECS` (default `3600`) for password resets
+/// - `EMAIL_VERIFICATION_TOKEN_TTL_SECS` (default `86400`) for email
+///   verification
+///
+/// Issuing a token invalidates the user's earlier unused tokens of the same
+/// purpose, so only the latest email works.
+pub const DEFAULT_PASSWORD_RESET_TTL_SECS: u64 = 3600;
+pub const DEFAULT_EMAIL_VERIFICATION_TTL_SECS: u64 = 86_400;
+
+/// What an account token may be redeemed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountTokenPurpose {
+    PasswordReset,
+    EmailVerification,
+}
+
+impl AccountTokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountTokenPurpose::PasswordReset => "password_reset",
+            AccountTokenPurpose::EmailVerification => "email_verification",
+        }
+    }
+}
+
+/// Token lifetimes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountTokenPolicy {
+    pub password_reset_ttl_secs: u64,
+    pub email_verification_ttl_secs: u64,
+}
+
+impl Default for AccountTokenPolicy {
+    fn default() -> Self {
+        Self {
+            password_reset_ttl_secs: DEFAULT_PASSWORD_RESET_TTL_SECS,
+            email_verification_ttl_secs: DEFAULT_EMAIL_VERIFICATION_TTL_SECS,
+        }
+    }
+}
+
+impl AccountTokenPolicy {
+    /// Load from the `*_TOKEN_TTL_SECS` variables.
+    pub fn from_env() -> Self {
+        Self::parse(
+            std::env::var("PASSWORD_RESET_TOKEN_TTL_SECS")
+                .ok()
+                .as_deref(),
+            std::env::var("EMAIL_VERIFICATION_TOKEN_TTL_SECS")
+                .ok()
+                .as_deref(),
+        )
+    }
+
+    /// Parse settings; unset, malformed or zero values keep their default.
+    pub fn parse(password_reset_ttl: Option<&str>, email_verification_ttl: Option<&str>) -> Self {
+        fn seconds(value: Option<&str>, default: u64) -> u64 {
+            value
+                .and_then(|raw| raw.trim().parse::<u64>().ok())
+                .filter(|parsed| *parsed > 0)
+                .unwrap_or(default)
+        }
+
+        Self {
+            password_reset_ttl_secs: seconds(password_reset_ttl, DEFAULT_PASSWORD_RESET_TTL_SECS),
+            email_verification_ttl_secs: seconds(
+                email_verification_ttl,
+                DEFAULT_EMAIL_VERIFICATION_TTL_SECS,
+            ),
+        }
+    }
+
+    pub fn ttl_secs(&self, purpose: AccountTokenPurpose) -> u64 {
+        match purpose {
+            AccountTokenPurpose::PasswordReset => self.password_reset_ttl_secs,
+            AccountTokenPurpose::EmailVerification => self.email_verification_ttl_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_parse_keeps_defaults_for_bad_values() {
+        assert_eq!(
+            AccountTokenPolicy::parse(None, None),
+            AccountTokenPolicy::default()
+        );
+
+        let policy = AccountTokenPolicy::parse(Some("600"), Some("0"));
+        assert_eq!(policy.ttl_secs(AccountTokenPurpose::PasswordReset), 600);
+        assert_eq!(
+            policy.ttl_secs(AccountTokenPurpose::EmailVerification),
+            DEFAULT_EMAIL_VERIFICATION_TTL_SECS
+        );
+        assert_eq!(
+            AccountTokenPolicy::parse(Some("soon"), None).password_reset_ttl_secs,
+            DEFAULT_PASSWORD_RESET_TTL_SECS
+        );
+    }
+}
@@ -0,0 +1,246 @@
+/// WASM module artifact storage
+///
+/// Uploaded modules are content-addressed by their SHA3-256 hash and stored
+/// in a pluggable backend.  Configure with:
+///
+/// - `ARTIFACT_STORE_BACKEND` — `filesystem` (default); any other value
+///   fails startup.  Other backends plug in by implementing [`ArtifactStore`]
+/// - `ARTIFACT_STORE_PATH` — root directory for the filesystem backend
+///   (defaults to `./artifacts`)
+/// - `WASM_MODULE_MAX_BYTES` — upload size limit (defaults to 10 MiB)
+/// - `TASK_CHECKPOINT_MAX_BYTES` — task checkpoint size limit (defaults to
+///   8 MiB); checkpoints share the store with modules
+use crate::deployment_profile;
+use crate::error::ApiError;
+use async_trait::async_trait;
+use sha3::{Digest, Sha3_256};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Prefix marking `TaskSubmission.wasm_module` as a reference to an uploaded module.
+pub const MODULE_REFERENCE_PREFIX: &str = "sha3-256:";
+
+/// Default upload size limit for WASM modules.
+pub const DEFAULT_MAX_MODULE_BYTES: usize = 10 * 1024 * 1024;
+
+/// `\0asm` magic followed by binary format version 1.
+const WASM_HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+/// Content-addressed storage for module binaries.
+#[async_trait]
+pub trait ArtifactStore: Send + Sync {
+    /// Store `bytes` under `hash`; storing an existing hash is a no-op.
+    async fn put(&self, hash: &str, bytes: &[u8]) -> anyhow::Result<()>;
+
+    /// Fetch the bytes stored under `hash`, if any.
+    async fn get(&self, hash: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Backend name for logs and responses.
+    fn backend_name(&self) -> &'static str;
+}
+
+/// Stores modules as `<root>/<first two hash chars>/<hash>.wasm`.
+pub struct FilesystemArtifactStore {
+    root: PathBuf,
+}
+
+impl FilesystemArtifactStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path_for(&self, hash: &str) -> PathBuf {
+        self.root
+            .join(&hash[..2.min(hash.len())])
+            .join(format!("{hash}.wasm"))
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for FilesystemArtifactStore {
+    async fn put(&self, hash: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        let path = self.path_for(hash);
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // Write to a temporary name and rename so readers never observe a
+        // partially written module.
+        let tmp_path = path.with_extension(format!("wasm.{}.tmp", uuid::Uuid::new_v4()));
+        tokio::fs::write(&tmp_path, bytes).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, hash: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path_for(hash)).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "filesystem"
+    }
+}
+
+/// Build the artifact store selected by `ARTIFACT_STORE_BACKEND`.  An
+/// unknown backend is an error, so a misconfigured deployment does not
+/// start up writing modules to local disk.
+pub fn artifact_store_from_env() -> anyhow::Result<Arc<dyn ArtifactStore>> {
+    artifact_store_for(std::env::var("ARTIFACT_STORE_BACKEND").ok().as_deref())
+}
+
+fn artifact_store_for(backend: Option<&str>) -> anyhow::Result<Arc<dyn ArtifactStore>> {
+    match backend.map(str::trim).unwrap_or_default() {
+        "" | "filesystem" => Ok(Arc::new(filesystem_store_from_env())),
+        other => anyhow::bail!(
+            "unsupported ARTIFACT_STORE_BACKEND {other:?}; use \"filesystem\" or install \
+             another backend with AppState::with_artifact_store"
+        ),
+    }
+}
+
+/// The filesystem backend rooted at `ARTIFACT_STORE_PATH`.
+pub fn filesystem_store_from_env() -> FilesystemArtifactStore {
+    let root = std::env::var("ARTIFACT_STORE_PATH").unwrap_or_else(|_| "./artifacts".to_string());
+    FilesystemArtifactStore::new(root)
+}
+
+/// Upload size limit from `WASM_MODULE_MAX_BYTES`.
+pub fn max_module_bytes() -> usize {
+    parse_max_module_bytes(deployment_profile::setting("WASM_MODULE_MAX_BYTES").as_deref())
+}
+
+fn parse_max_module_bytes(value: Option<&str>) -> usize {
+    value
+        .and_then(|raw| raw.parse::<usize>().ok())
+        .filter(|parsed| *parsed > 0)
+        .unwrap_or(DEFAULT_MAX_MODULE_BYTES)
+}
+
+/// Largest task checkpoint a node may upload.
+pub fn max_checkpoint_bytes() -> usize {
+    parse_max_checkpoint_bytes(std::env::var("TASK_CHECKPOINT_MAX_BYTES").ok().as_deref())
+}
+
+fn parse_max_checkpoint_bytes(value: Option<&str>) -> usize {
+    value
+        .and_then(|raw| raw.parse::<usize>().ok())
+        .filter(|parsed| *parsed > 0)
+        .unwrap_or(wasm_engine::DEFAULT_MAX_CHECKPOINT_BYTES)
+}
+
+/// Lowercase hex SHA3-256 of a module binary.
+pub fn module_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha3_256::digest(bytes))
+}
+
+/// Extract the hash from a `sha3-256:<hex>` module reference.
+///
+/// Returns `None` when `value` is not a reference at all and an error when it
+/// has the prefix but a malformed hash.
+pub fn parse_module_reference(value: &str) -> Result<Option<&str>, ApiError> {
+    let Some(hash) = value.strip_prefix(MODULE_REFERENCE_PREFIX) else {
+        return Ok(None);
+    };
+
+    if hash.len() != 64 || !hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err(ApiError::bad_request(
+            "wasm_module reference must be sha3-256: followed by 64 lowercase hex characters",
+        ));
+    }
+
+    Ok(Some(hash))
+}
+
+/// Check size and the WASM binary header of an upload.
+pub fn validate_wasm_module(bytes: &[u8], max_bytes: usize) -> Result<(), ApiError> {
+    if bytes.is_empty() {
+        return Err(ApiError::bad_request("Module body cannot be empty"));
+    }
+
+    if bytes.len() > max_bytes {
+        return Err(ApiError::bad_request(format!(
+            "Module cannot exceed {} bytes",
+            max_bytes
+        )));
+    }
+
+    if !bytes.starts_with(&WASM_HEADER) {
+        return Err(ApiError::bad_request(
+            "Module is not a WebAssembly binary (missing \\0asm version 1 header)",
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minimal_module() -> Vec<u8> {
+        WASM_HEADER.to_vec()
+    }
+
+    #[test]
+    fn validates_wasm_header_and_size() {
+        assert!(validate_wasm_module(&minimal_module(), 1024).is_ok());
+        assert!(validate_wasm_module(b"not wasm", 1024).is_err());
+        assert!(validate_wasm_module(&[], 1024).is_err());
+        assert!(validate_wasm_module(&minimal_module(), 4).is_err());
+    }
+
+    #[test]
+    fn parses_module_references() {
+        let hash = module_hash(&minimal_module());
+        let reference = format!("{MODULE_REFERENCE_PREFIX}{hash}");
+        assert_eq!(
+            parse_module_reference(&reference).unwrap(),
+            Some(hash.as_str())
+        );
+        assert_eq!(parse_module_reference("AGFzbQEAAAA=").unwrap(), None);
+        assert!(parse_module_reference("sha3-256:XYZ").is_err());
+    }
+
+    #[test]
+    fn parses_max_module_bytes() {
+        assert_eq!(parse_max_module_bytes(Some("2048")), 2048);
+        assert_eq!(parse_max_module_bytes(Some("0")), DEFAULT_MAX_MODULE_BYTES);
+        assert_eq!(parse_max_module_bytes(None), DEFAULT_MAX_MODULE_BYTES);
+        assert_eq!(
+            parse_max_checkpoint_bytes(Some("x")),
+            wasm_engine::DEFAULT_MAX_CHECKPOINT_BYTES
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_backends() {
+        for backend in [None, Some(""), Some("filesystem")] {
+            let store = artifact_store_for(backend).unwrap();
+            assert_eq!(store.backend_name(), "filesystem");
+        }
+        assert!(artifact_store_for(Some("s3")).is_err());
+    }
+
+    #[tokio::test]
+    async fn filesystem_store_round_trips() {
+        let root = std::env::temp_dir().join(format!("artifacts-{}", uuid::Uuid::new_v4()));
+        let store = FilesystemArtifactStore::new(&root);
+        let bytes = minimal_module();
+        let hash = module_hash(&bytes);
+
+        assert!(store.get(&hash).await.unwrap().is_none());
+        store.put(&hash, &bytes).await.unwrap();
+        store.put(&hash, &bytes).await.unwrap();
+        assert_eq!(store.get(&hash).await.unwrap(), Some(bytes));
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,175 @@
+/// Node attestation at registration
+///
+/// With `NODE_ENROLLMENT_KEYS` set to a comma-separated list of base64
+/// Ed25519 public keys, a newly registered node starts in the
+/// `pending_attestation` status, which no scheduler query treats as
+/// eligible, and its registration response carries a single-use nonce.  The
+/// node answers on `POST /api/v1/nodes/{node_id}/attestation` with the
+/// nonce signed by one of the listed enrollment keys (see
+/// `ambient_node::AttestationResponse`); a valid answer brings it `online`.
+///
+/// - `NODE_ATTESTATION_CHALLENGE_TTL_SECS` (default `300`): how long a nonce
+///   stays valid.  `POST /api/v1/nodes/{node_id}/attestation/challenge`
+///   issues a fresh one, replacing any earlier nonce.
+///
+/// A TPM / secure-element quote sent with the answer is stored with the
+/// node for audit; it is not checked against a vendor root here.
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+pub const DEFAULT_CHALLENGE_TTL_SECS: u64 = 300;
+
+/// Status of a registered node that has not answered its challenge yet.
+pub const PENDING_ATTESTATION_STATUS: &str = "pending_attestation";
+
+/// Largest quote accepted, base64-encoded.
+pub const MAX_QUOTE_B64_BYTES: usize = 16 * 1024;
+
+/// Trusted enrollment keys and the challenge lifetime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationPolicy {
+    /// Base64 Ed25519 public keys; empty disables attestation.
+    pub enrollment_keys: Vec<String>,
+    pub challenge_ttl_secs: u64,
+}
+
+impl Default for AttestationPolicy {
+    fn default() -> Self {
+        Self {
+            enrollment_keys: Vec::new(),
+            challenge_ttl_secs: DEFAULT_CHALLENGE_TTL_SECS,
+        }
+    }
+}
+
+impl AttestationPolicy {
+    /// Load from `NODE_ENROLLMENT_KEYS` and `NODE_ATTESTATION_CHALLENGE_TTL_SECS`.
+    pub fn from_env() -> Self {
+        Self::parse(
+            std::env::var("NODE_ENROLLMENT_KEYS").ok().as_deref(),
+            std::env::var("NODE_ATTESTATION_CHALLENGE_TTL_SECS")
+                .ok()
+                .as_deref(),
+        )
+    }
+
+    /// Parse settings; blank key entries are skipped and an unset,
+    /// malformed or zero TTL keeps the default.
+    pub fn parse(enrollment_keys: Option<&str>, challenge_ttl: Option<&str>) -> Self {
+        Self {
+            enrollment_keys: enrollment_keys
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(str::to_string)
+                .collect(),
+            challenge_ttl_secs: challenge_ttl
+                .and_then(|raw| raw.trim().parse().ok())
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_CHALLENGE_TTL_SECS),
+        }
+    }
+
+    /// Whether new nodes must attest before they are scheduled.
+    pub fn required(&self) -> bool {
+        !self.enrollment_keys.is_empty()
+    }
+
+    pub fn trusts(&self, enrollment_public_key: &str) -> bool {
+        self.enrollment_keys
+            .iter()
+            .any(|key| key == enrollment_public_key)
+    }
+}
+
+/// A nonce the node must sign with its enrollment key.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AttestationChallenge {
+    pub nonce: String,
+    pub expires_at: String,
+}
+
+/// A node's answer to its attestation challenge.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NodeAttestationRequest {
+    /// The nonce being answered.
+    pub nonce: String,
+    /// Base64 Ed25519 public key the answer is signed with; must be one of
+    /// the server's enrollment keys.
+    pub enrollment_public_key: String,
+    /// Base64 signature over `ambient-vcp/node-attestation/v1\n{node_id}\n{nonce}`.
+    pub signature: String,
+    /// Base64 TPM / secure-element quote over the nonce.
+    #[serde(default)]
+    pub quote: Option<String>,
+}
+
+impl NodeAttestationRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.nonce.is_empty() || self.nonce.len() > 128 {
+            return Err(ApiError::bad_request(
+                "nonce must be between 1 and 128 characters",
+            ));
+        }
+        if self
+            .quote
+            .as_ref()
+            .is_some_and(|quote| quote.len() > MAX_QUOTE_B64_BYTES)
+        {
+            return Err(ApiError::bad_request(format!(
+                "quote cannot exceed {} bytes",
+                MAX_QUOTE_B64_BYTES
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn into_response(self) -> ambient_node::AttestationResponse {
+        ambient_node::AttestationResponse {
+            nonce: self.nonce,
+            enrollment_public_key: self.enrollment_public_key,
+            signature: self.signature,
+            quote: self.quote,
+        }
+    }
+}
+
+/// A fresh random nonce (32 bytes, base64).
+pub fn generate_nonce() -> String {
+    use base64::Engine;
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_parse_keys_and_ttl() {
+        let disabled = AttestationPolicy::parse(None, None);
+        assert_eq!(disabled, AttestationPolicy::default());
+        assert!(!disabled.required());
+
+        let policy = AttestationPolicy::parse(Some(" key-a, ,key-b "), Some("0"));
+        assert!(policy.required());
+        assert!(policy.trusts("key-b"));
+        assert!(!policy.trusts("key-c"));
+        assert_eq!(policy.challenge_ttl_secs, DEFAULT_CHALLENGE_TTL_SECS);
+        assert_eq!(
+            AttestationPolicy::parse(None, Some("60")).challenge_ttl_secs,
+            60
+        );
+    }
+
+    #[test]
+    fn test_nonces_are_unique() {
+        assert_ne!(generate_nonce(), generate_nonce());
+    }
+}
@@ -6,12 +6,12 @@
 /// - Password hashing with bcrypt
 use crate::error::{ApiError, ApiResult};
 use axum::{extract::FromRequestParts, http::request::Parts};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use hmac::{Hmac, Mac};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
-use std::sync::Once;
+use std::sync::{Arc, Once, RwLock};
 use utoipa::ToSchema;
 
 type HmacSha256 = Hmac<Sha256>;
@@ -128,6 +128,54 @@ pub fn validate_hash_pepper_configuration() -> ApiResult<()> {
     Ok(())
 }
 
+/// Parse a `CONTROL_SIGNING_KEY` value: a base64 32-byte Ed25519 seed.
+fn parse_control_signing_key(value: &str) -> ApiResult<ambient_node::ControlSigner> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    let seed: [u8; 32] = STANDARD
+        .decode(value.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            ApiError::internal_error(
+                "CONTROL_SIGNING_KEY must be a base64 32-byte seed. Generate with: openssl rand -base64 32",
+            )
+        })?;
+    ambient_node::ControlSigner::from_bytes(seed)
+        .map_err(|e| ApiError::internal_error(format!("CONTROL_SIGNING_KEY: {}", e)))
+}
+
+/// Production requires a valid `CONTROL_SIGNING_KEY` so nodes can pin the
+/// coordinator's control key across restarts; elsewhere it is only checked
+/// when set.
+pub fn validate_control_key_configuration() -> ApiResult<()> {
+    match std::env::var("CONTROL_SIGNING_KEY") {
+        Ok(value) if !value.trim().is_empty() => parse_control_signing_key(&value).map(|_| ()),
+        _ if is_production_environment() => Err(ApiError::internal_error(
+            "PRODUCTION ERROR: CONTROL_SIGNING_KEY must be configured.",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Key that signs heartbeat and gateway-session responses.  Without a valid
+/// `CONTROL_SIGNING_KEY` a key is generated per process, so nodes must
+/// re-pin it after every restart.
+pub fn control_signer_from_env() -> ambient_node::ControlSigner {
+    if let Ok(value) = std::env::var("CONTROL_SIGNING_KEY") {
+        match parse_control_signing_key(&value) {
+            Ok(signer) => return signer,
+            Err(e) => tracing::error!("Ignoring CONTROL_SIGNING_KEY: {}", e.message),
+        }
+    }
+    let signer =
+        ambient_node::ControlSigner::generate().expect("system RNG should produce a control key");
+    tracing::warn!(
+        public_key = %signer.public_key_b64(),
+        "CONTROL_SIGNING_KEY not set; using an ephemeral control key that changes on restart"
+    );
+    signer
+}
+
 /// JWT Claims structure
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
@@ -141,6 +189,17 @@ pub struct Claims {
     pub exp: i64,
     /// Issued at (Unix timestamp)
     pub iat: i64,
+    /// Organization the token acts on behalf of, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+    /// Caller's role in `org_id` when the token was issued (informational;
+    /// access checks read current membership)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_role: Option<String>,
+    /// Scopes granted to this credential; tokens issued before scopes existed
+    /// fall back to the role's scopes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
 }
 
 impl Claims {
@@ -152,20 +211,49 @@ impl Claims {
         Self {
             sub: user_id,
             username,
+            scopes: Some(crate::rbac::role_scopes(&role)),
             role,
             exp: expiration.timestamp(),
             iat: now.timestamp(),
+            org_id: None,
+            org_role: None,
         }
     }
+
+    /// Scopes this credential may use.
+    pub fn granted_scopes(&self) -> Vec<String> {
+        self.scopes
+            .clone()
+            .unwrap_or_else(|| crate::rbac::role_scopes(&self.role))
+    }
+
+    /// Scope the claims to an organization.
+    pub fn with_org(mut self, org_id: uuid::Uuid, org_role: crate::orgs::OrgRole) -> Self {
+        self.org_id = Some(org_id.to_string());
+        self.org_role = Some(org_role.to_string());
+        self
+    }
 }
 
 /// Authentication configuration
+///
+/// Clones share one signing key ring, so rotating it through any clone
+/// affects them all.
 #[derive(Clone)]
 pub struct AuthConfig {
-    /// JWT secret key for token signing
+    /// JWT secret key; verifies legacy HS256 tokens and seals stored
+    /// signing keys
     jwt_secret: String,
     /// JWT token expiration in hours
     pub jwt_expiration_hours: i64,
+    key_rotation_secs: Option<u64>,
+    /// Until when tokens without a `kid` are checked as HS256 against
+    /// `jwt_secret`; never when `None`
+    legacy_hs256_until: Option<DateTime<Utc>>,
+    signing_keys: Arc<RwLock<Arc<crate::jwt_keys::SigningKeyRing>>>,
+    /// Where signing keys are shared with other replicas; see
+    /// [`crate::jwt_keys`]
+    key_store: Option<sqlx::PgPool>,
 }
 
 impl AuthConfig {
@@ -203,10 +291,114 @@ impl AuthConfig {
             .and_then(|v| v.parse().ok())
             .unwrap_or(24);
 
-        Ok(Self {
+        let legacy_hs256_until = match std::env::var("JWT_LEGACY_HS256_UNTIL") {
+            Ok(raw) => Some(
+                DateTime::parse_from_rfc3339(raw.trim())
+                    .map_err(|_| {
+                        ApiError::internal_error(
+                            "JWT_LEGACY_HS256_UNTIL must be an RFC 3339 timestamp",
+                        )
+                    })?
+                    .with_timezone(&Utc),
+            ),
+            Err(_) => None,
+        };
+        if let Some(until) = legacy_hs256_until {
+            tracing::warn!(%until, "Accepting legacy HS256 tokens signed with JWT_SECRET");
+        }
+
+        Ok(Self::new(
             jwt_secret,
             jwt_expiration_hours,
-        })
+            crate::jwt_keys::rotation_secs_from_env(),
+        )
+        .with_legacy_hs256_until(legacy_hs256_until))
+    }
+
+    /// Config whose signing keys rotate every `key_rotation_secs` (never
+    /// when `None`).  Keys are random and held in memory until
+    /// [`Self::with_key_store`] shares them.
+    pub fn new(
+        jwt_secret: String,
+        jwt_expiration_hours: i64,
+        key_rotation_secs: Option<u64>,
+    ) -> Self {
+        let ring = crate::jwt_keys::SigningKeyRing::rotate(
+            None,
+            key_rotation_secs,
+            token_lifetime_secs(jwt_expiration_hours),
+            crate::jwt_keys::period_at(key_rotation_secs, Utc::now().timestamp().max(0) as u64),
+        );
+        Self {
+            jwt_secret,
+            jwt_expiration_hours,
+            key_rotation_secs,
+            legacy_hs256_until: None,
+            signing_keys: Arc::new(RwLock::new(Arc::new(ring))),
+            key_store: None,
+        }
+    }
+
+    /// Keep accepting HS256 tokens without a `kid`, issued before key
+    /// rotation, until `until`.  Set it to one token lifetime after the
+    /// upgrade so those tokens can expire naturally.
+    pub fn with_legacy_hs256_until(mut self, until: Option<DateTime<Utc>>) -> Self {
+        self.legacy_hs256_until = until;
+        self
+    }
+
+    /// Keep signing keys in `db` so every replica, and this one after a
+    /// restart, signs and verifies with the same keys.  Loads the stored
+    /// ring before returning.
+    pub async fn with_key_store(mut self, db: sqlx::PgPool) -> ApiResult<Self> {
+        self.key_store = Some(db);
+        let period = self.signing_keys().period();
+        self.install_ring(period).await?;
+        Ok(self)
+    }
+
+    fn signing_keys(&self) -> Arc<crate::jwt_keys::SigningKeyRing> {
+        self.signing_keys.read().unwrap().clone()
+    }
+
+    /// Move the key ring to the current rotation period.
+    ///
+    /// # Returns
+    /// The `kid` of the new signing key, if the ring rotated.
+    pub async fn rotate_signing_keys(&self) -> ApiResult<Option<String>> {
+        self.rotate_signing_keys_at(Utc::now().timestamp().max(0) as u64)
+            .await
+    }
+
+    /// [`Self::rotate_signing_keys`] at Unix-epoch second `now`.
+    pub async fn rotate_signing_keys_at(&self, now: u64) -> ApiResult<Option<String>> {
+        let period = crate::jwt_keys::period_at(self.key_rotation_secs, now);
+        if period == self.signing_keys().period() {
+            return Ok(None);
+        }
+        self.install_ring(period).await.map(Some)
+    }
+
+    /// Replace the ring with the one for `period`, through the key store
+    /// when there is one, and return its signing `kid`.
+    async fn install_ring(&self, period: u64) -> ApiResult<String> {
+        let mut ring = crate::jwt_keys::SigningKeyRing::rotate(
+            Some(&self.signing_keys()),
+            self.key_rotation_secs,
+            token_lifetime_secs(self.jwt_expiration_hours),
+            period,
+        );
+        if let Some(db) = &self.key_store {
+            ring = crate::jwt_keys::sync_ring(db, &self.jwt_secret, &ring).await?;
+        }
+        let kid = ring.signing_key().0.to_string();
+        *self.signing_keys.write().unwrap() = Arc::new(ring);
+        Ok(kid)
+    }
+
+    /// Public keys that currently verify access tokens.
+    pub fn jwks(&self) -> crate::jwt_keys::JwkSet {
+        self.signing_keys().jwks()
     }
 
     /// Generate a JWT token for a user
@@ -216,14 +408,39 @@ impl AuthConfig {
         username: String,
         role: String,
     ) -> ApiResult<String> {
-        let claims = Claims::new(user_id, username, role, self.jwt_expiration_hours);
+        self.encode_claims(&Claims::new(
+            user_id,
+            username,
+            role,
+            self.jwt_expiration_hours,
+        ))
+    }
 
-        let token = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
-        )
-        .map_err(|e| {
+    /// Generate a JWT token scoped to an organization the user belongs to,
+    /// granting `scopes`
+    pub fn generate_org_token(
+        &self,
+        user_id: String,
+        username: String,
+        role: String,
+        org_id: uuid::Uuid,
+        org_role: crate::orgs::OrgRole,
+        scopes: Vec<String>,
+    ) -> ApiResult<String> {
+        let mut claims = Claims::new(user_id, username, role, self.jwt_expiration_hours)
+            .with_org(org_id, org_role);
+        claims.scopes = Some(scopes);
+        self.encode_claims(&claims)
+    }
+
+    fn encode_claims(&self, claims: &Claims) -> ApiResult<String> {
+        let ring = self.signing_keys();
+        let (kid, key) = ring.signing_key();
+        let header = Header {
+            kid: Some(kid.to_string()),
+            ..Header::new(Algorithm::EdDSA)
+        };
+        let token = encode(&header, claims, key).map_err(|e| {
             tracing::error!("Failed to generate JWT: {:?}", e);
             ApiError::internal_error("Failed to generate authentication token")
         })?;
@@ -232,25 +449,71 @@ impl AuthConfig {
     }
 
     /// Validate a JWT token and extract claims
+    ///
+    /// Tokens without a `kid` predate key rotation.  They are rejected
+    /// unless legacy HS256 tokens are still accepted, in which case they are
+    /// checked against `JWT_SECRET`.
     pub fn validate_token(&self, token: &str) -> ApiResult<Claims> {
-        let validation = Validation::default();
-
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
-            &validation,
-        )?;
+        let Some(kid) = decode_header(token)?.kid else {
+            if self
+                .legacy_hs256_until
+                .is_none_or(|until| Utc::now() >= until)
+            {
+                return Err(ApiError::unauthorized("Invalid authentication token"));
+            }
+            let token_data = decode::<Claims>(
+                token,
+                &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+                &Validation::default(),
+            )?;
+            return Ok(token_data.claims);
+        };
+
+        let ring = self.signing_keys();
+        let key = ring
+            .decoding_key(&kid)
+            .ok_or_else(|| ApiError::unauthorized("Invalid authentication token"))?;
+        let token_data = decode::<Claims>(token, key, &Validation::new(Algorithm::EdDSA))?;
 
         Ok(token_data.claims)
     }
 }
 
+/// How long previous keys stay in the ring: one token lifetime.
+fn token_lifetime_secs(jwt_expiration_hours: i64) -> u64 {
+    jwt_expiration_hours.max(0) as u64 * 3600
+}
+
 /// Authenticated user extracted from request
 #[derive(Debug, Clone)]
 pub struct AuthUser {
     pub user_id: String,
     pub username: String,
     pub role: String,
+    /// Organization context carried by the token, if any
+    pub org_id: Option<String>,
+}
+
+impl AuthUser {
+    pub fn from_claims(claims: &Claims) -> Self {
+        AuthUser {
+            user_id: claims.sub.clone(),
+            username: claims.username.clone(),
+            role: claims.role.clone(),
+            org_id: claims.org_id.clone(),
+        }
+    }
+
+    /// The token's organization context as a UUID.
+    pub fn org_uuid(&self) -> ApiResult<Option<uuid::Uuid>> {
+        self.org_id
+            .as_deref()
+            .map(|org_id| {
+                uuid::Uuid::parse_str(org_id)
+                    .map_err(|_| ApiError::unauthorized("Invalid organization in token"))
+            })
+            .transpose()
+    }
 }
 
 /// Extract authenticated user from Claims stored in request extensions by middleware
@@ -269,11 +532,7 @@ where
             )
         })?;
 
-        Ok(AuthUser {
-            user_id: claims.sub.clone(),
-            username: claims.username.clone(),
-            role: claims.role.clone(),
-        })
+        Ok(AuthUser::from_claims(claims))
     }
 }
 
@@ -412,6 +671,13 @@ pub fn hash_connect_session_token(token: &str) -> String {
     hash_with_hmac_sha256(token, &pepper)
 }
 
+/// Hash password reset and email verification tokens for storage and lookup.
+///
+/// Uses the refresh-token pepper, so no further secret needs provisioning.
+pub fn hash_account_token(token: &str) -> String {
+    hash_refresh_token(&format!("account:{token}"))
+}
+
 fn hash_with_hmac_sha256(input: &str, pepper: &str) -> String {
     let mut mac = HmacSha256::new_from_slice(pepper.as_bytes())
         .expect("HMAC accepts keys of any size for SHA256");
@@ -450,12 +716,7 @@ impl RegisterRequest {
             ));
         }
 
-        // Validate password strength
-        if self.password.len() < 8 {
-            return Err(ApiError::validation_error(
-                "Password must be at least 8 characters",
-            ));
-        }
+        validate_password(&self.password)?;
 
         if let Some(email) = &self.email {
             let trimmed = email.trim();
@@ -486,6 +747,73 @@ impl RegisterRequest {
     }
 }
 
+/// Password strength rules shared by registration and password reset.
+pub fn validate_password(password: &str) -> ApiResult<()> {
+    if password.len() < 8 {
+        return Err(ApiError::validation_error(
+            "Password must be at least 8 characters",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Request a password reset token, mailed to the account's email
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasswordResetRequest {
+    /// Username of the account to reset
+    pub username: String,
+}
+
+impl PasswordResetRequest {
+    pub fn validate(&self) -> ApiResult<()> {
+        if self.username.is_empty() || self.username.len() > 64 {
+            return Err(ApiError::validation_error(
+                "Username must be 1-64 characters",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Redeem a password reset token
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasswordResetConfirmRequest {
+    /// Token from the reset email
+    pub token: String,
+    /// New password (minimum 8 characters)
+    pub new_password: String,
+}
+
+impl PasswordResetConfirmRequest {
+    pub fn validate(&self) -> ApiResult<()> {
+        validate_account_token(&self.token)?;
+        validate_password(&self.new_password)
+    }
+}
+
+/// Redeem an email verification token
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EmailVerificationRequest {
+    /// Token from the verification email
+    pub token: String,
+}
+
+impl EmailVerificationRequest {
+    pub fn validate(&self) -> ApiResult<()> {
+        validate_account_token(&self.token)
+    }
+}
+
+fn validate_account_token(token: &str) -> ApiResult<()> {
+    if token.is_empty() || token.len() > 128 {
+        return Err(ApiError::validation_error("Token must be 1-128 characters"));
+    }
+
+    Ok(())
+}
+
 /// Read the bcrypt cost factor from the `BCRYPT_COST` environment variable.
 ///
 /// Defaults to `12` if unset.  Accepted range: 4–31 (bcrypt limits).
@@ -530,6 +858,131 @@ pub async fn verify_password_async(password: String, hash: String) -> ApiResult<
         .map_err(|_| ApiError::internal_error("Password verification task failed"))?
 }
 
+/// Check `password` against a throwaway hash at the configured cost, so a
+/// login for an unknown username takes as long as a wrong password.
+pub async fn verify_decoy_password_async(password: String) -> ApiResult<()> {
+    static DECOY_HASH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    tokio::task::spawn_blocking(move || {
+        let hash = match DECOY_HASH.get() {
+            Some(hash) => hash,
+            None => {
+                let hash = hash_password("decoy-password-for-unknown-users")?;
+                DECOY_HASH.get_or_init(|| hash)
+            }
+        };
+        verify_password(&password, hash).map(|_| ())
+    })
+    .await
+    .map_err(|_| ApiError::internal_error("Password verification task failed"))?
+}
+
+/// Scopes granted to the key issued at registration and to new keys that do
+/// not request explicit scopes.
+pub const DEFAULT_API_KEY_SCOPES: &[&str] = &["tasks:read", "tasks:write"];
+
+/// Maximum number of non-revoked API keys a single user may hold.
+pub const MAX_ACTIVE_API_KEYS_PER_USER: i64 = 25;
+
+/// Number of leading key characters stored in cleartext for identification.
+const API_KEY_PREFIX_LENGTH: usize = 8;
+
+/// Cleartext prefix stored alongside the key hash so users can tell keys apart.
+pub fn api_key_prefix(key: &str) -> String {
+    key.chars().take(API_KEY_PREFIX_LENGTH).collect()
+}
+
+/// API key creation request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// Human-readable label (1-128 characters)
+    pub name: String,
+    /// Scopes granted to the key; defaults to `tasks:read` and `tasks:write`
+    pub scopes: Option<Vec<String>>,
+    /// Days until the key expires (1-365); omit for a non-expiring key
+    pub expires_in_days: Option<i64>,
+}
+
+impl CreateApiKeyRequest {
+    /// Validate API key creation request
+    pub fn validate(&self) -> ApiResult<()> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(ApiError::validation_error("API key name cannot be empty"));
+        }
+
+        if name.len() > 128 {
+            return Err(ApiError::validation_error(
+                "API key name cannot exceed 128 characters",
+            ));
+        }
+
+        if let Some(scopes) = &self.scopes {
+            if scopes.is_empty() {
+                return Err(ApiError::validation_error(
+                    "scopes cannot be empty when provided",
+                ));
+            }
+
+            if let Some(unknown) = scopes
+                .iter()
+                .find(|scope| !crate::rbac::is_known_scope(scope))
+            {
+                return Err(ApiError::validation_error(format!(
+                    "Unknown scope '{}'. Valid scopes: {}, <resource>:*, *",
+                    unknown,
+                    crate::rbac::SCOPES.join(", ")
+                )));
+            }
+        }
+
+        if let Some(days) = self.expires_in_days {
+            if !(1..=365).contains(&days) {
+                return Err(ApiError::validation_error(
+                    "expires_in_days must be between 1 and 365",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scopes to grant, falling back to [`DEFAULT_API_KEY_SCOPES`].
+    pub fn resolved_scopes(&self) -> Vec<String> {
+        let mut scopes = match &self.scopes {
+            Some(scopes) => scopes.clone(),
+            None => DEFAULT_API_KEY_SCOPES
+                .iter()
+                .map(|scope| scope.to_string())
+                .collect(),
+        };
+        scopes.sort();
+        scopes.dedup();
+        scopes
+    }
+}
+
+/// API key metadata (never includes the key itself)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyInfo {
+    pub key_id: String,
+    pub name: Option<String>,
+    /// First characters of the key, for identification only
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+/// API key creation response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    pub key: ApiKeyInfo,
+    /// The full API key; shown once and never retrievable again
+    pub api_key: String,
+}
+
 /// Generate a secure API key
 pub fn generate_api_key() -> String {
     use rand::Rng;
@@ -601,6 +1054,124 @@ mod tests {
         let key = generate_api_key();
         assert!(key.starts_with("vcp_"));
         assert_eq!(key.len(), 36); // "vcp_" + 32 characters
+        assert_eq!(api_key_prefix(&key), key[..8]);
+    }
+
+    #[test]
+    fn test_create_api_key_request_validation() {
+        let valid = CreateApiKeyRequest {
+            name: "ci-pipeline".to_string(),
+            scopes: Some(vec!["tasks:read".to_string(), "nodes:read".to_string()]),
+            expires_in_days: Some(90),
+        };
+        assert!(valid.validate().is_ok());
+
+        let unknown_scope = CreateApiKeyRequest {
+            name: "ci-pipeline".to_string(),
+            scopes: Some(vec!["admin:write".to_string()]),
+            expires_in_days: None,
+        };
+        assert!(unknown_scope.validate().is_err());
+
+        let bad_expiry = CreateApiKeyRequest {
+            name: "ci-pipeline".to_string(),
+            scopes: None,
+            expires_in_days: Some(0),
+        };
+        assert!(bad_expiry.validate().is_err());
+
+        let blank_name = CreateApiKeyRequest {
+            name: "   ".to_string(),
+            scopes: None,
+            expires_in_days: None,
+        };
+        assert!(blank_name.validate().is_err());
+    }
+
+    #[test]
+    fn test_create_api_key_request_resolves_default_scopes() {
+        let request = CreateApiKeyRequest {
+            name: "default".to_string(),
+            scopes: None,
+            expires_in_days: None,
+        };
+        assert_eq!(
+            request.resolved_scopes(),
+            vec!["tasks:read".to_string(), "tasks:write".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tokens_stay_valid_across_key_rotation() {
+        let config = AuthConfig::new("rotation-test-secret".to_string(), 24, Some(3600));
+        let shared = config.clone();
+        let now = Utc::now().timestamp() as u64;
+        let initial = config.jwks();
+        let token = config
+            .generate_token("user-1".into(), "alice".into(), "user".into())
+            .unwrap();
+        assert_eq!(
+            decode_header(&token).unwrap().kid,
+            Some(initial.keys[1].kid.clone())
+        );
+
+        // The next key was published before it started signing.
+        let rotated = config
+            .rotate_signing_keys_at(now + 3600)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rotated, initial.keys[0].kid);
+        assert_eq!(shared.jwks().keys[1].kid, rotated);
+        assert_eq!(shared.validate_token(&token).unwrap().sub, "user-1");
+        let fresh = shared
+            .generate_token("user-1".into(), "alice".into(), "user".into())
+            .unwrap();
+        assert_eq!(decode_header(&fresh).unwrap().kid, Some(rotated));
+
+        // Keys leave the ring once their tokens have expired.
+        config
+            .rotate_signing_keys_at(now + 30 * 3600)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(config.validate_token(&token).is_err());
+    }
+
+    #[test]
+    fn test_tokens_without_kid_are_rejected_unless_legacy_tokens_are_accepted() {
+        let secret = "rotation-test-secret";
+        let config = AuthConfig::new(secret.to_string(), 24, None);
+        let claims = Claims::new("user-1".into(), "alice".into(), "user".into(), 1);
+        let legacy = encode(
+            &Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap();
+        assert!(config.validate_token(&legacy).is_err());
+
+        let expired = config
+            .clone()
+            .with_legacy_hs256_until(Some(Utc::now() - Duration::seconds(1)));
+        assert!(expired.validate_token(&legacy).is_err());
+
+        let accepting = config
+            .clone()
+            .with_legacy_hs256_until(Some(Utc::now() + Duration::hours(24)));
+        assert_eq!(accepting.validate_token(&legacy).unwrap().sub, "user-1");
+
+        // An HS256 token cannot borrow a kid to pass as a rotated key.
+        let forged = encode(
+            &Header {
+                kid: Some(config.jwks().keys[0].kid.clone()),
+                ..Header::default()
+            },
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap();
+        assert!(accepting.validate_token(&forged).is_err());
     }
 
     #[test]
@@ -664,4 +1235,24 @@ mod tests {
         let result = validate_hash_pepper_configuration();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_production_startup_guard_requires_valid_control_key() {
+        let _guard = env_test_lock().lock().unwrap();
+        let _environment = EnvVarGuard::set("ENVIRONMENT", "production");
+        let missing = EnvVarGuard::remove("CONTROL_SIGNING_KEY");
+        assert!(validate_control_key_configuration().is_err());
+        drop(missing);
+
+        let _short = EnvVarGuard::set("CONTROL_SIGNING_KEY", "c2hvcnQ=");
+        assert!(validate_control_key_configuration().is_err());
+
+        let seed = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+        let _valid = EnvVarGuard::set("CONTROL_SIGNING_KEY", seed);
+        assert!(validate_control_key_configuration().is_ok());
+        assert_eq!(
+            control_signer_from_env().public_key_b64(),
+            parse_control_signing_key(seed).unwrap().public_key_b64()
+        );
+    }
 }
@@ -0,0 +1,320 @@
+/// Synthetic end-to-end canaries
+///
+/// With `CANARY_USER_ID` set, a job submits a tiny task and a short connect
+/// session every `CANARY_INTERVAL_SECS` (default `300`, `0` disables), each
+/// pinned to a node picked at random among the online nodes that can take
+/// it, and follows them through the full path:
+///
+/// - task canaries — assignment, execution (the node's first heartbeat on
+///   the assignment), result and verification of the computed value
+/// - connect session canaries — assignment, relay (the relay picked the
+///   session up), usage (its gateway reported usage) and verification that
+///   stopping the session ended it and completed its task
+///
+/// Each stage's latency is recorded on the run and in the
+/// `canary_stage_duration_seconds` histogram.  A run that has not passed
+/// within `CANARY_TIMEOUT_SECS` (default `240`) fails at the stage it
+/// reached, and a failed run notifies the canary user through the
+/// notification outbox.  Canary tasks belong to the canary user, which
+/// must exist; connect session canaries use the destination policy
+/// `CANARY_DESTINATION_POLICY_ID` (default `policy_web_basic_v1`).
+/// `GET /api/v1/admin/canaries` lists recent runs.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const DEFAULT_INTERVAL_SECS: u64 = 300;
+pub const DEFAULT_TIMEOUT_SECS: u64 = 240;
+pub const DEFAULT_DESTINATION_POLICY_ID: &str = "policy_web_basic_v1";
+
+/// Seconds between checks on running canary runs.
+pub const CHECK_INTERVAL_SECS: u64 = 5;
+
+/// Longest canary timeout, so a canary task always times out before its
+/// `max_execution_time_sec` lets the server synthesize a result for it.
+pub const MAX_TIMEOUT_SECS: u64 = 1200;
+
+/// Seconds a canary task may run beyond the canary timeout.
+pub const TASK_EXECUTION_MARGIN_SECS: u64 = 60;
+
+/// Most runs one `GET /api/v1/admin/canaries` request returns.
+pub const MAX_CANARY_RUNS: i64 = 500;
+
+/// Canary job settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanaryConfig {
+    /// Owner of canary tasks and recipient of canary alerts
+    pub user_id: Uuid,
+    /// Time between canary runs of each kind
+    pub interval: Duration,
+    /// Time a run has to pass before it fails
+    pub timeout: Duration,
+    /// Destination policy of connect session canaries
+    pub destination_policy_id: String,
+}
+
+impl CanaryConfig {
+    /// Settings from `CANARY_*`, or `None` while canaries are off.
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok();
+        Self::parse(
+            var("CANARY_USER_ID").as_deref(),
+            var("CANARY_INTERVAL_SECS").as_deref(),
+            var("CANARY_TIMEOUT_SECS").as_deref(),
+            var("CANARY_DESTINATION_POLICY_ID").as_deref(),
+        )
+    }
+
+    pub fn parse(
+        user_id: Option<&str>,
+        interval_secs: Option<&str>,
+        timeout_secs: Option<&str>,
+        destination_policy_id: Option<&str>,
+    ) -> Option<Self> {
+        let user_id = user_id.and_then(|v| Uuid::parse_str(v.trim()).ok())?;
+        let interval_secs = interval_secs
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_INTERVAL_SECS);
+        if interval_secs == 0 {
+            return None;
+        }
+        let timeout_secs = timeout_secs
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(DEFAULT_TIMEOUT_SECS)
+            .min(MAX_TIMEOUT_SECS);
+        let destination_policy_id = destination_policy_id
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .unwrap_or(DEFAULT_DESTINATION_POLICY_ID)
+            .to_string();
+        Some(Self {
+            user_id,
+            interval: Duration::from_secs(interval_secs),
+            timeout: Duration::from_secs(timeout_secs),
+            destination_policy_id,
+        })
+    }
+}
+
+/// What a canary run exercises.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryKind {
+    /// A `computation` task
+    Task,
+    /// A `connect_only` task and its connect session
+    ConnectSession,
+}
+
+impl CanaryKind {
+    pub const ALL: [CanaryKind; 2] = [CanaryKind::Task, CanaryKind::ConnectSession];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CanaryKind::Task => "task",
+            CanaryKind::ConnectSession => "connect_session",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+
+    /// Stages a run of this kind passes through, in order.
+    pub fn stages(&self) -> &'static [CanaryStage] {
+        match self {
+            CanaryKind::Task => &[
+                CanaryStage::Assignment,
+                CanaryStage::Execution,
+                CanaryStage::Result,
+                CanaryStage::Verification,
+            ],
+            CanaryKind::ConnectSession => &[
+                CanaryStage::Assignment,
+                CanaryStage::Relay,
+                CanaryStage::Usage,
+                CanaryStage::Verification,
+            ],
+        }
+    }
+}
+
+/// A step of a canary run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryStage {
+    Assignment,
+    Execution,
+    Relay,
+    Result,
+    Usage,
+    Verification,
+}
+
+impl CanaryStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CanaryStage::Assignment => "assignment",
+            CanaryStage::Execution => "execution",
+            CanaryStage::Relay => "relay",
+            CanaryStage::Result => "result",
+            CanaryStage::Usage => "usage",
+            CanaryStage::Verification => "verification",
+        }
+    }
+}
+
+/// Operands of a task canary's expression; the node must return their sum.
+pub fn task_inputs(a: u32, b: u32) -> (serde_json::Value, serde_json::Value) {
+    (
+        serde_json::json!({ "expression": format!("{a} + {b}") }),
+        serde_json::json!({ "result": f64::from(a) + f64::from(b) }),
+    )
+}
+
+/// Check a task canary's result against what its inputs should produce.
+pub fn verify_task_result(
+    result: Option<&serde_json::Value>,
+    expected: &serde_json::Value,
+) -> Result<(), String> {
+    let want = expected
+        .get("result")
+        .and_then(|v| v.as_f64())
+        .ok_or_else(|| "canary run has no expected result".to_string())?;
+    let got = result
+        .and_then(|r| r.get("result"))
+        .and_then(|v| v.as_f64())
+        .ok_or_else(|| "task result has no numeric result".to_string())?;
+    if (got - want).abs() > 1e-9 {
+        return Err(format!("task returned {got}, expected {want}"));
+    }
+    Ok(())
+}
+
+/// The stage a run is waiting on, given when it reached each stage so far
+/// (`None` for stages not reached), or `None` once every stage passed.
+pub fn current_stage(
+    kind: CanaryKind,
+    reached: &[(CanaryStage, Option<DateTime<Utc>>)],
+) -> Option<CanaryStage> {
+    kind.stages().iter().copied().find(|stage| {
+        !reached
+            .iter()
+            .any(|(reached_stage, at)| reached_stage == stage && at.is_some())
+    })
+}
+
+/// Milliseconds each reached stage took since the previous one (or since
+/// `started_at` for the first).  Stages not reached are left out.
+pub fn stage_latencies(
+    started_at: DateTime<Utc>,
+    reached: &[(CanaryStage, Option<DateTime<Utc>>)],
+) -> BTreeMap<String, i64> {
+    let mut previous = started_at;
+    let mut latencies = BTreeMap::new();
+    for (stage, at) in reached {
+        if let Some(at) = at {
+            latencies.insert(
+                stage.as_str().to_string(),
+                (*at - previous).num_milliseconds().max(0),
+            );
+            previous = previous.max(*at);
+        }
+    }
+    latencies
+}
+
+/// One canary run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CanaryRun {
+    pub run_id: String,
+    pub kind: CanaryKind,
+    pub node_id: String,
+    /// Canary task, `None` once retention removed it
+    pub task_id: Option<String>,
+    /// Connect session of a `connect_session` run
+    pub session_id: Option<String>,
+    /// `running`, `passed` or `failed`
+    pub status: String,
+    /// Stage the run is waiting on, failed at, or `verification` once passed
+    pub stage: String,
+    /// Milliseconds each reached stage took, keyed by stage
+    pub stage_latencies_ms: BTreeMap<String, i64>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Query string for `GET /api/v1/admin/canaries`
+#[derive(Debug, Default, Deserialize)]
+pub struct CanaryRunsQuery {
+    /// Only runs of this kind: `task` or `connect_session`
+    pub kind: Option<String>,
+    /// Only runs with this status: `running`, `passed` or `failed`
+    pub status: Option<String>,
+    /// Most runs to return, newest first (default 50, at most 500)
+    pub limit: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_requires_a_canary_user() {
+        let user = "6f9619ff-8b86-d011-b42d-00c04fc964ff";
+        assert_eq!(CanaryConfig::parse(None, None, None, None), None);
+        assert_eq!(CanaryConfig::parse(Some("nope"), None, None, None), None);
+        assert_eq!(CanaryConfig::parse(Some(user), Some("0"), None, None), None);
+
+        let config = CanaryConfig::parse(Some(user), None, Some("99999"), Some(" ")).unwrap();
+        assert_eq!(config.interval, Duration::from_secs(DEFAULT_INTERVAL_SECS));
+        assert_eq!(config.timeout, Duration::from_secs(MAX_TIMEOUT_SECS));
+        assert_eq!(config.destination_policy_id, DEFAULT_DESTINATION_POLICY_ID);
+    }
+
+    #[test]
+    fn task_results_are_verified_against_inputs() {
+        let (inputs, expected) = task_inputs(17, 25);
+        assert_eq!(inputs["expression"], "17 + 25");
+        assert!(verify_task_result(Some(&serde_json::json!({"result": 42.0})), &expected).is_ok());
+        assert_eq!(
+            verify_task_result(Some(&serde_json::json!({"result": 41})), &expected),
+            Err("task returned 41, expected 42".to_string())
+        );
+        assert!(verify_task_result(None, &expected).is_err());
+    }
+
+    #[test]
+    fn stages_and_latencies_follow_reached_stages() {
+        let started = Utc::now();
+        let secs = |s| Some(started + chrono::Duration::seconds(s));
+        let reached = [
+            (CanaryStage::Assignment, secs(1)),
+            (CanaryStage::Execution, secs(4)),
+            (CanaryStage::Result, None),
+        ];
+        assert_eq!(
+            current_stage(CanaryKind::Task, &reached),
+            Some(CanaryStage::Result)
+        );
+        let latencies = stage_latencies(started, &reached);
+        assert_eq!(latencies.get("assignment"), Some(&1000));
+        assert_eq!(latencies.get("execution"), Some(&3000));
+        assert!(!latencies.contains_key("result"));
+
+        assert_eq!(
+            current_stage(CanaryKind::ConnectSession, &reached),
+            Some(CanaryStage::Relay)
+        );
+        let all: Vec<_> = CanaryKind::Task
+            .stages()
+            .iter()
+            .map(|stage| (*stage, secs(1)))
+            .collect();
+        assert_eq!(current_stage(CanaryKind::Task, &all), None);
+    }
+}
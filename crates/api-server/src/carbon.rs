@@ -0,0 +1,119 @@
+/// Grid carbon intensity configuration used for energy/CO2 reporting
+///
+/// Intensities are expressed in grams of CO2-equivalent per kWh and keyed by
+/// node region.  Configure them with:
+///
+/// - `CARBON_GRID_FACTORS` — comma-separated `region=g_per_kwh` pairs,
+///   e.g. `us-west=210,eu-north=30`
+/// - `CARBON_DEFAULT_GRID_FACTOR` — fallback for regions not listed
+///   (defaults to the global average of 475 g/kWh)
+use std::collections::HashMap;
+
+/// Global average grid intensity (g CO2e / kWh) used when nothing is configured.
+pub const DEFAULT_GRID_FACTOR_G_PER_KWH: f64 = 475.0;
+
+/// Region → grid carbon intensity lookup table.
+#[derive(Debug, Clone)]
+pub struct GridCarbonFactors {
+    factors: HashMap<String, f64>,
+    default_factor: f64,
+}
+
+impl Default for GridCarbonFactors {
+    fn default() -> Self {
+        Self {
+            factors: HashMap::new(),
+            default_factor: DEFAULT_GRID_FACTOR_G_PER_KWH,
+        }
+    }
+}
+
+impl GridCarbonFactors {
+    /// Load factors from `CARBON_GRID_FACTORS` and `CARBON_DEFAULT_GRID_FACTOR`.
+    pub fn from_env() -> Self {
+        Self::parse(
+            std::env::var("CARBON_GRID_FACTORS").ok().as_deref(),
+            std::env::var("CARBON_DEFAULT_GRID_FACTOR").ok().as_deref(),
+        )
+    }
+
+    /// Parse factor configuration; malformed or negative entries are skipped.
+    pub fn parse(factors: Option<&str>, default_factor: Option<&str>) -> Self {
+        let factors = factors
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let (region, value) = entry.split_once('=')?;
+                let region = region.trim();
+                let value = parse_factor(value)?;
+                (!region.is_empty()).then(|| (region.to_string(), value))
+            })
+            .collect();
+
+        let default_factor = default_factor
+            .and_then(parse_factor)
+            .unwrap_or(DEFAULT_GRID_FACTOR_G_PER_KWH);
+
+        Self {
+            factors,
+            default_factor,
+        }
+    }
+
+    /// Factors for exactly the listed regions, as recorded with a
+    /// scheduling decision.
+    pub fn from_factors(factors: HashMap<String, f64>) -> Self {
+        Self {
+            factors,
+            ..Self::default()
+        }
+    }
+
+    /// Grid intensity for a region in g CO2e / kWh.
+    pub fn factor_for(&self, region: &str) -> f64 {
+        self.factors
+            .get(region)
+            .copied()
+            .unwrap_or(self.default_factor)
+    }
+
+    /// Estimated emissions in grams for `energy_wh` consumed in `region`.
+    pub fn estimate_co2_grams(&self, region: &str, energy_wh: f64) -> f64 {
+        energy_wh / 1000.0 * self.factor_for(region)
+    }
+}
+
+fn parse_factor(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|parsed| parsed.is_finite() && *parsed >= 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_region_factors_and_default() {
+        let factors = GridCarbonFactors::parse(Some("us-west=210, eu-north=30"), Some("400"));
+        assert_eq!(factors.factor_for("us-west"), 210.0);
+        assert_eq!(factors.factor_for("eu-north"), 30.0);
+        assert_eq!(factors.factor_for("ap-south"), 400.0);
+    }
+
+    #[test]
+    fn skips_malformed_entries() {
+        let factors = GridCarbonFactors::parse(Some("us-west,=12,eu=-5,ap=abc"), Some("nope"));
+        assert_eq!(factors.factor_for("us-west"), DEFAULT_GRID_FACTOR_G_PER_KWH);
+        assert_eq!(factors.factor_for("eu"), DEFAULT_GRID_FACTOR_G_PER_KWH);
+        assert_eq!(factors.factor_for("ap"), DEFAULT_GRID_FACTOR_G_PER_KWH);
+    }
+
+    #[test]
+    fn estimates_co2_from_watt_hours() {
+        let factors = GridCarbonFactors::parse(Some("eu-north=30"), None);
+        assert!((factors.estimate_co2_grams("eu-north", 2_000.0) - 60.0).abs() < 1e-9);
+    }
+}
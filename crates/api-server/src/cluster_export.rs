@@ -0,0 +1,863 @@
+/// Snapshot-consistent cluster exports for offline analysis
+///
+/// `POST /api/v1/admin/cluster-exports` starts a background job that reads
+/// [`TABLES`] (nodes, telemetry rollups, tasks, task assignments and connect
+/// sessions) inside one `REPEATABLE READ READ ONLY` transaction, so every
+/// file reflects the same instant.  Each table is anonymized by an
+/// [`AnonymizationPolicy`], encoded as Parquet or CSV and stored in the
+/// artifact store under its SHA3-256 hash.
+///
+/// An incremental export only carries rows that changed since the last
+/// completed export's snapshot, less [`INCREMENTAL_OVERLAP_SECS`] to cover
+/// transactions that committed after that snapshot was taken.  Overlapping
+/// rows repeat, so consumers keep the newest row per key.
+///
+/// Configure the default policy with:
+///
+/// - `CLUSTER_EXPORT_IDENTIFIERS` — `hash` (default), `keep` or `drop`
+///   applied to node, task, session, user and org IDs
+/// - `CLUSTER_EXPORT_HASH_KEY` — HMAC key for hashed identifiers.  Without
+///   it every export uses a fresh random key, so hashes do not join across
+///   exports
+/// - `CLUSTER_EXPORT_DROP_COLUMNS` — comma-separated `table.column` list
+///   left out of every export
+/// - `CLUSTER_EXPORT_TIMESTAMP_PRECISION_SECS` — round timestamps down to
+///   this many seconds (default 0, exact)
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::Row;
+use std::sync::Arc;
+
+/// How far an incremental export reaches back before the previous snapshot.
+pub const INCREMENTAL_OVERLAP_SECS: i64 = 300;
+
+/// A running export older than this is treated as interrupted.
+pub const STALE_EXPORT_SECS: i64 = 3600;
+
+/// Most exports one `GET /api/v1/admin/cluster-exports` request returns.
+pub const MAX_CLUSTER_EXPORTS: i64 = 200;
+
+/// File encoding of an export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Parquet,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Parquet => "parquet",
+            Self::Csv => "csv",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "parquet" => Some(Self::Parquet),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Parquet => "application/vnd.apache.parquet",
+            Self::Csv => "text/csv",
+        }
+    }
+}
+
+/// What happens to identifier columns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentifierMode {
+    /// Replace with a keyed HMAC-SHA256, so rows still join
+    #[default]
+    Hash,
+    /// Export as stored
+    Keep,
+    /// Leave the columns out
+    Drop,
+}
+
+/// How exported rows are anonymized.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnonymizationPolicy {
+    #[serde(default)]
+    pub identifiers: IdentifierMode,
+    /// `table.column` entries left out
+    #[serde(default)]
+    pub drop_columns: Vec<String>,
+    /// Round timestamps down to this many seconds; 0 keeps them exact
+    #[serde(default)]
+    pub timestamp_precision_secs: u64,
+}
+
+impl AnonymizationPolicy {
+    pub fn from_env() -> Self {
+        Self::parse(
+            std::env::var("CLUSTER_EXPORT_IDENTIFIERS").ok().as_deref(),
+            std::env::var("CLUSTER_EXPORT_DROP_COLUMNS").ok().as_deref(),
+            std::env::var("CLUSTER_EXPORT_TIMESTAMP_PRECISION_SECS")
+                .ok()
+                .as_deref(),
+        )
+    }
+
+    /// Unknown identifier modes fall back to hashing.
+    pub fn parse(
+        identifiers: Option<&str>,
+        drop_columns: Option<&str>,
+        timestamp_precision_secs: Option<&str>,
+    ) -> Self {
+        let identifiers = match identifiers.map(str::trim) {
+            Some("keep") => IdentifierMode::Keep,
+            Some("drop") => IdentifierMode::Drop,
+            _ => IdentifierMode::Hash,
+        };
+        let drop_columns = drop_columns
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self {
+            identifiers,
+            drop_columns,
+            timestamp_precision_secs: timestamp_precision_secs
+                .and_then(|secs| secs.trim().parse().ok())
+                .unwrap_or(0),
+        }
+    }
+
+    /// Reject `drop_columns` entries that name no exported column, so a typo
+    /// cannot leak the column it meant to drop.
+    pub fn validate(&self) -> Result<(), String> {
+        for entry in &self.drop_columns {
+            let known = entry.split_once('.').is_some_and(|(table, column)| {
+                TABLES.iter().any(|spec| {
+                    spec.name == table && spec.columns.iter().any(|col| col.name == column)
+                })
+            });
+            if !known {
+                return Err(format!(
+                    "drop_columns entry '{entry}' is not an exported table.column"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn drops(&self, table: &str, column: &ColumnSpec) -> bool {
+        (column.identifier && self.identifiers == IdentifierMode::Drop)
+            || self
+                .drop_columns
+                .iter()
+                .any(|entry| entry.split_once('.') == Some((table, column.name)))
+    }
+}
+
+/// Type of an exported column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Text,
+    Int,
+    Float,
+    Bool,
+    Timestamp,
+}
+
+/// One exported column: its name, the SQL producing it and whether it
+/// identifies a node, task, session, user or org.
+#[derive(Debug)]
+pub struct ColumnSpec {
+    pub name: &'static str,
+    /// Cast to TEXT, BIGINT, DOUBLE PRECISION, BOOLEAN or TIMESTAMPTZ to
+    /// match `kind`
+    pub sql: &'static str,
+    pub kind: ColumnKind,
+    pub identifier: bool,
+}
+
+/// One exported table.
+#[derive(Debug)]
+pub struct TableSpec {
+    pub name: &'static str,
+    pub from: &'static str,
+    /// Predicate on `$1` (the incremental cutoff) selecting changed rows
+    pub changed_since: &'static str,
+    pub order_by: &'static str,
+    pub columns: &'static [ColumnSpec],
+}
+
+impl TableSpec {
+    /// Query for this table; `$1` is the incremental cutoff or NULL for all
+    /// rows.
+    pub fn select_sql(&self) -> String {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| format!("{} AS {}", column.sql, column.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "SELECT {columns} FROM {} WHERE ($1::TIMESTAMPTZ IS NULL OR {}) ORDER BY {}",
+            self.from, self.changed_since, self.order_by
+        )
+    }
+
+    /// Values of one result row, in column order.
+    pub fn decode_row(&self, row: &sqlx::postgres::PgRow) -> Result<Vec<ExportValue>, sqlx::Error> {
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| {
+                Ok(match column.kind {
+                    ColumnKind::Text => row
+                        .try_get::<Option<String>, _>(index)?
+                        .map_or(ExportValue::Null, ExportValue::Text),
+                    ColumnKind::Int => row
+                        .try_get::<Option<i64>, _>(index)?
+                        .map_or(ExportValue::Null, ExportValue::Int),
+                    ColumnKind::Float => row
+                        .try_get::<Option<f64>, _>(index)?
+                        .map_or(ExportValue::Null, ExportValue::Float),
+                    ColumnKind::Bool => row
+                        .try_get::<Option<bool>, _>(index)?
+                        .map_or(ExportValue::Null, ExportValue::Bool),
+                    ColumnKind::Timestamp => row
+                        .try_get::<Option<DateTime<Utc>>, _>(index)?
+                        .map_or(ExportValue::Null, ExportValue::Timestamp),
+                })
+            })
+            .collect()
+    }
+}
+
+const fn col(name: &'static str, sql: &'static str, kind: ColumnKind) -> ColumnSpec {
+    ColumnSpec {
+        name,
+        sql,
+        kind,
+        identifier: false,
+    }
+}
+
+const fn id(name: &'static str, sql: &'static str) -> ColumnSpec {
+    ColumnSpec {
+        name,
+        sql,
+        kind: ColumnKind::Text,
+        identifier: true,
+    }
+}
+
+use ColumnKind::{Bool, Float, Int, Text, Timestamp};
+
+/// Everything an export covers.  Free-form columns (labels, inputs,
+/// results, errors, keys) are never exported.
+pub const TABLES: &[TableSpec] = &[
+    TableSpec {
+        name: "nodes",
+        from: "nodes",
+        changed_since: "GREATEST(updated_at, deleted_at) > $1",
+        order_by: "node_id",
+        columns: &[
+            id("node_id", "node_id::TEXT"),
+            id("owner_id", "owner_id::TEXT"),
+            id("org_id", "org_id::TEXT"),
+            col("region", "region::TEXT", Text),
+            col("node_type", "node_type::TEXT", Text),
+            col("status", "status::TEXT", Text),
+            col("connect_slots", "connect_slots::BIGINT", Int),
+            col("wasm_slots", "wasm_slots::BIGINT", Int),
+            col("gpu_slots", "gpu_slots::BIGINT", Int),
+            col("asn", "asn::BIGINT", Int),
+            col("bandwidth_mbps", "bandwidth_mbps::DOUBLE PRECISION", Float),
+            col("cpu_cores", "cpu_cores::BIGINT", Int),
+            col("memory_gb", "memory_gb::DOUBLE PRECISION", Float),
+            col("gpu_available", "gpu_available", Bool),
+            col("health_score", "health_score::DOUBLE PRECISION", Float),
+            col(
+                "benchmark_ops_per_wh",
+                "benchmark_ops_per_wh::DOUBLE PRECISION",
+                Float,
+            ),
+            col("flap_count", "flap_count::BIGINT", Int),
+            col("registered_at", "registered_at::TIMESTAMPTZ", Timestamp),
+            col("last_heartbeat", "last_heartbeat::TIMESTAMPTZ", Timestamp),
+            col("updated_at", "updated_at::TIMESTAMPTZ", Timestamp),
+            col("deleted_at", "deleted_at::TIMESTAMPTZ", Timestamp),
+        ],
+    },
+    TableSpec {
+        name: "node_telemetry_rollups",
+        from: "node_telemetry_rollups",
+        // Open buckets are rewritten until they close; a day covers the
+        // widest (1d) bucket.
+        changed_since: "bucket_start >= $1 - INTERVAL '1 day'",
+        order_by: "node_id, resolution, bucket_start",
+        columns: &[
+            id("node_id", "node_id::TEXT"),
+            col("resolution", "resolution::TEXT", Text),
+            col("bucket_start", "bucket_start::TIMESTAMPTZ", Timestamp),
+            col("samples", "samples::BIGINT", Int),
+            col("health_score_avg", "health_score_avg", Float),
+            col("health_score_min", "health_score_min", Float),
+            col("cpu_usage_avg", "cpu_usage_avg", Float),
+            col("cpu_usage_max", "cpu_usage_max", Float),
+            col("memory_usage_avg", "memory_usage_avg", Float),
+            col("memory_usage_max", "memory_usage_max", Float),
+            col("network_latency_ms_avg", "network_latency_ms_avg", Float),
+            col("network_latency_ms_max", "network_latency_ms_max", Float),
+            col("bandwidth_mbps_avg", "bandwidth_mbps_avg", Float),
+            col("bandwidth_mbps_min", "bandwidth_mbps_min", Float),
+            col("temperature_c_avg", "temperature_c_avg", Float),
+            col("temperature_c_max", "temperature_c_max", Float),
+            col("power_watts_avg", "power_watts_avg", Float),
+            col("power_watts_max", "power_watts_max", Float),
+            col("active_tasks_avg", "active_tasks_avg", Float),
+            col("active_tasks_max", "active_tasks_max", Float),
+        ],
+    },
+    TableSpec {
+        name: "tasks",
+        from: "tasks",
+        changed_since: "GREATEST(updated_at, deleted_at) > $1",
+        order_by: "task_id",
+        columns: &[
+            id("task_id", "task_id::TEXT"),
+            id("creator_id", "creator_id::TEXT"),
+            id("org_id", "org_id::TEXT"),
+            col("task_type", "task_type::TEXT", Text),
+            col("status", "status::TEXT", Text),
+            col("scheduling_mode", "scheduling_mode::TEXT", Text),
+            col("slot_class", "slot_class::TEXT", Text),
+            col("priority", "priority::BIGINT", Int),
+            col("min_nodes", "min_nodes::BIGINT", Int),
+            col("require_gpu", "require_gpu", Bool),
+            col("require_proof", "require_proof", Bool),
+            col(
+                "max_execution_time_sec",
+                "max_execution_time_sec::BIGINT",
+                Int,
+            ),
+            col("retry_count", "retry_count::BIGINT", Int),
+            col("created_at", "created_at::TIMESTAMPTZ", Timestamp),
+            col("queued_at", "queued_at::TIMESTAMPTZ", Timestamp),
+            col("completed_at", "completed_at::TIMESTAMPTZ", Timestamp),
+            col("updated_at", "updated_at::TIMESTAMPTZ", Timestamp),
+            col("deleted_at", "deleted_at::TIMESTAMPTZ", Timestamp),
+        ],
+    },
+    TableSpec {
+        name: "task_assignments",
+        from: "task_assignments",
+        changed_since: "GREATEST(assigned_at, disconnected_at, execution_started_at, \
+                        execution_completed_at) > $1",
+        order_by: "task_id, node_id",
+        columns: &[
+            id("task_id", "task_id::TEXT"),
+            id("node_id", "node_id::TEXT"),
+            col("execution_status", "execution_status::TEXT", Text),
+            col("assigned_at", "assigned_at::TIMESTAMPTZ", Timestamp),
+            col(
+                "execution_started_at",
+                "execution_started_at::TIMESTAMPTZ",
+                Timestamp,
+            ),
+            col(
+                "execution_completed_at",
+                "execution_completed_at::TIMESTAMPTZ",
+                Timestamp,
+            ),
+            col("disconnected_at", "disconnected_at::TIMESTAMPTZ", Timestamp),
+            col("execution_time_ms", "execution_time_ms::BIGINT", Int),
+            col("cpu_time_ms", "cpu_time_ms::BIGINT", Int),
+            col("peak_memory_bytes", "peak_memory_bytes::BIGINT", Int),
+            col("energy_wh", "energy_wh::DOUBLE PRECISION", Float),
+        ],
+    },
+    TableSpec {
+        name: "connect_sessions",
+        from: "connect_sessions",
+        changed_since: "GREATEST(updated_at, ended_at, last_usage_at) > $1",
+        order_by: "session_id",
+        columns: &[
+            id("session_id", "session_id::TEXT"),
+            id("task_id", "task_id::TEXT"),
+            id("requester_id", "requester_id::TEXT"),
+            id("node_id", "node_id::TEXT"),
+            col("tunnel_protocol", "tunnel_protocol::TEXT", Text),
+            col("egress_profile", "egress_profile::TEXT", Text),
+            col("status", "status::TEXT", Text),
+            col(
+                "bandwidth_limit_mbps",
+                "bandwidth_limit_mbps::DOUBLE PRECISION",
+                Float,
+            ),
+            col("bytes_up", "bytes_up::BIGINT", Int),
+            col("bytes_down", "bytes_down::BIGINT", Int),
+            col("data_cap_bytes", "data_cap_bytes::BIGINT", Int),
+            col("energy_wh", "energy_wh::DOUBLE PRECISION", Float),
+            col("created_at", "created_at::TIMESTAMPTZ", Timestamp),
+            col("expires_at", "expires_at::TIMESTAMPTZ", Timestamp),
+            col("ended_at", "ended_at::TIMESTAMPTZ", Timestamp),
+            col("updated_at", "updated_at::TIMESTAMPTZ", Timestamp),
+        ],
+    },
+];
+
+/// One exported cell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportValue {
+    Null,
+    Text(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Timestamp(DateTime<Utc>),
+}
+
+/// A table ready to encode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportTable {
+    pub name: String,
+    pub columns: Vec<(String, ColumnKind)>,
+    pub rows: Vec<Vec<ExportValue>>,
+}
+
+/// Applies a policy to decoded rows.
+pub struct Anonymizer {
+    policy: AnonymizationPolicy,
+    key: Vec<u8>,
+}
+
+impl Anonymizer {
+    /// Hash identifiers with `key`, or with a random key when there is none.
+    pub fn new(policy: AnonymizationPolicy, key: Option<&[u8]>) -> Self {
+        let key = key.map(<[u8]>::to_vec).unwrap_or_else(|| {
+            let mut key = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            key
+        });
+        Self { policy, key }
+    }
+
+    /// `rows` as decoded by [`TableSpec::decode_row`], with dropped columns
+    /// removed, identifiers hashed and timestamps rounded.
+    pub fn apply(&self, spec: &TableSpec, rows: Vec<Vec<ExportValue>>) -> ExportTable {
+        let kept: Vec<(usize, &ColumnSpec)> = spec
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| !self.policy.drops(spec.name, column))
+            .collect();
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                kept.iter()
+                    .map(|(index, column)| self.cell(column, row[*index].clone()))
+                    .collect()
+            })
+            .collect();
+        ExportTable {
+            name: spec.name.to_string(),
+            columns: kept
+                .iter()
+                .map(|(_, column)| (column.name.to_string(), column.kind))
+                .collect(),
+            rows,
+        }
+    }
+
+    fn cell(&self, column: &ColumnSpec, value: ExportValue) -> ExportValue {
+        match value {
+            ExportValue::Text(text)
+                if column.identifier && self.policy.identifiers == IdentifierMode::Hash =>
+            {
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+                mac.update(text.as_bytes());
+                ExportValue::Text(hex::encode(mac.finalize().into_bytes()))
+            }
+            ExportValue::Timestamp(at) if self.policy.timestamp_precision_secs > 0 => {
+                let precision = self.policy.timestamp_precision_secs as i64;
+                let secs = at.timestamp().div_euclid(precision) * precision;
+                ExportValue::Timestamp(DateTime::from_timestamp(secs, 0).unwrap_or(at))
+            }
+            value => value,
+        }
+    }
+}
+
+/// Encode `table` as `format`.
+pub fn encode(table: &ExportTable, format: ExportFormat) -> anyhow::Result<Vec<u8>> {
+    match format {
+        ExportFormat::Csv => encode_csv(table),
+        ExportFormat::Parquet => encode_parquet(table),
+    }
+}
+
+/// Header row, then one line per row; NULL is an empty field and
+/// timestamps are RFC 3339 in UTC.
+fn encode_csv(table: &ExportTable) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(table.columns.iter().map(|(name, _)| name))?;
+    for row in &table.rows {
+        writer.write_record(row.iter().map(|value| match value {
+            ExportValue::Null => String::new(),
+            ExportValue::Text(text) => text.clone(),
+            ExportValue::Int(int) => int.to_string(),
+            ExportValue::Float(float) => float.to_string(),
+            ExportValue::Bool(flag) => flag.to_string(),
+            ExportValue::Timestamp(at) => at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        }))?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// One row group of optional columns: UTF-8 strings, INT64, DOUBLE,
+/// BOOLEAN and UTC millisecond timestamps.
+fn encode_parquet(table: &ExportTable) -> anyhow::Result<Vec<u8>> {
+    let fields: String = table
+        .columns
+        .iter()
+        .map(|(name, kind)| {
+            let physical = match kind {
+                ColumnKind::Text => "BYTE_ARRAY",
+                ColumnKind::Int | ColumnKind::Timestamp => "INT64",
+                ColumnKind::Float => "DOUBLE",
+                ColumnKind::Bool => "BOOLEAN",
+            };
+            let annotation = match kind {
+                ColumnKind::Text => " (UTF8)",
+                ColumnKind::Timestamp => " (TIMESTAMP_MILLIS)",
+                _ => "",
+            };
+            format!("OPTIONAL {physical} {name}{annotation}; ")
+        })
+        .collect();
+    let schema = parquet::schema::parser::parse_message_type(&format!(
+        "message {} {{ {fields}}}",
+        table.name
+    ))?;
+
+    let mut writer = SerializedFileWriter::new(
+        Vec::new(),
+        Arc::new(schema),
+        Arc::new(WriterProperties::builder().build()),
+    )?;
+    let mut row_group = writer.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        let cells = table.rows.iter().map(|row| &row[index]);
+        let levels: Vec<i16> = cells
+            .clone()
+            .map(|value| i16::from(*value != ExportValue::Null))
+            .collect();
+        match table.columns[index].1 {
+            ColumnKind::Text => {
+                let values: Vec<ByteArray> = cells
+                    .filter_map(|value| match value {
+                        ExportValue::Text(text) => Some(ByteArray::from(text.as_str())),
+                        _ => None,
+                    })
+                    .collect();
+                column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            ColumnKind::Int | ColumnKind::Timestamp => {
+                let values: Vec<i64> = cells
+                    .filter_map(|value| match value {
+                        ExportValue::Int(int) => Some(*int),
+                        ExportValue::Timestamp(at) => Some(at.timestamp_millis()),
+                        _ => None,
+                    })
+                    .collect();
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            ColumnKind::Float => {
+                let values: Vec<f64> = cells
+                    .filter_map(|value| match value {
+                        ExportValue::Float(float) => Some(*float),
+                        _ => None,
+                    })
+                    .collect();
+                column
+                    .typed::<DoubleType>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            ColumnKind::Bool => {
+                let values: Vec<bool> = cells
+                    .filter_map(|value| match value {
+                        ExportValue::Bool(flag) => Some(*flag),
+                        _ => None,
+                    })
+                    .collect();
+                column
+                    .typed::<BoolType>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+        }
+        column.close()?;
+        index += 1;
+    }
+    row_group.close()?;
+    Ok(writer.into_inner()?)
+}
+
+/// One file written by an export.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportFile {
+    pub table: String,
+    pub rows: u64,
+    pub bytes: u64,
+    /// SHA3-256 of the file, its key in the artifact store
+    pub hash: String,
+}
+
+/// One export job.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClusterExport {
+    pub export_id: String,
+    /// `running`, `completed` or `failed`
+    pub status: String,
+    pub format: ExportFormat,
+    pub incremental: bool,
+    /// Rows changed after this were exported; `None` for a full export
+    pub since: Option<DateTime<Utc>>,
+    /// The instant the files reflect
+    pub snapshot_at: Option<DateTime<Utc>>,
+    pub policy: AnonymizationPolicy,
+    /// Whether identifiers match those in other exports
+    pub stable_identifiers: bool,
+    pub files: Vec<ExportFile>,
+    pub error: Option<String>,
+    pub created_by: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Body of `POST /api/v1/admin/cluster-exports`
+#[derive(Debug, Default, Deserialize)]
+pub struct ClusterExportRequest {
+    /// `parquet` (default) or `csv`
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Only rows changed since the last completed export; a full export
+    /// when there is none
+    #[serde(default)]
+    pub incremental: bool,
+    /// Overrides the configured default policy
+    pub policy: Option<AnonymizationPolicy>,
+}
+
+/// Query string for `GET /api/v1/admin/cluster-exports`
+#[derive(Debug, Default, Deserialize)]
+pub struct ClusterExportsQuery {
+    /// Most exports to return, newest first (default 50, at most 200)
+    pub limit: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn sessions_spec() -> &'static TableSpec {
+        TABLES
+            .iter()
+            .find(|spec| spec.name == "connect_sessions")
+            .unwrap()
+    }
+
+    fn session_row(session_id: &str, created_at: DateTime<Utc>) -> Vec<ExportValue> {
+        sessions_spec()
+            .columns
+            .iter()
+            .map(|column| match column.name {
+                "session_id" => ExportValue::Text(session_id.to_string()),
+                "node_id" => ExportValue::Text("node-1".to_string()),
+                "status" => ExportValue::Text("active".to_string()),
+                "bytes_up" => ExportValue::Int(1_024),
+                "bandwidth_limit_mbps" => ExportValue::Float(12.5),
+                "created_at" => ExportValue::Timestamp(created_at),
+                _ => ExportValue::Null,
+            })
+            .collect()
+    }
+
+    fn cell<'a>(table: &'a ExportTable, row: usize, name: &str) -> &'a ExportValue {
+        let index = table
+            .columns
+            .iter()
+            .position(|(col, _)| col == name)
+            .unwrap();
+        &table.rows[row][index]
+    }
+
+    #[test]
+    fn policy_parses_and_rejects_unknown_columns() {
+        let policy = AnonymizationPolicy::parse(
+            Some("drop"),
+            Some(" nodes.region, connect_sessions.egress_profile ,"),
+            Some("3600"),
+        );
+        assert_eq!(policy.identifiers, IdentifierMode::Drop);
+        assert_eq!(
+            policy.drop_columns,
+            ["nodes.region", "connect_sessions.egress_profile"]
+        );
+        assert_eq!(policy.timestamp_precision_secs, 3600);
+        assert!(policy.validate().is_ok());
+
+        let default = AnonymizationPolicy::parse(Some("bogus"), None, Some("x"));
+        assert_eq!(default, AnonymizationPolicy::default());
+
+        let typo = AnonymizationPolicy::parse(None, Some("nodes.regoin"), None);
+        assert!(typo.validate().is_err());
+        let no_table = AnonymizationPolicy::parse(None, Some("region"), None);
+        assert!(no_table.validate().is_err());
+    }
+
+    #[test]
+    fn anonymizer_hashes_drops_and_rounds() {
+        let created_at = DateTime::parse_from_rfc3339("2026-03-01T10:42:17Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let rows = vec![
+            session_row("s-1", created_at),
+            session_row("s-2", created_at),
+        ];
+        let policy = AnonymizationPolicy {
+            identifiers: IdentifierMode::Hash,
+            drop_columns: vec!["connect_sessions.egress_profile".to_string()],
+            timestamp_precision_secs: 3600,
+        };
+
+        let table =
+            Anonymizer::new(policy.clone(), Some(b"key")).apply(sessions_spec(), rows.clone());
+        assert!(!table
+            .columns
+            .iter()
+            .any(|(name, _)| name == "egress_profile"));
+        let ExportValue::Text(hashed) = cell(&table, 0, "session_id") else {
+            panic!("hashed identifier is text");
+        };
+        assert_eq!(hashed.len(), 64);
+        assert_ne!(cell(&table, 0, "session_id"), cell(&table, 1, "session_id"));
+        // The same key hashes the same identifier the same way.
+        assert_eq!(cell(&table, 0, "node_id"), cell(&table, 1, "node_id"));
+        let again =
+            Anonymizer::new(policy.clone(), Some(b"key")).apply(sessions_spec(), rows.clone());
+        assert_eq!(cell(&again, 0, "node_id"), cell(&table, 0, "node_id"));
+        let other_key =
+            Anonymizer::new(policy, Some(b"other")).apply(sessions_spec(), rows.clone());
+        assert_ne!(cell(&other_key, 0, "node_id"), cell(&table, 0, "node_id"));
+        // NULL identifiers stay NULL; other columns pass through.
+        assert_eq!(cell(&table, 0, "task_id"), &ExportValue::Null);
+        assert_eq!(
+            cell(&table, 0, "status"),
+            &ExportValue::Text("active".into())
+        );
+        assert_eq!(
+            cell(&table, 0, "created_at"),
+            &ExportValue::Timestamp("2026-03-01T10:00:00Z".parse().unwrap())
+        );
+
+        let dropped = Anonymizer::new(
+            AnonymizationPolicy {
+                identifiers: IdentifierMode::Drop,
+                ..Default::default()
+            },
+            None,
+        )
+        .apply(sessions_spec(), rows);
+        let names: Vec<&str> = dropped
+            .columns
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        for identifier in ["session_id", "task_id", "requester_id", "node_id"] {
+            assert!(!names.contains(&identifier));
+        }
+        assert_eq!(dropped.rows[0].len(), names.len());
+        assert_eq!(
+            cell(&dropped, 0, "created_at"),
+            &ExportValue::Timestamp(created_at)
+        );
+    }
+
+    #[test]
+    fn encodes_csv_and_parquet() {
+        let created_at: DateTime<Utc> = "2026-03-01T10:42:17.250Z".parse().unwrap();
+        let table = Anonymizer::new(
+            AnonymizationPolicy {
+                identifiers: IdentifierMode::Keep,
+                ..Default::default()
+            },
+            None,
+        )
+        .apply(sessions_spec(), vec![session_row("s-1", created_at)]);
+
+        let csv = String::from_utf8(encode(&table, ExportFormat::Csv).unwrap()).unwrap();
+        let mut lines = csv.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("session_id,task_id,requester_id,node_id,"));
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("s-1,,,node-1,"));
+        assert!(row.contains(",active,12.5,1024,"));
+        assert!(row.contains(",2026-03-01T10:42:17.250Z,"));
+        assert_eq!(lines.next(), None);
+
+        let parquet = encode(&table, ExportFormat::Parquet).unwrap();
+        let path = std::env::temp_dir().join(format!("export-{}.parquet", uuid::Uuid::new_v4()));
+        std::fs::write(&path, &parquet).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 1);
+        let row = reader.get_row_iter(None).unwrap().next().unwrap().unwrap();
+        let fields: std::collections::BTreeMap<String, String> = row
+            .get_column_iter()
+            .map(|(name, field)| (name.clone(), field.to_string()))
+            .collect();
+        assert_eq!(fields["session_id"], "\"s-1\"");
+        assert_eq!(fields["task_id"], "null");
+        assert_eq!(fields["bytes_up"], "1024");
+        assert_eq!(fields["bandwidth_limit_mbps"], "12.5");
+        assert_eq!(
+            fields["created_at"],
+            parquet::record::Field::TimestampMillis(created_at.timestamp_millis()).to_string()
+        );
+        std::fs::remove_file(path).unwrap();
+
+        // An empty table still encodes with its header and schema.
+        let empty = ExportTable {
+            rows: Vec::new(),
+            ..table
+        };
+        assert_eq!(
+            String::from_utf8(encode(&empty, ExportFormat::Csv).unwrap())
+                .unwrap()
+                .lines()
+                .count(),
+            1
+        );
+        assert!(encode(&empty, ExportFormat::Parquet).is_ok());
+    }
+}
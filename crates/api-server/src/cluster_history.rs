@@ -0,0 +1,200 @@
+/// Cluster statistics history
+///
+/// A background job snapshots the aggregates behind
+/// `GET /api/v1/cluster/stats` into `cluster_stats_history` every
+/// `CLUSTER_STATS_SNAPSHOT_INTERVAL_SECONDS` (default `300`).  A replica
+/// skips its snapshot when another one recorded a snapshot within the last
+/// half interval, so running several replicas does not multiply rows.
+///
+/// `GET /api/v1/cluster/stats/history` downsamples the snapshots with the
+/// same `from`/`to`/`resolution` parameters as node telemetry
+/// (`crate::telemetry`), aggregating on the fly, and reports how each
+/// aggregate changed across the range.
+use crate::error::ApiError;
+use crate::telemetry::{TelemetryResolution, TelemetryWindow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Seconds between snapshots.
+pub fn snapshot_interval_seconds() -> u64 {
+    std::env::var("CLUSTER_STATS_SNAPSHOT_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &u64| *v > 0)
+        .unwrap_or(300)
+}
+
+/// Query string for `GET /api/v1/cluster/stats/history`
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ClusterStatsHistoryQuery {
+    /// Range start (RFC 3339); defaults to 24 hours before `to`.
+    pub from: Option<DateTime<Utc>>,
+    /// Range end (RFC 3339, exclusive); defaults to now.
+    pub to: Option<DateTime<Utc>>,
+    /// `raw`, `5m`, `1h`, `1d` or `auto` (default): the finest bucket width
+    /// that fits the range in 1000 points.
+    pub resolution: Option<String>,
+}
+
+impl ClusterStatsHistoryQuery {
+    pub fn resolve(&self, now: DateTime<Utc>) -> Result<TelemetryWindow, ApiError> {
+        crate::telemetry::resolve_window(self.from, self.to, self.resolution.as_deref(), now)
+    }
+}
+
+/// One bucket (or raw snapshot) of cluster statistics.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema, async_graphql::SimpleObject)]
+pub struct ClusterStatsPoint {
+    /// Bucket start, or the snapshot time at `raw` resolution.
+    pub timestamp: DateTime<Utc>,
+    /// Snapshots aggregated into this point.
+    pub samples: i64,
+    pub total_nodes_avg: f64,
+    pub total_nodes_max: i64,
+    pub healthy_nodes_avg: f64,
+    pub healthy_nodes_min: i64,
+    pub avg_health_score_avg: f64,
+    pub avg_health_score_min: f64,
+    /// Task counters are cumulative, so a bucket reports its latest value.
+    pub total_tasks: i64,
+    pub completed_tasks: i64,
+    pub failed_tasks: i64,
+    pub total_compute_capacity_avg: f64,
+}
+
+impl ClusterStatsPoint {
+    /// Decode a row selected by [`SERIES_SQL`].
+    pub fn from_row(row: &sqlx::postgres::PgRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+        Ok(Self {
+            timestamp: row.try_get("bucket_start")?,
+            samples: row.try_get("samples")?,
+            total_nodes_avg: row.try_get("total_nodes_avg")?,
+            total_nodes_max: row.try_get("total_nodes_max")?,
+            healthy_nodes_avg: row.try_get("healthy_nodes_avg")?,
+            healthy_nodes_min: row.try_get("healthy_nodes_min")?,
+            avg_health_score_avg: row.try_get("avg_health_score_avg")?,
+            avg_health_score_min: row.try_get("avg_health_score_min")?,
+            total_tasks: row.try_get("total_tasks")?,
+            completed_tasks: row.try_get("completed_tasks")?,
+            failed_tasks: row.try_get("failed_tasks")?,
+            total_compute_capacity_avg: row.try_get("total_compute_capacity_avg")?,
+        })
+    }
+}
+
+/// Change from the first to the last point of a range.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema, async_graphql::SimpleObject)]
+pub struct ClusterStatsTrend {
+    pub total_nodes: f64,
+    pub healthy_nodes: f64,
+    pub avg_health_score: f64,
+    pub total_compute_capacity: f64,
+    pub completed_tasks: i64,
+    pub failed_tasks: i64,
+    /// Share of tasks finishing within the range that failed; `null` when
+    /// none finished.
+    pub failure_rate: Option<f64>,
+}
+
+impl ClusterStatsTrend {
+    /// `None` for an empty series.
+    pub fn across(points: &[ClusterStatsPoint]) -> Option<Self> {
+        let (first, last) = (points.first()?, points.last()?);
+        let completed_tasks = last.completed_tasks - first.completed_tasks;
+        let failed_tasks = last.failed_tasks - first.failed_tasks;
+        let finished = completed_tasks + failed_tasks;
+        Some(Self {
+            total_nodes: last.total_nodes_avg - first.total_nodes_avg,
+            healthy_nodes: last.healthy_nodes_avg - first.healthy_nodes_avg,
+            avg_health_score: last.avg_health_score_avg - first.avg_health_score_avg,
+            total_compute_capacity: last.total_compute_capacity_avg
+                - first.total_compute_capacity_avg,
+            completed_tasks,
+            failed_tasks,
+            failure_rate: (finished > 0).then(|| failed_tasks as f64 / finished as f64),
+        })
+    }
+}
+
+/// Response for `GET /api/v1/cluster/stats/history`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ClusterStatsHistoryResponse {
+    pub resolution: TelemetryResolution,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub points: Vec<ClusterStatsPoint>,
+    /// `null` when the range holds no snapshots.
+    pub trend: Option<ClusterStatsTrend>,
+}
+
+/// Snapshots in a range, bucketed unless the width is `NULL` (`raw`).
+///
+/// Binds: `$1` bucket width in seconds or `NULL`, `$2` from, `$3` to,
+/// `$4` limit.
+pub const SERIES_SQL: &str = r#"
+SELECT CASE
+           WHEN $1::BIGINT IS NULL THEN MIN(recorded_at)
+           ELSE to_timestamp((floor(extract(epoch FROM MIN(recorded_at)) / $1::BIGINT) * $1::BIGINT)::DOUBLE PRECISION)
+       END AS bucket_start,
+       COUNT(*) AS samples,
+       AVG(total_nodes)::DOUBLE PRECISION AS total_nodes_avg,
+       MAX(total_nodes) AS total_nodes_max,
+       AVG(healthy_nodes)::DOUBLE PRECISION AS healthy_nodes_avg,
+       MIN(healthy_nodes) AS healthy_nodes_min,
+       AVG(avg_health_score) AS avg_health_score_avg,
+       MIN(avg_health_score) AS avg_health_score_min,
+       (ARRAY_AGG(total_tasks ORDER BY recorded_at DESC))[1] AS total_tasks,
+       (ARRAY_AGG(completed_tasks ORDER BY recorded_at DESC))[1] AS completed_tasks,
+       (ARRAY_AGG(failed_tasks ORDER BY recorded_at DESC))[1] AS failed_tasks,
+       AVG(total_compute_capacity) AS total_compute_capacity_avg
+FROM cluster_stats_history
+WHERE recorded_at >= $2
+  AND recorded_at < $3
+GROUP BY CASE
+             WHEN $1::BIGINT IS NULL THEN snapshot_id
+             ELSE floor(extract(epoch FROM recorded_at) / $1::BIGINT)::BIGINT
+         END
+ORDER BY bucket_start
+LIMIT $4
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(healthy_nodes: f64, completed_tasks: i64, failed_tasks: i64) -> ClusterStatsPoint {
+        ClusterStatsPoint {
+            timestamp: Utc::now(),
+            samples: 1,
+            total_nodes_avg: 10.0,
+            total_nodes_max: 10,
+            healthy_nodes_avg: healthy_nodes,
+            healthy_nodes_min: healthy_nodes as i64,
+            avg_health_score_avg: 80.0,
+            avg_health_score_min: 80.0,
+            total_tasks: completed_tasks + failed_tasks,
+            completed_tasks,
+            failed_tasks,
+            total_compute_capacity_avg: 64.0,
+        }
+    }
+
+    #[test]
+    fn trend_spans_first_to_last_point() {
+        assert_eq!(ClusterStatsTrend::across(&[]), None);
+
+        let trend =
+            ClusterStatsTrend::across(&[point(8.0, 10, 2), point(9.0, 12, 2), point(6.0, 16, 5)])
+                .unwrap();
+        assert_eq!(trend.healthy_nodes, -2.0);
+        assert_eq!(trend.total_nodes, 0.0);
+        assert_eq!((trend.completed_tasks, trend.failed_tasks), (6, 3));
+        assert_eq!(trend.failure_rate, Some(1.0 / 3.0));
+
+        let flat = ClusterStatsTrend::across(&[point(8.0, 10, 2)]).unwrap();
+        assert_eq!(flat.failure_rate, None);
+    }
+}
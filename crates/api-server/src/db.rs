@@ -3,8 +3,9 @@ use anyhow::{Context, Result};
 ///
 /// This module provides a connection pool and database operations
 /// for the Ambient AI VCP system. It uses sqlx for async database access.
-use sqlx::{postgres::PgPoolOptions, Executor, PgPool};
+use sqlx::{postgres::PgPoolOptions, PgPool};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::time::Duration;
 
 /// Database configuration
@@ -122,25 +123,279 @@ pub async fn create_pool(config: &DatabaseConfig) -> Result<PgPool> {
     Ok(pool)
 }
 
+/// Read replica configuration
+///
+/// Heavy reads (list endpoints, stats, telemetry history) go to a replica
+/// whose replay lag is within `max_lag`; writes and everything else stay on
+/// the primary.  With no replica within the limit, reads fall back to the
+/// primary.
+#[derive(Debug, Clone)]
+pub struct ReplicaConfig {
+    /// PostgreSQL connection URLs of the replicas
+    pub urls: Vec<String>,
+    /// Replay lag beyond which a replica stops serving reads
+    pub max_lag: Duration,
+    /// How often each replica's lag is checked
+    pub check_interval: Duration,
+}
+
+impl ReplicaConfig {
+    /// Read `DATABASE_REPLICA_URLS` (comma-separated), `DB_REPLICA_MAX_LAG_SECS`
+    /// (default `10`) and `DB_REPLICA_CHECK_INTERVAL_SECS` (default `5`).
+    /// `None` when no replica is configured.
+    pub fn from_env() -> Result<Option<Self>> {
+        let urls: Vec<String> = std::env::var("DATABASE_REPLICA_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect();
+        if urls.is_empty() {
+            return Ok(None);
+        }
+        for url in &urls {
+            sqlx::postgres::PgConnectOptions::from_str(url).context(
+                "DATABASE_REPLICA_URLS contains an invalid PostgreSQL connection string",
+            )?;
+        }
+
+        let secs = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &u64| *v > 0)
+                .unwrap_or(default)
+        };
+
+        Ok(Some(Self {
+            urls,
+            max_lag: Duration::from_secs(secs("DB_REPLICA_MAX_LAG_SECS", 10)),
+            check_interval: Duration::from_secs(secs("DB_REPLICA_CHECK_INTERVAL_SECS", 5)),
+        }))
+    }
+}
+
+/// Replay lag in seconds; zero on a primary or a replica that has replayed
+/// everything it received (an idle primary writes nothing to replay).
+const REPLICA_LAG_SQL: &str = r#"
+SELECT CASE
+           WHEN NOT pg_is_in_recovery() THEN 0
+           WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+           ELSE COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()), 0)
+       END::DOUBLE PRECISION
+"#;
+
+/// A replica that does not answer the lag check within this is evicted.
+const REPLICA_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Replica {
+    /// `host:port`, for logs and metrics
+    name: String,
+    pool: PgPool,
+    /// Reachable and within the lag limit at the last check
+    usable: AtomicBool,
+}
+
+/// Read replica pools with lag-aware selection.
+///
+/// Replicas start unusable; [`ReadReplicas::check_lag`] admits each one once
+/// it answers within the lag limit, and evicts it again when it falls behind
+/// or stops answering.
+pub struct ReadReplicas {
+    replicas: Vec<Replica>,
+    max_lag: Duration,
+    next: AtomicUsize,
+}
+
+impl ReadReplicas {
+    /// Open a lazy pool per replica, sized like the primary's, so an
+    /// unreachable replica does not hold up startup.
+    pub fn connect(config: &ReplicaConfig, pool_config: &DatabaseConfig) -> Result<Self> {
+        let pools = config
+            .urls
+            .iter()
+            .map(|url| {
+                let options = sqlx::postgres::PgConnectOptions::from_str(url)
+                    .context("Invalid replica connection string")?;
+                let name = format!("{}:{}", options.get_host(), options.get_port());
+                let pool = PgPoolOptions::new()
+                    .max_connections(pool_config.max_connections)
+                    .min_connections(0)
+                    .acquire_timeout(Duration::from_secs(pool_config.connection_timeout))
+                    .connect_lazy_with(options);
+                Ok((name, pool))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::from_pools(pools, config.max_lag))
+    }
+
+    /// Replicas over existing pools, all unusable until the first check.
+    pub fn from_pools(pools: Vec<(String, PgPool)>, max_lag: Duration) -> Self {
+        Self {
+            replicas: pools
+                .into_iter()
+                .map(|(name, pool)| Replica {
+                    name,
+                    pool,
+                    usable: AtomicBool::new(false),
+                })
+                .collect(),
+            max_lag,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Next usable replica in round-robin order; `None` sends the read to
+    /// the primary.
+    pub fn pool(&self) -> Option<&PgPool> {
+        let count = self.replicas.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..count)
+            .map(|offset| &self.replicas[(start + offset) % count])
+            .find(|replica| replica.usable.load(Ordering::Relaxed))
+            .map(|replica| &replica.pool)
+    }
+
+    /// Measure every replica's lag and admit or evict it.  Returns how many
+    /// replicas are usable.
+    pub async fn check_lag(&self) -> usize {
+        let lags = futures::future::join_all(self.replicas.iter().map(|replica| {
+            tokio::time::timeout(
+                REPLICA_CHECK_TIMEOUT,
+                sqlx::query_scalar::<_, f64>(REPLICA_LAG_SQL).fetch_one(&replica.pool),
+            )
+        }))
+        .await;
+
+        let mut usable = 0;
+        for (replica, lag) in self.replicas.iter().zip(lags) {
+            let lag_seconds = match lag {
+                Ok(Ok(lag_seconds)) => Some(lag_seconds),
+                Ok(Err(error)) => {
+                    tracing::debug!(replica = %replica.name, error = %error, "Replica lag check failed");
+                    None
+                }
+                Err(_) => None,
+            };
+            let now_usable = lag_seconds.is_some_and(|lag| lag <= self.max_lag.as_secs_f64());
+            let was_usable = replica.usable.swap(now_usable, Ordering::Relaxed);
+            crate::middleware::metrics::set_replica_lag(&replica.name, lag_seconds, now_usable);
+
+            if now_usable {
+                usable += 1;
+            }
+            if was_usable != now_usable {
+                if now_usable {
+                    tracing::info!(replica = %replica.name, "Read replica serving reads");
+                } else {
+                    tracing::warn!(
+                        replica = %replica.name,
+                        lag_seconds,
+                        max_lag_seconds = self.max_lag.as_secs(),
+                        "Read replica unreachable or lagging; its reads fall back"
+                    );
+                }
+            }
+        }
+        usable
+    }
+
+    /// Replicas configured, usable or not.
+    pub fn len(&self) -> usize {
+        self.replicas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replicas.is_empty()
+    }
+}
+
 /// Run database migrations
 ///
 /// This applies any pending database migrations to ensure the schema
-/// is up to date. Should be run on application startup.
+/// is up to date. Should be run on application startup.  Concurrent
+/// callers wait on the migration lock; see [`crate::migrations`].
 pub async fn run_migrations(pool: &PgPool) -> Result<()> {
-    tracing::info!("Running database migrations");
+    crate::migrations::migrate(pool, false, crate::migrations::lock_timeout_from_env()).await?;
+    Ok(())
+}
 
-    pool.execute("CREATE EXTENSION IF NOT EXISTS pgcrypto")
-        .await
-        .context("Failed to ensure pgcrypto extension exists")?;
+/// Indexes the scheduler's hot queries depend on, with the query each one
+/// serves.  Migrations create them; [`check_required_indexes`] reports any
+/// that are missing or left invalid by a failed concurrent build.
+pub const REQUIRED_INDEXES: &[(&str, &str)] = &[
+    (
+        "idx_nodes_schedulable_capabilities",
+        "candidate selection by node_type and capabilities",
+    ),
+    (
+        "idx_nodes_schedulable_health",
+        "candidate ordering by health_score",
+    ),
+    ("idx_nodes_live_last_heartbeat", "node offline sweep"),
+    (
+        "idx_connect_sessions_active_node",
+        "active connect-session check per node",
+    ),
+    (
+        "idx_task_assignments_active",
+        "duplicate assignment check per task",
+    ),
+    ("idx_task_assignments_active_node", "slot usage per node"),
+    ("idx_nodes_labels", "node_selector label matching"),
+    ("idx_tasks_pending_priority", "pending task queue order"),
+    ("idx_tasks_pending_queued_at", "starvation and aging sweep"),
+];
 
-    sqlx::migrate!("./migrations")
-        .run(pool)
-        .await
-        .context("Failed to run database migrations")?;
+/// Names of [`REQUIRED_INDEXES`] that are absent or not valid.
+pub async fn missing_indexes(pool: &PgPool) -> Result<Vec<&'static str>> {
+    let names: Vec<&str> = REQUIRED_INDEXES.iter().map(|(name, _)| *name).collect();
+    let present: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT c.relname::TEXT
+        FROM pg_class c
+        JOIN pg_index i ON i.indexrelid = c.oid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = current_schema()
+          AND c.relname = ANY($1)
+          AND i.indisvalid
+        "#,
+    )
+    .bind(&names)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list scheduler indexes")?;
 
-    tracing::info!("Database migrations completed successfully");
+    Ok(names
+        .into_iter()
+        .filter(|name| !present.iter().any(|p| p == name))
+        .collect())
+}
 
-    Ok(())
+/// Warn about each missing scheduler index.  Never fails startup: a missing
+/// index slows scheduling but does not break it.
+pub async fn check_required_indexes(pool: &PgPool) {
+    match missing_indexes(pool).await {
+        Ok(missing) if missing.is_empty() => {
+            tracing::debug!("All scheduler indexes present");
+        }
+        Ok(missing) => {
+            for name in missing {
+                let purpose = REQUIRED_INDEXES
+                    .iter()
+                    .find(|(index, _)| *index == name)
+                    .map_or("", |(_, purpose)| *purpose);
+                tracing::warn!(
+                    index = name,
+                    purpose,
+                    "Scheduler index is missing or invalid; affected queries will scan the full table"
+                );
+            }
+        }
+        Err(error) => {
+            tracing::warn!(error = %error, "Could not check scheduler indexes");
+        }
+    }
 }
 
 /// Health check for database connection
@@ -154,3 +409,53 @@ pub async fn health_check(pool: &PgPool) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replicas(names: &[&str]) -> ReadReplicas {
+        let pools = names
+            .iter()
+            .map(|name| {
+                let pool = PgPoolOptions::new()
+                    .connect_lazy(&format!("postgres://postgres@{name}:5432/vcp"))
+                    .unwrap();
+                (name.to_string(), pool)
+            })
+            .collect();
+        ReadReplicas::from_pools(pools, Duration::from_secs(10))
+    }
+
+    fn host(pool: &PgPool) -> String {
+        pool.connect_options().get_host().to_string()
+    }
+
+    #[tokio::test]
+    async fn reads_round_robin_over_usable_replicas_only() {
+        let replicas = replicas(&["replica-a", "replica-b", "replica-c"]);
+        assert!(replicas.pool().is_none(), "replicas start unusable");
+
+        replicas.replicas[0].usable.store(true, Ordering::Relaxed);
+        replicas.replicas[2].usable.store(true, Ordering::Relaxed);
+        let picked: Vec<String> = (0..4).map(|_| host(replicas.pool().unwrap())).collect();
+        assert!(picked.iter().all(|name| name != "replica-b"));
+        assert!(picked.contains(&"replica-a".to_string()));
+        assert!(picked.contains(&"replica-c".to_string()));
+    }
+
+    #[test]
+    fn required_indexes_are_created_by_migrations() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let sql: String = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        for (name, _) in REQUIRED_INDEXES {
+            assert!(
+                sql.contains(&format!("CREATE INDEX IF NOT EXISTS {name}\n")),
+                "{name} is not created by any migration"
+            );
+        }
+    }
+}
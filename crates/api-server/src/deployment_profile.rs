@@ -0,0 +1,137 @@
+//! Server defaults for named deployment profiles (`DEPLOYMENT_PROFILE`)
+//!
+//! The profile names match the node-side [`DeploymentProfile`]: a fleet of
+//! battery-powered edge nodes heartbeats slowly and runs one or two small
+//! tasks at a time, a datacenter fleet the opposite.  A profile only changes
+//! defaults; each setting below is still read from its own environment
+//! variable first, so individual overrides keep working.
+use ambient_node::DeploymentProfile;
+
+/// Settings a profile provides defaults for.
+pub const PROFILE_SETTINGS: &[&str] = &[
+    "NODE_HEARTBEAT_TIMEOUT_MINUTES",
+    "NODE_OFFLINE_SWEEP_INTERVAL_SECONDS",
+    "MAX_CONCURRENT_TASKS_PER_NODE",
+    "TASK_PRIORITY_AGING_SECS",
+    "TASK_STARVATION_RELAX_SECS",
+    "CONNECT_SESSION_MONITOR_INTERVAL_SECONDS",
+    "CONNECT_SESSION_DATA_CAP_MB",
+    "POLICY_BUNDLE_REFRESH_SECS",
+    "POLICY_BUNDLE_MAX_STALENESS_SECS",
+    "WASM_MODULE_MAX_BYTES",
+];
+
+/// The configured profile; unset or unknown names mean none.
+pub fn profile_from_env() -> Option<DeploymentProfile> {
+    let raw = std::env::var("DEPLOYMENT_PROFILE").ok()?;
+    match raw.trim().parse() {
+        Ok(profile) => Some(profile),
+        Err(err) => {
+            tracing::warn!("Ignoring DEPLOYMENT_PROFILE: {err:#}");
+            None
+        }
+    }
+}
+
+/// The profile's value for one of [`PROFILE_SETTINGS`].
+pub fn profile_default(profile: DeploymentProfile, name: &str) -> Option<&'static str> {
+    let value = match (profile, name) {
+        (DeploymentProfile::EdgeBattery, "NODE_HEARTBEAT_TIMEOUT_MINUTES") => "15",
+        (DeploymentProfile::EdgeBattery, "NODE_OFFLINE_SWEEP_INTERVAL_SECONDS") => "120",
+        (DeploymentProfile::EdgeBattery, "MAX_CONCURRENT_TASKS_PER_NODE") => "2",
+        (DeploymentProfile::EdgeBattery, "TASK_PRIORITY_AGING_SECS") => "600",
+        // Sparse fleets rarely have the preferred node type free.
+        (DeploymentProfile::EdgeBattery, "TASK_STARVATION_RELAX_SECS") => "300",
+        (DeploymentProfile::EdgeBattery, "CONNECT_SESSION_MONITOR_INTERVAL_SECONDS") => "60",
+        (DeploymentProfile::EdgeBattery, "CONNECT_SESSION_DATA_CAP_MB") => "512",
+        (DeploymentProfile::EdgeBattery, "POLICY_BUNDLE_REFRESH_SECS") => "3600",
+        (DeploymentProfile::EdgeBattery, "POLICY_BUNDLE_MAX_STALENESS_SECS") => "172800",
+        (DeploymentProfile::EdgeBattery, "WASM_MODULE_MAX_BYTES") => "4194304",
+
+        (DeploymentProfile::HomeRelay, "NODE_HEARTBEAT_TIMEOUT_MINUTES") => "10",
+        (DeploymentProfile::HomeRelay, "NODE_OFFLINE_SWEEP_INTERVAL_SECONDS") => "60",
+        (DeploymentProfile::HomeRelay, "MAX_CONCURRENT_TASKS_PER_NODE") => "4",
+        (DeploymentProfile::HomeRelay, "TASK_PRIORITY_AGING_SECS") => "300",
+        (DeploymentProfile::HomeRelay, "TASK_STARVATION_RELAX_SECS") => "600",
+        (DeploymentProfile::HomeRelay, "CONNECT_SESSION_MONITOR_INTERVAL_SECONDS") => "30",
+        (DeploymentProfile::HomeRelay, "CONNECT_SESSION_DATA_CAP_MB") => "4096",
+        (DeploymentProfile::HomeRelay, "POLICY_BUNDLE_REFRESH_SECS") => "900",
+        (DeploymentProfile::HomeRelay, "POLICY_BUNDLE_MAX_STALENESS_SECS") => "86400",
+        (DeploymentProfile::HomeRelay, "WASM_MODULE_MAX_BYTES") => "10485760",
+
+        (DeploymentProfile::DatacenterCompute, "NODE_HEARTBEAT_TIMEOUT_MINUTES") => "2",
+        (DeploymentProfile::DatacenterCompute, "NODE_OFFLINE_SWEEP_INTERVAL_SECONDS") => "30",
+        (DeploymentProfile::DatacenterCompute, "MAX_CONCURRENT_TASKS_PER_NODE") => "64",
+        (DeploymentProfile::DatacenterCompute, "TASK_PRIORITY_AGING_SECS") => "120",
+        (DeploymentProfile::DatacenterCompute, "TASK_STARVATION_RELAX_SECS") => "900",
+        (DeploymentProfile::DatacenterCompute, "CONNECT_SESSION_MONITOR_INTERVAL_SECONDS") => "15",
+        (DeploymentProfile::DatacenterCompute, "POLICY_BUNDLE_REFRESH_SECS") => "300",
+        (DeploymentProfile::DatacenterCompute, "POLICY_BUNDLE_MAX_STALENESS_SECS") => "3600",
+        (DeploymentProfile::DatacenterCompute, "WASM_MODULE_MAX_BYTES") => "67108864",
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// `name` from the environment, else the configured profile's default.
+pub fn setting(name: &str) -> Option<String> {
+    resolve(std::env::var(name).ok(), profile_from_env(), name)
+}
+
+/// The configured profile's default for `name`, ignoring its variable.
+pub fn profile_setting(name: &str) -> Option<String> {
+    profile_from_env()
+        .and_then(|profile| profile_default(profile, name))
+        .map(str::to_string)
+}
+
+fn resolve(
+    explicit: Option<String>,
+    profile: Option<DeploymentProfile>,
+    name: &str,
+) -> Option<String> {
+    explicit.or_else(|| {
+        profile
+            .and_then(|profile| profile_default(profile, name))
+            .map(str::to_string)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_settings_override_the_profile() {
+        let profile = Some(DeploymentProfile::EdgeBattery);
+        assert_eq!(
+            resolve(None, profile, "MAX_CONCURRENT_TASKS_PER_NODE").as_deref(),
+            Some("2")
+        );
+        assert_eq!(
+            resolve(Some("8".into()), profile, "MAX_CONCURRENT_TASKS_PER_NODE").as_deref(),
+            Some("8")
+        );
+        assert_eq!(resolve(None, None, "MAX_CONCURRENT_TASKS_PER_NODE"), None);
+    }
+
+    #[test]
+    fn profiles_only_cover_profile_settings() {
+        for profile in DeploymentProfile::ALL {
+            assert_eq!(profile_default(profile, "JWT_SECRET"), None);
+            for name in PROFILE_SETTINGS {
+                if let Some(value) = profile_default(profile, name) {
+                    assert!(value.parse::<u64>().is_ok(), "{profile} {name}={value}");
+                }
+            }
+        }
+        // Datacenter sessions stay uncapped unless a task sets a cap.
+        assert_eq!(
+            profile_default(
+                DeploymentProfile::DatacenterCompute,
+                "CONNECT_SESSION_DATA_CAP_MB"
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,147 @@
+/// Destination policies and the signed bundle relay nodes cache
+///
+/// Admins manage the allowlist behind each `destination_policy_id` with
+/// `PUT`/`DELETE /api/v1/admin/destination-policies/{policy_id}`.  Gateway
+/// session lists carry the policy's destinations, and
+/// `GET /api/v1/destination-policies/bundle` returns every policy as one
+/// versioned bundle signed with the control key (audience
+/// `destination-policies`), which nodes cache with
+/// `ambient_node::PolicyBundleCache` and keep enforcing while the control
+/// plane is unreachable, within the bundle's offline policy:
+///
+/// - `POLICY_BUNDLE_REFRESH_SECS` (default `300`): age after which nodes
+///   should fetch a newer bundle
+/// - `POLICY_BUNDLE_MAX_STALENESS_SECS` (default `86400`): age after which a
+///   cached bundle no longer admits any destination
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::deployment_profile;
+use crate::error::ApiError;
+
+pub const MAX_POLICY_ID_CHARS: usize = 128;
+pub const MAX_POLICY_DESTINATIONS: usize = 256;
+
+/// Longest destination rule (a DNS name, optionally with a `*.` prefix).
+const MAX_RULE_CHARS: usize = 255;
+
+/// Parse the offline policy nodes receive with every bundle; unset,
+/// malformed or zero values keep the defaults, and the max staleness is
+/// never below the refresh interval.
+pub fn parse_offline_policy(
+    refresh_secs: Option<&str>,
+    max_staleness_secs: Option<&str>,
+) -> ambient_node::OfflinePolicy {
+    let parse = |raw: Option<&str>, default: u64| {
+        raw.and_then(|raw| raw.trim().parse().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(default)
+    };
+    let refresh_after_secs = parse(refresh_secs, ambient_node::DEFAULT_POLICY_REFRESH_SECS);
+    ambient_node::OfflinePolicy {
+        refresh_after_secs,
+        max_staleness_secs: parse(
+            max_staleness_secs,
+            ambient_node::DEFAULT_POLICY_MAX_STALENESS_SECS,
+        )
+        .max(refresh_after_secs),
+    }
+}
+
+/// Load from `POLICY_BUNDLE_REFRESH_SECS` and `POLICY_BUNDLE_MAX_STALENESS_SECS`.
+pub fn offline_policy_from_env() -> ambient_node::OfflinePolicy {
+    parse_offline_policy(
+        deployment_profile::setting("POLICY_BUNDLE_REFRESH_SECS").as_deref(),
+        deployment_profile::setting("POLICY_BUNDLE_MAX_STALENESS_SECS").as_deref(),
+    )
+}
+
+/// Body of `PUT /api/v1/admin/destination-policies/{policy_id}`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DestinationPolicyRequest {
+    /// Host names, or `*.domain` for a domain and its subdomains.
+    pub allowed_destinations: Vec<String>,
+}
+
+impl DestinationPolicyRequest {
+    /// Lowercased, trimmed and de-duplicated rules.
+    pub fn normalized(&self) -> Result<Vec<String>, ApiError> {
+        if self.allowed_destinations.len() > MAX_POLICY_DESTINATIONS {
+            return Err(ApiError::bad_request(format!(
+                "allowed_destinations cannot contain more than {MAX_POLICY_DESTINATIONS} entries"
+            )));
+        }
+        let mut rules = Vec::with_capacity(self.allowed_destinations.len());
+        for rule in &self.allowed_destinations {
+            let rule = rule.trim().to_lowercase();
+            let host = rule.strip_prefix("*.").unwrap_or(&rule);
+            let valid = !host.is_empty()
+                && rule.len() <= MAX_RULE_CHARS
+                && host
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':'));
+            if !valid {
+                return Err(ApiError::bad_request(format!(
+                    "invalid destination rule: {rule:?}"
+                )));
+            }
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+        Ok(rules)
+    }
+}
+
+pub fn validate_policy_id(policy_id: &str) -> Result<(), ApiError> {
+    if policy_id.is_empty() || policy_id.len() > MAX_POLICY_ID_CHARS {
+        return Err(ApiError::bad_request(format!(
+            "policy_id must be between 1 and {MAX_POLICY_ID_CHARS} characters"
+        )));
+    }
+    Ok(())
+}
+
+/// A stored destination policy.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DestinationPolicy {
+    pub policy_id: String,
+    pub allowed_destinations: Vec<String>,
+    /// Bundle version this policy was last changed in.
+    pub bundle_version: i64,
+    pub updated_at: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offline_policy_parse() {
+        assert_eq!(
+            parse_offline_policy(None, Some("0")),
+            ambient_node::OfflinePolicy::default()
+        );
+        let policy = parse_offline_policy(Some("600"), Some("120"));
+        assert_eq!(policy.refresh_after_secs, 600);
+        assert_eq!(policy.max_staleness_secs, 600);
+    }
+
+    #[test]
+    fn test_destination_rules_are_normalized() {
+        let request = |rules: &[&str]| DestinationPolicyRequest {
+            allowed_destinations: rules.iter().map(|rule| rule.to_string()).collect(),
+        };
+        assert_eq!(
+            request(&[" *.Example.com", "*.example.com", "api.test"])
+                .normalized()
+                .unwrap(),
+            vec!["*.example.com", "api.test"]
+        );
+        assert!(request(&["*."]).normalized().is_err());
+        assert!(request(&["exa mple.com"]).normalized().is_err());
+        assert!(request(&["a.com"; MAX_POLICY_DESTINATIONS + 1])
+            .normalized()
+            .is_err());
+    }
+}
@@ -17,6 +17,10 @@ pub struct ApiError {
     pub error: String,
     /// User-friendly error message
     pub message: String,
+    /// Structured context for the error, e.g. the missing scope on a 403
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<serde_json::Value>,
     /// HTTP status code
     #[serde(skip)]
     pub status_code: StatusCode,
@@ -32,10 +36,17 @@ impl ApiError {
         Self {
             error: error.into(),
             message: message.into(),
+            details: None,
             status_code,
         }
     }
 
+    /// Attach structured details to the error body
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
     /// 400 Bad Request - Invalid request data
     pub fn bad_request(message: impl Into<String>) -> Self {
         Self::new("bad_request", message, StatusCode::BAD_REQUEST)
@@ -113,7 +124,19 @@ impl std::error::Error for ApiError {}
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let status = self.status_code;
-        (status, Json(self)).into_response()
+        let retry_after = self
+            .details
+            .as_ref()
+            .and_then(|details| details.get("retry_after"))
+            .and_then(|secs| secs.as_u64());
+        let mut response = (status, Json(self)).into_response();
+        if let Some(secs) = retry_after {
+            response.headers_mut().insert(
+                axum::http::header::RETRY_AFTER,
+                axum::http::HeaderValue::from(secs),
+            );
+        }
+        response
     }
 }
 
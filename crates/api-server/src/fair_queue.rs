@@ -0,0 +1,261 @@
+/// Weighted fair queuing of pending tasks across requesters
+///
+/// A requester is the organization that owns a task, or its creator for
+/// personal tasks.  When a node frees up, pending tasks are offered to it
+/// requester by requester: the requester with the fewest running tasks per
+/// unit of weight goes next, and within a requester tasks keep their aged
+/// priority order.  A heavy submitter therefore cannot starve everyone else.
+///
+/// Configure with:
+///
+/// - `TASK_FAIR_SHARE_WEIGHTS` — comma-separated `requester=weight` pairs,
+///   where a requester is `user:<uuid>` or `org:<uuid>`
+///   (e.g. `org:6f1c…=4,user:9a2e…=0.5`); unlisted requesters weigh `1`
+/// - `TASK_MAX_RUNNING_PER_REQUESTER` — running tasks a single requester may
+///   hold before its other tasks wait; unset or `0` disables the cap
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use uuid::Uuid;
+
+/// Share weight for requesters not listed in `TASK_FAIR_SHARE_WEIGHTS`.
+pub const DEFAULT_REQUESTER_WEIGHT: f64 = 1.0;
+
+/// Queue key for a task: its organization, else its creator.
+pub fn requester_key(org_id: Option<Uuid>, creator_id: Option<Uuid>) -> String {
+    match (org_id, creator_id) {
+        (Some(org_id), _) => format!("org:{org_id}"),
+        (None, Some(creator_id)) => format!("user:{creator_id}"),
+        (None, None) => "unowned".to_string(),
+    }
+}
+
+/// Metric label for a requester key: `org` or `user`.  Requester keys
+/// themselves are unbounded, so they stay out of metric labels.
+pub fn requester_kind(requester: &str) -> &'static str {
+    if requester.starts_with("org:") {
+        "org"
+    } else {
+        "user"
+    }
+}
+
+/// Fair-share weights and the per-requester concurrency cap.
+#[derive(Debug, Clone, Default)]
+pub struct FairShareConfig {
+    weights: HashMap<String, f64>,
+    max_running_per_requester: Option<u32>,
+}
+
+impl FairShareConfig {
+    /// Load from `TASK_FAIR_SHARE_WEIGHTS` and `TASK_MAX_RUNNING_PER_REQUESTER`.
+    pub fn from_env() -> Self {
+        Self::parse(
+            std::env::var("TASK_FAIR_SHARE_WEIGHTS").ok().as_deref(),
+            std::env::var("TASK_MAX_RUNNING_PER_REQUESTER")
+                .ok()
+                .as_deref(),
+        )
+    }
+
+    /// Parse configuration; malformed or non-positive weights are skipped.
+    pub fn parse(weights: Option<&str>, max_running: Option<&str>) -> Self {
+        let weights = weights
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let (requester, weight) = entry.split_once('=')?;
+                let requester = requester.trim();
+                let weight = weight.trim().parse::<f64>().ok()?;
+                (!requester.is_empty() && weight.is_finite() && weight > 0.0)
+                    .then(|| (requester.to_string(), weight))
+            })
+            .collect();
+
+        let max_running_per_requester = max_running
+            .and_then(|raw| raw.trim().parse::<u32>().ok())
+            .filter(|cap| *cap > 0);
+
+        Self {
+            weights,
+            max_running_per_requester,
+        }
+    }
+
+    /// Exactly these weights and cap, as recorded with a scheduling decision.
+    pub fn with_weights(
+        weights: HashMap<String, f64>,
+        max_running_per_requester: Option<u32>,
+    ) -> Self {
+        Self {
+            weights,
+            max_running_per_requester,
+        }
+    }
+
+    pub fn weight_for(&self, requester: &str) -> f64 {
+        self.weights
+            .get(requester)
+            .copied()
+            .unwrap_or(DEFAULT_REQUESTER_WEIGHT)
+    }
+
+    pub fn max_running_per_requester(&self) -> Option<u32> {
+        self.max_running_per_requester
+    }
+
+    /// Whether a requester already running `running` tasks may start another.
+    pub fn admits(&self, running: i64) -> bool {
+        self.max_running_per_requester
+            .is_none_or(|cap| running < cap as i64)
+    }
+}
+
+/// A pending task considered for a node, in aged priority order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedTask {
+    pub task_id: Uuid,
+    pub requester: String,
+    /// Running tasks the requester already holds.
+    pub requester_running: i64,
+    /// Whether the task already holds some of its nodes; such tasks finish
+    /// gathering nodes regardless of the cap.
+    pub partially_assigned: bool,
+}
+
+/// The fair order in which to offer `tasks` (given in priority order) to a
+/// node, plus the requesters whose tasks were held back by the cap.
+pub fn fair_order(
+    tasks: Vec<QueuedTask>,
+    config: &FairShareConfig,
+) -> (Vec<QueuedTask>, Vec<String>) {
+    // Ties go to the requester whose best task ranks highest in `tasks`.
+    let mut queues: BTreeMap<String, VecDeque<QueuedTask>> = BTreeMap::new();
+    let mut first_seen: HashMap<String, usize> = HashMap::new();
+    let mut served: HashMap<String, i64> = HashMap::new();
+    for (position, task) in tasks.into_iter().enumerate() {
+        first_seen.entry(task.requester.clone()).or_insert(position);
+        served
+            .entry(task.requester.clone())
+            .or_insert(task.requester_running);
+        queues
+            .entry(task.requester.clone())
+            .or_default()
+            .push_back(task);
+    }
+
+    let mut ordered = Vec::new();
+    let mut deferred = Vec::new();
+    loop {
+        let next = queues
+            .iter()
+            .filter(|(_, queue)| !queue.is_empty())
+            .map(|(requester, _)| {
+                let share = served[requester] as f64 / config.weight_for(requester);
+                (requester.clone(), share, first_seen[requester])
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.2.cmp(&b.2)));
+        let Some((requester, _, _)) = next else {
+            break;
+        };
+
+        let queue = queues.get_mut(&requester).expect("requester has a queue");
+        let task = queue.pop_front().expect("queue is non-empty");
+        if task.partially_assigned {
+            ordered.push(task);
+            continue;
+        }
+        if !config.admits(served[&requester]) {
+            // The rest of this requester's new tasks wait for a running one
+            // to finish.
+            queue.retain(|queued| queued.partially_assigned);
+            deferred.push(requester);
+            continue;
+        }
+        *served.get_mut(&requester).expect("requester is tracked") += 1;
+        ordered.push(task);
+    }
+
+    (ordered, deferred)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(requester: &str, running: i64) -> QueuedTask {
+        QueuedTask {
+            task_id: Uuid::new_v4(),
+            requester: requester.to_string(),
+            requester_running: running,
+            partially_assigned: false,
+        }
+    }
+
+    fn requesters(tasks: &[QueuedTask]) -> Vec<&str> {
+        tasks.iter().map(|task| task.requester.as_str()).collect()
+    }
+
+    #[test]
+    fn interleaves_heavy_and_light_requesters() {
+        let mut tasks: Vec<QueuedTask> = (0..4).map(|_| task("user:heavy", 0)).collect();
+        tasks.push(task("user:light", 0));
+        tasks.push(task("user:light", 0));
+
+        let (ordered, deferred) = fair_order(tasks, &FairShareConfig::default());
+        assert_eq!(
+            requesters(&ordered),
+            vec![
+                "user:heavy",
+                "user:light",
+                "user:heavy",
+                "user:light",
+                "user:heavy",
+                "user:heavy"
+            ]
+        );
+        assert!(deferred.is_empty());
+    }
+
+    #[test]
+    fn metric_labels_carry_only_the_requester_kind() {
+        let org = requester_key(Some(Uuid::new_v4()), Some(Uuid::new_v4()));
+        let user = requester_key(None, Some(Uuid::new_v4()));
+        assert_eq!(requester_kind(&org), "org");
+        assert_eq!(requester_kind(&user), "user");
+        assert_eq!(requester_kind(&requester_key(None, None)), "user");
+    }
+
+    #[test]
+    fn weights_and_running_tasks_shift_the_share() {
+        let config = FairShareConfig::parse(Some("org:big=3, bad, user:x=-1"), None);
+        assert_eq!(config.weight_for("org:big"), 3.0);
+        assert_eq!(config.weight_for("user:x"), DEFAULT_REQUESTER_WEIGHT);
+
+        let mut tasks = vec![task("user:small", 1)];
+        tasks.extend((0..4).map(|_| task("org:big", 0)));
+        let (ordered, _) = fair_order(tasks, &config);
+        assert_eq!(
+            requesters(&ordered),
+            vec!["org:big", "org:big", "org:big", "user:small", "org:big"]
+        );
+    }
+
+    #[test]
+    fn cap_defers_new_tasks_but_not_partial_ones() {
+        let config = FairShareConfig::parse(None, Some("2"));
+        let mut partial = task("user:a", 2);
+        partial.partially_assigned = true;
+        let tasks = vec![
+            task("user:a", 2),
+            partial.clone(),
+            task("user:b", 1),
+            task("user:b", 1),
+        ];
+
+        let (ordered, deferred) = fair_order(tasks, &config);
+        assert_eq!(requesters(&ordered), vec!["user:b", "user:a"]);
+        assert_eq!(ordered[1].task_id, partial.task_id);
+        assert_eq!(deferred, vec!["user:a".to_string(), "user:b".to_string()]);
+        assert!(FairShareConfig::parse(None, Some("0")).admits(1_000));
+    }
+}
@@ -0,0 +1,77 @@
+/// Circuit breaker for nodes that flap between online and offline
+///
+/// The offline sweep marks nodes that stop heartbeating `offline`; a later
+/// heartbeat brings the node back `online` and counts one flap.  A node that
+/// flaps too often gets no new assignments until a cool-down ends, while
+/// work it already holds is left alone:
+///
+/// - `NODE_FLAP_THRESHOLD` (default `3`): flaps that open the breaker; `0`
+///   disables it
+/// - `NODE_FLAP_WINDOW_SECS` (default `900`): flaps are counted from the
+///   first one in this window
+/// - `NODE_FLAP_COOLDOWN_SECS` (default `600`): how long the breaker stays
+///   open
+///
+/// The count restarts when the breaker opens.  `MeshCoordinator` applies the
+/// same rules to its in-memory nodes.
+use mesh_coordinator::{
+    FlapBreakerConfig, DEFAULT_FLAP_COOLDOWN_SECS, DEFAULT_FLAP_THRESHOLD, DEFAULT_FLAP_WINDOW_SECS,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Load the breaker settings from the `NODE_FLAP_*` variables.
+pub fn config_from_env() -> FlapBreakerConfig {
+    let var = |name| std::env::var(name).ok();
+    parse_config(
+        var("NODE_FLAP_THRESHOLD").as_deref(),
+        var("NODE_FLAP_WINDOW_SECS").as_deref(),
+        var("NODE_FLAP_COOLDOWN_SECS").as_deref(),
+    )
+}
+
+/// Parse settings; unset or malformed values keep their default and a `0`
+/// threshold disables the breaker.
+pub fn parse_config(
+    threshold: Option<&str>,
+    window: Option<&str>,
+    cooldown: Option<&str>,
+) -> FlapBreakerConfig {
+    fn number<T: std::str::FromStr>(value: Option<&str>, default: T) -> T {
+        value
+            .and_then(|raw| raw.trim().parse().ok())
+            .unwrap_or(default)
+    }
+
+    FlapBreakerConfig {
+        threshold: Some(number(threshold, DEFAULT_FLAP_THRESHOLD)).filter(|n| *n > 0),
+        window_secs: number(window, DEFAULT_FLAP_WINDOW_SECS).max(1),
+        cooldown_secs: number(cooldown, DEFAULT_FLAP_COOLDOWN_SECS).max(1),
+    }
+}
+
+/// A node's flap breaker as shown in `NodeInfo`.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct NodeCircuitBreaker {
+    /// Set while the node is excluded from new assignments
+    pub open: bool,
+    /// Flaps counted in the current window
+    pub recent_flaps: u32,
+    /// When the cool-down ends, while open
+    pub open_until: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config_defaults_and_disabling() {
+        assert_eq!(parse_config(None, None, None), FlapBreakerConfig::default());
+
+        let config = parse_config(Some("0"), Some("often"), Some("120"));
+        assert_eq!(config.threshold, None);
+        assert_eq!(config.window_secs, DEFAULT_FLAP_WINDOW_SECS);
+        assert_eq!(config.cooldown_secs, 120);
+    }
+}
@@ -0,0 +1,133 @@
+/// ASN lookup for node addresses, used to spread redundant work across ISPs
+///
+/// Configure with `GEOIP_ASN_DB`, the path to a MaxMind-format ASN database
+/// (e.g. GeoLite2-ASN.mmdb).  Without it nodes are placed by the ASN they
+/// self-report at registration, if any.
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use utoipa::ToSchema;
+
+/// How a node's ASN was determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AsnSource {
+    /// Looked up from the address the registration arrived from.
+    Geoip,
+    /// Reported by the node (`asn` in the registration body).
+    SelfReported,
+}
+
+impl AsnSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AsnSource::Geoip => "geoip",
+            AsnSource::SelfReported => "self_reported",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "geoip" => Some(AsnSource::Geoip),
+            "self_reported" => Some(AsnSource::SelfReported),
+            _ => None,
+        }
+    }
+}
+
+/// ASN database loaded into memory.
+pub struct AsnDatabase {
+    reader: maxminddb::Reader<Vec<u8>>,
+}
+
+impl std::fmt::Debug for AsnDatabase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsnDatabase")
+            .field("database_type", &self.reader.metadata.database_type)
+            .finish()
+    }
+}
+
+impl AsnDatabase {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            reader: maxminddb::Reader::open_readfile(path)?,
+        })
+    }
+
+    /// Open `GEOIP_ASN_DB` if set.  A missing or unreadable file is logged
+    /// and treated as unconfigured.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("GEOIP_ASN_DB").ok()?;
+        match Self::open(&path) {
+            Ok(db) => {
+                tracing::info!(path, "Loaded GeoIP ASN database");
+                Some(db)
+            }
+            Err(err) => {
+                tracing::warn!(path, "Failed to load GeoIP ASN database: {err}");
+                None
+            }
+        }
+    }
+
+    /// ASN announcing `ip`, or `None` for private addresses and misses.
+    pub fn lookup(&self, ip: IpAddr) -> Option<u32> {
+        if !is_public(ip) {
+            return None;
+        }
+        self.reader
+            .lookup::<maxminddb::geoip2::Asn>(ip)
+            .ok()?
+            .autonomous_system_number
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation())
+        }
+        IpAddr::V6(v6) => {
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                // fc00::/7 unique local, fe80::/10 link local
+                || (v6.segments()[0] & 0xfe00) == 0xfc00
+                || (v6.segments()[0] & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// ASN to record for a node: the GeoIP answer for its observed address wins,
+/// since a self-report cannot be checked, then the self-reported value.
+pub fn resolve_node_asn(
+    db: Option<&AsnDatabase>,
+    observed_ip: Option<IpAddr>,
+    reported: Option<u32>,
+) -> Option<(u32, AsnSource)> {
+    db.zip(observed_ip)
+        .and_then(|(db, ip)| db.lookup(ip))
+        .map(|asn| (asn, AsnSource::Geoip))
+        .or_else(|| reported.map(|asn| (asn, AsnSource::SelfReported)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_node_asn_falls_back_to_self_report() {
+        assert_eq!(
+            resolve_node_asn(None, Some("8.8.8.8".parse().unwrap()), Some(15169)),
+            Some((15169, AsnSource::SelfReported))
+        );
+        assert_eq!(resolve_node_asn(None, None, None), None);
+        assert!(!is_public("10.1.2.3".parse().unwrap()));
+        assert!(!is_public("fd00::1".parse().unwrap()));
+        assert!(is_public("2001:4860::8888".parse().unwrap()));
+    }
+}
@@ -0,0 +1,661 @@
+/// GraphQL API
+///
+/// `POST /api/v1/graphql` serves nodes, tasks, connect sessions and cluster
+/// stats as one schema, so a client selects only the fields it renders and
+/// follows relations — a task's assigned nodes and their telemetry — in a
+/// single round trip instead of one REST call per resource.
+///
+/// The schema is read-only and adds no new visibility rules: each field
+/// checks the scope the equivalent `GET` route requires
+/// ([`rbac::required_scope`]) and fetches through the same [`AppState`]
+/// methods, so a caller sees exactly what the REST API would show them.  A
+/// denied or failing field resolves to `null` with an entry in `errors`
+/// carrying the REST error code, while the rest of the query still resolves.
+///
+/// Nested nodes are batched per request with a [`DataLoader`], and queries
+/// are capped at [`MAX_DEPTH`] levels and [`MAX_COMPLEXITY`] fields.
+use crate::auth::Claims;
+use crate::error::{ApiError, ApiResult};
+use crate::models::{ClusterStats, ConnectSessionInfo, NodeCapabilities, NodeInfo, TaskInfo};
+use crate::rbac;
+use crate::state::AppState;
+use crate::telemetry::TelemetryPoint;
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object, Schema, SimpleObject,
+};
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Deepest selection a query may nest.
+pub const MAX_DEPTH: usize = 10;
+
+/// Most fields a query may select, counting each list field once.
+pub const MAX_COMPLEXITY: usize = 1000;
+
+/// Items a list field returns when `limit` is omitted.
+pub const DEFAULT_LIST_LIMIT: usize = 100;
+
+/// Largest `limit` a list field accepts.
+pub const MAX_LIST_LIMIT: usize = 1000;
+
+pub type ApiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// The schema, built on first use.
+pub fn schema() -> &'static ApiSchema {
+    static SCHEMA: OnceLock<ApiSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+            .limit_depth(MAX_DEPTH)
+            .limit_complexity(MAX_COMPLEXITY)
+            .finish()
+    })
+}
+
+/// Body of `POST /api/v1/graphql`
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQlRequest {
+    /// GraphQL document
+    pub query: String,
+    /// Operation to run when the document holds several
+    pub operation_name: Option<String>,
+    /// Values for the operation's variables
+    #[schema(value_type = Option<Object>)]
+    pub variables: Option<serde_json::Value>,
+}
+
+/// The authenticated caller a query runs as.
+pub struct Viewer {
+    pub user_id: Uuid,
+    pub org_id: Option<Uuid>,
+    pub role: String,
+    pub scopes: Vec<String>,
+}
+
+impl Viewer {
+    pub fn from_claims(claims: &Claims) -> ApiResult<Self> {
+        let parse = |value: &str| {
+            Uuid::parse_str(value).map_err(|_| ApiError::unauthorized("Invalid token subject"))
+        };
+        Ok(Self {
+            user_id: parse(&claims.sub)?,
+            org_id: claims.org_id.as_deref().map(parse).transpose()?,
+            role: claims.role.clone(),
+            scopes: claims.granted_scopes(),
+        })
+    }
+}
+
+/// Run `request` as `viewer`.
+pub async fn execute(
+    state: Arc<AppState>,
+    viewer: Viewer,
+    request: GraphQlRequest,
+) -> async_graphql::Response {
+    let mut query = async_graphql::Request::new(request.query);
+    if let Some(operation_name) = request.operation_name {
+        query = query.operation_name(operation_name);
+    }
+    if let Some(variables) = request.variables {
+        query = query.variables(async_graphql::Variables::from_json(variables));
+    }
+    let nodes = DataLoader::new(
+        NodeLoader {
+            state: Arc::clone(&state),
+        },
+        tokio::spawn,
+    );
+    schema()
+        .execute(query.data(state).data(viewer).data(nodes))
+        .await
+}
+
+/// Report an [`ApiError`] as a field error with its code and status.
+fn field_error(err: ApiError) -> async_graphql::Error {
+    let mut error = async_graphql::Error::new(err.message.clone()).extend_with(|_, extensions| {
+        extensions.set("code", err.error.clone());
+        extensions.set("status", err.status_code.as_u16());
+    });
+    if let Some(details) = err.details {
+        error = error.extend_with(|_, extensions| {
+            if let Ok(details) = async_graphql::Value::from_json(details.clone()) {
+                extensions.set("details", details);
+            }
+        });
+    }
+    error
+}
+
+/// The viewer, once they hold the scope `GET route` requires over REST.
+fn authorize<'a>(ctx: &Context<'a>, route: &str) -> async_graphql::Result<&'a Viewer> {
+    let viewer = ctx.data::<Viewer>()?;
+    let required = rbac::required_scope(&Method::GET, route).ok_or_else(|| {
+        field_error(ApiError::forbidden(
+            "Field is not covered by the permission matrix",
+        ))
+    })?;
+    rbac::authorize(&viewer.role, &viewer.scopes, required).map_err(field_error)?;
+    Ok(viewer)
+}
+
+fn state<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a Arc<AppState>> {
+    ctx.data::<Arc<AppState>>()
+}
+
+fn list_limit(limit: Option<i32>) -> async_graphql::Result<usize> {
+    match limit {
+        None => Ok(DEFAULT_LIST_LIMIT),
+        Some(limit) if (1..=MAX_LIST_LIMIT as i32).contains(&limit) => Ok(limit as usize),
+        Some(_) => Err(field_error(ApiError::bad_request(format!(
+            "limit must be between 1 and {MAX_LIST_LIMIT}"
+        )))),
+    }
+}
+
+/// Serialized name of a status enum, as the REST API reports it.
+fn status_name<T: serde::Serialize>(status: &T) -> String {
+    serde_json::to_value(status)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Batches node lookups within one query.
+pub struct NodeLoader {
+    state: Arc<AppState>,
+}
+
+impl Loader<String> for NodeLoader {
+    type Value = NodeInfo;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, node_ids: &[String]) -> Result<HashMap<String, NodeInfo>, Self::Error> {
+        let nodes = self.state.get_nodes(node_ids).await.map_err(field_error)?;
+        Ok(nodes
+            .into_iter()
+            .map(|node| (node.node_id.clone(), node))
+            .collect())
+    }
+}
+
+async fn load_nodes(ctx: &Context<'_>, node_ids: &[String]) -> async_graphql::Result<Vec<Node>> {
+    authorize(ctx, "/api/v1/nodes")?;
+    let loader = ctx.data::<DataLoader<NodeLoader>>()?;
+    let mut found = loader.load_many(node_ids.iter().cloned()).await?;
+    Ok(node_ids
+        .iter()
+        .filter_map(|node_id| found.remove(node_id))
+        .map(Node)
+        .collect())
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A node by ID; `null` when it does not exist.
+    async fn node(
+        &self,
+        ctx: &Context<'_>,
+        node_id: String,
+    ) -> async_graphql::Result<Option<Node>> {
+        Ok(load_nodes(ctx, &[node_id]).await?.pop())
+    }
+
+    /// Registered nodes, newest first, optionally only those in `status`.
+    async fn nodes(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<String>,
+        limit: Option<i32>,
+    ) -> async_graphql::Result<Vec<Node>> {
+        authorize(ctx, "/api/v1/nodes")?;
+        let limit = list_limit(limit)?;
+        Ok(state(ctx)?
+            .list_nodes()
+            .await
+            .into_iter()
+            .filter(|node| status.as_ref().is_none_or(|status| &node.status == status))
+            .take(limit)
+            .map(Node)
+            .collect())
+    }
+
+    /// A task visible to the caller; `null` when it does not exist or
+    /// belongs to someone else.
+    async fn task(
+        &self,
+        ctx: &Context<'_>,
+        task_id: String,
+    ) -> async_graphql::Result<Option<Task>> {
+        let viewer = authorize(ctx, "/api/v1/tasks/:task_id")?;
+        Ok(state(ctx)?
+            .get_task(&task_id, viewer.user_id)
+            .await
+            .map(Task))
+    }
+
+    /// Tasks visible to the caller (theirs, or their organization's when the
+    /// token carries one), newest first.
+    async fn tasks(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<String>,
+        limit: Option<i32>,
+    ) -> async_graphql::Result<Vec<Task>> {
+        let viewer = authorize(ctx, "/api/v1/tasks")?;
+        let limit = list_limit(limit)?;
+        Ok(state(ctx)?
+            .list_tasks_in_org(viewer.user_id, viewer.org_id)
+            .await
+            .into_iter()
+            .filter(|task| {
+                status
+                    .as_ref()
+                    .is_none_or(|status| &status_name(&task.status) == status)
+            })
+            .take(limit)
+            .map(Task)
+            .collect())
+    }
+
+    /// One of the caller's connect sessions.
+    async fn session(
+        &self,
+        ctx: &Context<'_>,
+        session_id: String,
+    ) -> async_graphql::Result<Option<ConnectSession>> {
+        let viewer = authorize(ctx, "/api/v1/connect-sessions/:session_id")?;
+        Ok(state(ctx)?
+            .get_connect_session(&session_id, viewer.user_id)
+            .await
+            .map_err(field_error)?
+            .map(ConnectSession))
+    }
+
+    /// The caller's connect sessions, newest first.
+    async fn sessions(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<String>,
+        limit: Option<i32>,
+    ) -> async_graphql::Result<Vec<ConnectSession>> {
+        let viewer = authorize(ctx, "/api/v1/connect-sessions/:session_id")?;
+        let limit = list_limit(limit)?;
+        // Filter after fetching so a status filter still returns `limit` rows
+        // when older sessions match.
+        let fetch = if status.is_some() {
+            MAX_LIST_LIMIT
+        } else {
+            limit
+        };
+        Ok(state(ctx)?
+            .list_connect_sessions(viewer.user_id, fetch as i64)
+            .await
+            .map_err(field_error)?
+            .into_iter()
+            .filter(|session| {
+                status
+                    .as_ref()
+                    .is_none_or(|status| &status_name(&session.status) == status)
+            })
+            .take(limit)
+            .map(ConnectSession)
+            .collect())
+    }
+
+    /// Current cluster aggregates.
+    async fn cluster_stats(&self, ctx: &Context<'_>) -> async_graphql::Result<ClusterStats> {
+        authorize(ctx, "/api/v1/cluster/stats")?;
+        Ok(state(ctx)?.get_cluster_stats().await)
+    }
+
+    /// Cluster aggregates over time; see `GET /api/v1/cluster/stats/history`.
+    async fn cluster_stats_history(
+        &self,
+        ctx: &Context<'_>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        resolution: Option<String>,
+    ) -> async_graphql::Result<ClusterStatsHistory> {
+        authorize(ctx, "/api/v1/cluster/stats/history")?;
+        let window = crate::telemetry::resolve_window(from, to, resolution.as_deref(), Utc::now())
+            .map_err(field_error)?;
+        let history = state(ctx)?
+            .get_cluster_stats_history(window)
+            .await
+            .map_err(field_error)?;
+        Ok(ClusterStatsHistory {
+            resolution: history.resolution.as_str().to_string(),
+            from: history.from,
+            to: history.to,
+            points: history.points,
+            trend: history.trend,
+        })
+    }
+}
+
+/// A registered node.
+pub struct Node(NodeInfo);
+
+#[Object]
+impl Node {
+    async fn node_id(&self) -> &str {
+        &self.0.node_id
+    }
+
+    async fn region(&self) -> &str {
+        &self.0.region
+    }
+
+    async fn node_type(&self) -> &str {
+        &self.0.node_type
+    }
+
+    async fn owner_id(&self) -> &str {
+        &self.0.owner_id
+    }
+
+    async fn status(&self) -> &str {
+        &self.0.status
+    }
+
+    async fn health_score(&self) -> f64 {
+        self.0.health_score
+    }
+
+    async fn capabilities(&self) -> &NodeCapabilities {
+        &self.0.capabilities
+    }
+
+    /// Placement labels.
+    async fn labels(&self) -> &BTreeMap<String, String> {
+        &self.0.labels
+    }
+
+    async fn asn(&self) -> Option<u32> {
+        self.0.asn
+    }
+
+    /// Whether the flap circuit breaker currently keeps new work away.
+    async fn circuit_open(&self) -> bool {
+        self.0.circuit_breaker.open
+    }
+
+    async fn registered_at(&self) -> &str {
+        &self.0.registered_at
+    }
+
+    async fn last_seen(&self) -> &str {
+        &self.0.last_seen
+    }
+
+    /// Downsampled heartbeat metrics; see `GET /api/v1/nodes/{node_id}/telemetry`.
+    /// Only the node's owner and their organization may read it.
+    async fn telemetry(
+        &self,
+        ctx: &Context<'_>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        resolution: Option<String>,
+    ) -> async_graphql::Result<Option<NodeTelemetry>> {
+        let viewer = authorize(ctx, "/api/v1/nodes/:node_id/telemetry")?;
+        let window = crate::telemetry::resolve_window(from, to, resolution.as_deref(), Utc::now())
+            .map_err(field_error)?;
+        let telemetry = state(ctx)?
+            .get_node_telemetry(&self.0.node_id, viewer.user_id, window)
+            .await
+            .map_err(field_error)?;
+        Ok(Some(NodeTelemetry {
+            resolution: telemetry.resolution.as_str().to_string(),
+            from: telemetry.from,
+            to: telemetry.to,
+            points: telemetry.points,
+        }))
+    }
+}
+
+/// A submitted task.
+pub struct Task(TaskInfo);
+
+#[Object]
+impl Task {
+    async fn task_id(&self) -> &str {
+        &self.0.task_id
+    }
+
+    async fn task_type(&self) -> &str {
+        &self.0.task_type
+    }
+
+    /// `pending`, `running`, `completed`, `failed` or `unschedulable`.
+    async fn status(&self) -> String {
+        status_name(&self.0.status)
+    }
+
+    async fn priority(&self) -> u8 {
+        self.0.priority
+    }
+
+    async fn retry_count(&self) -> u32 {
+        self.0.retry_count
+    }
+
+    async fn last_error(&self) -> Option<&str> {
+        self.0.last_error.as_deref()
+    }
+
+    async fn queue_position(&self) -> Option<i64> {
+        self.0.queue_position
+    }
+
+    async fn proof_id(&self) -> Option<&str> {
+        self.0.proof_id.as_deref()
+    }
+
+    async fn result(&self) -> Option<async_graphql::Json<&serde_json::Value>> {
+        self.0.result.as_ref().map(async_graphql::Json)
+    }
+
+    async fn created_at(&self) -> &str {
+        &self.0.created_at
+    }
+
+    async fn updated_at(&self) -> &str {
+        &self.0.updated_at
+    }
+
+    async fn assigned_node_ids(&self) -> &[String] {
+        &self.0.assigned_nodes
+    }
+
+    /// Nodes currently working on the task.
+    async fn assigned_nodes(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Vec<Node>>> {
+        load_nodes(ctx, &self.0.assigned_nodes).await.map(Some)
+    }
+
+    /// Nodes that worked on the task before being disconnected from it.
+    async fn former_assigned_nodes(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<Vec<Node>>> {
+        load_nodes(ctx, &self.0.former_assigned_nodes)
+            .await
+            .map(Some)
+    }
+}
+
+/// A `connect_only` relay session.
+pub struct ConnectSession(ConnectSessionInfo);
+
+#[Object]
+impl ConnectSession {
+    async fn session_id(&self) -> &str {
+        &self.0.session_id
+    }
+
+    /// `active`, `ended` or `expired`.
+    async fn status(&self) -> String {
+        status_name(&self.0.status)
+    }
+
+    async fn internet_active(&self) -> bool {
+        self.0.internet_active
+    }
+
+    async fn tunnel_protocol(&self) -> &str {
+        &self.0.tunnel_protocol
+    }
+
+    async fn egress_profile(&self) -> &str {
+        &self.0.egress_profile
+    }
+
+    async fn bandwidth_limit_mbps(&self) -> f64 {
+        self.0.bandwidth_limit_mbps
+    }
+
+    async fn bytes_up(&self) -> u64 {
+        self.0.usage.bytes_up
+    }
+
+    async fn bytes_down(&self) -> u64 {
+        self.0.usage.bytes_down
+    }
+
+    async fn data_cap_bytes(&self) -> Option<u64> {
+        self.0.usage.data_cap_bytes
+    }
+
+    async fn created_at(&self) -> &str {
+        &self.0.created_at
+    }
+
+    async fn expires_at(&self) -> &str {
+        &self.0.expires_at
+    }
+
+    async fn ended_at(&self) -> Option<&str> {
+        self.0.ended_at.as_deref()
+    }
+
+    async fn task_id(&self) -> &str {
+        &self.0.task_id
+    }
+
+    /// The `connect_only` task backing the session.
+    async fn task(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Task>> {
+        let viewer = authorize(ctx, "/api/v1/tasks/:task_id")?;
+        Ok(state(ctx)?
+            .get_task(&self.0.task_id, viewer.user_id)
+            .await
+            .map(Task))
+    }
+
+    async fn node_id(&self) -> &str {
+        &self.0.node_id
+    }
+
+    /// The gateway node relaying the session.
+    async fn node(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Node>> {
+        Ok(load_nodes(ctx, std::slice::from_ref(&self.0.node_id))
+            .await?
+            .pop())
+    }
+}
+
+/// A node's telemetry series.
+#[derive(SimpleObject)]
+pub struct NodeTelemetry {
+    /// `raw`, `5m`, `1h` or `1d`.
+    pub resolution: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub points: Vec<TelemetryPoint>,
+}
+
+/// The cluster statistics series.
+#[derive(SimpleObject)]
+pub struct ClusterStatsHistory {
+    /// `raw`, `5m`, `1h` or `1d`.
+    pub resolution: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub points: Vec<crate::cluster_history::ClusterStatsPoint>,
+    pub trend: Option<crate::cluster_history::ClusterStatsTrend>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewer(role: &str, scopes: &[&str]) -> Viewer {
+        Viewer {
+            user_id: Uuid::new_v4(),
+            org_id: None,
+            role: role.to_string(),
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+        }
+    }
+
+    async fn run(viewer: Viewer, query: &str) -> serde_json::Value {
+        let response = execute(
+            Arc::new(AppState::new(None)),
+            viewer,
+            GraphQlRequest {
+                query: query.to_string(),
+                operation_name: None,
+                variables: None,
+            },
+        )
+        .await;
+        serde_json::to_value(response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn fields_check_their_rest_scope() {
+        let response = run(
+            viewer("user", &["nodes:read"]),
+            "{ nodes { nodeId } tasks { taskId } }",
+        )
+        .await;
+
+        assert_eq!(response["data"]["nodes"], serde_json::json!([]));
+        let errors = response["errors"].as_array().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["path"], serde_json::json!(["tasks"]));
+        assert_eq!(errors[0]["extensions"]["status"], 403);
+        assert_eq!(
+            errors[0]["extensions"]["details"]["required_scope"],
+            "tasks:read"
+        );
+    }
+
+    #[tokio::test]
+    async fn deep_queries_are_rejected() {
+        let nested = "{ task(taskId: \"t\") { assignedNodes { nodeId } } }";
+        let response = run(viewer("user", &["*"]), nested).await;
+        assert!(response.get("errors").is_none(), "{response}");
+
+        let deep = "{ __schema { types { fields { type { ofType { ofType { ofType { ofType { ofType { ofType { name } } } } } } } } } } }";
+        let response = run(viewer("user", &["*"]), deep).await;
+        assert!(response["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("nested too deep"));
+    }
+
+    #[test]
+    fn list_limit_is_bounded() {
+        assert_eq!(list_limit(None).unwrap(), DEFAULT_LIST_LIMIT);
+        assert_eq!(list_limit(Some(5)).unwrap(), 5);
+        assert!(list_limit(Some(0)).is_err());
+        assert!(list_limit(Some(MAX_LIST_LIMIT as i32 + 1)).is_err());
+    }
+}
@@ -0,0 +1,561 @@
+/// Optional gRPC listener for node agents
+///
+/// Serves the node-facing control plane defined in `proto/node_agent.proto`
+/// next to the REST API: registration, a bidirectional heartbeat stream,
+/// pushed task assignments, result submission and peer routing (route
+/// queries, invalidation, relay load reports and relay drain announcements).  Messages carry the REST
+/// JSON documents, so validation and signing are shared with the HTTP
+/// handlers.  Built with the `grpc` feature and configured with:
+///
+/// - `GRPC_ENABLED` — `true` to start the listener
+/// - `GRPC_PORT` — TCP port (default `50051`)
+/// - `GRPC_CERT_PATH` / `GRPC_KEY_PATH` — PEM certificate chain and private
+///   key; without them the listener speaks plaintext HTTP/2 and should sit
+///   behind a TLS-terminating proxy
+///
+/// Calls authenticate with `authorization: Bearer <jwt>` or `x-api-key`
+/// metadata and need the scope of their REST counterpart.
+use crate::auth::{AuthUser, Claims};
+use crate::error::ApiError;
+use crate::models::{NodeHeartbeatRequest, NodeRegistration, NodeTaskResult};
+use crate::peer_routes::{PeerRouteInvalidation, RelayDrainAnnouncement, RelayLoadReport};
+use crate::state::AppState;
+use axum::http::Method;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+use uuid::Uuid;
+
+pub mod proto {
+    tonic::include_proto!("ambient.node_agent.v1");
+}
+
+use proto::node_agent_server::{NodeAgent, NodeAgentServer};
+
+/// Default TCP port of the gRPC listener.
+pub const DEFAULT_GRPC_PORT: u16 = 50051;
+
+/// How often an assignment watch re-reads assignments, catching those made
+/// by other replicas.
+const ASSIGNMENT_RESYNC_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Messages buffered per outbound stream before the sender waits.
+const STREAM_BUFFER: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    pub addr: SocketAddr,
+    pub tls: Option<GrpcTls>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcTls {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+impl GrpcConfig {
+    /// Read the listener configuration; `Ok(None)` when gRPC is disabled.
+    pub fn from_env(host: &str) -> anyhow::Result<Option<Self>> {
+        if !parse_enabled(std::env::var("GRPC_ENABLED").ok().as_deref()) {
+            return Ok(None);
+        }
+
+        let port = std::env::var("GRPC_PORT")
+            .ok()
+            .and_then(|raw| raw.parse::<u16>().ok())
+            .unwrap_or(DEFAULT_GRPC_PORT);
+        let tls = match (
+            std::env::var("GRPC_CERT_PATH").ok(),
+            std::env::var("GRPC_KEY_PATH").ok(),
+        ) {
+            (Some(cert_path), Some(key_path)) => Some(GrpcTls {
+                cert_path: cert_path.into(),
+                key_path: key_path.into(),
+            }),
+            (None, None) => None,
+            _ => anyhow::bail!("GRPC_CERT_PATH and GRPC_KEY_PATH must be set together"),
+        };
+
+        Ok(Some(Self {
+            addr: format!("{host}:{port}").parse()?,
+            tls,
+        }))
+    }
+}
+
+fn parse_enabled(value: Option<&str>) -> bool {
+    matches!(
+        value.map(|raw| raw.trim().to_ascii_lowercase()).as_deref(),
+        Some("1" | "true" | "yes")
+    )
+}
+
+/// Serve the node agent service until the listener fails.
+pub async fn serve(state: Arc<AppState>, config: GrpcConfig) -> anyhow::Result<()> {
+    let mut builder = tonic::transport::Server::builder();
+    if let Some(tls) = &config.tls {
+        let identity = tonic::transport::Identity::from_pem(
+            std::fs::read(&tls.cert_path)?,
+            std::fs::read(&tls.key_path)?,
+        );
+        builder =
+            builder.tls_config(tonic::transport::ServerTlsConfig::new().identity(identity))?;
+        tracing::info!("gRPC node agent API listening on {} (TLS)", config.addr);
+    } else {
+        tracing::warn!(
+            "gRPC node agent API listening on {} without TLS; terminate TLS in front of it",
+            config.addr
+        );
+    }
+
+    builder
+        .add_service(NodeAgentServer::new(NodeAgentService::new(state)))
+        .serve(config.addr)
+        .await?;
+    Ok(())
+}
+
+impl From<ApiError> for Status {
+    fn from(err: ApiError) -> Self {
+        let code = match err.status_code.as_u16() {
+            400 | 413 | 422 => tonic::Code::InvalidArgument,
+            401 => tonic::Code::Unauthenticated,
+            403 => tonic::Code::PermissionDenied,
+            404 => tonic::Code::NotFound,
+            409 => tonic::Code::AlreadyExists,
+            429 => tonic::Code::ResourceExhausted,
+            503 => tonic::Code::Unavailable,
+            504 => tonic::Code::DeadlineExceeded,
+            _ => tonic::Code::Internal,
+        };
+        Status::new(code, err.message)
+    }
+}
+
+/// [`NodeAgent`] backed by the same [`AppState`] as the REST API.
+#[derive(Clone)]
+pub struct NodeAgentService {
+    state: Arc<AppState>,
+}
+
+impl NodeAgentService {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    /// Authenticate a call and check it holds the scope `method` on the
+    /// REST `route` needs.
+    async fn authorize(
+        &self,
+        metadata: &tonic::metadata::MetadataMap,
+        method: Method,
+        route: &str,
+    ) -> Result<Claims, Status> {
+        let headers = metadata.clone().into_headers();
+        let claims = crate::middleware::auth::claims_from_headers(&self.state, &headers).await?;
+        let required = crate::rbac::required_scope(&method, route).ok_or_else(|| {
+            Status::permission_denied("Call is not covered by the permission matrix")
+        })?;
+        crate::rbac::authorize(&claims.role, &claims.granted_scopes(), required)?;
+        Ok(claims)
+    }
+}
+
+fn user_uuid(claims: &Claims) -> Result<Uuid, Status> {
+    Uuid::parse_str(&claims.sub).map_err(|_| Status::internal("Invalid user ID format"))
+}
+
+fn parse_json<T: serde::de::DeserializeOwned>(field: &str, raw: &str) -> Result<T, Status> {
+    serde_json::from_str(raw).map_err(|err| Status::invalid_argument(format!("{field}: {err}")))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, Status> {
+    serde_json::to_string(value).map_err(|err| Status::internal(err.to_string()))
+}
+
+fn task_assignment(assignment: &serde_json::Value) -> Option<proto::TaskAssignment> {
+    let field = |name: &str| assignment.get(name)?.as_str().map(str::to_string);
+    Some(proto::TaskAssignment {
+        task_id: field("task_id")?,
+        task_type: field("task_type").unwrap_or_default(),
+        execution_status: field("execution_status").unwrap_or_default(),
+    })
+}
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl NodeAgent for NodeAgentService {
+    async fn register(
+        &self,
+        request: Request<proto::RegisterRequest>,
+    ) -> Result<Response<proto::RegisterResponse>, Status> {
+        let claims = self
+            .authorize(request.metadata(), Method::POST, "/api/v1/nodes")
+            .await?;
+        let observed_ip = request.remote_addr().map(|addr| addr.ip());
+        let registration: NodeRegistration =
+            parse_json("registration_json", &request.get_ref().registration_json)?;
+        registration.validate()?;
+
+        tracing::info!(
+            node_id = %registration.node_id,
+            user = %claims.username,
+            "Registering node over gRPC"
+        );
+
+        let node = self
+            .state
+            .register_node_in_org(
+                registration,
+                user_uuid(&claims)?,
+                AuthUser::from_claims(&claims).org_uuid()?,
+                observed_ip,
+            )
+            .await?;
+
+        Ok(Response::new(proto::RegisterResponse {
+            node_json: to_json(&node)?,
+        }))
+    }
+
+    type HeartbeatStream = ResponseStream<proto::HeartbeatResponse>;
+
+    async fn heartbeat(
+        &self,
+        request: Request<Streaming<proto::HeartbeatRequest>>,
+    ) -> Result<Response<Self::HeartbeatStream>, Status> {
+        let claims = self
+            .authorize(
+                request.metadata(),
+                Method::PUT,
+                "/api/v1/nodes/:node_id/heartbeat",
+            )
+            .await?;
+        let owner_id = user_uuid(&claims)?;
+        let mut inbound = request.into_inner();
+        let state = self.state.clone();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+
+        tokio::spawn(async move {
+            while let Some(message) = inbound.next().await {
+                let reply = match message {
+                    Ok(message) => heartbeat_reply(&state, owner_id, message).await,
+                    Err(status) => {
+                        tracing::debug!("gRPC heartbeat stream closed: {status}");
+                        break;
+                    }
+                };
+                // Errors end the stream, as they would for a unary call.
+                let failed = reply.is_err();
+                if tx.send(reply).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    type WatchAssignmentsStream = ResponseStream<proto::TaskAssignment>;
+
+    async fn watch_assignments(
+        &self,
+        request: Request<proto::WatchAssignmentsRequest>,
+    ) -> Result<Response<Self::WatchAssignmentsStream>, Status> {
+        let claims = self
+            .authorize(request.metadata(), Method::GET, "/api/v1/nodes/:node_id")
+            .await?;
+        let node_id = request.into_inner().node_id;
+        if !self
+            .state
+            .check_node_ownership(&node_id, user_uuid(&claims)?)
+            .await?
+        {
+            return Err(Status::not_found(format!(
+                "Node {node_id} not found or you don't have permission to watch it"
+            )));
+        }
+
+        // Subscribe before the first read so no assignment falls in between.
+        let events = self.state.subscribe_assignments();
+        let state = self.state.clone();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        tokio::spawn(watch_assignments(state, node_id, events, tx));
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn submit_result(
+        &self,
+        request: Request<proto::SubmitResultRequest>,
+    ) -> Result<Response<proto::SubmitResultResponse>, Status> {
+        let claims = self
+            .authorize(
+                request.metadata(),
+                Method::POST,
+                "/api/v1/tasks/:task_id/result",
+            )
+            .await?;
+        let request = request.into_inner();
+        let task_id = Uuid::parse_str(&request.task_id)
+            .map_err(|_| Status::invalid_argument("task_id must be a valid UUID"))?;
+        let submission: NodeTaskResult = parse_json("result_json", &request.result_json)?;
+        submission.validate()?;
+
+        tracing::info!(
+            node_id = %submission.node_id,
+            %task_id,
+            "Node submitting result over gRPC"
+        );
+
+        let result = self
+            .state
+            .submit_task_result(task_id, submission, user_uuid(&claims)?)
+            .await?;
+
+        Ok(Response::new(proto::SubmitResultResponse {
+            response_json: to_json(&result)?,
+        }))
+    }
+
+    async fn find_peer_route(
+        &self,
+        request: Request<proto::FindPeerRouteRequest>,
+    ) -> Result<Response<proto::PeerRouteResponse>, Status> {
+        let claims = self
+            .authorize(
+                request.metadata(),
+                Method::GET,
+                "/api/v1/nodes/:node_id/route",
+            )
+            .await?;
+        let request = request.into_inner();
+        let route = self
+            .state
+            .find_peer_route(&request.node_id, user_uuid(&claims)?, request.has_internet)
+            .await?;
+
+        Ok(Response::new(proto::PeerRouteResponse {
+            route_json: to_json(&route)?,
+        }))
+    }
+
+    async fn invalidate_peer_route(
+        &self,
+        request: Request<proto::InvalidatePeerRouteRequest>,
+    ) -> Result<Response<proto::PeerRouteResponse>, Status> {
+        let claims = self
+            .authorize(
+                request.metadata(),
+                Method::POST,
+                "/api/v1/nodes/:node_id/route/invalidate",
+            )
+            .await?;
+        let request = request.into_inner();
+        let invalidation: PeerRouteInvalidation =
+            parse_json("invalidation_json", &request.invalidation_json)?;
+        invalidation.validate()?;
+
+        let route = self
+            .state
+            .invalidate_peer_route(&request.node_id, user_uuid(&claims)?, &invalidation)
+            .await?;
+
+        Ok(Response::new(proto::PeerRouteResponse {
+            route_json: to_json(&route)?,
+        }))
+    }
+
+    async fn report_relay_load(
+        &self,
+        request: Request<proto::ReportRelayLoadRequest>,
+    ) -> Result<Response<proto::ReportRelayLoadResponse>, Status> {
+        let claims = self
+            .authorize(
+                request.metadata(),
+                Method::PUT,
+                "/api/v1/nodes/:node_id/relay-load",
+            )
+            .await?;
+        let request = request.into_inner();
+        let report: RelayLoadReport = parse_json("load_json", &request.load_json)?;
+        report.validate()?;
+
+        if !self
+            .state
+            .report_relay_load(&request.node_id, user_uuid(&claims)?, &report)
+            .await?
+        {
+            return Err(Status::not_found(format!(
+                "Node {} not found or you don't have permission to report for it",
+                request.node_id
+            )));
+        }
+
+        Ok(Response::new(proto::ReportRelayLoadResponse {
+            response_json: to_json(&serde_json::json!({
+                "node_id": request.node_id,
+                "utilization": report.utilization,
+                "saturated": report.saturated,
+            }))?,
+        }))
+    }
+
+    async fn announce_relay_drain(
+        &self,
+        request: Request<proto::AnnounceRelayDrainRequest>,
+    ) -> Result<Response<proto::AnnounceRelayDrainResponse>, Status> {
+        let claims = self
+            .authorize(
+                request.metadata(),
+                Method::POST,
+                "/api/v1/nodes/:node_id/relay-drain",
+            )
+            .await?;
+        let request = request.into_inner();
+        let announcement: RelayDrainAnnouncement =
+            parse_json("announcement_json", &request.announcement_json)?;
+        announcement.validate()?;
+
+        let Some(response) = self
+            .state
+            .announce_relay_drain(&request.node_id, user_uuid(&claims)?, &announcement)
+            .await?
+        else {
+            return Err(Status::not_found(format!(
+                "Node {} not found, not owned by you, or not online",
+                request.node_id
+            )));
+        };
+
+        Ok(Response::new(proto::AnnounceRelayDrainResponse {
+            response_json: to_json(&response)?,
+        }))
+    }
+}
+
+async fn heartbeat_reply(
+    state: &AppState,
+    owner_id: Uuid,
+    message: proto::HeartbeatRequest,
+) -> Result<proto::HeartbeatResponse, Status> {
+    let request: NodeHeartbeatRequest = if message.heartbeat_json.trim().is_empty() {
+        NodeHeartbeatRequest::default()
+    } else {
+        parse_json("heartbeat_json", &message.heartbeat_json)?
+    };
+    request.validate()?;
+
+    let node_id = message.node_id;
+    let Some(result) = state
+        .update_node_heartbeat(&node_id, owner_id, &request)
+        .await?
+    else {
+        return Err(Status::not_found(format!(
+            "Node {node_id} not found or you don't have permission to update it"
+        )));
+    };
+
+    let response = state.sign_control_response(
+        &node_id,
+        crate::heartbeat_response(&node_id, &request, result),
+    );
+    Ok(proto::HeartbeatResponse {
+        response_json: to_json(&response)?,
+        node_id,
+    })
+}
+
+/// Push `node_id`'s assignments to `tx`: all current ones, then each new one
+/// when this process makes it or a periodic re-read finds it.
+async fn watch_assignments(
+    state: Arc<AppState>,
+    node_id: String,
+    mut events: broadcast::Receiver<String>,
+    tx: mpsc::Sender<Result<proto::TaskAssignment, Status>>,
+) {
+    let mut resync = tokio::time::interval(ASSIGNMENT_RESYNC_INTERVAL);
+    let mut sent: HashSet<String> = HashSet::new();
+
+    loop {
+        tokio::select! {
+            _ = resync.tick() => {}
+            event = events.recv() => match event {
+                Ok(assigned) if assigned != node_id => continue,
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = tx.closed() => return,
+        }
+
+        let assignments = match state.active_assignments_for_node(&node_id).await {
+            Ok(assignments) => assignments,
+            Err(err) => {
+                let _ = tx.send(Err(err.into())).await;
+                return;
+            }
+        };
+
+        let active: HashSet<String> = assignments
+            .iter()
+            .filter_map(task_assignment)
+            .map(|assignment| assignment.task_id.clone())
+            .collect();
+        for assignment in assignments.iter().filter_map(task_assignment) {
+            if sent.contains(&assignment.task_id) {
+                continue;
+            }
+            sent.insert(assignment.task_id.clone());
+            if tx.send(Ok(assignment)).await.is_err() {
+                return;
+            }
+        }
+        // Forget finished assignments so a reassignment is pushed again.
+        sent.retain(|task_id| active.contains(task_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_errors_map_to_grpc_codes() {
+        let cases = [
+            (ApiError::bad_request("bad"), tonic::Code::InvalidArgument),
+            (ApiError::unauthorized("who"), tonic::Code::Unauthenticated),
+            (ApiError::forbidden("no"), tonic::Code::PermissionDenied),
+            (ApiError::not_found("gone"), tonic::Code::NotFound),
+            (
+                ApiError::service_unavailable("down"),
+                tonic::Code::Unavailable,
+            ),
+            (ApiError::internal_error("oops"), tonic::Code::Internal),
+        ];
+        for (err, code) in cases {
+            let message = err.message.clone();
+            let status = Status::from(err);
+            assert_eq!(status.code(), code);
+            assert_eq!(status.message(), message);
+        }
+
+        let assignment = task_assignment(&serde_json::json!({
+            "task_id": "t-1",
+            "task_type": "connect_only",
+            "execution_status": "assigned",
+        }))
+        .unwrap();
+        assert_eq!(assignment.task_id, "t-1");
+        assert_eq!(assignment.task_type, "connect_only");
+        assert!(task_assignment(&serde_json::json!({ "task_type": "x" })).is_none());
+    }
+}
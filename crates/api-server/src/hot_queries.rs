@@ -0,0 +1,290 @@
+//! SQL for the scheduler's hot paths
+//!
+//! These statements run on every task submission, heartbeat and sweep, so
+//! their plans matter at fleet scale.  They live here rather than inline in
+//! `state.rs` so `tests/query_plan_test.rs` can EXPLAIN exactly what the
+//! server runs; `db::REQUIRED_INDEXES` lists the indexes they rely on.
+
+/// Nodes that can take one more attachment of a task, best first.  Nodes
+/// whose flap circuit breaker is open, that sit in an excluded region or
+/// whose operator opted out of the task type are skipped, as is every node
+/// but the one a pinned task names; nodes in a preferred region rank ahead
+/// of the rest.
+///
+/// Binds: `$1` node types that may serve the task, `$2` min CPU cores, `$3` min memory GB,
+/// `$4` min bandwidth Mbps, `$5` GPU required, `$6` task ID, `$7` default
+/// slots per pool, `$8` candidate limit (`NULL` for all), `$9` forbid nodes
+/// relaying an active connect session, `$10` retry-excluded node IDs, `$11`
+/// any node type allowed, `$12` slot class, `$13` node selector, `$14`
+/// preferred regions, `$15` excluded regions, `$16` regions the node must
+/// be in (`NULL` for any), `$17` task type, `$18` minimum health score of a
+/// proof verifier, which must also have a signing key and be independent of
+/// the proof's submitter and the task's other verifiers (`NULL` for other
+/// task types).
+pub const CANDIDATE_NODES: &str = r#"
+SELECT n.node_id, n.region, n.asn, n.health_score, n.benchmark_ops_per_wh
+FROM nodes n
+LEFT JOIN task_assignments ta
+  ON ta.node_id = n.node_id
+ AND ta.disconnected_at IS NULL
+ AND EXISTS (
+     SELECT 1
+     FROM tasks busy
+     WHERE busy.task_id = ta.task_id
+       AND busy.slot_class = $12
+ )
+WHERE n.deleted_at IS NULL
+  AND n.status = 'online'
+  AND (n.flap_breaker_until IS NULL OR n.flap_breaker_until <= NOW())
+  AND (n.node_type = ANY($1) OR $11)
+  AND n.cpu_cores >= $2
+  AND n.memory_gb >= $3
+  AND n.bandwidth_mbps >= $4
+  AND ($5 = FALSE OR n.gpu_available = TRUE)
+  AND (
+        $9 = FALSE
+        OR NOT EXISTS (
+            SELECT 1
+            FROM connect_sessions cs_busy
+            WHERE cs_busy.node_id = n.node_id
+              AND cs_busy.status = 'active'
+              AND cs_busy.expires_at > NOW()
+        )
+      )
+  AND NOT EXISTS (
+      SELECT 1
+      FROM task_assignments existing
+      WHERE existing.task_id = $6
+        AND existing.node_id = n.node_id
+        AND existing.disconnected_at IS NULL
+  )
+  AND NOT (n.node_id = ANY($10))
+  AND n.labels @> $13
+  AND NOT (n.region = ANY($15))
+  AND ($16::TEXT[] IS NULL OR n.region = ANY($16))
+  AND (CARDINALITY(n.allowed_task_types) = 0 OR $17 = ANY(n.allowed_task_types))
+  AND NOT ($17 = ANY(n.blocked_task_types))
+  AND ($18::DOUBLE PRECISION IS NULL OR (
+        n.health_score >= $18
+        AND n.signing_public_key IS NOT NULL
+        AND NOT verifier_conflicts_with_task(n.node_id, $6)
+      ))
+  AND NOT EXISTS (
+      SELECT 1
+      FROM tasks pinned
+      WHERE pinned.task_id = $6
+        AND pinned.pinned_node_id <> n.node_id
+  )
+GROUP BY n.node_id
+-- n.health_score, n.registered_at and the slot columns are omitted from
+-- GROUP BY because they are functionally dependent on n.node_id (the
+-- primary key).  Only assignments in the task's own slot pool count.
+HAVING COUNT(ta.task_id) < COALESCE(
+    CASE $12
+        WHEN 'connect' THEN n.connect_slots
+        WHEN 'gpu' THEN n.gpu_slots
+        ELSE n.wasm_slots
+    END,
+    $7
+)
+ORDER BY n.region = ANY($14) DESC, n.health_score DESC, n.registered_at ASC
+LIMIT $8
+"#;
+
+/// Whether one node meets a pending task's requirements.
+///
+/// Binds: `$1` node ID, `$2` node types that may serve the task, `$3` min CPU cores, `$4`
+/// min memory GB, `$5` min bandwidth Mbps, `$6` GPU required, `$7` forbid
+/// an active connect session, `$8` any node type allowed, `$9` node selector,
+/// `$10` excluded regions, `$11` regions the node must be in (`NULL` for
+/// any), `$12` task type, `$13` minimum health score of a proof verifier,
+/// which must also have a signing key and be independent of the proof's
+/// submitter and the task's other verifiers (`NULL` for other task types),
+/// `$14` task ID.
+pub const NODE_ELIGIBLE_FOR_TASK: &str = r#"
+SELECT EXISTS (
+    SELECT 1
+    FROM nodes n
+    WHERE n.node_id = $1
+      AND n.deleted_at IS NULL
+      AND n.status = 'online'
+      AND (n.flap_breaker_until IS NULL OR n.flap_breaker_until <= NOW())
+      AND (n.node_type = ANY($2) OR $8)
+      AND n.cpu_cores >= $3
+      AND n.memory_gb >= $4
+      AND n.bandwidth_mbps >= $5
+      AND ($6 = FALSE OR n.gpu_available = TRUE)
+      AND n.labels @> $9
+      AND NOT (n.region = ANY($10))
+      AND ($11::TEXT[] IS NULL OR n.region = ANY($11))
+      AND (CARDINALITY(n.allowed_task_types) = 0 OR $12 = ANY(n.allowed_task_types))
+      AND NOT ($12 = ANY(n.blocked_task_types))
+      AND ($13::DOUBLE PRECISION IS NULL OR (
+            n.health_score >= $13
+            AND n.signing_public_key IS NOT NULL
+            AND NOT verifier_conflicts_with_task(n.node_id, $14)
+          ))
+      AND (
+            $7 = FALSE
+            OR NOT EXISTS (
+                SELECT 1
+                FROM connect_sessions cs_busy
+                WHERE cs_busy.node_id = n.node_id
+                  AND cs_busy.status = 'active'
+                  AND cs_busy.expires_at > NOW()
+            )
+          )
+)
+"#;
+
+/// Pending tasks still short of `min_nodes` that a node with free slots in
+/// the given pools could join, highest aged priority first.  Tasks pinned
+/// to another node are left out.
+///
+/// Binds: `$1` priority aging seconds, `$2` node ID, `$3` open slot classes.
+pub const PENDING_TASKS_FOR_NODE: &str = r#"
+-- Running tasks are counted once per requester rather than once per
+-- pending task.
+WITH running AS MATERIALIZED (
+    SELECT org_id, creator_id, COUNT(*) AS running
+    FROM tasks
+    WHERE status = 'running'
+    GROUP BY org_id, creator_id
+)
+SELECT
+    t.task_id,
+    t.task_type,
+    t.min_nodes,
+    t.require_gpu,
+    t.org_id,
+    t.creator_id,
+    t.slot_class,
+    t.node_selector,
+    t.diversity,
+    t.excluded_regions,
+    CASE WHEN t.region_strictness = 'require' THEN t.preferred_regions END AS required_regions,
+    t.constraints_relaxed_at IS NOT NULL AS constraints_relaxed,
+    COALESCE(COUNT(ta.node_id), 0) AS assigned_nodes,
+    COALESCE(
+        (
+            SELECT SUM(r.running)
+            FROM running r
+            WHERE r.org_id IS NOT DISTINCT FROM t.org_id
+              AND (t.org_id IS NOT NULL OR r.creator_id IS NOT DISTINCT FROM t.creator_id)
+        ),
+        0
+    )::BIGINT AS requester_running
+FROM tasks t
+LEFT JOIN task_assignments ta ON ta.task_id = t.task_id AND ta.disconnected_at IS NULL
+WHERE t.status = 'pending'
+  AND t.deleted_at IS NULL
+  AND (t.next_attempt_at IS NULL OR t.next_attempt_at <= NOW())
+  AND NOT ($2 = ANY(t.retry_excluded_nodes))
+  AND (t.pinned_node_id IS NULL OR t.pinned_node_id = $2)
+  AND t.slot_class = ANY($3)
+GROUP BY t.task_id, t.task_type, t.min_nodes, t.require_gpu
+HAVING COALESCE(COUNT(ta.node_id), 0) < t.min_nodes
+ORDER BY
+    t.priority + FLOOR(EXTRACT(EPOCH FROM (NOW() - t.created_at)) / $1) DESC,
+    t.created_at ASC
+"#;
+
+/// Free slots in each of one node's slot pools.
+///
+/// Binds: `$1` node ID, `$2` default slots per pool.
+pub const NODE_FREE_SLOTS: &str = r#"
+SELECT
+    COALESCE(n.connect_slots, $2)
+        - COUNT(t.task_id) FILTER (WHERE t.slot_class = 'connect') AS connect_free,
+    COALESCE(n.wasm_slots, $2)
+        - COUNT(t.task_id) FILTER (WHERE t.slot_class = 'wasm') AS wasm_free,
+    COALESCE(n.gpu_slots, $2)
+        - COUNT(t.task_id) FILTER (WHERE t.slot_class = 'gpu') AS gpu_free
+FROM nodes n
+LEFT JOIN task_assignments ta
+  ON ta.node_id = n.node_id
+ AND ta.disconnected_at IS NULL
+LEFT JOIN tasks t ON t.task_id = ta.task_id
+WHERE n.node_id = $1
+GROUP BY n.node_id
+"#;
+
+/// Mark live nodes whose heartbeat is older than the threshold offline,
+/// returning their IDs.
+///
+/// Binds: `$1` threshold in minutes.
+pub const SWEEP_OFFLINE_NODES: &str = r#"
+WITH swept AS (
+    UPDATE nodes
+    SET status = 'offline', updated_at = NOW()
+    WHERE status IN ('online', 'draining')
+      AND deleted_at IS NULL
+      AND last_heartbeat < NOW() - (interval '1 minute' * $1)
+    RETURNING node_id
+)
+SELECT node_id FROM swept
+"#;
+
+/// Attach a task to the listed nodes in one statement, best first, reviving
+/// disconnected assignments of the same pair.  Run with the task row and the
+/// node rows locked: a node is skipped once its pool for the task's slot
+/// class is full, and no more nodes are attached than the task still needs.
+/// Returns the attached node IDs.
+///
+/// Binds: `$1` task ID, `$2` node IDs in preference order, `$3` slot class,
+/// `$4` default slots per pool, `$5` the task's min nodes.
+pub const ASSIGN_TASK_TO_NODES: &str = r#"
+INSERT INTO task_assignments (task_id, node_id)
+SELECT $1, wanted.node_id
+FROM unnest($2::TEXT[]) WITH ORDINALITY AS wanted(node_id, position)
+JOIN nodes n ON n.node_id = wanted.node_id
+WHERE NOT EXISTS (
+      SELECT 1
+      FROM task_assignments existing
+      WHERE existing.task_id = $1
+        AND existing.node_id = n.node_id
+        AND existing.disconnected_at IS NULL
+  )
+  AND (
+      SELECT COUNT(*)
+      FROM task_assignments ta
+      JOIN tasks busy ON busy.task_id = ta.task_id
+      WHERE ta.node_id = n.node_id
+        AND ta.disconnected_at IS NULL
+        AND busy.slot_class = $3
+  ) < COALESCE(
+      CASE $3
+          WHEN 'connect' THEN n.connect_slots
+          WHEN 'gpu' THEN n.gpu_slots
+          ELSE n.wasm_slots
+      END,
+      $4
+  )
+ORDER BY wanted.position
+LIMIT GREATEST(
+    $5 - (
+        SELECT COUNT(*)
+        FROM task_assignments attached
+        WHERE attached.task_id = $1
+          AND attached.disconnected_at IS NULL
+    ),
+    0
+)
+ON CONFLICT (task_id, node_id)
+DO UPDATE SET assigned_at = NOW(), disconnected_at = NULL,
+              execution_status = 'assigned',
+              execution_started_at = NULL,
+              execution_completed_at = NULL
+WHERE task_assignments.disconnected_at IS NOT NULL
+RETURNING node_id
+"#;
+
+/// Every hot query, by name, for plan checks.
+pub const ALL: &[(&str, &str)] = &[
+    ("candidate_nodes", CANDIDATE_NODES),
+    ("node_eligible_for_task", NODE_ELIGIBLE_FOR_TASK),
+    ("pending_tasks_for_node", PENDING_TASKS_FOR_NODE),
+    ("node_free_slots", NODE_FREE_SLOTS),
+    ("assign_task_to_nodes", ASSIGN_TASK_TO_NODES),
+    ("sweep_offline_nodes", SWEEP_OFFLINE_NODES),
+];
@@ -0,0 +1,190 @@
+/// Optional HTTP/3 (QUIC) listener
+///
+/// Serves the same router as the TCP listener over QUIC so nodes on lossy
+/// links avoid TCP head-of-line blocking.  Built with the `http3` feature and
+/// configured with:
+///
+/// - `HTTP3_ENABLED` — `true` to start the listener
+/// - `HTTP3_PORT` — UDP port (defaults to the TCP `PORT`)
+/// - `HTTP3_CERT_PATH` / `HTTP3_KEY_PATH` — PEM certificate chain and private
+///   key (QUIC always requires TLS)
+///
+/// TCP responses advertise the listener with an `Alt-Svc` header so clients
+/// can discover it; see [`alt_svc_value`].
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::Router;
+use bytes::{Buf, Bytes, BytesMut};
+use http_body_util::BodyExt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// ALPN protocol identifier for HTTP/3.
+pub const H3_ALPN: &[u8] = b"h3";
+
+/// Largest request body buffered from an HTTP/3 stream; per-route body
+/// limits still apply once the request reaches the router.
+const MAX_H3_REQUEST_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Http3Config {
+    pub addr: SocketAddr,
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+impl Http3Config {
+    /// Read the listener configuration; `Ok(None)` when HTTP/3 is disabled.
+    pub fn from_env(host: &str, default_port: u16) -> anyhow::Result<Option<Self>> {
+        if !parse_enabled(std::env::var("HTTP3_ENABLED").ok().as_deref()) {
+            return Ok(None);
+        }
+
+        let port = std::env::var("HTTP3_PORT")
+            .ok()
+            .and_then(|raw| raw.parse::<u16>().ok())
+            .unwrap_or(default_port);
+        let cert_path = std::env::var("HTTP3_CERT_PATH")
+            .map_err(|_| anyhow::anyhow!("HTTP3_ENABLED requires HTTP3_CERT_PATH"))?;
+        let key_path = std::env::var("HTTP3_KEY_PATH")
+            .map_err(|_| anyhow::anyhow!("HTTP3_ENABLED requires HTTP3_KEY_PATH"))?;
+
+        Ok(Some(Self {
+            addr: format!("{host}:{port}").parse()?,
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+        }))
+    }
+}
+
+fn parse_enabled(value: Option<&str>) -> bool {
+    matches!(
+        value.map(|raw| raw.trim().to_ascii_lowercase()).as_deref(),
+        Some("1" | "true" | "yes")
+    )
+}
+
+/// `Alt-Svc` header value advertising HTTP/3 on `port` for 24 hours.
+pub fn alt_svc_value(port: u16) -> String {
+    format!("h3=\":{port}\"; ma=86400")
+}
+
+fn server_config(config: &Http3Config) -> anyhow::Result<quinn::ServerConfig> {
+    use rustls_pki_types::pem::PemObject;
+    use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+
+    let certs = CertificateDer::pem_file_iter(&config.cert_path)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| anyhow::anyhow!("invalid HTTP3_CERT_PATH: {err}"))?;
+    let key = PrivateKeyDer::from_pem_file(&config.key_path)
+        .map_err(|err| anyhow::anyhow!("invalid HTTP3_KEY_PATH: {err}"))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut tls = rustls::ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    tls.alpn_protocols = vec![H3_ALPN.to_vec()];
+    tls.max_early_data_size = 0;
+
+    let crypto = quinn::crypto::rustls::QuicServerConfig::try_from(tls)?;
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(crypto)))
+}
+
+/// Accept QUIC connections on `config.addr` and serve `router` over HTTP/3
+/// until the endpoint is closed.
+pub async fn serve(router: Router, config: Http3Config) -> anyhow::Result<()> {
+    let endpoint = quinn::Endpoint::server(server_config(&config)?, config.addr)?;
+    tracing::info!("API Server listening on https://{} (HTTP/3)", config.addr);
+
+    while let Some(incoming) = endpoint.accept().await {
+        let router = router.clone();
+        tokio::spawn(async move {
+            let remote = incoming.remote_address();
+            match incoming.await {
+                Ok(conn) => {
+                    if let Err(err) = serve_connection(router, conn, remote).await {
+                        tracing::debug!(%remote, "HTTP/3 connection closed: {err}");
+                    }
+                }
+                Err(err) => tracing::debug!(%remote, "QUIC handshake failed: {err}"),
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn serve_connection(
+    router: Router,
+    conn: quinn::Connection,
+    remote: SocketAddr,
+) -> anyhow::Result<()> {
+    let mut h3_conn = h3::server::builder()
+        .build::<_, Bytes>(h3_quinn::Connection::new(conn))
+        .await?;
+
+    while let Some(resolver) = h3_conn.accept().await? {
+        let router = router.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let (request, mut stream) = resolver.resolve_request().await?;
+
+                let mut body = BytesMut::new();
+                while let Some(mut chunk) = stream.recv_data().await? {
+                    if body.len() + chunk.remaining() > MAX_H3_REQUEST_BYTES {
+                        anyhow::bail!("request body exceeds {MAX_H3_REQUEST_BYTES} bytes");
+                    }
+                    body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+                }
+
+                // Handlers and rate limiting read the peer address the same way
+                // they do for TCP connections.
+                let (parts, ()) = request.into_parts();
+                let mut request = axum::http::Request::from_parts(parts, Body::from(body.freeze()));
+                request.extensions_mut().insert(ConnectInfo(remote));
+
+                let response = router.oneshot(request).await?;
+                let (parts, mut body) = response.into_parts();
+                stream
+                    .send_response(axum::http::Response::from_parts(parts, ()))
+                    .await?;
+
+                while let Some(frame) = body.frame().await {
+                    if let Ok(chunk) = frame?.into_data() {
+                        stream.send_data(chunk).await?;
+                    }
+                }
+                stream.finish().await?;
+                Ok(())
+            }
+            .await;
+
+            if let Err(err) = result {
+                tracing::debug!(%remote, "HTTP/3 request failed: {err}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_enabled_flag() {
+        assert!(parse_enabled(Some("true")));
+        assert!(parse_enabled(Some("1")));
+        assert!(!parse_enabled(Some("false")));
+        assert!(!parse_enabled(None));
+    }
+
+    #[test]
+    fn formats_alt_svc_header() {
+        assert_eq!(alt_svc_value(8443), "h3=\":8443\"; ma=86400");
+    }
+}
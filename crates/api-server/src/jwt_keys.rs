@@ -0,0 +1,362 @@
+/// Rotating Ed25519 keys that sign access tokens
+///
+/// Access tokens are signed with EdDSA and name their key in the `kid`
+/// header.  Each rotation period gets its own randomly generated key.  With
+/// a database the seeds are stored in `jwt_signing_keys`, sealed with a key
+/// derived from `JWT_SECRET`: the first replica to need a period's key
+/// inserts one and every replica uses whichever was stored, so replicas sign
+/// alike and a restart keeps issued tokens valid.  Without a database keys
+/// live in memory and a restart invalidates the tokens they signed.
+///
+/// - `JWT_KEY_ROTATION_SECS` (default `86400`, at least
+///   [`MIN_KEY_ROTATION_SECS`]): how long one key signs new tokens; `0`
+///   keeps a single key
+///
+/// Besides the current key, a ring verifies the next one, which is published
+/// early so clients caching `/.well-known/jwks.json` know it before it signs,
+/// and enough previous ones to outlive the tokens they signed.
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+pub const DEFAULT_KEY_ROTATION_SECS: u64 = 86_400;
+
+/// Shortest rotation period; shorter settings are raised to it so the ring
+/// never has to retain more than a token lifetime's worth of hourly keys.
+pub const MIN_KEY_ROTATION_SECS: u64 = 3_600;
+
+/// DER prefix that wraps a bare Ed25519 seed as a PKCS#8 v1 private key
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// Ed25519 private key seed
+pub type Seed = [u8; 32];
+
+/// Load the rotation period from `JWT_KEY_ROTATION_SECS`.
+pub fn rotation_secs_from_env() -> Option<u64> {
+    parse_rotation_secs(std::env::var("JWT_KEY_ROTATION_SECS").ok().as_deref())
+}
+
+/// Parse the rotation period; unset or malformed values keep the default,
+/// `0` disables rotation and anything shorter than
+/// [`MIN_KEY_ROTATION_SECS`] is raised to it.
+pub fn parse_rotation_secs(value: Option<&str>) -> Option<u64> {
+    Some(
+        value
+            .and_then(|raw| raw.trim().parse().ok())
+            .unwrap_or(DEFAULT_KEY_ROTATION_SECS),
+    )
+    .filter(|secs| *secs > 0)
+    .map(|secs| secs.max(MIN_KEY_ROTATION_SECS))
+}
+
+/// Rotation period that contains Unix-epoch second `now`.
+pub fn period_at(rotation_secs: Option<u64>, now: u64) -> u64 {
+    rotation_secs.map_or(0, |secs| now / secs)
+}
+
+#[derive(Clone)]
+struct SigningKey {
+    period: u64,
+    kid: String,
+    seed: Seed,
+    public_key: Vec<u8>,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl SigningKey {
+    fn generate(period: u64) -> Self {
+        let mut seed = [0u8; 32];
+        SystemRandom::new()
+            .fill(&mut seed)
+            .expect("system RNG should produce a signing key");
+        Self::from_seed(period, seed)
+    }
+
+    fn from_seed(period: u64, seed: Seed) -> Self {
+        let pair = Ed25519KeyPair::from_seed_unchecked(&seed).expect("seed is 32 bytes");
+        let public_key = pair.public_key().as_ref().to_vec();
+        let mut pkcs8 = ED25519_PKCS8_PREFIX.to_vec();
+        pkcs8.extend_from_slice(&seed);
+
+        Self {
+            period,
+            kid: format!("vcp-{}", period),
+            seed,
+            encoding: EncodingKey::from_ed_der(&pkcs8),
+            decoding: DecodingKey::from_ed_der(&public_key),
+            public_key,
+        }
+    }
+}
+
+/// Keys in use during one rotation period
+pub struct SigningKeyRing {
+    period: u64,
+    /// Newest first
+    keys: Vec<SigningKey>,
+}
+
+impl SigningKeyRing {
+    /// Periods the ring for `period` holds keys for, newest first: the next
+    /// one, the current one and those whose tokens may not have expired.
+    pub fn periods(rotation_secs: Option<u64>, token_lifetime_secs: u64, period: u64) -> Vec<u64> {
+        match rotation_secs {
+            Some(secs) => {
+                let retained = token_lifetime_secs.div_ceil(secs);
+                (period.saturating_sub(retained)..=period + 1)
+                    .rev()
+                    .collect()
+            }
+            None => vec![period],
+        }
+    }
+
+    /// Ring for `period`, keeping the keys `previous` holds for periods that
+    /// are still needed and generating random ones for the rest.
+    pub fn rotate(
+        previous: Option<&Self>,
+        rotation_secs: Option<u64>,
+        token_lifetime_secs: u64,
+        period: u64,
+    ) -> Self {
+        let seeds: HashMap<u64, Seed> = previous
+            .map(|ring| ring.seeds().collect())
+            .unwrap_or_default();
+        Self::from_seeds(
+            period,
+            Self::periods(rotation_secs, token_lifetime_secs, period),
+            &seeds,
+        )
+    }
+
+    /// Ring for `period` holding a key for each of `periods` (newest first),
+    /// from `seeds` where one is given and random otherwise.
+    pub fn from_seeds(period: u64, periods: Vec<u64>, seeds: &HashMap<u64, Seed>) -> Self {
+        Self {
+            period,
+            keys: periods
+                .into_iter()
+                .map(|period| match seeds.get(&period) {
+                    Some(seed) => SigningKey::from_seed(period, *seed),
+                    None => SigningKey::generate(period),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    /// Seed of each key in the ring, by period.
+    pub fn seeds(&self) -> impl Iterator<Item = (u64, Seed)> + '_ {
+        self.keys.iter().map(|key| (key.period, key.seed))
+    }
+
+    /// `kid` and key that sign new tokens.
+    pub fn signing_key(&self) -> (&str, &EncodingKey) {
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.period == self.period)
+            .expect("ring holds its current key");
+        (&key.kid, &key.encoding)
+    }
+
+    /// Key that verifies tokens carrying `kid`, if it is still in the ring.
+    pub fn decoding_key(&self, kid: &str) -> Option<&DecodingKey> {
+        self.keys
+            .iter()
+            .find(|key| key.kid == kid)
+            .map(|key| &key.decoding)
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .keys
+                .iter()
+                .map(|key| Jwk {
+                    kty: "OKP".to_string(),
+                    crv: "Ed25519".to_string(),
+                    alg: "EdDSA".to_string(),
+                    key_use: "sig".to_string(),
+                    kid: key.kid.clone(),
+                    x: URL_SAFE_NO_PAD.encode(&key.public_key),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Store the seeds of `candidate` for periods `jwt_signing_keys` has no key
+/// for, drop keys older than the ring, and return the ring built from what
+/// is stored, which is the same on every replica.
+pub async fn sync_ring(
+    db: &PgPool,
+    jwt_secret: &str,
+    candidate: &SigningKeyRing,
+) -> anyhow::Result<SigningKeyRing> {
+    let sealing_key = sealing_key(jwt_secret);
+    let periods: Vec<u64> = candidate.keys.iter().map(|key| key.period).collect();
+    let oldest = periods.iter().copied().min().unwrap_or(candidate.period);
+
+    let mut tx = db.begin().await?;
+    for (period, seed) in candidate.seeds() {
+        sqlx::query(
+            r#"
+            INSERT INTO jwt_signing_keys (period, sealed_seed)
+            VALUES ($1, $2)
+            ON CONFLICT (period) DO NOTHING
+            "#,
+        )
+        .bind(period as i64)
+        .bind(seal_seed(&sealing_key, period, &seed))
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query("DELETE FROM jwt_signing_keys WHERE period < $1")
+        .bind(oldest as i64)
+        .execute(&mut *tx)
+        .await?;
+    let rows: Vec<(i64, Vec<u8>)> =
+        sqlx::query_as("SELECT period, sealed_seed FROM jwt_signing_keys WHERE period = ANY($1)")
+            .bind(
+                periods
+                    .iter()
+                    .map(|period| *period as i64)
+                    .collect::<Vec<_>>(),
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+    tx.commit().await?;
+
+    let mut seeds = HashMap::new();
+    for (period, sealed) in rows {
+        let period = period as u64;
+        let seed = open_seed(&sealing_key, period, &sealed).ok_or_else(|| {
+            anyhow::anyhow!(
+                "JWT signing key for period {period} does not open with JWT_SECRET; \
+                 clear jwt_signing_keys after changing JWT_SECRET"
+            )
+        })?;
+        seeds.insert(period, seed);
+    }
+    Ok(SigningKeyRing::from_seeds(
+        candidate.period,
+        periods,
+        &seeds,
+    ))
+}
+
+/// AES-256-GCM key for stored seeds, derived from `JWT_SECRET`.
+fn sealing_key(jwt_secret: &str) -> LessSafeKey {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(jwt_secret.as_bytes()).expect("HMAC accepts any key size");
+    mac.update(b"jwt-signing-key-seal");
+    let key = UnboundKey::new(&AES_256_GCM, &mac.finalize().into_bytes())
+        .expect("HMAC-SHA256 output is an AES-256 key");
+    LessSafeKey::new(key)
+}
+
+/// Nonce followed by the sealed seed; the period is authenticated with it.
+fn seal_seed(key: &LessSafeKey, period: u64, seed: &Seed) -> Vec<u8> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .expect("system RNG should produce a nonce");
+    let mut sealed = seed.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(period.to_be_bytes()),
+        &mut sealed,
+    )
+    .expect("AES-GCM seals a 32-byte seed");
+    [nonce.as_slice(), &sealed].concat()
+}
+
+fn open_seed(key: &LessSafeKey, period: u64, sealed: &[u8]) -> Option<Seed> {
+    let (nonce, ciphertext) = sealed.split_at_checked(NONCE_LEN)?;
+    let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+    let mut buffer = ciphertext.to_vec();
+    let seed = key
+        .open_in_place(nonce, Aad::from(period.to_be_bytes()), &mut buffer)
+        .ok()?;
+    seed.try_into().ok()
+}
+
+/// A public signing key (RFC 8037 `OKP` key)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub alg: String,
+    #[serde(rename = "use")]
+    pub key_use: String,
+    pub kid: String,
+    /// Base64url public key
+    pub x: String,
+}
+
+/// Keys currently accepted for access tokens, newest first
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rotation_secs_defaults_and_disabling() {
+        assert_eq!(parse_rotation_secs(None), Some(DEFAULT_KEY_ROTATION_SECS));
+        assert_eq!(
+            parse_rotation_secs(Some("daily")),
+            Some(DEFAULT_KEY_ROTATION_SECS)
+        );
+        assert_eq!(parse_rotation_secs(Some("7200")), Some(7200));
+        assert_eq!(parse_rotation_secs(Some("1")), Some(MIN_KEY_ROTATION_SECS));
+        assert_eq!(parse_rotation_secs(Some("0")), None);
+    }
+
+    #[test]
+    fn test_ring_publishes_next_key_and_retains_previous_ones() {
+        let ring = SigningKeyRing::rotate(None, Some(3600), 7200, 10);
+        let kids: Vec<_> = ring.jwks().keys.into_iter().map(|key| key.kid).collect();
+        assert_eq!(kids, ["vcp-11", "vcp-10", "vcp-9", "vcp-8"]);
+        assert_eq!(ring.signing_key().0, "vcp-10");
+        assert!(ring.decoding_key("vcp-7").is_none());
+
+        // Rotating keeps the keys still in use; new periods get fresh ones.
+        let later = SigningKeyRing::rotate(Some(&ring), Some(3600), 7200, 11);
+        assert_eq!(later.jwks().keys[1].x, ring.jwks().keys[0].x);
+        let other = SigningKeyRing::rotate(None, Some(3600), 7200, 10);
+        assert_ne!(other.jwks().keys[1].x, ring.jwks().keys[1].x);
+
+        let single = SigningKeyRing::rotate(None, None, 7200, 0);
+        assert_eq!(single.jwks().keys.len(), 1);
+    }
+
+    #[test]
+    fn test_sealed_seeds_open_only_with_their_secret_and_period() {
+        let key = sealing_key("secret");
+        let seed = [7u8; 32];
+        let sealed = seal_seed(&key, 10, &seed);
+        assert_eq!(open_seed(&key, 10, &sealed), Some(seed));
+        assert_eq!(open_seed(&key, 11, &sealed), None);
+        assert_eq!(open_seed(&sealing_key("other"), 10, &sealed), None);
+        assert_eq!(open_seed(&key, 10, &sealed[..8]), None);
+    }
+}
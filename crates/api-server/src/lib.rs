@@ -12,26 +12,72 @@
 // - `handlers/health.rs`   — Health-check endpoint
 // - `handlers/proofs.rs`   — ZK proof verification handlers
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, StatusCode},
     middleware as axum_middleware,
-    routing::{get, post, put},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::{delete, get, post, put},
     Json, Router,
 };
+use futures::StreamExt;
 use sqlx::Row;
 use std::{sync::Arc, time::Duration};
 use tower_http::services::ServeDir;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use utoipa::OpenApi;
 use uuid::Uuid;
 
+pub mod account_tokens;
+pub mod artifacts;
+pub mod attestation;
 pub mod auth;
+pub mod canary;
+pub mod carbon;
+pub mod cluster_export;
+pub mod cluster_history;
 pub mod db;
+pub mod deployment_profile;
+pub mod destination_policies;
 pub mod error;
+pub mod fair_queue;
+pub mod flap_breaker;
+pub mod geoip;
+pub mod graphql;
+#[cfg(feature = "grpc")]
+#[allow(clippy::result_large_err)] // tonic handlers return `tonic::Status`
+pub mod grpc;
+pub mod hot_queries;
+#[cfg(feature = "http3")]
+pub mod http3;
+pub mod jwt_keys;
+pub mod lockout;
 pub mod middleware;
+pub mod migrations;
 pub mod models;
+pub mod node_rankings;
+pub mod notifier;
+pub mod orgs;
+pub mod otel;
+pub mod peer_routes;
+pub mod proof_batches;
+pub mod proof_offload;
 pub mod rate_limit;
+pub mod rbac;
+pub mod retention;
+pub mod scheduler_decisions;
+pub mod scheduling;
+pub mod starvation;
 pub mod state;
+pub mod status;
+pub mod task_search;
+pub mod task_stats;
+pub mod telemetry;
+pub mod telemetry_export;
+pub mod transparency;
 
 use error::{ApiError, ApiResult};
 use models::*;
@@ -42,50 +88,192 @@ use state::AppState;
 #[openapi(
     paths(
         health_check,
+        get_server_time,
+        get_control_key,
+        get_jwks,
+        get_transparency,
+        get_transparency_history,
+        get_status,
         register_node,
         list_nodes,
+        get_node_rankings,
         get_node,
+        update_node,
         delete_node,
         reject_node,
+        drain_node,
+        undrain_node,
+        attest_node,
+        renew_attestation_challenge,
         update_heartbeat,
+        update_heartbeats_batch,
         get_node_heartbeat_activity,
+        get_node_telemetry,
         get_node_gateway_sessions,
+        report_gateway_session_usage,
+        get_node_peer_route,
+        invalidate_node_peer_route,
+        report_relay_load,
+        announce_relay_drain,
+        get_destination_policy_bundle,
         submit_task,
         get_task,
         list_tasks,
+        search_tasks,
         delete_task,
+        restore_task,
         submit_task_result,
+        list_task_secret_recipients,
+        put_task_secrets,
+        get_task_secrets_for_node,
+        put_task_checkpoint,
+        get_task_checkpoint,
+        get_task_provenance,
+        get_task_timeline,
+        append_task_logs,
+        stream_task_logs,
         start_connect_session,
         get_connect_session,
         heartbeat_connect_session,
+        extend_connect_session,
+        report_connect_session_usage,
         stop_connect_session,
         verify_proof,
+        create_proof_batch,
+        get_proof_batch,
+        get_proof_quota,
+        get_proof,
         get_cluster_stats,
+        get_cluster_stats_history,
+        get_task_type_stats,
+        graphql_query,
+        get_usage_report,
+        upload_wasm_module,
+        download_wasm_module,
         register_user,
         login,
         refresh_token,
+        request_password_reset,
+        confirm_password_reset,
+        verify_email,
+        create_api_key,
+        list_api_keys,
+        revoke_api_key,
+        create_organization,
+        list_organizations,
+        list_organization_members,
+        set_organization_member,
+        remove_organization_member,
+        issue_organization_token,
     ),
     components(schemas(
         HealthResponse,
+        ServerTimeResponse,
+        ControlKeyResponse,
+        jwt_keys::Jwk,
+        jwt_keys::JwkSet,
+        NodeHeartbeatRequest,
+        NodeHeartbeatBatchRequest,
+        NodeHeartbeatBatchItem,
         NodeRegistration,
         NodeInfo,
+        node_rankings::NodeRankingsResponse,
+        node_rankings::NodeRanking,
+        node_rankings::RankingBreakdown,
+        node_rankings::RankingComponent,
+        flap_breaker::NodeCircuitBreaker,
+        NodeSlots,
+        SlotClass,
+        geoip::AsnSource,
         TaskSubmission,
         TaskInfo,
         TaskStatus,
+        TaskRequirements,
+        TaskEgressRule,
+        SchedulingMode,
+        DiversityMode,
         NodeTaskResult,
         ConnectSessionStartRequest,
+        ConnectSessionExtendRequest,
         ConnectSessionInfo,
+        ConnectSessionUsage,
+        ConnectSessionUsageReport,
         ConnectSessionStartResponse,
         ConnectSessionStatus,
         ProofVerificationRequest,
         ProofVerificationResponse,
+        proof_batches::ProofBatchRequest,
+        proof_batches::ProofBatch,
+        proof_batches::ProofBatchResult,
+        proof_batches::ProofQuota,
+        ProofRecord,
         ClusterStats,
+        cluster_history::ClusterStatsHistoryResponse,
+        graphql::GraphQlRequest,
+        cluster_history::ClusterStatsPoint,
+        cluster_history::ClusterStatsTrend,
+        task_stats::TaskTypeStats,
+        task_search::TaskSearchHit,
+        GatewaySessionUsageReport,
+        peer_routes::PeerRouteInvalidation,
+        peer_routes::PeerRouteResponse,
+        peer_routes::RelayLoadReport,
+        peer_routes::RelayDrainAnnouncement,
+        peer_routes::RelayDrainResponse,
+        peer_routes::PeerRouteChange,
+        peer_routes::SessionMigration,
+        UsageReport,
+        WasmModuleInfo,
+        SealedTaskSecret,
+        TaskLogBatch,
+        TaskLogLine,
+        TaskLogEntry,
+        TaskSecretsUpload,
+        TaskSecretRecipient,
+        TaskCheckpointInfo,
+        NodeDrainResponse,
+        NodeUpdateRequest,
+        attestation::AttestationChallenge,
+        attestation::NodeAttestationRequest,
+        TaskProvenance,
+        TaskTimeline,
+        TaskTimelineEvent,
+        TaskSandboxReport,
+        RegionEnergyUsage,
+        TaskQueueWait,
+        CreateOrganizationRequest,
+        OrganizationInfo,
+        OrganizationMember,
+        SetOrganizationMemberRequest,
+        orgs::OrgRole,
+        telemetry::NodeTelemetryResponse,
+        telemetry::TelemetryPoint,
+        telemetry::TelemetryResolution,
+        transparency::TransparencySnapshot,
+        transparency::NodeCounts,
+        transparency::UptimeDistribution,
+        transparency::WindowedCount,
+        transparency::SignedTransparencySnapshot,
+        transparency::TransparencyResponse,
+        status::StatusResponse,
+        status::ComponentStatus,
+        status::ComponentReport,
+        status::UptimePercentages,
+        status::RegionRelayCapacity,
+        status::IncidentStatus,
+        status::IncidentSummary,
         ApiError,
         auth::RegisterRequest,
         auth::LoginRequest,
         auth::LoginResponse,
         auth::RefreshTokenRequest,
         auth::RefreshTokenResponse,
+        auth::PasswordResetRequest,
+        auth::PasswordResetConfirmRequest,
+        auth::EmailVerificationRequest,
+        auth::CreateApiKeyRequest,
+        auth::CreateApiKeyResponse,
+        auth::ApiKeyInfo,
     ))
 )]
 struct ApiDoc;
@@ -116,7 +304,134 @@ async fn health_check() -> Json<HealthResponse> {
     })
 }
 
+/// Server clock for node skew probes
+#[utoipa::path(
+    get,
+    path = "/api/v1/time",
+    responses(
+        (status = 200, description = "Server receive and transmit timestamps", body = ServerTimeResponse)
+    )
+)]
+async fn get_server_time() -> Json<ServerTimeResponse> {
+    let received_at_ms = chrono::Utc::now().timestamp_millis();
+    Json(ServerTimeResponse {
+        received_at_ms,
+        transmitted_at_ms: chrono::Utc::now().timestamp_millis(),
+    })
+}
+
+/// Public key nodes pin to verify signed heartbeat and gateway-session
+/// responses
+///
+/// Fetching it over the network trusts the network once; operators should
+/// distribute it out of band where possible.
+#[utoipa::path(
+    get,
+    path = "/api/v1/control-key",
+    responses(
+        (status = 200, description = "Coordinator control public key", body = ControlKeyResponse)
+    )
+)]
+async fn get_control_key(State(state): State<Arc<AppState>>) -> Json<ControlKeyResponse> {
+    Json(ControlKeyResponse {
+        public_key: state.control_public_key(),
+        algorithm: "ed25519".to_string(),
+    })
+}
+
+/// Public keys that verify access tokens, as a JSON Web Key Set
+///
+/// Lists the next signing key before it is used and previous keys until
+/// their tokens expire, so clients that refetch the set on an unknown `kid`
+/// keep validating tokens across rotations.
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses(
+        (status = 200, description = "Token signing public keys", body = jwt_keys::JwkSet),
+        (status = 500, description = "Authentication not configured", body = ApiError)
+    )
+)]
+async fn get_jwks(State(state): State<Arc<AppState>>) -> ApiResult<Json<jwt_keys::JwkSet>> {
+    Ok(Json(state.auth_config()?.jwks()))
+}
+
+/// Newest signed cluster snapshot for third-party verification
+///
+/// Publishes one on the spot if the periodic job has not run yet.  Check
+/// `snapshot.document` with `ambient_node::verify_signed_record` against
+/// `snapshot.public_key`, ideally one obtained out of band.
+#[utoipa::path(
+    get,
+    path = "/api/v1/transparency",
+    responses(
+        (status = 200, description = "Latest transparency snapshot", body = transparency::TransparencyResponse),
+        (status = 503, description = "Database not configured", body = ApiError)
+    )
+)]
+async fn get_transparency(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<transparency::TransparencyResponse>> {
+    let latest = state
+        .list_transparency_snapshots(&transparency::TransparencyHistoryQuery {
+            limit: Some(1),
+            ..Default::default()
+        })
+        .await?
+        .pop();
+    let snapshot = match latest {
+        Some(snapshot) => snapshot,
+        None => state.publish_transparency_snapshot().await?,
+    };
+
+    Ok(Json(transparency::TransparencyResponse {
+        public_key: state.control_public_key(),
+        algorithm: "ed25519".to_string(),
+        audience: transparency::TRANSPARENCY_AUDIENCE.to_string(),
+        snapshot,
+    }))
+}
+
+/// Component status, relay capacity, recent incidents and uptime for a
+/// public status page
+///
+/// Derived from the synthetic canaries, node availability and recent proof
+/// verification times; see `api_server::status`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/status",
+    responses(
+        (status = 200, description = "Current service status", body = status::StatusResponse),
+        (status = 503, description = "Database not configured", body = ApiError)
+    )
+)]
+async fn get_status(State(state): State<Arc<AppState>>) -> ApiResult<Json<status::StatusResponse>> {
+    Ok(Json(state.collect_status().await?))
+}
+
+/// Earlier signed cluster snapshots, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/transparency/history",
+    params(transparency::TransparencyHistoryQuery),
+    responses(
+        (status = 200, description = "Stored transparency snapshots", body = Vec<transparency::SignedTransparencySnapshot>),
+        (status = 503, description = "Database not configured", body = ApiError)
+    )
+)]
+async fn get_transparency_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<transparency::TransparencyHistoryQuery>,
+) -> ApiResult<Json<Vec<transparency::SignedTransparencySnapshot>>> {
+    Ok(Json(state.list_transparency_snapshots(&query).await?))
+}
+
 /// Register a new node
+///
+/// When the server has enrollment keys configured the node starts in
+/// `pending_attestation`, and the response's `attestation_challenge` nonce
+/// must be answered on `POST /api/v1/nodes/{node_id}/attestation` before it
+/// is assigned work.
 #[utoipa::path(
     post,
     path = "/api/v1/nodes",
@@ -133,6 +448,7 @@ async fn health_check() -> Json<HealthResponse> {
 async fn register_node(
     State(state): State<Arc<AppState>>,
     auth_user: auth::AuthUser,
+    client_ip: Option<axum::Extension<rate_limit::ClientIp>>,
     Json(registration): Json<NodeRegistration>,
 ) -> ApiResult<(StatusCode, Json<NodeInfo>)> {
     registration.validate()?;
@@ -146,7 +462,14 @@ async fn register_node(
     let user_id = Uuid::parse_str(&auth_user.user_id)
         .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
 
-    let node_info = state.register_node(registration, user_id).await?;
+    let node_info = state
+        .register_node_in_org(
+            registration,
+            user_id,
+            auth_user.org_uuid()?,
+            client_ip.map(|axum::Extension(rate_limit::ClientIp(ip))| ip),
+        )
+        .await?;
 
     Ok((StatusCode::CREATED, Json(node_info)))
 }
@@ -164,6 +487,31 @@ async fn list_nodes(State(state): State<Arc<AppState>>) -> Json<Vec<NodeInfo>> {
     Json(nodes)
 }
 
+/// Rank the nodes that can take a task type
+///
+/// Nodes are scored on their success rate and median execution time for the
+/// task type, their health and their overall reputation; each entry carries
+/// the per-component breakdown behind its score.
+#[utoipa::path(
+    get,
+    path = "/api/v1/nodes/rankings",
+    params(node_rankings::NodeRankingsQuery),
+    responses(
+        (status = 200, description = "Nodes ranked best first", body = NodeRankingsResponse),
+        (status = 400, description = "Unknown task type or invalid limit", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_node_rankings(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<node_rankings::NodeRankingsQuery>,
+) -> ApiResult<Json<node_rankings::NodeRankingsResponse>> {
+    let (entry, limit) = query.resolve()?;
+    Ok(Json(state.rank_nodes(entry, limit).await?))
+}
+
 /// Get a specific node
 #[utoipa::path(
     get,
@@ -188,6 +536,51 @@ async fn get_node(
     Ok(Json(node))
 }
 
+/// Update a node's type, capabilities and labels (owner only)
+///
+/// Omitted fields keep their current value; `labels` replaces the whole set.  Pending tasks are re-matched
+/// against the new capabilities; running assignments are unaffected.
+#[utoipa::path(
+    patch,
+    path = "/api/v1/nodes/{node_id}",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    request_body = NodeUpdateRequest,
+    responses(
+        (status = 200, description = "Node updated", body = NodeInfo),
+        (status = 400, description = "Invalid update", body = ApiError),
+        (status = 404, description = "Node not found or you don't have permission to update it", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn update_node(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+    Json(update): Json<NodeUpdateRequest>,
+) -> ApiResult<Json<NodeInfo>> {
+    update.validate()?;
+
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let node = state
+        .update_node(&node_id, user_id, &update)
+        .await?
+        .ok_or_else(|| {
+            ApiError::not_found_or_forbidden(format!(
+                "Node {} not found or you don't have permission to update it",
+                node_id
+            ))
+        })?;
+
+    info!("Updated node {} for user {}", node_id, auth_user.username);
+    Ok(Json(node))
+}
+
 /// Delete a node (soft delete)
 #[utoipa::path(
     delete,
@@ -274,13 +667,178 @@ async fn reject_node(
     })))
 }
 
+/// Drain a node (owner only)
+///
+/// The node stops receiving new tasks.  Checkpointable tasks are handed to
+/// other nodes, which resume from the last checkpoint; other tasks finish in
+/// place.
+#[utoipa::path(
+    post,
+    path = "/api/v1/nodes/{node_id}/drain",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    responses(
+        (status = 200, description = "Node draining", body = NodeDrainResponse),
+        (status = 404, description = "Node not found, not owned, or not online", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn drain_node(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+) -> ApiResult<Json<NodeDrainResponse>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let drained = state.drain_node(&node_id, user_id).await?.ok_or_else(|| {
+        ApiError::not_found_or_forbidden(format!(
+            "Node {} not found, not online, or you don't have permission to drain it",
+            node_id
+        ))
+    })?;
+
+    info!(
+        %node_id,
+        handed_off = drained.handed_off_tasks.len(),
+        "Node drain requested by {}",
+        auth_user.username
+    );
+    Ok(Json(drained))
+}
+
+/// Return a draining node to service (owner only)
+#[utoipa::path(
+    delete,
+    path = "/api/v1/nodes/{node_id}/drain",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    responses(
+        (status = 200, description = "Node back online"),
+        (status = 404, description = "Node not found, not owned, or not draining", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn undrain_node(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    if !state.undrain_node(&node_id, user_id).await? {
+        return Err(ApiError::not_found_or_forbidden(format!(
+            "Node {} not found, not draining, or you don't have permission to undrain it",
+            node_id
+        )));
+    }
+
+    Ok(Json(serde_json::json!({
+        "node_id": node_id,
+        "status": "online"
+    })))
+}
+
+/// Answer a node's attestation challenge (owner only)
+///
+/// The nonce from registration (or from `/attestation/challenge`) signed
+/// with a trusted enrollment key brings a `pending_attestation` node online.
+#[utoipa::path(
+    post,
+    path = "/api/v1/nodes/{node_id}/attestation",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    request_body = NodeAttestationRequest,
+    responses(
+        (status = 200, description = "Node attested and online", body = NodeInfo),
+        (status = 400, description = "Invalid signature or unknown/expired nonce", body = ApiError),
+        (status = 403, description = "Enrollment key is not trusted", body = ApiError),
+        (status = 404, description = "Node not found or not owned", body = ApiError),
+        (status = 409, description = "Node is not pending attestation", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn attest_node(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+    Json(attestation): Json<attestation::NodeAttestationRequest>,
+) -> ApiResult<Json<NodeInfo>> {
+    attestation.validate()?;
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let node = state
+        .attest_node(&node_id, user_id, attestation)
+        .await?
+        .ok_or_else(|| {
+            ApiError::not_found_or_forbidden(format!(
+                "Node {} not found or you don't have permission to attest it",
+                node_id
+            ))
+        })?;
+    Ok(Json(node))
+}
+
+/// Issue a fresh attestation nonce for a pending node (owner only)
+///
+/// Replaces the node's earlier nonce, e.g. after it expired.
+#[utoipa::path(
+    post,
+    path = "/api/v1/nodes/{node_id}/attestation/challenge",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    responses(
+        (status = 200, description = "New challenge", body = AttestationChallenge),
+        (status = 404, description = "Node not found or not owned", body = ApiError),
+        (status = 409, description = "Node is not pending attestation", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn renew_attestation_challenge(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+) -> ApiResult<Json<attestation::AttestationChallenge>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let challenge = state
+        .renew_attestation_challenge(&node_id, user_id)
+        .await?
+        .ok_or_else(|| {
+            ApiError::not_found_or_forbidden(format!(
+                "Node {} not found or you don't have permission to manage it",
+                node_id
+            ))
+        })?;
+    Ok(Json(challenge))
+}
+
 /// Update node heartbeat
+///
+/// The response carries a `control_signature` (see `GET /api/v1/control-key`)
+/// that nodes verify before acting on `assigned_tasks`.
 #[utoipa::path(
     put,
     path = "/api/v1/nodes/{node_id}/heartbeat",
     params(
         ("node_id" = String, Path, description = "Node ID")
     ),
+    request_body(content = Option<NodeHeartbeatRequest>, description = "Optional clock skew report and full or delta-encoded node state"),
     responses(
         (status = 200, description = "Heartbeat updated successfully"),
         (status = 404, description = "Node not found or you don't have permission to update it", body = ApiError)
@@ -293,12 +851,19 @@ async fn update_heartbeat(
     State(state): State<Arc<AppState>>,
     auth_user: auth::AuthUser,
     Path(node_id): Path<String>,
+    body: Option<Json<NodeHeartbeatRequest>>,
 ) -> ApiResult<Json<serde_json::Value>> {
+    // Heartbeats historically carried no body; treat a missing one as empty.
+    let Json(request) = body.unwrap_or_default();
+    request.validate()?;
+
     // Parse user_id
     let user_id = Uuid::parse_str(&auth_user.user_id)
         .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
 
-    let heartbeat = state.update_node_heartbeat(&node_id, user_id).await?;
+    let heartbeat = state
+        .update_node_heartbeat(&node_id, user_id, &request)
+        .await?;
 
     let Some(result) = heartbeat else {
         return Err(ApiError::not_found_or_forbidden(format!(
@@ -307,6 +872,71 @@ async fn update_heartbeat(
         )));
     };
 
+    Ok(Json(state.sign_control_response(
+        &node_id,
+        heartbeat_response(&node_id, &request, result),
+    )))
+}
+
+/// Update heartbeats for several of your nodes in one request
+///
+/// Intended for supervisors running many local nodes.  All heartbeats are
+/// recorded in one transaction, and the request fails unless the caller owns
+/// every listed node.  `results` holds one entry per heartbeat, in request
+/// order, shaped like the single-node heartbeat response and signed for
+/// that node.
+#[utoipa::path(
+    put,
+    path = "/api/v1/nodes/heartbeat/batch",
+    request_body = NodeHeartbeatBatchRequest,
+    responses(
+        (status = 200, description = "Heartbeats updated successfully"),
+        (status = 400, description = "Malformed batch", body = ApiError),
+        (status = 404, description = "A node was not found or is not owned by you", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn update_heartbeats_batch(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Json(batch): Json<NodeHeartbeatBatchRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    batch.validate()?;
+
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let results = state
+        .update_node_heartbeats_batch(user_id, &batch.heartbeats)
+        .await?;
+
+    let results: Vec<serde_json::Value> = batch
+        .heartbeats
+        .iter()
+        .zip(results)
+        .map(|(item, result)| {
+            state.sign_control_response(
+                &item.node_id,
+                heartbeat_response(&item.node_id, &item.heartbeat, result),
+            )
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "message": "Heartbeats updated successfully",
+        "count": results.len(),
+        "results": results,
+    })))
+}
+
+/// Response body for one node's heartbeat.
+pub(crate) fn heartbeat_response(
+    node_id: &str,
+    request: &NodeHeartbeatRequest,
+    result: state::NodeHeartbeatResult,
+) -> serde_json::Value {
     // Extract plain task_id list for backward-compat consumers; also expose
     // the richer objects (with task_type and execution_status) so nodes can
     // identify connect_only assignments and activate their data-plane gateway.
@@ -316,17 +946,33 @@ async fn update_heartbeat(
         .filter_map(|t| t.get("task_id").and_then(|v| v.as_str()))
         .collect();
 
-    Ok(Json(serde_json::json!({
+    let clock_skew_exceeds_tolerance = request
+        .clock_skew_ms
+        .map(|skew| skew.abs() > AppState::clock_skew_tolerance_seconds() * 1000);
+    if clock_skew_exceeds_tolerance == Some(true) {
+        warn!(
+            node_id,
+            clock_skew_ms = request.clock_skew_ms,
+            "Node clock skew exceeds tolerance"
+        );
+    }
+
+    serde_json::json!({
         "message": "Heartbeat updated successfully",
         "node_id": node_id,
         "timestamp": chrono::Utc::now().to_rfc3339(),
+        "server_time_ms": chrono::Utc::now().timestamp_millis(),
+        "clock_skew_ms": request.clock_skew_ms,
+        "clock_skew_exceeds_tolerance": clock_skew_exceeds_tolerance,
         "health_score": result.health_score,
         "node_status": result.node_status,
         "active_tasks": result.active_task_count,
         "assigned_task_ids": assigned_task_ids,
         "assigned_tasks": result.assigned_tasks,
-        "internet_active": result.internet_active
-    })))
+        "internet_active": result.internet_active,
+        "state_seq": result.state_seq,
+        "resync_required": result.resync_required
+    })
 }
 
 fn connect_only_completion_delay(inputs: &serde_json::Value) -> Duration {
@@ -367,7 +1013,9 @@ async fn submit_task(
     // Capture max_execution_time_sec before task is moved into submit_task.
     let max_execution_time_sec = task.requirements.max_execution_time_sec;
 
-    let task_info = state.submit_task(task, creator_id).await?;
+    let task_info = state
+        .submit_task_in_org(task, creator_id, auth_user.org_uuid()?)
+        .await?;
 
     if task_info.status == TaskStatus::Running {
         let state_for_completion = Arc::clone(&state);
@@ -378,7 +1026,8 @@ async fn submit_task(
             // connect_only tasks complete after their declared session duration.
             // All other task types wait up to max_execution_time_sec for a node
             // to submit real results via POST /tasks/{id}/result; only then does
-            // the fallback synthetic completion fire.
+            // the fallback synthetic completion fire.  Tasks with a retry policy
+            // are instead re-queued by the task retry sweep.
             let delay = if task_type == "connect_only" {
                 connect_only_completion_delay(&task_inputs)
             } else {
@@ -386,6 +1035,24 @@ async fn submit_task(
             };
             tokio::time::sleep(delay).await;
 
+            // An extended connect session keeps its task running until the
+            // new expiry; re-check after each wait in case it grew again.
+            if task_type == "connect_only" {
+                loop {
+                    match state_for_completion
+                        .connect_only_completion_deferral(task_id)
+                        .await
+                    {
+                        Ok(Some(remaining)) => tokio::time::sleep(remaining).await,
+                        Ok(None) => break,
+                        Err(err) => {
+                            error!(%task_id, "Failed to check connect session extension: {err}");
+                            break;
+                        }
+                    }
+                }
+            }
+
             if let Err(err) = state_for_completion
                 .complete_task_if_running(task_id, task_type, task_inputs)
                 .await
@@ -493,31 +1160,42 @@ async fn heartbeat_connect_session(
     Ok(Json(session))
 }
 
-/// Stop an active connect session.
+/// Extend an active connect session.
+///
+/// Pushes `expires_at` forward so work can continue without restarting the
+/// session.  The node's gateway session and the backing `connect_only` task
+/// follow the new expiry.
 #[utoipa::path(
     post,
-    path = "/api/v1/connect-sessions/{session_id}/stop",
+    path = "/api/v1/connect-sessions/{session_id}/extend",
     params(
         ("session_id" = String, Path, description = "Session ID")
     ),
+    request_body = ConnectSessionExtendRequest,
     responses(
-        (status = 200, description = "Connect session stopped", body = ConnectSessionInfo),
-        (status = 404, description = "Session not found or inactive", body = ApiError)
+        (status = 200, description = "Connect session extended", body = ConnectSessionInfo),
+        (status = 400, description = "Invalid extension", body = ApiError),
+        (status = 403, description = "Extension limit or maximum lifetime reached", body = ApiError),
+        (status = 404, description = "Session not found or inactive", body = ApiError),
+        (status = 409, description = "Session expired or its task is no longer running", body = ApiError)
     ),
     security(
         ("bearer_auth" = [])
     )
 )]
-async fn stop_connect_session(
+async fn extend_connect_session(
     State(state): State<Arc<AppState>>,
     auth_user: auth::AuthUser,
     Path(session_id): Path<String>,
+    Json(request): Json<ConnectSessionExtendRequest>,
 ) -> ApiResult<Json<ConnectSessionInfo>> {
+    request.validate()?;
+
     let requester_id = Uuid::parse_str(&auth_user.user_id)
         .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
 
     let Some(session) = state
-        .stop_connect_session(&session_id, requester_id)
+        .extend_connect_session(&session_id, requester_id, request.extend_seconds)
         .await?
     else {
         return Err(ApiError::not_found_or_forbidden(
@@ -528,19 +1206,99 @@ async fn stop_connect_session(
     Ok(Json(session))
 }
 
-/// Get task information
+/// Report bytes relayed for a connect session (node owner)
+///
+/// The gateway relaying the session reports each interval's traffic.  Usage
+/// accumulates on the session; an active session that reaches its data cap
+/// is ended, and the node drops it from its gateway session list.  Reports
+/// whose `seq` was already accepted are ignored.
 #[utoipa::path(
-    get,
-    path = "/api/v1/tasks/{task_id}",
+    post,
+    path = "/api/v1/connect-sessions/{session_id}/usage",
     params(
-        ("task_id" = String, Path, description = "Task ID")
+        ("session_id" = String, Path, description = "Session ID")
     ),
+    request_body = ConnectSessionUsageReport,
     responses(
-        (status = 200, description = "Task information", body = TaskInfo),
-        (status = 404, description = "Task not found", body = ApiError)
+        (status = 200, description = "Usage recorded", body = ConnectSessionInfo),
+        (status = 400, description = "Invalid report", body = ApiError),
+        (status = 404, description = "Session not relayed by a node you own", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-async fn get_task(
+async fn report_connect_session_usage(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(session_id): Path<String>,
+    Json(report): Json<ConnectSessionUsageReport>,
+) -> ApiResult<Json<ConnectSessionInfo>> {
+    report.validate()?;
+
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let Some(session) = state
+        .report_connect_session_usage(&session_id, owner_id, &report)
+        .await?
+    else {
+        return Err(ApiError::not_found_or_forbidden(
+            "Session not relayed by a node you own",
+        ));
+    };
+
+    Ok(Json(session))
+}
+
+/// Stop an active connect session.
+#[utoipa::path(
+    post,
+    path = "/api/v1/connect-sessions/{session_id}/stop",
+    params(
+        ("session_id" = String, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 200, description = "Connect session stopped", body = ConnectSessionInfo),
+        (status = 404, description = "Session not found or inactive", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn stop_connect_session(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<ConnectSessionInfo>> {
+    let requester_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let Some(session) = state
+        .stop_connect_session(&session_id, requester_id)
+        .await?
+    else {
+        return Err(ApiError::not_found_or_forbidden(
+            "Connect session not found or inactive",
+        ));
+    };
+
+    Ok(Json(session))
+}
+
+/// Get task information
+#[utoipa::path(
+    get,
+    path = "/api/v1/tasks/{task_id}",
+    params(
+        ("task_id" = String, Path, description = "Task ID")
+    ),
+    responses(
+        (status = 200, description = "Task information", body = TaskInfo),
+        (status = 404, description = "Task not found", body = ApiError)
+    )
+)]
+async fn get_task(
     State(state): State<Arc<AppState>>,
     auth_user: auth::AuthUser,
     Path(task_id): Path<String>,
@@ -571,11 +1329,47 @@ async fn list_tasks(
 ) -> ApiResult<Json<Vec<TaskInfo>>> {
     let requester_id = Uuid::parse_str(&auth_user.user_id)
         .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
-    let tasks = state.list_tasks(requester_id).await;
+    let tasks = state
+        .list_tasks_in_org(requester_id, auth_user.org_uuid()?)
+        .await;
     Ok(Json(tasks))
 }
 
+/// Search tasks by content
+///
+/// Matches `q` against each visible task's type, inputs and result as a
+/// full-text query and as a substring, and against task and connect
+/// session IDs.  Results are ordered by relevance, then recency.
+#[utoipa::path(
+    get,
+    path = "/api/v1/tasks/search",
+    params(task_search::TaskSearchQuery),
+    responses(
+        (status = 200, description = "Matching tasks, most relevant first", body = [TaskSearchHit]),
+        (status = 400, description = "Empty or oversized query, or invalid limit", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn search_tasks(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Query(query): Query<task_search::TaskSearchQuery>,
+) -> ApiResult<Json<Vec<task_search::TaskSearchHit>>> {
+    let requester_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let (q, limit) = query.resolve()?;
+    let hits = state
+        .search_tasks(requester_id, auth_user.org_uuid()?, q, limit)
+        .await?;
+    Ok(Json(hits))
+}
+
 /// Delete a task
+///
+/// The task is soft-deleted: it disappears from task reads and its nodes
+/// are freed, but it can be restored until the retention job purges it.
 #[utoipa::path(
     delete,
     path = "/api/v1/tasks/{task_id}",
@@ -613,419 +1407,2057 @@ async fn delete_task(
     })))
 }
 
-/// Submit a task result from a node
+/// Restore a deleted task
 ///
-/// Called by a node owner after the node has completed its portion of a task.
-/// If the task was submitted with `require_proof = true`, the request must
-/// include `proof_data` and `public_inputs` (Base64-encoded) and the proof
-/// will be verified before the result is accepted.
+/// Brings back a task deleted with `DELETE /api/v1/tasks/{task_id}` before
+/// the retention job purged it.  A task deleted before it finished is queued
+/// and scheduled again.
 #[utoipa::path(
     post,
-    path = "/api/v1/tasks/{task_id}/result",
+    path = "/api/v1/tasks/{task_id}/restore",
     params(
         ("task_id" = String, Path, description = "Task ID")
     ),
-    request_body = NodeTaskResult,
     responses(
-        (status = 200, description = "Task result accepted and task marked completed"),
-        (status = 400, description = "Invalid request or proof verification failed", body = ApiError),
-        (status = 404, description = "Task or node not found", body = ApiError)
+        (status = 200, description = "Restored task", body = TaskInfo),
+        (status = 404, description = "No deleted task with this ID", body = ApiError)
     ),
     security(
         ("bearer_auth" = [])
     )
 )]
-async fn submit_task_result(
+async fn restore_task(
     State(state): State<Arc<AppState>>,
     auth_user: auth::AuthUser,
     Path(task_id): Path<String>,
-    Json(submission): Json<models::NodeTaskResult>,
-) -> ApiResult<Json<serde_json::Value>> {
-    submission.validate()?;
-
-    let owner_id = Uuid::parse_str(&auth_user.user_id)
+) -> ApiResult<Json<TaskInfo>> {
+    let requester_id = Uuid::parse_str(&auth_user.user_id)
         .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
-    let task_uuid = Uuid::parse_str(&task_id)
-        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
-
-    info!(
-        "Node {} submitting result for task {} (user {})",
-        submission.node_id, task_id, auth_user.username
-    );
 
-    let result = state
-        .submit_task_result(task_uuid, submission, owner_id)
-        .await?;
+    if !state.restore_task(&task_id, requester_id).await? {
+        return Err(ApiError::not_found_or_forbidden(format!(
+            "Deleted task {} not found",
+            task_id
+        )));
+    }
 
-    Ok(Json(result))
+    state
+        .get_task(&task_id, requester_id)
+        .await
+        .map(Json)
+        .ok_or_else(|| ApiError::internal_error("Restored task could not be loaded"))
 }
 
+/// List the nodes a task's secrets must be sealed to
+///
+/// Returns each actively assigned node with its `secrets_public_key`.  Only
+/// the task creator may call this.
 #[utoipa::path(
     get,
-    path = "/api/v1/nodes/{node_id}/heartbeat/activity",
+    path = "/api/v1/tasks/{task_id}/secrets/recipients",
     params(
-        ("node_id" = String, Path, description = "Node ID")
+        ("task_id" = String, Path, description = "Task ID")
     ),
     responses(
-        (status = 200, description = "Task activity events returned successfully"),
-        (status = 404, description = "Node not found or you don't have permission", body = ApiError)
+        (status = 200, description = "Assigned nodes and their secrets keys", body = Vec<TaskSecretRecipient>),
+        (status = 404, description = "Task not found or not owned by you", body = ApiError)
     ),
     security(
         ("bearer_auth" = [])
     )
 )]
-async fn get_node_heartbeat_activity(
+async fn list_task_secret_recipients(
     State(state): State<Arc<AppState>>,
     auth_user: auth::AuthUser,
-    Path(node_id): Path<String>,
-) -> ApiResult<Json<serde_json::Value>> {
-    let owner_id = Uuid::parse_str(&auth_user.user_id)
+    Path(task_id): Path<String>,
+) -> ApiResult<Json<Vec<TaskSecretRecipient>>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
         .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
 
-    let events = state
-        .get_node_cleared_task_events(&node_id, owner_id)
-        .await?;
-
-    Ok(Json(serde_json::json!({ "events": events })))
+    Ok(Json(
+        state
+            .list_task_secret_recipients(task_uuid, user_id)
+            .await?,
+    ))
 }
 
-/// Get active gateway sessions for a node (node-owner only)
+/// Attach sealed secrets to a task
 ///
-/// Returns the list of `connect_only` relay sessions currently assigned to this
-/// node including the cleartext `session_token` needed by `DataPlaneGateway` to
-/// validate incoming relay connections.  The node should poll this endpoint
-/// (e.g. every heartbeat cycle) and call `gateway.add_session()` for new
-/// entries and `gateway.revoke_session()` for entries that disappear.
+/// Replaces any secrets previously attached.  Each secret is sealed by the
+/// requester to one assigned node's key; the server cannot decrypt it.
 #[utoipa::path(
-    get,
-    path = "/api/v1/nodes/{node_id}/gateway-sessions",
+    put,
+    path = "/api/v1/tasks/{task_id}/secrets",
     params(
-        ("node_id" = String, Path, description = "Node ID")
+        ("task_id" = String, Path, description = "Task ID")
     ),
+    request_body = TaskSecretsUpload,
     responses(
-        (status = 200, description = "Active gateway sessions returned"),
-        (status = 404, description = "Node not found or you don't have permission", body = ApiError)
+        (status = 200, description = "Sealed secrets stored"),
+        (status = 400, description = "Malformed secret or node not eligible", body = ApiError),
+        (status = 404, description = "Task not found or not owned by you", body = ApiError)
     ),
     security(
         ("bearer_auth" = [])
     )
 )]
-async fn get_node_gateway_sessions(
+async fn put_task_secrets(
     State(state): State<Arc<AppState>>,
     auth_user: auth::AuthUser,
-    Path(node_id): Path<String>,
+    Path(task_id): Path<String>,
+    Json(upload): Json<TaskSecretsUpload>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    let owner_id = Uuid::parse_str(&auth_user.user_id)
+    upload.validate()?;
+
+    let user_id = Uuid::parse_str(&auth_user.user_id)
         .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
 
-    let sessions = state.get_node_gateway_sessions(&node_id, owner_id).await?;
+    let stored = state.put_task_secrets(task_uuid, upload, user_id).await?;
+    info!(%task_id, stored, "Sealed task secrets attached");
 
-    Ok(Json(serde_json::json!({ "sessions": sessions })))
+    Ok(Json(serde_json::json!({
+        "task_id": task_id,
+        "secrets_stored": stored,
+    })))
 }
 
+/// Fetch the secrets sealed to one of your nodes for a task
+///
+/// The node must be actively assigned to the task.  Secrets are deleted once
+/// the node's attempt completes or fails.
 #[utoipa::path(
-    post,
-    path = "/api/v1/proofs/verify",
-    request_body = ProofVerificationRequest,
+    get,
+    path = "/api/v1/tasks/{task_id}/secrets/{node_id}",
+    params(
+        ("task_id" = String, Path, description = "Task ID"),
+        ("node_id" = String, Path, description = "Assigned node ID")
+    ),
     responses(
-        (status = 200, description = "Proof verification result", body = ProofVerificationResponse),
-        (status = 400, description = "Invalid request", body = ApiError)
+        (status = 200, description = "Sealed secrets for the node", body = Vec<SealedTaskSecret>),
+        (status = 404, description = "Node not found, not owned, or not assigned", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-async fn verify_proof(
+async fn get_task_secrets_for_node(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<ProofVerificationRequest>,
-) -> ApiResult<Json<ProofVerificationResponse>> {
-    // Validate request first
-    request.validate()?;
-
-    info!(
-        "Verifying proof for task: {} (proof size: {} bytes)",
-        request.task_id,
-        request.proof_data.len()
-    );
-
-    let response = state.verify_proof(request).await?;
-
-    if response.valid {
-        info!(
-            "Proof verified successfully for task: {} in {}ms",
-            response.task_id, response.verification_time_ms
-        );
-    } else {
-        info!(
-            "Proof verification failed for task: {} - {}",
-            response.task_id,
-            response.error_message.as_deref().unwrap_or("unknown error")
-        );
-    }
+    auth_user: auth::AuthUser,
+    Path((task_id, node_id)): Path<(String, String)>,
+) -> ApiResult<Json<Vec<SealedTaskSecret>>> {
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
 
-    Ok(Json(response))
+    Ok(Json(
+        state
+            .get_task_secrets_for_node(task_uuid, &node_id, owner_id)
+            .await?,
+    ))
 }
 
-/// Get cluster statistics
+/// Provenance bundle for a task
+///
+/// Includes the signed sandbox report of every node that submitted a result,
+/// verified against the node's registered signing key on receipt.
 #[utoipa::path(
     get,
-    path = "/api/v1/cluster/stats",
+    path = "/api/v1/tasks/{task_id}/provenance",
+    params(
+        ("task_id" = String, Path, description = "Task ID")
+    ),
     responses(
-        (status = 200, description = "Cluster statistics", body = ClusterStats)
+        (status = 200, description = "Task provenance", body = TaskProvenance),
+        (status = 404, description = "Task not found or not visible to you", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-async fn get_cluster_stats(State(state): State<Arc<AppState>>) -> Json<ClusterStats> {
-    let stats = state.get_cluster_stats().await;
-    Json(stats)
+async fn get_task_provenance(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(task_id): Path<String>,
+) -> ApiResult<Json<TaskProvenance>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
+
+    state
+        .get_task_provenance(task_uuid, user_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found_or_forbidden(format!("Task {} not found", task_id)))
 }
 
-/// Register a new user
+/// Lifecycle timeline for a task
+///
+/// Orders submission, each assignment, execution start, checkpoints, result
+/// submissions, proof verification, the final outcome and queued
+/// notifications by time.
 #[utoipa::path(
-    post,
-    path = "/api/v1/auth/register",
-    request_body = RegisterRequest,
+    get,
+    path = "/api/v1/tasks/{task_id}/timeline",
+    params(
+        ("task_id" = String, Path, description = "Task ID")
+    ),
     responses(
-        (status = 201, description = "User registered successfully"),
-        (status = 400, description = "Invalid request", body = ApiError)
+        (status = 200, description = "Task timeline", body = TaskTimeline),
+        (status = 404, description = "Task not found or not visible to you", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-async fn register_user(
+async fn get_task_timeline(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<auth::RegisterRequest>,
-) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
-    let Some(db) = &state.db else {
-        return Err(ApiError::service_unavailable("Database not configured"));
-    };
+    auth_user: auth::AuthUser,
+    Path(task_id): Path<String>,
+) -> ApiResult<Json<TaskTimeline>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
 
-    request.validate()?;
+    state
+        .get_task_timeline(task_uuid, user_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found_or_forbidden(format!("Task {} not found", task_id)))
+}
 
-    info!("Registering user: {}", request.username);
+/// Upload a state checkpoint for a checkpointable task
+///
+/// The request body is the raw snapshot written by the module through the
+/// `checkpoint_write` host function, and `X-Checkpoint-Seq` carries its
+/// sequence number.  Only snapshots newer than the stored one are accepted.
+#[utoipa::path(
+    put,
+    path = "/api/v1/tasks/{task_id}/checkpoint/{node_id}",
+    params(
+        ("task_id" = String, Path, description = "Task ID"),
+        ("node_id" = String, Path, description = "Assigned node ID"),
+        ("X-Checkpoint-Seq" = u64, Header, description = "Checkpoint sequence number")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 201, description = "Checkpoint stored", body = TaskCheckpointInfo),
+        (status = 400, description = "Task not checkpointable, or empty or oversized body", body = ApiError),
+        (status = 404, description = "Node not found, not owned, or not assigned", body = ApiError),
+        (status = 409, description = "Task not running, or sequence not newer than stored", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn put_task_checkpoint(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path((task_id, node_id)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<TaskCheckpointInfo>)> {
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
+    let seq = headers
+        .get("x-checkpoint-seq")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .ok_or_else(|| ApiError::bad_request("X-Checkpoint-Seq header must be an integer"))?;
+
+    let checkpoint = state
+        .store_task_checkpoint(task_uuid, &node_id, seq, &body, owner_id)
+        .await?;
+    info!(%task_id, %node_id, seq, size_bytes = checkpoint.size_bytes, "Task checkpoint stored");
+
+    Ok((StatusCode::CREATED, Json(checkpoint)))
+}
+
+/// Download the latest checkpoint of a task to resume it
+///
+/// The node must be actively assigned to the task.  It should verify the
+/// `X-Checkpoint-Sha3-256` header against the body before handing the
+/// snapshot to the module through `checkpoint_read`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/tasks/{task_id}/checkpoint/{node_id}",
+    params(
+        ("task_id" = String, Path, description = "Task ID"),
+        ("node_id" = String, Path, description = "Assigned node ID")
+    ),
+    responses(
+        (status = 200, description = "Checkpoint bytes", content_type = "application/octet-stream"),
+        (status = 204, description = "No checkpoint yet; start from scratch"),
+        (status = 404, description = "Node not found, not owned, or not assigned", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_task_checkpoint(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path((task_id, node_id)): Path<(String, String)>,
+) -> ApiResult<axum::response::Response> {
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
+
+    let Some((checkpoint, bytes)) = state
+        .get_task_checkpoint(task_uuid, &node_id, owner_id)
+        .await?
+    else {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (
+                header::HeaderName::from_static("x-checkpoint-seq"),
+                checkpoint.seq.to_string(),
+            ),
+            (
+                header::HeaderName::from_static("x-checkpoint-sha3-256"),
+                checkpoint.sha3_256,
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// Report execution log lines from a node
+///
+/// The node must be actively assigned to the task.  Lines beyond the
+/// per-task cap are dropped and counted in `dropped`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/tasks/{task_id}/logs",
+    params(
+        ("task_id" = String, Path, description = "Task ID")
+    ),
+    request_body = TaskLogBatch,
+    responses(
+        (status = 200, description = "Log lines stored"),
+        (status = 400, description = "Malformed log batch", body = ApiError),
+        (status = 404, description = "Node not found, not owned, or not assigned", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn append_task_logs(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(task_id): Path<String>,
+    Json(batch): Json<TaskLogBatch>,
+) -> ApiResult<Json<serde_json::Value>> {
+    batch.validate()?;
+
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
+
+    let submitted = batch.lines.len();
+    let accepted = state.append_task_logs(task_uuid, batch, owner_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "task_id": task_id,
+        "accepted": accepted,
+        "dropped": submitted - accepted,
+    })))
+}
+
+/// Most log lines read per stream poll.
+const TASK_LOG_STREAM_PAGE: i64 = 500;
+
+/// Stream a task's execution logs as Server-Sent Events
+///
+/// Each line is sent as a `log` event (JSON `TaskLogEntry`) whose id is its
+/// `log_id`, so reconnecting clients resume with `Last-Event-ID`.  Once the
+/// task has completed or failed and every line has been sent, an `end` event
+/// closes the stream.
+#[utoipa::path(
+    get,
+    path = "/api/v1/tasks/{task_id}/logs/stream",
+    params(
+        ("task_id" = String, Path, description = "Task ID"),
+        ("Last-Event-ID" = Option<i64>, Header, description = "Resume after this log id")
+    ),
+    responses(
+        (status = 200, description = "Event stream of `log` events followed by `end`", content_type = "text/event-stream"),
+        (status = 404, description = "Task not found or not owned by you", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn stream_task_logs(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(task_id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> ApiResult<Sse<impl futures::Stream<Item = Result<Event, std::convert::Infallible>>>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
+    let after = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .unwrap_or(0);
+
+    // Authorize before switching to the event stream so errors keep their
+    // HTTP status codes.
+    state.list_task_logs(task_uuid, user_id, after, 0).await?;
+    let poll_interval = Duration::from_millis(AppState::task_log_poll_interval_ms());
+
+    let stream = futures::stream::unfold(
+        Some((state, after, Duration::ZERO)),
+        move |cursor| async move {
+            let (state, mut after, delay) = cursor?;
+            tokio::time::sleep(delay).await;
+
+            let (entries, finished) = match state
+                .list_task_logs(task_uuid, user_id, after, TASK_LOG_STREAM_PAGE)
+                .await
+            {
+                Ok(page) => page,
+                Err(err) => {
+                    return Some((
+                        vec![Event::default().event("error").data(err.message)],
+                        None,
+                    ))
+                }
+            };
+
+            // A full page means more lines are waiting; fetch them without sleeping.
+            let more_pending = entries.len() as i64 == TASK_LOG_STREAM_PAGE;
+            let mut events: Vec<Event> = entries
+                .iter()
+                .filter_map(|entry| {
+                    after = entry.log_id;
+                    Event::default()
+                        .id(entry.log_id.to_string())
+                        .event("log")
+                        .json_data(entry)
+                        .ok()
+                })
+                .collect();
+
+            if finished && !more_pending {
+                events.push(Event::default().event("end").data("task finished"));
+                return Some((events, None));
+            }
+
+            let delay = if more_pending {
+                Duration::ZERO
+            } else {
+                poll_interval
+            };
+            Some((events, Some((state, after, delay))))
+        },
+    )
+    .flat_map(|events| futures::stream::iter(events.into_iter().map(Ok)));
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Submit a task result from a node
+///
+/// Called by a node owner after the node has completed its portion of a task.
+/// If the task was submitted with `require_proof = true`, the request must
+/// include `proof_data` and `public_inputs` (Base64-encoded) and the proof
+/// will be verified before the result is accepted.
+#[utoipa::path(
+    post,
+    path = "/api/v1/tasks/{task_id}/result",
+    params(
+        ("task_id" = String, Path, description = "Task ID")
+    ),
+    request_body = NodeTaskResult,
+    responses(
+        (status = 200, description = "Task result accepted and task marked completed"),
+        (status = 400, description = "Invalid request or proof verification failed", body = ApiError),
+        (status = 404, description = "Task or node not found", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn submit_task_result(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(task_id): Path<String>,
+    Json(submission): Json<models::NodeTaskResult>,
+) -> ApiResult<Json<serde_json::Value>> {
+    submission.validate()?;
+
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let task_uuid = Uuid::parse_str(&task_id)
+        .map_err(|_| ApiError::bad_request("task_id must be a valid UUID"))?;
+
+    info!(
+        "Node {} submitting result for task {} (user {})",
+        submission.node_id, task_id, auth_user.username
+    );
+
+    let result = state
+        .submit_task_result(task_uuid, submission, owner_id)
+        .await?;
+
+    Ok(Json(result))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/nodes/{node_id}/heartbeat/activity",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    responses(
+        (status = 200, description = "Task activity events returned successfully"),
+        (status = 404, description = "Node not found or you don't have permission", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_node_heartbeat_activity(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let events = state
+        .get_node_cleared_task_events(&node_id, owner_id)
+        .await?;
+
+    Ok(Json(serde_json::json!({ "events": events })))
+}
+
+/// Downsampled health metrics of a node for dashboard graphs
+///
+/// Points are `resolution`-wide buckets (or single heartbeat samples at
+/// `raw`) with the average and worst value of each metric.
+#[utoipa::path(
+    get,
+    path = "/api/v1/nodes/{node_id}/telemetry",
+    params(
+        ("node_id" = String, Path, description = "Node ID"),
+        telemetry::TelemetryQuery
+    ),
+    responses(
+        (status = 200, description = "Telemetry series returned", body = NodeTelemetryResponse),
+        (status = 400, description = "Invalid range or resolution", body = ApiError),
+        (status = 404, description = "Node not found or you don't have permission", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_node_telemetry(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+    Query(query): Query<telemetry::TelemetryQuery>,
+) -> ApiResult<Json<telemetry::NodeTelemetryResponse>> {
+    let window = query.resolve(chrono::Utc::now())?;
+    let requester_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    Ok(Json(
+        state
+            .get_node_telemetry(&node_id, requester_id, window)
+            .await?,
+    ))
+}
+
+/// Get active gateway sessions for a node (node-owner only)
+///
+/// Returns the list of `connect_only` relay sessions currently assigned to this
+/// node including the cleartext `session_token` needed by `DataPlaneGateway` to
+/// validate incoming relay connections.  The node should poll this endpoint
+/// (e.g. every heartbeat cycle) and call `gateway.add_session()` for new
+/// entries and `gateway.revoke_session()` for entries that disappear, after
+/// checking the response's `control_signature`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/nodes/{node_id}/gateway-sessions",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    responses(
+        (status = 200, description = "Active gateway sessions returned"),
+        (status = 404, description = "Node not found or you don't have permission", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_node_gateway_sessions(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let sessions = state.get_node_gateway_sessions(&node_id, owner_id).await?;
+
+    Ok(Json(state.sign_control_response(
+        &node_id,
+        serde_json::json!({ "sessions": sessions }),
+    )))
+}
+
+/// Every destination policy as one bundle signed with the control key
+///
+/// Relay nodes cache the bundle with `ambient_node::PolicyBundleCache` and
+/// check `allowlist_domains` sessions against it while the control plane is
+/// unreachable.  The signature's audience is `destination-policies`; the
+/// bundle's `version` increases with every policy change and its
+/// `offline_policy` says how long a cached copy stays valid.
+#[utoipa::path(
+    get,
+    path = "/api/v1/destination-policies/bundle",
+    responses(
+        (status = 200, description = "Signed destination-policy bundle")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_destination_policy_bundle(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    Ok(Json(state.signed_destination_policy_bundle().await?))
+}
+
+/// Report metered energy for a gateway session relayed by this node (node-owner only)
+#[utoipa::path(
+    post,
+    path = "/api/v1/nodes/{node_id}/gateway-sessions/{session_id}/usage",
+    params(
+        ("node_id" = String, Path, description = "Node ID"),
+        ("session_id" = String, Path, description = "Session ID")
+    ),
+    request_body = GatewaySessionUsageReport,
+    responses(
+        (status = 200, description = "Session usage recorded"),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Session not found on a node you own", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn report_gateway_session_usage(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path((node_id, session_id)): Path<(String, String)>,
+    Json(report): Json<GatewaySessionUsageReport>,
+) -> ApiResult<Json<serde_json::Value>> {
+    report.validate()?;
+
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let energy_wh = report.energy_wh;
+
+    let recorded = state
+        .report_gateway_session_usage(&node_id, &session_id, owner_id, report)
+        .await?;
+
+    if !recorded {
+        return Err(ApiError::not_found_or_forbidden(
+            "Session not found on a node you own",
+        ));
+    }
+
+    Ok(Json(serde_json::json!({
+        "session_id": session_id,
+        "node_id": node_id,
+        "energy_wh": energy_wh
+    })))
+}
+
+/// Who a node should relay through right now (node-owner only)
+///
+/// Resolved over the online `universal`/`open` nodes with the load they
+/// reported and the relays this node invalidated; see `peer_routes`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/nodes/{node_id}/route",
+    params(
+        ("node_id" = String, Path, description = "Node ID"),
+        peer_routes::PeerRouteQuery
+    ),
+    responses(
+        (status = 200, description = "Current peer route; `route` is null without a path", body = PeerRouteResponse),
+        (status = 404, description = "Node not found or you don't have permission", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_node_peer_route(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+    Query(query): Query<peer_routes::PeerRouteQuery>,
+) -> ApiResult<Json<peer_routes::PeerRouteResponse>> {
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    Ok(Json(
+        state
+            .find_peer_route(&node_id, owner_id, query.has_internet)
+            .await?,
+    ))
+}
+
+/// Stop routing a node through a relay that failed it (node-owner only)
+///
+/// The relay is skipped for this node until the invalidation expires; the
+/// response is the route to use instead.
+#[utoipa::path(
+    post,
+    path = "/api/v1/nodes/{node_id}/route/invalidate",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    request_body = PeerRouteInvalidation,
+    responses(
+        (status = 200, description = "Relay invalidated; new route returned", body = PeerRouteResponse),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Node or relay not found", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn invalidate_node_peer_route(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+    Json(invalidation): Json<peer_routes::PeerRouteInvalidation>,
+) -> ApiResult<Json<peer_routes::PeerRouteResponse>> {
+    invalidation.validate()?;
+
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    Ok(Json(
+        state
+            .invalidate_peer_route(&node_id, owner_id, &invalidation)
+            .await?,
+    ))
+}
+
+/// Report a relay's own load for route scoring (node-owner only)
+#[utoipa::path(
+    put,
+    path = "/api/v1/nodes/{node_id}/relay-load",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    request_body = RelayLoadReport,
+    responses(
+        (status = 200, description = "Relay load recorded"),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Node not found or you don't have permission", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn report_relay_load(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+    Json(report): Json<peer_routes::RelayLoadReport>,
+) -> ApiResult<Json<serde_json::Value>> {
+    report.validate()?;
+
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    if !state.report_relay_load(&node_id, owner_id, &report).await? {
+        return Err(ApiError::not_found_or_forbidden(
+            "Node not found or not owned by you",
+        ));
+    }
+
+    Ok(Json(serde_json::json!({
+        "node_id": node_id,
+        "utilization": report.utilization,
+        "saturated": report.saturated
+    })))
+}
+
+/// Announce that a relay is shutting down (node-owner only)
+///
+/// Drains the node, re-routes peers relaying through it and moves its
+/// connect sessions to other relays.  The relay keeps serving its sessions
+/// until the drain deadline.
+#[utoipa::path(
+    post,
+    path = "/api/v1/nodes/{node_id}/relay-drain",
+    params(
+        ("node_id" = String, Path, description = "Node ID")
+    ),
+    request_body = RelayDrainAnnouncement,
+    responses(
+        (status = 200, description = "Relay draining", body = RelayDrainResponse),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Node not found, not owned by you, or not online", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn announce_relay_drain(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(node_id): Path<String>,
+    Json(announcement): Json<peer_routes::RelayDrainAnnouncement>,
+) -> ApiResult<Json<peer_routes::RelayDrainResponse>> {
+    announcement.validate()?;
+
+    let owner_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    state
+        .announce_relay_drain(&node_id, owner_id, &announcement)
+        .await?
+        .map(Json)
+        .ok_or_else(|| {
+            ApiError::not_found_or_forbidden("Node not found, not owned by you, or not online")
+        })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/proofs/verify",
+    request_body = ProofVerificationRequest,
+    responses(
+        (status = 200, description = "Proof verification result", body = ProofVerificationResponse),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Task not found or not yours", body = ApiError),
+        (status = 429, description = "Verification quota exceeded", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn verify_proof(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Json(request): Json<ProofVerificationRequest>,
+) -> ApiResult<Json<ProofVerificationResponse>> {
+    // Validate request first
+    request.validate()?;
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    info!(
+        "Verifying proof for task: {} (proof size: {} bytes)",
+        request.task_id,
+        request.proof_data.len()
+    );
+
+    let response = state.verify_proof(request, user_id).await?;
+
+    if response.valid {
+        info!(
+            "Proof verified successfully for task: {} in {}ms",
+            response.task_id, response.verification_time_ms
+        );
+    } else {
+        info!(
+            "Proof verification failed for task: {} - {}",
+            response.task_id,
+            response.error_message.as_deref().unwrap_or("unknown error")
+        );
+    }
+
+    Ok(Json(response))
+}
+
+/// Queue proofs for background verification
+///
+/// Verifies up to 100 proofs, for any task, against the caller's quota.
+/// Poll `GET /api/v1/proofs/batches/{batch_id}` for results.
+#[utoipa::path(
+    post,
+    path = "/api/v1/proofs/batches",
+    request_body = proof_batches::ProofBatchRequest,
+    responses(
+        (status = 202, description = "Batch queued", body = proof_batches::ProofBatch),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 409, description = "Too many batches running", body = ApiError),
+        (status = 429, description = "Verification quota exceeded", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn create_proof_batch(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Json(request): Json<proof_batches::ProofBatchRequest>,
+) -> ApiResult<(StatusCode, Json<proof_batches::ProofBatch>)> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let batch = state.create_proof_batch(request, user_id).await?;
+    let batch_id = Uuid::parse_str(&batch.batch_id)
+        .map_err(|_| ApiError::internal_error("Invalid batch ID format"))?;
+    let job_state = Arc::clone(&state);
+    tokio::spawn(async move { job_state.run_proof_batch(batch_id).await });
+    Ok((StatusCode::ACCEPTED, Json(batch)))
+}
+
+/// Proof batch progress and results
+#[utoipa::path(
+    get,
+    path = "/api/v1/proofs/batches/{batch_id}",
+    params(
+        ("batch_id" = String, Path, description = "Batch ID")
+    ),
+    responses(
+        (status = 200, description = "Batch and results so far", body = proof_batches::ProofBatch),
+        (status = 404, description = "Batch not found or not yours", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_proof_batch(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(batch_id): Path<String>,
+) -> ApiResult<Json<proof_batches::ProofBatch>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let batch_uuid = Uuid::parse_str(&batch_id)
+        .map_err(|_| ApiError::bad_request("batch_id must be a valid UUID"))?;
+
+    state
+        .get_proof_batch(batch_uuid, user_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| {
+            ApiError::not_found_or_forbidden(format!("Proof batch {} not found", batch_id))
+        })
+}
+
+/// Caller's proof verification quota
+#[utoipa::path(
+    get,
+    path = "/api/v1/proofs/quota",
+    responses(
+        (status = 200, description = "Verifications used and remaining", body = proof_batches::ProofQuota)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_proof_quota(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+) -> ApiResult<Json<proof_batches::ProofQuota>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    Ok(Json(state.proof_quota(user_id).await?))
+}
+
+/// Stored proof
+///
+/// Visible to the user who submitted it and to anyone who can see the task it
+/// belongs to.
+#[utoipa::path(
+    get,
+    path = "/api/v1/proofs/{proof_id}",
+    params(
+        ("proof_id" = String, Path, description = "Proof ID")
+    ),
+    responses(
+        (status = 200, description = "Proof and its verification outcome", body = ProofRecord),
+        (status = 404, description = "Proof not found or not visible to you", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_proof(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(proof_id): Path<String>,
+) -> ApiResult<Json<ProofRecord>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let proof_uuid = Uuid::parse_str(&proof_id)
+        .map_err(|_| ApiError::bad_request("proof_id must be a valid UUID"))?;
+
+    state
+        .get_proof(proof_uuid, user_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found_or_forbidden(format!("Proof {} not found", proof_id)))
+}
+
+/// Get cluster statistics
+#[utoipa::path(
+    get,
+    path = "/api/v1/cluster/stats",
+    responses(
+        (status = 200, description = "Cluster statistics", body = ClusterStats)
+    )
+)]
+async fn get_cluster_stats(State(state): State<Arc<AppState>>) -> Json<ClusterStats> {
+    let stats = state.get_cluster_stats().await;
+    Json(stats)
+}
+
+/// Cluster statistics over time for dashboard graphs
+///
+/// Points are `resolution`-wide buckets (or single snapshots at `raw`) of
+/// the periodic cluster stats snapshots; `trend` is the change from the
+/// first to the last point.
+#[utoipa::path(
+    get,
+    path = "/api/v1/cluster/stats/history",
+    params(cluster_history::ClusterStatsHistoryQuery),
+    responses(
+        (status = 200, description = "Cluster stats series returned", body = ClusterStatsHistoryResponse),
+        (status = 400, description = "Invalid range or resolution", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_cluster_stats_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<cluster_history::ClusterStatsHistoryQuery>,
+) -> ApiResult<Json<cluster_history::ClusterStatsHistoryResponse>> {
+    let window = query.resolve(chrono::Utc::now())?;
+    Ok(Json(state.get_cluster_stats_history(window).await?))
+}
+
+/// Resources nodes reported per task type
+///
+/// Totals and per-attempt averages of execution time, CPU time, peak
+/// sandbox memory and energy, for estimating what a task will cost.
+#[utoipa::path(
+    get,
+    path = "/api/v1/cluster/task-stats",
+    responses(
+        (status = 200, description = "Statistics for every task type with a reported attempt", body = [TaskTypeStats])
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_task_type_stats(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<Vec<task_stats::TaskTypeStats>>> {
+    Ok(Json(state.list_task_type_stats().await?))
+}
+
+/// Query nodes, tasks, connect sessions and cluster stats over GraphQL
+///
+/// Each field requires the scope of the matching `GET` route and returns what that route would.  The
+/// response is a standard GraphQL response: fields that fail are `null` and listed in `errors`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/graphql",
+    request_body = graphql::GraphQlRequest,
+    responses(
+        (status = 200, description = "GraphQL response with `data` and any field `errors`", body = Object)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn graphql_query(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(claims): axum::Extension<auth::Claims>,
+    Json(request): Json<graphql::GraphQlRequest>,
+) -> ApiResult<Json<async_graphql::Response>> {
+    let viewer = graphql::Viewer::from_claims(&claims)?;
+    Ok(Json(graphql::execute(state, viewer, request).await))
+}
+
+/// Get the caller's energy usage and estimated carbon emissions
+///
+/// Aggregates energy reported by nodes for the caller's tasks and connect
+/// sessions, grouped by node region.  CO2 estimates use the per-region grid
+/// factors configured via `CARBON_GRID_FACTORS`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage",
+    responses(
+        (status = 200, description = "Energy and carbon usage report", body = UsageReport)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_usage_report(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+) -> ApiResult<Json<UsageReport>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    Ok(Json(state.get_usage_report(user_id).await?))
+}
+
+/// Upload a WASM module to the artifact store
+///
+/// The request body is the raw `.wasm` binary.  The returned `reference` can
+/// be used as `TaskSubmission.wasm_module` instead of inlining the module.
+#[utoipa::path(
+    post,
+    path = "/api/v1/modules",
+    request_body(content = Vec<u8>, content_type = "application/wasm"),
+    responses(
+        (status = 201, description = "Module stored", body = WasmModuleInfo),
+        (status = 400, description = "Empty, oversized, or non-WASM body", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn upload_wasm_module(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<WasmModuleInfo>)> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let module = state.store_wasm_module(&body, user_id).await?;
+    info!(module_hash = %module.module_hash, size_bytes = module.size_bytes, "WASM module uploaded");
+
+    Ok((StatusCode::CREATED, Json(module)))
+}
+
+/// Download a WASM module by its SHA3-256 hash
+///
+/// Nodes should verify the `X-Module-Sha3-256` header against the hash of the
+/// received body before executing it.
+#[utoipa::path(
+    get,
+    path = "/api/v1/modules/{module_hash}",
+    params(
+        ("module_hash" = String, Path, description = "Hex SHA3-256 of the module")
+    ),
+    responses(
+        (status = 200, description = "Module binary", content_type = "application/wasm"),
+        (status = 404, description = "Module not found", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn download_wasm_module(
+    State(state): State<Arc<AppState>>,
+    Path(module_hash): Path<String>,
+) -> ApiResult<impl IntoResponse> {
+    let bytes = state
+        .get_wasm_module_bytes(&module_hash)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Module {} not found", module_hash)))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/wasm".to_string()),
+            (
+                header::HeaderName::from_static("x-module-sha3-256"),
+                module_hash,
+            ),
+        ],
+        bytes,
+    ))
+}
+
+/// Register a new user
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/register",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "User registered successfully"),
+        (status = 400, description = "Invalid request", body = ApiError)
+    )
+)]
+async fn register_user(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<auth::RegisterRequest>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    let Some(db) = &state.db else {
+        return Err(ApiError::service_unavailable("Database not configured"));
+    };
+
+    request.validate()?;
+
+    info!("Registering user: {}", request.username);
+
+    let password_hash = auth::hash_password_async(request.password.clone()).await?;
+    let api_key = auth::generate_api_key();
+    let api_key_hash = auth::hash_api_key(&api_key);
+
+    let user_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO users (username, password_hash, role, email)
+        VALUES ($1, $2, $3, $4)
+        RETURNING user_id
+        "#,
+    )
+    .bind(&request.username)
+    .bind(&password_hash)
+    .bind("user")
+    .bind(request.email.as_deref())
+    .fetch_one(db)
+    .await
+    .map_err(|e| {
+        if let sqlx::Error::Database(ref db_err) = e {
+            if db_err.constraint().is_some() {
+                return ApiError::conflict("Username already exists");
+            }
+        }
+        ApiError::from(e)
+    })?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO api_keys (user_id, key_hash, key_prefix, name, scopes)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(user_id)
+    .bind(&api_key_hash)
+    .bind(auth::api_key_prefix(&api_key))
+    .bind("default")
+    .bind(
+        auth::DEFAULT_API_KEY_SCOPES
+            .iter()
+            .map(|scope| scope.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .execute(db)
+    .await?;
+
+    info!(
+        "User registered successfully: {} ({})",
+        request.username, user_id
+    );
+
+    if request.email.is_some() {
+        if let Err(err) = state.send_email_verification(user_id).await {
+            warn!(%user_id, "Failed to send email verification: {err}");
+        }
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "user_id": user_id.to_string(),
+            "username": request.username,
+            "email": request.email,
+            "api_key": api_key,
+            "email_verified": false,
+            "message": "User registered successfully. Save your API key - it won't be shown again."
+        })),
+    ))
+}
+
+/// Login endpoint
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Login successful", body = LoginResponse),
+        (status = 401, description = "Invalid credentials", body = ApiError),
+        (status = 429, description = "Account or client IP locked after repeated failures; `details.retry_after` gives the seconds left", body = ApiError)
+    )
+)]
+async fn login(
+    State(state): State<Arc<AppState>>,
+    client_ip: Option<axum::Extension<rate_limit::ClientIp>>,
+    Json(request): Json<auth::LoginRequest>,
+) -> ApiResult<Json<auth::LoginResponse>> {
+    let Some(db) = &state.db else {
+        return Err(ApiError::service_unavailable("Database not configured"));
+    };
+
+    // Validate login request
+    request.validate()?;
+
+    info!("Login attempt for user: {}", request.username);
+
+    use lockout::LockoutScope;
+    let ip = client_ip.map(|axum::Extension(rate_limit::ClientIp(ip))| ip.to_string());
+    if let Some(ip) = &ip {
+        if let Some(retry_after) = state.login_lock_remaining(LockoutScope::Ip, ip).await? {
+            return Err(lockout::locked_error(LockoutScope::Ip, retry_after));
+        }
+    }
+
+    // Accounts are locked by the submitted username, before it is looked
+    // up, so unknown and existing usernames lock and answer alike.
+    if let Some(retry_after) = state
+        .login_lock_remaining(LockoutScope::User, &request.username)
+        .await?
+    {
+        return Err(lockout::locked_error(LockoutScope::User, retry_after));
+    }
+
+    let Some(user_row) = sqlx::query(
+        r#"
+        SELECT user_id, username, password_hash, role
+        FROM users
+        WHERE username = $1
+        "#,
+    )
+    .bind(&request.username)
+    .fetch_optional(db)
+    .await?
+    else {
+        auth::verify_decoy_password_async(request.password.clone()).await?;
+        return Err(failed_login(&state, &request.username, ip.as_deref()).await?);
+    };
+
+    let user_id: Uuid = user_row.get("user_id");
+    let username: String = user_row.get("username");
+    let password_hash: String = user_row.get("password_hash");
+    let role: String = user_row.get("role");
+
+    let password_valid =
+        auth::verify_password_async(request.password.clone(), password_hash).await?;
+    if !password_valid {
+        return Err(failed_login(&state, &request.username, ip.as_deref()).await?);
+    }
+    state
+        .clear_login_lockout(LockoutScope::User, &username)
+        .await?;
+
+    sqlx::query("UPDATE users SET last_login = NOW() WHERE user_id = $1")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    let auth_config = state.auth_config()?;
+    let token = auth_config.generate_token(user_id.to_string(), username.clone(), role)?;
+
+    // Generate refresh token
+    let refresh_token = auth::generate_refresh_token();
+    let refresh_token_hash = auth::hash_refresh_token(&refresh_token);
+    let expires_at = chrono::Utc::now() + chrono::Duration::days(30); // 30 days
+
+    // Store refresh token in database
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(user_id)
+    .bind(&refresh_token_hash)
+    .bind(expires_at)
+    .execute(db)
+    .await?;
+
+    info!("Login successful for user: {}", username);
+
+    Ok(Json(auth::LoginResponse {
+        access_token: token,
+        refresh_token: Some(refresh_token),
+        token_type: "Bearer".to_string(),
+        expires_in: auth_config.jwt_expiration_hours * 3600,
+    }))
+}
+
+/// Count a failed login against the submitted username, whether or not it
+/// exists, and the client IP.  Returns the error to answer with: the lock if
+/// this failure triggered one, otherwise invalid credentials.
+async fn failed_login(state: &AppState, username: &str, ip: Option<&str>) -> ApiResult<ApiError> {
+    let subjects = std::iter::once((lockout::LockoutScope::User, username.to_string()))
+        .chain(ip.map(|ip| (lockout::LockoutScope::Ip, ip.to_string())));
+
+    let mut locked = None;
+    for (scope, subject) in subjects {
+        if let Some(secs) = state.record_login_failure(scope, &subject).await? {
+            locked.get_or_insert(lockout::locked_error(scope, secs));
+        }
+    }
+    Ok(locked.unwrap_or_else(|| ApiError::unauthorized("Invalid username or password")))
+}
+
+/// Request a password reset
+///
+/// Mails a single-use reset token to the account's email.  The response is
+/// the same whether or not the account exists.
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/password-reset/request",
+    request_body = PasswordResetRequest,
+    responses(
+        (status = 202, description = "Reset email sent if the account has an email"),
+        (status = 422, description = "Invalid request", body = ApiError)
+    )
+)]
+async fn request_password_reset(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<auth::PasswordResetRequest>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    request.validate()?;
+
+    state.request_password_reset(&request.username).await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "message": "If the account exists and has an email address, a reset token has been sent."
+        })),
+    ))
+}
+
+/// Reset a password with a reset token
+///
+/// Sets the new password, signs the account out everywhere and lifts any
+/// login lockout.
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/password-reset/confirm",
+    request_body = PasswordResetConfirmRequest,
+    responses(
+        (status = 200, description = "Password reset"),
+        (status = 400, description = "Token is invalid, used or expired", body = ApiError),
+        (status = 422, description = "Invalid request", body = ApiError)
+    )
+)]
+async fn confirm_password_reset(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<auth::PasswordResetConfirmRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    request.validate()?;
+
+    let password_hash = auth::hash_password_async(request.new_password).await?;
+    let Some(user_id) = state
+        .confirm_password_reset(&request.token, &password_hash)
+        .await?
+    else {
+        return Err(ApiError::bad_request(
+            "Reset token is invalid, used or expired",
+        ));
+    };
+
+    info!(%user_id, "Password reset completed");
+
+    Ok(Json(serde_json::json!({
+        "message": "Password reset. Log in with the new password."
+    })))
+}
+
+/// Verify an email address
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/verify-email",
+    request_body = EmailVerificationRequest,
+    responses(
+        (status = 200, description = "Email verified"),
+        (status = 400, description = "Token is invalid, used or expired", body = ApiError),
+        (status = 422, description = "Invalid request", body = ApiError)
+    )
+)]
+async fn verify_email(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<auth::EmailVerificationRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    request.validate()?;
+
+    if !state.verify_email(&request.token).await? {
+        return Err(ApiError::bad_request(
+            "Verification token is invalid, used or expired",
+        ));
+    }
+
+    Ok(Json(serde_json::json!({ "message": "Email verified" })))
+}
+
+/// Refresh token endpoint
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/refresh",
+    request_body = RefreshTokenRequest,
+    responses(
+        (status = 200, description = "Token refreshed successfully", body = RefreshTokenResponse),
+        (status = 401, description = "Invalid, expired or reused refresh token; reuse revokes all of the user's sessions", body = ApiError)
+    )
+)]
+async fn refresh_token(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<auth::RefreshTokenRequest>,
+) -> ApiResult<Json<auth::RefreshTokenResponse>> {
+    let Some(db) = &state.db else {
+        return Err(ApiError::service_unavailable("Database not configured"));
+    };
+
+    let token_hash = auth::hash_refresh_token(&request.refresh_token);
+
+    // Fetch refresh token from database
+    let token_row = sqlx::query(
+        r#"
+        SELECT rt.token_id, rt.family_id, rt.user_id, rt.expires_at, rt.revoked_at,
+               rt.revoked_reason, u.username, u.role
+        FROM refresh_tokens rt
+        JOIN users u ON rt.user_id = u.user_id
+        WHERE rt.token_hash = $1
+        "#,
+    )
+    .bind(&token_hash)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| ApiError::unauthorized("Invalid refresh token"))?;
+
+    let token_id: Uuid = token_row.get("token_id");
+    let family_id: Uuid = token_row.get("family_id");
+    let user_id: Uuid = token_row.get("user_id");
+    let username: String = token_row.get("username");
+    let role: String = token_row.get("role");
+    let expires_at: chrono::DateTime<chrono::Utc> = token_row.get("expires_at");
+    let revoked_at: Option<chrono::DateTime<chrono::Utc>> = token_row.get("revoked_at");
+    let revoked_reason: Option<String> = token_row.get("revoked_reason");
+
+    // A rotated token presented again has been replayed by someone
+    if revoked_reason.as_deref() == Some("rotated") {
+        state
+            .revoke_refresh_token_family_on_reuse(user_id, token_id, family_id)
+            .await?;
+        return Err(ApiError::unauthorized(
+            "Refresh token reuse detected; all sessions have been revoked",
+        ));
+    }
+
+    // Check if token is revoked
+    if revoked_at.is_some() {
+        return Err(ApiError::unauthorized("Refresh token has been revoked"));
+    }
+
+    // Check if token is expired
+    if expires_at < chrono::Utc::now() {
+        return Err(ApiError::unauthorized("Refresh token has expired"));
+    }
+
+    // Revoke old refresh token; losing a race to a concurrent rotation of the
+    // same token is reuse too
+    let rotated = sqlx::query(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = NOW(), revoked_reason = 'rotated'
+        WHERE token_id = $1 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(token_id)
+    .execute(db)
+    .await?
+    .rows_affected();
+    if rotated == 0 {
+        state
+            .revoke_refresh_token_family_on_reuse(user_id, token_id, family_id)
+            .await?;
+        return Err(ApiError::unauthorized(
+            "Refresh token reuse detected; all sessions have been revoked",
+        ));
+    }
+
+    // Generate new JWT access token
+    let auth_config = state.auth_config()?;
+    let access_token = auth_config.generate_token(user_id.to_string(), username, role)?;
+
+    // Generate new refresh token
+    let new_refresh_token = auth::generate_refresh_token();
+    let new_token_hash = auth::hash_refresh_token(&new_refresh_token);
+    let new_expires_at = chrono::Utc::now() + chrono::Duration::days(30);
+
+    // Store new refresh token in the same family
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (user_id, token_hash, expires_at, family_id)
+        VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(user_id)
+    .bind(&new_token_hash)
+    .bind(new_expires_at)
+    .bind(family_id)
+    .execute(db)
+    .await?;
 
-    let password_hash = auth::hash_password_async(request.password.clone()).await?;
-    let api_key = auth::generate_api_key();
-    let api_key_hash = auth::hash_api_key(&api_key);
+    info!("Token refreshed successfully for user_id: {}", user_id);
 
-    let user_id: Uuid = sqlx::query_scalar(
+    Ok(Json(auth::RefreshTokenResponse {
+        access_token,
+        refresh_token: new_refresh_token,
+        token_type: "Bearer".to_string(),
+        expires_in: auth_config.jwt_expiration_hours * 3600,
+    }))
+}
+
+fn map_api_key_row(row: sqlx::postgres::PgRow) -> auth::ApiKeyInfo {
+    let to_rfc3339 = |column: &str| {
+        row.get::<Option<chrono::DateTime<chrono::Utc>>, _>(column)
+            .map(|ts| ts.to_rfc3339())
+    };
+
+    auth::ApiKeyInfo {
+        key_id: row.get::<Uuid, _>("key_id").to_string(),
+        name: row.get("name"),
+        key_prefix: row.get("key_prefix"),
+        scopes: row
+            .get::<Option<Vec<String>>, _>("scopes")
+            .unwrap_or_default(),
+        created_at: to_rfc3339("created_at"),
+        expires_at: to_rfc3339("expires_at"),
+        last_used_at: to_rfc3339("last_used_at"),
+        revoked_at: to_rfc3339("revoked_at"),
+    }
+}
+
+/// Create a named API key with scopes and an optional expiry
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/api-keys",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "API key created", body = CreateApiKeyResponse),
+        (status = 400, description = "Active API key limit reached", body = ApiError),
+        (status = 403, description = "Scope beyond the caller's role or credential", body = ApiError),
+        (status = 422, description = "Invalid request", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn create_api_key(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    axum::Extension(claims): axum::Extension<auth::Claims>,
+    Json(request): Json<auth::CreateApiKeyRequest>,
+) -> ApiResult<(StatusCode, Json<auth::CreateApiKeyResponse>)> {
+    request.validate()?;
+
+    let scopes = request.resolved_scopes();
+    let beyond_role = rbac::scopes_beyond_role(&auth_user.role, &scopes);
+    if !beyond_role.is_empty() {
+        return Err(ApiError::forbidden(format!(
+            "Role '{}' cannot grant scope '{}'",
+            auth_user.role, beyond_role[0]
+        ))
+        .with_details(serde_json::json!({
+            "denied_scopes": beyond_role,
+            "role": auth_user.role,
+        })));
+    }
+
+    // A credential can only hand on scopes it holds itself.
+    let granted = claims.granted_scopes();
+    let beyond_credential = rbac::scopes_beyond(&granted, &scopes);
+    if !beyond_credential.is_empty() {
+        return Err(ApiError::forbidden(format!(
+            "Credential cannot grant scope '{}' it does not hold",
+            beyond_credential[0]
+        ))
+        .with_details(serde_json::json!({
+            "denied_scopes": beyond_credential,
+            "granted_scopes": granted,
+        })));
+    }
+
+    let Some(db) = &state.db else {
+        return Err(ApiError::service_unavailable("Database not configured"));
+    };
+
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let active_keys: i64 = sqlx::query_scalar(
         r#"
-        INSERT INTO users (username, password_hash, role, email)
-        VALUES ($1, $2, $3, $4)
-        RETURNING user_id
+        SELECT COUNT(*)
+        FROM api_keys
+        WHERE user_id = $1
+          AND revoked_at IS NULL
+          AND (expires_at IS NULL OR expires_at > NOW())
         "#,
     )
-    .bind(&request.username)
-    .bind(&password_hash)
-    .bind("user")
-    .bind(request.email.as_deref())
+    .bind(user_id)
     .fetch_one(db)
-    .await
-    .map_err(|e| {
-        if let sqlx::Error::Database(ref db_err) = e {
-            if db_err.constraint().is_some() {
-                return ApiError::conflict("Username already exists");
-            }
-        }
-        ApiError::from(e)
-    })?;
+    .await?;
 
-    sqlx::query(
+    if active_keys >= auth::MAX_ACTIVE_API_KEYS_PER_USER {
+        return Err(ApiError::bad_request(format!(
+            "Active API key limit of {} reached. Revoke an existing key first.",
+            auth::MAX_ACTIVE_API_KEYS_PER_USER
+        )));
+    }
+
+    let api_key = auth::generate_api_key();
+    let expires_at = request
+        .expires_in_days
+        .map(|days| chrono::Utc::now() + chrono::Duration::days(days));
+
+    let row = sqlx::query(
         r#"
-        INSERT INTO api_keys (user_id, key_hash, key_prefix, name, scopes)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO api_keys (user_id, key_hash, key_prefix, name, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING key_id, name, key_prefix, scopes, created_at, expires_at, last_used_at, revoked_at
         "#,
     )
     .bind(user_id)
-    .bind(&api_key_hash)
-    .bind(api_key.chars().take(8).collect::<String>())
-    .bind("default")
-    .bind(vec!["tasks:read".to_string(), "tasks:write".to_string()])
-    .execute(db)
+    .bind(auth::hash_api_key(&api_key))
+    .bind(auth::api_key_prefix(&api_key))
+    .bind(request.name.trim())
+    .bind(&scopes)
+    .bind(expires_at)
+    .fetch_one(db)
     .await?;
 
+    let key = map_api_key_row(row);
     info!(
-        "User registered successfully: {} ({})",
-        request.username, user_id
+        "API key {} ({}) created for user {}",
+        key.key_id, key.key_prefix, auth_user.username
     );
 
     Ok((
         StatusCode::CREATED,
-        Json(serde_json::json!({
-            "user_id": user_id.to_string(),
-            "username": request.username,
-            "email": request.email,
-            "api_key": api_key,
-            "message": "User registered successfully. Save your API key - it won't be shown again."
-        })),
+        Json(auth::CreateApiKeyResponse { key, api_key }),
     ))
 }
 
-/// Login endpoint
+/// List the caller's API keys (prefixes and metadata only)
 #[utoipa::path(
-    post,
-    path = "/api/v1/auth/login",
-    request_body = LoginRequest,
+    get,
+    path = "/api/v1/auth/api-keys",
     responses(
-        (status = 200, description = "Login successful", body = LoginResponse),
-        (status = 401, description = "Invalid credentials", body = ApiError)
+        (status = 200, description = "API keys owned by the caller", body = Vec<ApiKeyInfo>)
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-async fn login(
+async fn list_api_keys(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<auth::LoginRequest>,
-) -> ApiResult<Json<auth::LoginResponse>> {
+    auth_user: auth::AuthUser,
+) -> ApiResult<Json<Vec<auth::ApiKeyInfo>>> {
     let Some(db) = &state.db else {
         return Err(ApiError::service_unavailable("Database not configured"));
     };
 
-    // Validate login request
-    request.validate()?;
-
-    info!("Login attempt for user: {}", request.username);
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
 
-    let user_row = sqlx::query(
+    let rows = sqlx::query(
         r#"
-        SELECT user_id, username, password_hash, role
-        FROM users
-        WHERE username = $1
+        SELECT key_id, name, key_prefix, scopes, created_at, expires_at, last_used_at, revoked_at
+        FROM api_keys
+        WHERE user_id = $1
+        ORDER BY created_at DESC
         "#,
     )
-    .bind(&request.username)
-    .fetch_optional(db)
-    .await?
-    .ok_or_else(|| ApiError::unauthorized("Invalid username or password"))?;
-
-    let user_id: Uuid = user_row.get("user_id");
-    let username: String = user_row.get("username");
-    let password_hash: String = user_row.get("password_hash");
-    let role: String = user_row.get("role");
-
-    let password_valid =
-        auth::verify_password_async(request.password.clone(), password_hash).await?;
-    if !password_valid {
-        return Err(ApiError::unauthorized("Invalid username or password"));
-    }
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
 
-    sqlx::query("UPDATE users SET last_login = NOW() WHERE user_id = $1")
-        .bind(user_id)
-        .execute(db)
-        .await?;
+    Ok(Json(rows.into_iter().map(map_api_key_row).collect()))
+}
 
-    let auth_config = state.auth_config()?;
-    let token = auth_config.generate_token(user_id.to_string(), username.clone(), role)?;
+/// Revoke one of the caller's API keys
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/api-keys/{key_id}",
+    params(
+        ("key_id" = String, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "API key revoked"),
+        (status = 404, description = "API key not found or already revoked", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn revoke_api_key(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(key_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let Some(db) = &state.db else {
+        return Err(ApiError::service_unavailable("Database not configured"));
+    };
 
-    // Generate refresh token
-    let refresh_token = auth::generate_refresh_token();
-    let refresh_token_hash = auth::hash_refresh_token(&refresh_token);
-    let expires_at = chrono::Utc::now() + chrono::Duration::days(30); // 30 days
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let key_uuid = Uuid::parse_str(&key_id)
+        .map_err(|_| ApiError::bad_request("key_id must be a valid UUID"))?;
 
-    // Store refresh token in database
-    sqlx::query(
+    let revoked = sqlx::query(
         r#"
-        INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
-        VALUES ($1, $2, $3)
+        UPDATE api_keys
+        SET revoked_at = NOW(), revoked_reason = 'revoked_by_user'
+        WHERE key_id = $1 AND user_id = $2 AND revoked_at IS NULL
         "#,
     )
+    .bind(key_uuid)
     .bind(user_id)
-    .bind(&refresh_token_hash)
-    .bind(expires_at)
     .execute(db)
-    .await?;
+    .await?
+    .rows_affected();
+
+    if revoked == 0 {
+        return Err(ApiError::not_found_or_forbidden(format!(
+            "API key {} not found or already revoked",
+            key_id
+        )));
+    }
+
+    info!("API key {} revoked by user {}", key_id, auth_user.username);
+
+    Ok(Json(serde_json::json!({
+        "message": "API key revoked successfully",
+        "key_id": key_id
+    })))
+}
+
+/// Create an organization owned by the caller
+#[utoipa::path(
+    post,
+    path = "/api/v1/orgs",
+    request_body = CreateOrganizationRequest,
+    responses(
+        (status = 201, description = "Organization created", body = OrganizationInfo),
+        (status = 400, description = "Invalid organization name", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn create_organization(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Json(request): Json<CreateOrganizationRequest>,
+) -> ApiResult<(StatusCode, Json<OrganizationInfo>)> {
+    request.validate()?;
+
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let org = state.create_organization(request, user_id).await?;
+    info!(
+        "Organization {} created by user {}",
+        org.org_id, auth_user.username
+    );
+
+    Ok((StatusCode::CREATED, Json(org)))
+}
+
+/// List the organizations the caller belongs to
+#[utoipa::path(
+    get,
+    path = "/api/v1/orgs",
+    responses(
+        (status = 200, description = "Caller's organizations", body = Vec<OrganizationInfo>)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn list_organizations(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+) -> ApiResult<Json<Vec<OrganizationInfo>>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    Ok(Json(state.list_user_organizations(user_id).await?))
+}
+
+/// List an organization's members
+#[utoipa::path(
+    get,
+    path = "/api/v1/orgs/{org_id}/members",
+    params(
+        ("org_id" = String, Path, description = "Organization ID")
+    ),
+    responses(
+        (status = 200, description = "Organization members", body = Vec<OrganizationMember>),
+        (status = 404, description = "Organization not found or caller is not a member", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn list_organization_members(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(org_id): Path<String>,
+) -> ApiResult<Json<Vec<OrganizationMember>>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let org_uuid = Uuid::parse_str(&org_id)
+        .map_err(|_| ApiError::bad_request("org_id must be a valid UUID"))?;
+
+    Ok(Json(state.list_org_members(org_uuid, user_id).await?))
+}
+
+/// Add a user to an organization or change their role
+#[utoipa::path(
+    put,
+    path = "/api/v1/orgs/{org_id}/members",
+    params(
+        ("org_id" = String, Path, description = "Organization ID")
+    ),
+    request_body = SetOrganizationMemberRequest,
+    responses(
+        (status = 200, description = "Membership updated", body = OrganizationMember),
+        (status = 403, description = "Caller's role cannot grant this role", body = ApiError),
+        (status = 404, description = "Organization or user not found", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn set_organization_member(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(org_id): Path<String>,
+    Json(request): Json<SetOrganizationMemberRequest>,
+) -> ApiResult<Json<OrganizationMember>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let org_uuid = Uuid::parse_str(&org_id)
+        .map_err(|_| ApiError::bad_request("org_id must be a valid UUID"))?;
+
+    let member = state.set_org_member(org_uuid, user_id, request).await?;
+    info!(
+        "User {} set {} to {} in organization {}",
+        auth_user.username, member.username, member.role, org_id
+    );
+
+    Ok(Json(member))
+}
+
+/// Remove a member from an organization, or leave it
+#[utoipa::path(
+    delete,
+    path = "/api/v1/orgs/{org_id}/members/{user_id}",
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+        ("user_id" = String, Path, description = "User ID of the member to remove")
+    ),
+    responses(
+        (status = 200, description = "Member removed"),
+        (status = 404, description = "Member not found", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn remove_organization_member(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path((org_id, member_id)): Path<(String, String)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let org_uuid = Uuid::parse_str(&org_id)
+        .map_err(|_| ApiError::bad_request("org_id must be a valid UUID"))?;
+    let member_uuid = Uuid::parse_str(&member_id)
+        .map_err(|_| ApiError::bad_request("user_id must be a valid UUID"))?;
 
-    info!("Login successful for user: {}", username);
+    let removed = state
+        .remove_org_member(org_uuid, user_id, member_uuid)
+        .await?;
 
-    Ok(Json(auth::LoginResponse {
-        access_token: token,
-        refresh_token: Some(refresh_token),
-        token_type: "Bearer".to_string(),
-        expires_in: auth_config.jwt_expiration_hours * 3600,
-    }))
+    if !removed {
+        return Err(ApiError::not_found_or_forbidden(format!(
+            "User {} is not a member of organization {}",
+            member_id, org_id
+        )));
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "Member removed successfully",
+        "org_id": org_id,
+        "user_id": member_id
+    })))
 }
 
-/// Refresh token endpoint
+/// Issue an access token scoped to one of the caller's organizations
+///
+/// Nodes registered and tasks submitted with this token are owned by the
+/// organization, and `GET /tasks` lists the organization's tasks.
 #[utoipa::path(
     post,
-    path = "/api/v1/auth/refresh",
-    request_body = RefreshTokenRequest,
+    path = "/api/v1/orgs/{org_id}/token",
+    params(
+        ("org_id" = String, Path, description = "Organization ID")
+    ),
     responses(
-        (status = 200, description = "Token refreshed successfully", body = RefreshTokenResponse),
-        (status = 401, description = "Invalid or expired refresh token", body = ApiError)
+        (status = 200, description = "Organization-scoped access token", body = auth::LoginResponse),
+        (status = 404, description = "Organization not found or caller is not a member", body = ApiError)
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-async fn refresh_token(
+async fn issue_organization_token(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<auth::RefreshTokenRequest>,
-) -> ApiResult<Json<auth::RefreshTokenResponse>> {
-    let Some(db) = &state.db else {
-        return Err(ApiError::service_unavailable("Database not configured"));
-    };
-
-    let token_hash = auth::hash_refresh_token(&request.refresh_token);
-
-    // Fetch refresh token from database
-    let token_row = sqlx::query(
-        r#"
-        SELECT rt.user_id, rt.expires_at, rt.revoked_at, u.username, u.role
-        FROM refresh_tokens rt
-        JOIN users u ON rt.user_id = u.user_id
-        WHERE rt.token_hash = $1
-        "#,
-    )
-    .bind(&token_hash)
-    .fetch_optional(db)
-    .await?
-    .ok_or_else(|| ApiError::unauthorized("Invalid refresh token"))?;
-
-    let user_id: Uuid = token_row.get("user_id");
-    let username: String = token_row.get("username");
-    let role: String = token_row.get("role");
-    let expires_at: chrono::DateTime<chrono::Utc> = token_row.get("expires_at");
-    let revoked_at: Option<chrono::DateTime<chrono::Utc>> = token_row.get("revoked_at");
-
-    // Check if token is revoked
-    if revoked_at.is_some() {
-        return Err(ApiError::unauthorized("Refresh token has been revoked"));
-    }
-
-    // Check if token is expired
-    if expires_at < chrono::Utc::now() {
-        return Err(ApiError::unauthorized("Refresh token has expired"));
-    }
+    auth_user: auth::AuthUser,
+    axum::Extension(claims): axum::Extension<auth::Claims>,
+    Path(org_id): Path<String>,
+) -> ApiResult<Json<auth::LoginResponse>> {
+    let user_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let org_uuid = Uuid::parse_str(&org_id)
+        .map_err(|_| ApiError::bad_request("org_id must be a valid UUID"))?;
 
-    // Revoke old refresh token
-    sqlx::query(
-        r#"
-        UPDATE refresh_tokens 
-        SET revoked_at = NOW(), revoked_reason = 'rotated'
-        WHERE token_hash = $1
-        "#,
-    )
-    .bind(&token_hash)
-    .execute(db)
-    .await?;
+    let org_role = state
+        .require_org_role(org_uuid, user_id, orgs::OrgRole::Viewer)
+        .await?;
 
-    // Generate new JWT access token
+    // The org token never carries more than the credential that asked for it.
+    let scopes = rbac::scopes_within_role(&auth_user.role, &claims.granted_scopes());
     let auth_config = state.auth_config()?;
-    let access_token = auth_config.generate_token(user_id.to_string(), username, role)?;
-
-    // Generate new refresh token
-    let new_refresh_token = auth::generate_refresh_token();
-    let new_token_hash = auth::hash_refresh_token(&new_refresh_token);
-    let new_expires_at = chrono::Utc::now() + chrono::Duration::days(30);
-
-    // Store new refresh token
-    sqlx::query(
-        r#"
-        INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
-        VALUES ($1, $2, $3)
-        "#,
-    )
-    .bind(user_id)
-    .bind(&new_token_hash)
-    .bind(new_expires_at)
-    .execute(db)
-    .await?;
-
-    info!("Token refreshed successfully for user_id: {}", user_id);
+    let token = auth_config.generate_org_token(
+        auth_user.user_id,
+        auth_user.username,
+        auth_user.role,
+        org_uuid,
+        org_role,
+        scopes,
+    )?;
 
-    Ok(Json(auth::RefreshTokenResponse {
-        access_token,
-        refresh_token: new_refresh_token,
+    Ok(Json(auth::LoginResponse {
+        access_token: token,
+        refresh_token: None,
         token_type: "Bearer".to_string(),
         expires_in: auth_config.jwt_expiration_hours * 3600,
     }))
@@ -1044,53 +3476,386 @@ async fn admin_users() -> ApiResult<Json<serde_json::Value>> {
     Err(ApiError::not_implemented("admin user management"))
 }
 
-async fn admin_throttle_overrides() -> ApiResult<Json<serde_json::Value>> {
-    Err(ApiError::not_implemented("admin throttle overrides"))
+/// Every throttle override, including expired ones.
+async fn admin_list_throttle_overrides(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<Vec<ThrottleOverride>>> {
+    Ok(Json(state.list_throttle_overrides().await?))
+}
+
+/// Set a user's or API key's rate limit override; applies immediately on this
+/// replica and within `THROTTLE_OVERRIDE_RELOAD_SECONDS` on the others.
+async fn admin_set_throttle_override(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Json(request): Json<ThrottleOverrideRequest>,
+) -> ApiResult<Json<ThrottleOverride>> {
+    request.validate()?;
+    let admin_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let throttle_override = state.upsert_throttle_override(&request, admin_id).await?;
+    info!(
+        subject_type = request.subject_type.as_str(),
+        subject_id = %request.subject_id,
+        multiplier = request.multiplier,
+        burst = request.burst,
+        "Throttle override set by {}",
+        auth_user.username
+    );
+    Ok(Json(throttle_override))
+}
+
+/// Remove a throttle override, restoring the default limits.
+async fn admin_delete_throttle_override(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(override_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let override_uuid = Uuid::parse_str(&override_id)
+        .map_err(|_| ApiError::bad_request("override_id must be a valid UUID"))?;
+
+    if !state.delete_throttle_override(override_uuid).await? {
+        return Err(ApiError::not_found(format!(
+            "Throttle override {} not found",
+            override_id
+        )));
+    }
+    info!(
+        %override_id,
+        "Throttle override removed by {}", auth_user.username
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Every destination policy.
+async fn admin_list_destination_policies(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<Vec<destination_policies::DestinationPolicy>>> {
+    Ok(Json(state.list_destination_policies().await?))
+}
+
+/// Create or replace a destination policy; relay nodes pick it up with
+/// their next bundle fetch.
+async fn admin_put_destination_policy(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(policy_id): Path<String>,
+    Json(request): Json<destination_policies::DestinationPolicyRequest>,
+) -> ApiResult<Json<destination_policies::DestinationPolicy>> {
+    destination_policies::validate_policy_id(&policy_id)?;
+    let allowed_destinations = request.normalized()?;
+    let admin_id = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+
+    let policy = state
+        .upsert_destination_policy(&policy_id, &allowed_destinations, admin_id)
+        .await?;
+    info!(
+        %policy_id,
+        destinations = allowed_destinations.len(),
+        bundle_version = policy.bundle_version,
+        "Destination policy set by {}",
+        auth_user.username
+    );
+    Ok(Json(policy))
+}
+
+/// Remove a destination policy.
+async fn admin_delete_destination_policy(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path(policy_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !state.delete_destination_policy(&policy_id).await? {
+        return Err(ApiError::not_found(format!(
+            "Destination policy {} not found",
+            policy_id
+        )));
+    }
+    info!(
+        %policy_id,
+        "Destination policy removed by {}", auth_user.username
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Accounts and client IPs that are locked or have recent failed logins.
+async fn admin_list_login_lockouts(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<Vec<lockout::LoginLockout>>> {
+    Ok(Json(state.list_login_lockouts().await?))
+}
+
+/// Unlock an account (`user`, by user ID) or client IP (`ip`) and restart
+/// its backoff.
+async fn admin_unlock_login(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Path((scope, subject)): Path<(String, String)>,
+) -> ApiResult<StatusCode> {
+    let scope = lockout::LockoutScope::parse(&scope)
+        .ok_or_else(|| ApiError::bad_request("scope must be 'user' or 'ip'"))?;
+
+    if !state.clear_login_lockout(scope, &subject).await? {
+        return Err(ApiError::not_found(format!(
+            "No login lockout for {} {}",
+            scope.as_str(),
+            subject
+        )));
+    }
+    info!(
+        scope = scope.as_str(),
+        %subject,
+        "Login lockout cleared by {}", auth_user.username
+    );
+    Ok(StatusCode::NO_CONTENT)
 }
 
 async fn admin_audit_log() -> ApiResult<Json<serde_json::Value>> {
     Err(ApiError::not_implemented("admin audit log"))
 }
 
+/// Dry-run retention report: rows past each table's retention window.
+async fn admin_retention_report(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<retention::RetentionReport>> {
+    Ok(Json(state.run_retention(Some(true)).await?))
+}
+
+/// Recent synthetic canary runs, newest first.
+async fn admin_list_canaries(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<canary::CanaryRunsQuery>,
+) -> ApiResult<Json<Vec<canary::CanaryRun>>> {
+    Ok(Json(state.list_canary_runs(&query).await?))
+}
+
+/// Recorded scheduling decisions, newest first.
+async fn admin_list_scheduler_decisions(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<scheduler_decisions::SchedulerDecisionsQuery>,
+) -> ApiResult<Json<Vec<scheduler_decisions::SchedulerDecision>>> {
+    Ok(Json(state.list_scheduler_decisions(&query).await?))
+}
+
+/// Re-run a recorded scheduling decision against the current rules.
+async fn admin_replay_scheduler_decision(
+    State(state): State<Arc<AppState>>,
+    Path(decision_id): Path<i64>,
+) -> ApiResult<Json<scheduler_decisions::DecisionReplay>> {
+    Ok(Json(state.replay_scheduler_decision(decision_id).await?))
+}
+
+/// Start a snapshot-consistent cluster export in the background.
+async fn admin_start_cluster_export(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+    Json(request): Json<cluster_export::ClusterExportRequest>,
+) -> ApiResult<(StatusCode, Json<cluster_export::ClusterExport>)> {
+    let created_by = Uuid::parse_str(&auth_user.user_id)
+        .map_err(|_| ApiError::internal_error("Invalid user ID format"))?;
+    let export = state.create_cluster_export(request, created_by).await?;
+    let export_id = Uuid::parse_str(&export.export_id)
+        .map_err(|_| ApiError::internal_error("Invalid export ID format"))?;
+    let job_state = Arc::clone(&state);
+    tokio::spawn(async move { job_state.run_cluster_export(export_id).await });
+    Ok((StatusCode::ACCEPTED, Json(export)))
+}
+
+/// Cluster exports, newest first.
+async fn admin_list_cluster_exports(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<cluster_export::ClusterExportsQuery>,
+) -> ApiResult<Json<Vec<cluster_export::ClusterExport>>> {
+    Ok(Json(state.list_cluster_exports(&query).await?))
+}
+
+/// One cluster export and the files it wrote.
+async fn admin_get_cluster_export(
+    State(state): State<Arc<AppState>>,
+    Path(export_id): Path<Uuid>,
+) -> ApiResult<Json<cluster_export::ClusterExport>> {
+    Ok(Json(state.get_cluster_export(export_id).await?))
+}
+
+/// Download one table of a completed cluster export.
+async fn admin_download_cluster_export_file(
+    State(state): State<Arc<AppState>>,
+    Path((export_id, table)): Path<(Uuid, String)>,
+) -> ApiResult<impl IntoResponse> {
+    let (format, bytes) = state.get_cluster_export_file(export_id, &table).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{table}.{}\"", format.as_str()),
+            ),
+        ],
+        bytes,
+    ))
+}
+
+/// Fleet report of node kinds and nodes still registering with legacy aliases.
+async fn admin_node_kinds(State(state): State<Arc<AppState>>) -> ApiResult<Json<NodeKindReport>> {
+    Ok(Json(state.node_kind_report().await?))
+}
+
+/// Run the retention job now, honouring `RETENTION_DRY_RUN`.
+async fn admin_run_retention(
+    State(state): State<Arc<AppState>>,
+    auth_user: auth::AuthUser,
+) -> ApiResult<Json<retention::RetentionReport>> {
+    let report = state.run_retention(None).await?;
+    info!(
+        purged = report.purged_rows(),
+        dry_run = report.dry_run,
+        "Retention run triggered by {}",
+        auth_user.username
+    );
+    Ok(Json(report))
+}
+
 /// Build the API router
 pub fn create_router(state: Arc<AppState>) -> Router {
     let public_routes = Router::new()
         .route("/health", get(health_check))
+        .route("/time", get(get_server_time))
+        .route("/control-key", get(get_control_key))
+        .route("/transparency", get(get_transparency))
+        .route("/transparency/history", get(get_transparency_history))
+        .route("/status", get(get_status))
         .route("/auth/register", post(register_user))
         .route("/auth/login", post(login))
-        .route("/auth/refresh", post(refresh_token));
+        .route("/auth/refresh", post(refresh_token))
+        .route("/auth/password-reset/request", post(request_password_reset))
+        .route("/auth/password-reset/confirm", post(confirm_password_reset))
+        .route("/auth/verify-email", post(verify_email));
 
     let protected_routes = Router::new()
+        .route("/auth/api-keys", post(create_api_key).get(list_api_keys))
+        .route("/auth/api-keys/:key_id", delete(revoke_api_key))
         .route("/nodes", post(register_node).get(list_nodes))
-        .route("/nodes/:node_id", get(get_node).delete(delete_node))
+        .route("/nodes/rankings", get(get_node_rankings))
+        .route(
+            "/nodes/:node_id",
+            get(get_node).patch(update_node).delete(delete_node),
+        )
         .route("/nodes/:node_id/reject", post(reject_node))
+        .route(
+            "/nodes/:node_id/drain",
+            post(drain_node).delete(undrain_node),
+        )
+        .route("/nodes/:node_id/attestation", post(attest_node))
+        .route(
+            "/nodes/:node_id/attestation/challenge",
+            post(renew_attestation_challenge),
+        )
         .route("/nodes/:node_id/heartbeat", put(update_heartbeat))
+        .route("/nodes/heartbeat/batch", put(update_heartbeats_batch))
         .route(
             "/nodes/:node_id/heartbeat/activity",
             get(get_node_heartbeat_activity),
         )
+        .route("/nodes/:node_id/telemetry", get(get_node_telemetry))
         .route(
             "/nodes/:node_id/gateway-sessions",
             get(get_node_gateway_sessions),
         )
+        .route(
+            "/nodes/:node_id/gateway-sessions/:session_id/usage",
+            post(report_gateway_session_usage),
+        )
+        .route("/nodes/:node_id/route", get(get_node_peer_route))
+        .route(
+            "/nodes/:node_id/route/invalidate",
+            post(invalidate_node_peer_route),
+        )
+        .route("/nodes/:node_id/relay-load", put(report_relay_load))
+        .route("/nodes/:node_id/relay-drain", post(announce_relay_drain))
+        .route(
+            "/destination-policies/bundle",
+            get(get_destination_policy_bundle),
+        )
         .route("/tasks", post(submit_task).get(list_tasks))
+        .route("/tasks/search", get(search_tasks))
         .route("/tasks/:task_id", get(get_task).delete(delete_task))
+        .route("/tasks/:task_id/restore", post(restore_task))
         .route("/tasks/:task_id/result", post(submit_task_result))
+        .route("/tasks/:task_id/secrets", put(put_task_secrets))
+        .route(
+            "/tasks/:task_id/secrets/recipients",
+            get(list_task_secret_recipients),
+        )
+        .route(
+            "/tasks/:task_id/secrets/:node_id",
+            get(get_task_secrets_for_node),
+        )
+        .route(
+            "/tasks/:task_id/checkpoint/:node_id",
+            put(put_task_checkpoint)
+                .get(get_task_checkpoint)
+                .layer(DefaultBodyLimit::max(artifacts::max_checkpoint_bytes() + 1)),
+        )
+        .route("/tasks/:task_id/provenance", get(get_task_provenance))
+        .route("/tasks/:task_id/timeline", get(get_task_timeline))
+        .route("/tasks/:task_id/logs", post(append_task_logs))
+        .route("/tasks/:task_id/logs/stream", get(stream_task_logs))
         .route("/connect-sessions/start", post(start_connect_session))
         .route("/connect-sessions/:session_id", get(get_connect_session))
         .route(
             "/connect-sessions/:session_id/heartbeat",
             post(heartbeat_connect_session),
         )
+        .route(
+            "/connect-sessions/:session_id/extend",
+            post(extend_connect_session),
+        )
         .route(
             "/connect-sessions/:session_id/stop",
             post(stop_connect_session),
         )
+        .route(
+            "/connect-sessions/:session_id/usage",
+            post(report_connect_session_usage),
+        )
         .route("/proofs/verify", post(verify_proof))
+        .route(
+            "/proofs/batches",
+            post(create_proof_batch)
+                .layer(DefaultBodyLimit::max(proof_batches::MAX_BATCH_BODY_BYTES)),
+        )
+        .route("/proofs/batches/:batch_id", get(get_proof_batch))
+        .route("/proofs/quota", get(get_proof_quota))
+        .route("/proofs/:proof_id", get(get_proof))
         .route("/cluster/stats", get(get_cluster_stats))
+        .route("/cluster/stats/history", get(get_cluster_stats_history))
+        .route("/cluster/task-stats", get(get_task_type_stats))
+        .route("/graphql", post(graphql_query))
+        .route("/usage", get(get_usage_report))
+        .route(
+            "/modules",
+            post(upload_wasm_module)
+                .layer(DefaultBodyLimit::max(artifacts::max_module_bytes() + 1)),
+        )
+        .route("/modules/:module_hash", get(download_wasm_module))
+        .route("/orgs", post(create_organization).get(list_organizations))
+        .route(
+            "/orgs/:org_id/members",
+            get(list_organization_members).put(set_organization_member),
+        )
+        .route(
+            "/orgs/:org_id/members/:user_id",
+            delete(remove_organization_member),
+        )
+        .route("/orgs/:org_id/token", post(issue_organization_token))
+        .layer(axum_middleware::from_fn(
+            middleware::auth::require_scope_middleware,
+        ))
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
-            middleware::auth::jwt_auth_middleware,
+            middleware::auth::credential_auth_middleware,
         ));
 
     let api_key_routes = Router::new()
@@ -1102,21 +3867,73 @@ pub fn create_router(state: Arc<AppState>) -> Router {
 
     let admin_routes = Router::new()
         .route("/admin/users", get(admin_users))
-        .route("/admin/throttle-overrides", post(admin_throttle_overrides))
+        .route(
+            "/admin/throttle-overrides",
+            get(admin_list_throttle_overrides).post(admin_set_throttle_override),
+        )
+        .route(
+            "/admin/throttle-overrides/:override_id",
+            delete(admin_delete_throttle_override),
+        )
+        .route("/admin/login-lockouts", get(admin_list_login_lockouts))
+        .route(
+            "/admin/login-lockouts/:scope/:subject",
+            delete(admin_unlock_login),
+        )
         .route("/admin/audit-log", get(admin_audit_log))
+        .route(
+            "/admin/retention",
+            get(admin_retention_report).post(admin_run_retention),
+        )
+        .route("/admin/node-kinds", get(admin_node_kinds))
+        .route("/admin/canaries", get(admin_list_canaries))
+        .route(
+            "/admin/scheduler-decisions",
+            get(admin_list_scheduler_decisions),
+        )
+        .route(
+            "/admin/scheduler-decisions/:decision_id/replay",
+            post(admin_replay_scheduler_decision),
+        )
+        .route(
+            "/admin/cluster-exports",
+            get(admin_list_cluster_exports).post(admin_start_cluster_export),
+        )
+        .route(
+            "/admin/cluster-exports/:export_id",
+            get(admin_get_cluster_export),
+        )
+        .route(
+            "/admin/cluster-exports/:export_id/files/:table",
+            get(admin_download_cluster_export_file),
+        )
+        .route(
+            "/admin/destination-policies",
+            get(admin_list_destination_policies),
+        )
+        .route(
+            "/admin/destination-policies/:policy_id",
+            put(admin_put_destination_policy).delete(admin_delete_destination_policy),
+        )
         .layer(axum_middleware::from_fn(
             middleware::auth::require_admin_middleware,
         ))
+        .layer(axum_middleware::from_fn(
+            middleware::auth::require_scope_middleware,
+        ))
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
-            middleware::auth::jwt_auth_middleware,
+            middleware::auth::credential_auth_middleware,
         ));
 
     let api_routes = Router::new()
         .merge(public_routes)
         .merge(protected_routes)
         .merge(api_key_routes)
-        .merge(admin_routes);
+        .merge(admin_routes)
+        .layer(axum_middleware::from_fn(
+            middleware::codec::control_plane_codec_middleware,
+        ));
 
     // Create OpenAPI JSON route (still using utoipa for spec generation)
     let openapi_json = utoipa::openapi::OpenApiBuilder::from(ApiDoc::openapi()).build();
@@ -1130,9 +3947,12 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .layer(axum_middleware::from_fn(
             middleware::auth::require_admin_middleware,
         ))
+        .layer(axum_middleware::from_fn(
+            middleware::auth::require_scope_middleware,
+        ))
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
-            middleware::auth::jwt_auth_middleware,
+            middleware::auth::credential_auth_middleware,
         ));
 
     // Resolve the fonts directory.  In Docker the working directory is /app and
@@ -1146,6 +3966,7 @@ pub fn create_router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", get(dashboard))
         .route("/swagger-ui", get(swagger_ui))
+        .route("/.well-known/jwks.json", get(get_jwks))
         .nest_service("/assets/fonts", ServeDir::new(fonts_dir))
         .nest("/api/v1", api_routes)
         .merge(docs_router)
@@ -1159,7 +3980,10 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .layer(axum_middleware::from_fn(
             middleware::headers::security_headers_middleware,
         ))
-        .layer(axum_middleware::from_fn(rate_limit::rate_limit_middleware))
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit_middleware,
+        ))
         .layer(middleware::cors::create_cors_layer())
         .with_state(state)
 }
@@ -1174,6 +3998,46 @@ mod tests {
         assert_eq!(response.status, "healthy");
     }
 
+    #[test]
+    fn every_documented_protected_route_has_a_scope() {
+        use axum::http::Method;
+        let public = [
+            "/api/v1/health",
+            "/api/v1/time",
+            "/api/v1/control-key",
+            "/api/v1/transparency",
+            "/api/v1/transparency/history",
+            "/api/v1/status",
+            "/.well-known/jwks.json",
+            "/api/v1/auth/register",
+            "/api/v1/auth/login",
+            "/api/v1/auth/refresh",
+            "/api/v1/auth/password-reset/request",
+            "/api/v1/auth/password-reset/confirm",
+            "/api/v1/auth/verify-email",
+        ];
+        for (path, item) in ApiDoc::openapi().paths.paths {
+            if public.contains(&path.as_str()) || rbac::is_scope_exempt(&path) {
+                continue;
+            }
+            let route = path.replace('{', ":").replace('}', "");
+            for method in item.operations.keys() {
+                let method = match method {
+                    utoipa::openapi::PathItemType::Get => Method::GET,
+                    utoipa::openapi::PathItemType::Post => Method::POST,
+                    utoipa::openapi::PathItemType::Put => Method::PUT,
+                    utoipa::openapi::PathItemType::Delete => Method::DELETE,
+                    utoipa::openapi::PathItemType::Patch => Method::PATCH,
+                    _ => panic!("unexpected method on {path}"),
+                };
+                assert!(
+                    rbac::required_scope(&method, &route).is_some(),
+                    "{method} {route} has no scope in the permission matrix"
+                );
+            }
+        }
+    }
+
     #[test]
     fn connect_only_completion_delay_respects_payload_duration() {
         let value = serde_json::json!({"duration_seconds": 300});
@@ -0,0 +1,206 @@
+/// Login lockout after repeated failed attempts
+///
+/// Failed logins are counted per submitted username, whether or not the
+/// account exists, and per client IP.  Once either count reaches its
+/// threshold within the failure window, further logins for that username or
+/// from that IP are refused with a `429 account_locked` error carrying
+/// `retry_after` until the lock expires.  An unknown username therefore
+/// fails and locks exactly like an existing one:
+///
+/// - `LOGIN_LOCKOUT_USER_THRESHOLD` (default `5`): failures per username
+/// - `LOGIN_LOCKOUT_IP_THRESHOLD` (default `20`): failures per client IP
+/// - `LOGIN_LOCKOUT_WINDOW_SECS` (default `900`): failures older than this
+///   stop counting
+/// - `LOGIN_LOCKOUT_BASE_SECS` (default `60`): first lock duration; each
+///   further lock doubles it
+/// - `LOGIN_LOCKOUT_MAX_SECS` (default `3600`): longest lock
+///
+/// The doubling restarts after a successful login, an admin unlock, or a day
+/// without failures.  A threshold of `0` disables that kind of lock.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+pub const DEFAULT_USER_THRESHOLD: u32 = 5;
+pub const DEFAULT_IP_THRESHOLD: u32 = 20;
+pub const DEFAULT_WINDOW_SECS: u64 = 900;
+pub const DEFAULT_BASE_LOCK_SECS: u64 = 60;
+pub const DEFAULT_MAX_LOCK_SECS: u64 = 3600;
+
+/// What a failure counter and lock apply to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LockoutScope {
+    /// An account, keyed by the username logins submit
+    User,
+    /// A client IP address
+    Ip,
+}
+
+impl LockoutScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LockoutScope::User => "user",
+            LockoutScope::Ip => "ip",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "user" => Some(LockoutScope::User),
+            "ip" => Some(LockoutScope::Ip),
+            _ => None,
+        }
+    }
+}
+
+/// Thresholds and lock durations.  A `None` threshold disables that scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub user_threshold: Option<u32>,
+    pub ip_threshold: Option<u32>,
+    pub window_secs: u64,
+    pub base_lock_secs: u64,
+    pub max_lock_secs: u64,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            user_threshold: Some(DEFAULT_USER_THRESHOLD),
+            ip_threshold: Some(DEFAULT_IP_THRESHOLD),
+            window_secs: DEFAULT_WINDOW_SECS,
+            base_lock_secs: DEFAULT_BASE_LOCK_SECS,
+            max_lock_secs: DEFAULT_MAX_LOCK_SECS,
+        }
+    }
+}
+
+impl LockoutPolicy {
+    /// Load from the `LOGIN_LOCKOUT_*` variables.
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok();
+        Self::parse(
+            var("LOGIN_LOCKOUT_USER_THRESHOLD").as_deref(),
+            var("LOGIN_LOCKOUT_IP_THRESHOLD").as_deref(),
+            var("LOGIN_LOCKOUT_WINDOW_SECS").as_deref(),
+            var("LOGIN_LOCKOUT_BASE_SECS").as_deref(),
+            var("LOGIN_LOCKOUT_MAX_SECS").as_deref(),
+        )
+    }
+
+    /// Parse settings; unset or malformed values keep their default and a
+    /// `0` threshold disables that scope.
+    pub fn parse(
+        user_threshold: Option<&str>,
+        ip_threshold: Option<&str>,
+        window: Option<&str>,
+        base_lock: Option<&str>,
+        max_lock: Option<&str>,
+    ) -> Self {
+        fn number<T: std::str::FromStr>(value: Option<&str>, default: T) -> T {
+            value
+                .and_then(|raw| raw.trim().parse().ok())
+                .unwrap_or(default)
+        }
+
+        let base_lock_secs = number(base_lock, DEFAULT_BASE_LOCK_SECS).max(1);
+        Self {
+            user_threshold: Some(number(user_threshold, DEFAULT_USER_THRESHOLD)).filter(|n| *n > 0),
+            ip_threshold: Some(number(ip_threshold, DEFAULT_IP_THRESHOLD)).filter(|n| *n > 0),
+            window_secs: number(window, DEFAULT_WINDOW_SECS).max(1),
+            base_lock_secs,
+            max_lock_secs: number(max_lock, DEFAULT_MAX_LOCK_SECS).max(base_lock_secs),
+        }
+    }
+
+    pub fn threshold(&self, scope: LockoutScope) -> Option<u32> {
+        match scope {
+            LockoutScope::User => self.user_threshold,
+            LockoutScope::Ip => self.ip_threshold,
+        }
+    }
+
+    /// Duration of the lock after `previous_locks` earlier ones: the base
+    /// duration doubled per earlier lock, capped at the maximum.
+    pub fn lock_secs(&self, previous_locks: u32) -> u64 {
+        2u64.checked_pow(previous_locks)
+            .and_then(|factor| self.base_lock_secs.checked_mul(factor))
+            .map_or(self.max_lock_secs, |secs| secs.min(self.max_lock_secs))
+    }
+}
+
+/// A lock or failure count as listed to admins.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LoginLockout {
+    pub scope: LockoutScope,
+    /// Username or IP address
+    pub subject: String,
+    /// Failures within the current window
+    pub failed_attempts: i32,
+    /// Locks applied since the backoff last restarted
+    pub lockout_count: i32,
+    pub last_failure_at: String,
+    /// Set while the subject is locked
+    pub locked_until: Option<String>,
+}
+
+/// The `429` returned while `scope` is locked for `retry_after` more seconds.
+pub fn locked_error(scope: LockoutScope, retry_after: u64) -> ApiError {
+    let subject = match scope {
+        LockoutScope::User => "account",
+        LockoutScope::Ip => "address",
+    };
+    ApiError::new(
+        "account_locked",
+        format!(
+            "Too many failed login attempts for this {}; retry in {} seconds",
+            subject, retry_after
+        ),
+        axum::http::StatusCode::TOO_MANY_REQUESTS,
+    )
+    .with_details(json!({ "scope": scope, "retry_after": retry_after }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_parse_defaults_and_disabling() {
+        assert_eq!(
+            LockoutPolicy::parse(None, None, None, None, None),
+            LockoutPolicy::default()
+        );
+
+        let policy =
+            LockoutPolicy::parse(Some("3"), Some("0"), Some("bogus"), Some("30"), Some("10"));
+        assert_eq!(policy.user_threshold, Some(3));
+        assert_eq!(policy.ip_threshold, None);
+        assert_eq!(policy.window_secs, DEFAULT_WINDOW_SECS);
+        // The maximum never drops below the base duration.
+        assert_eq!(policy.max_lock_secs, 30);
+    }
+
+    #[test]
+    fn test_lock_duration_doubles_up_to_max() {
+        let policy = LockoutPolicy::default();
+        assert_eq!(policy.lock_secs(0), 60);
+        assert_eq!(policy.lock_secs(1), 120);
+        assert_eq!(policy.lock_secs(5), 1920);
+        assert_eq!(policy.lock_secs(6), 3600);
+        assert_eq!(policy.lock_secs(200), 3600);
+    }
+
+    #[test]
+    fn test_locked_error_carries_retry_after() {
+        let err = locked_error(LockoutScope::User, 42);
+        assert_eq!(err.status_code, axum::http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(err.error, "account_locked");
+        let details = err.details.unwrap();
+        assert_eq!(details["retry_after"], 42);
+        assert_eq!(details["scope"], "user");
+    }
+}
@@ -1,44 +1,80 @@
 use anyhow::Result;
-use api_server::{create_router, db, rate_limit, state::AppState};
+use api_server::middleware::metrics::observe_sweep_duration;
+use api_server::{create_router, db, deployment_profile, migrations, rate_limit, state::AppState};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::{info, Level};
+use std::time::{Duration, Instant};
+use tracing::info;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load environment variables from .env file if present
     dotenvy::dotenv().ok();
 
-    // Initialize tracing
-    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+    // Initialize tracing (and OTLP export when configured)
+    let tracing_guard = api_server::otel::init_tracing()?;
+    if tracing_guard.exporting() {
+        info!("Exporting traces over OTLP");
+    }
+
+    // `api-server migrate [--dry-run | --status]` migrates out of band and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        return run_migrate_command(&args[1..]).await;
+    }
 
     info!("Starting Ambient AI VCP API Server");
     info!("Version: {}", env!("CARGO_PKG_VERSION"));
+    if let Some(profile) = deployment_profile::profile_from_env() {
+        info!(%profile, "Using deployment profile defaults");
+    }
 
     // Validate secret configuration before startup proceeds in production
     api_server::auth::validate_hash_pepper_configuration()?;
+    api_server::auth::validate_control_key_configuration()?;
 
     // Initialize database connection
-    let pool = if let Some(db_config) = db::DatabaseConfig::from_env()? {
+    let (pool, read_replicas) = if let Some(db_config) = db::DatabaseConfig::from_env()? {
         let pool = db::create_pool(&db_config).await?;
 
-        // Run database migrations
-        db::run_migrations(&pool).await?;
+        // Run database migrations, or only check the schema when they run
+        // out of band with `api-server migrate`
+        if migrations::migrate_on_startup() {
+            db::run_migrations(&pool).await?;
+        } else {
+            let status = migrations::verify_schema(&pool).await?;
+            info!(
+                version = status.current_version,
+                "Database schema matches this release"
+            );
+        }
+        db::check_required_indexes(&pool).await;
 
         // Verify database connection
         db::health_check(&pool).await?;
         info!("Database connection established and verified");
-        Some(pool)
+
+        // Connect read replicas; heavy reads use them once they are within the lag limit
+        let read_replicas = match db::ReplicaConfig::from_env()? {
+            Some(replica_config) => {
+                let replicas = Arc::new(db::ReadReplicas::connect(&replica_config, &db_config)?);
+                let usable = replicas.check_lag().await;
+                info!(
+                    replicas = replicas.len(),
+                    usable,
+                    max_lag_secs = replica_config.max_lag.as_secs(),
+                    "Read replicas configured"
+                );
+                Some((replicas, replica_config.check_interval))
+            }
+            None => None,
+        };
+        (Some(pool), read_replicas)
     } else {
         tracing::warn!("Starting in stateless mode (no database configured)");
-        None
+        (None, None)
     };
 
-    // Start rate limiter cleanup task
-    rate_limit::start_cleanup_task().await;
-    info!("Rate limiter cleanup task started");
-
     // Get port from environment or use default
     let port = std::env::var("PORT")
         .ok()
@@ -48,8 +84,81 @@ async fn main() -> Result<()> {
     let addr = format!("0.0.0.0:{}", port);
 
     // Create application state
-    let auth_config = api_server::auth::AuthConfig::from_env()?;
-    let state = Arc::new(AppState::new(pool).with_auth_config(auth_config));
+    let mut auth_config = api_server::auth::AuthConfig::from_env()?;
+    match &pool {
+        Some(pool) => auth_config = auth_config.with_key_store(pool.clone()).await?,
+        None => tracing::warn!(
+            "No database; JWT signing keys are held in memory and tokens do not survive a restart"
+        ),
+    }
+    let notifier = api_server::notifier::notifier_from_env()?;
+    info!("Notification backend: {}", notifier.backend_name());
+    let dispatch_config = api_server::notifier::DispatchConfig::from_env();
+    let outbox_poll_interval = dispatch_config.outbox_poll_interval;
+    let notifications =
+        api_server::notifier::NotificationDispatcher::start(notifier, dispatch_config);
+    let key_rotation_config = auth_config.clone();
+    let artifact_store = api_server::artifacts::artifact_store_from_env()?;
+    info!("Artifact store backend: {}", artifact_store.backend_name());
+    let mut app_state = AppState::new(pool)
+        .with_auth_config(auth_config)
+        .with_notifications(notifications)
+        .with_artifact_store(artifact_store);
+    if let Some((replicas, _)) = &read_replicas {
+        app_state = app_state.with_read_replicas(Arc::clone(replicas));
+    }
+    let exporters = api_server::telemetry_export::exporters_from_env()?;
+    if !exporters.is_empty() {
+        let names: Vec<&str> = exporters.iter().map(|exporter| exporter.name()).collect();
+        info!(exporters = ?names, "Telemetry export enabled");
+        app_state =
+            app_state.with_telemetry_export(api_server::telemetry_export::TelemetryExport::start(
+                exporters,
+                api_server::telemetry_export::ExportConfig::from_env(),
+            ));
+    }
+    let state = Arc::new(app_state);
+
+    // Start rate limiter cleanup task
+    rate_limit::start_cleanup_task(state.rate_limiter().clone());
+    info!("Rate limiter cleanup task started");
+
+    // Start JWT key rotation — moves the signing key ring to each new
+    // JWT_KEY_ROTATION_SECS period.  Replicas share each period's key through
+    // the jwt_signing_keys table, so this only has to notice the period
+    // change.
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            ticker.tick().await;
+            match key_rotation_config.rotate_signing_keys().await {
+                Ok(Some(kid)) => info!(kid, "Rotated JWT signing key"),
+                Ok(None) => {}
+                Err(err) => tracing::error!("JWT signing key rotation failed: {err}"),
+            }
+        }
+    });
+    info!("JWT key rotation task started");
+
+    // Start notification outbox dispatcher — delivers notifications that
+    // state changes committed to the outbox, retrying failed deliveries.
+    let outbox_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(outbox_poll_interval);
+        loop {
+            ticker.tick().await;
+            let started = Instant::now();
+            let result = outbox_state.drain_notification_outbox().await;
+            observe_sweep_duration("notification_outbox", started.elapsed());
+            if let Err(err) = result {
+                tracing::error!("Notification outbox drain failed: {err}");
+            }
+        }
+    });
+    info!(
+        outbox_poll_secs = outbox_poll_interval.as_secs(),
+        "Notification outbox dispatcher started"
+    );
 
     let monitor_interval_seconds = AppState::connect_session_monitor_interval_seconds();
     let monitor_state = Arc::clone(&state);
@@ -57,7 +166,10 @@ async fn main() -> Result<()> {
         let mut ticker = tokio::time::interval(Duration::from_secs(monitor_interval_seconds));
         loop {
             ticker.tick().await;
-            match monitor_state.sweep_connect_sessions().await {
+            let started = Instant::now();
+            let result = monitor_state.sweep_connect_sessions().await;
+            observe_sweep_duration("connect_sessions", started.elapsed());
+            match result {
                 Ok(swept) if swept > 0 => {
                     info!(swept, "Connect session monitor swept stale sessions");
                 }
@@ -75,17 +187,20 @@ async fn main() -> Result<()> {
 
     // Start node offline sweep — marks nodes as offline when they have not
     // sent a heartbeat within NODE_HEARTBEAT_TIMEOUT_MINUTES (default: 5).
-    let node_sweep_interval_seconds: u64 = std::env::var("NODE_OFFLINE_SWEEP_INTERVAL_SECONDS")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .filter(|v: &u64| *v > 0)
-        .unwrap_or(60);
+    let node_sweep_interval_seconds: u64 =
+        deployment_profile::setting("NODE_OFFLINE_SWEEP_INTERVAL_SECONDS")
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(60);
     let node_sweep_state = Arc::clone(&state);
     tokio::spawn(async move {
         let mut ticker = tokio::time::interval(Duration::from_secs(node_sweep_interval_seconds));
         loop {
             ticker.tick().await;
-            match node_sweep_state.sweep_offline_nodes().await {
+            let started = Instant::now();
+            let result = node_sweep_state.sweep_offline_nodes().await;
+            observe_sweep_duration("node_offline", started.elapsed());
+            match result {
                 Ok(swept) if swept > 0 => {
                     info!(swept, "Node offline sweep marked nodes offline");
                 }
@@ -110,7 +225,10 @@ async fn main() -> Result<()> {
         let mut ticker = tokio::time::interval(Duration::from_secs(node_sweep_interval_seconds));
         loop {
             ticker.tick().await;
-            match node_removal_state.purge_stale_offline_nodes().await {
+            let started = Instant::now();
+            let result = node_removal_state.purge_stale_offline_nodes().await;
+            observe_sweep_duration("node_removal", started.elapsed());
+            match result {
                 Ok(purged) if purged > 0 => {
                     info!(purged, "Node removal sweep purged stale offline nodes");
                 }
@@ -123,9 +241,262 @@ async fn main() -> Result<()> {
     });
     info!("Node removal sweep task started");
 
+    // Start task retry sweep — fails timed-out attempts of tasks that have a
+    // retry policy and re-queues tasks whose retry backoff has elapsed.
+    let task_retry_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(monitor_interval_seconds));
+        loop {
+            ticker.tick().await;
+            let started = Instant::now();
+            let result = task_retry_state.sweep_task_retries().await;
+            observe_sweep_duration("task_retry", started.elapsed());
+            match result {
+                Ok(handled) if handled > 0 => {
+                    info!(
+                        handled,
+                        "Task retry sweep handled failed or re-queued tasks"
+                    );
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    tracing::error!("Task retry sweep failed: {err}");
+                }
+            }
+        }
+    });
+    info!(monitor_interval_seconds, "Task retry sweep task started");
+
+    // Start task starvation sweep — relaxes soft constraints on long-pending
+    // tasks, flags them as starving, and finally marks them unschedulable.
+    let task_starvation_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(monitor_interval_seconds));
+        loop {
+            ticker.tick().await;
+            let started = Instant::now();
+            let result = task_starvation_state.sweep_starving_tasks().await;
+            observe_sweep_duration("task_starvation", started.elapsed());
+            match result {
+                Ok(aged) if aged > 0 => {
+                    info!(aged, "Task starvation sweep aged long-pending tasks");
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    tracing::error!("Task starvation sweep failed: {err}");
+                }
+            }
+        }
+    });
+    info!(
+        monitor_interval_seconds,
+        "Task starvation sweep task started"
+    );
+
+    // Start retention job — purges disconnected assignments, ended connect
+    // sessions and heartbeat events past their retention windows.
+    let retention_interval_seconds: u64 = std::env::var("RETENTION_SWEEP_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &u64| *v > 0)
+        .unwrap_or(3600);
+    let retention_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(retention_interval_seconds));
+        loop {
+            ticker.tick().await;
+            let started = Instant::now();
+            let result = retention_state.run_retention(None).await;
+            observe_sweep_duration("retention", started.elapsed());
+            if let Err(err) = result {
+                tracing::error!("Retention run failed: {err}");
+            }
+        }
+    });
+    info!(retention_interval_seconds, "Retention job started");
+
+    // Start telemetry rollup job — folds closed buckets of heartbeat samples
+    // into 5m/1h/1d rollups for the node telemetry API.
+    let telemetry_rollup_interval_seconds: u64 = std::env::var("TELEMETRY_ROLLUP_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &u64| *v > 0)
+        .unwrap_or(300);
+    let telemetry_rollup_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticker =
+            tokio::time::interval(Duration::from_secs(telemetry_rollup_interval_seconds));
+        loop {
+            ticker.tick().await;
+            let started = Instant::now();
+            let result = telemetry_rollup_state.run_telemetry_rollup().await;
+            observe_sweep_duration("telemetry_rollup", started.elapsed());
+            match result {
+                Ok(buckets) if buckets > 0 => {
+                    info!(buckets, "Telemetry rollup wrote buckets");
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    tracing::error!("Telemetry rollup failed: {err}");
+                }
+            }
+        }
+    });
+    info!(
+        telemetry_rollup_interval_seconds,
+        "Telemetry rollup job started"
+    );
+
+    // Start replica lag monitor — admits replicas within the lag limit to
+    // serve reads and sends reads back to the primary when they fall behind.
+    if let Some((replicas, check_interval)) = read_replicas {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(check_interval);
+            loop {
+                ticker.tick().await;
+                replicas.check_lag().await;
+            }
+        });
+        info!(
+            check_interval_secs = check_interval.as_secs(),
+            "Replica lag monitor started"
+        );
+    }
+
+    // Start cluster stats snapshot job — records the cluster aggregates for
+    // GET /api/v1/cluster/stats/history.
+    let cluster_stats_interval_seconds = api_server::cluster_history::snapshot_interval_seconds();
+    let cluster_stats_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(cluster_stats_interval_seconds));
+        loop {
+            ticker.tick().await;
+            let started = Instant::now();
+            let result = cluster_stats_state
+                .snapshot_cluster_stats(cluster_stats_interval_seconds / 2)
+                .await;
+            observe_sweep_duration("cluster_stats_snapshot", started.elapsed());
+            if let Err(err) = result {
+                tracing::error!("Cluster stats snapshot failed: {err}");
+            }
+        }
+    });
+    info!(
+        cluster_stats_interval_seconds,
+        "Cluster stats snapshot job started"
+    );
+
+    // Start transparency job — publishes signed cluster aggregates for
+    // GET /api/v1/transparency.
+    let transparency_interval_seconds = api_server::transparency::snapshot_interval_seconds();
+    let transparency_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(transparency_interval_seconds));
+        loop {
+            ticker.tick().await;
+            let started = Instant::now();
+            let result = transparency_state.publish_transparency_snapshot().await;
+            observe_sweep_duration("transparency_snapshot", started.elapsed());
+            if let Err(err) = result {
+                tracing::error!("Transparency snapshot failed: {err}");
+            }
+        }
+    });
+    info!(
+        transparency_interval_seconds,
+        "Transparency snapshot job started"
+    );
+
+    // Start canary job — pushes synthetic tasks and connect sessions
+    // through the fleet when CANARY_USER_ID is set.
+    if let Some(canary_config) = api_server::canary::CanaryConfig::from_env() {
+        let canary_interval_seconds = canary_config.interval.as_secs();
+        let canary_state = Arc::clone(&state);
+        let check_interval = Duration::from_secs(api_server::canary::CHECK_INTERVAL_SECS);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(check_interval);
+            loop {
+                ticker.tick().await;
+                let started = Instant::now();
+                let result = canary_state.run_canaries(&canary_config).await;
+                observe_sweep_duration("canaries", started.elapsed());
+                if let Err(err) = result {
+                    tracing::error!("Canary run failed: {err}");
+                }
+            }
+        });
+        info!(canary_interval_seconds, "Canary job started");
+    }
+
+    // Start throttle override reload — picks up overrides changed through
+    // another replica or expired since the last load.
+    let throttle_reload_interval_seconds: u64 = std::env::var("THROTTLE_OVERRIDE_RELOAD_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &u64| *v > 0)
+        .unwrap_or(30);
+    let throttle_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticker =
+            tokio::time::interval(Duration::from_secs(throttle_reload_interval_seconds));
+        loop {
+            ticker.tick().await;
+            let started = Instant::now();
+            let result = throttle_state.reload_throttle_overrides().await;
+            observe_sweep_duration("throttle_overrides", started.elapsed());
+            if let Err(err) = result {
+                tracing::error!("Throttle override reload failed: {err}");
+            }
+        }
+    });
+    info!(
+        throttle_reload_interval_seconds,
+        "Throttle override reload job started"
+    );
+
+    // Optionally serve the node agent control plane over gRPC.
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_config) = api_server::grpc::GrpcConfig::from_env("0.0.0.0")? {
+        let grpc_state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = api_server::grpc::serve(grpc_state, grpc_config).await {
+                tracing::error!("gRPC listener failed: {err}");
+            }
+        });
+    }
+
     // Create router
     let app = create_router(state);
 
+    // Optionally serve the same router over HTTP/3 and advertise it to TCP
+    // clients via Alt-Svc.
+    #[cfg(feature = "http3")]
+    let app = match api_server::http3::Http3Config::from_env("0.0.0.0", port)? {
+        Some(h3_config) => {
+            let alt_svc = axum::http::HeaderValue::from_str(&api_server::http3::alt_svc_value(
+                h3_config.addr.port(),
+            ))?;
+            let h3_app = app.clone();
+            tokio::spawn(async move {
+                if let Err(err) = api_server::http3::serve(h3_app, h3_config).await {
+                    tracing::error!("HTTP/3 listener failed: {err}");
+                }
+            });
+            app.layer(axum::middleware::map_response(
+                move |mut response: axum::response::Response| {
+                    let alt_svc = alt_svc.clone();
+                    async move {
+                        response
+                            .headers_mut()
+                            .insert(axum::http::header::ALT_SVC, alt_svc);
+                        response
+                    }
+                },
+            ))
+        }
+        None => app,
+    };
+
     // Start server
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     info!("API Server listening on http://{}", addr);
@@ -141,3 +512,55 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// `api-server migrate`: apply pending migrations under the migration lock.
+/// `--dry-run` only validates them; `--status` only reports.
+async fn run_migrate_command(args: &[String]) -> Result<()> {
+    let (dry_run, status_only) = match args {
+        [] => (false, false),
+        [flag] if flag == "--dry-run" => (true, false),
+        [flag] if flag == "--status" => (false, true),
+        _ => anyhow::bail!("usage: api-server migrate [--dry-run | --status]"),
+    };
+    let Some(db_config) = db::DatabaseConfig::from_env()? else {
+        anyhow::bail!("DATABASE_URL is not set; nothing to migrate");
+    };
+    let pool = db::create_pool(&db_config).await?;
+
+    if status_only {
+        let mut conn = pool.acquire().await?;
+        let status = migrations::schema_status(&mut conn).await?;
+        println!(
+            "schema version: {}",
+            status
+                .current_version
+                .map_or_else(|| "none".to_string(), |v| v.to_string())
+        );
+        println!(
+            "release version: {}",
+            status
+                .expected_version
+                .map_or_else(|| "none".to_string(), |v| v.to_string())
+        );
+        println!("pending: {:?}", status.pending);
+        for problem in status.problems() {
+            println!("problem: {problem}");
+        }
+        if !status.problems().is_empty() {
+            anyhow::bail!("schema cannot be migrated by this release");
+        }
+        return Ok(());
+    }
+
+    let report = migrations::migrate(&pool, dry_run, migrations::lock_timeout_from_env()).await?;
+    match (report.pending.is_empty(), report.applied) {
+        (true, _) => println!("schema is up to date"),
+        (false, true) => println!("applied migrations {:?}", report.pending),
+        (false, false) => println!(
+            "dry run passed; {} pending migrations would apply: {:?}",
+            report.pending.len(),
+            report.pending
+        ),
+    }
+    Ok(())
+}
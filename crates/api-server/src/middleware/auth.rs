@@ -2,9 +2,10 @@
 
 use crate::auth::{hash_api_key, Claims};
 use crate::error::ApiError;
+use crate::rbac;
 use axum::{
     body::Body,
-    extract::{Request, State},
+    extract::{MatchedPath, Request, State},
     http::{header, HeaderMap},
     middleware::Next,
     response::Response,
@@ -20,6 +21,98 @@ pub async fn jwt_auth_middleware(
     mut request: Request<Body>,
     next: Next,
 ) -> Result<Response, ApiError> {
+    let claims = claims_from_bearer(&state, &headers)?;
+    request.extensions_mut().insert(claims);
+    Ok(next.run(request).await)
+}
+
+/// API key auth middleware.
+/// Accepts `X-API-Key: <key>` and resolves the associated user and scopes.
+pub async fn api_key_auth_middleware(
+    State(state): State<Arc<crate::state::AppState>>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let key = api_key_header(request.headers())
+        .ok_or_else(|| ApiError::unauthorized("Missing X-API-Key header"))?;
+    let claims = claims_from_api_key(&state, &key).await?;
+    request.extensions_mut().insert(claims);
+    Ok(next.run(request).await)
+}
+
+/// Accept either an `X-API-Key` header or a `Bearer` JWT.
+pub async fn credential_auth_middleware(
+    State(state): State<Arc<crate::state::AppState>>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let claims = claims_from_headers(&state, request.headers()).await?;
+    request.extensions_mut().insert(claims);
+    Ok(next.run(request).await)
+}
+
+/// Claims for an `X-API-Key` header if present, else a `Bearer` JWT.
+pub async fn claims_from_headers(
+    state: &crate::state::AppState,
+    headers: &HeaderMap,
+) -> Result<Claims, ApiError> {
+    match api_key_header(headers) {
+        Some(key) => claims_from_api_key(state, &key).await,
+        None => claims_from_bearer(state, headers),
+    }
+}
+
+/// Enforce the route's scope from [`rbac::required_scope`].
+///
+/// Must run after an authentication middleware.  Routes missing from the
+/// matrix are denied so new endpoints cannot ship unguarded.
+pub async fn require_scope_middleware(
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let claims = request
+        .extensions()
+        .get::<Claims>()
+        .ok_or_else(|| ApiError::unauthorized("Authentication required"))?;
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or_else(|| request.uri().path());
+
+    if rbac::is_scope_exempt(route) {
+        return Ok(next.run(request).await);
+    }
+
+    let Some(required) = rbac::required_scope(request.method(), route) else {
+        warn!("No scope mapped for {} {}", request.method(), route);
+        return Err(ApiError::forbidden(
+            "Route is not covered by the permission matrix",
+        ));
+    };
+
+    if let Err(denied) = rbac::authorize(&claims.role, &claims.granted_scopes(), required) {
+        warn!(
+            "Scope deny user={} role={} required={}",
+            claims.username, claims.role, required
+        );
+        return Err(denied);
+    }
+
+    Ok(next.run(request).await)
+}
+
+fn api_key_header(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+fn claims_from_bearer(
+    state: &crate::state::AppState,
+    headers: &HeaderMap,
+) -> Result<Claims, ApiError> {
     let auth_header = headers
         .get(header::AUTHORIZATION)
         .and_then(|h| h.to_str().ok())
@@ -39,32 +132,23 @@ pub async fn jwt_auth_middleware(
         claims.username, claims.role
     );
 
-    request.extensions_mut().insert(claims);
-    Ok(next.run(request).await)
+    Ok(claims)
 }
 
-/// API key auth middleware.
-/// Accepts `X-API-Key: <key>` and resolves the associated user and scopes.
-pub async fn api_key_auth_middleware(
-    State(state): State<Arc<crate::state::AppState>>,
-    mut request: Request<Body>,
-    next: Next,
-) -> Result<Response, ApiError> {
+/// Resolve an API key to claims carrying the key's scopes.
+async fn claims_from_api_key(
+    state: &crate::state::AppState,
+    key: &str,
+) -> Result<Claims, ApiError> {
     let Some(db) = &state.db else {
         return Err(ApiError::service_unavailable("Database not configured"));
     };
 
-    let key = request
-        .headers()
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-        .ok_or_else(|| ApiError::unauthorized("Missing X-API-Key header"))?;
-
     let key_hash = hash_api_key(key);
 
     let row = sqlx::query(
         r#"
-        SELECT u.user_id, u.username, u.role, ak.scopes, ak.revoked_at, ak.expires_at
+        SELECT ak.key_id, u.user_id, u.username, u.role, ak.scopes, ak.revoked_at, ak.expires_at
         FROM api_keys ak
         JOIN users u ON ak.user_id = u.user_id
         WHERE ak.key_hash = $1
@@ -87,26 +171,26 @@ pub async fn api_key_auth_middleware(
         }
     }
 
-    let claims = Claims {
+    // A key without stored scopes gets none, never the role's defaults.
+    let scopes: Vec<String> = row.try_get("scopes").unwrap_or_default();
+
+    sqlx::query("UPDATE api_keys SET last_used_at = NOW() WHERE key_id = $1")
+        .bind(row.get::<uuid::Uuid, _>("key_id"))
+        .execute(db)
+        .await?;
+
+    Ok(Claims {
         sub: row.get::<uuid::Uuid, _>("user_id").to_string(),
         username: row.get("username"),
         role: row.get("role"),
         exp: (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp(),
         iat: chrono::Utc::now().timestamp(),
-    };
-
-    let scopes: Vec<String> = row.try_get("scopes").unwrap_or_default();
-
-    request.extensions_mut().insert(claims);
-    request.extensions_mut().insert(ApiScopes(scopes));
-
-    Ok(next.run(request).await)
+        org_id: None,
+        org_role: None,
+        scopes: Some(scopes),
+    })
 }
 
-/// Scoped permission set extracted from JWT/API key.
-#[derive(Debug, Clone)]
-pub struct ApiScopes(pub Vec<String>);
-
 /// Require one of the configured roles for a route.
 pub async fn require_admin_middleware(
     request: Request<Body>,
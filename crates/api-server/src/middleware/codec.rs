@@ -0,0 +1,217 @@
+/// Control-plane body negotiation
+///
+/// Lets nodes on constrained backhauls exchange MessagePack instead of JSON
+/// and zstd-compress bodies in either direction.  Handlers keep working with
+/// JSON: MessagePack requests are transcoded before extraction, and JSON
+/// responses are transcoded and compressed on the way out according to the
+/// client's `Accept` and `Accept-Encoding` headers.
+use crate::error::ApiError;
+use ambient_node::codec::{
+    header_accepts, zstd_compress, zstd_decompress, BodyFormat, CONTENT_TYPE_JSON,
+    CONTENT_TYPE_MSGPACK, DEFAULT_ZSTD_LEVEL, ENCODING_ZSTD, MAX_DECODED_BYTES, MIN_COMPRESS_BYTES,
+};
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Decode MessagePack / zstd request bodies and encode responses to match
+/// what the client accepts.
+pub async fn control_plane_codec_middleware(
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let accept_msgpack = header_str(request.headers(), header::ACCEPT)
+        .is_some_and(|accept| header_accepts(accept, CONTENT_TYPE_MSGPACK));
+    let accept_zstd = header_str(request.headers(), header::ACCEPT_ENCODING)
+        .is_some_and(|accept| header_accepts(accept, ENCODING_ZSTD));
+
+    let request = decode_request(request).await?;
+    let response = next.run(request).await;
+
+    if !accept_msgpack && !accept_zstd {
+        return Ok(response);
+    }
+    Ok(encode_response(response, accept_msgpack, accept_zstd).await)
+}
+
+async fn decode_request(request: Request<Body>) -> Result<Request<Body>, ApiError> {
+    let zstd = match header_str(request.headers(), header::CONTENT_ENCODING).map(str::trim) {
+        None | Some("") | Some("identity") => false,
+        Some(encoding) if encoding.eq_ignore_ascii_case(ENCODING_ZSTD) => true,
+        Some(other) => {
+            return Err(ApiError::new(
+                "unsupported_media_type",
+                format!("Unsupported Content-Encoding '{other}'"),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ))
+        }
+    };
+    let msgpack = header_str(request.headers(), header::CONTENT_TYPE)
+        .and_then(BodyFormat::from_content_type)
+        == Some(BodyFormat::MsgPack);
+
+    if !zstd && !msgpack {
+        return Ok(request);
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let mut bytes = to_bytes(body, MAX_DECODED_BYTES)
+        .await
+        .map_err(|_| ApiError::bad_request("Request body too large"))?
+        .to_vec();
+
+    if zstd {
+        bytes = zstd_decompress(&bytes, MAX_DECODED_BYTES)
+            .map_err(|_| ApiError::bad_request("Invalid or oversized zstd request body"))?;
+        parts.headers.remove(header::CONTENT_ENCODING);
+    }
+    if msgpack {
+        let value: serde_json::Value = BodyFormat::MsgPack
+            .deserialize(&bytes)
+            .map_err(|_| ApiError::bad_request("Invalid MessagePack request body"))?;
+        bytes = serde_json::to_vec(&value)
+            .map_err(|_| ApiError::bad_request("Invalid MessagePack request body"))?;
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(CONTENT_TYPE_JSON),
+        );
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    Ok(Request::from_parts(parts, Body::from(bytes)))
+}
+
+async fn encode_response(response: Response, msgpack: bool, zstd: bool) -> Response {
+    // Streams (SSE) and binary downloads pass through untouched.
+    let is_json = header_str(response.headers(), header::CONTENT_TYPE)
+        .and_then(BodyFormat::from_content_type)
+        == Some(BodyFormat::Json);
+    if !is_json || response.headers().contains_key(header::CONTENT_ENCODING) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return ApiError::internal_error("Failed to encode response").into_response();
+    };
+    let mut bytes = bytes.to_vec();
+
+    if msgpack {
+        let transcoded = BodyFormat::Json
+            .deserialize::<serde_json::Value>(&bytes)
+            .and_then(|value| BodyFormat::MsgPack.serialize(&value));
+        if let Ok(transcoded) = transcoded {
+            bytes = transcoded;
+            parts.headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(CONTENT_TYPE_MSGPACK),
+            );
+        }
+    }
+    if zstd && bytes.len() >= MIN_COMPRESS_BYTES {
+        if let Ok(compressed) = zstd_compress(&bytes, DEFAULT_ZSTD_LEVEL) {
+            bytes = compressed;
+            parts.headers.insert(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(ENCODING_ZSTD),
+            );
+        }
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.append(
+        header::VARY,
+        HeaderValue::from_static("accept, accept-encoding"),
+    );
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::control_plane_codec_middleware;
+    use ambient_node::codec::{decode_body, ControlPlaneCodec};
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+        middleware,
+        routing::{get, post},
+        Json, Router,
+    };
+    use serde_json::{json, Value};
+    use tower::util::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/echo",
+                post(|Json(body): Json<Value>| async move { Json(body) }),
+            )
+            .route("/text", get(|| async { "plain" }))
+            .layer(middleware::from_fn(control_plane_codec_middleware))
+    }
+
+    #[tokio::test]
+    async fn test_msgpack_zstd_round_trip() {
+        let payload = json!({"node_id": "node-1", "state": {"samples": vec![42.0; 128]}});
+        let codec = ControlPlaneCodec::compact();
+        let encoded = codec.encode(&payload).unwrap();
+
+        let mut request = Request::post("/echo")
+            .header(header::CONTENT_TYPE, encoded.content_type)
+            .header(header::CONTENT_ENCODING, encoded.content_encoding.unwrap());
+        for (name, value) in codec.accept_headers() {
+            request = request.header(name, value);
+        }
+        let response = app()
+            .oneshot(request.body(Body::from(encoded.bytes)).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let content_type = response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let encoding = response.headers()[header::CONTENT_ENCODING]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let decoded: Value = decode_body(&body, Some(&content_type), Some(&encoding)).unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    #[tokio::test]
+    async fn test_plain_requests_pass_through() {
+        let response = app()
+            .oneshot(
+                Request::get("/text")
+                    .header(header::ACCEPT_ENCODING, "zstd")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+
+        let response = app()
+            .oneshot(
+                Request::post("/echo")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::CONTENT_ENCODING, "br")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
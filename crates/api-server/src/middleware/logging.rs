@@ -1,17 +1,25 @@
 /// Logging and request tracing middleware
 ///
-/// Adds structured logging with request IDs and timing
-use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+/// Adds structured logging with request IDs, timing and W3C trace context
+use crate::otel;
+use ambient_node::{TraceParent, TRACEPARENT_HEADER};
+use axum::{body::Body, extract::Request, http::HeaderValue, middleware::Next, response::Response};
 use std::time::Instant;
-use tracing::{info, info_span, Instrument};
+use tracing::{field, info, info_span, Instrument};
 use uuid::Uuid;
 
-/// Add request ID and tracing span to request
+/// Add request ID and tracing span to request.  The span continues the
+/// caller's `traceparent`, and the response returns the one to propagate.
 pub async fn request_tracing_middleware(mut request: Request<Body>, next: Next) -> Response {
     let start = Instant::now();
     let request_id = Uuid::new_v4().to_string();
     let method = request.method().clone();
     let uri = request.uri().clone();
+    let incoming = request
+        .headers()
+        .get(TRACEPARENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceParent::parse);
 
     // Store request ID in extensions
     request.extensions_mut().insert(request_id.clone());
@@ -22,10 +30,16 @@ pub async fn request_tracing_middleware(mut request: Request<Body>, next: Next)
         request_id = %request_id,
         method = %method,
         uri = %uri.path(),
+        trace_id = field::Empty,
     );
+    let trace = otel::continue_trace(&span, incoming);
+    span.record("trace_id", field::display(trace.trace_id_hex()));
 
     async move {
-        let response = next.run(request).await;
+        let mut response = otel::scope_request_trace(trace, next.run(request)).await;
+        if let Ok(value) = HeaderValue::from_str(&trace.to_string()) {
+            response.headers_mut().insert(TRACEPARENT_HEADER, value);
+        }
         let duration = start.elapsed();
         let status = response.status();
 
@@ -1,9 +1,10 @@
 /// Prometheus metrics middleware and exporter
 ///
 /// Exposes /metrics endpoint and tracks per-route metrics
+use crate::state::AppState;
 use axum::{
     body::Body,
-    extract::Request,
+    extract::{Request, State},
     http::StatusCode,
     middleware::Next,
     response::{IntoResponse, Response},
@@ -12,10 +13,13 @@ use axum::{
 };
 use lazy_static::lazy_static;
 use prometheus::{
-    register_histogram_vec, register_int_counter_vec, Encoder, HistogramVec, IntCounterVec,
-    TextEncoder,
+    register_gauge_vec, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge_vec, Encoder, GaugeVec, HistogramVec, IntCounter, IntCounterVec,
+    IntGaugeVec, TextEncoder,
 };
-use std::time::Instant;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::error;
 
 lazy_static! {
@@ -42,6 +46,239 @@ lazy_static! {
         &["method", "endpoint", "status"]
     )
     .unwrap();
+
+    /// Time tasks spend pending before their nodes are attached, per
+    /// requester kind
+    static ref TASK_QUEUE_WAIT_SECONDS: HistogramVec = register_histogram_vec!(
+        "task_queue_wait_seconds",
+        "Seconds from task submission (or re-queue) until it starts running",
+        &["kind"],
+        vec![1.0, 5.0, 15.0, 60.0, 300.0, 900.0, 3600.0, 14400.0]
+    )
+    .unwrap();
+
+    /// Task starts deferred because the requester hit its running-task cap
+    static ref TASK_FAIR_SHARE_DEFERRALS: IntCounterVec = register_int_counter_vec!(
+        "task_fair_share_deferrals",
+        "Task starts deferred by the per-requester concurrency cap",
+        &["kind"]
+    )
+    .unwrap();
+
+    /// Task status transitions that lost a compare-and-swap race and re-read the task
+    static ref TASK_VERSION_CONFLICTS: IntCounterVec = register_int_counter_vec!(
+        "task_version_conflicts",
+        "Task status transitions retried because another writer changed the task first",
+        &["transition"]
+    )
+    .unwrap();
+
+    /// Records handed to each telemetry exporter, by outcome
+    static ref TELEMETRY_EXPORT_RECORDS: IntCounterVec = register_int_counter_vec!(
+        "telemetry_export_records",
+        "Records sent to a telemetry exporter (result=exported or failed)",
+        &["exporter", "result"]
+    )
+    .unwrap();
+
+    /// Records dropped because the telemetry export queue was full
+    static ref TELEMETRY_EXPORT_DROPPED: IntCounter = register_int_counter!(
+        "telemetry_export_dropped",
+        "Telemetry records dropped because the export queue was full"
+    )
+    .unwrap();
+
+    /// Rows deleted by the retention job, per table
+    static ref RETENTION_ROWS_PURGED: IntCounterVec = register_int_counter_vec!(
+        "retention_rows_purged",
+        "Rows deleted by the retention job",
+        &["table"]
+    )
+    .unwrap();
+
+    /// Rows past their retention window at the last retention run, per table
+    static ref RETENTION_ROWS_ELIGIBLE: IntGaugeVec = register_int_gauge_vec!(
+        "retention_rows_eligible",
+        "Rows past their retention window at the start of the last run",
+        &["table"]
+    )
+    .unwrap();
+
+    /// Tasks per status, refreshed from the database on each scrape
+    static ref TASKS_BY_STATUS: IntGaugeVec = register_int_gauge_vec!(
+        "scheduler_tasks",
+        "Tasks per status (pending, running, completed, failed)",
+        &["status"]
+    )
+    .unwrap();
+
+    /// Live nodes per status, refreshed from the database on each scrape
+    static ref NODES_BY_STATUS: IntGaugeVec = register_int_gauge_vec!(
+        "scheduler_nodes",
+        "Registered nodes per status (online, offline, draining, ...)",
+        &["status"]
+    )
+    .unwrap();
+
+    /// Connect sessions per status, refreshed from the database on each scrape
+    static ref CONNECT_SESSIONS_BY_STATUS: IntGaugeVec = register_int_gauge_vec!(
+        "connect_sessions",
+        "Connect sessions per status",
+        &["status"]
+    )
+    .unwrap();
+
+    /// Duration of one scheduler assignment pass
+    static ref ASSIGNMENT_DURATION_SECONDS: HistogramVec = register_histogram_vec!(
+        "scheduler_assignment_duration_seconds",
+        "Seconds spent attaching nodes to a task (pass=task) or pending tasks to a node (pass=node)",
+        &["pass"],
+        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
+    )
+    .unwrap();
+
+    /// Replay lag of each read replica at its last check
+    static ref DB_REPLICA_LAG_SECONDS: GaugeVec = register_gauge_vec!(
+        "db_replica_lag_seconds",
+        "Replay lag of a read replica at its last check (-1 when unreachable)",
+        &["replica"]
+    )
+    .unwrap();
+
+    /// Whether each read replica currently serves reads
+    static ref DB_REPLICA_USABLE: IntGaugeVec = register_int_gauge_vec!(
+        "db_replica_usable",
+        "1 while a read replica serves reads, 0 while its reads fall back to the primary",
+        &["replica"]
+    )
+    .unwrap();
+
+    /// Duration of each background sweep run
+    static ref SWEEP_DURATION_SECONDS: HistogramVec = register_histogram_vec!(
+        "sweep_duration_seconds",
+        "Seconds taken by one run of a background sweep job",
+        &["sweep"],
+        vec![0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 15.0, 60.0]
+    )
+    .unwrap();
+
+    /// Latency of each stage of a synthetic canary run
+    static ref CANARY_STAGE_DURATION_SECONDS: HistogramVec = register_histogram_vec!(
+        "canary_stage_duration_seconds",
+        "Seconds a canary run spent reaching each stage since the previous one",
+        &["kind", "stage"],
+        vec![0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0]
+    )
+    .unwrap();
+
+    /// Finished canary runs per outcome
+    static ref CANARY_RUNS: IntCounterVec = register_int_counter_vec!(
+        "canary_runs_total",
+        "Finished synthetic canary runs",
+        &["kind", "result"]
+    )
+    .unwrap();
+}
+
+/// Which scheduler gauge family a status count belongs to.
+#[derive(Debug, Clone, Copy)]
+pub enum PipelineGauge {
+    Tasks,
+    Nodes,
+    ConnectSessions,
+}
+
+/// Replace a gauge family with fresh per-status counts; statuses absent
+/// from `counts` drop out instead of keeping a stale value.
+pub fn set_pipeline_counts(gauge: PipelineGauge, counts: &[(String, i64)]) {
+    let gauge = match gauge {
+        PipelineGauge::Tasks => &*TASKS_BY_STATUS,
+        PipelineGauge::Nodes => &*NODES_BY_STATUS,
+        PipelineGauge::ConnectSessions => &*CONNECT_SESSIONS_BY_STATUS,
+    };
+    gauge.reset();
+    for (status, count) in counts {
+        gauge.with_label_values(&[status]).set(*count);
+    }
+}
+
+/// Record how long one scheduler assignment pass took.
+pub fn observe_assignment_duration(pass: &str, elapsed: Duration) {
+    ASSIGNMENT_DURATION_SECONDS
+        .with_label_values(&[pass])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Record a read replica's lag check; `None` when it did not answer.
+pub fn set_replica_lag(replica: &str, lag_seconds: Option<f64>, usable: bool) {
+    DB_REPLICA_LAG_SECONDS
+        .with_label_values(&[replica])
+        .set(lag_seconds.unwrap_or(-1.0));
+    DB_REPLICA_USABLE
+        .with_label_values(&[replica])
+        .set(i64::from(usable));
+}
+
+/// Record how long one run of a background sweep took.
+pub fn observe_sweep_duration(sweep: &str, elapsed: Duration) {
+    SWEEP_DURATION_SECONDS
+        .with_label_values(&[sweep])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Record how long a task waited in the queue before running.
+pub fn observe_task_queue_wait(requester: &str, seconds: f64) {
+    TASK_QUEUE_WAIT_SECONDS
+        .with_label_values(&[crate::fair_queue::requester_kind(requester)])
+        .observe(seconds.max(0.0));
+}
+
+/// Record that a requester's task was held back by its concurrency cap.
+pub fn record_fair_share_deferral(requester: &str) {
+    TASK_FAIR_SHARE_DEFERRALS
+        .with_label_values(&[crate::fair_queue::requester_kind(requester)])
+        .inc();
+}
+
+/// Record that a task status transition lost a version race.
+pub fn record_task_version_conflict(transition: &str) {
+    TASK_VERSION_CONFLICTS
+        .with_label_values(&[transition])
+        .inc();
+}
+
+/// Record a batch handed to a telemetry exporter.
+pub fn record_telemetry_export(exporter: &str, exported: bool, records: usize) {
+    TELEMETRY_EXPORT_RECORDS
+        .with_label_values(&[exporter, if exported { "exported" } else { "failed" }])
+        .inc_by(records as u64);
+}
+
+/// Record a telemetry record dropped at a full export queue.
+pub fn record_telemetry_export_dropped() {
+    TELEMETRY_EXPORT_DROPPED.inc();
+}
+
+/// Record the outcome of a retention run for one table.
+pub fn record_retention_run(table: &str, eligible: i64, purged: i64) {
+    RETENTION_ROWS_ELIGIBLE
+        .with_label_values(&[table])
+        .set(eligible);
+    RETENTION_ROWS_PURGED
+        .with_label_values(&[table])
+        .inc_by(purged.max(0) as u64);
+}
+
+/// Record a finished canary run and how long each of its stages took.
+pub fn record_canary_run(kind: &str, passed: bool, stage_latencies_ms: &BTreeMap<String, i64>) {
+    for (stage, ms) in stage_latencies_ms {
+        CANARY_STAGE_DURATION_SECONDS
+            .with_label_values(&[kind, stage])
+            .observe(*ms as f64 / 1000.0);
+    }
+    CANARY_RUNS
+        .with_label_values(&[kind, if passed { "passed" } else { "failed" }])
+        .inc();
 }
 
 /// Metrics collection middleware
@@ -97,7 +334,12 @@ fn normalize_endpoint(path: &str) -> String {
 }
 
 /// Metrics endpoint handler
-async fn metrics_handler() -> impl IntoResponse {
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    // Serve the last known pipeline gauges if the refresh fails.
+    if let Err(e) = state.refresh_pipeline_metrics().await {
+        error!("Failed to refresh pipeline metrics: {}", e.message);
+    }
+
     let encoder = TextEncoder::new();
     let metric_families = prometheus::gather();
 
@@ -120,10 +362,7 @@ async fn metrics_handler() -> impl IntoResponse {
 }
 
 /// Create metrics router
-pub fn create_metrics_router<S>() -> Router<S>
-where
-    S: Clone + Send + Sync + 'static,
-{
+pub fn create_metrics_router() -> Router<Arc<AppState>> {
     Router::new().route("/metrics", get(metrics_handler))
 }
 
@@ -131,6 +370,20 @@ where
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_pipeline_counts_replace_previous_statuses() {
+        let counts = |status: &str| NODES_BY_STATUS.with_label_values(&[status]).get();
+        set_pipeline_counts(
+            PipelineGauge::Nodes,
+            &[("online".to_string(), 3), ("draining".to_string(), 1)],
+        );
+        assert_eq!(counts("online"), 3);
+
+        set_pipeline_counts(PipelineGauge::Nodes, &[("online".to_string(), 2)]);
+        assert_eq!(counts("online"), 2);
+        assert_eq!(counts("draining"), 0);
+    }
+
     #[test]
     fn test_normalize_endpoint() {
         assert_eq!(
@@ -1,5 +1,6 @@
 /// Middleware modules for API server
 pub mod auth;
+pub mod codec;
 pub mod cors;
 pub mod headers;
 pub mod logging;
@@ -0,0 +1,445 @@
+//! Schema migrations with a cluster-wide lock and pre-flight checks.
+//!
+//! Replicas booting together all try to migrate.  [`migrate`] serialises
+//! them on a PostgreSQL advisory lock held on a dedicated connection, then
+//! compares the migrations compiled into this binary with
+//! `_sqlx_migrations` before touching anything:
+//!
+//! - a migration recorded as failed, an applied migration whose SQL has
+//!   changed, or applied migrations this binary does not know (the schema
+//!   is ahead of it) stop the run with a diagnostic;
+//! - pending migrations are first run inside one transaction that is rolled
+//!   back (the dry run), so a migration that would fail is found before any
+//!   of them is applied.
+//!
+//! With `DB_MIGRATE_ON_STARTUP=false` the server only checks the schema at
+//! startup ([`verify_schema`]) and migrations run out of band with
+//! `api-server migrate`.
+
+use anyhow::{bail, Context, Result};
+use sqlx::migrate::{Migration, Migrator};
+use sqlx::postgres::PgConnection;
+use sqlx::{ConnectOptions, Connection, Executor, PgPool};
+use std::time::{Duration, Instant};
+
+/// Migrations compiled into this binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Advisory lock key taken for the whole migration run ("vcp_migr").
+pub const MIGRATION_LOCK_KEY: i64 = 0x7663_705f_6d69_6772;
+
+/// How often a replica waiting for the lock retries.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether startup applies pending migrations (`DB_MIGRATE_ON_STARTUP`,
+/// default `true`).  When `false`, startup only runs [`verify_schema`].
+pub fn migrate_on_startup() -> bool {
+    !matches!(
+        std::env::var("DB_MIGRATE_ON_STARTUP")
+            .ok()
+            .map(|raw| raw.trim().to_ascii_lowercase())
+            .as_deref(),
+        Some("0" | "false" | "no")
+    )
+}
+
+/// How long to wait for another replica's migration run
+/// (`DB_MIGRATION_LOCK_TIMEOUT_SECS`, default `300`).
+pub fn lock_timeout_from_env() -> Duration {
+    Duration::from_secs(
+        std::env::var("DB_MIGRATION_LOCK_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(300),
+    )
+}
+
+/// One row of `_sqlx_migrations`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub checksum: Vec<u8>,
+    pub success: bool,
+}
+
+/// How the database schema compares with this binary's migrations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaStatus {
+    /// Latest migration compiled into this binary.
+    pub expected_version: Option<i64>,
+    /// Latest migration applied successfully.
+    pub current_version: Option<i64>,
+    /// Known migrations not applied yet, oldest first.
+    pub pending: Vec<i64>,
+    /// Applied migrations this binary does not know.
+    pub unknown: Vec<i64>,
+    /// A migration recorded as failed part-way.
+    pub dirty: Option<i64>,
+    /// Applied migrations whose SQL differs from this binary's.
+    pub checksum_mismatch: Vec<i64>,
+}
+
+impl SchemaStatus {
+    /// Compare `embedded` migrations with the `applied` rows.
+    pub fn compare(embedded: &[Migration], applied: &[AppliedMigration]) -> Self {
+        let embedded: Vec<&Migration> = embedded
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .collect();
+        let mut status = Self {
+            expected_version: embedded.iter().map(|migration| migration.version).max(),
+            current_version: applied
+                .iter()
+                .filter(|row| row.success)
+                .map(|row| row.version)
+                .max(),
+            dirty: applied
+                .iter()
+                .filter(|row| !row.success)
+                .map(|row| row.version)
+                .min(),
+            ..Self::default()
+        };
+
+        for migration in &embedded {
+            match applied.iter().find(|row| row.version == migration.version) {
+                None => status.pending.push(migration.version),
+                Some(row) if row.checksum != *migration.checksum => {
+                    status.checksum_mismatch.push(migration.version)
+                }
+                Some(_) => {}
+            }
+        }
+        status.pending.sort_unstable();
+        status.unknown = applied
+            .iter()
+            .map(|row| row.version)
+            .filter(|version| !embedded.iter().any(|m| m.version == *version))
+            .collect();
+        status.unknown.sort_unstable();
+        status
+    }
+
+    /// Schema matches this binary exactly.
+    pub fn is_current(&self) -> bool {
+        self.pending.is_empty() && self.problems().is_empty()
+    }
+
+    /// Reasons no migration may run, one sentence each.  Pending migrations
+    /// are not a problem here; [`verify_schema`] reports those.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Some(version) = self.dirty {
+            problems.push(format!(
+                "migration {version} is recorded as failed; repair the schema by hand and \
+                 delete its row from _sqlx_migrations before migrating again"
+            ));
+        }
+        if !self.checksum_mismatch.is_empty() {
+            problems.push(format!(
+                "applied migrations {} differ from this binary's copies; migrations must \
+                 not be edited once released",
+                join(&self.checksum_mismatch)
+            ));
+        }
+        if !self.unknown.is_empty() {
+            problems.push(format!(
+                "the schema is ahead of this binary: migrations {} are applied but unknown \
+                 (expected at most {}); deploy the release that added them",
+                join(&self.unknown),
+                self.expected_version
+                    .map_or_else(|| "none".to_string(), |v| v.to_string())
+            ));
+        }
+        problems
+    }
+}
+
+fn join(versions: &[i64]) -> String {
+    versions
+        .iter()
+        .map(i64::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// What a [`migrate`] run did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Pending migrations found, oldest first.
+    pub pending: Vec<i64>,
+    /// Whether they were applied (`false` for a dry run or when none were
+    /// pending).
+    pub applied: bool,
+    /// Schema version after the run.
+    pub version: Option<i64>,
+}
+
+/// Read `_sqlx_migrations` and compare it with [`MIGRATOR`].  A database
+/// that was never migrated has every migration pending.
+pub async fn schema_status(conn: &mut PgConnection) -> Result<SchemaStatus> {
+    let table_exists: bool =
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&mut *conn)
+            .await
+            .context("Failed to look up the migrations table")?;
+    let applied = if table_exists {
+        sqlx::query_as::<_, (i64, Vec<u8>, bool)>(
+            "SELECT version, checksum, success FROM _sqlx_migrations ORDER BY version",
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context("Failed to read applied migrations")?
+        .into_iter()
+        .map(|(version, checksum, success)| AppliedMigration {
+            version,
+            checksum,
+            success,
+        })
+        .collect()
+    } else {
+        Vec::new()
+    };
+    Ok(SchemaStatus::compare(&MIGRATOR.migrations, &applied))
+}
+
+/// Migrate the schema under the migration lock.  With `dry_run` the pending
+/// migrations are only validated.  Fails without applying anything when the
+/// schema is dirty, edited or ahead of this binary, or when a pending
+/// migration fails its dry run.
+pub async fn migrate(
+    pool: &PgPool,
+    dry_run: bool,
+    lock_timeout: Duration,
+) -> Result<MigrationReport> {
+    // A dedicated connection: closing it releases the lock whatever happens.
+    let mut conn = pool
+        .connect_options()
+        .connect()
+        .await
+        .context("Failed to open a connection for migrations")?;
+    acquire_lock(&mut conn, lock_timeout).await?;
+
+    let result = migrate_locked(&mut conn, dry_run).await;
+    if let Err(error) = conn.close().await {
+        tracing::warn!(error = %error, "Failed to close the migration connection");
+    }
+    result
+}
+
+async fn acquire_lock(conn: &mut PgConnection, timeout: Duration) -> Result<()> {
+    let started = Instant::now();
+    let mut waiting = false;
+    loop {
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(MIGRATION_LOCK_KEY)
+            .fetch_one(&mut *conn)
+            .await
+            .context("Failed to request the migration lock")?;
+        if locked {
+            if waiting {
+                tracing::info!(
+                    waited_ms = started.elapsed().as_millis() as u64,
+                    "Acquired migration lock"
+                );
+            }
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            bail!(
+                "Timed out after {}s waiting for the migration lock; another replica or \
+                 `api-server migrate` is still migrating (advisory lock {MIGRATION_LOCK_KEY})",
+                timeout.as_secs()
+            );
+        }
+        if !waiting {
+            tracing::info!("Another instance is migrating; waiting for the migration lock");
+            waiting = true;
+        }
+        tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+    }
+}
+
+async fn migrate_locked(conn: &mut PgConnection, dry_run: bool) -> Result<MigrationReport> {
+    let status = schema_status(conn).await?;
+    let problems = status.problems();
+    if !problems.is_empty() {
+        bail!("Refusing to migrate: {}", problems.join("; "));
+    }
+    if status.pending.is_empty() {
+        tracing::info!(
+            version = status.current_version,
+            "Database schema is up to date"
+        );
+        return Ok(MigrationReport {
+            pending: Vec::new(),
+            applied: false,
+            version: status.current_version,
+        });
+    }
+
+    tracing::info!(
+        from = status.current_version,
+        to = status.expected_version,
+        pending = status.pending.len(),
+        "Validating pending migrations"
+    );
+    preflight(conn, &status.pending).await?;
+    if dry_run {
+        return Ok(MigrationReport {
+            pending: status.pending,
+            applied: false,
+            version: status.current_version,
+        });
+    }
+
+    tracing::info!("Running database migrations");
+    conn.execute("CREATE EXTENSION IF NOT EXISTS pgcrypto")
+        .await
+        .context("Failed to ensure pgcrypto extension exists")?;
+    MIGRATOR
+        .run_direct(&mut *conn)
+        .await
+        .context("Failed to run database migrations")?;
+    tracing::info!(
+        version = status.expected_version,
+        "Database migrations completed successfully"
+    );
+
+    Ok(MigrationReport {
+        pending: status.pending,
+        applied: true,
+        version: status.expected_version,
+    })
+}
+
+/// Run the `pending` migrations in one transaction and roll it back.
+async fn preflight(conn: &mut PgConnection, pending: &[i64]) -> Result<()> {
+    let mut tx = conn
+        .begin()
+        .await
+        .context("Failed to start the migration dry run")?;
+    tx.execute("CREATE EXTENSION IF NOT EXISTS pgcrypto")
+        .await
+        .context("Failed to ensure pgcrypto extension exists")?;
+    for migration in MIGRATOR
+        .iter()
+        .filter(|migration| pending.contains(&migration.version))
+    {
+        tx.execute(&*migration.sql).await.with_context(|| {
+            format!(
+                "Dry run failed at migration {} ({}); nothing was applied",
+                migration.version, migration.description
+            )
+        })?;
+    }
+    tx.rollback()
+        .await
+        .context("Failed to roll back the migration dry run")?;
+    Ok(())
+}
+
+/// Refuse to start unless the schema matches this binary exactly.  Used when
+/// `DB_MIGRATE_ON_STARTUP=false`.
+pub async fn verify_schema(pool: &PgPool) -> Result<SchemaStatus> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .context("Failed to acquire a connection to check the schema")?;
+    let status = schema_status(&mut conn).await?;
+
+    let mut problems = status.problems();
+    if !status.pending.is_empty() {
+        problems.push(format!(
+            "the schema is behind this binary: migrations {} are pending (at {}, expected {}); \
+             run `api-server migrate`",
+            join(&status.pending),
+            status
+                .current_version
+                .map_or_else(|| "none".to_string(), |v| v.to_string()),
+            status
+                .expected_version
+                .map_or_else(|| "none".to_string(), |v| v.to_string())
+        ));
+    }
+    if !problems.is_empty() {
+        bail!("Refusing to start: {}", problems.join("; "));
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::migrate::MigrationType;
+    use std::borrow::Cow;
+
+    fn migration(version: i64, sql: &'static str) -> Migration {
+        Migration::new(
+            version,
+            Cow::Borrowed("test"),
+            MigrationType::Simple,
+            Cow::Borrowed(sql),
+        )
+    }
+
+    fn applied(migration: &Migration, success: bool) -> AppliedMigration {
+        AppliedMigration {
+            version: migration.version,
+            checksum: migration.checksum.to_vec(),
+            success,
+        }
+    }
+
+    #[test]
+    fn status_reports_pending_and_ahead_schemas() {
+        let embedded = [migration(1, "SELECT 1"), migration(2, "SELECT 2")];
+
+        let behind = SchemaStatus::compare(&embedded, &[applied(&embedded[0], true)]);
+        assert_eq!(behind.pending, vec![2]);
+        assert_eq!(behind.current_version, Some(1));
+        assert!(behind.problems().is_empty());
+        assert!(!behind.is_current());
+
+        let current = SchemaStatus::compare(
+            &embedded,
+            &[applied(&embedded[0], true), applied(&embedded[1], true)],
+        );
+        assert!(current.is_current());
+
+        let newer = migration(3, "SELECT 3");
+        let ahead = SchemaStatus::compare(
+            &embedded,
+            &[
+                applied(&embedded[0], true),
+                applied(&embedded[1], true),
+                applied(&newer, true),
+            ],
+        );
+        assert_eq!(ahead.unknown, vec![3]);
+        assert!(ahead.problems()[0].contains("ahead of this binary"));
+    }
+
+    #[test]
+    fn status_flags_failed_and_edited_migrations() {
+        let embedded = [migration(1, "SELECT 1"), migration(2, "SELECT 2")];
+        let mut edited = applied(&embedded[0], true);
+        edited.checksum = vec![0; 48];
+
+        let status = SchemaStatus::compare(&embedded, &[edited, applied(&embedded[1], false)]);
+        assert_eq!(status.dirty, Some(2));
+        assert_eq!(status.checksum_mismatch, vec![1]);
+        assert_eq!(status.problems().len(), 2);
+    }
+
+    #[test]
+    fn compiled_migrations_are_ordered_and_unique() {
+        let versions: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            SchemaStatus::compare(&MIGRATOR.migrations, &[]).pending,
+            versions
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use crate::error::ApiError;
+use ambient_node::NodeKind;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use utoipa::ToSchema;
 
 /// Health check response
@@ -10,6 +12,205 @@ pub struct HealthResponse {
     pub timestamp: String,
 }
 
+/// Server clock readings for NTP-style skew probes (Unix milliseconds)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ServerTimeResponse {
+    /// When the request reached the handler.
+    pub received_at_ms: i64,
+    /// When the response was produced.
+    pub transmitted_at_ms: i64,
+}
+
+/// Coordinator control key that signs node-facing responses
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ControlKeyResponse {
+    /// Base64 Ed25519 public key for `ambient_node::ControlVerifier`.
+    pub public_key: String,
+    pub algorithm: String,
+}
+
+/// Optional body for `PUT /api/v1/nodes/{node_id}/heartbeat`
+///
+/// Nodes may attach their reported state (telemetry fields and the like) as
+/// either a full snapshot (`baseline_seq` absent) or a delta against the
+/// snapshot acknowledged at `baseline_seq`.  A `null` field in a delta removes
+/// that field.  When the server's stored sequence does not match
+/// `baseline_seq`, the delta is discarded and the response asks for a full
+/// resync.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct NodeHeartbeatRequest {
+    /// Node clock minus server clock in milliseconds, from the node's skew probe.
+    #[serde(default)]
+    pub clock_skew_ms: Option<i64>,
+    /// Sequence number of the state carried by this heartbeat.
+    #[serde(default)]
+    pub seq: Option<u64>,
+    /// Sequence the delta applies to; absent for a full snapshot.
+    #[serde(default)]
+    pub baseline_seq: Option<u64>,
+    /// Full state or changed fields, depending on `baseline_seq`.
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub state: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Largest clock skew a node may report (one day).
+pub const MAX_REPORTED_CLOCK_SKEW_MS: i64 = 86_400_000;
+
+/// Most fields a node's heartbeat state may hold.
+pub const MAX_HEARTBEAT_STATE_FIELDS: usize = 64;
+
+/// Largest serialized heartbeat state, in bytes.
+pub const MAX_HEARTBEAT_STATE_BYTES: usize = 16 * 1024;
+
+impl NodeHeartbeatRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if let Some(skew) = self.clock_skew_ms {
+            if skew.abs() > MAX_REPORTED_CLOCK_SKEW_MS {
+                return Err(ApiError::bad_request(format!(
+                    "clock_skew_ms must be within ±{}",
+                    MAX_REPORTED_CLOCK_SKEW_MS
+                )));
+            }
+        }
+
+        match (self.seq, self.baseline_seq) {
+            (None, Some(_)) => {
+                return Err(ApiError::bad_request("baseline_seq requires seq"));
+            }
+            (None, None) if self.state.is_some() => {
+                return Err(ApiError::bad_request("state requires seq"));
+            }
+            (Some(seq), Some(baseline)) if baseline >= seq => {
+                return Err(ApiError::bad_request("baseline_seq must be less than seq"));
+            }
+            _ => {}
+        }
+
+        if let Some(ref state) = self.state {
+            if state.len() > MAX_HEARTBEAT_STATE_FIELDS {
+                return Err(ApiError::bad_request(format!(
+                    "state may contain at most {} fields",
+                    MAX_HEARTBEAT_STATE_FIELDS
+                )));
+            }
+            if state.keys().any(|key| key.is_empty() || key.len() > 64) {
+                return Err(ApiError::bad_request(
+                    "state field names must be 1-64 characters",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reconstruct the node's state from this heartbeat.
+    ///
+    /// `stored_seq` / `stored_state` are what the server last accepted.
+    /// Returns `None` when the heartbeat carries a delta whose baseline does
+    /// not match, so the node must resend a full snapshot.  A heartbeat
+    /// without `seq` leaves the stored state untouched.
+    pub fn apply_state(
+        &self,
+        stored_seq: Option<u64>,
+        stored_state: Option<&serde_json::Map<String, serde_json::Value>>,
+    ) -> Result<Option<HeartbeatStateUpdate>, ApiError> {
+        let Some(seq) = self.seq else {
+            return Ok(Some(HeartbeatStateUpdate {
+                seq: stored_seq,
+                state: None,
+            }));
+        };
+
+        let state = match self.baseline_seq {
+            None => self.state.clone().unwrap_or_default(),
+            Some(baseline) => {
+                let (Some(stored_seq), Some(stored_state)) = (stored_seq, stored_state) else {
+                    return Ok(None);
+                };
+                if stored_seq != baseline {
+                    return Ok(None);
+                }
+                let mut merged = stored_state.clone();
+                for (key, value) in self.state.iter().flatten() {
+                    if value.is_null() {
+                        merged.remove(key);
+                    } else {
+                        merged.insert(key.clone(), value.clone());
+                    }
+                }
+                merged
+            }
+        };
+
+        if state.len() > MAX_HEARTBEAT_STATE_FIELDS
+            || serde_json::to_vec(&state).map_or(true, |raw| raw.len() > MAX_HEARTBEAT_STATE_BYTES)
+        {
+            return Err(ApiError::bad_request(format!(
+                "state may contain at most {} fields and {} bytes",
+                MAX_HEARTBEAT_STATE_FIELDS, MAX_HEARTBEAT_STATE_BYTES
+            )));
+        }
+
+        Ok(Some(HeartbeatStateUpdate {
+            seq: Some(seq),
+            state: Some(state),
+        }))
+    }
+}
+
+/// Most nodes one batch heartbeat may cover.
+pub const MAX_HEARTBEAT_BATCH_NODES: usize = 100;
+
+/// Body for `PUT /api/v1/nodes/heartbeat/batch`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NodeHeartbeatBatchRequest {
+    pub heartbeats: Vec<NodeHeartbeatBatchItem>,
+}
+
+/// One node's heartbeat within a batch
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NodeHeartbeatBatchItem {
+    pub node_id: String,
+    #[serde(flatten)]
+    pub heartbeat: NodeHeartbeatRequest,
+}
+
+impl NodeHeartbeatBatchRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.heartbeats.is_empty() || self.heartbeats.len() > MAX_HEARTBEAT_BATCH_NODES {
+            return Err(ApiError::bad_request(format!(
+                "heartbeats must contain 1-{} entries",
+                MAX_HEARTBEAT_BATCH_NODES
+            )));
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for item in &self.heartbeats {
+            if !seen.insert(item.node_id.as_str()) {
+                return Err(ApiError::bad_request(format!(
+                    "node {} appears more than once",
+                    item.node_id
+                )));
+            }
+            item.heartbeat.validate().map_err(|err| {
+                ApiError::bad_request(format!("node {}: {}", item.node_id, err.message))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Node state accepted from a heartbeat by [`NodeHeartbeatRequest::apply_state`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeartbeatStateUpdate {
+    /// Sequence to store; the previous one when the heartbeat carried no state.
+    pub seq: Option<u64>,
+    /// Reconstructed state to store, or `None` to keep the stored state.
+    pub state: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
 /// Node registration request
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct NodeRegistration {
@@ -18,6 +219,35 @@ pub struct NodeRegistration {
     pub node_type: String,
     pub capabilities: NodeCapabilities,
     pub observability_port: Option<u16>,
+    /// Benchmark-derived energy efficiency (work units per watt-hour), used by
+    /// green scheduling to prefer efficient nodes.
+    #[serde(default)]
+    pub benchmark_ops_per_wh: Option<f64>,
+    /// Base64 X25519 public key requesters use to seal task secrets to this
+    /// node.  Nodes without a key cannot receive secrets.
+    #[serde(default)]
+    pub secrets_public_key: Option<String>,
+    /// Concurrent task slots per class; omitted classes use the server default.
+    #[serde(default)]
+    pub slots: Option<NodeSlots>,
+    /// Base64 Ed25519 public key the node signs sandbox reports with
+    /// (`ambient_node::NodeSigningKey::public_key_b64`).
+    #[serde(default)]
+    pub signing_public_key: Option<String>,
+    /// Free-form placement labels (e.g. `gpu_vendor=nvidia`,
+    /// `zone=eu-west-1a`) matched by task `node_selector`s.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Autonomous system number of the node's uplink.  Used for placement
+    /// only when the server has no GeoIP answer for the node's address.
+    #[serde(default)]
+    pub asn: Option<u32>,
+    /// Task types the operator accepts; empty accepts every type.
+    #[serde(default)]
+    pub allowed_task_types: Vec<String>,
+    /// Task types the operator refuses, even if also allowed.
+    #[serde(default)]
+    pub blocked_task_types: Vec<String>,
 }
 
 impl NodeRegistration {
@@ -48,32 +278,286 @@ impl NodeRegistration {
             return Err(ApiError::bad_request("region cannot exceed 32 characters"));
         }
 
-        // Validate node_type
-        const VALID_NODE_TYPES: &[&str] = &[
-            "compute",
-            "gateway",
-            "storage",
-            "validator",
-            "open_internet",
-            "any",
-            "feen_resonator",
-        ];
-        if !VALID_NODE_TYPES.contains(&self.node_type.as_str()) {
+        validate_node_type(&self.node_type)?;
+
+        // Validate capabilities
+        self.capabilities.validate()?;
+
+        if let Some(ops_per_wh) = self.benchmark_ops_per_wh {
+            if !ops_per_wh.is_finite() || ops_per_wh <= 0.0 {
+                return Err(ApiError::bad_request(
+                    "benchmark_ops_per_wh must be a positive finite number",
+                ));
+            }
+        }
+
+        if let Some(ref key) = self.secrets_public_key {
+            validate_base64_len("secrets_public_key", key, 32)?;
+        }
+
+        if let Some(ref slots) = self.slots {
+            slots.validate()?;
+        }
+
+        if let Some(ref key) = self.signing_public_key {
+            validate_base64_len("signing_public_key", key, 32)?;
+        }
+
+        validate_labels("labels", &self.labels)?;
+        validate_task_type_filter("allowed_task_types", &self.allowed_task_types)?;
+        validate_task_type_filter("blocked_task_types", &self.blocked_task_types)?;
+
+        if self.asn == Some(0) {
+            return Err(ApiError::bad_request("asn must be a non-zero AS number"));
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_node_type(node_type: &str) -> Result<(), ApiError> {
+    if NodeKind::parse(node_type).is_none() {
+        let valid: Vec<&str> = NodeKind::ALL.iter().map(|kind| kind.as_str()).collect();
+        return Err(ApiError::bad_request(format!(
+            "node_type must be one of: {}",
+            valid.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Canonical spelling of a validated `node_type`, plus the legacy alias it
+/// was given as, if any, to store in `nodes.legacy_node_type`.
+pub fn normalize_node_type(node_type: &str) -> (String, Option<String>) {
+    match NodeKind::parse(node_type) {
+        Some(kind) if kind.as_str() != node_type => {
+            (kind.as_str().to_string(), Some(node_type.to_string()))
+        }
+        _ => (node_type.to_string(), None),
+    }
+}
+
+/// Deprecation notice for a node that registered with a legacy `node_type`.
+pub fn node_kind_warnings(legacy_node_type: Option<&str>) -> Vec<String> {
+    legacy_node_type
+        .and_then(|legacy| Some((legacy, NodeKind::legacy_alias(legacy)?)))
+        .map(|(legacy, kind)| {
+            format!(
+                "node_type '{legacy}' is deprecated and was stored as '{kind}'; register with '{kind}'"
+            )
+        })
+        .into_iter()
+        .collect()
+}
+
+/// Most labels a node may carry, and most entries in a task's node selector.
+pub const MAX_NODE_LABELS: usize = 32;
+
+/// Longest label key or value.
+pub const MAX_NODE_LABEL_LEN: usize = 63;
+
+/// Check node labels or a node selector: bounded count, keys of
+/// alphanumerics and `-_./`, values of alphanumerics and `-_.`.
+pub fn validate_labels(field: &str, labels: &BTreeMap<String, String>) -> Result<(), ApiError> {
+    if labels.len() > MAX_NODE_LABELS {
+        return Err(ApiError::bad_request(format!(
+            "{} cannot have more than {} entries",
+            field, MAX_NODE_LABELS
+        )));
+    }
+    for (key, value) in labels {
+        if key.is_empty()
+            || key.len() > MAX_NODE_LABEL_LEN
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+        {
+            return Err(ApiError::bad_request(format!(
+                "{} key {:?} must be 1-{} characters of letters, digits, '-', '_', '.' or '/'",
+                field, key, MAX_NODE_LABEL_LEN
+            )));
+        }
+        if value.len() > MAX_NODE_LABEL_LEN
+            || !value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
             return Err(ApiError::bad_request(format!(
-                "node_type must be one of: {}",
-                VALID_NODE_TYPES.join(", ")
+                "{} value for {} must be at most {} characters of letters, digits, '-', '_' or '.'",
+                field, key, MAX_NODE_LABEL_LEN
             )));
         }
+    }
+    Ok(())
+}
 
-        // Validate capabilities
-        self.capabilities.validate()?;
+/// Check an operator's task-type allow or block list: registered task
+/// types only, each listed once.
+pub fn validate_task_type_filter(field: &str, task_types: &[String]) -> Result<(), ApiError> {
+    for (i, task_type) in task_types.iter().enumerate() {
+        if task_type_registry_entry(task_type).is_none() {
+            return Err(ApiError::bad_request(format!(
+                "{} entry {:?} is not a task type; expected one of: {}",
+                field,
+                task_type,
+                TASK_TYPE_REGISTRY
+                    .iter()
+                    .map(|entry| entry.task_type)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        if task_types[..i].contains(task_type) {
+            return Err(ApiError::bad_request(format!(
+                "{} lists {} more than once",
+                field, task_type
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Partial update of a registered node.  Omitted fields keep their value;
+/// the merged capabilities must pass the registration limits.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct NodeUpdateRequest {
+    pub node_type: Option<String>,
+    pub bandwidth_mbps: Option<f64>,
+    pub cpu_cores: Option<u32>,
+    pub memory_gb: Option<f64>,
+    pub gpu_available: Option<bool>,
+    /// Replaces the node's labels when present.
+    pub labels: Option<BTreeMap<String, String>>,
+    /// Replaces the node's accepted task types when present.
+    pub allowed_task_types: Option<Vec<String>>,
+    /// Replaces the node's refused task types when present.
+    pub blocked_task_types: Option<Vec<String>>,
+}
+
+impl NodeUpdateRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.node_type.is_none()
+            && self.bandwidth_mbps.is_none()
+            && self.cpu_cores.is_none()
+            && self.memory_gb.is_none()
+            && self.gpu_available.is_none()
+            && self.labels.is_none()
+            && self.allowed_task_types.is_none()
+            && self.blocked_task_types.is_none()
+        {
+            return Err(ApiError::bad_request(
+                "update must change at least one field",
+            ));
+        }
+        if let Some(ref node_type) = self.node_type {
+            validate_node_type(node_type)?;
+        }
+        if let Some(ref labels) = self.labels {
+            validate_labels("labels", labels)?;
+        }
+        if let Some(ref task_types) = self.allowed_task_types {
+            validate_task_type_filter("allowed_task_types", task_types)?;
+        }
+        if let Some(ref task_types) = self.blocked_task_types {
+            validate_task_type_filter("blocked_task_types", task_types)?;
+        }
+        Ok(())
+    }
+
+    /// `current` with this update applied, validated as at registration.
+    pub fn apply(&self, current: &NodeCapabilities) -> Result<NodeCapabilities, ApiError> {
+        let merged = NodeCapabilities {
+            bandwidth_mbps: self.bandwidth_mbps.unwrap_or(current.bandwidth_mbps),
+            cpu_cores: self.cpu_cores.unwrap_or(current.cpu_cores),
+            memory_gb: self.memory_gb.unwrap_or(current.memory_gb),
+            gpu_available: self.gpu_available.unwrap_or(current.gpu_available),
+        };
+        merged.validate()?;
+        Ok(merged)
+    }
+}
+
+/// Capacity pool a task occupies on each node it is assigned to.
+///
+/// Pools are limited independently, so a node saturated with relay sessions
+/// can still take compute work and vice versa.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SlotClass {
+    /// `connect_only` relay sessions.
+    Connect,
+    /// Sandboxed compute without a GPU.
+    Wasm,
+    /// Tasks that require a GPU.
+    Gpu,
+}
+
+impl SlotClass {
+    pub fn for_task(task_type: &str, require_gpu: bool) -> Self {
+        let entry = task_type_registry_entry(task_type);
+        if task_type == "connect_only" {
+            Self::Connect
+        } else if require_gpu || entry.is_some_and(|entry| entry.minimum_capabilities.gpu_available)
+        {
+            Self::Gpu
+        } else {
+            Self::Wasm
+        }
+    }
+
+    /// Parse a stored `tasks.slot_class`, treating unknown values as `wasm`.
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "connect" => Self::Connect,
+            "gpu" => Self::Gpu,
+            _ => Self::Wasm,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connect => "connect",
+            Self::Wasm => "wasm",
+            Self::Gpu => "gpu",
+        }
+    }
+}
+
+/// Most slots a node may advertise for one class.
+pub const MAX_NODE_SLOTS: u32 = 10_000;
+
+/// Per-class concurrent task slots advertised by a node.  `None` falls back to
+/// `MAX_CONCURRENT_TASKS_PER_NODE`; `0` refuses that class entirely.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Default, PartialEq, Eq)]
+pub struct NodeSlots {
+    #[serde(default)]
+    pub connect: Option<u32>,
+    #[serde(default)]
+    pub wasm: Option<u32>,
+    #[serde(default)]
+    pub gpu: Option<u32>,
+}
 
+impl NodeSlots {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        for (class, slots) in [
+            (SlotClass::Connect, self.connect),
+            (SlotClass::Wasm, self.wasm),
+            (SlotClass::Gpu, self.gpu),
+        ] {
+            if slots.is_some_and(|slots| slots > MAX_NODE_SLOTS) {
+                return Err(ApiError::bad_request(format!(
+                    "slots.{} cannot exceed {MAX_NODE_SLOTS}",
+                    class.as_str()
+                )));
+            }
+        }
         Ok(())
     }
 }
 
 /// Node capabilities
-#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, async_graphql::SimpleObject)]
 pub struct NodeCapabilities {
     pub bandwidth_mbps: f64,
     pub cpu_cores: u32,
@@ -111,17 +595,22 @@ impl NodeCapabilities {
 #[derive(Debug, Clone)]
 pub struct TaskTypeRegistryEntry {
     pub task_type: &'static str,
-    pub preferred_node_type: &'static str,
+    pub preferred_node_type: NodeKind,
+    /// Whether a starving task may fall back from `preferred_node_type` to
+    /// any node that meets `minimum_capabilities`.  Off for task types that
+    /// need what only the preferred node type provides.
+    pub node_type_relaxable: bool,
     pub minimum_capabilities: NodeCapabilities,
     pub max_execution_time_sec: u64,
     pub max_input_size_mb: usize,
     pub allow_wasm_module: bool,
 }
 
-pub const TASK_TYPE_REGISTRY: [TaskTypeRegistryEntry; 6] = [
+pub const TASK_TYPE_REGISTRY: [TaskTypeRegistryEntry; 7] = [
     TaskTypeRegistryEntry {
         task_type: "federated_learning",
-        preferred_node_type: "compute",
+        preferred_node_type: NodeKind::Compute,
+        node_type_relaxable: true,
         minimum_capabilities: NodeCapabilities {
             bandwidth_mbps: 500.0,
             cpu_cores: 8,
@@ -134,7 +623,8 @@ pub const TASK_TYPE_REGISTRY: [TaskTypeRegistryEntry; 6] = [
     },
     TaskTypeRegistryEntry {
         task_type: "zk_proof",
-        preferred_node_type: "compute",
+        preferred_node_type: NodeKind::Compute,
+        node_type_relaxable: true,
         minimum_capabilities: NodeCapabilities {
             bandwidth_mbps: 100.0,
             cpu_cores: 8,
@@ -147,7 +637,8 @@ pub const TASK_TYPE_REGISTRY: [TaskTypeRegistryEntry; 6] = [
     },
     TaskTypeRegistryEntry {
         task_type: "wasm_execution",
-        preferred_node_type: "compute",
+        preferred_node_type: NodeKind::Compute,
+        node_type_relaxable: true,
         minimum_capabilities: NodeCapabilities {
             bandwidth_mbps: 100.0,
             cpu_cores: 4,
@@ -160,7 +651,8 @@ pub const TASK_TYPE_REGISTRY: [TaskTypeRegistryEntry; 6] = [
     },
     TaskTypeRegistryEntry {
         task_type: "computation",
-        preferred_node_type: "compute",
+        preferred_node_type: NodeKind::Compute,
+        node_type_relaxable: true,
         minimum_capabilities: NodeCapabilities {
             bandwidth_mbps: 50.0,
             cpu_cores: 4,
@@ -173,7 +665,8 @@ pub const TASK_TYPE_REGISTRY: [TaskTypeRegistryEntry; 6] = [
     },
     TaskTypeRegistryEntry {
         task_type: "connect_only",
-        preferred_node_type: "open_internet",
+        preferred_node_type: NodeKind::OpenInternet,
+        node_type_relaxable: false,
         minimum_capabilities: NodeCapabilities {
             bandwidth_mbps: 50.0,
             cpu_cores: 1,
@@ -186,7 +679,8 @@ pub const TASK_TYPE_REGISTRY: [TaskTypeRegistryEntry; 6] = [
     },
     TaskTypeRegistryEntry {
         task_type: "feen_connectivity",
-        preferred_node_type: "feen_resonator",
+        preferred_node_type: NodeKind::FeenResonator,
+        node_type_relaxable: false,
         minimum_capabilities: NodeCapabilities {
             bandwidth_mbps: 100.0,
             cpu_cores: 4,
@@ -197,8 +691,30 @@ pub const TASK_TYPE_REGISTRY: [TaskTypeRegistryEntry; 6] = [
         max_input_size_mb: 5,
         allow_wasm_module: false,
     },
+    TaskTypeRegistryEntry {
+        task_type: crate::proof_offload::VERIFY_PROOF_TASK_TYPE,
+        preferred_node_type: NodeKind::Compute,
+        node_type_relaxable: true,
+        minimum_capabilities: NodeCapabilities {
+            bandwidth_mbps: 10.0,
+            cpu_cores: 1,
+            memory_gb: 1.0,
+            gpu_available: false,
+        },
+        max_execution_time_sec: crate::proof_offload::VERIFY_TIMEOUT_SECS,
+        max_input_size_mb: 1,
+        allow_wasm_module: false,
+    },
 ];
 
+impl TaskTypeRegistryEntry {
+    /// `node_type` values of nodes that may take this task type before any
+    /// starvation relaxation; see [`NodeKind::can_serve`].
+    pub fn serving_node_types(&self) -> Vec<&'static str> {
+        NodeKind::serving_node_types(self.preferred_node_type)
+    }
+}
+
 pub fn task_type_registry_entry(task_type: &str) -> Option<&'static TaskTypeRegistryEntry> {
     TASK_TYPE_REGISTRY
         .iter()
@@ -218,17 +734,164 @@ pub struct NodeInfo {
     pub registered_at: String,
     pub last_seen: String,
     pub observability_port: Option<u16>,
+    /// Advertised slot pools; `None` entries use the server default.
+    pub slots: NodeSlots,
+    pub labels: BTreeMap<String, String>,
+    /// ASN used to spread redundant work across networks, when known.
+    pub asn: Option<u32>,
+    pub asn_source: Option<crate::geoip::AsnSource>,
+    /// Deprecation notices, e.g. for a legacy `node_type` alias.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Flap circuit breaker; while open the node gets no new assignments.
+    pub circuit_breaker: crate::flap_breaker::NodeCircuitBreaker,
+    /// When the node proved its enrollment key, if it has.
+    pub attested_at: Option<String>,
+    /// Task types the operator accepts; empty accepts every type.
+    pub allowed_task_types: Vec<String>,
+    /// Task types the operator refuses.
+    pub blocked_task_types: Vec<String>,
+    /// Nonce to sign, returned at registration while attestation is required.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attestation_challenge: Option<crate::attestation::AttestationChallenge>,
+}
+
+/// Fleet report of node kinds, for tracking the move off legacy aliases.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NodeKindReport {
+    /// Active nodes per canonical `node_type`.
+    pub kinds: BTreeMap<String, i64>,
+    /// Active nodes whose last registration or update used a legacy alias.
+    pub legacy_nodes: Vec<LegacyNodeKind>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LegacyNodeKind {
+    pub node_id: String,
+    pub owner_id: String,
+    pub node_type: String,
+    pub legacy_node_type: String,
+    pub last_seen: String,
+}
+
+/// Largest rate limit multiplier an override may set.
+pub const MAX_THROTTLE_MULTIPLIER: f64 = 100.0;
+
+/// Largest burst an override may set.
+pub const MAX_THROTTLE_BURST: i32 = 10_000;
+
+/// Credential a throttle override applies to.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ThrottleSubjectType {
+    /// Every JWT-authenticated request of a user.
+    User,
+    /// Requests authenticated with one API key.
+    ApiKey,
+}
+
+impl ThrottleSubjectType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::ApiKey => "api_key",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "user" => Some(Self::User),
+            "api_key" => Some(Self::ApiKey),
+            _ => None,
+        }
+    }
+}
+
+/// Body of `POST /api/v1/admin/throttle-overrides`; replaces any override of
+/// the same subject.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ThrottleOverrideRequest {
+    pub subject_type: ThrottleSubjectType,
+    /// `user_id` or API key `key_id`.
+    pub subject_id: String,
+    /// Factor applied to each tier's requests per minute (0 < x <= 100);
+    /// below 1 tightens the limit.
+    pub multiplier: f64,
+    /// Bucket size replacing each tier's burst; the tier default when unset.
+    #[serde(default)]
+    pub burst: Option<i32>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// The override lapses at this time (RFC 3339).
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ThrottleOverrideRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if !(self.multiplier > 0.0 && self.multiplier <= MAX_THROTTLE_MULTIPLIER) {
+            return Err(ApiError::bad_request(format!(
+                "multiplier must be greater than 0 and at most {MAX_THROTTLE_MULTIPLIER}"
+            )));
+        }
+        if let Some(burst) = self.burst {
+            if !(1..=MAX_THROTTLE_BURST).contains(&burst) {
+                return Err(ApiError::bad_request(format!(
+                    "burst must be between 1 and {MAX_THROTTLE_BURST}"
+                )));
+            }
+        }
+        if self
+            .reason
+            .as_ref()
+            .is_some_and(|reason| reason.len() > 500)
+        {
+            return Err(ApiError::bad_request(
+                "reason must be at most 500 characters",
+            ));
+        }
+        if self
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Utc::now())
+        {
+            return Err(ApiError::bad_request("expires_at must be in the future"));
+        }
+        Ok(())
+    }
+}
+
+/// A stored throttle override.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ThrottleOverride {
+    pub override_id: String,
+    pub subject_type: ThrottleSubjectType,
+    pub subject_id: String,
+    pub multiplier: f64,
+    pub burst: Option<i32>,
+    pub reason: Option<String>,
+    pub expires_at: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
 }
 
 /// Task submission request
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct TaskSubmission {
     pub task_type: String,
-    pub wasm_module: Option<String>, // Base64 encoded WASM module
+    /// Base64 encoded WASM module, or `sha3-256:<hash>` referencing a module
+    /// uploaded via `POST /api/v1/modules`.
+    pub wasm_module: Option<String>,
     pub inputs: serde_json::Value,
     pub requirements: TaskRequirements,
+    /// Scheduling priority from 0 (default) to `MAX_TASK_PRIORITY`; higher runs first.
+    #[serde(default)]
+    pub priority: u8,
 }
 
+/// Highest priority a requester may assign to a task.
+pub const MAX_TASK_PRIORITY: u8 = 10;
+
 impl TaskSubmission {
     /// Validate task submission data
     pub fn validate(&self) -> Result<(), ApiError> {
@@ -244,6 +907,12 @@ impl TaskSubmission {
             ))
         })?;
 
+        if self.task_type == crate::proof_offload::VERIFY_PROOF_TASK_TYPE {
+            return Err(ApiError::bad_request(
+                "verify_proof tasks are created by the server for offloaded proof batches",
+            ));
+        }
+
         // Validate WASM module policy and size if provided
         if let Some(ref module) = self.wasm_module {
             if !task_type_entry.allow_wasm_module {
@@ -253,7 +922,8 @@ impl TaskSubmission {
                 )));
             }
 
-            if module.len() > task_type_entry.max_input_size_mb * 1024 * 1024 {
+            let is_reference = crate::artifacts::parse_module_reference(module)?.is_some();
+            if !is_reference && module.len() > task_type_entry.max_input_size_mb * 1024 * 1024 {
                 return Err(ApiError::bad_request(format!(
                     "wasm_module cannot exceed {}MB for task_type {}",
                     task_type_entry.max_input_size_mb, self.task_type
@@ -261,6 +931,22 @@ impl TaskSubmission {
             }
         }
 
+        // Only sandboxed WASM tasks run behind the egress capability layer.
+        if !self.requirements.egress.is_empty() && !task_type_entry.allow_wasm_module {
+            return Err(ApiError::bad_request(format!(
+                "requirements.egress is not allowed for task_type {}",
+                self.task_type
+            )));
+        }
+
+        // Checkpoints are written through WASM host functions.
+        if self.requirements.checkpointable && !task_type_entry.allow_wasm_module {
+            return Err(ApiError::bad_request(format!(
+                "requirements.checkpointable is not allowed for task_type {}",
+                self.task_type
+            )));
+        }
+
         if self.requirements.max_execution_time_sec > task_type_entry.max_execution_time_sec {
             return Err(ApiError::bad_request(format!(
                 "max_execution_time_sec cannot exceed {} for task_type {}",
@@ -268,6 +954,13 @@ impl TaskSubmission {
             )));
         }
 
+        if self.priority > MAX_TASK_PRIORITY {
+            return Err(ApiError::bad_request(format!(
+                "priority must be between 0 and {}",
+                MAX_TASK_PRIORITY
+            )));
+        }
+
         // Deep validate arbitrary JSON payloads.
         validate_json_depth(&self.inputs, 0)?;
 
@@ -316,6 +1009,7 @@ fn validate_connect_only_inputs(inputs: &serde_json::Value) -> Result<(), ApiErr
         "bandwidth_limit_mbps",
         "egress_profile",
         "destination_policy_id",
+        "data_cap_mb",
     ];
 
     for key in obj.keys() {
@@ -405,8 +1099,19 @@ fn validate_connect_only_inputs(inputs: &serde_json::Value) -> Result<(), ApiErr
         ));
     }
 
-    Ok(())
-}
+    if let Some(data_cap_mb) = obj.get("data_cap_mb") {
+        if !data_cap_mb
+            .as_u64()
+            .is_some_and(|mb| (1..=MAX_CONNECT_SESSION_DATA_CAP_MB).contains(&mb))
+        {
+            return Err(ApiError::bad_request(format!(
+                "data_cap_mb must be an integer between 1 and {MAX_CONNECT_SESSION_DATA_CAP_MB}"
+            )));
+        }
+    }
+
+    Ok(())
+}
 
 fn validate_feen_connectivity_inputs(inputs: &serde_json::Value) -> Result<(), ApiError> {
     let obj = inputs
@@ -479,6 +1184,190 @@ pub struct TaskRequirements {
     pub max_execution_time_sec: u64,
     pub require_gpu: bool,
     pub require_proof: bool,
+    /// Node selection mode; `green` prefers low-carbon regions and efficient nodes.
+    #[serde(default)]
+    pub scheduling_mode: SchedulingMode,
+    /// Times the task is re-queued after a node times out or reports failure.
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Delay before a re-queued task becomes eligible for assignment again.
+    #[serde(default)]
+    pub retry_backoff_sec: u64,
+    /// Network destinations the task's sandbox may reach.  Empty (the
+    /// default) means no network access.
+    #[serde(default)]
+    pub egress: Vec<TaskEgressRule>,
+    /// The module snapshots its state through the checkpoint host functions,
+    /// so a draining or failed node's work can resume on another node.
+    #[serde(default)]
+    pub checkpointable: bool,
+    /// Labels a node must carry, all with equal values, to be assigned.
+    #[serde(default)]
+    pub node_selector: BTreeMap<String, String>,
+    /// How strictly the task's nodes are spread across ASNs and regions.
+    #[serde(default)]
+    pub diversity: DiversityMode,
+    /// Node regions to place the task in, e.g. near the requester for
+    /// latency-sensitive `connect_only` sessions.
+    #[serde(default)]
+    pub preferred_regions: Vec<String>,
+    /// Node regions the task must never be placed in.
+    #[serde(default)]
+    pub excluded_regions: Vec<String>,
+    /// Whether `preferred_regions` only ranks nodes or restricts them.
+    #[serde(default)]
+    pub region_strictness: RegionStrictness,
+}
+
+/// Most regions a task may list in `preferred_regions` or `excluded_regions`.
+pub const MAX_TASK_REGIONS: usize = 16;
+
+/// Most egress rules a task may declare.
+pub const MAX_TASK_EGRESS_RULES: usize = 16;
+
+/// A network destination declared by a task.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct TaskEgressRule {
+    /// Hostname, IP address, or `*.example.com` for any subdomain.
+    pub host: String,
+    /// Allowed destination ports; at least one is required.
+    pub ports: Vec<u16>,
+}
+
+impl TaskEgressRule {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let host = self.host.strip_prefix("*.").unwrap_or(&self.host);
+        if host.is_empty() || self.host.len() > 253 {
+            return Err(ApiError::bad_request(
+                "egress host must be between 1 and 253 characters",
+            ));
+        }
+        if !host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        {
+            return Err(ApiError::bad_request(format!(
+                "egress host {} must be a hostname, IPv4 address, or *.domain wildcard",
+                self.host
+            )));
+        }
+        if self.ports.is_empty() || self.ports.len() > 16 || self.ports.contains(&0) {
+            return Err(ApiError::bad_request(format!(
+                "egress rule for {} must list between 1 and 16 non-zero ports",
+                self.host
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn to_sandbox_rule(&self) -> wasm_engine::EgressRule {
+        wasm_engine::EgressRule::new(self.host.clone(), self.ports.clone())
+    }
+}
+
+/// How eligible nodes are ranked when a task is assigned.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulingMode {
+    /// Highest health score first.
+    #[default]
+    Standard,
+    /// Weighted by region carbon intensity, node energy efficiency, and health.
+    Green,
+}
+
+impl SchedulingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Standard => "standard",
+            Self::Green => "green",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "green" => Self::Green,
+            _ => Self::Standard,
+        }
+    }
+}
+
+/// Placement of a task's nodes relative to each other's networks.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DiversityMode {
+    /// Rank nodes without regard to where the others are.
+    Off,
+    /// Favour nodes in a different ASN (or region, when the ASN is unknown)
+    /// from the task's other nodes, falling back to any eligible node.
+    #[default]
+    Prefer,
+    /// Only assign nodes in a different ASN/region from the task's other
+    /// nodes; the task waits for such nodes rather than doubling up.
+    Require,
+}
+
+impl DiversityMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Prefer => "prefer",
+            Self::Require => "require",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "off" => Self::Off,
+            "require" => Self::Require,
+            _ => Self::Prefer,
+        }
+    }
+}
+
+/// How a task's `preferred_regions` constrain node selection.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionStrictness {
+    /// Rank nodes in a preferred region first, falling back to any other
+    /// eligible (non-excluded) region.
+    #[default]
+    Prefer,
+    /// Only assign nodes in a preferred region; the task waits for one
+    /// rather than landing elsewhere.
+    Require,
+}
+
+impl RegionStrictness {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Prefer => "prefer",
+            Self::Require => "require",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "require" => Self::Require,
+            _ => Self::Prefer,
+        }
+    }
+}
+
+fn validate_regions(field: &str, regions: &[String]) -> Result<(), ApiError> {
+    if regions.len() > MAX_TASK_REGIONS {
+        return Err(ApiError::bad_request(format!(
+            "{} cannot list more than {} regions",
+            field, MAX_TASK_REGIONS
+        )));
+    }
+    if regions.iter().any(|r| r.is_empty() || r.len() > 32) {
+        return Err(ApiError::bad_request(format!(
+            "{} entries must be between 1 and 32 characters",
+            field
+        )));
+    }
+    Ok(())
 }
 
 impl TaskRequirements {
@@ -498,10 +1387,295 @@ impl TaskRequirements {
             ));
         }
 
+        if self.max_retries > 10 {
+            return Err(ApiError::bad_request(
+                "max_retries must be between 0 and 10",
+            ));
+        }
+
+        if self.retry_backoff_sec > 3600 {
+            return Err(ApiError::bad_request(
+                "retry_backoff_sec must be between 0 and 3600",
+            ));
+        }
+
+        if self.egress.len() > MAX_TASK_EGRESS_RULES {
+            return Err(ApiError::bad_request(format!(
+                "a task cannot declare more than {} egress rules",
+                MAX_TASK_EGRESS_RULES
+            )));
+        }
+        for rule in &self.egress {
+            rule.validate()?;
+        }
+
+        validate_labels("node_selector", &self.node_selector)?;
+
+        validate_regions("preferred_regions", &self.preferred_regions)?;
+        validate_regions("excluded_regions", &self.excluded_regions)?;
+        if let Some(region) = self
+            .preferred_regions
+            .iter()
+            .find(|r| self.excluded_regions.contains(r))
+        {
+            return Err(ApiError::bad_request(format!(
+                "region {} cannot be both preferred and excluded",
+                region
+            )));
+        }
+        if self.region_strictness == RegionStrictness::Require && self.preferred_regions.is_empty()
+        {
+            return Err(ApiError::bad_request(
+                "region_strictness require needs at least one preferred region",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Largest plaintext a sealed secret may carry.
+pub const MAX_SEALED_SECRET_BYTES: usize = 16 * 1024;
+
+/// Most secrets a task may carry per node.
+pub const MAX_SECRETS_PER_NODE: usize = 32;
+
+/// Poly1305 authentication tag appended to every sealed ciphertext.
+const SEALED_SECRET_TAG_BYTES: usize = 16;
+
+fn validate_base64_len(field: &str, value: &str, expected: usize) -> Result<Vec<u8>, ApiError> {
+    let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, value)
+        .map_err(|_| ApiError::bad_request(format!("{} is not valid base64", field)))?;
+    if bytes.len() != expected {
+        return Err(ApiError::bad_request(format!(
+            "{} must decode to {} bytes",
+            field, expected
+        )));
+    }
+    Ok(bytes)
+}
+
+/// A task secret sealed by the requester to one assigned node's
+/// `secrets_public_key` (X25519 + HKDF-SHA256 + ChaCha20-Poly1305, secret name
+/// as associated data).  The server stores and relays it but cannot open it.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SealedTaskSecret {
+    pub node_id: String,
+    pub name: String,
+    /// Base64 ephemeral X25519 public key (32 bytes).
+    pub ephemeral_public_key: String,
+    /// Base64 ChaCha20-Poly1305 nonce (12 bytes).
+    pub nonce: String,
+    /// Base64 ciphertext including the authentication tag.
+    pub ciphertext: String,
+}
+
+impl SealedTaskSecret {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.name.is_empty() || self.name.len() > 64 {
+            return Err(ApiError::bad_request(
+                "secret name must be between 1 and 64 characters",
+            ));
+        }
+        if !self
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        {
+            return Err(ApiError::bad_request(
+                "secret name can only contain alphanumeric characters, '_', '-', and '.'",
+            ));
+        }
+
+        validate_base64_len("ephemeral_public_key", &self.ephemeral_public_key, 32)?;
+        validate_base64_len("nonce", &self.nonce, 12)?;
+
+        let ciphertext =
+            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &self.ciphertext)
+                .map_err(|_| ApiError::bad_request("ciphertext is not valid base64"))?;
+        if ciphertext.len() < SEALED_SECRET_TAG_BYTES
+            || ciphertext.len() > MAX_SEALED_SECRET_BYTES + SEALED_SECRET_TAG_BYTES
+        {
+            return Err(ApiError::bad_request(format!(
+                "ciphertext must hold between 0 and {} bytes of sealed data",
+                MAX_SEALED_SECRET_BYTES
+            )));
+        }
+
         Ok(())
     }
 }
 
+/// Replace the sealed secrets attached to a task
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TaskSecretsUpload {
+    pub secrets: Vec<SealedTaskSecret>,
+}
+
+impl TaskSecretsUpload {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let mut seen = std::collections::HashSet::new();
+        let mut per_node: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+
+        for secret in &self.secrets {
+            secret.validate()?;
+            if !seen.insert((secret.node_id.as_str(), secret.name.as_str())) {
+                return Err(ApiError::bad_request(format!(
+                    "secret {} is sealed more than once for node {}",
+                    secret.name, secret.node_id
+                )));
+            }
+            let count = per_node.entry(secret.node_id.as_str()).or_default();
+            *count += 1;
+            if *count > MAX_SECRETS_PER_NODE {
+                return Err(ApiError::bad_request(format!(
+                    "a task cannot carry more than {} secrets per node",
+                    MAX_SECRETS_PER_NODE
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An assigned node a requester can seal task secrets to
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaskSecretRecipient {
+    pub node_id: String,
+    /// `None` when the node registered without a secrets key.
+    pub secrets_public_key: Option<String>,
+}
+
+/// Most log lines a node may send in one request.
+pub const MAX_TASK_LOG_BATCH_LINES: usize = 200;
+
+/// Longest accepted log line, in bytes.
+pub const MAX_TASK_LOG_LINE_BYTES: usize = 8 * 1024;
+
+/// Most log lines retained per task; later lines are dropped.
+pub const MAX_TASK_LOG_LINES: i64 = 10_000;
+
+const TASK_LOG_LEVELS: &[&str] = &["debug", "info", "warn", "error"];
+
+/// Execution log lines reported by an assigned node
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TaskLogBatch {
+    pub node_id: String,
+    pub lines: Vec<TaskLogLine>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TaskLogLine {
+    /// One of `debug`, `info` (default), `warn`, `error`.
+    #[serde(default = "default_task_log_level")]
+    pub level: String,
+    pub message: String,
+}
+
+fn default_task_log_level() -> String {
+    "info".to_string()
+}
+
+impl TaskLogBatch {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.lines.is_empty() || self.lines.len() > MAX_TASK_LOG_BATCH_LINES {
+            return Err(ApiError::bad_request(format!(
+                "lines must contain 1-{} entries",
+                MAX_TASK_LOG_BATCH_LINES
+            )));
+        }
+
+        for line in &self.lines {
+            if !TASK_LOG_LEVELS.contains(&line.level.as_str()) {
+                return Err(ApiError::bad_request(format!(
+                    "level must be one of: {}",
+                    TASK_LOG_LEVELS.join(", ")
+                )));
+            }
+            if line.message.len() > MAX_TASK_LOG_LINE_BYTES {
+                return Err(ApiError::bad_request(format!(
+                    "log lines cannot exceed {} bytes",
+                    MAX_TASK_LOG_LINE_BYTES
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A stored task log line, sent as the `log` event of the log stream
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TaskLogEntry {
+    /// Monotonic id, also used as the SSE event id for `Last-Event-ID` resume.
+    pub log_id: i64,
+    pub node_id: String,
+    pub level: String,
+    pub message: String,
+    pub logged_at: String,
+}
+
+/// Create an organization; the caller becomes its owner
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateOrganizationRequest {
+    pub name: String,
+}
+
+impl CreateOrganizationRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let name = self.name.trim();
+        if name.is_empty() || name.len() > 128 {
+            return Err(ApiError::bad_request(
+                "name must be between 1 and 128 characters",
+            ));
+        }
+        if name.chars().any(char::is_control) {
+            return Err(ApiError::bad_request(
+                "name cannot contain control characters",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// An organization and the caller's role in it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrganizationInfo {
+    pub org_id: String,
+    pub name: String,
+    pub role: crate::orgs::OrgRole,
+    pub created_at: String,
+}
+
+/// A member of an organization
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrganizationMember {
+    pub user_id: String,
+    pub username: String,
+    pub role: crate::orgs::OrgRole,
+    pub joined_at: String,
+}
+
+/// Add a user to an organization or change their role
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetOrganizationMemberRequest {
+    pub username: String,
+    pub role: crate::orgs::OrgRole,
+}
+
+/// Metadata for an uploaded WASM module
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WasmModuleInfo {
+    /// Hex SHA3-256 of the module binary.
+    pub module_hash: String,
+    /// Value to use as `TaskSubmission.wasm_module`.
+    pub reference: String,
+    pub size_bytes: i64,
+    pub storage_backend: String,
+    pub created_at: String,
+}
+
 /// Task information
 #[derive(Debug, Serialize, ToSchema, Clone)]
 pub struct TaskInfo {
@@ -514,6 +1688,121 @@ pub struct TaskInfo {
     pub updated_at: String,
     pub result: Option<serde_json::Value>,
     pub proof_id: Option<String>,
+    pub priority: u8,
+    /// Number of times the task has been re-queued after a failed attempt.
+    pub retry_count: u32,
+    /// Reason the most recent attempt failed, if any.
+    pub last_error: Option<String>,
+    /// 1-based position in the pending queue (after aging); `None` once scheduled.
+    pub queue_position: Option<i64>,
+    /// Network destinations the task was allowed to reach.
+    pub egress: Vec<TaskEgressRule>,
+    /// Whether the preferred node type was dropped after the task waited
+    /// past `TASK_STARVATION_RELAX_SECS`.
+    pub constraints_relaxed: bool,
+    /// When the task was flagged as starving, if it has been.
+    pub starving_since: Option<String>,
+    /// Why no node matched, recorded when the task became `unschedulable`.
+    pub scheduling_diagnostics: Option<serde_json::Value>,
+    /// Whether the task can be handed off between nodes from a checkpoint.
+    pub checkpointable: bool,
+    /// Sequence number of the newest stored checkpoint, if any.
+    pub checkpoint_seq: Option<u64>,
+    pub node_selector: BTreeMap<String, String>,
+    pub diversity: DiversityMode,
+    pub preferred_regions: Vec<String>,
+    pub excluded_regions: Vec<String>,
+    pub region_strictness: RegionStrictness,
+    /// W3C trace context of the submitting request.  Node agents send a
+    /// child of it as `traceparent` on their requests for this task.
+    pub traceparent: Option<String>,
+}
+
+/// Metadata of a stored task checkpoint.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TaskCheckpointInfo {
+    pub task_id: String,
+    /// Node that uploaded the checkpoint.
+    pub node_id: String,
+    pub seq: u64,
+    /// Hex SHA3-256 of the checkpoint bytes.
+    pub sha3_256: String,
+    pub size_bytes: u64,
+    pub created_at: String,
+}
+
+/// A verified sandbox report in a task's provenance bundle.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TaskSandboxReport {
+    pub node_id: String,
+    /// Hex SHA3-256 of the canonical report bytes.
+    pub report_sha3_256: String,
+    /// Base64 Ed25519 key the node registered and signed with.
+    pub signer_public_key: String,
+    pub signature: String,
+    /// `wasm_engine::SandboxReport`: limits, capabilities, hostcall counts
+    /// and checkpoint scratch entries touched.
+    #[schema(value_type = Object)]
+    pub report: serde_json::Value,
+    /// `wasm_engine::environment_fingerprint` of the report's limits and
+    /// capabilities.
+    pub environment_sha3_256: Option<String>,
+    pub created_at: String,
+}
+
+/// Evidence of how a task was executed.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TaskProvenance {
+    pub task_id: String,
+    pub task_type: String,
+    pub status: TaskStatus,
+    /// Hex SHA3-256 of the task's WASM module, whether it was submitted
+    /// inline or as a `sha3-256:` reference; `None` for tasks without one.
+    pub module_hash: Option<String>,
+    /// Inputs the task was submitted with.
+    #[schema(value_type = Object)]
+    pub inputs: serde_json::Value,
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<serde_json::Value>,
+    pub proof_id: Option<String>,
+    /// Network destinations the task declared.
+    pub egress: Vec<TaskEgressRule>,
+    /// One signed report per node that submitted a result.
+    pub sandbox_reports: Vec<TaskSandboxReport>,
+}
+
+/// One entry in a task's timeline.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TaskTimelineEvent {
+    /// `submitted`, `constraints_relaxed`, `starving`, `assigned`, `started`,
+    /// `checkpointed`, `result_submitted`, `assignment_failed`, `handed_off`,
+    /// `proof_verified`, `completed`, `failed`, `unschedulable`,
+    /// `notification_queued`, or another audit action recorded for the task.
+    pub event: String,
+    pub at: String,
+    /// Node the event happened on, for assignment and checkpoint events.
+    pub node_id: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<serde_json::Value>,
+}
+
+/// Everything recorded about a task's life, oldest first.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TaskTimeline {
+    pub task_id: String,
+    pub status: TaskStatus,
+    pub events: Vec<TaskTimelineEvent>,
+}
+
+/// Outcome of draining a node.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct NodeDrainResponse {
+    pub node_id: String,
+    pub status: String,
+    /// Checkpointable tasks moved off the node to resume elsewhere.
+    pub handed_off_tasks: Vec<String>,
+    /// Tasks left to finish on the node because they cannot be checkpointed.
+    pub finishing_tasks: Vec<String>,
 }
 
 /// Task status
@@ -524,6 +1813,8 @@ pub enum TaskStatus {
     Running,
     Completed,
     Failed,
+    /// Pending past `TASK_UNSCHEDULABLE_SECS` without matching enough nodes.
+    Unschedulable,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -552,6 +1843,86 @@ impl ConnectSessionStartRequest {
     }
 }
 
+/// Request to push an active connect session's expiry forward.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ConnectSessionExtendRequest {
+    /// Seconds to add to the current expiry (1–3600).
+    pub extend_seconds: u64,
+}
+
+impl ConnectSessionExtendRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.extend_seconds == 0 || self.extend_seconds > 3600 {
+            return Err(ApiError::bad_request(
+                "extend_seconds must be between 1 and 3600",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Largest data cap a `connect_only` task may set, in megabytes (10 TB).
+pub const MAX_CONNECT_SESSION_DATA_CAP_MB: u64 = 10_000_000;
+
+/// Most bytes one usage report may add in either direction (1 TiB).
+pub const MAX_CONNECT_SESSION_USAGE_BYTES: u64 = 1 << 40;
+
+/// Bytes a gateway relayed for a connect session during one reporting
+/// interval.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ConnectSessionUsageReport {
+    /// Node relaying the session
+    pub node_id: String,
+    /// Bytes sent from the requester towards the internet
+    pub bytes_up: u64,
+    /// Bytes delivered back to the requester
+    pub bytes_down: u64,
+    /// Length of the interval in seconds (1–3600)
+    pub interval_seconds: u64,
+    /// Increases with every report; a report at or below the last accepted
+    /// `seq` is ignored, so retries are safe.
+    pub seq: u64,
+}
+
+impl ConnectSessionUsageReport {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.node_id.is_empty() {
+            return Err(ApiError::bad_request("node_id cannot be empty"));
+        }
+        if self.interval_seconds == 0 || self.interval_seconds > 3600 {
+            return Err(ApiError::bad_request(
+                "interval_seconds must be between 1 and 3600",
+            ));
+        }
+        if self.bytes_up > MAX_CONNECT_SESSION_USAGE_BYTES
+            || self.bytes_down > MAX_CONNECT_SESSION_USAGE_BYTES
+        {
+            return Err(ApiError::bad_request(format!(
+                "bytes_up and bytes_down cannot exceed {MAX_CONNECT_SESSION_USAGE_BYTES} per report"
+            )));
+        }
+        if self.seq == 0 || self.seq > i64::MAX as u64 {
+            return Err(ApiError::bad_request("seq must be a positive integer"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Data relayed for a connect session so far.
+#[derive(Debug, Serialize, ToSchema, Clone, Default)]
+pub struct ConnectSessionUsage {
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+    pub total_bytes: u64,
+    /// Bytes after which the session is ended; `None` when uncapped
+    pub data_cap_bytes: Option<u64>,
+    /// Whether usage has reached `data_cap_bytes`
+    pub data_cap_reached: bool,
+    pub last_reported_at: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum ConnectSessionStatus {
@@ -579,6 +1950,8 @@ pub struct ConnectSessionInfo {
     pub expires_at: String,
     pub last_heartbeat_at: Option<String>,
     pub ended_at: Option<String>,
+    /// Bytes relayed so far, as reported by the gateway node
+    pub usage: ConnectSessionUsage,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -588,7 +1961,7 @@ pub struct ConnectSessionStartResponse {
 }
 
 /// Proof verification request
-#[derive(Debug, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ProofVerificationRequest {
     pub task_id: String,
     pub proof_data: String,         // Base64 encoded proof
@@ -663,10 +2036,36 @@ pub struct ProofVerificationResponse {
     pub verified_at: String,
     pub verification_time_ms: u64,
     pub error_message: Option<String>,
+    /// Stored proof, retrievable at `GET /api/v1/proofs/{proof_id}`
+    pub proof_id: Option<String>,
+}
+
+/// A stored proof and the outcome of its verification
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct ProofRecord {
+    pub proof_id: String,
+    /// Task the proof belongs to, if any
+    pub task_id: Option<String>,
+    /// Node that submitted the proof with its task result
+    pub node_id: Option<String>,
+    pub circuit_id: String,
+    pub proof_system: String,
+    /// Base64 encoded proof
+    pub proof_data: String,
+    /// Base64 encoded public inputs
+    pub public_inputs: String,
+    pub verified: bool,
+    pub error_message: Option<String>,
+    pub verification_time_ms: Option<u64>,
+    pub created_at: String,
+    /// `None` while verifier nodes are still checking an offloaded proof
+    pub verified_at: Option<String>,
+    /// `verify_proof` task an offloaded proof was handed to verifier nodes as
+    pub verification_task_id: Option<String>,
 }
 
 /// Cluster statistics
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, async_graphql::SimpleObject)]
 pub struct ClusterStats {
     pub total_nodes: usize,
     pub healthy_nodes: usize,
@@ -683,7 +2082,7 @@ pub struct ClusterStats {
 /// For tasks that require proof (`require_proof = true`) the caller should
 /// include `proof_data` and `public_inputs` (both Base64-encoded).  The
 /// server verifies the proof before marking the task as completed.
-#[derive(Debug, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct NodeTaskResult {
     /// ID of the node that performed the work.
     pub node_id: String,
@@ -697,6 +2096,34 @@ pub struct NodeTaskResult {
     pub public_inputs: Option<String>,
     /// Circuit identifier; defaults to `"default"` when omitted.
     pub circuit_id: Option<String>,
+    /// When the proof was generated (Unix seconds, node clock).  Corrected by
+    /// the node's reported clock skew and rejected outside the server tolerance.
+    #[serde(default)]
+    pub proof_timestamp: Option<u64>,
+    /// Energy the node attributed to this task (watt-hours), from its power telemetry.
+    #[serde(default)]
+    pub energy_wh: Option<f64>,
+    /// CPU time the sandboxed execution used (milliseconds), from
+    /// `wasm_engine::ResourceUsage`.
+    #[serde(default)]
+    pub cpu_time_ms: Option<u64>,
+    /// Largest linear memory the sandboxed module reached (bytes).
+    #[serde(default)]
+    pub peak_memory_bytes: Option<u64>,
+    /// Set when execution failed on this node; the task is retried elsewhere
+    /// if its retry policy allows, otherwise it is marked failed.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Sandbox report signed with the node's `signing_public_key`
+    /// (`ambient_node::SignedSandboxReport`), kept in the task's provenance.
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub sandbox_report: Option<ambient_node::SignedSandboxReport>,
+    /// Verdict on the offloaded proof of a `verify_proof` task, signed with
+    /// the node's `signing_public_key` (`ambient_node::SignedProofVerdict`).
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub proof_verdict: Option<ambient_node::SignedProofVerdict>,
 }
 
 impl NodeTaskResult {
@@ -708,6 +2135,38 @@ impl NodeTaskResult {
             return Err(ApiError::bad_request("node_id cannot exceed 64 characters"));
         }
 
+        if let Some(energy_wh) = self.energy_wh {
+            validate_energy_wh(energy_wh)?;
+        }
+
+        for (field, value, max) in [
+            (
+                "execution_time_ms",
+                self.execution_time_ms,
+                MAX_REPORTED_DURATION_MS,
+            ),
+            ("cpu_time_ms", self.cpu_time_ms, MAX_REPORTED_DURATION_MS),
+            (
+                "peak_memory_bytes",
+                self.peak_memory_bytes,
+                MAX_REPORTED_MEMORY_BYTES,
+            ),
+        ] {
+            if value.is_some_and(|value| value > max) {
+                return Err(ApiError::bad_request(format!(
+                    "{field} cannot exceed {max}"
+                )));
+            }
+        }
+
+        if let Some(ref error) = self.error {
+            if error.is_empty() || error.len() > 2048 {
+                return Err(ApiError::bad_request(
+                    "error must be between 1 and 2048 characters",
+                ));
+            }
+        }
+
         if let Some(ref proof_data) = self.proof_data {
             if proof_data.len() > 100_000 {
                 return Err(ApiError::bad_request(
@@ -738,10 +2197,441 @@ impl NodeTaskResult {
     }
 }
 
+/// Upper bound on a single energy report (1 MWh) to reject corrupt telemetry.
+const MAX_REPORTED_ENERGY_WH: f64 = 1_000_000.0;
+/// Thirty days: far beyond any task's execution limit.
+const MAX_REPORTED_DURATION_MS: u64 = 30 * 24 * 60 * 60 * 1000;
+/// 1 TiB.
+const MAX_REPORTED_MEMORY_BYTES: u64 = 1 << 40;
+
+fn validate_energy_wh(energy_wh: f64) -> Result<(), ApiError> {
+    if !energy_wh.is_finite() || !(0.0..=MAX_REPORTED_ENERGY_WH).contains(&energy_wh) {
+        return Err(ApiError::bad_request(
+            "energy_wh must be a finite value between 0 and 1,000,000",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Usage report sent by a relay node for an active gateway session.
+///
+/// `energy_wh` is the cumulative energy metered for the session so far; the
+/// server keeps the largest value reported.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GatewaySessionUsageReport {
+    pub energy_wh: f64,
+}
+
+impl GatewaySessionUsageReport {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        validate_energy_wh(self.energy_wh)
+    }
+}
+
+/// Energy and estimated emissions attributed to a single node region.
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct RegionEnergyUsage {
+    pub region: String,
+    pub energy_wh: f64,
+    /// Grid carbon intensity applied to this region (g CO2e / kWh).
+    pub grid_factor_g_per_kwh: f64,
+    pub estimated_co2_grams: f64,
+}
+
+/// Time a user's tasks spent pending before they started running.
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct TaskQueueWait {
+    /// Tasks that have started running at least once.
+    pub tasks_started: i64,
+    /// Mean of each started task's total pending time, across re-queues
+    /// (`null` when none started).
+    pub avg_wait_seconds: Option<f64>,
+    pub max_wait_seconds: Option<f64>,
+}
+
+/// Per-user energy and carbon usage across submitted tasks and relay sessions.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UsageReport {
+    pub user_id: String,
+    /// Energy reported by nodes for tasks created by this user (Wh).
+    pub task_energy_wh: f64,
+    /// Energy reported by relay nodes for this user's connect sessions (Wh).
+    pub session_energy_wh: f64,
+    pub total_energy_wh: f64,
+    pub estimated_co2_grams: f64,
+    /// CPU time nodes reported for attempts at this user's tasks (ms).
+    pub task_cpu_time_ms: i64,
+    pub task_queue_wait: TaskQueueWait,
+    pub regions: Vec<RegionEnergyUsage>,
+    pub generated_at: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn heartbeat(
+        seq: u64,
+        baseline_seq: Option<u64>,
+        state: serde_json::Value,
+    ) -> NodeHeartbeatRequest {
+        NodeHeartbeatRequest {
+            seq: Some(seq),
+            baseline_seq,
+            state: state.as_object().cloned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn legacy_node_types_normalize_with_a_warning() {
+        assert_eq!(
+            normalize_node_type("any"),
+            ("universal".to_string(), Some("any".to_string()))
+        );
+        assert_eq!(
+            normalize_node_type("compute"),
+            ("compute".to_string(), None)
+        );
+        assert_eq!(
+            node_kind_warnings(Some("worker")),
+            vec!["node_type 'worker' is deprecated and was stored as 'compute'; register with 'compute'"]
+        );
+        assert!(node_kind_warnings(None).is_empty());
+        assert!(node_kind_warnings(Some("compute")).is_empty());
+    }
+
+    #[test]
+    fn heartbeat_deltas_rebuild_state_and_detect_gaps() {
+        let full = heartbeat(1, None, serde_json::json!({"cpu": 10, "mem": 40}));
+        let applied = full.apply_state(None, None).unwrap().unwrap();
+        assert_eq!(applied.seq, Some(1));
+        let stored = applied.state.unwrap();
+
+        let delta = heartbeat(2, Some(1), serde_json::json!({"cpu": 55, "mem": null}));
+        let applied = delta.apply_state(Some(1), Some(&stored)).unwrap().unwrap();
+        assert_eq!(applied.seq, Some(2));
+        assert_eq!(
+            serde_json::Value::Object(applied.state.unwrap()),
+            serde_json::json!({"cpu": 55})
+        );
+
+        // A lost heartbeat leaves the server at seq 1, so a delta from 2 is a gap.
+        let gap = heartbeat(3, Some(2), serde_json::json!({}));
+        assert_eq!(gap.apply_state(Some(1), Some(&stored)).unwrap(), None);
+        assert_eq!(gap.apply_state(None, None).unwrap(), None);
+
+        // Legacy heartbeats keep whatever state is stored.
+        let legacy = NodeHeartbeatRequest::default();
+        assert_eq!(
+            legacy.apply_state(Some(7), Some(&stored)).unwrap(),
+            Some(HeartbeatStateUpdate {
+                seq: Some(7),
+                state: None
+            })
+        );
+    }
+
+    #[test]
+    fn slot_class_follows_task_type_and_gpu() {
+        assert_eq!(
+            SlotClass::for_task("connect_only", false),
+            SlotClass::Connect
+        );
+        assert_eq!(SlotClass::for_task("computation", false), SlotClass::Wasm);
+        assert_eq!(SlotClass::for_task("zk_proof", true), SlotClass::Gpu);
+
+        let slots = NodeSlots {
+            connect: Some(0),
+            wasm: Some(MAX_NODE_SLOTS + 1),
+            gpu: None,
+        };
+        assert!(slots.validate().is_err());
+        assert!(NodeSlots::default().validate().is_ok());
+    }
+
+    #[test]
+    fn node_updates_merge_and_validate() {
+        let current = NodeCapabilities {
+            bandwidth_mbps: 100.0,
+            cpu_cores: 4,
+            memory_gb: 8.0,
+            gpu_available: false,
+        };
+        let update: NodeUpdateRequest =
+            serde_json::from_value(serde_json::json!({"gpu_available": true, "cpu_cores": 8}))
+                .unwrap();
+        assert!(update.validate().is_ok());
+        let merged = update.apply(&current).unwrap();
+        assert!(merged.gpu_available);
+        assert_eq!(merged.cpu_cores, 8);
+        assert_eq!(merged.memory_gb, 8.0);
+
+        assert!(NodeUpdateRequest::default().validate().is_err());
+        let bad_type = NodeUpdateRequest {
+            node_type: Some("mainframe".into()),
+            ..Default::default()
+        };
+        assert!(bad_type.validate().is_err());
+        let bad_cpu = NodeUpdateRequest {
+            cpu_cores: Some(0),
+            ..Default::default()
+        };
+        assert!(bad_cpu.apply(&current).is_err());
+    }
+
+    #[test]
+    fn labels_are_bounded_and_charset_checked() {
+        let labels = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        assert!(validate_labels(
+            "labels",
+            &labels(&[("gpu_vendor", "nvidia"), ("topology.io/zone", "eu-west-1a")])
+        )
+        .is_ok());
+        assert!(validate_labels("labels", &labels(&[("", "x")])).is_err());
+        assert!(validate_labels("labels", &labels(&[("zone", "eu west")])).is_err());
+        assert!(validate_labels("labels", &labels(&[("zone", &"a".repeat(64))])).is_err());
+        let too_many: BTreeMap<String, String> = (0..=MAX_NODE_LABELS)
+            .map(|i| (format!("k{i}"), "v".to_string()))
+            .collect();
+        assert!(validate_labels("node_selector", &too_many).is_err());
+    }
+
+    #[test]
+    fn task_type_filters_take_registered_types_once() {
+        let list = |types: &[&str]| types.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert!(validate_task_type_filter("blocked_task_types", &[]).is_ok());
+        assert!(validate_task_type_filter(
+            "blocked_task_types",
+            &list(&["connect_only", "federated_learning"])
+        )
+        .is_ok());
+        assert!(validate_task_type_filter("allowed_task_types", &list(&["mining"])).is_err());
+        assert!(validate_task_type_filter(
+            "allowed_task_types",
+            &list(&["connect_only", "connect_only"])
+        )
+        .is_err());
+
+        let update: NodeUpdateRequest =
+            serde_json::from_value(serde_json::json!({"blocked_task_types": ["connect_only"]}))
+                .unwrap();
+        assert!(update.validate().is_ok());
+        let update: NodeUpdateRequest =
+            serde_json::from_value(serde_json::json!({"allowed_task_types": ["nope"]})).unwrap();
+        assert!(update.validate().is_err());
+    }
+
+    #[test]
+    fn task_log_batches_are_bounded() {
+        let batch = |level: &str, message: String, count: usize| TaskLogBatch {
+            node_id: "node-1".to_string(),
+            lines: (0..count)
+                .map(|_| TaskLogLine {
+                    level: level.to_string(),
+                    message: message.clone(),
+                })
+                .collect(),
+        };
+        assert!(batch("info", "started".into(), 1).validate().is_ok());
+        assert!(batch("info", "started".into(), 0).validate().is_err());
+        assert!(batch("trace", "started".into(), 1).validate().is_err());
+        assert!(batch("warn", "x".repeat(MAX_TASK_LOG_LINE_BYTES + 1), 1)
+            .validate()
+            .is_err());
+        assert!(batch("info", "x".into(), MAX_TASK_LOG_BATCH_LINES + 1)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn heartbeat_batches_reject_duplicates_and_invalid_items() {
+        let batch: NodeHeartbeatBatchRequest = serde_json::from_value(serde_json::json!({
+            "heartbeats": [
+                {"node_id": "node-1"},
+                {"node_id": "node-2", "clock_skew_ms": 15, "seq": 1, "state": {"cpu": 3}}
+            ]
+        }))
+        .unwrap();
+        assert!(batch.validate().is_ok());
+        assert_eq!(batch.heartbeats[1].heartbeat.clock_skew_ms, Some(15));
+
+        let duplicate: NodeHeartbeatBatchRequest = serde_json::from_value(serde_json::json!({
+            "heartbeats": [{"node_id": "node-1"}, {"node_id": "node-1"}]
+        }))
+        .unwrap();
+        assert!(duplicate.validate().is_err());
+
+        let invalid: NodeHeartbeatBatchRequest = serde_json::from_value(serde_json::json!({
+            "heartbeats": [{"node_id": "node-1", "baseline_seq": 3}]
+        }))
+        .unwrap();
+        assert!(invalid.validate().is_err());
+
+        let empty = NodeHeartbeatBatchRequest { heartbeats: vec![] };
+        assert!(empty.validate().is_err());
+    }
+
+    #[test]
+    fn heartbeat_sequence_fields_are_validated() {
+        assert!(heartbeat(2, Some(1), serde_json::json!({}))
+            .validate()
+            .is_ok());
+        assert!(heartbeat(1, Some(1), serde_json::json!({}))
+            .validate()
+            .is_err());
+        let orphan_baseline = NodeHeartbeatRequest {
+            baseline_seq: Some(1),
+            ..Default::default()
+        };
+        assert!(orphan_baseline.validate().is_err());
+        let too_many: serde_json::Map<_, _> = (0..=MAX_HEARTBEAT_STATE_FIELDS)
+            .map(|i| (format!("f{i}"), serde_json::json!(i)))
+            .collect();
+        assert!(heartbeat(1, None, serde_json::Value::Object(too_many))
+            .validate()
+            .is_err());
+    }
+
+    fn sealed_secret(node_id: &str, name: &str) -> SealedTaskSecret {
+        let b64 = |bytes: &[u8]| {
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes)
+        };
+        SealedTaskSecret {
+            node_id: node_id.to_string(),
+            name: name.to_string(),
+            ephemeral_public_key: b64(&[7u8; 32]),
+            nonce: b64(&[1u8; 12]),
+            ciphertext: b64(&[9u8; 40]),
+        }
+    }
+
+    #[test]
+    fn task_egress_rules_require_ports_and_wasm_task_type() {
+        let rule = |host: &str, ports: Vec<u16>| TaskEgressRule {
+            host: host.to_string(),
+            ports,
+        };
+        assert!(rule("api.example.com", vec![443]).validate().is_ok());
+        assert!(rule("*.data.org", vec![443, 8443]).validate().is_ok());
+        assert!(rule("api.example.com", vec![]).validate().is_err());
+        assert!(rule("*", vec![443]).validate().is_err());
+        assert!(rule("http://x", vec![443]).validate().is_err());
+
+        let submission = |task_type: &str| TaskSubmission {
+            task_type: task_type.to_string(),
+            wasm_module: None,
+            inputs: serde_json::json!({}),
+            requirements: TaskRequirements {
+                min_nodes: 1,
+                max_execution_time_sec: 60,
+                require_gpu: false,
+                require_proof: false,
+                scheduling_mode: SchedulingMode::Standard,
+                max_retries: 0,
+                retry_backoff_sec: 0,
+                egress: vec![rule("api.example.com", vec![443])],
+                checkpointable: false,
+                node_selector: Default::default(),
+                diversity: Default::default(),
+                preferred_regions: Vec::new(),
+                excluded_regions: Vec::new(),
+                region_strictness: Default::default(),
+            },
+            priority: 0,
+        };
+        assert!(submission("wasm_execution").validate().is_ok());
+        assert!(submission("computation").validate().is_err());
+
+        let mut checkpointed = submission("wasm_execution");
+        checkpointed.requirements.checkpointable = true;
+        assert!(checkpointed.validate().is_ok());
+        checkpointed.task_type = "computation".to_string();
+        checkpointed.requirements.egress.clear();
+        assert!(checkpointed.validate().is_err());
+    }
+
+    #[test]
+    fn task_region_placement_is_validated() {
+        let requirements = |placement: serde_json::Value| {
+            let mut value = serde_json::json!({
+                "min_nodes": 1,
+                "max_execution_time_sec": 60,
+                "require_gpu": false,
+                "require_proof": false,
+            });
+            value
+                .as_object_mut()
+                .unwrap()
+                .extend(placement.as_object().unwrap().clone());
+            serde_json::from_value::<TaskRequirements>(value)
+                .unwrap()
+                .validate()
+        };
+        assert!(requirements(serde_json::json!({})).is_ok());
+        assert!(requirements(serde_json::json!({
+            "preferred_regions": ["eu-west"],
+            "excluded_regions": ["us-east"],
+            "region_strictness": "require",
+        }))
+        .is_ok());
+        assert!(requirements(serde_json::json!({ "region_strictness": "require" })).is_err());
+        assert!(requirements(serde_json::json!({
+            "preferred_regions": ["eu-west"],
+            "excluded_regions": ["eu-west"],
+        }))
+        .is_err());
+        assert!(requirements(serde_json::json!({ "excluded_regions": [""] })).is_err());
+        let too_many: Vec<String> = (0..17).map(|i| format!("r{i}")).collect();
+        assert!(requirements(serde_json::json!({ "preferred_regions": too_many })).is_err());
+    }
+
+    #[test]
+    fn task_secrets_upload_validates_shape_and_duplicates() {
+        let upload = TaskSecretsUpload {
+            secrets: vec![
+                sealed_secret("node-1", "db_token"),
+                sealed_secret("node-2", "db_token"),
+            ],
+        };
+        assert!(upload.validate().is_ok());
+
+        let duplicate = TaskSecretsUpload {
+            secrets: vec![
+                sealed_secret("node-1", "db_token"),
+                sealed_secret("node-1", "db_token"),
+            ],
+        };
+        assert!(duplicate.validate().is_err());
+
+        let mut bad_nonce = sealed_secret("node-1", "db_token");
+        bad_nonce.nonce = "AAAA".to_string();
+        assert!(bad_nonce.validate().is_err());
+
+        assert!(sealed_secret("node-1", "bad name").validate().is_err());
+    }
+
+    #[test]
+    fn gateway_session_usage_report_rejects_invalid_energy() {
+        assert!(GatewaySessionUsageReport { energy_wh: 12.5 }
+            .validate()
+            .is_ok());
+        assert!(GatewaySessionUsageReport { energy_wh: -1.0 }
+            .validate()
+            .is_err());
+        assert!(GatewaySessionUsageReport {
+            energy_wh: f64::INFINITY
+        }
+        .validate()
+        .is_err());
+    }
+
     #[test]
     fn connect_session_start_request_accepts_supported_protocols() {
         let request = ConnectSessionStartRequest {
@@ -761,4 +2651,13 @@ mod tests {
 
         assert!(request.validate().is_err());
     }
+
+    #[test]
+    fn connect_session_extend_request_bounds_extension() {
+        let extend = |extend_seconds| ConnectSessionExtendRequest { extend_seconds }.validate();
+        assert!(extend(600).is_ok());
+        assert!(extend(3600).is_ok());
+        assert!(extend(0).is_err());
+        assert!(extend(3601).is_err());
+    }
 }
@@ -0,0 +1,353 @@
+/// Node rankings for requesters
+///
+/// `GET /api/v1/nodes/rankings?task_type=` ranks the active nodes that can
+/// take a task type so requesters can pick nodes with data behind them.
+/// Each node is scored on four components normalised to `[0, 1]`:
+///
+/// - `success`: share of the node's finished attempts at the task type that
+///   completed, smoothed towards 0.5 so one lucky attempt does not top the
+///   list.
+/// - `speed`: the fastest median execution time in the pool divided by the
+///   node's own median for the task type; 0.5 without samples.
+/// - `health`: `health_score` / 100.
+/// - `reputation`: `ambient_node::Reputation::score` over every attempt the
+///   node finished, whatever the task type.
+///
+/// The score is the weighted sum, and every component is returned with its
+/// weight and contribution so the ranking can be checked by hand.
+use crate::error::ApiError;
+use crate::models::{task_type_registry_entry, TaskTypeRegistryEntry, TASK_TYPE_REGISTRY};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+pub const SUCCESS_WEIGHT: f64 = 0.4;
+pub const SPEED_WEIGHT: f64 = 0.2;
+pub const HEALTH_WEIGHT: f64 = 0.2;
+pub const REPUTATION_WEIGHT: f64 = 0.2;
+
+/// Score for a component the node has no history for.
+const NEUTRAL_SCORE: f64 = 0.5;
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 500;
+
+/// Query string for `GET /api/v1/nodes/rankings`
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NodeRankingsQuery {
+    /// Task type to rank nodes for.
+    pub task_type: String,
+    /// Nodes to return, best first (default 50, at most 500).
+    pub limit: Option<usize>,
+}
+
+impl NodeRankingsQuery {
+    pub fn resolve(&self) -> Result<(&'static TaskTypeRegistryEntry, usize), ApiError> {
+        let entry = task_type_registry_entry(&self.task_type).ok_or_else(|| {
+            ApiError::bad_request(format!(
+                "task_type must be one of: {}",
+                TASK_TYPE_REGISTRY
+                    .iter()
+                    .map(|entry| entry.task_type)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?;
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(ApiError::bad_request(format!(
+                "limit must be between 1 and {MAX_LIMIT}"
+            )));
+        }
+        Ok((entry, limit))
+    }
+}
+
+/// What is known about a node before scoring.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankingCandidate {
+    pub node_id: String,
+    pub region: String,
+    pub node_type: String,
+    pub status: String,
+    /// Health score in the 0–100 range stored on `nodes.health_score`.
+    pub health_score: f64,
+    /// Attempts at the ranked task type that completed or failed.
+    pub completed_attempts: u64,
+    pub failed_attempts: u64,
+    pub median_execution_time_ms: Option<f64>,
+    /// Attempts at any task type, for reputation.
+    pub total_completed_attempts: u64,
+    pub total_failed_attempts: u64,
+}
+
+impl RankingCandidate {
+    /// Decode a row selected by [`CANDIDATES_SQL`].
+    pub fn from_row(row: &sqlx::postgres::PgRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+        let count = |column: &str| row.try_get::<i64, _>(column).map(|n| n.max(0) as u64);
+        Ok(Self {
+            node_id: row.try_get("node_id")?,
+            region: row.try_get("region")?,
+            node_type: row.try_get("node_type")?,
+            status: row.try_get("status")?,
+            health_score: row.try_get("health_score")?,
+            completed_attempts: count("completed_attempts")?,
+            failed_attempts: count("failed_attempts")?,
+            median_execution_time_ms: row.try_get("median_execution_time_ms")?,
+            total_completed_attempts: count("total_completed_attempts")?,
+            total_failed_attempts: count("total_failed_attempts")?,
+        })
+    }
+}
+
+/// Active nodes of the given types whose operators accept the task type,
+/// with their attempt history.
+///
+/// Binds: `$1` task type, `$2` node types that serve it.  Execution time is
+/// what the node reported, or the span between its start and completion.
+pub const CANDIDATES_SQL: &str = r#"
+SELECT n.node_id, n.region, n.node_type, n.status, n.health_score,
+       COUNT(*) FILTER (WHERE t.task_type = $1 AND ta.execution_status = 'completed') AS completed_attempts,
+       COUNT(*) FILTER (WHERE t.task_type = $1 AND ta.execution_status = 'failed') AS failed_attempts,
+       percentile_cont(0.5) WITHIN GROUP (
+           ORDER BY COALESCE(
+               ta.execution_time_ms::DOUBLE PRECISION,
+               extract(epoch FROM ta.execution_completed_at - ta.execution_started_at) * 1000
+           )
+       ) FILTER (WHERE t.task_type = $1 AND ta.execution_status = 'completed') AS median_execution_time_ms,
+       COUNT(*) FILTER (WHERE ta.execution_status = 'completed') AS total_completed_attempts,
+       COUNT(*) FILTER (WHERE ta.execution_status = 'failed') AS total_failed_attempts
+FROM nodes n
+LEFT JOIN task_assignments ta ON ta.node_id = n.node_id
+LEFT JOIN tasks t ON t.task_id = ta.task_id
+WHERE n.deleted_at IS NULL
+  AND n.status != 'rejected'
+  AND n.node_type = ANY($2)
+  AND (CARDINALITY(n.allowed_task_types) = 0 OR $1 = ANY(n.allowed_task_types))
+  AND NOT ($1 = ANY(n.blocked_task_types))
+GROUP BY n.node_id
+"#;
+
+/// One scoring component.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct RankingComponent {
+    /// Normalised to `[0, 1]`.
+    pub value: f64,
+    pub weight: f64,
+    /// `value * weight`; the components' contributions sum to the score.
+    pub contribution: f64,
+}
+
+impl RankingComponent {
+    fn new(value: f64, weight: f64) -> Self {
+        let value = value.clamp(0.0, 1.0);
+        Self {
+            value,
+            weight,
+            contribution: value * weight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct RankingBreakdown {
+    pub success: RankingComponent,
+    pub speed: RankingComponent,
+    pub health: RankingComponent,
+    pub reputation: RankingComponent,
+}
+
+/// A ranked node with the inputs and components behind its score.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct NodeRanking {
+    /// 1 for the best node.
+    pub rank: usize,
+    pub node_id: String,
+    pub region: String,
+    pub node_type: String,
+    pub status: String,
+    /// Weighted sum of the breakdown, in `[0, 1]`.
+    pub score: f64,
+    pub health_score: f64,
+    pub completed_attempts: u64,
+    pub failed_attempts: u64,
+    /// `null` when the node has no finished attempts at the task type.
+    pub success_rate: Option<f64>,
+    /// `null` when the node has not completed the task type.
+    pub median_execution_time_ms: Option<f64>,
+    pub breakdown: RankingBreakdown,
+}
+
+/// Response for `GET /api/v1/nodes/rankings`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NodeRankingsResponse {
+    pub task_type: String,
+    pub rankings: Vec<NodeRanking>,
+}
+
+fn breakdown(candidate: &RankingCandidate, fastest_median_ms: Option<f64>) -> RankingBreakdown {
+    let finished = candidate.completed_attempts + candidate.failed_attempts;
+    let success = (candidate.completed_attempts as f64 + 1.0) / (finished as f64 + 2.0);
+
+    let speed = match (candidate.median_execution_time_ms, fastest_median_ms) {
+        (Some(median), Some(fastest)) if median > 0.0 => fastest / median,
+        (Some(_), Some(_)) => 1.0,
+        _ => NEUTRAL_SCORE,
+    };
+
+    let reputation = ambient_node::reputation::Reputation {
+        completed_tasks: candidate.total_completed_attempts,
+        failed_tasks: candidate.total_failed_attempts,
+        ..Default::default()
+    }
+    .score();
+
+    RankingBreakdown {
+        success: RankingComponent::new(success, SUCCESS_WEIGHT),
+        speed: RankingComponent::new(speed, SPEED_WEIGHT),
+        health: RankingComponent::new(candidate.health_score / 100.0, HEALTH_WEIGHT),
+        reputation: RankingComponent::new(reputation, REPUTATION_WEIGHT),
+    }
+}
+
+/// Score `candidates` and return the best `limit`, best first.  Ties go to
+/// the healthier node, then the lower node ID.
+pub fn rank(candidates: Vec<RankingCandidate>, limit: usize) -> Vec<NodeRanking> {
+    let fastest_median_ms = candidates
+        .iter()
+        .filter_map(|candidate| candidate.median_execution_time_ms)
+        .reduce(f64::min);
+
+    let mut ranked: Vec<NodeRanking> = candidates
+        .into_iter()
+        .map(|candidate| {
+            let breakdown = breakdown(&candidate, fastest_median_ms);
+            let finished = candidate.completed_attempts + candidate.failed_attempts;
+            NodeRanking {
+                rank: 0,
+                score: breakdown.success.contribution
+                    + breakdown.speed.contribution
+                    + breakdown.health.contribution
+                    + breakdown.reputation.contribution,
+                success_rate: (finished > 0)
+                    .then(|| candidate.completed_attempts as f64 / finished as f64),
+                node_id: candidate.node_id,
+                region: candidate.region,
+                node_type: candidate.node_type,
+                status: candidate.status,
+                health_score: candidate.health_score,
+                completed_attempts: candidate.completed_attempts,
+                failed_attempts: candidate.failed_attempts,
+                median_execution_time_ms: candidate.median_execution_time_ms,
+                breakdown,
+            }
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.health_score.total_cmp(&a.health_score))
+            .then_with(|| a.node_id.cmp(&b.node_id))
+    });
+    ranked.truncate(limit);
+    for (index, ranking) in ranked.iter_mut().enumerate() {
+        ranking.rank = index + 1;
+    }
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(
+        node_id: &str,
+        completed: u64,
+        failed: u64,
+        median_ms: Option<f64>,
+    ) -> RankingCandidate {
+        RankingCandidate {
+            node_id: node_id.to_string(),
+            region: "us-east".to_string(),
+            node_type: "compute".to_string(),
+            status: "online".to_string(),
+            health_score: 90.0,
+            completed_attempts: completed,
+            failed_attempts: failed,
+            median_execution_time_ms: median_ms,
+            total_completed_attempts: completed,
+            total_failed_attempts: failed,
+        }
+    }
+
+    #[test]
+    fn reliable_history_outranks_a_single_success() {
+        let ranked = rank(
+            vec![
+                candidate("lucky", 1, 0, Some(100.0)),
+                candidate("steady", 40, 2, Some(100.0)),
+                candidate("flaky", 5, 5, Some(100.0)),
+            ],
+            10,
+        );
+        let order: Vec<_> = ranked.iter().map(|r| r.node_id.as_str()).collect();
+        assert_eq!(order, ["steady", "lucky", "flaky"]);
+        assert_eq!(ranked[0].rank, 1);
+        assert_eq!(ranked[2].success_rate, Some(0.5));
+    }
+
+    #[test]
+    fn speed_is_relative_to_the_fastest_median() {
+        let ranked = rank(
+            vec![
+                candidate("slow", 10, 0, Some(400.0)),
+                candidate("fast", 10, 0, Some(100.0)),
+                candidate("new", 0, 0, None),
+            ],
+            10,
+        );
+        let speed = |id: &str| {
+            ranked
+                .iter()
+                .find(|r| r.node_id == id)
+                .unwrap()
+                .breakdown
+                .speed
+                .value
+        };
+        assert_eq!(speed("fast"), 1.0);
+        assert_eq!(speed("slow"), 0.25);
+        assert_eq!(speed("new"), NEUTRAL_SCORE);
+        assert_eq!(ranked[0].node_id, "fast");
+    }
+
+    #[test]
+    fn score_is_the_sum_of_contributions_and_limit_applies() {
+        let ranked = rank(
+            vec![candidate("a", 3, 1, Some(50.0)), candidate("b", 0, 0, None)],
+            1,
+        );
+        assert_eq!(ranked.len(), 1);
+        let b = &ranked[0].breakdown;
+        let sum = b.success.contribution
+            + b.speed.contribution
+            + b.health.contribution
+            + b.reputation.contribution;
+        assert!((ranked[0].score - sum).abs() < 1e-12);
+        assert!((b.health.value - 0.9).abs() < 1e-12);
+    }
+
+    #[test]
+    fn query_rejects_unknown_task_types_and_bad_limits() {
+        let query = |task_type: &str, limit| NodeRankingsQuery {
+            task_type: task_type.to_string(),
+            limit,
+        };
+        assert!(query("computation", None).resolve().is_ok());
+        assert!(query("mining", None).resolve().is_err());
+        assert!(query("computation", Some(0)).resolve().is_err());
+        assert!(query("computation", Some(MAX_LIMIT + 1)).resolve().is_err());
+    }
+}
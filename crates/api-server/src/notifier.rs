@@ -0,0 +1,662 @@
+/// Task and user notifications
+///
+/// Task notifications are written to the `notification_outbox` table in the
+/// transaction that changes the task, and a background dispatcher drains the
+/// outbox with retries.  Each delivery carries the outbox row's id in
+/// `delivery_id`, which stays the same across retries so receivers can drop
+/// duplicates.  Account token mails skip the outbox so cleartext tokens never
+/// reach the database; they go through a bounded in-memory queue instead.
+/// Either way request handlers never wait on a mail server or webhook.
+/// Configure with:
+///
+/// - `NOTIFIER_BACKEND` — `smtp`, `webhook`, or `none`; when unset, `smtp` is
+///   used if `SMTP_HOST` is set, then `webhook` if `NOTIFY_WEBHOOK_URL` is set,
+///   otherwise `none`
+/// - `SMTP_HOST`, `SMTP_PORT` (default `587`), `SMTP_USERNAME`,
+///   `SMTP_PASSWORD`, `SMTP_TLS` (`starttls` default, `tls`, or `none`) and
+///   `EMAIL_FROM` for the SMTP backend
+/// - `NOTIFY_WEBHOOK_URL` and optional `NOTIFY_WEBHOOK_SECRET` (HMAC-SHA256
+///   signature in `X-Ambient-Signature`) for the webhook backend
+/// - `NOTIFY_QUEUE_CAPACITY` (default `1024`) for the in-memory queue,
+///   `NOTIFY_MAX_ATTEMPTS` (default `3`) for both, and
+///   `NOTIFY_OUTBOX_POLL_SECS` (default `5`) for how often the outbox is
+///   drained
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Default number of queued notifications before new ones are dropped.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// Default delivery attempts per notification.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Default seconds between outbox drains.
+pub const DEFAULT_OUTBOX_POLL_SECS: u64 = 5;
+
+/// Outbox rows claimed per drain.
+pub const OUTBOX_BATCH_SIZE: i64 = 100;
+
+/// How long a claimed outbox row is left alone before another drain may
+/// retry it, in case the claiming replica died mid-delivery.
+pub const OUTBOX_CLAIM_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    TaskCompleted,
+    TaskFailed,
+    TaskStarving,
+    TaskUnschedulable,
+    CanaryFailed,
+    PasswordReset,
+    EmailVerification,
+}
+
+/// A message for one user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    /// Outbox row id, identical across retries of one delivery
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery_id: Option<String>,
+    pub event: NotificationEvent,
+    pub user_id: String,
+    /// Delivery address for email backends; never sent to webhooks.
+    #[serde(skip)]
+    pub email: Option<String>,
+    pub task_id: Option<String>,
+    pub subject: String,
+    pub body: String,
+    pub payload: serde_json::Value,
+    pub created_at: String,
+}
+
+impl Notification {
+    pub fn task_completed(
+        user_id: uuid::Uuid,
+        email: Option<String>,
+        task_id: uuid::Uuid,
+        result: serde_json::Value,
+    ) -> Self {
+        Self {
+            delivery_id: None,
+            event: NotificationEvent::TaskCompleted,
+            user_id: user_id.to_string(),
+            email,
+            task_id: Some(task_id.to_string()),
+            subject: format!("Task Completed: {task_id}"),
+            body: format!(
+                "Your task {task_id} has completed.\n\nResult:\n{}",
+                serde_json::to_string_pretty(&result)
+                    .unwrap_or_else(|_| "<failed to serialize task result>".to_string())
+            ),
+            payload: result,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn task_failed(
+        user_id: uuid::Uuid,
+        email: Option<String>,
+        task_id: uuid::Uuid,
+        reason: &str,
+        attempts: u32,
+    ) -> Self {
+        Self {
+            delivery_id: None,
+            event: NotificationEvent::TaskFailed,
+            user_id: user_id.to_string(),
+            email,
+            task_id: Some(task_id.to_string()),
+            subject: format!("Task Failed: {task_id}"),
+            body: format!(
+                "Your task {task_id} failed after {attempts} attempt(s).\n\nLast error:\n{reason}"
+            ),
+            payload: serde_json::json!({ "error": reason, "attempts": attempts }),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn task_starving(
+        user_id: uuid::Uuid,
+        email: Option<String>,
+        task_id: uuid::Uuid,
+        pending_secs: u64,
+    ) -> Self {
+        Self {
+            delivery_id: None,
+            event: NotificationEvent::TaskStarving,
+            user_id: user_id.to_string(),
+            email,
+            task_id: Some(task_id.to_string()),
+            subject: format!("Task Starving: {task_id}"),
+            body: format!(
+                "Your task {task_id} has been pending for {pending_secs}s without enough \
+                 eligible nodes."
+            ),
+            payload: serde_json::json!({ "pending_seconds": pending_secs }),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn task_unschedulable(
+        user_id: uuid::Uuid,
+        email: Option<String>,
+        task_id: uuid::Uuid,
+        diagnostics: serde_json::Value,
+    ) -> Self {
+        Self {
+            delivery_id: None,
+            event: NotificationEvent::TaskUnschedulable,
+            user_id: user_id.to_string(),
+            email,
+            task_id: Some(task_id.to_string()),
+            subject: format!("Task Unschedulable: {task_id}"),
+            body: format!(
+                "Your task {task_id} could not be scheduled and will not run.\n\nDiagnostics:\n{}",
+                serde_json::to_string_pretty(&diagnostics)
+                    .unwrap_or_else(|_| "<failed to serialize diagnostics>".to_string())
+            ),
+            payload: diagnostics,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// A synthetic canary run failed; `run` is its `crate::canary::CanaryRun`.
+    pub fn canary_failed(
+        user_id: uuid::Uuid,
+        email: Option<String>,
+        task_id: Option<uuid::Uuid>,
+        run: serde_json::Value,
+    ) -> Self {
+        let kind = run["kind"].as_str().unwrap_or("unknown").to_string();
+        let node_id = run["node_id"].as_str().unwrap_or("unknown").to_string();
+        let stage = run["stage"].as_str().unwrap_or("unknown").to_string();
+        let error = run["error"].as_str().unwrap_or("unknown error").to_string();
+        Self {
+            delivery_id: None,
+            event: NotificationEvent::CanaryFailed,
+            user_id: user_id.to_string(),
+            email,
+            task_id: task_id.map(|id| id.to_string()),
+            subject: format!("Canary Failed: {kind} via {node_id}"),
+            body: format!("A {kind} canary through node {node_id} failed at {stage}:\n{error}"),
+            payload: run,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Password reset token for `user_id`; the token is only in the message,
+    /// never stored in clear.
+    pub fn password_reset(
+        user_id: uuid::Uuid,
+        email: Option<String>,
+        token: &str,
+        expires_in_secs: u64,
+    ) -> Self {
+        Self {
+            delivery_id: None,
+            event: NotificationEvent::PasswordReset,
+            user_id: user_id.to_string(),
+            email,
+            task_id: None,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "A password reset was requested for your account.\n\n\
+                 Reset token (valid for {} minutes, single use):\n{token}\n\n\
+                 Submit it to POST /api/v1/auth/password-reset/confirm. If you did not \
+                 request this, you can ignore this message.",
+                expires_in_secs.div_ceil(60)
+            ),
+            payload: serde_json::json!({ "token": token, "expires_in_seconds": expires_in_secs }),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Verification token for the address `email`.
+    pub fn email_verification(
+        user_id: uuid::Uuid,
+        email: String,
+        token: &str,
+        expires_in_secs: u64,
+    ) -> Self {
+        Self {
+            delivery_id: None,
+            event: NotificationEvent::EmailVerification,
+            user_id: user_id.to_string(),
+            email: Some(email),
+            task_id: None,
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Confirm this address for your account.\n\n\
+                 Verification token (valid for {} hours, single use):\n{token}\n\n\
+                 Submit it to POST /api/v1/auth/verify-email.",
+                expires_in_secs.div_ceil(3600)
+            ),
+            payload: serde_json::json!({ "token": token, "expires_in_seconds": expires_in_secs }),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Delivery backend for notifications.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()>;
+
+    /// Backend name for logs.
+    fn backend_name(&self) -> &'static str;
+}
+
+/// Discards every notification.
+pub struct NoopNotifier;
+
+#[async_trait]
+impl Notifier for NoopNotifier {
+    async fn notify(&self, _notification: &Notification) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "none"
+    }
+}
+
+/// Sends email through an SMTP relay.
+pub struct SmtpNotifier {
+    transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+    from: lettre::message::Mailbox,
+}
+
+impl SmtpNotifier {
+    pub fn from_env() -> anyhow::Result<Self> {
+        use lettre::transport::smtp::authentication::Credentials;
+        use lettre::{AsyncSmtpTransport, Tokio1Executor};
+
+        let host = std::env::var("SMTP_HOST")
+            .map_err(|_| anyhow::anyhow!("SMTP notifier requires SMTP_HOST"))?;
+        let port = std::env::var("SMTP_PORT")
+            .ok()
+            .and_then(|raw| raw.parse::<u16>().ok())
+            .unwrap_or(587);
+
+        let from = std::env::var("EMAIL_FROM")
+            .map_err(|_| anyhow::anyhow!("SMTP notifier requires EMAIL_FROM"))?;
+        if from.contains('\r') || from.contains('\n') {
+            anyhow::bail!("EMAIL_FROM cannot contain carriage return or newline characters");
+        }
+        let from = from
+            .parse()
+            .map_err(|_| anyhow::anyhow!("EMAIL_FROM must be a valid email address"))?;
+
+        let mut builder = match std::env::var("SMTP_TLS").as_deref() {
+            Ok("tls") => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+            Ok("none") => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?,
+        }
+        .port(port);
+
+        if let (Ok(username), Ok(password)) = (
+            std::env::var("SMTP_USERNAME"),
+            std::env::var("SMTP_PASSWORD"),
+        ) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+        use lettre::AsyncTransport;
+
+        // Users without an email address simply do not get mail.
+        let Some(ref email) = notification.email else {
+            return Ok(());
+        };
+        if email.contains('\r') || email.contains('\n') {
+            anyhow::bail!("Email cannot contain carriage return or newline characters");
+        }
+
+        let message = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(email
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Email must be a valid address"))?)
+            .subject(&notification.subject)
+            .body(notification.body.clone())?;
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "smtp"
+    }
+}
+
+/// POSTs each notification as JSON to a fixed URL.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<String>,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: impl Into<String>, secret: Option<String>) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            url: url.into(),
+            secret,
+        })
+    }
+
+    pub fn from_env() -> anyhow::Result<Self> {
+        let url = std::env::var("NOTIFY_WEBHOOK_URL")
+            .map_err(|_| anyhow::anyhow!("webhook notifier requires NOTIFY_WEBHOOK_URL"))?;
+        Self::new(url, std::env::var("NOTIFY_WEBHOOK_SECRET").ok())
+    }
+}
+
+/// Hex HMAC-SHA256 of `body`, sent as `X-Ambient-Signature: sha256=<hex>`.
+pub fn webhook_signature(secret: &str, body: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(notification)?;
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(ref secret) = self.secret {
+            request = request.header(
+                "X-Ambient-Signature",
+                format!("sha256={}", webhook_signature(secret, &body)),
+            );
+        }
+
+        request.body(body).send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "webhook"
+    }
+}
+
+fn select_backend(
+    explicit: Option<&str>,
+    smtp_configured: bool,
+    webhook_configured: bool,
+) -> &'static str {
+    match explicit.map(str::trim) {
+        Some("smtp") => "smtp",
+        Some("webhook") => "webhook",
+        Some("none") => "none",
+        _ if smtp_configured => "smtp",
+        _ if webhook_configured => "webhook",
+        _ => "none",
+    }
+}
+
+/// Build the notifier selected by `NOTIFIER_BACKEND`.
+pub fn notifier_from_env() -> anyhow::Result<Arc<dyn Notifier>> {
+    let backend = select_backend(
+        std::env::var("NOTIFIER_BACKEND").ok().as_deref(),
+        std::env::var("SMTP_HOST").is_ok(),
+        std::env::var("NOTIFY_WEBHOOK_URL").is_ok(),
+    );
+
+    Ok(match backend {
+        "smtp" => Arc::new(SmtpNotifier::from_env()?),
+        "webhook" => Arc::new(WebhookNotifier::from_env()?),
+        _ => Arc::new(NoopNotifier),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct DispatchConfig {
+    pub capacity: usize,
+    pub max_attempts: u32,
+    /// Delay before the second attempt; grows linearly per attempt.
+    pub retry_delay: Duration,
+    pub outbox_poll_interval: Duration,
+}
+
+impl Default for DispatchConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_QUEUE_CAPACITY,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_delay: Duration::from_secs(2),
+            outbox_poll_interval: Duration::from_secs(DEFAULT_OUTBOX_POLL_SECS),
+        }
+    }
+}
+
+impl DispatchConfig {
+    pub fn from_env() -> Self {
+        let parse = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|raw| raw.parse::<usize>().ok())
+                .filter(|parsed| *parsed > 0)
+        };
+        let defaults = Self::default();
+        Self {
+            capacity: parse("NOTIFY_QUEUE_CAPACITY").unwrap_or(defaults.capacity),
+            max_attempts: parse("NOTIFY_MAX_ATTEMPTS")
+                .map(|attempts| attempts.min(10) as u32)
+                .unwrap_or(defaults.max_attempts),
+            outbox_poll_interval: parse("NOTIFY_OUTBOX_POLL_SECS")
+                .map(|secs| Duration::from_secs(secs as u64))
+                .unwrap_or(defaults.outbox_poll_interval),
+            ..defaults
+        }
+    }
+}
+
+/// Handle to the notification backend and its in-memory queue.
+#[derive(Clone)]
+pub struct NotificationDispatcher {
+    tx: mpsc::Sender<Notification>,
+    notifier: Arc<dyn Notifier>,
+    config: DispatchConfig,
+    backend: &'static str,
+}
+
+impl NotificationDispatcher {
+    /// Spawn the delivery worker on the current Tokio runtime.
+    pub fn start(notifier: Arc<dyn Notifier>, config: DispatchConfig) -> Self {
+        let (tx, mut rx) = mpsc::channel::<Notification>(config.capacity);
+        let backend = notifier.backend_name();
+        let dispatcher = Self {
+            tx,
+            notifier: notifier.clone(),
+            config: config.clone(),
+            backend,
+        };
+
+        tokio::spawn(async move {
+            while let Some(notification) = rx.recv().await {
+                for attempt in 1..=config.max_attempts {
+                    match notifier.notify(&notification).await {
+                        Ok(()) => break,
+                        Err(err) if attempt < config.max_attempts => {
+                            tracing::debug!(backend, attempt, "Notification attempt failed: {err}");
+                            tokio::time::sleep(config.retry_delay * attempt).await;
+                        }
+                        Err(err) => {
+                            tracing::warn!(
+                                backend,
+                                event = ?notification.event,
+                                task_id = ?notification.task_id,
+                                "Notification dropped after {attempt} attempts: {err}"
+                            );
+                        }
+                    }
+                }
+            }
+        });
+
+        dispatcher
+    }
+
+    /// Queue a notification; returns `false` if the queue is full or closed.
+    pub fn enqueue(&self, notification: Notification) -> bool {
+        match self.tx.try_send(notification) {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::warn!(
+                    backend = self.backend,
+                    "Notification queue rejected message: {err}"
+                );
+                false
+            }
+        }
+    }
+
+    /// Make one delivery attempt, as the outbox dispatcher does for each
+    /// claimed row.
+    pub async fn deliver(&self, notification: &Notification) -> anyhow::Result<()> {
+        self.notifier.notify(notification).await
+    }
+
+    pub fn config(&self) -> &DispatchConfig {
+        &self.config
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.backend
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+
+    struct FlakyNotifier {
+        failures_left: AtomicU32,
+        delivered: Mutex<Vec<NotificationEvent>>,
+    }
+
+    #[async_trait]
+    impl Notifier for FlakyNotifier {
+        async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+            if self
+                .failures_left
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                anyhow::bail!("temporary failure");
+            }
+            self.delivered.lock().unwrap().push(notification.event);
+            Ok(())
+        }
+
+        fn backend_name(&self) -> &'static str {
+            "flaky"
+        }
+    }
+
+    #[test]
+    fn selects_backend_from_env_values() {
+        assert_eq!(select_backend(Some("webhook"), true, false), "webhook");
+        assert_eq!(select_backend(Some("none"), true, true), "none");
+        assert_eq!(select_backend(None, true, true), "smtp");
+        assert_eq!(select_backend(None, false, true), "webhook");
+        assert_eq!(select_backend(None, false, false), "none");
+    }
+
+    #[test]
+    fn signs_webhook_bodies() {
+        let signature = webhook_signature("secret", b"{}");
+        assert_eq!(signature.len(), 64);
+        assert_ne!(signature, webhook_signature("other", b"{}"));
+    }
+
+    #[test]
+    fn webhook_payload_omits_email() {
+        let notification = Notification::task_completed(
+            uuid::Uuid::new_v4(),
+            Some("user@example.com".to_string()),
+            uuid::Uuid::new_v4(),
+            serde_json::json!({"ok": true}),
+        );
+        let json = serde_json::to_string(&notification).unwrap();
+        assert!(!json.contains("user@example.com"));
+        assert!(json.contains("task_completed"));
+    }
+
+    #[test]
+    fn outbox_round_trip_keeps_notification() {
+        let notification = Notification::task_starving(
+            uuid::Uuid::new_v4(),
+            Some("user@example.com".to_string()),
+            uuid::Uuid::new_v4(),
+            90,
+        );
+        let stored = serde_json::to_value(&notification).unwrap();
+        assert!(stored.get("delivery_id").is_none());
+
+        let restored: Notification = serde_json::from_value(stored).unwrap();
+        assert_eq!(restored.event, NotificationEvent::TaskStarving);
+        assert_eq!(restored.task_id, notification.task_id);
+        assert_eq!(restored.payload["pending_seconds"], 90);
+        assert_eq!(restored.email, None);
+    }
+
+    #[tokio::test]
+    async fn dispatcher_retries_until_delivered() {
+        let notifier = Arc::new(FlakyNotifier {
+            failures_left: AtomicU32::new(1),
+            delivered: Mutex::new(Vec::new()),
+        });
+        let dispatcher = NotificationDispatcher::start(
+            notifier.clone(),
+            DispatchConfig {
+                capacity: 4,
+                max_attempts: 2,
+                retry_delay: Duration::from_millis(1),
+                outbox_poll_interval: Duration::from_secs(1),
+            },
+        );
+
+        assert!(dispatcher.enqueue(Notification::task_failed(
+            uuid::Uuid::new_v4(),
+            None,
+            uuid::Uuid::new_v4(),
+            "boom",
+            1,
+        )));
+
+        for _ in 0..100 {
+            if !notifier.delivered.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(
+            *notifier.delivered.lock().unwrap(),
+            vec![NotificationEvent::TaskFailed]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+/// Organization membership roles
+///
+/// Nodes and tasks created while a token carries org context belong to that
+/// org.  Members act on org resources according to their role:
+///
+/// - `viewer` — read org tasks, task logs and node activity
+/// - `member` — also register and operate nodes (heartbeats, results, logs)
+///   and submit or delete tasks
+/// - `admin` — also reject or delete org nodes and manage non-owner members
+/// - `owner` — also grant or revoke the owner role
+///
+/// Access is checked against `organization_members` on every request through
+/// the `org_role_at_least` SQL function, so role changes take effect without
+/// waiting for tokens to expire.
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Membership role within an organization, ordered from least to most
+/// privileged.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OrgRole {
+    Viewer,
+    Member,
+    Admin,
+    Owner,
+}
+
+impl OrgRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Member => "member",
+            Self::Admin => "admin",
+            Self::Owner => "owner",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "viewer" => Some(Self::Viewer),
+            "member" => Some(Self::Member),
+            "admin" => Some(Self::Admin),
+            "owner" => Some(Self::Owner),
+            _ => None,
+        }
+    }
+
+    /// Whether this role grants everything `required` does.
+    pub fn at_least(&self, required: OrgRole) -> bool {
+        *self >= required
+    }
+
+    /// Whether a member with this role may set `target`'s role to `new_role`.
+    ///
+    /// Admins manage viewers, members and other admins; only owners may touch
+    /// the owner role.
+    pub fn can_assign(&self, target_current: Option<OrgRole>, new_role: OrgRole) -> bool {
+        let touches_owner = new_role == Self::Owner || target_current == Some(Self::Owner);
+        if touches_owner {
+            *self == Self::Owner
+        } else {
+            self.at_least(Self::Admin)
+        }
+    }
+}
+
+impl std::fmt::Display for OrgRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_are_ordered_by_privilege() {
+        assert!(OrgRole::Owner.at_least(OrgRole::Admin));
+        assert!(OrgRole::Member.at_least(OrgRole::Viewer));
+        assert!(!OrgRole::Viewer.at_least(OrgRole::Member));
+        assert_eq!(OrgRole::parse("admin"), Some(OrgRole::Admin));
+        assert_eq!(OrgRole::parse("root"), None);
+    }
+
+    #[test]
+    fn only_owners_manage_the_owner_role() {
+        assert!(OrgRole::Admin.can_assign(Some(OrgRole::Viewer), OrgRole::Member));
+        assert!(OrgRole::Admin.can_assign(None, OrgRole::Admin));
+        assert!(!OrgRole::Admin.can_assign(None, OrgRole::Owner));
+        assert!(!OrgRole::Admin.can_assign(Some(OrgRole::Owner), OrgRole::Member));
+        assert!(OrgRole::Owner.can_assign(Some(OrgRole::Owner), OrgRole::Admin));
+        assert!(!OrgRole::Member.can_assign(None, OrgRole::Viewer));
+    }
+}
@@ -0,0 +1,147 @@
+/// OpenTelemetry trace export and W3C trace-context propagation
+///
+/// Spans are exported over OTLP/HTTP when `OTEL_EXPORTER_OTLP_ENDPOINT` (or
+/// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`) is set; otherwise only the log
+/// output is installed.  Either way every response carries a `traceparent`
+/// header and new tasks record the submitting request's trace context.
+use ambient_node::TraceParent;
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry::KeyValue;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Service name reported to the collector unless `OTEL_SERVICE_NAME` is set.
+pub const DEFAULT_SERVICE_NAME: &str = "ambient-api-server";
+
+tokio::task_local! {
+    static REQUEST_TRACE: TraceParent;
+}
+
+/// Flushes buffered spans when dropped; keep it alive for the process.
+pub struct TracingGuard {
+    exporting: bool,
+}
+
+impl TracingGuard {
+    pub fn exporting(&self) -> bool {
+        self.exporting
+    }
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if self.exporting {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+fn otlp_endpoint_configured() -> bool {
+    [
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    ]
+    .iter()
+    .any(|var| std::env::var(var).is_ok_and(|value| !value.trim().is_empty()))
+}
+
+fn service_name() -> String {
+    std::env::var("OTEL_SERVICE_NAME")
+        .ok()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string())
+}
+
+/// Install the global tracing subscriber: log output at INFO plus an OTLP
+/// span exporter when an endpoint is configured.  Must run inside the Tokio
+/// runtime.
+pub fn init_tracing() -> anyhow::Result<TracingGuard> {
+    let registry = tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(tracing_subscriber::fmt::layer());
+
+    if !otlp_endpoint_configured() {
+        registry.init();
+        return Ok(TracingGuard { exporting: false });
+    }
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().http())
+        .with_trace_config(opentelemetry_sdk::trace::config().with_resource(
+            opentelemetry_sdk::Resource::new([KeyValue::new("service.name", service_name())]),
+        ))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    registry
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .init();
+    Ok(TracingGuard { exporting: true })
+}
+
+/// Parent `span` on the caller's trace (if it sent one) and return the
+/// `traceparent` downstream hops should use.
+///
+/// With export enabled that is the span's own context; without it the
+/// caller's trace is continued with a fresh span id, or a new trace started.
+pub fn continue_trace(span: &tracing::Span, incoming: Option<TraceParent>) -> TraceParent {
+    if let Some(parent) = incoming {
+        let remote = SpanContext::new(
+            TraceId::from_bytes(parent.trace_id),
+            SpanId::from_bytes(parent.parent_id),
+            if parent.sampled {
+                TraceFlags::SAMPLED
+            } else {
+                TraceFlags::default()
+            },
+            true,
+            TraceState::default(),
+        );
+        span.set_parent(opentelemetry::Context::new().with_remote_span_context(remote));
+    }
+
+    let context = span.context();
+    let own = context.span().span_context().clone();
+    if own.is_valid() {
+        return TraceParent {
+            trace_id: own.trace_id().to_bytes(),
+            parent_id: own.span_id().to_bytes(),
+            sampled: own.is_sampled(),
+        };
+    }
+    incoming.map_or_else(TraceParent::new_root, |parent| parent.child())
+}
+
+/// Run `future` with `trace` as the current request's trace context.
+pub async fn scope_request_trace<F: std::future::Future>(
+    trace: TraceParent,
+    future: F,
+) -> F::Output {
+    REQUEST_TRACE.scope(trace, future).await
+}
+
+/// Trace context of the request being served, if any.
+pub fn current_traceparent() -> Option<TraceParent> {
+    REQUEST_TRACE.try_with(|trace| *trace).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_continue_trace_keeps_the_callers_trace_id() {
+        let incoming =
+            TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        let span = tracing::info_span!("request");
+        let outgoing = continue_trace(&span, Some(incoming));
+        assert_eq!(outgoing.trace_id, incoming.trace_id);
+        assert_ne!(outgoing.parent_id, incoming.parent_id);
+
+        assert_eq!(current_traceparent(), None);
+        let seen = scope_request_trace(outgoing, async { current_traceparent() }).await;
+        assert_eq!(seen, Some(outgoing));
+    }
+}
@@ -0,0 +1,322 @@
+/// Peer route queries for node agents
+///
+/// `GET /api/v1/nodes/{node_id}/route` answers "who should I relay through
+/// right now" with `mesh_coordinator::PeerRouter` over the registered nodes:
+/// online `universal`/`open` nodes whose flap breaker is closed, with standby
+/// relays spread across ASNs.  Node agents feed back into the choice:
+///
+/// - `POST /api/v1/nodes/{node_id}/route/invalidate`: a source drops a relay
+///   that failed it; the relay is skipped for that source for
+///   `ROUTE_FEEDBACK_TTL_SECS`.
+/// - `PUT /api/v1/nodes/{node_id}/relay-load`: a relay reports its own
+///   utilization.  Less loaded relays rank first and saturated ones are only
+///   chosen when no other relay is left, until the report goes stale.
+///
+/// Load also counts the peers each relay was assigned by earlier queries and
+/// its recent `connect_session_usage` against its `bandwidth_mbps`.  A node
+/// keeps its relay unless another is better by `REBALANCE_MARGIN`, so agents
+/// re-querying their route (at least every `ROUTE_FEEDBACK_TTL_SECS`, after
+/// which an assignment lapses) are rebalanced off overloaded relays.
+///
+/// Feedback is stored in `relay_load_reports`, `peer_route_invalidations` and
+/// `peer_route_assignments` so every replica answers the same way.
+///
+/// A relay about to restart announces it with
+/// `POST /api/v1/nodes/{node_id}/relay-drain`: the node is drained, the nodes
+/// routed through it are re-routed, and its connect sessions move to another
+/// relay while it keeps serving them for the grace window.
+use crate::error::ApiError;
+use mesh_coordinator::{
+    NodeConnectivityStatus, PeerRoute, PeerRouter, RelayLoad, RelayTraffic,
+    MAX_RELAY_DRAIN_GRACE_SECS,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Query string for `GET /api/v1/nodes/{node_id}/route`
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PeerRouteQuery {
+    /// Whether the node has a direct internet path right now; defaults to
+    /// its registered status (`online` counts as direct).
+    pub has_internet: Option<bool>,
+}
+
+/// Body of `POST /api/v1/nodes/{node_id}/route/invalidate`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PeerRouteInvalidation {
+    /// Relay the node stops routing through
+    pub relay_node_id: String,
+    /// As in [`PeerRouteQuery`], for the route returned instead
+    #[serde(default)]
+    pub has_internet: Option<bool>,
+}
+
+impl PeerRouteInvalidation {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.relay_node_id.trim().is_empty() || self.relay_node_id.len() > 64 {
+            return Err(ApiError::bad_request(
+                "relay_node_id must be between 1 and 64 characters",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Body of `PUT /api/v1/nodes/{node_id}/relay-load`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RelayLoadReport {
+    /// Share of the relay's capacity in use, 0.0–1.0; at
+    /// `RELAY_SATURATION_UTILIZATION` or above the relay counts as saturated
+    pub utilization: f64,
+    /// Set when the relay refuses further peers, whatever its utilization
+    #[serde(default)]
+    pub saturated: bool,
+}
+
+impl RelayLoadReport {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if !self.utilization.is_finite() || !(0.0..=1.0).contains(&self.utilization) {
+            return Err(ApiError::bad_request(
+                "utilization must be a finite value between 0 and 1",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Body of `POST /api/v1/nodes/{node_id}/relay-drain`
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RelayDrainAnnouncement {
+    /// Seconds until the relay goes away, at most `MAX_RELAY_DRAIN_GRACE_SECS`;
+    /// defaults to `RELAY_DRAIN_GRACE_SECS`
+    #[serde(default)]
+    pub grace_seconds: Option<u64>,
+}
+
+impl RelayDrainAnnouncement {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if let Some(grace_seconds) = self.grace_seconds {
+            if grace_seconds > MAX_RELAY_DRAIN_GRACE_SECS {
+                return Err(ApiError::bad_request(format!(
+                    "grace_seconds must be at most {MAX_RELAY_DRAIN_GRACE_SECS}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A node moved off a draining relay.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PeerRouteChange {
+    pub node_id: String,
+    /// Relay it routes through now; `null` when no relay is left
+    pub relay_node_id: Option<String>,
+}
+
+/// A connect session handed to another relay.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionMigration {
+    pub session_id: String,
+    pub node_id: String,
+}
+
+/// Outcome of a relay drain announcement.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RelayDrainResponse {
+    pub node_id: String,
+    pub status: String,
+    /// When the relay stops serving sessions (RFC 3339)
+    pub drain_deadline: String,
+    /// Nodes that were routed through the relay
+    pub rerouted_nodes: Vec<PeerRouteChange>,
+    /// Sessions now served by another relay; the draining relay keeps
+    /// serving them until the deadline
+    pub migrated_sessions: Vec<SessionMigration>,
+    /// Sessions no other relay could take; they end at the deadline unless
+    /// a replacement turns up first
+    pub remaining_sessions: Vec<String>,
+    /// Checkpointable tasks handed to other nodes, as for `/drain`
+    pub handed_off_tasks: Vec<String>,
+}
+
+/// A node's current peer route.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PeerRouteResponse {
+    pub node_id: String,
+    /// `hops` is empty for direct access, otherwise the relay to use, with
+    /// `standby` relays to fail over to; `null` when no path exists
+    #[schema(value_type = Option<Object>)]
+    pub route: Option<PeerRoute>,
+}
+
+/// A registered relay candidate as read from `nodes`, `relay_load_reports`
+/// and `connect_session_usage`.
+#[derive(Debug, Clone)]
+pub struct RelayCandidate {
+    pub node_id: String,
+    pub node_type: String,
+    pub asn: Option<u32>,
+    pub region: String,
+    pub load: Option<RelayLoad>,
+    pub traffic: Option<RelayTraffic>,
+}
+
+/// Route `source_node_id` through `candidates`, which must all be online,
+/// skipping `invalidated` relays.  `assignments` are the current
+/// `(source, relay)` pairs, including the source's own.
+pub fn resolve_route(
+    source_node_id: &str,
+    has_internet: bool,
+    candidates: &[RelayCandidate],
+    assignments: &[(String, String)],
+    invalidated: &[String],
+    now: u64,
+) -> Option<PeerRoute> {
+    let mut router = PeerRouter::new();
+    let source_status = if has_internet {
+        NodeConnectivityStatus::Online
+    } else {
+        NodeConnectivityStatus::Offline
+    };
+    router.update_node(source_node_id, "", source_status);
+    for candidate in candidates
+        .iter()
+        .filter(|candidate| candidate.node_id != source_node_id)
+    {
+        router.update_node(
+            &candidate.node_id,
+            &candidate.node_type,
+            NodeConnectivityStatus::Online,
+        );
+        router.set_network_location(
+            &candidate.node_id,
+            ambient_node::NetworkLocation::new(candidate.asn, candidate.region.clone()),
+        );
+        if let Some(load) = candidate.load {
+            router.report_relay_load(
+                &candidate.node_id,
+                load.utilization,
+                load.saturated,
+                load.reported_at,
+            );
+        }
+        if let Some(traffic) = candidate.traffic {
+            router.record_relay_traffic(
+                &candidate.node_id,
+                traffic.mbps,
+                traffic.capacity_mbps,
+                traffic.measured_at,
+            );
+        }
+    }
+    for (source, relay) in assignments {
+        router.set_assignment(source, relay);
+    }
+    for relay_id in invalidated {
+        router.invalidate_route(source_node_id, relay_id, now);
+    }
+    router.find_route_at(source_node_id, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(node_id: &str, node_type: &str, load: Option<RelayLoad>) -> RelayCandidate {
+        RelayCandidate {
+            node_id: node_id.to_string(),
+            node_type: node_type.to_string(),
+            asn: None,
+            region: "us-east".to_string(),
+            load,
+            traffic: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_route_uses_feedback() {
+        let saturated = RelayLoad {
+            utilization: 0.4,
+            saturated: true,
+            reported_at: 1_000,
+        };
+        let candidates = vec![
+            candidate("relay-a", "universal", Some(saturated)),
+            candidate("relay-b", "open", None),
+            candidate("worker", "compute", None),
+        ];
+
+        let route = resolve_route("source", true, &candidates, &[], &[], 1_010).unwrap();
+        assert!(route.is_direct());
+
+        let route = resolve_route("source", false, &candidates, &[], &[], 1_010).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-b");
+        assert_eq!(route.standby[0].node_id, "relay-a");
+
+        let invalidated = vec!["relay-b".to_string()];
+        let route = resolve_route("source", false, &candidates, &[], &invalidated, 1_010).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-a");
+        assert!(route.standby.is_empty());
+
+        // A relay never routes through itself.
+        let route = resolve_route("relay-b", false, &candidates, &[], &[], 1_010).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-a");
+    }
+
+    #[test]
+    fn test_resolve_route_weighs_assignments_and_traffic() {
+        let candidates = vec![
+            candidate("relay-a", "universal", None),
+            candidate("relay-b", "universal", None),
+        ];
+        let assignments: Vec<(String, String)> = (0..2)
+            .map(|i| (format!("peer-{i}"), "relay-a".to_string()))
+            .collect();
+        let route = resolve_route("source", false, &candidates, &assignments, &[], 1_010).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-b");
+
+        // The source stays on its relay while the other is not clearly better.
+        let mut sticky = assignments.clone();
+        sticky.push(("source".to_string(), "relay-a".to_string()));
+        let route = resolve_route("source", false, &candidates, &sticky, &[], 1_010).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-a");
+
+        let mut busy = candidates.clone();
+        busy[0].traffic = Some(RelayTraffic {
+            mbps: 90.0,
+            capacity_mbps: 100.0,
+            measured_at: 1_000,
+        });
+        let route = resolve_route("source", false, &busy, &sticky, &[], 1_010).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-b");
+    }
+
+    #[test]
+    fn test_reports_are_validated() {
+        let report = |utilization| RelayLoadReport {
+            utilization,
+            saturated: false,
+        };
+        assert!(report(0.0).validate().is_ok());
+        assert!(report(1.0).validate().is_ok());
+        assert!(report(1.5).validate().is_err());
+        assert!(report(f64::NAN).validate().is_err());
+
+        let drain = |grace_seconds| RelayDrainAnnouncement { grace_seconds };
+        assert!(drain(None).validate().is_ok());
+        assert!(drain(Some(MAX_RELAY_DRAIN_GRACE_SECS)).validate().is_ok());
+        assert!(drain(Some(MAX_RELAY_DRAIN_GRACE_SECS + 1))
+            .validate()
+            .is_err());
+
+        let invalidation = |relay: &str| PeerRouteInvalidation {
+            relay_node_id: relay.to_string(),
+            has_internet: None,
+        };
+        assert!(invalidation("relay-a").validate().is_ok());
+        assert!(invalidation(" ").validate().is_err());
+        assert!(invalidation(&"r".repeat(65)).validate().is_err());
+    }
+}
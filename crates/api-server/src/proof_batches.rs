@@ -0,0 +1,213 @@
+/// Proof verification quotas and asynchronous verification batches
+///
+/// `POST /api/v1/proofs/verify` verifies one proof while the caller waits,
+/// and only for a task the caller created or is a member of the owning
+/// organization for.  Anything else — proofs for other tasks, or many proofs
+/// at once — goes through a batch: `POST /api/v1/proofs/batches` queues up
+/// to [`MAX_BATCH_PROOFS`] proofs and returns `202` with a batch ID, and
+/// `GET /api/v1/proofs/batches/{batch_id}` reports progress and results.
+/// A batch proof is linked to its task only when the submitter can see it.
+///
+/// Every standalone verification, synchronous or batched, counts toward the
+/// submitter's quota of `PROOF_VERIFY_DAILY_QUOTA` proofs (default 1000,
+/// `0` for no limit) over a rolling [`QUOTA_WINDOW_SECS`].  Queued batch
+/// proofs count as soon as the batch is accepted, and a request over the
+/// quota gets `429`.  `GET /api/v1/proofs/quota` reports the caller's usage.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+use crate::models::ProofVerificationRequest;
+
+/// Most proofs one batch may hold.
+pub const MAX_BATCH_PROOFS: usize = 100;
+
+/// Batches one user may have running at once.
+pub const MAX_RUNNING_BATCHES: i64 = 2;
+
+/// A running batch older than this is treated as interrupted.
+pub const STALE_BATCH_SECS: i64 = 3600;
+
+/// Rolling window the verification quota is counted over.
+pub const QUOTA_WINDOW_SECS: i64 = 86_400;
+
+/// Request body limit for a full batch of maximum-size proofs.
+pub const MAX_BATCH_BODY_BYTES: usize = MAX_BATCH_PROOFS * 112 * 1024;
+
+/// Standalone verifications allowed per user per window
+/// (`PROOF_VERIFY_DAILY_QUOTA`); `None` when unlimited.
+pub fn daily_quota() -> Option<i64> {
+    match std::env::var("PROOF_VERIFY_DAILY_QUOTA")
+        .ok()
+        .and_then(|v| v.trim().parse::<i64>().ok())
+    {
+        Some(quota) if quota <= 0 => None,
+        Some(quota) => Some(quota),
+        None => Some(1000),
+    }
+}
+
+/// A caller's verification quota
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ProofQuota {
+    /// Verifications allowed per window; `None` when unlimited
+    pub limit: Option<i64>,
+    /// Verifications done or queued in the current window
+    pub used: i64,
+    pub remaining: Option<i64>,
+    pub window_secs: i64,
+}
+
+impl ProofQuota {
+    pub fn new(limit: Option<i64>, used: i64) -> Self {
+        Self {
+            limit,
+            used,
+            remaining: limit.map(|limit| (limit - used).max(0)),
+            window_secs: QUOTA_WINDOW_SECS,
+        }
+    }
+
+    /// `429` unless `count` more verifications fit in the quota.
+    pub fn check(&self, count: i64) -> Result<(), ApiError> {
+        match self.remaining {
+            Some(remaining) if count > remaining => Err(ApiError::rate_limited(format!(
+                "Proof verification quota exceeded: {} of {} used in the last {}h",
+                self.used,
+                self.limit.unwrap_or_default(),
+                self.window_secs / 3600
+            ))
+            .with_details(serde_json::json!(self))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Body of `POST /api/v1/proofs/batches`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ProofBatchRequest {
+    pub proofs: Vec<ProofVerificationRequest>,
+}
+
+impl ProofBatchRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.proofs.is_empty() {
+            return Err(ApiError::bad_request("proofs cannot be empty"));
+        }
+        if self.proofs.len() > MAX_BATCH_PROOFS {
+            return Err(ApiError::bad_request(format!(
+                "A batch holds at most {MAX_BATCH_PROOFS} proofs"
+            )));
+        }
+        for (position, proof) in self.proofs.iter().enumerate() {
+            proof.validate().map_err(|err| {
+                ApiError::bad_request(format!("proofs[{position}]: {}", err.message))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of one proof in a batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProofBatchResult {
+    /// Index of the proof in the submitted batch
+    pub position: usize,
+    pub task_id: String,
+    pub valid: bool,
+    pub verification_time_ms: u64,
+    pub error_message: Option<String>,
+    /// Stored proof, retrievable at `GET /api/v1/proofs/{proof_id}`
+    pub proof_id: Option<String>,
+}
+
+/// A verification batch and the results so far
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ProofBatch {
+    pub batch_id: String,
+    /// `running`, `completed` or `failed`
+    pub status: String,
+    pub total: usize,
+    /// Proofs verified so far
+    pub completed: usize,
+    /// Proofs found valid so far
+    pub valid: usize,
+    pub results: Vec<ProofBatchResult>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl ProofBatch {
+    pub fn new(
+        batch_id: String,
+        status: String,
+        total: usize,
+        results: Vec<ProofBatchResult>,
+        error: Option<String>,
+        created_at: DateTime<Utc>,
+        finished_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            batch_id,
+            status,
+            total,
+            completed: results.len(),
+            valid: results.iter().filter(|result| result.valid).count(),
+            results,
+            error,
+            created_at,
+            finished_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proof() -> ProofVerificationRequest {
+        ProofVerificationRequest {
+            task_id: "task-1".to_string(),
+            proof_data: "AAAA".to_string(),
+            public_inputs: "AAAA".to_string(),
+            circuit_id: None,
+        }
+    }
+
+    #[test]
+    fn quota_allows_up_to_the_limit() {
+        let quota = ProofQuota::new(Some(10), 8);
+        assert_eq!(quota.remaining, Some(2));
+        assert!(quota.check(2).is_ok());
+        let err = quota.check(3).unwrap_err();
+        assert_eq!(err.status_code, axum::http::StatusCode::TOO_MANY_REQUESTS);
+        assert!(ProofQuota::new(None, 1_000_000).check(100).is_ok());
+        assert_eq!(ProofQuota::new(Some(5), 9).remaining, Some(0));
+    }
+
+    #[test]
+    fn batch_requests_are_bounded_and_validated() {
+        assert!(ProofBatchRequest { proofs: vec![] }.validate().is_err());
+        assert!(ProofBatchRequest {
+            proofs: vec![proof(); MAX_BATCH_PROOFS + 1]
+        }
+        .validate()
+        .is_err());
+
+        let mut bad = proof();
+        bad.proof_data = "not base64!".to_string();
+        let err = ProofBatchRequest {
+            proofs: vec![proof(), bad],
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.message.starts_with("proofs[1]"));
+        assert!(ProofBatchRequest {
+            proofs: vec![proof(); 3]
+        }
+        .validate()
+        .is_ok());
+    }
+}
@@ -0,0 +1,126 @@
+/// Proof verification offload to verifier nodes
+///
+/// With `PROOF_VERIFY_OFFLOAD=true`, proofs queued through
+/// `POST /api/v1/proofs/batches` are not verified on the API server.  Each
+/// one is stored unverified and handed out as a [`VERIFY_PROOF_TASK_TYPE`]
+/// task to [`quorum`] verifier nodes: online nodes with a registered
+/// `signing_public_key` and a health score of at least
+/// `PROOF_VERIFIER_MIN_HEALTH` (default 80).  A verifier checks the proof
+/// named in [`VerifyProofInputs`] and submits a task result carrying an
+/// `ambient_node::SignedProofVerdict`.  The proof is settled, and the batch
+/// result recorded, once a quorum of verdicts agree; if the verifiers split
+/// so that neither side can reach it, the proof is recorded as unverified.
+///
+/// Synchronous `POST /api/v1/proofs/verify` and proofs attached to task
+/// results are always verified by the server.
+use serde::{Deserialize, Serialize};
+
+/// Task type verifier nodes pick offloaded proofs up as.
+pub const VERIFY_PROOF_TASK_TYPE: &str = "verify_proof";
+
+/// Largest quorum `PROOF_VERIFY_QUORUM` may ask for.
+pub const MAX_QUORUM: u32 = 5;
+
+/// How long a verifier has to return its verdict.
+pub const VERIFY_TIMEOUT_SECS: u64 = 300;
+
+/// Error recorded on a proof whose verifiers could not agree.
+pub const QUORUM_NOT_REACHED: &str = "Verifier quorum not reached";
+
+/// Whether batch proofs go to verifier nodes (`PROOF_VERIFY_OFFLOAD`).
+pub fn offload_enabled() -> bool {
+    std::env::var("PROOF_VERIFY_OFFLOAD")
+        .map(|v| matches!(v.trim(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Agreeing verdicts needed to settle a proof (`PROOF_VERIFY_QUORUM`,
+/// default 2, at most [`MAX_QUORUM`]).
+pub fn quorum() -> u32 {
+    std::env::var("PROOF_VERIFY_QUORUM")
+        .ok()
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .unwrap_or(2)
+        .clamp(1, MAX_QUORUM)
+}
+
+/// Lowest health score a node may verify proofs with
+/// (`PROOF_VERIFIER_MIN_HEALTH`, 0–100).
+pub fn verifier_min_health() -> f64 {
+    std::env::var("PROOF_VERIFIER_MIN_HEALTH")
+        .ok()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|score| score.is_finite())
+        .unwrap_or(80.0)
+        .clamp(0.0, 100.0)
+}
+
+/// Minimum health score a node needs to take a task of `task_type`, bound
+/// into the scheduler's candidate queries; `None` for every task type but
+/// [`VERIFY_PROOF_TASK_TYPE`].
+pub fn min_health_score(task_type: &str) -> Option<f64> {
+    (task_type == VERIFY_PROOF_TASK_TYPE).then(verifier_min_health)
+}
+
+/// Inputs of a `verify_proof` task
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerifyProofInputs {
+    pub proof_id: String,
+    pub batch_id: String,
+    /// Index of the proof in its batch
+    pub position: usize,
+    /// `task_id` the proof was submitted for
+    pub proof_task_id: String,
+    /// Base64 encoded proof
+    pub proof_data: String,
+    /// Base64 encoded public inputs
+    pub public_inputs: String,
+    pub circuit_id: String,
+}
+
+/// Where the verdicts on one proof stand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuorumOutcome {
+    /// Outstanding verifiers could still settle the proof either way.
+    Pending,
+    /// A quorum agreed the proof is valid (`true`) or invalid (`false`).
+    Agreed(bool),
+    /// Neither outcome can reach the quorum any more.
+    Deadlocked,
+}
+
+/// Settle a proof from its verdicts so far.  `outstanding` is the number of
+/// assigned verifiers that have not returned a verdict yet.
+pub fn tally(valid: u32, invalid: u32, outstanding: u32, quorum: u32) -> QuorumOutcome {
+    if valid >= quorum {
+        QuorumOutcome::Agreed(true)
+    } else if invalid >= quorum {
+        QuorumOutcome::Agreed(false)
+    } else if valid + outstanding < quorum && invalid + outstanding < quorum {
+        QuorumOutcome::Deadlocked
+    } else {
+        QuorumOutcome::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tally_waits_for_a_quorum_of_agreeing_verdicts() {
+        assert_eq!(tally(0, 0, 2, 2), QuorumOutcome::Pending);
+        assert_eq!(tally(1, 0, 1, 2), QuorumOutcome::Pending);
+        assert_eq!(tally(2, 0, 0, 2), QuorumOutcome::Agreed(true));
+        assert_eq!(tally(1, 2, 0, 2), QuorumOutcome::Agreed(false));
+        assert_eq!(tally(1, 1, 0, 2), QuorumOutcome::Deadlocked);
+        assert_eq!(tally(1, 1, 1, 2), QuorumOutcome::Pending);
+        assert_eq!(tally(1, 0, 0, 1), QuorumOutcome::Agreed(true));
+    }
+
+    #[test]
+    fn only_proof_verification_needs_a_minimum_health_score() {
+        assert_eq!(min_health_score("computation"), None);
+        assert!(min_health_score(VERIFY_PROOF_TASK_TYPE).is_some());
+    }
+}
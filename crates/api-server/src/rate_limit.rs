@@ -1,9 +1,17 @@
 /// Rate limiting middleware
+///
+/// Requests are limited per client IP and tier.  Users and API keys with a
+/// throttle override get their own bucket instead, with the tier's limits
+/// scaled by the override; overrides are loaded from the database into the
+/// limiter by [`RateLimiter::set_overrides`] and replaced wholesale on each
+/// reload.  Each [`AppState`] owns its limiter, so separate apps in one
+/// process never share buckets.
 use crate::error::ApiError;
+use crate::state::AppState;
 use axum::{
     body::Body,
-    extract::Request,
-    http::{HeaderValue, StatusCode},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
@@ -12,7 +20,7 @@ use std::{
     collections::HashMap,
     net::{IpAddr, SocketAddr},
     str::FromStr,
-    sync::Arc,
+    sync::{Arc, RwLock},
     time::{Duration, Instant},
 };
 use tokio::sync::Mutex;
@@ -58,9 +66,13 @@ impl RateLimitTier {
         // paths that merely contain a keyword (e.g. "/api/v1/some-nodes-report"
         // containing "/nodes" but not being a node-registration endpoint).
         let path = path.trim_end_matches('/');
-        if path.contains("/auth/login") || path.contains("/auth/register") {
+        if path.contains("/auth/login")
+            || path.contains("/auth/register")
+            || path.contains("/auth/password-reset")
+            || path.contains("/auth/verify-email")
+        {
             Self::Auth
-        } else if path.contains("/proofs/verify") {
+        } else if path.contains("/proofs/verify") || path.ends_with("/proofs/batches") {
             Self::ProofVerification
         } else if path.contains("/tasks") {
             Self::TaskSubmission
@@ -76,6 +88,63 @@ impl RateLimitTier {
     }
 }
 
+/// Rate limit adjustment for one user or API key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitOverride {
+    /// Factor applied to the tier's requests per minute.
+    pub multiplier: f64,
+    /// Bucket size replacing the tier's burst.
+    pub burst: Option<u32>,
+}
+
+impl RateLimitOverride {
+    /// A tier's `(rpm, burst)` under this override.
+    pub fn apply(&self, (rpm, burst): (u32, u32)) -> (u32, u32) {
+        let rpm = (rpm as f64 * self.multiplier)
+            .ceil()
+            .clamp(1.0, u32::MAX as f64) as u32;
+        (rpm, self.burst.unwrap_or(burst))
+    }
+}
+
+/// Active throttle overrides.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitOverrides {
+    /// By `user_id`, for JWT-authenticated requests.
+    pub users: HashMap<String, RateLimitOverride>,
+    /// By API key hash ([`crate::auth::hash_api_key`]), with the key's
+    /// `key_id`, so keys resolve without a database lookup.
+    pub api_keys: HashMap<String, (String, RateLimitOverride)>,
+}
+
+impl RateLimitOverrides {
+    pub fn len(&self) -> usize {
+        self.users.len() + self.api_keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// What a bucket counts requests of.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    Ip(IpAddr),
+    User(String),
+    ApiKey(String),
+}
+
+impl std::fmt::Display for RateLimitKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ip(ip) => write!(f, "{ip}"),
+            Self::User(user_id) => write!(f, "user:{user_id}"),
+            Self::ApiKey(key_id) => write!(f, "api_key:{key_id}"),
+        }
+    }
+}
+
 fn env_u32(key: &str, default: u32) -> u32 {
     std::env::var(key)
         .ok()
@@ -101,6 +170,14 @@ impl TokenBucket {
         }
     }
 
+    /// Apply limits changed by an override reload, keeping at most the new
+    /// capacity of saved tokens.
+    fn resize(&mut self, requests_per_minute: u32, burst_capacity: u32) {
+        self.capacity = burst_capacity as f64;
+        self.refill_rate = requests_per_minute as f64 / 60.0;
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
     fn try_consume(&mut self) -> bool {
         let now = Instant::now();
         let elapsed = now.duration_since(self.last_refill).as_secs_f64();
@@ -115,9 +192,13 @@ impl TokenBucket {
     }
 }
 
+/// Token buckets and throttle overrides.  Clones share both.
 #[derive(Clone)]
 pub struct RateLimiter {
-    buckets: Arc<Mutex<HashMap<(IpAddr, RateLimitTier), TokenBucket>>>,
+    buckets: Arc<Mutex<HashMap<(RateLimitKey, RateLimitTier), TokenBucket>>>,
+    overrides: Arc<RwLock<Arc<RateLimitOverrides>>>,
+    /// `(rpm, burst)` replacing [`RateLimitTier::config`] for some tiers
+    tier_limits: HashMap<RateLimitTier, (u32, u32)>,
     redis: Option<redis::Client>,
 }
 
@@ -128,24 +209,53 @@ impl RateLimiter {
             .and_then(|url| redis::Client::open(url).ok());
         Self {
             buckets: Arc::new(Mutex::new(HashMap::new())),
+            overrides: Arc::default(),
+            tier_limits: HashMap::new(),
             redis,
         }
     }
 
+    /// Limit `tier` to `(rpm, burst)` instead of its environment settings.
+    pub fn with_tier_limits(mut self, tier: RateLimitTier, limits: (u32, u32)) -> Self {
+        self.tier_limits.insert(tier, limits);
+        self
+    }
+
+    /// A tier's `(rpm, burst)` before throttle overrides.
+    pub fn tier_limits(&self, tier: RateLimitTier) -> (u32, u32) {
+        self.tier_limits
+            .get(&tier)
+            .copied()
+            .unwrap_or_else(|| tier.config())
+    }
+
+    /// Replace the active throttle overrides.
+    pub fn set_overrides(&self, overrides: RateLimitOverrides) {
+        *self.overrides.write().unwrap() = Arc::new(overrides);
+    }
+
+    pub fn overrides(&self) -> Arc<RateLimitOverrides> {
+        self.overrides.read().unwrap().clone()
+    }
+
+    /// Count one request of `key` against the tier limits `(rpm, burst)`.
     pub async fn check_rate_limit(
         &self,
-        ip: IpAddr,
+        key: &RateLimitKey,
         tier: RateLimitTier,
+        (rpm, burst): (u32, u32),
     ) -> Result<(), (StatusCode, String)> {
         if self.redis.is_some() {
-            return self.check_rate_limit_redis(ip, tier).await;
+            return self
+                .check_rate_limit_redis(key, tier, rpm.saturating_add(burst))
+                .await;
         }
 
         let mut buckets = self.buckets.lock().await;
-        let (rpm, burst) = tier.config();
         let bucket = buckets
-            .entry((ip, tier))
+            .entry((key.clone(), tier))
             .or_insert_with(|| TokenBucket::new(rpm, burst));
+        bucket.resize(rpm, burst);
 
         if bucket.try_consume() {
             Ok(())
@@ -159,8 +269,9 @@ impl RateLimiter {
 
     async fn check_rate_limit_redis(
         &self,
-        ip: IpAddr,
+        key: &RateLimitKey,
         tier: RateLimitTier,
+        limit: u32,
     ) -> Result<(), (StatusCode, String)> {
         let client = match &self.redis {
             Some(c) => c,
@@ -176,8 +287,7 @@ impl RateLimiter {
                 )
             })?;
 
-        let (rpm, burst) = tier.config();
-        let key = format!("rl:{}:{:?}", ip, tier);
+        let key = format!("rl:{}:{:?}", key, tier);
         let window_secs = 60_u64;
         let script = redis::Script::new(
             r#"
@@ -195,7 +305,7 @@ impl RateLimiter {
         let allowed: i32 = script
             .key(key)
             .arg(window_secs as i32)
-            .arg((rpm + burst) as i32)
+            .arg(limit as i32)
             .invoke_async(&mut conn)
             .await
             .map_err(|_| {
@@ -228,21 +338,49 @@ impl Default for RateLimiter {
     }
 }
 
-static GLOBAL_RATE_LIMITER: tokio::sync::OnceCell<Arc<RateLimiter>> =
-    tokio::sync::OnceCell::const_new();
+/// Client address the rate limiter attributed a request to (after trusted
+/// proxy headers), available to handlers as a request extension.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// The overridden user or API key a request authenticates as, if any.
+///
+/// Only credentials that verify count: a JWT must validate and an API key
+/// must hash to an overridden key, so forged credentials fall back to the
+/// client IP's bucket.
+fn overridden_subject(
+    state: &AppState,
+    headers: &HeaderMap,
+    overrides: &RateLimitOverrides,
+) -> Option<(RateLimitKey, RateLimitOverride)> {
+    if let Some(key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+        if overrides.api_keys.is_empty() {
+            return None;
+        }
+        let (key_id, adjustment) = overrides.api_keys.get(&crate::auth::hash_api_key(key))?;
+        return Some((RateLimitKey::ApiKey(key_id.clone()), *adjustment));
+    }
 
-async fn global_rate_limiter() -> &'static Arc<RateLimiter> {
-    GLOBAL_RATE_LIMITER
-        .get_or_init(|| async { Arc::new(RateLimiter::new()) })
-        .await
+    if overrides.users.is_empty() {
+        return None;
+    }
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())?
+        .strip_prefix("Bearer ")?;
+    let claims = state.auth_config().ok()?.validate_token(token).ok()?;
+    let adjustment = overrides.users.get(&claims.sub)?;
+    Some((RateLimitKey::User(claims.sub), *adjustment))
 }
 
 pub async fn rate_limit_middleware(
-    request: Request<Body>,
+    State(state): State<Arc<AppState>>,
+    mut request: Request<Body>,
     next: Next,
 ) -> Result<Response, ApiError> {
     let tier = RateLimitTier::from_path(request.uri().path());
     let ip = extract_client_ip(&request)?;
+    request.extensions_mut().insert(ClientIp(ip));
 
     // Local node processes can legitimately re-register frequently during startup,
     // integration testing, or rapid restarts. Avoid blocking loopback node
@@ -255,12 +393,17 @@ pub async fn rate_limit_middleware(
         return Ok(next.run(request).await);
     }
 
-    let rate_limiter = global_rate_limiter().await;
+    let rate_limiter = state.rate_limiter();
+    let overrides = rate_limiter.overrides();
+    let (key, limits) = match overridden_subject(&state, request.headers(), &overrides) {
+        Some((key, adjustment)) => (key, adjustment.apply(rate_limiter.tier_limits(tier))),
+        None => (RateLimitKey::Ip(ip), rate_limiter.tier_limits(tier)),
+    };
 
-    match rate_limiter.check_rate_limit(ip, tier).await {
+    match rate_limiter.check_rate_limit(&key, tier, limits).await {
         Ok(()) => Ok(next.run(request).await),
         Err((status, message)) => {
-            warn!("Rate limit exceeded for IP: {} on tier: {:?}", ip, tier);
+            warn!("Rate limit exceeded for {} on tier: {:?}", key, tier);
             let mut response = ApiError::new("rate_limited", message, status).into_response();
             response
                 .headers_mut()
@@ -314,9 +457,8 @@ fn extract_client_ip(request: &Request<Body>) -> Result<IpAddr, ApiError> {
     Ok(remote_ip)
 }
 
-pub async fn start_cleanup_task() {
-    let rate_limiter = global_rate_limiter().await.clone();
-
+/// Periodically drop idle buckets of `rate_limiter`.
+pub fn start_cleanup_task(rate_limiter: RateLimiter) {
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(300));
         loop {
@@ -336,10 +478,22 @@ mod tests {
             RateLimitTier::from_path("/api/v1/auth/login"),
             RateLimitTier::Auth
         );
+        assert_eq!(
+            RateLimitTier::from_path("/api/v1/auth/password-reset/request"),
+            RateLimitTier::Auth
+        );
         assert_eq!(
             RateLimitTier::from_path("/api/v1/proofs/verify"),
             RateLimitTier::ProofVerification
         );
+        assert_eq!(
+            RateLimitTier::from_path("/api/v1/proofs/batches"),
+            RateLimitTier::ProofVerification
+        );
+        assert_eq!(
+            RateLimitTier::from_path("/api/v1/proofs/batches/5f0c"),
+            RateLimitTier::General
+        );
     }
 
     #[test]
@@ -361,15 +515,61 @@ mod tests {
         assert_eq!(ip, IpAddr::from([127, 0, 0, 1]));
     }
 
-    /// Verify that the middleware and cleanup task share the same global limiter
-    /// instance, so cleanup actually reclaims memory from active rate-limiting state.
     #[tokio::test]
-    async fn test_middleware_and_cleanup_share_same_limiter() {
-        let limiter_a = global_rate_limiter().await;
-        let limiter_b = global_rate_limiter().await;
-        assert!(
-            Arc::ptr_eq(limiter_a, limiter_b),
-            "middleware and cleanup task must share the same RateLimiter instance"
+    async fn test_override_buckets_are_separate_and_resizable() {
+        let adjustment = RateLimitOverride {
+            multiplier: 0.5,
+            burst: Some(2),
+        };
+        assert_eq!(adjustment.apply((60, 10)), (30, 2));
+        assert_eq!(
+            RateLimitOverride {
+                multiplier: 0.001,
+                burst: None
+            }
+            .apply((60, 10)),
+            (1, 10)
         );
+
+        let limiter = RateLimiter {
+            redis: None,
+            ..RateLimiter::new()
+        };
+        let user = RateLimitKey::User("u-1".to_string());
+        let ip = RateLimitKey::Ip(IpAddr::from([203, 0, 113, 9]));
+        let tier = RateLimitTier::General;
+        for _ in 0..2 {
+            assert!(limiter.check_rate_limit(&user, tier, (1, 2)).await.is_ok());
+        }
+        assert!(limiter.check_rate_limit(&user, tier, (1, 2)).await.is_err());
+        assert!(limiter.check_rate_limit(&ip, tier, (1, 2)).await.is_ok());
+
+        let configured = RateLimiter::new().with_tier_limits(RateLimitTier::Auth, (600, 100));
+        assert_eq!(configured.tier_limits(RateLimitTier::Auth), (600, 100));
+        assert_eq!(
+            configured.tier_limits(RateLimitTier::General),
+            RateLimitTier::General.config()
+        );
+
+        // A reload that shrinks the burst caps the saved tokens.
+        let key = RateLimitKey::ApiKey("k-1".to_string());
+        assert!(limiter.check_rate_limit(&key, tier, (1, 5)).await.is_ok());
+        assert!(limiter.check_rate_limit(&key, tier, (1, 1)).await.is_ok());
+        assert!(limiter.check_rate_limit(&key, tier, (1, 1)).await.is_err());
+    }
+
+    /// Clones, like the one handed to the cleanup task, share buckets with
+    /// the app's limiter; separate apps' limiters do not.
+    #[tokio::test]
+    async fn test_clones_share_buckets_and_apps_do_not() {
+        let state = AppState::new(None);
+        let cleanup = state.rate_limiter().clone();
+        assert!(Arc::ptr_eq(&state.rate_limiter().buckets, &cleanup.buckets));
+
+        let other = AppState::new(None);
+        assert!(!Arc::ptr_eq(
+            &state.rate_limiter().buckets,
+            &other.rate_limiter().buckets
+        ));
     }
 }
@@ -0,0 +1,355 @@
+/// Scoped permissions for protected routes
+///
+/// Every authenticated route maps to one scope of the form
+/// `<resource>:<action>` in [`required_scope`].  JWTs carry the scopes granted
+/// at login and API keys the scopes chosen when the key was created; a request
+/// is allowed when the credential holds the scope *and* the user's role
+/// permits it, so a key cannot outgrow its owner.
+///
+/// `*` grants every scope and `<resource>:*` every action on one resource.
+use crate::error::ApiError;
+use axum::http::Method;
+
+/// Scopes checked by the route matrix.
+pub const SCOPES: &[&str] = &[
+    "tasks:read",
+    "tasks:write",
+    "nodes:read",
+    "nodes:manage",
+    "sessions:manage",
+    "cluster:read",
+    "proofs:read",
+    "proofs:write",
+    "modules:read",
+    "modules:write",
+    "orgs:read",
+    "orgs:manage",
+    "auth:manage",
+    "admin:users",
+    "admin:throttle",
+    "admin:audit",
+    "admin:metrics",
+    "admin:retention",
+    "admin:fleet",
+];
+
+/// Older API keys were issued `nodes:write`; it grants `nodes:manage`.
+const SCOPE_ALIASES: &[(&str, &str)] = &[("nodes:write", "nodes:manage")];
+
+/// Scopes a role may exercise.  `admin` holds everything; every other role
+/// holds all non-admin scopes.
+pub fn role_scopes(role: &str) -> Vec<String> {
+    if role == "admin" {
+        return vec!["*".to_string()];
+    }
+    SCOPES
+        .iter()
+        .filter(|scope| !scope.starts_with("admin:"))
+        .map(|scope| scope.to_string())
+        .collect()
+}
+
+/// Whether `scope` may be granted: a matrix scope, a legacy alias, `*`, or
+/// `<resource>:*` for a known resource.
+pub fn is_known_scope(scope: &str) -> bool {
+    if scope == "*" || SCOPES.contains(&scope) || canonical(scope) != scope {
+        return true;
+    }
+    scope
+        .strip_suffix(":*")
+        .is_some_and(|resource| SCOPES.iter().any(|known| resource_of(known) == resource))
+}
+
+/// Whether `granted` satisfies `required`.
+pub fn scope_allows(granted: &[String], required: &str) -> bool {
+    granted.iter().any(|scope| {
+        let scope = canonical(scope);
+        scope == "*"
+            || scope == required
+            || scope
+                .strip_suffix(":*")
+                .is_some_and(|resource| resource == resource_of(required))
+    })
+}
+
+/// Scopes in `requested` that `role` does not permit.
+pub fn scopes_beyond_role<'a>(role: &str, requested: &'a [String]) -> Vec<&'a str> {
+    scopes_beyond(&role_scopes(role), requested)
+}
+
+/// Scopes in `requested` that `granted` does not cover, e.g. scopes a
+/// credential asks to hand on but does not hold itself.
+pub fn scopes_beyond<'a>(granted: &[String], requested: &'a [String]) -> Vec<&'a str> {
+    requested
+        .iter()
+        .map(String::as_str)
+        .filter(|scope| !covered_by(granted, scope))
+        .collect()
+}
+
+/// The scopes of `granted` that `role` permits, for credentials derived from
+/// another one.  Wildcards the role does not fully cover are expanded to the
+/// matrix scopes it does.
+pub fn scopes_within_role(role: &str, granted: &[String]) -> Vec<String> {
+    let ceiling = role_scopes(role);
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in granted {
+        let candidates = if covered_by(&ceiling, scope) {
+            vec![canonical(scope).to_string()]
+        } else {
+            SCOPES
+                .iter()
+                .filter(|known| scope_allows(std::slice::from_ref(scope), known))
+                .filter(|known| scope_allows(&ceiling, known))
+                .map(|known| known.to_string())
+                .collect()
+        };
+        for candidate in candidates {
+            if !scopes.contains(&candidate) {
+                scopes.push(candidate);
+            }
+        }
+    }
+    scopes
+}
+
+/// Check that a credential holding `granted` and belonging to `role` may use
+/// `required`.  The 403 names the missing scope and what was granted.
+pub fn authorize(role: &str, granted: &[String], required: &str) -> Result<(), ApiError> {
+    if scope_allows(granted, required) && scope_allows(&role_scopes(role), required) {
+        return Ok(());
+    }
+    Err(
+        ApiError::forbidden(format!("Missing required scope '{required}'")).with_details(
+            serde_json::json!({
+                "required_scope": required,
+                "granted_scopes": granted,
+                "role": role,
+            }),
+        ),
+    )
+}
+
+/// Scope required for `method` on a matched route template such as
+/// `/api/v1/tasks/:task_id`.
+///
+/// Returns `None` for routes that need no scope (API key self-service) and
+/// for routes missing from the matrix, which callers must deny.
+pub fn required_scope(method: &Method, route: &str) -> Option<&'static str> {
+    let route = route.strip_prefix("/api/v1").unwrap_or(route);
+    let read = method == Method::GET || method == Method::HEAD;
+
+    let scope = match route {
+        "/tasks/:task_id/restore" => "tasks:write",
+        "/tasks" | "/tasks/:task_id" | "/tasks/:task_id/secrets" => {
+            if read {
+                "tasks:read"
+            } else {
+                "tasks:write"
+            }
+        }
+        "/tasks/search"
+        | "/tasks/:task_id/secrets/recipients"
+        | "/tasks/:task_id/logs/stream"
+        | "/tasks/:task_id/provenance"
+        | "/tasks/:task_id/timeline" => "tasks:read",
+        // Node-side task operations act as the node operator.
+        "/tasks/:task_id/result"
+        | "/tasks/:task_id/logs"
+        | "/tasks/:task_id/secrets/:node_id"
+        | "/tasks/:task_id/checkpoint/:node_id" => "nodes:manage",
+        "/nodes" | "/nodes/:node_id" => {
+            if read {
+                "nodes:read"
+            } else {
+                "nodes:manage"
+            }
+        }
+        "/nodes/rankings" | "/nodes/:node_id/heartbeat/activity" | "/nodes/:node_id/telemetry" => {
+            "nodes:read"
+        }
+        "/nodes/:node_id/reject"
+        | "/nodes/:node_id/drain"
+        | "/nodes/:node_id/attestation"
+        | "/nodes/:node_id/attestation/challenge"
+        | "/nodes/:node_id/heartbeat"
+        | "/nodes/heartbeat/batch"
+        | "/nodes/:node_id/gateway-sessions"
+        | "/nodes/:node_id/gateway-sessions/:session_id/usage"
+        | "/nodes/:node_id/route"
+        | "/nodes/:node_id/route/invalidate"
+        | "/nodes/:node_id/relay-load"
+        | "/nodes/:node_id/relay-drain"
+        | "/destination-policies/bundle"
+        | "/connect-sessions/:session_id/usage" => "nodes:manage",
+        "/connect-sessions/start"
+        | "/connect-sessions/:session_id"
+        | "/connect-sessions/:session_id/heartbeat"
+        | "/connect-sessions/:session_id/extend"
+        | "/connect-sessions/:session_id/stop" => "sessions:manage",
+        "/cluster/stats" | "/cluster/stats/history" | "/cluster/task-stats" | "/usage" => {
+            "cluster:read"
+        }
+        "/proofs/verify" | "/proofs/batches" => "proofs:write",
+        "/proofs/:proof_id" | "/proofs/batches/:batch_id" | "/proofs/quota" => "proofs:read",
+        "/modules" | "/modules/:module_hash" => {
+            if read {
+                "modules:read"
+            } else {
+                "modules:write"
+            }
+        }
+        "/orgs" | "/orgs/:org_id/members" | "/orgs/:org_id/members/:user_id" => {
+            if read {
+                "orgs:read"
+            } else {
+                "orgs:manage"
+            }
+        }
+        "/orgs/:org_id/token" => "orgs:read",
+        "/auth/api-keys" | "/auth/api-keys/:key_id" => "auth:manage",
+        "/admin/users" | "/admin/login-lockouts" | "/admin/login-lockouts/:scope/:subject" => {
+            "admin:users"
+        }
+        "/admin/throttle-overrides" | "/admin/throttle-overrides/:override_id" => "admin:throttle",
+        "/admin/audit-log" => "admin:audit",
+        "/admin/retention" => "admin:retention",
+        "/admin/node-kinds"
+        | "/admin/canaries"
+        | "/admin/scheduler-decisions"
+        | "/admin/scheduler-decisions/:decision_id/replay"
+        | "/admin/cluster-exports"
+        | "/admin/cluster-exports/:export_id"
+        | "/admin/cluster-exports/:export_id/files/:table"
+        | "/admin/destination-policies"
+        | "/admin/destination-policies/:policy_id" => "admin:fleet",
+        "/metrics" => "admin:metrics",
+        _ => return None,
+    };
+    Some(scope)
+}
+
+/// Routes any authenticated caller may use without a scope.  GraphQL checks
+/// each field's scope itself; key validation only echoes the caller.
+pub fn is_scope_exempt(route: &str) -> bool {
+    let route = route.strip_prefix("/api/v1").unwrap_or(route);
+    route == "/auth/api-key/validate" || route == "/graphql"
+}
+
+fn canonical(scope: &str) -> &str {
+    SCOPE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == scope)
+        .map_or(scope, |(_, target)| target)
+}
+
+fn resource_of(scope: &str) -> &str {
+    scope.split(':').next().unwrap_or(scope)
+}
+
+fn covered_by(ceiling: &[String], scope: &str) -> bool {
+    if scope == "*" {
+        return ceiling.iter().any(|granted| granted == "*");
+    }
+    if let Some(resource) = scope.strip_suffix(":*") {
+        return SCOPES
+            .iter()
+            .filter(|known| resource_of(known) == resource)
+            .all(|known| scope_allows(ceiling, known));
+    }
+    scope_allows(ceiling, canonical(scope))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn matrix_maps_method_and_route() {
+        assert_eq!(
+            required_scope(&Method::GET, "/api/v1/tasks/:task_id"),
+            Some("tasks:read")
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/api/v1/tasks/:task_id"),
+            Some("tasks:write")
+        );
+        assert_eq!(
+            required_scope(&Method::PUT, "/api/v1/nodes/:node_id/heartbeat"),
+            Some("nodes:manage")
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/api/v1/connect-sessions/start"),
+            Some("sessions:manage")
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/metrics"),
+            Some("admin:metrics")
+        );
+        assert_eq!(required_scope(&Method::GET, "/api/v1/unknown"), None);
+        assert_eq!(
+            required_scope(&Method::POST, "/api/v1/auth/api-keys"),
+            Some("auth:manage")
+        );
+        assert!(!is_scope_exempt("/api/v1/auth/api-keys"));
+        assert!(is_scope_exempt("/api/v1/graphql"));
+    }
+
+    #[test]
+    fn wildcards_and_aliases_grant_scopes() {
+        assert!(scope_allows(&scopes(&["*"]), "admin:audit"));
+        assert!(scope_allows(&scopes(&["admin:*"]), "admin:users"));
+        assert!(!scope_allows(&scopes(&["admin:*"]), "tasks:read"));
+        assert!(scope_allows(&scopes(&["nodes:write"]), "nodes:manage"));
+        assert!(!scope_allows(&scopes(&["tasks:read"]), "tasks:write"));
+        assert!(is_known_scope("nodes:write"));
+        assert!(is_known_scope("tasks:*"));
+        assert!(!is_known_scope("admin:write"));
+        assert!(!is_known_scope("billing:*"));
+    }
+
+    #[test]
+    fn role_caps_granted_scopes() {
+        let wildcard = scopes(&["*"]);
+        assert!(authorize("user", &wildcard, "tasks:write").is_ok());
+        let denied = authorize("user", &wildcard, "admin:users").unwrap_err();
+        assert_eq!(denied.status_code, axum::http::StatusCode::FORBIDDEN);
+        assert_eq!(
+            denied.details.as_ref().unwrap()["required_scope"],
+            "admin:users"
+        );
+        assert!(authorize("admin", &wildcard, "admin:users").is_ok());
+
+        assert_eq!(
+            scopes_beyond_role("user", &scopes(&["tasks:*", "admin:*", "*"])),
+            vec!["admin:*", "*"]
+        );
+        assert!(scopes_beyond_role("admin", &scopes(&["*"])).is_empty());
+        assert_eq!(
+            scopes_beyond(
+                &scopes(&["tasks:*"]),
+                &scopes(&["tasks:read", "auth:manage"])
+            ),
+            vec!["auth:manage"]
+        );
+    }
+
+    #[test]
+    fn derived_scopes_stay_within_the_role() {
+        assert_eq!(
+            scopes_within_role("user", &scopes(&["tasks:read", "nodes:write"])),
+            scopes(&["tasks:read", "nodes:manage"])
+        );
+        let from_wildcard = scopes_within_role("user", &scopes(&["*"]));
+        assert_eq!(from_wildcard, role_scopes("user"));
+        assert_eq!(
+            scopes_within_role("user", &scopes(&["admin:*"])),
+            Vec::<String>::new()
+        );
+        assert_eq!(scopes_within_role("admin", &scopes(&["*"])), scopes(&["*"]));
+    }
+}
@@ -0,0 +1,275 @@
+/// Scheduler decision log and replay
+///
+/// Every scheduling decision is recorded in `scheduler_decisions` with
+/// everything it was made from, so a postmortem can see why a task landed
+/// where it did and check a fix against it:
+///
+/// - `placement` — nodes chosen for a task from its eligible candidates
+///   ([`crate::scheduling::place_task`]); `chosen` holds the nodes offered,
+///   best first, and `attached` those the task took
+/// - `pickup` — the fair-share order pending tasks were offered to a
+///   heartbeating node in ([`crate::fair_queue::fair_order`]); `chosen`
+///   holds the task IDs in that order and `attached` those the node took
+///
+/// Each row carries the [`crate::scheduling::RULE_VERSIONS`] in force and a
+/// SHA-256 hash of its inputs.  Replaying a decision
+/// (`POST /api/v1/admin/scheduler-decisions/{decision_id}/replay`) re-runs
+/// the current rules on the recorded inputs and reports whether they still
+/// choose the same.  Recording is best-effort: a failed insert is logged and
+/// never fails scheduling.  Rows are purged after
+/// `RETENTION_SCHEDULER_DECISIONS_DAYS` (see [`crate::retention`]).
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+use crate::fair_queue::{FairShareConfig, QueuedTask};
+use crate::scheduling::{PlacementInputs, RULE_VERSIONS};
+
+/// Most decisions one `GET /api/v1/admin/scheduler-decisions` request
+/// returns.
+pub const MAX_SCHEDULER_DECISIONS: i64 = 500;
+
+/// Inputs of a pickup decision: the pending tasks a node could take, in
+/// aged priority order, and the fair-share settings they were ordered by.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PickupInputs {
+    pub node_id: String,
+    pub queue: Vec<QueuedTask>,
+    /// Weight of each requester in `queue`
+    pub weights: BTreeMap<String, f64>,
+    pub max_running_per_requester: Option<u32>,
+}
+
+impl PickupInputs {
+    /// Snapshot of `queue` and the parts of `config` that order it.
+    pub fn new(node_id: &str, queue: Vec<QueuedTask>, config: &FairShareConfig) -> Self {
+        let weights = queue
+            .iter()
+            .map(|task| (task.requester.clone(), config.weight_for(&task.requester)))
+            .collect();
+        Self {
+            node_id: node_id.to_string(),
+            queue,
+            weights,
+            max_running_per_requester: config.max_running_per_requester(),
+        }
+    }
+}
+
+/// What a decision was made from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DecisionInputs {
+    Placement(PlacementInputs),
+    Pickup(PickupInputs),
+}
+
+impl DecisionInputs {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Placement(_) => "placement",
+            Self::Pickup(_) => "pickup",
+        }
+    }
+
+    /// Hex SHA-256 of the inputs' JSON.
+    pub fn candidate_set_hash(&self) -> String {
+        let json = serde_json::to_vec(self).expect("decision inputs serialize");
+        hex::encode(Sha256::digest(json))
+    }
+
+    /// What the current rules choose from these inputs.
+    pub fn decide(&self) -> Vec<String> {
+        match self {
+            Self::Placement(inputs) => crate::scheduling::place_task(inputs),
+            Self::Pickup(inputs) => {
+                let config = FairShareConfig::with_weights(
+                    inputs.weights.clone().into_iter().collect(),
+                    inputs.max_running_per_requester,
+                );
+                crate::fair_queue::fair_order(inputs.queue.clone(), &config)
+                    .0
+                    .into_iter()
+                    .map(|task| task.task_id.to_string())
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The current version of every scheduling rule.
+pub fn rule_versions() -> BTreeMap<String, u32> {
+    RULE_VERSIONS
+        .iter()
+        .map(|(rule, version)| (rule.to_string(), *version))
+        .collect()
+}
+
+/// One recorded decision.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchedulerDecision {
+    pub decision_id: i64,
+    /// `placement` or `pickup`
+    pub kind: String,
+    /// Task a placement was made for
+    pub task_id: Option<String>,
+    /// Node a pickup was made for
+    pub node_id: Option<String>,
+    pub rule_versions: BTreeMap<String, u32>,
+    pub candidate_set_hash: String,
+    pub inputs: DecisionInputs,
+    /// Nodes (placement) or task IDs (pickup) offered, in order
+    pub chosen: Vec<String>,
+    /// Those actually attached
+    pub attached: Vec<String>,
+    pub decided_at: DateTime<Utc>,
+}
+
+/// Query string for `GET /api/v1/admin/scheduler-decisions`
+#[derive(Debug, Default, Deserialize)]
+pub struct SchedulerDecisionsQuery {
+    /// Placements for this task and pickups that attached it
+    pub task_id: Option<uuid::Uuid>,
+    /// Pickups for this node and placements that chose it
+    pub node_id: Option<String>,
+    /// Only decisions of this kind: `placement` or `pickup`
+    pub kind: Option<String>,
+    /// Most decisions to return, newest first (default 50, at most 500)
+    pub limit: Option<i64>,
+}
+
+/// Outcome of re-running a recorded decision.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecisionReplay {
+    pub decision_id: i64,
+    pub kind: String,
+    /// What the decision chose when it was made
+    pub recorded: Vec<String>,
+    /// What the current rules choose from the same inputs
+    pub replayed: Vec<String>,
+    pub matches: bool,
+    /// Rules whose version differs from the recorded one
+    pub changed_rules: Vec<String>,
+    /// Whether the stored inputs still hash to the recorded hash
+    pub inputs_intact: bool,
+}
+
+/// Re-run `decision` against the current rules.
+pub fn replay(decision: &SchedulerDecision) -> DecisionReplay {
+    let replayed = decision.inputs.decide();
+    let current = rule_versions();
+    let changed_rules = current
+        .iter()
+        .filter(|(rule, version)| decision.rule_versions.get(*rule) != Some(version))
+        .map(|(rule, _)| rule.clone())
+        .collect();
+    DecisionReplay {
+        decision_id: decision.decision_id,
+        kind: decision.kind.clone(),
+        matches: replayed == decision.chosen,
+        recorded: decision.chosen.clone(),
+        replayed,
+        changed_rules,
+        inputs_intact: decision.inputs.candidate_set_hash() == decision.candidate_set_hash,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DiversityMode, SchedulingMode};
+    use crate::scheduling::PlacementCandidate;
+
+    fn candidate(node_id: &str, region: &str, health_score: f64) -> PlacementCandidate {
+        PlacementCandidate {
+            node_id: node_id.to_string(),
+            region: region.to_string(),
+            asn: None,
+            health_score,
+            benchmark_ops_per_wh: None,
+        }
+    }
+
+    fn decision(inputs: DecisionInputs, chosen: Vec<String>) -> SchedulerDecision {
+        SchedulerDecision {
+            decision_id: 7,
+            kind: inputs.kind().to_string(),
+            task_id: None,
+            node_id: None,
+            rule_versions: rule_versions(),
+            candidate_set_hash: inputs.candidate_set_hash(),
+            inputs,
+            attached: chosen.clone(),
+            chosen,
+            decided_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn placement_replays_against_current_rules() {
+        let inputs = DecisionInputs::Placement(PlacementInputs {
+            scheduling_mode: SchedulingMode::Standard,
+            diversity: DiversityMode::Off,
+            min_nodes: 1,
+            wanted: 1,
+            preferred_regions: vec!["eu-west".to_string()],
+            carbon_factors: BTreeMap::new(),
+            candidates: vec![
+                candidate("node-us", "us-east", 0.9),
+                candidate("node-eu", "eu-west", 0.5),
+            ],
+            taken: Vec::new(),
+        });
+        assert_eq!(inputs.decide(), ["node-eu", "node-us"]);
+
+        let replay = replay(&decision(inputs.clone(), inputs.decide()));
+        assert!(replay.matches);
+        assert!(replay.inputs_intact);
+        assert!(replay.changed_rules.is_empty());
+
+        let mut stale = decision(inputs, vec!["node-us".to_string()]);
+        stale
+            .rule_versions
+            .insert("region_preference".to_string(), 0);
+        stale.candidate_set_hash = "0".repeat(64);
+        let replay = super::replay(&stale);
+        assert!(!replay.matches);
+        assert!(!replay.inputs_intact);
+        assert_eq!(replay.changed_rules, ["region_preference"]);
+    }
+
+    #[test]
+    fn pickup_replays_the_fair_share_order() {
+        let task = |requester: &str, requester_running| QueuedTask {
+            task_id: uuid::Uuid::new_v4(),
+            requester: requester.to_string(),
+            requester_running,
+            partially_assigned: false,
+        };
+        let queue = vec![
+            task("org:busy", 5),
+            task("org:busy", 5),
+            task("org:idle", 0),
+        ];
+        let config = FairShareConfig::parse(Some("org:busy=10"), Some("6"));
+        let inputs = DecisionInputs::Pickup(PickupInputs::new("node-1", queue.clone(), &config));
+        let DecisionInputs::Pickup(pickup) = &inputs else {
+            unreachable!()
+        };
+        assert_eq!(pickup.weights["org:busy"], 10.0);
+        assert_eq!(pickup.weights["org:idle"], 1.0);
+
+        let expected: Vec<String> = crate::fair_queue::fair_order(queue, &config)
+            .0
+            .into_iter()
+            .map(|task| task.task_id.to_string())
+            .collect();
+        assert_eq!(inputs.decide(), expected);
+
+        // The hash survives a round trip through the stored JSON.
+        let stored: DecisionInputs =
+            serde_json::from_value(serde_json::to_value(&inputs).unwrap()).unwrap();
+        assert_eq!(stored.candidate_set_hash(), inputs.candidate_set_hash());
+    }
+}
@@ -0,0 +1,317 @@
+/// Node ranking policies used when assigning tasks
+///
+/// The default scheduler orders eligible nodes by health score directly in
+/// SQL.  Policies that depend on configuration held by the server (such as
+/// region carbon intensity) rank the eligible candidates here instead.
+use crate::carbon::GridCarbonFactors;
+use crate::models::{DiversityMode, RegionStrictness, SchedulingMode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Version of each rule that turns a candidate set into a decision.  Bump a
+/// rule's version whenever its behaviour changes, so a replayed decision
+/// (see [`crate::scheduler_decisions`]) shows which rules moved since it was
+/// made.
+pub const RULE_VERSIONS: [(&str, u32); 5] = [
+    ("standard_ranking", 1),
+    ("green_ranking", 1),
+    ("region_preference", 1),
+    ("network_diversity", 1),
+    ("fair_share", 1),
+];
+
+/// Grid intensity (g CO2e / kWh) treated as the dirtiest possible region when
+/// normalising carbon scores.
+const CARBON_REFERENCE_MAX_G_PER_KWH: f64 = 1_000.0;
+
+/// Efficiency score given to nodes that have not published a benchmark.
+const UNBENCHMARKED_EFFICIENCY_SCORE: f64 = 0.5;
+
+const GREEN_CARBON_WEIGHT: f64 = 0.6;
+const GREEN_EFFICIENCY_WEIGHT: f64 = 0.25;
+const GREEN_HEALTH_WEIGHT: f64 = 0.15;
+
+/// An eligible node considered by green scheduling.
+#[derive(Debug, Clone)]
+pub struct GreenCandidate {
+    pub node_id: String,
+    pub region: String,
+    /// Health score in the 0–100 range stored on `nodes.health_score`.
+    pub health_score: f64,
+    pub benchmark_ops_per_wh: Option<f64>,
+}
+
+/// Score a candidate in `[0, 1]`; higher is greener.
+///
+/// Efficiency is normalised against the most efficient candidate in the same
+/// pool so the score is meaningful regardless of the benchmark's units.
+pub fn green_score(
+    candidate: &GreenCandidate,
+    factors: &GridCarbonFactors,
+    best_ops_per_wh: Option<f64>,
+) -> f64 {
+    let carbon_score = 1.0
+        - (factors.factor_for(&candidate.region) / CARBON_REFERENCE_MAX_G_PER_KWH).clamp(0.0, 1.0);
+
+    let efficiency_score = match (candidate.benchmark_ops_per_wh, best_ops_per_wh) {
+        (Some(ops), Some(best)) if best > 0.0 => (ops / best).clamp(0.0, 1.0),
+        _ => UNBENCHMARKED_EFFICIENCY_SCORE,
+    };
+
+    let health_score = (candidate.health_score / 100.0).clamp(0.0, 1.0);
+
+    carbon_score * GREEN_CARBON_WEIGHT
+        + efficiency_score * GREEN_EFFICIENCY_WEIGHT
+        + health_score * GREEN_HEALTH_WEIGHT
+}
+
+/// Return up to `limit` node IDs ordered from greenest to least green.
+///
+/// Ties keep the incoming order, so callers should pass candidates already
+/// sorted by their standard preference (health, then registration age).
+pub fn rank_green_candidates(
+    candidates: Vec<GreenCandidate>,
+    factors: &GridCarbonFactors,
+    limit: usize,
+) -> Vec<String> {
+    let best_ops_per_wh = candidates
+        .iter()
+        .filter_map(|candidate| candidate.benchmark_ops_per_wh)
+        .reduce(f64::max);
+
+    let mut scored: Vec<(f64, GreenCandidate)> = candidates
+        .into_iter()
+        .map(|candidate| (green_score(&candidate, factors, best_ops_per_wh), candidate))
+        .collect();
+
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, candidate)| candidate.node_id)
+        .collect()
+}
+
+/// A task's region placement preferences, as stored on its row.
+#[derive(Debug, Clone, Default)]
+pub struct RegionPlacement {
+    pub preferred: Vec<String>,
+    pub excluded: Vec<String>,
+    pub strictness: RegionStrictness,
+}
+
+impl RegionPlacement {
+    /// Regions a node must be in, or `None` when any non-excluded region will do.
+    pub fn required(&self) -> Option<&[String]> {
+        (self.strictness == RegionStrictness::Require).then_some(self.preferred.as_slice())
+    }
+
+    /// Move nodes in a preferred region ahead of the others, keeping the
+    /// ranking within each group.  Nodes whose region is unknown count as
+    /// not preferred.
+    pub fn rank_preferred_first<'a>(
+        &self,
+        ranked: Vec<String>,
+        region_of: impl Fn(&str) -> Option<&'a str>,
+    ) -> Vec<String> {
+        if self.preferred.is_empty() {
+            return ranked;
+        }
+        let (mut preferred, rest): (Vec<String>, Vec<String>) =
+            ranked.into_iter().partition(|node_id| {
+                region_of(node_id).is_some_and(|region| self.preferred.iter().any(|p| p == region))
+            });
+        preferred.extend(rest);
+        preferred
+    }
+}
+
+/// An eligible node as the candidate query returned it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacementCandidate {
+    pub node_id: String,
+    pub region: String,
+    pub asn: Option<u32>,
+    pub health_score: f64,
+    pub benchmark_ops_per_wh: Option<f64>,
+}
+
+/// Everything a task's node placement is decided from once the candidate
+/// query ran.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacementInputs {
+    pub scheduling_mode: SchedulingMode,
+    pub diversity: DiversityMode,
+    pub min_nodes: u32,
+    /// Nodes the task still needs
+    pub wanted: usize,
+    pub preferred_regions: Vec<String>,
+    /// Grid intensity of each candidate region, for green ranking
+    #[serde(default)]
+    pub carbon_factors: BTreeMap<String, f64>,
+    /// Candidates in the candidate query's order
+    pub candidates: Vec<PlacementCandidate>,
+    /// Network locations of the task's current nodes, when spreading
+    #[serde(default)]
+    pub taken: Vec<ambient_node::NetworkLocation>,
+}
+
+impl PlacementInputs {
+    /// Whether the task's nodes are spread across networks; only a task run
+    /// on several nodes has anything to spread.
+    pub fn spread(&self) -> bool {
+        self.diversity != DiversityMode::Off && self.min_nodes > 1
+    }
+}
+
+/// Nodes to offer the task, best first.  The assignment insert re-checks
+/// capacity and takes as many as the task still needs.
+pub fn place_task(inputs: &PlacementInputs) -> Vec<String> {
+    let spread = inputs.spread();
+    let regions = RegionPlacement {
+        preferred: inputs.preferred_regions.clone(),
+        ..Default::default()
+    };
+    let region_of: BTreeMap<&str, &str> = inputs
+        .candidates
+        .iter()
+        .map(|c| (c.node_id.as_str(), c.region.as_str()))
+        .collect();
+
+    // Green ranking is re-ordered by region preference afterwards, so it
+    // must not cut preferred nodes off first.
+    let ranked_limit = if spread || !regions.preferred.is_empty() {
+        inputs.candidates.len()
+    } else {
+        inputs.wanted
+    };
+    let ranked: Vec<String> = match inputs.scheduling_mode {
+        SchedulingMode::Standard => inputs
+            .candidates
+            .iter()
+            .map(|c| c.node_id.clone())
+            .collect(),
+        SchedulingMode::Green => {
+            let factors = GridCarbonFactors::from_factors(
+                inputs
+                    .carbon_factors
+                    .iter()
+                    .map(|(region, factor)| (region.clone(), *factor))
+                    .collect(),
+            );
+            rank_green_candidates(
+                inputs
+                    .candidates
+                    .iter()
+                    .map(|c| GreenCandidate {
+                        node_id: c.node_id.clone(),
+                        region: c.region.clone(),
+                        health_score: c.health_score,
+                        benchmark_ops_per_wh: c.benchmark_ops_per_wh,
+                    })
+                    .collect(),
+                &factors,
+                ranked_limit,
+            )
+        }
+    };
+    let ranked = regions.rank_preferred_first(ranked, |node_id| region_of.get(node_id).copied());
+
+    if !spread {
+        return ranked;
+    }
+    let location_of: BTreeMap<&str, ambient_node::NetworkLocation> = inputs
+        .candidates
+        .iter()
+        .map(|c| {
+            (
+                c.node_id.as_str(),
+                ambient_node::NetworkLocation::new(c.asn, c.region.clone()),
+            )
+        })
+        .collect();
+    ambient_node::spread_by_network(
+        ranked
+            .into_iter()
+            .filter_map(|node_id| {
+                let location = location_of.get(node_id.as_str())?.clone();
+                Some((node_id, location))
+            })
+            .collect(),
+        &inputs.taken,
+        inputs.wanted,
+        inputs.diversity == DiversityMode::Require,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(node_id: &str, region: &str, ops: Option<f64>) -> GreenCandidate {
+        GreenCandidate {
+            node_id: node_id.to_string(),
+            region: region.to_string(),
+            health_score: 90.0,
+            benchmark_ops_per_wh: ops,
+        }
+    }
+
+    #[test]
+    fn prefers_low_carbon_regions() {
+        let factors = GridCarbonFactors::parse(Some("coal=900,hydro=20"), None);
+        let ranked = rank_green_candidates(
+            vec![
+                candidate("dirty", "coal", None),
+                candidate("clean", "hydro", None),
+            ],
+            &factors,
+            2,
+        );
+        assert_eq!(ranked, vec!["clean".to_string(), "dirty".to_string()]);
+    }
+
+    #[test]
+    fn efficiency_breaks_ties_within_region() {
+        let factors = GridCarbonFactors::parse(Some("eu=100"), None);
+        let ranked = rank_green_candidates(
+            vec![
+                candidate("slow", "eu", Some(100.0)),
+                candidate("fast", "eu", Some(400.0)),
+            ],
+            &factors,
+            1,
+        );
+        assert_eq!(ranked, vec!["fast".to_string()]);
+    }
+
+    #[test]
+    fn unbenchmarked_nodes_score_neutral_efficiency() {
+        let factors = GridCarbonFactors::default();
+        let best = Some(200.0);
+        let unknown = green_score(&candidate("a", "x", None), &factors, best);
+        let half = green_score(&candidate("b", "x", Some(100.0)), &factors, best);
+        assert!((unknown - half).abs() < 1e-9);
+    }
+
+    #[test]
+    fn preferred_regions_rank_first_in_order() {
+        let placement = RegionPlacement {
+            preferred: vec!["eu-west".to_string()],
+            ..Default::default()
+        };
+        let regions = std::collections::HashMap::from([
+            ("a", "us-east"),
+            ("b", "eu-west"),
+            ("c", "us-east"),
+            ("d", "eu-west"),
+        ]);
+        let ranked = placement
+            .rank_preferred_first(["a", "b", "c", "d"].map(String::from).to_vec(), |node_id| {
+                regions.get(node_id).copied()
+            });
+        assert_eq!(ranked, ["b", "d", "a", "c"].map(String::from).to_vec());
+        assert_eq!(placement.required(), None);
+    }
+}
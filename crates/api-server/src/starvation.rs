@@ -0,0 +1,221 @@
+/// Aging of tasks that stay pending because no node matches them
+///
+/// The longer a task waits in the pending queue (counted from `queued_at`),
+/// the further it moves through these stages:
+///
+/// 1. after `TASK_STARVATION_RELAX_SECS` (default `600`) its soft
+///    constraints are relaxed — the preferred node type gives way to any
+///    node meeting the minimum capabilities, for task types that allow it
+/// 2. after `TASK_STARVATION_WARN_SECS` (default `1800`) it is flagged as
+///    starving and its creator is notified
+/// 3. after `TASK_UNSCHEDULABLE_SECS` (default `3600`) it is marked
+///    `unschedulable` with a diagnostics summary explaining which
+///    requirement no node met
+///
+/// Setting a threshold to `0` disables that stage.
+use serde::Serialize;
+use serde_json::json;
+
+pub const DEFAULT_RELAX_AFTER_SECS: u64 = 600;
+pub const DEFAULT_STARVING_AFTER_SECS: u64 = 1800;
+pub const DEFAULT_UNSCHEDULABLE_AFTER_SECS: u64 = 3600;
+
+/// Thresholds, in seconds pending, for each aging stage.  `None` disables it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StarvationPolicy {
+    pub relax_after_secs: Option<u64>,
+    pub starving_after_secs: Option<u64>,
+    pub unschedulable_after_secs: Option<u64>,
+}
+
+impl Default for StarvationPolicy {
+    fn default() -> Self {
+        Self {
+            relax_after_secs: Some(DEFAULT_RELAX_AFTER_SECS),
+            starving_after_secs: Some(DEFAULT_STARVING_AFTER_SECS),
+            unschedulable_after_secs: Some(DEFAULT_UNSCHEDULABLE_AFTER_SECS),
+        }
+    }
+}
+
+impl StarvationPolicy {
+    /// Load from `TASK_STARVATION_RELAX_SECS`, `TASK_STARVATION_WARN_SECS`
+    /// and `TASK_UNSCHEDULABLE_SECS`.
+    pub fn from_env() -> Self {
+        Self::parse(
+            crate::deployment_profile::setting("TASK_STARVATION_RELAX_SECS").as_deref(),
+            std::env::var("TASK_STARVATION_WARN_SECS").ok().as_deref(),
+            std::env::var("TASK_UNSCHEDULABLE_SECS").ok().as_deref(),
+        )
+    }
+
+    /// Parse thresholds; unset or malformed values keep their default and
+    /// `0` disables the stage.
+    pub fn parse(relax: Option<&str>, starving: Option<&str>, unschedulable: Option<&str>) -> Self {
+        fn threshold(value: Option<&str>, default: u64) -> Option<u64> {
+            let secs = value
+                .and_then(|raw| raw.trim().parse::<u64>().ok())
+                .unwrap_or(default);
+            (secs > 0).then_some(secs)
+        }
+
+        Self {
+            relax_after_secs: threshold(relax, DEFAULT_RELAX_AFTER_SECS),
+            starving_after_secs: threshold(starving, DEFAULT_STARVING_AFTER_SECS),
+            unschedulable_after_secs: threshold(unschedulable, DEFAULT_UNSCHEDULABLE_AFTER_SECS),
+        }
+    }
+
+    /// Whether a task pending for `pending_secs` has reached `stage`.
+    pub fn reached(&self, stage: StarvationStage, pending_secs: u64) -> bool {
+        let threshold = match stage {
+            StarvationStage::Waiting => return true,
+            StarvationStage::Relaxed => self.relax_after_secs,
+            StarvationStage::Starving => self.starving_after_secs,
+            StarvationStage::Unschedulable => self.unschedulable_after_secs,
+        };
+        threshold.is_some_and(|secs| pending_secs >= secs)
+    }
+
+    /// The furthest stage a task pending for `pending_secs` has reached.
+    pub fn stage_for(&self, pending_secs: u64) -> StarvationStage {
+        [
+            StarvationStage::Unschedulable,
+            StarvationStage::Starving,
+            StarvationStage::Relaxed,
+        ]
+        .into_iter()
+        .find(|stage| self.reached(*stage, pending_secs))
+        .unwrap_or(StarvationStage::Waiting)
+    }
+
+    /// Seconds pending before the earliest enabled stage applies.
+    pub fn earliest_threshold_secs(&self) -> Option<u64> {
+        [
+            self.relax_after_secs,
+            self.starving_after_secs,
+            self.unschedulable_after_secs,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StarvationStage {
+    Waiting,
+    Relaxed,
+    Starving,
+    Unschedulable,
+}
+
+/// How many nodes survive each eligibility filter for one task, applied in
+/// order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EligibilityFunnel {
+    pub online_nodes: i64,
+    pub node_type_matches: i64,
+    pub capability_matches: i64,
+    /// Capable nodes that are not excluded by earlier failed attempts and
+    /// still have a free task slot.
+    pub available_nodes: i64,
+}
+
+impl EligibilityFunnel {
+    /// The first filter that left fewer than `nodes_needed` nodes.
+    pub fn blocking_constraint(&self, nodes_needed: i64) -> Option<&'static str> {
+        [
+            (self.online_nodes, "online_nodes"),
+            (self.node_type_matches, "node_type"),
+            (self.capability_matches, "minimum_capabilities"),
+            (self.available_nodes, "node_capacity"),
+        ]
+        .into_iter()
+        .find(|(count, _)| *count < nodes_needed)
+        .map(|(_, constraint)| constraint)
+    }
+}
+
+/// Summary stored on a task when it is marked `unschedulable`.
+pub fn diagnostics_summary(
+    pending_secs: u64,
+    nodes_needed: i64,
+    nodes_assigned: i64,
+    node_type: &str,
+    constraints_relaxed: bool,
+    funnel: &EligibilityFunnel,
+) -> serde_json::Value {
+    let blocking = funnel.blocking_constraint(nodes_needed);
+    let reason = match blocking {
+        Some("online_nodes") => "not enough nodes are online",
+        Some("node_type") => "not enough online nodes have the required node type",
+        Some("minimum_capabilities") => {
+            "not enough nodes of the required type meet the minimum capabilities"
+        }
+        Some("node_capacity") => {
+            "every capable node has a full slot pool or is excluded after a failed attempt"
+        }
+        _ => "eligible nodes exist but none accepted the task before the deadline",
+    };
+
+    json!({
+        "reason": reason,
+        "blocking_constraint": blocking,
+        "pending_seconds": pending_secs,
+        "nodes_needed": nodes_needed,
+        "nodes_assigned": nodes_assigned,
+        "node_type": if constraints_relaxed { "any" } else { node_type },
+        "constraints_relaxed": constraints_relaxed,
+        "eligibility": funnel,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_follow_thresholds() {
+        let policy = StarvationPolicy::default();
+        assert_eq!(policy.stage_for(0), StarvationStage::Waiting);
+        assert_eq!(policy.stage_for(600), StarvationStage::Relaxed);
+        assert_eq!(policy.stage_for(1800), StarvationStage::Starving);
+        assert_eq!(policy.stage_for(7200), StarvationStage::Unschedulable);
+
+        let policy = StarvationPolicy::parse(Some("0"), Some("bogus"), Some("0"));
+        assert_eq!(policy.relax_after_secs, None);
+        assert_eq!(
+            policy.starving_after_secs,
+            Some(DEFAULT_STARVING_AFTER_SECS)
+        );
+        assert_eq!(policy.stage_for(1_000_000), StarvationStage::Starving);
+        assert!(!policy.reached(StarvationStage::Relaxed, 1_000_000));
+        assert_eq!(
+            policy.earliest_threshold_secs(),
+            Some(DEFAULT_STARVING_AFTER_SECS)
+        );
+        assert_eq!(
+            StarvationPolicy::parse(Some("0"), Some("0"), Some("0")).earliest_threshold_secs(),
+            None
+        );
+    }
+
+    #[test]
+    fn diagnostics_name_the_blocking_constraint() {
+        let funnel = EligibilityFunnel {
+            online_nodes: 5,
+            node_type_matches: 3,
+            capability_matches: 1,
+            available_nodes: 1,
+        };
+        assert_eq!(funnel.blocking_constraint(1), None);
+        assert_eq!(funnel.blocking_constraint(2), Some("minimum_capabilities"));
+        assert_eq!(funnel.blocking_constraint(4), Some("node_type"));
+
+        let summary = diagnostics_summary(3600, 2, 1, "compute", true, &funnel);
+        assert_eq!(summary["blocking_constraint"], "minimum_capabilities");
+        assert_eq!(summary["node_type"], "any");
+        assert_eq!(summary["eligibility"]["online_nodes"], 5);
+    }
+}
@@ -13,8 +13,12 @@
 /// - `state/tasks.rs`    — Task operations
 /// - `state/sessions.rs` — Connect session management
 /// - `state/auth.rs`     — Auth-related state operations
+use crate::deployment_profile;
 use crate::error::{ApiError, ApiResult};
 use crate::models::*;
+use crate::orgs::OrgRole;
+use crate::starvation::StarvationStage;
+use crate::task_stats::{AttemptUsage, TaskTypeStats};
 use federated_learning::{FederatedAggregator, LayerWeights, ModelWeights, PrivacyBudget};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
@@ -38,14 +42,198 @@ pub struct NodeHeartbeatResult {
     /// meaning internet relay is currently live.  Browsers and phones can use this
     /// flag to confirm that internet is still on without inspecting individual tasks.
     pub internet_active: bool,
+    /// Heartbeat state sequence the server now holds, if the node reports state.
+    pub state_seq: Option<u64>,
+    /// `true` when a state delta did not match the stored sequence; the node
+    /// must send a full snapshot next.
+    pub resync_required: bool,
+}
+
+/// Node fields read while recording a heartbeat, before task assignment.
+struct RecordedHeartbeat {
+    health_score: f64,
+    status: String,
+    state_seq: Option<u64>,
+    resync_required: bool,
+    /// The history row, for telemetry export
+    sample: crate::telemetry_export::NodeSample,
+}
+
+/// Result of `fail_task_attempt`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskAttemptOutcome {
+    /// Task status after the failure: `"pending"` when re-queued, else `"failed"`.
+    pub status: &'static str,
+    /// Retries consumed so far, including this one when re-queued.
+    pub retry_count: u32,
+    /// Retries still available under the task's policy.
+    pub retries_remaining: u32,
+}
+
+/// Task state read and written by one successful `fail_task_attempt`.
+struct FailedAttempt {
+    task_type: String,
+    min_nodes: i32,
+    require_gpu: bool,
+    max_retries: i32,
+    /// Retries consumed before this failure
+    retry_count: i32,
+    will_retry: bool,
+    /// Nodes whose assignment this failure released
+    freed_nodes: Vec<String>,
+}
+
+/// Attempts at a task status transition before giving up with `409`.
+///
+/// Status transitions are compare-and-swap updates on `tasks.version`: each
+/// one applies only while the version it read is unchanged and bumps it, so
+/// the loser of a race re-reads the task and decides again.
+const TASK_TRANSITION_ATTEMPTS: u32 = 5;
+
+/// Outcome of one compare-and-swap attempt at a task status transition.
+enum TaskTransition<T> {
+    Applied(T),
+    /// Another writer changed the task since it was read.
+    Conflict,
+}
+
+/// `nodes` columns decoded by `AppState::node_info_from_row`.
+const NODE_INFO_COLUMNS: &str = "node_id, region, node_type, owner_id, bandwidth_mbps, cpu_cores, \
+     memory_gb, gpu_available, health_score, status, registered_at, last_seen, observability_port, \
+     connect_slots, wasm_slots, gpu_slots, labels, legacy_node_type, \
+     asn, asn_source, flap_count, first_flap_at, flap_breaker_until, attested_at, \
+     allowed_task_types, blocked_task_types";
+
+/// 1-based position of task `t` in the aged priority queue, `NULL` unless
+/// it is pending.  `aging` is the bind holding the priority aging seconds.
+fn task_queue_position_sql(aging: &str) -> String {
+    format!(
+        r#"CASE WHEN t.status = 'pending' THEN (
+            SELECT COUNT(*) + 1
+            FROM tasks q
+            WHERE q.status = 'pending'
+              AND q.deleted_at IS NULL
+              AND (
+                    q.priority + FLOOR(EXTRACT(EPOCH FROM (NOW() - q.created_at)) / {aging}),
+                    t.created_at
+                  ) > (
+                    t.priority + FLOOR(EXTRACT(EPOCH FROM (NOW() - t.created_at)) / {aging}),
+                    q.created_at
+                  )
+        ) END"#
+    )
+}
+
+/// Node aggregates behind `GET /cluster/stats` and its history snapshots.
+const CLUSTER_NODE_STATS_SQL: &str = r#"
+    SELECT
+        COUNT(*) FILTER (WHERE status != 'rejected') as total_nodes,
+        COUNT(*) FILTER (WHERE status = 'online' AND health_score >= 70.0) as healthy_nodes,
+        COALESCE(AVG(health_score) FILTER (WHERE status != 'rejected'), 0.0) as avg_health_score,
+        COALESCE(SUM(cpu_cores * memory_gb) FILTER (WHERE status != 'rejected'), 0.0) as total_compute_capacity
+    FROM nodes
+    WHERE deleted_at IS NULL
+"#;
+
+/// Task aggregates behind `GET /cluster/stats` and its history snapshots.
+const CLUSTER_TASK_STATS_SQL: &str = r#"
+    SELECT
+        COUNT(*) as total_tasks,
+        COUNT(*) FILTER (WHERE status = 'completed') as completed_tasks,
+        COUNT(*) FILTER (WHERE status = 'failed') as failed_tasks
+    FROM tasks
+    WHERE deleted_at IS NULL
+"#;
+
+/// `canary_runs` columns decoded by `map_canary_run_row`.
+const CANARY_RUN_COLUMNS: &str = "run_id, kind, node_id, task_id, session_id, status, stage, \
+     stage_latencies_ms, error, started_at, finished_at";
+
+const SCHEDULER_DECISION_COLUMNS: &str = "decision_id, kind, task_id, node_id, rule_versions, \
+     candidate_set_hash, inputs, chosen, attached, decided_at";
+
+/// `cluster_exports` columns decoded by `map_cluster_export_row`.
+const CLUSTER_EXPORT_COLUMNS: &str = "export_id, status, format, incremental, since, snapshot_at, \
+     policy, stable_identifiers, files, error, created_by, started_at, finished_at";
+
+/// How a canary run ended.
+enum CanaryRunOutcome {
+    Passed,
+    /// The stage it failed at and why.
+    Failed(crate::canary::CanaryStage, String),
+}
+
+/// Largest decoded standalone proof the verifier is run on.
+const MAX_PROOF_BYTES: usize = 75_000;
+
+const PROOF_VERIFICATION_FAILED: &str = "Proof verification failed: invalid proof or public inputs";
+
+/// `proof_batches` columns decoded by `map_proof_batch_row`.
+const PROOF_BATCH_COLUMNS: &str =
+    "batch_id, status, total, results, error, created_at, finished_at";
+
+/// A standalone proof and its verification outcome.
+struct VerifiedProof {
+    proof: zk_prover::ZKProof,
+    valid: bool,
+    verification_time_ms: u64,
+    error_message: Option<&'static str>,
+}
+
+/// A proof and its verification outcome, ready to store.
+struct StoredProof<'a> {
+    task_id: Option<Uuid>,
+    node_id: Option<&'a str>,
+    submitted_by: Uuid,
+    proof: &'a zk_prover::ZKProof,
+    verified: bool,
+    verification_time_ms: u64,
+    error_message: Option<&'a str>,
 }
 
 /// Application state with database connection pool
 pub struct AppState {
     /// PostgreSQL connection pool
     pub db: Option<PgPool>,
-    /// Cached authentication configuration (set once at startup)
-    auth_config: Option<crate::auth::AuthConfig>,
+    /// Cached authentication configuration (set once at startup, or on
+    /// first use from the environment)
+    auth_config: std::sync::OnceLock<crate::auth::AuthConfig>,
+    /// Region grid carbon intensities used for usage reporting
+    carbon_factors: crate::carbon::GridCarbonFactors,
+    /// Fair-share weights and per-requester caps for pending task selection
+    fair_share: crate::fair_queue::FairShareConfig,
+    /// Aging thresholds for tasks no node matches
+    starvation: crate::starvation::StarvationPolicy,
+    /// Retention windows for historical rows
+    retention: crate::retention::RetentionPolicy,
+    /// Failed-login thresholds and lock durations
+    login_lockout: crate::lockout::LockoutPolicy,
+    /// Flap counts that keep unstable nodes out of new assignments
+    flap_breaker: mesh_coordinator::FlapBreakerConfig,
+    /// Lifetimes of password reset and email verification tokens
+    account_tokens: crate::account_tokens::AccountTokenPolicy,
+    /// Receives rows before the retention job deletes them; none skips archiving
+    retention_archiver: Option<std::sync::Arc<dyn crate::retention::RetentionArchiver>>,
+    /// Content-addressed storage for uploaded WASM modules
+    artifact_store: std::sync::Arc<dyn crate::artifacts::ArtifactStore>,
+    /// Background queue for task/user notifications; none disables them
+    notifications: Option<crate::notifier::NotificationDispatcher>,
+    /// Queue to external time-series stores; none disables export
+    telemetry_export: Option<crate::telemetry_export::TelemetryExport>,
+    /// Signs heartbeat and gateway-session responses for node-side anti-replay
+    control_signer: std::sync::Arc<ambient_node::ControlSigner>,
+    /// ASN lookup for registering nodes; none records self-reported ASNs only
+    asn_db: Option<std::sync::Arc<crate::geoip::AsnDatabase>>,
+    /// Node IDs that just received a task assignment, for push subscribers
+    assignment_events: tokio::sync::broadcast::Sender<String>,
+    /// Replicas serving heavy reads; none reads everything from `db`
+    read_replicas: Option<std::sync::Arc<crate::db::ReadReplicas>>,
+    /// Enrollment keys new nodes must attest with; none admits them directly
+    attestation: crate::attestation::AttestationPolicy,
+    /// Staleness limits sent with every destination-policy bundle
+    offline_policy: ambient_node::OfflinePolicy,
+    /// Request buckets and throttle overrides for this app's routes
+    rate_limiter: crate::rate_limit::RateLimiter,
 }
 
 impl AppState {
@@ -55,6 +243,17 @@ impl AppState {
             .ok_or_else(|| ApiError::service_unavailable("Database not configured"))
     }
 
+    /// Pool for heavy reads that tolerate replica lag: a usable replica, or
+    /// the primary when there is none.
+    fn read_db(&self) -> ApiResult<&PgPool> {
+        let primary = self.require_db()?;
+        Ok(self
+            .read_replicas
+            .as_ref()
+            .and_then(|replicas| replicas.pool())
+            .unwrap_or(primary))
+    }
+
     async fn select_active_connect_node_for_task(
         &self,
         task_id: Uuid,
@@ -69,12 +268,15 @@ impl AppState {
               AND ta.disconnected_at IS NULL
               AND n.deleted_at IS NULL
               AND n.status = 'online'
-              AND (n.node_type = 'open_internet' OR n.node_type = 'any')
+              AND n.node_type = ANY($2)
             ORDER BY ta.assigned_at ASC
             LIMIT 1
             "#,
         )
         .bind(task_id)
+        .bind(ambient_node::NodeKind::serving_node_types(
+            ambient_node::NodeKind::OpenInternet,
+        ))
         .fetch_optional(db)
         .await?;
 
@@ -95,11 +297,77 @@ impl AppState {
     fn max_active_task_attachments_per_node() -> i64 {
         let configured = std::env::var("MAX_CONCURRENT_TASKS_PER_NODE")
             .ok()
-            .or_else(|| std::env::var("MAX_ACTIVE_TASK_ATTACHMENTS_PER_NODE").ok());
+            .or_else(|| std::env::var("MAX_ACTIVE_TASK_ATTACHMENTS_PER_NODE").ok())
+            .or_else(|| deployment_profile::profile_setting("MAX_CONCURRENT_TASKS_PER_NODE"));
 
         Self::parse_max_active_task_attachments_per_node(configured.as_deref())
     }
 
+    fn parse_clock_skew_tolerance_seconds(value: Option<&str>) -> i64 {
+        value
+            .and_then(|raw| raw.parse::<i64>().ok())
+            .filter(|parsed| *parsed > 0)
+            .unwrap_or(300)
+    }
+
+    /// Largest skew-corrected difference between a node-supplied timestamp and
+    /// server time that is accepted (`CLOCK_SKEW_TOLERANCE_SECS`).
+    pub fn clock_skew_tolerance_seconds() -> i64 {
+        Self::parse_clock_skew_tolerance_seconds(
+            std::env::var("CLOCK_SKEW_TOLERANCE_SECS").ok().as_deref(),
+        )
+    }
+
+    fn parse_task_log_poll_interval_ms(value: Option<&str>) -> u64 {
+        value
+            .and_then(|raw| raw.parse::<u64>().ok())
+            .filter(|parsed| *parsed > 0)
+            .unwrap_or(1000)
+    }
+
+    /// How often open task log streams check for new lines
+    /// (`TASK_LOG_POLL_INTERVAL_MS`).
+    pub fn task_log_poll_interval_ms() -> u64 {
+        Self::parse_task_log_poll_interval_ms(
+            std::env::var("TASK_LOG_POLL_INTERVAL_MS").ok().as_deref(),
+        )
+    }
+
+    fn parse_task_priority_aging_seconds(value: Option<&str>) -> f64 {
+        value
+            .and_then(|raw| raw.parse::<f64>().ok())
+            .filter(|parsed| parsed.is_finite() && *parsed > 0.0)
+            .unwrap_or(300.0)
+    }
+
+    /// Seconds a pending task must wait to gain one priority level.
+    fn task_priority_aging_seconds() -> f64 {
+        Self::parse_task_priority_aging_seconds(
+            deployment_profile::setting("TASK_PRIORITY_AGING_SECS").as_deref(),
+        )
+    }
+
+    fn parse_task_egress_allowlist(value: Option<&str>) -> Vec<wasm_engine::EgressRule> {
+        value
+            .unwrap_or_default()
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .filter_map(|entry| {
+                let rule = wasm_engine::EgressRule::parse(entry);
+                if rule.is_none() {
+                    tracing::warn!(entry, "Ignoring malformed TASK_EGRESS_ALLOWLIST entry");
+                }
+                rule
+            })
+            .collect()
+    }
+
+    /// Operator egress policy (`TASK_EGRESS_ALLOWLIST`); every rule a task
+    /// declares must be covered by one of these.  Empty denies all egress.
+    fn task_egress_allowlist() -> Vec<wasm_engine::EgressRule> {
+        Self::parse_task_egress_allowlist(std::env::var("TASK_EGRESS_ALLOWLIST").ok().as_deref())
+    }
+
     fn parse_connect_session_monitor_interval_seconds(value: Option<&str>) -> u64 {
         value
             .and_then(|raw| raw.parse::<u64>().ok())
@@ -108,32 +376,240 @@ impl AppState {
     }
 
     pub fn connect_session_monitor_interval_seconds() -> u64 {
-        let configured = std::env::var("CONNECT_SESSION_MONITOR_INTERVAL_SECONDS").ok();
+        let configured = deployment_profile::setting("CONNECT_SESSION_MONITOR_INTERVAL_SECONDS");
         Self::parse_connect_session_monitor_interval_seconds(configured.as_deref())
     }
 
+    fn parse_connect_session_max_extensions(value: Option<&str>) -> u32 {
+        value.and_then(|raw| raw.parse::<u32>().ok()).unwrap_or(3)
+    }
+
+    /// How many times one connect session may be extended
+    /// (`CONNECT_SESSION_MAX_EXTENSIONS`); `0` disables extension.
+    pub fn connect_session_max_extensions() -> u32 {
+        Self::parse_connect_session_max_extensions(
+            std::env::var("CONNECT_SESSION_MAX_EXTENSIONS")
+                .ok()
+                .as_deref(),
+        )
+    }
+
+    fn parse_relay_drain_grace_seconds(value: Option<&str>) -> u64 {
+        value
+            .and_then(|raw| raw.parse::<u64>().ok())
+            .unwrap_or(mesh_coordinator::DEFAULT_RELAY_DRAIN_GRACE_SECS)
+            .min(mesh_coordinator::MAX_RELAY_DRAIN_GRACE_SECS)
+    }
+
+    /// Grace window of a relay drain announcement that names none
+    /// (`RELAY_DRAIN_GRACE_SECS`).
+    pub fn relay_drain_grace_seconds() -> u64 {
+        Self::parse_relay_drain_grace_seconds(
+            std::env::var("RELAY_DRAIN_GRACE_SECS").ok().as_deref(),
+        )
+    }
+
+    fn parse_connect_session_max_lifetime_seconds(value: Option<&str>) -> i64 {
+        value
+            .and_then(|raw| raw.parse::<i64>().ok())
+            .filter(|parsed| *parsed > 0)
+            .unwrap_or(14_400)
+    }
+
+    /// Longest a connect session may run from its start, extensions included
+    /// (`CONNECT_SESSION_MAX_LIFETIME_SECS`).
+    pub fn connect_session_max_lifetime_seconds() -> i64 {
+        Self::parse_connect_session_max_lifetime_seconds(
+            std::env::var("CONNECT_SESSION_MAX_LIFETIME_SECS")
+                .ok()
+                .as_deref(),
+        )
+    }
+
+    fn parse_connect_session_data_cap_mb(value: Option<&str>) -> Option<u64> {
+        value
+            .and_then(|raw| raw.parse::<u64>().ok())
+            .filter(|parsed| *parsed > 0)
+    }
+
+    /// Server-wide data cap per connect session in megabytes
+    /// (`CONNECT_SESSION_DATA_CAP_MB`).  A task's own `data_cap_mb` can only
+    /// lower it; unset or `0` leaves sessions uncapped unless their task sets
+    /// one.
+    pub fn connect_session_data_cap_mb() -> Option<u64> {
+        Self::parse_connect_session_data_cap_mb(
+            deployment_profile::setting("CONNECT_SESSION_DATA_CAP_MB").as_deref(),
+        )
+    }
+
     /// Create new application state with database pool
     pub fn new(db: Option<PgPool>) -> Self {
         Self {
             db,
-            auth_config: None,
+            auth_config: std::sync::OnceLock::new(),
+            carbon_factors: crate::carbon::GridCarbonFactors::from_env(),
+            fair_share: crate::fair_queue::FairShareConfig::from_env(),
+            starvation: crate::starvation::StarvationPolicy::from_env(),
+            retention: crate::retention::RetentionPolicy::from_env(),
+            login_lockout: crate::lockout::LockoutPolicy::from_env(),
+            flap_breaker: crate::flap_breaker::config_from_env(),
+            account_tokens: crate::account_tokens::AccountTokenPolicy::from_env(),
+            retention_archiver: crate::retention::retention_archiver_from_env(),
+            artifact_store: std::sync::Arc::new(crate::artifacts::filesystem_store_from_env()),
+            notifications: None,
+            telemetry_export: None,
+            control_signer: std::sync::Arc::new(crate::auth::control_signer_from_env()),
+            asn_db: crate::geoip::AsnDatabase::from_env().map(std::sync::Arc::new),
+            assignment_events: tokio::sync::broadcast::channel(1024).0,
+            read_replicas: None,
+            attestation: crate::attestation::AttestationPolicy::from_env(),
+            offline_policy: crate::destination_policies::offline_policy_from_env(),
+            rate_limiter: crate::rate_limit::RateLimiter::new(),
+        }
+    }
+
+    /// Replace the request rate limiter.
+    pub fn with_rate_limiter(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.rate_limiter = limiter;
+        self
+    }
+
+    /// Limiter applied by [`crate::rate_limit::rate_limit_middleware`].
+    pub fn rate_limiter(&self) -> &crate::rate_limit::RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// Receive the ID of each node given a new task assignment by this
+    /// process.  Other replicas' assignments are not seen, so subscribers
+    /// should also re-read assignments periodically.
+    pub fn subscribe_assignments(&self) -> tokio::sync::broadcast::Receiver<String> {
+        self.assignment_events.subscribe()
+    }
+
+    /// Replace the control key that signs node-facing responses.
+    pub fn with_control_signer(mut self, signer: ambient_node::ControlSigner) -> Self {
+        self.control_signer = std::sync::Arc::new(signer);
+        self
+    }
+
+    /// Base64 public half of the control key.
+    pub fn control_public_key(&self) -> String {
+        self.control_signer.public_key_b64()
+    }
+
+    /// Sign a response addressed to `node_id` (see
+    /// `ambient_node::ControlVerifier`).
+    pub fn sign_control_response(
+        &self,
+        node_id: &str,
+        body: serde_json::Value,
+    ) -> serde_json::Value {
+        self.control_signer
+            .sign(node_id, body, chrono::Utc::now().timestamp_millis())
+    }
+
+    /// Route task and user notifications through `dispatcher`.
+    pub fn with_notifications(
+        mut self,
+        dispatcher: crate::notifier::NotificationDispatcher,
+    ) -> Self {
+        self.notifications = Some(dispatcher);
+        self
+    }
+
+    /// Serve list, stats and telemetry history reads from `replicas`.
+    pub fn with_read_replicas(mut self, replicas: std::sync::Arc<crate::db::ReadReplicas>) -> Self {
+        self.read_replicas = Some(replicas);
+        self
+    }
+
+    /// Forward heartbeat samples and task outcomes through `export`.
+    pub fn with_telemetry_export(
+        mut self,
+        export: crate::telemetry_export::TelemetryExport,
+    ) -> Self {
+        self.telemetry_export = Some(export);
+        self
+    }
+
+    /// Queue a record for the telemetry exporters, if any.
+    fn export_telemetry(&self, record: crate::telemetry_export::ExportRecord) {
+        if let Some(export) = &self.telemetry_export {
+            export.record(record);
         }
     }
 
+    /// Replace the WASM module artifact backend.
+    pub fn with_artifact_store(
+        mut self,
+        store: std::sync::Arc<dyn crate::artifacts::ArtifactStore>,
+    ) -> Self {
+        self.artifact_store = store;
+        self
+    }
+
+    /// Archive rows through `archiver` before the retention job deletes them.
+    pub fn with_retention_archiver(
+        mut self,
+        archiver: std::sync::Arc<dyn crate::retention::RetentionArchiver>,
+    ) -> Self {
+        self.retention_archiver = Some(archiver);
+        self
+    }
+
+    /// Replace the failed-login lockout policy.
+    pub fn with_login_lockout(mut self, policy: crate::lockout::LockoutPolicy) -> Self {
+        self.login_lockout = policy;
+        self
+    }
+
+    /// Replace the node attestation enrollment keys and challenge lifetime.
+    pub fn with_attestation_policy(
+        mut self,
+        policy: crate::attestation::AttestationPolicy,
+    ) -> Self {
+        self.attestation = policy;
+        self
+    }
+
+    /// Replace the staleness limits nodes apply to cached policy bundles.
+    pub fn with_offline_policy(mut self, policy: ambient_node::OfflinePolicy) -> Self {
+        self.offline_policy = policy;
+        self
+    }
+
+    /// Replace the node flap circuit breaker settings.
+    pub fn with_flap_breaker(mut self, config: mesh_coordinator::FlapBreakerConfig) -> Self {
+        self.flap_breaker = config;
+        self
+    }
+
+    /// Replace the password reset and email verification token lifetimes.
+    pub fn with_account_token_policy(
+        mut self,
+        policy: crate::account_tokens::AccountTokenPolicy,
+    ) -> Self {
+        self.account_tokens = policy;
+        self
+    }
+
     /// Store a pre-built [`AuthConfig`] so the server pays the env-var read
     /// cost once at startup rather than on every authenticated request.
     pub fn with_auth_config(mut self, config: crate::auth::AuthConfig) -> Self {
-        self.auth_config = Some(config);
+        self.auth_config = std::sync::OnceLock::from(config);
         self
     }
 
     /// Return the cached [`AuthConfig`], falling back to reading from the
-    /// environment when no config has been pre-loaded (e.g. in tests).
+    /// environment when no config has been pre-loaded (e.g. in tests).  The
+    /// fallback is cached too: its signing keys are random, so every request
+    /// has to share them.
     pub fn auth_config(&self) -> crate::error::ApiResult<crate::auth::AuthConfig> {
-        match &self.auth_config {
-            Some(cfg) => Ok(cfg.clone()),
-            None => crate::auth::AuthConfig::from_env(),
+        if let Some(config) = self.auth_config.get() {
+            return Ok(config.clone());
         }
+        let config = crate::auth::AuthConfig::from_env()?;
+        Ok(self.auth_config.get_or_init(|| config).clone())
     }
 
     /// Register a new node in the database with owner tracking
@@ -141,9 +617,48 @@ impl AppState {
         &self,
         registration: NodeRegistration,
         owner_id: Uuid,
+    ) -> ApiResult<NodeInfo> {
+        self.register_node_in_org(registration, owner_id, None, None)
+            .await
+    }
+
+    /// Register a node owned by `org_id`, which the registering user must be a
+    /// member of.  With `None` the node is personal to `owner_id`.
+    ///
+    /// `observed_ip` is the address the registration came from; with a GeoIP
+    /// database configured its ASN takes precedence over a self-reported one.
+    #[tracing::instrument(skip_all, fields(node_id = %registration.node_id))]
+    pub async fn register_node_in_org(
+        &self,
+        registration: NodeRegistration,
+        owner_id: Uuid,
+        org_id: Option<Uuid>,
+        observed_ip: Option<std::net::IpAddr>,
     ) -> ApiResult<NodeInfo> {
         let db = self.require_db()?;
+        if let Some(org_id) = org_id {
+            self.require_org_role(org_id, owner_id, OrgRole::Member)
+                .await?;
+        }
         let now = chrono::Utc::now();
+        let slots = registration.slots.clone().unwrap_or_default();
+        let (node_type, legacy_node_type) = normalize_node_type(&registration.node_type);
+        if let Some(ref legacy) = legacy_node_type {
+            tracing::warn!(
+                node_id = %registration.node_id,
+                legacy_node_type = %legacy,
+                node_type = %node_type,
+                "Node registered with a deprecated node_type alias"
+            );
+        }
+        let network =
+            crate::geoip::resolve_node_asn(self.asn_db.as_deref(), observed_ip, registration.asn);
+        // Nodes that must attest stay out of scheduling until they do.
+        let status = if self.attestation.required() {
+            crate::attestation::PENDING_ATTESTATION_STATUS
+        } else {
+            "online"
+        };
 
         // Insert node into database with owner_id
         sqlx::query(
@@ -151,97 +666,252 @@ impl AppState {
             INSERT INTO nodes (
                 node_id, region, node_type, bandwidth_mbps, cpu_cores, 
                 memory_gb, gpu_available, health_score, status, 
-                registered_at, last_seen, owner_id, last_heartbeat, observability_port
+                registered_at, last_seen, owner_id, last_heartbeat, observability_port,
+                benchmark_ops_per_wh, secrets_public_key, org_id,
+                connect_slots, wasm_slots, gpu_slots, signing_public_key, labels,
+                legacy_node_type, asn, asn_source, allowed_task_types, blocked_task_types
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+                $18, $19, $20, $21, $22, $23, $24, $25, $26, $27
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
             "#,
         )
         .bind(&registration.node_id)
         .bind(&registration.region)
-        .bind(&registration.node_type)
+        .bind(&node_type)
         .bind(registration.capabilities.bandwidth_mbps)
         .bind(registration.capabilities.cpu_cores as i32)
         .bind(registration.capabilities.memory_gb)
         .bind(registration.capabilities.gpu_available)
         .bind(100.0_f64)
-        .bind("online")
+        .bind(status)
         .bind(now)
         .bind(now)
         .bind(owner_id)
         .bind(now)
         .bind(registration.observability_port.map(|p| p as i32))
+        .bind(registration.benchmark_ops_per_wh)
+        .bind(&registration.secrets_public_key)
+        .bind(org_id)
+        .bind(slots.connect.map(|v| v as i32))
+        .bind(slots.wasm.map(|v| v as i32))
+        .bind(slots.gpu.map(|v| v as i32))
+        .bind(&registration.signing_public_key)
+        .bind(serde_json::json!(registration.labels))
+        .bind(&legacy_node_type)
+        .bind(network.map(|(asn, _)| i64::from(asn)))
+        .bind(network.map(|(_, source)| source.as_str()))
+        .bind(&registration.allowed_task_types)
+        .bind(&registration.blocked_task_types)
         .execute(db)
         .await?;
 
-        // Attempt to attach the newly registered node to pending tasks that still
-        // need additional workers.
-        self.assign_pending_tasks_for_node(&registration.node_id)
-            .await?;
+        let attestation_challenge = if self.attestation.required() {
+            Some(
+                self.issue_attestation_challenge(&registration.node_id)
+                    .await?,
+            )
+        } else {
+            // Attempt to attach the newly registered node to pending tasks that still
+            // need additional workers.
+            self.assign_pending_tasks_for_node(&registration.node_id)
+                .await?;
+            None
+        };
 
         // Return the created node
         let node_info = NodeInfo {
             node_id: registration.node_id,
             region: registration.region,
-            node_type: registration.node_type,
+            node_type,
             capabilities: registration.capabilities,
             health_score: 100.0,
-            status: "online".to_string(),
+            status: status.to_string(),
             owner_id: owner_id.to_string(),
             registered_at: now.to_rfc3339(),
             last_seen: now.to_rfc3339(),
             observability_port: registration.observability_port,
+            slots,
+            labels: registration.labels,
+            asn: network.map(|(asn, _)| asn),
+            asn_source: network.map(|(_, source)| source),
+            warnings: node_kind_warnings(legacy_node_type.as_deref()),
+            circuit_breaker: Default::default(),
+            attested_at: None,
+            allowed_task_types: registration.allowed_task_types,
+            blocked_task_types: registration.blocked_task_types,
+            attestation_challenge,
         };
 
         Ok(node_info)
     }
 
+    /// Store a fresh nonce for `node_id`, replacing any earlier one.
+    async fn issue_attestation_challenge(
+        &self,
+        node_id: &str,
+    ) -> ApiResult<crate::attestation::AttestationChallenge> {
+        let db = self.require_db()?;
+        let nonce = crate::attestation::generate_nonce();
+        let expires_at = chrono::Utc::now()
+            + chrono::Duration::seconds(self.attestation.challenge_ttl_secs as i64);
+
+        sqlx::query(
+            r#"
+            INSERT INTO node_attestation_challenges (node_id, nonce, expires_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (node_id) DO UPDATE
+            SET nonce = EXCLUDED.nonce,
+                expires_at = EXCLUDED.expires_at,
+                created_at = NOW()
+            "#,
+        )
+        .bind(node_id)
+        .bind(&nonce)
+        .bind(expires_at)
+        .execute(db)
+        .await?;
+
+        Ok(crate::attestation::AttestationChallenge {
+            nonce,
+            expires_at: expires_at.to_rfc3339(),
+        })
+    }
+
+    /// Issue a new attestation nonce for a node still pending attestation.
+    ///
+    /// # Returns
+    /// `None` when the node does not exist or `owner_id` may not manage it.
+    pub async fn renew_attestation_challenge(
+        &self,
+        node_id: &str,
+        owner_id: Uuid,
+    ) -> ApiResult<Option<crate::attestation::AttestationChallenge>> {
+        let db = self.require_db()?;
+        if !self.check_node_ownership(node_id, owner_id).await? {
+            return Ok(None);
+        }
+        let status: String = sqlx::query_scalar("SELECT status FROM nodes WHERE node_id = $1")
+            .bind(node_id)
+            .fetch_one(db)
+            .await?;
+        if status != crate::attestation::PENDING_ATTESTATION_STATUS {
+            return Err(ApiError::conflict(format!(
+                "Node {} is not pending attestation",
+                node_id
+            )));
+        }
+
+        Ok(Some(self.issue_attestation_challenge(node_id).await?))
+    }
+
+    /// Check a node's answer to its attestation challenge and, when it is
+    /// valid, bring the node online and offer it pending tasks.
+    ///
+    /// The nonce is consumed by the first valid answer.
+    ///
+    /// # Returns
+    /// `None` when the node does not exist or `owner_id` may not manage it.
+    #[tracing::instrument(skip_all, fields(%node_id))]
+    pub async fn attest_node(
+        &self,
+        node_id: &str,
+        owner_id: Uuid,
+        attestation: crate::attestation::NodeAttestationRequest,
+    ) -> ApiResult<Option<NodeInfo>> {
+        let db = self.require_db()?;
+        if !self.check_node_ownership(node_id, owner_id).await? {
+            return Ok(None);
+        }
+        if !self.attestation.trusts(&attestation.enrollment_public_key) {
+            return Err(ApiError::forbidden(
+                "enrollment_public_key is not a trusted enrollment key",
+            ));
+        }
+        let quote = attestation.quote.clone();
+        let response = attestation.into_response();
+        if !response.verify(node_id) {
+            return Err(ApiError::bad_request("attestation signature is invalid"));
+        }
+
+        let mut tx = db.begin().await?;
+        let consumed = sqlx::query(
+            r#"
+            DELETE FROM node_attestation_challenges
+            WHERE node_id = $1
+              AND nonce = $2
+              AND expires_at > NOW()
+            "#,
+        )
+        .bind(node_id)
+        .bind(&response.nonce)
+        .execute(&mut *tx)
+        .await?;
+        if consumed.rows_affected() == 0 {
+            return Err(ApiError::bad_request(
+                "nonce does not match an unexpired attestation challenge for this node",
+            ));
+        }
+
+        let attested = sqlx::query(
+            r#"
+            UPDATE nodes
+            SET status = 'online',
+                attested_at = NOW(),
+                enrollment_public_key = $2,
+                attestation_quote = $3,
+                updated_at = NOW()
+            WHERE node_id = $1
+              AND status = 'pending_attestation'
+            "#,
+        )
+        .bind(node_id)
+        .bind(&response.enrollment_public_key)
+        .bind(&quote)
+        .execute(&mut *tx)
+        .await?;
+        if attested.rows_affected() == 0 {
+            return Err(ApiError::conflict(format!(
+                "Node {} is not pending attestation",
+                node_id
+            )));
+        }
+        tx.commit().await?;
+
+        tracing::info!(
+            node_id,
+            enrollment_public_key = %response.enrollment_public_key,
+            quote = quote.is_some(),
+            "Node attested"
+        );
+        self.assign_pending_tasks_for_node(node_id).await?;
+
+        Ok(self.get_node(node_id).await)
+    }
+
     /// List all nodes from the database (excludes soft-deleted nodes)
     pub async fn list_nodes(&self) -> Vec<NodeInfo> {
-        let Some(db) = &self.db else {
+        let Ok(db) = self.read_db() else {
             return vec![];
         };
 
-        let result = sqlx::query(
+        let result = sqlx::query(&format!(
             r#"
-            SELECT 
-                node_id, region, node_type, owner_id, bandwidth_mbps, cpu_cores,
-                memory_gb, gpu_available, health_score, status,
-                registered_at, last_seen, observability_port
+            SELECT {NODE_INFO_COLUMNS}
             FROM nodes
             WHERE deleted_at IS NULL
               AND status != 'rejected'
             ORDER BY registered_at DESC
-            "#,
-        )
+            "#
+        ))
         .fetch_all(db)
         .await;
 
         match result {
             Ok(rows) => rows
-                .into_iter()
-                .map(|row| NodeInfo {
-                    node_id: row.get("node_id"),
-                    region: row.get("region"),
-                    node_type: row.get("node_type"),
-                    owner_id: row.get::<Uuid, _>("owner_id").to_string(),
-                    capabilities: NodeCapabilities {
-                        bandwidth_mbps: row.get("bandwidth_mbps"),
-                        cpu_cores: row.get::<i32, _>("cpu_cores") as u32,
-                        memory_gb: row.get("memory_gb"),
-                        gpu_available: row.get("gpu_available"),
-                    },
-                    health_score: row.get("health_score"),
-                    status: row.get("status"),
-                    registered_at: row
-                        .get::<chrono::DateTime<chrono::Utc>, _>("registered_at")
-                        .to_rfc3339(),
-                    last_seen: row
-                        .get::<chrono::DateTime<chrono::Utc>, _>("last_seen")
-                        .to_rfc3339(),
-                    observability_port: row
-                        .get::<Option<i32>, _>("observability_port")
-                        .map(|p| p as u16),
-                })
+                .iter()
+                .map(|row| self.node_info_from_row(row))
                 .collect(),
             Err(e) => {
                 tracing::error!("Failed to list nodes: {:?}", e);
@@ -256,44 +926,19 @@ impl AppState {
             return None;
         };
 
-        let result = sqlx::query(
+        let result = sqlx::query(&format!(
             r#"
-            SELECT 
-                node_id, region, node_type, owner_id, bandwidth_mbps, cpu_cores,
-                memory_gb, gpu_available, health_score, status,
-                registered_at, last_seen, observability_port
+            SELECT {NODE_INFO_COLUMNS}
             FROM nodes
             WHERE node_id = $1 AND deleted_at IS NULL
-            "#,
-        )
+            "#
+        ))
         .bind(node_id)
         .fetch_optional(db)
         .await;
 
         match result {
-            Ok(Some(row)) => Some(NodeInfo {
-                node_id: row.get("node_id"),
-                region: row.get("region"),
-                node_type: row.get("node_type"),
-                owner_id: row.get::<Uuid, _>("owner_id").to_string(),
-                capabilities: NodeCapabilities {
-                    bandwidth_mbps: row.get("bandwidth_mbps"),
-                    cpu_cores: row.get::<i32, _>("cpu_cores") as u32,
-                    memory_gb: row.get("memory_gb"),
-                    gpu_available: row.get("gpu_available"),
-                },
-                health_score: row.get("health_score"),
-                status: row.get("status"),
-                registered_at: row
-                    .get::<chrono::DateTime<chrono::Utc>, _>("registered_at")
-                    .to_rfc3339(),
-                last_seen: row
-                    .get::<chrono::DateTime<chrono::Utc>, _>("last_seen")
-                    .to_rfc3339(),
-                observability_port: row
-                    .get::<Option<i32>, _>("observability_port")
-                    .map(|p| p as u16),
-            }),
+            Ok(Some(row)) => Some(self.node_info_from_row(&row)),
             Ok(None) => None,
             Err(e) => {
                 tracing::error!("Failed to get node {}: {:?}", node_id, e);
@@ -302,8 +947,209 @@ impl AppState {
         }
     }
 
+    /// Rank the active nodes that can take `entry`'s task type; see
+    /// [`crate::node_rankings`].
+    pub async fn rank_nodes(
+        &self,
+        entry: &TaskTypeRegistryEntry,
+        limit: usize,
+    ) -> ApiResult<crate::node_rankings::NodeRankingsResponse> {
+        let db = self.read_db()?;
+        let rows = sqlx::query(crate::node_rankings::CANDIDATES_SQL)
+            .bind(entry.task_type)
+            .bind(entry.serving_node_types())
+            .fetch_all(db)
+            .await?;
+        let candidates = rows
+            .iter()
+            .map(crate::node_rankings::RankingCandidate::from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(crate::node_rankings::NodeRankingsResponse {
+            task_type: entry.task_type.to_string(),
+            rankings: crate::node_rankings::rank(candidates, limit),
+        })
+    }
+
+    /// Get several nodes in one query (excludes soft-deleted nodes); unknown
+    /// IDs are left out.
+    pub async fn get_nodes(&self, node_ids: &[String]) -> ApiResult<Vec<NodeInfo>> {
+        let Some(db) = &self.db else {
+            return Ok(vec![]);
+        };
+
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {NODE_INFO_COLUMNS}
+            FROM nodes
+            WHERE node_id = ANY($1) AND deleted_at IS NULL
+            "#
+        ))
+        .bind(node_ids)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| self.node_info_from_row(row))
+            .collect())
+    }
+
+    /// Decode a row selecting [`NODE_INFO_COLUMNS`].
+    fn node_info_from_row(&self, row: &sqlx::postgres::PgRow) -> NodeInfo {
+        NodeInfo {
+            node_id: row.get("node_id"),
+            region: row.get("region"),
+            node_type: row.get("node_type"),
+            owner_id: row.get::<Uuid, _>("owner_id").to_string(),
+            capabilities: NodeCapabilities {
+                bandwidth_mbps: row.get("bandwidth_mbps"),
+                cpu_cores: row.get::<i32, _>("cpu_cores") as u32,
+                memory_gb: row.get("memory_gb"),
+                gpu_available: row.get("gpu_available"),
+            },
+            health_score: row.get("health_score"),
+            status: row.get("status"),
+            registered_at: row
+                .get::<chrono::DateTime<chrono::Utc>, _>("registered_at")
+                .to_rfc3339(),
+            last_seen: row
+                .get::<chrono::DateTime<chrono::Utc>, _>("last_seen")
+                .to_rfc3339(),
+            observability_port: row
+                .get::<Option<i32>, _>("observability_port")
+                .map(|p| p as u16),
+            slots: node_slots_from_row(row),
+            labels: parse_node_labels(row.get("labels")),
+            asn: node_asn_from_row(row),
+            asn_source: row
+                .get::<Option<String>, _>("asn_source")
+                .and_then(|source| crate::geoip::AsnSource::parse(&source)),
+            warnings: node_kind_warnings(row.get("legacy_node_type")),
+            circuit_breaker: node_circuit_breaker_from_row(row, &self.flap_breaker),
+            attested_at: row
+                .get::<Option<chrono::DateTime<chrono::Utc>>, _>("attested_at")
+                .map(|at| at.to_rfc3339()),
+            allowed_task_types: row.get("allowed_task_types"),
+            blocked_task_types: row.get("blocked_task_types"),
+            attestation_challenge: None,
+        }
+    }
+
+    /// Validate, store, and record an uploaded WASM module.
+    ///
+    /// Uploads are idempotent: re-uploading an existing binary returns the
+    /// original metadata.
+    pub async fn store_wasm_module(
+        &self,
+        bytes: &[u8],
+        uploaded_by: Uuid,
+    ) -> ApiResult<WasmModuleInfo> {
+        let db = self.require_db()?;
+        crate::artifacts::validate_wasm_module(bytes, crate::artifacts::max_module_bytes())?;
+
+        let module_hash = crate::artifacts::module_hash(bytes);
+        self.artifact_store
+            .put(&module_hash, bytes)
+            .await
+            .map_err(|err| {
+                tracing::error!(module_hash, "Failed to store WASM module: {err:#}");
+                ApiError::internal_error("Failed to store module")
+            })?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO wasm_modules (module_hash, size_bytes, storage_backend, uploaded_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (module_hash) DO UPDATE SET module_hash = EXCLUDED.module_hash
+            RETURNING module_hash, size_bytes, storage_backend, created_at
+            "#,
+        )
+        .bind(&module_hash)
+        .bind(bytes.len() as i64)
+        .bind(self.artifact_store.backend_name())
+        .bind(uploaded_by)
+        .fetch_one(db)
+        .await?;
+
+        Ok(map_wasm_module_row(&row))
+    }
+
+    /// Metadata for an uploaded module, if it exists.
+    pub async fn get_wasm_module_info(
+        &self,
+        module_hash: &str,
+    ) -> ApiResult<Option<WasmModuleInfo>> {
+        let db = self.require_db()?;
+        let row = sqlx::query(
+            r#"
+            SELECT module_hash, size_bytes, storage_backend, created_at
+            FROM wasm_modules
+            WHERE module_hash = $1
+            "#,
+        )
+        .bind(module_hash)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(row.as_ref().map(map_wasm_module_row))
+    }
+
+    /// Module binary from the artifact store, re-hashed to guard against
+    /// corrupted or tampered storage.
+    pub async fn get_wasm_module_bytes(&self, module_hash: &str) -> ApiResult<Option<Vec<u8>>> {
+        if self.get_wasm_module_info(module_hash).await?.is_none() {
+            return Ok(None);
+        }
+
+        let bytes = self.artifact_store.get(module_hash).await.map_err(|err| {
+            tracing::error!(module_hash, "Failed to read WASM module: {err:#}");
+            ApiError::internal_error("Failed to read module")
+        })?;
+
+        match bytes {
+            Some(bytes) if crate::artifacts::module_hash(&bytes) == module_hash => Ok(Some(bytes)),
+            Some(_) => {
+                tracing::error!(module_hash, "Stored WASM module failed hash verification");
+                Err(ApiError::internal_error(
+                    "Stored module failed integrity check",
+                ))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Submit a task to the database
     pub async fn submit_task(&self, task: TaskSubmission, creator_id: Uuid) -> ApiResult<TaskInfo> {
+        self.submit_task_in_org(task, creator_id, None).await
+    }
+
+    /// Submit a task owned by `org_id`, which the creator must be a member of.
+    /// With `None` the task is personal to the creator.
+    #[tracing::instrument(skip_all, fields(task_type = %task.task_type))]
+    pub async fn submit_task_in_org(
+        &self,
+        task: TaskSubmission,
+        creator_id: Uuid,
+        org_id: Option<Uuid>,
+    ) -> ApiResult<TaskInfo> {
+        if let Some(org_id) = org_id {
+            self.require_org_role(org_id, creator_id, OrgRole::Member)
+                .await?;
+        }
+        self.insert_submitted_task(task, creator_id, org_id, None)
+            .await
+    }
+
+    /// Store a submitted task and attach the nodes available for it.  A task
+    /// with `pinned_node_id` is only ever attached to that node.
+    async fn insert_submitted_task(
+        &self,
+        task: TaskSubmission,
+        creator_id: Uuid,
+        org_id: Option<Uuid>,
+        pinned_node_id: Option<&str>,
+    ) -> ApiResult<TaskInfo> {
         let db = self.require_db()?;
         let task_id = Uuid::new_v4();
         let now = chrono::Utc::now();
@@ -312,14 +1158,52 @@ impl AppState {
         let task_registry_entry = task_type_registry_entry(&task.task_type)
             .ok_or_else(|| crate::error::ApiError::bad_request("Unsupported task_type"))?;
 
+        let requested_egress: Vec<wasm_engine::EgressRule> = task
+            .requirements
+            .egress
+            .iter()
+            .map(TaskEgressRule::to_sandbox_rule)
+            .collect();
+        if let Some(rule) =
+            wasm_engine::uncovered_egress(&requested_egress, &Self::task_egress_allowlist())
+        {
+            return Err(ApiError::bad_request(format!(
+                "egress to {} on ports {:?} is not permitted by operator policy",
+                rule.host, rule.ports
+            )));
+        }
+
+        if let Some(module_hash) = task
+            .wasm_module
+            .as_deref()
+            .map(crate::artifacts::parse_module_reference)
+            .transpose()?
+            .flatten()
+        {
+            if self.get_wasm_module_info(module_hash).await?.is_none() {
+                return Err(ApiError::bad_request(format!(
+                    "wasm_module references unknown module {}",
+                    module_hash
+                )));
+            }
+        }
+
+        let traceparent = crate::otel::current_traceparent().map(|trace| trace.to_string());
+
         // Insert task into database
         sqlx::query(
             r#"
             INSERT INTO tasks (
                 task_id, task_type, status, wasm_module, inputs,
-                min_nodes, max_execution_time_sec, require_gpu, require_proof, creator_id
+                min_nodes, max_execution_time_sec, require_gpu, require_proof, creator_id,
+                scheduling_mode, priority, max_retries, retry_backoff_sec, egress, org_id,
+                slot_class, checkpointable, node_selector, traceparent, diversity,
+                preferred_regions, excluded_regions, region_strictness, pinned_node_id
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
+                $19, $20, $21, $22, $23, $24, $25
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
             "#,
         )
         .bind(task_id)
@@ -332,6 +1216,21 @@ impl AppState {
         .bind(task.requirements.require_gpu)
         .bind(task.requirements.require_proof)
         .bind(creator_id)
+        .bind(task.requirements.scheduling_mode.as_str())
+        .bind(task.priority as i16)
+        .bind(task.requirements.max_retries as i32)
+        .bind(task.requirements.retry_backoff_sec as i64)
+        .bind(serde_json::json!(task.requirements.egress))
+        .bind(org_id)
+        .bind(SlotClass::for_task(&task.task_type, task.requirements.require_gpu).as_str())
+        .bind(task.requirements.checkpointable)
+        .bind(serde_json::json!(task.requirements.node_selector))
+        .bind(&traceparent)
+        .bind(task.requirements.diversity.as_str())
+        .bind(&task.requirements.preferred_regions)
+        .bind(&task.requirements.excluded_regions)
+        .bind(task.requirements.region_strictness.as_str())
+        .bind(pinned_node_id)
         .execute(db)
         .await?;
 
@@ -363,34 +1262,103 @@ impl AppState {
             updated_at: now.to_rfc3339(),
             result: None,
             proof_id: None,
+            priority: task.priority,
+            retry_count: 0,
+            last_error: None,
+            queue_position: self.get_task_queue_position(task_id).await?,
+            egress: task.requirements.egress,
+            constraints_relaxed: false,
+            starving_since: None,
+            scheduling_diagnostics: None,
+            checkpointable: task.requirements.checkpointable,
+            checkpoint_seq: None,
+            node_selector: task.requirements.node_selector,
+            diversity: task.requirements.diversity,
+            preferred_regions: task.requirements.preferred_regions,
+            excluded_regions: task.requirements.excluded_regions,
+            region_strictness: task.requirements.region_strictness,
+            traceparent,
         };
 
         Ok(task_info)
     }
 
+    #[tracing::instrument(skip_all, fields(%task_id))]
     pub async fn complete_task_if_running(
         &self,
         task_id: Uuid,
         task_type: String,
         task_inputs: serde_json::Value,
     ) -> ApiResult<()> {
-        let Ok(db) = self.require_db() else {
+        if self.require_db().is_err() {
             return Ok(());
-        };
+        }
+
+        let freed_nodes = retry_task_transition(task_id, "synthetic_completion", || {
+            self.try_complete_task_if_running(task_id, &task_type, &task_inputs)
+        })
+        .await?;
+
+        for node_id in freed_nodes {
+            self.assign_pending_tasks_for_node(&node_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// One attempt at synthesizing a running task's result.  Returns the
+    /// nodes freed by the completion.
+    async fn try_complete_task_if_running(
+        &self,
+        task_id: Uuid,
+        task_type: &str,
+        task_inputs: &serde_json::Value,
+    ) -> ApiResult<TaskTransition<Vec<String>>> {
+        let db = self.require_db()?;
+        let task_row =
+            sqlx::query("SELECT status, version, max_retries FROM tasks WHERE task_id = $1")
+                .bind(task_id)
+                .fetch_optional(db)
+                .await?;
 
-        let task_status = match self.get_task_status(task_id).await? {
-            Some(s) => s,
-            None => return Ok(()),
+        let Some(task_row) = task_row else {
+            return Ok(TaskTransition::Applied(Vec::new()));
         };
 
-        if task_status != "running" {
-            return Ok(());
+        // Tasks with a retry policy are re-queued by `sweep_task_retries`
+        // when their nodes time out; never synthesize a result for them.
+        if task_row.get::<String, _>("status") != "running"
+            || task_row.get::<i32, _>("max_retries") > 0
+        {
+            return Ok(TaskTransition::Applied(Vec::new()));
         }
+        let version: i64 = task_row.get("version");
 
-        let result = analyze_task_payload(&task_type, &task_inputs);
+        let result = analyze_task_payload(task_type, task_inputs);
 
         let mut tx = db.begin().await?;
 
+        let completed = sqlx::query(
+            r#"
+            UPDATE tasks
+            SET status = 'completed', result = $1, updated_at = NOW(),
+                completed_at = NOW(), version = version + 1
+            WHERE task_id = $2
+              AND version = $3
+            "#,
+        )
+        .bind(&result)
+        .bind(task_id)
+        .bind(version)
+        .execute(&mut *tx)
+        .await?;
+
+        if completed.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(TaskTransition::Conflict);
+        }
+        self.enqueue_task_completed(&mut tx, task_id).await?;
+
         let assigned_nodes_for_completed_task = sqlx::query_scalar::<_, String>(
             r#"
                 SELECT node_id
@@ -403,20 +1371,7 @@ impl AppState {
         .fetch_all(&mut *tx)
         .await?;
 
-        sqlx::query(
-            r#"
-            UPDATE tasks
-            SET status = 'completed', result = $1, updated_at = NOW()
-            WHERE task_id = $2
-              AND status = 'running'
-            "#,
-        )
-        .bind(&result)
-        .bind(task_id)
-        .execute(&mut *tx)
-        .await?;
-
-        let should_disconnect_assignments = should_disconnect_assignments_on_completion(&task_type);
+        let should_disconnect_assignments = should_disconnect_assignments_on_completion(task_type);
         if should_disconnect_assignments {
             // Mark assignments as completed before disconnecting so the execution
             // lifecycle is fully recorded (assigned → in_progress → completed).
@@ -438,13 +1393,11 @@ impl AppState {
 
         tx.commit().await?;
 
-        if should_disconnect_assignments {
-            for node_id in assigned_nodes_for_completed_task {
-                self.assign_pending_tasks_for_node(&node_id).await?;
-            }
-        }
-
-        Ok(())
+        Ok(TaskTransition::Applied(if should_disconnect_assignments {
+            assigned_nodes_for_completed_task
+        } else {
+            Vec::new()
+        }))
     }
 
     async fn get_assigned_nodes(&self, task_id: Uuid) -> ApiResult<Vec<String>> {
@@ -465,6 +1418,26 @@ impl AppState {
         Ok(assigned_nodes)
     }
 
+    /// 1-based position of a pending task in the aged priority queue.
+    async fn get_task_queue_position(&self, task_id: Uuid) -> ApiResult<Option<i64>> {
+        let db = self.require_db()?;
+        let position = sqlx::query_scalar::<_, Option<i64>>(&format!(
+            r#"
+            SELECT {queue_position}
+            FROM tasks t
+            WHERE t.task_id = $1
+            "#,
+            queue_position = task_queue_position_sql("$2"),
+        ))
+        .bind(task_id)
+        .bind(Self::task_priority_aging_seconds())
+        .fetch_optional(db)
+        .await?
+        .flatten();
+
+        Ok(position)
+    }
+
     async fn get_task_status(&self, task_id: Uuid) -> ApiResult<Option<String>> {
         let db = self.require_db()?;
         let status = sqlx::query_scalar::<_, String>(
@@ -501,6 +1474,7 @@ impl AppState {
         Ok(())
     }
 
+    #[tracing::instrument(skip_all, fields(%task_id, min_nodes = min_nodes))]
     async fn assign_available_nodes_for_task(
         &self,
         task_id: Uuid,
@@ -508,6 +1482,28 @@ impl AppState {
         task_registry_entry: &TaskTypeRegistryEntry,
         min_nodes: u32,
         require_gpu: bool,
+    ) -> ApiResult<()> {
+        let started = std::time::Instant::now();
+        let result = self
+            .attach_nodes_to_task(
+                task_id,
+                task_type,
+                task_registry_entry,
+                min_nodes,
+                require_gpu,
+            )
+            .await;
+        crate::middleware::metrics::observe_assignment_duration("task", started.elapsed());
+        result
+    }
+
+    async fn attach_nodes_to_task(
+        &self,
+        task_id: Uuid,
+        task_type: &str,
+        task_registry_entry: &TaskTypeRegistryEntry,
+        min_nodes: u32,
+        require_gpu: bool,
     ) -> ApiResult<()> {
         let db = self.require_db()?;
         let max_attachments = Self::max_active_task_attachments_per_node();
@@ -532,143 +1528,417 @@ impl AppState {
         let additional_nodes_needed = min_nodes as i64 - assigned_nodes;
         let forbid_active_connect_session = task_type == "connect_only";
 
-        let node_ids = sqlx::query_scalar::<_, String>(
+        let task_policy = sqlx::query(
             r#"
-            SELECT n.node_id
-            FROM nodes n
-            LEFT JOIN task_assignments ta
-              ON ta.node_id = n.node_id
-             AND ta.disconnected_at IS NULL
-            WHERE n.deleted_at IS NULL
-              AND n.status = 'online'
-              AND (n.node_type = $1 OR n.node_type = 'any')
-              AND n.cpu_cores >= $2
-              AND n.memory_gb >= $3
-              AND n.bandwidth_mbps >= $4
-              AND ($5 = FALSE OR n.gpu_available = TRUE)
-              AND (
-                    $9 = FALSE
-                    OR NOT EXISTS (
-                        SELECT 1
-                        FROM connect_sessions cs_busy
-                        WHERE cs_busy.node_id = n.node_id
-                          AND cs_busy.status = 'active'
-                          AND cs_busy.expires_at > NOW()
-                    )
-                  )
-              AND NOT EXISTS (
-                  SELECT 1
-                  FROM task_assignments existing
-                  WHERE existing.task_id = $6
-                    AND existing.node_id = n.node_id
-                    AND existing.disconnected_at IS NULL
-              )
-            GROUP BY n.node_id
-            -- n.health_score and n.registered_at are omitted from GROUP BY because
-            -- they are functionally dependent on n.node_id (the primary key).
-            HAVING COUNT(ta.task_id) < $7
-            ORDER BY n.health_score DESC, n.registered_at ASC
-            LIMIT $8
-            "#,
-        )
-        .bind(task_registry_entry.preferred_node_type)
-        .bind(task_registry_entry.minimum_capabilities.cpu_cores as i32)
-        .bind(task_registry_entry.minimum_capabilities.memory_gb)
-        .bind(task_registry_entry.minimum_capabilities.bandwidth_mbps)
-        .bind(require_gpu || task_registry_entry.minimum_capabilities.gpu_available)
+            SELECT
+                t.scheduling_mode,
+                t.retry_excluded_nodes,
+                t.node_selector,
+                t.diversity,
+                t.preferred_regions,
+                t.excluded_regions,
+                t.region_strictness,
+                t.constraints_relaxed_at IS NOT NULL AS constraints_relaxed,
+                COALESCE(t.next_attempt_at > NOW(), FALSE) AS backing_off,
+                t.org_id,
+                t.creator_id,
+                (
+                    SELECT COUNT(*)
+                    FROM tasks r
+                    WHERE r.status = 'running'
+                      AND r.org_id IS NOT DISTINCT FROM t.org_id
+                      AND (t.org_id IS NOT NULL OR r.creator_id IS NOT DISTINCT FROM t.creator_id)
+                ) AS requester_running
+            FROM tasks t
+            WHERE t.task_id = $1
+            "#,
+        )
         .bind(task_id)
-        .bind(max_attachments)
-        .bind(additional_nodes_needed)
-        .bind(forbid_active_connect_session)
-        .fetch_all(db)
+        .fetch_optional(db)
         .await?;
 
-        for node_id in node_ids {
-            sqlx::query(
-                r#"
-                INSERT INTO task_assignments (task_id, node_id)
-                VALUES ($1, $2)
-                ON CONFLICT (task_id, node_id)
-                DO UPDATE SET assigned_at = NOW(), disconnected_at = NULL,
-                              execution_status = 'assigned',
-                              execution_started_at = NULL,
-                              execution_completed_at = NULL
-                WHERE task_assignments.disconnected_at IS NOT NULL
-                "#,
-            )
+        let (
+            scheduling_mode,
+            retry_excluded_nodes,
+            node_selector,
+            diversity,
+            regions,
+            constraints_relaxed,
+        ) = match task_policy {
+            Some(row) => {
+                // A re-queued task waits out its retry backoff before new
+                // nodes are attached; the retry sweep picks it up afterwards.
+                if row.get::<bool, _>("backing_off") {
+                    return self
+                        .update_task_status_from_assignments(task_id, min_nodes)
+                        .await;
+                }
+                // A task that has not started yet waits while its requester
+                // is at the running-task cap.
+                if assigned_nodes == 0
+                    && !self
+                        .fair_share
+                        .admits(row.get::<i64, _>("requester_running"))
+                {
+                    crate::middleware::metrics::record_fair_share_deferral(
+                        &crate::fair_queue::requester_key(row.get("org_id"), row.get("creator_id")),
+                    );
+                    return self
+                        .update_task_status_from_assignments(task_id, min_nodes)
+                        .await;
+                }
+                (
+                    SchedulingMode::parse(&row.get::<String, _>("scheduling_mode")),
+                    row.get::<Vec<String>, _>("retry_excluded_nodes"),
+                    row.get::<serde_json::Value, _>("node_selector"),
+                    DiversityMode::parse(&row.get::<String, _>("diversity")),
+                    crate::scheduling::RegionPlacement {
+                        preferred: row.get("preferred_regions"),
+                        excluded: row.get("excluded_regions"),
+                        strictness: RegionStrictness::parse(row.get("region_strictness")),
+                    },
+                    row.get::<bool, _>("constraints_relaxed"),
+                )
+            }
+            None => (
+                SchedulingMode::default(),
+                Vec::new(),
+                serde_json::json!({}),
+                DiversityMode::default(),
+                crate::scheduling::RegionPlacement::default(),
+                false,
+            ),
+        };
+        let any_node_type = constraints_relaxed && task_registry_entry.node_type_relaxable;
+        // Only a task run on several nodes has anything to spread.
+        let spread = diversity != DiversityMode::Off && min_nodes > 1;
+
+        // Green scheduling ranks every eligible node in Rust (region carbon
+        // factors live in server config), and spreading needs candidates
+        // beyond the top few, so in both cases the candidate query is unbounded.
+        let candidate_limit = (scheduling_mode == SchedulingMode::Standard && !spread)
+            .then_some(additional_nodes_needed);
+
+        let candidates = sqlx::query(crate::hot_queries::CANDIDATE_NODES)
+            .bind(task_registry_entry.serving_node_types())
+            .bind(task_registry_entry.minimum_capabilities.cpu_cores as i32)
+            .bind(task_registry_entry.minimum_capabilities.memory_gb)
+            .bind(task_registry_entry.minimum_capabilities.bandwidth_mbps)
+            .bind(require_gpu || task_registry_entry.minimum_capabilities.gpu_available)
             .bind(task_id)
-            .bind(node_id)
-            .execute(db)
+            .bind(max_attachments)
+            .bind(candidate_limit)
+            .bind(forbid_active_connect_session)
+            .bind(&retry_excluded_nodes)
+            .bind(any_node_type)
+            .bind(SlotClass::for_task(task_type, require_gpu).as_str())
+            .bind(&node_selector)
+            .bind(&regions.preferred)
+            .bind(&regions.excluded)
+            .bind(regions.required())
+            .bind(task_type)
+            .bind(crate::proof_offload::min_health_score(task_type))
+            .fetch_all(db)
+            .await?;
+
+        let candidates: Vec<crate::scheduling::PlacementCandidate> = candidates
+            .iter()
+            .map(|row| crate::scheduling::PlacementCandidate {
+                node_id: row.get("node_id"),
+                region: row.get("region"),
+                asn: node_asn_from_row(row),
+                health_score: row.get("health_score"),
+                benchmark_ops_per_wh: row.get("benchmark_ops_per_wh"),
+            })
+            .collect();
+        let carbon_factors = match scheduling_mode {
+            SchedulingMode::Standard => std::collections::BTreeMap::new(),
+            SchedulingMode::Green => candidates
+                .iter()
+                .map(|c| (c.region.clone(), self.carbon_factors.factor_for(&c.region)))
+                .collect(),
+        };
+        let taken = if spread {
+            self.assigned_network_locations(task_id).await?
+        } else {
+            Vec::new()
+        };
+        let inputs = crate::scheduling::PlacementInputs {
+            scheduling_mode,
+            diversity,
+            min_nodes,
+            wanted: additional_nodes_needed.max(0) as usize,
+            preferred_regions: regions.preferred.clone(),
+            carbon_factors,
+            candidates,
+            taken,
+        };
+        let node_ids = crate::scheduling::place_task(&inputs);
+
+        let attached = self
+            .insert_task_assignments(
+                task_id,
+                &node_ids,
+                SlotClass::for_task(task_type, require_gpu),
+                min_nodes,
+            )
             .await?;
+        if !node_ids.is_empty() {
+            self.record_scheduler_decision(
+                Some(task_id),
+                None,
+                &crate::scheduler_decisions::DecisionInputs::Placement(inputs),
+                &node_ids,
+                &attached,
+            )
+            .await;
         }
 
         self.update_task_status_from_assignments(task_id, min_nodes)
             .await
     }
 
-    async fn active_attachment_count_for_node(&self, node_id: &str) -> ApiResult<i64> {
+    /// Attach `task_id` to `node_ids` (best first) in one transaction.
+    ///
+    /// Candidates are chosen without locks, so the task row and then the
+    /// node rows, in node ID order, are locked before a single multi-row
+    /// insert re-checks slot capacity and how many nodes the task still
+    /// needs.  Concurrent submissions and heartbeats therefore cannot
+    /// overfill a node's slot pool or attach a task to more than `min_nodes`
+    /// nodes.  A `verify_proof` task also re-checks, under its lock, that
+    /// each verifier is independent of the submitter and of every other
+    /// verifier, keeping only the best node per owner.
+    ///
+    /// # Returns
+    /// The nodes attached, which are notified of the new assignment.
+    async fn insert_task_assignments(
+        &self,
+        task_id: Uuid,
+        node_ids: &[String],
+        slot_class: SlotClass,
+        min_nodes: u32,
+    ) -> ApiResult<Vec<String>> {
+        if node_ids.is_empty() {
+            return Ok(Vec::new());
+        }
         let db = self.require_db()?;
-        let count = sqlx::query_scalar::<_, i64>(
-            r#"
-            SELECT COUNT(*)
-            FROM task_assignments
-            WHERE node_id = $1
-              AND disconnected_at IS NULL
-            "#,
-        )
-        .bind(node_id)
-        .fetch_one(db)
-        .await?;
+        let mut tx = db.begin().await?;
+        let task_type: Option<String> =
+            sqlx::query_scalar("SELECT task_type FROM tasks WHERE task_id = $1 FOR UPDATE")
+                .bind(task_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let independent: Vec<String>;
+        let node_ids = if task_type.as_deref() == Some(crate::proof_offload::VERIFY_PROOF_TASK_TYPE)
+        {
+            independent = sqlx::query_scalar(
+                r#"
+                SELECT node_id
+                FROM (
+                    SELECT DISTINCT ON (n.owner_id) wanted.node_id, wanted.position
+                    FROM unnest($2::TEXT[]) WITH ORDINALITY AS wanted(node_id, position)
+                    JOIN nodes n ON n.node_id = wanted.node_id
+                    WHERE NOT verifier_conflicts_with_task(n.node_id, $1)
+                    ORDER BY n.owner_id, wanted.position
+                ) best_per_owner
+                ORDER BY position
+                "#,
+            )
+            .bind(task_id)
+            .bind(node_ids)
+            .fetch_all(&mut *tx)
+            .await?;
+            &independent[..]
+        } else {
+            node_ids
+        };
+        if node_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        sqlx::query("SELECT 1 FROM nodes WHERE node_id = ANY($1) ORDER BY node_id FOR UPDATE")
+            .bind(node_ids)
+            .execute(&mut *tx)
+            .await?;
+        let attached: Vec<String> = sqlx::query_scalar(crate::hot_queries::ASSIGN_TASK_TO_NODES)
+            .bind(task_id)
+            .bind(node_ids)
+            .bind(slot_class.as_str())
+            .bind(Self::max_active_task_attachments_per_node())
+            .bind(min_nodes as i32)
+            .fetch_all(&mut *tx)
+            .await?;
+        tx.commit().await?;
 
-        Ok(count)
+        for node_id in &attached {
+            let _ = self.assignment_events.send(node_id.clone());
+        }
+        Ok(attached)
     }
 
+    /// ASN and region of a node, or `None` for an unknown node.
+    async fn node_network_location(
+        &self,
+        node_id: &str,
+    ) -> ApiResult<Option<ambient_node::NetworkLocation>> {
+        let db = self.require_db()?;
+        let row = sqlx::query("SELECT asn, region FROM nodes WHERE node_id = $1")
+            .bind(node_id)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(row.map(|row| {
+            ambient_node::NetworkLocation::new(
+                node_asn_from_row(&row),
+                row.get::<String, _>("region"),
+            )
+        }))
+    }
+
+    /// ASN and region of each node currently attached to `task_id`.
+    async fn assigned_network_locations(
+        &self,
+        task_id: Uuid,
+    ) -> ApiResult<Vec<ambient_node::NetworkLocation>> {
+        let db = self.require_db()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT n.asn, n.region
+            FROM task_assignments ta
+            JOIN nodes n ON n.node_id = ta.node_id
+            WHERE ta.task_id = $1
+              AND ta.disconnected_at IS NULL
+            "#,
+        )
+        .bind(task_id)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                ambient_node::NetworkLocation::new(
+                    node_asn_from_row(row),
+                    row.get::<String, _>("region"),
+                )
+            })
+            .collect())
+    }
+
+    /// Free slots in each of a node's slot pools, or `None` for an unknown
+    /// node.  Pools the node did not size use `MAX_CONCURRENT_TASKS_PER_NODE`.
+    async fn free_slots_for_node(
+        &self,
+        node_id: &str,
+    ) -> ApiResult<Option<std::collections::HashMap<SlotClass, i64>>> {
+        let db = self.require_db()?;
+        let row = sqlx::query(crate::hot_queries::NODE_FREE_SLOTS)
+            .bind(node_id)
+            .bind(Self::max_active_task_attachments_per_node())
+            .fetch_optional(db)
+            .await?;
+
+        Ok(row.map(|row| {
+            [
+                (SlotClass::Connect, row.get::<i64, _>("connect_free")),
+                (SlotClass::Wasm, row.get::<i64, _>("wasm_free")),
+                (SlotClass::Gpu, row.get::<i64, _>("gpu_free")),
+            ]
+            .into_iter()
+            .collect()
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(%node_id))]
     async fn assign_pending_tasks_for_node(&self, node_id: &str) -> ApiResult<()> {
+        let started = std::time::Instant::now();
+        let result = self.attach_pending_tasks_to_node(node_id).await;
+        crate::middleware::metrics::observe_assignment_duration("node", started.elapsed());
+        result
+    }
+
+    async fn attach_pending_tasks_to_node(&self, node_id: &str) -> ApiResult<()> {
         let Ok(db) = self.require_db() else {
             return Ok(());
         };
 
-        let max_attachments = Self::max_active_task_attachments_per_node();
-        let mut current_attachments = self.active_attachment_count_for_node(node_id).await?;
+        let Some(mut free_slots) = self.free_slots_for_node(node_id).await? else {
+            return Ok(());
+        };
+        let open_classes = |free_slots: &std::collections::HashMap<SlotClass, i64>| {
+            free_slots
+                .iter()
+                .filter(|(_, free)| **free > 0)
+                .map(|(class, _)| class.as_str())
+                .collect::<Vec<_>>()
+        };
 
-        if current_attachments >= max_attachments {
+        if open_classes(&free_slots).is_empty() {
             tracing::warn!(
                 node_id,
-                max_attachments,
-                "Node reached active task attachment limit; skipping pending task attachment"
+                "Node has no free task slots; skipping pending task attachment"
             );
             return Ok(());
         }
 
-        let pending_tasks = sqlx::query(
-            r#"
-            SELECT
-                t.task_id,
-                t.task_type,
-                t.min_nodes,
-                t.require_gpu,
-                COALESCE(COUNT(ta.node_id), 0) AS assigned_nodes
-            FROM tasks t
-            LEFT JOIN task_assignments ta ON ta.task_id = t.task_id AND ta.disconnected_at IS NULL
-            WHERE t.status = 'pending'
-            GROUP BY t.task_id, t.task_type, t.min_nodes, t.require_gpu
-            HAVING COALESCE(COUNT(ta.node_id), 0) < t.min_nodes
-            ORDER BY t.created_at ASC
-            "#,
-        )
-        .fetch_all(db)
-        .await?;
+        let pending_tasks = sqlx::query(crate::hot_queries::PENDING_TASKS_FOR_NODE)
+            .bind(Self::task_priority_aging_seconds())
+            .bind(node_id)
+            .bind(open_classes(&free_slots))
+            .fetch_all(db)
+            .await?;
+
+        // Offer this node's free slots to requesters in fair-share order
+        // rather than strictly by priority and age.
+        let queued = pending_tasks
+            .iter()
+            .map(|task| crate::fair_queue::QueuedTask {
+                task_id: task.get("task_id"),
+                requester: crate::fair_queue::requester_key(
+                    task.get("org_id"),
+                    task.get("creator_id"),
+                ),
+                requester_running: task.get("requester_running"),
+                partially_assigned: task.get::<i64, _>("assigned_nodes") > 0,
+            })
+            .collect::<Vec<_>>();
+        let pickup = crate::scheduler_decisions::PickupInputs::new(
+            node_id,
+            queued.clone(),
+            &self.fair_share,
+        );
+        let (fair_order, deferred) = crate::fair_queue::fair_order(queued, &self.fair_share);
+        let offered: Vec<String> = fair_order
+            .iter()
+            .map(|queued| queued.task_id.to_string())
+            .collect();
+        let mut taken: Vec<String> = Vec::new();
+        for requester in &deferred {
+            crate::middleware::metrics::record_fair_share_deferral(requester);
+        }
+        let mut pending_by_id: std::collections::HashMap<Uuid, sqlx::postgres::PgRow> =
+            pending_tasks
+                .into_iter()
+                .map(|task| (task.get("task_id"), task))
+                .collect();
+        let pending_tasks = fair_order
+            .into_iter()
+            .filter_map(|queued| pending_by_id.remove(&queued.task_id));
+        let mut node_location: Option<ambient_node::NetworkLocation> = None;
 
         for task in pending_tasks {
-            if current_attachments >= max_attachments {
+            if open_classes(&free_slots).is_empty() {
                 tracing::info!(
                     node_id,
-                    max_attachments,
-                    "Stopped attaching node to pending tasks after reaching active task attachment limit"
+                    "Stopped attaching node to pending tasks after filling every slot pool"
                 );
                 break;
             }
 
+            let slot_class = SlotClass::parse(task.get("slot_class"));
+            if free_slots.get(&slot_class).copied().unwrap_or(0) <= 0 {
+                continue;
+            }
+
             let task_id: Uuid = task.get("task_id");
             let task_type: String = task.get("task_type");
             let min_nodes: i32 = task.get("min_nodes");
@@ -679,72 +1949,78 @@ impl AppState {
             };
 
             let forbid_active_connect_session = task_type == "connect_only";
+            let any_node_type = task.get::<bool, _>("constraints_relaxed")
+                && task_registry_entry.node_type_relaxable;
 
-            let node_is_eligible = sqlx::query_scalar::<_, bool>(
-                r#"
-                SELECT EXISTS (
-                    SELECT 1
-                    FROM nodes n
-                    WHERE n.node_id = $1
-                      AND n.deleted_at IS NULL
-                      AND n.status = 'online'
-                      AND (n.node_type = $2 OR n.node_type = 'any')
-                      AND n.cpu_cores >= $3
-                      AND n.memory_gb >= $4
-                      AND n.bandwidth_mbps >= $5
-                      AND ($6 = FALSE OR n.gpu_available = TRUE)
-                      AND (
-                            $7 = FALSE
-                            OR NOT EXISTS (
-                                SELECT 1
-                                FROM connect_sessions cs_busy
-                                WHERE cs_busy.node_id = n.node_id
-                                  AND cs_busy.status = 'active'
-                                  AND cs_busy.expires_at > NOW()
-                            )
-                          )
-                )
-                "#,
-            )
-            .bind(node_id)
-            .bind(task_registry_entry.preferred_node_type)
-            .bind(task_registry_entry.minimum_capabilities.cpu_cores as i32)
-            .bind(task_registry_entry.minimum_capabilities.memory_gb)
-            .bind(task_registry_entry.minimum_capabilities.bandwidth_mbps)
-            .bind(require_gpu || task_registry_entry.minimum_capabilities.gpu_available)
-            .bind(forbid_active_connect_session)
-            .fetch_one(db)
-            .await?;
+            let node_is_eligible =
+                sqlx::query_scalar::<_, bool>(crate::hot_queries::NODE_ELIGIBLE_FOR_TASK)
+                    .bind(node_id)
+                    .bind(task_registry_entry.serving_node_types())
+                    .bind(task_registry_entry.minimum_capabilities.cpu_cores as i32)
+                    .bind(task_registry_entry.minimum_capabilities.memory_gb)
+                    .bind(task_registry_entry.minimum_capabilities.bandwidth_mbps)
+                    .bind(require_gpu || task_registry_entry.minimum_capabilities.gpu_available)
+                    .bind(forbid_active_connect_session)
+                    .bind(any_node_type)
+                    .bind(task.get::<serde_json::Value, _>("node_selector"))
+                    .bind(task.get::<Vec<String>, _>("excluded_regions"))
+                    .bind(task.get::<Option<Vec<String>>, _>("required_regions"))
+                    .bind(&task_type)
+                    .bind(crate::proof_offload::min_health_score(&task_type))
+                    .bind(task_id)
+                    .fetch_one(db)
+                    .await?;
 
             if !node_is_eligible {
                 continue;
             }
 
-            let rows = sqlx::query(
-                r#"
-                INSERT INTO task_assignments (task_id, node_id)
-                VALUES ($1, $2)
-                ON CONFLICT (task_id, node_id)
-                DO UPDATE SET assigned_at = NOW(), disconnected_at = NULL,
-                              execution_status = 'assigned',
-                              execution_started_at = NULL,
-                              execution_completed_at = NULL
-                WHERE task_assignments.disconnected_at IS NOT NULL
-                "#,
-            )
-            .bind(task_id)
-            .bind(node_id)
-            .execute(db)
-            .await?;
+            // A `require` task only takes nodes outside the failure domains
+            // of the nodes it already has.
+            if DiversityMode::parse(task.get("diversity")) == DiversityMode::Require
+                && task.get::<i64, _>("assigned_nodes") > 0
+            {
+                if node_location.is_none() {
+                    node_location = self.node_network_location(node_id).await?;
+                }
+                let taken = self.assigned_network_locations(task_id).await?;
+                if node_location.as_ref().is_some_and(|location| {
+                    taken
+                        .iter()
+                        .any(|other| location.shares_failure_domain(other))
+                }) {
+                    continue;
+                }
+            }
 
-            if rows.rows_affected() > 0 {
-                current_attachments += 1;
+            let attached = self
+                .insert_task_assignments(
+                    task_id,
+                    &[node_id.to_string()],
+                    slot_class,
+                    min_nodes as u32,
+                )
+                .await?;
+            if !attached.is_empty() {
+                *free_slots.entry(slot_class).or_default() -= 1;
+                taken.push(task_id.to_string());
             }
 
             self.update_task_status_from_assignments(task_id, min_nodes as u32)
                 .await?;
         }
 
+        if !taken.is_empty() {
+            self.record_scheduler_decision(
+                None,
+                Some(node_id),
+                &crate::scheduler_decisions::DecisionInputs::Pickup(pickup),
+                &offered,
+                &taken,
+            )
+            .await;
+        }
+
         Ok(())
     }
 
@@ -753,41 +2029,99 @@ impl AppState {
         task_id: Uuid,
         min_nodes: u32,
     ) -> ApiResult<()> {
+        retry_task_transition(task_id, "assignment_status", || {
+            self.try_update_task_status_from_assignments(task_id, min_nodes)
+        })
+        .await
+    }
+
+    async fn try_update_task_status_from_assignments(
+        &self,
+        task_id: Uuid,
+        min_nodes: u32,
+    ) -> ApiResult<TaskTransition<()>> {
         let db = self.require_db()?;
-        let assigned_nodes: i64 = sqlx::query_scalar(
+        let task_row = sqlx::query(
             r#"
-            SELECT COUNT(*)
-            FROM task_assignments
-            WHERE task_id = $1
-              AND disconnected_at IS NULL
+            SELECT
+                t.status,
+                t.version,
+                (
+                    SELECT COUNT(*)
+                    FROM task_assignments ta
+                    WHERE ta.task_id = t.task_id
+                      AND ta.disconnected_at IS NULL
+                ) AS assigned_nodes
+            FROM tasks t
+            WHERE t.task_id = $1
             "#,
         )
         .bind(task_id)
-        .fetch_one(db)
+        .fetch_optional(db)
         .await?;
 
+        let Some(task_row) = task_row else {
+            return Ok(TaskTransition::Applied(()));
+        };
+        let previous_status: String = task_row.get("status");
+        if matches!(
+            previous_status.as_str(),
+            "completed" | "failed" | "unschedulable"
+        ) {
+            return Ok(TaskTransition::Applied(()));
+        }
+
+        let assigned_nodes: i64 = task_row.get("assigned_nodes");
         let next_status = if assigned_nodes >= min_nodes as i64 {
             "running"
         } else {
             "pending"
         };
 
-        sqlx::query(
+        let transition = sqlx::query(
             r#"
-            UPDATE tasks
-            SET status = $1, updated_at = NOW()
-            WHERE task_id = $2
-              AND status NOT IN ('completed', 'failed')
+            UPDATE tasks t
+            SET status = $1,
+                updated_at = NOW(),
+                version = t.version + CASE WHEN t.status = $1 THEN 0 ELSE 1 END,
+                queue_wait_seconds = CASE
+                    WHEN t.status = 'pending' AND $1 = 'running' THEN
+                        COALESCE(t.queue_wait_seconds, 0) + GREATEST(
+                            EXTRACT(EPOCH FROM (NOW() - GREATEST(t.queued_at, t.next_attempt_at)))::FLOAT8,
+                            0
+                        )
+                    ELSE t.queue_wait_seconds
+                END
+            WHERE t.task_id = $2
+              AND t.version = $3
+            RETURNING
+                t.org_id,
+                t.creator_id,
+                EXTRACT(EPOCH FROM (NOW() - GREATEST(t.queued_at, t.next_attempt_at)))::FLOAT8
+                    AS queued_seconds
             "#,
         )
         .bind(next_status)
         .bind(task_id)
-        .execute(db)
+        .bind(task_row.get::<i64, _>("version"))
+        .fetch_optional(db)
         .await?;
 
-        Ok(())
+        let Some(row) = transition else {
+            return Ok(TaskTransition::Conflict);
+        };
+
+        if next_status == "running" && previous_status == "pending" {
+            crate::middleware::metrics::observe_task_queue_wait(
+                &crate::fair_queue::requester_key(row.get("org_id"), row.get("creator_id")),
+                row.get("queued_seconds"),
+            );
+        }
+
+        Ok(TaskTransition::Applied(()))
     }
 
+    #[tracing::instrument(skip_all)]
     pub async fn start_connect_session(
         &self,
         request: ConnectSessionStartRequest,
@@ -802,8 +2136,9 @@ impl AppState {
             SELECT t.inputs, t.status
             FROM tasks t
             WHERE t.task_id = $1
-              AND t.creator_id = $2
+              AND (t.creator_id = $2 OR org_role_at_least(t.org_id, $2, 'member'))
               AND t.task_type = 'connect_only'
+              AND t.deleted_at IS NULL
             "#,
         )
         .bind(task_uuid)
@@ -861,6 +2196,14 @@ impl AppState {
             .ok_or_else(|| {
                 ApiError::bad_request("connect_only task missing bandwidth_limit_mbps")
             })?;
+        let data_cap_bytes = [
+            input_obj.get("data_cap_mb").and_then(|v| v.as_u64()),
+            Self::connect_session_data_cap_mb(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .map(|mb| mb.saturating_mul(1_000_000));
         let node_id = self
             .select_active_connect_node_for_task(task_uuid)
             .await?
@@ -882,9 +2225,9 @@ impl AppState {
                 session_id, task_id, requester_id, node_id, tunnel_protocol,
                 egress_profile, destination_policy_id, bandwidth_limit_mbps,
                 session_token_hash, session_token_cleartext,
-                status, created_at, expires_at, last_heartbeat_at
+                status, created_at, expires_at, last_heartbeat_at, data_cap_bytes
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $12, 'active', $10, $11, $10)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $12, 'active', $10, $11, $10, $13)
             ON CONFLICT (session_id)
             DO UPDATE SET
                 node_id = EXCLUDED.node_id,
@@ -898,7 +2241,12 @@ impl AppState {
                 created_at = EXCLUDED.created_at,
                 expires_at = EXCLUDED.expires_at,
                 ended_at = NULL,
-                last_heartbeat_at = EXCLUDED.last_heartbeat_at
+                last_heartbeat_at = EXCLUDED.last_heartbeat_at,
+                bytes_up = 0,
+                bytes_down = 0,
+                data_cap_bytes = EXCLUDED.data_cap_bytes,
+                usage_seq = 0,
+                last_usage_at = NULL
             "#,
         )
         .bind(&session_id)
@@ -913,6 +2261,7 @@ impl AppState {
         .bind(now)
         .bind(expires_at)
         .bind(&session_token) // $12 — cleartext token for gateway node use
+        .bind(data_cap_bytes.map(|bytes| bytes.min(i64::MAX as u64) as i64))
         .execute(db)
         .await?;
 
@@ -931,6 +2280,10 @@ impl AppState {
             expires_at: expires_at.to_rfc3339(),
             last_heartbeat_at: Some(now.to_rfc3339()),
             ended_at: None,
+            usage: ConnectSessionUsage {
+                data_cap_bytes,
+                ..Default::default()
+            },
         };
 
         // Automatically record a task_connected event in heartbeat history so that
@@ -1059,7 +2412,8 @@ impl AppState {
             r#"
             SELECT session_id, task_id, requester_id, node_id, tunnel_protocol,
                    egress_profile, destination_policy_id, bandwidth_limit_mbps,
-                   status, created_at, expires_at, last_heartbeat_at, ended_at
+                   status, created_at, expires_at, last_heartbeat_at, ended_at,
+                   bytes_up, bytes_down, data_cap_bytes, last_usage_at
             FROM connect_sessions
             WHERE session_id = $1
               AND requester_id = $2
@@ -1073,6 +2427,36 @@ impl AppState {
         Ok(row.map(map_connect_session_row))
     }
 
+    /// The requester's connect sessions, newest first.
+    pub async fn list_connect_sessions(
+        &self,
+        requester_id: Uuid,
+        limit: i64,
+    ) -> ApiResult<Vec<ConnectSessionInfo>> {
+        let Ok(db) = self.read_db() else {
+            return Ok(vec![]);
+        };
+
+        let rows = sqlx::query(
+            r#"
+            SELECT session_id, task_id, requester_id, node_id, tunnel_protocol,
+                   egress_profile, destination_policy_id, bandwidth_limit_mbps,
+                   status, created_at, expires_at, last_heartbeat_at, ended_at,
+                   bytes_up, bytes_down, data_cap_bytes, last_usage_at
+            FROM connect_sessions
+            WHERE requester_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(requester_id)
+        .bind(limit)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows.into_iter().map(map_connect_session_row).collect())
+    }
+
     pub async fn heartbeat_connect_session(
         &self,
         session_id: &str,
@@ -1089,7 +2473,8 @@ impl AppState {
               AND status = 'active'
             RETURNING session_id, task_id, requester_id, node_id, tunnel_protocol,
                    egress_profile, destination_policy_id, bandwidth_limit_mbps,
-                   status, created_at, expires_at, last_heartbeat_at, ended_at
+                   status, created_at, expires_at, last_heartbeat_at, ended_at,
+                   bytes_up, bytes_down, data_cap_bytes, last_usage_at
             "#,
         )
         .bind(session_id)
@@ -1128,9 +2513,31 @@ impl AppState {
         let task_uuid = Uuid::parse_str(&session.task_id)
             .map_err(|_| ApiError::internal_error("Invalid task ID format"))?;
 
-        if let Some(replacement_node_id) =
-            self.select_active_connect_node_for_task(task_uuid).await?
-        {
+        let replacement = self.select_active_connect_node_for_task(task_uuid).await?;
+
+        // A draining relay keeps its sessions until its deadline while no
+        // replacement is available.
+        if replacement.is_none() {
+            let relay_draining = sqlx::query_scalar::<_, bool>(
+                r#"
+                SELECT EXISTS (
+                    SELECT 1 FROM nodes
+                    WHERE node_id = $1
+                      AND deleted_at IS NULL
+                      AND status = 'draining'
+                      AND relay_drain_deadline > NOW()
+                )
+                "#,
+            )
+            .bind(&session.node_id)
+            .fetch_one(db)
+            .await?;
+            if relay_draining {
+                return Ok(Some(session));
+            }
+        }
+
+        if let Some(replacement_node_id) = replacement {
             if replacement_node_id != session.node_id {
                 let replacement_row = sqlx::query(
                     r#"
@@ -1142,7 +2549,8 @@ impl AppState {
                       AND status = 'active'
                     RETURNING session_id, task_id, requester_id, node_id, tunnel_protocol,
                            egress_profile, destination_policy_id, bandwidth_limit_mbps,
-                           status, created_at, expires_at, last_heartbeat_at, ended_at
+                           status, created_at, expires_at, last_heartbeat_at, ended_at,
+                           bytes_up, bytes_down, data_cap_bytes, last_usage_at
                     "#,
                 )
                 .bind(&replacement_node_id)
@@ -1217,6 +2625,134 @@ impl AppState {
         self.get_connect_session(session_id, requester_id).await
     }
 
+    /// Push an active session's expiry forward by `extend_seconds`.
+    ///
+    /// Refused once the session has used its extensions or would outlive
+    /// the maximum session lifetime.  The node picks the new expiry up from
+    /// its gateway session list, and the backing `connect_only` task's
+    /// completion timer defers to it.  Returns `None` when no active session
+    /// matches.
+    pub async fn extend_connect_session(
+        &self,
+        session_id: &str,
+        requester_id: Uuid,
+        extend_seconds: u64,
+    ) -> ApiResult<Option<ConnectSessionInfo>> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+
+        let current = sqlx::query(
+            r#"
+            SELECT cs.created_at, cs.expires_at, cs.extension_count, t.status AS task_status
+            FROM connect_sessions cs
+            JOIN tasks t ON t.task_id = cs.task_id
+            WHERE cs.session_id = $1
+              AND cs.requester_id = $2
+              AND cs.status = 'active'
+            FOR UPDATE OF cs
+            "#,
+        )
+        .bind(session_id)
+        .bind(requester_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(current) = current else {
+            return Ok(None);
+        };
+
+        let created_at: chrono::DateTime<chrono::Utc> = current.get("created_at");
+        let expires_at: chrono::DateTime<chrono::Utc> = current.get("expires_at");
+        let extension_count: i32 = current.get("extension_count");
+        let task_status: String = current.get("task_status");
+
+        if expires_at <= chrono::Utc::now() {
+            return Err(ApiError::conflict("Connect session has already expired"));
+        }
+        if task_status != "running" {
+            return Err(ApiError::conflict(
+                "Connect session's task is no longer running",
+            ));
+        }
+
+        let max_extensions = Self::connect_session_max_extensions();
+        if extension_count as i64 >= max_extensions as i64 {
+            return Err(ApiError::forbidden(format!(
+                "Connect session has used all {} extensions",
+                max_extensions
+            ))
+            .with_details(serde_json::json!({
+                "extension_count": extension_count,
+                "max_extensions": max_extensions,
+            })));
+        }
+
+        let new_expires_at = expires_at + chrono::Duration::seconds(extend_seconds as i64);
+        let max_expires_at =
+            created_at + chrono::Duration::seconds(Self::connect_session_max_lifetime_seconds());
+        if new_expires_at > max_expires_at {
+            return Err(ApiError::forbidden(
+                "Extension would exceed the maximum connect session lifetime",
+            )
+            .with_details(serde_json::json!({
+                "max_expires_at": max_expires_at.to_rfc3339(),
+            })));
+        }
+
+        let row = sqlx::query(
+            r#"
+            UPDATE connect_sessions
+            SET expires_at = $3,
+                extension_count = extension_count + 1,
+                updated_at = NOW()
+            WHERE session_id = $1
+              AND requester_id = $2
+            RETURNING session_id, task_id, requester_id, node_id, tunnel_protocol,
+                   egress_profile, destination_policy_id, bandwidth_limit_mbps,
+                   status, created_at, expires_at, last_heartbeat_at, ended_at,
+                   bytes_up, bytes_down, data_cap_bytes, last_usage_at
+            "#,
+        )
+        .bind(session_id)
+        .bind(requester_id)
+        .bind(new_expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(map_connect_session_row(row)))
+    }
+
+    /// How much longer an extended session keeps its `connect_only` task
+    /// running past the task's original completion timer.  `None` once no
+    /// extended session for the task is still active.
+    pub async fn connect_only_completion_deferral(
+        &self,
+        task_id: Uuid,
+    ) -> ApiResult<Option<std::time::Duration>> {
+        let Ok(db) = self.require_db() else {
+            return Ok(None);
+        };
+
+        let remaining_ms: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT (EXTRACT(EPOCH FROM MAX(expires_at) - NOW()) * 1000)::BIGINT
+            FROM connect_sessions
+            WHERE task_id = $1
+              AND status = 'active'
+              AND extension_count > 0
+              AND expires_at > NOW()
+            "#,
+        )
+        .bind(task_id)
+        .fetch_one(db)
+        .await?;
+
+        Ok(remaining_ms
+            .filter(|ms| *ms > 0)
+            .map(|ms| std::time::Duration::from_millis(ms as u64)))
+    }
+
     pub async fn stop_connect_session(
         &self,
         session_id: &str,
@@ -1232,7 +2768,8 @@ impl AppState {
               AND status = 'active'
             RETURNING session_id, task_id, requester_id, node_id, tunnel_protocol,
                    egress_profile, destination_policy_id, bandwidth_limit_mbps,
-                   status, created_at, expires_at, last_heartbeat_at, ended_at
+                   status, created_at, expires_at, last_heartbeat_at, ended_at,
+                   bytes_up, bytes_down, data_cap_bytes, last_usage_at
             "#,
         )
         .bind(session_id)
@@ -1282,21 +2819,48 @@ impl AppState {
             None => serde_json::json!({"task_type": "connect_only", "status": "session_ended"}),
         };
 
+        retry_task_transition(task_id, "connect_session_end", || {
+            self.try_complete_connect_only_task(task_id, &result)
+        })
+        .await
+    }
+
+    async fn try_complete_connect_only_task(
+        &self,
+        task_id: Uuid,
+        result: &serde_json::Value,
+    ) -> ApiResult<TaskTransition<()>> {
+        let db = self.require_db()?;
+        let task_row = sqlx::query("SELECT status, version FROM tasks WHERE task_id = $1")
+            .bind(task_id)
+            .fetch_optional(db)
+            .await?;
+
         let mut tx = db.begin().await?;
 
-        // Only update if still running — idempotent against concurrent calls.
-        sqlx::query(
-            r#"
-            UPDATE tasks
-            SET status = 'completed', result = $1, updated_at = NOW()
-            WHERE task_id = $2
-              AND status = 'running'
-            "#,
-        )
-        .bind(&result)
-        .bind(task_id)
-        .execute(&mut *tx)
-        .await?;
+        // Only complete a running task — idempotent against concurrent calls.
+        if let Some(task_row) = task_row.filter(|row| row.get::<String, _>("status") == "running") {
+            let completed = sqlx::query(
+                r#"
+                UPDATE tasks
+                SET status = 'completed', result = $1, updated_at = NOW(),
+                    completed_at = NOW(), version = version + 1
+                WHERE task_id = $2
+                  AND version = $3
+                "#,
+            )
+            .bind(result)
+            .bind(task_id)
+            .bind(task_row.get::<i64, _>("version"))
+            .execute(&mut *tx)
+            .await?;
+
+            if completed.rows_affected() == 0 {
+                tx.rollback().await?;
+                return Ok(TaskTransition::Conflict);
+            }
+            self.enqueue_task_completed(&mut tx, task_id).await?;
+        }
 
         // Mark all assignments as completed regardless of disconnected_at state —
         // the sweep CTE may have already disconnected them.
@@ -1317,10 +2881,11 @@ impl AppState {
         self.disconnect_task_assignments(task_id, &mut tx).await?;
 
         tx.commit().await?;
-        Ok(())
+        Ok(TaskTransition::Applied(()))
     }
 
     /// Sweep active connect sessions and terminate sessions bound to expired/deleted nodes.
+    #[tracing::instrument(skip_all)]
     pub async fn sweep_connect_sessions(&self) -> ApiResult<usize> {
         let Ok(db) = self.require_db() else {
             return Ok(0);
@@ -1345,6 +2910,7 @@ impl AppState {
                   AND (
                         cs.expires_at < NOW()
                         OR n.node_id IS NULL
+                        OR (n.status = 'draining' AND n.relay_drain_deadline <= NOW())
                       )
             ),
             updated_sessions AS (
@@ -1409,7 +2975,13 @@ impl AppState {
         Ok(affected_task_ids.len())
     }
 
-    /// Delete a task created by the requesting user
+    /// Soft-delete a task created by the requesting user
+    ///
+    /// Sets `deleted_at`, frees the task's nodes and ends its active connect
+    /// sessions.  A running task goes back to `pending` so that restoring it
+    /// reschedules it.  The row is kept, with its result and proofs, until
+    /// the retention job purges it.
+    #[tracing::instrument(skip_all, fields(%task_id))]
     pub async fn delete_task(&self, task_id: &str, requester_id: Uuid) -> ApiResult<bool> {
         let db = self.require_db()?;
         let task_uuid = match Uuid::parse_str(task_id) {
@@ -1426,7 +2998,8 @@ impl AppState {
             LEFT JOIN task_assignments ta
                    ON ta.task_id = t.task_id AND ta.disconnected_at IS NULL
             WHERE t.task_id = $1
-              AND t.creator_id = $2
+              AND t.deleted_at IS NULL
+              AND (t.creator_id = $2 OR org_role_at_least(t.org_id, $2, 'member'))
             "#,
         )
         .bind(task_uuid)
@@ -1442,18 +3015,38 @@ impl AppState {
             .filter_map(|r| r.try_get::<String, _>("node_id").ok())
             .collect();
 
+        let mut tx = db.begin().await?;
         let result = sqlx::query(
             r#"
-            DELETE FROM tasks
+            UPDATE tasks
+            SET deleted_at = NOW(), updated_at = NOW(), version = version + 1,
+                status = CASE WHEN status = 'running' THEN 'pending' ELSE status END
             WHERE task_id = $1
-              AND creator_id = $2
+              AND deleted_at IS NULL
+              AND (creator_id = $2 OR org_role_at_least(org_id, $2, 'member'))
             "#,
         )
         .bind(task_uuid)
         .bind(requester_id)
-        .execute(db)
+        .execute(&mut *tx)
         .await?;
 
+        if result.rows_affected() > 0 {
+            self.disconnect_task_assignments(task_uuid, &mut tx).await?;
+            sqlx::query(
+                r#"
+                UPDATE connect_sessions
+                SET status = 'ended', ended_at = NOW(), updated_at = NOW()
+                WHERE task_id = $1
+                  AND status = 'active'
+                "#,
+            )
+            .bind(task_uuid)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
         if result.rows_affected() > 0 {
             // Record a cleared-task event in heartbeat history for each assigned node.
             // health_score and active_tasks are 0 because this is an event marker, not
@@ -1495,1381 +3088,7786 @@ impl AppState {
         Ok(false)
     }
 
-    /// Get recent task activity events (task_cleared and task_connected) from heartbeat history for a node
-    pub async fn get_node_cleared_task_events(
+    /// Restore a soft-deleted task.  A task deleted before it finished is
+    /// queued again and rescheduled.  Returns `false` when no deleted task
+    /// matches.
+    #[tracing::instrument(skip_all, fields(%task_id))]
+    pub async fn restore_task(&self, task_id: &str, requester_id: Uuid) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        let Ok(task_uuid) = Uuid::parse_str(task_id) else {
+            return Ok(false);
+        };
+
+        let Some(row) = sqlx::query(
+            r#"
+            UPDATE tasks
+            SET deleted_at = NULL, updated_at = NOW(), version = version + 1,
+                queued_at = CASE WHEN status = 'pending' THEN NOW() ELSE queued_at END
+            WHERE task_id = $1
+              AND deleted_at IS NOT NULL
+              AND (creator_id = $2 OR org_role_at_least(org_id, $2, 'member'))
+            RETURNING task_type, status, min_nodes, require_gpu
+            "#,
+        )
+        .bind(task_uuid)
+        .bind(requester_id)
+        .fetch_optional(db)
+        .await?
+        else {
+            return Ok(false);
+        };
+
+        let task_type: String = row.get("task_type");
+        let status: String = row.get("status");
+        if status == "pending" {
+            if let Some(task_registry_entry) = task_type_registry_entry(&task_type) {
+                self.assign_available_nodes_for_task(
+                    task_uuid,
+                    &task_type,
+                    task_registry_entry,
+                    row.get::<i32, _>("min_nodes") as u32,
+                    row.get("require_gpu"),
+                )
+                .await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// List the actively assigned nodes of a task and the keys their
+    /// secrets must be sealed to.  Only the task creator may list them.
+    pub async fn list_task_secret_recipients(
+        &self,
+        task_id: Uuid,
+        requester_id: Uuid,
+    ) -> ApiResult<Vec<TaskSecretRecipient>> {
+        let db = self.require_db()?;
+        self.require_task_write_access(task_id, requester_id)
+            .await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT ta.node_id, n.secrets_public_key
+            FROM task_assignments ta
+            JOIN nodes n ON n.node_id = ta.node_id
+            WHERE ta.task_id = $1
+              AND ta.disconnected_at IS NULL
+            ORDER BY ta.node_id
+            "#,
+        )
+        .bind(task_id)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TaskSecretRecipient {
+                node_id: row.get("node_id"),
+                secrets_public_key: row.get("secrets_public_key"),
+            })
+            .collect())
+    }
+
+    /// Replace the sealed secrets attached to a task.
+    ///
+    /// Every secret must target a node actively assigned to the task that
+    /// published a secrets key.  Ciphertext is stored as-is; the server never
+    /// holds a key that could open it.
+    pub async fn put_task_secrets(
+        &self,
+        task_id: Uuid,
+        upload: TaskSecretsUpload,
+        requester_id: Uuid,
+    ) -> ApiResult<usize> {
+        let db = self.require_db()?;
+        self.require_task_write_access(task_id, requester_id)
+            .await?;
+
+        let recipients: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT ta.node_id
+            FROM task_assignments ta
+            JOIN nodes n ON n.node_id = ta.node_id
+            WHERE ta.task_id = $1
+              AND ta.disconnected_at IS NULL
+              AND n.secrets_public_key IS NOT NULL
+            "#,
+        )
+        .bind(task_id)
+        .fetch_all(db)
+        .await?;
+
+        if let Some(secret) = upload
+            .secrets
+            .iter()
+            .find(|secret| !recipients.contains(&secret.node_id))
+        {
+            return Err(ApiError::bad_request(format!(
+                "Node {} is not an assigned node with a secrets key",
+                secret.node_id
+            )));
+        }
+
+        let mut tx = db.begin().await?;
+        sqlx::query("DELETE FROM task_sealed_secrets WHERE task_id = $1")
+            .bind(task_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for secret in &upload.secrets {
+            sqlx::query(
+                r#"
+                INSERT INTO task_sealed_secrets
+                    (task_id, node_id, name, ephemeral_public_key, nonce, ciphertext)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+            )
+            .bind(task_id)
+            .bind(&secret.node_id)
+            .bind(&secret.name)
+            .bind(&secret.ephemeral_public_key)
+            .bind(&secret.nonce)
+            .bind(&secret.ciphertext)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(upload.secrets.len())
+    }
+
+    /// Fetch the secrets sealed to `node_id` for a task.  The caller must own
+    /// the node and the node must still be actively assigned.
+    pub async fn get_task_secrets_for_node(
         &self,
+        task_id: Uuid,
         node_id: &str,
         owner_id: Uuid,
-    ) -> ApiResult<Vec<serde_json::Value>> {
-        let Some(db) = &self.db else {
-            return Ok(vec![]);
-        };
+    ) -> ApiResult<Vec<SealedTaskSecret>> {
+        let db = self.require_db()?;
 
-        // Verify node ownership
-        let exists: bool = sqlx::query_scalar(
-            r#"SELECT EXISTS(SELECT 1 FROM nodes WHERE node_id = $1 AND owner_id = $2 AND deleted_at IS NULL)"#,
+        let is_assigned: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM task_assignments ta
+                JOIN nodes n ON n.node_id = ta.node_id
+                WHERE ta.task_id = $1
+                  AND ta.node_id = $2
+                  AND ta.disconnected_at IS NULL
+                  AND (n.owner_id = $3 OR org_role_at_least(n.org_id, $3, 'member'))
+                  AND n.deleted_at IS NULL
+            )
+            "#,
         )
+        .bind(task_id)
         .bind(node_id)
         .bind(owner_id)
         .fetch_one(db)
         .await?;
 
-        if !exists {
-            return Err(ApiError::not_found_or_forbidden(format!(
-                "Node {} not found or you don't have permission to view it",
-                node_id
-            )));
+        if !is_assigned {
+            return Err(ApiError::not_found_or_forbidden(
+                "Node not found, not owned by you, or not assigned to this task",
+            ));
         }
 
         let rows = sqlx::query(
             r#"
-            SELECT metadata, recorded_at
-            FROM node_heartbeat_history
-            WHERE node_id = $1
-              AND status IN ('task_cleared', 'task_connected')
-            ORDER BY recorded_at DESC
-            LIMIT 50
+            SELECT node_id, name, ephemeral_public_key, nonce, ciphertext
+            FROM task_sealed_secrets
+            WHERE task_id = $1 AND node_id = $2
+            ORDER BY name
             "#,
         )
+        .bind(task_id)
         .bind(node_id)
         .fetch_all(db)
         .await?;
 
-        let events: Vec<serde_json::Value> = rows
+        Ok(rows
             .into_iter()
-            .filter_map(|row| {
-                let metadata: Option<serde_json::Value> = row.try_get("metadata").ok();
-                let recorded_at: chrono::DateTime<chrono::Utc> = row.try_get("recorded_at").ok()?;
-                metadata.map(|mut m| {
-                    m["cleared_at"] = serde_json::Value::String(recorded_at.to_rfc3339());
-                    m
-                })
+            .map(|row| SealedTaskSecret {
+                node_id: row.get("node_id"),
+                name: row.get("name"),
+                ephemeral_public_key: row.get("ephemeral_public_key"),
+                nonce: row.get("nonce"),
+                ciphertext: row.get("ciphertext"),
             })
-            .collect();
-
-        Ok(events)
+            .collect())
     }
 
-    /// Get a specific task from the database
-    pub async fn get_task(&self, task_id: &str, requester_id: Uuid) -> Option<TaskInfo> {
-        let Some(db) = &self.db else {
-            return None;
-        };
-
-        let task_uuid = match Uuid::parse_str(task_id) {
-            Ok(uuid) => uuid,
-            Err(_) => return None,
-        };
+    /// Record the newest checkpoint uploaded by a node actively assigned to
+    /// a checkpointable task.  Sequence numbers must increase so that a slow
+    /// upload from a drained node cannot overwrite a newer snapshot.
+    #[tracing::instrument(skip_all, fields(%task_id, %node_id, seq = seq))]
+    pub async fn store_task_checkpoint(
+        &self,
+        task_id: Uuid,
+        node_id: &str,
+        seq: u64,
+        bytes: &[u8],
+        owner_id: Uuid,
+    ) -> ApiResult<TaskCheckpointInfo> {
+        let db = self.require_db()?;
 
-        let result = sqlx::query(
+        let task = sqlx::query(
             r#"
-            SELECT 
-                t.task_id, t.task_type, t.status, t.result, t.proof_id,
-                t.created_at, t.updated_at,
-                COALESCE(
-                    (
-                        SELECT ARRAY_AGG(ta.node_id)
-                        FROM task_assignments ta
-                        WHERE ta.task_id = t.task_id
-                          AND ta.disconnected_at IS NULL
-                    ),
-                    ARRAY[]::VARCHAR[]
-                ) as assigned_nodes,
-                COALESCE(
-                    (
-                        SELECT ARRAY_AGG(ta.node_id)
-                        FROM task_assignments ta
-                        WHERE ta.task_id = t.task_id
-                          AND ta.disconnected_at IS NOT NULL
-                    ),
-                    ARRAY[]::VARCHAR[]
-                ) as former_assigned_nodes
+            SELECT
+                t.status,
+                t.checkpointable,
+                (SELECT c.seq FROM task_checkpoints c WHERE c.task_id = t.task_id) AS stored_seq
             FROM tasks t
+            JOIN task_assignments ta ON ta.task_id = t.task_id
+            JOIN nodes n ON n.node_id = ta.node_id
             WHERE t.task_id = $1
-              AND t.creator_id = $2
+              AND ta.node_id = $2
+              AND ta.disconnected_at IS NULL
+              AND (n.owner_id = $3 OR org_role_at_least(n.org_id, $3, 'member'))
+              AND n.deleted_at IS NULL
             "#,
         )
-        .bind(task_uuid)
-        .bind(requester_id)
+        .bind(task_id)
+        .bind(node_id)
+        .bind(owner_id)
         .fetch_optional(db)
-        .await;
-
-        match result {
-            Ok(Some(row)) => {
-                let task_id_uuid: Uuid = row.get("task_id");
-                let status_text: String = row.get("status");
-                if let Err(e) = self
-                    .notify_if_task_completed(task_id_uuid, &status_text)
-                    .await
-                {
-                    tracing::warn!("Failed to send completion email: {:?}", e);
-                }
+        .await?
+        .ok_or_else(|| {
+            ApiError::not_found_or_forbidden(
+                "Node not found, not owned by you, or not assigned to this task",
+            )
+        })?;
 
-                Some(TaskInfo {
-                    task_id: task_id_uuid.to_string(),
-                    task_type: row.get("task_type"),
-                    status: parse_task_status(&status_text),
-                    assigned_nodes: row.get::<Vec<String>, _>("assigned_nodes"),
-                    former_assigned_nodes: row.get::<Vec<String>, _>("former_assigned_nodes"),
-                    created_at: row
-                        .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
-                        .to_rfc3339(),
-                    updated_at: row
-                        .get::<chrono::DateTime<chrono::Utc>, _>("updated_at")
-                        .to_rfc3339(),
-                    result: row.try_get("result").ok(),
-                    proof_id: row.try_get("proof_id").ok(),
-                })
-            }
-            Ok(None) => None,
-            Err(e) => {
-                tracing::error!("Failed to get task {}: {:?}", task_id, e);
-                None
-            }
+        if !task.get::<bool, _>("checkpointable") {
+            return Err(ApiError::bad_request("Task is not checkpointable"));
         }
-    }
-
-    /// List all tasks from the database
-    pub async fn list_tasks(&self, requester_id: Uuid) -> Vec<TaskInfo> {
-        let Some(db) = &self.db else {
-            return vec![];
-        };
-
-        let result = sqlx::query(
+        if task.get::<String, _>("status") != "running" {
+            return Err(ApiError::conflict(
+                "Checkpoints can only be stored while the task is running",
+            ));
+        }
+        if bytes.is_empty() {
+            return Err(ApiError::bad_request("Checkpoint body is empty"));
+        }
+        let max_bytes = crate::artifacts::max_checkpoint_bytes();
+        if bytes.len() > max_bytes {
+            return Err(ApiError::bad_request(format!(
+                "Checkpoint exceeds the {max_bytes} byte limit"
+            )));
+        }
+        if seq == 0 || seq > i64::MAX as u64 {
+            return Err(ApiError::bad_request(
+                "Checkpoint seq must be a positive integer",
+            ));
+        }
+        let stale = |stored: i64| {
+            ApiError::conflict(format!(
+                "Checkpoint seq {seq} is not newer than stored seq {stored}"
+            ))
+        };
+        if let Some(stored) = task.get::<Option<i64>, _>("stored_seq") {
+            if seq as i64 <= stored {
+                return Err(stale(stored));
+            }
+        }
+
+        let checkpoint_hash = crate::artifacts::module_hash(bytes);
+        self.artifact_store
+            .put(&checkpoint_hash, bytes)
+            .await
+            .map_err(|err| {
+                tracing::error!(checkpoint_hash, "Failed to store task checkpoint: {err:#}");
+                ApiError::internal_error("Failed to store checkpoint")
+            })?;
+
+        // The WHERE clause closes the race between the check above and a
+        // concurrent upload of a newer snapshot.
+        let created_at: Option<chrono::DateTime<chrono::Utc>> = sqlx::query_scalar(
             r#"
-            SELECT 
-                t.task_id, t.task_type, t.status, t.result, t.proof_id,
-                t.created_at, t.updated_at,
-                COALESCE(
-                    (
-                        SELECT ARRAY_AGG(ta.node_id)
-                        FROM task_assignments ta
-                        WHERE ta.task_id = t.task_id
-                          AND ta.disconnected_at IS NULL
-                    ),
-                    ARRAY[]::VARCHAR[]
-                ) as assigned_nodes,
-                COALESCE(
-                    (
-                        SELECT ARRAY_AGG(ta.node_id)
-                        FROM task_assignments ta
-                        WHERE ta.task_id = t.task_id
-                          AND ta.disconnected_at IS NOT NULL
-                    ),
-                    ARRAY[]::VARCHAR[]
-                ) as former_assigned_nodes
-            FROM tasks t
-            WHERE t.creator_id = $1
-            ORDER BY t.created_at DESC
+            INSERT INTO task_checkpoints (task_id, node_id, seq, checkpoint_hash, size_bytes)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (task_id) DO UPDATE
+            SET node_id = EXCLUDED.node_id,
+                seq = EXCLUDED.seq,
+                checkpoint_hash = EXCLUDED.checkpoint_hash,
+                size_bytes = EXCLUDED.size_bytes,
+                created_at = NOW()
+            WHERE task_checkpoints.seq < EXCLUDED.seq
+            RETURNING created_at
             "#,
         )
-        .bind(requester_id)
-        .fetch_all(db)
-        .await;
+        .bind(task_id)
+        .bind(node_id)
+        .bind(seq as i64)
+        .bind(&checkpoint_hash)
+        .bind(bytes.len() as i64)
+        .fetch_optional(db)
+        .await?;
 
-        let tasks: Vec<TaskInfo> = match result {
-            Ok(rows) => rows
-                .into_iter()
-                .map(|row| TaskInfo {
-                    task_id: row.get::<Uuid, _>("task_id").to_string(),
-                    task_type: row.get("task_type"),
-                    status: parse_task_status(&row.get::<String, _>("status")),
-                    assigned_nodes: row.get::<Vec<String>, _>("assigned_nodes"),
-                    former_assigned_nodes: row.get::<Vec<String>, _>("former_assigned_nodes"),
-                    created_at: row
-                        .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
-                        .to_rfc3339(),
-                    updated_at: row
-                        .get::<chrono::DateTime<chrono::Utc>, _>("updated_at")
-                        .to_rfc3339(),
-                    result: row.try_get("result").ok(),
-                    proof_id: row.try_get("proof_id").ok(),
-                })
-                .collect(),
-            Err(e) => {
-                tracing::error!("Failed to list tasks: {:?}", e);
-                vec![]
-            }
+        let Some(created_at) = created_at else {
+            let stored: i64 =
+                sqlx::query_scalar("SELECT seq FROM task_checkpoints WHERE task_id = $1")
+                    .bind(task_id)
+                    .fetch_one(db)
+                    .await?;
+            return Err(stale(stored));
         };
 
-        for task in &tasks {
-            if task.status == TaskStatus::Completed {
-                if let Ok(task_uuid) = Uuid::parse_str(&task.task_id) {
-                    if let Err(e) = self.notify_if_task_completed(task_uuid, "completed").await {
-                        tracing::warn!("Failed to send completion email: {:?}", e);
-                    }
-                }
-            }
-        }
-
-        tasks
+        Ok(TaskCheckpointInfo {
+            task_id: task_id.to_string(),
+            node_id: node_id.to_string(),
+            seq,
+            sha3_256: checkpoint_hash,
+            size_bytes: bytes.len() as u64,
+            created_at: created_at.to_rfc3339(),
+        })
     }
 
-    async fn notify_if_task_completed(&self, task_id: Uuid, status: &str) -> ApiResult<()> {
-        let Ok(db) = self.require_db() else {
-            return Ok(());
-        };
-
-        if status != "completed" {
-            return Ok(());
-        }
+    /// The newest checkpoint of a task, for a node actively assigned to it
+    /// that is resuming the work.  Bytes are re-hashed on the way out.
+    pub async fn get_task_checkpoint(
+        &self,
+        task_id: Uuid,
+        node_id: &str,
+        owner_id: Uuid,
+    ) -> ApiResult<Option<(TaskCheckpointInfo, Vec<u8>)>> {
+        let db = self.require_db()?;
 
         let row = sqlx::query(
             r#"
-            SELECT t.result, u.email
-            FROM tasks t
-            LEFT JOIN users u ON u.user_id = t.creator_id
-            WHERE t.task_id = $1
-              AND t.status = 'completed'
-              AND t.completion_email_sent_at IS NULL
+            SELECT c.node_id, c.seq, c.checkpoint_hash, c.size_bytes, c.created_at
+            FROM task_assignments ta
+            JOIN nodes n ON n.node_id = ta.node_id
+            LEFT JOIN task_checkpoints c ON c.task_id = ta.task_id
+            WHERE ta.task_id = $1
+              AND ta.node_id = $2
+              AND ta.disconnected_at IS NULL
+              AND (n.owner_id = $3 OR org_role_at_least(n.org_id, $3, 'member'))
+              AND n.deleted_at IS NULL
             "#,
         )
         .bind(task_id)
+        .bind(node_id)
+        .bind(owner_id)
         .fetch_optional(db)
-        .await?;
+        .await?
+        .ok_or_else(|| {
+            ApiError::not_found_or_forbidden(
+                "Node not found, not owned by you, or not assigned to this task",
+            )
+        })?;
 
-        let Some(row) = row else {
-            return Ok(());
+        let Some(checkpoint_hash) = row.get::<Option<String>, _>("checkpoint_hash") else {
+            return Ok(None);
         };
 
-        let email = row.try_get::<Option<String>, _>("email").ok().flatten();
-        let Some(email) = email.filter(|v| !v.trim().is_empty()) else {
-            return Ok(());
-        };
+        let bytes = self
+            .artifact_store
+            .get(&checkpoint_hash)
+            .await
+            .map_err(|err| {
+                tracing::error!(checkpoint_hash, "Failed to read task checkpoint: {err:#}");
+                ApiError::internal_error("Failed to read checkpoint")
+            })?
+            .ok_or_else(|| {
+                tracing::error!(
+                    checkpoint_hash,
+                    "Task checkpoint missing from artifact store"
+                );
+                ApiError::internal_error("Stored checkpoint is missing")
+            })?;
 
-        let result_payload = row
-            .try_get::<Option<serde_json::Value>, _>("result")
-            .ok()
-            .flatten()
-            .unwrap_or(serde_json::json!({"message": "Task completed"}));
+        if crate::artifacts::module_hash(&bytes) != checkpoint_hash {
+            tracing::error!(
+                checkpoint_hash,
+                "Stored task checkpoint failed hash verification"
+            );
+            return Err(ApiError::internal_error(
+                "Stored checkpoint failed integrity check",
+            ));
+        }
 
-        self.send_completion_email(&email, task_id, &result_payload)
-            .await?;
+        Ok(Some((
+            TaskCheckpointInfo {
+                task_id: task_id.to_string(),
+                node_id: row.get("node_id"),
+                seq: row.get::<i64, _>("seq") as u64,
+                sha3_256: checkpoint_hash,
+                size_bytes: row.get::<i64, _>("size_bytes") as u64,
+                created_at: row
+                    .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
+                    .to_rfc3339(),
+            },
+            bytes,
+        )))
+    }
 
-        sqlx::query(
+    /// Store log lines from a node actively assigned to the task.
+    ///
+    /// Returns how many lines were kept; lines beyond `MAX_TASK_LOG_LINES`
+    /// per task are dropped.
+    pub async fn append_task_logs(
+        &self,
+        task_id: Uuid,
+        batch: TaskLogBatch,
+        owner_id: Uuid,
+    ) -> ApiResult<usize> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+
+        // Lock the task row so concurrent batches cannot overshoot the cap.
+        let stored: Option<i64> = sqlx::query_scalar(
             r#"
-            UPDATE tasks
-            SET completion_email_sent_at = NOW()
-            WHERE task_id = $1
+            SELECT (SELECT COUNT(*) FROM task_logs WHERE task_id = t.task_id)
+            FROM tasks t
+            JOIN task_assignments ta ON ta.task_id = t.task_id
+            JOIN nodes n ON n.node_id = ta.node_id
+            WHERE t.task_id = $1
+              AND ta.node_id = $2
+              AND ta.disconnected_at IS NULL
+              AND (n.owner_id = $3 OR org_role_at_least(n.org_id, $3, 'member'))
+              AND n.deleted_at IS NULL
+            FOR UPDATE OF t
             "#,
         )
         .bind(task_id)
-        .execute(db)
+        .bind(&batch.node_id)
+        .bind(owner_id)
+        .fetch_optional(&mut *tx)
         .await?;
 
-        Ok(())
+        let Some(stored) = stored else {
+            return Err(ApiError::not_found_or_forbidden(
+                "Node not found, not owned by you, or not assigned to this task",
+            ));
+        };
+
+        let room = (MAX_TASK_LOG_LINES - stored).max(0) as usize;
+        let (levels, messages): (Vec<String>, Vec<String>) = batch
+            .lines
+            .into_iter()
+            .take(room)
+            .map(|line| (line.level, line.message))
+            .unzip();
+
+        if !levels.is_empty() {
+            sqlx::query(
+                r#"
+                INSERT INTO task_logs (task_id, node_id, level, message)
+                SELECT $1, $2, level, message
+                FROM UNNEST($3::VARCHAR[], $4::TEXT[]) WITH ORDINALITY AS l(level, message, n)
+                ORDER BY n
+                "#,
+            )
+            .bind(task_id)
+            .bind(&batch.node_id)
+            .bind(&levels)
+            .bind(&messages)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(levels.len())
     }
 
-    async fn send_completion_email(
+    /// Log lines after `after_log_id` for a task the requester created, plus
+    /// whether the task has finished (no further lines are expected).
+    pub async fn list_task_logs(
         &self,
-        recipient: &str,
         task_id: Uuid,
-        result: &serde_json::Value,
-    ) -> ApiResult<()> {
-        use lettre::message::Mailbox;
-        use lettre::Message;
-        use tokio::io::AsyncWriteExt;
-        use tokio::process::Command;
-
-        let sender = std::env::var("EMAIL_FROM")
-            .map_err(|_| ApiError::internal_error("EMAIL_FROM not configured"))?;
+        requester_id: Uuid,
+        after_log_id: i64,
+        limit: i64,
+    ) -> ApiResult<(Vec<TaskLogEntry>, bool)> {
+        let db = self.require_db()?;
 
-        if sender.contains('\r') || sender.contains('\n') {
-            return Err(ApiError::internal_error(
-                "EMAIL_FROM cannot contain carriage return or newline characters",
-            ));
-        }
+        let status: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT status FROM tasks
+            WHERE task_id = $1
+              AND deleted_at IS NULL
+              AND (creator_id = $2 OR org_role_at_least(org_id, $2, 'viewer'))
+            "#,
+        )
+        .bind(task_id)
+        .bind(requester_id)
+        .fetch_optional(db)
+        .await?;
 
-        if recipient.contains('\r') || recipient.contains('\n') {
-            return Err(ApiError::validation_error(
-                "Email cannot contain carriage return or newline characters",
+        let Some(status) = status else {
+            return Err(ApiError::not_found_or_forbidden(
+                "Task not found or not owned by you",
             ));
-        }
+        };
 
-        let sender_mailbox = sender
-            .parse::<Mailbox>()
-            .map_err(|_| ApiError::internal_error("EMAIL_FROM must be a valid email address"))?;
-        let recipient_mailbox = recipient
-            .parse::<Mailbox>()
-            .map_err(|_| ApiError::validation_error("Email must be a valid address"))?;
+        let rows = sqlx::query(
+            r#"
+            SELECT log_id, node_id, level, message, logged_at
+            FROM task_logs
+            WHERE task_id = $1 AND log_id > $2
+            ORDER BY log_id
+            LIMIT $3
+            "#,
+        )
+        .bind(task_id)
+        .bind(after_log_id)
+        .bind(limit)
+        .fetch_all(db)
+        .await?;
 
-        let subject = format!("Task Completed: {task_id}");
-        let body = format!(
-            "Your task {task_id} has completed.\n\nResult:\n{}",
-            serde_json::to_string_pretty(result)
-                .unwrap_or_else(|_| "<failed to serialize task result>".to_string())
-        );
+        let entries = rows
+            .into_iter()
+            .map(|row| TaskLogEntry {
+                log_id: row.get("log_id"),
+                node_id: row.get("node_id"),
+                level: row.get("level"),
+                message: row.get("message"),
+                logged_at: row
+                    .get::<chrono::DateTime<chrono::Utc>, _>("logged_at")
+                    .to_rfc3339(),
+            })
+            .collect();
 
-        let message = Message::builder()
-            .from(sender_mailbox)
-            .to(recipient_mailbox)
-            .subject(subject)
-            .body(body)
-            .map_err(|_| ApiError::internal_error("Failed to build completion email message"))?;
-
-        let mut child = Command::new("sendmail")
-            .arg("-t")
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|_| ApiError::internal_error("sendmail command is not available"))?;
-
-        if let Some(stdin) = child.stdin.as_mut() {
-            stdin
-                .write_all(&message.formatted())
-                .await
-                .map_err(|_| ApiError::internal_error("Failed writing message to sendmail"))?;
-        }
+        Ok((
+            entries,
+            matches!(status.as_str(), "completed" | "failed" | "unschedulable"),
+        ))
+    }
 
-        let output = child
-            .wait_with_output()
-            .await
-            .map_err(|_| ApiError::internal_error("Failed waiting for sendmail completion"))?;
+    /// Fail unless the requester created the task or is at least a member of
+    /// the organization that owns it.
+    async fn require_task_write_access(&self, task_id: Uuid, requester_id: Uuid) -> ApiResult<()> {
+        let db = self.require_db()?;
+        let has_access: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM tasks
+                WHERE task_id = $1
+                  AND deleted_at IS NULL
+                  AND (creator_id = $2 OR org_role_at_least(org_id, $2, 'member'))
+            )
+            "#,
+        )
+        .bind(task_id)
+        .bind(requester_id)
+        .fetch_one(db)
+        .await?;
 
-        if !output.status.success() {
-            return Err(ApiError::internal_error(format!(
-                "sendmail failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            )));
+        if !has_access {
+            return Err(ApiError::not_found_or_forbidden(
+                "Task not found or not owned by you",
+            ));
         }
-
         Ok(())
     }
 
-    /// Verify a ZK proof using actual cryptographic verification
-    pub async fn verify_proof(
+    /// Create an organization owned by `owner_id`.
+    pub async fn create_organization(
         &self,
-        request: ProofVerificationRequest,
-    ) -> ApiResult<ProofVerificationResponse> {
-        use std::time::Instant;
-        use zk_prover::{ZKProof, ZKVerifier};
-
-        // Validate the request
-        request.validate()?;
-
-        let start = Instant::now();
-
-        // Decode proof data and public inputs
-        let proof_data = request.decode_proof_data()?;
-        let public_inputs_data = request.decode_public_inputs()?;
-
-        // Create ZK proof object
-        let circuit_id = request.circuit_id.unwrap_or_else(|| "default".to_string());
-        let proof = ZKProof::new(proof_data, public_inputs_data.clone(), circuit_id);
-
-        // Verify proof size constraints
-        if proof.size() > 75_000 {
-            // 75KB max decoded proof size
-            return Ok(ProofVerificationResponse {
-                valid: false,
-                task_id: request.task_id,
-                verified_at: chrono::Utc::now().to_rfc3339(),
-                verification_time_ms: start.elapsed().as_millis() as u64,
-                error_message: Some("Proof size exceeds maximum allowed size".to_string()),
-            });
-        }
+        request: CreateOrganizationRequest,
+        owner_id: Uuid,
+    ) -> ApiResult<OrganizationInfo> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
 
-        // Initialize verifier with default verification key
-        // In production, you would load the appropriate verification key based on circuit_id
-        let verifier = ZKVerifier::default();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO organizations (name, created_by)
+            VALUES ($1, $2)
+            RETURNING org_id, created_at
+            "#,
+        )
+        .bind(request.name.trim())
+        .bind(owner_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let org_id: Uuid = row.get("org_id");
 
-        // Perform cryptographic verification off the async runtime worker.
-        let public_inputs_for_verify = public_inputs_data.clone();
-        let valid = tokio::task::spawn_blocking(move || {
-            verifier.verify_proof(&proof, &public_inputs_for_verify)
-        })
-        .await
-        .map_err(|_| crate::error::ApiError::internal_error("Proof verification task failed"))?;
+        sqlx::query(
+            "INSERT INTO organization_members (org_id, user_id, role) VALUES ($1, $2, 'owner')",
+        )
+        .bind(org_id)
+        .bind(owner_id)
+        .execute(&mut *tx)
+        .await?;
 
-        let verification_time_ms = start.elapsed().as_millis() as u64;
+        tx.commit().await?;
 
-        Ok(ProofVerificationResponse {
-            valid,
-            task_id: request.task_id,
-            verified_at: chrono::Utc::now().to_rfc3339(),
-            verification_time_ms,
-            error_message: if !valid {
-                Some("Proof verification failed: invalid proof or public inputs".to_string())
-            } else {
-                None
-            },
+        Ok(OrganizationInfo {
+            org_id: org_id.to_string(),
+            name: request.name.trim().to_string(),
+            role: OrgRole::Owner,
+            created_at: row
+                .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
+                .to_rfc3339(),
         })
     }
 
-    /// Get cluster statistics from the database
-    pub async fn get_cluster_stats(&self) -> ClusterStats {
-        let Some(db) = &self.db else {
-            return ClusterStats {
-                total_nodes: 0,
-                healthy_nodes: 0,
-                total_tasks: 0,
-                completed_tasks: 0,
-                failed_tasks: 0,
-                avg_health_score: 0.0,
-                total_compute_capacity: 0.0,
-            };
-        };
-
-        // Get node statistics
-        let node_stats = sqlx::query(
-            r#"
-            SELECT 
-                COUNT(*) FILTER (WHERE status != 'rejected') as total_nodes,
-                COUNT(*) FILTER (WHERE status = 'online' AND health_score >= 70.0) as healthy_nodes,
-                COALESCE(AVG(health_score) FILTER (WHERE status != 'rejected'), 0.0) as avg_health_score,
-                COALESCE(SUM(cpu_cores * memory_gb) FILTER (WHERE status != 'rejected'), 0.0) as total_compute_capacity
-            FROM nodes
-            WHERE deleted_at IS NULL
-            "#,
-        )
-        .fetch_one(db)
-        .await;
-
-        // Get task statistics
-        let task_stats = sqlx::query(
+    /// Organizations `user_id` belongs to, with their role in each.
+    pub async fn list_user_organizations(&self, user_id: Uuid) -> ApiResult<Vec<OrganizationInfo>> {
+        let db = self.require_db()?;
+        let rows = sqlx::query(
             r#"
-            SELECT 
-                COUNT(*) as total_tasks,
-                COUNT(*) FILTER (WHERE status = 'completed') as completed_tasks,
-                COUNT(*) FILTER (WHERE status = 'failed') as failed_tasks
-            FROM tasks
+            SELECT o.org_id, o.name, o.created_at, m.role
+            FROM organization_members m
+            JOIN organizations o ON o.org_id = m.org_id
+            WHERE m.user_id = $1
+            ORDER BY o.name
             "#,
         )
-        .fetch_one(db)
-        .await;
+        .bind(user_id)
+        .fetch_all(db)
+        .await?;
 
-        match (node_stats, task_stats) {
-            (Ok(nodes), Ok(tasks)) => ClusterStats {
-                total_nodes: nodes.get::<i64, _>("total_nodes") as usize,
-                healthy_nodes: nodes.get::<i64, _>("healthy_nodes") as usize,
-                total_tasks: tasks.get::<i64, _>("total_tasks") as usize,
-                completed_tasks: tasks.get::<i64, _>("completed_tasks") as usize,
-                failed_tasks: tasks.get::<i64, _>("failed_tasks") as usize,
-                avg_health_score: nodes.get("avg_health_score"),
-                total_compute_capacity: nodes.get("total_compute_capacity"),
-            },
-            _ => {
-                tracing::error!("Failed to get cluster stats");
-                ClusterStats {
-                    total_nodes: 0,
-                    healthy_nodes: 0,
-                    total_tasks: 0,
-                    completed_tasks: 0,
-                    failed_tasks: 0,
-                    avg_health_score: 0.0,
-                    total_compute_capacity: 0.0,
-                }
-            }
-        }
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(OrganizationInfo {
+                    org_id: row.get::<Uuid, _>("org_id").to_string(),
+                    name: row.get("name"),
+                    role: OrgRole::parse(row.get("role"))?,
+                    created_at: row
+                        .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
+                        .to_rfc3339(),
+                })
+            })
+            .collect())
     }
 
-    /// Check if a user owns a specific node
-    pub async fn check_node_ownership(&self, node_id: &str, user_id: Uuid) -> ApiResult<bool> {
+    /// `user_id`'s role in `org_id`, or `None` if they are not a member.
+    pub async fn org_role(&self, org_id: Uuid, user_id: Uuid) -> ApiResult<Option<OrgRole>> {
         let db = self.require_db()?;
-        let result = sqlx::query_scalar::<_, i64>(
-            r#"
-            SELECT COUNT(*)
-            FROM nodes
-            WHERE node_id = $1 AND owner_id = $2 AND deleted_at IS NULL
-            "#,
+        let role: Option<String> = sqlx::query_scalar(
+            "SELECT role FROM organization_members WHERE org_id = $1 AND user_id = $2",
         )
-        .bind(node_id)
+        .bind(org_id)
         .bind(user_id)
-        .fetch_one(db)
+        .fetch_optional(db)
         .await?;
-
-        Ok(result > 0)
+        Ok(role.as_deref().and_then(OrgRole::parse))
     }
 
-    /// Soft delete a node (sets deleted_at timestamp)
-    pub async fn delete_node(&self, node_id: &str, owner_id: Uuid) -> ApiResult<bool> {
-        let db = self.require_db()?;
-        // Verify ownership
-        if !self.check_node_ownership(node_id, owner_id).await? {
-            return Ok(false);
+    /// Fail unless `user_id` holds at least `required` in `org_id`.
+    pub async fn require_org_role(
+        &self,
+        org_id: Uuid,
+        user_id: Uuid,
+        required: OrgRole,
+    ) -> ApiResult<OrgRole> {
+        match self.org_role(org_id, user_id).await? {
+            Some(role) if role.at_least(required) => Ok(role),
+            Some(_) => Err(ApiError::forbidden(format!(
+                "This action requires the {} role in the organization",
+                required
+            ))),
+            None => Err(ApiError::not_found_or_forbidden(
+                "Organization not found or you are not a member",
+            )),
         }
+    }
 
-        let now = chrono::Utc::now();
-
-        // Start a transaction to ensure atomicity
-        let mut tx = db.begin().await?;
+    pub async fn list_org_members(
+        &self,
+        org_id: Uuid,
+        requester_id: Uuid,
+    ) -> ApiResult<Vec<OrganizationMember>> {
+        self.require_org_role(org_id, requester_id, OrgRole::Viewer)
+            .await?;
+        let db = self.require_db()?;
 
-        // Mark the node as deleted
-        let result = sqlx::query(
+        let rows = sqlx::query(
             r#"
-            UPDATE nodes
-            SET deleted_at = $1, status = 'offline', updated_at = $1
-            WHERE node_id = $2 AND owner_id = $3 AND deleted_at IS NULL
+            SELECT m.user_id, u.username, m.role, m.joined_at
+            FROM organization_members m
+            JOIN users u ON u.user_id = m.user_id
+            WHERE m.org_id = $1
+            ORDER BY u.username
             "#,
         )
-        .bind(now)
-        .bind(node_id)
-        .bind(owner_id)
-        .execute(&mut *tx)
+        .bind(org_id)
+        .fetch_all(db)
         .await?;
 
-        if result.rows_affected() == 0 {
-            tx.rollback().await?;
-            return Ok(false);
-        }
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(OrganizationMember {
+                    user_id: row.get::<Uuid, _>("user_id").to_string(),
+                    username: row.get("username"),
+                    role: OrgRole::parse(row.get("role"))?,
+                    joined_at: row
+                        .get::<chrono::DateTime<chrono::Utc>, _>("joined_at")
+                        .to_rfc3339(),
+                })
+            })
+            .collect())
+    }
 
-        // Get all tasks affected by this node deletion
-        let affected_tasks = sqlx::query(
+    /// Add `request.username` to the organization or change their role.
+    ///
+    /// Admins manage non-owner roles; only owners grant or revoke ownership,
+    /// and the last owner cannot be demoted.
+    pub async fn set_org_member(
+        &self,
+        org_id: Uuid,
+        requester_id: Uuid,
+        request: SetOrganizationMemberRequest,
+    ) -> ApiResult<OrganizationMember> {
+        let requester_role = self
+            .require_org_role(org_id, requester_id, OrgRole::Admin)
+            .await?;
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+
+        // Serialize membership changes per org so the last-owner check holds.
+        sqlx::query("SELECT 1 FROM organizations WHERE org_id = $1 FOR UPDATE")
+            .bind(org_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let target = sqlx::query(
             r#"
-            SELECT ta.task_id, t.min_nodes
-            FROM task_assignments ta
-            JOIN tasks t ON t.task_id = ta.task_id
-            WHERE ta.node_id = $1
-              AND ta.disconnected_at IS NULL
+            SELECT u.user_id, m.role
+            FROM users u
+            LEFT JOIN organization_members m ON m.user_id = u.user_id AND m.org_id = $2
+            WHERE u.username = $1
             "#,
         )
-        .bind(node_id)
-        .fetch_all(&mut *tx)
-        .await?;
+        .bind(&request.username)
+        .bind(org_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+
+        let target_id: Uuid = target.get("user_id");
+        let current_role = target
+            .get::<Option<String>, _>("role")
+            .as_deref()
+            .and_then(OrgRole::parse);
+
+        if !requester_role.can_assign(current_role, request.role) {
+            return Err(ApiError::forbidden(
+                "Only organization owners can grant or revoke the owner role",
+            ));
+        }
+        if current_role == Some(OrgRole::Owner) && request.role != OrgRole::Owner {
+            Self::ensure_other_owner(&mut tx, org_id, target_id).await?;
+        }
 
-        // Mark all task assignments from this node as disconnected,
-        // and fail any that were in_progress so the status history is accurate.
-        sqlx::query(
+        let joined_at: chrono::DateTime<chrono::Utc> = sqlx::query_scalar(
             r#"
-            UPDATE task_assignments
-            SET disconnected_at = $1,
-                execution_status = CASE
-                    WHEN execution_status = 'in_progress' THEN 'failed'
-                    ELSE execution_status
-                END
-            WHERE node_id = $2
-              AND disconnected_at IS NULL
+            INSERT INTO organization_members (org_id, user_id, role)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (org_id, user_id) DO UPDATE SET role = EXCLUDED.role
+            RETURNING joined_at
             "#,
         )
-        .bind(now)
-        .bind(node_id)
-        .execute(&mut *tx)
+        .bind(org_id)
+        .bind(target_id)
+        .bind(request.role.as_str())
+        .fetch_one(&mut *tx)
         .await?;
 
         tx.commit().await?;
 
-        // For each affected task, update its status and attempt reassignment
-        for task_row in affected_tasks {
-            let task_id: Uuid = task_row.get("task_id");
-            let min_nodes: i32 = task_row.get("min_nodes");
+        Ok(OrganizationMember {
+            user_id: target_id.to_string(),
+            username: request.username,
+            role: request.role,
+            joined_at: joined_at.to_rfc3339(),
+        })
+    }
 
-            // Update task status based on remaining connected nodes
-            self.update_task_status_from_assignments(task_id, min_nodes as u32)
-                .await?;
+    /// Remove `member_id` from the organization.  Members may always leave;
+    /// removing others requires admin, and removing an owner requires owner.
+    pub async fn remove_org_member(
+        &self,
+        org_id: Uuid,
+        requester_id: Uuid,
+        member_id: Uuid,
+    ) -> ApiResult<bool> {
+        let required = if member_id == requester_id {
+            OrgRole::Viewer
+        } else {
+            OrgRole::Admin
+        };
+        let requester_role = self
+            .require_org_role(org_id, requester_id, required)
+            .await?;
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
 
-            // Try to assign the task to other available nodes
-            if let Ok(Some(task_status)) = self.get_task_status(task_id).await {
-                if task_status == "pending" {
-                    // Get task details for reassignment
-                    let task_details = sqlx::query(
-                        r#"
-                        SELECT task_type, require_gpu
-                        FROM tasks
-                        WHERE task_id = $1
-                        "#,
-                    )
-                    .bind(task_id)
-                    .fetch_optional(db)
-                    .await?;
+        sqlx::query("SELECT 1 FROM organizations WHERE org_id = $1 FOR UPDATE")
+            .bind(org_id)
+            .execute(&mut *tx)
+            .await?;
 
-                    if let Some(task_row) = task_details {
-                        let task_type: String = task_row.get("task_type");
-                        let require_gpu: bool = task_row.get("require_gpu");
+        let member_role: Option<String> = sqlx::query_scalar(
+            "SELECT role FROM organization_members WHERE org_id = $1 AND user_id = $2",
+        )
+        .bind(org_id)
+        .bind(member_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(member_role) = member_role.as_deref().and_then(OrgRole::parse) else {
+            return Ok(false);
+        };
 
-                        if let Some(task_registry_entry) = task_type_registry_entry(&task_type) {
-                            // Try to find and assign alternative nodes
-                            let _ = self
-                                .assign_available_nodes_for_task(
-                                    task_id,
-                                    &task_type,
-                                    task_registry_entry,
-                                    min_nodes as u32,
-                                    require_gpu,
-                                )
-                                .await;
-                        }
-                    }
-                }
-            } else {
-                tracing::warn!(
-                    task_id = %task_id,
-                    "Failed to retrieve task status during node deletion; skipping automatic reassignment for this task"
-                );
+        if member_role == OrgRole::Owner {
+            if requester_role != OrgRole::Owner {
+                return Err(ApiError::forbidden(
+                    "Only organization owners can remove an owner",
+                ));
             }
+            Self::ensure_other_owner(&mut tx, org_id, member_id).await?;
         }
 
+        sqlx::query("DELETE FROM organization_members WHERE org_id = $1 AND user_id = $2")
+            .bind(org_id)
+            .bind(member_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
         Ok(true)
     }
 
-    /// Update node heartbeat timestamp and record activity history.
-    ///
-    /// On success returns `Some(NodeHeartbeatResult)` containing the current
-    /// active task count, the enriched task list (with `task_type` so the node
-    /// knows which assignments are `connect_only` and require gateway-mode),
-    /// the node's `health_score`, and its `node_status`.
-    ///
-    /// Also advances `execution_status` from `'assigned'` → `'in_progress'` for
-    /// every task the node is currently connected to, registering the moment the
-    /// node confirmed it is actively working.
-    ///
-    /// Returns `None` when the node is not found or does not belong to `owner_id`.
-    pub async fn update_node_heartbeat(
-        &self,
-        node_id: &str,
-        owner_id: Uuid,
-    ) -> ApiResult<Option<NodeHeartbeatResult>> {
-        let db = self.require_db()?;
-        // Fetch current node state (also verifies ownership and existence)
-        let node_row = sqlx::query(
+    async fn ensure_other_owner(
+        conn: &mut sqlx::PgConnection,
+        org_id: Uuid,
+        leaving_owner: Uuid,
+    ) -> ApiResult<()> {
+        let other_owners: i64 = sqlx::query_scalar(
             r#"
-            SELECT health_score, status
-            FROM nodes
-            WHERE node_id = $1 AND owner_id = $2 AND deleted_at IS NULL
+            SELECT COUNT(*)
+            FROM organization_members
+            WHERE org_id = $1 AND role = 'owner' AND user_id != $2
             "#,
         )
-        .bind(node_id)
-        .bind(owner_id)
-        .fetch_optional(db)
+        .bind(org_id)
+        .bind(leaving_owner)
+        .fetch_one(&mut *conn)
         .await?;
 
-        let Some(node_row) = node_row else {
-            return Ok(None);
-        };
+        if other_owners == 0 {
+            return Err(ApiError::bad_request(
+                "An organization must keep at least one owner",
+            ));
+        }
+        Ok(())
+    }
 
-        let health_score: f64 = node_row.get("health_score");
-        let status: String = node_row.get("status");
+    /// Get recent task activity events (task_cleared and task_connected) from heartbeat history for a node
+    pub async fn get_node_cleared_task_events(
+        &self,
+        node_id: &str,
+        owner_id: Uuid,
+    ) -> ApiResult<Vec<serde_json::Value>> {
+        let Some(db) = &self.db else {
+            return Ok(vec![]);
+        };
 
-        let now = chrono::Utc::now();
-        let result = sqlx::query(
-            r#"
-            UPDATE nodes
-            SET last_heartbeat = $1, last_seen = $1, updated_at = $1
-            WHERE node_id = $2 AND owner_id = $3 AND deleted_at IS NULL
-            "#,
+        // Verify node ownership
+        let exists: bool = sqlx::query_scalar(
+            r#"SELECT EXISTS(SELECT 1 FROM nodes WHERE node_id = $1 AND (owner_id = $2 OR org_role_at_least(org_id, $2, 'viewer')) AND deleted_at IS NULL)"#,
         )
-        .bind(now)
         .bind(node_id)
         .bind(owner_id)
-        .execute(db)
+        .fetch_one(db)
         .await?;
 
-        if result.rows_affected() == 0 {
-            return Ok(None);
-        }
-
-        // Count currently active task assignments for this node (pre-assignment snapshot
-        // for the heartbeat history record).
-        let active_tasks_before: i64 = sqlx::query_scalar(
+        if !exists {
+            return Err(ApiError::not_found_or_forbidden(format!(
+                "Node {} not found or you don't have permission to view it",
+                node_id
+            )));
+        }
+
+        let rows = sqlx::query(
             r#"
-            SELECT COUNT(*)
-            FROM task_assignments
-            WHERE node_id = $1 AND disconnected_at IS NULL
+            SELECT metadata, recorded_at
+            FROM node_heartbeat_history
+            WHERE node_id = $1
+              AND status IN ('task_cleared', 'task_connected')
+            ORDER BY recorded_at DESC
+            LIMIT 50
             "#,
         )
         .bind(node_id)
+        .fetch_all(db)
+        .await?;
+
+        let events: Vec<serde_json::Value> = rows
+            .into_iter()
+            .filter_map(|row| {
+                let metadata: Option<serde_json::Value> = row.try_get("metadata").ok();
+                let recorded_at: chrono::DateTime<chrono::Utc> = row.try_get("recorded_at").ok()?;
+                metadata.map(|mut m| {
+                    m["cleared_at"] = serde_json::Value::String(recorded_at.to_rfc3339());
+                    m
+                })
+            })
+            .collect();
+
+        Ok(events)
+    }
+
+    /// Telemetry series for a node the requester owns or can view.
+    ///
+    /// Buckets already rolled up come from `node_telemetry_rollups`; later
+    /// buckets are aggregated from raw heartbeat samples.
+    pub async fn get_node_telemetry(
+        &self,
+        node_id: &str,
+        requester_id: Uuid,
+        window: crate::telemetry::TelemetryWindow,
+    ) -> ApiResult<crate::telemetry::NodeTelemetryResponse> {
+        use crate::telemetry::{TelemetryPoint, MAX_TELEMETRY_POINTS};
+
+        let db = self.read_db()?;
+
+        let visible: bool = sqlx::query_scalar(
+            r#"SELECT EXISTS(SELECT 1 FROM nodes WHERE node_id = $1 AND (owner_id = $2 OR org_role_at_least(org_id, $2, 'viewer')) AND deleted_at IS NULL)"#,
+        )
+        .bind(node_id)
+        .bind(requester_id)
         .fetch_one(db)
         .await?;
 
-        // Record heartbeat activity in history table
-        sqlx::query(
+        if !visible {
+            return Err(ApiError::not_found_or_forbidden(format!(
+                "Node {} not found or you don't have permission to view it",
+                node_id
+            )));
+        }
+
+        let rows = match window.resolution.bucket_secs() {
+            None => {
+                sqlx::query(&crate::telemetry::raw_series_sql())
+                    .bind(node_id)
+                    .bind(window.from)
+                    .bind(window.to)
+                    .bind(MAX_TELEMETRY_POINTS)
+                    .fetch_all(db)
+                    .await?
+            }
+            Some(width) => {
+                let last_rollup: Option<chrono::DateTime<chrono::Utc>> = sqlx::query_scalar(
+                    r#"
+                    SELECT MAX(bucket_start)
+                    FROM node_telemetry_rollups
+                    WHERE node_id = $1 AND resolution = $2
+                    "#,
+                )
+                .bind(node_id)
+                .bind(window.resolution.as_str())
+                .fetch_one(db)
+                .await?;
+                let raw_from = last_rollup
+                    .map(|bucket| bucket + chrono::Duration::seconds(width))
+                    .map_or(window.from, |next| next.max(window.from));
+
+                let mut rows = sqlx::query(&crate::telemetry::rollup_series_sql())
+                    .bind(node_id)
+                    .bind(window.resolution.as_str())
+                    .bind(window.from)
+                    .bind(raw_from.min(window.to))
+                    .fetch_all(db)
+                    .await?;
+                if raw_from < window.to {
+                    rows.extend(
+                        sqlx::query(&crate::telemetry::raw_bucket_series_sql())
+                            .bind(node_id)
+                            .bind(width)
+                            .bind(raw_from)
+                            .bind(window.to)
+                            .fetch_all(db)
+                            .await?,
+                    );
+                }
+                rows
+            }
+        };
+
+        let points = rows
+            .iter()
+            .map(TelemetryPoint::from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(crate::telemetry::NodeTelemetryResponse {
+            node_id: node_id.to_string(),
+            resolution: window.resolution,
+            from: window.from,
+            to: window.to,
+            points,
+        })
+    }
+
+    /// Get a specific task from the database
+    #[tracing::instrument(skip_all, fields(%task_id))]
+    pub async fn get_task(&self, task_id: &str, requester_id: Uuid) -> Option<TaskInfo> {
+        let Some(db) = &self.db else {
+            return None;
+        };
+
+        let task_uuid = match Uuid::parse_str(task_id) {
+            Ok(uuid) => uuid,
+            Err(_) => return None,
+        };
+
+        let result = sqlx::query(&format!(
+            r#"
+            SELECT 
+                t.task_id, t.task_type, t.status, t.result, t.proof_id,
+                t.created_at, t.updated_at, t.priority, t.retry_count, t.last_error, t.egress,
+                t.constraints_relaxed_at IS NOT NULL AS constraints_relaxed, t.starving_at,
+                t.scheduling_diagnostics, t.checkpointable, t.node_selector, t.traceparent,
+                t.diversity, t.preferred_regions, t.excluded_regions, t.region_strictness,
+                (SELECT c.seq FROM task_checkpoints c WHERE c.task_id = t.task_id) AS checkpoint_seq,
+                COALESCE(
+                    (
+                        SELECT ARRAY_AGG(ta.node_id)
+                        FROM task_assignments ta
+                        WHERE ta.task_id = t.task_id
+                          AND ta.disconnected_at IS NULL
+                    ),
+                    ARRAY[]::VARCHAR[]
+                ) as assigned_nodes,
+                COALESCE(
+                    (
+                        SELECT ARRAY_AGG(ta.node_id)
+                        FROM task_assignments ta
+                        WHERE ta.task_id = t.task_id
+                          AND ta.disconnected_at IS NOT NULL
+                    ),
+                    ARRAY[]::VARCHAR[]
+                ) as former_assigned_nodes,
+                {queue_position} as queue_position
+            FROM tasks t
+            WHERE t.task_id = $1
+              AND t.deleted_at IS NULL
+              AND (t.creator_id = $2 OR org_role_at_least(t.org_id, $2, 'viewer'))
+            "#,
+            queue_position = task_queue_position_sql("$3"),
+        ))
+        .bind(task_uuid)
+        .bind(requester_id)
+        .bind(Self::task_priority_aging_seconds())
+        .fetch_optional(db)
+        .await;
+
+        match result {
+            Ok(Some(row)) => {
+                let task_id_uuid: Uuid = row.get("task_id");
+                let status_text: String = row.get("status");
+
+                Some(TaskInfo {
+                    task_id: task_id_uuid.to_string(),
+                    task_type: row.get("task_type"),
+                    status: parse_task_status(&status_text),
+                    assigned_nodes: row.get::<Vec<String>, _>("assigned_nodes"),
+                    former_assigned_nodes: row.get::<Vec<String>, _>("former_assigned_nodes"),
+                    created_at: row
+                        .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
+                        .to_rfc3339(),
+                    updated_at: row
+                        .get::<chrono::DateTime<chrono::Utc>, _>("updated_at")
+                        .to_rfc3339(),
+                    result: row.try_get("result").ok(),
+                    proof_id: row.try_get("proof_id").ok(),
+                    priority: row.get::<i16, _>("priority") as u8,
+                    retry_count: row.get::<i32, _>("retry_count") as u32,
+                    last_error: row.get("last_error"),
+                    queue_position: row.get("queue_position"),
+                    egress: parse_task_egress(row.get("egress")),
+                    constraints_relaxed: row.get("constraints_relaxed"),
+                    starving_since: row
+                        .get::<Option<chrono::DateTime<chrono::Utc>>, _>("starving_at")
+                        .map(|v| v.to_rfc3339()),
+                    scheduling_diagnostics: row.get("scheduling_diagnostics"),
+                    checkpointable: row.get("checkpointable"),
+                    checkpoint_seq: row
+                        .get::<Option<i64>, _>("checkpoint_seq")
+                        .map(|seq| seq as u64),
+                    node_selector: parse_node_labels(row.get("node_selector")),
+                    diversity: DiversityMode::parse(row.get("diversity")),
+                    preferred_regions: row.get("preferred_regions"),
+                    excluded_regions: row.get("excluded_regions"),
+                    region_strictness: RegionStrictness::parse(row.get("region_strictness")),
+                    traceparent: row.get("traceparent"),
+                })
+            }
+            Ok(None) => None,
+            Err(e) => {
+                tracing::error!("Failed to get task {}: {:?}", task_id, e);
+                None
+            }
+        }
+    }
+
+    /// List all tasks from the database
+    pub async fn list_tasks(&self, requester_id: Uuid) -> Vec<TaskInfo> {
+        self.list_tasks_in_org(requester_id, None).await
+    }
+
+    /// Tasks visible to the requester that match a search; see
+    /// [`crate::task_search`].
+    pub async fn search_tasks(
+        &self,
+        requester_id: Uuid,
+        org_id: Option<Uuid>,
+        q: &str,
+        limit: i64,
+    ) -> ApiResult<Vec<crate::task_search::TaskSearchHit>> {
+        let db = self.read_db()?;
+        let rows = sqlx::query(crate::task_search::SEARCH_SQL)
+            .bind(q)
+            .bind(crate::task_search::substring_pattern(q))
+            .bind(crate::task_search::as_task_id(q))
+            .bind(requester_id)
+            .bind(org_id)
+            .bind(limit)
+            .fetch_all(db)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| crate::task_search::TaskSearchHit {
+                task_id: row.get::<Uuid, _>("task_id").to_string(),
+                task_type: row.get("task_type"),
+                status: parse_task_status(&row.get::<String, _>("status")),
+                created_at: row
+                    .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
+                    .to_rfc3339(),
+                updated_at: row
+                    .get::<chrono::DateTime<chrono::Utc>, _>("updated_at")
+                    .to_rfc3339(),
+                score: row.get("score"),
+                snippet: row.get("snippet"),
+            })
+            .collect())
+    }
+
+    /// List the requester's own tasks, or with `org_id` every task owned by
+    /// that organization (requires membership).
+    #[tracing::instrument(skip_all)]
+    pub async fn list_tasks_in_org(
+        &self,
+        requester_id: Uuid,
+        org_id: Option<Uuid>,
+    ) -> Vec<TaskInfo> {
+        let Ok(db) = self.read_db() else {
+            return vec![];
+        };
+
+        let result = sqlx::query(&format!(
+            r#"
+            SELECT 
+                t.task_id, t.task_type, t.status, t.result, t.proof_id,
+                t.created_at, t.updated_at, t.priority, t.retry_count, t.last_error, t.egress,
+                t.constraints_relaxed_at IS NOT NULL AS constraints_relaxed, t.starving_at,
+                t.scheduling_diagnostics, t.checkpointable, t.node_selector, t.traceparent,
+                t.diversity, t.preferred_regions, t.excluded_regions, t.region_strictness,
+                (SELECT c.seq FROM task_checkpoints c WHERE c.task_id = t.task_id) AS checkpoint_seq,
+                COALESCE(
+                    (
+                        SELECT ARRAY_AGG(ta.node_id)
+                        FROM task_assignments ta
+                        WHERE ta.task_id = t.task_id
+                          AND ta.disconnected_at IS NULL
+                    ),
+                    ARRAY[]::VARCHAR[]
+                ) as assigned_nodes,
+                COALESCE(
+                    (
+                        SELECT ARRAY_AGG(ta.node_id)
+                        FROM task_assignments ta
+                        WHERE ta.task_id = t.task_id
+                          AND ta.disconnected_at IS NOT NULL
+                    ),
+                    ARRAY[]::VARCHAR[]
+                ) as former_assigned_nodes,
+                {queue_position} as queue_position
+            FROM tasks t
+            WHERE CASE
+                WHEN $3::UUID IS NULL THEN t.creator_id = $1
+                ELSE t.org_id = $3 AND org_role_at_least($3, $1, 'viewer')
+            END
+              AND t.deleted_at IS NULL
+            ORDER BY t.created_at DESC
+            "#,
+            queue_position = task_queue_position_sql("$2"),
+        ))
+        .bind(requester_id)
+        .bind(Self::task_priority_aging_seconds())
+        .bind(org_id)
+        .fetch_all(db)
+        .await;
+
+        match result {
+            Ok(rows) => rows
+                .into_iter()
+                .map(|row| TaskInfo {
+                    task_id: row.get::<Uuid, _>("task_id").to_string(),
+                    task_type: row.get("task_type"),
+                    status: parse_task_status(&row.get::<String, _>("status")),
+                    assigned_nodes: row.get::<Vec<String>, _>("assigned_nodes"),
+                    former_assigned_nodes: row.get::<Vec<String>, _>("former_assigned_nodes"),
+                    created_at: row
+                        .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
+                        .to_rfc3339(),
+                    updated_at: row
+                        .get::<chrono::DateTime<chrono::Utc>, _>("updated_at")
+                        .to_rfc3339(),
+                    result: row.try_get("result").ok(),
+                    proof_id: row.try_get("proof_id").ok(),
+                    priority: row.get::<i16, _>("priority") as u8,
+                    retry_count: row.get::<i32, _>("retry_count") as u32,
+                    last_error: row.get("last_error"),
+                    queue_position: row.get("queue_position"),
+                    egress: parse_task_egress(row.get("egress")),
+                    constraints_relaxed: row.get("constraints_relaxed"),
+                    starving_since: row
+                        .get::<Option<chrono::DateTime<chrono::Utc>>, _>("starving_at")
+                        .map(|v| v.to_rfc3339()),
+                    scheduling_diagnostics: row.get("scheduling_diagnostics"),
+                    checkpointable: row.get("checkpointable"),
+                    checkpoint_seq: row
+                        .get::<Option<i64>, _>("checkpoint_seq")
+                        .map(|seq| seq as u64),
+                    node_selector: parse_node_labels(row.get("node_selector")),
+                    diversity: DiversityMode::parse(row.get("diversity")),
+                    preferred_regions: row.get("preferred_regions"),
+                    excluded_regions: row.get("excluded_regions"),
+                    region_strictness: RegionStrictness::parse(row.get("region_strictness")),
+                    traceparent: row.get("traceparent"),
+                })
+                .collect(),
+            Err(e) => {
+                tracing::error!("Failed to list tasks: {:?}", e);
+                vec![]
+            }
+        }
+    }
+
+    /// Add the one-time completion notification for `task_id` to the outbox
+    /// within `tx`, the transaction that completed the task.
+    ///
+    /// `completion_email_sent_at` is claimed in the same transaction, so a
+    /// task is announced as completed at most once.
+    async fn enqueue_task_completed(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        task_id: Uuid,
+    ) -> ApiResult<()> {
+        if self.notifications.is_none() {
+            return Ok(());
+        }
+
+        let claimed = sqlx::query_scalar::<_, Option<serde_json::Value>>(
+            r#"
+            UPDATE tasks
+            SET completion_email_sent_at = NOW()
+            WHERE task_id = $1
+              AND status = 'completed'
+              AND completion_email_sent_at IS NULL
+            RETURNING result
+            "#,
+        )
+        .bind(task_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        let Some(result) = claimed else {
+            return Ok(());
+        };
+        let result_payload = result.unwrap_or(serde_json::json!({"message": "Task completed"}));
+
+        self.enqueue_task_notification(tx, task_id, |creator_id| {
+            crate::notifier::Notification::task_completed(creator_id, None, task_id, result_payload)
+        })
+        .await
+    }
+
+    /// Add the notification built by `build` for the creator of `task_id` to
+    /// the outbox within `tx`, so it exists exactly when the change it
+    /// reports commits.  The recipient's email is looked up at delivery.
+    async fn enqueue_task_notification(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        task_id: Uuid,
+        build: impl FnOnce(Uuid) -> crate::notifier::Notification,
+    ) -> ApiResult<()> {
+        let Some(notifications) = self.notifications.as_ref() else {
+            return Ok(());
+        };
+
+        let creator_id: Option<Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT t.creator_id
+            FROM tasks t
+            JOIN users u ON u.user_id = t.creator_id
+            WHERE t.task_id = $1
+              -- Canary tasks alert through their canary run instead.
+              AND NOT EXISTS (SELECT 1 FROM canary_runs cr WHERE cr.task_id = t.task_id)
+            "#,
+        )
+        .bind(task_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        let Some(creator_id) = creator_id else {
+            return Ok(());
+        };
+        let notification = build(creator_id);
+        let event = notification.event;
+        let payload = serde_json::to_value(&notification)
+            .map_err(|_| ApiError::internal_error("Failed to encode notification"))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO notification_outbox (event, user_id, task_id, notification)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(payload["event"].as_str())
+        .bind(creator_id)
+        .bind(task_id)
+        .bind(&payload)
+        .execute(&mut **tx)
+        .await?;
+
+        // Note the queued notification in the audit log so it appears in the
+        // task's timeline.
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (user_id, action, resource_type, resource_id, status, metadata)
+            VALUES ($1, 'notification_queued', 'task', $2, 'queued', $3)
+            "#,
+        )
+        .bind(creator_id)
+        .bind(task_id.to_string())
+        .bind(serde_json::json!({ "event": event, "backend": notifications.backend_name() }))
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Deliver due notifications from the outbox.
+    ///
+    /// Each due row is claimed by pushing `next_attempt_at` past
+    /// `OUTBOX_CLAIM_SECS`, so concurrent drains on other replicas skip it
+    /// and a drain that dies mid-delivery leaves it for a later retry.  A
+    /// delivered row is stamped `delivered_at`; a failed one is retried after
+    /// a linear backoff until `NOTIFY_MAX_ATTEMPTS`, then stamped
+    /// `failed_at`.  Returns the number of notifications delivered.
+    #[tracing::instrument(skip_all)]
+    pub async fn drain_notification_outbox(&self) -> ApiResult<usize> {
+        let (Ok(db), Some(notifications)) = (self.require_db(), self.notifications.as_ref()) else {
+            return Ok(0);
+        };
+
+        let claimed = sqlx::query(
+            r#"
+            WITH due AS (
+                SELECT outbox_id
+                FROM notification_outbox
+                WHERE delivered_at IS NULL
+                  AND failed_at IS NULL
+                  AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            UPDATE notification_outbox o
+            SET next_attempt_at = NOW() + make_interval(secs => $2),
+                attempts = o.attempts + 1
+            FROM due
+            WHERE o.outbox_id = due.outbox_id
+            RETURNING
+                o.outbox_id,
+                o.task_id,
+                o.user_id,
+                o.notification,
+                o.attempts,
+                (SELECT email FROM users u WHERE u.user_id = o.user_id) AS email
+            "#,
+        )
+        .bind(crate::notifier::OUTBOX_BATCH_SIZE)
+        .bind(crate::notifier::OUTBOX_CLAIM_SECS as f64)
+        .fetch_all(db)
+        .await?;
+
+        let config = notifications.config();
+        let mut delivered = 0;
+        for row in claimed {
+            let outbox_id: i64 = row.get("outbox_id");
+            let attempts: i32 = row.get("attempts");
+            let task_id: Option<Uuid> = row.get("task_id");
+            let stored: serde_json::Value = row.get("notification");
+            let event = stored["event"].clone();
+
+            let outcome = match serde_json::from_value::<crate::notifier::Notification>(stored) {
+                Ok(mut notification) => {
+                    notification.delivery_id = Some(outbox_id.to_string());
+                    notification.email = row
+                        .get::<Option<String>, _>("email")
+                        .filter(|email| !email.trim().is_empty());
+                    notifications.deliver(&notification).await
+                }
+                Err(err) => Err(anyhow::anyhow!("undecodable notification: {err}")),
+            };
+
+            let (error, give_up) = match &outcome {
+                Ok(_) => (None, false),
+                Err(err) => (
+                    Some(err.to_string()),
+                    attempts as u32 >= config.max_attempts,
+                ),
+            };
+            let mut tx = db.begin().await?;
+            sqlx::query(
+                r#"
+                UPDATE notification_outbox
+                SET delivered_at = CASE WHEN $2::TEXT IS NULL THEN NOW() END,
+                    failed_at = CASE WHEN $3 THEN NOW() END,
+                    last_error = $2,
+                    next_attempt_at = NOW() + make_interval(secs => $4)
+                WHERE outbox_id = $1
+                "#,
+            )
+            .bind(outbox_id)
+            .bind(&error)
+            .bind(give_up)
+            .bind((config.retry_delay * attempts.max(1) as u32).as_secs_f64())
+            .execute(&mut *tx)
+            .await?;
+
+            if let Some(task_id) = task_id.filter(|_| error.is_none() || give_up) {
+                let (action, status) = if error.is_none() {
+                    ("notification_delivered", "delivered")
+                } else {
+                    ("notification_failed", "failed")
+                };
+                sqlx::query(
+                    r#"
+                    INSERT INTO audit_log (user_id, action, resource_type, resource_id, status, metadata)
+                    VALUES ($1, $2, 'task', $3, $4, $5)
+                    "#,
+                )
+                .bind(row.get::<Uuid, _>("user_id"))
+                .bind(action)
+                .bind(task_id.to_string())
+                .bind(status)
+                .bind(serde_json::json!({
+                    "event": event,
+                    "backend": notifications.backend_name(),
+                    "delivery_id": outbox_id.to_string(),
+                    "attempts": attempts,
+                }))
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+
+            match outcome {
+                Ok(()) => delivered += 1,
+                Err(err) if give_up => tracing::warn!(
+                    outbox_id,
+                    attempts,
+                    backend = notifications.backend_name(),
+                    "Notification dropped after {attempts} attempts: {err}"
+                ),
+                Err(err) => {
+                    tracing::debug!(outbox_id, attempts, "Notification attempt failed: {err}")
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Verify a ZK proof using actual cryptographic verification and store
+    /// it with the outcome.
+    ///
+    /// With a database, `request.task_id` must name a task the submitter
+    /// created or is a member of the owning organization for, and the
+    /// verification counts toward the submitter's quota; other proofs go
+    /// through [`Self::create_proof_batch`].
+    pub async fn verify_proof(
+        &self,
+        request: ProofVerificationRequest,
+        submitted_by: Uuid,
+    ) -> ApiResult<ProofVerificationResponse> {
+        // Validate the request
+        request.validate()?;
+
+        let task_id = match &self.db {
+            Some(db) => {
+                let task_id: Uuid = sqlx::query_scalar(
+                    r#"
+                    SELECT task_id FROM tasks
+                    WHERE task_id = $1
+                      AND deleted_at IS NULL
+                      AND (creator_id = $2 OR org_role_at_least(org_id, $2, 'member'))
+                    "#,
+                )
+                .bind(Uuid::parse_str(&request.task_id).ok())
+                .bind(submitted_by)
+                .fetch_optional(db)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::not_found_or_forbidden(
+                        "Task not found or not yours; verify proofs for other tasks with POST /api/v1/proofs/batches",
+                    )
+                })?;
+                self.proof_quota(submitted_by).await?.check(1)?;
+                Some(task_id)
+            }
+            None => None,
+        };
+
+        let verified = verify_proof_request(&request).await?;
+        let proof_id = match &self.db {
+            Some(_) => Some(
+                self.store_proof(StoredProof {
+                    task_id,
+                    node_id: None,
+                    submitted_by,
+                    proof: &verified.proof,
+                    verified: verified.valid,
+                    verification_time_ms: verified.verification_time_ms,
+                    error_message: verified.error_message,
+                })
+                .await?
+                .to_string(),
+            ),
+            None => None,
+        };
+
+        Ok(ProofVerificationResponse {
+            valid: verified.valid,
+            task_id: request.task_id,
+            verified_at: chrono::Utc::now().to_rfc3339(),
+            verification_time_ms: verified.verification_time_ms,
+            error_message: verified.error_message.map(str::to_string),
+            proof_id,
+        })
+    }
+
+    /// The submitter's standalone verifications in the quota window: settled
+    /// proofs without a node plus proofs still unsettled in running batches.
+    pub async fn proof_quota(
+        &self,
+        submitted_by: Uuid,
+    ) -> ApiResult<crate::proof_batches::ProofQuota> {
+        use crate::proof_batches::{daily_quota, ProofQuota, QUOTA_WINDOW_SECS};
+
+        let db = self.require_db()?;
+        let used: i64 = sqlx::query_scalar(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM proofs
+                 WHERE submitted_by = $1
+                   AND node_id IS NULL
+                   AND verified_at IS NOT NULL
+                   AND created_at > NOW() - make_interval(secs => $2))
+              + (SELECT COALESCE(SUM(total - jsonb_array_length(results)), 0) FROM proof_batches
+                 WHERE submitted_by = $1 AND status = 'running')
+            "#,
+        )
+        .bind(submitted_by)
+        .bind(QUOTA_WINDOW_SECS as f64)
+        .fetch_one(db)
+        .await?;
+        Ok(ProofQuota::new(daily_quota(), used))
+    }
+
+    /// Queue a batch of proofs for verification; [`Self::run_proof_batch`]
+    /// does the work.  The whole batch must fit in the submitter's quota.
+    pub async fn create_proof_batch(
+        &self,
+        request: crate::proof_batches::ProofBatchRequest,
+        submitted_by: Uuid,
+    ) -> ApiResult<crate::proof_batches::ProofBatch> {
+        use crate::proof_batches::{MAX_RUNNING_BATCHES, STALE_BATCH_SECS};
+
+        request.validate()?;
+        let db = self.require_db()?;
+
+        // A batch left running by a crashed or restarted server never
+        // finishes; stop counting it against the quota.
+        sqlx::query(
+            r#"
+            UPDATE proof_batches
+            SET status = 'failed', error = 'interrupted', requests = '[]', finished_at = NOW()
+            WHERE submitted_by = $1
+              AND status = 'running'
+              AND created_at < NOW() - make_interval(secs => $2)
+            "#,
+        )
+        .bind(submitted_by)
+        .bind(STALE_BATCH_SECS as f64)
+        .execute(db)
+        .await?;
+
+        let running: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM proof_batches WHERE submitted_by = $1 AND status = 'running'",
+        )
+        .bind(submitted_by)
+        .fetch_one(db)
+        .await?;
+        if running >= MAX_RUNNING_BATCHES {
+            return Err(ApiError::conflict(format!(
+                "{running} proof batches are already running; wait for one to finish"
+            )));
+        }
+        self.proof_quota(submitted_by)
+            .await?
+            .check(request.proofs.len() as i64)?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO proof_batches (batch_id, submitted_by, total, requests)
+            VALUES ($1, $2, $3, $4)
+            RETURNING {PROOF_BATCH_COLUMNS}
+            "#
+        ))
+        .bind(Uuid::new_v4())
+        .bind(submitted_by)
+        .bind(request.proofs.len() as i32)
+        .bind(serde_json::json!(request.proofs))
+        .fetch_one(db)
+        .await?;
+        Ok(map_proof_batch_row(&row))
+    }
+
+    /// Verify a queued batch proof by proof, marking it failed on error.
+    pub async fn run_proof_batch(&self, batch_id: Uuid) {
+        let Err(err) = self.verify_proof_batch(batch_id).await else {
+            return;
+        };
+        tracing::error!(%batch_id, "Proof batch failed: {}", err.message);
+        let Ok(db) = self.require_db() else {
+            return;
+        };
+        let result = sqlx::query(
+            r#"
+            UPDATE proof_batches
+            SET status = 'failed', error = $2, requests = '[]', finished_at = NOW()
+            WHERE batch_id = $1 AND status = 'running'
+            "#,
+        )
+        .bind(batch_id)
+        .bind(&err.message)
+        .execute(db)
+        .await;
+        if let Err(err) = result {
+            tracing::error!(%batch_id, "Failed to record proof batch failure: {err}");
+        }
+    }
+
+    /// Verify and store each queued proof, appending its result as it
+    /// finishes so pollers see progress.  With proof verification offload
+    /// on, proofs are handed to verifier nodes instead and their results
+    /// appended as each reaches a quorum.
+    async fn verify_proof_batch(&self, batch_id: Uuid) -> ApiResult<()> {
+        use crate::proof_batches::ProofBatchResult;
+
+        let db = self.require_db()?;
+        let offload = crate::proof_offload::offload_enabled();
+        let (submitted_by, requests): (Uuid, serde_json::Value) = sqlx::query_as(
+            "SELECT submitted_by, requests FROM proof_batches WHERE batch_id = $1 AND status = 'running'",
+        )
+        .bind(batch_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Proof batch not found"))?;
+        let requests: Vec<ProofVerificationRequest> = serde_json::from_value(requests)
+            .map_err(|err| ApiError::internal_error(format!("Unreadable batch proofs: {err}")))?;
+
+        for (position, request) in requests.into_iter().enumerate() {
+            // Linked only to a task the submitter can see, as for stored
+            // task results.
+            let task_id: Option<Uuid> = sqlx::query_scalar(
+                r#"
+                SELECT task_id FROM tasks
+                WHERE task_id = $1
+                  AND deleted_at IS NULL
+                  AND (creator_id = $2 OR org_role_at_least(org_id, $2, 'viewer'))
+                "#,
+            )
+            .bind(Uuid::parse_str(&request.task_id).ok())
+            .bind(submitted_by)
+            .fetch_optional(db)
+            .await?;
+
+            // Oversized proofs are rejected here without a verifier.
+            if offload {
+                let proof = decode_proof_request(&request)?;
+                if proof.size() <= MAX_PROOF_BYTES {
+                    self.offload_batch_proof(
+                        batch_id,
+                        position,
+                        &request,
+                        proof,
+                        task_id,
+                        submitted_by,
+                    )
+                    .await?;
+                    continue;
+                }
+            }
+
+            let verified = verify_proof_request(&request).await?;
+            let proof_id = self
+                .store_proof(StoredProof {
+                    task_id,
+                    node_id: None,
+                    submitted_by,
+                    proof: &verified.proof,
+                    verified: verified.valid,
+                    verification_time_ms: verified.verification_time_ms,
+                    error_message: verified.error_message,
+                })
+                .await?;
+            let result = ProofBatchResult {
+                position,
+                task_id: request.task_id,
+                valid: verified.valid,
+                verification_time_ms: verified.verification_time_ms,
+                error_message: verified.error_message.map(str::to_string),
+                proof_id: Some(proof_id.to_string()),
+            };
+            sqlx::query("UPDATE proof_batches SET results = results || $2 WHERE batch_id = $1")
+                .bind(batch_id)
+                .bind(serde_json::json!([result]))
+                .execute(db)
+                .await?;
+        }
+
+        self.finish_proof_batch_if_settled(batch_id).await
+    }
+
+    /// Mark a running batch completed once every proof in it has a result.
+    async fn finish_proof_batch_if_settled(&self, batch_id: Uuid) -> ApiResult<()> {
+        let db = self.require_db()?;
+        let finished = sqlx::query(
+            r#"
+            UPDATE proof_batches
+            SET status = 'completed', requests = '[]', finished_at = NOW()
+            WHERE batch_id = $1
+              AND status = 'running'
+              AND jsonb_array_length(results) >= total
+            "#,
+        )
+        .bind(batch_id)
+        .execute(db)
+        .await?;
+        if finished.rows_affected() > 0 {
+            tracing::info!(%batch_id, "Proof batch completed");
+        }
+        Ok(())
+    }
+
+    /// Store a batch proof unverified and queue it for verifier nodes as a
+    /// `verify_proof` task.
+    async fn offload_batch_proof(
+        &self,
+        batch_id: Uuid,
+        position: usize,
+        request: &ProofVerificationRequest,
+        proof: zk_prover::ZKProof,
+        task_id: Option<Uuid>,
+        submitted_by: Uuid,
+    ) -> ApiResult<()> {
+        use crate::proof_offload::{
+            quorum, VerifyProofInputs, VERIFY_PROOF_TASK_TYPE, VERIFY_TIMEOUT_SECS,
+        };
+
+        let db = self.require_db()?;
+        let proof_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO proofs (
+                task_id, submitted_by, proof_data, public_inputs, circuit_id, proof_system, verified
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, FALSE)
+            RETURNING proof_id
+            "#,
+        )
+        .bind(task_id)
+        .bind(submitted_by)
+        .bind(&proof.proof_data)
+        .bind(&proof.public_inputs)
+        .bind(&proof.circuit_id)
+        .bind(&proof.proof_system)
+        .fetch_one(db)
+        .await?;
+
+        let inputs = VerifyProofInputs {
+            proof_id: proof_id.to_string(),
+            batch_id: batch_id.to_string(),
+            position,
+            proof_task_id: request.task_id.clone(),
+            proof_data: request.proof_data.clone(),
+            public_inputs: request.public_inputs.clone(),
+            circuit_id: proof.circuit_id.clone(),
+        };
+        let task = TaskSubmission {
+            task_type: VERIFY_PROOF_TASK_TYPE.to_string(),
+            wasm_module: None,
+            inputs: serde_json::json!(inputs),
+            requirements: TaskRequirements {
+                min_nodes: quorum(),
+                max_execution_time_sec: VERIFY_TIMEOUT_SECS,
+                require_gpu: false,
+                require_proof: false,
+                scheduling_mode: SchedulingMode::Standard,
+                max_retries: 2,
+                retry_backoff_sec: 0,
+                egress: Vec::new(),
+                checkpointable: false,
+                node_selector: Default::default(),
+                diversity: Default::default(),
+                preferred_regions: Vec::new(),
+                excluded_regions: Vec::new(),
+                region_strictness: Default::default(),
+            },
+            priority: 0,
+        };
+        let info = self
+            .insert_submitted_task(task, submitted_by, None, None)
+            .await?;
+        sqlx::query("UPDATE proofs SET verification_task_id = $2 WHERE proof_id = $1")
+            .bind(proof_id)
+            .bind(Uuid::parse_str(&info.task_id).ok())
+            .execute(db)
+            .await?;
+        tracing::info!(%batch_id, %proof_id, verify_task_id = %info.task_id, "Proof offloaded to verifier nodes");
+        Ok(())
+    }
+
+    /// A proof batch, if the requester submitted it.
+    pub async fn get_proof_batch(
+        &self,
+        batch_id: Uuid,
+        requester_id: Uuid,
+    ) -> ApiResult<Option<crate::proof_batches::ProofBatch>> {
+        let db = self.require_db()?;
+        let row = sqlx::query(&format!(
+            "SELECT {PROOF_BATCH_COLUMNS} FROM proof_batches WHERE batch_id = $1 AND submitted_by = $2"
+        ))
+        .bind(batch_id)
+        .bind(requester_id)
+        .fetch_optional(db)
+        .await?;
+        Ok(row.as_ref().map(map_proof_batch_row))
+    }
+
+    /// Store a proof with its verification outcome and return its ID.
+    async fn store_proof(&self, stored: StoredProof<'_>) -> ApiResult<Uuid> {
+        let db = self.require_db()?;
+        let proof_id = sqlx::query_scalar(
+            r#"
+            INSERT INTO proofs (
+                task_id, node_id, submitted_by, proof_data, public_inputs,
+                circuit_id, proof_system, verified, verification_time_ms,
+                error_message, verified_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NOW())
+            RETURNING proof_id
+            "#,
+        )
+        .bind(stored.task_id)
+        .bind(stored.node_id)
+        .bind(stored.submitted_by)
+        .bind(&stored.proof.proof_data)
+        .bind(&stored.proof.public_inputs)
+        .bind(&stored.proof.circuit_id)
+        .bind(&stored.proof.proof_system)
+        .bind(stored.verified)
+        .bind(stored.verification_time_ms as i64)
+        .bind(stored.error_message)
+        .fetch_one(db)
+        .await?;
+        Ok(proof_id)
+    }
+
+    /// A stored proof, if the requester submitted it or can see its task.
+    pub async fn get_proof(
+        &self,
+        proof_id: Uuid,
+        requester_id: Uuid,
+    ) -> ApiResult<Option<ProofRecord>> {
+        use base64::Engine;
+        let db = self.require_db()?;
+
+        let Some(row) = sqlx::query(
+            r#"
+            SELECT p.proof_id, p.task_id, p.node_id, p.circuit_id, p.proof_system,
+                   p.proof_data, p.public_inputs, p.verified, p.error_message,
+                   p.verification_time_ms, p.created_at, p.verified_at,
+                   p.verification_task_id
+            FROM proofs p
+            LEFT JOIN tasks t ON t.task_id = p.task_id
+            WHERE p.proof_id = $1
+              AND (
+                  p.submitted_by = $2
+                  OR t.creator_id = $2
+                  OR org_role_at_least(t.org_id, $2, 'viewer')
+              )
+            "#,
+        )
+        .bind(proof_id)
+        .bind(requester_id)
+        .fetch_optional(db)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let encode = |column: &str| {
+            base64::engine::general_purpose::STANDARD.encode(row.get::<Vec<u8>, _>(column))
+        };
+        Ok(Some(ProofRecord {
+            proof_id: proof_id.to_string(),
+            task_id: row
+                .get::<Option<Uuid>, _>("task_id")
+                .map(|id| id.to_string()),
+            node_id: row.get("node_id"),
+            circuit_id: row.get("circuit_id"),
+            proof_system: row.get("proof_system"),
+            proof_data: encode("proof_data"),
+            public_inputs: encode("public_inputs"),
+            verified: row.get("verified"),
+            error_message: row.get("error_message"),
+            verification_time_ms: row
+                .get::<Option<i64>, _>("verification_time_ms")
+                .map(|ms| ms as u64),
+            created_at: row
+                .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
+                .to_rfc3339(),
+            verified_at: row
+                .get::<Option<chrono::DateTime<chrono::Utc>>, _>("verified_at")
+                .map(|at| at.to_rfc3339()),
+            verification_task_id: row
+                .get::<Option<Uuid>, _>("verification_task_id")
+                .map(|id| id.to_string()),
+        }))
+    }
+
+    /// Get cluster statistics from the database
+    pub async fn get_cluster_stats(&self) -> ClusterStats {
+        let Ok(db) = self.read_db() else {
+            return ClusterStats {
+                total_nodes: 0,
+                healthy_nodes: 0,
+                total_tasks: 0,
+                completed_tasks: 0,
+                failed_tasks: 0,
+                avg_health_score: 0.0,
+                total_compute_capacity: 0.0,
+            };
+        };
+
+        // Get node statistics
+        let node_stats = sqlx::query(CLUSTER_NODE_STATS_SQL).fetch_one(db).await;
+
+        // Get task statistics
+        let task_stats = sqlx::query(CLUSTER_TASK_STATS_SQL).fetch_one(db).await;
+
+        match (node_stats, task_stats) {
+            (Ok(nodes), Ok(tasks)) => ClusterStats {
+                total_nodes: nodes.get::<i64, _>("total_nodes") as usize,
+                healthy_nodes: nodes.get::<i64, _>("healthy_nodes") as usize,
+                total_tasks: tasks.get::<i64, _>("total_tasks") as usize,
+                completed_tasks: tasks.get::<i64, _>("completed_tasks") as usize,
+                failed_tasks: tasks.get::<i64, _>("failed_tasks") as usize,
+                avg_health_score: nodes.get("avg_health_score"),
+                total_compute_capacity: nodes.get("total_compute_capacity"),
+            },
+            _ => {
+                tracing::error!("Failed to get cluster stats");
+                ClusterStats {
+                    total_nodes: 0,
+                    healthy_nodes: 0,
+                    total_tasks: 0,
+                    completed_tasks: 0,
+                    failed_tasks: 0,
+                    avg_health_score: 0.0,
+                    total_compute_capacity: 0.0,
+                }
+            }
+        }
+    }
+
+    /// Record the current cluster statistics in `cluster_stats_history`,
+    /// unless a snapshot was recorded within the last `min_gap_secs`.
+    /// Returns whether a row was written.
+    pub async fn snapshot_cluster_stats(&self, min_gap_secs: u64) -> ApiResult<bool> {
+        let db = self.require_db()?;
+
+        let inserted = sqlx::query(&format!(
+            r#"
+            INSERT INTO cluster_stats_history (
+                total_nodes, healthy_nodes, total_tasks, completed_tasks, failed_tasks,
+                avg_health_score, total_compute_capacity
+            )
+            SELECT n.total_nodes, n.healthy_nodes, t.total_tasks, t.completed_tasks, t.failed_tasks,
+                   n.avg_health_score, n.total_compute_capacity
+            FROM ({CLUSTER_NODE_STATS_SQL}) n
+            CROSS JOIN ({CLUSTER_TASK_STATS_SQL}) t
+            WHERE NOT EXISTS (
+                SELECT 1 FROM cluster_stats_history
+                WHERE recorded_at > NOW() - make_interval(secs => $1)
+            )
+            "#
+        ))
+        .bind(min_gap_secs as f64)
+        .execute(db)
+        .await?
+        .rows_affected();
+
+        Ok(inserted > 0)
+    }
+
+    /// Downsampled cluster statistics over `window`.
+    pub async fn get_cluster_stats_history(
+        &self,
+        window: crate::telemetry::TelemetryWindow,
+    ) -> ApiResult<crate::cluster_history::ClusterStatsHistoryResponse> {
+        use crate::cluster_history::{ClusterStatsPoint, ClusterStatsTrend, SERIES_SQL};
+
+        let db = self.read_db()?;
+
+        let points = sqlx::query(SERIES_SQL)
+            .bind(window.resolution.bucket_secs())
+            .bind(window.from)
+            .bind(window.to)
+            .bind(crate::telemetry::MAX_TELEMETRY_POINTS)
+            .fetch_all(db)
+            .await?
+            .iter()
+            .map(ClusterStatsPoint::from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(crate::cluster_history::ClusterStatsHistoryResponse {
+            resolution: window.resolution,
+            from: window.from,
+            to: window.to,
+            trend: ClusterStatsTrend::across(&points),
+            points,
+        })
+    }
+
+    /// One pass of the canary job: advance the running canary runs, failing
+    /// those past the timeout, then start a run of each kind that is due.
+    #[tracing::instrument(skip_all)]
+    pub async fn run_canaries(&self, config: &crate::canary::CanaryConfig) -> ApiResult<()> {
+        let db = self.require_db()?;
+        let running: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT run_id FROM canary_runs WHERE status = 'running' ORDER BY started_at",
+        )
+        .fetch_all(db)
+        .await?;
+        for run_id in running {
+            if let Err(err) = self.advance_canary_run(run_id, config).await {
+                tracing::error!(%run_id, "Canary run check failed: {err}");
+            }
+        }
+
+        for kind in crate::canary::CanaryKind::ALL {
+            if let Err(err) = self.start_canary_run(kind, config).await {
+                tracing::error!(kind = kind.as_str(), "Starting canary run failed: {err}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Submit a canary of `kind` pinned to a random eligible node, unless a
+    /// run of that kind is still going or started within the interval.
+    /// Returns the new run's ID.
+    pub async fn start_canary_run(
+        &self,
+        kind: crate::canary::CanaryKind,
+        config: &crate::canary::CanaryConfig,
+    ) -> ApiResult<Option<Uuid>> {
+        use crate::canary::CanaryKind;
+        use rand::Rng;
+
+        let db = self.require_db()?;
+        const NOT_DUE: &str = r#"
+            EXISTS (
+                SELECT 1 FROM canary_runs
+                WHERE kind = $1
+                  AND (status = 'running' OR started_at > NOW() - make_interval(secs => $2))
+            )
+        "#;
+        let interval_secs = config.interval.as_secs_f64();
+        let not_due: bool = sqlx::query_scalar(&format!("SELECT {NOT_DUE}"))
+            .bind(kind.as_str())
+            .bind(interval_secs)
+            .fetch_one(db)
+            .await?;
+        if not_due {
+            return Ok(None);
+        }
+
+        let task_type = match kind {
+            CanaryKind::Task => "computation",
+            CanaryKind::ConnectSession => "connect_only",
+        };
+        let entry = task_type_registry_entry(task_type)
+            .ok_or_else(|| ApiError::internal_error("Canary task type is not registered"))?;
+        let node_id: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT n.node_id
+            FROM nodes n
+            WHERE n.deleted_at IS NULL
+              AND n.status = 'online'
+              AND (n.flap_breaker_until IS NULL OR n.flap_breaker_until <= NOW())
+              AND n.node_type = ANY($1)
+              AND n.cpu_cores >= $2
+              AND n.memory_gb >= $3
+              AND n.bandwidth_mbps >= $4
+              AND (CARDINALITY(n.allowed_task_types) = 0 OR $5 = ANY(n.allowed_task_types))
+              AND NOT ($5 = ANY(n.blocked_task_types))
+              AND (
+                    $6 = FALSE
+                    OR NOT EXISTS (
+                        SELECT 1
+                        FROM connect_sessions cs_busy
+                        WHERE cs_busy.node_id = n.node_id
+                          AND cs_busy.status = 'active'
+                          AND cs_busy.expires_at > NOW()
+                    )
+                  )
+              AND (
+                    SELECT COUNT(*)
+                    FROM task_assignments ta
+                    JOIN tasks busy ON busy.task_id = ta.task_id
+                    WHERE ta.node_id = n.node_id
+                      AND ta.disconnected_at IS NULL
+                      AND busy.slot_class = $7
+                  ) < COALESCE(
+                    CASE $7
+                        WHEN 'connect' THEN n.connect_slots
+                        WHEN 'gpu' THEN n.gpu_slots
+                        ELSE n.wasm_slots
+                    END,
+                    $8
+                  )
+            ORDER BY random()
+            LIMIT 1
+            "#,
+        )
+        .bind(entry.serving_node_types())
+        .bind(entry.minimum_capabilities.cpu_cores as i32)
+        .bind(entry.minimum_capabilities.memory_gb)
+        .bind(entry.minimum_capabilities.bandwidth_mbps)
+        .bind(task_type)
+        .bind(kind == CanaryKind::ConnectSession)
+        .bind(SlotClass::for_task(task_type, false).as_str())
+        .bind(Self::max_active_task_attachments_per_node())
+        .fetch_optional(db)
+        .await?;
+        let Some(node_id) = node_id else {
+            tracing::warn!(
+                kind = kind.as_str(),
+                "No node can take a canary run; skipping it"
+            );
+            return Ok(None);
+        };
+
+        let run_id = Uuid::new_v4();
+        let timeout_secs = config.timeout.as_secs();
+        let (inputs, expected, session_id) = match kind {
+            CanaryKind::Task => {
+                let mut rng = rand::thread_rng();
+                let (inputs, expected) =
+                    crate::canary::task_inputs(rng.gen_range(1..1000), rng.gen_range(1..1000));
+                (inputs, expected, None)
+            }
+            CanaryKind::ConnectSession => {
+                let session_id = format!("canary_{}", run_id.simple());
+                let inputs = serde_json::json!({
+                    "session_id": session_id,
+                    "requester_id": config.user_id.to_string(),
+                    "duration_seconds": timeout_secs,
+                    "bandwidth_limit_mbps": 1.0,
+                    "egress_profile": "allowlist_domains",
+                    "destination_policy_id": config.destination_policy_id,
+                    "data_cap_mb": 1,
+                });
+                (inputs, serde_json::json!({}), Some(session_id))
+            }
+        };
+
+        // Claim the run first so replicas racing on the same tick do not
+        // both submit a canary.
+        let claimed = sqlx::query(&format!(
+            r#"
+            INSERT INTO canary_runs (run_id, kind, node_id, session_id, expected, stage)
+            SELECT $3, $1, $4, $5, $6, 'assignment'
+            WHERE NOT {NOT_DUE}
+            "#
+        ))
+        .bind(kind.as_str())
+        .bind(interval_secs)
+        .bind(run_id)
+        .bind(&node_id)
+        .bind(&session_id)
+        .bind(&expected)
+        .execute(db)
+        .await?
+        .rows_affected();
+        if claimed == 0 {
+            return Ok(None);
+        }
+
+        let task = TaskSubmission {
+            task_type: task_type.to_string(),
+            wasm_module: None,
+            inputs,
+            requirements: TaskRequirements {
+                min_nodes: 1,
+                max_execution_time_sec: match kind {
+                    CanaryKind::Task => timeout_secs + crate::canary::TASK_EXECUTION_MARGIN_SECS,
+                    CanaryKind::ConnectSession => timeout_secs,
+                },
+                require_gpu: false,
+                require_proof: false,
+                scheduling_mode: SchedulingMode::Standard,
+                max_retries: 0,
+                retry_backoff_sec: 0,
+                egress: Vec::new(),
+                checkpointable: false,
+                node_selector: Default::default(),
+                diversity: Default::default(),
+                preferred_regions: Vec::new(),
+                excluded_regions: Vec::new(),
+                region_strictness: Default::default(),
+            },
+            priority: MAX_TASK_PRIORITY,
+        };
+        let submitted = match task.validate() {
+            Ok(()) => {
+                self.insert_submitted_task(task, config.user_id, None, Some(&node_id))
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        match submitted {
+            Ok(info) => {
+                let task_id = Uuid::parse_str(&info.task_id)
+                    .map_err(|_| ApiError::internal_error("Canary task ID is not a UUID"))?;
+                sqlx::query("UPDATE canary_runs SET task_id = $2 WHERE run_id = $1")
+                    .bind(run_id)
+                    .bind(task_id)
+                    .execute(db)
+                    .await?;
+                tracing::info!(%run_id, %task_id, kind = kind.as_str(), node_id, "Canary run started");
+            }
+            Err(err) => {
+                let mut tx = db.begin().await?;
+                self.finish_canary_run(
+                    &mut tx,
+                    config,
+                    run_id,
+                    CanaryRunOutcome::Failed(
+                        crate::canary::CanaryStage::Assignment,
+                        format!("submitting the canary task failed: {}", err.message),
+                    ),
+                    &std::collections::BTreeMap::new(),
+                )
+                .await?;
+                tx.commit().await?;
+            }
+        }
+        Ok(Some(run_id))
+    }
+
+    /// Check how far one running canary run got and finish it once it
+    /// passed, failed or timed out.
+    async fn advance_canary_run(
+        &self,
+        run_id: Uuid,
+        config: &crate::canary::CanaryConfig,
+    ) -> ApiResult<()> {
+        use crate::canary::{CanaryKind, CanaryStage};
+
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+        let run = sqlx::query(
+            r#"
+            SELECT kind, node_id, task_id, session_id, expected, started_at
+            FROM canary_runs
+            WHERE run_id = $1
+              AND status = 'running'
+            FOR UPDATE SKIP LOCKED
+            "#,
+        )
+        .bind(run_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(run) = run else {
+            return Ok(());
+        };
+
+        let kind = CanaryKind::parse(run.get("kind")).unwrap_or(CanaryKind::Task);
+        let node_id: String = run.get("node_id");
+        let task_id: Option<Uuid> = run.get("task_id");
+        let session_id: Option<String> = run.get("session_id");
+        let started_at: chrono::DateTime<chrono::Utc> = run.get("started_at");
+        let timed_out = chrono::Utc::now()
+            >= started_at
+                + chrono::Duration::from_std(config.timeout)
+                    .unwrap_or_else(|_| chrono::Duration::seconds(0));
+
+        let Some(task_id) = task_id else {
+            if timed_out {
+                self.finish_canary_run(
+                    &mut tx,
+                    config,
+                    run_id,
+                    CanaryRunOutcome::Failed(
+                        CanaryStage::Assignment,
+                        "canary task was never submitted".to_string(),
+                    ),
+                    &std::collections::BTreeMap::new(),
+                )
+                .await?;
+                tx.commit().await?;
+            }
+            return Ok(());
+        };
+
+        let state = sqlx::query(
+            r#"
+            SELECT t.status, t.result, t.last_error, t.completed_at,
+                   ta.assigned_at, ta.execution_started_at,
+                   cs.status AS session_status, cs.created_at AS session_created_at,
+                   cs.last_usage_at
+            FROM tasks t
+            LEFT JOIN LATERAL (
+                SELECT assigned_at, execution_started_at
+                FROM task_assignments
+                WHERE task_id = t.task_id
+                  AND node_id = $2
+                ORDER BY assigned_at DESC
+                LIMIT 1
+            ) ta ON TRUE
+            LEFT JOIN connect_sessions cs ON cs.session_id = $3
+            WHERE t.task_id = $1
+            "#,
+        )
+        .bind(task_id)
+        .bind(&node_id)
+        .bind(&session_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(state) = state else {
+            self.finish_canary_run(
+                &mut tx,
+                config,
+                run_id,
+                CanaryRunOutcome::Failed(
+                    CanaryStage::Assignment,
+                    "canary task disappeared".to_string(),
+                ),
+                &std::collections::BTreeMap::new(),
+            )
+            .await?;
+            tx.commit().await?;
+            return Ok(());
+        };
+
+        let task_status: String = state.get("status");
+        type At = Option<chrono::DateTime<chrono::Utc>>;
+        let assigned_at: At = state.get("assigned_at");
+        let execution_started_at: At = state.get("execution_started_at");
+        let session_created_at: At = state.get("session_created_at");
+        let mut reached = match kind {
+            CanaryKind::Task => vec![
+                (CanaryStage::Assignment, assigned_at),
+                (CanaryStage::Execution, execution_started_at),
+                (
+                    CanaryStage::Result,
+                    state
+                        .get::<At, _>("completed_at")
+                        .filter(|_| task_status == "completed"),
+                ),
+            ],
+            CanaryKind::ConnectSession => vec![
+                (CanaryStage::Assignment, assigned_at),
+                // The relay confirmed the assignment and the session was
+                // routed to it.
+                (
+                    CanaryStage::Relay,
+                    execution_started_at
+                        .zip(session_created_at)
+                        .map(|(started, created)| started.max(created)),
+                ),
+                (CanaryStage::Usage, state.get::<At, _>("last_usage_at")),
+            ],
+        };
+        let stage =
+            crate::canary::current_stage(kind, &reached).unwrap_or(CanaryStage::Verification);
+        let failed_task = matches!(task_status.as_str(), "failed" | "unschedulable");
+
+        let outcome = match kind {
+            CanaryKind::Task if task_status == "completed" => {
+                match crate::canary::verify_task_result(
+                    state.get::<Option<serde_json::Value>, _>("result").as_ref(),
+                    &run.get::<serde_json::Value, _>("expected"),
+                ) {
+                    Ok(()) => {
+                        reached.push((CanaryStage::Verification, Some(chrono::Utc::now())));
+                        Some(CanaryRunOutcome::Passed)
+                    }
+                    Err(err) => Some(CanaryRunOutcome::Failed(CanaryStage::Verification, err)),
+                }
+            }
+            CanaryKind::ConnectSession if stage == CanaryStage::Verification => {
+                let session_id = session_id.as_deref().unwrap_or_default();
+                let stopped = self
+                    .stop_connect_session(session_id, config.user_id)
+                    .await?;
+                let completed: bool =
+                    sqlx::query_scalar("SELECT status = 'completed' FROM tasks WHERE task_id = $1")
+                        .bind(task_id)
+                        .fetch_one(db)
+                        .await?;
+                let ended =
+                    stopped.is_some_and(|info| matches!(info.status, ConnectSessionStatus::Ended));
+                if ended && completed {
+                    reached.push((CanaryStage::Verification, Some(chrono::Utc::now())));
+                    Some(CanaryRunOutcome::Passed)
+                } else {
+                    Some(CanaryRunOutcome::Failed(
+                        CanaryStage::Verification,
+                        if ended {
+                            "stopping the session did not complete its task".to_string()
+                        } else {
+                            "the session was no longer active when stopped".to_string()
+                        },
+                    ))
+                }
+            }
+            _ if failed_task || task_status == "completed" => Some(CanaryRunOutcome::Failed(
+                stage,
+                state
+                    .get::<Option<String>, _>("last_error")
+                    .unwrap_or_else(|| format!("canary task ended as {task_status}")),
+            )),
+            _ if timed_out => Some(CanaryRunOutcome::Failed(
+                stage,
+                format!(
+                    "timed out after {}s waiting for {}",
+                    config.timeout.as_secs(),
+                    stage.as_str()
+                ),
+            )),
+            CanaryKind::ConnectSession
+                if task_status == "running" && session_created_at.is_none() =>
+            {
+                // Assigned: open the session the relay is meant to pick up.
+                match self
+                    .start_connect_session(
+                        ConnectSessionStartRequest {
+                            task_id: task_id.to_string(),
+                            tunnel_protocol: None,
+                        },
+                        config.user_id,
+                    )
+                    .await
+                {
+                    Ok(_) => None,
+                    Err(err) => Some(CanaryRunOutcome::Failed(
+                        CanaryStage::Relay,
+                        format!("starting the connect session failed: {}", err.message),
+                    )),
+                }
+            }
+            _ => None,
+        };
+
+        let latencies = crate::canary::stage_latencies(started_at, &reached);
+        match outcome {
+            Some(outcome) => {
+                // Release whatever the failed canary still holds.
+                if matches!(outcome, CanaryRunOutcome::Failed(..))
+                    && matches!(task_status.as_str(), "pending" | "running")
+                {
+                    let released = match session_id.as_deref().filter(|_| {
+                        state.get::<Option<String>, _>("session_status").as_deref()
+                            == Some("active")
+                    }) {
+                        Some(session_id) => self
+                            .stop_connect_session(session_id, config.user_id)
+                            .await
+                            .map(|_| ()),
+                        None => self
+                            .fail_task_attempt(task_id, &node_id, "canary run failed")
+                            .await
+                            .map(|_| ()),
+                    };
+                    if let Err(err) = released {
+                        tracing::warn!(%run_id, %task_id, "Releasing failed canary task failed: {err}");
+                    }
+                }
+                self.finish_canary_run(&mut tx, config, run_id, outcome, &latencies)
+                    .await?;
+            }
+            None => {
+                sqlx::query(
+                    "UPDATE canary_runs SET stage = $2, stage_latencies_ms = $3 WHERE run_id = $1",
+                )
+                .bind(run_id)
+                .bind(stage.as_str())
+                .bind(serde_json::json!(latencies))
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Record the end of a canary run, and alert the canary user when it
+    /// failed.
+    async fn finish_canary_run(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        config: &crate::canary::CanaryConfig,
+        run_id: Uuid,
+        outcome: CanaryRunOutcome,
+        latencies: &std::collections::BTreeMap<String, i64>,
+    ) -> ApiResult<()> {
+        use crate::canary::CanaryStage;
+
+        let (status, stage, error) = match outcome {
+            CanaryRunOutcome::Passed => ("passed", CanaryStage::Verification, None),
+            CanaryRunOutcome::Failed(stage, error) => ("failed", stage, Some(error)),
+        };
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE canary_runs
+            SET status = $2, stage = $3, stage_latencies_ms = $4, error = $5,
+                finished_at = NOW()
+            WHERE run_id = $1
+            RETURNING {CANARY_RUN_COLUMNS}
+            "#
+        ))
+        .bind(run_id)
+        .bind(status)
+        .bind(stage.as_str())
+        .bind(serde_json::json!(latencies))
+        .bind(&error)
+        .fetch_one(&mut **tx)
+        .await?;
+        let run = map_canary_run_row(&row);
+        crate::middleware::metrics::record_canary_run(
+            run.kind.as_str(),
+            error.is_none(),
+            &run.stage_latencies_ms,
+        );
+
+        let Some(error) = error else {
+            tracing::info!(%run_id, kind = run.kind.as_str(), node_id = %run.node_id, "Canary run passed");
+            return Ok(());
+        };
+        tracing::warn!(
+            %run_id,
+            kind = run.kind.as_str(),
+            node_id = %run.node_id,
+            stage = stage.as_str(),
+            error,
+            "Canary run failed"
+        );
+        if self.notifications.is_none() {
+            return Ok(());
+        }
+        let task_id = run
+            .task_id
+            .as_deref()
+            .and_then(|id| Uuid::parse_str(id).ok());
+        let payload = serde_json::to_value(crate::notifier::Notification::canary_failed(
+            config.user_id,
+            None,
+            task_id,
+            serde_json::to_value(&run)
+                .map_err(|_| ApiError::internal_error("Failed to encode canary run"))?,
+        ))
+        .map_err(|_| ApiError::internal_error("Failed to encode notification"))?;
+        sqlx::query(
+            r#"
+            INSERT INTO notification_outbox (event, user_id, task_id, notification)
+            SELECT $1, u.user_id, $3, $4
+            FROM users u
+            WHERE u.user_id = $2
+            "#,
+        )
+        .bind(payload["event"].as_str())
+        .bind(config.user_id)
+        .bind(task_id)
+        .bind(&payload)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Recent canary runs, newest first.
+    pub async fn list_canary_runs(
+        &self,
+        query: &crate::canary::CanaryRunsQuery,
+    ) -> ApiResult<Vec<crate::canary::CanaryRun>> {
+        let db = self.read_db()?;
+        if let Some(kind) = query.kind.as_deref() {
+            if crate::canary::CanaryKind::parse(kind).is_none() {
+                return Err(ApiError::bad_request(
+                    "kind must be one of: task, connect_session",
+                ));
+            }
+        }
+        if let Some(status) = query.status.as_deref() {
+            if !["running", "passed", "failed"].contains(&status) {
+                return Err(ApiError::bad_request(
+                    "status must be one of: running, passed, failed",
+                ));
+            }
+        }
+        let limit = query
+            .limit
+            .unwrap_or(50)
+            .clamp(1, crate::canary::MAX_CANARY_RUNS);
+
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {CANARY_RUN_COLUMNS}
+            FROM canary_runs
+            WHERE ($1::VARCHAR IS NULL OR kind = $1)
+              AND ($2::VARCHAR IS NULL OR status = $2)
+            ORDER BY started_at DESC
+            LIMIT $3
+            "#
+        ))
+        .bind(&query.kind)
+        .bind(&query.status)
+        .bind(limit)
+        .fetch_all(db)
+        .await?;
+        Ok(rows.iter().map(map_canary_run_row).collect())
+    }
+
+    /// Record one scheduling decision.  Failures are logged, never returned:
+    /// the decision log must not hold up scheduling.
+    async fn record_scheduler_decision(
+        &self,
+        task_id: Option<Uuid>,
+        node_id: Option<&str>,
+        inputs: &crate::scheduler_decisions::DecisionInputs,
+        chosen: &[String],
+        attached: &[String],
+    ) {
+        let Ok(db) = self.require_db() else {
+            return;
+        };
+        let result = sqlx::query(
+            r#"
+            INSERT INTO scheduler_decisions
+                (kind, task_id, node_id, rule_versions, candidate_set_hash, inputs, chosen, attached)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(inputs.kind())
+        .bind(task_id)
+        .bind(node_id)
+        .bind(serde_json::json!(crate::scheduler_decisions::rule_versions()))
+        .bind(inputs.candidate_set_hash())
+        .bind(serde_json::json!(inputs))
+        .bind(chosen)
+        .bind(attached)
+        .execute(db)
+        .await;
+        if let Err(err) = result {
+            tracing::warn!(
+                ?task_id,
+                ?node_id,
+                "failed to record scheduler decision: {err}"
+            );
+        }
+    }
+
+    /// Recorded scheduling decisions, newest first.
+    pub async fn list_scheduler_decisions(
+        &self,
+        query: &crate::scheduler_decisions::SchedulerDecisionsQuery,
+    ) -> ApiResult<Vec<crate::scheduler_decisions::SchedulerDecision>> {
+        let db = self.read_db()?;
+        if let Some(kind) = query.kind.as_deref() {
+            if !["placement", "pickup"].contains(&kind) {
+                return Err(ApiError::bad_request(
+                    "kind must be one of: placement, pickup",
+                ));
+            }
+        }
+        let limit = query
+            .limit
+            .unwrap_or(50)
+            .clamp(1, crate::scheduler_decisions::MAX_SCHEDULER_DECISIONS);
+
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {SCHEDULER_DECISION_COLUMNS}
+            FROM scheduler_decisions
+            WHERE ($1::UUID IS NULL
+                   OR task_id = $1
+                   OR (kind = 'pickup' AND attached @> ARRAY[$1::TEXT]))
+              AND ($2::VARCHAR IS NULL OR node_id = $2 OR $2 = ANY(chosen))
+              AND ($3::VARCHAR IS NULL OR kind = $3)
+            ORDER BY decided_at DESC, decision_id DESC
+            LIMIT $4
+            "#
+        ))
+        .bind(query.task_id)
+        .bind(&query.node_id)
+        .bind(&query.kind)
+        .bind(limit)
+        .fetch_all(db)
+        .await?;
+        rows.iter().map(map_scheduler_decision_row).collect()
+    }
+
+    /// Re-run a recorded decision against the current scheduling rules.
+    pub async fn replay_scheduler_decision(
+        &self,
+        decision_id: i64,
+    ) -> ApiResult<crate::scheduler_decisions::DecisionReplay> {
+        let db = self.read_db()?;
+        let row = sqlx::query(&format!(
+            "SELECT {SCHEDULER_DECISION_COLUMNS} FROM scheduler_decisions WHERE decision_id = $1"
+        ))
+        .bind(decision_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Scheduler decision not found"))?;
+        Ok(crate::scheduler_decisions::replay(
+            &map_scheduler_decision_row(&row)?,
+        ))
+    }
+
+    /// Record a new cluster export; [`Self::run_cluster_export`] does the
+    /// work.  An incremental export starts from the last completed export's
+    /// snapshot, less the overlap.
+    pub async fn create_cluster_export(
+        &self,
+        request: crate::cluster_export::ClusterExportRequest,
+        created_by: Uuid,
+    ) -> ApiResult<crate::cluster_export::ClusterExport> {
+        use crate::cluster_export::{
+            AnonymizationPolicy, INCREMENTAL_OVERLAP_SECS, STALE_EXPORT_SECS,
+        };
+
+        let db = self.require_db()?;
+        let policy = request.policy.unwrap_or_else(AnonymizationPolicy::from_env);
+        policy.validate().map_err(ApiError::bad_request)?;
+
+        // An export left running by a crashed or restarted server never
+        // finishes; let a new one start.
+        sqlx::query(
+            r#"
+            UPDATE cluster_exports
+            SET status = 'failed', error = 'interrupted', finished_at = NOW()
+            WHERE status = 'running'
+              AND started_at < NOW() - make_interval(secs => $1)
+            "#,
+        )
+        .bind(STALE_EXPORT_SECS as f64)
+        .execute(db)
+        .await?;
+
+        let since: Option<chrono::DateTime<chrono::Utc>> = if request.incremental {
+            sqlx::query_scalar::<_, Option<chrono::DateTime<chrono::Utc>>>(
+                "SELECT MAX(snapshot_at) FROM cluster_exports WHERE status = 'completed'",
+            )
+            .fetch_one(db)
+            .await?
+            .map(|at| at - chrono::Duration::seconds(INCREMENTAL_OVERLAP_SECS))
+        } else {
+            None
+        };
+        let stable_identifiers = policy.identifiers != crate::cluster_export::IdentifierMode::Hash
+            || std::env::var("CLUSTER_EXPORT_HASH_KEY").is_ok_and(|key| !key.is_empty());
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO cluster_exports
+                (export_id, format, incremental, since, policy, stable_identifiers, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT DO NOTHING
+            RETURNING {CLUSTER_EXPORT_COLUMNS}
+            "#
+        ))
+        .bind(Uuid::new_v4())
+        .bind(request.format.as_str())
+        .bind(request.incremental)
+        .bind(since)
+        .bind(serde_json::json!(policy))
+        .bind(stable_identifiers)
+        .bind(created_by)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::conflict("A cluster export is already running"))?;
+        Ok(map_cluster_export_row(&row))
+    }
+
+    /// Run a recorded export to completion, marking it failed on error.
+    pub async fn run_cluster_export(&self, export_id: Uuid) {
+        let Err(err) = self.write_cluster_export(export_id).await else {
+            return;
+        };
+        tracing::error!(%export_id, "Cluster export failed: {}", err.message);
+        let Ok(db) = self.require_db() else {
+            return;
+        };
+        let result = sqlx::query(
+            r#"
+            UPDATE cluster_exports
+            SET status = 'failed', error = $2, finished_at = NOW()
+            WHERE export_id = $1 AND status = 'running'
+            "#,
+        )
+        .bind(export_id)
+        .bind(&err.message)
+        .execute(db)
+        .await;
+        if let Err(err) = result {
+            tracing::error!(%export_id, "Failed to record cluster export failure: {err}");
+        }
+    }
+
+    /// Read every exported table from one snapshot, then encode and store
+    /// the files.
+    async fn write_cluster_export(&self, export_id: Uuid) -> ApiResult<()> {
+        use crate::cluster_export::{encode, Anonymizer, ExportFile, TABLES};
+
+        let export = self.get_cluster_export(export_id).await?;
+
+        // A replica's snapshot reflects the last transaction it replayed,
+        // not its clock.
+        let mut tx = self.read_db()?.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+        let snapshot_at: chrono::DateTime<chrono::Utc> =
+            sqlx::query_scalar("SELECT COALESCE(pg_last_xact_replay_timestamp(), NOW())")
+                .fetch_one(&mut *tx)
+                .await?;
+        let mut tables = Vec::with_capacity(TABLES.len());
+        for spec in TABLES {
+            let rows = sqlx::query(&spec.select_sql())
+                .bind(export.since)
+                .fetch_all(&mut *tx)
+                .await?;
+            let rows = rows
+                .iter()
+                .map(|row| spec.decode_row(row))
+                .collect::<Result<Vec<_>, _>>()?;
+            tables.push((spec, rows));
+        }
+        tx.commit().await?;
+
+        let key = std::env::var("CLUSTER_EXPORT_HASH_KEY")
+            .ok()
+            .filter(|key| !key.is_empty());
+        let anonymizer = Anonymizer::new(export.policy.clone(), key.as_deref().map(str::as_bytes));
+        let mut files = Vec::with_capacity(tables.len());
+        for (spec, rows) in tables {
+            let table = anonymizer.apply(spec, rows);
+            let bytes = encode(&table, export.format).map_err(|err| {
+                ApiError::internal_error(format!("Failed to encode {}: {err:#}", spec.name))
+            })?;
+            let hash = crate::artifacts::module_hash(&bytes);
+            self.artifact_store
+                .put(&hash, &bytes)
+                .await
+                .map_err(|err| {
+                    ApiError::internal_error(format!("Failed to store {}: {err:#}", spec.name))
+                })?;
+            files.push(ExportFile {
+                table: spec.name.to_string(),
+                rows: table.rows.len() as u64,
+                bytes: bytes.len() as u64,
+                hash,
+            });
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE cluster_exports
+            SET status = 'completed', snapshot_at = $2, files = $3, finished_at = NOW()
+            WHERE export_id = $1
+            "#,
+        )
+        .bind(export_id)
+        .bind(snapshot_at)
+        .bind(serde_json::json!(files))
+        .execute(self.require_db()?)
+        .await?;
+        tracing::info!(%export_id, format = export.format.as_str(), "Cluster export completed");
+        Ok(())
+    }
+
+    /// Cluster exports, newest first.
+    pub async fn list_cluster_exports(
+        &self,
+        query: &crate::cluster_export::ClusterExportsQuery,
+    ) -> ApiResult<Vec<crate::cluster_export::ClusterExport>> {
+        let db = self.require_db()?;
+        let limit = query
+            .limit
+            .unwrap_or(50)
+            .clamp(1, crate::cluster_export::MAX_CLUSTER_EXPORTS);
+        let rows = sqlx::query(&format!(
+            "SELECT {CLUSTER_EXPORT_COLUMNS} FROM cluster_exports ORDER BY started_at DESC LIMIT $1"
+        ))
+        .bind(limit)
+        .fetch_all(db)
+        .await?;
+        Ok(rows.iter().map(map_cluster_export_row).collect())
+    }
+
+    pub async fn get_cluster_export(
+        &self,
+        export_id: Uuid,
+    ) -> ApiResult<crate::cluster_export::ClusterExport> {
+        let db = self.require_db()?;
+        let row = sqlx::query(&format!(
+            "SELECT {CLUSTER_EXPORT_COLUMNS} FROM cluster_exports WHERE export_id = $1"
+        ))
+        .bind(export_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Cluster export not found"))?;
+        Ok(map_cluster_export_row(&row))
+    }
+
+    /// One table of a completed export from the artifact store, re-hashed
+    /// to guard against corrupted or tampered storage.
+    pub async fn get_cluster_export_file(
+        &self,
+        export_id: Uuid,
+        table: &str,
+    ) -> ApiResult<(crate::cluster_export::ExportFormat, Vec<u8>)> {
+        let export = self.get_cluster_export(export_id).await?;
+        let file = export
+            .files
+            .iter()
+            .find(|file| file.table == table)
+            .ok_or_else(|| ApiError::not_found("Export has no such table"))?;
+        let bytes = self
+            .artifact_store
+            .get(&file.hash)
+            .await
+            .map_err(|err| {
+                tracing::error!(%export_id, table, "Failed to read export file: {err:#}");
+                ApiError::internal_error("Failed to read export file")
+            })?
+            .ok_or_else(|| ApiError::not_found("Export file is no longer stored"))?;
+        if crate::artifacts::module_hash(&bytes) != file.hash {
+            tracing::error!(%export_id, table, "Stored export file failed hash verification");
+            return Err(ApiError::internal_error(
+                "Stored export file failed integrity check",
+            ));
+        }
+        Ok((export.format, bytes))
+    }
+
+    /// Component status, relay capacity, incidents and uptime for the public
+    /// status page.
+    #[tracing::instrument(skip_all)]
+    pub async fn collect_status(&self) -> ApiResult<crate::status::StatusResponse> {
+        use crate::status::{
+            canary_component, canary_component_status, incidents_from_runs,
+            proof_verification_status, ComponentReport, ComponentStatus, FinishedCanary,
+            IncidentStatus, RegionRelayCapacity, StatusResponse, UptimePercentages,
+            INCIDENT_WINDOW_DAYS,
+        };
+        use ambient_node::NodeKind;
+
+        let db = self.read_db()?;
+        let now = chrono::Utc::now();
+
+        let runs = sqlx::query(
+            r#"
+            SELECT kind, status, stage, started_at, finished_at
+            FROM canary_runs
+            WHERE status IN ('passed', 'failed')
+              AND finished_at >= $1
+            ORDER BY finished_at ASC, started_at ASC
+            "#,
+        )
+        .bind(now - chrono::Duration::days(INCIDENT_WINDOW_DAYS))
+        .fetch_all(db)
+        .await?;
+        let runs: Vec<FinishedCanary> = runs
+            .iter()
+            .filter_map(|row| {
+                Some(FinishedCanary {
+                    kind: crate::canary::CanaryKind::parse(row.get("kind"))?,
+                    passed: row.get::<String, _>("status") == "passed",
+                    stage: row.get("stage"),
+                    started_at: row.get("started_at"),
+                    finished_at: row.get("finished_at"),
+                })
+            })
+            .collect();
+        let incidents = incidents_from_runs(&runs);
+
+        let uptime_rows = sqlx::query(
+            r#"
+            SELECT kind,
+                   COUNT(*) FILTER (WHERE finished_at >= $1) AS finished_24h,
+                   COUNT(*) FILTER (WHERE finished_at >= $1 AND status = 'passed') AS passed_24h,
+                   COUNT(*) FILTER (WHERE finished_at >= $2) AS finished_7d,
+                   COUNT(*) FILTER (WHERE finished_at >= $2 AND status = 'passed') AS passed_7d,
+                   COUNT(*) AS finished_30d,
+                   COUNT(*) FILTER (WHERE status = 'passed') AS passed_30d
+            FROM canary_runs
+            WHERE status IN ('passed', 'failed')
+              AND finished_at >= $3
+            GROUP BY kind
+            "#,
+        )
+        .bind(now - chrono::Duration::hours(24))
+        .bind(now - chrono::Duration::days(7))
+        .bind(now - chrono::Duration::days(30))
+        .fetch_all(db)
+        .await?;
+        let uptime_of = |kind: crate::canary::CanaryKind| {
+            uptime_rows
+                .iter()
+                .find(|row| row.get::<String, _>("kind") == kind.as_str())
+                .map(|row| UptimePercentages {
+                    last_24h: UptimePercentages::percentage(
+                        row.get("passed_24h"),
+                        row.get("finished_24h"),
+                    ),
+                    last_7d: UptimePercentages::percentage(
+                        row.get("passed_7d"),
+                        row.get("finished_7d"),
+                    ),
+                    last_30d: UptimePercentages::percentage(
+                        row.get("passed_30d"),
+                        row.get("finished_30d"),
+                    ),
+                })
+                .unwrap_or_default()
+        };
+
+        let compute_nodes: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM nodes
+            WHERE deleted_at IS NULL
+              AND status = 'online'
+              AND node_type = ANY($1)
+            "#,
+        )
+        .bind(NodeKind::serving_node_types(NodeKind::Compute))
+        .fetch_one(db)
+        .await?;
+
+        let proof_p95_ms: Option<f64> = sqlx::query_scalar(
+            r#"
+            SELECT percentile_cont(0.95) WITHIN GROUP (ORDER BY verification_time_ms)
+            FROM proofs
+            WHERE created_at >= NOW() - INTERVAL '1 hour'
+              AND verification_time_ms IS NOT NULL
+            "#,
+        )
+        .fetch_one(db)
+        .await?;
+
+        let region_rows = sqlx::query(
+            r#"
+            SELECT n.region,
+                   COUNT(*) AS relays_online,
+                   COUNT(*) FILTER (WHERE n.status = 'draining') AS relays_draining,
+                   COUNT(*) FILTER (
+                       WHERE n.status = 'online' AND COALESCE(l.saturated AND l.fresh, FALSE)
+                   ) AS relays_saturated,
+                   COUNT(*) FILTER (
+                       WHERE n.status = 'online' AND NOT COALESCE(l.saturated AND l.fresh, FALSE)
+                   ) AS relays_available,
+                   COALESCE(SUM(n.bandwidth_mbps) FILTER (WHERE n.status = 'online'), 0)
+                       AS capacity_mbps,
+                   AVG(l.utilization) FILTER (WHERE l.fresh) AS utilization
+            FROM nodes n
+            LEFT JOIN LATERAL (
+                SELECT r.utilization, r.saturated,
+                       r.reported_at > NOW() - make_interval(secs => $2) AS fresh
+                FROM relay_load_reports r
+                WHERE r.node_id = n.node_id
+            ) l ON TRUE
+            WHERE n.deleted_at IS NULL
+              AND n.status IN ('online', 'draining')
+              AND n.node_type = ANY($1)
+            GROUP BY n.region
+            ORDER BY n.region
+            "#,
+        )
+        .bind(NodeKind::serving_node_types(NodeKind::OpenInternet))
+        .bind(mesh_coordinator::ROUTE_FEEDBACK_TTL_SECS as f64)
+        .fetch_all(db)
+        .await?;
+        let relay_regions: Vec<RegionRelayCapacity> = region_rows
+            .iter()
+            .map(|row| {
+                let relays_online: i64 = row.get("relays_online");
+                let relays_available: i64 = row.get("relays_available");
+                RegionRelayCapacity {
+                    region: row.get("region"),
+                    status: RegionRelayCapacity::status_for(relays_online, relays_available),
+                    relays_online,
+                    relays_available,
+                    relays_saturated: row.get("relays_saturated"),
+                    relays_draining: row.get("relays_draining"),
+                    capacity_mbps: row.get("capacity_mbps"),
+                    utilization: row.get("utilization"),
+                }
+            })
+            .collect();
+
+        let ongoing = |kind| {
+            incidents.iter().find(|incident| {
+                incident.status == IncidentStatus::Ongoing
+                    && incident.component == canary_component(kind)
+            })
+        };
+        let available_relays: i64 = relay_regions.iter().map(|r| r.relays_available).sum();
+        let components = vec![
+            ComponentReport {
+                component: "api".to_string(),
+                status: ComponentStatus::Operational,
+                uptime: UptimePercentages::default(),
+            },
+            ComponentReport {
+                component: canary_component(crate::canary::CanaryKind::Task).to_string(),
+                status: canary_component_status(
+                    ongoing(crate::canary::CanaryKind::Task),
+                    compute_nodes,
+                ),
+                uptime: uptime_of(crate::canary::CanaryKind::Task),
+            },
+            ComponentReport {
+                component: "proof_verification".to_string(),
+                status: proof_verification_status(proof_p95_ms),
+                uptime: UptimePercentages::default(),
+            },
+            ComponentReport {
+                component: canary_component(crate::canary::CanaryKind::ConnectSession).to_string(),
+                status: canary_component_status(
+                    ongoing(crate::canary::CanaryKind::ConnectSession),
+                    available_relays,
+                ),
+                uptime: uptime_of(crate::canary::CanaryKind::ConnectSession),
+            },
+        ];
+
+        Ok(StatusResponse {
+            generated_at: now,
+            status: components
+                .iter()
+                .map(|component| component.status)
+                .max()
+                .unwrap_or(ComponentStatus::Operational),
+            components,
+            relay_regions,
+            incidents,
+        })
+    }
+
+    /// Compute the cluster aggregates published on the transparency endpoint.
+    #[tracing::instrument(skip_all)]
+    pub async fn collect_transparency_snapshot(
+        &self,
+    ) -> ApiResult<crate::transparency::TransparencySnapshot> {
+        use crate::transparency::{
+            NodeCounts, TransparencySnapshot, UptimeDistribution, WindowedCount,
+            TRANSPARENCY_WINDOW_HOURS, UPTIME_SLOT_SECONDS,
+        };
+
+        let db = self.require_db()?;
+        let now = chrono::Utc::now();
+        let window_start = now - chrono::Duration::hours(TRANSPARENCY_WINDOW_HOURS);
+
+        let mut nodes = NodeCounts::default();
+        let kinds = sqlx::query(
+            r#"
+            SELECT node_type,
+                   COUNT(*) AS nodes,
+                   COUNT(*) FILTER (WHERE status = 'online') AS online
+            FROM nodes
+            WHERE deleted_at IS NULL
+              AND status != 'rejected'
+            GROUP BY node_type
+            "#,
+        )
+        .fetch_all(db)
+        .await?;
+        for row in kinds {
+            let count: i64 = row.get("nodes");
+            nodes.total += count;
+            nodes.online += row.get::<i64, _>("online");
+            nodes.by_kind.insert(row.get("node_type"), count);
+        }
+
+        let mut uptime = UptimeDistribution::default();
+        let heartbeat_slots = sqlx::query(
+            r#"
+            SELECT
+                GREATEST(n.registered_at, $1) AS since,
+                COUNT(DISTINCT FLOOR(EXTRACT(EPOCH FROM h.recorded_at) / $2)) AS up_slots
+            FROM nodes n
+            LEFT JOIN node_heartbeat_history h
+              ON h.node_id = n.node_id
+             AND h.recorded_at >= $1
+             AND h.status NOT IN ('task_cleared', 'task_connected')
+            WHERE n.deleted_at IS NULL
+              AND n.status != 'rejected'
+            GROUP BY n.node_id
+            "#,
+        )
+        .bind(window_start)
+        .bind(UPTIME_SLOT_SECONDS as f64)
+        .fetch_all(db)
+        .await?;
+        for row in heartbeat_slots {
+            let since: chrono::DateTime<chrono::Utc> = row.get("since");
+            let elapsed = (now - since).num_seconds().max(1);
+            let slots = (elapsed + UPTIME_SLOT_SECONDS - 1) / UPTIME_SLOT_SECONDS;
+            let up_slots: i64 = row.get("up_slots");
+            uptime.record((up_slots as f64 / slots as f64).min(1.0));
+        }
+
+        let tasks = sqlx::query(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE status = 'completed') AS completed,
+                COUNT(*) FILTER (WHERE status = 'completed' AND updated_at >= $1)
+                    AS completed_recently,
+                COUNT(proof_verified_at) AS proofs,
+                COUNT(*) FILTER (WHERE proof_verified_at >= $1) AS proofs_recently
+            FROM tasks
+            "#,
+        )
+        .bind(window_start)
+        .fetch_one(db)
+        .await?;
+
+        Ok(TransparencySnapshot {
+            generated_at: now,
+            window_hours: TRANSPARENCY_WINDOW_HOURS,
+            nodes,
+            uptime,
+            tasks_completed: WindowedCount {
+                total: tasks.get("completed"),
+                last_window: tasks.get("completed_recently"),
+            },
+            proofs_verified: WindowedCount {
+                total: tasks.get("proofs"),
+                last_window: tasks.get("proofs_recently"),
+            },
+        })
+    }
+
+    /// Compute, sign and store a transparency snapshot.
+    #[tracing::instrument(skip_all)]
+    pub async fn publish_transparency_snapshot(
+        &self,
+    ) -> ApiResult<crate::transparency::SignedTransparencySnapshot> {
+        let db = self.require_db()?;
+        let snapshot = self.collect_transparency_snapshot().await?;
+        let generated_at = snapshot.generated_at;
+        let document = self.sign_control_response(
+            crate::transparency::TRANSPARENCY_AUDIENCE,
+            serde_json::to_value(snapshot)
+                .map_err(|_| ApiError::internal_error("Failed to encode transparency snapshot"))?,
+        );
+        let public_key = self.control_public_key();
+
+        let snapshot_id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO transparency_snapshots (generated_at, public_key, document)
+            VALUES ($1, $2, $3)
+            RETURNING snapshot_id
+            "#,
+        )
+        .bind(generated_at)
+        .bind(&public_key)
+        .bind(&document)
+        .fetch_one(db)
+        .await?;
+
+        Ok(crate::transparency::SignedTransparencySnapshot {
+            snapshot_id,
+            public_key,
+            document,
+        })
+    }
+
+    /// Stored transparency snapshots in `[from, to)`, newest first.
+    pub async fn list_transparency_snapshots(
+        &self,
+        query: &crate::transparency::TransparencyHistoryQuery,
+    ) -> ApiResult<Vec<crate::transparency::SignedTransparencySnapshot>> {
+        let db = self.require_db()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT snapshot_id, public_key, document
+            FROM transparency_snapshots
+            WHERE ($1::TIMESTAMPTZ IS NULL OR generated_at >= $1)
+              AND ($2::TIMESTAMPTZ IS NULL OR generated_at < $2)
+            ORDER BY generated_at DESC, snapshot_id DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(query.from)
+        .bind(query.to)
+        .bind(query.limit())
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| crate::transparency::SignedTransparencySnapshot {
+                snapshot_id: row.get("snapshot_id"),
+                public_key: row.get("public_key"),
+                document: row.get("document"),
+            })
+            .collect())
+    }
+
+    /// Check if a user owns a specific node or administers the organization
+    /// that owns it
+    pub async fn check_node_ownership(&self, node_id: &str, user_id: Uuid) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        let result = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*)
+            FROM nodes
+            WHERE node_id = $1
+              AND (owner_id = $2 OR org_role_at_least(org_id, $2, 'admin'))
+              AND deleted_at IS NULL
+            "#,
+        )
+        .bind(node_id)
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+
+        Ok(result > 0)
+    }
+
+    /// Soft delete a node (sets deleted_at timestamp)
+    #[tracing::instrument(skip_all, fields(%node_id))]
+    pub async fn delete_node(&self, node_id: &str, owner_id: Uuid) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        // Verify ownership
+        if !self.check_node_ownership(node_id, owner_id).await? {
+            return Ok(false);
+        }
+
+        let now = chrono::Utc::now();
+
+        // Start a transaction to ensure atomicity
+        let mut tx = db.begin().await?;
+
+        // Mark the node as deleted
+        let result = sqlx::query(
+            r#"
+            UPDATE nodes
+            SET deleted_at = $1, status = 'offline', updated_at = $1
+            WHERE node_id = $2 AND (owner_id = $3 OR org_role_at_least(org_id, $3, 'admin')) AND deleted_at IS NULL
+            "#,
+        )
+        .bind(now)
+        .bind(node_id)
+        .bind(owner_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        // Get all tasks affected by this node deletion
+        let affected_tasks = sqlx::query(
+            r#"
+            SELECT ta.task_id, t.min_nodes
+            FROM task_assignments ta
+            JOIN tasks t ON t.task_id = ta.task_id
+            WHERE ta.node_id = $1
+              AND ta.disconnected_at IS NULL
+            "#,
+        )
+        .bind(node_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // Mark all task assignments from this node as disconnected,
+        // and fail any that were in_progress so the status history is accurate.
+        sqlx::query(
+            r#"
+            UPDATE task_assignments
+            SET disconnected_at = $1,
+                execution_status = CASE
+                    WHEN execution_status = 'in_progress' THEN 'failed'
+                    ELSE execution_status
+                END
+            WHERE node_id = $2
+              AND disconnected_at IS NULL
+            "#,
+        )
+        .bind(now)
+        .bind(node_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        // For each affected task, update its status and attempt reassignment
+        for task_row in affected_tasks {
+            let task_id: Uuid = task_row.get("task_id");
+            let min_nodes: i32 = task_row.get("min_nodes");
+
+            // Update task status based on remaining connected nodes
+            self.update_task_status_from_assignments(task_id, min_nodes as u32)
+                .await?;
+
+            // Try to assign the task to other available nodes
+            if let Ok(Some(task_status)) = self.get_task_status(task_id).await {
+                if task_status == "pending" {
+                    // Get task details for reassignment
+                    let task_details = sqlx::query(
+                        r#"
+                        SELECT task_type, require_gpu
+                        FROM tasks
+                        WHERE task_id = $1
+                        "#,
+                    )
+                    .bind(task_id)
+                    .fetch_optional(db)
+                    .await?;
+
+                    if let Some(task_row) = task_details {
+                        let task_type: String = task_row.get("task_type");
+                        let require_gpu: bool = task_row.get("require_gpu");
+
+                        if let Some(task_registry_entry) = task_type_registry_entry(&task_type) {
+                            // Try to find and assign alternative nodes
+                            let _ = self
+                                .assign_available_nodes_for_task(
+                                    task_id,
+                                    &task_type,
+                                    task_registry_entry,
+                                    min_nodes as u32,
+                                    require_gpu,
+                                )
+                                .await;
+                        }
+                    }
+                }
+            } else {
+                tracing::warn!(
+                    task_id = %task_id,
+                    "Failed to retrieve task status during node deletion; skipping automatic reassignment for this task"
+                );
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Update node heartbeat timestamp and record activity history.
+    ///
+    /// On success returns `Some(NodeHeartbeatResult)` containing the current
+    /// active task count, the enriched task list (with `task_type` so the node
+    /// knows which assignments are `connect_only` and require gateway-mode),
+    /// the node's `health_score`, and its `node_status`.
+    ///
+    /// Also advances `execution_status` from `'assigned'` → `'in_progress'` for
+    /// every task the node is currently connected to, registering the moment the
+    /// node confirmed it is actively working.
+    ///
+    /// Returns `None` when the node is not found or does not belong to `owner_id`.
+    #[tracing::instrument(skip_all, fields(%node_id))]
+    pub async fn update_node_heartbeat(
+        &self,
+        node_id: &str,
+        owner_id: Uuid,
+        request: &NodeHeartbeatRequest,
+    ) -> ApiResult<Option<NodeHeartbeatResult>> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+        let Some(recorded) = Self::record_node_heartbeat(
+            &mut tx,
+            node_id,
+            owner_id,
+            request,
+            &self.flap_breaker,
+            chrono::Utc::now(),
+        )
+        .await?
+        else {
+            return Ok(None);
+        };
+        tx.commit().await?;
+
+        self.export_telemetry(crate::telemetry_export::ExportRecord::Node(
+            recorded.sample.clone(),
+        ));
+        self.node_heartbeat_result(node_id, recorded)
+            .await
+            .map(Some)
+    }
+
+    /// Record heartbeats for several nodes of one owner in a single
+    /// transaction.
+    ///
+    /// Fails without recording anything unless `owner_id` owns every node.
+    /// Results are returned in request order.
+    #[tracing::instrument(skip_all, fields(count = heartbeats.len()))]
+    pub async fn update_node_heartbeats_batch(
+        &self,
+        owner_id: Uuid,
+        heartbeats: &[NodeHeartbeatBatchItem],
+    ) -> ApiResult<Vec<NodeHeartbeatResult>> {
+        let db = self.require_db()?;
+        let now = chrono::Utc::now();
+
+        // Lock nodes in a stable order so overlapping batches cannot deadlock.
+        let mut order: Vec<usize> = (0..heartbeats.len()).collect();
+        order.sort_by(|a, b| heartbeats[*a].node_id.cmp(&heartbeats[*b].node_id));
+
+        let mut tx = db.begin().await?;
+        let mut recorded = Vec::with_capacity(heartbeats.len());
+        for index in order {
+            let item = &heartbeats[index];
+            let Some(heartbeat) = Self::record_node_heartbeat(
+                &mut tx,
+                &item.node_id,
+                owner_id,
+                &item.heartbeat,
+                &self.flap_breaker,
+                now,
+            )
+            .await?
+            else {
+                return Err(ApiError::not_found_or_forbidden(format!(
+                    "Node {} not found or you don't have permission to update it",
+                    item.node_id
+                )));
+            };
+            recorded.push((index, heartbeat));
+        }
+        tx.commit().await?;
+
+        recorded.sort_by_key(|(index, _)| *index);
+        let mut results = Vec::with_capacity(recorded.len());
+        for (index, heartbeat) in recorded {
+            self.export_telemetry(crate::telemetry_export::ExportRecord::Node(
+                heartbeat.sample.clone(),
+            ));
+            results.push(
+                self.node_heartbeat_result(&heartbeats[index].node_id, heartbeat)
+                    .await?,
+            );
+        }
+        Ok(results)
+    }
+
+    /// Liveness, reported state, history, and assignment activity for one
+    /// heartbeat.  Returns `None` when the node is not found or not owned.
+    async fn record_node_heartbeat(
+        conn: &mut sqlx::PgConnection,
+        node_id: &str,
+        owner_id: Uuid,
+        request: &NodeHeartbeatRequest,
+        flap_breaker: &mesh_coordinator::FlapBreakerConfig,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> ApiResult<Option<RecordedHeartbeat>> {
+        // Fetch current node state (also verifies ownership and existence).
+        // The row lock serializes concurrent heartbeats so state deltas apply
+        // in order.
+        let node_row = sqlx::query(
+            r#"
+            SELECT health_score, status, heartbeat_seq, heartbeat_state, flap_count, first_flap_at
+            FROM nodes
+            WHERE node_id = $1 AND (owner_id = $2 OR org_role_at_least(org_id, $2, 'member')) AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(node_id)
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some(node_row) = node_row else {
+            return Ok(None);
+        };
+
+        let health_score: f64 = node_row.get("health_score");
+        let mut status: String = node_row.get("status");
+        if status == "offline" {
+            Self::revive_offline_node(conn, node_id, &node_row, flap_breaker, now).await?;
+            status = "online".to_string();
+        }
+        let stored_seq = node_row
+            .get::<Option<i64>, _>("heartbeat_seq")
+            .map(|seq| seq as u64);
+        let stored_state = node_row
+            .get::<Option<serde_json::Value>, _>("heartbeat_state")
+            .and_then(|state| match state {
+                serde_json::Value::Object(map) => Some(map),
+                _ => None,
+            });
+        let state_update = request.apply_state(stored_seq, stored_state.as_ref())?;
+        let resync_required = state_update.is_none();
+        let (state_seq, new_state) = match state_update {
+            Some(update) => (update.seq, update.state),
+            None => (stored_seq, None),
+        };
+        // Only state carried by this heartbeat is sampled, so a node that
+        // stops reporting leaves gaps rather than repeating stale values.
+        let telemetry = new_state
+            .as_ref()
+            .map(crate::telemetry::HeartbeatTelemetry::from_state)
+            .unwrap_or_default();
+
+        sqlx::query(
+            r#"
+            UPDATE nodes
+            SET last_heartbeat = $1, last_seen = $1, updated_at = $1,
+                clock_skew_ms = COALESCE($3, clock_skew_ms),
+                clock_skew_reported_at = CASE WHEN $3 IS NULL THEN clock_skew_reported_at ELSE $1 END,
+                heartbeat_seq = CASE WHEN $5 IS NULL THEN heartbeat_seq ELSE $4 END,
+                heartbeat_state = COALESCE($5, heartbeat_state)
+            WHERE node_id = $2
+            "#,
+        )
+        .bind(now)
+        .bind(node_id)
+        .bind(request.clock_skew_ms)
+        .bind(state_seq.map(|seq| seq as i64))
+        .bind(new_state.map(serde_json::Value::Object))
+        .execute(&mut *conn)
+        .await?;
+
+        // Count currently active task assignments for this node (pre-assignment snapshot
+        // for the heartbeat history record).
+        let active_tasks_before: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM task_assignments
+            WHERE node_id = $1 AND disconnected_at IS NULL
+            "#,
+        )
+        .bind(node_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        // Record heartbeat activity in history table
+        sqlx::query(
+            r#"
+            INSERT INTO node_heartbeat_history
+                (node_id, health_score, active_tasks, status, recorded_at,
+                 cpu_usage, memory_usage, network_latency_ms, bandwidth_mbps,
+                 temperature_c, power_watts)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+        )
+        .bind(node_id)
+        .bind(health_score)
+        .bind(active_tasks_before as i32)
+        .bind(&status)
+        .bind(now)
+        .bind(telemetry.cpu_usage)
+        .bind(telemetry.memory_usage)
+        .bind(telemetry.network_latency_ms)
+        .bind(telemetry.bandwidth_mbps)
+        .bind(telemetry.temperature_c)
+        .bind(telemetry.power_watts)
+        .execute(&mut *conn)
+        .await?;
+
+        // Activity registration: advance execution_status from 'assigned' to
+        // 'in_progress' for every task this node is actively connected to.
+        // This records the moment the node confirmed it is working on the task.
+        sqlx::query(
+            r#"
+            UPDATE task_assignments
+            SET execution_status = 'in_progress',
+                execution_started_at = COALESCE(execution_started_at, $1)
+            WHERE node_id = $2
+              AND disconnected_at IS NULL
+              AND execution_status = 'assigned'
+            "#,
+        )
+        .bind(now)
+        .bind(node_id)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(Some(RecordedHeartbeat {
+            sample: crate::telemetry_export::NodeSample {
+                node_id: node_id.to_string(),
+                recorded_at: now,
+                status: status.clone(),
+                health_score,
+                active_tasks: active_tasks_before,
+                cpu_usage: telemetry.cpu_usage,
+                memory_usage: telemetry.memory_usage,
+                network_latency_ms: telemetry.network_latency_ms,
+                bandwidth_mbps: telemetry.bandwidth_mbps,
+                temperature_c: telemetry.temperature_c,
+                power_watts: telemetry.power_watts,
+            },
+            health_score,
+            status,
+            state_seq,
+            resync_required,
+        }))
+    }
+
+    /// Bring a node the offline sweep marked `offline` back online and count
+    /// the flap, opening its circuit breaker once it flaps too often.
+    async fn revive_offline_node(
+        conn: &mut sqlx::PgConnection,
+        node_id: &str,
+        node_row: &sqlx::postgres::PgRow,
+        flap_breaker: &mesh_coordinator::FlapBreakerConfig,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> ApiResult<()> {
+        let first_flap_at =
+            node_row.get::<Option<chrono::DateTime<chrono::Utc>>, _>("first_flap_at");
+        let counted = flap_breaker.count_flap(
+            node_row.get::<i32, _>("flap_count").max(0) as u32,
+            first_flap_at.map_or(0, |at| at.timestamp().max(0) as u64),
+            now.timestamp().max(0) as u64,
+        );
+        let (flap_count, first_flap_at, breaker_until) = match counted {
+            Some(counted) => (
+                counted.count as i32,
+                (counted.count > 0)
+                    .then(|| chrono::DateTime::from_timestamp(counted.window_started_at as i64, 0))
+                    .flatten(),
+                counted
+                    .open_until
+                    .and_then(|until| chrono::DateTime::from_timestamp(until as i64, 0)),
+            ),
+            None => (0, None, None),
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE nodes
+            SET status = 'online',
+                flap_count = $2,
+                first_flap_at = $3,
+                flap_breaker_until = COALESCE($4, flap_breaker_until)
+            WHERE node_id = $1
+            "#,
+        )
+        .bind(node_id)
+        .bind(flap_count)
+        .bind(first_flap_at)
+        .bind(breaker_until)
+        .execute(&mut *conn)
+        .await?;
+
+        if let Some(until) = breaker_until {
+            tracing::warn!(
+                node_id,
+                %until,
+                "Node is flapping; excluding it from new assignments"
+            );
+        }
+        Ok(())
+    }
+
+    /// Assign eligible pending tasks to a node that just heartbeated and
+    /// build its heartbeat response.
+    async fn node_heartbeat_result(
+        &self,
+        node_id: &str,
+        recorded: RecordedHeartbeat,
+    ) -> ApiResult<NodeHeartbeatResult> {
+        self.require_db()?;
+
+        // Sync any pending tasks that this node is eligible for.
+        self.assign_pending_tasks_for_node(node_id).await?;
+
+        // Fetch active task assignments *after* any new assignments so the
+        // response accurately reflects the node's current connected-task state.
+        let assigned_tasks = self.active_assignments_for_node(node_id).await?;
+
+        let active_task_count = assigned_tasks.len() as i64;
+        let internet_active = assigned_tasks
+            .iter()
+            .any(|t| t.get("task_type").and_then(|v| v.as_str()) == Some("connect_only"));
+
+        Ok(NodeHeartbeatResult {
+            active_task_count,
+            assigned_tasks,
+            health_score: recorded.health_score,
+            node_status: recorded.status,
+            internet_active,
+            state_seq: recorded.state_seq,
+            resync_required: recorded.resync_required,
+        })
+    }
+
+    /// A node's active task assignments, oldest first, with each task's
+    /// type so the node knows which are connect_only (requiring gateway-mode
+    /// activation) and which are compute tasks.
+    pub async fn active_assignments_for_node(
+        &self,
+        node_id: &str,
+    ) -> ApiResult<Vec<serde_json::Value>> {
+        let db = self.require_db()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT ta.task_id::TEXT, t.task_type, ta.execution_status
+            FROM task_assignments ta
+            JOIN tasks t ON t.task_id = ta.task_id
+            WHERE ta.node_id = $1 AND ta.disconnected_at IS NULL
+            ORDER BY ta.assigned_at ASC
+            "#,
+        )
+        .bind(node_id)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                serde_json::json!({
+                    "task_id": row.get::<String, _>("task_id"),
+                    "task_type": row.get::<String, _>("task_type"),
+                    "execution_status": row.get::<String, _>("execution_status"),
+                })
+            })
+            .collect())
+    }
+
+    /// Reject a node owned by the requesting user
+    #[tracing::instrument(skip_all, fields(%node_id))]
+    pub async fn reject_node(&self, node_id: &str, owner_id: Uuid) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        // Verify ownership
+        if !self.check_node_ownership(node_id, owner_id).await? {
+            return Ok(false);
+        }
+
+        let now = chrono::Utc::now();
+        let result = sqlx::query(
+            r#"
+            UPDATE nodes
+            SET status = 'rejected', updated_at = $1
+            WHERE node_id = $2 AND (owner_id = $3 OR org_role_at_least(org_id, $3, 'admin')) AND deleted_at IS NULL
+            "#,
+        )
+        .bind(now)
+        .bind(node_id)
+        .bind(owner_id)
+        .execute(db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        // Collect all tasks that had an active assignment to this node so we can
+        // update their status and attempt reassignment after disconnecting.
+        let affected_tasks = sqlx::query(
+            r#"
+            SELECT ta.task_id, t.min_nodes
+            FROM task_assignments ta
+            JOIN tasks t ON t.task_id = ta.task_id
+            WHERE ta.node_id = $1
+              AND ta.disconnected_at IS NULL
+            "#,
+        )
+        .bind(node_id)
+        .fetch_all(db)
+        .await?;
+
+        // Disconnect all active task assignments for this rejected node,
+        // and fail any that were in_progress so the status history is accurate.
+        sqlx::query(
+            r#"
+            UPDATE task_assignments
+            SET disconnected_at = $1,
+                execution_status = CASE
+                    WHEN execution_status = 'in_progress' THEN 'failed'
+                    ELSE execution_status
+                END
+            WHERE node_id = $2
+              AND disconnected_at IS NULL
+            "#,
+        )
+        .bind(now)
+        .bind(node_id)
+        .execute(db)
+        .await?;
+
+        // For each previously-assigned task, fix its status and try to find
+        // replacement nodes so the task is not left permanently stuck in 'running'.
+        for task_row in affected_tasks {
+            let task_id: Uuid = task_row.get("task_id");
+            let min_nodes: i32 = task_row.get("min_nodes");
+
+            self.update_task_status_from_assignments(task_id, min_nodes as u32)
+                .await?;
+
+            match self.get_task_status(task_id).await {
+                Ok(Some(task_status)) => {
+                    if task_status == "pending" {
+                        let task_details = sqlx::query(
+                            r#"
+                            SELECT task_type, require_gpu
+                            FROM tasks
+                            WHERE task_id = $1
+                            "#,
+                        )
+                        .bind(task_id)
+                        .fetch_optional(db)
+                        .await?;
+
+                        if let Some(task_row) = task_details {
+                            let task_type: String = task_row.get("task_type");
+                            let require_gpu: bool = task_row.get("require_gpu");
+
+                            if let Some(task_registry_entry) = task_type_registry_entry(&task_type)
+                            {
+                                if let Err(err) = self
+                                    .assign_available_nodes_for_task(
+                                        task_id,
+                                        &task_type,
+                                        task_registry_entry,
+                                        min_nodes as u32,
+                                        require_gpu,
+                                    )
+                                    .await
+                                {
+                                    tracing::warn!(
+                                        task_id = %task_id,
+                                        "Failed to reassign task after node rejection: {err}"
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    tracing::warn!(
+                        task_id = %task_id,
+                        "Failed to retrieve task status during node rejection: {err}; skipping automatic reassignment for this task"
+                    );
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Stop scheduling new work on a node and hand its checkpointable tasks
+    /// to other nodes, which resume from the last uploaded checkpoint.  Tasks
+    /// that cannot be checkpointed stay to finish on the node.
+    ///
+    /// Returns `None` when the node does not exist, is not owned by the
+    /// caller, or is neither online nor already draining.
+    #[tracing::instrument(skip_all, fields(%node_id))]
+    pub async fn drain_node(
+        &self,
+        node_id: &str,
+        owner_id: Uuid,
+    ) -> ApiResult<Option<NodeDrainResponse>> {
+        let db = self.require_db()?;
+        if !self.check_node_ownership(node_id, owner_id).await? {
+            return Ok(None);
+        }
+
+        let result = sqlx::query(
+            r#"
+            UPDATE nodes
+            SET status = 'draining', updated_at = NOW()
+            WHERE node_id = $1
+              AND status IN ('online', 'draining')
+              AND deleted_at IS NULL
+            "#,
+        )
+        .bind(node_id)
+        .execute(db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let handed_off = sqlx::query(
+            r#"
+            WITH handed_off AS (
+                UPDATE task_assignments ta
+                SET disconnected_at = NOW(), execution_status = 'handed_off'
+                FROM tasks t
+                WHERE t.task_id = ta.task_id
+                  AND ta.node_id = $1
+                  AND ta.disconnected_at IS NULL
+                  AND t.checkpointable
+                  AND t.status IN ('pending', 'running')
+                RETURNING ta.task_id
+            )
+            UPDATE tasks t
+            SET queued_at = NOW(), updated_at = NOW()
+            FROM handed_off
+            WHERE t.task_id = handed_off.task_id
+            RETURNING t.task_id, t.task_type, t.min_nodes, t.require_gpu
+            "#,
+        )
+        .bind(node_id)
+        .fetch_all(db)
+        .await?;
+
+        let mut handed_off_tasks = Vec::with_capacity(handed_off.len());
+        for task_row in handed_off {
+            let task_id: Uuid = task_row.get("task_id");
+            let task_type: String = task_row.get("task_type");
+            let min_nodes: i32 = task_row.get("min_nodes");
+            let require_gpu: bool = task_row.get("require_gpu");
+            handed_off_tasks.push(task_id.to_string());
+
+            self.update_task_status_from_assignments(task_id, min_nodes as u32)
+                .await?;
+
+            if self.get_task_status(task_id).await?.as_deref() == Some("pending") {
+                if let Some(entry) = task_type_registry_entry(&task_type) {
+                    if let Err(err) = self
+                        .assign_available_nodes_for_task(
+                            task_id,
+                            &task_type,
+                            entry,
+                            min_nodes as u32,
+                            require_gpu,
+                        )
+                        .await
+                    {
+                        tracing::warn!(
+                            task_id = %task_id,
+                            "Failed to reassign task handed off by draining node: {err}"
+                        );
+                    }
+                }
+            }
+        }
+
+        let finishing_tasks: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT task_id
+            FROM task_assignments
+            WHERE node_id = $1 AND disconnected_at IS NULL
+            ORDER BY assigned_at
+            "#,
+        )
+        .bind(node_id)
+        .fetch_all(db)
+        .await?;
+
+        tracing::info!(
+            node_id,
+            handed_off = handed_off_tasks.len(),
+            finishing = finishing_tasks.len(),
+            "Node draining"
+        );
+
+        Ok(Some(NodeDrainResponse {
+            node_id: node_id.to_string(),
+            status: "draining".to_string(),
+            handed_off_tasks,
+            finishing_tasks: finishing_tasks.iter().map(Uuid::to_string).collect(),
+        }))
+    }
+
+    /// Return a draining node to service and offer it pending work.
+    pub async fn undrain_node(&self, node_id: &str, owner_id: Uuid) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        if !self.check_node_ownership(node_id, owner_id).await? {
+            return Ok(false);
+        }
+
+        let result = sqlx::query(
+            r#"
+            UPDATE nodes
+            SET status = 'online', relay_drain_deadline = NULL, updated_at = NOW()
+            WHERE node_id = $1 AND status = 'draining' AND deleted_at IS NULL
+            "#,
+        )
+        .bind(node_id)
+        .execute(db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        self.assign_pending_tasks_for_node(node_id).await?;
+        Ok(true)
+    }
+
+    /// Apply a capability or label update to a node and re-run pending task
+    /// attachment against the new capabilities.  Existing assignments are
+    /// left in place.  Returns `None` when the node does not exist or the
+    /// caller may not manage it.
+    ///
+    /// The node row stays locked from read to write, so concurrent partial
+    /// updates apply one after the other instead of overwriting each other's
+    /// fields.
+    pub async fn update_node(
+        &self,
+        node_id: &str,
+        owner_id: Uuid,
+        update: &NodeUpdateRequest,
+    ) -> ApiResult<Option<NodeInfo>> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+        let Some(row) = sqlx::query(&format!(
+            r#"
+            SELECT {NODE_INFO_COLUMNS}
+            FROM nodes
+            WHERE node_id = $1
+              AND (owner_id = $2 OR org_role_at_least(org_id, $2, 'admin'))
+              AND deleted_at IS NULL
+            FOR UPDATE
+            "#
+        ))
+        .bind(node_id)
+        .bind(owner_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let current = self.node_info_from_row(&row);
+
+        let capabilities = update.apply(&current.capabilities)?;
+        let (node_type, legacy_node_type) =
+            normalize_node_type(update.node_type.as_deref().unwrap_or(&current.node_type));
+        let labels = update.labels.as_ref().unwrap_or(&current.labels);
+        let allowed_task_types = update
+            .allowed_task_types
+            .as_ref()
+            .unwrap_or(&current.allowed_task_types);
+        let blocked_task_types = update
+            .blocked_task_types
+            .as_ref()
+            .unwrap_or(&current.blocked_task_types);
+
+        let result = sqlx::query(
+            r#"
+            UPDATE nodes
+            SET node_type = $2, bandwidth_mbps = $3, cpu_cores = $4,
+                memory_gb = $5, gpu_available = $6, labels = $7,
+                legacy_node_type = CASE WHEN $8 THEN $9 ELSE legacy_node_type END,
+                allowed_task_types = $10, blocked_task_types = $11,
+                updated_at = NOW()
+            WHERE node_id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(node_id)
+        .bind(&node_type)
+        .bind(capabilities.bandwidth_mbps)
+        .bind(capabilities.cpu_cores as i32)
+        .bind(capabilities.memory_gb)
+        .bind(capabilities.gpu_available)
+        .bind(serde_json::json!(labels))
+        .bind(update.node_type.is_some())
+        .bind(&legacy_node_type)
+        .bind(allowed_task_types)
+        .bind(blocked_task_types)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        tx.commit().await?;
+
+        self.assign_pending_tasks_for_node(node_id).await?;
+        Ok(self.get_node(node_id).await)
+    }
+
+    /// List nodes owned by a specific user
+    pub async fn list_user_nodes(&self, owner_id: Uuid) -> Vec<NodeInfo> {
+        let Some(db) = &self.db else {
+            return vec![];
+        };
+
+        let result = sqlx::query(&format!(
+            r#"
+            SELECT {NODE_INFO_COLUMNS}
+            FROM nodes
+            WHERE owner_id = $1 AND deleted_at IS NULL
+              AND status != 'rejected'
+            ORDER BY registered_at DESC
+            "#
+        ))
+        .bind(owner_id)
+        .fetch_all(db)
+        .await;
+
+        match result {
+            Ok(rows) => rows
+                .iter()
+                .map(|row| self.node_info_from_row(row))
+                .collect(),
+            Err(e) => {
+                tracing::error!("Failed to list user nodes: {:?}", e);
+                vec![]
+            }
+        }
+    }
+
+    /// Refresh the task, node and connect-session gauges exported on
+    /// `/metrics` from current row counts.
+    #[tracing::instrument(skip_all)]
+    pub async fn refresh_pipeline_metrics(&self) -> ApiResult<()> {
+        use crate::middleware::metrics::{set_pipeline_counts, PipelineGauge};
+
+        let Ok(db) = self.require_db() else {
+            return Ok(());
+        };
+
+        let queries = [
+            (
+                PipelineGauge::Tasks,
+                "SELECT status, COUNT(*) AS n FROM tasks WHERE deleted_at IS NULL GROUP BY status",
+            ),
+            (
+                PipelineGauge::Nodes,
+                "SELECT status, COUNT(*) AS n FROM nodes WHERE deleted_at IS NULL GROUP BY status",
+            ),
+            (
+                PipelineGauge::ConnectSessions,
+                "SELECT status, COUNT(*) AS n FROM connect_sessions GROUP BY status",
+            ),
+        ];
+        for (gauge, sql) in queries {
+            let counts: Vec<(String, i64)> = sqlx::query_as(sql).fetch_all(db).await?;
+            set_pipeline_counts(gauge, &counts);
+        }
+        Ok(())
+    }
+
+    /// Node counts per kind plus the active nodes still using a legacy
+    /// `node_type` alias.
+    pub async fn node_kind_report(&self) -> ApiResult<NodeKindReport> {
+        let db = self.require_db()?;
+
+        let kinds = sqlx::query(
+            r#"
+            SELECT node_type, COUNT(*) AS nodes
+            FROM nodes
+            WHERE deleted_at IS NULL AND status != 'rejected'
+            GROUP BY node_type
+            "#,
+        )
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|row| (row.get("node_type"), row.get("nodes")))
+        .collect();
+
+        let legacy_nodes = sqlx::query(
+            r#"
+            SELECT node_id, owner_id, node_type, legacy_node_type, last_seen
+            FROM nodes
+            WHERE legacy_node_type IS NOT NULL
+              AND deleted_at IS NULL AND status != 'rejected'
+            ORDER BY last_seen DESC
+            "#,
+        )
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|row| LegacyNodeKind {
+            node_id: row.get("node_id"),
+            owner_id: row.get::<Uuid, _>("owner_id").to_string(),
+            node_type: row.get("node_type"),
+            legacy_node_type: row.get("legacy_node_type"),
+            last_seen: row
+                .get::<chrono::DateTime<chrono::Utc>, _>("last_seen")
+                .to_rfc3339(),
+        })
+        .collect();
+
+        Ok(NodeKindReport {
+            kinds,
+            legacy_nodes,
+        })
+    }
+
+    /// All throttle overrides, including expired ones, newest first.
+    pub async fn list_throttle_overrides(&self) -> ApiResult<Vec<ThrottleOverride>> {
+        let db = self.require_db()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT override_id, subject_type, subject_id, multiplier, burst, reason,
+                   expires_at, created_by, created_at, updated_at
+            FROM throttle_overrides
+            ORDER BY updated_at DESC
+            "#,
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows.iter().map(throttle_override_from_row).collect())
+    }
+
+    /// Create or replace the override of a user or API key and apply it to
+    /// the rate limiter.
+    #[tracing::instrument(skip_all, fields(subject_type = request.subject_type.as_str()))]
+    pub async fn upsert_throttle_override(
+        &self,
+        request: &ThrottleOverrideRequest,
+        created_by: Uuid,
+    ) -> ApiResult<ThrottleOverride> {
+        let db = self.require_db()?;
+        let subject_id = Uuid::parse_str(&request.subject_id)
+            .map_err(|_| ApiError::bad_request("subject_id must be a valid UUID"))?;
+
+        let subject_exists: bool = match request.subject_type {
+            ThrottleSubjectType::User => {
+                sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE user_id = $1)")
+            }
+            ThrottleSubjectType::ApiKey => {
+                sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM api_keys WHERE key_id = $1)")
+            }
+        }
+        .bind(subject_id)
+        .fetch_one(db)
+        .await?;
+        if !subject_exists {
+            return Err(ApiError::not_found(format!(
+                "No {} {}",
+                request.subject_type.as_str(),
+                subject_id
+            )));
+        }
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO throttle_overrides
+                (subject_type, subject_id, multiplier, burst, reason, expires_at, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (subject_type, subject_id) DO UPDATE
+            SET multiplier = EXCLUDED.multiplier,
+                burst = EXCLUDED.burst,
+                reason = EXCLUDED.reason,
+                expires_at = EXCLUDED.expires_at,
+                created_by = EXCLUDED.created_by,
+                updated_at = NOW()
+            RETURNING override_id, subject_type, subject_id, multiplier, burst, reason,
+                      expires_at, created_by, created_at, updated_at
+            "#,
+        )
+        .bind(request.subject_type.as_str())
+        .bind(subject_id)
+        .bind(request.multiplier)
+        .bind(request.burst)
+        .bind(&request.reason)
+        .bind(request.expires_at)
+        .bind(created_by)
+        .fetch_one(db)
+        .await?;
+
+        self.reload_throttle_overrides().await?;
+        Ok(throttle_override_from_row(&row))
+    }
+
+    /// Delete an override and apply the change to the rate limiter.
+    /// Returns `false` when no override has that ID.
+    pub async fn delete_throttle_override(&self, override_id: Uuid) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        let deleted = sqlx::query("DELETE FROM throttle_overrides WHERE override_id = $1")
+            .bind(override_id)
+            .execute(db)
+            .await?
+            .rows_affected()
+            > 0;
+
+        if deleted {
+            self.reload_throttle_overrides().await?;
+        }
+        Ok(deleted)
+    }
+
+    /// Load unexpired throttle overrides into the rate limiter, replacing
+    /// the previous set.  Returns how many are active.
+    ///
+    /// API key overrides are keyed by key hash so the limiter can match them
+    /// before authentication; revoked and expired keys are left out.
+    pub async fn reload_throttle_overrides(&self) -> ApiResult<usize> {
+        let db = self.require_db()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT o.subject_type, o.subject_id, o.multiplier, o.burst, ak.key_hash
+            FROM throttle_overrides o
+            LEFT JOIN api_keys ak
+              ON o.subject_type = 'api_key' AND ak.key_id = o.subject_id
+             AND ak.revoked_at IS NULL
+             AND (ak.expires_at IS NULL OR ak.expires_at > NOW())
+            WHERE o.expires_at IS NULL OR o.expires_at > NOW()
+            "#,
+        )
+        .fetch_all(db)
+        .await?;
+
+        let mut overrides = crate::rate_limit::RateLimitOverrides::default();
+        for row in rows {
+            let subject_id = row.get::<Uuid, _>("subject_id").to_string();
+            let adjustment = crate::rate_limit::RateLimitOverride {
+                multiplier: row.get("multiplier"),
+                burst: row
+                    .get::<Option<i32>, _>("burst")
+                    .and_then(|burst| u32::try_from(burst).ok()),
+            };
+            match ThrottleSubjectType::parse(row.get("subject_type")) {
+                Some(ThrottleSubjectType::User) => {
+                    overrides.users.insert(subject_id, adjustment);
+                }
+                Some(ThrottleSubjectType::ApiKey) => {
+                    if let Some(key_hash) = row.get::<Option<String>, _>("key_hash") {
+                        overrides
+                            .api_keys
+                            .insert(key_hash, (subject_id, adjustment));
+                    }
+                }
+                None => {}
+            }
+        }
+
+        let active = overrides.len();
+        self.rate_limiter.set_overrides(overrides);
+        Ok(active)
+    }
+
+    /// Seconds left on an active login lock of `subject`, if any.
+    pub async fn login_lock_remaining(
+        &self,
+        scope: crate::lockout::LockoutScope,
+        subject: &str,
+    ) -> ApiResult<Option<u64>> {
+        if self.login_lockout.threshold(scope).is_none() {
+            return Ok(None);
+        }
+        let db = self.require_db()?;
+        let remaining: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT CEIL(EXTRACT(EPOCH FROM locked_until - NOW()))::BIGINT
+            FROM login_lockouts
+            WHERE scope = $1 AND subject = $2 AND locked_until > NOW()
+            "#,
+        )
+        .bind(scope.as_str())
+        .bind(subject)
+        .fetch_optional(db)
+        .await?;
+        Ok(remaining.map(|secs| secs.max(1) as u64))
+    }
+
+    /// Count a failed login against `subject`.  When this failure reaches the
+    /// scope's threshold the subject is locked and the lock duration in
+    /// seconds is returned.
+    pub async fn record_login_failure(
+        &self,
+        scope: crate::lockout::LockoutScope,
+        subject: &str,
+    ) -> ApiResult<Option<u64>> {
+        let Some(threshold) = self.login_lockout.threshold(scope) else {
+            return Ok(None);
+        };
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+
+        // Failures outside the window stop counting, and a quiet day restarts
+        // the backoff.
+        let row = sqlx::query(
+            r#"
+            INSERT INTO login_lockouts (scope, subject, failed_attempts, last_failure_at)
+            VALUES ($1, $2, 1, NOW())
+            ON CONFLICT (scope, subject) DO UPDATE
+            SET failed_attempts = CASE
+                    WHEN login_lockouts.last_failure_at < NOW() - make_interval(secs => $3)
+                    THEN 1
+                    ELSE login_lockouts.failed_attempts + 1
+                END,
+                lockout_count = CASE
+                    WHEN login_lockouts.last_failure_at < NOW() - INTERVAL '1 day' THEN 0
+                    ELSE login_lockouts.lockout_count
+                END,
+                last_failure_at = NOW()
+            RETURNING failed_attempts, lockout_count
+            "#,
+        )
+        .bind(scope.as_str())
+        .bind(subject)
+        .bind(self.login_lockout.window_secs as f64)
+        .fetch_one(&mut *tx)
+        .await?;
+        let failed_attempts: i32 = row.get("failed_attempts");
+        let lockout_count: i32 = row.get("lockout_count");
+
+        if (failed_attempts as u32) < threshold {
+            tx.commit().await?;
+            return Ok(None);
+        }
+
+        let lock_secs = self.login_lockout.lock_secs(lockout_count.max(0) as u32);
+        sqlx::query(
+            r#"
+            UPDATE login_lockouts
+            SET locked_until = NOW() + make_interval(secs => $3),
+                lockout_count = lockout_count + 1,
+                failed_attempts = 0
+            WHERE scope = $1 AND subject = $2
+            "#,
+        )
+        .bind(scope.as_str())
+        .bind(subject)
+        .bind(lock_secs as f64)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::warn!(
+            scope = scope.as_str(),
+            subject,
+            lock_secs,
+            previous_locks = lockout_count,
+            "Login locked after repeated failures"
+        );
+        Ok(Some(lock_secs))
+    }
+
+    /// Forget the failures and lock history of `subject`, as after a
+    /// successful login or an admin unlock.  Returns `false` when nothing
+    /// was recorded for it.
+    pub async fn clear_login_lockout(
+        &self,
+        scope: crate::lockout::LockoutScope,
+        subject: &str,
+    ) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        let cleared = sqlx::query("DELETE FROM login_lockouts WHERE scope = $1 AND subject = $2")
+            .bind(scope.as_str())
+            .bind(subject)
+            .execute(db)
+            .await?
+            .rows_affected()
+            > 0;
+        Ok(cleared)
+    }
+
+    /// Subjects that are locked or have failures in the current window.
+    pub async fn list_login_lockouts(&self) -> ApiResult<Vec<crate::lockout::LoginLockout>> {
+        let db = self.require_db()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT scope, subject, failed_attempts, lockout_count, last_failure_at, locked_until
+            FROM login_lockouts
+            WHERE locked_until > NOW()
+               OR last_failure_at > NOW() - make_interval(secs => $1)
+            ORDER BY last_failure_at DESC
+            "#,
+        )
+        .bind(self.login_lockout.window_secs as f64)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let locked_until: Option<chrono::DateTime<chrono::Utc>> = row.get("locked_until");
+                crate::lockout::LoginLockout {
+                    scope: crate::lockout::LockoutScope::parse(row.get("scope"))
+                        .unwrap_or(crate::lockout::LockoutScope::Ip),
+                    subject: row.get("subject"),
+                    failed_attempts: row.get("failed_attempts"),
+                    lockout_count: row.get("lockout_count"),
+                    last_failure_at: row
+                        .get::<chrono::DateTime<chrono::Utc>, _>("last_failure_at")
+                        .to_rfc3339(),
+                    locked_until: locked_until
+                        .filter(|until| *until > chrono::Utc::now())
+                        .map(|until| until.to_rfc3339()),
+                }
+            })
+            .collect())
+    }
+
+    /// Respond to a rotated refresh token being presented again: revoke its
+    /// family and every other active session of the user, and record a
+    /// security audit event.  Returns how many tokens were revoked.
+    pub async fn revoke_refresh_token_family_on_reuse(
+        &self,
+        user_id: Uuid,
+        token_id: Uuid,
+        family_id: Uuid,
+    ) -> ApiResult<u64> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+
+        // The family belongs to the user, so this covers it too.
+        let revoked = sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW(), revoked_reason = 'reuse_detected'
+            WHERE user_id = $1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (user_id, action, resource_type, resource_id, status, metadata)
+            VALUES ($1, 'refresh_token_reuse', 'refresh_token_family', $2, 'revoked', $3)
+            "#,
+        )
+        .bind(user_id)
+        .bind(family_id.to_string())
+        .bind(serde_json::json!({
+            "reused_token_id": token_id,
+            "revoked_tokens": revoked,
+        }))
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        tracing::warn!(
+            %user_id,
+            %family_id,
+            %token_id,
+            revoked,
+            "Refresh token reuse detected; revoked all sessions for user"
+        );
+        Ok(revoked)
+    }
+
+    /// Notification queue able to deliver account tokens; a token nobody
+    /// can receive is not worth issuing.
+    fn account_token_mailer(&self) -> Option<&crate::notifier::NotificationDispatcher> {
+        self.notifications
+            .as_ref()
+            .filter(|notifications| notifications.backend_name() != "none")
+    }
+
+    /// Issue a single-use token for `purpose`, invalidating the user's
+    /// earlier unused ones.  Returns the cleartext token and its lifetime.
+    async fn issue_account_token(
+        &self,
+        user_id: Uuid,
+        purpose: crate::account_tokens::AccountTokenPurpose,
+        email: Option<&str>,
+    ) -> ApiResult<(String, u64)> {
+        let db = self.require_db()?;
+        let ttl_secs = self.account_tokens.ttl_secs(purpose);
+        let token = crate::auth::generate_refresh_token();
+        let mut tx = db.begin().await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM account_tokens
+            WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(purpose.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO account_tokens (user_id, purpose, token_hash, email, expires_at)
+            VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5))
+            "#,
+        )
+        .bind(user_id)
+        .bind(purpose.as_str())
+        .bind(crate::auth::hash_account_token(&token))
+        .bind(email)
+        .bind(ttl_secs as f64)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok((token, ttl_secs))
+    }
+
+    /// Mail a password reset token to the account's email.  Unknown
+    /// usernames and accounts without an email are silently ignored so the
+    /// endpoint does not reveal which accounts exist.
+    pub async fn request_password_reset(&self, username: &str) -> ApiResult<()> {
+        let db = self.require_db()?;
+        let Some(notifications) = self.account_token_mailer() else {
+            tracing::warn!("Password reset requested but notifications are disabled");
+            return Ok(());
+        };
+
+        let row = sqlx::query(
+            "SELECT user_id, email FROM users WHERE username = $1 AND email IS NOT NULL",
+        )
+        .bind(username)
+        .fetch_optional(db)
+        .await?;
+        let Some(row) = row else {
+            return Ok(());
+        };
+
+        let user_id: Uuid = row.get("user_id");
+        let email: String = row.get("email");
+        let (token, ttl_secs) = self
+            .issue_account_token(
+                user_id,
+                crate::account_tokens::AccountTokenPurpose::PasswordReset,
+                None,
+            )
+            .await?;
+        notifications.enqueue(crate::notifier::Notification::password_reset(
+            user_id,
+            Some(email),
+            &token,
+            ttl_secs,
+        ));
+        Ok(())
+    }
+
+    /// Mail a verification token for the user's current email, if any.
+    pub async fn send_email_verification(&self, user_id: Uuid) -> ApiResult<()> {
+        let db = self.require_db()?;
+        let Some(notifications) = self.account_token_mailer() else {
+            return Ok(());
+        };
+
+        let email: Option<String> = sqlx::query_scalar(
+            "SELECT email FROM users WHERE user_id = $1 AND email_verified_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?
+        .flatten();
+        let Some(email) = email else {
+            return Ok(());
+        };
+
+        let (token, ttl_secs) = self
+            .issue_account_token(
+                user_id,
+                crate::account_tokens::AccountTokenPurpose::EmailVerification,
+                Some(&email),
+            )
+            .await?;
+        notifications.enqueue(crate::notifier::Notification::email_verification(
+            user_id, email, &token, ttl_secs,
+        ));
+        Ok(())
+    }
+
+    /// Redeem a password reset token: store `password_hash`, revoke every
+    /// session of the user and lift any lock on the account.  Returns the
+    /// user, or `None` if the token is unknown, used or expired.
+    pub async fn confirm_password_reset(
+        &self,
+        token: &str,
+        password_hash: &str,
+    ) -> ApiResult<Option<Uuid>> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+
+        let user_id: Option<Uuid> = sqlx::query_scalar(
+            r#"
+            UPDATE account_tokens
+            SET used_at = NOW()
+            WHERE token_hash = $1
+              AND purpose = 'password_reset'
+              AND used_at IS NULL
+              AND expires_at > NOW()
+            RETURNING user_id
+            "#,
+        )
+        .bind(crate::auth::hash_account_token(token))
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        sqlx::query("UPDATE users SET password_hash = $2 WHERE user_id = $1")
+            .bind(user_id)
+            .bind(password_hash)
+            .execute(&mut *tx)
+            .await?;
+
+        let revoked = sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW(), revoked_reason = 'password_reset'
+            WHERE user_id = $1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query(
+            r#"
+            DELETE FROM login_lockouts
+            WHERE scope = 'user'
+              AND subject = (SELECT username FROM users WHERE user_id = $1)
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (user_id, action, resource_type, resource_id, status, metadata)
+            VALUES ($1, 'password_reset', 'user', $2, 'success', $3)
+            "#,
+        )
+        .bind(user_id)
+        .bind(user_id.to_string())
+        .bind(serde_json::json!({ "revoked_tokens": revoked }))
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(user_id))
+    }
+
+    /// Redeem an email verification token.  Returns `false` if the token is
+    /// unknown, used or expired, or the account's email changed since it
+    /// was sent.
+    pub async fn verify_email(&self, token: &str) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+
+        let redeemed = sqlx::query(
+            r#"
+            UPDATE account_tokens
+            SET used_at = NOW()
+            WHERE token_hash = $1
+              AND purpose = 'email_verification'
+              AND used_at IS NULL
+              AND expires_at > NOW()
+            RETURNING user_id, email
+            "#,
+        )
+        .bind(crate::auth::hash_account_token(token))
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(redeemed) = redeemed else {
+            return Ok(false);
+        };
+
+        let verified = sqlx::query(
+            r#"
+            UPDATE users
+            SET email_verified_at = COALESCE(email_verified_at, NOW())
+            WHERE user_id = $1 AND email = $2
+            "#,
+        )
+        .bind(redeemed.get::<Uuid, _>("user_id"))
+        .bind(redeemed.get::<Option<String>, _>("email"))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        tx.commit().await?;
+        Ok(verified)
+    }
+
+    /// Sweep nodes that have not sent a heartbeat within the configured
+    /// threshold and mark them as offline.  Also disconnects their active
+    /// task assignments and attempts to reassign those tasks to other nodes.
+    ///
+    /// The timeout is controlled by the `NODE_HEARTBEAT_TIMEOUT_MINUTES`
+    /// environment variable (default: 5 minutes).
+    ///
+    /// Returns the number of nodes swept offline.
+    #[tracing::instrument(skip_all)]
+    pub async fn sweep_offline_nodes(&self) -> ApiResult<usize> {
+        let Ok(db) = self.require_db() else {
+            return Ok(0);
+        };
+
+        let threshold_minutes: i64 = deployment_profile::setting("NODE_HEARTBEAT_TIMEOUT_MINUTES")
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &i64| *v > 0)
+            .unwrap_or(5);
+
+        // Mark stale online nodes offline and collect their IDs in one statement.
+        let stale_node_ids: Vec<String> =
+            sqlx::query_scalar(crate::hot_queries::SWEEP_OFFLINE_NODES)
+                .bind(threshold_minutes)
+                .fetch_all(db)
+                .await?;
+
+        if stale_node_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let count = stale_node_ids.len();
+
+        for node_id in &stale_node_ids {
+            // Collect the tasks this node was still actively assigned to.
+            let affected_tasks = sqlx::query(
+                r#"
+                SELECT ta.task_id, t.min_nodes, t.task_type, t.require_gpu
+                FROM task_assignments ta
+                JOIN tasks t ON t.task_id = ta.task_id
+                WHERE ta.node_id = $1
+                  AND ta.disconnected_at IS NULL
+                "#,
+            )
+            .bind(node_id)
+            .fetch_all(db)
+            .await?;
+
+            // Disconnect all active assignments for this node.
+            sqlx::query(
+                r#"
+                UPDATE task_assignments
+                SET disconnected_at = NOW(),
+                    execution_status = CASE
+                        WHEN execution_status = 'in_progress' THEN 'failed'
+                        ELSE execution_status
+                    END
+                WHERE node_id = $1 AND disconnected_at IS NULL
+                "#,
+            )
+            .bind(node_id)
+            .execute(db)
+            .await?;
+
+            // For each affected task update its status and attempt reassignment.
+            for task_row in affected_tasks {
+                let task_id: Uuid = task_row.get("task_id");
+                let min_nodes: i32 = task_row.get("min_nodes");
+                let task_type: String = task_row.get("task_type");
+                let require_gpu: bool = task_row.get("require_gpu");
+
+                let _ = self
+                    .update_task_status_from_assignments(task_id, min_nodes as u32)
+                    .await;
+
+                if let Ok(Some(status)) = self.get_task_status(task_id).await {
+                    if status == "pending" {
+                        if let Some(entry) = task_type_registry_entry(&task_type) {
+                            let _ = self
+                                .assign_available_nodes_for_task(
+                                    task_id,
+                                    &task_type,
+                                    entry,
+                                    min_nodes as u32,
+                                    require_gpu,
+                                )
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        tracing::info!(swept = count, "Node offline sweep completed");
+        Ok(count)
+    }
+
+    /// Soft-delete nodes that have been in the `offline` state for longer
+    /// than `NODE_REMOVAL_TIMEOUT_MINUTES` (default: 5 minutes) without
+    /// reconnecting.
+    ///
+    /// A soft-deleted node (`deleted_at IS NOT NULL`) disappears from the
+    /// node list and from cluster statistics, so the dashboard totals drop to
+    /// zero once all nodes have been purged.
+    ///
+    /// Returns the number of nodes removed.
+    #[tracing::instrument(skip_all)]
+    pub async fn purge_stale_offline_nodes(&self) -> ApiResult<usize> {
+        let Ok(db) = self.require_db() else {
+            return Ok(0);
+        };
+
+        let threshold_minutes: i64 = std::env::var("NODE_REMOVAL_TIMEOUT_MINUTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &i64| *v > 0)
+            .unwrap_or(5);
+
+        let purged: Vec<String> = sqlx::query_scalar(
+            r#"
+            WITH purged AS (
+                UPDATE nodes
+                SET deleted_at = NOW(), updated_at = NOW()
+                WHERE status = 'offline'
+                  AND deleted_at IS NULL
+                  AND updated_at < NOW() - (interval '1 minute' * $1)
+                RETURNING node_id
+            )
+            SELECT node_id FROM purged
+            "#,
+        )
+        .bind(threshold_minutes)
+        .fetch_all(db)
+        .await?;
+
+        let count = purged.len();
+        tracing::info!(purged = count, "Stale offline node purge completed");
+        Ok(count)
+    }
+
+    /// Purge history past its retention window, or with `dry_run` only count
+    /// it.  Uses the configured dry-run setting when `dry_run` is `None`.
+    #[tracing::instrument(skip_all)]
+    pub async fn run_retention(
+        &self,
+        dry_run: Option<bool>,
+    ) -> ApiResult<crate::retention::RetentionReport> {
+        use crate::retention::{RetentionTableReport, RetentionTarget, MAX_BATCHES_PER_RUN};
+
+        let db = self.require_db()?;
+        let policy = self.retention;
+        let dry_run = dry_run.unwrap_or(policy.dry_run);
+        let mut tables = Vec::with_capacity(RetentionTarget::ALL.len());
+
+        for target in RetentionTarget::ALL {
+            let Some(days) = policy.window_days(target) else {
+                tables.push(RetentionTableReport {
+                    table: target,
+                    retention_days: None,
+                    eligible_rows: 0,
+                    purged_rows: 0,
+                });
+                continue;
+            };
+
+            let eligible_rows: i64 = sqlx::query_scalar(target.count_sql())
+                .bind(days as i32)
+                .fetch_one(db)
+                .await?;
+
+            let mut purged_rows = 0i64;
+            if !dry_run && eligible_rows > 0 {
+                for _ in 0..MAX_BATCHES_PER_RUN {
+                    let mut tx = db.begin().await?;
+                    let rows: Vec<serde_json::Value> = sqlx::query_scalar(target.delete_sql())
+                        .bind(days as i32)
+                        .bind(policy.batch_size)
+                        .fetch_all(&mut *tx)
+                        .await?;
+
+                    if let (Some(archiver), false) = (&self.retention_archiver, rows.is_empty()) {
+                        // Dropping the transaction rolls the delete back.
+                        archiver.archive(target, &rows).await.map_err(|err| {
+                            tracing::error!(
+                                table = target.as_str(),
+                                "Failed to archive rows before purge: {err:#}"
+                            );
+                            ApiError::internal_error("Failed to archive rows before purge")
+                        })?;
+                    }
+                    tx.commit().await?;
+
+                    purged_rows += rows.len() as i64;
+                    if (rows.len() as i64) < policy.batch_size {
+                        break;
+                    }
+                }
+            }
+
+            crate::middleware::metrics::record_retention_run(
+                target.as_str(),
+                eligible_rows,
+                purged_rows,
+            );
+            tables.push(RetentionTableReport {
+                table: target,
+                retention_days: Some(days),
+                eligible_rows,
+                purged_rows,
+            });
+        }
+
+        let report = crate::retention::RetentionReport { dry_run, tables };
+        tracing::info!(
+            dry_run,
+            purged = report.purged_rows(),
+            report = %serde_json::to_string(&report.tables).unwrap_or_default(),
+            "Retention run completed"
+        );
+        Ok(report)
+    }
+
+    /// Fold closed buckets of raw heartbeat samples into telemetry rollups.
+    /// Each resolution resumes at its newest rolled-up bucket, which is
+    /// recomputed.  Returns the number of buckets written.
+    #[tracing::instrument(skip_all)]
+    pub async fn run_telemetry_rollup(&self) -> ApiResult<u64> {
+        use crate::telemetry::TelemetryResolution;
+
+        let db = self.require_db()?;
+        let now = chrono::Utc::now();
+        let sql = crate::telemetry::rollup_sql();
+        let mut written = 0u64;
+
+        for resolution in TelemetryResolution::ROLLUPS {
+            let Some(width) = resolution.bucket_secs() else {
+                continue;
+            };
+            let resume_at: Option<chrono::DateTime<chrono::Utc>> = sqlx::query_scalar(
+                r#"
+                SELECT MAX(bucket_start)
+                FROM node_telemetry_rollups
+                WHERE resolution = $1
+                "#,
+            )
+            .bind(resolution.as_str())
+            .fetch_one(db)
+            .await?;
+
+            let result = sqlx::query(&sql)
+                .bind(resolution.as_str())
+                .bind(width)
+                .bind(resume_at)
+                .bind(resolution.bucket_start(now))
+                .execute(db)
+                .await?;
+            written += result.rows_affected();
+        }
+
+        Ok(written)
+    }
+
+    /// Record a failed execution attempt by `node_id` and apply the task's
+    /// retry policy.
+    ///
+    /// The node's assignment is marked `failed` and detached.  While retries
+    /// remain the task returns to `pending`, the node is excluded from future
+    /// attempts, and reassignment waits `retry_backoff_sec`.  Once retries are
+    /// exhausted the task is marked `failed` and every assignment is released.
+    #[tracing::instrument(skip_all, fields(%task_id, %node_id))]
+    pub async fn fail_task_attempt(
+        &self,
+        task_id: Uuid,
+        node_id: &str,
+        reason: &str,
+    ) -> ApiResult<TaskAttemptOutcome> {
+        self.fail_reported_attempt(task_id, node_id, reason, None)
+            .await
+    }
+
+    /// [`Self::fail_task_attempt`] for an attempt whose node reported the
+    /// error, and possibly the resources it used, itself.  A reported
+    /// attempt counts towards its task type's statistics.
+    async fn fail_reported_attempt(
+        &self,
+        task_id: Uuid,
+        node_id: &str,
+        reason: &str,
+        usage: Option<AttemptUsage>,
+    ) -> ApiResult<TaskAttemptOutcome> {
+        let failed = retry_task_transition(task_id, "attempt_failed", || {
+            self.try_fail_task_attempt(task_id, node_id, reason, usage)
+        })
+        .await?;
+        let FailedAttempt {
+            task_type,
+            min_nodes,
+            require_gpu,
+            max_retries,
+            retry_count,
+            will_retry,
+            freed_nodes,
+        } = failed;
+
+        tracing::warn!(
+            %task_id,
+            node_id,
+            reason,
+            retry_count,
+            max_retries,
+            will_retry,
+            "Task attempt failed"
+        );
+        self.export_telemetry(crate::telemetry_export::ExportRecord::Task(
+            crate::telemetry_export::TaskMetric {
+                task_id,
+                task_type: task_type.clone(),
+                node_id: node_id.to_string(),
+                outcome: if will_retry {
+                    crate::telemetry_export::TaskOutcome::Retrying
+                } else {
+                    crate::telemetry_export::TaskOutcome::Failed
+                },
+                recorded_at: chrono::Utc::now(),
+                retry_count: retry_count as u32,
+                duration_ms: None,
+                energy_wh: usage.and_then(|usage| usage.energy_wh),
+            },
+        ));
+
+        if will_retry {
+            if let Some(entry) = task_type_registry_entry(&task_type) {
+                self.assign_available_nodes_for_task(
+                    task_id,
+                    &task_type,
+                    entry,
+                    min_nodes as u32,
+                    require_gpu,
+                )
+                .await?;
+            }
+        }
+
+        for freed_node in freed_nodes {
+            let _ = self.assign_pending_tasks_for_node(&freed_node).await;
+        }
+
+        let retry_count = if will_retry {
+            retry_count + 1
+        } else {
+            retry_count
+        };
+
+        Ok(TaskAttemptOutcome {
+            status: if will_retry { "pending" } else { "failed" },
+            retry_count: retry_count as u32,
+            retries_remaining: (max_retries - retry_count).max(0) as u32,
+        })
+    }
+
+    async fn try_fail_task_attempt(
+        &self,
+        task_id: Uuid,
+        node_id: &str,
+        reason: &str,
+        usage: Option<AttemptUsage>,
+    ) -> ApiResult<TaskTransition<FailedAttempt>> {
+        let db = self.require_db()?;
+        let task_row = sqlx::query(
+            r#"
+            SELECT task_type, min_nodes, require_gpu, max_retries, retry_count,
+                   retry_backoff_sec, version
+            FROM tasks
+            WHERE task_id = $1
+              AND status IN ('pending', 'running')
+            "#,
+        )
+        .bind(task_id)
+        .fetch_optional(db)
+        .await?;
+
+        let Some(task_row) = task_row else {
+            return Err(ApiError::bad_request("Task is not in an executable state"));
+        };
+
+        let task_type: String = task_row.get("task_type");
+        let min_nodes: i32 = task_row.get("min_nodes");
+        let require_gpu: bool = task_row.get("require_gpu");
+        let max_retries: i32 = task_row.get("max_retries");
+        let retry_count: i32 = task_row.get("retry_count");
+        let retry_backoff_sec: i64 = task_row.get("retry_backoff_sec");
+        let version: i64 = task_row.get("version");
+
+        let will_retry = retry_count < max_retries;
+        let mut freed_nodes = vec![node_id.to_string()];
+
+        let mut tx = db.begin().await?;
+
+        let transitioned = if will_retry {
+            sqlx::query(
+                r#"
+                UPDATE tasks
+                SET status = 'pending',
+                    retry_count = retry_count + 1,
+                    last_error = $2,
+                    retry_excluded_nodes = ARRAY_APPEND(retry_excluded_nodes, $3::VARCHAR),
+                    next_attempt_at = CASE
+                        WHEN $4 > 0 THEN NOW() + (interval '1 second' * $4)
+                        ELSE NULL
+                    END,
+                    queued_at = NOW(),
+                    constraints_relaxed_at = NULL,
+                    starving_at = NULL,
+                    updated_at = NOW(),
+                    version = version + 1
+                WHERE task_id = $1
+                  AND version = $5
+                "#,
+            )
+            .bind(task_id)
+            .bind(reason)
+            .bind(node_id)
+            .bind(retry_backoff_sec)
+            .bind(version)
+            .execute(&mut *tx)
+            .await?
+        } else {
+            sqlx::query(
+                r#"
+                UPDATE tasks
+                SET status = 'failed',
+                    last_error = $2,
+                    result = $3,
+                    next_attempt_at = NULL,
+                    updated_at = NOW(),
+                    completed_at = NOW(),
+                    version = version + 1
+                WHERE task_id = $1
+                  AND version = $4
+                "#,
+            )
+            .bind(task_id)
+            .bind(reason)
+            .bind(serde_json::json!({
+                "error": reason,
+                "attempts": retry_count + 1,
+            }))
+            .bind(version)
+            .execute(&mut *tx)
+            .await?
+        };
+
+        if transitioned.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(TaskTransition::Conflict);
+        }
+
+        let failed_assignment = sqlx::query(
+            r#"
+            UPDATE task_assignments
+            SET execution_status = 'failed',
+                execution_completed_at = NOW(),
+                disconnected_at = NOW()
+            WHERE task_id = $1 AND node_id = $2 AND disconnected_at IS NULL
+            "#,
+        )
+        .bind(task_id)
+        .bind(node_id)
+        .execute(&mut *tx)
+        .await?;
+
+        // The attempt already ended, e.g. its result landed or the node handed
+        // the task off; there is nothing left to fail.
+        if failed_assignment.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(ApiError::bad_request(
+                "Node is not actively assigned to this task",
+            ));
+        }
+        if let Some(usage) = usage {
+            self.record_attempt_usage(&mut tx, task_id, node_id, &task_type, false, usage)
+                .await?;
+        }
+
+        // The failed node is excluded from further attempts, and a task that
+        // has exhausted its retries runs nowhere, so drop the sealed secrets
+        // that can no longer be used.
+        sqlx::query(
+            r#"
+            DELETE FROM task_sealed_secrets
+            WHERE task_id = $1 AND ($2 OR node_id = $3)
+            "#,
+        )
+        .bind(task_id)
+        .bind(!will_retry)
+        .bind(node_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if !will_retry {
+            let remaining_nodes = sqlx::query_scalar::<_, String>(
+                r#"
+                SELECT node_id
+                FROM task_assignments
+                WHERE task_id = $1
+                  AND disconnected_at IS NULL
+                "#,
+            )
+            .bind(task_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            self.disconnect_task_assignments(task_id, &mut tx).await?;
+            freed_nodes.extend(remaining_nodes);
+
+            self.enqueue_task_notification(&mut tx, task_id, |creator_id| {
+                crate::notifier::Notification::task_failed(
+                    creator_id,
+                    None,
+                    task_id,
+                    reason,
+                    (retry_count + 1) as u32,
+                )
+            })
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(TaskTransition::Applied(FailedAttempt {
+            task_type,
+            min_nodes,
+            require_gpu,
+            max_retries,
+            retry_count,
+            will_retry,
+            freed_nodes,
+        }))
+    }
+
+    /// Apply retry policies that depend on elapsed time.
+    ///
+    /// - Active assignments of tasks with `max_retries > 0` that have exceeded
+    ///   `max_execution_time_sec` without a result are failed as timeouts.
+    /// - Pending tasks whose retry backoff has elapsed are offered to
+    ///   eligible nodes again.
+    ///
+    /// Returns the number of timed-out attempts plus re-queued tasks handled.
+    #[tracing::instrument(skip_all)]
+    pub async fn sweep_task_retries(&self) -> ApiResult<usize> {
+        let Ok(db) = self.require_db() else {
+            return Ok(0);
+        };
+
+        let timed_out = sqlx::query(
+            r#"
+            SELECT ta.task_id, ta.node_id
+            FROM task_assignments ta
+            JOIN tasks t ON t.task_id = ta.task_id
+            WHERE t.max_retries > 0
+              AND t.status IN ('pending', 'running')
+              AND t.task_type <> 'connect_only'
+              AND ta.disconnected_at IS NULL
+              AND ta.execution_status IN ('assigned', 'in_progress')
+              AND ta.assigned_at < NOW() - (interval '1 second' * t.max_execution_time_sec)
+            "#,
+        )
+        .fetch_all(db)
+        .await?;
+
+        let mut handled = 0;
+        for row in timed_out {
+            let task_id: Uuid = row.get("task_id");
+            let node_id: String = row.get("node_id");
+            match self
+                .fail_task_attempt(task_id, &node_id, "execution timed out")
+                .await
+            {
+                Ok(_) => handled += 1,
+                Err(err) => {
+                    tracing::warn!(%task_id, node_id, "Failed to time out task attempt: {err:?}");
+                }
+            }
+        }
+
+        let ready_tasks = sqlx::query(
+            r#"
+            UPDATE tasks
+            SET next_attempt_at = NULL
+            WHERE status = 'pending'
+              AND next_attempt_at IS NOT NULL
+              AND next_attempt_at <= NOW()
+            RETURNING task_id, task_type, min_nodes, require_gpu
+            "#,
+        )
+        .fetch_all(db)
+        .await?;
+
+        for row in ready_tasks {
+            let task_id: Uuid = row.get("task_id");
+            let task_type: String = row.get("task_type");
+            let min_nodes: i32 = row.get("min_nodes");
+            let require_gpu: bool = row.get("require_gpu");
+
+            if let Some(entry) = task_type_registry_entry(&task_type) {
+                self.assign_available_nodes_for_task(
+                    task_id,
+                    &task_type,
+                    entry,
+                    min_nodes as u32,
+                    require_gpu,
+                )
+                .await?;
+                handled += 1;
+            }
+        }
+
+        Ok(handled)
+    }
+
+    /// Age tasks that have been pending too long (see [`crate::starvation`]).
+    ///
+    /// - Past the relax threshold, the preferred node type is dropped for
+    ///   task types that allow it and the task is offered to nodes again.
+    /// - Past the starving threshold, the task is flagged and its creator
+    ///   notified once.
+    /// - Past the unschedulable threshold, the task is marked
+    ///   `unschedulable` with a diagnostics summary and any partial
+    ///   assignments are released.
+    ///
+    /// Returns the number of tasks whose stage changed.
+    #[tracing::instrument(skip_all)]
+    pub async fn sweep_starving_tasks(&self) -> ApiResult<usize> {
+        let Ok(db) = self.require_db() else {
+            return Ok(0);
+        };
+        let Some(earliest_secs) = self.starvation.earliest_threshold_secs() else {
+            return Ok(0);
+        };
+
+        let aged_tasks = sqlx::query(
+            r#"
+            SELECT
+                t.task_id,
+                t.task_type,
+                t.min_nodes,
+                t.require_gpu,
+                t.constraints_relaxed_at IS NOT NULL AS constraints_relaxed,
+                t.starving_at IS NOT NULL AS starving,
+                t.version,
+                EXTRACT(EPOCH FROM (NOW() - t.queued_at))::BIGINT AS pending_secs
+            FROM tasks t
+            WHERE t.status = 'pending'
+              AND t.deleted_at IS NULL
+              AND (t.next_attempt_at IS NULL OR t.next_attempt_at <= NOW())
+              AND t.queued_at <= NOW() - (interval '1 second' * $1)
+            ORDER BY t.queued_at ASC
+            "#,
+        )
+        .bind(earliest_secs as i64)
+        .fetch_all(db)
+        .await?;
+
+        let mut handled = 0;
+        for row in aged_tasks {
+            let task_id: Uuid = row.get("task_id");
+            let task_type: String = row.get("task_type");
+            let min_nodes: i32 = row.get("min_nodes");
+            let require_gpu: bool = row.get("require_gpu");
+            let pending_secs = row.get::<i64, _>("pending_secs").max(0) as u64;
+            let Some(entry) = task_type_registry_entry(&task_type) else {
+                continue;
+            };
+
+            let result = if self
+                .starvation
+                .reached(StarvationStage::Unschedulable, pending_secs)
+            {
+                self.mark_task_unschedulable(
+                    task_id,
+                    entry,
+                    min_nodes,
+                    require_gpu,
+                    pending_secs,
+                    row.get("constraints_relaxed"),
+                    row.get("version"),
+                )
+                .await
+            } else {
+                self.age_pending_task(
+                    task_id,
+                    entry,
+                    min_nodes,
+                    require_gpu,
+                    pending_secs,
+                    row.get("constraints_relaxed"),
+                    row.get("starving"),
+                )
+                .await
+            };
+
+            match result {
+                Ok(true) => handled += 1,
+                Ok(false) => {}
+                Err(err) => {
+                    tracing::warn!(%task_id, "Failed to age starving task: {err:?}");
+                }
+            }
+        }
+
+        Ok(handled)
+    }
+
+    /// Relax and/or flag a pending task that has not yet hit the
+    /// unschedulable threshold.  Returns whether anything changed.
+    #[allow(clippy::too_many_arguments)]
+    async fn age_pending_task(
+        &self,
+        task_id: Uuid,
+        entry: &TaskTypeRegistryEntry,
+        min_nodes: i32,
+        require_gpu: bool,
+        pending_secs: u64,
+        constraints_relaxed: bool,
+        starving: bool,
+    ) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        let mut changed = false;
+
+        if !constraints_relaxed
+            && entry.node_type_relaxable
+            && self
+                .starvation
+                .reached(StarvationStage::Relaxed, pending_secs)
+        {
+            let relaxed = sqlx::query(
+                r#"
+                UPDATE tasks
+                SET constraints_relaxed_at = NOW(), updated_at = NOW()
+                WHERE task_id = $1
+                  AND status = 'pending'
+                  AND constraints_relaxed_at IS NULL
+                "#,
+            )
+            .bind(task_id)
+            .execute(db)
+            .await?;
+
+            if relaxed.rows_affected() > 0 {
+                tracing::info!(
+                    %task_id,
+                    pending_secs,
+                    preferred_node_type = entry.preferred_node_type.as_str(),
+                    "Relaxed preferred node type for long-pending task"
+                );
+                changed = true;
+                self.assign_available_nodes_for_task(
+                    task_id,
+                    entry.task_type,
+                    entry,
+                    min_nodes as u32,
+                    require_gpu,
+                )
+                .await?;
+            }
+        }
+
+        if !starving
+            && self
+                .starvation
+                .reached(StarvationStage::Starving, pending_secs)
+        {
+            let mut tx = db.begin().await?;
+            let flagged = sqlx::query(
+                r#"
+                UPDATE tasks
+                SET starving_at = NOW(), updated_at = NOW()
+                WHERE task_id = $1
+                  AND status = 'pending'
+                  AND starving_at IS NULL
+                "#,
+            )
+            .bind(task_id)
+            .execute(&mut *tx)
+            .await?;
+
+            if flagged.rows_affected() > 0 {
+                self.enqueue_task_notification(&mut tx, task_id, |creator_id| {
+                    crate::notifier::Notification::task_starving(
+                        creator_id,
+                        None,
+                        task_id,
+                        pending_secs,
+                    )
+                })
+                .await?;
+                tx.commit().await?;
+
+                tracing::warn!(%task_id, pending_secs, "Task is starving");
+                changed = true;
+            } else {
+                tx.rollback().await?;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Give up on a pending task: record why no node matched, mark it
+    /// `unschedulable` and release any nodes it was partially holding.
+    #[allow(clippy::too_many_arguments)]
+    async fn mark_task_unschedulable(
+        &self,
+        task_id: Uuid,
+        entry: &TaskTypeRegistryEntry,
+        min_nodes: i32,
+        require_gpu: bool,
+        pending_secs: u64,
+        constraints_relaxed: bool,
+        version: i64,
+    ) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        let any_node_type = constraints_relaxed && entry.node_type_relaxable;
+
+        let funnel_row = sqlx::query(
+            r#"
+            SELECT
+                COUNT(*) AS online_nodes,
+                COUNT(*) FILTER (WHERE type_ok) AS node_type_matches,
+                COUNT(*) FILTER (WHERE type_ok AND capable) AS capability_matches,
+                COUNT(*) FILTER (
+                    WHERE type_ok AND capable AND NOT excluded AND active_attachments < slot_limit
+                ) AS available_nodes,
+                (
+                    SELECT COUNT(*)
+                    FROM task_assignments ta
+                    WHERE ta.task_id = $7
+                      AND ta.disconnected_at IS NULL
+                ) AS nodes_assigned
+            FROM (
+                SELECT
+                    (n.node_type = ANY($1) OR $2) AS type_ok,
+                    (
+                        n.cpu_cores >= $3
+                        AND n.memory_gb >= $4
+                        AND n.bandwidth_mbps >= $5
+                        AND ($6 = FALSE OR n.gpu_available = TRUE)
+                        AND n.labels @> (SELECT t.node_selector FROM tasks t WHERE t.task_id = $7)
+                        AND EXISTS (
+                            SELECT 1
+                            FROM tasks t
+                            WHERE t.task_id = $7
+                              AND NOT (n.region = ANY(t.excluded_regions))
+                              AND (
+                                    t.region_strictness <> 'require'
+                                    OR n.region = ANY(t.preferred_regions)
+                                  )
+                        )
+                    ) AS capable,
+                    (
+                        n.node_id = ANY(
+                            SELECT UNNEST(t.retry_excluded_nodes) FROM tasks t WHERE t.task_id = $7
+                        )
+                        OR COALESCE(n.flap_breaker_until > NOW(), FALSE)
+                        -- The operator opted the node out of this task type.
+                        OR NOT (
+                            CARDINALITY(n.allowed_task_types) = 0
+                            OR $10 = ANY(n.allowed_task_types)
+                        )
+                        OR $10 = ANY(n.blocked_task_types)
+                        -- The task is pinned to another node.
+                        OR EXISTS (
+                            SELECT 1 FROM tasks t
+                            WHERE t.task_id = $7 AND t.pinned_node_id <> n.node_id
+                        )
+                    ) AS excluded,
+                    (
+                        SELECT COUNT(*)
+                        FROM task_assignments active
+                        JOIN tasks busy ON busy.task_id = active.task_id
+                        WHERE active.node_id = n.node_id
+                          AND active.disconnected_at IS NULL
+                          AND active.task_id <> $7
+                          AND busy.slot_class = $9
+                    ) AS active_attachments,
+                    COALESCE(
+                        CASE $9
+                            WHEN 'connect' THEN n.connect_slots
+                            WHEN 'gpu' THEN n.gpu_slots
+                            ELSE n.wasm_slots
+                        END,
+                        $8
+                    ) AS slot_limit
+                FROM nodes n
+                WHERE n.deleted_at IS NULL
+                  AND n.status = 'online'
+            ) candidates
+            "#,
+        )
+        .bind(entry.serving_node_types())
+        .bind(any_node_type)
+        .bind(entry.minimum_capabilities.cpu_cores as i32)
+        .bind(entry.minimum_capabilities.memory_gb)
+        .bind(entry.minimum_capabilities.bandwidth_mbps)
+        .bind(require_gpu || entry.minimum_capabilities.gpu_available)
+        .bind(task_id)
+        .bind(Self::max_active_task_attachments_per_node())
+        .bind(SlotClass::for_task(entry.task_type, require_gpu).as_str())
+        .bind(entry.task_type)
+        .fetch_one(db)
+        .await?;
+
+        let funnel = crate::starvation::EligibilityFunnel {
+            online_nodes: funnel_row.get("online_nodes"),
+            node_type_matches: funnel_row.get("node_type_matches"),
+            capability_matches: funnel_row.get("capability_matches"),
+            available_nodes: funnel_row.get("available_nodes"),
+        };
+        let diagnostics = crate::starvation::diagnostics_summary(
+            pending_secs,
+            min_nodes as i64,
+            funnel_row.get("nodes_assigned"),
+            entry.preferred_node_type.as_str(),
+            any_node_type,
+            &funnel,
+        );
+        let reason = diagnostics["reason"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let mut tx = db.begin().await?;
+        let marked = sqlx::query(
+            r#"
+            UPDATE tasks
+            SET status = 'unschedulable',
+                scheduling_diagnostics = $2,
+                last_error = $3,
+                updated_at = NOW(),
+                completed_at = NOW(),
+                version = version + 1
+            WHERE task_id = $1
+              AND version = $4
+            "#,
+        )
+        .bind(task_id)
+        .bind(&diagnostics)
+        .bind(format!("unschedulable: {reason}"))
+        .bind(version)
+        .execute(&mut *tx)
+        .await?;
+
+        // The task moved on since the sweep read it; the next sweep decides
+        // again from its new state.
+        if marked.rows_affected() == 0 {
+            tx.rollback().await?;
+            crate::middleware::metrics::record_task_version_conflict("unschedulable");
+            return Ok(false);
+        }
+
+        let freed_nodes: Vec<String> = sqlx::query_scalar(
+            r#"
+            UPDATE task_assignments
+            SET disconnected_at = NOW()
+            WHERE task_id = $1
+              AND disconnected_at IS NULL
+            RETURNING node_id
+            "#,
+        )
+        .bind(task_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        self.enqueue_task_notification(&mut tx, task_id, |creator_id| {
+            crate::notifier::Notification::task_unschedulable(
+                creator_id,
+                None,
+                task_id,
+                diagnostics,
+            )
+        })
+        .await?;
+        tx.commit().await?;
+
+        tracing::warn!(%task_id, pending_secs, reason, "Task marked unschedulable");
+
+        for freed_node in freed_nodes {
+            let _ = self.assign_pending_tasks_for_node(&freed_node).await;
+        }
+
+        Ok(true)
+    }
+
+    /// Accept an execution result submitted by a node owner.
+    ///
+    /// Validates that:
+    /// - The authenticated user owns the reporting node.
+    /// - The node is actively assigned to the task.
+    /// - The task is in a runnable state (`running` or `pending`).
+    /// - If `proof_data` is supplied, the ZK proof is verified before the
+    ///   result is stored.
+    ///
+    /// On success the task is marked `completed` and the result is persisted.
+    /// All remaining node assignments are disconnected so those nodes become
+    /// available for other pending tasks.
+    #[tracing::instrument(skip_all, fields(%task_id))]
+    pub async fn submit_task_result(
+        &self,
+        task_id: Uuid,
+        submission: NodeTaskResult,
+        owner_id: Uuid,
+    ) -> ApiResult<serde_json::Value> {
+        let db = self.require_db()?;
+        // Verify the reporting node belongs to the authenticated user.
+        let node_exists: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM nodes
+                WHERE node_id = $1 AND (owner_id = $2 OR org_role_at_least(org_id, $2, 'member')) AND deleted_at IS NULL
+            )
+            "#,
+        )
+        .bind(&submission.node_id)
+        .bind(owner_id)
+        .fetch_one(db)
+        .await?;
+
+        if !node_exists {
+            return Err(ApiError::not_found_or_forbidden(
+                "Node not found or not owned by you",
+            ));
+        }
+
+        // Fetch task metadata.
+        let task_row = sqlx::query(
+            r#"
+            SELECT task_type, status, require_proof, retry_count
+            FROM tasks
+            WHERE task_id = $1
+            "#,
+        )
+        .bind(task_id)
+        .fetch_optional(db)
+        .await?;
+
+        let Some(task_row) = task_row else {
+            return Err(ApiError::not_found(format!("Task {} not found", task_id)));
+        };
+
+        let task_type: String = task_row.get("task_type");
+        let task_status: String = task_row.get("status");
+        let require_proof: bool = task_row.get("require_proof");
+        let retry_count = task_row.get::<i32, _>("retry_count") as u32;
+
+        // connect_only tasks complete via session lifecycle, not node results.
+        if task_type == "connect_only" {
+            return Err(ApiError::bad_request(
+                "connect_only tasks complete via the connect-session API, not this endpoint",
+            ));
+        }
+
+        if task_status != "running" && task_status != "pending" {
+            return Err(ApiError::bad_request(format!(
+                "Task is not in an executable state (current status: {})",
+                task_status
+            )));
+        }
+
+        // Verify the node is actively assigned to this task.
+        let is_assigned: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM task_assignments
+                WHERE task_id = $1 AND node_id = $2 AND disconnected_at IS NULL
+            )
+            "#,
+        )
+        .bind(task_id)
+        .bind(&submission.node_id)
+        .fetch_one(db)
+        .await?;
+
+        if !is_assigned {
+            return Err(ApiError::bad_request(
+                "Node is not actively assigned to this task",
+            ));
+        }
+
+        // Keep the sandbox report for failed attempts too: it is evidence of
+        // what the sandbox allowed either way.
+        if let Some(ref signed) = submission.sandbox_report {
+            self.record_sandbox_report(task_id, &submission.node_id, signed)
+                .await?;
+        }
+
+        // A failed attempt re-queues the task (or fails it once retries are
+        // exhausted) instead of recording a result.
+        if let Some(ref error) = submission.error {
+            let outcome = self
+                .fail_reported_attempt(
+                    task_id,
+                    &submission.node_id,
+                    error,
+                    Some(AttemptUsage::from_submission(&submission)),
+                )
+                .await?;
+            return Ok(serde_json::json!({
+                "task_id": task_id.to_string(),
+                "status": outcome.status,
+                "node_id": submission.node_id,
+                "error": error,
+                "energy_wh": submission.energy_wh,
+                "cpu_time_ms": submission.cpu_time_ms,
+                "peak_memory_bytes": submission.peak_memory_bytes,
+                "retry_count": outcome.retry_count,
+                "retries_remaining": outcome.retries_remaining,
+            }));
+        }
+
+        if task_type == crate::proof_offload::VERIFY_PROOF_TASK_TYPE {
+            return self.record_proof_verdict(task_id, &submission).await;
+        }
+
+        // Enforce proof requirement declared on the task.
+        if require_proof && submission.proof_data.is_none() {
+            return Err(ApiError::bad_request(
+                "This task requires a ZK proof; include proof_data and public_inputs",
+            ));
+        }
+
+        // Reject proofs stamped outside the clock tolerance, after correcting
+        // for the skew the node last reported in its heartbeat.
+        if let (Some(_), Some(proof_timestamp)) =
+            (&submission.proof_data, submission.proof_timestamp)
+        {
+            let clock_skew_ms: Option<i64> =
+                sqlx::query_scalar("SELECT clock_skew_ms FROM nodes WHERE node_id = $1")
+                    .bind(&submission.node_id)
+                    .fetch_one(db)
+                    .await?;
+
+            let corrected_ms =
+                (proof_timestamp as i64).saturating_mul(1000) - clock_skew_ms.unwrap_or(0);
+            let drift_ms = (corrected_ms - chrono::Utc::now().timestamp_millis()).abs();
+            let tolerance_secs = Self::clock_skew_tolerance_seconds();
+
+            if drift_ms > tolerance_secs * 1000 {
+                return Err(ApiError::bad_request(format!(
+                    "proof_timestamp is {}s away from server time (tolerance {}s)",
+                    drift_ms / 1000,
+                    tolerance_secs
+                )));
+            }
+        }
+
+        // Verify the ZK proof when provided and store it, accepted or not.
+        let proof_id = if let Some(ref proof_data_b64) = submission.proof_data {
+            let proof_bytes =
+                base64::Engine::decode(&base64::engine::general_purpose::STANDARD, proof_data_b64)
+                    .map_err(|_| ApiError::bad_request("proof_data is not valid base64"))?;
+
+            let public_inputs_bytes = submission
+                .public_inputs
+                .as_deref()
+                .map(|pi| {
+                    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, pi)
+                        .map_err(|_| ApiError::bad_request("public_inputs is not valid base64"))
+                })
+                .transpose()?
+                .unwrap_or_default();
+
+            let circuit_id = submission
+                .circuit_id
+                .clone()
+                .unwrap_or_else(|| "default".to_string());
+            let proof = zk_prover::ZKProof::new(proof_bytes, public_inputs_bytes, circuit_id);
+
+            let start = std::time::Instant::now();
+            let (proof, valid) = verify_zk_proof(proof).await?;
+            let proof_id = self
+                .store_proof(StoredProof {
+                    task_id: Some(task_id),
+                    node_id: Some(&submission.node_id),
+                    submitted_by: owner_id,
+                    proof: &proof,
+                    verified: valid,
+                    verification_time_ms: start.elapsed().as_millis() as u64,
+                    error_message: (!valid).then_some(PROOF_VERIFICATION_FAILED),
+                })
+                .await?;
+
+            if !valid {
+                return Err(ApiError::bad_request(PROOF_VERIFICATION_FAILED)
+                    .with_details(serde_json::json!({ "proof_id": proof_id.to_string() })));
+            }
+            Some(proof_id)
+        } else {
+            None
+        };
+
+        let now = chrono::Utc::now();
+        let execution_started_at = retry_task_transition(task_id, "result_submitted", || {
+            self.try_record_task_result(task_id, &submission, proof_id, now)
+        })
+        .await?;
+        self.export_telemetry(crate::telemetry_export::ExportRecord::Task(
+            crate::telemetry_export::TaskMetric {
+                task_id,
+                task_type,
+                node_id: submission.node_id.clone(),
+                outcome: crate::telemetry_export::TaskOutcome::Completed,
+                recorded_at: now,
+                retry_count,
+                duration_ms: execution_started_at
+                    .map(|started| (now - started).num_milliseconds().max(0)),
+                energy_wh: submission.energy_wh,
+            },
+        ));
+
+        // Let freed nodes pick up pending tasks.
+        let freed_nodes: Vec<String> =
+            sqlx::query_scalar(r#"SELECT node_id FROM task_assignments WHERE task_id = $1"#)
+                .bind(task_id)
+                .fetch_all(db)
+                .await
+                .unwrap_or_default();
+
+        for node_id in freed_nodes {
+            let _ = self.assign_pending_tasks_for_node(&node_id).await;
+        }
+
+        Ok(serde_json::json!({
+            "task_id": task_id.to_string(),
+            "status": "completed",
+            "node_id": submission.node_id,
+            "proof_verified": proof_id.is_some(),
+            "proof_id": proof_id.map(|id| id.to_string()),
+            "energy_wh": submission.energy_wh,
+            "cpu_time_ms": submission.cpu_time_ms,
+            "peak_memory_bytes": submission.peak_memory_bytes,
+            "completed_at": now.to_rfc3339(),
+        }))
+    }
+
+    /// Record a verifier node's signed verdict on an offloaded proof.  Once
+    /// the verdicts reach a quorum, or can no longer reach one, the proof is
+    /// settled, its batch result appended and the `verify_proof` task
+    /// completed with the outcome.
+    async fn record_proof_verdict(
+        &self,
+        task_id: Uuid,
+        submission: &NodeTaskResult,
+    ) -> ApiResult<serde_json::Value> {
+        use crate::proof_offload::{tally, QuorumOutcome, VerifyProofInputs, QUORUM_NOT_REACHED};
+
+        let db = self.require_db()?;
+        let signed = submission.proof_verdict.as_ref().ok_or_else(|| {
+            ApiError::bad_request("verify_proof results must include a signed proof_verdict")
+        })?;
+
+        let (inputs, quorum): (serde_json::Value, i32) =
+            sqlx::query_as("SELECT inputs, min_nodes FROM tasks WHERE task_id = $1")
+                .bind(task_id)
+                .fetch_one(db)
+                .await?;
+        let inputs: VerifyProofInputs = serde_json::from_value(inputs).map_err(|err| {
+            ApiError::internal_error(format!("Unreadable verify_proof inputs: {err}"))
+        })?;
+        if signed.verdict.task_id != task_id.to_string()
+            || signed.verdict.proof_id != inputs.proof_id
+        {
+            return Err(ApiError::bad_request(
+                "proof_verdict does not name this task and its proof",
+            ));
+        }
+        self.require_registered_signer(&submission.node_id, &signed.public_key, "proof_verdict")
+            .await?;
+        if !signed.verify() {
+            return Err(ApiError::bad_request("proof_verdict signature is invalid"));
+        }
+        let proof_id = Uuid::parse_str(&inputs.proof_id)
+            .map_err(|_| ApiError::internal_error("verify_proof inputs name an invalid proof"))?;
+
+        // Verdicts on one task are tallied one at a time.
+        let mut tx = db.begin().await?;
+        sqlx::query("SELECT 1 FROM tasks WHERE task_id = $1 FOR UPDATE")
+            .bind(task_id)
+            .execute(&mut *tx)
+            .await?;
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO proof_verdicts (task_id, node_id, proof_id, valid, signer_public_key, signature)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (task_id, node_id) DO NOTHING
+            "#,
+        )
+        .bind(task_id)
+        .bind(&submission.node_id)
+        .bind(proof_id)
+        .bind(signed.verdict.valid)
+        .bind(&signed.public_key)
+        .bind(&signed.signature)
+        .execute(&mut *tx)
+        .await?;
+        if inserted.rows_affected() == 0 {
+            return Err(ApiError::conflict(
+                "This node already submitted a verdict for the task",
+            ));
+        }
+        let (valid, invalid, outstanding): (i64, i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE v.valid),
+                COUNT(*) FILTER (WHERE NOT v.valid),
+                (SELECT COUNT(*) FROM task_assignments ta
+                 WHERE ta.task_id = $1
+                   AND ta.disconnected_at IS NULL
+                   AND NOT EXISTS (
+                       SELECT 1 FROM proof_verdicts voted
+                       WHERE voted.task_id = ta.task_id AND voted.node_id = ta.node_id
+                   ))
+            FROM proof_verdicts v
+            WHERE v.task_id = $1
+            "#,
+        )
+        .bind(task_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let outcome = tally(
+            valid as u32,
+            invalid as u32,
+            outstanding as u32,
+            quorum.max(1) as u32,
+        );
+        let settled = match outcome {
+            QuorumOutcome::Pending => None,
+            QuorumOutcome::Agreed(valid) => {
+                Some((valid, (!valid).then_some(PROOF_VERIFICATION_FAILED)))
+            }
+            QuorumOutcome::Deadlocked => Some((false, Some(QUORUM_NOT_REACHED))),
+        };
+        let Some((proof_valid, error_message)) = settled else {
+            // Wait for the other verifiers with this node's attempt done.
+            sqlx::query(
+                r#"
+                UPDATE task_assignments
+                SET execution_status = 'completed', execution_completed_at = NOW()
+                WHERE task_id = $1 AND node_id = $2 AND disconnected_at IS NULL
+                "#,
+            )
+            .bind(task_id)
+            .bind(&submission.node_id)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(serde_json::json!({
+                "task_id": task_id.to_string(),
+                "status": "awaiting_quorum",
+                "node_id": submission.node_id,
+                "proof_id": inputs.proof_id,
+                "valid_verdicts": valid,
+                "invalid_verdicts": invalid,
+                "quorum": quorum,
+            }));
+        };
+
+        let verification_time_ms: i64 = sqlx::query_scalar(
+            r#"
+            UPDATE proofs
+            SET verified = $2, error_message = $3, verified_at = NOW(),
+                verification_time_ms = (extract(epoch FROM NOW() - created_at) * 1000)::BIGINT
+            WHERE proof_id = $1
+            RETURNING verification_time_ms
+            "#,
+        )
+        .bind(proof_id)
+        .bind(proof_valid)
+        .bind(error_message)
+        .fetch_one(&mut *tx)
+        .await?;
+        let batch_result = crate::proof_batches::ProofBatchResult {
+            position: inputs.position,
+            task_id: inputs.proof_task_id.clone(),
+            valid: proof_valid,
+            verification_time_ms: verification_time_ms.max(0) as u64,
+            error_message: error_message.map(str::to_string),
+            proof_id: Some(inputs.proof_id.clone()),
+        };
+        sqlx::query(
+            "UPDATE proof_batches SET results = results || $2 WHERE batch_id = $1 AND status = 'running'",
+        )
+        .bind(Uuid::parse_str(&inputs.batch_id).ok())
+        .bind(serde_json::json!([batch_result]))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        if let Ok(batch_id) = Uuid::parse_str(&inputs.batch_id) {
+            self.finish_proof_batch_if_settled(batch_id).await?;
+        }
+
+        let settled_result = NodeTaskResult {
+            result: serde_json::json!({
+                "proof_id": inputs.proof_id,
+                "valid": proof_valid,
+                "valid_verdicts": valid,
+                "invalid_verdicts": invalid,
+                "error_message": error_message,
+            }),
+            ..submission.clone()
+        };
+        let now = chrono::Utc::now();
+        retry_task_transition(task_id, "result_submitted", || {
+            self.try_record_task_result(task_id, &settled_result, None, now)
+        })
+        .await?;
+        tracing::info!(%task_id, %proof_id, valid = proof_valid, "Offloaded proof settled by verifier quorum");
+
+        let freed_nodes: Vec<String> =
+            sqlx::query_scalar(r#"SELECT node_id FROM task_assignments WHERE task_id = $1"#)
+                .bind(task_id)
+                .fetch_all(db)
+                .await
+                .unwrap_or_default();
+        for node_id in freed_nodes {
+            let _ = self.assign_pending_tasks_for_node(&node_id).await;
+        }
+
+        Ok(serde_json::json!({
+            "task_id": task_id.to_string(),
+            "status": "completed",
+            "node_id": submission.node_id,
+            "proof_id": inputs.proof_id,
+            "proof_verified": proof_valid,
+            "valid_verdicts": valid,
+            "invalid_verdicts": invalid,
+            "completed_at": now.to_rfc3339(),
+        }))
+    }
+
+    /// One attempt at storing a node's result and completing the task.
+    /// Returns when the node started executing it, if it confirmed that.
+    async fn try_record_task_result(
+        &self,
+        task_id: Uuid,
+        submission: &NodeTaskResult,
+        proof_id: Option<Uuid>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> ApiResult<TaskTransition<Option<chrono::DateTime<chrono::Utc>>>> {
+        let db = self.require_db()?;
+        // Re-check the state validated before proof verification: a timeout
+        // or another result may have ended this attempt in the meantime.
+        let task_row = sqlx::query(
+            r#"
+            SELECT
+                t.task_type,
+                t.status,
+                t.version,
+                EXISTS (
+                    SELECT 1 FROM task_assignments ta
+                    WHERE ta.task_id = t.task_id
+                      AND ta.node_id = $2
+                      AND ta.disconnected_at IS NULL
+                ) AS is_assigned
+            FROM tasks t
+            WHERE t.task_id = $1
+            "#,
+        )
+        .bind(task_id)
+        .bind(&submission.node_id)
+        .fetch_optional(db)
+        .await?;
+
+        let Some(task_row) = task_row else {
+            return Err(ApiError::not_found(format!("Task {} not found", task_id)));
+        };
+        let task_status: String = task_row.get("status");
+        if task_status != "running" && task_status != "pending" {
+            return Err(ApiError::bad_request(format!(
+                "Task is not in an executable state (current status: {})",
+                task_status
+            )));
+        }
+        if !task_row.get::<bool, _>("is_assigned") {
+            return Err(ApiError::bad_request(
+                "Node is not actively assigned to this task",
+            ));
+        }
+
+        let mut tx = db.begin().await?;
+
+        // Persist result and mark task completed.
+        let completed = sqlx::query(
+            r#"
+            UPDATE tasks
+            SET status = 'completed', result = $1, updated_at = $2, completed_at = $2,
+                proof_verified_at = CASE WHEN $4::text IS NOT NULL THEN $2 ELSE proof_verified_at END,
+                proof_id = COALESCE($4, proof_id),
+                version = version + 1
+            WHERE task_id = $3
+              AND version = $5
+            "#,
+        )
+        .bind(&submission.result)
+        .bind(now)
+        .bind(task_id)
+        .bind(proof_id.map(|id| id.to_string()))
+        .bind(task_row.get::<i64, _>("version"))
+        .execute(&mut *tx)
+        .await?;
+
+        if completed.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(TaskTransition::Conflict);
+        }
+        self.enqueue_task_completed(&mut tx, task_id).await?;
+
+        // Mark submitting node's assignment as completed and record the
+        // resources it reported using for this task.
+        let execution_started_at: Option<chrono::DateTime<chrono::Utc>> = sqlx::query_scalar(
             r#"
-            INSERT INTO node_heartbeat_history
-                (node_id, health_score, active_tasks, status, recorded_at)
-            VALUES ($1, $2, $3, $4, $5)
+            UPDATE task_assignments
+            SET execution_status = 'completed', execution_completed_at = $1
+            WHERE task_id = $2 AND node_id = $3 AND disconnected_at IS NULL
+            RETURNING execution_started_at
             "#,
         )
-        .bind(node_id)
-        .bind(health_score)
-        .bind(active_tasks_before as i32)
-        .bind(&status)
         .bind(now)
-        .execute(db)
+        .bind(task_id)
+        .bind(&submission.node_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+        self.record_attempt_usage(
+            &mut tx,
+            task_id,
+            &submission.node_id,
+            task_row.get("task_type"),
+            true,
+            AttemptUsage::from_submission(submission),
+        )
         .await?;
 
-        // Activity registration: advance execution_status from 'assigned' to
-        // 'in_progress' for every task this node is actively connected to.
-        // This records the moment the node confirmed it is working on the task.
+        // Disconnect all remaining active assignments for this task.
         sqlx::query(
             r#"
             UPDATE task_assignments
-            SET execution_status = 'in_progress',
-                execution_started_at = COALESCE(execution_started_at, $1)
-            WHERE node_id = $2
-              AND disconnected_at IS NULL
-              AND execution_status = 'assigned'
+            SET disconnected_at = $1
+            WHERE task_id = $2 AND disconnected_at IS NULL
             "#,
         )
         .bind(now)
-        .bind(node_id)
-        .execute(db)
+        .bind(task_id)
+        .execute(&mut *tx)
         .await?;
 
-        // Sync any pending tasks that this node is eligible for.
-        self.assign_pending_tasks_for_node(node_id).await?;
+        // No node needs the task's sealed secrets any more.
+        sqlx::query("DELETE FROM task_sealed_secrets WHERE task_id = $1")
+            .bind(task_id)
+            .execute(&mut *tx)
+            .await?;
 
-        // Fetch active task assignments *after* any new assignments so the
-        // response accurately reflects the node's current connected-task state.
-        // Also joins tasks to return task_type so the node knows which tasks
-        // are connect_only (requiring gateway-mode activation) vs compute tasks.
-        let assigned_task_rows = sqlx::query(
+        tx.commit().await?;
+        Ok(TaskTransition::Applied(execution_started_at))
+    }
+
+    /// Store the resources a node reported for its attempt on the attempt's
+    /// assignment and add them to the task type's statistics.
+    async fn record_attempt_usage(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        task_id: Uuid,
+        node_id: &str,
+        task_type: &str,
+        completed: bool,
+        usage: AttemptUsage,
+    ) -> ApiResult<()> {
+        let as_bigint = |value: Option<u64>| value.map(|value| value as i64);
+        sqlx::query(
             r#"
-            SELECT ta.task_id::TEXT, t.task_type, ta.execution_status
-            FROM task_assignments ta
-            JOIN tasks t ON t.task_id = ta.task_id
-            WHERE ta.node_id = $1 AND ta.disconnected_at IS NULL
-            ORDER BY ta.assigned_at ASC
+            UPDATE task_assignments
+            SET execution_time_ms = COALESCE($3, execution_time_ms),
+                cpu_time_ms = COALESCE($4, cpu_time_ms),
+                peak_memory_bytes = COALESCE($5, peak_memory_bytes),
+                energy_wh = COALESCE($6, energy_wh)
+            WHERE task_id = $1 AND node_id = $2
             "#,
         )
+        .bind(task_id)
         .bind(node_id)
-        .fetch_all(db)
+        .bind(as_bigint(usage.execution_time_ms))
+        .bind(as_bigint(usage.cpu_time_ms))
+        .bind(as_bigint(usage.peak_memory_bytes))
+        .bind(usage.energy_wh)
+        .execute(&mut **tx)
         .await?;
 
-        let assigned_tasks: Vec<serde_json::Value> = assigned_task_rows
-            .into_iter()
-            .map(|row| {
-                serde_json::json!({
-                    "task_id": row.get::<String, _>("task_id"),
-                    "task_type": row.get::<String, _>("task_type"),
-                    "execution_status": row.get::<String, _>("execution_status"),
-                })
-            })
-            .collect();
-
-        let active_task_count = assigned_tasks.len() as i64;
-        let internet_active = assigned_tasks
-            .iter()
-            .any(|t| t.get("task_type").and_then(|v| v.as_str()) == Some("connect_only"));
+        sqlx::query(crate::task_stats::ROLL_UP_SQL)
+            .bind(task_type)
+            .bind(completed)
+            .bind(as_bigint(usage.execution_time_ms))
+            .bind(as_bigint(usage.cpu_time_ms))
+            .bind(as_bigint(usage.peak_memory_bytes))
+            .bind(usage.energy_wh)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
 
-        Ok(Some(NodeHeartbeatResult {
-            active_task_count,
-            assigned_tasks,
-            health_score,
-            node_status: status,
-            internet_active,
-        }))
+    /// Resource statistics for every task type with a reported attempt.
+    pub async fn list_task_type_stats(&self) -> ApiResult<Vec<TaskTypeStats>> {
+        let db = self.read_db()?;
+        let rows = sqlx::query(crate::task_stats::LIST_SQL)
+            .fetch_all(db)
+            .await?;
+        rows.iter()
+            .map(|row| TaskTypeStats::from_row(row).map_err(Into::into))
+            .collect()
     }
 
-    /// Reject a node owned by the requesting user
-    pub async fn reject_node(&self, node_id: &str, owner_id: Uuid) -> ApiResult<bool> {
+    /// Verify a node's signed sandbox report against the signing key it
+    /// registered and store it in the task's provenance.
+    async fn record_sandbox_report(
+        &self,
+        task_id: Uuid,
+        node_id: &str,
+        signed: &ambient_node::SignedSandboxReport,
+    ) -> ApiResult<()> {
         let db = self.require_db()?;
-        // Verify ownership
-        if !self.check_node_ownership(node_id, owner_id).await? {
-            return Ok(false);
+        self.require_registered_signer(node_id, &signed.public_key, "sandbox_report")
+            .await?;
+        if !signed.verify() {
+            return Err(ApiError::bad_request("sandbox_report signature is invalid"));
         }
 
-        let now = chrono::Utc::now();
-        let result = sqlx::query(
+        let report = serde_json::to_value(&signed.report)
+            .map_err(|_| ApiError::internal_error("Failed to encode sandbox report"))?;
+
+        sqlx::query(
             r#"
-            UPDATE nodes
-            SET status = 'rejected', updated_at = $1
-            WHERE node_id = $2 AND owner_id = $3 AND deleted_at IS NULL
+            INSERT INTO task_sandbox_reports (
+                task_id, node_id, report, report_hash, signer_public_key, signature
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (task_id, node_id) DO UPDATE
+            SET report = EXCLUDED.report,
+                report_hash = EXCLUDED.report_hash,
+                signer_public_key = EXCLUDED.signer_public_key,
+                signature = EXCLUDED.signature,
+                created_at = NOW()
             "#,
         )
-        .bind(now)
+        .bind(task_id)
         .bind(node_id)
-        .bind(owner_id)
+        .bind(report)
+        .bind(signed.report.digest())
+        .bind(&signed.public_key)
+        .bind(&signed.signature)
         .execute(db)
         .await?;
 
-        if result.rows_affected() == 0 {
-            return Ok(false);
+        Ok(())
+    }
+
+    /// Reject a signed `field` whose key is not the node's registered
+    /// `signing_public_key`.
+    async fn require_registered_signer(
+        &self,
+        node_id: &str,
+        public_key: &str,
+        field: &str,
+    ) -> ApiResult<()> {
+        let db = self.require_db()?;
+        let signing_key: Option<String> =
+            sqlx::query_scalar("SELECT signing_public_key FROM nodes WHERE node_id = $1")
+                .bind(node_id)
+                .fetch_one(db)
+                .await?;
+
+        match signing_key {
+            None => Err(ApiError::bad_request(format!(
+                "{field} requires the node to register a signing_public_key"
+            ))),
+            Some(key) if key != public_key => Err(ApiError::bad_request(format!(
+                "{field} is not signed with the node's registered signing key"
+            ))),
+            Some(_) => Ok(()),
         }
+    }
 
-        // Collect all tasks that had an active assignment to this node so we can
-        // update their status and attempt reassignment after disconnecting.
-        let affected_tasks = sqlx::query(
+    /// Provenance bundle for a task the requester can read: declared egress,
+    /// proof reference and the signed sandbox report of every node that
+    /// submitted a result.
+    pub async fn get_task_provenance(
+        &self,
+        task_id: Uuid,
+        requester_id: Uuid,
+    ) -> ApiResult<Option<TaskProvenance>> {
+        let db = self.require_db()?;
+
+        let Some(task) = sqlx::query(
             r#"
-            SELECT ta.task_id, t.min_nodes
-            FROM task_assignments ta
-            JOIN tasks t ON t.task_id = ta.task_id
-            WHERE ta.node_id = $1
-              AND ta.disconnected_at IS NULL
+            SELECT task_type, status, wasm_module, inputs, result, proof_id, egress
+            FROM tasks
+            WHERE task_id = $1
+              AND deleted_at IS NULL
+              AND (creator_id = $2 OR org_role_at_least(org_id, $2, 'viewer'))
             "#,
         )
-        .bind(node_id)
-        .fetch_all(db)
-        .await?;
+        .bind(task_id)
+        .bind(requester_id)
+        .fetch_optional(db)
+        .await?
+        else {
+            return Ok(None);
+        };
 
-        // Disconnect all active task assignments for this rejected node,
-        // and fail any that were in_progress so the status history is accurate.
-        sqlx::query(
+        let module_hash = task
+            .get::<Option<String>, _>("wasm_module")
+            .and_then(|module| provenance_module_hash(&module));
+
+        let reports = sqlx::query(
             r#"
-            UPDATE task_assignments
-            SET disconnected_at = $1,
-                execution_status = CASE
-                    WHEN execution_status = 'in_progress' THEN 'failed'
-                    ELSE execution_status
-                END
-            WHERE node_id = $2
-              AND disconnected_at IS NULL
+            SELECT node_id, report, report_hash, signer_public_key, signature, created_at
+            FROM task_sandbox_reports
+            WHERE task_id = $1
+            ORDER BY created_at
             "#,
         )
-        .bind(now)
-        .bind(node_id)
-        .execute(db)
+        .bind(task_id)
+        .fetch_all(db)
         .await?;
 
-        // For each previously-assigned task, fix its status and try to find
-        // replacement nodes so the task is not left permanently stuck in 'running'.
-        for task_row in affected_tasks {
-            let task_id: Uuid = task_row.get("task_id");
-            let min_nodes: i32 = task_row.get("min_nodes");
+        Ok(Some(TaskProvenance {
+            task_id: task_id.to_string(),
+            task_type: task.get("task_type"),
+            status: parse_task_status(&task.get::<String, _>("status")),
+            module_hash,
+            inputs: task.get("inputs"),
+            result: task.try_get("result").ok().flatten(),
+            proof_id: task.try_get("proof_id").ok(),
+            egress: parse_task_egress(task.get("egress")),
+            sandbox_reports: reports
+                .into_iter()
+                .map(|row| {
+                    let report: serde_json::Value = row.get("report");
+                    TaskSandboxReport {
+                        node_id: row.get("node_id"),
+                        report_sha3_256: row.get("report_hash"),
+                        signer_public_key: row.get("signer_public_key"),
+                        signature: row.get("signature"),
+                        environment_sha3_256: serde_json::from_value::<wasm_engine::SandboxReport>(
+                            report.clone(),
+                        )
+                        .ok()
+                        .map(|report| report.environment_fingerprint()),
+                        report,
+                        created_at: row
+                            .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
+                            .to_rfc3339(),
+                    }
+                })
+                .collect(),
+        }))
+    }
 
-            self.update_task_status_from_assignments(task_id, min_nodes as u32)
-                .await?;
+    /// Ordered lifecycle events for a task the requester can read, assembled
+    /// from the task row, its assignments, checkpoint and audit entries.
+    pub async fn get_task_timeline(
+        &self,
+        task_id: Uuid,
+        requester_id: Uuid,
+    ) -> ApiResult<Option<TaskTimeline>> {
+        type Timestamp = chrono::DateTime<chrono::Utc>;
+        let db = self.require_db()?;
 
-            match self.get_task_status(task_id).await {
-                Ok(Some(task_status)) => {
-                    if task_status == "pending" {
-                        let task_details = sqlx::query(
-                            r#"
-                            SELECT task_type, require_gpu
-                            FROM tasks
-                            WHERE task_id = $1
-                            "#,
-                        )
-                        .bind(task_id)
-                        .fetch_optional(db)
-                        .await?;
+        let Some(task) = sqlx::query(
+            r#"
+            SELECT status, created_at, updated_at, completed_at, proof_verified_at,
+                   constraints_relaxed_at, starving_at, retry_count, last_error
+            FROM tasks
+            WHERE task_id = $1
+              AND deleted_at IS NULL
+              AND (creator_id = $2 OR org_role_at_least(org_id, $2, 'viewer'))
+            "#,
+        )
+        .bind(task_id)
+        .bind(requester_id)
+        .fetch_optional(db)
+        .await?
+        else {
+            return Ok(None);
+        };
 
-                        if let Some(task_row) = task_details {
-                            let task_type: String = task_row.get("task_type");
-                            let require_gpu: bool = task_row.get("require_gpu");
+        let mut events: Vec<(Timestamp, TaskTimelineEvent)> = Vec::new();
+        let mut push = |at: Option<Timestamp>,
+                        event: &str,
+                        node_id: Option<String>,
+                        details: Option<serde_json::Value>| {
+            if let Some(at) = at {
+                events.push((
+                    at,
+                    TaskTimelineEvent {
+                        event: event.to_string(),
+                        at: at.to_rfc3339(),
+                        node_id,
+                        details,
+                    },
+                ));
+            }
+        };
 
-                            if let Some(task_registry_entry) = task_type_registry_entry(&task_type)
-                            {
-                                if let Err(err) = self
-                                    .assign_available_nodes_for_task(
-                                        task_id,
-                                        &task_type,
-                                        task_registry_entry,
-                                        min_nodes as u32,
-                                        require_gpu,
-                                    )
-                                    .await
-                                {
-                                    tracing::warn!(
-                                        task_id = %task_id,
-                                        "Failed to reassign task after node rejection: {err}"
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
-                Ok(None) => {}
-                Err(err) => {
-                    tracing::warn!(
-                        task_id = %task_id,
-                        "Failed to retrieve task status during node rejection: {err}; skipping automatic reassignment for this task"
-                    );
-                }
+        let status: String = task.get("status");
+        push(task.get("created_at"), "submitted", None, None);
+        push(
+            task.get("constraints_relaxed_at"),
+            "constraints_relaxed",
+            None,
+            None,
+        );
+        push(task.get("starving_at"), "starving", None, None);
+
+        let assignments = sqlx::query(
+            r#"
+            SELECT node_id, assigned_at, execution_status, execution_started_at,
+                   execution_completed_at, disconnected_at, energy_wh,
+                   execution_time_ms, cpu_time_ms, peak_memory_bytes
+            FROM task_assignments
+            WHERE task_id = $1
+            "#,
+        )
+        .bind(task_id)
+        .fetch_all(db)
+        .await?;
+
+        for row in assignments {
+            let node_id: String = row.get("node_id");
+            let ended_at: Option<Timestamp> = row
+                .get::<Option<Timestamp>, _>("execution_completed_at")
+                .or(row.get("disconnected_at"));
+            push(
+                row.get("assigned_at"),
+                "assigned",
+                Some(node_id.clone()),
+                None,
+            );
+            push(
+                row.get("execution_started_at"),
+                "started",
+                Some(node_id.clone()),
+                None,
+            );
+            let usage = AttemptUsage::from_assignment_row(&row).timeline_details();
+            match row.get::<String, _>("execution_status").as_str() {
+                "completed" => push(ended_at, "result_submitted", Some(node_id), usage),
+                "failed" => push(ended_at, "assignment_failed", Some(node_id), usage),
+                "handed_off" => push(ended_at, "handed_off", Some(node_id), None),
+                _ => {}
             }
         }
 
-        Ok(true)
-    }
-
-    /// List nodes owned by a specific user
-    pub async fn list_user_nodes(&self, owner_id: Uuid) -> Vec<NodeInfo> {
-        let Some(db) = &self.db else {
-            return vec![];
-        };
+        let checkpoint =
+            sqlx::query("SELECT node_id, seq, created_at FROM task_checkpoints WHERE task_id = $1")
+                .bind(task_id)
+                .fetch_optional(db)
+                .await?;
+        if let Some(row) = checkpoint {
+            push(
+                row.get("created_at"),
+                "checkpointed",
+                Some(row.get("node_id")),
+                Some(serde_json::json!({ "seq": row.get::<i64, _>("seq") })),
+            );
+        }
+
+        push(task.get("proof_verified_at"), "proof_verified", None, None);
+        if matches!(status.as_str(), "completed" | "failed" | "unschedulable") {
+            // Tasks finished before `completed_at` was maintained fall back to
+            // their last update.
+            let finished_at: Option<Timestamp> = task
+                .get::<Option<Timestamp>, _>("completed_at")
+                .or(task.get("updated_at"));
+            let details = (status != "completed").then(|| {
+                serde_json::json!({
+                    "error": task.get::<Option<String>, _>("last_error"),
+                    "retry_count": task.get::<i32, _>("retry_count"),
+                })
+            });
+            push(finished_at, &status, None, details);
+        }
 
-        let result = sqlx::query(
+        let audit = sqlx::query(
             r#"
-            SELECT 
-                node_id, region, node_type, owner_id, bandwidth_mbps, cpu_cores,
-                memory_gb, gpu_available, health_score, status,
-                registered_at, last_seen, observability_port
-            FROM nodes
-            WHERE owner_id = $1 AND deleted_at IS NULL
-              AND status != 'rejected'
-            ORDER BY registered_at DESC
+            SELECT action, status, metadata, created_at
+            FROM audit_log
+            WHERE resource_type = 'task' AND resource_id = $1
             "#,
         )
-        .bind(owner_id)
+        .bind(task_id.to_string())
         .fetch_all(db)
-        .await;
-
-        match result {
-            Ok(rows) => rows
-                .into_iter()
-                .map(|row| NodeInfo {
-                    node_id: row.get("node_id"),
-                    region: row.get("region"),
-                    node_type: row.get("node_type"),
-                    owner_id: row.get::<Uuid, _>("owner_id").to_string(),
-                    capabilities: NodeCapabilities {
-                        bandwidth_mbps: row.get("bandwidth_mbps"),
-                        cpu_cores: row.get::<i32, _>("cpu_cores") as u32,
-                        memory_gb: row.get("memory_gb"),
-                        gpu_available: row.get("gpu_available"),
-                    },
-                    health_score: row.get("health_score"),
-                    status: row.get("status"),
-                    registered_at: row
-                        .get::<chrono::DateTime<chrono::Utc>, _>("registered_at")
-                        .to_rfc3339(),
-                    last_seen: row
-                        .get::<chrono::DateTime<chrono::Utc>, _>("last_seen")
-                        .to_rfc3339(),
-                    observability_port: row
-                        .get::<Option<i32>, _>("observability_port")
-                        .map(|p| p as u16),
-                })
-                .collect(),
-            Err(e) => {
-                tracing::error!("Failed to list user nodes: {:?}", e);
-                vec![]
+        .await?;
+        for row in audit {
+            let action: String = row.get("action");
+            let mut details = row
+                .get::<Option<serde_json::Value>, _>("metadata")
+                .unwrap_or_else(|| serde_json::json!({}));
+            if let Some(fields) = details.as_object_mut() {
+                fields.insert(
+                    "status".to_string(),
+                    serde_json::Value::String(row.get("status")),
+                );
             }
+            push(row.get("created_at"), &action, None, Some(details));
         }
+
+        // Stable sort: events sharing a timestamp keep the order they were
+        // gathered in, which follows the task's lifecycle.
+        events.sort_by_key(|(at, _)| *at);
+
+        Ok(Some(TaskTimeline {
+            task_id: task_id.to_string(),
+            status: parse_task_status(&status),
+            events: events.into_iter().map(|(_, event)| event).collect(),
+        }))
     }
 
-    /// Sweep nodes that have not sent a heartbeat within the configured
-    /// threshold and mark them as offline.  Also disconnects their active
-    /// task assignments and attempts to reassign those tasks to other nodes.
-    ///
-    /// The timeout is controlled by the `NODE_HEARTBEAT_TIMEOUT_MINUTES`
-    /// environment variable (default: 5 minutes).
+    /// Record bytes a gateway relayed for `session_id` during one interval.
     ///
-    /// Returns the number of nodes swept offline.
-    pub async fn sweep_offline_nodes(&self) -> ApiResult<usize> {
-        let Ok(db) = self.require_db() else {
-            return Ok(0);
-        };
-
-        let threshold_minutes: i64 = std::env::var("NODE_HEARTBEAT_TIMEOUT_MINUTES")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .filter(|v: &i64| *v > 0)
-            .unwrap_or(5);
+    /// The caller must own (or be an org member for) the node currently
+    /// relaying the session.  Reports for ended sessions still count, so a
+    /// gateway's final interval is kept.  A report whose `seq` is not above
+    /// the last accepted one is a retry and changes nothing.  An active
+    /// session whose usage reaches its data cap is ended and its
+    /// `connect_only` task completed.  Returns `None` when no session matches.
+    pub async fn report_connect_session_usage(
+        &self,
+        session_id: &str,
+        owner_id: Uuid,
+        report: &ConnectSessionUsageReport,
+    ) -> ApiResult<Option<ConnectSessionInfo>> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
 
-        // Mark stale online nodes offline and collect their IDs in one statement.
-        let stale_node_ids: Vec<String> = sqlx::query_scalar(
+        let current = sqlx::query(
             r#"
-            WITH swept AS (
-                UPDATE nodes
-                SET status = 'offline', updated_at = NOW()
-                WHERE status = 'online'
-                  AND deleted_at IS NULL
-                  AND last_heartbeat < NOW() - (interval '1 minute' * $1)
-                RETURNING node_id
-            )
-            SELECT node_id FROM swept
+            SELECT cs.usage_seq, cs.status, cs.bytes_up + cs.bytes_down AS total_bytes,
+                   cs.data_cap_bytes
+            FROM connect_sessions cs
+            JOIN nodes n ON n.node_id = cs.node_id
+            WHERE cs.session_id = $1
+              AND cs.node_id = $2
+              AND (n.owner_id = $3 OR org_role_at_least(n.org_id, $3, 'member'))
+              AND n.deleted_at IS NULL
+            FOR UPDATE OF cs
             "#,
         )
-        .bind(threshold_minutes)
-        .fetch_all(db)
+        .bind(session_id)
+        .bind(&report.node_id)
+        .bind(owner_id)
+        .fetch_optional(&mut *tx)
         .await?;
 
-        if stale_node_ids.is_empty() {
-            return Ok(0);
-        }
-
-        let count = stale_node_ids.len();
+        let Some(current) = current else {
+            return Ok(None);
+        };
 
-        for node_id in &stale_node_ids {
-            // Collect the tasks this node was still actively assigned to.
-            let affected_tasks = sqlx::query(
+        let seq = report.seq as i64;
+        if seq <= current.get::<i64, _>("usage_seq") {
+            let row = sqlx::query(
                 r#"
-                SELECT ta.task_id, t.min_nodes, t.task_type, t.require_gpu
-                FROM task_assignments ta
-                JOIN tasks t ON t.task_id = ta.task_id
-                WHERE ta.node_id = $1
-                  AND ta.disconnected_at IS NULL
+                SELECT session_id, task_id, requester_id, node_id, tunnel_protocol,
+                       egress_profile, destination_policy_id, bandwidth_limit_mbps,
+                       status, created_at, expires_at, last_heartbeat_at, ended_at,
+                       bytes_up, bytes_down, data_cap_bytes, last_usage_at
+                FROM connect_sessions
+                WHERE session_id = $1
                 "#,
             )
-            .bind(node_id)
-            .fetch_all(db)
+            .bind(session_id)
+            .fetch_one(&mut *tx)
             .await?;
+            return Ok(Some(map_connect_session_row(row)));
+        }
 
-            // Disconnect all active assignments for this node.
-            sqlx::query(
-                r#"
-                UPDATE task_assignments
-                SET disconnected_at = NOW(),
-                    execution_status = CASE
-                        WHEN execution_status = 'in_progress' THEN 'failed'
-                        ELSE execution_status
-                    END
-                WHERE node_id = $1 AND disconnected_at IS NULL
-                "#,
-            )
-            .bind(node_id)
-            .execute(db)
-            .await?;
+        let now = chrono::Utc::now();
+        sqlx::query(
+            r#"
+            INSERT INTO connect_session_usage
+                (session_id, node_id, seq, bytes_up, bytes_down, interval_start, interval_end)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(session_id)
+        .bind(&report.node_id)
+        .bind(seq)
+        .bind(report.bytes_up as i64)
+        .bind(report.bytes_down as i64)
+        .bind(now - chrono::Duration::seconds(report.interval_seconds as i64))
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
 
-            // For each affected task update its status and attempt reassignment.
-            for task_row in affected_tasks {
-                let task_id: Uuid = task_row.get("task_id");
-                let min_nodes: i32 = task_row.get("min_nodes");
-                let task_type: String = task_row.get("task_type");
-                let require_gpu: bool = task_row.get("require_gpu");
+        let total_bytes = current
+            .get::<i64, _>("total_bytes")
+            .saturating_add(report.bytes_up as i64)
+            .saturating_add(report.bytes_down as i64);
+        let capped = current.get::<String, _>("status") == "active"
+            && current
+                .get::<Option<i64>, _>("data_cap_bytes")
+                .is_some_and(|cap| total_bytes >= cap);
 
-                let _ = self
-                    .update_task_status_from_assignments(task_id, min_nodes as u32)
-                    .await;
+        let row = sqlx::query(
+            r#"
+            UPDATE connect_sessions
+            SET bytes_up = bytes_up + $2,
+                bytes_down = bytes_down + $3,
+                usage_seq = $4,
+                last_usage_at = $5,
+                status = CASE WHEN $6 THEN 'ended' ELSE status END,
+                ended_at = CASE WHEN $6 THEN NOW() ELSE ended_at END,
+                updated_at = NOW()
+            WHERE session_id = $1
+            RETURNING session_id, task_id, requester_id, node_id, tunnel_protocol,
+                   egress_profile, destination_policy_id, bandwidth_limit_mbps,
+                   status, created_at, expires_at, last_heartbeat_at, ended_at,
+                   bytes_up, bytes_down, data_cap_bytes, last_usage_at
+            "#,
+        )
+        .bind(session_id)
+        .bind(report.bytes_up as i64)
+        .bind(report.bytes_down as i64)
+        .bind(seq)
+        .bind(now)
+        .bind(capped)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
 
-                if let Ok(Some(status)) = self.get_task_status(task_id).await {
-                    if status == "pending" {
-                        if let Some(entry) = task_type_registry_entry(&task_type) {
-                            let _ = self
-                                .assign_available_nodes_for_task(
-                                    task_id,
-                                    &task_type,
-                                    entry,
-                                    min_nodes as u32,
-                                    require_gpu,
-                                )
-                                .await;
-                        }
-                    }
-                }
-            }
+        let session = map_connect_session_row(row);
+        if capped {
+            tracing::info!(
+                session_id,
+                total_bytes = session.usage.total_bytes,
+                "Connect session reached its data cap; ending it"
+            );
+            let task_id = Uuid::parse_str(&session.task_id)
+                .map_err(|_| ApiError::internal_error("Invalid task ID format"))?;
+            self.complete_connect_only_task(task_id).await?;
         }
 
-        tracing::info!(swept = count, "Node offline sweep completed");
-        Ok(count)
+        Ok(Some(session))
     }
 
-    /// Soft-delete nodes that have been in the `offline` state for longer
-    /// than `NODE_REMOVAL_TIMEOUT_MINUTES` (default: 5 minutes) without
-    /// reconnecting.
-    ///
-    /// A soft-deleted node (`deleted_at IS NOT NULL`) disappears from the
-    /// node list and from cluster statistics, so the dashboard totals drop to
-    /// zero once all nodes have been purged.
-    ///
-    /// Returns the number of nodes removed.
-    pub async fn purge_stale_offline_nodes(&self) -> ApiResult<usize> {
-        let Ok(db) = self.require_db() else {
-            return Ok(0);
-        };
+    /// Record the cumulative energy a relay node has metered for one of its
+    /// gateway sessions.  Returns `false` when the session is not bound to this
+    /// node or the node is not owned by the caller.
+    pub async fn report_gateway_session_usage(
+        &self,
+        node_id: &str,
+        session_id: &str,
+        owner_id: Uuid,
+        report: GatewaySessionUsageReport,
+    ) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        let updated = sqlx::query(
+            r#"
+            UPDATE connect_sessions cs
+            SET energy_wh = GREATEST(COALESCE(cs.energy_wh, 0), $4),
+                updated_at = NOW()
+            FROM nodes n
+            WHERE cs.session_id = $1
+              AND cs.node_id = $2
+              AND n.node_id = cs.node_id
+              AND (n.owner_id = $3 OR org_role_at_least(n.org_id, $3, 'member'))
+              AND n.deleted_at IS NULL
+            "#,
+        )
+        .bind(session_id)
+        .bind(node_id)
+        .bind(owner_id)
+        .bind(report.energy_wh)
+        .execute(db)
+        .await?
+        .rows_affected();
 
-        let threshold_minutes: i64 = std::env::var("NODE_REMOVAL_TIMEOUT_MINUTES")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .filter(|v: &i64| *v > 0)
-            .unwrap_or(5);
+        Ok(updated > 0)
+    }
 
-        let purged: Vec<String> = sqlx::query_scalar(
+    /// Aggregate energy and estimated CO2 for a user's tasks and connect sessions.
+    ///
+    /// Energy is grouped by the region of the node that reported it so each
+    /// region's configured grid intensity is applied.
+    pub async fn get_usage_report(&self, user_id: Uuid) -> ApiResult<UsageReport> {
+        let db = self.read_db()?;
+
+        let rows = sqlx::query(
             r#"
-            WITH purged AS (
-                UPDATE nodes
-                SET deleted_at = NOW(), updated_at = NOW()
-                WHERE status = 'offline'
-                  AND deleted_at IS NULL
-                  AND updated_at < NOW() - (interval '1 minute' * $1)
-                RETURNING node_id
-            )
-            SELECT node_id FROM purged
+            SELECT region,
+                   COALESCE(SUM(task_wh), 0)::DOUBLE PRECISION AS task_wh,
+                   COALESCE(SUM(session_wh), 0)::DOUBLE PRECISION AS session_wh
+            FROM (
+                SELECT n.region, ta.energy_wh AS task_wh, NULL::DOUBLE PRECISION AS session_wh
+                FROM task_assignments ta
+                JOIN tasks t ON t.task_id = ta.task_id
+                JOIN nodes n ON n.node_id = ta.node_id
+                WHERE t.creator_id = $1 AND ta.energy_wh IS NOT NULL
+                UNION ALL
+                SELECT n.region, NULL, cs.energy_wh
+                FROM connect_sessions cs
+                JOIN nodes n ON n.node_id = cs.node_id
+                WHERE cs.requester_id = $1 AND cs.energy_wh IS NOT NULL
+            ) usage
+            GROUP BY region
+            ORDER BY region
             "#,
         )
-        .bind(threshold_minutes)
+        .bind(user_id)
         .fetch_all(db)
         .await?;
 
-        let count = purged.len();
-        tracing::info!(purged = count, "Stale offline node purge completed");
-        Ok(count)
+        let mut task_energy_wh = 0.0;
+        let mut session_energy_wh = 0.0;
+        let regions: Vec<RegionEnergyUsage> = rows
+            .into_iter()
+            .map(|row| {
+                let region: String = row.get("region");
+                let task_wh: f64 = row.get("task_wh");
+                let session_wh: f64 = row.get("session_wh");
+                task_energy_wh += task_wh;
+                session_energy_wh += session_wh;
+
+                let energy_wh = task_wh + session_wh;
+                RegionEnergyUsage {
+                    grid_factor_g_per_kwh: self.carbon_factors.factor_for(&region),
+                    estimated_co2_grams: self.carbon_factors.estimate_co2_grams(&region, energy_wh),
+                    region,
+                    energy_wh,
+                }
+            })
+            .collect();
+
+        let task_cpu_time_ms: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(SUM(ta.cpu_time_ms), 0)::BIGINT
+            FROM task_assignments ta
+            JOIN tasks t ON t.task_id = ta.task_id
+            WHERE t.creator_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+
+        let queue_wait = sqlx::query(
+            r#"
+            SELECT COUNT(queue_wait_seconds) AS tasks_started,
+                   AVG(queue_wait_seconds)::DOUBLE PRECISION AS avg_wait_seconds,
+                   MAX(queue_wait_seconds)::DOUBLE PRECISION AS max_wait_seconds
+            FROM tasks
+            WHERE creator_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+
+        Ok(UsageReport {
+            user_id: user_id.to_string(),
+            task_energy_wh,
+            session_energy_wh,
+            total_energy_wh: task_energy_wh + session_energy_wh,
+            estimated_co2_grams: regions.iter().map(|r| r.estimated_co2_grams).sum(),
+            task_cpu_time_ms,
+            task_queue_wait: TaskQueueWait {
+                tasks_started: queue_wait.get("tasks_started"),
+                avg_wait_seconds: queue_wait.get("avg_wait_seconds"),
+                max_wait_seconds: queue_wait.get("max_wait_seconds"),
+            },
+            regions,
+            generated_at: chrono::Utc::now().to_rfc3339(),
+        })
     }
 
-    /// Accept an execution result submitted by a node owner.
+    /// Return the active gateway sessions that the given node should be relaying.
     ///
-    /// Validates that:
-    /// - The authenticated user owns the reporting node.
-    /// - The node is actively assigned to the task.
-    /// - The task is in a runnable state (`running` or `pending`).
-    /// - If `proof_data` is supplied, the ZK proof is verified before the
-    ///   result is stored.
+    /// Called by `open_internet` / relay nodes so they can populate their
+    /// `DataPlaneGateway` session store without relying on a static sessions file.
+    /// The response includes the cleartext `session_token` that the gateway uses
+    /// to authenticate incoming relay connections from clients.
     ///
-    /// On success the task is marked `completed` and the result is persisted.
-    /// All remaining node assignments are disconnected so those nodes become
-    /// available for other pending tasks.
-    pub async fn submit_task_result(
+    /// Only sessions are returned where:
+    /// - status is `active` and expiry is in the future
+    /// - `connect_sessions.node_id` matches this node
+    /// - the node is owned by the requesting user (ownership verified first)
+    pub async fn get_node_gateway_sessions(
         &self,
-        task_id: Uuid,
-        submission: NodeTaskResult,
+        node_id: &str,
         owner_id: Uuid,
-    ) -> ApiResult<serde_json::Value> {
-        let db = self.require_db()?;
-        // Verify the reporting node belongs to the authenticated user.
-        let node_exists: bool = sqlx::query_scalar(
+    ) -> ApiResult<Vec<ambient_node::GatewaySession>> {
+        let Some(db) = &self.db else {
+            return Ok(vec![]);
+        };
+
+        // Verify ownership before exposing session tokens.
+        let owns_node: bool = sqlx::query_scalar(
             r#"
             SELECT EXISTS (
                 SELECT 1 FROM nodes
-                WHERE node_id = $1 AND owner_id = $2 AND deleted_at IS NULL
+                WHERE node_id = $1 AND (owner_id = $2 OR org_role_at_least(org_id, $2, 'member')) AND deleted_at IS NULL
             )
             "#,
         )
-        .bind(&submission.node_id)
+        .bind(node_id)
         .bind(owner_id)
         .fetch_one(db)
         .await?;
 
-        if !node_exists {
+        if !owns_node {
             return Err(ApiError::not_found_or_forbidden(
                 "Node not found or not owned by you",
             ));
         }
 
-        // Fetch task metadata.
-        let task_row = sqlx::query(
+        let rows = sqlx::query(
             r#"
-            SELECT task_type, status, require_proof
-            FROM tasks
-            WHERE task_id = $1
+            SELECT
+                cs.session_id,
+                cs.session_token_cleartext,
+                cs.egress_profile,
+                cs.destination_policy_id,
+                COALESCE(dp.allowed_destinations, '{}') AS allowed_destinations,
+                cs.bandwidth_limit_mbps,
+                cs.tunnel_protocol,
+                FLOOR(EXTRACT(EPOCH FROM LEAST(
+                    cs.expires_at,
+                    CASE WHEN cs.node_id = $1 THEN n.relay_drain_deadline ELSE cs.drain_deadline END
+                )))::BIGINT AS expires_at_epoch
+            FROM connect_sessions cs
+            JOIN nodes n ON n.node_id = $1
+            LEFT JOIN destination_policies dp ON dp.policy_id = cs.destination_policy_id
+            WHERE (
+                    cs.node_id = $1
+                    -- Sessions moved off this relay while it drains.
+                    OR (cs.drained_from_node_id = $1 AND cs.drain_deadline > NOW())
+                  )
+              AND cs.status = 'active'
+              AND cs.expires_at > NOW()
+              AND cs.session_token_cleartext IS NOT NULL
             "#,
         )
-        .bind(task_id)
-        .fetch_optional(db)
+        .bind(node_id)
+        .fetch_all(db)
         .await?;
 
-        let Some(task_row) = task_row else {
-            return Err(ApiError::not_found(format!("Task {} not found", task_id)));
-        };
+        let sessions = rows
+            .into_iter()
+            .filter_map(|row| {
+                let session_id: String = row.try_get("session_id").ok()?;
+                let session_token: String = row.try_get("session_token_cleartext").ok()?;
+                let egress_profile: String = row.try_get("egress_profile").ok()?;
+                let destination_policy_id: String = row.try_get("destination_policy_id").ok()?;
+                let allowed_destinations: Vec<String> = row.try_get("allowed_destinations").ok()?;
+                let bandwidth_limit_mbps: f64 = row.try_get("bandwidth_limit_mbps").ok()?;
+                let tunnel_protocol: String = row.try_get("tunnel_protocol").ok()?;
+                let expires_at_epoch: i64 = row.try_get("expires_at_epoch").ok()?;
 
-        let task_type: String = task_row.get("task_type");
-        let task_status: String = task_row.get("status");
-        let require_proof: bool = task_row.get("require_proof");
+                Some(ambient_node::GatewaySession {
+                    session_id,
+                    session_token,
+                    egress_profile,
+                    destination_policy_id,
+                    // Empty when the policy is not stored; nodes then fall back
+                    // to their cached policy bundle.
+                    allowed_destinations,
+                    bandwidth_limit_mbps: Some(bandwidth_limit_mbps),
+                    tunnel_protocol: Some(tunnel_protocol),
+                    // .max(0): defensive guard against sub-second clock skew between
+                    // app-server and DB that could produce a slightly negative epoch
+                    // when the session expires almost immediately.
+                    expires_at_epoch_seconds: expires_at_epoch.max(0) as u64,
+                })
+            })
+            .collect();
 
-        // connect_only tasks complete via session lifecycle, not node results.
-        if task_type == "connect_only" {
-            return Err(ApiError::bad_request(
-                "connect_only tasks complete via the connect-session API, not this endpoint",
+        Ok(sessions)
+    }
+
+    /// Resolve the peer route of a node the requester owns; see
+    /// [`crate::peer_routes`].  `has_internet` overrides the node's
+    /// registered status.
+    pub async fn find_peer_route(
+        &self,
+        node_id: &str,
+        owner_id: Uuid,
+        has_internet: Option<bool>,
+    ) -> ApiResult<crate::peer_routes::PeerRouteResponse> {
+        let db = self.require_db()?;
+        if !self.check_node_ownership(node_id, owner_id).await? {
+            return Err(ApiError::not_found_or_forbidden(
+                "Node not found or not owned by you",
             ));
         }
 
-        if task_status != "running" && task_status != "pending" {
-            return Err(ApiError::bad_request(format!(
-                "Task is not in an executable state (current status: {})",
-                task_status
-            )));
-        }
+        let status: String = sqlx::query_scalar("SELECT status FROM nodes WHERE node_id = $1")
+            .bind(node_id)
+            .fetch_one(db)
+            .await?;
+        self.resolve_peer_route(node_id, has_internet.unwrap_or(status == "online"))
+            .await
+    }
 
-        // Verify the node is actively assigned to this task.
-        let is_assigned: bool = sqlx::query_scalar(
+    /// Resolve and record `node_id`'s peer route, without an ownership check.
+    async fn resolve_peer_route(
+        &self,
+        node_id: &str,
+        has_internet: bool,
+    ) -> ApiResult<crate::peer_routes::PeerRouteResponse> {
+        let db = self.require_db()?;
+
+        // Relays whose flap breaker is open would drop the route again soon.
+        let rows = sqlx::query(
             r#"
-            SELECT EXISTS (
-                SELECT 1 FROM task_assignments
-                WHERE task_id = $1 AND node_id = $2 AND disconnected_at IS NULL
-            )
+            SELECT n.node_id, n.node_type, n.asn, n.region, n.bandwidth_mbps,
+                   l.utilization, l.saturated,
+                   FLOOR(EXTRACT(EPOCH FROM l.reported_at))::BIGINT AS reported_at_epoch,
+                   t.bytes AS recent_bytes
+            FROM nodes n
+            LEFT JOIN relay_load_reports l ON l.node_id = n.node_id
+            LEFT JOIN LATERAL (
+                SELECT SUM(u.bytes_up + u.bytes_down)::BIGINT AS bytes
+                FROM connect_session_usage u
+                WHERE u.node_id = n.node_id
+                  AND u.interval_end > NOW() - make_interval(secs => $2)
+            ) t ON TRUE
+            WHERE n.deleted_at IS NULL
+              AND n.status = 'online'
+              AND n.node_id <> $1
+              AND NOT COALESCE(n.flap_breaker_until > NOW(), FALSE)
             "#,
         )
-        .bind(task_id)
-        .bind(&submission.node_id)
-        .fetch_one(db)
+        .bind(node_id)
+        .bind(mesh_coordinator::ROUTE_FEEDBACK_TTL_SECS as f64)
+        .fetch_all(db)
         .await?;
-
-        if !is_assigned {
-            return Err(ApiError::bad_request(
-                "Node is not actively assigned to this task",
-            ));
-        }
-
-        // Enforce proof requirement declared on the task.
-        if require_proof && submission.proof_data.is_none() {
-            return Err(ApiError::bad_request(
-                "This task requires a ZK proof; include proof_data and public_inputs",
-            ));
-        }
-
-        // Verify ZK proof when provided.
-        let proof_verified = if let Some(ref proof_data_b64) = submission.proof_data {
-            let proof_bytes =
-                base64::Engine::decode(&base64::engine::general_purpose::STANDARD, proof_data_b64)
-                    .map_err(|_| ApiError::bad_request("proof_data is not valid base64"))?;
-
-            let public_inputs_bytes = submission
-                .public_inputs
-                .as_deref()
-                .map(|pi| {
-                    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, pi)
-                        .map_err(|_| ApiError::bad_request("public_inputs is not valid base64"))
-                })
-                .transpose()?
-                .unwrap_or_default();
-
-            let circuit_id = submission
-                .circuit_id
-                .clone()
-                .unwrap_or_else(|| "default".to_string());
-            let proof =
-                zk_prover::ZKProof::new(proof_bytes, public_inputs_bytes.clone(), circuit_id);
-
-            let valid = tokio::task::spawn_blocking(move || {
-                let verifier = zk_prover::ZKVerifier::default();
-                verifier.verify_proof(&proof, &public_inputs_bytes)
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        let candidates: Vec<crate::peer_routes::RelayCandidate> = rows
+            .iter()
+            .map(|row| {
+                let reported_at = row.get::<Option<i64>, _>("reported_at_epoch");
+                crate::peer_routes::RelayCandidate {
+                    node_id: row.get("node_id"),
+                    node_type: row.get("node_type"),
+                    asn: row
+                        .get::<Option<i64>, _>("asn")
+                        .and_then(|asn| u32::try_from(asn).ok()),
+                    region: row.get("region"),
+                    load: reported_at.map(|reported_at| mesh_coordinator::RelayLoad {
+                        utilization: row.get::<Option<f64>, _>("utilization").unwrap_or(0.0),
+                        saturated: row.get::<Option<bool>, _>("saturated").unwrap_or(false),
+                        reported_at: reported_at.max(0) as u64,
+                    }),
+                    traffic: Some(mesh_coordinator::RelayTraffic {
+                        mbps: row.get::<Option<i64>, _>("recent_bytes").unwrap_or(0) as f64 * 8.0
+                            / 1_000_000.0
+                            / mesh_coordinator::ROUTE_FEEDBACK_TTL_SECS as f64,
+                        capacity_mbps: row.get("bandwidth_mbps"),
+                        measured_at: now,
+                    }),
+                }
             })
-            .await
-            .map_err(|_| ApiError::internal_error("Proof verification task failed"))?;
-
-            if !valid {
-                return Err(ApiError::bad_request(
-                    "Proof verification failed: invalid proof or public inputs",
-                ));
-            }
-            true
-        } else {
-            false
-        };
-
-        let now = chrono::Utc::now();
-        let mut tx = db.begin().await?;
+            .collect();
 
-        // Persist result and mark task completed.
-        sqlx::query(
+        let invalidated: Vec<String> = sqlx::query_scalar(
             r#"
-            UPDATE tasks
-            SET status = 'completed', result = $1, updated_at = $2
-            WHERE task_id = $3
-              AND status IN ('running', 'pending')
+            SELECT relay_node_id FROM peer_route_invalidations
+            WHERE source_node_id = $1 AND expires_at > NOW()
             "#,
         )
-        .bind(&submission.result)
-        .bind(now)
-        .bind(task_id)
-        .execute(&mut *tx)
+        .bind(node_id)
+        .fetch_all(db)
         .await?;
 
-        // Mark submitting node's assignment as completed.
-        sqlx::query(
+        let assignments: Vec<(String, String)> = sqlx::query_as(
             r#"
-            UPDATE task_assignments
-            SET execution_status = 'completed', execution_completed_at = $1
-            WHERE task_id = $2 AND node_id = $3 AND disconnected_at IS NULL
+            SELECT source_node_id, relay_node_id FROM peer_route_assignments
+            WHERE assigned_at > NOW() - make_interval(secs => $1)
             "#,
         )
-        .bind(now)
-        .bind(task_id)
-        .bind(&submission.node_id)
-        .execute(&mut *tx)
+        .bind(mesh_coordinator::ROUTE_FEEDBACK_TTL_SECS as f64)
+        .fetch_all(db)
         .await?;
 
-        // Disconnect all remaining active assignments for this task.
+        let route = crate::peer_routes::resolve_route(
+            node_id,
+            has_internet,
+            &candidates,
+            &assignments,
+            &invalidated,
+            now,
+        );
+
+        // Count the node against its relay for later queries.
+        match route.as_ref().and_then(|route| route.hops.first()) {
+            Some(relay) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO peer_route_assignments (source_node_id, relay_node_id, assigned_at)
+                    VALUES ($1, $2, NOW())
+                    ON CONFLICT (source_node_id)
+                    DO UPDATE SET relay_node_id = EXCLUDED.relay_node_id,
+                                  assigned_at = EXCLUDED.assigned_at
+                    "#,
+                )
+                .bind(node_id)
+                .bind(&relay.node_id)
+                .execute(db)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM peer_route_assignments WHERE source_node_id = $1")
+                    .bind(node_id)
+                    .execute(db)
+                    .await?;
+            }
+        }
+
+        Ok(crate::peer_routes::PeerRouteResponse {
+            node_id: node_id.to_string(),
+            route,
+        })
+    }
+
+    /// Stop routing a node the requester owns through `relay_node_id` for
+    /// `ROUTE_FEEDBACK_TTL_SECS`, then resolve its route again.
+    pub async fn invalidate_peer_route(
+        &self,
+        node_id: &str,
+        owner_id: Uuid,
+        invalidation: &crate::peer_routes::PeerRouteInvalidation,
+    ) -> ApiResult<crate::peer_routes::PeerRouteResponse> {
+        let db = self.require_db()?;
+        if !self.check_node_ownership(node_id, owner_id).await? {
+            return Err(ApiError::not_found_or_forbidden(
+                "Node not found or not owned by you",
+            ));
+        }
+
+        let mut tx = db.begin().await?;
         sqlx::query(
+            "DELETE FROM peer_route_invalidations WHERE source_node_id = $1 AND expires_at <= NOW()",
+        )
+        .bind(node_id)
+        .execute(&mut *tx)
+        .await?;
+        let recorded = sqlx::query(
             r#"
-            UPDATE task_assignments
-            SET disconnected_at = $1
-            WHERE task_id = $2 AND disconnected_at IS NULL
+            INSERT INTO peer_route_invalidations (source_node_id, relay_node_id, expires_at)
+            SELECT $1, node_id, NOW() + make_interval(secs => $3)
+            FROM nodes
+            WHERE node_id = $2
+            ON CONFLICT (source_node_id, relay_node_id)
+            DO UPDATE SET expires_at = EXCLUDED.expires_at
             "#,
         )
-        .bind(now)
-        .bind(task_id)
+        .bind(node_id)
+        .bind(&invalidation.relay_node_id)
+        .bind(mesh_coordinator::ROUTE_FEEDBACK_TTL_SECS as f64)
         .execute(&mut *tx)
-        .await?;
-
+        .await?
+        .rows_affected();
+        if recorded == 0 {
+            return Err(ApiError::not_found("Relay node not found"));
+        }
         tx.commit().await?;
 
-        // Let freed nodes pick up pending tasks.
-        let freed_nodes: Vec<String> =
-            sqlx::query_scalar(r#"SELECT node_id FROM task_assignments WHERE task_id = $1"#)
-                .bind(task_id)
-                .fetch_all(db)
-                .await
-                .unwrap_or_default();
+        tracing::info!(
+            node_id,
+            relay_node_id = %invalidation.relay_node_id,
+            "Peer route invalidated"
+        );
+        self.find_peer_route(node_id, owner_id, invalidation.has_internet)
+            .await
+    }
 
-        for node_id in freed_nodes {
-            let _ = self.assign_pending_tasks_for_node(&node_id).await;
+    /// Record the load a relay the requester owns reports for itself.
+    /// Returns `false` when the node is not found or not owned.
+    pub async fn report_relay_load(
+        &self,
+        node_id: &str,
+        owner_id: Uuid,
+        report: &crate::peer_routes::RelayLoadReport,
+    ) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        if !self.check_node_ownership(node_id, owner_id).await? {
+            return Ok(false);
         }
 
-        Ok(serde_json::json!({
-            "task_id": task_id.to_string(),
-            "status": "completed",
-            "node_id": submission.node_id,
-            "proof_verified": proof_verified,
-            "completed_at": now.to_rfc3339(),
-        }))
+        sqlx::query(
+            r#"
+            INSERT INTO relay_load_reports (node_id, utilization, saturated, reported_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (node_id)
+            DO UPDATE SET utilization = EXCLUDED.utilization,
+                          saturated = EXCLUDED.saturated,
+                          reported_at = EXCLUDED.reported_at
+            "#,
+        )
+        .bind(node_id)
+        .bind(report.utilization)
+        .bind(report.saturated)
+        .execute(db)
+        .await?;
+        Ok(true)
     }
 
-    /// Return the active gateway sessions that the given node should be relaying.
-    ///
-    /// Called by `open_internet` / relay nodes so they can populate their
-    /// `DataPlaneGateway` session store without relying on a static sessions file.
-    /// The response includes the cleartext `session_token` that the gateway uses
-    /// to authenticate incoming relay connections from clients.
+    /// A relay the requester owns is about to shut down: drain it, re-route
+    /// the nodes routed through it and hand its connect sessions to other
+    /// relays.  The relay keeps serving its sessions, capped at the drain
+    /// deadline, so clients move before it disappears.
     ///
-    /// Only sessions are returned where:
-    /// - status is `active` and expiry is in the future
-    /// - `connect_sessions.node_id` matches this node
-    /// - the node is owned by the requesting user (ownership verified first)
-    pub async fn get_node_gateway_sessions(
+    /// Returns `None` when the node does not exist, is not owned by the
+    /// caller, or is neither online nor already draining.
+    #[tracing::instrument(skip_all, fields(%node_id))]
+    pub async fn announce_relay_drain(
         &self,
         node_id: &str,
         owner_id: Uuid,
-    ) -> ApiResult<Vec<ambient_node::GatewaySession>> {
-        let Some(db) = &self.db else {
-            return Ok(vec![]);
+        announcement: &crate::peer_routes::RelayDrainAnnouncement,
+    ) -> ApiResult<Option<crate::peer_routes::RelayDrainResponse>> {
+        let db = self.require_db()?;
+        let Some(drained) = self.drain_node(node_id, owner_id).await? else {
+            return Ok(None);
         };
+        let grace_seconds = announcement
+            .grace_seconds
+            .unwrap_or_else(Self::relay_drain_grace_seconds);
 
-        // Verify ownership before exposing session tokens.
-        let owns_node: bool = sqlx::query_scalar(
+        // A repeated announcement may bring the deadline forward, not back.
+        let drain_deadline: chrono::DateTime<chrono::Utc> = sqlx::query_scalar(
             r#"
-            SELECT EXISTS (
-                SELECT 1 FROM nodes
-                WHERE node_id = $1 AND owner_id = $2 AND deleted_at IS NULL
-            )
+            UPDATE nodes
+            SET relay_drain_deadline = LEAST(
+                    relay_drain_deadline,
+                    NOW() + make_interval(secs => $2)
+                )
+            WHERE node_id = $1
+            RETURNING relay_drain_deadline
             "#,
         )
         .bind(node_id)
-        .bind(owner_id)
+        .bind(grace_seconds as f64)
         .fetch_one(db)
         .await?;
 
-        if !owns_node {
-            return Err(ApiError::not_found_or_forbidden(
-                "Node not found or not owned by you",
-            ));
+        // The relay is no longer a candidate now that it is draining.
+        let sources: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT source_node_id FROM peer_route_assignments
+            WHERE relay_node_id = $1
+              AND assigned_at > NOW() - make_interval(secs => $2)
+            ORDER BY source_node_id
+            "#,
+        )
+        .bind(node_id)
+        .bind(mesh_coordinator::ROUTE_FEEDBACK_TTL_SECS as f64)
+        .fetch_all(db)
+        .await?;
+        let mut rerouted_nodes = Vec::with_capacity(sources.len());
+        for source in sources {
+            let route = self.resolve_peer_route(&source, false).await?;
+            rerouted_nodes.push(crate::peer_routes::PeerRouteChange {
+                relay_node_id: route
+                    .route
+                    .and_then(|route| route.hops.into_iter().next())
+                    .map(|hop| hop.node_id),
+                node_id: source,
+            });
         }
 
-        let rows = sqlx::query(
+        let sessions = sqlx::query(
             r#"
-            SELECT
-                cs.session_id,
-                cs.session_token_cleartext,
-                cs.egress_profile,
-                cs.destination_policy_id,
-                EXTRACT(EPOCH FROM cs.expires_at)::BIGINT AS expires_at_epoch
+            SELECT cs.session_id, cs.task_id, t.task_type, t.min_nodes, t.require_gpu
             FROM connect_sessions cs
-            WHERE cs.node_id = $1
-              AND cs.status = 'active'
-              AND cs.expires_at > NOW()
-              AND cs.session_token_cleartext IS NOT NULL
+            JOIN tasks t ON t.task_id = cs.task_id
+            WHERE cs.node_id = $1 AND cs.status = 'active'
+            ORDER BY cs.created_at
             "#,
         )
         .bind(node_id)
         .fetch_all(db)
         .await?;
+        let mut migrated_sessions = Vec::new();
+        let mut remaining_sessions = Vec::new();
+        for session_row in sessions {
+            let session_id: String = session_row.get("session_id");
+            let task_id: Uuid = session_row.get("task_id");
+            let task_type: String = session_row.get("task_type");
+            let min_nodes: i32 = session_row.get("min_nodes");
+            let require_gpu: bool = session_row.get("require_gpu");
+
+            // Free the task's slot on this relay so a replacement is attached.
+            sqlx::query(
+                r#"
+                UPDATE task_assignments
+                SET disconnected_at = NOW(), execution_status = 'handed_off'
+                WHERE task_id = $1 AND node_id = $2 AND disconnected_at IS NULL
+                "#,
+            )
+            .bind(task_id)
+            .bind(node_id)
+            .execute(db)
+            .await?;
+            self.update_task_status_from_assignments(task_id, min_nodes as u32)
+                .await?;
+            if self.get_task_status(task_id).await?.as_deref() == Some("pending") {
+                if let Some(entry) = task_type_registry_entry(&task_type) {
+                    if let Err(err) = self
+                        .assign_available_nodes_for_task(
+                            task_id,
+                            &task_type,
+                            entry,
+                            min_nodes as u32,
+                            require_gpu,
+                        )
+                        .await
+                    {
+                        tracing::warn!(
+                            task_id = %task_id,
+                            "Failed to attach a replacement relay for draining node: {err}"
+                        );
+                    }
+                }
+            }
 
-        let sessions = rows
-            .into_iter()
-            .filter_map(|row| {
-                let session_id: String = row.try_get("session_id").ok()?;
-                let session_token: String = row.try_get("session_token_cleartext").ok()?;
-                let egress_profile: String = row.try_get("egress_profile").ok()?;
-                let destination_policy_id: String = row.try_get("destination_policy_id").ok()?;
-                let expires_at_epoch: i64 = row.try_get("expires_at_epoch").ok()?;
+            let replacement = self.select_active_connect_node_for_task(task_id).await?;
+            let moved = match &replacement {
+                Some(replacement) => {
+                    sqlx::query(
+                        r#"
+                        UPDATE connect_sessions
+                        SET node_id = $3, drained_from_node_id = $2,
+                            drain_deadline = $4, updated_at = NOW()
+                        WHERE session_id = $1 AND node_id = $2 AND status = 'active'
+                        "#,
+                    )
+                    .bind(&session_id)
+                    .bind(node_id)
+                    .bind(replacement)
+                    .bind(drain_deadline)
+                    .execute(db)
+                    .await?
+                    .rows_affected()
+                        > 0
+                }
+                None => false,
+            };
+            match replacement.filter(|_| moved) {
+                Some(replacement) => {
+                    self.record_task_connected_heartbeat_event(
+                        &replacement,
+                        task_id,
+                        &session_id,
+                        &task_type,
+                        chrono::Utc::now(),
+                    )
+                    .await;
+                    migrated_sessions.push(crate::peer_routes::SessionMigration {
+                        session_id,
+                        node_id: replacement,
+                    });
+                }
+                None => remaining_sessions.push(session_id),
+            }
+        }
 
-                Some(ambient_node::GatewaySession {
-                    session_id,
-                    session_token,
-                    egress_profile,
-                    destination_policy_id,
-                    // allowed_destinations is populated by a destination-policy store
-                    // (not yet implemented).  Nodes with egress_profile other than
-                    // "allowlist_domains" do not require this list to be populated.
-                    allowed_destinations: vec![],
-                    // .max(0): defensive guard against sub-second clock skew between
-                    // app-server and DB that could produce a slightly negative epoch
-                    // when the session expires almost immediately.
-                    expires_at_epoch_seconds: expires_at_epoch.max(0) as u64,
-                })
-            })
-            .collect();
+        tracing::info!(
+            node_id,
+            grace_seconds,
+            rerouted = rerouted_nodes.len(),
+            migrated = migrated_sessions.len(),
+            remaining = remaining_sessions.len(),
+            "Relay draining"
+        );
 
-        Ok(sessions)
+        Ok(Some(crate::peer_routes::RelayDrainResponse {
+            node_id: node_id.to_string(),
+            status: drained.status,
+            drain_deadline: drain_deadline.to_rfc3339(),
+            rerouted_nodes,
+            migrated_sessions,
+            remaining_sessions,
+            handed_off_tasks: drained.handed_off_tasks,
+        }))
+    }
+
+    /// Every destination policy, by ID.
+    pub async fn list_destination_policies(
+        &self,
+    ) -> ApiResult<Vec<crate::destination_policies::DestinationPolicy>> {
+        let db = self.require_db()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT policy_id, allowed_destinations, bundle_version, updated_at
+            FROM destination_policies
+            ORDER BY policy_id
+            "#,
+        )
+        .fetch_all(db)
+        .await?;
+        Ok(rows.iter().map(destination_policy_from_row).collect())
+    }
+
+    /// Create or replace a destination policy in a new bundle version.
+    pub async fn upsert_destination_policy(
+        &self,
+        policy_id: &str,
+        allowed_destinations: &[String],
+        created_by: Uuid,
+    ) -> ApiResult<crate::destination_policies::DestinationPolicy> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+        let version = bump_destination_policy_version(&mut tx).await?;
+        let row = sqlx::query(
+            r#"
+            INSERT INTO destination_policies
+                (policy_id, allowed_destinations, bundle_version, created_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (policy_id) DO UPDATE
+            SET allowed_destinations = EXCLUDED.allowed_destinations,
+                bundle_version = EXCLUDED.bundle_version,
+                created_by = EXCLUDED.created_by,
+                updated_at = NOW()
+            RETURNING policy_id, allowed_destinations, bundle_version, updated_at
+            "#,
+        )
+        .bind(policy_id)
+        .bind(allowed_destinations)
+        .bind(version)
+        .bind(created_by)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(destination_policy_from_row(&row))
+    }
+
+    /// Delete a destination policy, bumping the bundle version.  Returns
+    /// `false` when no policy has that ID.
+    pub async fn delete_destination_policy(&self, policy_id: &str) -> ApiResult<bool> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+        let deleted = sqlx::query("DELETE FROM destination_policies WHERE policy_id = $1")
+            .bind(policy_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+            > 0;
+        if deleted {
+            bump_destination_policy_version(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    /// Every destination policy as one bundle signed with the control key
+    /// (see `ambient_node::PolicyBundleCache`).
+    pub async fn signed_destination_policy_bundle(&self) -> ApiResult<serde_json::Value> {
+        let db = self.require_db()?;
+        let mut tx = db.begin().await?;
+        // Read the version and the policies from one snapshot.
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut *tx)
+            .await?;
+        let version: i64 =
+            sqlx::query_scalar("SELECT version FROM destination_policy_bundle WHERE singleton")
+                .fetch_optional(&mut *tx)
+                .await?
+                .unwrap_or_default();
+        let policies = sqlx::query(
+            "SELECT policy_id, allowed_destinations FROM destination_policies ORDER BY policy_id",
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|row| ambient_node::EgressPolicy {
+            id: row.get("policy_id"),
+            allowed_destinations: row.get("allowed_destinations"),
+        })
+        .collect();
+        tx.commit().await?;
+
+        let bundle = ambient_node::PolicyBundle {
+            version: version.max(0) as u64,
+            offline_policy: self.offline_policy,
+            policies,
+        };
+        Ok(self.sign_control_response(
+            ambient_node::POLICY_BUNDLE_AUDIENCE,
+            serde_json::to_value(bundle)
+                .map_err(|_| ApiError::internal_error("Failed to encode policy bundle"))?,
+        ))
+    }
+}
+
+async fn bump_destination_policy_version(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> ApiResult<i64> {
+    Ok(sqlx::query_scalar(
+        r#"
+        INSERT INTO destination_policy_bundle (singleton, version)
+        VALUES (TRUE, 1)
+        ON CONFLICT (singleton) DO UPDATE
+        SET version = destination_policy_bundle.version + 1
+        RETURNING version
+        "#,
+    )
+    .fetch_one(&mut **tx)
+    .await?)
+}
+
+fn destination_policy_from_row(
+    row: &sqlx::postgres::PgRow,
+) -> crate::destination_policies::DestinationPolicy {
+    crate::destination_policies::DestinationPolicy {
+        policy_id: row.get("policy_id"),
+        allowed_destinations: row.get("allowed_destinations"),
+        bundle_version: row.get("bundle_version"),
+        updated_at: row
+            .get::<chrono::DateTime<chrono::Utc>, _>("updated_at")
+            .to_rfc3339(),
+    }
+}
+
+/// Hash of a task's `wasm_module`: the hash a `sha3-256:` reference names, or
+/// that of an inline base64 module.
+fn provenance_module_hash(module: &str) -> Option<String> {
+    use base64::Engine;
+    match crate::artifacts::parse_module_reference(module) {
+        Ok(Some(hash)) => Some(hash.to_string()),
+        Ok(None) => base64::engine::general_purpose::STANDARD
+            .decode(module)
+            .ok()
+            .map(|bytes| crate::artifacts::module_hash(&bytes)),
+        Err(_) => None,
+    }
+}
+
+fn map_wasm_module_row(row: &sqlx::postgres::PgRow) -> WasmModuleInfo {
+    let module_hash: String = row.get("module_hash");
+    WasmModuleInfo {
+        reference: format!(
+            "{}{}",
+            crate::artifacts::MODULE_REFERENCE_PREFIX,
+            module_hash
+        ),
+        module_hash,
+        size_bytes: row.get("size_bytes"),
+        storage_backend: row.get("storage_backend"),
+        created_at: row
+            .get::<chrono::DateTime<chrono::Utc>, _>("created_at")
+            .to_rfc3339(),
     }
 }
 
@@ -2878,6 +10876,85 @@ fn should_disconnect_assignments_on_completion(task_type: &str) -> bool {
     true
 }
 
+/// Check a proof against its own public inputs off the async runtime worker.
+/// Decode and verify a standalone proof.  Proofs over the 75KB decoded size
+/// limit are rejected without running the verifier; the rest are checked
+/// with the default verification key.  In production, the key would be
+/// chosen by circuit_id.
+async fn verify_proof_request(request: &ProofVerificationRequest) -> ApiResult<VerifiedProof> {
+    let start = std::time::Instant::now();
+    let proof = decode_proof_request(request)?;
+
+    let (proof, valid, error_message) = if proof.size() > MAX_PROOF_BYTES {
+        (
+            proof,
+            false,
+            Some("Proof size exceeds maximum allowed size"),
+        )
+    } else {
+        let (proof, valid) = verify_zk_proof(proof).await?;
+        (proof, valid, (!valid).then_some(PROOF_VERIFICATION_FAILED))
+    };
+    Ok(VerifiedProof {
+        proof,
+        valid,
+        verification_time_ms: start.elapsed().as_millis() as u64,
+        error_message,
+    })
+}
+
+/// Decode a standalone proof's data and public inputs.
+fn decode_proof_request(request: &ProofVerificationRequest) -> ApiResult<zk_prover::ZKProof> {
+    let proof_data = request.decode_proof_data()?;
+    let public_inputs_data = request.decode_public_inputs()?;
+    let circuit_id = request
+        .circuit_id
+        .clone()
+        .unwrap_or_else(|| "default".to_string());
+    Ok(zk_prover::ZKProof::new(
+        proof_data,
+        public_inputs_data,
+        circuit_id,
+    ))
+}
+
+async fn verify_zk_proof(proof: zk_prover::ZKProof) -> ApiResult<(zk_prover::ZKProof, bool)> {
+    tokio::task::spawn_blocking(move || {
+        let valid = zk_prover::ZKVerifier::default().verify_proof(&proof, &proof.public_inputs);
+        (proof, valid)
+    })
+    .await
+    .map_err(|_| ApiError::internal_error("Proof verification task failed"))
+}
+
+/// Run a task status transition until its compare-and-swap applies, re-reading
+/// the task after every conflict.
+async fn retry_task_transition<T, F, Fut>(
+    task_id: Uuid,
+    transition: &'static str,
+    mut attempt: F,
+) -> ApiResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = ApiResult<TaskTransition<T>>>,
+{
+    for _ in 0..TASK_TRANSITION_ATTEMPTS {
+        match attempt().await? {
+            TaskTransition::Applied(value) => return Ok(value),
+            TaskTransition::Conflict => {
+                crate::middleware::metrics::record_task_version_conflict(transition);
+                tracing::debug!(%task_id, transition, "Task changed concurrently; retrying");
+            }
+        }
+    }
+
+    Err(
+        ApiError::conflict("Task was modified concurrently; retry the request").with_details(
+            serde_json::json!({"task_id": task_id.to_string(), "transition": transition}),
+        ),
+    )
+}
+
 fn analyze_task_payload(task_type: &str, inputs: &serde_json::Value) -> serde_json::Value {
     if task_type == "connect_only" {
         return analyze_connect_only_payload(inputs);
@@ -3758,6 +11835,83 @@ fn parse_connect_session_status(status: &str) -> ConnectSessionStatus {
     }
 }
 
+fn map_canary_run_row(row: &sqlx::postgres::PgRow) -> crate::canary::CanaryRun {
+    crate::canary::CanaryRun {
+        run_id: row.get::<Uuid, _>("run_id").to_string(),
+        kind: crate::canary::CanaryKind::parse(row.get("kind"))
+            .unwrap_or(crate::canary::CanaryKind::Task),
+        node_id: row.get("node_id"),
+        task_id: row
+            .get::<Option<Uuid>, _>("task_id")
+            .map(|id| id.to_string()),
+        session_id: row.get("session_id"),
+        status: row.get("status"),
+        stage: row.get("stage"),
+        stage_latencies_ms: serde_json::from_value(row.get("stage_latencies_ms"))
+            .unwrap_or_default(),
+        error: row.get("error"),
+        started_at: row.get("started_at"),
+        finished_at: row.get("finished_at"),
+    }
+}
+
+fn map_proof_batch_row(row: &sqlx::postgres::PgRow) -> crate::proof_batches::ProofBatch {
+    crate::proof_batches::ProofBatch::new(
+        row.get::<Uuid, _>("batch_id").to_string(),
+        row.get("status"),
+        row.get::<i32, _>("total") as usize,
+        serde_json::from_value(row.get("results")).unwrap_or_default(),
+        row.get("error"),
+        row.get("created_at"),
+        row.get("finished_at"),
+    )
+}
+
+fn map_cluster_export_row(row: &sqlx::postgres::PgRow) -> crate::cluster_export::ClusterExport {
+    crate::cluster_export::ClusterExport {
+        export_id: row.get::<Uuid, _>("export_id").to_string(),
+        status: row.get("status"),
+        format: crate::cluster_export::ExportFormat::parse(row.get("format")).unwrap_or_default(),
+        incremental: row.get("incremental"),
+        since: row.get("since"),
+        snapshot_at: row.get("snapshot_at"),
+        policy: serde_json::from_value(row.get("policy")).unwrap_or_default(),
+        stable_identifiers: row.get("stable_identifiers"),
+        files: serde_json::from_value(row.get("files")).unwrap_or_default(),
+        error: row.get("error"),
+        created_by: row
+            .get::<Option<Uuid>, _>("created_by")
+            .map(|id| id.to_string()),
+        started_at: row.get("started_at"),
+        finished_at: row.get("finished_at"),
+    }
+}
+
+fn map_scheduler_decision_row(
+    row: &sqlx::postgres::PgRow,
+) -> ApiResult<crate::scheduler_decisions::SchedulerDecision> {
+    let decision_id: i64 = row.get("decision_id");
+    let inputs = serde_json::from_value(row.get("inputs")).map_err(|err| {
+        ApiError::internal_error(format!(
+            "Scheduler decision {decision_id} has unreadable inputs: {err}"
+        ))
+    })?;
+    Ok(crate::scheduler_decisions::SchedulerDecision {
+        decision_id,
+        kind: row.get("kind"),
+        task_id: row
+            .get::<Option<Uuid>, _>("task_id")
+            .map(|id| id.to_string()),
+        node_id: row.get("node_id"),
+        rule_versions: serde_json::from_value(row.get("rule_versions")).unwrap_or_default(),
+        candidate_set_hash: row.get("candidate_set_hash"),
+        inputs,
+        chosen: row.get("chosen"),
+        attached: row.get("attached"),
+        decided_at: row.get("decided_at"),
+    })
+}
+
 fn map_connect_session_row(row: sqlx::postgres::PgRow) -> ConnectSessionInfo {
     let status = parse_connect_session_status(&row.get::<String, _>("status"));
     let internet_active = matches!(status, ConnectSessionStatus::Active);
@@ -3784,6 +11938,85 @@ fn map_connect_session_row(row: sqlx::postgres::PgRow) -> ConnectSessionInfo {
         ended_at: row
             .get::<Option<chrono::DateTime<chrono::Utc>>, _>("ended_at")
             .map(|v| v.to_rfc3339()),
+        usage: connect_session_usage_from_row(&row),
+    }
+}
+
+fn connect_session_usage_from_row(row: &sqlx::postgres::PgRow) -> ConnectSessionUsage {
+    let bytes_up = row.get::<i64, _>("bytes_up").max(0) as u64;
+    let bytes_down = row.get::<i64, _>("bytes_down").max(0) as u64;
+    let total_bytes = bytes_up.saturating_add(bytes_down);
+    let data_cap_bytes = row
+        .get::<Option<i64>, _>("data_cap_bytes")
+        .map(|cap| cap.max(0) as u64);
+    ConnectSessionUsage {
+        bytes_up,
+        bytes_down,
+        total_bytes,
+        data_cap_bytes,
+        data_cap_reached: data_cap_bytes.is_some_and(|cap| total_bytes >= cap),
+        last_reported_at: row
+            .get::<Option<chrono::DateTime<chrono::Utc>>, _>("last_usage_at")
+            .map(|v| v.to_rfc3339()),
+    }
+}
+
+/// Decode a node's advertised slot pools from `connect_slots`, `wasm_slots`
+/// and `gpu_slots`.
+fn node_slots_from_row(row: &sqlx::postgres::PgRow) -> NodeSlots {
+    let slots = |column: &str| row.get::<Option<i32>, _>(column).map(|v| v.max(0) as u32);
+    NodeSlots {
+        connect: slots("connect_slots"),
+        wasm: slots("wasm_slots"),
+        gpu: slots("gpu_slots"),
+    }
+}
+
+fn node_asn_from_row(row: &sqlx::postgres::PgRow) -> Option<u32> {
+    row.get::<Option<i64>, _>("asn")
+        .and_then(|asn| u32::try_from(asn).ok())
+}
+
+fn node_circuit_breaker_from_row(
+    row: &sqlx::postgres::PgRow,
+    config: &mesh_coordinator::FlapBreakerConfig,
+) -> crate::flap_breaker::NodeCircuitBreaker {
+    let now = chrono::Utc::now();
+    let open_until = row
+        .get::<Option<chrono::DateTime<chrono::Utc>>, _>("flap_breaker_until")
+        .filter(|until| *until > now);
+    let recent_flaps = row
+        .get::<Option<chrono::DateTime<chrono::Utc>>, _>("first_flap_at")
+        .filter(|first| (now - *first).num_seconds() <= config.window_secs as i64)
+        .map_or(0, |_| row.get::<i32, _>("flap_count").max(0) as u32);
+    crate::flap_breaker::NodeCircuitBreaker {
+        open: open_until.is_some(),
+        recent_flaps,
+        open_until: open_until.map(|until| until.to_rfc3339()),
+    }
+}
+
+fn throttle_override_from_row(row: &sqlx::postgres::PgRow) -> ThrottleOverride {
+    let timestamp = |column: &str| {
+        row.get::<chrono::DateTime<chrono::Utc>, _>(column)
+            .to_rfc3339()
+    };
+    ThrottleOverride {
+        override_id: row.get::<Uuid, _>("override_id").to_string(),
+        subject_type: ThrottleSubjectType::parse(row.get("subject_type"))
+            .unwrap_or(ThrottleSubjectType::User),
+        subject_id: row.get::<Uuid, _>("subject_id").to_string(),
+        multiplier: row.get("multiplier"),
+        burst: row.get("burst"),
+        reason: row.get("reason"),
+        expires_at: row
+            .get::<Option<chrono::DateTime<chrono::Utc>>, _>("expires_at")
+            .map(|expires_at| expires_at.to_rfc3339()),
+        created_by: row
+            .get::<Option<Uuid>, _>("created_by")
+            .map(|created_by| created_by.to_string()),
+        created_at: timestamp("created_at"),
+        updated_at: timestamp("updated_at"),
     }
 }
 
@@ -3794,14 +12027,42 @@ fn parse_task_status(status: &str) -> TaskStatus {
         "running" => TaskStatus::Running,
         "completed" => TaskStatus::Completed,
         "failed" => TaskStatus::Failed,
+        "unschedulable" => TaskStatus::Unschedulable,
         _ => TaskStatus::Pending,
     }
 }
 
+/// Decode the `tasks.egress` JSON column, treating malformed values as no egress.
+/// Decode a `labels` or `node_selector` JSON object.
+fn parse_node_labels(value: serde_json::Value) -> std::collections::BTreeMap<String, String> {
+    serde_json::from_value(value).unwrap_or_default()
+}
+
+fn parse_task_egress(value: Option<serde_json::Value>) -> Vec<TaskEgressRule> {
+    value
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn parses_task_egress_allowlist() {
+        let rules = AppState::parse_task_egress_allowlist(Some(
+            "api.example.com:443, *.data.org:443|8443,,bad:port",
+        ));
+        assert_eq!(
+            rules,
+            vec![
+                wasm_engine::EgressRule::new("api.example.com", vec![443]),
+                wasm_engine::EgressRule::new("*.data.org", vec![443, 8443]),
+            ]
+        );
+        assert!(AppState::parse_task_egress_allowlist(None).is_empty());
+    }
+
     #[test]
     fn parses_max_active_task_attachments_env_value() {
         assert_eq!(
@@ -3830,6 +12091,69 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parses_clock_skew_tolerance_seconds() {
+        assert_eq!(AppState::parse_clock_skew_tolerance_seconds(Some("30")), 30);
+        assert_eq!(AppState::parse_clock_skew_tolerance_seconds(Some("0")), 300);
+        assert_eq!(AppState::parse_clock_skew_tolerance_seconds(None), 300);
+    }
+
+    #[test]
+    fn parses_task_log_poll_interval_ms() {
+        assert_eq!(AppState::parse_task_log_poll_interval_ms(Some("250")), 250);
+        assert_eq!(AppState::parse_task_log_poll_interval_ms(Some("0")), 1000);
+        assert_eq!(AppState::parse_task_log_poll_interval_ms(None), 1000);
+    }
+
+    #[test]
+    fn parses_task_priority_aging_seconds() {
+        assert_eq!(
+            AppState::parse_task_priority_aging_seconds(Some("60")),
+            60.0
+        );
+        assert_eq!(
+            AppState::parse_task_priority_aging_seconds(Some("0")),
+            300.0
+        );
+        assert_eq!(
+            AppState::parse_task_priority_aging_seconds(Some("nan")),
+            300.0
+        );
+        assert_eq!(AppState::parse_task_priority_aging_seconds(None), 300.0);
+    }
+
+    #[test]
+    fn parses_connect_session_extension_limits() {
+        assert_eq!(AppState::parse_connect_session_max_extensions(None), 3);
+        assert_eq!(AppState::parse_connect_session_max_extensions(Some("0")), 0);
+        assert_eq!(
+            AppState::parse_connect_session_max_extensions(Some("-1")),
+            3
+        );
+        assert_eq!(
+            AppState::parse_connect_session_max_lifetime_seconds(Some("7200")),
+            7200
+        );
+        assert_eq!(
+            AppState::parse_connect_session_max_lifetime_seconds(Some("0")),
+            14_400
+        );
+    }
+
+    #[test]
+    fn parses_connect_session_data_cap() {
+        assert_eq!(AppState::parse_connect_session_data_cap_mb(None), None);
+        assert_eq!(AppState::parse_connect_session_data_cap_mb(Some("0")), None);
+        assert_eq!(
+            AppState::parse_connect_session_data_cap_mb(Some("lots")),
+            None
+        );
+        assert_eq!(
+            AppState::parse_connect_session_data_cap_mb(Some("500")),
+            Some(500)
+        );
+    }
+
     #[test]
     fn parses_connect_session_monitor_interval_seconds() {
         assert_eq!(
@@ -4201,4 +12525,18 @@ mod tests {
         let expired_status = parse_connect_session_status("expired");
         assert!(!matches!(expired_status, ConnectSessionStatus::Active));
     }
+
+    #[test]
+    fn provenance_module_hash_covers_inline_and_referenced_modules() {
+        use base64::Engine;
+        let bytes = b"\0asm\x01\0\0\0";
+        let hash = crate::artifacts::module_hash(bytes);
+        let inline = base64::engine::general_purpose::STANDARD.encode(bytes);
+        assert_eq!(provenance_module_hash(&inline), Some(hash.clone()));
+        assert_eq!(
+            provenance_module_hash(&format!("sha3-256:{}", hash)),
+            Some(hash)
+        );
+        assert_eq!(provenance_module_hash("sha3-256:XYZ"), None);
+    }
 }
@@ -0,0 +1,307 @@
+/// Public status page data
+///
+/// `GET /api/v1/status` reports, without authentication, how each part of
+/// the service is doing:
+///
+/// - `api` — answering, with its database reachable
+/// - `scheduler` — task canaries (see [`crate::canary`]) and online nodes
+///   that take `computation` work
+/// - `proof_verification` — 95th percentile verification time over the last
+///   hour, degraded above [`PROOF_VERIFICATION_DEGRADED_MS`]
+/// - `relay` — connect session canaries and relay capacity, which is also
+///   broken down per region
+///
+/// A component whose canary failed is `degraded`, and an `outage` after
+/// [`OUTAGE_FAILED_RUNS`] failures in a row or with no node left to serve
+/// it.  Consecutive failed canary runs of one kind form an incident, which
+/// the next passing run resolves.  Uptime is the share of finished canary
+/// runs that passed, so only canary-backed components report it.  Nothing
+/// here names a node, a user or a task.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::canary::CanaryKind;
+
+/// Consecutive failed canary runs that make a component an outage.
+pub const OUTAGE_FAILED_RUNS: u32 = 3;
+
+/// 95th percentile proof verification time above which verification is
+/// degraded.
+pub const PROOF_VERIFICATION_DEGRADED_MS: f64 = 5000.0;
+
+/// Days of canary runs incidents are drawn from.
+pub const INCIDENT_WINDOW_DAYS: i64 = 7;
+
+/// Most incidents one status response lists.
+pub const MAX_INCIDENTS: usize = 20;
+
+/// Health of a component, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Operational,
+    Degraded,
+    Outage,
+}
+
+/// Component a canary kind exercises.
+pub fn canary_component(kind: CanaryKind) -> &'static str {
+    match kind {
+        CanaryKind::Task => "scheduler",
+        CanaryKind::ConnectSession => "relay",
+    }
+}
+
+/// Response of `GET /api/v1/status`
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct StatusResponse {
+    pub generated_at: DateTime<Utc>,
+    /// Worst status of any component
+    pub status: ComponentStatus,
+    pub components: Vec<ComponentReport>,
+    /// Relay capacity per node region
+    pub relay_regions: Vec<RegionRelayCapacity>,
+    /// Incidents of the last 7 days, newest first
+    pub incidents: Vec<IncidentSummary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ComponentReport {
+    /// `api`, `scheduler`, `proof_verification` or `relay`
+    pub component: String,
+    pub status: ComponentStatus,
+    pub uptime: UptimePercentages,
+}
+
+/// Percentage of finished canary runs that passed, `None` without runs in
+/// the window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct UptimePercentages {
+    pub last_24h: Option<f64>,
+    pub last_7d: Option<f64>,
+    pub last_30d: Option<f64>,
+}
+
+impl UptimePercentages {
+    /// Percentage of `passed` out of `finished`, rounded to two decimals.
+    pub fn percentage(passed: i64, finished: i64) -> Option<f64> {
+        (finished > 0).then(|| (passed as f64 * 10_000.0 / finished as f64).round() / 100.0)
+    }
+}
+
+/// Online relays of one region.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RegionRelayCapacity {
+    pub region: String,
+    pub status: ComponentStatus,
+    pub relays_online: i64,
+    /// Relays neither draining nor reporting saturation
+    pub relays_available: i64,
+    pub relays_saturated: i64,
+    pub relays_draining: i64,
+    /// Bandwidth of the relays not draining
+    pub capacity_mbps: f64,
+    /// Mean utilization (0.0–1.0) the relays recently reported, if any did
+    pub utilization: Option<f64>,
+}
+
+impl RegionRelayCapacity {
+    /// `outage` with no relay available, `degraded` with fewer than half of
+    /// them available.
+    pub fn status_for(relays_online: i64, relays_available: i64) -> ComponentStatus {
+        if relays_available <= 0 {
+            ComponentStatus::Outage
+        } else if relays_available * 2 < relays_online {
+            ComponentStatus::Degraded
+        } else {
+            ComponentStatus::Operational
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentStatus {
+    Ongoing,
+    Resolved,
+}
+
+/// A run of consecutive failed canaries of one kind.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct IncidentSummary {
+    pub component: String,
+    pub status: IncidentStatus,
+    /// Canary stage the latest failed run stopped at
+    pub stage: String,
+    /// Start of the first failed run
+    pub started_at: DateTime<Utc>,
+    /// End of the run that passed again
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub failed_runs: u32,
+}
+
+/// A finished canary run, as incidents are drawn from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FinishedCanary {
+    pub kind: CanaryKind,
+    pub passed: bool,
+    pub stage: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+/// Incidents in `runs` (ordered by `finished_at`), newest first.
+pub fn incidents_from_runs(runs: &[FinishedCanary]) -> Vec<IncidentSummary> {
+    let mut incidents = Vec::new();
+    for kind in CanaryKind::ALL {
+        let mut open: Option<IncidentSummary> = None;
+        for run in runs.iter().filter(|run| run.kind == kind) {
+            match (&mut open, run.passed) {
+                (Some(incident), false) => {
+                    incident.failed_runs += 1;
+                    incident.stage = run.stage.clone();
+                }
+                (None, false) => {
+                    open = Some(IncidentSummary {
+                        component: canary_component(kind).to_string(),
+                        status: IncidentStatus::Ongoing,
+                        stage: run.stage.clone(),
+                        started_at: run.started_at,
+                        resolved_at: None,
+                        failed_runs: 1,
+                    });
+                }
+                (Some(_), true) => {
+                    let mut incident = open.take().expect("incident is open");
+                    incident.status = IncidentStatus::Resolved;
+                    incident.resolved_at = Some(run.finished_at);
+                    incidents.push(incident);
+                }
+                (None, true) => {}
+            }
+        }
+        incidents.extend(open);
+    }
+    incidents.sort_by_key(|incident| std::cmp::Reverse(incident.started_at));
+    incidents.truncate(MAX_INCIDENTS);
+    incidents
+}
+
+/// Status of a canary-backed component from its ongoing incident and the
+/// nodes that can serve it.
+pub fn canary_component_status(
+    ongoing: Option<&IncidentSummary>,
+    serving_nodes: i64,
+) -> ComponentStatus {
+    match ongoing.map_or(0, |incident| incident.failed_runs) {
+        _ if serving_nodes <= 0 => ComponentStatus::Outage,
+        0 => ComponentStatus::Operational,
+        failed if failed >= OUTAGE_FAILED_RUNS => ComponentStatus::Outage,
+        _ => ComponentStatus::Degraded,
+    }
+}
+
+/// Status of proof verification from its recent 95th percentile time.
+pub fn proof_verification_status(p95_ms: Option<f64>) -> ComponentStatus {
+    match p95_ms {
+        Some(ms) if ms > PROOF_VERIFICATION_DEGRADED_MS => ComponentStatus::Degraded,
+        _ => ComponentStatus::Operational,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(kind: CanaryKind, passed: bool, minute: i64) -> FinishedCanary {
+        let base = DateTime::parse_from_rfc3339("2026-10-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        FinishedCanary {
+            kind,
+            passed,
+            stage: if passed { "verification" } else { "execution" }.to_string(),
+            started_at: base + chrono::Duration::minutes(minute),
+            finished_at: base + chrono::Duration::minutes(minute + 1),
+        }
+    }
+
+    #[test]
+    fn consecutive_failures_form_incidents() {
+        let runs = [
+            run(CanaryKind::Task, true, 0),
+            run(CanaryKind::Task, false, 5),
+            run(CanaryKind::ConnectSession, false, 6),
+            run(CanaryKind::Task, false, 10),
+            run(CanaryKind::Task, true, 15),
+            run(CanaryKind::Task, false, 20),
+        ];
+        let incidents = incidents_from_runs(&runs);
+        assert_eq!(incidents.len(), 3);
+
+        assert_eq!(incidents[0].component, "scheduler");
+        assert_eq!(incidents[0].status, IncidentStatus::Ongoing);
+        assert_eq!(incidents[0].failed_runs, 1);
+
+        assert_eq!(incidents[1].component, "relay");
+        assert_eq!(incidents[1].status, IncidentStatus::Ongoing);
+
+        assert_eq!(incidents[2].status, IncidentStatus::Resolved);
+        assert_eq!(incidents[2].failed_runs, 2);
+        assert_eq!(incidents[2].started_at, runs[1].started_at);
+        assert_eq!(incidents[2].resolved_at, Some(runs[4].finished_at));
+    }
+
+    #[test]
+    fn component_status_follows_failures_and_capacity() {
+        let mut incident = incidents_from_runs(&[run(CanaryKind::Task, false, 0)]).remove(0);
+        assert_eq!(
+            canary_component_status(None, 2),
+            ComponentStatus::Operational
+        );
+        assert_eq!(canary_component_status(None, 0), ComponentStatus::Outage);
+        assert_eq!(
+            canary_component_status(Some(&incident), 2),
+            ComponentStatus::Degraded
+        );
+        incident.failed_runs = OUTAGE_FAILED_RUNS;
+        assert_eq!(
+            canary_component_status(Some(&incident), 2),
+            ComponentStatus::Outage
+        );
+
+        assert_eq!(
+            proof_verification_status(Some(6000.0)),
+            ComponentStatus::Degraded
+        );
+        assert_eq!(
+            proof_verification_status(None),
+            ComponentStatus::Operational
+        );
+
+        assert_eq!(
+            RegionRelayCapacity::status_for(4, 1),
+            ComponentStatus::Degraded
+        );
+        assert_eq!(
+            RegionRelayCapacity::status_for(4, 0),
+            ComponentStatus::Outage
+        );
+        assert_eq!(
+            RegionRelayCapacity::status_for(4, 2),
+            ComponentStatus::Operational
+        );
+    }
+
+    #[test]
+    fn uptime_percentages_round_to_two_decimals() {
+        assert_eq!(UptimePercentages::percentage(0, 0), None);
+        assert_eq!(UptimePercentages::percentage(2, 3), Some(66.67));
+        assert_eq!(UptimePercentages::percentage(5, 5), Some(100.0));
+        assert_eq!(
+            ComponentStatus::Outage.max(ComponentStatus::Degraded),
+            ComponentStatus::Outage
+        );
+    }
+}
@@ -0,0 +1,141 @@
+/// Task search
+///
+/// `GET /api/v1/tasks/search?q=` finds the caller's tasks (or, with an org
+/// context, the org's) by content.  `tasks.search_text` holds the task type,
+/// the first 8 KiB of the inputs JSON and the first 2 KiB of the result; a
+/// task matches when
+///
+/// - its `search_text` matches `q` as a web-style full-text query (words,
+///   `"quoted phrases"`, `-excluded`, `or`),
+/// - `q` appears in its `search_text` as a case-insensitive substring, or
+/// - `q` is its task ID or the ID of one of its connect sessions.
+///
+/// Exact ID matches rank first, then full-text relevance, then recency.
+use crate::error::ApiError;
+use crate::models::TaskStatus;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const MAX_QUERY_CHARS: usize = 256;
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+/// Query string for `GET /api/v1/tasks/search`
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TaskSearchQuery {
+    /// Words, phrases or an ID to look for (1–256 characters).
+    pub q: String,
+    /// Results to return (default 20, at most 100).
+    pub limit: Option<i64>,
+}
+
+impl TaskSearchQuery {
+    /// The trimmed query text and the limit.
+    pub fn resolve(&self) -> Result<(&str, i64), ApiError> {
+        let q = self.q.trim();
+        if q.is_empty() || q.chars().count() > MAX_QUERY_CHARS {
+            return Err(ApiError::bad_request(format!(
+                "q must be between 1 and {MAX_QUERY_CHARS} characters"
+            )));
+        }
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(ApiError::bad_request(format!(
+                "limit must be between 1 and {MAX_LIMIT}"
+            )));
+        }
+        Ok((q, limit))
+    }
+}
+
+/// `q` as an `ILIKE` pattern matching it anywhere, with wildcards escaped.
+pub fn substring_pattern(q: &str) -> String {
+    let mut pattern = String::with_capacity(q.len() + 2);
+    pattern.push('%');
+    for c in q.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Binds: `$1` query text, `$2` substring pattern, `$3` query as a task ID
+/// (`NULL` unless it parses as a UUID), `$4` requester, `$5` org or `NULL`,
+/// `$6` limit.
+pub const SEARCH_SQL: &str = r#"
+WITH query AS (
+    SELECT websearch_to_tsquery('simple', $1) AS ts
+),
+direct AS (
+    SELECT $3::UUID AS task_id
+    UNION
+    SELECT cs.task_id FROM connect_sessions cs WHERE cs.session_id = $1
+)
+SELECT t.task_id, t.task_type, t.status, t.created_at, t.updated_at,
+       (CASE WHEN t.task_id IN (SELECT task_id FROM direct) THEN 1 ELSE 0 END
+        + ts_rank_cd(to_tsvector('simple', t.search_text), query.ts))::DOUBLE PRECISION AS score,
+       ts_headline('simple', t.search_text, query.ts,
+                   'MaxFragments=1, MaxWords=24, MinWords=8') AS snippet
+FROM tasks t, query
+WHERE CASE
+          WHEN $5::UUID IS NULL THEN t.creator_id = $4
+          ELSE t.org_id = $5 AND org_role_at_least($5, $4, 'viewer')
+      END
+  AND t.deleted_at IS NULL
+  AND (
+        to_tsvector('simple', t.search_text) @@ query.ts
+        OR t.search_text ILIKE $2
+        OR t.task_id IN (SELECT task_id FROM direct)
+      )
+ORDER BY score DESC, t.created_at DESC
+LIMIT $6
+"#;
+
+/// One task matching a search.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TaskSearchHit {
+    pub task_id: String,
+    pub task_type: String,
+    pub status: TaskStatus,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Higher is more relevant; exact ID matches score at least 1.
+    pub score: f64,
+    /// Matching excerpt of the task's inputs or result, with matched words
+    /// wrapped in `<b>` tags.
+    pub snippet: String,
+}
+
+/// The query as a task ID, if it is one.
+pub fn as_task_id(q: &str) -> Option<Uuid> {
+    Uuid::parse_str(q).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substring_pattern_escapes_wildcards() {
+        assert_eq!(substring_pattern("sess-1"), "%sess-1%");
+        assert_eq!(substring_pattern("50%_off\\"), "%50\\%\\_off\\\\%");
+    }
+
+    #[test]
+    fn query_is_trimmed_and_bounded() {
+        let query = |q: &str, limit| TaskSearchQuery {
+            q: q.to_string(),
+            limit,
+        };
+        assert_eq!(query("  report ", None).resolve().unwrap(), ("report", 20));
+        assert!(query("   ", None).resolve().is_err());
+        assert!(query(&"x".repeat(257), None).resolve().is_err());
+        assert!(query("report", Some(0)).resolve().is_err());
+        assert!(query("report", Some(101)).resolve().is_err());
+    }
+}
@@ -0,0 +1,184 @@
+/// Per-task-type resource statistics
+///
+/// Nodes report the resources an attempt consumed (wall-clock and CPU time,
+/// peak sandbox memory, energy) with its result or error.  The values are
+/// stored on the attempt's `task_assignments` row and rolled into running
+/// totals in `task_type_stats`, in the same transaction that ends the
+/// attempt, so every reported attempt is counted exactly once.
+///
+/// `GET /api/v1/cluster/task-stats` serves the totals with per-type
+/// averages, which is what submitters and the scheduler use to estimate a
+/// task's cost.
+use crate::models::NodeTaskResult;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Resources one attempt consumed, as its node reported them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AttemptUsage {
+    pub execution_time_ms: Option<u64>,
+    pub cpu_time_ms: Option<u64>,
+    pub peak_memory_bytes: Option<u64>,
+    pub energy_wh: Option<f64>,
+}
+
+impl AttemptUsage {
+    pub fn from_submission(submission: &NodeTaskResult) -> Self {
+        Self {
+            execution_time_ms: submission.execution_time_ms,
+            cpu_time_ms: submission.cpu_time_ms,
+            peak_memory_bytes: submission.peak_memory_bytes,
+            energy_wh: submission.energy_wh,
+        }
+    }
+
+    /// Reported values for the task timeline; `None` when nothing was
+    /// reported.
+    pub fn timeline_details(&self) -> Option<serde_json::Value> {
+        let mut details = serde_json::Map::new();
+        let mut put = |key: &str, value: Option<serde_json::Value>| {
+            if let Some(value) = value {
+                details.insert(key.to_string(), value);
+            }
+        };
+        put("execution_time_ms", self.execution_time_ms.map(Into::into));
+        put("cpu_time_ms", self.cpu_time_ms.map(Into::into));
+        put("peak_memory_bytes", self.peak_memory_bytes.map(Into::into));
+        put("energy_wh", self.energy_wh.map(Into::into));
+        (!details.is_empty()).then_some(serde_json::Value::Object(details))
+    }
+
+    /// Decode the usage columns of a `task_assignments` row.
+    pub fn from_assignment_row(row: &sqlx::postgres::PgRow) -> Self {
+        use sqlx::Row;
+        let unsigned = |column: &str| {
+            row.get::<Option<i64>, _>(column)
+                .map(|value| value.max(0) as u64)
+        };
+        Self {
+            execution_time_ms: unsigned("execution_time_ms"),
+            cpu_time_ms: unsigned("cpu_time_ms"),
+            peak_memory_bytes: unsigned("peak_memory_bytes"),
+            energy_wh: row.get("energy_wh"),
+        }
+    }
+}
+
+/// Add one reported attempt to its task type's totals.
+///
+/// Binds: `$1` task type, `$2` whether the attempt completed, `$3`
+/// execution time (ms), `$4` CPU time (ms), `$5` peak memory (bytes), `$6`
+/// energy (Wh); `$3`–`$6` are `NULL` when not reported.
+pub const ROLL_UP_SQL: &str = r#"
+INSERT INTO task_type_stats AS s (
+    task_type, attempts, completed_attempts,
+    execution_time_samples, execution_time_ms_total,
+    cpu_time_samples, cpu_time_ms_total,
+    peak_memory_samples, peak_memory_bytes_total, peak_memory_bytes_max,
+    energy_samples, energy_wh_total, updated_at
+)
+VALUES (
+    $1, 1, CASE WHEN $2 THEN 1 ELSE 0 END,
+    CASE WHEN $3::BIGINT IS NULL THEN 0 ELSE 1 END, COALESCE($3::BIGINT, 0),
+    CASE WHEN $4::BIGINT IS NULL THEN 0 ELSE 1 END, COALESCE($4::BIGINT, 0),
+    CASE WHEN $5::BIGINT IS NULL THEN 0 ELSE 1 END, COALESCE($5::BIGINT, 0), COALESCE($5::BIGINT, 0),
+    CASE WHEN $6::DOUBLE PRECISION IS NULL THEN 0 ELSE 1 END, COALESCE($6::DOUBLE PRECISION, 0),
+    NOW()
+)
+ON CONFLICT (task_type) DO UPDATE
+SET attempts = s.attempts + 1,
+    completed_attempts = s.completed_attempts + EXCLUDED.completed_attempts,
+    execution_time_samples = s.execution_time_samples + EXCLUDED.execution_time_samples,
+    execution_time_ms_total = s.execution_time_ms_total + EXCLUDED.execution_time_ms_total,
+    cpu_time_samples = s.cpu_time_samples + EXCLUDED.cpu_time_samples,
+    cpu_time_ms_total = s.cpu_time_ms_total + EXCLUDED.cpu_time_ms_total,
+    peak_memory_samples = s.peak_memory_samples + EXCLUDED.peak_memory_samples,
+    peak_memory_bytes_total = s.peak_memory_bytes_total + EXCLUDED.peak_memory_bytes_total,
+    peak_memory_bytes_max = GREATEST(s.peak_memory_bytes_max, EXCLUDED.peak_memory_bytes_max),
+    energy_samples = s.energy_samples + EXCLUDED.energy_samples,
+    energy_wh_total = s.energy_wh_total + EXCLUDED.energy_wh_total,
+    updated_at = EXCLUDED.updated_at
+"#;
+
+/// Totals and averages for every task type with a reported attempt.
+pub const LIST_SQL: &str = r#"
+SELECT task_type, attempts, completed_attempts,
+       execution_time_samples, cpu_time_samples, peak_memory_samples, energy_samples,
+       execution_time_ms_total::DOUBLE PRECISION AS execution_time_ms_total,
+       cpu_time_ms_total::DOUBLE PRECISION AS cpu_time_ms_total,
+       peak_memory_bytes_total::DOUBLE PRECISION AS peak_memory_bytes_total,
+       peak_memory_bytes_max, energy_wh_total, updated_at
+FROM task_type_stats
+ORDER BY task_type
+"#;
+
+/// Resource statistics for one task type.
+///
+/// Averages cover only the attempts that reported the metric and are `null`
+/// when none did.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct TaskTypeStats {
+    pub task_type: String,
+    /// Attempts whose node reported a result or an error.
+    pub attempts: i64,
+    pub completed_attempts: i64,
+    pub avg_execution_time_ms: Option<f64>,
+    pub avg_cpu_time_ms: Option<f64>,
+    pub avg_peak_memory_bytes: Option<f64>,
+    /// `null` when no attempt reported its memory.
+    pub max_peak_memory_bytes: Option<i64>,
+    pub avg_energy_wh: Option<f64>,
+    pub total_cpu_time_ms: f64,
+    pub total_energy_wh: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TaskTypeStats {
+    /// Decode a row selected by [`LIST_SQL`].
+    pub fn from_row(row: &sqlx::postgres::PgRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+        let average = |total: f64, samples: i64| (samples > 0).then(|| total / samples as f64);
+        let memory_samples: i64 = row.try_get("peak_memory_samples")?;
+        let cpu_time_ms_total: f64 = row.try_get("cpu_time_ms_total")?;
+        let energy_wh_total: f64 = row.try_get("energy_wh_total")?;
+        Ok(Self {
+            task_type: row.try_get("task_type")?,
+            attempts: row.try_get("attempts")?,
+            completed_attempts: row.try_get("completed_attempts")?,
+            avg_execution_time_ms: average(
+                row.try_get("execution_time_ms_total")?,
+                row.try_get("execution_time_samples")?,
+            ),
+            avg_cpu_time_ms: average(cpu_time_ms_total, row.try_get("cpu_time_samples")?),
+            avg_peak_memory_bytes: average(row.try_get("peak_memory_bytes_total")?, memory_samples),
+            max_peak_memory_bytes: (memory_samples > 0)
+                .then(|| row.try_get("peak_memory_bytes_max"))
+                .transpose()?,
+            avg_energy_wh: average(energy_wh_total, row.try_get("energy_samples")?),
+            total_cpu_time_ms: cpu_time_ms_total,
+            total_energy_wh: energy_wh_total,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeline_details_list_only_reported_values() {
+        assert_eq!(AttemptUsage::default().timeline_details(), None);
+
+        let usage = AttemptUsage {
+            cpu_time_ms: Some(40),
+            peak_memory_bytes: Some(1 << 20),
+            ..Default::default()
+        };
+        assert_eq!(
+            usage.timeline_details(),
+            Some(serde_json::json!({ "cpu_time_ms": 40, "peak_memory_bytes": 1048576 }))
+        );
+    }
+}
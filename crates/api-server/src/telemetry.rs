@@ -0,0 +1,531 @@
+/// Historical node telemetry
+///
+/// Heartbeats that carry reported state (`ambient_node::telemetry_state`)
+/// also store a sample of the node's metrics in `node_heartbeat_history`.
+/// A rollup job folds closed buckets of those samples into
+/// `node_telemetry_rollups` at `5m`, `1h` and `1d` resolution every
+/// `TELEMETRY_ROLLUP_INTERVAL_SECONDS` (default `300`).
+///
+/// `GET /api/v1/nodes/{id}/telemetry` serves rolled-up buckets and
+/// aggregates raw samples on the fly for buckets the job has not reached
+/// yet, so the newest point is never more than one heartbeat old.
+///
+/// Raw samples are purged by the retention job after
+/// `RETENTION_HEARTBEAT_SAMPLES_DAYS`, sub-daily rollups after
+/// `RETENTION_TELEMETRY_ROLLUPS_DAYS`; daily rollups are kept.
+use crate::error::ApiError;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Most points one telemetry query may return.
+pub const MAX_TELEMETRY_POINTS: i64 = 1000;
+
+/// Range served when `from` is omitted.
+pub const DEFAULT_TELEMETRY_RANGE_HOURS: i64 = 24;
+
+/// Longest range served at `raw` resolution.
+pub const MAX_RAW_TELEMETRY_RANGE_HOURS: i64 = 24;
+
+/// Which aggregate marks a bad bucket for a metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extreme {
+    Min,
+    Max,
+}
+
+impl Extreme {
+    fn as_str(self) -> &'static str {
+        match self {
+            Extreme::Min => "min",
+            Extreme::Max => "max",
+        }
+    }
+}
+
+/// Sampled metrics, named as their `node_heartbeat_history` columns.  Each
+/// is rolled up as `<metric>_avg` and `<metric>_min` or `<metric>_max`.
+pub const TELEMETRY_METRICS: &[(&str, Extreme)] = &[
+    ("health_score", Extreme::Min),
+    ("cpu_usage", Extreme::Max),
+    ("memory_usage", Extreme::Max),
+    ("network_latency_ms", Extreme::Max),
+    ("bandwidth_mbps", Extreme::Min),
+    ("temperature_c", Extreme::Max),
+    ("power_watts", Extreme::Max),
+    ("active_tasks", Extreme::Max),
+];
+
+fn buckets_spanned(span_secs: i64, width: i64) -> i64 {
+    (span_secs + width - 1) / width
+}
+
+/// Downsampling step of a telemetry series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum TelemetryResolution {
+    /// One point per heartbeat sample.
+    #[serde(rename = "raw")]
+    Raw,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    Hour,
+    #[serde(rename = "1d")]
+    Day,
+}
+
+impl TelemetryResolution {
+    /// Resolutions the rollup job maintains, finest first.
+    pub const ROLLUPS: [TelemetryResolution; 3] = [
+        TelemetryResolution::FiveMinutes,
+        TelemetryResolution::Hour,
+        TelemetryResolution::Day,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TelemetryResolution::Raw => "raw",
+            TelemetryResolution::FiveMinutes => "5m",
+            TelemetryResolution::Hour => "1h",
+            TelemetryResolution::Day => "1d",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "raw" => Some(TelemetryResolution::Raw),
+            "5m" => Some(TelemetryResolution::FiveMinutes),
+            "1h" => Some(TelemetryResolution::Hour),
+            "1d" => Some(TelemetryResolution::Day),
+            _ => None,
+        }
+    }
+
+    /// Bucket width in seconds; `None` for raw samples.
+    pub fn bucket_secs(self) -> Option<i64> {
+        match self {
+            TelemetryResolution::Raw => None,
+            TelemetryResolution::FiveMinutes => Some(300),
+            TelemetryResolution::Hour => Some(3600),
+            TelemetryResolution::Day => Some(86_400),
+        }
+    }
+
+    /// Finest rollup that covers `span_secs` in at most
+    /// [`MAX_TELEMETRY_POINTS`] buckets.
+    pub fn auto(span_secs: i64) -> Self {
+        Self::ROLLUPS
+            .into_iter()
+            .find(|resolution| {
+                resolution
+                    .bucket_secs()
+                    .is_some_and(|width| buckets_spanned(span_secs, width) <= MAX_TELEMETRY_POINTS)
+            })
+            .unwrap_or(TelemetryResolution::Day)
+    }
+
+    /// Start of the bucket containing `at`.
+    pub fn bucket_start(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        match self.bucket_secs() {
+            Some(width) => Utc
+                .timestamp_opt(at.timestamp().div_euclid(width) * width, 0)
+                .single()
+                .unwrap_or(at),
+            None => at,
+        }
+    }
+}
+
+/// Query string for `GET /api/v1/nodes/{node_id}/telemetry`
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TelemetryQuery {
+    /// Range start (RFC 3339); defaults to 24 hours before `to`.
+    pub from: Option<DateTime<Utc>>,
+    /// Range end (RFC 3339, exclusive); defaults to now.
+    pub to: Option<DateTime<Utc>>,
+    /// `raw`, `5m`, `1h`, `1d` or `auto` (default): the finest rollup that
+    /// fits the range in 1000 points.
+    pub resolution: Option<String>,
+}
+
+/// A validated telemetry query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TelemetryWindow {
+    /// Aligned down to the start of its bucket.
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub resolution: TelemetryResolution,
+}
+
+impl TelemetryQuery {
+    pub fn resolve(&self, now: DateTime<Utc>) -> Result<TelemetryWindow, ApiError> {
+        resolve_window(self.from, self.to, self.resolution.as_deref(), now)
+    }
+}
+
+/// Validate a `from`/`to`/`resolution` query, shared by the other series
+/// endpoints that downsample the same way.
+pub fn resolve_window(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    resolution: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<TelemetryWindow, ApiError> {
+    let to = to.unwrap_or(now);
+    let from = from.unwrap_or(to - Duration::hours(DEFAULT_TELEMETRY_RANGE_HOURS));
+    if from >= to {
+        return Err(ApiError::bad_request("from must be before to"));
+    }
+    let span_secs = (to - from).num_seconds().max(1);
+
+    let resolution = match resolution {
+        None | Some("auto") => TelemetryResolution::auto(span_secs),
+        Some(raw) => TelemetryResolution::parse(raw).ok_or_else(|| {
+            ApiError::bad_request("resolution must be one of: raw, 5m, 1h, 1d, auto")
+        })?,
+    };
+
+    match resolution.bucket_secs() {
+        None if span_secs > MAX_RAW_TELEMETRY_RANGE_HOURS * 3600 => {
+            return Err(ApiError::bad_request(format!(
+                "raw ranges may span at most {} hours",
+                MAX_RAW_TELEMETRY_RANGE_HOURS
+            )));
+        }
+        Some(width) if buckets_spanned(span_secs, width) > MAX_TELEMETRY_POINTS => {
+            return Err(ApiError::bad_request(format!(
+                "range needs more than {} points at {}; use a coarser resolution",
+                MAX_TELEMETRY_POINTS,
+                resolution.as_str()
+            )));
+        }
+        _ => {}
+    }
+
+    Ok(TelemetryWindow {
+        from: resolution.bucket_start(from),
+        to,
+        resolution,
+    })
+}
+
+/// One bucket (or raw sample) of a node's telemetry.  Metrics the node did
+/// not report in the bucket are `null`.
+#[derive(Debug, Clone, Default, Serialize, ToSchema, async_graphql::SimpleObject)]
+pub struct TelemetryPoint {
+    /// Bucket start, or the sample time at `raw` resolution.
+    pub timestamp: DateTime<Utc>,
+    /// Heartbeat samples aggregated into this point.
+    pub samples: i64,
+    pub health_score_avg: Option<f64>,
+    pub health_score_min: Option<f64>,
+    pub cpu_usage_avg: Option<f64>,
+    pub cpu_usage_max: Option<f64>,
+    pub memory_usage_avg: Option<f64>,
+    pub memory_usage_max: Option<f64>,
+    pub network_latency_ms_avg: Option<f64>,
+    pub network_latency_ms_max: Option<f64>,
+    pub bandwidth_mbps_avg: Option<f64>,
+    pub bandwidth_mbps_min: Option<f64>,
+    pub temperature_c_avg: Option<f64>,
+    pub temperature_c_max: Option<f64>,
+    pub power_watts_avg: Option<f64>,
+    pub power_watts_max: Option<f64>,
+    pub active_tasks_avg: Option<f64>,
+    pub active_tasks_max: Option<f64>,
+}
+
+impl TelemetryPoint {
+    /// Decode a row selected by one of the series queries below.
+    pub fn from_row(row: &sqlx::postgres::PgRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+        Ok(Self {
+            timestamp: row.try_get("bucket_start")?,
+            samples: row.try_get("samples")?,
+            health_score_avg: row.try_get("health_score_avg")?,
+            health_score_min: row.try_get("health_score_min")?,
+            cpu_usage_avg: row.try_get("cpu_usage_avg")?,
+            cpu_usage_max: row.try_get("cpu_usage_max")?,
+            memory_usage_avg: row.try_get("memory_usage_avg")?,
+            memory_usage_max: row.try_get("memory_usage_max")?,
+            network_latency_ms_avg: row.try_get("network_latency_ms_avg")?,
+            network_latency_ms_max: row.try_get("network_latency_ms_max")?,
+            bandwidth_mbps_avg: row.try_get("bandwidth_mbps_avg")?,
+            bandwidth_mbps_min: row.try_get("bandwidth_mbps_min")?,
+            temperature_c_avg: row.try_get("temperature_c_avg")?,
+            temperature_c_max: row.try_get("temperature_c_max")?,
+            power_watts_avg: row.try_get("power_watts_avg")?,
+            power_watts_max: row.try_get("power_watts_max")?,
+            active_tasks_avg: row.try_get("active_tasks_avg")?,
+            active_tasks_max: row.try_get("active_tasks_max")?,
+        })
+    }
+}
+
+/// Response for `GET /api/v1/nodes/{node_id}/telemetry`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NodeTelemetryResponse {
+    pub node_id: String,
+    pub resolution: TelemetryResolution,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub points: Vec<TelemetryPoint>,
+}
+
+/// Metrics a heartbeat's reported state contributes to its history row.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HeartbeatTelemetry {
+    pub cpu_usage: Option<f64>,
+    pub memory_usage: Option<f64>,
+    pub network_latency_ms: Option<i32>,
+    pub bandwidth_mbps: Option<f64>,
+    pub temperature_c: Option<f64>,
+    pub power_watts: Option<f64>,
+}
+
+impl HeartbeatTelemetry {
+    /// Read the `ambient_node::TelemetrySample` fields from node state,
+    /// ignoring missing, non-numeric or out-of-range values.
+    pub fn from_state(state: &serde_json::Map<String, serde_json::Value>) -> Self {
+        let number = |key: &str| {
+            state
+                .get(key)
+                .and_then(serde_json::Value::as_f64)
+                .filter(|value| value.is_finite() && *value >= 0.0)
+        };
+        let percent = |key: &str| number(key).filter(|value| *value <= 100.0);
+
+        Self {
+            cpu_usage: percent("cpu_usage_percent"),
+            memory_usage: percent("memory_usage_percent"),
+            network_latency_ms: number("avg_latency_ms")
+                .filter(|value| *value <= i32::MAX as f64)
+                .map(|value| value.round() as i32),
+            bandwidth_mbps: number("bandwidth_mbps"),
+            temperature_c: state
+                .get("temperature_c")
+                .and_then(serde_json::Value::as_f64)
+                .filter(|value| value.is_finite()),
+            power_watts: number("power_watts"),
+        }
+    }
+}
+
+/// Bucket start of `recorded_at` for a width bound as `$2` seconds.
+const BUCKET_EXPR: &str =
+    "to_timestamp((floor(extract(epoch FROM recorded_at) / $2::BIGINT) * $2::BIGINT)::DOUBLE PRECISION)";
+
+fn aggregate_columns() -> String {
+    TELEMETRY_METRICS
+        .iter()
+        .map(|(metric, extreme)| {
+            format!(
+                "AVG({metric})::DOUBLE PRECISION AS {metric}_avg, \
+                 {fold}({metric})::DOUBLE PRECISION AS {metric}_{suffix}",
+                fold = extreme.as_str().to_uppercase(),
+                suffix = extreme.as_str(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n       ")
+}
+
+fn rollup_columns() -> Vec<String> {
+    TELEMETRY_METRICS
+        .iter()
+        .flat_map(|(metric, extreme)| {
+            [
+                format!("{metric}_avg"),
+                format!("{metric}_{}", extreme.as_str()),
+            ]
+        })
+        .collect()
+}
+
+/// Upserts rollup buckets from raw samples.
+///
+/// Binds: `$1` resolution, `$2` bucket width in seconds, `$3` earliest
+/// sample (`NULL` for all), `$4` end of the last closed bucket.
+pub fn rollup_sql() -> String {
+    let columns = rollup_columns();
+    let updates = columns
+        .iter()
+        .map(|column| format!("{column} = EXCLUDED.{column}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        r#"
+INSERT INTO node_telemetry_rollups (node_id, resolution, bucket_start, samples, {columns})
+SELECT node_id, $1, {BUCKET_EXPR} AS bucket_start, COUNT(*),
+       {aggregates}
+FROM node_heartbeat_history
+WHERE status NOT IN ('task_cleared', 'task_connected')
+  AND ($3::TIMESTAMPTZ IS NULL OR recorded_at >= $3)
+  AND recorded_at < $4
+GROUP BY node_id, bucket_start
+ON CONFLICT (node_id, resolution, bucket_start) DO UPDATE
+SET samples = EXCLUDED.samples, {updates}
+"#,
+        columns = columns.join(", "),
+        aggregates = aggregate_columns(),
+    )
+}
+
+/// Rolled-up buckets of one node.
+///
+/// Binds: `$1` node ID, `$2` resolution, `$3` from, `$4` to.
+pub fn rollup_series_sql() -> String {
+    format!(
+        r#"
+SELECT bucket_start, samples::BIGINT AS samples, {columns}
+FROM node_telemetry_rollups
+WHERE node_id = $1
+  AND resolution = $2
+  AND bucket_start >= $3
+  AND bucket_start < $4
+ORDER BY bucket_start
+"#,
+        columns = rollup_columns().join(", "),
+    )
+}
+
+/// Buckets of one node aggregated from raw samples.
+///
+/// Binds: `$1` node ID, `$2` bucket width in seconds, `$3` from, `$4` to.
+pub fn raw_bucket_series_sql() -> String {
+    format!(
+        r#"
+SELECT {BUCKET_EXPR} AS bucket_start, COUNT(*) AS samples,
+       {aggregates}
+FROM node_heartbeat_history
+WHERE node_id = $1
+  AND status NOT IN ('task_cleared', 'task_connected')
+  AND recorded_at >= $3
+  AND recorded_at < $4
+GROUP BY 1
+ORDER BY 1
+"#,
+        aggregates = aggregate_columns(),
+    )
+}
+
+/// Raw samples of one node.
+///
+/// Binds: `$1` node ID, `$2` from, `$3` to, `$4` limit.
+pub fn raw_series_sql() -> String {
+    let columns = TELEMETRY_METRICS
+        .iter()
+        .map(|(metric, extreme)| {
+            format!(
+                "{metric}::DOUBLE PRECISION AS {metric}_avg, \
+                 {metric}::DOUBLE PRECISION AS {metric}_{}",
+                extreme.as_str()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n       ");
+    format!(
+        r#"
+SELECT recorded_at AS bucket_start, 1::BIGINT AS samples,
+       {columns}
+FROM node_heartbeat_history
+WHERE node_id = $1
+  AND status NOT IN ('task_cleared', 'task_connected')
+  AND recorded_at >= $2
+  AND recorded_at < $3
+ORDER BY recorded_at
+LIMIT $4
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().into()
+    }
+
+    #[test]
+    fn query_picks_resolution_and_bounds_points() {
+        let now = at("2026-03-01T12:34:56Z");
+
+        let window = TelemetryQuery::default().resolve(now).unwrap();
+        assert_eq!(window.resolution, TelemetryResolution::FiveMinutes);
+        assert_eq!(window.from, at("2026-02-28T12:30:00Z"));
+        assert_eq!(window.to, now);
+
+        let month = TelemetryQuery {
+            from: Some(now - Duration::days(30)),
+            ..Default::default()
+        };
+        assert_eq!(
+            month.resolve(now).unwrap().resolution,
+            TelemetryResolution::Hour
+        );
+
+        let too_fine = TelemetryQuery {
+            from: Some(now - Duration::days(30)),
+            resolution: Some("5m".into()),
+            ..Default::default()
+        };
+        assert!(too_fine.resolve(now).is_err());
+
+        let long_raw = TelemetryQuery {
+            from: Some(now - Duration::hours(25)),
+            resolution: Some("raw".into()),
+            ..Default::default()
+        };
+        assert!(long_raw.resolve(now).is_err());
+
+        let backwards = TelemetryQuery {
+            from: Some(now),
+            to: Some(now - Duration::hours(1)),
+            ..Default::default()
+        };
+        assert!(backwards.resolve(now).is_err());
+
+        let unknown = TelemetryQuery {
+            resolution: Some("1w".into()),
+            ..Default::default()
+        };
+        assert!(unknown.resolve(now).is_err());
+    }
+
+    #[test]
+    fn heartbeat_state_yields_bounded_metrics() {
+        let state = serde_json::json!({
+            "cpu_usage_percent": 42.5,
+            "memory_usage_percent": 180.0,
+            "avg_latency_ms": 12.6,
+            "bandwidth_mbps": "fast",
+            "temperature_c": -4.0,
+            "power_watts": 95.0,
+        });
+        let telemetry = HeartbeatTelemetry::from_state(state.as_object().unwrap());
+        assert_eq!(
+            telemetry,
+            HeartbeatTelemetry {
+                cpu_usage: Some(42.5),
+                memory_usage: None,
+                network_latency_ms: Some(13),
+                bandwidth_mbps: None,
+                temperature_c: Some(-4.0),
+                power_watts: Some(95.0),
+            }
+        );
+    }
+
+    #[test]
+    fn every_rollup_column_is_created_by_the_migration() {
+        let migration = include_str!("../migrations/20260301000008_add_node_telemetry_rollups.sql");
+        for column in rollup_columns() {
+            assert!(
+                migration.contains(&format!("    {column} DOUBLE PRECISION,\n")),
+                "{column} missing from node_telemetry_rollups"
+            );
+        }
+    }
+}
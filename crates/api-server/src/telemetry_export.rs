@@ -0,0 +1,763 @@
+/// Telemetry export to external time-series stores
+///
+/// Operators who already run InfluxDB or TimescaleDB can have the API server
+/// forward every node heartbeat sample and task attempt outcome there.
+/// Records pass through a bounded in-memory queue: when it is full, new
+/// records are dropped and counted in `telemetry_export_dropped`, so a slow
+/// or unreachable store never slows request handling.  A background worker
+/// sends batches of up to `TELEMETRY_EXPORT_BATCH_SIZE` (default `500`)
+/// records when a batch fills or every `TELEMETRY_EXPORT_FLUSH_MS` (default
+/// `1000`), retrying a failed batch once before dropping it.
+///
+/// Configure with `TELEMETRY_EXPORTERS`, a comma-separated list of:
+///
+/// - `influxdb` (feature `influxdb`) — InfluxDB line protocol posted to
+///   `INFLUXDB_URL` `/api/v2/write` with `INFLUXDB_TOKEN`, `INFLUXDB_ORG` and
+///   `INFLUXDB_BUCKET`, as measurements `vcp_node_telemetry` and
+///   `vcp_task_metrics`
+/// - `timescale` (feature `timescale`) — rows inserted into tables of the
+///   same names in `TIMESCALE_DATABASE_URL`, created on first use and turned
+///   into hypertables when the `timescaledb` extension is installed
+///
+/// plus `TELEMETRY_EXPORT_QUEUE_CAPACITY` (default `10000`).  Unset or empty,
+/// nothing is exported.
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Default number of queued records before new ones are dropped.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+
+/// Default records per exporter call.
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Default milliseconds a partial batch waits before it is sent.
+pub const DEFAULT_FLUSH_MS: u64 = 1000;
+
+/// Delay before a failed batch is retried.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Measurement (InfluxDB) or table (TimescaleDB) for node samples.
+pub const NODE_TELEMETRY_NAME: &str = "vcp_node_telemetry";
+
+/// Measurement (InfluxDB) or table (TimescaleDB) for task outcomes.
+pub const TASK_METRICS_NAME: &str = "vcp_task_metrics";
+
+/// One heartbeat sample, as recorded in `node_heartbeat_history`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeSample {
+    pub node_id: String,
+    pub recorded_at: DateTime<Utc>,
+    pub status: String,
+    pub health_score: f64,
+    pub active_tasks: i64,
+    pub cpu_usage: Option<f64>,
+    pub memory_usage: Option<f64>,
+    pub network_latency_ms: Option<i32>,
+    pub bandwidth_mbps: Option<f64>,
+    pub temperature_c: Option<f64>,
+    pub power_watts: Option<f64>,
+}
+
+/// How a task attempt ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskOutcome {
+    Completed,
+    /// The attempt failed and the task was re-queued.
+    Retrying,
+    /// The attempt failed and the task has no retries left.
+    Failed,
+}
+
+impl TaskOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskOutcome::Completed => "completed",
+            TaskOutcome::Retrying => "retrying",
+            TaskOutcome::Failed => "failed",
+        }
+    }
+}
+
+/// The end of one task attempt on one node.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskMetric {
+    pub task_id: Uuid,
+    pub task_type: String,
+    pub node_id: String,
+    pub outcome: TaskOutcome,
+    pub recorded_at: DateTime<Utc>,
+    /// Retries used before this attempt.
+    pub retry_count: u32,
+    /// From the node confirming the assignment to the result, when known.
+    pub duration_ms: Option<i64>,
+    pub energy_wh: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportRecord {
+    Node(NodeSample),
+    Task(TaskMetric),
+}
+
+/// A time-series store records are forwarded to.
+#[async_trait]
+pub trait TelemetryExporter: Send + Sync {
+    /// Write one batch; an error fails the whole batch.
+    async fn export(&self, batch: &[ExportRecord]) -> anyhow::Result<()>;
+
+    fn name(&self) -> &'static str;
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportConfig {
+    pub capacity: usize,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_QUEUE_CAPACITY,
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: Duration::from_millis(DEFAULT_FLUSH_MS),
+        }
+    }
+}
+
+impl ExportConfig {
+    pub fn from_env() -> Self {
+        let parse = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|raw| raw.parse::<usize>().ok())
+                .filter(|parsed| *parsed > 0)
+        };
+        let defaults = Self::default();
+        Self {
+            capacity: parse("TELEMETRY_EXPORT_QUEUE_CAPACITY").unwrap_or(defaults.capacity),
+            batch_size: parse("TELEMETRY_EXPORT_BATCH_SIZE").unwrap_or(defaults.batch_size),
+            flush_interval: parse("TELEMETRY_EXPORT_FLUSH_MS")
+                .map(|ms| Duration::from_millis(ms as u64))
+                .unwrap_or(defaults.flush_interval),
+        }
+    }
+}
+
+/// Build the exporters named in `TELEMETRY_EXPORTERS`.
+pub fn exporters_from_env() -> anyhow::Result<Vec<Arc<dyn TelemetryExporter>>> {
+    let names = std::env::var("TELEMETRY_EXPORTERS").unwrap_or_default();
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(exporter_from_env)
+        .collect()
+}
+
+fn exporter_from_env(name: &str) -> anyhow::Result<Arc<dyn TelemetryExporter>> {
+    match name {
+        #[cfg(feature = "influxdb")]
+        "influxdb" => Ok(Arc::new(influxdb::InfluxDbExporter::from_env()?)),
+        #[cfg(feature = "timescale")]
+        "timescale" => Ok(Arc::new(timescale::TimescaleExporter::from_env()?)),
+        // Reached only for backends compiled out of this build.
+        #[allow(unreachable_patterns)]
+        "influxdb" | "timescale" => anyhow::bail!(
+            "TELEMETRY_EXPORTERS names {name}, but the server was built without the `{name}` feature"
+        ),
+        other => anyhow::bail!("unknown telemetry exporter {other:?} in TELEMETRY_EXPORTERS"),
+    }
+}
+
+/// Handle to the export queue; cheap to clone.
+#[derive(Clone)]
+pub struct TelemetryExport {
+    tx: mpsc::Sender<ExportRecord>,
+}
+
+impl TelemetryExport {
+    /// Spawn the export worker on the current Tokio runtime.
+    pub fn start(exporters: Vec<Arc<dyn TelemetryExporter>>, config: ExportConfig) -> Self {
+        let (tx, rx) = mpsc::channel(config.capacity);
+        tokio::spawn(run_worker(rx, exporters, config));
+        Self { tx }
+    }
+
+    /// Queue a record without waiting; drops it if the queue is full.
+    pub fn record(&self, record: ExportRecord) {
+        if self.tx.try_send(record).is_err() {
+            crate::middleware::metrics::record_telemetry_export_dropped();
+        }
+    }
+}
+
+async fn run_worker(
+    mut rx: mpsc::Receiver<ExportRecord>,
+    exporters: Vec<Arc<dyn TelemetryExporter>>,
+    config: ExportConfig,
+) {
+    let mut batch = Vec::with_capacity(config.batch_size);
+    loop {
+        // Wait for the first record, then give the batch up to one flush
+        // interval to fill.
+        match rx.recv().await {
+            Some(record) => batch.push(record),
+            None => return,
+        }
+        let deadline = tokio::time::Instant::now() + config.flush_interval;
+        let mut closed = false;
+        while batch.len() < config.batch_size {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(record)) => batch.push(record),
+                Ok(None) => {
+                    closed = true;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        for exporter in &exporters {
+            flush(exporter.as_ref(), &batch).await;
+        }
+        batch.clear();
+        if closed {
+            return;
+        }
+    }
+}
+
+async fn flush(exporter: &dyn TelemetryExporter, batch: &[ExportRecord]) {
+    let name = exporter.name();
+    let mut result = exporter.export(batch).await;
+    if let Err(err) = &result {
+        tracing::debug!(exporter = name, "Telemetry export failed, retrying: {err}");
+        tokio::time::sleep(RETRY_DELAY).await;
+        result = exporter.export(batch).await;
+    }
+    if let Err(err) = &result {
+        tracing::warn!(
+            exporter = name,
+            records = batch.len(),
+            "Telemetry batch dropped: {err}"
+        );
+    }
+    crate::middleware::metrics::record_telemetry_export(name, result.is_ok(), batch.len());
+}
+
+#[cfg(feature = "influxdb")]
+pub mod influxdb {
+    //! InfluxDB v2 line protocol exporter
+    use super::{ExportRecord, TelemetryExporter, NODE_TELEMETRY_NAME, TASK_METRICS_NAME};
+    use async_trait::async_trait;
+    use std::fmt::Write;
+
+    pub struct InfluxDbExporter {
+        client: reqwest::Client,
+        write_url: String,
+        token: Option<String>,
+    }
+
+    impl InfluxDbExporter {
+        pub fn new(
+            url: &str,
+            org: &str,
+            bucket: &str,
+            token: Option<String>,
+        ) -> anyhow::Result<Self> {
+            let write_url = reqwest::Url::parse_with_params(
+                &format!("{}/api/v2/write", url.trim_end_matches('/')),
+                &[("org", org), ("bucket", bucket), ("precision", "ms")],
+            )?;
+            Ok(Self {
+                client: reqwest::Client::builder()
+                    .timeout(std::time::Duration::from_secs(10))
+                    .build()?,
+                write_url: write_url.to_string(),
+                token,
+            })
+        }
+
+        pub fn from_env() -> anyhow::Result<Self> {
+            let var = |name: &str| {
+                std::env::var(name)
+                    .map_err(|_| anyhow::anyhow!("the influxdb telemetry exporter needs {name}"))
+            };
+            Self::new(
+                &var("INFLUXDB_URL")?,
+                &var("INFLUXDB_ORG")?,
+                &var("INFLUXDB_BUCKET")?,
+                std::env::var("INFLUXDB_TOKEN").ok(),
+            )
+        }
+    }
+
+    #[async_trait]
+    impl TelemetryExporter for InfluxDbExporter {
+        async fn export(&self, batch: &[ExportRecord]) -> anyhow::Result<()> {
+            let body = batch
+                .iter()
+                .map(line_protocol)
+                .collect::<Vec<_>>()
+                .join("\n");
+            let mut request = self.client.post(&self.write_url).body(body);
+            if let Some(token) = &self.token {
+                request = request.header("Authorization", format!("Token {token}"));
+            }
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                anyhow::bail!(
+                    "InfluxDB returned {}: {}",
+                    response.status(),
+                    response.text().await.unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            "influxdb"
+        }
+    }
+
+    /// Escape a measurement, tag key or tag value.
+    fn escape_tag(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace(',', "\\,")
+            .replace('=', "\\=")
+            .replace(' ', "\\ ")
+    }
+
+    /// Quote a string field value.
+    fn quote_field(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    /// One record as a line; absent metrics are left out.
+    pub fn line_protocol(record: &ExportRecord) -> String {
+        let mut fields: Vec<String> = Vec::new();
+        let float = |fields: &mut Vec<String>, name: &str, value: Option<f64>| {
+            if let Some(value) = value.filter(|value| value.is_finite()) {
+                fields.push(format!("{name}={value}"));
+            }
+        };
+        let (measurement, tags, timestamp) = match record {
+            ExportRecord::Node(sample) => {
+                float(&mut fields, "health_score", Some(sample.health_score));
+                float(&mut fields, "cpu_usage", sample.cpu_usage);
+                float(&mut fields, "memory_usage", sample.memory_usage);
+                float(&mut fields, "bandwidth_mbps", sample.bandwidth_mbps);
+                float(&mut fields, "temperature_c", sample.temperature_c);
+                float(&mut fields, "power_watts", sample.power_watts);
+                fields.push(format!("active_tasks={}i", sample.active_tasks));
+                if let Some(latency) = sample.network_latency_ms {
+                    fields.push(format!("network_latency_ms={latency}i"));
+                }
+                (
+                    NODE_TELEMETRY_NAME,
+                    vec![("node_id", &sample.node_id), ("status", &sample.status)],
+                    sample.recorded_at,
+                )
+            }
+            ExportRecord::Task(metric) => {
+                float(&mut fields, "energy_wh", metric.energy_wh);
+                fields.push(format!(
+                    "task_id={}",
+                    quote_field(&metric.task_id.to_string())
+                ));
+                fields.push(format!("retry_count={}i", metric.retry_count));
+                if let Some(duration) = metric.duration_ms {
+                    fields.push(format!("duration_ms={duration}i"));
+                }
+                (
+                    TASK_METRICS_NAME,
+                    vec![
+                        ("task_type", &metric.task_type),
+                        ("node_id", &metric.node_id),
+                    ],
+                    metric.recorded_at,
+                )
+            }
+        };
+
+        let mut line = escape_tag(measurement);
+        for (key, value) in tags {
+            // Empty tag values are not allowed in line protocol.
+            if !value.is_empty() {
+                let _ = write!(line, ",{}={}", key, escape_tag(value));
+            }
+        }
+        if let ExportRecord::Task(metric) = record {
+            let _ = write!(line, ",outcome={}", metric.outcome.as_str());
+        }
+        let _ = write!(
+            line,
+            " {} {}",
+            fields.join(","),
+            timestamp.timestamp_millis()
+        );
+        line
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::super::{NodeSample, TaskMetric, TaskOutcome};
+        use super::*;
+
+        fn at(rfc3339: &str) -> chrono::DateTime<chrono::Utc> {
+            chrono::DateTime::parse_from_rfc3339(rfc3339)
+                .unwrap()
+                .into()
+        }
+
+        #[test]
+        fn encodes_records_as_line_protocol() {
+            let sample = ExportRecord::Node(NodeSample {
+                node_id: "edge node,1".to_string(),
+                recorded_at: at("2026-03-01T12:00:00Z"),
+                status: "online".to_string(),
+                health_score: 97.5,
+                active_tasks: 2,
+                cpu_usage: Some(41.0),
+                memory_usage: None,
+                network_latency_ms: Some(18),
+                bandwidth_mbps: None,
+                temperature_c: Some(f64::NAN),
+                power_watts: None,
+            });
+            assert_eq!(
+                line_protocol(&sample),
+                "vcp_node_telemetry,node_id=edge\\ node\\,1,status=online \
+                 health_score=97.5,cpu_usage=41,active_tasks=2i,network_latency_ms=18i \
+                 1772366400000"
+            );
+
+            let task = ExportRecord::Task(TaskMetric {
+                task_id: uuid::Uuid::nil(),
+                task_type: "computation".to_string(),
+                node_id: "node-1".to_string(),
+                outcome: TaskOutcome::Retrying,
+                recorded_at: at("2026-03-01T12:00:00.250Z"),
+                retry_count: 1,
+                duration_ms: None,
+                energy_wh: Some(0.5),
+            });
+            assert_eq!(
+                line_protocol(&task),
+                "vcp_task_metrics,task_type=computation,node_id=node-1,outcome=retrying \
+                 energy_wh=0.5,task_id=\"00000000-0000-0000-0000-000000000000\",retry_count=1i \
+                 1772366400250"
+            );
+        }
+    }
+}
+
+#[cfg(feature = "timescale")]
+pub mod timescale {
+    //! TimescaleDB (or plain PostgreSQL) exporter
+    use super::{ExportRecord, TelemetryExporter, NODE_TELEMETRY_NAME, TASK_METRICS_NAME};
+    use async_trait::async_trait;
+    use sqlx::PgPool;
+    use tokio::sync::OnceCell;
+
+    pub struct TimescaleExporter {
+        pool: PgPool,
+        schema_ready: OnceCell<()>,
+    }
+
+    impl TimescaleExporter {
+        /// Connects lazily, so a store that is down at startup only fails
+        /// exports.
+        pub fn new(database_url: &str) -> anyhow::Result<Self> {
+            Ok(Self {
+                pool: sqlx::postgres::PgPoolOptions::new()
+                    .max_connections(2)
+                    .acquire_timeout(std::time::Duration::from_secs(5))
+                    .connect_lazy(database_url)?,
+                schema_ready: OnceCell::new(),
+            })
+        }
+
+        pub fn from_env() -> anyhow::Result<Self> {
+            let url = std::env::var("TIMESCALE_DATABASE_URL").map_err(|_| {
+                anyhow::anyhow!("the timescale telemetry exporter needs TIMESCALE_DATABASE_URL")
+            })?;
+            Self::new(&url)
+        }
+
+        async fn ensure_schema(&self) -> anyhow::Result<()> {
+            self.schema_ready
+                .get_or_try_init(|| async {
+                    sqlx::query(&format!(
+                        r#"
+                        CREATE TABLE IF NOT EXISTS {NODE_TELEMETRY_NAME} (
+                            time TIMESTAMPTZ NOT NULL,
+                            node_id TEXT NOT NULL,
+                            status TEXT NOT NULL,
+                            health_score DOUBLE PRECISION NOT NULL,
+                            active_tasks BIGINT NOT NULL,
+                            cpu_usage DOUBLE PRECISION,
+                            memory_usage DOUBLE PRECISION,
+                            network_latency_ms INTEGER,
+                            bandwidth_mbps DOUBLE PRECISION,
+                            temperature_c DOUBLE PRECISION,
+                            power_watts DOUBLE PRECISION
+                        )
+                        "#
+                    ))
+                    .execute(&self.pool)
+                    .await?;
+                    sqlx::query(&format!(
+                        r#"
+                        CREATE TABLE IF NOT EXISTS {TASK_METRICS_NAME} (
+                            time TIMESTAMPTZ NOT NULL,
+                            task_id UUID NOT NULL,
+                            task_type TEXT NOT NULL,
+                            node_id TEXT NOT NULL,
+                            outcome TEXT NOT NULL,
+                            retry_count INTEGER NOT NULL,
+                            duration_ms BIGINT,
+                            energy_wh DOUBLE PRECISION
+                        )
+                        "#
+                    ))
+                    .execute(&self.pool)
+                    .await?;
+                    // PL/pgSQL resolves create_hypertable only when it runs,
+                    // so plain PostgreSQL gets ordinary tables.
+                    sqlx::query(&format!(
+                        r#"
+                        DO $$
+                        BEGIN
+                            IF EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb') THEN
+                                PERFORM create_hypertable('{NODE_TELEMETRY_NAME}', 'time', if_not_exists => TRUE);
+                                PERFORM create_hypertable('{TASK_METRICS_NAME}', 'time', if_not_exists => TRUE);
+                            END IF;
+                        END
+                        $$
+                        "#
+                    ))
+                    .execute(&self.pool)
+                    .await?;
+                    Ok::<_, anyhow::Error>(())
+                })
+                .await?;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl TelemetryExporter for TimescaleExporter {
+        async fn export(&self, batch: &[ExportRecord]) -> anyhow::Result<()> {
+            self.ensure_schema().await?;
+
+            let samples: Vec<_> = batch
+                .iter()
+                .filter_map(|record| match record {
+                    ExportRecord::Node(sample) => Some(sample),
+                    ExportRecord::Task(_) => None,
+                })
+                .collect();
+            let metrics: Vec<_> = batch
+                .iter()
+                .filter_map(|record| match record {
+                    ExportRecord::Task(metric) => Some(metric),
+                    ExportRecord::Node(_) => None,
+                })
+                .collect();
+
+            let mut tx = self.pool.begin().await?;
+            if !samples.is_empty() {
+                sqlx::query(&format!(
+                    r#"
+                    INSERT INTO {NODE_TELEMETRY_NAME} (
+                        time, node_id, status, health_score, active_tasks, cpu_usage,
+                        memory_usage, network_latency_ms, bandwidth_mbps, temperature_c, power_watts
+                    )
+                    SELECT * FROM UNNEST(
+                        $1::TIMESTAMPTZ[], $2::TEXT[], $3::TEXT[], $4::DOUBLE PRECISION[],
+                        $5::BIGINT[], $6::DOUBLE PRECISION[], $7::DOUBLE PRECISION[],
+                        $8::INTEGER[], $9::DOUBLE PRECISION[], $10::DOUBLE PRECISION[],
+                        $11::DOUBLE PRECISION[]
+                    )
+                    "#
+                ))
+                .bind(samples.iter().map(|s| s.recorded_at).collect::<Vec<_>>())
+                .bind(
+                    samples
+                        .iter()
+                        .map(|s| s.node_id.clone())
+                        .collect::<Vec<_>>(),
+                )
+                .bind(samples.iter().map(|s| s.status.clone()).collect::<Vec<_>>())
+                .bind(samples.iter().map(|s| s.health_score).collect::<Vec<_>>())
+                .bind(samples.iter().map(|s| s.active_tasks).collect::<Vec<_>>())
+                .bind(samples.iter().map(|s| s.cpu_usage).collect::<Vec<_>>())
+                .bind(samples.iter().map(|s| s.memory_usage).collect::<Vec<_>>())
+                .bind(
+                    samples
+                        .iter()
+                        .map(|s| s.network_latency_ms)
+                        .collect::<Vec<_>>(),
+                )
+                .bind(samples.iter().map(|s| s.bandwidth_mbps).collect::<Vec<_>>())
+                .bind(samples.iter().map(|s| s.temperature_c).collect::<Vec<_>>())
+                .bind(samples.iter().map(|s| s.power_watts).collect::<Vec<_>>())
+                .execute(&mut *tx)
+                .await?;
+            }
+            if !metrics.is_empty() {
+                sqlx::query(&format!(
+                    r#"
+                    INSERT INTO {TASK_METRICS_NAME} (
+                        time, task_id, task_type, node_id, outcome, retry_count, duration_ms, energy_wh
+                    )
+                    SELECT * FROM UNNEST(
+                        $1::TIMESTAMPTZ[], $2::UUID[], $3::TEXT[], $4::TEXT[], $5::TEXT[],
+                        $6::INTEGER[], $7::BIGINT[], $8::DOUBLE PRECISION[]
+                    )
+                    "#
+                ))
+                .bind(metrics.iter().map(|m| m.recorded_at).collect::<Vec<_>>())
+                .bind(metrics.iter().map(|m| m.task_id).collect::<Vec<_>>())
+                .bind(metrics.iter().map(|m| m.task_type.clone()).collect::<Vec<_>>())
+                .bind(metrics.iter().map(|m| m.node_id.clone()).collect::<Vec<_>>())
+                .bind(
+                    metrics
+                        .iter()
+                        .map(|m| m.outcome.as_str().to_string())
+                        .collect::<Vec<_>>(),
+                )
+                .bind(metrics.iter().map(|m| m.retry_count as i32).collect::<Vec<_>>())
+                .bind(metrics.iter().map(|m| m.duration_ms).collect::<Vec<_>>())
+                .bind(metrics.iter().map(|m| m.energy_wh).collect::<Vec<_>>())
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            "timescale"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    /// Records batch sizes; fails its first call when `fail_once` is set.
+    #[derive(Default)]
+    struct RecordingExporter {
+        batches: Mutex<Vec<usize>>,
+        fail_once: AtomicBool,
+    }
+
+    #[async_trait]
+    impl TelemetryExporter for RecordingExporter {
+        async fn export(&self, batch: &[ExportRecord]) -> anyhow::Result<()> {
+            if self.fail_once.swap(false, Ordering::SeqCst) {
+                anyhow::bail!("store unavailable");
+            }
+            self.batches.lock().unwrap().push(batch.len());
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+    }
+
+    fn sample(node_id: &str) -> ExportRecord {
+        ExportRecord::Node(NodeSample {
+            node_id: node_id.to_string(),
+            recorded_at: Utc::now(),
+            status: "online".to_string(),
+            health_score: 100.0,
+            active_tasks: 0,
+            cpu_usage: None,
+            memory_usage: None,
+            network_latency_ms: None,
+            bandwidth_mbps: None,
+            temperature_c: None,
+            power_watts: None,
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn batches_by_size_and_interval_and_retries_once() {
+        let exporter = Arc::new(RecordingExporter::default());
+        exporter.fail_once.store(true, Ordering::SeqCst);
+        let export = TelemetryExport::start(
+            vec![exporter.clone()],
+            ExportConfig {
+                capacity: 100,
+                batch_size: 3,
+                flush_interval: Duration::from_millis(1000),
+            },
+        );
+
+        for index in 0..7 {
+            export.record(sample(&format!("node-{index}")));
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+
+        // Two full batches (the first after one retry), then the remainder
+        // once the flush interval passes.
+        assert_eq!(*exporter.batches.lock().unwrap(), vec![3, 3, 1]);
+    }
+
+    #[tokio::test]
+    async fn full_queue_drops_records_instead_of_waiting() {
+        struct StuckExporter;
+
+        #[async_trait]
+        impl TelemetryExporter for StuckExporter {
+            async fn export(&self, _batch: &[ExportRecord]) -> anyhow::Result<()> {
+                std::future::pending().await
+            }
+
+            fn name(&self) -> &'static str {
+                "stuck"
+            }
+        }
+
+        let export = TelemetryExport::start(
+            vec![Arc::new(StuckExporter)],
+            ExportConfig {
+                capacity: 2,
+                batch_size: 1,
+                flush_interval: Duration::from_millis(1),
+            },
+        );
+        let started = std::time::Instant::now();
+        for index in 0..1000 {
+            export.record(sample(&format!("node-{index}")));
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn unknown_or_disabled_exporters_are_rejected() {
+        assert!(exporter_from_env("prometheus").is_err());
+        #[cfg(not(feature = "influxdb"))]
+        assert!(exporter_from_env("influxdb")
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("feature"));
+    }
+}
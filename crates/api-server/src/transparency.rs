@@ -0,0 +1,154 @@
+/// Signed cluster aggregates for public utilization claims
+///
+/// A job computes a [`TransparencySnapshot`] every
+/// `TRANSPARENCY_SNAPSHOT_INTERVAL_SECONDS` (default `3600`), signs it with
+/// the control key (audience [`TRANSPARENCY_AUDIENCE`]) and stores it.
+/// `GET /api/v1/transparency` serves the newest snapshot and
+/// `GET /api/v1/transparency/history` older ones, each with the public key
+/// that signed it; `ambient_node::verify_signed_record` checks them.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+
+/// `ControlSignature::node_id` of signed transparency snapshots.
+pub const TRANSPARENCY_AUDIENCE: &str = "transparency";
+
+/// Window the uptime distribution and recent counters cover.
+pub const TRANSPARENCY_WINDOW_HOURS: i64 = 24;
+
+/// Width of the slots node uptime is measured in: a node is up for a slot
+/// when it sent at least one heartbeat in it.
+pub const UPTIME_SLOT_SECONDS: i64 = 300;
+
+/// Most snapshots one history request returns.
+pub const MAX_TRANSPARENCY_HISTORY: i64 = 500;
+
+/// Seconds between published snapshots.
+pub fn snapshot_interval_seconds() -> u64 {
+    std::env::var("TRANSPARENCY_SNAPSHOT_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &u64| *v > 0)
+        .unwrap_or(3600)
+}
+
+/// Cluster aggregates at `generated_at`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TransparencySnapshot {
+    pub generated_at: DateTime<Utc>,
+    /// Hours covered by `uptime` and the `last_window` counters.
+    pub window_hours: i64,
+    pub nodes: NodeCounts,
+    pub uptime: UptimeDistribution,
+    pub tasks_completed: WindowedCount,
+    /// Task results whose ZK proof verified.
+    pub proofs_verified: WindowedCount,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NodeCounts {
+    /// Registered nodes, excluding deleted and rejected ones.
+    pub total: i64,
+    pub online: i64,
+    /// `total` per `node_type`.
+    pub by_kind: BTreeMap<String, i64>,
+}
+
+/// Nodes by the share of the window's 5-minute slots they heartbeated in,
+/// counting only slots since the node registered.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UptimeDistribution {
+    pub below_50: i64,
+    pub from_50_to_90: i64,
+    pub from_90_to_99: i64,
+    pub at_least_99: i64,
+}
+
+impl UptimeDistribution {
+    /// Count one node with uptime `ratio` (0.0–1.0).
+    pub fn record(&mut self, ratio: f64) {
+        let bucket = match ratio {
+            r if r >= 0.99 => &mut self.at_least_99,
+            r if r >= 0.90 => &mut self.from_90_to_99,
+            r if r >= 0.50 => &mut self.from_50_to_90,
+            _ => &mut self.below_50,
+        };
+        *bucket += 1;
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WindowedCount {
+    pub total: i64,
+    pub last_window: i64,
+}
+
+/// A stored snapshot as published.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SignedTransparencySnapshot {
+    pub snapshot_id: i64,
+    /// Base64 Ed25519 key that signed `document`.
+    pub public_key: String,
+    /// The [`TransparencySnapshot`] plus its `control_signature`.
+    #[schema(value_type = Object)]
+    pub document: serde_json::Value,
+}
+
+/// Response of `GET /api/v1/transparency`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TransparencyResponse {
+    /// Key currently signing new snapshots.
+    pub public_key: String,
+    pub algorithm: String,
+    pub audience: String,
+    pub snapshot: SignedTransparencySnapshot,
+}
+
+/// Query string for `GET /api/v1/transparency/history`
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TransparencyHistoryQuery {
+    /// Only snapshots generated at or after this time (RFC 3339).
+    pub from: Option<DateTime<Utc>>,
+    /// Only snapshots generated before this time (RFC 3339).
+    pub to: Option<DateTime<Utc>>,
+    /// Newest first, at most 500 (default 100).
+    pub limit: Option<i64>,
+}
+
+impl TransparencyHistoryQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(100).clamp(1, MAX_TRANSPARENCY_HISTORY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uptime_distribution_buckets() {
+        let mut uptime = UptimeDistribution::default();
+        for ratio in [1.0, 0.995, 0.95, 0.9, 0.6, 0.49, 0.0] {
+            uptime.record(ratio);
+        }
+        assert_eq!(
+            uptime,
+            UptimeDistribution {
+                below_50: 2,
+                from_50_to_90: 1,
+                from_90_to_99: 2,
+                at_least_99: 2,
+            }
+        );
+        assert_eq!(
+            TransparencyHistoryQuery {
+                limit: Some(10_000),
+                ..Default::default()
+            }
+            .limit(),
+            MAX_TRANSPARENCY_HISTORY
+        );
+    }
+}
@@ -0,0 +1,237 @@
+#![cfg(feature = "grpc")]
+
+use api_server::grpc::proto::node_agent_client::NodeAgentClient;
+use api_server::grpc::{self, proto};
+use api_server::{auth::AuthConfig, auth::Claims, state::AppState};
+use std::sync::Arc;
+use tonic::Code;
+
+const SECRET: &str = "grpc-test-secret-that-is-long-enough-0123456789";
+
+fn token_with_scopes(scopes: Option<Vec<&str>>) -> String {
+    token_for(uuid::Uuid::new_v4(), scopes)
+}
+
+fn token_for(user_id: uuid::Uuid, scopes: Option<Vec<&str>>) -> String {
+    let mut claims = Claims::new(
+        user_id.to_string(),
+        "grpc".to_string(),
+        "user".to_string(),
+        1,
+    );
+    claims.scopes = scopes.map(|scopes| scopes.into_iter().map(str::to_string).collect());
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(SECRET.as_bytes()),
+    )
+    .unwrap()
+}
+
+fn authed<T>(message: T, token: &str) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    request
+        .metadata_mut()
+        .insert("authorization", format!("Bearer {token}").parse().unwrap());
+    request
+}
+
+fn test_state(db: Option<sqlx::PgPool>) -> Arc<AppState> {
+    std::env::set_var("JWT_SECRET", SECRET);
+    // Tokens here are HS256-signed with the secret, the legacy path.
+    let config = AuthConfig::from_env()
+        .unwrap()
+        .with_legacy_hs256_until(Some(chrono::Utc::now() + chrono::Duration::hours(1)));
+    Arc::new(AppState::new(db).with_auth_config(config))
+}
+
+/// Serve the node agent service for `state` on an ephemeral port.
+async fn serve(state: Arc<AppState>) -> NodeAgentClient<tonic::transport::Channel> {
+    // Bind an ephemeral TCP port, then release it for the listener.
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    tokio::spawn(grpc::serve(state, grpc::GrpcConfig { addr, tls: None }));
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    NodeAgentClient::connect(format!("http://{addr}"))
+        .await
+        .unwrap()
+}
+
+/// Serve the node agent service without a database and check that calls are
+/// authenticated, scoped and validated before reaching it.
+#[tokio::test]
+async fn test_node_agent_auth_and_validation_over_grpc() {
+    let mut client = serve(test_state(None)).await;
+    let register = || proto::RegisterRequest {
+        registration_json: "{not json".to_string(),
+    };
+
+    let status = client.register(register()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let read_only = token_with_scopes(Some(vec!["nodes:read"]));
+    let status = client
+        .register(authed(register(), &read_only))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let token = token_with_scopes(None);
+    let status = client
+        .register(authed(register(), &token))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let status = client
+        .submit_result(authed(
+            proto::SubmitResultRequest {
+                task_id: "not-a-uuid".to_string(),
+                result_json: "{}".to_string(),
+            },
+            &token,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // Errors on the heartbeat stream arrive as its final message.
+    let heartbeats = tokio_stream::iter(vec![proto::HeartbeatRequest {
+        node_id: "node-1".to_string(),
+        heartbeat_json: String::new(),
+    }]);
+    let mut replies = client
+        .heartbeat(authed(heartbeats, &token))
+        .await
+        .unwrap()
+        .into_inner();
+    let status = replies.message().await.unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+}
+
+/// Register over gRPC, then see a new task pushed on the assignment watch and
+/// reported by the heartbeat stream.
+#[tokio::test]
+async fn test_assignments_are_pushed_over_grpc() {
+    let Ok(db_url) = std::env::var("TEST_DATABASE_URL") else {
+        println!("Skipping test_assignments_are_pushed_over_grpc — no TEST_DATABASE_URL set");
+        return;
+    };
+    let pool = sqlx::PgPool::connect(&db_url).await.unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let owner_id = uuid::Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(owner_id)
+        .bind(format!("grpc-owner-{owner_id}"))
+        .execute(&pool)
+        .await
+        .unwrap();
+    let state = test_state(Some(pool.clone()));
+    let mut client = serve(state.clone()).await;
+    let token = token_for(owner_id, None);
+
+    let node_id = format!("grpc-node-{}", &owner_id.simple().to_string()[..8]);
+    let registration = serde_json::json!({
+        "node_id": node_id,
+        "region": "us-east",
+        "node_type": "compute",
+        "capabilities": {
+            "bandwidth_mbps": 500.0,
+            "cpu_cores": 8,
+            "memory_gb": 16.0,
+            "gpu_available": false
+        },
+        "observability_port": null
+    });
+    let registered = client
+        .register(authed(
+            proto::RegisterRequest {
+                registration_json: registration.to_string(),
+            },
+            &token,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let node: serde_json::Value = serde_json::from_str(&registered.node_json).unwrap();
+    assert_eq!(node["node_id"], node_id.as_str());
+
+    let mut assignments = client
+        .watch_assignments(authed(
+            proto::WatchAssignmentsRequest {
+                node_id: node_id.clone(),
+            },
+            &token,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let task = state
+        .submit_task(
+            api_server::models::TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "grpc"}),
+                requirements: api_server::models::TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 300,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: Default::default(),
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            owner_id,
+        )
+        .await
+        .unwrap();
+
+    let pushed = tokio::time::timeout(std::time::Duration::from_secs(5), assignments.message())
+        .await
+        .expect("assignment should be pushed")
+        .unwrap()
+        .unwrap();
+    assert_eq!(pushed.task_id, task.task_id);
+    assert_eq!(pushed.task_type, "computation");
+
+    let heartbeats = tokio_stream::iter(vec![proto::HeartbeatRequest {
+        node_id: node_id.clone(),
+        heartbeat_json: String::new(),
+    }]);
+    let mut replies = client
+        .heartbeat(authed(heartbeats, &token))
+        .await
+        .unwrap()
+        .into_inner();
+    let reply = replies.message().await.unwrap().unwrap();
+    let ack: serde_json::Value = serde_json::from_str(&reply.response_json).unwrap();
+    assert_eq!(reply.node_id, node_id);
+    assert!(ack["assigned_task_ids"]
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!(task.task_id)));
+    assert!(ack.get("control_signature").is_some());
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "http3")]
+
+use ambient_node::h3_client::Http3Client;
+use api_server::{create_router, http3, state::AppState};
+use bytes::Bytes;
+use std::sync::Arc;
+
+/// Serve the real router over HTTP/3 with a self-signed certificate and fetch
+/// `/api/v1/health` through the node's QUIC client.
+#[tokio::test]
+async fn test_health_over_http3() {
+    let dir = std::env::temp_dir().join(format!("http3-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    std::fs::write(&cert_path, cert.cert.pem()).unwrap();
+    std::fs::write(&key_path, cert.key_pair.serialize_pem()).unwrap();
+
+    // Bind an ephemeral UDP port, then release it for the listener.
+    let addr = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let router = create_router(Arc::new(AppState::new(None)));
+    tokio::spawn(http3::serve(
+        router,
+        http3::Http3Config {
+            addr,
+            cert_path: cert_path.clone(),
+            key_path,
+        },
+    ));
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let mut client = Http3Client::connect(addr, "localhost", Some(&cert_path))
+        .await
+        .unwrap();
+    let request =
+        axum::http::Request::get(format!("https://localhost:{}/api/v1/health", addr.port()))
+            .body(Bytes::new())
+            .unwrap();
+    let response = client.send(request).await.unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert!(body.get("status").is_some());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -1,5 +1,6 @@
 use api_server::models::*;
 use api_server::state::AppState;
+use api_server::telemetry::TelemetryQuery;
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -21,6 +22,14 @@ fn test_node_validation_empty_id() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     assert!(node_reg.validate().is_err());
@@ -40,6 +49,14 @@ fn test_node_validation_invalid_type() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     assert!(node_reg.validate().is_err());
@@ -59,6 +76,14 @@ fn test_node_validation_invalid_bandwidth() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     assert!(node_reg.validate().is_err());
@@ -78,6 +103,14 @@ fn test_node_validation_bandwidth_below_whitelist_min() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     assert!(node_reg.validate().is_err());
@@ -97,6 +130,14 @@ fn test_node_validation_invalid_cpu_cores() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     assert!(node_reg.validate().is_err());
@@ -116,6 +157,14 @@ fn test_node_validation_invalid_memory() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     assert!(node_reg.validate().is_err());
@@ -135,15 +184,23 @@ fn test_node_validation_valid() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     assert!(node_reg.validate().is_ok());
 }
 
-/// Test node validation - "any" node type is valid for universal nodes
+/// Test node validation - "universal" and its older spelling "any" are valid
 #[test]
 fn test_node_validation_any_type() {
-    let node_reg = NodeRegistration {
+    let mut node_reg = NodeRegistration {
         node_id: "universal-node".to_string(),
         region: "us-west".to_string(),
         node_type: "any".to_string(),
@@ -154,9 +211,20 @@ fn test_node_validation_any_type() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     assert!(node_reg.validate().is_ok());
+
+    node_reg.node_type = "universal".to_string();
+    assert!(node_reg.validate().is_ok());
 }
 
 /// Test node validation - open internet node type is valid
@@ -173,6 +241,14 @@ fn test_node_validation_open_internet_type() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     assert!(node_reg.validate().is_ok());
@@ -190,12 +266,83 @@ fn test_task_validation_invalid_type() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
+        },
+        priority: 0,
+    };
+
+    assert!(task_sub.validate().is_err());
+}
+
+/// Test task validation - priority above the maximum
+#[test]
+fn test_task_validation_invalid_priority() {
+    let mut task_sub = TaskSubmission {
+        task_type: "computation".to_string(),
+        wasm_module: None,
+        inputs: serde_json::json!({}),
+        requirements: TaskRequirements {
+            min_nodes: 1,
+            max_execution_time_sec: 300,
+            require_gpu: false,
+            require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: MAX_TASK_PRIORITY,
     };
+    assert!(task_sub.validate().is_ok());
 
+    task_sub.priority = MAX_TASK_PRIORITY + 1;
     assert!(task_sub.validate().is_err());
 }
 
+/// Test task validation - retry policy bounds
+#[test]
+fn test_task_validation_retry_policy_bounds() {
+    let mut requirements = TaskRequirements {
+        min_nodes: 1,
+        max_execution_time_sec: 300,
+        require_gpu: false,
+        require_proof: false,
+        scheduling_mode: SchedulingMode::Standard,
+        max_retries: 10,
+        retry_backoff_sec: 3600,
+        egress: vec![],
+        checkpointable: false,
+        node_selector: Default::default(),
+        diversity: Default::default(),
+        preferred_regions: Vec::new(),
+        excluded_regions: Vec::new(),
+        region_strictness: Default::default(),
+    };
+    assert!(requirements.validate().is_ok());
+
+    requirements.max_retries = 11;
+    assert!(requirements.validate().is_err());
+
+    requirements.max_retries = 3;
+    requirements.retry_backoff_sec = 3601;
+    assert!(requirements.validate().is_err());
+}
+
 /// Test task validation - invalid min_nodes (zero)
 #[test]
 fn test_task_validation_invalid_min_nodes() {
@@ -208,7 +355,18 @@ fn test_task_validation_invalid_min_nodes() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     assert!(task_sub.validate().is_err());
@@ -226,7 +384,18 @@ fn test_task_validation_invalid_time() {
             max_execution_time_sec: 0,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     assert!(task_sub.validate().is_err());
@@ -244,7 +413,18 @@ fn test_task_validation_valid() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     assert!(task_sub.validate().is_ok());
@@ -262,7 +442,18 @@ fn test_task_validation_rejects_wasm_for_non_wasm_type() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     assert!(task_sub.validate().is_err());
@@ -280,7 +471,18 @@ fn test_task_validation_enforces_task_registry_runtime_limit() {
             max_execution_time_sec: 1200,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     assert!(task_sub.validate().is_err());
@@ -305,7 +507,18 @@ fn test_task_validation_connect_only_valid() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     assert!(task_sub.validate().is_ok());
@@ -331,7 +544,18 @@ fn test_task_validation_connect_only_rejects_unknown_key() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     assert!(task_sub.validate().is_err());
@@ -356,7 +580,18 @@ fn test_task_validation_connect_only_rejects_invalid_min_nodes() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     assert!(task_sub.validate().is_err());
@@ -396,7 +631,18 @@ async fn test_pending_task_captures_newly_registered_node() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     let creator_id = Uuid::new_v4();
@@ -419,6 +665,14 @@ async fn test_pending_task_captures_newly_registered_node() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     state
@@ -480,6 +734,14 @@ async fn test_completed_task_marks_assignments_disconnected() {
                     gpu_available: false,
                 },
                 observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
             },
             Uuid::new_v4(),
         )
@@ -496,7 +758,18 @@ async fn test_completed_task_marks_assignments_disconnected() {
             max_execution_time_sec: 120,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     let submitted_task = state
@@ -570,11 +843,25 @@ async fn test_task_assignment_uses_universal_node_type_any() {
                     gpu_available: false,
                 },
                 observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
             },
             Uuid::new_v4(),
         )
         .await
         .expect("universal any node should register");
+    let registered = state
+        .get_node(&universal_node_id)
+        .await
+        .expect("registered node should exist");
+    assert_eq!(registered.node_type, "universal");
+    assert_eq!(registered.warnings.len(), 1);
 
     let creator_id = Uuid::new_v4();
     let submitted_task = state
@@ -588,7 +875,18 @@ async fn test_task_assignment_uses_universal_node_type_any() {
                     max_execution_time_sec: 300,
                     require_gpu: false,
                     require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
                 },
+                priority: 0,
             },
             creator_id,
         )
@@ -649,6 +947,14 @@ async fn test_task_assignment_excludes_non_matching_node_types() {
                     gpu_available: false,
                 },
                 observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
             },
             Uuid::new_v4(),
         )
@@ -667,7 +973,18 @@ async fn test_task_assignment_excludes_non_matching_node_types() {
                     max_execution_time_sec: 300,
                     require_gpu: false,
                     require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
                 },
+                priority: 0,
             },
             creator_id,
         )
@@ -714,6 +1031,14 @@ async fn test_state_register_node() {
             gpu_available: false,
         },
         observability_port: None,
+        benchmark_ops_per_wh: None,
+        secrets_public_key: None,
+        slots: None,
+        signing_public_key: None,
+        labels: Default::default(),
+        asn: None,
+        allowed_task_types: Vec::new(),
+        blocked_task_types: Vec::new(),
     };
 
     let node_info = state.register_node(node_reg).await.unwrap();
@@ -770,6 +1095,14 @@ async fn test_node_deletion_disconnects_tasks_and_reassigns() {
                     gpu_available: false,
                 },
                 observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
             },
             owner_id,
         )
@@ -791,6 +1124,14 @@ async fn test_node_deletion_disconnects_tasks_and_reassigns() {
                     gpu_available: false,
                 },
                 observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
             },
             owner_id,
         )
@@ -808,7 +1149,18 @@ async fn test_node_deletion_disconnects_tasks_and_reassigns() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     let submitted_task = state
@@ -921,6 +1273,14 @@ async fn test_node_deletion_reverts_task_to_pending_without_fallback() {
                     gpu_available: false,
                 },
                 observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
             },
             owner_id,
         )
@@ -938,7 +1298,18 @@ async fn test_node_deletion_reverts_task_to_pending_without_fallback() {
             max_execution_time_sec: 300,
             require_gpu: false,
             require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
         },
+        priority: 0,
     };
 
     let submitted_task = state
@@ -1021,6 +1392,14 @@ async fn test_node_rejection_disconnects_task_assignments() {
                     gpu_available: false,
                 },
                 observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
             },
             owner_id,
         )
@@ -1039,7 +1418,18 @@ async fn test_node_rejection_disconnects_task_assignments() {
                     max_execution_time_sec: 300,
                     require_gpu: false,
                     require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
                 },
+                priority: 0,
             },
             creator_id,
         )
@@ -1152,6 +1542,14 @@ async fn test_heartbeat_triggers_pending_task_assignment() {
                     gpu_available: false,
                 },
                 observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
             },
             owner_id,
         )
@@ -1170,7 +1568,18 @@ async fn test_heartbeat_triggers_pending_task_assignment() {
                     max_execution_time_sec: 300,
                     require_gpu: false,
                     require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
                 },
+                priority: 0,
             },
             creator_id,
         )
@@ -1203,7 +1612,18 @@ async fn test_heartbeat_triggers_pending_task_assignment() {
                     max_execution_time_sec: 300,
                     require_gpu: false,
                     require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
                 },
+                priority: 0,
             },
             creator_id,
         )
@@ -1216,7 +1636,7 @@ async fn test_heartbeat_triggers_pending_task_assignment() {
     // 5. Send a heartbeat — this must call assign_pending_tasks_for_node and connect
     //    the node to task2 if it wasn't already assigned.
     let result = state
-        .update_node_heartbeat(&node_id, owner_id)
+        .update_node_heartbeat(&node_id, owner_id, &NodeHeartbeatRequest::default())
         .await
         .expect("heartbeat should succeed")
         .expect("heartbeat should return Some for a known node");
@@ -1247,3 +1667,6214 @@ async fn test_heartbeat_triggers_pending_task_assignment() {
         .await
         .expect("cleanup tables after integration test");
 }
+
+#[tokio::test]
+async fn test_heartbeat_telemetry_rolls_up_into_series() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_heartbeat_telemetry_rolls_up_into_series — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let owner_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(owner_id)
+        .bind(format!("telemetry-owner-{owner_id}"))
+        .execute(&pool)
+        .await
+        .expect("create node owner");
+    let node_id = format!("telemetry-node-{}", Uuid::new_v4());
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "us-west".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            owner_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    for (seq, cpu) in [(1, 20.0), (2, 60.0)] {
+        let heartbeat = NodeHeartbeatRequest {
+            seq: Some(seq),
+            state: serde_json::json!({"cpu_usage_percent": cpu, "avg_latency_ms": 8.0})
+                .as_object()
+                .cloned(),
+            ..Default::default()
+        };
+        state
+            .update_node_heartbeat(&node_id, owner_id, &heartbeat)
+            .await
+            .expect("heartbeat should succeed")
+            .expect("heartbeat should return Some for a known node");
+    }
+
+    // Move the samples into a closed bucket so the rollup job picks them up.
+    sqlx::query(
+        "UPDATE node_heartbeat_history SET recorded_at = recorded_at - INTERVAL '2 days' WHERE node_id = $1",
+    )
+    .bind(&node_id)
+    .execute(&pool)
+    .await
+    .expect("backdate heartbeat samples");
+
+    let written = state
+        .run_telemetry_rollup()
+        .await
+        .expect("telemetry rollup should succeed");
+    assert!(
+        written >= 3,
+        "expected a bucket per resolution, got {written}"
+    );
+
+    let now = chrono::Utc::now();
+    for resolution in ["5m", "1h", "raw"] {
+        let window = TelemetryQuery {
+            from: Some(now - chrono::Duration::hours(60)),
+            to: Some(now - chrono::Duration::hours(36)),
+            resolution: Some(resolution.to_string()),
+        }
+        .resolve(now)
+        .expect("telemetry window should be valid");
+        let series = state
+            .get_node_telemetry(&node_id, owner_id, window)
+            .await
+            .expect("telemetry series should load");
+
+        let samples: i64 = series.points.iter().map(|point| point.samples).sum();
+        assert_eq!(samples, 2, "{resolution}: {:?}", series.points);
+        let peak = series
+            .points
+            .iter()
+            .filter_map(|point| point.cpu_usage_max)
+            .fold(0.0, f64::max);
+        assert_eq!(peak, 60.0, "{resolution}");
+    }
+
+    assert!(state
+        .get_node_telemetry(
+            &node_id,
+            Uuid::new_v4(),
+            TelemetryQuery::default().resolve(now).unwrap()
+        )
+        .await
+        .is_err());
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_require_diversity_spreads_task_across_asns() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_require_diversity_spreads_task_across_asns — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let owner_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(owner_id)
+        .bind(format!("diversity-owner-{owner_id}"))
+        .execute(&pool)
+        .await
+        .expect("create node and task owner");
+
+    let register = |node_id: &'static str, asn: u32| {
+        let state = &state;
+        async move {
+            state
+                .register_node(
+                    NodeRegistration {
+                        node_id: node_id.to_string(),
+                        region: "us-east".to_string(),
+                        node_type: "compute".to_string(),
+                        capabilities: NodeCapabilities {
+                            bandwidth_mbps: 500.0,
+                            cpu_cores: 8,
+                            memory_gb: 16.0,
+                            gpu_available: false,
+                        },
+                        observability_port: None,
+                        benchmark_ops_per_wh: None,
+                        secrets_public_key: None,
+                        slots: None,
+                        signing_public_key: None,
+                        labels: Default::default(),
+                        asn: Some(asn),
+                        allowed_task_types: Vec::new(),
+                        blocked_task_types: Vec::new(),
+                    },
+                    owner_id,
+                )
+                .await
+                .expect("node registration should succeed")
+        }
+    };
+
+    let node = register("diverse-a1", 7922).await;
+    assert_eq!(node.asn, Some(7922));
+    assert_eq!(
+        node.asn_source,
+        Some(api_server::geoip::AsnSource::SelfReported)
+    );
+    register("diverse-a2", 7922).await;
+    register("diverse-b1", 3320).await;
+
+    let task = TaskSubmission {
+        task_type: "computation".to_string(),
+        wasm_module: None,
+        inputs: serde_json::json!({"job": "diversity"}),
+        requirements: TaskRequirements {
+            min_nodes: 3,
+            max_execution_time_sec: 300,
+            require_gpu: false,
+            require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries: 0,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: DiversityMode::Require,
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
+        },
+        priority: 0,
+    };
+    let submitted = state
+        .submit_task(task, owner_id)
+        .await
+        .expect("task submission should succeed");
+    let mut assigned = submitted.assigned_nodes.clone();
+    assigned.sort();
+    assert_eq!(assigned, vec!["diverse-a1", "diverse-b1"]);
+    assert_eq!(submitted.status, TaskStatus::Pending);
+
+    // Another node in an ASN the task already uses is not attached.
+    register("diverse-a3", 7922).await;
+    let task = state.get_task(&submitted.task_id, owner_id).await.unwrap();
+    assert!(!task.assigned_nodes.contains(&"diverse-a3".to_string()));
+    assert_eq!(task.diversity, DiversityMode::Require);
+
+    register("diverse-c1", 16509).await;
+    let task = state.get_task(&submitted.task_id, owner_id).await.unwrap();
+    assert!(task.assigned_nodes.contains(&"diverse-c1".to_string()));
+    assert_eq!(task.status, TaskStatus::Running);
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_region_placement_prefers_requires_and_excludes_regions() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping test_region_placement_prefers_requires_and_excludes_regions — no TEST_DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let owner_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(owner_id)
+        .bind(format!("region-owner-{owner_id}"))
+        .execute(&pool)
+        .await
+        .expect("create node and task owner");
+
+    let register = |node_id: &'static str, region: &'static str| {
+        let state = &state;
+        async move {
+            state
+                .register_node(
+                    NodeRegistration {
+                        node_id: node_id.to_string(),
+                        region: region.to_string(),
+                        node_type: "compute".to_string(),
+                        capabilities: NodeCapabilities {
+                            bandwidth_mbps: 500.0,
+                            cpu_cores: 8,
+                            memory_gb: 16.0,
+                            gpu_available: false,
+                        },
+                        observability_port: None,
+                        benchmark_ops_per_wh: None,
+                        secrets_public_key: None,
+                        slots: None,
+                        signing_public_key: None,
+                        labels: Default::default(),
+                        asn: None,
+                        allowed_task_types: Vec::new(),
+                        blocked_task_types: Vec::new(),
+                    },
+                    owner_id,
+                )
+                .await
+                .expect("node registration should succeed")
+        }
+    };
+    let submit = |preferred: &[&str], excluded: &[&str], strictness: RegionStrictness| {
+        let state = &state;
+        let task = TaskSubmission {
+            task_type: "computation".to_string(),
+            wasm_module: None,
+            inputs: serde_json::json!({"job": "region"}),
+            requirements: TaskRequirements {
+                min_nodes: 1,
+                max_execution_time_sec: 300,
+                require_gpu: false,
+                require_proof: false,
+                scheduling_mode: SchedulingMode::Standard,
+                max_retries: 0,
+                retry_backoff_sec: 0,
+                egress: vec![],
+                checkpointable: false,
+                node_selector: Default::default(),
+                diversity: Default::default(),
+                preferred_regions: preferred.iter().map(|r| r.to_string()).collect(),
+                excluded_regions: excluded.iter().map(|r| r.to_string()).collect(),
+                region_strictness: strictness,
+            },
+            priority: 0,
+        };
+        async move {
+            state
+                .submit_task(task, owner_id)
+                .await
+                .expect("task submission should succeed")
+        }
+    };
+
+    register("region-us-1", "us-east").await;
+    register("region-us-2", "us-east").await;
+    register("region-eu-1", "eu-west").await;
+
+    // A preferred region wins over otherwise better-ranked nodes.
+    let preferred = submit(&["eu-west"], &[], RegionStrictness::Prefer).await;
+    assert_eq!(preferred.assigned_nodes, vec!["region-eu-1"]);
+    assert_eq!(preferred.preferred_regions, vec!["eu-west"]);
+
+    // With no node in the preferred region, `prefer` falls back.
+    let fallback = submit(&["ap-south"], &[], RegionStrictness::Prefer).await;
+    assert_eq!(fallback.status, TaskStatus::Running);
+
+    // Excluded regions are never used.
+    let excluded = submit(&[], &["us-east"], RegionStrictness::Prefer).await;
+    assert_eq!(excluded.assigned_nodes, vec!["region-eu-1"]);
+
+    // `require` waits for a node in the region, which the node-side
+    // scheduler attaches when it registers.
+    let required = submit(&["ap-south"], &[], RegionStrictness::Require).await;
+    assert!(required.assigned_nodes.is_empty());
+    assert_eq!(required.status, TaskStatus::Pending);
+    register("region-ap-1", "ap-south").await;
+    let required = state.get_task(&required.task_id, owner_id).await.unwrap();
+    assert_eq!(required.assigned_nodes, vec!["region-ap-1"]);
+    assert_eq!(required.region_strictness, RegionStrictness::Require);
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_task_type_opt_outs_are_honored_by_assignment() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping test_task_type_opt_outs_are_honored_by_assignment — no TEST_DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let owner_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(owner_id)
+        .bind(format!("opt-out-owner-{owner_id}"))
+        .execute(&pool)
+        .await
+        .expect("create node and task owner");
+
+    let register = |node_id: &'static str, allowed: &[&str], blocked: &[&str]| {
+        let state = &state;
+        let registration = NodeRegistration {
+            node_id: node_id.to_string(),
+            region: "us-east".to_string(),
+            node_type: "compute".to_string(),
+            capabilities: NodeCapabilities {
+                bandwidth_mbps: 500.0,
+                cpu_cores: 8,
+                memory_gb: 16.0,
+                gpu_available: false,
+            },
+            observability_port: None,
+            benchmark_ops_per_wh: None,
+            secrets_public_key: None,
+            slots: None,
+            signing_public_key: None,
+            labels: Default::default(),
+            asn: None,
+            allowed_task_types: allowed.iter().map(|t| t.to_string()).collect(),
+            blocked_task_types: blocked.iter().map(|t| t.to_string()).collect(),
+        };
+        async move {
+            state
+                .register_node(registration, owner_id)
+                .await
+                .expect("node registration should succeed")
+        }
+    };
+
+    let blocking = register("opt-out-blocked", &[], &["computation"]).await;
+    assert_eq!(blocking.blocked_task_types, vec!["computation"]);
+    register("opt-out-allow-list", &["wasm_execution"], &[]).await;
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "opt-out"}),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 300,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: vec![],
+                    excluded_regions: vec![],
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            owner_id,
+        )
+        .await
+        .expect("task submission should succeed");
+    // Neither node accepts `computation`.
+    assert!(task.assigned_nodes.is_empty());
+    assert_eq!(task.status, TaskStatus::Pending);
+
+    // Lifting the block through the update endpoint re-matches pending work.
+    let updated = state
+        .update_node(
+            "opt-out-blocked",
+            owner_id,
+            &NodeUpdateRequest {
+                blocked_task_types: Some(vec![]),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+        .expect("owner may update the node");
+    assert!(updated.blocked_task_types.is_empty());
+    let task = state.get_task(&task.task_id, owner_id).await.unwrap();
+    assert_eq!(task.assigned_nodes, vec!["opt-out-blocked"]);
+
+    let allow_list = state.get_node("opt-out-allow-list").await.unwrap();
+    assert_eq!(allow_list.allowed_task_types, vec!["wasm_execution"]);
+
+    // Concurrent partial updates of different fields both survive.
+    let labels = NodeUpdateRequest {
+        labels: Some([("rack".to_string(), "r7".to_string())].into()),
+        ..Default::default()
+    };
+    let cores = NodeUpdateRequest {
+        cpu_cores: Some(16),
+        ..Default::default()
+    };
+    let (first, second) = tokio::join!(
+        state.update_node("opt-out-allow-list", owner_id, &labels),
+        state.update_node("opt-out-allow-list", owner_id, &cores),
+    );
+    assert!(first.unwrap().is_some() && second.unwrap().is_some());
+    let allow_list = state.get_node("opt-out-allow-list").await.unwrap();
+    assert_eq!(
+        allow_list.labels.get("rack").map(String::as_str),
+        Some("r7")
+    );
+    assert_eq!(allow_list.capabilities.cpu_cores, 16);
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_node_attestation_gates_scheduling_until_nonce_is_signed() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping test_node_attestation_gates_scheduling_until_nonce_is_signed — no TEST_DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let enrollment_key = ambient_node::NodeSigningKey::generate().unwrap();
+    let state = AppState::new(Some(pool.clone())).with_attestation_policy(
+        api_server::attestation::AttestationPolicy {
+            enrollment_keys: vec![enrollment_key.public_key_b64()],
+            ..Default::default()
+        },
+    );
+    let owner_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(owner_id)
+        .bind(format!("attestation-owner-{owner_id}"))
+        .execute(&pool)
+        .await
+        .expect("create node and task owner");
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "attested"}),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 300,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            owner_id,
+        )
+        .await
+        .expect("task submission should succeed");
+
+    let node = state
+        .register_node(
+            NodeRegistration {
+                node_id: "attested-node".to_string(),
+                region: "us-east".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            owner_id,
+        )
+        .await
+        .expect("node registration should succeed");
+    assert_eq!(node.status, "pending_attestation");
+    let challenge = node
+        .attestation_challenge
+        .expect("registration issues a nonce");
+
+    // A pending node is not scheduled.
+    let pending = state.get_task(&task.task_id, owner_id).await.unwrap();
+    assert!(pending.assigned_nodes.is_empty());
+
+    let answer = |key: &ambient_node::NodeSigningKey, nonce: &str| {
+        let signed = ambient_node::AttestationResponse::sign("attested-node", nonce, key);
+        api_server::attestation::NodeAttestationRequest {
+            nonce: signed.nonce,
+            enrollment_public_key: signed.enrollment_public_key,
+            signature: signed.signature,
+            quote: None,
+        }
+    };
+
+    let untrusted = ambient_node::NodeSigningKey::generate().unwrap();
+    let err = state
+        .attest_node(
+            "attested-node",
+            owner_id,
+            answer(&untrusted, &challenge.nonce),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code, axum::http::StatusCode::FORBIDDEN);
+
+    let err = state
+        .attest_node(
+            "attested-node",
+            owner_id,
+            answer(&enrollment_key, "made-up"),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code, axum::http::StatusCode::BAD_REQUEST);
+
+    assert!(state
+        .attest_node(
+            "attested-node",
+            Uuid::new_v4(),
+            answer(&enrollment_key, &challenge.nonce)
+        )
+        .await
+        .unwrap()
+        .is_none());
+
+    let attested = state
+        .attest_node(
+            "attested-node",
+            owner_id,
+            answer(&enrollment_key, &challenge.nonce),
+        )
+        .await
+        .unwrap()
+        .expect("owner can attest the node");
+    assert_eq!(attested.status, "online");
+    assert!(attested.attested_at.is_some());
+
+    let running = state.get_task(&task.task_id, owner_id).await.unwrap();
+    assert_eq!(running.assigned_nodes, vec!["attested-node"]);
+
+    // The nonce is single-use.
+    assert!(state
+        .attest_node(
+            "attested-node",
+            owner_id,
+            answer(&enrollment_key, &challenge.nonce),
+        )
+        .await
+        .is_err());
+    let err = state
+        .renew_attestation_challenge("attested-node", owner_id)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code, axum::http::StatusCode::CONFLICT);
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_destination_policy_bundle_is_signed_and_versioned() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping test_destination_policy_bundle_is_signed_and_versioned — no TEST_DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE destination_policies, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let offline_policy = ambient_node::OfflinePolicy {
+        refresh_after_secs: 60,
+        max_staleness_secs: 600,
+    };
+    let state = AppState::new(Some(pool.clone())).with_offline_policy(offline_policy);
+    let admin_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(admin_id)
+        .bind(format!("policy-admin-{admin_id}"))
+        .execute(&pool)
+        .await
+        .expect("create policy admin");
+
+    let mut cache = ambient_node::PolicyBundleCache::new(state.control_public_key());
+    let empty_version = cache
+        .install(state.signed_destination_policy_bundle().await.unwrap())
+        .expect("bundle verifies against the control key");
+
+    let policy = state
+        .upsert_destination_policy(
+            "policy_web_basic_v1",
+            &["*.example.com".to_string()],
+            admin_id,
+        )
+        .await
+        .unwrap();
+    assert_eq!(policy.bundle_version as u64, empty_version + 1);
+
+    let version = cache
+        .install(state.signed_destination_policy_bundle().await.unwrap())
+        .unwrap();
+    assert_eq!(version, empty_version + 1);
+    assert_eq!(cache.bundle().unwrap().offline_policy, offline_policy);
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    assert_eq!(
+        cache
+            .allowed_destinations("policy_web_basic_v1", now_ms)
+            .unwrap(),
+        ["*.example.com"]
+    );
+
+    // Deleting bumps the version, so the node drops the policy too.
+    assert!(state
+        .delete_destination_policy("policy_web_basic_v1")
+        .await
+        .unwrap());
+    assert!(!state
+        .delete_destination_policy("policy_web_basic_v1")
+        .await
+        .unwrap());
+    assert_eq!(
+        cache
+            .install(state.signed_destination_policy_bundle().await.unwrap())
+            .unwrap(),
+        empty_version + 2
+    );
+    assert!(cache
+        .allowed_destinations("policy_web_basic_v1", now_ms)
+        .is_err());
+    assert!(state.list_destination_policies().await.unwrap().is_empty());
+
+    sqlx::query("TRUNCATE TABLE destination_policies, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_transparency_snapshot_is_signed_and_kept_in_history() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping test_transparency_snapshot_is_signed_and_kept_in_history — no TEST_DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query(
+        "TRUNCATE TABLE task_assignments, tasks, nodes, users, transparency_snapshots CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let owner_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(owner_id)
+        .bind(format!("transparency-owner-{owner_id}"))
+        .execute(&pool)
+        .await
+        .expect("create node owner");
+    let node_id = format!("transparency-node-{}", Uuid::new_v4());
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "eu-west".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            owner_id,
+        )
+        .await
+        .expect("node registration should succeed");
+    state
+        .update_node_heartbeat(&node_id, owner_id, &NodeHeartbeatRequest::default())
+        .await
+        .expect("heartbeat should succeed");
+
+    let published = state
+        .publish_transparency_snapshot()
+        .await
+        .expect("snapshot should publish");
+    let (document, signature) =
+        ambient_node::verify_signed_record(&published.public_key, published.document.clone())
+            .expect("snapshot signature should verify");
+    assert_eq!(
+        signature.node_id,
+        api_server::transparency::TRANSPARENCY_AUDIENCE
+    );
+    let snapshot: api_server::transparency::TransparencySnapshot =
+        serde_json::from_value(document).expect("signed document should be a snapshot");
+    assert_eq!(snapshot.nodes.total, 1);
+    assert_eq!(snapshot.nodes.by_kind.get("compute"), Some(&1));
+    assert_eq!(snapshot.uptime.at_least_99, 1);
+
+    let history = state
+        .list_transparency_snapshots(&Default::default())
+        .await
+        .expect("history should load");
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].snapshot_id, published.snapshot_id);
+    assert_eq!(history[0].document, published.document);
+
+    sqlx::query(
+        "TRUNCATE TABLE task_assignments, tasks, nodes, users, transparency_snapshots CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_cluster_stats_history_downsamples_snapshots() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping test_cluster_stats_history_downsamples_snapshots — no TEST_DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query(
+        "TRUNCATE TABLE task_assignments, tasks, nodes, users, cluster_stats_history CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let owner_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(owner_id)
+        .bind(format!("history-owner-{owner_id}"))
+        .execute(&pool)
+        .await
+        .expect("create node owner");
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: format!("history-node-{}", Uuid::new_v4()),
+                region: "eu-west".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            owner_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    assert!(state
+        .snapshot_cluster_stats(150)
+        .await
+        .expect("snapshot should be recorded"));
+    assert!(
+        !state
+            .snapshot_cluster_stats(150)
+            .await
+            .expect("second snapshot should be skipped"),
+        "a snapshot within the gap is not repeated"
+    );
+
+    // Backdate the snapshot and add older ones in the same hour.
+    sqlx::query("UPDATE cluster_stats_history SET recorded_at = '2026-03-01T10:40:00Z'")
+        .execute(&pool)
+        .await
+        .expect("backdate snapshot");
+    for (at, healthy, completed, failed) in [
+        ("2026-03-01T10:05:00Z", 0_i64, 10_i64, 1_i64),
+        ("2026-03-01T10:20:00Z", 1, 14, 3),
+        ("2026-03-01T09:50:00Z", 0, 8, 1),
+    ] {
+        sqlx::query(
+            r#"
+            INSERT INTO cluster_stats_history (
+                recorded_at, total_nodes, healthy_nodes, total_tasks, completed_tasks,
+                failed_tasks, avg_health_score, total_compute_capacity
+            )
+            VALUES ($1::TIMESTAMPTZ, 1, $2, $3 + $4, $3, $4, 50.0, 128.0)
+            "#,
+        )
+        .bind(at)
+        .bind(healthy)
+        .bind(completed)
+        .bind(failed)
+        .execute(&pool)
+        .await
+        .expect("insert snapshot");
+    }
+
+    let at = |rfc3339: &str| -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .into()
+    };
+    let query = |resolution: &str| api_server::cluster_history::ClusterStatsHistoryQuery {
+        from: Some(at("2026-03-01T10:00:00Z")),
+        to: Some(at("2026-03-01T11:00:00Z")),
+        resolution: Some(resolution.to_string()),
+    };
+
+    let raw = state
+        .get_cluster_stats_history(query("raw").resolve(at("2026-03-02T00:00:00Z")).unwrap())
+        .await
+        .expect("raw history should load");
+    assert_eq!(raw.points.len(), 3, "the 09:50 snapshot is out of range");
+    assert!(raw.points.iter().all(|point| point.samples == 1));
+    assert_eq!(raw.points[0].timestamp, at("2026-03-01T10:05:00Z"));
+    assert_eq!(raw.points[2].total_nodes_max, 1);
+    let trend = raw.trend.expect("trend over three points");
+    assert_eq!(trend.completed_tasks, -10, "the live snapshot has no tasks");
+
+    let hourly = state
+        .get_cluster_stats_history(query("1h").resolve(at("2026-03-02T00:00:00Z")).unwrap())
+        .await
+        .expect("hourly history should load");
+    assert_eq!(hourly.points.len(), 1);
+    let bucket = &hourly.points[0];
+    assert_eq!(bucket.timestamp, at("2026-03-01T10:00:00Z"));
+    assert_eq!(bucket.samples, 3);
+    assert_eq!(bucket.healthy_nodes_min, 0);
+    assert_eq!(
+        bucket.completed_tasks, 0,
+        "counters report the latest snapshot"
+    );
+    assert_eq!(bucket.total_nodes_max, 1);
+
+    sqlx::query(
+        "TRUNCATE TABLE task_assignments, tasks, nodes, users, cluster_stats_history CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_throttle_overrides_apply_to_users_and_api_keys() {
+    use tower::ServiceExt;
+
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping test_throttle_overrides_apply_to_users_and_api_keys — no TEST_DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE throttle_overrides, api_keys, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    std::env::set_var(
+        "JWT_SECRET",
+        "throttle-test-secret-that-is-long-enough-0123456789",
+    );
+    let auth_config = api_server::auth::AuthConfig::from_env().unwrap();
+    let state = std::sync::Arc::new(
+        AppState::new(Some(pool.clone())).with_auth_config(auth_config.clone()),
+    );
+
+    let admin_id = Uuid::new_v4();
+    let user_id = Uuid::new_v4();
+    for (id, name) in [(admin_id, "throttle-admin"), (user_id, "throttle-user")] {
+        sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+            .bind(id)
+            .bind(format!("{name}-{id}"))
+            .execute(&pool)
+            .await
+            .expect("create user");
+    }
+    let api_key = api_server::auth::generate_api_key();
+    let key_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO api_keys (user_id, key_hash, key_prefix) VALUES ($1, $2, 'test') RETURNING key_id",
+    )
+    .bind(user_id)
+    .bind(api_server::auth::hash_api_key(&api_key))
+    .fetch_one(&pool)
+    .await
+    .expect("create api key");
+
+    let request = |subject_type, subject_id: Uuid, burst| ThrottleOverrideRequest {
+        subject_type,
+        subject_id: subject_id.to_string(),
+        multiplier: 0.1,
+        burst: Some(burst),
+        reason: Some("integration test".to_string()),
+        expires_at: None,
+    };
+
+    let missing = state
+        .upsert_throttle_override(
+            &request(ThrottleSubjectType::User, Uuid::new_v4(), 1),
+            admin_id,
+        )
+        .await;
+    assert_eq!(
+        missing.unwrap_err().status_code,
+        axum::http::StatusCode::NOT_FOUND
+    );
+
+    state
+        .upsert_throttle_override(&request(ThrottleSubjectType::User, user_id, 5), admin_id)
+        .await
+        .expect("set user override");
+    // Setting it again replaces the first one.
+    let user_override = state
+        .upsert_throttle_override(&request(ThrottleSubjectType::User, user_id, 1), admin_id)
+        .await
+        .expect("replace user override");
+    assert_eq!(user_override.burst, Some(1));
+    state
+        .upsert_throttle_override(&request(ThrottleSubjectType::ApiKey, key_id, 2), admin_id)
+        .await
+        .expect("set api key override");
+
+    let listed = state.list_throttle_overrides().await.unwrap();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(state.reload_throttle_overrides().await.unwrap(), 2);
+
+    // The overridden credentials get their own, smaller buckets.
+    let router = api_server::create_router(state.clone());
+    let token = auth_config
+        .generate_token(
+            user_id.to_string(),
+            "throttle-user".to_string(),
+            "user".to_string(),
+        )
+        .unwrap();
+    let call = |header: (&'static str, String)| {
+        let router = router.clone();
+        async move {
+            router
+                .oneshot(
+                    axum::http::Request::get("/api/v1/health")
+                        .header(header.0, header.1)
+                        .body(axum::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap()
+                .status()
+        }
+    };
+    let bearer = ("authorization", format!("Bearer {token}"));
+    assert_eq!(call(bearer.clone()).await, axum::http::StatusCode::OK);
+    assert_eq!(
+        call(bearer.clone()).await,
+        axum::http::StatusCode::TOO_MANY_REQUESTS
+    );
+    let key_header = ("x-api-key", api_key.clone());
+    for _ in 0..2 {
+        assert_eq!(call(key_header.clone()).await, axum::http::StatusCode::OK);
+    }
+    assert_eq!(
+        call(key_header.clone()).await,
+        axum::http::StatusCode::TOO_MANY_REQUESTS
+    );
+
+    // Deleting the user's override drops it from the limiter.
+    assert!(state
+        .delete_throttle_override(Uuid::parse_str(&user_override.override_id).unwrap())
+        .await
+        .unwrap());
+    assert_eq!(state.reload_throttle_overrides().await.unwrap(), 1);
+
+    sqlx::query("TRUNCATE TABLE throttle_overrides, api_keys, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+    state.reload_throttle_overrides().await.unwrap();
+}
+
+/// Replaying a rotated refresh token revokes its family and the user's other
+/// sessions, and leaves a security audit event.
+#[tokio::test]
+async fn test_refresh_token_reuse_revokes_all_sessions() {
+    use tower::ServiceExt;
+
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_refresh_token_reuse_revokes_all_sessions — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE refresh_tokens, audit_log, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    std::env::set_var(
+        "JWT_SECRET",
+        "refresh-reuse-secret-that-is-long-enough-0123456789",
+    );
+    let auth_config = api_server::auth::AuthConfig::from_env().unwrap();
+    let state =
+        std::sync::Arc::new(AppState::new(Some(pool.clone())).with_auth_config(auth_config));
+
+    let user_id = Uuid::new_v4();
+    let username = format!("refresh-user-{}", &user_id.simple().to_string()[..8]);
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(&username)
+        .bind(api_server::auth::hash_password("Correct-Horse-7").unwrap())
+        .execute(&pool)
+        .await
+        .expect("create user");
+
+    let router = api_server::create_router(state);
+    let post = |path: &'static str, body: serde_json::Value| {
+        let router = router.clone();
+        async move {
+            let response = router
+                .oneshot(
+                    axum::http::Request::post(path)
+                        .header("content-type", "application/json")
+                        .body(axum::body::Body::from(body.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap_or_default();
+            (status, json)
+        }
+    };
+    let login = || {
+        post(
+            "/api/v1/auth/login",
+            serde_json::json!({"username": username, "password": "Correct-Horse-7"}),
+        )
+    };
+    let refresh = |token: serde_json::Value| {
+        post(
+            "/api/v1/auth/refresh",
+            serde_json::json!({ "refresh_token": token }),
+        )
+    };
+
+    // Two devices log in; the first rotates its token once.
+    let (status, first) = login().await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+    let (_, second) = login().await;
+    let (status, rotated) = refresh(first["refresh_token"].clone()).await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+
+    // Replaying the rotated token is detected...
+    let (status, body) = refresh(first["refresh_token"].clone()).await;
+    assert_eq!(status, axum::http::StatusCode::UNAUTHORIZED);
+    assert!(body.to_string().contains("reuse detected"));
+
+    // ...and revokes both the rest of its family and the other device.
+    for token in [&rotated["refresh_token"], &second["refresh_token"]] {
+        let (status, _) = refresh(token.clone()).await;
+        assert_eq!(status, axum::http::StatusCode::UNAUTHORIZED);
+    }
+    let active: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM refresh_tokens WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(active, 0);
+
+    let families: i64 = sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT family_id) FROM refresh_tokens WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(families, 2);
+
+    let (action, metadata): (String, serde_json::Value) =
+        sqlx::query_as("SELECT action, metadata FROM audit_log WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(&pool)
+            .await
+            .expect("reuse should be audited once");
+    assert_eq!(action, "refresh_token_reuse");
+    assert_eq!(metadata["revoked_tokens"], 2);
+
+    sqlx::query("TRUNCATE TABLE refresh_tokens, audit_log, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+/// Failed logins lock the account and client IP with growing lock durations
+/// until they expire or an admin unlocks them.
+#[tokio::test]
+async fn test_login_lockout_backoff_and_admin_unlock() {
+    use api_server::lockout::LockoutPolicy;
+    use api_server::rate_limit::{RateLimitTier, RateLimiter};
+    use axum::http::StatusCode;
+    use tower::ServiceExt;
+
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_login_lockout_backoff_and_admin_unlock — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE login_lockouts, refresh_tokens, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    std::env::set_var(
+        "JWT_SECRET",
+        "lockout-test-secret-that-is-long-enough-0123456789",
+    );
+    let auth_config = api_server::auth::AuthConfig::from_env().unwrap();
+    let policy = LockoutPolicy {
+        user_threshold: Some(2),
+        ip_threshold: Some(3),
+        ..LockoutPolicy::default()
+    };
+    let state = std::sync::Arc::new(
+        AppState::new(Some(pool.clone()))
+            .with_auth_config(auth_config.clone())
+            .with_login_lockout(policy)
+            // Keep the auth rate limit out of the way of the lockout under test.
+            .with_rate_limiter(
+                RateLimiter::new().with_tier_limits(RateLimitTier::Auth, (600, 100)),
+            ),
+    );
+
+    let user_id = Uuid::new_v4();
+    let username = format!("lockout-user-{}", &user_id.simple().to_string()[..8]);
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(&username)
+        .bind(api_server::auth::hash_password("Correct-Horse-7").unwrap())
+        .execute(&pool)
+        .await
+        .expect("create user");
+    let admin_token = auth_config
+        .generate_token(
+            Uuid::new_v4().to_string(),
+            "lockout-admin".to_string(),
+            "admin".to_string(),
+        )
+        .unwrap();
+
+    let router = api_server::create_router(state.clone());
+    let send = |request: axum::http::Request<axum::body::Body>| {
+        let router = router.clone();
+        async move {
+            let response = router.oneshot(request).await.unwrap();
+            let status = response.status();
+            let retry_after = response
+                .headers()
+                .get("retry-after")
+                .map(|value| value.to_str().unwrap().to_string());
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap_or_default();
+            (status, retry_after, body)
+        }
+    };
+    let login = |username: &str, password: &str| {
+        send(
+            axum::http::Request::post("/api/v1/auth/login")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(
+                    serde_json::json!({"username": username, "password": password}).to_string(),
+                ))
+                .unwrap(),
+        )
+    };
+    let unlock = |path: String| {
+        send(
+            axum::http::Request::delete(path)
+                .header("authorization", format!("Bearer {admin_token}"))
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+    };
+
+    // The second failure locks the account for the base duration.
+    let (status, _, _) = login(&username, "wrong").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, retry_after, account_locked) = login(&username, "wrong").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(account_locked["error"], "account_locked");
+    assert_eq!(account_locked["details"]["scope"], "user");
+    assert_eq!(account_locked["details"]["retry_after"], 60);
+    assert_eq!(retry_after.as_deref(), Some("60"));
+
+    // Even the right password is refused while locked.
+    let (status, _, body) = login(&username, "Correct-Horse-7").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["details"]["scope"], "user");
+
+    // Failures for unknown usernames count against the client IP...
+    let (status, _, body) = login("no-such-user", "wrong").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["details"]["scope"], "ip");
+    assert_eq!(state.list_login_lockouts().await.unwrap().len(), 3);
+    let (status, _, _) = unlock("/api/v1/admin/login-lockouts/ip/127.0.0.1".to_string()).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // ...and against the username, which locks with the same answer as an
+    // existing account.
+    let (status, retry_after, body) = login("no-such-user", "wrong").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(retry_after.as_deref(), Some("60"));
+    assert_eq!(body, account_locked);
+    let (status, _, _) = unlock("/api/v1/admin/login-lockouts/ip/127.0.0.1".to_string()).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // Once the lock expires, the next lock lasts twice as long.
+    sqlx::query("UPDATE login_lockouts SET locked_until = NOW() - INTERVAL '1 second'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    login(&username, "wrong").await;
+    let (status, _, body) = login(&username, "wrong").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["details"]["retry_after"], 120);
+
+    // An admin unlock lets the user back in.
+    let (status, _, _) = unlock(format!("/api/v1/admin/login-lockouts/user/{username}")).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _, _) = unlock(format!("/api/v1/admin/login-lockouts/user/{username}")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _, body) = login(&username, "Correct-Horse-7").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    sqlx::query("TRUNCATE TABLE login_lockouts, refresh_tokens, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_connect_session_extension_limits_and_propagation() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_connect_session_extension_limits_and_propagation — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE connect_sessions, task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    std::env::set_var("CONNECT_SESSION_MAX_EXTENSIONS", "3");
+    std::env::set_var("CONNECT_SESSION_MAX_LIFETIME_SECS", "5000");
+
+    let state = AppState::new(Some(pool.clone()));
+    let user_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(user_id)
+        .bind(format!(
+            "extend-user-{}",
+            &user_id.simple().to_string()[..8]
+        ))
+        .execute(&pool)
+        .await
+        .expect("create user");
+
+    let node_id = format!("extend-node-{}", &Uuid::new_v4().simple().to_string()[..8]);
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "us-east".to_string(),
+                node_type: "open_internet".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 4,
+                    memory_gb: 8.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            user_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "connect_only".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({
+                    "session_id": "sess_extend",
+                    "requester_id": user_id.to_string(),
+                    "duration_seconds": 300,
+                    "bandwidth_limit_mbps": 20,
+                    "egress_profile": "allowlist_domains",
+                    "destination_policy_id": "policy_web_basic_v1"
+                }),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 300,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            user_id,
+        )
+        .await
+        .expect("connect_only task submission should succeed");
+    assert_eq!(task.status, TaskStatus::Running);
+    let task_id = Uuid::parse_str(&task.task_id).unwrap();
+
+    let started = state
+        .start_connect_session(
+            ConnectSessionStartRequest {
+                task_id: task.task_id.clone(),
+                tunnel_protocol: None,
+            },
+            user_id,
+        )
+        .await
+        .expect("connect session should start");
+    let original_expiry =
+        chrono::DateTime::parse_from_rfc3339(&started.session.expires_at).unwrap();
+
+    // Unextended sessions leave the completion timer alone.
+    assert!(state
+        .connect_only_completion_deferral(task_id)
+        .await
+        .unwrap()
+        .is_none());
+
+    let extended = state
+        .extend_connect_session("sess_extend", user_id, 600)
+        .await
+        .unwrap()
+        .expect("active session should be extended");
+    let extended_expiry = chrono::DateTime::parse_from_rfc3339(&extended.expires_at).unwrap();
+    // The stored expiry is truncated to microseconds.
+    assert!(
+        ((extended_expiry - original_expiry).num_milliseconds() - 600_000).abs() <= 1,
+        "expiry moves forward by the requested amount"
+    );
+
+    // The node's gateway session and the task's completion timer follow.
+    let gateway_sessions = state
+        .get_node_gateway_sessions(&node_id, user_id)
+        .await
+        .unwrap();
+    assert_eq!(gateway_sessions.len(), 1);
+    assert_eq!(
+        gateway_sessions[0].expires_at_epoch_seconds as i64,
+        extended_expiry.timestamp()
+    );
+    let deferral = state
+        .connect_only_completion_deferral(task_id)
+        .await
+        .unwrap()
+        .expect("extended session defers task completion");
+    assert!(deferral > std::time::Duration::from_secs(800));
+
+    state
+        .extend_connect_session("sess_extend", user_id, 3600)
+        .await
+        .unwrap()
+        .expect("second extension within limits");
+
+    // 300 + 600 + 3600 + 600 seconds would pass the 5000 second lifetime.
+    let err = state
+        .extend_connect_session("sess_extend", user_id, 600)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code, axum::http::StatusCode::FORBIDDEN);
+    assert!(err.details.unwrap().get("max_expires_at").is_some());
+
+    state
+        .extend_connect_session("sess_extend", user_id, 500)
+        .await
+        .unwrap()
+        .expect("extension up to the lifetime cap");
+
+    let err = state
+        .extend_connect_session("sess_extend", user_id, 1)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code, axum::http::StatusCode::FORBIDDEN);
+    assert_eq!(err.details.unwrap()["max_extensions"], 3);
+
+    // Other users and stopped sessions cannot be extended.
+    assert!(state
+        .extend_connect_session("sess_extend", Uuid::new_v4(), 60)
+        .await
+        .unwrap()
+        .is_none());
+    state
+        .stop_connect_session("sess_extend", user_id)
+        .await
+        .unwrap();
+    assert!(state
+        .extend_connect_session("sess_extend", user_id, 60)
+        .await
+        .unwrap()
+        .is_none());
+
+    std::env::remove_var("CONNECT_SESSION_MAX_EXTENSIONS");
+    std::env::remove_var("CONNECT_SESSION_MAX_LIFETIME_SECS");
+
+    sqlx::query("TRUNCATE TABLE connect_sessions, task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_connect_session_usage_accumulates_and_enforces_data_cap() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_connect_session_usage_accumulates_and_enforces_data_cap — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE connect_sessions, task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let user_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(user_id)
+        .bind(format!("usage-user-{}", &user_id.simple().to_string()[..8]))
+        .execute(&pool)
+        .await
+        .expect("create user");
+
+    let node_id = format!("usage-node-{}", &Uuid::new_v4().simple().to_string()[..8]);
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "us-east".to_string(),
+                node_type: "open_internet".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 4,
+                    memory_gb: 8.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            user_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "connect_only".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({
+                    "session_id": "sess_usage",
+                    "requester_id": user_id.to_string(),
+                    "duration_seconds": 300,
+                    "bandwidth_limit_mbps": 20,
+                    "egress_profile": "allowlist_domains",
+                    "destination_policy_id": "policy_web_basic_v1",
+                    "data_cap_mb": 1
+                }),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 300,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            user_id,
+        )
+        .await
+        .expect("connect_only task submission should succeed");
+
+    let started = state
+        .start_connect_session(
+            ConnectSessionStartRequest {
+                task_id: task.task_id.clone(),
+                tunnel_protocol: None,
+            },
+            user_id,
+        )
+        .await
+        .expect("connect session should start");
+    assert_eq!(started.session.usage.data_cap_bytes, Some(1_000_000));
+
+    let report = |seq, bytes_up, bytes_down| ConnectSessionUsageReport {
+        node_id: node_id.clone(),
+        bytes_up,
+        bytes_down,
+        interval_seconds: 60,
+        seq,
+    };
+
+    let session = state
+        .report_connect_session_usage("sess_usage", user_id, &report(1, 400_000, 200_000))
+        .await
+        .unwrap()
+        .expect("node owner can report usage");
+    assert!(matches!(session.status, ConnectSessionStatus::Active));
+    assert_eq!(session.usage.total_bytes, 600_000);
+    assert!(session.usage.last_reported_at.is_some());
+
+    // A retried report is not counted twice.
+    let session = state
+        .report_connect_session_usage("sess_usage", user_id, &report(1, 400_000, 200_000))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(session.usage.total_bytes, 600_000);
+
+    // Only the owner of the relaying node may report.
+    assert!(state
+        .report_connect_session_usage("sess_usage", Uuid::new_v4(), &report(2, 1, 1))
+        .await
+        .unwrap()
+        .is_none());
+    let mut elsewhere = report(2, 1, 1);
+    elsewhere.node_id = "some-other-node".to_string();
+    assert!(state
+        .report_connect_session_usage("sess_usage", user_id, &elsewhere)
+        .await
+        .unwrap()
+        .is_none());
+
+    let session = state
+        .report_connect_session_usage("sess_usage", user_id, &report(2, 300_000, 150_000))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(session.status, ConnectSessionStatus::Ended));
+    assert!(session.usage.data_cap_reached);
+    assert_eq!(session.usage.bytes_up, 700_000);
+    assert_eq!(session.usage.bytes_down, 350_000);
+
+    // The capped session's task completes and the gateway drops the session.
+    let task_id = Uuid::parse_str(&task.task_id).unwrap();
+    let status: String = sqlx::query_scalar("SELECT status FROM tasks WHERE task_id = $1")
+        .bind(task_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "completed");
+    assert!(state
+        .get_node_gateway_sessions(&node_id, user_id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // A final interval after the session ended still counts.
+    state
+        .report_connect_session_usage("sess_usage", user_id, &report(3, 10, 20))
+        .await
+        .unwrap()
+        .unwrap();
+    let requester_view = state
+        .get_connect_session("sess_usage", user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(requester_view.usage.total_bytes, 1_050_030);
+    let intervals: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM connect_session_usage WHERE session_id = 'sess_usage'",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(intervals, 3);
+
+    sqlx::query("TRUNCATE TABLE connect_sessions, task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_notification_outbox_retries_until_delivered() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_notification_outbox_retries_until_delivered — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query(
+        "TRUNCATE TABLE notification_outbox, task_assignments, tasks, nodes, audit_log, users CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables before integration test");
+
+    let (tx, mut mailbox) = tokio::sync::mpsc::unbounded_channel();
+    let notifier = std::sync::Arc::new(FlakyNotifier {
+        failures_left: std::sync::atomic::AtomicU32::new(1),
+        delivered: tx,
+    });
+    let dispatcher = api_server::notifier::NotificationDispatcher::start(
+        notifier.clone(),
+        api_server::notifier::DispatchConfig {
+            max_attempts: 2,
+            retry_delay: std::time::Duration::ZERO,
+            ..Default::default()
+        },
+    );
+    let state = AppState::new(Some(pool.clone())).with_notifications(dispatcher);
+    let user_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO users (user_id, username, password_hash, email) VALUES ($1, $2, 'x', $3)",
+    )
+    .bind(user_id)
+    .bind("outbox-user")
+    .bind("outbox-user@example.com")
+    .execute(&pool)
+    .await
+    .expect("create user");
+
+    for i in 0..2 {
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: format!(
+                        "outbox-node-{i}-{}",
+                        &Uuid::new_v4().simple().to_string()[..8]
+                    ),
+                    region: "us-east".to_string(),
+                    node_type: "compute".to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 500.0,
+                        cpu_cores: 8,
+                        memory_gb: 16.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: None,
+                    signing_public_key: None,
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                user_id,
+            )
+            .await
+            .expect("node registration should succeed");
+    }
+    let assigned_node = |task_id: Uuid| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_scalar::<_, String>(
+                "SELECT node_id FROM task_assignments WHERE task_id = $1 AND disconnected_at IS NULL",
+            )
+            .bind(task_id)
+            .fetch_one(&pool)
+            .await
+            .expect("task has an active assignment")
+        }
+    };
+
+    let submit = |max_retries: u32| TaskSubmission {
+        task_type: "computation".to_string(),
+        wasm_module: None,
+        inputs: serde_json::json!({"job": "outbox"}),
+        requirements: TaskRequirements {
+            min_nodes: 1,
+            max_execution_time_sec: 120,
+            require_gpu: false,
+            require_proof: false,
+            scheduling_mode: SchedulingMode::Standard,
+            max_retries,
+            retry_backoff_sec: 0,
+            egress: vec![],
+            checkpointable: false,
+            node_selector: Default::default(),
+            diversity: Default::default(),
+            preferred_regions: Vec::new(),
+            excluded_regions: Vec::new(),
+            region_strictness: Default::default(),
+        },
+        priority: 0,
+    };
+
+    // A re-queued attempt notifies nobody; the final failure commits its
+    // notification with the task change, but nothing is sent until a drain.
+    let task = state.submit_task(submit(1), user_id).await.unwrap();
+    let task_id = Uuid::parse_str(&task.task_id).unwrap();
+    state
+        .fail_task_attempt(task_id, &assigned_node(task_id).await, "boom")
+        .await
+        .unwrap();
+    let outbox_rows = || async {
+        sqlx::query_as::<_, (String, i32, bool, bool)>(
+            r#"
+            SELECT event, attempts, delivered_at IS NOT NULL, failed_at IS NOT NULL
+            FROM notification_outbox
+            ORDER BY outbox_id
+            "#,
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap()
+    };
+    assert!(outbox_rows().await.is_empty());
+
+    state
+        .fail_task_attempt(task_id, &assigned_node(task_id).await, "boom again")
+        .await
+        .unwrap();
+    assert_eq!(
+        outbox_rows().await,
+        [("task_failed".to_string(), 0, false, false)]
+    );
+    assert!(mailbox.try_recv().is_err());
+
+    // The first attempt fails and is retried on the next drain.
+    assert_eq!(state.drain_notification_outbox().await.unwrap(), 0);
+    assert_eq!(
+        outbox_rows().await,
+        [("task_failed".to_string(), 1, false, false)]
+    );
+    assert_eq!(state.drain_notification_outbox().await.unwrap(), 1);
+    assert_eq!(
+        outbox_rows().await,
+        [("task_failed".to_string(), 2, true, false)]
+    );
+    let delivered = mailbox.try_recv().expect("a notification was delivered");
+    assert_eq!(
+        delivered.event,
+        api_server::notifier::NotificationEvent::TaskFailed
+    );
+    assert_eq!(delivered.email.as_deref(), Some("outbox-user@example.com"));
+    assert!(delivered.delivery_id.is_some());
+
+    // Delivered rows are never sent again.
+    assert_eq!(state.drain_notification_outbox().await.unwrap(), 0);
+    assert!(mailbox.try_recv().is_err());
+
+    // A notification that keeps failing is given up after NOTIFY_MAX_ATTEMPTS.
+    notifier
+        .failures_left
+        .store(u32::MAX, std::sync::atomic::Ordering::SeqCst);
+    let task = state.submit_task(submit(0), user_id).await.unwrap();
+    let task_id = Uuid::parse_str(&task.task_id).unwrap();
+    state
+        .fail_task_attempt(task_id, &assigned_node(task_id).await, "boom")
+        .await
+        .unwrap();
+    state.drain_notification_outbox().await.unwrap();
+    state.drain_notification_outbox().await.unwrap();
+    state.drain_notification_outbox().await.unwrap();
+    assert_eq!(
+        outbox_rows().await[1],
+        ("task_failed".to_string(), 2, false, true)
+    );
+
+    let timeline = state
+        .get_task_timeline(task_id, user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let events: Vec<_> = timeline.events.iter().map(|e| e.event.as_str()).collect();
+    assert_eq!(
+        &events[events.len() - 2..],
+        ["notification_queued", "notification_failed"]
+    );
+
+    sqlx::query(
+        "TRUNCATE TABLE notification_outbox, task_assignments, tasks, nodes, audit_log, users CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables after integration test");
+}
+
+/// Fails the first `failures_left` deliveries, then forwards notifications
+/// to the test.
+struct FlakyNotifier {
+    failures_left: std::sync::atomic::AtomicU32,
+    delivered: tokio::sync::mpsc::UnboundedSender<api_server::notifier::Notification>,
+}
+
+#[async_trait::async_trait]
+impl api_server::notifier::Notifier for FlakyNotifier {
+    async fn notify(
+        &self,
+        notification: &api_server::notifier::Notification,
+    ) -> anyhow::Result<()> {
+        use std::sync::atomic::Ordering;
+        if self
+            .failures_left
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+        {
+            anyhow::bail!("temporary failure");
+        }
+        self.delivered.send(notification.clone())?;
+        Ok(())
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "flaky"
+    }
+}
+
+/// Forwards each notification to the test instead of delivering it.
+struct CapturingNotifier(tokio::sync::mpsc::UnboundedSender<api_server::notifier::Notification>);
+
+#[async_trait::async_trait]
+impl api_server::notifier::Notifier for CapturingNotifier {
+    async fn notify(
+        &self,
+        notification: &api_server::notifier::Notification,
+    ) -> anyhow::Result<()> {
+        self.0.send(notification.clone())?;
+        Ok(())
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "capture"
+    }
+}
+
+#[tokio::test]
+async fn test_password_reset_and_email_verification_tokens() {
+    use api_server::rate_limit::{RateLimitTier, RateLimiter};
+    use axum::http::StatusCode;
+    use tower::ServiceExt;
+
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_password_reset_and_email_verification_tokens — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE account_tokens, refresh_tokens, audit_log, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    std::env::set_var(
+        "JWT_SECRET",
+        "account-token-secret-that-is-long-enough-0123456789",
+    );
+    let (tx, mut mailbox) = tokio::sync::mpsc::unbounded_channel();
+    let dispatcher = api_server::notifier::NotificationDispatcher::start(
+        std::sync::Arc::new(CapturingNotifier(tx)),
+        Default::default(),
+    );
+    let auth_config = api_server::auth::AuthConfig::from_env().unwrap();
+    let state = std::sync::Arc::new(
+        AppState::new(Some(pool.clone()))
+            .with_auth_config(auth_config)
+            .with_notifications(dispatcher)
+            // These endpoints share the auth tier; keep it out of the way.
+            .with_rate_limiter(
+                RateLimiter::new().with_tier_limits(RateLimitTier::Auth, (600, 100)),
+            ),
+    );
+    let router = api_server::create_router(state);
+
+    let post = |path: &'static str, body: serde_json::Value| {
+        let router = router.clone();
+        async move {
+            let response = router
+                .oneshot(
+                    axum::http::Request::post(path)
+                        .header("content-type", "application/json")
+                        .body(axum::body::Body::from(body.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap_or_default();
+            (status, json)
+        }
+    };
+    let mut next_token = || {
+        let notification = mailbox.try_recv().expect("a notification was sent");
+        notification.payload["token"].as_str().unwrap().to_string()
+    };
+
+    // Registration with an email sends a verification token.
+    let username = format!("reset_{}", &Uuid::new_v4().simple().to_string()[..8]);
+    let (status, body) = post(
+        "/api/v1/auth/register",
+        serde_json::json!({
+            "username": username,
+            "password": "Original-Pass-1",
+            "email": "reset-user@example.com",
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let user_id = Uuid::parse_str(body["user_id"].as_str().unwrap()).unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let verification = next_token();
+
+    let (status, _) = post(
+        "/api/v1/auth/verify-email",
+        serde_json::json!({ "token": verification }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let verified: bool =
+        sqlx::query_scalar("SELECT email_verified_at IS NOT NULL FROM users WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert!(verified);
+    let (status, _) = post(
+        "/api/v1/auth/verify-email",
+        serde_json::json!({ "token": verification }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "tokens are single use");
+
+    let (status, session) = post(
+        "/api/v1/auth/login",
+        serde_json::json!({ "username": username, "password": "Original-Pass-1" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Unknown accounts get the same answer and no email.
+    let (status, _) = post(
+        "/api/v1/auth/password-reset/request",
+        serde_json::json!({ "username": "no_such_user" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    // Only the latest reset token works.
+    for _ in 0..2 {
+        let (status, _) = post(
+            "/api/v1/auth/password-reset/request",
+            serde_json::json!({ "username": username }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let stale = next_token();
+    let reset = next_token();
+    assert!(mailbox.try_recv().is_err());
+
+    let confirm = |token: String| {
+        post(
+            "/api/v1/auth/password-reset/confirm",
+            serde_json::json!({ "token": token, "new_password": "Replacement-Pass-2" }),
+        )
+    };
+    assert_eq!(confirm(stale).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(confirm(reset.clone()).await.0, StatusCode::OK);
+    assert_eq!(confirm(reset).await.0, StatusCode::BAD_REQUEST);
+
+    // The old password and sessions stop working.
+    let (status, _) = post(
+        "/api/v1/auth/login",
+        serde_json::json!({ "username": username, "password": "Original-Pass-1" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = post(
+        "/api/v1/auth/refresh",
+        serde_json::json!({ "refresh_token": session["refresh_token"] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = post(
+        "/api/v1/auth/login",
+        serde_json::json!({ "username": username, "password": "Replacement-Pass-2" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    sqlx::query("TRUNCATE TABLE account_tokens, refresh_tokens, audit_log, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_task_timeline_orders_lifecycle_events() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_task_timeline_orders_lifecycle_events — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, audit_log, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let (tx, mut mailbox) = tokio::sync::mpsc::unbounded_channel();
+    let dispatcher = api_server::notifier::NotificationDispatcher::start(
+        std::sync::Arc::new(CapturingNotifier(tx)),
+        Default::default(),
+    );
+    let state = AppState::new(Some(pool.clone())).with_notifications(dispatcher);
+    let user_id = Uuid::new_v4();
+    let stranger_id = Uuid::new_v4();
+    for (id, name) in [
+        (user_id, "timeline-user"),
+        (stranger_id, "timeline-stranger"),
+    ] {
+        sqlx::query(
+            "INSERT INTO users (user_id, username, password_hash, email) VALUES ($1, $2, 'x', $3)",
+        )
+        .bind(id)
+        .bind(name)
+        .bind(format!("{name}@example.com"))
+        .execute(&pool)
+        .await
+        .expect("create user");
+    }
+
+    let node_id = format!(
+        "timeline-node-{}",
+        &Uuid::new_v4().simple().to_string()[..8]
+    );
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "us-east".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            user_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "timeline"}),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 120,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            user_id,
+        )
+        .await
+        .expect("task submission should succeed");
+    assert_eq!(task.status, TaskStatus::Running);
+    let task_id = Uuid::parse_str(&task.task_id).unwrap();
+
+    state
+        .submit_task_result(
+            task_id,
+            NodeTaskResult {
+                node_id: node_id.clone(),
+                result: serde_json::json!({"answer": 42}),
+                execution_time_ms: Some(10),
+                proof_data: None,
+                public_inputs: None,
+                circuit_id: None,
+                proof_timestamp: None,
+                energy_wh: Some(1.5),
+                cpu_time_ms: Some(420),
+                peak_memory_bytes: Some(2 * 1024 * 1024),
+                error: None,
+                sandbox_report: None,
+                proof_verdict: None,
+            },
+            user_id,
+        )
+        .await
+        .expect("node result should be accepted");
+
+    // Completing the task queued its notification in the outbox.
+    assert_eq!(state.drain_notification_outbox().await.unwrap(), 1);
+    let notification = tokio::time::timeout(std::time::Duration::from_secs(5), mailbox.recv())
+        .await
+        .expect("completion notification delivered")
+        .unwrap();
+    assert_eq!(
+        notification.event,
+        api_server::notifier::NotificationEvent::TaskCompleted
+    );
+
+    let timeline = state
+        .get_task_timeline(task_id, user_id)
+        .await
+        .unwrap()
+        .expect("creator can read the timeline");
+    assert_eq!(timeline.status, TaskStatus::Completed);
+    let events: Vec<_> = timeline.events.iter().map(|e| e.event.as_str()).collect();
+    assert_eq!(
+        events,
+        [
+            "submitted",
+            "assigned",
+            "result_submitted",
+            "completed",
+            "notification_queued",
+            "notification_delivered"
+        ]
+    );
+    assert_eq!(
+        timeline.events[1].node_id.as_deref(),
+        Some(node_id.as_str())
+    );
+    assert_eq!(
+        timeline.events[2].details,
+        Some(serde_json::json!({
+            "execution_time_ms": 10,
+            "cpu_time_ms": 420,
+            "peak_memory_bytes": 2 * 1024 * 1024,
+            "energy_wh": 1.5,
+        }))
+    );
+    assert_eq!(
+        timeline.events[4].details.as_ref().unwrap()["event"],
+        "task_completed"
+    );
+    let times: Vec<_> = timeline
+        .events
+        .iter()
+        .map(|e| chrono::DateTime::parse_from_rfc3339(&e.at).unwrap())
+        .collect();
+    assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    assert!(state
+        .get_task_timeline(task_id, stranger_id)
+        .await
+        .unwrap()
+        .is_none());
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, audit_log, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_reported_resource_usage_rolls_into_task_type_stats() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_reported_resource_usage_rolls_into_task_type_stats — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users, task_type_stats CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let user_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO users (user_id, username, password_hash, email) VALUES ($1, 'usage-user', 'x', 'usage-user@example.com')",
+    )
+    .bind(user_id)
+    .execute(&pool)
+    .await
+    .expect("create user");
+
+    let node_id = format!("usage-node-{}", &Uuid::new_v4().simple().to_string()[..8]);
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "us-east".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            user_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    let submit = || async {
+        let task = state
+            .submit_task(
+                TaskSubmission {
+                    task_type: "computation".to_string(),
+                    wasm_module: None,
+                    inputs: serde_json::json!({"job": "usage"}),
+                    requirements: TaskRequirements {
+                        min_nodes: 1,
+                        max_execution_time_sec: 120,
+                        require_gpu: false,
+                        require_proof: false,
+                        scheduling_mode: SchedulingMode::Standard,
+                        max_retries: 0,
+                        retry_backoff_sec: 0,
+                        egress: vec![],
+                        checkpointable: false,
+                        node_selector: Default::default(),
+                        diversity: Default::default(),
+                        preferred_regions: Vec::new(),
+                        excluded_regions: Vec::new(),
+                        region_strictness: Default::default(),
+                    },
+                    priority: 0,
+                },
+                user_id,
+            )
+            .await
+            .expect("task submission should succeed");
+        assert_eq!(task.status, TaskStatus::Running);
+        Uuid::parse_str(&task.task_id).unwrap()
+    };
+    let report = |cpu_time_ms, peak_memory_bytes, error: Option<&str>| NodeTaskResult {
+        node_id: node_id.clone(),
+        result: serde_json::json!({}),
+        execution_time_ms: Some(50),
+        proof_data: None,
+        public_inputs: None,
+        circuit_id: None,
+        proof_timestamp: None,
+        energy_wh: None,
+        cpu_time_ms,
+        peak_memory_bytes,
+        error: error.map(str::to_string),
+        sandbox_report: None,
+        proof_verdict: None,
+    };
+
+    // A failed attempt reports what it used before trapping.
+    let failed_task = submit().await;
+    let response = state
+        .submit_task_result(
+            failed_task,
+            report(Some(30), Some(1 << 20), Some("trap")),
+            user_id,
+        )
+        .await
+        .expect("node error should be accepted");
+    assert_eq!(response["status"], "failed");
+    assert_eq!(response["cpu_time_ms"], 30);
+
+    let completed_task = submit().await;
+    let response = state
+        .submit_task_result(
+            completed_task,
+            report(Some(90), Some(3 << 20), None),
+            user_id,
+        )
+        .await
+        .expect("node result should be accepted");
+    assert_eq!(response["status"], "completed");
+    assert_eq!(response["peak_memory_bytes"], 3 << 20);
+
+    let (cpu_time_ms, peak_memory_bytes): (Option<i64>, Option<i64>) = sqlx::query_as(
+        "SELECT cpu_time_ms, peak_memory_bytes FROM task_assignments WHERE task_id = $1",
+    )
+    .bind(failed_task)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!((cpu_time_ms, peak_memory_bytes), (Some(30), Some(1 << 20)));
+
+    let stats = state.list_task_type_stats().await.unwrap();
+    assert_eq!(stats.len(), 1);
+    let stats = &stats[0];
+    assert_eq!(stats.task_type, "computation");
+    assert_eq!((stats.attempts, stats.completed_attempts), (2, 1));
+    assert_eq!(stats.avg_execution_time_ms, Some(50.0));
+    assert_eq!(stats.avg_cpu_time_ms, Some(60.0));
+    assert_eq!(stats.avg_peak_memory_bytes, Some((2 << 20) as f64));
+    assert_eq!(stats.max_peak_memory_bytes, Some(3 << 20));
+    assert_eq!(stats.avg_energy_wh, None);
+    assert_eq!(stats.total_cpu_time_ms, 120.0);
+
+    let usage = state.get_usage_report(user_id).await.unwrap();
+    assert_eq!(usage.task_cpu_time_ms, 120);
+    // Both tasks started straight away, so their recorded waits are short.
+    assert_eq!(usage.task_queue_wait.tasks_started, 2);
+    assert!(usage.task_queue_wait.max_wait_seconds.unwrap() < 60.0);
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users, task_type_stats CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_node_rankings_favour_nodes_with_task_type_history() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_node_rankings_favour_nodes_with_task_type_history — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users, task_type_stats CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let user_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO users (user_id, username, password_hash, email) VALUES ($1, 'ranking-user', 'x', 'ranking-user@example.com')",
+    )
+    .bind(user_id)
+    .execute(&pool)
+    .await
+    .expect("create user");
+
+    let register = |node_id: String| {
+        let state = &state;
+        async move {
+            state
+                .register_node(
+                    NodeRegistration {
+                        node_id,
+                        region: "us-east".to_string(),
+                        node_type: "compute".to_string(),
+                        capabilities: NodeCapabilities {
+                            bandwidth_mbps: 500.0,
+                            cpu_cores: 8,
+                            memory_gb: 16.0,
+                            gpu_available: false,
+                        },
+                        observability_port: None,
+                        benchmark_ops_per_wh: None,
+                        secrets_public_key: None,
+                        slots: None,
+                        signing_public_key: None,
+                        labels: Default::default(),
+                        asn: None,
+                        allowed_task_types: Vec::new(),
+                        blocked_task_types: Vec::new(),
+                    },
+                    user_id,
+                )
+                .await
+                .expect("node registration should succeed");
+        }
+    };
+
+    let veteran = format!(
+        "ranking-veteran-{}",
+        &Uuid::new_v4().simple().to_string()[..8]
+    );
+    register(veteran.clone()).await;
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "ranking"}),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 120,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            user_id,
+        )
+        .await
+        .expect("task submission should succeed");
+    assert_eq!(task.assigned_nodes, vec![veteran.clone()]);
+    state
+        .submit_task_result(
+            Uuid::parse_str(&task.task_id).unwrap(),
+            NodeTaskResult {
+                node_id: veteran.clone(),
+                result: serde_json::json!({}),
+                execution_time_ms: Some(250),
+                proof_data: None,
+                public_inputs: None,
+                circuit_id: None,
+                proof_timestamp: None,
+                energy_wh: None,
+                cpu_time_ms: None,
+                peak_memory_bytes: None,
+                error: None,
+                sandbox_report: None,
+                proof_verdict: None,
+            },
+            user_id,
+        )
+        .await
+        .expect("node result should be accepted");
+
+    let newcomer = format!(
+        "ranking-newcomer-{}",
+        &Uuid::new_v4().simple().to_string()[..8]
+    );
+    register(newcomer.clone()).await;
+
+    let entry = api_server::models::task_type_registry_entry("computation").unwrap();
+    let response = state.rank_nodes(entry, 10).await.unwrap();
+    let order: Vec<_> = response
+        .rankings
+        .iter()
+        .map(|ranking| ranking.node_id.as_str())
+        .collect();
+    assert_eq!(order, [veteran.as_str(), newcomer.as_str()]);
+
+    let top = &response.rankings[0];
+    assert_eq!((top.rank, top.completed_attempts), (1, 1));
+    assert_eq!(top.success_rate, Some(1.0));
+    assert_eq!(top.median_execution_time_ms, Some(250.0));
+    assert_eq!(top.breakdown.speed.value, 1.0);
+    assert_eq!(top.breakdown.reputation.value, 1.0);
+    let newcomer_ranking = &response.rankings[1];
+    assert_eq!(newcomer_ranking.success_rate, None);
+    assert_eq!(newcomer_ranking.breakdown.speed.value, 0.5);
+
+    // Nodes that cannot take the task type are not ranked for it.
+    let connect = api_server::models::task_type_registry_entry("connect_only").unwrap();
+    assert!(state
+        .rank_nodes(connect, 10)
+        .await
+        .unwrap()
+        .rankings
+        .is_empty());
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users, task_type_stats CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_read_replicas_admit_caught_up_pools_and_skip_unreachable_ones() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_read_replicas_admit_caught_up_pools_and_skip_unreachable_ones — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    // The test database stands in for a caught-up replica; nothing listens
+    // on port 1.
+    let unreachable = sqlx::postgres::PgPoolOptions::new()
+        .acquire_timeout(std::time::Duration::from_secs(1))
+        .connect_lazy("postgres://postgres@127.0.0.1:1/vcp")
+        .unwrap();
+    let replicas = std::sync::Arc::new(api_server::db::ReadReplicas::from_pools(
+        vec![
+            ("unreachable".to_string(), unreachable),
+            ("caught-up".to_string(), pool.clone()),
+        ],
+        std::time::Duration::from_secs(10),
+    ));
+    assert!(replicas.pool().is_none());
+    assert_eq!(replicas.check_lag().await, 1);
+    for _ in 0..4 {
+        assert_eq!(
+            replicas.pool().unwrap().connect_options().get_port(),
+            pool.connect_options().get_port()
+        );
+    }
+
+    // Reads go through the replica; the primary pool is still required.
+    let state = AppState::new(Some(pool.clone())).with_read_replicas(replicas);
+    state.list_task_type_stats().await.unwrap();
+    let stats = state.get_cluster_stats().await;
+    assert!(stats.total_nodes >= stats.healthy_nodes);
+}
+
+#[tokio::test]
+async fn test_task_search_matches_inputs_ids_and_respects_visibility() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_task_search_matches_inputs_ids_and_respects_visibility — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let user_id = Uuid::new_v4();
+    let stranger_id = Uuid::new_v4();
+    for (id, name) in [(user_id, "search-user"), (stranger_id, "search-stranger")] {
+        sqlx::query(
+            "INSERT INTO users (user_id, username, password_hash, email) VALUES ($1, $2, 'x', $3)",
+        )
+        .bind(id)
+        .bind(name)
+        .bind(format!("{name}@example.com"))
+        .execute(&pool)
+        .await
+        .expect("create user");
+    }
+
+    let submit = |inputs: serde_json::Value, creator: Uuid| {
+        let state = &state;
+        async move {
+            let task = state
+                .submit_task(
+                    TaskSubmission {
+                        task_type: "computation".to_string(),
+                        wasm_module: None,
+                        inputs,
+                        requirements: TaskRequirements {
+                            min_nodes: 1,
+                            max_execution_time_sec: 120,
+                            require_gpu: false,
+                            require_proof: false,
+                            scheduling_mode: SchedulingMode::Standard,
+                            max_retries: 0,
+                            retry_backoff_sec: 0,
+                            egress: vec![],
+                            checkpointable: false,
+                            node_selector: Default::default(),
+                            diversity: Default::default(),
+                            preferred_regions: Vec::new(),
+                            excluded_regions: Vec::new(),
+                            region_strictness: Default::default(),
+                        },
+                        priority: 0,
+                    },
+                    creator,
+                )
+                .await
+                .expect("task submission should succeed");
+            task.task_id
+        }
+    };
+
+    let report = submit(
+        serde_json::json!({"prompt": "Summarise the quarterly revenue report"}),
+        user_id,
+    )
+    .await;
+    let tagged = submit(serde_json::json!({"ticket": "INC-4471-b"}), user_id).await;
+    let _other = submit(
+        serde_json::json!({"prompt": "Translate the quarterly memo"}),
+        user_id,
+    )
+    .await;
+    let _foreign = submit(
+        serde_json::json!({"prompt": "Summarise the quarterly revenue report"}),
+        stranger_id,
+    )
+    .await;
+
+    let search = |q: &str| {
+        let state = &state;
+        let q = q.to_string();
+        async move {
+            state
+                .search_tasks(user_id, None, &q, 20)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|hit| hit.task_id)
+                .collect::<Vec<_>>()
+        }
+    };
+
+    // Word queries rank the better match first and never leak other users' tasks.
+    let hits = search("quarterly revenue").await;
+    assert_eq!(hits, vec![report.clone()]);
+    assert_eq!(search("quarterly").await.len(), 2);
+    // Substrings that are not whole words still match.
+    assert_eq!(search("4471-b").await, vec![tagged.clone()]);
+    // A task ID finds that task.
+    assert_eq!(search(&tagged).await, vec![tagged.clone()]);
+    assert!(search("nonexistent-phrase").await.is_empty());
+
+    let snippet = &state
+        .search_tasks(user_id, None, "revenue", 1)
+        .await
+        .unwrap()[0]
+        .snippet;
+    assert!(snippet.contains("<b>revenue</b>"), "{snippet}");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_deleted_task_is_hidden_restorable_and_purged() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_deleted_task_is_hidden_restorable_and_purged — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let user_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO users (user_id, username, password_hash, email) VALUES ($1, 'soft-delete-user', 'x', 'soft-delete@example.com')",
+    )
+    .bind(user_id)
+    .execute(&pool)
+    .await
+    .expect("create user");
+
+    let node_id = format!(
+        "soft-delete-node-{}",
+        &Uuid::new_v4().simple().to_string()[..8]
+    );
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "us-east".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            user_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "soft-delete"}),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 120,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            user_id,
+        )
+        .await
+        .expect("task submission should succeed");
+    assert_eq!(task.assigned_nodes, vec![node_id.clone()]);
+    let task_uuid = Uuid::parse_str(&task.task_id).unwrap();
+
+    // Deleting hides the task and frees its node but keeps the row.
+    assert!(state.delete_task(&task.task_id, user_id).await.unwrap());
+    assert!(state.get_task(&task.task_id, user_id).await.is_none());
+    assert!(state.list_tasks(user_id).await.is_empty());
+    assert!(
+        !state.delete_task(&task.task_id, user_id).await.unwrap(),
+        "a deleted task cannot be deleted again"
+    );
+    let (status, active): (String, i64) = sqlx::query_as(
+        r#"
+        SELECT t.status,
+               (SELECT COUNT(*) FROM task_assignments ta
+                WHERE ta.task_id = t.task_id AND ta.disconnected_at IS NULL)
+        FROM tasks t
+        WHERE t.task_id = $1 AND t.deleted_at IS NOT NULL
+        "#,
+    )
+    .bind(task_uuid)
+    .fetch_one(&pool)
+    .await
+    .expect("deleted task row is kept");
+    assert_eq!(status, "pending");
+    assert_eq!(active, 0);
+
+    // Only the owner can restore, and restoring reschedules the task.
+    assert!(!state
+        .restore_task(&task.task_id, Uuid::new_v4())
+        .await
+        .unwrap());
+    assert!(state.restore_task(&task.task_id, user_id).await.unwrap());
+    assert!(!state.restore_task(&task.task_id, user_id).await.unwrap());
+    let restored = state
+        .get_task(&task.task_id, user_id)
+        .await
+        .expect("restored task is visible");
+    assert_eq!(restored.status, TaskStatus::Running);
+    assert_eq!(restored.assigned_nodes, vec![node_id.clone()]);
+
+    // Tasks deleted longer ago than the retention window are purged.
+    assert!(state.delete_task(&task.task_id, user_id).await.unwrap());
+    sqlx::query("UPDATE tasks SET deleted_at = NOW() - INTERVAL '40 days' WHERE task_id = $1")
+        .bind(task_uuid)
+        .execute(&pool)
+        .await
+        .expect("age deleted task");
+    let report = state.run_retention(Some(false)).await.unwrap();
+    let deleted_tasks = report
+        .tables
+        .iter()
+        .find(|table| table.table == api_server::retention::RetentionTarget::DeletedTasks)
+        .expect("deleted tasks are covered by retention");
+    assert_eq!(deleted_tasks.purged_rows, 1);
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE task_id = $1")
+        .bind(task_uuid)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+    assert!(!state.restore_task(&task.task_id, user_id).await.unwrap());
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_flapping_node_is_excluded_until_breaker_closes() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_flapping_node_is_excluded_until_breaker_closes — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state =
+        AppState::new(Some(pool.clone())).with_flap_breaker(mesh_coordinator::FlapBreakerConfig {
+            threshold: Some(2),
+            window_secs: 900,
+            cooldown_secs: 600,
+        });
+    let user_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(user_id)
+        .bind("flap-user")
+        .execute(&pool)
+        .await
+        .expect("create user");
+
+    let node_id = format!("flap-node-{}", &Uuid::new_v4().simple().to_string()[..8]);
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "us-east".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            user_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    // Each flap: the sweep finds the heartbeat stale, then the node returns.
+    let flap = || async {
+        sqlx::query(
+            "UPDATE nodes SET last_heartbeat = NOW() - interval '1 hour' WHERE node_id = $1",
+        )
+        .bind(&node_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert_eq!(state.sweep_offline_nodes().await.unwrap(), 1);
+        let heartbeat = state
+            .update_node_heartbeat(&node_id, user_id, &NodeHeartbeatRequest::default())
+            .await
+            .unwrap()
+            .expect("owner can heartbeat");
+        assert_eq!(heartbeat.node_status, "online");
+    };
+
+    flap().await;
+    let node = state.get_node(&node_id).await.unwrap();
+    assert_eq!(node.status, "online");
+    assert!(!node.circuit_breaker.open);
+    assert_eq!(node.circuit_breaker.recent_flaps, 1);
+
+    flap().await;
+    let node = state.get_node(&node_id).await.unwrap();
+    assert!(node.circuit_breaker.open);
+    assert!(node.circuit_breaker.open_until.is_some());
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "flap"}),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 120,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            user_id,
+        )
+        .await
+        .expect("task submission should succeed");
+    assert_eq!(task.status, TaskStatus::Pending);
+    assert!(task.assigned_nodes.is_empty());
+
+    // Heartbeats during the cool-down pick up no work; afterwards they do.
+    let heartbeat = state
+        .update_node_heartbeat(&node_id, user_id, &NodeHeartbeatRequest::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(heartbeat.active_task_count, 0);
+
+    sqlx::query(
+        "UPDATE nodes SET flap_breaker_until = NOW() - interval '1 second' WHERE node_id = $1",
+    )
+    .bind(&node_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let heartbeat = state
+        .update_node_heartbeat(&node_id, user_id, &NodeHeartbeatRequest::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(heartbeat.active_task_count, 1);
+    assert!(!state.get_node(&node_id).await.unwrap().circuit_breaker.open);
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+/// Concurrent submissions and heartbeats racing for the same nodes must not
+/// overfill a node's slot pool or attach a task to more nodes than it needs.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_submissions_respect_node_slots() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_concurrent_submissions_respect_node_slots — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = std::sync::Arc::new(AppState::new(Some(pool.clone())));
+    let user_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(user_id)
+        .bind("contention-user")
+        .execute(&pool)
+        .await
+        .expect("create user");
+
+    let mut node_ids = Vec::new();
+    for i in 0..2 {
+        let node_id = format!(
+            "contention-node-{i}-{}",
+            &Uuid::new_v4().simple().to_string()[..8]
+        );
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: node_id.clone(),
+                    region: "us-east".to_string(),
+                    node_type: "compute".to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 500.0,
+                        cpu_cores: 8,
+                        memory_gb: 16.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: Some(NodeSlots {
+                        wasm: Some(2),
+                        ..Default::default()
+                    }),
+                    signing_public_key: None,
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                user_id,
+            )
+            .await
+            .expect("node registration should succeed");
+        node_ids.push(node_id);
+    }
+
+    let mut handles = Vec::new();
+    for i in 0..24 {
+        let state = std::sync::Arc::clone(&state);
+        handles.push(tokio::spawn(async move {
+            state
+                .submit_task(
+                    TaskSubmission {
+                        task_type: "computation".to_string(),
+                        wasm_module: None,
+                        inputs: serde_json::json!({"job": i}),
+                        requirements: TaskRequirements {
+                            min_nodes: 1,
+                            max_execution_time_sec: 120,
+                            require_gpu: false,
+                            require_proof: false,
+                            scheduling_mode: SchedulingMode::Standard,
+                            max_retries: 0,
+                            retry_backoff_sec: 0,
+                            egress: vec![],
+                            checkpointable: false,
+                            node_selector: Default::default(),
+                            diversity: Default::default(),
+                            preferred_regions: Vec::new(),
+                            excluded_regions: Vec::new(),
+                            region_strictness: Default::default(),
+                        },
+                        priority: 0,
+                    },
+                    user_id,
+                )
+                .await
+                .map(|_| ())
+        }));
+    }
+    for node_id in node_ids.iter().cycle().take(8) {
+        let state = std::sync::Arc::clone(&state);
+        let node_id = node_id.clone();
+        handles.push(tokio::spawn(async move {
+            state
+                .update_node_heartbeat(&node_id, user_id, &NodeHeartbeatRequest::default())
+                .await
+                .map(|_| ())
+        }));
+    }
+    for handle in handles {
+        handle
+            .await
+            .expect("task should not panic")
+            .expect("submission or heartbeat should succeed");
+    }
+
+    let per_node: Vec<i64> = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM task_assignments
+        WHERE disconnected_at IS NULL
+        GROUP BY node_id
+        "#,
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(per_node, vec![2, 2]);
+
+    let most_nodes_per_task: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT MAX(attached)
+        FROM (
+            SELECT COUNT(*) AS attached
+            FROM task_assignments
+            WHERE disconnected_at IS NULL
+            GROUP BY task_id
+        ) per_task
+        "#,
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(most_nodes_per_task, Some(1));
+
+    let running: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE status = 'running'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(running, 4);
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_result_and_timeout_races_resolve_to_one_outcome() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_result_and_timeout_races_resolve_to_one_outcome — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = std::sync::Arc::new(AppState::new(Some(pool.clone())));
+    let user_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(user_id)
+        .bind("race-user")
+        .execute(&pool)
+        .await
+        .expect("create user");
+
+    let node_id = format!("race-node-{}", &Uuid::new_v4().simple().to_string()[..8]);
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "us-east".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            user_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    let submit_running_task = |round: u32| {
+        let state = state.clone();
+        async move {
+            let task = state
+                .submit_task(
+                    TaskSubmission {
+                        task_type: "computation".to_string(),
+                        wasm_module: None,
+                        inputs: serde_json::json!({"job": "race", "round": round}),
+                        requirements: TaskRequirements {
+                            min_nodes: 1,
+                            max_execution_time_sec: 120,
+                            require_gpu: false,
+                            require_proof: false,
+                            scheduling_mode: SchedulingMode::Standard,
+                            max_retries: 1,
+                            retry_backoff_sec: 0,
+                            egress: vec![],
+                            checkpointable: false,
+                            node_selector: Default::default(),
+                            diversity: Default::default(),
+                            preferred_regions: Vec::new(),
+                            excluded_regions: Vec::new(),
+                            region_strictness: Default::default(),
+                        },
+                        priority: 0,
+                    },
+                    user_id,
+                )
+                .await
+                .expect("task submission should succeed");
+            assert_eq!(task.status, TaskStatus::Running);
+            Uuid::parse_str(&task.task_id).unwrap()
+        }
+    };
+    let result_for = |round: u32| NodeTaskResult {
+        node_id: node_id.clone(),
+        result: serde_json::json!({"round": round}),
+        execution_time_ms: Some(10),
+        proof_data: None,
+        public_inputs: None,
+        circuit_id: None,
+        proof_timestamp: None,
+        energy_wh: None,
+        cpu_time_ms: None,
+        peak_memory_bytes: None,
+        error: None,
+        sandbox_report: None,
+        proof_verdict: None,
+    };
+    let task_state = |task_id: Uuid| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_as::<_, (String, i32, Option<serde_json::Value>)>(
+                "SELECT status, retry_count, result FROM tasks WHERE task_id = $1",
+            )
+            .bind(task_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        }
+    };
+
+    // A timeout that lands first ends the attempt; the late result is refused.
+    let task_id = submit_running_task(0).await;
+    state
+        .fail_task_attempt(task_id, &node_id, "execution timed out")
+        .await
+        .expect("timeout fails the attempt");
+    assert!(state
+        .submit_task_result(task_id, result_for(0), user_id)
+        .await
+        .is_err());
+    assert_eq!(task_state(task_id).await, ("pending".to_string(), 1, None));
+
+    // A result that lands first completes the task; the late timeout is refused.
+    let task_id = submit_running_task(1).await;
+    state
+        .submit_task_result(task_id, result_for(1), user_id)
+        .await
+        .expect("result completes the task");
+    assert!(state
+        .fail_task_attempt(task_id, &node_id, "execution timed out")
+        .await
+        .is_err());
+    assert_eq!(
+        task_state(task_id).await,
+        (
+            "completed".to_string(),
+            0,
+            Some(serde_json::json!({"round": 1}))
+        )
+    );
+
+    // Racing them leaves exactly one winner and a task state that matches it.
+    for round in 2..12 {
+        let task_id = submit_running_task(round).await;
+        let (result, timeout) = tokio::join!(
+            tokio::spawn({
+                let state = state.clone();
+                let submission = result_for(round);
+                async move { state.submit_task_result(task_id, submission, user_id).await }
+            }),
+            tokio::spawn({
+                let state = state.clone();
+                let node_id = node_id.clone();
+                async move {
+                    state
+                        .fail_task_attempt(task_id, &node_id, "execution timed out")
+                        .await
+                }
+            }),
+        );
+        let (result, timeout) = (result.unwrap(), timeout.unwrap());
+        assert!(
+            result.is_ok() != timeout.is_ok(),
+            "round {round}: result {result:?}, timeout {timeout:?}"
+        );
+
+        let expected = if result.is_ok() {
+            (
+                "completed".to_string(),
+                0,
+                Some(serde_json::json!({"round": round})),
+            )
+        } else {
+            ("pending".to_string(), 1, None)
+        };
+        assert_eq!(task_state(task_id).await, expected, "round {round}");
+    }
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_proofs_are_stored_and_linked_to_tasks() {
+    use base64::Engine;
+    use zk_prover::{prover::ZKProver, ExecutionTrace};
+
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_proofs_are_stored_and_linked_to_tasks — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let owner_id = Uuid::new_v4();
+    let stranger_id = Uuid::new_v4();
+    for (user_id, username) in [(owner_id, "proof-owner"), (stranger_id, "proof-stranger")] {
+        sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+            .bind(user_id)
+            .bind(username)
+            .execute(&pool)
+            .await
+            .expect("create user");
+    }
+
+    let node_id = format!("proof-node-{}", &Uuid::new_v4().simple().to_string()[..8]);
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "us-east".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            owner_id,
+        )
+        .await
+        .expect("node registration should succeed");
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "prove"}),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 120,
+                    require_gpu: false,
+                    require_proof: true,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            owner_id,
+        )
+        .await
+        .expect("task submission should succeed");
+    let task_id = Uuid::parse_str(&task.task_id).unwrap();
+
+    let proof = ZKProver::default()
+        .generate_proof(ExecutionTrace {
+            module_hash: "proof_storage_module".to_string(),
+            function_name: "run".to_string(),
+            inputs: vec![1, 2, 3],
+            outputs: vec![4, 5, 6],
+            execution_time_ms: 10,
+            gas_used: 100,
+            timestamp: 1,
+        })
+        .expect("generate proof");
+    let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    let result_with_proof = |proof_data: &[u8]| NodeTaskResult {
+        node_id: node_id.clone(),
+        result: serde_json::json!({"answer": 42}),
+        execution_time_ms: Some(10),
+        proof_data: Some(encode(proof_data)),
+        public_inputs: Some(encode(&proof.public_inputs)),
+        circuit_id: None,
+        proof_timestamp: None,
+        energy_wh: None,
+        cpu_time_ms: None,
+        peak_memory_bytes: None,
+        error: None,
+        sandbox_report: None,
+        proof_verdict: None,
+    };
+
+    // A rejected proof is kept with its outcome and named in the error.
+    let rejected = state
+        .submit_task_result(task_id, result_with_proof(&[0xde; 128]), owner_id)
+        .await
+        .expect_err("invalid proof is rejected");
+    let rejected_id = rejected.details.as_ref().unwrap()["proof_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let stored = state
+        .get_proof(Uuid::parse_str(&rejected_id).unwrap(), owner_id)
+        .await
+        .unwrap()
+        .expect("rejected proof is stored");
+    assert!(!stored.verified);
+    assert!(stored.error_message.is_some());
+    assert_eq!(stored.node_id.as_deref(), Some(node_id.as_str()));
+
+    // An accepted proof is linked from the completed task.
+    let accepted = state
+        .submit_task_result(task_id, result_with_proof(&proof.proof_data), owner_id)
+        .await
+        .expect("valid proof is accepted");
+    let proof_id = accepted["proof_id"].as_str().unwrap().to_string();
+    let task = state.get_task(&task.task_id, owner_id).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert_eq!(task.proof_id.as_deref(), Some(proof_id.as_str()));
+
+    let proof_uuid = Uuid::parse_str(&proof_id).unwrap();
+    let stored = state
+        .get_proof(proof_uuid, owner_id)
+        .await
+        .unwrap()
+        .expect("owner sees the proof");
+    assert!(stored.verified);
+    assert_eq!(stored.task_id.as_deref(), Some(task.task_id.as_str()));
+    assert_eq!(stored.proof_data, encode(&proof.proof_data));
+    assert_eq!(stored.public_inputs, encode(&proof.public_inputs));
+    assert!(stored.verified_at.is_some());
+    assert!(state
+        .get_proof(proof_uuid, stranger_id)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Synchronous verification is limited to the caller's own tasks and
+    // links the stored proof to the task.
+    let standalone_request = || ProofVerificationRequest {
+        task_id: task.task_id.clone(),
+        proof_data: encode(&proof.proof_data),
+        public_inputs: encode(&proof.public_inputs),
+        circuit_id: None,
+    };
+    let refused = state
+        .verify_proof(standalone_request(), stranger_id)
+        .await
+        .expect_err("stranger cannot verify synchronously against another's task");
+    assert_eq!(refused.status_code, axum::http::StatusCode::NOT_FOUND);
+    let response = state
+        .verify_proof(standalone_request(), owner_id)
+        .await
+        .expect("owner verification runs");
+    assert!(response.valid);
+    let owned = state
+        .get_proof(
+            Uuid::parse_str(response.proof_id.as_deref().unwrap()).unwrap(),
+            owner_id,
+        )
+        .await
+        .unwrap()
+        .expect("owner sees the proof");
+    assert_eq!(owned.task_id.as_deref(), Some(task.task_id.as_str()));
+
+    // A batch verifies proofs for any task and stores each for its
+    // submitter, but does not attach it to a task the submitter cannot see.
+    let batch = state
+        .create_proof_batch(
+            api_server::proof_batches::ProofBatchRequest {
+                proofs: vec![standalone_request(), standalone_request()],
+            },
+            stranger_id,
+        )
+        .await
+        .expect("batch is queued");
+    assert_eq!(batch.status, "running");
+    assert_eq!(batch.total, 2);
+    let quota = state.proof_quota(stranger_id).await.unwrap();
+    assert_eq!(quota.used, 2, "queued proofs count toward the quota");
+
+    let batch_id = Uuid::parse_str(&batch.batch_id).unwrap();
+    state.run_proof_batch(batch_id).await;
+    let batch = state
+        .get_proof_batch(batch_id, stranger_id)
+        .await
+        .unwrap()
+        .expect("submitter sees the batch");
+    assert_eq!(batch.status, "completed");
+    assert_eq!((batch.completed, batch.valid), (2, 2));
+    assert!(state
+        .get_proof_batch(batch_id, owner_id)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(state.proof_quota(stranger_id).await.unwrap().used, 2);
+
+    let standalone_id = Uuid::parse_str(batch.results[0].proof_id.as_deref().unwrap()).unwrap();
+    let standalone = state
+        .get_proof(standalone_id, stranger_id)
+        .await
+        .unwrap()
+        .expect("submitter sees the proof");
+    assert_eq!(standalone.task_id, None);
+    assert!(state
+        .get_proof(standalone_id, owner_id)
+        .await
+        .unwrap()
+        .is_none());
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+/// With offload on, batch proofs go to signing verifier nodes as
+/// `verify_proof` tasks and settle once a quorum of verdicts agree.
+#[tokio::test]
+async fn test_offloaded_proofs_settle_on_a_verifier_quorum() {
+    use ambient_node::{NodeSigningKey, ProofVerdict, SignedProofVerdict};
+    use base64::Engine;
+    use zk_prover::{prover::ZKProver, ExecutionTrace};
+
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_offloaded_proofs_settle_on_a_verifier_quorum — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+    std::env::set_var("PROOF_VERIFY_OFFLOAD", "true");
+    std::env::set_var("PROOF_VERIFY_QUORUM", "2");
+
+    let state = AppState::new(Some(pool.clone()));
+    let submitter_id = Uuid::new_v4();
+    let operators: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+    for (user_id, username) in std::iter::once((submitter_id, "proof-submitter".to_string())).chain(
+        operators
+            .iter()
+            .enumerate()
+            .map(|(index, id)| (*id, format!("verifier-operator-{index}"))),
+    ) {
+        sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+            .bind(user_id)
+            .bind(username)
+            .execute(&pool)
+            .await
+            .expect("create user");
+    }
+
+    // Three signing verifiers and one node without a signing key, which is
+    // never handed a proof, each run by its own operator.
+    let mut verifiers = std::collections::HashMap::new();
+    let mut operator_of = std::collections::HashMap::new();
+    for (index, operator_id) in operators.iter().copied().enumerate() {
+        let node_id = format!(
+            "verifier-{index}-{}",
+            &Uuid::new_v4().simple().to_string()[..8]
+        );
+        let key = (index < 3).then(|| NodeSigningKey::generate().unwrap());
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: node_id.clone(),
+                    region: "us-east".to_string(),
+                    node_type: "compute".to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 500.0,
+                        cpu_cores: 8,
+                        memory_gb: 16.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: None,
+                    signing_public_key: key.as_ref().map(NodeSigningKey::public_key_b64),
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                operator_id,
+            )
+            .await
+            .expect("node registration should succeed");
+        operator_of.insert(node_id.clone(), operator_id);
+        if let Some(key) = key {
+            verifiers.insert(node_id, key);
+        }
+    }
+
+    let proof = ZKProver::default()
+        .generate_proof(ExecutionTrace {
+            module_hash: "offload_module".to_string(),
+            function_name: "run".to_string(),
+            inputs: vec![1, 2, 3],
+            outputs: vec![4, 5, 6],
+            execution_time_ms: 10,
+            gas_used: 100,
+            timestamp: 1,
+        })
+        .expect("generate proof");
+    let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    let request = |proof_data: &[u8]| ProofVerificationRequest {
+        task_id: Uuid::new_v4().to_string(),
+        proof_data: encode(proof_data),
+        public_inputs: encode(&proof.public_inputs),
+        circuit_id: None,
+    };
+    let batch = state
+        .create_proof_batch(
+            api_server::proof_batches::ProofBatchRequest {
+                proofs: vec![request(&proof.proof_data), request(&[0xde; 128])],
+            },
+            submitter_id,
+        )
+        .await
+        .expect("batch is queued");
+    let batch_id = Uuid::parse_str(&batch.batch_id).unwrap();
+    state.run_proof_batch(batch_id).await;
+
+    let batch = state
+        .get_proof_batch(batch_id, submitter_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(batch.status, "running");
+    assert_eq!(batch.completed, 0, "the server verified nothing itself");
+    assert_eq!(state.proof_quota(submitter_id).await.unwrap().used, 2);
+
+    let verify_task = |position: i64| {
+        let pool = pool.clone();
+        async move {
+            let task_id: Uuid = sqlx::query_scalar(
+                "SELECT task_id FROM tasks WHERE task_type = 'verify_proof' AND (inputs->>'position')::BIGINT = $1",
+            )
+            .bind(position)
+            .fetch_one(&pool)
+            .await
+            .expect("proof is offloaded as a verify_proof task");
+            let assigned: Vec<String> = sqlx::query_scalar(
+                "SELECT node_id FROM task_assignments WHERE task_id = $1 AND disconnected_at IS NULL ORDER BY node_id",
+            )
+            .bind(task_id)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+            let proof_id: Uuid =
+                sqlx::query_scalar("SELECT proof_id FROM proofs WHERE verification_task_id = $1")
+                    .bind(task_id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            (task_id, assigned, proof_id)
+        }
+    };
+    let verdict = |task_id: Uuid, proof_id: Uuid, node_id: &str, key: &NodeSigningKey, valid| {
+        NodeTaskResult {
+            node_id: node_id.to_string(),
+            result: serde_json::json!({}),
+            execution_time_ms: Some(5),
+            proof_data: None,
+            public_inputs: None,
+            circuit_id: None,
+            proof_timestamp: None,
+            energy_wh: None,
+            cpu_time_ms: None,
+            peak_memory_bytes: None,
+            error: None,
+            sandbox_report: None,
+            proof_verdict: Some(SignedProofVerdict::sign(
+                ProofVerdict {
+                    task_id: task_id.to_string(),
+                    proof_id: proof_id.to_string(),
+                    valid,
+                },
+                key,
+            )),
+        }
+    };
+
+    // Two agreeing verdicts settle the valid proof.
+    let (task_id, assigned, proof_id) = verify_task(0).await;
+    assert_eq!(assigned.len(), 2);
+    assert!(assigned
+        .iter()
+        .all(|node_id| verifiers.contains_key(node_id)));
+    let (first, second) = (&assigned[0], &assigned[1]);
+
+    let forged = state
+        .submit_task_result(
+            task_id,
+            verdict(task_id, proof_id, first, &verifiers[second], true),
+            operator_of[first],
+        )
+        .await
+        .expect_err("verdict signed with another node's key is rejected");
+    assert_eq!(forged.status_code, axum::http::StatusCode::BAD_REQUEST);
+
+    let pending = state
+        .submit_task_result(
+            task_id,
+            verdict(task_id, proof_id, first, &verifiers[first], true),
+            operator_of[first],
+        )
+        .await
+        .expect("first verdict is recorded");
+    assert_eq!(pending["status"], "awaiting_quorum");
+    let repeated = state
+        .submit_task_result(
+            task_id,
+            verdict(task_id, proof_id, first, &verifiers[first], true),
+            operator_of[first],
+        )
+        .await
+        .expect_err("a node votes once");
+    assert_eq!(repeated.status_code, axum::http::StatusCode::CONFLICT);
+
+    let settled = state
+        .submit_task_result(
+            task_id,
+            verdict(task_id, proof_id, second, &verifiers[second], true),
+            operator_of[second],
+        )
+        .await
+        .expect("second verdict settles the proof");
+    assert_eq!(settled["status"], "completed");
+    assert_eq!(settled["proof_verified"], true);
+    let stored = state
+        .get_proof(proof_id, submitter_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(stored.verified);
+    assert!(stored.verified_at.is_some());
+    assert_eq!(
+        stored.verification_task_id.as_deref(),
+        Some(task_id.to_string().as_str())
+    );
+
+    // Split verdicts cannot reach the quorum, so the proof stays unverified.
+    let (task_id, assigned, proof_id) = verify_task(1).await;
+    assert_eq!(assigned.len(), 2);
+    state
+        .submit_task_result(
+            task_id,
+            verdict(
+                task_id,
+                proof_id,
+                &assigned[0],
+                &verifiers[&assigned[0]],
+                true,
+            ),
+            operator_of[&assigned[0]],
+        )
+        .await
+        .expect("first verdict is recorded");
+    let deadlocked = state
+        .submit_task_result(
+            task_id,
+            verdict(
+                task_id,
+                proof_id,
+                &assigned[1],
+                &verifiers[&assigned[1]],
+                false,
+            ),
+            operator_of[&assigned[1]],
+        )
+        .await
+        .expect("disagreeing verdict settles the proof");
+    assert_eq!(deadlocked["proof_verified"], false);
+    let stored = state
+        .get_proof(proof_id, submitter_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!stored.verified);
+    assert_eq!(
+        stored.error_message.as_deref(),
+        Some(api_server::proof_offload::QUORUM_NOT_REACHED)
+    );
+
+    let batch = state
+        .get_proof_batch(batch_id, submitter_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(batch.status, "completed");
+    assert_eq!((batch.completed, batch.valid), (2, 1));
+
+    std::env::remove_var("PROOF_VERIFY_OFFLOAD");
+    std::env::remove_var("PROOF_VERIFY_QUORUM");
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+/// A proof is never verified by the submitter's own nodes, by nodes of an
+/// org the submitter belongs to, or by two nodes of one operator.
+#[tokio::test]
+async fn test_offloaded_proofs_skip_verifiers_tied_to_the_submitter() {
+    use ambient_node::NodeSigningKey;
+    use base64::Engine;
+
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_offloaded_proofs_skip_verifiers_tied_to_the_submitter — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+    std::env::set_var("PROOF_VERIFY_OFFLOAD", "true");
+    std::env::set_var("PROOF_VERIFY_QUORUM", "3");
+
+    let state = AppState::new(Some(pool.clone()));
+    let submitter_id = Uuid::new_v4();
+    let colleague_id = Uuid::new_v4();
+    let operator_a = Uuid::new_v4();
+    let operator_b = Uuid::new_v4();
+    for (user_id, username) in [
+        (submitter_id, "tied-submitter"),
+        (colleague_id, "tied-colleague"),
+        (operator_a, "independent-a"),
+        (operator_b, "independent-b"),
+    ] {
+        sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+            .bind(user_id)
+            .bind(username)
+            .execute(&pool)
+            .await
+            .expect("create user");
+    }
+    let org_id: Uuid =
+        sqlx::query_scalar("INSERT INTO organizations (name) VALUES ('lab') RETURNING org_id")
+            .fetch_one(&pool)
+            .await
+            .expect("create org");
+    for user_id in [submitter_id, colleague_id] {
+        sqlx::query(
+            "INSERT INTO organization_members (org_id, user_id, role) VALUES ($1, $2, 'member')",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .expect("join org");
+    }
+
+    // Every node signs and is healthy; only ownership sets them apart.
+    let owners = [
+        ("own", submitter_id),
+        ("own", submitter_id),
+        ("colleague", colleague_id),
+        ("a", operator_a),
+        ("a", operator_a),
+        ("b", operator_b),
+    ];
+    let mut owner_of = std::collections::HashMap::new();
+    for (index, (label, owner_id)) in owners.into_iter().enumerate() {
+        let node_id = format!("tied-{label}-{index}");
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: node_id.clone(),
+                    region: "us-east".to_string(),
+                    node_type: "compute".to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 500.0,
+                        cpu_cores: 8,
+                        memory_gb: 16.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: None,
+                    signing_public_key: Some(NodeSigningKey::generate().unwrap().public_key_b64()),
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                owner_id,
+            )
+            .await
+            .expect("node registration should succeed");
+        owner_of.insert(node_id, owner_id);
+    }
+
+    let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    let batch = state
+        .create_proof_batch(
+            api_server::proof_batches::ProofBatchRequest {
+                proofs: vec![ProofVerificationRequest {
+                    task_id: Uuid::new_v4().to_string(),
+                    proof_data: encode(&[0xab; 128]),
+                    public_inputs: encode(&[0xcd; 32]),
+                    circuit_id: None,
+                }],
+            },
+            submitter_id,
+        )
+        .await
+        .expect("batch is queued");
+    state
+        .run_proof_batch(Uuid::parse_str(&batch.batch_id).unwrap())
+        .await;
+
+    let task_id: Uuid =
+        sqlx::query_scalar("SELECT task_id FROM tasks WHERE task_type = 'verify_proof'")
+            .fetch_one(&pool)
+            .await
+            .expect("proof is offloaded as a verify_proof task");
+    let assigned = || {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_scalar::<_, String>(
+                "SELECT node_id FROM task_assignments WHERE task_id = $1 ORDER BY node_id",
+            )
+            .bind(task_id)
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+        }
+    };
+
+    // One node per independent operator; the quorum of three stays unmet.
+    let verifiers = assigned().await;
+    assert_eq!(verifiers.len(), 2, "{verifiers:?}");
+    let mut verifier_owners: Vec<Uuid> = verifiers.iter().map(|id| owner_of[id]).collect();
+    verifier_owners.sort();
+    let mut independent = vec![operator_a, operator_b];
+    independent.sort();
+    assert_eq!(verifier_owners, independent);
+
+    // Heartbeats from the excluded nodes do not attach them either.
+    for node_id in owner_of.keys() {
+        state
+            .update_node_heartbeat(node_id, owner_of[node_id], &NodeHeartbeatRequest::default())
+            .await
+            .expect("heartbeat should succeed");
+    }
+    assert_eq!(assigned().await, verifiers);
+
+    std::env::remove_var("PROOF_VERIFY_OFFLOAD");
+    std::env::remove_var("PROOF_VERIFY_QUORUM");
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users, organizations CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+/// A GraphQL query follows task → assigned nodes → telemetry in one round
+/// trip, and each field keeps the visibility rules of its REST route.
+#[tokio::test]
+async fn test_graphql_resolves_nested_task_nodes_and_telemetry() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_graphql_resolves_nested_task_nodes_and_telemetry — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = std::sync::Arc::new(AppState::new(Some(pool.clone())));
+    let owner_id = Uuid::new_v4();
+    let stranger_id = Uuid::new_v4();
+    for (id, name) in [
+        (owner_id, "graphql-owner"),
+        (stranger_id, "graphql-stranger"),
+    ] {
+        sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+            .bind(id)
+            .bind(name)
+            .execute(&pool)
+            .await
+            .expect("create user");
+    }
+
+    let node_id = format!("graphql-node-{}", &Uuid::new_v4().simple().to_string()[..8]);
+    state
+        .register_node(
+            NodeRegistration {
+                node_id: node_id.clone(),
+                region: "eu-west".to_string(),
+                node_type: "compute".to_string(),
+                capabilities: NodeCapabilities {
+                    bandwidth_mbps: 500.0,
+                    cpu_cores: 8,
+                    memory_gb: 16.0,
+                    gpu_available: false,
+                },
+                observability_port: None,
+                benchmark_ops_per_wh: None,
+                secrets_public_key: None,
+                slots: None,
+                signing_public_key: None,
+                labels: Default::default(),
+                asn: None,
+                allowed_task_types: Vec::new(),
+                blocked_task_types: Vec::new(),
+            },
+            owner_id,
+        )
+        .await
+        .expect("node registration should succeed");
+    state
+        .update_node_heartbeat(&node_id, owner_id, &NodeHeartbeatRequest::default())
+        .await
+        .expect("heartbeat should succeed");
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "graphql"}),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 120,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            owner_id,
+        )
+        .await
+        .expect("task submission should succeed");
+    assert_eq!(task.status, TaskStatus::Running);
+
+    let variables = serde_json::json!({ "nodeId": node_id });
+    let query = |viewer_id: Uuid| {
+        let state = state.clone();
+        let variables = variables.clone();
+        async move {
+            let response = api_server::graphql::execute(
+                state,
+                api_server::graphql::Viewer {
+                    user_id: viewer_id,
+                    org_id: None,
+                    role: "user".to_string(),
+                    scopes: vec!["*".to_string()],
+                },
+                api_server::graphql::GraphQlRequest {
+                    query: r#"query($nodeId: String!) {
+                        tasks { taskId status assignedNodes { nodeId capabilities { cpuCores } } }
+                        node(nodeId: $nodeId) {
+                            nodeId
+                            telemetry(resolution: "raw") { resolution points { samples healthScoreAvg } }
+                        }
+                        clusterStats { totalNodes }
+                    }"#
+                    .to_string(),
+                    operation_name: None,
+                    variables: Some(variables),
+                },
+            )
+            .await;
+            serde_json::to_value(response).unwrap()
+        }
+    };
+
+    let owned = query(owner_id).await;
+    assert!(owned.get("errors").is_none(), "{owned}");
+    let tasks = owned["data"]["tasks"].as_array().unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0]["taskId"], task.task_id);
+    assert_eq!(tasks[0]["status"], "running");
+    assert_eq!(tasks[0]["assignedNodes"][0]["nodeId"], node_id);
+    assert_eq!(tasks[0]["assignedNodes"][0]["capabilities"]["cpuCores"], 8);
+    let telemetry = &owned["data"]["node"]["telemetry"];
+    assert_eq!(telemetry["resolution"], "raw");
+    assert_eq!(telemetry["points"][0]["samples"], 1);
+    assert_eq!(owned["data"]["clusterStats"]["totalNodes"], 1);
+
+    // A stranger sees the node but neither the task nor the node's telemetry.
+    let stranger = query(stranger_id).await;
+    assert_eq!(stranger["data"]["tasks"], serde_json::json!([]));
+    assert_eq!(stranger["data"]["node"]["nodeId"], node_id);
+    assert!(stranger["data"]["node"]["telemetry"].is_null());
+    assert_eq!(
+        stranger["errors"][0]["path"],
+        serde_json::json!(["node", "telemetry"])
+    );
+    assert_eq!(stranger["errors"][0]["extensions"]["status"], 404);
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_peer_route_follows_relay_feedback() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping test_peer_route_follows_relay_feedback — no TEST_DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let user_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(user_id)
+        .bind("route-user")
+        .execute(&pool)
+        .await
+        .expect("create user");
+
+    let suffix = &Uuid::new_v4().simple().to_string()[..8];
+    let worker = format!("route-worker-{suffix}");
+    let relay_a = format!("route-relay-a-{suffix}");
+    let relay_b = format!("route-relay-b-{suffix}");
+    for (node_id, node_type) in [
+        (&worker, "compute"),
+        (&relay_a, "universal"),
+        (&relay_b, "universal"),
+    ] {
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: node_id.clone(),
+                    region: "us-east".to_string(),
+                    node_type: node_type.to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 500.0,
+                        cpu_cores: 8,
+                        memory_gb: 16.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: None,
+                    signing_public_key: None,
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                user_id,
+            )
+            .await
+            .expect("node registration should succeed");
+    }
+
+    let relay_of = |response: &api_server::peer_routes::PeerRouteResponse| {
+        response.route.as_ref().unwrap().hops[0].node_id.clone()
+    };
+
+    let direct = state.find_peer_route(&worker, user_id, None).await.unwrap();
+    assert!(direct.route.unwrap().is_direct());
+
+    // Relays rank by node ID until one reports it is saturated.
+    let route = state
+        .find_peer_route(&worker, user_id, Some(false))
+        .await
+        .unwrap();
+    assert_eq!(relay_of(&route), relay_a);
+
+    let saturated = api_server::peer_routes::RelayLoadReport {
+        utilization: 0.95,
+        saturated: false,
+    };
+    assert!(state
+        .report_relay_load(&relay_a, user_id, &saturated)
+        .await
+        .unwrap());
+    let route = state
+        .find_peer_route(&worker, user_id, Some(false))
+        .await
+        .unwrap();
+    assert_eq!(relay_of(&route), relay_b);
+
+    // Dropping relay-b leaves only the saturated relay.
+    let route = state
+        .invalidate_peer_route(
+            &worker,
+            user_id,
+            &api_server::peer_routes::PeerRouteInvalidation {
+                relay_node_id: relay_b.clone(),
+                has_internet: Some(false),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(relay_of(&route), relay_a);
+    assert!(route.route.unwrap().standby.is_empty());
+
+    // Other users can neither query nor report for the nodes.
+    let stranger = Uuid::new_v4();
+    assert!(state
+        .find_peer_route(&worker, stranger, None)
+        .await
+        .is_err());
+    assert!(!state
+        .report_relay_load(&relay_b, stranger, &saturated)
+        .await
+        .unwrap());
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_peer_routes_spread_across_relays() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping test_peer_routes_spread_across_relays — no TEST_DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let user_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(user_id)
+        .bind("spread-user")
+        .execute(&pool)
+        .await
+        .expect("create user");
+
+    let suffix = &Uuid::new_v4().simple().to_string()[..8];
+    let relay_a = format!("spread-relay-a-{suffix}");
+    let relay_b = format!("spread-relay-b-{suffix}");
+    let workers: Vec<String> = (0..3)
+        .map(|i| format!("spread-worker-{i}-{suffix}"))
+        .collect();
+    let nodes = [(&relay_a, "universal"), (&relay_b, "universal")]
+        .into_iter()
+        .chain(workers.iter().map(|worker| (worker, "compute")));
+    for (node_id, node_type) in nodes {
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: node_id.clone(),
+                    region: "us-east".to_string(),
+                    node_type: node_type.to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 500.0,
+                        cpu_cores: 8,
+                        memory_gb: 16.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: None,
+                    signing_public_key: None,
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                user_id,
+            )
+            .await
+            .expect("node registration should succeed");
+    }
+
+    let mut relays = Vec::new();
+    for worker in &workers {
+        let route = state
+            .find_peer_route(worker, user_id, Some(false))
+            .await
+            .unwrap();
+        relays.push(route.route.unwrap().hops[0].node_id.clone());
+    }
+    assert_eq!(
+        relays,
+        vec![relay_a.clone(), relay_b.clone(), relay_a.clone()]
+    );
+
+    // Re-querying keeps a node on its relay.
+    let route = state
+        .find_peer_route(&workers[1], user_id, Some(false))
+        .await
+        .unwrap();
+    assert_eq!(route.route.unwrap().hops[0].node_id, relay_b);
+
+    // A node that is online again gives its relay back.
+    let direct = state
+        .find_peer_route(&workers[0], user_id, Some(true))
+        .await
+        .unwrap();
+    assert!(direct.route.unwrap().is_direct());
+    let assigned: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM peer_route_assignments WHERE relay_node_id = $1")
+            .bind(&relay_a)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(assigned, 1);
+
+    sqlx::query("TRUNCATE TABLE task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_relay_drain_moves_routes_and_sessions() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_relay_drain_moves_routes_and_sessions — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE connect_sessions, task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    let state = AppState::new(Some(pool.clone()));
+    let user_id = Uuid::new_v4();
+    let stranger = Uuid::new_v4();
+    for (id, name) in [
+        (user_id, "drain-relay-user"),
+        (stranger, "drain-relay-stranger"),
+    ] {
+        sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+            .bind(id)
+            .bind(name)
+            .execute(&pool)
+            .await
+            .expect("create user");
+    }
+
+    let suffix = &Uuid::new_v4().simple().to_string()[..8];
+    let relay_a = format!("drain-relay-a-{suffix}");
+    let relay_b = format!("drain-relay-b-{suffix}");
+    let worker = format!("drain-worker-{suffix}");
+    for (node_id, node_type) in [
+        (&relay_a, "universal"),
+        (&relay_b, "universal"),
+        (&worker, "compute"),
+    ] {
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: node_id.clone(),
+                    region: "us-east".to_string(),
+                    node_type: node_type.to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 500.0,
+                        cpu_cores: 8,
+                        memory_gb: 16.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: None,
+                    signing_public_key: None,
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                user_id,
+            )
+            .await
+            .expect("node registration should succeed");
+    }
+
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "connect_only".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({
+                    "session_id": "sess_relay_drain",
+                    "requester_id": user_id.to_string(),
+                    "duration_seconds": 3600,
+                    "bandwidth_limit_mbps": 20,
+                    "egress_profile": "allowlist_domains",
+                    "destination_policy_id": "policy_web_basic_v1"
+                }),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 3600,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: Vec::new(),
+                    excluded_regions: Vec::new(),
+                    region_strictness: Default::default(),
+                },
+                priority: 0,
+            },
+            user_id,
+        )
+        .await
+        .expect("connect_only task submission should succeed");
+    let started = state
+        .start_connect_session(
+            ConnectSessionStartRequest {
+                task_id: task.task_id.clone(),
+                tunnel_protocol: None,
+            },
+            user_id,
+        )
+        .await
+        .expect("connect session should start");
+    let serving = started.session.node_id.clone();
+    let other = if serving == relay_a {
+        relay_b.clone()
+    } else {
+        relay_a.clone()
+    };
+
+    // Route the worker through the relay that will drain.
+    sqlx::query(
+        "INSERT INTO peer_route_assignments (source_node_id, relay_node_id) VALUES ($1, $2)",
+    )
+    .bind(&worker)
+    .bind(&serving)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let announcement = api_server::peer_routes::RelayDrainAnnouncement {
+        grace_seconds: Some(120),
+    };
+    assert!(state
+        .announce_relay_drain(&serving, stranger, &announcement)
+        .await
+        .unwrap()
+        .is_none());
+
+    let drained = state
+        .announce_relay_drain(&serving, user_id, &announcement)
+        .await
+        .unwrap()
+        .expect("owner can drain the relay");
+    assert_eq!(drained.status, "draining");
+    assert_eq!(drained.rerouted_nodes.len(), 1);
+    assert_eq!(drained.rerouted_nodes[0].node_id, worker);
+    assert_eq!(
+        drained.rerouted_nodes[0].relay_node_id.as_ref(),
+        Some(&other)
+    );
+    assert_eq!(drained.migrated_sessions.len(), 1);
+    assert_eq!(drained.migrated_sessions[0].node_id, other);
+    assert!(drained.remaining_sessions.is_empty());
+    let deadline = chrono::DateTime::parse_from_rfc3339(&drained.drain_deadline).unwrap();
+
+    // Both relays serve the session until the deadline, then only the new one.
+    let old_gateway = state
+        .get_node_gateway_sessions(&serving, user_id)
+        .await
+        .unwrap();
+    assert_eq!(old_gateway.len(), 1);
+    assert_eq!(
+        old_gateway[0].expires_at_epoch_seconds as i64,
+        deadline.timestamp()
+    );
+    let new_gateway = state
+        .get_node_gateway_sessions(&other, user_id)
+        .await
+        .unwrap();
+    assert_eq!(new_gateway.len(), 1);
+    assert!(new_gateway[0].expires_at_epoch_seconds as i64 > deadline.timestamp());
+
+    // A second announcement cannot push the deadline back.
+    let again = state
+        .announce_relay_drain(
+            &serving,
+            user_id,
+            &api_server::peer_routes::RelayDrainAnnouncement {
+                grace_seconds: Some(600),
+            },
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(again.drain_deadline, drained.drain_deadline);
+
+    // Past the deadline the old relay lets the session go.
+    sqlx::query("UPDATE connect_sessions SET drain_deadline = NOW() - INTERVAL '1 second'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert!(state
+        .get_node_gateway_sessions(&serving, user_id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert!(state.undrain_node(&serving, user_id).await.unwrap());
+    let deadline_cleared: bool =
+        sqlx::query_scalar("SELECT relay_drain_deadline IS NULL FROM nodes WHERE node_id = $1")
+            .bind(&serving)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert!(deadline_cleared);
+
+    sqlx::query("TRUNCATE TABLE connect_sessions, task_assignments, tasks, nodes, users CASCADE")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_canaries_run_through_the_full_stack() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_canaries_run_through_the_full_stack — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query(
+        "TRUNCATE TABLE canary_runs, notification_outbox, connect_sessions, task_assignments, \
+         tasks, nodes, audit_log, users CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables before integration test");
+
+    let (tx, _mailbox) = tokio::sync::mpsc::unbounded_channel();
+    let dispatcher = api_server::notifier::NotificationDispatcher::start(
+        std::sync::Arc::new(CapturingNotifier(tx)),
+        Default::default(),
+    );
+    let state = AppState::new(Some(pool.clone())).with_notifications(dispatcher);
+    let canary_user = Uuid::new_v4();
+    let operator = Uuid::new_v4();
+    for (id, name) in [(canary_user, "canary-user"), (operator, "canary-operator")] {
+        sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+            .bind(id)
+            .bind(name)
+            .execute(&pool)
+            .await
+            .expect("create user");
+    }
+
+    let suffix = &Uuid::new_v4().simple().to_string()[..8];
+    let worker = format!("canary-worker-{suffix}");
+    let relay = format!("canary-relay-{suffix}");
+    for (node_id, node_type) in [(&worker, "compute"), (&relay, "open_internet")] {
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: node_id.clone(),
+                    region: "us-east".to_string(),
+                    node_type: node_type.to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 500.0,
+                        cpu_cores: 8,
+                        memory_gb: 16.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: None,
+                    signing_public_key: None,
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                operator,
+            )
+            .await
+            .expect("node registration should succeed");
+    }
+
+    let config = api_server::canary::CanaryConfig::parse(
+        Some(&canary_user.to_string()),
+        Some("300"),
+        Some("60"),
+        None,
+    )
+    .expect("canary config");
+    let run_of = |kind: &'static str| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_as::<_, (Uuid, String, Uuid, Option<String>)>(
+                r#"
+                SELECT run_id, node_id, task_id, session_id
+                FROM canary_runs
+                WHERE kind = $1 AND status = 'running'
+                "#,
+            )
+            .bind(kind)
+            .fetch_one(&pool)
+            .await
+            .expect("running canary run")
+        }
+    };
+
+    // Each kind starts once per interval, pinned to the only node that can
+    // take it.
+    state.run_canaries(&config).await.unwrap();
+    state.run_canaries(&config).await.unwrap();
+    let runs: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM canary_runs")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(runs, 2);
+    let (task_run, task_node, task_id, _) = run_of("task").await;
+    let (session_run, session_node, session_task_id, session_id) = run_of("connect_session").await;
+    assert_eq!(task_node, worker);
+    assert_eq!(session_node, relay);
+    let pinned: Option<String> =
+        sqlx::query_scalar("SELECT pinned_node_id FROM tasks WHERE task_id = $1")
+            .bind(task_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(pinned.as_deref(), Some(worker.as_str()));
+
+    // The nodes pick up their assignments and the worker computes the sum.
+    for node_id in [&worker, &relay] {
+        state
+            .update_node_heartbeat(node_id, operator, &NodeHeartbeatRequest::default())
+            .await
+            .expect("heartbeat should succeed");
+    }
+    let inputs: serde_json::Value =
+        sqlx::query_scalar("SELECT inputs FROM tasks WHERE task_id = $1")
+            .bind(task_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    let sum: f64 = inputs["expression"]
+        .as_str()
+        .unwrap()
+        .split(" + ")
+        .map(|operand| operand.parse::<f64>().unwrap())
+        .sum();
+    state
+        .submit_task_result(
+            task_id,
+            NodeTaskResult {
+                node_id: worker.clone(),
+                result: serde_json::json!({ "result": sum }),
+                execution_time_ms: Some(5),
+                proof_data: None,
+                public_inputs: None,
+                circuit_id: None,
+                proof_timestamp: None,
+                energy_wh: None,
+                cpu_time_ms: None,
+                peak_memory_bytes: None,
+                error: None,
+                sandbox_report: None,
+                proof_verdict: None,
+            },
+            operator,
+        )
+        .await
+        .expect("canary result should be accepted");
+
+    // The task canary passes; the session canary opens its session.
+    state.run_canaries(&config).await.unwrap();
+    let session_id = session_id.expect("session canary has a session ID");
+    let session_status: String =
+        sqlx::query_scalar("SELECT status FROM connect_sessions WHERE session_id = $1")
+            .bind(&session_id)
+            .fetch_one(&pool)
+            .await
+            .expect("canary session should be started");
+    assert_eq!(session_status, "active");
+
+    state
+        .report_connect_session_usage(
+            &session_id,
+            operator,
+            &ConnectSessionUsageReport {
+                node_id: relay.clone(),
+                bytes_up: 1_000,
+                bytes_down: 4_000,
+                interval_seconds: 5,
+                seq: 1,
+            },
+        )
+        .await
+        .expect("usage report should be accepted");
+    state.run_canaries(&config).await.unwrap();
+
+    let runs = state
+        .list_canary_runs(&api_server::canary::CanaryRunsQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(runs.len(), 2);
+    for run in &runs {
+        assert_eq!(run.status, "passed", "{run:?}");
+        assert_eq!(run.stage, "verification");
+        assert!(run.finished_at.is_some());
+        let stages: Vec<&str> = run
+            .kind
+            .stages()
+            .iter()
+            .map(|stage| stage.as_str())
+            .collect();
+        assert!(
+            stages
+                .iter()
+                .all(|stage| run.stage_latencies_ms.contains_key(*stage)),
+            "{run:?}"
+        );
+    }
+    let session_task_status: String =
+        sqlx::query_scalar("SELECT status FROM tasks WHERE task_id = $1")
+            .bind(session_task_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(session_task_status, "completed");
+    assert!(runs.iter().any(|run| run.run_id == task_run.to_string()));
+    assert!(runs.iter().any(|run| run.run_id == session_run.to_string()));
+
+    // Once due again, canaries that stall time out at the stage they
+    // reached, release their task and alert the canary user.
+    sqlx::query("UPDATE canary_runs SET started_at = NOW() - interval '1 hour'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    state.run_canaries(&config).await.unwrap();
+    let (_, _, stalled_task_id, _) = run_of("task").await;
+    sqlx::query(
+        "UPDATE canary_runs SET started_at = NOW() - interval '61 seconds' WHERE status = 'running'",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    state.run_canaries(&config).await.unwrap();
+
+    let failed = state
+        .list_canary_runs(&api_server::canary::CanaryRunsQuery {
+            status: Some("failed".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(failed.len(), 2);
+    let stalled_task = failed
+        .iter()
+        .find(|run| run.kind == api_server::canary::CanaryKind::Task)
+        .unwrap();
+    assert_eq!(stalled_task.stage, "execution");
+    assert!(stalled_task
+        .error
+        .as_deref()
+        .is_some_and(|error| error.contains("timed out")));
+    let stalled_session = failed
+        .iter()
+        .find(|run| run.kind == api_server::canary::CanaryKind::ConnectSession)
+        .unwrap();
+    assert_eq!(stalled_session.stage, "relay");
+    let stalled_status: String = sqlx::query_scalar("SELECT status FROM tasks WHERE task_id = $1")
+        .bind(stalled_task_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stalled_status, "failed");
+
+    let alerts: Vec<(String, Uuid)> =
+        sqlx::query_as("SELECT event, user_id FROM notification_outbox ORDER BY outbox_id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        alerts,
+        vec![
+            ("canary_failed".to_string(), canary_user),
+            ("canary_failed".to_string(), canary_user),
+        ]
+    );
+
+    assert!(state
+        .list_canary_runs(&api_server::canary::CanaryRunsQuery {
+            kind: Some("nope".to_string()),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+    sqlx::query(
+        "TRUNCATE TABLE canary_runs, notification_outbox, connect_sessions, task_assignments, \
+         tasks, nodes, audit_log, users CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_status_reports_components_relay_regions_and_incidents() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_status_reports_components_relay_regions_and_incidents — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query(
+        "TRUNCATE TABLE canary_runs, relay_load_reports, task_assignments, tasks, nodes, users \
+         CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables before integration test");
+
+    use api_server::status::{ComponentStatus, IncidentStatus};
+
+    let state = AppState::new(Some(pool.clone()));
+    let operator = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(operator)
+        .bind(format!("status-operator-{operator}"))
+        .execute(&pool)
+        .await
+        .expect("create user");
+
+    let status = state.collect_status().await.expect("status");
+    let component = |status: &api_server::status::StatusResponse, name: &str| {
+        status
+            .components
+            .iter()
+            .find(|c| c.component == name)
+            .map(|c| c.status)
+            .unwrap()
+    };
+    assert_eq!(component(&status, "api"), ComponentStatus::Operational);
+    assert_eq!(component(&status, "scheduler"), ComponentStatus::Outage);
+    assert_eq!(component(&status, "relay"), ComponentStatus::Outage);
+    assert_eq!(status.status, ComponentStatus::Outage);
+
+    let suffix = &Uuid::new_v4().simple().to_string()[..8];
+    let nodes = [
+        (format!("status-worker-{suffix}"), "compute", "us-east"),
+        (
+            format!("status-relay-a-{suffix}"),
+            "open_internet",
+            "us-east",
+        ),
+        (
+            format!("status-relay-b-{suffix}"),
+            "open_internet",
+            "us-east",
+        ),
+        (format!("status-relay-c-{suffix}"), "universal", "eu-west"),
+    ];
+    for (node_id, node_type, region) in &nodes {
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: node_id.clone(),
+                    region: region.to_string(),
+                    node_type: node_type.to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 100.0,
+                        cpu_cores: 4,
+                        memory_gb: 8.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: None,
+                    signing_public_key: None,
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                operator,
+            )
+            .await
+            .expect("node registration should succeed");
+    }
+    sqlx::query(
+        "INSERT INTO relay_load_reports (node_id, utilization, saturated) VALUES ($1, 0.97, TRUE)",
+    )
+    .bind(&nodes[1].0)
+    .execute(&pool)
+    .await
+    .expect("report relay load");
+    sqlx::query("UPDATE nodes SET status = 'draining' WHERE node_id = $1")
+        .bind(&nodes[2].0)
+        .execute(&pool)
+        .await
+        .expect("drain relay");
+
+    // Task canaries: one resolved incident, then a passing run; connect
+    // session canaries: failing now.
+    for (kind, status, stage, minutes_ago) in [
+        ("task", "passed", "verification", 60),
+        ("task", "failed", "execution", 50),
+        ("task", "failed", "result", 40),
+        ("task", "passed", "verification", 30),
+        ("connect_session", "passed", "verification", 20),
+        ("connect_session", "failed", "relay", 10),
+    ] {
+        sqlx::query(
+            r#"
+            INSERT INTO canary_runs (run_id, kind, node_id, status, stage, started_at, finished_at)
+            VALUES ($1, $2, $3, $4, $5,
+                    NOW() - make_interval(mins => $6),
+                    NOW() - make_interval(mins => $6) + INTERVAL '1 minute')
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(kind)
+        .bind(&nodes[0].0)
+        .bind(status)
+        .bind(stage)
+        .bind(minutes_ago)
+        .execute(&pool)
+        .await
+        .expect("insert canary run");
+    }
+
+    let status = state.collect_status().await.expect("status");
+    assert_eq!(
+        component(&status, "scheduler"),
+        ComponentStatus::Operational
+    );
+    assert_eq!(component(&status, "relay"), ComponentStatus::Degraded);
+    assert_eq!(
+        component(&status, "proof_verification"),
+        ComponentStatus::Operational
+    );
+    assert_eq!(status.status, ComponentStatus::Degraded);
+
+    let scheduler = status
+        .components
+        .iter()
+        .find(|c| c.component == "scheduler")
+        .unwrap();
+    assert_eq!(scheduler.uptime.last_24h, Some(50.0));
+    assert_eq!(scheduler.uptime.last_30d, Some(50.0));
+
+    let regions: Vec<(&str, ComponentStatus, i64, i64)> = status
+        .relay_regions
+        .iter()
+        .map(|r| {
+            (
+                r.region.as_str(),
+                r.status,
+                r.relays_online,
+                r.relays_available,
+            )
+        })
+        .collect();
+    assert_eq!(
+        regions,
+        [
+            ("eu-west", ComponentStatus::Operational, 1, 1),
+            ("us-east", ComponentStatus::Outage, 2, 0),
+        ]
+    );
+    let us_east = &status.relay_regions[1];
+    assert_eq!((us_east.relays_saturated, us_east.relays_draining), (1, 1));
+    assert_eq!(us_east.capacity_mbps, 100.0);
+    assert_eq!(us_east.utilization, Some(0.97));
+
+    assert_eq!(status.incidents.len(), 2);
+    assert_eq!(status.incidents[0].component, "relay");
+    assert_eq!(status.incidents[0].status, IncidentStatus::Ongoing);
+    assert_eq!(status.incidents[0].stage, "relay");
+    assert_eq!(status.incidents[1].component, "scheduler");
+    assert_eq!(status.incidents[1].status, IncidentStatus::Resolved);
+    assert_eq!(status.incidents[1].failed_runs, 2);
+    assert_eq!(status.incidents[1].stage, "result");
+
+    sqlx::query(
+        "TRUNCATE TABLE canary_runs, relay_load_reports, task_assignments, tasks, nodes, users \
+         CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_scheduler_decisions_are_recorded_and_replay() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_scheduler_decisions_are_recorded_and_replay — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query(
+        "TRUNCATE TABLE scheduler_decisions, task_assignments, tasks, nodes, users CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables before integration test");
+
+    use api_server::scheduler_decisions::SchedulerDecisionsQuery;
+
+    let state = AppState::new(Some(pool.clone()));
+    let owner_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(owner_id)
+        .bind(format!("decisions-owner-{owner_id}"))
+        .execute(&pool)
+        .await
+        .expect("create node and task owner");
+
+    let register = |node_id: &'static str, region: &'static str| {
+        let state = &state;
+        async move {
+            state
+                .register_node(
+                    NodeRegistration {
+                        node_id: node_id.to_string(),
+                        region: region.to_string(),
+                        node_type: "compute".to_string(),
+                        capabilities: NodeCapabilities {
+                            bandwidth_mbps: 500.0,
+                            cpu_cores: 8,
+                            memory_gb: 16.0,
+                            gpu_available: false,
+                        },
+                        observability_port: None,
+                        benchmark_ops_per_wh: None,
+                        secrets_public_key: None,
+                        slots: None,
+                        signing_public_key: None,
+                        labels: Default::default(),
+                        asn: None,
+                        allowed_task_types: Vec::new(),
+                        blocked_task_types: Vec::new(),
+                    },
+                    owner_id,
+                )
+                .await
+                .expect("node registration should succeed")
+        }
+    };
+    let submit = |preferred: &[&str], strictness: RegionStrictness| {
+        let state = &state;
+        let task = TaskSubmission {
+            task_type: "computation".to_string(),
+            wasm_module: None,
+            inputs: serde_json::json!({"job": "decisions"}),
+            requirements: TaskRequirements {
+                min_nodes: 1,
+                max_execution_time_sec: 300,
+                require_gpu: false,
+                require_proof: false,
+                scheduling_mode: SchedulingMode::Standard,
+                max_retries: 0,
+                retry_backoff_sec: 0,
+                egress: vec![],
+                checkpointable: false,
+                node_selector: Default::default(),
+                diversity: Default::default(),
+                preferred_regions: preferred.iter().map(|r| r.to_string()).collect(),
+                excluded_regions: vec![],
+                region_strictness: strictness,
+            },
+            priority: 0,
+        };
+        async move {
+            state
+                .submit_task(task, owner_id)
+                .await
+                .expect("task submission should succeed")
+        }
+    };
+
+    register("decisions-us-1", "us-east").await;
+    register("decisions-eu-1", "eu-west").await;
+
+    // A placement records the candidates, the nodes offered and those taken.
+    let placed = submit(&["eu-west"], RegionStrictness::Prefer).await;
+    let task_id = Uuid::parse_str(&placed.task_id).unwrap();
+    let decisions = state
+        .list_scheduler_decisions(&SchedulerDecisionsQuery {
+            task_id: Some(task_id),
+            ..Default::default()
+        })
+        .await
+        .expect("list decisions");
+    assert_eq!(decisions.len(), 1);
+    let placement = &decisions[0];
+    assert_eq!(placement.kind, "placement");
+    assert_eq!(placement.chosen[0], "decisions-eu-1");
+    assert_eq!(placement.attached, vec!["decisions-eu-1"]);
+    assert_eq!(placement.candidate_set_hash.len(), 64);
+
+    let replay = state
+        .replay_scheduler_decision(placement.decision_id)
+        .await
+        .expect("replay placement");
+    assert!(replay.matches, "{replay:?}");
+    assert!(replay.inputs_intact);
+    assert!(replay.changed_rules.is_empty());
+
+    // A task waiting for its region is picked up by the node that brings it.
+    let waiting = submit(&["ap-south"], RegionStrictness::Require).await;
+    assert!(waiting.assigned_nodes.is_empty());
+    register("decisions-ap-1", "ap-south").await;
+    let decisions = state
+        .list_scheduler_decisions(&SchedulerDecisionsQuery {
+            task_id: Some(Uuid::parse_str(&waiting.task_id).unwrap()),
+            ..Default::default()
+        })
+        .await
+        .expect("list decisions");
+    assert_eq!(decisions.len(), 1);
+    let pickup = &decisions[0];
+    assert_eq!(pickup.kind, "pickup");
+    assert_eq!(pickup.node_id.as_deref(), Some("decisions-ap-1"));
+    assert_eq!(pickup.attached, vec![waiting.task_id.clone()]);
+
+    let replay = state
+        .replay_scheduler_decision(pickup.decision_id)
+        .await
+        .expect("replay pickup");
+    assert!(replay.matches, "{replay:?}");
+    assert_eq!(replay.replayed, pickup.chosen);
+
+    let by_node = state
+        .list_scheduler_decisions(&SchedulerDecisionsQuery {
+            node_id: Some("decisions-eu-1".to_string()),
+            kind: Some("placement".to_string()),
+            ..Default::default()
+        })
+        .await
+        .expect("list decisions");
+    assert!(by_node
+        .iter()
+        .any(|decision| decision.decision_id == placement.decision_id));
+    assert!(state
+        .list_scheduler_decisions(&SchedulerDecisionsQuery {
+            kind: Some("bogus".to_string()),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+    assert!(state.replay_scheduler_decision(i64::MAX).await.is_err());
+
+    sqlx::query(
+        "TRUNCATE TABLE scheduler_decisions, task_assignments, tasks, nodes, users CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables after integration test");
+}
+
+#[tokio::test]
+async fn test_cluster_exports_are_snapshot_files_and_incremental() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_cluster_exports_are_snapshot_files_and_incremental — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query(
+        "TRUNCATE TABLE cluster_exports, connect_sessions, task_assignments, tasks, nodes, users \
+         CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables before integration test");
+
+    use api_server::cluster_export::{
+        AnonymizationPolicy, ClusterExportRequest, ClusterExportsQuery, ExportFormat,
+        IdentifierMode,
+    };
+
+    let root = std::env::temp_dir().join(format!("cluster-exports-{}", Uuid::new_v4()));
+    let state = AppState::new(Some(pool.clone())).with_artifact_store(std::sync::Arc::new(
+        api_server::artifacts::FilesystemArtifactStore::new(&root),
+    ));
+    let admin_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, 'x')")
+        .bind(admin_id)
+        .bind(format!("exports-admin-{admin_id}"))
+        .execute(&pool)
+        .await
+        .expect("create export admin");
+
+    for node_id in ["export-node-1", "export-node-2"] {
+        state
+            .register_node(
+                NodeRegistration {
+                    node_id: node_id.to_string(),
+                    region: "eu-west".to_string(),
+                    node_type: "compute".to_string(),
+                    capabilities: NodeCapabilities {
+                        bandwidth_mbps: 500.0,
+                        cpu_cores: 8,
+                        memory_gb: 16.0,
+                        gpu_available: false,
+                    },
+                    observability_port: None,
+                    benchmark_ops_per_wh: None,
+                    secrets_public_key: None,
+                    slots: None,
+                    signing_public_key: None,
+                    labels: Default::default(),
+                    asn: None,
+                    allowed_task_types: Vec::new(),
+                    blocked_task_types: Vec::new(),
+                },
+                admin_id,
+            )
+            .await
+            .expect("node registration should succeed");
+    }
+    let task = state
+        .submit_task(
+            TaskSubmission {
+                task_type: "computation".to_string(),
+                wasm_module: None,
+                inputs: serde_json::json!({"job": "export"}),
+                requirements: TaskRequirements {
+                    min_nodes: 1,
+                    max_execution_time_sec: 300,
+                    require_gpu: false,
+                    require_proof: false,
+                    scheduling_mode: SchedulingMode::Standard,
+                    max_retries: 0,
+                    retry_backoff_sec: 0,
+                    egress: vec![],
+                    checkpointable: false,
+                    node_selector: Default::default(),
+                    diversity: Default::default(),
+                    preferred_regions: vec![],
+                    excluded_regions: vec![],
+                    region_strictness: RegionStrictness::Prefer,
+                },
+                priority: 0,
+            },
+            admin_id,
+        )
+        .await
+        .expect("task submission should succeed");
+    assert_eq!(task.assigned_nodes.len(), 1);
+
+    let rows = |export: &api_server::cluster_export::ClusterExport, table: &str| {
+        export
+            .files
+            .iter()
+            .find(|file| file.table == table)
+            .map(|file| file.rows)
+    };
+
+    // A full CSV export keeping identifiers.
+    let full = state
+        .create_cluster_export(
+            ClusterExportRequest {
+                format: ExportFormat::Csv,
+                incremental: false,
+                policy: Some(AnonymizationPolicy {
+                    identifiers: IdentifierMode::Keep,
+                    drop_columns: vec!["nodes.region".to_string()],
+                    timestamp_precision_secs: 0,
+                }),
+            },
+            admin_id,
+        )
+        .await
+        .expect("start export");
+    assert_eq!(full.status, "running");
+    assert!(full.stable_identifiers);
+
+    // Only one export runs at a time.
+    let busy = state
+        .create_cluster_export(ClusterExportRequest::default(), admin_id)
+        .await
+        .expect_err("a second export conflicts");
+    assert_eq!(busy.status_code, axum::http::StatusCode::CONFLICT);
+
+    let full_id = Uuid::parse_str(&full.export_id).unwrap();
+    state.run_cluster_export(full_id).await;
+    let full = state.get_cluster_export(full_id).await.expect("get export");
+    assert_eq!(full.status, "completed", "{:?}", full.error);
+    assert!(full.snapshot_at.is_some());
+    assert_eq!(full.since, None);
+    assert_eq!(full.files.len(), 5);
+    assert_eq!(rows(&full, "nodes"), Some(2));
+    assert_eq!(rows(&full, "tasks"), Some(1));
+    assert_eq!(rows(&full, "task_assignments"), Some(1));
+    assert_eq!(rows(&full, "connect_sessions"), Some(0));
+
+    let (format, bytes) = state
+        .get_cluster_export_file(full_id, "nodes")
+        .await
+        .expect("download nodes");
+    assert_eq!(format, ExportFormat::Csv);
+    let csv = String::from_utf8(bytes).unwrap();
+    let header = csv.lines().next().unwrap();
+    assert!(header.starts_with("node_id,owner_id,org_id,node_type,"));
+    assert!(!header.contains("region"));
+    assert!(csv.contains("export-node-1,"));
+    assert!(state
+        .get_cluster_export_file(full_id, "users")
+        .await
+        .is_err());
+
+    // An incremental Parquet export with hashed identifiers only carries
+    // what changed since the full export's snapshot.
+    for table in ["nodes", "tasks"] {
+        sqlx::query(&format!(
+            "UPDATE {table} SET updated_at = NOW() - interval '1 hour'"
+        ))
+        .execute(&pool)
+        .await
+        .expect("age rows");
+    }
+    sqlx::query(
+        "UPDATE task_assignments SET assigned_at = NOW() - interval '1 hour', \
+         execution_started_at = NULL",
+    )
+    .execute(&pool)
+    .await
+    .expect("age assignments");
+    sqlx::query("UPDATE nodes SET updated_at = NOW() WHERE node_id = 'export-node-2'")
+        .execute(&pool)
+        .await
+        .expect("touch node");
+
+    let incremental = state
+        .create_cluster_export(
+            ClusterExportRequest {
+                format: ExportFormat::Parquet,
+                incremental: true,
+                policy: Some(AnonymizationPolicy::default()),
+            },
+            admin_id,
+        )
+        .await
+        .expect("start incremental export");
+    assert_eq!(
+        incremental.since,
+        full.snapshot_at
+            .map(|at| at - chrono::Duration::seconds(300))
+    );
+    let incremental_id = Uuid::parse_str(&incremental.export_id).unwrap();
+    state.run_cluster_export(incremental_id).await;
+    let incremental = state
+        .get_cluster_export(incremental_id)
+        .await
+        .expect("get export");
+    assert_eq!(incremental.status, "completed", "{:?}", incremental.error);
+    assert_eq!(rows(&incremental, "nodes"), Some(1));
+    assert_eq!(rows(&incremental, "tasks"), Some(0));
+    assert_eq!(rows(&incremental, "task_assignments"), Some(0));
+    let (format, bytes) = state
+        .get_cluster_export_file(incremental_id, "nodes")
+        .await
+        .expect("download nodes");
+    assert_eq!(format, ExportFormat::Parquet);
+    assert!(bytes.starts_with(b"PAR1"));
+    assert!(!bytes
+        .windows(b"export-node-2".len())
+        .any(|window| window == b"export-node-2"));
+
+    // Unknown columns in a policy are rejected rather than ignored.
+    assert!(state
+        .create_cluster_export(
+            ClusterExportRequest {
+                policy: Some(AnonymizationPolicy {
+                    drop_columns: vec!["nodes.regoin".to_string()],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            admin_id,
+        )
+        .await
+        .is_err());
+
+    let listed = state
+        .list_cluster_exports(&ClusterExportsQuery::default())
+        .await
+        .expect("list exports");
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].export_id, incremental.export_id);
+
+    sqlx::query(
+        "TRUNCATE TABLE cluster_exports, connect_sessions, task_assignments, tasks, nodes, users \
+         CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("cleanup tables after integration test");
+    let _ = std::fs::remove_dir_all(root);
+}
+
+#[tokio::test]
+async fn test_api_key_scopes_gate_protected_routes() {
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_api_key_scopes_gate_protected_routes — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    std::env::set_var(
+        "JWT_SECRET",
+        "api-key-scope-test-secret-that-is-long-enough-0123",
+    );
+    let router = api_server::create_router(std::sync::Arc::new(AppState::new(Some(pool))));
+
+    let call = |method: Method, uri: &str, credential: (&str, String), body: serde_json::Value| {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(credential.0, credential.1)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let router = router.clone();
+        async move {
+            let response = router.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            (
+                status,
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default(),
+            )
+        }
+    };
+    let anonymous = || ("x-request-source", "test".to_string());
+
+    let username = format!("keys_{}", &Uuid::new_v4().simple().to_string()[..12]);
+    let credentials = serde_json::json!({"username": username, "password": "correct-horse-9"});
+    let (status, _) = call(
+        Method::POST,
+        "/api/v1/auth/register",
+        anonymous(),
+        credentials.clone(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, login) = call(Method::POST, "/api/v1/auth/login", anonymous(), credentials).await;
+    assert_eq!(status, StatusCode::OK);
+    let jwt = || {
+        (
+            header::AUTHORIZATION.as_str(),
+            format!("Bearer {}", login["access_token"].as_str().unwrap()),
+        )
+    };
+
+    let (status, created) = call(
+        Method::POST,
+        "/api/v1/auth/api-keys",
+        jwt(),
+        serde_json::json!({"name": "read-only", "scopes": ["tasks:read"]}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let key = || {
+        (
+            "x-api-key",
+            created["api_key"].as_str().unwrap().to_string(),
+        )
+    };
+
+    let (status, _) = call(Method::GET, "/api/v1/tasks", key(), serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = call(Method::POST, "/api/v1/tasks", key(), serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body["message"].as_str().unwrap().contains("tasks:write"));
+
+    // A narrow key cannot mint a broader one.
+    let (status, _) = call(
+        Method::POST,
+        "/api/v1/auth/api-keys",
+        key(),
+        serde_json::json!({"name": "escalated", "scopes": ["tasks:write"]}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_jwt_signing_keys_are_shared_through_the_database() {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!(
+                "Skipping test_jwt_signing_keys_are_shared_through_the_database — no TEST_DATABASE_URL set"
+            );
+            return;
+        }
+    };
+
+    let pool = PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for integration test");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("migrations should apply successfully for integration test");
+
+    sqlx::query("TRUNCATE TABLE jwt_signing_keys")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables before integration test");
+
+    use api_server::auth::AuthConfig;
+    let secret = "key-store-test-secret-that-is-long-enough-0123456789";
+    let replica = || AuthConfig::new(secret.to_string(), 24, Some(3600));
+
+    // Two replicas start with different random keys and converge on the
+    // stored ones.
+    let first = replica().with_key_store(pool.clone()).await.unwrap();
+    let second = replica();
+    assert_ne!(first.jwks().keys[1].x, second.jwks().keys[1].x);
+    let second = second.with_key_store(pool.clone()).await.unwrap();
+    assert_eq!(first.jwks().keys, second.jwks().keys);
+
+    let token = first
+        .generate_token("user-1".into(), "alice".into(), "user".into())
+        .unwrap();
+    assert_eq!(second.validate_token(&token).unwrap().sub, "user-1");
+
+    // Rotating either replica picks up the key the other published.
+    let now = chrono::Utc::now().timestamp() as u64;
+    let rotated = first.rotate_signing_keys_at(now + 3600).await.unwrap();
+    assert_eq!(
+        second.rotate_signing_keys_at(now + 3600).await.unwrap(),
+        rotated
+    );
+    assert_eq!(first.jwks().keys, second.jwks().keys);
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM jwt_signing_keys")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    // The key that fell out of the ring was deleted.
+    assert_eq!(stored as usize, first.jwks().keys.len());
+
+    // A replica with another secret cannot open the stored keys.
+    assert!(AuthConfig::new(
+        "another-secret-that-is-long-enough-0123456789".into(),
+        24,
+        Some(3600)
+    )
+    .with_key_store(pool.clone())
+    .await
+    .is_err());
+
+    sqlx::query("TRUNCATE TABLE jwt_signing_keys")
+        .execute(&pool)
+        .await
+        .expect("cleanup tables after integration test");
+}
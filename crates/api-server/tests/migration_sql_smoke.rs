@@ -17,3 +17,35 @@ async fn migration_sql_files_are_postgres_compatible() {
         .await
         .expect("migrations should apply successfully on PostgreSQL");
 }
+
+#[tokio::test]
+async fn concurrent_migration_runs_serialise_on_the_lock() {
+    let db_url = match std::env::var("DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            println!("Skipping migration lock test — no DATABASE_URL set");
+            return;
+        }
+    };
+
+    let pool = sqlx::PgPool::connect(&db_url)
+        .await
+        .expect("connect to postgres for migration lock test");
+    let timeout = std::time::Duration::from_secs(60);
+
+    let (first, second) = tokio::join!(
+        api_server::migrations::migrate(&pool, false, timeout),
+        api_server::migrations::migrate(&pool, false, timeout),
+    );
+    first.expect("first migration run should succeed");
+    second.expect("second migration run should succeed");
+
+    let dry_run = api_server::migrations::migrate(&pool, true, timeout)
+        .await
+        .expect("dry run on a current schema should succeed");
+    assert!(dry_run.pending.is_empty());
+    let status = api_server::migrations::verify_schema(&pool)
+        .await
+        .expect("schema should match this build after migrating");
+    assert!(status.is_current());
+}
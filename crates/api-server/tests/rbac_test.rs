@@ -0,0 +1,123 @@
+use api_server::{auth::AuthConfig, auth::Claims, create_router, state::AppState};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use http_body_util::BodyExt;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+const SECRET: &str = "rbac-test-secret-that-is-long-enough-0123456789";
+
+fn router() -> (axum::Router, AuthConfig) {
+    std::env::set_var("JWT_SECRET", SECRET);
+    // Tokens below are HS256-signed with the secret, the legacy path.
+    let config = AuthConfig::from_env()
+        .unwrap()
+        .with_legacy_hs256_until(Some(chrono::Utc::now() + chrono::Duration::hours(1)));
+    let state = AppState::new(None).with_auth_config(config.clone());
+    (create_router(Arc::new(state)), config)
+}
+
+fn token_with_scopes(scopes: Option<Vec<&str>>) -> String {
+    let mut claims = Claims::new(
+        uuid::Uuid::new_v4().to_string(),
+        "scoped".to_string(),
+        "user".to_string(),
+        1,
+    );
+    claims.scopes = scopes.map(|scopes| scopes.into_iter().map(str::to_string).collect());
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(SECRET.as_bytes()),
+    )
+    .unwrap()
+}
+
+async fn call(
+    router: &axum::Router,
+    method: Method,
+    uri: &str,
+    token: &str,
+) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{}"))
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn test_scopes_gate_protected_routes() {
+    let (router, config) = router();
+
+    let user = config
+        .generate_token(
+            uuid::Uuid::new_v4().to_string(),
+            "alice".to_string(),
+            "user".to_string(),
+        )
+        .unwrap();
+    let (status, _) = call(&router, Method::GET, "/api/v1/tasks", &user).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = call(&router, Method::GET, "/api/v1/admin/audit-log", &user).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["required_scope"], "admin:audit");
+
+    let read_only = token_with_scopes(Some(vec!["tasks:read"]));
+    let (status, _) = call(&router, Method::GET, "/api/v1/tasks", &read_only).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = call(&router, Method::POST, "/api/v1/tasks", &read_only).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["required_scope"], "tasks:write");
+    assert_eq!(
+        body["details"]["granted_scopes"],
+        serde_json::json!(["tasks:read"])
+    );
+
+    // Tokens issued before scopes existed fall back to the role's scopes.
+    let legacy = token_with_scopes(None);
+    let (status, _) = call(&router, Method::GET, "/api/v1/tasks", &legacy).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_scoped_credentials_cannot_mint_broader_keys() {
+    let (router, _) = router();
+
+    // Managing keys is a scope of its own.
+    let read_only = token_with_scopes(Some(vec!["tasks:read"]));
+    let (status, body) = call(&router, Method::POST, "/api/v1/auth/api-keys", &read_only).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["required_scope"], "auth:manage");
+    let (status, _) = call(&router, Method::GET, "/api/v1/auth/api-keys", &read_only).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Holding it does not let a credential hand on scopes it lacks.
+    let key_manager = token_with_scopes(Some(vec!["auth:manage", "tasks:read"]));
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/api/v1/auth/api-keys")
+        .header(header::AUTHORIZATION, format!("Bearer {key_manager}"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            r#"{"name": "escalated", "scopes": ["tasks:read", "nodes:manage"]}"#,
+        ))
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(
+        body["details"]["denied_scopes"],
+        serde_json::json!(["nodes:manage"])
+    );
+}
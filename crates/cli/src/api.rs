@@ -0,0 +1,265 @@
+//! Minimal API client shared by the operator commands
+//!
+//! Calls run under the request budgets of [`ambient_node::request_budget`]:
+//! clock probes under `heartbeat`, everything else under `control`.
+use ambient_node::{BudgetedClient, ClockSample, EndpointClass, RequestBudgets};
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Tokens returned by login or refresh.
+pub struct Session {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+}
+
+pub struct ApiClient {
+    base_url: String,
+    client: BudgetedClient,
+}
+
+impl ApiClient {
+    pub fn new(base_url: &str) -> Result<Self> {
+        Self::with_budgets(base_url, RequestBudgets::default())
+    }
+
+    pub fn with_budgets(base_url: &str, budgets: RequestBudgets) -> Result<Self> {
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: BudgetedClient::new(budgets)?,
+        })
+    }
+
+    fn http(&self) -> &reqwest::Client {
+        self.client.client()
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/v1{}", self.base_url, path)
+    }
+
+    /// Authenticate with an API key (`vcp_...`) or an access token.
+    fn authorize(&self, request: reqwest::RequestBuilder, token: &str) -> reqwest::RequestBuilder {
+        if token.starts_with("vcp_") {
+            request.header("X-API-Key", token)
+        } else {
+            request.bearer_auth(token)
+        }
+    }
+
+    /// Send a request to `endpoint` under `class`'s budget and decode the
+    /// JSON body, turning API errors into their `message`.
+    async fn send(
+        &self,
+        class: EndpointClass,
+        endpoint: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Value> {
+        let response = self.client.send(class, endpoint, request).await?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let message = body["message"].as_str().unwrap_or("no error message");
+            bail!("API returned {}: {}", status, message);
+        }
+        Ok(body)
+    }
+
+    /// Server version from `GET /health`.
+    pub async fn health(&self) -> Result<String> {
+        let body = self
+            .send(
+                EndpointClass::Control,
+                "health",
+                self.http().get(self.url("/health")),
+            )
+            .await?;
+        Ok(body["version"].as_str().unwrap_or("unknown").to_string())
+    }
+
+    pub async fn register(
+        &self,
+        username: &str,
+        password: &str,
+        email: Option<&str>,
+    ) -> Result<()> {
+        self.send(
+            EndpointClass::Control,
+            "register",
+            self.http().post(self.url("/auth/register")).json(&json!({
+                "username": username,
+                "password": password,
+                "email": email,
+            })),
+        )
+        .await
+        .context("Registration failed")?;
+        Ok(())
+    }
+
+    pub async fn login(&self, username: &str, password: &str) -> Result<Session> {
+        let body = self
+            .send(
+                EndpointClass::Control,
+                "login",
+                self.http().post(self.url("/auth/login")).json(&json!({
+                    "username": username,
+                    "password": password,
+                })),
+            )
+            .await
+            .context("Login failed")?;
+        session_from(&body)
+    }
+
+    /// Exchange a refresh token; the server rotates it, so callers must keep
+    /// the returned one.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<Session> {
+        let body = self
+            .send(
+                EndpointClass::Control,
+                "refresh",
+                self.http()
+                    .post(self.url("/auth/refresh"))
+                    .json(&json!({ "refresh_token": refresh_token })),
+            )
+            .await
+            .context("Token refresh failed")?;
+        session_from(&body)
+    }
+
+    pub async fn register_node(&self, token: &str, registration: &Value) -> Result<()> {
+        self.send(
+            EndpointClass::Control,
+            "register-node",
+            self.authorize(self.http().post(self.url("/nodes")), token)
+                .json(registration),
+        )
+        .await
+        .context("Node registration failed")?;
+        Ok(())
+    }
+
+    /// The node as `GET /nodes/{node_id}` reports it.
+    pub async fn node(&self, token: &str, node_id: &str) -> Result<Value> {
+        self.send(
+            EndpointClass::Control,
+            "node",
+            self.authorize(
+                self.http().get(self.url(&format!("/nodes/{}", node_id))),
+                token,
+            ),
+        )
+        .await
+    }
+
+    /// Apply a partial update with `PATCH /nodes/{node_id}` and return the
+    /// updated node.
+    pub async fn update_node(&self, token: &str, node_id: &str, update: &Value) -> Result<Value> {
+        self.send(
+            EndpointClass::Control,
+            "update-node",
+            self.authorize(
+                self.http().patch(self.url(&format!("/nodes/{}", node_id))),
+                token,
+            )
+            .json(update),
+        )
+        .await
+        .context("Node update failed")
+    }
+
+    /// A task's provenance bundle from `GET /tasks/{task_id}/provenance`.
+    pub async fn task_provenance(&self, token: &str, task_id: &str) -> Result<Value> {
+        self.send(
+            EndpointClass::Control,
+            "task-provenance",
+            self.authorize(
+                self.http()
+                    .get(self.url(&format!("/tasks/{}/provenance", task_id))),
+                token,
+            ),
+        )
+        .await
+        .with_context(|| format!("Failed to fetch provenance of task {}", task_id))
+    }
+
+    /// Module bytes from the registry's `GET /modules/{module_hash}`.
+    pub async fn module(&self, token: &str, module_hash: &str) -> Result<Vec<u8>> {
+        let response = self
+            .client
+            .send(
+                EndpointClass::Control,
+                "module",
+                self.authorize(
+                    self.http()
+                        .get(self.url(&format!("/modules/{}", module_hash))),
+                    token,
+                ),
+            )
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body: Value = response.json().await.unwrap_or(Value::Null);
+            let message = body["message"].as_str().unwrap_or("no error message");
+            bail!("API returned {}: {}", status, message);
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Check a credential by listing the caller's API keys, which any
+    /// authenticated user may do.
+    pub async fn check_auth(&self, token: &str) -> Result<()> {
+        self.send(
+            EndpointClass::Control,
+            "api-keys",
+            self.authorize(self.http().get(self.url("/auth/api-keys")), token),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// One NTP-style exchange with `GET /time`.
+    pub async fn clock_sample(&self) -> Result<ClockSample> {
+        let t0 = unix_millis();
+        let body = self
+            .send(
+                EndpointClass::Heartbeat,
+                "time",
+                self.http().get(self.url("/time")),
+            )
+            .await?;
+        let t3 = unix_millis();
+        let field = |name: &str| {
+            body[name]
+                .as_i64()
+                .ok_or_else(|| anyhow!("Time response has no {}", name))
+        };
+        Ok(ClockSample::from_exchange(
+            t0,
+            field("received_at_ms")?,
+            field("transmitted_at_ms")?,
+            t3,
+        ))
+    }
+}
+
+fn session_from(body: &Value) -> Result<Session> {
+    Ok(Session {
+        access_token: body["access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Response has no access token"))?
+            .to_string(),
+        refresh_token: body["refresh_token"].as_str().map(str::to_string),
+    })
+}
+
+fn unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64)
+}
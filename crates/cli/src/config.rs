@@ -0,0 +1,156 @@
+//! Operator config written by `setup` and read by `doctor`
+use ambient_node::{DeploymentProfile, DeploymentSettings};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE: &str = "config.json";
+
+/// Capabilities advertised at registration, as `NodeCapabilities` in the API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectedCapabilities {
+    pub bandwidth_mbps: f64,
+    pub cpu_cores: u32,
+    pub memory_gb: f64,
+    pub gpu_available: bool,
+}
+
+/// What setup wrote to `config.json`.  Key files sit next to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeConfig {
+    pub api_url: String,
+    pub username: String,
+    pub node_id: String,
+    pub region: String,
+    pub node_type: String,
+    pub capabilities: DetectedCapabilities,
+    pub secrets_key_file: PathBuf,
+    pub signing_key_file: PathBuf,
+    /// Latest refresh token, rotated whenever a command uses it
+    pub refresh_token: Option<String>,
+    /// Task types this node accepts; empty accepts every type
+    #[serde(default)]
+    pub allowed_task_types: Vec<String>,
+    /// Task types this node refuses, even if also allowed
+    #[serde(default)]
+    pub blocked_task_types: Vec<String>,
+    /// Preset for backhaul, gateway, sandbox and request settings; `None`
+    /// keeps the built-in defaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployment_profile: Option<DeploymentProfile>,
+    /// Individual settings applied on top of the profile, shaped like
+    /// `DeploymentSettings`
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub profile_overrides: serde_json::Value,
+}
+
+impl NodeConfig {
+    /// Read `config.json` from `dir`, or `None` when setup has not run.
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let raw =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&raw)
+            .map(Some)
+            .with_context(|| format!("{} is not a valid config", path.display()))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        write_private(&dir.join(CONFIG_FILE), &serde_json::to_vec_pretty(self)?)
+    }
+
+    /// The profile's settings with this config's overrides applied.
+    pub fn deployment_settings(&self) -> Result<DeploymentSettings> {
+        DeploymentSettings::resolve(self.deployment_profile, &self.profile_overrides)
+            .context("Invalid profile_overrides in config")
+    }
+}
+
+/// Deployment settings for commands that run without setup: `profile`, or
+/// else the config's, with the config's overrides applied.  A missing config
+/// just means built-in defaults.
+pub fn resolve_deployment_settings(
+    profile: Option<DeploymentProfile>,
+    config_dir: Option<&Path>,
+) -> Result<DeploymentSettings> {
+    let config_dir = match config_dir {
+        Some(dir) => Some(dir.to_path_buf()),
+        None => default_config_dir().ok(),
+    };
+    let config = match &config_dir {
+        Some(dir) => NodeConfig::load(dir)?,
+        None => None,
+    };
+    match config {
+        Some(config) => DeploymentSettings::resolve(
+            profile.or(config.deployment_profile),
+            &config.profile_overrides,
+        )
+        .context("Invalid profile_overrides in config"),
+        None => DeploymentSettings::resolve(profile, &serde_json::Value::Null),
+    }
+}
+
+/// `$AMBIENT_VCP_CONFIG_DIR`, else `$XDG_CONFIG_HOME/ambient-vcp`, else
+/// `~/.config/ambient-vcp`.
+pub fn default_config_dir() -> Result<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    resolve_config_dir(
+        var("AMBIENT_VCP_CONFIG_DIR").map(PathBuf::from),
+        var("XDG_CONFIG_HOME").map(PathBuf::from),
+        var("HOME").map(PathBuf::from),
+    )
+    .ok_or_else(|| anyhow!("Cannot find a config directory; pass --config-dir"))
+}
+
+fn resolve_config_dir(
+    explicit: Option<PathBuf>,
+    xdg_config_home: Option<PathBuf>,
+    home: Option<PathBuf>,
+) -> Option<PathBuf> {
+    explicit
+        .or_else(|| xdg_config_home.map(|dir| dir.join("ambient-vcp")))
+        .or_else(|| home.map(|dir| dir.join(".config").join("ambient-vcp")))
+}
+
+/// Write a file only its owner can read.
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_config_dir_precedence() {
+        let home = Some(PathBuf::from("/home/op"));
+        assert_eq!(
+            resolve_config_dir(None, None, home.clone()),
+            Some(PathBuf::from("/home/op/.config/ambient-vcp"))
+        );
+        assert_eq!(
+            resolve_config_dir(None, Some(PathBuf::from("/xdg")), home.clone()),
+            Some(PathBuf::from("/xdg/ambient-vcp"))
+        );
+        assert_eq!(
+            resolve_config_dir(Some(PathBuf::from("/etc/vcp")), None, home),
+            Some(PathBuf::from("/etc/vcp"))
+        );
+        assert_eq!(resolve_config_dir(None, None, None), None);
+    }
+}
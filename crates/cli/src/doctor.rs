@@ -0,0 +1,595 @@
+//! `ambient-vcp doctor`: self-test and diagnostics bundle
+//!
+//! Runs each check it can with the config `setup` wrote (or the flags given),
+//! prints one line per check and writes a JSON bundle for support.  Tokens,
+//! passwords and keys are redacted from the bundle.
+use crate::api::ApiClient;
+use crate::config::{default_config_dir, write_private, NodeConfig, CONFIG_FILE};
+use ambient_node::connectivity::backhaul::health::{ProbeResult, ProbeTarget, ProbeType};
+use ambient_node::connectivity::backhaul::{HealthProber, ProbeConfig};
+use ambient_node::{ClockSample, ClockSkewProbe};
+use anyhow::{bail, Result};
+use clap::Args;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use wasm_engine::WasmEngine;
+
+/// Exchanges with `GET /time` used to estimate clock skew.
+const CLOCK_PROBES: usize = 4;
+
+/// Skew above this is worth correcting with `gateway --clock-offset-ms`.
+const CLOCK_SKEW_WARN_MS: i64 = 1_000;
+
+/// The API's default `CLOCK_SKEW_TOLERANCE_SECS`; proofs stamped further
+/// off are rejected.
+const CLOCK_SKEW_FAIL_MS: i64 = 300_000;
+
+/// Environment variables worth including in the bundle.
+const BUNDLE_ENV_PREFIXES: &[&str] =
+    &["AMBIENT_", "VCP_", "WASM_", "NODE_", "GATEWAY_", "RUST_LOG"];
+
+/// Name fragments whose values never leave the machine.
+const SENSITIVE_NAMES: &[&str] = &["TOKEN", "SECRET", "PASSWORD", "KEY", "PEPPER", "CREDENTIAL"];
+
+const REDACTED: &str = "[redacted]";
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+    /// Directory holding config.json (default: as for setup)
+    #[arg(long)]
+    config_dir: Option<PathBuf>,
+
+    /// API server base URL (default: from config)
+    #[arg(long)]
+    api_url: Option<String>,
+
+    /// Access token or API key (default: $AMBIENT_VCP_TOKEN, else a session
+    /// refreshed from the config)
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Node to check (default: from config)
+    #[arg(long)]
+    node_id: Option<String>,
+
+    /// Gateway listen address to test
+    #[arg(long, default_value = "0.0.0.0:7000")]
+    gateway_listen: String,
+
+    /// Where to write the diagnostics bundle
+    /// (default: ./ambient-vcp-diagnostics-<unix time>.json)
+    #[arg(long)]
+    bundle: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+}
+
+/// Everything the checks found, as written to the bundle.
+#[derive(Debug, Default, Serialize)]
+struct Diagnostics {
+    checks: Vec<Check>,
+    config: Option<Value>,
+    node: Option<Value>,
+    clock: Option<ClockSample>,
+    backhaul_probes: Vec<ProbeResult>,
+}
+
+impl Diagnostics {
+    fn record(&mut self, name: &'static str, status: CheckStatus, detail: impl Into<String>) {
+        let check = Check {
+            name,
+            status,
+            detail: detail.into(),
+        };
+        let mark = match check.status {
+            CheckStatus::Pass => "✓",
+            CheckStatus::Warn => "!",
+            CheckStatus::Fail => "✗",
+            CheckStatus::Skip => "-",
+        };
+        println!("  {} {}: {}", mark, check.name, check.detail);
+        self.checks.push(check);
+    }
+
+    fn count(&self, status: CheckStatus) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == status)
+            .count()
+    }
+}
+
+pub async fn run(args: DoctorArgs) -> Result<()> {
+    use CheckStatus::*;
+
+    println!("Running ambient-vcp diagnostics...");
+    let mut diag = Diagnostics::default();
+
+    // Config
+    let config_dir = match args.config_dir {
+        Some(dir) => Some(dir),
+        None => default_config_dir().ok(),
+    };
+    let mut config = None;
+    match &config_dir {
+        None => diag.record("Config", Warn, "no config directory; pass --config-dir"),
+        Some(dir) => match NodeConfig::load(dir) {
+            Ok(Some(loaded)) => {
+                diag.record("Config", Pass, dir.join(CONFIG_FILE).display().to_string());
+                config = Some(loaded);
+            }
+            Ok(None) => diag.record(
+                "Config",
+                Warn,
+                format!("no config in {}; run `ambient-vcp setup`", dir.display()),
+            ),
+            Err(e) => diag.record("Config", Fail, format!("{:#}", e)),
+        },
+    }
+
+    // API reachability
+    let api_url = args
+        .api_url
+        .clone()
+        .or_else(|| config.as_ref().map(|c| c.api_url.clone()));
+    let mut api = None;
+    match &api_url {
+        None => diag.record("API reachable", Skip, "no API URL; pass --api-url"),
+        Some(url) => match ApiClient::new(url) {
+            Ok(client) => match client.health().await {
+                Ok(version) => {
+                    diag.record(
+                        "API reachable",
+                        Pass,
+                        format!("{} (version {})", client.base_url(), version),
+                    );
+                    api = Some(client);
+                }
+                Err(e) => diag.record("API reachable", Fail, format!("{}: {}", url, e)),
+            },
+            Err(e) => diag.record("API reachable", Fail, format!("{:#}", e)),
+        },
+    }
+
+    // Authentication
+    let mut token = None;
+    match &api {
+        None => diag.record("Authentication", Skip, "API not reachable"),
+        Some(api) => {
+            let supplied = args
+                .token
+                .clone()
+                .or_else(|| std::env::var("AMBIENT_VCP_TOKEN").ok())
+                .filter(|token| !token.is_empty());
+            match supplied {
+                Some(supplied) => match api.check_auth(&supplied).await {
+                    Ok(()) => {
+                        diag.record("Authentication", Pass, "supplied credential accepted");
+                        token = Some(supplied);
+                    }
+                    Err(e) => diag.record("Authentication", Fail, format!("{:#}", e)),
+                },
+                None => match (&mut config, &config_dir) {
+                    (Some(cfg), Some(dir)) if cfg.refresh_token.is_some() => {
+                        let refresh_token = cfg.refresh_token.clone().unwrap_or_default();
+                        match api.refresh(&refresh_token).await {
+                            Ok(session) => {
+                                // The server rotated the refresh token; keep the new one.
+                                cfg.refresh_token = session.refresh_token;
+                                let saved = cfg.save(dir);
+                                diag.record(
+                                    "Authentication",
+                                    if saved.is_ok() { Pass } else { Warn },
+                                    match saved {
+                                        Ok(()) => format!("session refreshed for {}", cfg.username),
+                                        Err(e) => format!(
+                                            "session refreshed, but the rotated token was not saved: {:#}",
+                                            e
+                                        ),
+                                    },
+                                );
+                                token = Some(session.access_token);
+                            }
+                            Err(e) => diag.record(
+                                "Authentication",
+                                Fail,
+                                format!("{:#}; run `ambient-vcp setup --force` to log in again", e),
+                            ),
+                        }
+                    }
+                    _ => diag.record(
+                        "Authentication",
+                        Skip,
+                        "no credential; pass --token or run `ambient-vcp setup`",
+                    ),
+                },
+            }
+        }
+    }
+
+    // Node status as the API reports it
+    let node_id = args
+        .node_id
+        .clone()
+        .or_else(|| config.as_ref().map(|c| c.node_id.clone()));
+    match (&api, &token, &node_id) {
+        (Some(api), Some(token), Some(node_id)) => match api.node(token, node_id).await {
+            Ok(node) => {
+                let status = node["status"].as_str().unwrap_or("unknown").to_string();
+                let detail = format!(
+                    "{} is {} (health {:.1}, last seen {})",
+                    node_id,
+                    status,
+                    node["health_score"].as_f64().unwrap_or(0.0),
+                    node["last_seen"].as_str().unwrap_or("never"),
+                );
+                diag.record(
+                    "Node status",
+                    if status == "online" { Pass } else { Warn },
+                    detail,
+                );
+                diag.node = Some(node);
+            }
+            Err(e) => diag.record("Node status", Fail, format!("{}: {:#}", node_id, e)),
+        },
+        (_, _, None) => diag.record("Node status", Skip, "no node ID; pass --node-id"),
+        _ => diag.record("Node status", Skip, "not authenticated"),
+    }
+
+    // Task types the config opts into, as mirrored on the server
+    if let (Some(cfg), Some(node)) = (&config, &diag.node) {
+        let server_list = |field: &str| -> Vec<String> {
+            node[field]
+                .as_array()
+                .map(|types| {
+                    types
+                        .iter()
+                        .filter_map(|t| t.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let in_sync = server_list("allowed_task_types") == cfg.allowed_task_types
+            && server_list("blocked_task_types") == cfg.blocked_task_types;
+        let (status, detail) = if in_sync {
+            (
+                Pass,
+                format!(
+                    "{} allowed, {} blocked",
+                    cfg.allowed_task_types.len(),
+                    cfg.blocked_task_types.len()
+                ),
+            )
+        } else {
+            (
+                Warn,
+                "config and server differ; run `ambient-vcp task-types` to push the config"
+                    .to_string(),
+            )
+        };
+        diag.record("Task types", status, detail);
+    }
+
+    // Deployment profile and the overrides on top of it
+    if let Some(cfg) = &config {
+        match cfg.deployment_settings() {
+            Ok(settings) => diag.record(
+                "Deployment profile",
+                Pass,
+                format!(
+                    "{} (probe every {}s, sandbox {} MB / {}s)",
+                    cfg.deployment_profile
+                        .map_or("none, built-in defaults", |profile| profile.as_str()),
+                    settings.backhaul.probe_config.interval_secs,
+                    settings.sandbox.memory_mb,
+                    settings.sandbox.timeout_seconds
+                ),
+            ),
+            Err(e) => diag.record("Deployment profile", Fail, format!("{:#}", e)),
+        }
+    }
+
+    // Clock skew
+    match &api {
+        None => diag.record("Clock skew", Skip, "API not reachable"),
+        Some(api) => {
+            let mut probe = ClockSkewProbe::new();
+            let mut last_error = None;
+            for _ in 0..CLOCK_PROBES {
+                match api.clock_sample().await {
+                    Ok(sample) => probe.record(sample),
+                    Err(e) => last_error = Some(e),
+                }
+            }
+            match (probe.estimate(), last_error) {
+                (Some(sample), _) => {
+                    let skew = sample.offset_ms.abs();
+                    let detail = format!(
+                        "server clock is {} ms {} (round trip {} ms)",
+                        skew,
+                        if sample.offset_ms >= 0 {
+                            "ahead"
+                        } else {
+                            "behind"
+                        },
+                        sample.round_trip_ms
+                    );
+                    let (status, detail) = if skew > CLOCK_SKEW_FAIL_MS {
+                        (Fail, format!("{}; proofs will be rejected", detail))
+                    } else if skew > CLOCK_SKEW_WARN_MS {
+                        (
+                            Warn,
+                            format!(
+                                "{}; sync the clock or run the gateway with --clock-offset-ms {}",
+                                detail, sample.offset_ms
+                            ),
+                        )
+                    } else {
+                        (Pass, detail)
+                    };
+                    diag.record("Clock skew", status, detail);
+                    diag.clock = Some(sample);
+                }
+                (None, Some(e)) => diag.record("Clock skew", Fail, format!("{:#}", e)),
+                (None, None) => diag.record("Clock skew", Skip, "no samples"),
+            }
+        }
+    }
+
+    // WASM runtime
+    let roots = wasm_engine::module_roots();
+    let missing: Vec<&String> = roots
+        .iter()
+        .filter(|root| !std::path::Path::new(root).is_dir())
+        .collect();
+    if !WasmEngine::runtime_available() {
+        diag.record(
+            "WASM runtime",
+            Warn,
+            "built without the wasm-runtime feature; WASM tasks will fail",
+        );
+    } else if missing.len() == roots.len() {
+        diag.record(
+            "WASM runtime",
+            Warn,
+            format!(
+                "no module root exists ({}); set WASM_ALLOWED_ROOTS",
+                roots.join(", ")
+            ),
+        );
+    } else {
+        diag.record(
+            "WASM runtime",
+            Pass,
+            format!("WasmEdge available, module roots: {}", roots.join(", ")),
+        );
+    }
+
+    // Gateway port
+    match std::net::TcpListener::bind(&args.gateway_listen) {
+        Ok(_) => diag.record(
+            "Gateway port",
+            Pass,
+            format!("{} can be bound", args.gateway_listen),
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => diag.record(
+            "Gateway port",
+            Warn,
+            format!(
+                "{} is in use; a gateway may already be running",
+                args.gateway_listen
+            ),
+        ),
+        Err(e) => diag.record(
+            "Gateway port",
+            Fail,
+            format!("{}: {}", args.gateway_listen, e),
+        ),
+    }
+
+    // Backhaul probes
+    let mut probe_config = ProbeConfig::default();
+    if let Some(target) = api
+        .as_ref()
+        .and_then(|api| control_plane_target(api.base_url()))
+    {
+        probe_config.targets.insert(0, target);
+    }
+    let mut prober = HealthProber::new("default".to_string(), probe_config);
+    let results = prober.probe_once().await;
+    let succeeded = results.iter().filter(|result| result.success).count();
+    let summary = results
+        .iter()
+        .map(|result| match (result.success, result.rtt_ms) {
+            (true, Some(rtt)) => format!("{} {} ms", result.target_name, rtt),
+            _ => format!(
+                "{} failed ({})",
+                result.target_name,
+                result.error.as_deref().unwrap_or("unknown error")
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    diag.record(
+        "Backhaul probes",
+        if succeeded == results.len() {
+            Pass
+        } else if succeeded > 0 {
+            Warn
+        } else {
+            Fail
+        },
+        summary,
+    );
+    diag.backhaul_probes = results;
+
+    // Bundle
+    diag.config = config.as_ref().and_then(|c| serde_json::to_value(c).ok());
+    let bundle_path = args.bundle.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "ambient-vcp-diagnostics-{}.json",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        ))
+    });
+    let bundle = bundle(&diag, std::env::vars());
+    write_private(&bundle_path, &serde_json::to_vec_pretty(&bundle)?)?;
+
+    let failed = diag.count(Fail);
+    println!(
+        "\n{} passed, {} warnings, {} failed, {} skipped",
+        diag.count(Pass),
+        diag.count(Warn),
+        failed,
+        diag.count(Skip)
+    );
+    println!("Diagnostics bundle: {}", bundle_path.display());
+    if failed > 0 {
+        bail!("{} diagnostics checks failed", failed);
+    }
+    Ok(())
+}
+
+/// Probe target for the API host, so backhaul results show whether the
+/// control plane itself is reachable.
+fn control_plane_target(base_url: &str) -> Option<ProbeTarget> {
+    let url = reqwest::Url::parse(base_url).ok()?;
+    Some(ProbeTarget {
+        name: "control-plane".to_string(),
+        address: url.host_str()?.to_string(),
+        port: url.port_or_known_default()?,
+        probe_type: ProbeType::TcpConnect,
+    })
+}
+
+/// The support bundle: findings plus host details, with secrets redacted.
+fn bundle(diag: &Diagnostics, env: impl Iterator<Item = (String, String)>) -> Value {
+    let mut value = json!({
+        "generated_at_unix": SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+        "cli_version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "diagnostics": diag,
+        "environment": bundle_env(env),
+    });
+    redact(&mut value);
+    value
+}
+
+/// Relevant environment variables, sensitive values redacted.
+fn bundle_env(env: impl Iterator<Item = (String, String)>) -> BTreeMap<String, String> {
+    env.filter(|(name, _)| {
+        BUNDLE_ENV_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    })
+    .map(|(name, value)| {
+        let value = if is_sensitive(&name) {
+            REDACTED.to_string()
+        } else {
+            value
+        };
+        (name, value)
+    })
+    .collect()
+}
+
+/// Replace the value of every object key that names a secret.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, entry) in map.iter_mut() {
+                if is_sensitive(key) && !entry.is_null() {
+                    *entry = Value::String(REDACTED.to_string());
+                } else {
+                    redact(entry);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn is_sensitive(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    // Key files are paths, not keys, and public keys are public.
+    if upper.ends_with("_FILE") || upper.contains("PUBLIC_KEY") {
+        return false;
+    }
+    SENSITIVE_NAMES
+        .iter()
+        .any(|fragment| upper.contains(fragment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_redacts_secrets() {
+        let diag = Diagnostics {
+            config: Some(json!({
+                "node_id": "node-1",
+                "refresh_token": "rt_secret",
+                "secrets_key_file": "/etc/vcp/secrets.key",
+            })),
+            node: Some(json!({"signing_public_key": "pk", "status": "online"})),
+            ..Default::default()
+        };
+        let env = [
+            ("AMBIENT_VCP_TOKEN", "jwt"),
+            ("WASM_ALLOWED_ROOTS", "/srv/wasm"),
+            ("NODE_API_KEY", "vcp_abc"),
+            ("HOME", "/root"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let bundle = bundle(&diag, env);
+        let config = &bundle["diagnostics"]["config"];
+        assert_eq!(config["refresh_token"], REDACTED);
+        assert_eq!(config["node_id"], "node-1");
+        assert_eq!(config["secrets_key_file"], "/etc/vcp/secrets.key");
+        assert_eq!(bundle["diagnostics"]["node"]["signing_public_key"], "pk");
+
+        let env = &bundle["environment"];
+        assert_eq!(env["AMBIENT_VCP_TOKEN"], REDACTED);
+        assert_eq!(env["NODE_API_KEY"], REDACTED);
+        assert_eq!(env["WASM_ALLOWED_ROOTS"], "/srv/wasm");
+        assert!(env.get("HOME").is_none());
+        assert!(!bundle.to_string().contains("rt_secret"));
+    }
+
+    #[test]
+    fn test_control_plane_target_uses_url_port() {
+        let target = control_plane_target("https://api.example.com").unwrap();
+        assert_eq!(
+            (target.address.as_str(), target.port),
+            ("api.example.com", 443)
+        );
+        let target = control_plane_target("http://10.0.0.5:3000").unwrap();
+        assert_eq!((target.address.as_str(), target.port), ("10.0.0.5", 3000));
+        assert!(control_plane_target("not a url").is_none());
+    }
+}
@@ -1,18 +1,30 @@
 #[cfg(feature = "observability")]
 use ambient_node::LocalObservabilityServer;
 use ambient_node::{
-    AmbientNode, DataPlaneGateway, GatewayConfig, NodeId, SafetyPolicy, TelemetrySample,
+    AccessLog, AmbientNode, DataPlaneGateway, DeploymentProfile, FirewallBackend, GatewayConfig,
+    GatewaySessionSyncConfig, GatewaySessionSyncer, GatewayTlsConfig, GatewayUsageReporter,
+    HealthScoreConfig, HttpConnectProxy, HttpConnectProxyConfig, NodeId, PolicyBundleCache,
+    PowerEstimate, RequestBudgets, SafetyPolicy, SessionConnectionLimits, SessionsFileReloader,
+    TelemetryCollector, TelemetryCollectorConfig, TransparentBinding, TransparentConfig,
+    DEFAULT_TELEMETRY_INTERVAL_SECS,
 };
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use mesh_coordinator::{MeshCoordinator, TaskAssignmentStrategy};
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
-#[cfg(feature = "observability")]
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{info, Level};
 
+mod api;
+mod config;
+mod doctor;
+mod replay;
+mod service;
+mod setup;
+mod task_types;
+
 #[derive(Parser)]
 #[command(name = "ambient-vcp")]
 #[command(about = "Ambient AI + Verifiable Computation Protocol CLI", long_about = None)]
@@ -21,6 +33,8 @@ struct Cli {
     command: Commands,
 }
 
+// Parsed once at startup; the gateway's many flags are not worth boxing.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Start an ambient node
@@ -45,25 +59,196 @@ enum Commands {
         /// Observability server port (default: 9090)
         #[arg(long, default_value_t = 9090)]
         observability_port: u16,
+
+        /// Seconds between host telemetry samples
+        #[arg(long, default_value_t = DEFAULT_TELEMETRY_INTERVAL_SECS)]
+        telemetry_interval: u64,
+
+        /// HOST:PORT whose TCP connect time is reported as latency, e.g. the
+        /// API server (repeatable)
+        #[arg(long = "latency-probe")]
+        latency_probes: Vec<String>,
+
+        /// Estimated draw at idle, used when the host has no power sensor
+        #[arg(long, requires = "power_max_watts")]
+        power_idle_watts: Option<f64>,
+
+        /// Estimated draw at full CPU, used when the host has no power sensor
+        #[arg(long, requires = "power_idle_watts")]
+        power_max_watts: Option<f64>,
+
+        /// JSON file of health score weights (`bandwidth`, `latency`,
+        /// `compute`, `reputation`, summing to 1); defaults to 40/30/20/10
+        #[arg(long)]
+        health_config: Option<PathBuf>,
     },
 
     /// Start a data-plane gateway for connect_only relay sessions
     Gateway {
-        /// Listen address for the relay tunnel entrypoint
-        #[arg(long, default_value = "0.0.0.0:7000")]
-        listen: String,
+        /// Listen address for the relay tunnel entrypoint (default: 0.0.0.0:7000)
+        #[arg(long)]
+        listen: Option<String>,
 
         /// JSON file containing active gateway sessions
+        #[arg(long, required_unless_present = "sync_sessions")]
+        sessions_file: Option<PathBuf>,
+
+        /// Deployment profile whose gateway defaults apply (default: the
+        /// config's); flags below still override it
+        #[arg(long)]
+        profile: Option<DeploymentProfile>,
+
+        /// Directory holding config.json, read for its profile and overrides
+        /// when present (default: as for setup)
+        #[arg(long)]
+        config_dir: Option<PathBuf>,
+
+        /// Upstream connect timeout in seconds (default: 5, or the profile's)
+        #[arg(long)]
+        connect_timeout_seconds: Option<u64>,
+
+        /// Idle timeout in seconds for handshake + relay sessions (default:
+        /// 600, or the profile's)
+        #[arg(long)]
+        idle_timeout_seconds: Option<u64>,
+
+        /// Coordinator clock minus local clock in milliseconds, applied to
+        /// session expiry checks on nodes with a skewed clock
+        #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+        clock_offset_ms: i64,
+
+        /// Seconds a live relay may outlast its session's expiry before it
+        /// is torn down (default: 5, or the profile's)
+        #[arg(long)]
+        expiry_grace_seconds: Option<u64>,
+
+        /// File caching the signed destination-policy bundle, used for
+        /// allowlist sessions provisioned without destinations
+        #[arg(long, requires = "control_public_key")]
+        policy_bundle: Option<PathBuf>,
+
+        /// Coordinator control public key (base64) that signs policy bundles
+        #[arg(long)]
+        control_public_key: Option<String>,
+
+        /// PEM certificate chain the listener presents; enables TLS, which
+        /// sessions with tunnel_protocol "mtls" require
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+
+        /// PEM private key for --tls-cert
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+
+        /// Also accept SOCKS5 clients on this address (e.g. 0.0.0.0:1080);
+        /// they log in with the session ID and token as username/password
+        #[arg(long)]
+        socks5_listen: Option<String>,
+
+        /// Also serve an HTTP CONNECT proxy on this address (e.g.
+        /// 0.0.0.0:3128); each tunnel authenticates as a session with
+        /// Proxy-Authorization Bearer <token> or Basic <session_id:token>
+        #[arg(long)]
+        http_connect_listen: Option<String>,
+
+        /// Also relay connections the node's firewall redirects here (e.g.
+        /// 0.0.0.0:7070), recovering each one's original destination, so
+        /// endpoints need no proxy settings
+        #[arg(long, requires = "transparent_bind")]
+        transparent_listen: Option<String>,
+
+        /// SOURCE=SESSION_ID: intercepted connections from SOURCE (an
+        /// address or CIDR range) relay under SESSION_ID; repeatable
+        #[arg(long, requires = "transparent_listen")]
+        transparent_bind: Vec<TransparentBinding>,
+
+        /// Interface whose traffic is redirected (default: wlan0)
+        #[arg(long, requires = "transparent_listen")]
+        transparent_interface: Option<String>,
+
+        /// Firewall the redirect rules are written for: iptables or
+        /// nftables (default: iptables)
+        #[arg(long, requires = "transparent_listen")]
+        transparent_firewall: Option<FirewallBackend>,
+
+        /// Install the redirect rules at startup instead of expecting them
+        /// to be in place
+        #[arg(long, requires = "transparent_listen")]
+        transparent_install_rules: bool,
+
+        /// Seconds to cache resolved destination addresses; 0 disables the
+        /// cache (default: 60, or the profile's)
+        #[arg(long)]
+        dns_cache_ttl_seconds: Option<u64>,
+
+        /// Relay to loopback, private and link-local addresses, which are
+        /// refused by default (single-host development only)
+        #[arg(long)]
+        allow_internal_upstreams: bool,
+
+        /// Tunnels one session may hold open at once; 0 is unlimited
+        /// (default: 128, or the profile's)
+        #[arg(long)]
+        max_session_tunnels: Option<u32>,
+
+        /// New connections one session may open per minute; 0 is unlimited
+        /// (default: 600, or the profile's)
+        #[arg(long)]
+        max_session_connections_per_minute: Option<u32>,
+
+        /// Connections one session may open over its lifetime; 0 is
+        /// unlimited (default: 0, or the profile's)
         #[arg(long)]
-        sessions_file: PathBuf,
+        max_session_connections: Option<u64>,
 
-        /// Upstream connect timeout in seconds
+        /// Seconds between re-reads of --sessions-file; sessions added,
+        /// changed or removed in it are applied to the running gateway.
+        /// 0 reads it once at startup
         #[arg(long, default_value_t = 5)]
-        connect_timeout_seconds: u64,
+        sessions_reload_seconds: u64,
+
+        /// Unix socket accepting JSON-line requests to add, revoke and list
+        /// sessions or start a drain at runtime
+        #[arg(long)]
+        admin_socket: Option<PathBuf>,
+
+        /// Poll the control plane for this node's relay sessions, using the
+        /// api_url and node_id in config.json; responses must be signed when
+        /// --control-public-key is given
+        #[arg(long)]
+        sync_sessions: bool,
+
+        /// API key or access token for --sync-sessions (default:
+        /// $AMBIENT_VCP_TOKEN); an API key does not expire
+        #[arg(long, requires = "sync_sessions")]
+        token: Option<String>,
+
+        /// Seconds between --sync-sessions polls
+        #[arg(long, default_value_t = ambient_node::DEFAULT_SESSION_SYNC_SECS)]
+        sessions_sync_seconds: u64,
+
+        /// Seconds between reports of each session's relayed bytes to the
+        /// control plane, with --sync-sessions; 0 disables them
+        #[arg(long, default_value_t = ambient_node::DEFAULT_USAGE_REPORT_SECS)]
+        usage_report_seconds: u64,
+
+        /// Append a JSON line per relayed connection (session, destination,
+        /// bytes, duration, termination reason) to this file
+        #[arg(long)]
+        access_log: Option<PathBuf>,
+
+        /// Size in MiB at which --access-log is rotated
+        #[arg(long, default_value_t = 10, requires = "access_log")]
+        access_log_max_mb: u64,
+
+        /// Rotated --access-log files kept
+        #[arg(long, default_value_t = ambient_node::DEFAULT_ACCESS_LOG_KEEP, requires = "access_log")]
+        access_log_keep: u32,
 
-        /// Idle timeout in seconds for handshake + relay sessions
-        #[arg(long, default_value_t = 600)]
-        idle_timeout_seconds: u64,
+        /// Serve per-session usage counters at
+        /// http://127.0.0.1:<port>/gateway/usage
+        #[arg(long)]
+        observability_port: Option<u16>,
     },
 
     /// Start a mesh coordinator
@@ -84,8 +269,26 @@ enum Commands {
         id: String,
     },
 
-    /// Run health check
-    Health,
+    /// Check API access, node status, clock, WASM runtime, gateway port and
+    /// backhaul, and write a redacted diagnostics bundle
+    #[command(alias = "health")]
+    Doctor(doctor::DoctorArgs),
+
+    /// Guided first-run setup: account, node registration, config and service
+    Setup(setup::SetupArgs),
+
+    /// Choose which task types this node accepts and sync them to the server
+    TaskTypes(task_types::TaskTypesArgs),
+
+    /// Re-execute a finished task locally and check its outputs and trace
+    /// commitments against the signed sandbox reports
+    Replay(replay::ReplayArgs),
+
+    /// Install the node agent as a systemd unit (launchd daemon on macOS)
+    InstallService(service::InstallArgs),
+
+    /// Stop and remove a service created by install-service
+    UninstallService(service::UninstallArgs),
 }
 
 #[tokio::main]
@@ -102,20 +305,165 @@ async fn main() -> Result<()> {
             node_type,
             observability,
             observability_port,
+            telemetry_interval,
+            latency_probes,
+            power_idle_watts,
+            power_max_watts,
+            health_config,
         } => {
-            run_node(id, region, node_type, observability, observability_port).await?;
+            let telemetry = TelemetryCollectorConfig {
+                interval: Duration::from_secs(telemetry_interval.max(1)),
+                latency_targets: latency_probes,
+                power_estimate: power_idle_watts.zip(power_max_watts).map(
+                    |(idle_watts, max_watts)| PowerEstimate {
+                        idle_watts,
+                        max_watts,
+                    },
+                ),
+                ..TelemetryCollectorConfig::default()
+            };
+            let health_config = match health_config {
+                Some(path) => load_health_config(&path)?,
+                None => HealthScoreConfig::default(),
+            };
+            run_node(
+                id,
+                region,
+                node_type,
+                observability,
+                observability_port,
+                telemetry,
+                health_config,
+            )
+            .await?;
         }
         Commands::Gateway {
             listen,
             sessions_file,
+            profile,
+            config_dir,
             connect_timeout_seconds,
             idle_timeout_seconds,
+            clock_offset_ms,
+            expiry_grace_seconds,
+            policy_bundle,
+            control_public_key,
+            tls_cert,
+            tls_key,
+            socks5_listen,
+            http_connect_listen,
+            transparent_listen,
+            transparent_bind,
+            transparent_interface,
+            transparent_firewall,
+            transparent_install_rules,
+            dns_cache_ttl_seconds,
+            allow_internal_upstreams,
+            max_session_tunnels,
+            max_session_connections_per_minute,
+            max_session_connections,
+            sessions_reload_seconds,
+            admin_socket,
+            sync_sessions,
+            token,
+            sessions_sync_seconds,
+            usage_report_seconds,
+            access_log,
+            access_log_max_mb,
+            access_log_keep,
+            observability_port,
         } => {
+            let settings = config::resolve_deployment_settings(profile, config_dir.as_deref())?;
+            let session_sync = if sync_sessions {
+                Some(session_sync_config(
+                    config_dir.as_deref(),
+                    token,
+                    control_public_key.clone(),
+                    settings.requests,
+                )?)
+            } else {
+                None
+            };
+            let policy_cache = policy_bundle
+                .map(|path| PolicyBundleCache::open(path, control_public_key.unwrap_or_default()))
+                .transpose()?;
+            let defaults = settings.gateway;
+            let config = GatewayConfig {
+                listen_addr: listen.unwrap_or(defaults.listen_addr),
+                connect_timeout_seconds: connect_timeout_seconds
+                    .unwrap_or(defaults.connect_timeout_seconds),
+                idle_timeout_seconds: idle_timeout_seconds.unwrap_or(defaults.idle_timeout_seconds),
+                clock_offset_ms,
+                expiry_grace_seconds: expiry_grace_seconds.unwrap_or(defaults.expiry_grace_seconds),
+                tls: tls_cert
+                    .zip(tls_key)
+                    .map(|(cert_path, key_path)| GatewayTlsConfig {
+                        cert_path,
+                        key_path,
+                    })
+                    .or(defaults.tls),
+                socks5_listen_addr: socks5_listen.or(defaults.socks5_listen_addr),
+                dns_cache_ttl_seconds: dns_cache_ttl_seconds
+                    .unwrap_or(defaults.dns_cache_ttl_seconds),
+                session_limits: SessionConnectionLimits {
+                    max_concurrent_tunnels: max_session_tunnels
+                        .unwrap_or(defaults.session_limits.max_concurrent_tunnels),
+                    max_new_connections_per_minute: max_session_connections_per_minute
+                        .unwrap_or(defaults.session_limits.max_new_connections_per_minute),
+                    max_total_connections: max_session_connections
+                        .unwrap_or(defaults.session_limits.max_total_connections),
+                },
+                transparent: match transparent_listen {
+                    Some(listen_addr) => {
+                        let defaults = defaults.transparent.unwrap_or_default();
+                        Some(TransparentConfig {
+                            listen_addr,
+                            interface: transparent_interface.unwrap_or(defaults.interface),
+                            bindings: transparent_bind,
+                            firewall: transparent_firewall.unwrap_or(defaults.firewall),
+                            install_rules: transparent_install_rules || defaults.install_rules,
+                        })
+                    }
+                    None => defaults.transparent,
+                },
+                allow_internal_upstreams: allow_internal_upstreams
+                    || defaults.allow_internal_upstreams,
+            };
+            let http_connect = http_connect_listen.map(|listen_addr| HttpConnectProxyConfig {
+                listen_addr,
+                enabled: true,
+                connect_timeout_secs: config.connect_timeout_seconds,
+                idle_timeout_secs: config.idle_timeout_seconds,
+                allow_internal_upstreams: config.allow_internal_upstreams,
+                ..HttpConnectProxyConfig::default()
+            });
+            info!("Starting data-plane gateway on {}", config.listen_addr);
+            let mut gateway = DataPlaneGateway::new(config, Vec::new());
+            if let Some(cache) = policy_cache {
+                info!(version = ?cache.version(), "Loaded destination-policy bundle cache");
+                gateway = gateway.with_policy_cache(cache);
+            }
+            if let Some(path) = access_log {
+                let log = AccessLog::open(
+                    path,
+                    access_log_max_mb.saturating_mul(1024 * 1024),
+                    access_log_keep,
+                )?;
+                info!(path = %log.path().display(), "Writing gateway access log");
+                gateway = gateway.with_access_log(log);
+            }
             run_gateway(
-                listen,
+                gateway,
                 sessions_file,
-                connect_timeout_seconds,
-                idle_timeout_seconds,
+                sessions_reload_seconds,
+                admin_socket,
+                session_sync.map(|config| SessionSync {
+                    config,
+                    sync_seconds: sessions_sync_seconds,
+                    usage_report_seconds,
+                }),
+                http_connect,
+                observability_port,
             )
             .await?;
         }
@@ -128,48 +476,79 @@ async fn main() -> Result<()> {
         Commands::Info { id } => {
             show_node_info(id).await?;
         }
-        Commands::Health => {
-            run_health_check().await?;
+        Commands::Doctor(args) => {
+            doctor::run(args).await?;
+        }
+        Commands::Setup(args) => {
+            setup::run(args).await?;
+        }
+        Commands::TaskTypes(args) => {
+            task_types::run(args).await?;
+        }
+        Commands::Replay(args) => {
+            replay::run(args).await?;
+        }
+        Commands::InstallService(args) => {
+            service::install(args)?;
+        }
+        Commands::UninstallService(args) => {
+            service::uninstall(args)?;
         }
     }
 
     Ok(())
 }
 
+/// Read health score weights from a JSON file; the weights are validated as
+/// they are deserialized.
+fn load_health_config(path: &std::path::Path) -> Result<HealthScoreConfig> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+    serde_json::from_str(&raw)
+        .map_err(|e| anyhow::anyhow!("Invalid health config {}: {}", path.display(), e))
+}
+
 async fn run_node(
     id: String,
     region: String,
     node_type: String,
     observability: bool,
     observability_port: u16,
+    telemetry: TelemetryCollectorConfig,
+    health_config: HealthScoreConfig,
 ) -> Result<()> {
     info!("Starting ambient node: {}", id);
 
     let node_id = NodeId::new(&id, &region, &node_type)
         .map_err(|e| anyhow::anyhow!("Invalid node ID: {}", e))?;
     let policy = SafetyPolicy::default();
-    let mut node = AmbientNode::new(node_id, policy);
-
-    // Simulate telemetry collection
-    let telemetry = TelemetrySample {
-        bandwidth_mbps: 100.0,
-        upload_bandwidth_mbps: 0.0,
-        download_bandwidth_mbps: 0.0,
-        avg_latency_ms: 20.0,
-        cpu_usage_percent: 50.0,
-        memory_usage_percent: 60.0,
-        temperature_c: 65.0,
-        power_watts: 150.0,
-        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
-    };
+    let node_arc = Arc::new(RwLock::new(
+        AmbientNode::new(node_id, policy).with_health_config(health_config),
+    ));
 
-    node.ingest_telemetry(telemetry);
+    // Sample host telemetry now and on every interval
+    let (_collector, samples) = TelemetryCollector::new(telemetry)
+        .spawn(Arc::clone(&node_arc))
+        .await;
+    let sample = samples.borrow().clone();
 
     info!("Node ID: {}", id);
     info!("Region: {}", region);
     info!("Type: {}", node_type);
-    info!("Health Score: {:.2}", node.health_score());
-    info!("Safe Mode: {}", node.is_safe_mode());
+    info!(
+        cpu = sample.cpu_usage_percent,
+        memory = sample.memory_usage_percent,
+        temperature = sample.temperature_c,
+        power = sample.power_watts,
+        bandwidth_mbps = sample.bandwidth_mbps,
+        latency_ms = sample.avg_latency_ms,
+        "Telemetry"
+    );
+    {
+        let node = node_arc.read().await;
+        info!("Health Score: {:.2}", node.health_score());
+        info!("Safe Mode: {}", node.is_safe_mode());
+    }
 
     info!("Node running... Press Ctrl+C to stop");
 
@@ -178,9 +557,6 @@ async fn run_node(
     if observability {
         info!("Local observability enabled on port {}", observability_port);
 
-        // Wrap node in Arc<RwLock<>> for shared access
-        let node_arc = Arc::new(RwLock::new(node));
-
         // Create and start observability server
         let server = LocalObservabilityServer::new(observability_port, node_arc);
         server.print_curl_command();
@@ -218,23 +594,117 @@ async fn run_node(
     Ok(())
 }
 
+/// Where `--sync-sessions` polls, from config.json and the token.
+fn session_sync_config(
+    config_dir: Option<&std::path::Path>,
+    token: Option<String>,
+    control_public_key: Option<String>,
+    requests: RequestBudgets,
+) -> Result<GatewaySessionSyncConfig> {
+    let config_dir = match config_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => config::default_config_dir()?,
+    };
+    let node_config = config::NodeConfig::load(&config_dir)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "--sync-sessions needs {} in {}; run `ambient-vcp setup` first",
+            config::CONFIG_FILE,
+            config_dir.display()
+        )
+    })?;
+    let token = token
+        .or_else(|| std::env::var("AMBIENT_VCP_TOKEN").ok())
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("--sync-sessions needs --token or $AMBIENT_VCP_TOKEN"))?;
+    Ok(GatewaySessionSyncConfig {
+        api_url: node_config.api_url,
+        node_id: node_config.node_id,
+        token,
+        control_public_key,
+        requests,
+    })
+}
+
+/// `--sync-sessions` settings.
+struct SessionSync {
+    config: GatewaySessionSyncConfig,
+    sync_seconds: u64,
+    /// 0 leaves usage unreported
+    usage_report_seconds: u64,
+}
+
 async fn run_gateway(
-    listen: String,
-    sessions_file: PathBuf,
-    connect_timeout_seconds: u64,
-    idle_timeout_seconds: u64,
+    gateway: DataPlaneGateway,
+    sessions_file: Option<PathBuf>,
+    sessions_reload_seconds: u64,
+    admin_socket: Option<PathBuf>,
+    session_sync: Option<SessionSync>,
+    http_connect: Option<HttpConnectProxyConfig>,
+    observability_port: Option<u16>,
 ) -> Result<()> {
-    info!("Starting data-plane gateway on {}", listen);
+    if let Some(sessions_file) = sessions_file {
+        let mut reloader = SessionsFileReloader::new(gateway.clone(), &sessions_file);
+        let loaded = reloader.reload().await?;
+        info!(
+            sessions = loaded.upserted.len(),
+            "Loaded gateway sessions file"
+        );
+        if sessions_reload_seconds > 0 {
+            tokio::spawn(reloader.run(Duration::from_secs(sessions_reload_seconds)));
+        }
+    }
 
-    let gateway = DataPlaneGateway::from_sessions_file(
-        GatewayConfig {
-            listen_addr: listen,
-            connect_timeout_seconds,
-            idle_timeout_seconds,
-        },
-        sessions_file,
-    )
-    .await?;
+    if let Some(sync) = session_sync {
+        info!(node_id = %sync.config.node_id, api_url = %sync.config.api_url, "Syncing gateway sessions from the control plane");
+        if sync.usage_report_seconds > 0 {
+            let reporter = GatewayUsageReporter::new(gateway.clone(), sync.config.clone())?;
+            tokio::spawn(reporter.run(Duration::from_secs(sync.usage_report_seconds)));
+        }
+        let syncer = GatewaySessionSyncer::new(gateway.clone(), sync.config)?;
+        tokio::spawn(syncer.run(Duration::from_secs(sync.sync_seconds.max(1))));
+    }
+
+    if let Some(config) = http_connect {
+        info!(listen_addr = %config.listen_addr, "Serving HTTP CONNECT proxy for gateway sessions");
+        let proxy = HttpConnectProxy::new(config).with_gateway(gateway.clone());
+        tokio::spawn(async move {
+            if let Err(err) = proxy.run().await {
+                tracing::error!("HTTP CONNECT proxy stopped: {err:#}");
+            }
+        });
+    }
+
+    if let Some(port) = observability_port {
+        #[cfg(feature = "observability")]
+        {
+            let server = LocalObservabilityServer::for_gateway(port, gateway.usage());
+            server.print_curl_command();
+            tokio::spawn(async move {
+                if let Err(err) = server.run().await {
+                    tracing::error!("Observability server error: {}", err);
+                }
+            });
+        }
+        #[cfg(not(feature = "observability"))]
+        anyhow::bail!("--observability-port {port} needs the observability feature");
+    }
+
+    if let Some(path) = admin_socket {
+        #[cfg(unix)]
+        {
+            let socket = ambient_node::GatewayAdminSocket::bind(gateway.clone(), path)?;
+            tokio::spawn(async move {
+                if let Err(err) = socket.run().await {
+                    tracing::warn!("gateway admin socket stopped: {err:#}");
+                }
+            });
+        }
+        #[cfg(not(unix))]
+        anyhow::bail!(
+            "--admin-socket {} needs Unix domain sockets",
+            path.display()
+        );
+    }
 
     gateway.run().await
 }
@@ -277,17 +747,3 @@ async fn show_node_info(id: String) -> Result<()> {
     info!("This would show detailed node information in a production system");
     Ok(())
 }
-
-async fn run_health_check() -> Result<()> {
-    info!("Running system health check...");
-
-    // Check system components
-    info!("✓ Ambient Node module loaded");
-    info!("✓ Data-plane gateway module loaded");
-    info!("✓ WASM Engine module loaded");
-    info!("✓ ZK Prover module loaded");
-    info!("✓ Mesh Coordinator module loaded");
-
-    info!("All systems operational!");
-    Ok(())
-}
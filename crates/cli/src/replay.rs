@@ -0,0 +1,316 @@
+//! `ambient-vcp replay`: re-execute a finished task and check its commitments
+//!
+//! Fetches the task's provenance bundle, pulls its module from the registry
+//! (or takes `--module`), and re-runs it once per signed sandbox report with
+//! that report's limits and capabilities.  Each replay is checked against the
+//! report: signature, module hash, environment fingerprint, success and trace
+//! hash.  The trace hash commits to the inputs and outputs, so a matching
+//! trace means the replay reproduced the node's output.  Exits non-zero when
+//! any check fails.
+use crate::api::ApiClient;
+use crate::config::{default_config_dir, NodeConfig};
+use ambient_node::{RequestBudgets, SignedSandboxReport};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use serde_json::Value;
+use sha3::{Digest, Sha3_256};
+use std::path::{Path, PathBuf};
+use wasm_engine::{ExecutionTrace, SandboxReport, WasmCall, WasmEngine, WasmResult, WasmRuntime};
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// Task to replay
+    #[arg(long)]
+    task: String,
+
+    /// Only replay the report of this node (default: every report)
+    #[arg(long)]
+    node: Option<String>,
+
+    /// Local copy of the module, for tasks whose module was submitted inline
+    /// rather than uploaded to the registry
+    #[arg(long)]
+    module: Option<PathBuf>,
+
+    /// API base URL (default: the config's api_url)
+    #[arg(long)]
+    api_url: Option<String>,
+
+    /// Directory holding config.json (default: as for setup)
+    #[arg(long)]
+    config_dir: Option<PathBuf>,
+
+    /// Access token or API key (default: $AMBIENT_VCP_TOKEN, else a session
+    /// refreshed from the config)
+    #[arg(long)]
+    token: Option<String>,
+}
+
+/// One comparison between a sandbox report and its replay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Check {
+    fn new(name: &'static str, expected: impl ToString, actual: impl ToString) -> Self {
+        Self {
+            name,
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        }
+    }
+
+    pub fn matches(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/// Compare a replay with the report of the original execution.
+/// `recorded_environment` is the fingerprint the server derived from the
+/// stored report; `replay_environment` that of the engine the replay ran in.
+pub fn compare(
+    report: &SandboxReport,
+    recorded_environment: Option<&str>,
+    replay_environment: &str,
+    result: &WasmResult,
+    trace: &ExecutionTrace,
+) -> Vec<Check> {
+    vec![
+        Check::new("module hash", &report.module_hash, &trace.module_hash),
+        Check::new(
+            "environment",
+            recorded_environment.unwrap_or(&report.environment_fingerprint()),
+            replay_environment,
+        ),
+        Check::new("success", report.success, result.success),
+        Check::new("trace hash", &report.trace_hash, trace.hash()),
+    ]
+}
+
+pub async fn run(args: ReplayArgs) -> Result<()> {
+    if !WasmEngine::runtime_available() {
+        bail!(
+            "this build cannot execute modules; rebuild ambient-vcp with --features wasm-runtime"
+        );
+    }
+
+    let config_dir = match args.config_dir {
+        Some(dir) => dir,
+        None => default_config_dir()?,
+    };
+    let mut config = NodeConfig::load(&config_dir)?;
+    let (api_url, budgets) = match (&args.api_url, &config) {
+        (Some(url), _) => (url.clone(), RequestBudgets::default()),
+        (None, Some(config)) => (
+            config.api_url.clone(),
+            config.deployment_settings()?.requests,
+        ),
+        (None, None) => bail!("no config.json found; pass --api-url"),
+    };
+    let api = ApiClient::with_budgets(&api_url, budgets)?;
+
+    let supplied = args
+        .token
+        .or_else(|| std::env::var("AMBIENT_VCP_TOKEN").ok())
+        .filter(|token| !token.is_empty());
+    let token = match (supplied, config.as_mut()) {
+        (Some(token), _) => token,
+        (None, Some(config)) => {
+            let refresh_token = config.refresh_token.clone().ok_or_else(|| {
+                anyhow!("no credential; pass --token or run `ambient-vcp setup --force`")
+            })?;
+            let session = api.refresh(&refresh_token).await?;
+            // The server rotated the refresh token; keep the new one.
+            config.refresh_token = session.refresh_token;
+            config.save(&config_dir)?;
+            session.access_token
+        }
+        (None, None) => bail!("no credential; pass --token or set AMBIENT_VCP_TOKEN"),
+    };
+
+    let provenance = api.task_provenance(&token, &args.task).await?;
+    let reports: Vec<&Value> = provenance["sandbox_reports"]
+        .as_array()
+        .map(|reports| {
+            reports
+                .iter()
+                .filter(|r| args.node.as_deref().is_none_or(|node| r["node_id"] == node))
+                .collect()
+        })
+        .unwrap_or_default();
+    if reports.is_empty() {
+        bail!(
+            "task {} has no sandbox reports{} to replay against",
+            args.task,
+            args.node
+                .as_deref()
+                .map(|node| format!(" from {}", node))
+                .unwrap_or_default()
+        );
+    }
+
+    let module_hash = provenance["module_hash"]
+        .as_str()
+        .ok_or_else(|| anyhow!("task {} has no WASM module", args.task))?;
+    let bytes = match &args.module {
+        Some(path) => {
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?
+        }
+        None => api
+            .module(&token, module_hash)
+            .await
+            .with_context(|| format!("Failed to fetch module {}; pass --module", module_hash))?,
+    };
+    let actual_hash = format!("{:x}", Sha3_256::digest(&bytes));
+    if actual_hash != module_hash {
+        bail!(
+            "module hashes to {}, but the task ran {}",
+            actual_hash,
+            module_hash
+        );
+    }
+    let module_path = stage_module(module_hash, &bytes)?;
+
+    println!(
+        "Replaying task {} ({}), module {}",
+        args.task,
+        provenance["task_type"].as_str().unwrap_or("unknown"),
+        module_hash
+    );
+    let mut failed = 0;
+    for entry in &reports {
+        let node_id = entry["node_id"].as_str().unwrap_or("unknown");
+        let outcome = replay_report(entry, &module_path, &provenance["inputs"]).await;
+        match outcome {
+            Ok(checks) => {
+                let ok = checks.iter().all(Check::matches);
+                println!("  {}: {}", node_id, if ok { "MATCH" } else { "MISMATCH" });
+                for check in &checks {
+                    if check.matches() {
+                        println!("    {:<12} ok", check.name);
+                    } else {
+                        println!(
+                            "    {:<12} expected {}, got {}",
+                            check.name, check.expected, check.actual
+                        );
+                    }
+                }
+                if !ok {
+                    failed += 1;
+                }
+            }
+            Err(e) => {
+                println!("  {}: ERROR {:#}", node_id, e);
+                failed += 1;
+            }
+        }
+    }
+    let _ = std::fs::remove_file(&module_path);
+
+    if failed > 0 {
+        bail!("{} of {} replays did not match", failed, reports.len());
+    }
+    Ok(())
+}
+
+/// Verify one provenance entry's signature, re-execute its call and compare.
+async fn replay_report(entry: &Value, module_path: &Path, inputs: &Value) -> Result<Vec<Check>> {
+    let report: SandboxReport = serde_json::from_value(entry["report"].clone())
+        .context("sandbox report is not a wasm_engine::SandboxReport")?;
+    let signed = SignedSandboxReport {
+        report: report.clone(),
+        public_key: entry["signer_public_key"]
+            .as_str()
+            .unwrap_or_default()
+            .into(),
+        signature: entry["signature"].as_str().unwrap_or_default().into(),
+    };
+    if !signed.verify() {
+        bail!("report signature does not verify");
+    }
+
+    let engine = WasmEngine::new(WasmRuntime::WasmEdge, report.limits.clone())
+        .with_capabilities(report.capabilities.clone());
+    let call = WasmCall::for_task(
+        module_path.to_string_lossy(),
+        report.function_name.clone(),
+        inputs,
+    );
+    let (result, trace) = engine.execute_with_trace(call).await?;
+    Ok(compare(
+        &report,
+        entry["environment_sha3_256"].as_str(),
+        &engine.environment_fingerprint(),
+        &result,
+        &trace,
+    ))
+}
+
+/// Write the module under the first `WASM_ALLOWED_ROOTS` directory, the only
+/// place the engine loads modules from.
+fn stage_module(module_hash: &str, bytes: &[u8]) -> Result<PathBuf> {
+    let root = wasm_engine::module_roots()
+        .into_iter()
+        .find(|root| !root.is_empty())
+        .ok_or_else(|| anyhow!("WASM_ALLOWED_ROOTS names no directory"))?;
+    std::fs::create_dir_all(&root).with_context(|| format!("Failed to create {}", root))?;
+    let path = Path::new(&root).join(format!("replay-{}.wasm", module_hash));
+    std::fs::write(&path, bytes).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_engine::{
+        environment_fingerprint, SandboxAuditor, SandboxCapabilities, SandboxLimits,
+    };
+
+    fn execution(output: Vec<u8>) -> (WasmResult, ExecutionTrace) {
+        let call = WasmCall::for_task("m.wasm", "run", &serde_json::json!({"n": 3}));
+        let trace = ExecutionTrace::new(
+            "abc".into(),
+            call.function_name,
+            call.inputs,
+            output.clone(),
+            5,
+            7,
+        );
+        let result = WasmResult {
+            output,
+            execution_time_ms: 5,
+            gas_used: 7,
+            success: true,
+            error: None,
+            usage: Default::default(),
+        };
+        (result, trace)
+    }
+
+    #[test]
+    fn replay_matches_only_the_same_output_and_sandbox() {
+        let limits = SandboxLimits::strict();
+        let caps = SandboxCapabilities::default();
+        let (result, trace) = execution(vec![9]);
+        let report = SandboxAuditor::new().finish(&trace, &result, &limits, &caps);
+        let env = environment_fingerprint(&limits, &caps);
+
+        let checks = compare(&report, Some(&env), &env, &result, &trace);
+        assert!(checks.iter().all(Check::matches), "{:?}", checks);
+
+        let (result, trace) = execution(vec![8]);
+        let failed: Vec<_> = compare(&report, None, &env, &result, &trace)
+            .into_iter()
+            .filter(|check| !check.matches())
+            .map(|check| check.name)
+            .collect();
+        assert_eq!(failed, vec!["trace hash"]);
+
+        let other_env = environment_fingerprint(&SandboxLimits::default(), &caps);
+        let checks = compare(&report, Some(&env), &other_env, &result, &trace);
+        assert!(!checks[1].matches());
+    }
+}
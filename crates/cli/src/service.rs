@@ -0,0 +1,686 @@
+//! OS service packaging for the node agent
+//!
+//! `install-service` renders a systemd unit (or a launchd daemon plist on
+//! macOS) that runs `ambient-vcp node` with a restart policy and, for
+//! systemd, a sandbox that drops every capability unless `--routing` keeps
+//! the ones the routing commands need.  The agent's environment, which may
+//! hold credentials, goes into a separate `<name>.env` file readable by root
+//! only rather than into the world-readable unit.  launchd has no such file,
+//! so the plist itself is readable by root only.  `uninstall-service` stops
+//! the service and removes its files again.
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+const DEFAULT_SERVICE_NAME: &str = "ambient-vcp-node";
+const SYSTEMD_UNIT_DIR: &str = "/etc/systemd/system";
+const LAUNCHD_DAEMON_DIR: &str = "/Library/LaunchDaemons";
+const LAUNCHD_LABEL_PREFIX: &str = "com.ambient-vcp";
+
+/// Capabilities kept when the node manages routes and raw sockets.
+const ROUTING_CAPABILITIES: &str = "CAP_NET_ADMIN CAP_NET_RAW";
+
+/// Seconds the service manager waits before restarting a failed agent.
+const RESTART_DELAY_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ServiceFormat {
+    Systemd,
+    Launchd,
+}
+
+impl ServiceFormat {
+    fn native() -> Self {
+        if cfg!(target_os = "macos") {
+            Self::Launchd
+        } else {
+            Self::Systemd
+        }
+    }
+
+    fn default_dir(self) -> &'static str {
+        match self {
+            Self::Systemd => SYSTEMD_UNIT_DIR,
+            Self::Launchd => LAUNCHD_DAEMON_DIR,
+        }
+    }
+
+    fn file_name(self, name: &str) -> String {
+        match self {
+            Self::Systemd => format!("{}.service", name),
+            Self::Launchd => format!("{}.plist", launchd_label(name)),
+        }
+    }
+}
+
+/// Mode of files holding `--env` values: owner read/write only.
+const PRIVATE_FILE_MODE: u32 = 0o600;
+
+#[derive(Debug, Args)]
+pub struct InstallArgs {
+    /// Node ID
+    #[arg(short, long)]
+    id: String,
+
+    /// Region
+    #[arg(short, long, default_value = "us-west")]
+    region: String,
+
+    /// Node type
+    #[arg(short = 't', long, default_value = "compute")]
+    node_type: String,
+
+    /// Enable the local observability interface in the service
+    #[arg(long)]
+    observability: bool,
+
+    /// Observability server port
+    #[arg(long, default_value_t = 9090)]
+    observability_port: u16,
+
+    /// Service name (systemd unit name, launchd label suffix)
+    #[arg(long, default_value = DEFAULT_SERVICE_NAME)]
+    name: String,
+
+    /// Service manager to target (defaults to the host's)
+    #[arg(long, value_enum)]
+    format: Option<ServiceFormat>,
+
+    /// Agent binary the service runs (defaults to this executable)
+    #[arg(long)]
+    binary: Option<PathBuf>,
+
+    /// Account the agent runs as (defaults to the service manager's)
+    #[arg(long)]
+    user: Option<String>,
+
+    /// Environment variable for the agent, as KEY=VALUE (repeatable)
+    #[arg(long = "env", value_parser = parse_env_var)]
+    env: Vec<(String, String)>,
+
+    /// Keep CAP_NET_ADMIN and CAP_NET_RAW for nodes that manage routes
+    #[arg(long)]
+    routing: bool,
+
+    /// Directory the service file is written to
+    #[arg(long)]
+    unit_dir: Option<PathBuf>,
+
+    /// Replace an existing service file
+    #[arg(long)]
+    force: bool,
+
+    /// Write and enable the service without starting it
+    #[arg(long)]
+    no_start: bool,
+
+    /// Print the service file instead of installing it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct UninstallArgs {
+    /// Service name given to install-service
+    #[arg(long, default_value = DEFAULT_SERVICE_NAME)]
+    name: String,
+
+    /// Service manager to target (defaults to the host's)
+    #[arg(long, value_enum)]
+    format: Option<ServiceFormat>,
+
+    /// Directory the service file was written to
+    #[arg(long)]
+    unit_dir: Option<PathBuf>,
+}
+
+impl InstallArgs {
+    /// Defaults for installing the given node, as used by `setup`.
+    pub fn for_node(id: &str, region: &str, node_type: &str, routing: bool) -> Self {
+        Self {
+            id: id.to_string(),
+            region: region.to_string(),
+            node_type: node_type.to_string(),
+            observability: false,
+            observability_port: 9090,
+            name: DEFAULT_SERVICE_NAME.to_string(),
+            format: None,
+            binary: None,
+            user: None,
+            env: Vec::new(),
+            routing,
+            unit_dir: None,
+            force: false,
+            no_start: false,
+            dry_run: false,
+        }
+    }
+}
+
+/// What the rendered service runs and how.
+#[derive(Debug, Clone)]
+pub struct ServiceSpec {
+    pub name: String,
+    pub binary: PathBuf,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    /// Where a systemd unit loads `env` from
+    pub env_file: PathBuf,
+    pub user: Option<String>,
+    pub routing: bool,
+}
+
+impl ServiceSpec {
+    fn from_args(args: &InstallArgs, binary: PathBuf, env_file: PathBuf) -> Self {
+        let mut agent_args = vec![
+            "node".to_string(),
+            "--id".to_string(),
+            args.id.clone(),
+            "--region".to_string(),
+            args.region.clone(),
+            "--node-type".to_string(),
+            args.node_type.clone(),
+        ];
+        if args.observability {
+            agent_args.push("--observability".to_string());
+            agent_args.push("--observability-port".to_string());
+            agent_args.push(args.observability_port.to_string());
+        }
+
+        let mut env = args.env.clone();
+        if !env.iter().any(|(key, _)| key == "RUST_LOG") {
+            env.insert(0, ("RUST_LOG".to_string(), "info".to_string()));
+        }
+
+        Self {
+            name: args.name.clone(),
+            binary,
+            args: agent_args,
+            env,
+            env_file,
+            user: args.user.clone(),
+            routing: args.routing,
+        }
+    }
+
+    pub fn render(&self, format: ServiceFormat) -> String {
+        match format {
+            ServiceFormat::Systemd => render_systemd_unit(self),
+            ServiceFormat::Launchd => render_launchd_plist(self),
+        }
+    }
+}
+
+pub fn install(args: InstallArgs) -> Result<()> {
+    validate_service_name(&args.name)?;
+    let format = args.format.unwrap_or_else(ServiceFormat::native);
+
+    let binary = match &args.binary {
+        Some(binary) => binary.clone(),
+        None => std::env::current_exe().context("Failed to locate the ambient-vcp binary")?,
+    };
+    let binary = binary
+        .canonicalize()
+        .with_context(|| format!("Agent binary {} not found", binary.display()))?;
+
+    let env_file = env_file_path(&args.name, args.unit_dir.as_deref());
+    let spec = ServiceSpec::from_args(&args, binary, env_file);
+    let contents = spec.render(format);
+
+    if args.dry_run {
+        print!("{}", contents);
+        if format == ServiceFormat::Systemd {
+            print!(
+                "\n# {} (mode {:o})\n{}",
+                spec.env_file.display(),
+                PRIVATE_FILE_MODE,
+                render_systemd_env_file(&spec)
+            );
+        }
+        return Ok(());
+    }
+
+    if format == ServiceFormat::Launchd && args.routing && args.user.is_some() {
+        warn!("launchd has no capability sets; routing commands need the daemon to run as root");
+    }
+
+    let path = service_path(format, &args.name, args.unit_dir.as_deref());
+    if path.exists() && !args.force {
+        bail!(
+            "{} already exists; pass --force to replace it",
+            path.display()
+        );
+    }
+    write_service_files(format, &spec, &path, &contents)?;
+
+    match format {
+        ServiceFormat::Systemd => {
+            run("systemctl", &["daemon-reload"])?;
+            if args.no_start {
+                run("systemctl", &["enable", &args.name])?;
+            } else {
+                run("systemctl", &["enable", "--now", &args.name])?;
+            }
+        }
+        ServiceFormat::Launchd => {
+            if !args.no_start {
+                run("launchctl", &["load", "-w", &path.to_string_lossy()])?;
+            }
+        }
+    }
+
+    info!("Installed service {}", args.name);
+    Ok(())
+}
+
+pub fn uninstall(args: UninstallArgs) -> Result<()> {
+    validate_service_name(&args.name)?;
+    let format = args.format.unwrap_or_else(ServiceFormat::native);
+
+    let path = service_path(format, &args.name, args.unit_dir.as_deref());
+    if !path.exists() {
+        bail!("No service installed at {}", path.display());
+    }
+
+    // The service may already be stopped or never have been loaded; removing
+    // the file is what matters.
+    let stopped = match format {
+        ServiceFormat::Systemd => run("systemctl", &["disable", "--now", &args.name]),
+        ServiceFormat::Launchd => run("launchctl", &["unload", "-w", &path.to_string_lossy()]),
+    };
+    if let Err(e) = stopped {
+        warn!("Could not stop service {}: {}", args.name, e);
+    }
+
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    info!("Removed {}", path.display());
+    let env_file = env_file_path(&args.name, args.unit_dir.as_deref());
+    if format == ServiceFormat::Systemd && env_file.exists() {
+        std::fs::remove_file(&env_file)
+            .with_context(|| format!("Failed to remove {}", env_file.display()))?;
+        info!("Removed {}", env_file.display());
+    }
+
+    if format == ServiceFormat::Systemd {
+        run("systemctl", &["daemon-reload"])?;
+    }
+
+    info!("Uninstalled service {}", args.name);
+    Ok(())
+}
+
+fn service_path(format: ServiceFormat, name: &str, dir: Option<&Path>) -> PathBuf {
+    dir.unwrap_or_else(|| Path::new(format.default_dir()))
+        .join(format.file_name(name))
+}
+
+/// The systemd environment file, next to the unit.
+fn env_file_path(name: &str, dir: Option<&Path>) -> PathBuf {
+    dir.unwrap_or_else(|| Path::new(SYSTEMD_UNIT_DIR))
+        .join(format!("{}.env", name))
+}
+
+/// Write the service file at `path` and, for systemd, its environment file.
+/// Files that hold `--env` values are private.
+fn write_service_files(
+    format: ServiceFormat,
+    spec: &ServiceSpec,
+    path: &Path,
+    contents: &str,
+) -> Result<()> {
+    match format {
+        ServiceFormat::Systemd => {
+            write_private_file(&spec.env_file, &render_systemd_env_file(spec))?;
+            info!("Wrote {}", spec.env_file.display());
+            std::fs::write(path, contents)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        ServiceFormat::Launchd => write_private_file(path, contents)?,
+    }
+    info!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// Replace `path` with `contents`, created with [`PRIVATE_FILE_MODE`] so the
+/// values are never readable by other users, not even briefly.
+fn write_private_file(path: &Path, contents: &str) -> Result<()> {
+    use std::io::Write;
+    #[cfg(unix)]
+    use std::os::unix::fs::OpenOptionsExt;
+
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to replace {}", path.display())),
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(PRIVATE_FILE_MODE);
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !status.success() {
+        bail!("{} {} exited with {}", program, args.join(" "), status);
+    }
+    Ok(())
+}
+
+fn validate_service_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        bail!(
+            "Invalid service name '{}': use letters, digits, '-', '_' or '.'",
+            name
+        );
+    }
+    Ok(())
+}
+
+fn parse_env_var(raw: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", raw))?;
+    let valid_key = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_key {
+        return Err(format!("invalid environment variable name '{}'", key));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn launchd_label(name: &str) -> String {
+    format!("{}.{}", LAUNCHD_LABEL_PREFIX, name)
+}
+
+fn render_systemd_unit(spec: &ServiceSpec) -> String {
+    let mut exec_start = vec![systemd_quote(&spec.binary.to_string_lossy())];
+    exec_start.extend(spec.args.iter().map(|arg| systemd_quote(arg)));
+
+    let mut unit = format!(
+        "[Unit]\n\
+         Description=Ambient AI VCP node agent ({name})\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         StartLimitIntervalSec=300\n\
+         StartLimitBurst=10\n\
+         \n\
+         [Service]\n\
+         Type=simple\n\
+         ExecStart={exec_start}\n\
+         Restart=on-failure\n\
+         RestartSec={RESTART_DELAY_SECS}\n",
+        name = spec.name,
+        exec_start = exec_start.join(" "),
+    );
+    if let Some(user) = &spec.user {
+        unit.push_str(&format!("User={}\n", user));
+    }
+    unit.push_str(&format!(
+        "EnvironmentFile={}\n",
+        systemd_quote(&spec.env_file.to_string_lossy())
+    ));
+
+    unit.push_str(&format!(
+        "StateDirectory={name}\n\
+         WorkingDirectory=%S/{name}\n",
+        name = spec.name
+    ));
+    unit.push_str(
+        "NoNewPrivileges=true\n\
+         ProtectSystem=strict\n\
+         ProtectHome=true\n\
+         PrivateTmp=true\n\
+         PrivateDevices=true\n\
+         ProtectKernelTunables=true\n\
+         ProtectKernelModules=true\n\
+         ProtectControlGroups=true\n\
+         RestrictSUIDSGID=true\n\
+         LockPersonality=true\n",
+    );
+    if spec.routing {
+        unit.push_str(&format!(
+            "CapabilityBoundingSet={caps}\n\
+             AmbientCapabilities={caps}\n\
+             RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6 AF_NETLINK AF_PACKET\n",
+            caps = ROUTING_CAPABILITIES
+        ));
+    } else {
+        unit.push_str(
+            "CapabilityBoundingSet=\n\
+             RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6\n",
+        );
+    }
+
+    unit.push_str(
+        "\n\
+         [Install]\n\
+         WantedBy=multi-user.target\n",
+    );
+    unit
+}
+
+/// The `EnvironmentFile=` of a systemd unit: one double-quoted
+/// `KEY="value"` per line, which systemd reads without expanding anything.
+fn render_systemd_env_file(spec: &ServiceSpec) -> String {
+    spec.env
+        .iter()
+        .map(|(key, value)| {
+            let mut quoted = String::with_capacity(value.len());
+            for c in value.chars() {
+                if matches!(c, '"' | '\\' | '`' | '$') {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            format!("{}=\"{}\"\n", key, quoted)
+        })
+        .collect()
+}
+
+/// Quote one word for a systemd `ExecStart=` or `EnvironmentFile=` line.
+fn systemd_quote(word: &str) -> String {
+    // `%` starts a specifier and `$` a variable expansion in unit settings.
+    let escaped = word.replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty()
+        && !escaped
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        return escaped;
+    }
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn render_launchd_plist(spec: &ServiceSpec) -> String {
+    let mut plist = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n",
+    );
+    plist.push_str(&format!(
+        "  <key>Label</key>\n  <string>{}</string>\n",
+        xml_escape(&launchd_label(&spec.name))
+    ));
+
+    plist.push_str("  <key>ProgramArguments</key>\n  <array>\n");
+    plist.push_str(&format!(
+        "    <string>{}</string>\n",
+        xml_escape(&spec.binary.to_string_lossy())
+    ));
+    for arg in &spec.args {
+        plist.push_str(&format!("    <string>{}</string>\n", xml_escape(arg)));
+    }
+    plist.push_str("  </array>\n");
+
+    if !spec.env.is_empty() {
+        plist.push_str("  <key>EnvironmentVariables</key>\n  <dict>\n");
+        for (key, value) in &spec.env {
+            plist.push_str(&format!(
+                "    <key>{}</key>\n    <string>{}</string>\n",
+                xml_escape(key),
+                xml_escape(value)
+            ));
+        }
+        plist.push_str("  </dict>\n");
+    }
+    if let Some(user) = &spec.user {
+        plist.push_str(&format!(
+            "  <key>UserName</key>\n  <string>{}</string>\n",
+            xml_escape(user)
+        ));
+    }
+
+    let log = xml_escape(&format!("/var/log/{}.log", spec.name));
+    plist.push_str(&format!(
+        "  <key>RunAtLoad</key>\n  <true/>\n\
+         \x20 <key>KeepAlive</key>\n  <dict>\n\
+         \x20   <key>SuccessfulExit</key>\n    <false/>\n\
+         \x20 </dict>\n\
+         \x20 <key>ThrottleInterval</key>\n  <integer>{RESTART_DELAY_SECS}</integer>\n\
+         \x20 <key>StandardOutPath</key>\n  <string>{log}</string>\n\
+         \x20 <key>StandardErrorPath</key>\n  <string>{log}</string>\n\
+         </dict>\n\
+         </plist>\n"
+    ));
+    plist
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(routing: bool) -> ServiceSpec {
+        ServiceSpec {
+            name: "ambient-vcp-node".to_string(),
+            binary: PathBuf::from("/opt/ambient vcp/ambient-vcp"),
+            args: vec!["node".to_string(), "--id".to_string(), "node-1".to_string()],
+            env: vec![
+                ("RUST_LOG".to_string(), "info".to_string()),
+                ("API_TOKEN".to_string(), "a$b%c\"d".to_string()),
+            ],
+            env_file: PathBuf::from("/etc/systemd/system/ambient-vcp-node.env"),
+            user: Some("ambient".to_string()),
+            routing,
+        }
+    }
+
+    #[test]
+    fn test_systemd_unit_sandboxes_and_keeps_routing_capabilities() {
+        let unit = spec(false).render(ServiceFormat::Systemd);
+        assert!(unit.contains("ExecStart=\"/opt/ambient vcp/ambient-vcp\" node --id node-1\n"));
+        assert!(unit.contains("EnvironmentFile=/etc/systemd/system/ambient-vcp-node.env\n"));
+        assert!(!unit.contains("API_TOKEN"));
+        assert!(unit.contains("Restart=on-failure\n"));
+        assert!(unit.contains("User=ambient\n"));
+        assert!(unit.contains("ProtectSystem=strict\n"));
+        assert!(unit.contains("CapabilityBoundingSet=\n"));
+        assert!(!unit.contains("AmbientCapabilities"));
+
+        let unit = spec(true).render(ServiceFormat::Systemd);
+        assert!(unit.contains("CapabilityBoundingSet=CAP_NET_ADMIN CAP_NET_RAW\n"));
+        assert!(unit.contains("AmbientCapabilities=CAP_NET_ADMIN CAP_NET_RAW\n"));
+        assert!(unit.contains("AF_NETLINK"));
+    }
+
+    #[cfg(unix)]
+    fn file_mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_systemd_env_file_is_private_and_quoted() {
+        assert_eq!(
+            render_systemd_env_file(&spec(false)),
+            "RUST_LOG=\"info\"\nAPI_TOKEN=\"a\\$b%c\\\"d\"\n"
+        );
+
+        let dir = std::env::temp_dir().join(format!("service-systemd-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut spec = spec(false);
+        spec.env_file = dir.join("ambient-vcp-node.env");
+        let unit = dir.join("ambient-vcp-node.service");
+        write_service_files(ServiceFormat::Systemd, &spec, &unit, "[Unit]\n").unwrap();
+        assert_eq!(file_mode(&spec.env_file), PRIVATE_FILE_MODE);
+        assert!(std::fs::read_to_string(&spec.env_file)
+            .unwrap()
+            .contains("API_TOKEN="));
+        std::fs::remove_dir_all(dir).unwrap();
+
+        let path = std::env::temp_dir().join(format!("service-env-{}", std::process::id()));
+        write_private_file(&path, "OLD=1\n").unwrap();
+        write_private_file(&path, "NEW=1\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "NEW=1\n");
+        assert_eq!(file_mode(&path), PRIVATE_FILE_MODE);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_launchd_plist_holding_env_is_private() {
+        let spec = spec(false);
+        let plist = spec.render(ServiceFormat::Launchd);
+        assert!(plist.contains("<key>API_TOKEN</key>"));
+
+        let path = std::env::temp_dir().join(format!("service-plist-{}", std::process::id()));
+        std::fs::write(&path, "stale").unwrap();
+        write_service_files(ServiceFormat::Launchd, &spec, &path, &plist).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), plist);
+        assert_eq!(file_mode(&path), PRIVATE_FILE_MODE);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_launchd_plist_escapes_values() {
+        let mut spec = spec(false);
+        spec.env.push(("NOTE".to_string(), "<a&b>".to_string()));
+        let plist = spec.render(ServiceFormat::Launchd);
+        assert!(plist.contains("<string>com.ambient-vcp.ambient-vcp-node</string>"));
+        assert!(plist.contains("<string>/opt/ambient vcp/ambient-vcp</string>"));
+        assert!(plist.contains("<string>&lt;a&amp;b&gt;</string>"));
+        assert!(plist.contains("<key>SuccessfulExit</key>\n    <false/>"));
+    }
+
+    #[test]
+    fn test_parse_env_var_and_service_names() {
+        assert_eq!(
+            parse_env_var("API_URL=http://x?a=b"),
+            Ok(("API_URL".to_string(), "http://x?a=b".to_string()))
+        );
+        assert!(parse_env_var("NOEQUALS").is_err());
+        assert!(parse_env_var("1BAD=x").is_err());
+
+        assert!(validate_service_name("ambient-vcp-node").is_ok());
+        assert!(validate_service_name("../etc/passwd").is_err());
+        assert!(validate_service_name("").is_err());
+    }
+}
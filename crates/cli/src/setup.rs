@@ -0,0 +1,448 @@
+//! First-run setup wizard
+//!
+//! `ambient-vcp setup` walks an operator from nothing to a registered node:
+//! it logs in (or registers an account), detects the host's capabilities,
+//! registers the node with freshly generated keys, writes the config and key
+//! files, optionally installs the OS service and finishes with a
+//! connectivity self-test against the API.
+use crate::api::ApiClient;
+use crate::config::{
+    default_config_dir, write_private, DetectedCapabilities, NodeConfig, CONFIG_FILE,
+};
+use crate::service;
+use ambient_node::{DeploymentProfile, NodeSecretKey, NodeSigningKey};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use serde_json::json;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const DEFAULT_API_URL: &str = "http://localhost:3000";
+const SECRETS_KEY_FILE: &str = "secrets.key";
+const SIGNING_KEY_FILE: &str = "signing.key";
+
+/// Bandwidth offered when the operator accepts the default; it cannot be
+/// measured without a speed test.
+const DEFAULT_BANDWIDTH_MBPS: f64 = 100.0;
+
+#[derive(Debug, Args)]
+pub struct SetupArgs {
+    /// API server base URL
+    #[arg(long, default_value = DEFAULT_API_URL)]
+    api_url: String,
+
+    /// Directory for the config and key files
+    /// (default: $AMBIENT_VCP_CONFIG_DIR or ~/.config/ambient-vcp)
+    #[arg(long)]
+    config_dir: Option<PathBuf>,
+
+    /// Replace an existing config
+    #[arg(long)]
+    force: bool,
+
+    /// Only accept this task type (repeatable; default: every type)
+    #[arg(long = "allow-task-type", value_name = "TASK_TYPE")]
+    allowed_task_types: Vec<String>,
+
+    /// Never accept this task type (repeatable)
+    #[arg(long = "block-task-type", value_name = "TASK_TYPE")]
+    blocked_task_types: Vec<String>,
+
+    /// Deployment profile: edge-battery, home-relay or datacenter-compute
+    #[arg(long)]
+    profile: Option<DeploymentProfile>,
+}
+
+pub async fn run(args: SetupArgs) -> Result<()> {
+    let mut prompt = Prompt::stdio();
+    let config_dir = match args.config_dir {
+        Some(dir) => dir,
+        None => default_config_dir()?,
+    };
+    let config_path = config_dir.join(CONFIG_FILE);
+    if config_path.exists() && !args.force {
+        bail!(
+            "{} already exists; pass --force to set up again",
+            config_path.display()
+        );
+    }
+
+    println!("Ambient AI VCP node setup\n");
+
+    let api_url = prompt.ask("API server URL", Some(&args.api_url))?;
+    let api = ApiClient::new(&api_url)?;
+    let version = api
+        .health()
+        .await
+        .with_context(|| format!("Cannot reach the API at {}", api.base_url()))?;
+    println!(
+        "Connected to API {} (version {})\n",
+        api.base_url(),
+        version
+    );
+
+    // Account
+    let has_account = prompt.confirm("Do you already have an account?", false)?;
+    let username = prompt.ask("Username", None)?;
+    let password = prompt.secret("Password")?;
+    if !has_account {
+        let email = prompt.ask("Email for task notifications (optional)", Some(""))?;
+        api.register(
+            &username,
+            &password,
+            Some(email.as_str()).filter(|e| !e.is_empty()),
+        )
+        .await?;
+        println!("Registered account {}", username);
+    }
+    let session = api.login(&username, &password).await?;
+    println!("Logged in as {}\n", username);
+
+    // Node
+    let node_id = prompt.ask("Node ID", Some(&default_node_id()))?;
+    let region = prompt.ask("Region", Some("us-west"))?;
+    let node_type = prompt.ask("Node type", Some("compute"))?;
+    let mut capabilities = detect_capabilities();
+    println!(
+        "Detected {} CPU cores, {:.1} GB memory, GPU: {}",
+        capabilities.cpu_cores,
+        capabilities.memory_gb,
+        if capabilities.gpu_available {
+            "yes"
+        } else {
+            "no"
+        }
+    );
+    let bandwidth = prompt.ask(
+        "Uplink bandwidth in Mbps",
+        Some(&capabilities.bandwidth_mbps.to_string()),
+    )?;
+    capabilities.bandwidth_mbps = bandwidth
+        .parse()
+        .map_err(|_| anyhow!("Bandwidth must be a number, got '{}'", bandwidth))?;
+
+    let secrets_key = NodeSecretKey::generate();
+    let signing_key = NodeSigningKey::generate()?;
+    api.register_node(
+        &session.access_token,
+        &json!({
+            "node_id": node_id,
+            "region": region,
+            "node_type": node_type,
+            "capabilities": capabilities,
+            "observability_port": null,
+            "secrets_public_key": secrets_key.public_key_b64(),
+            "signing_public_key": signing_key.public_key_b64(),
+            "allowed_task_types": args.allowed_task_types,
+            "blocked_task_types": args.blocked_task_types,
+        }),
+    )
+    .await?;
+    println!("Registered node {}\n", node_id);
+
+    // Config and keys
+    std::fs::create_dir_all(&config_dir)
+        .with_context(|| format!("Failed to create {}", config_dir.display()))?;
+    let secrets_key_file = config_dir.join(SECRETS_KEY_FILE);
+    let signing_key_file = config_dir.join(SIGNING_KEY_FILE);
+    write_private(&secrets_key_file, &*secrets_key.to_bytes())?;
+    write_private(&signing_key_file, &*signing_key.to_bytes())?;
+    let config = NodeConfig {
+        api_url: api.base_url().to_string(),
+        username,
+        node_id: node_id.clone(),
+        region: region.clone(),
+        node_type: node_type.clone(),
+        capabilities,
+        secrets_key_file,
+        signing_key_file,
+        refresh_token: session.refresh_token,
+        allowed_task_types: args.allowed_task_types,
+        blocked_task_types: args.blocked_task_types,
+        deployment_profile: args.profile,
+        profile_overrides: serde_json::Value::Null,
+    };
+    config.save(&config_dir)?;
+    println!("Wrote {}\n", config_path.display());
+
+    // Service
+    if prompt.confirm("Install the node agent as an OS service?", false)? {
+        let routing = matches!(
+            node_type.as_str(),
+            "gateway" | "open_internet" | "universal"
+        ) && prompt.confirm("Keep network admin capabilities for routing?", true)?;
+        match service::install(service::InstallArgs::for_node(
+            &node_id, &region, &node_type, routing,
+        )) {
+            Ok(()) => println!("Service installed\n"),
+            // Setup already succeeded; the operator can retry this step alone.
+            Err(e) => println!(
+                "Service not installed: {:#}\nRetry with `sudo ambient-vcp install-service --id {}`\n",
+                e, node_id
+            ),
+        }
+    }
+
+    // Self-test
+    println!("Running connectivity self-test...");
+    let checks = self_test(&api, &session.access_token, &node_id).await;
+    let mut failed = 0;
+    for check in &checks {
+        match &check.outcome {
+            Ok(detail) => println!("  ✓ {}: {}", check.name, detail),
+            Err(e) => {
+                failed += 1;
+                println!("  ✗ {}: {}", check.name, e);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} self-test checks failed", failed, checks.len());
+    }
+
+    println!("\nSetup complete. Start the node with:");
+    println!(
+        "  ambient-vcp node --id {} --region {} --node-type {}",
+        node_id, region, node_type
+    );
+    Ok(())
+}
+
+/// Host name reduced to the characters a node ID allows.
+fn default_node_id() -> String {
+    let hostname = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| command_output("hostname", &[]))
+        .unwrap_or_default();
+    node_id_from_hostname(&hostname)
+}
+
+fn node_id_from_hostname(hostname: &str) -> String {
+    let id: String = hostname
+        .trim()
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .take(64)
+        .collect();
+    if id.is_empty() {
+        "node-001".to_string()
+    } else {
+        id
+    }
+}
+
+fn detect_capabilities() -> DetectedCapabilities {
+    let cpu_cores = std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1);
+    let memory_bytes = std::fs::read_to_string("/proc/meminfo")
+        .ok()
+        .and_then(|meminfo| parse_meminfo_total(&meminfo))
+        .or_else(|| {
+            command_output("sysctl", &["-n", "hw.memsize"])?
+                .trim()
+                .parse()
+                .ok()
+        });
+    let gpu_available = ["/dev/nvidia0", "/dev/kfd"]
+        .iter()
+        .any(|device| Path::new(device).exists());
+
+    clamp_capabilities(DetectedCapabilities {
+        bandwidth_mbps: DEFAULT_BANDWIDTH_MBPS,
+        cpu_cores,
+        memory_gb: memory_bytes.map_or(1.0, |bytes| bytes as f64 / 1_073_741_824.0),
+        gpu_available,
+    })
+}
+
+/// `MemTotal` from `/proc/meminfo`, in bytes.
+fn parse_meminfo_total(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Keep detected values inside the ranges node registration accepts.
+fn clamp_capabilities(caps: DetectedCapabilities) -> DetectedCapabilities {
+    DetectedCapabilities {
+        bandwidth_mbps: caps.bandwidth_mbps.clamp(10.0, 100_000.0),
+        cpu_cores: caps.cpu_cores.clamp(1, 256),
+        memory_gb: (caps.memory_gb * 10.0).round().clamp(10.0, 20_480.0) / 10.0,
+        gpu_available: caps.gpu_available,
+    }
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Line-based prompts on stdin/stdout.
+struct Prompt<R, W> {
+    input: R,
+    output: W,
+}
+
+impl Prompt<std::io::StdinLock<'static>, std::io::Stdout> {
+    fn stdio() -> Self {
+        Self {
+            input: std::io::stdin().lock(),
+            output: std::io::stdout(),
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            bail!("Setup cancelled: input closed");
+        }
+        Ok(line.trim().to_string())
+    }
+
+    /// Ask until the answer is non-empty or a default applies.
+    fn ask(&mut self, question: &str, default: Option<&str>) -> Result<String> {
+        loop {
+            match default {
+                Some(default) if !default.is_empty() => {
+                    write!(self.output, "{} [{}]: ", question, default)?
+                }
+                _ => write!(self.output, "{}: ", question)?,
+            }
+            self.output.flush()?;
+            let answer = self.read_line()?;
+            if !answer.is_empty() {
+                return Ok(answer);
+            }
+            if let Some(default) = default {
+                return Ok(default.to_string());
+            }
+        }
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        loop {
+            write!(self.output, "{} [{}]: ", question, hint)?;
+            self.output.flush()?;
+            match self.read_line()?.to_ascii_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => writeln!(self.output, "Please answer y or n")?,
+            }
+        }
+    }
+
+    /// Like `ask`, without echoing the answer when stdin is a terminal.
+    fn secret(&mut self, question: &str) -> Result<String> {
+        let terminal = std::io::stdin().is_terminal();
+        let set_echo = |on: bool| {
+            if terminal {
+                let _ = Command::new("stty")
+                    .arg(if on { "echo" } else { "-echo" })
+                    .status();
+            }
+        };
+        set_echo(false);
+        let answer = self.ask(question, None);
+        set_echo(true);
+        if terminal {
+            writeln!(self.output)?;
+        }
+        answer
+    }
+}
+
+struct SelfTestCheck {
+    name: &'static str,
+    outcome: Result<String>,
+}
+
+async fn self_test(api: &ApiClient, token: &str, node_id: &str) -> Vec<SelfTestCheck> {
+    vec![
+        SelfTestCheck {
+            name: "API reachable",
+            outcome: api
+                .health()
+                .await
+                .map(|version| format!("version {}", version)),
+        },
+        SelfTestCheck {
+            name: "Clock",
+            outcome: api.clock_sample().await.map(|sample| {
+                format!(
+                    "offset {} ms, round trip {} ms",
+                    sample.offset_ms, sample.round_trip_ms
+                )
+            }),
+        },
+        SelfTestCheck {
+            name: "Node visible",
+            outcome: api
+                .node(token, node_id)
+                .await
+                .map(|node| format!("status {}", node["status"].as_str().unwrap_or("unknown"))),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detection_helpers() {
+        let meminfo = "MemTotal:       16318480 kB\nMemFree:         1024 kB\n";
+        assert_eq!(parse_meminfo_total(meminfo), Some(16_318_480 * 1024));
+        assert_eq!(parse_meminfo_total("MemFree: 1 kB\n"), None);
+
+        let caps = clamp_capabilities(DetectedCapabilities {
+            bandwidth_mbps: 1.0,
+            cpu_cores: 512,
+            memory_gb: 0.5,
+            gpu_available: false,
+        });
+        assert_eq!(caps.bandwidth_mbps, 10.0);
+        assert_eq!(caps.cpu_cores, 256);
+        assert_eq!(caps.memory_gb, 1.0);
+
+        assert_eq!(node_id_from_hostname("edge box.example.com\n"), "edge-box");
+        assert_eq!(node_id_from_hostname(""), "node-001");
+    }
+
+    #[test]
+    fn test_prompt_defaults_and_retries() {
+        let input = b"\n\nnode-7\nmaybe\ny\n";
+        let mut output = Vec::new();
+        let mut prompt = Prompt {
+            input: &input[..],
+            output: &mut output,
+        };
+        assert_eq!(prompt.ask("Region", Some("us-west")).unwrap(), "us-west");
+        // No default: blank answers ask again.
+        assert_eq!(prompt.ask("Node ID", None).unwrap(), "node-7");
+        assert!(prompt.confirm("Install?", false).unwrap());
+        assert!(prompt.ask("Username", None).is_err());
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Region [us-west]: "));
+        assert!(output.contains("Please answer y or n"));
+    }
+}
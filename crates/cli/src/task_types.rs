@@ -0,0 +1,111 @@
+//! `ambient-vcp task-types`: choose which task types this node runs
+//!
+//! The lists live in `config.json` and are mirrored to the server with
+//! `PATCH /nodes/{id}`, whose assignment queries never give the node a task
+//! type it does not accept.  Run without flags to push the config's lists
+//! again, e.g. after editing the file by hand.
+use crate::api::ApiClient;
+use crate::config::{default_config_dir, NodeConfig, CONFIG_FILE};
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use serde_json::json;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct TaskTypesArgs {
+    /// Directory holding config.json (default: as for setup)
+    #[arg(long)]
+    config_dir: Option<PathBuf>,
+
+    /// Access token or API key (default: $AMBIENT_VCP_TOKEN, else a session
+    /// refreshed from the config)
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Only accept these task types; replaces the allow list
+    #[arg(long = "allow", value_name = "TASK_TYPE", num_args = 1..)]
+    allowed: Option<Vec<String>>,
+
+    /// Never accept these task types; replaces the block list
+    #[arg(long = "block", value_name = "TASK_TYPE", num_args = 1..)]
+    blocked: Option<Vec<String>>,
+
+    /// Empty both lists, accepting every task type again
+    #[arg(long, conflicts_with_all = ["allowed", "blocked"])]
+    clear: bool,
+}
+
+pub async fn run(args: TaskTypesArgs) -> Result<()> {
+    let config_dir = match args.config_dir {
+        Some(dir) => dir,
+        None => default_config_dir()?,
+    };
+    let mut config = NodeConfig::load(&config_dir)?.ok_or_else(|| {
+        anyhow!(
+            "no {} in {}; run `ambient-vcp setup` first",
+            CONFIG_FILE,
+            config_dir.display()
+        )
+    })?;
+
+    if args.clear {
+        config.allowed_task_types.clear();
+        config.blocked_task_types.clear();
+    }
+    if let Some(allowed) = args.allowed {
+        config.allowed_task_types = allowed;
+    }
+    if let Some(blocked) = args.blocked {
+        config.blocked_task_types = blocked;
+    }
+
+    let api = ApiClient::with_budgets(&config.api_url, config.deployment_settings()?.requests)?;
+    let supplied = args
+        .token
+        .or_else(|| std::env::var("AMBIENT_VCP_TOKEN").ok())
+        .filter(|token| !token.is_empty());
+    let token = match supplied {
+        Some(token) => token,
+        None => {
+            let refresh_token = config.refresh_token.clone().ok_or_else(|| {
+                anyhow!("no credential; pass --token or run `ambient-vcp setup --force`")
+            })?;
+            let session = api.refresh(&refresh_token).await?;
+            // The server rotated the refresh token; keep the new one.
+            config.refresh_token = session.refresh_token;
+            config.save(&config_dir)?;
+            session.access_token
+        }
+    };
+
+    // The server validates the lists, so the config only changes once it
+    // has accepted them.
+    let node = api
+        .update_node(
+            &token,
+            &config.node_id,
+            &json!({
+                "allowed_task_types": config.allowed_task_types,
+                "blocked_task_types": config.blocked_task_types,
+            }),
+        )
+        .await
+        .with_context(|| format!("Failed to update task types of {}", config.node_id))?;
+    config.save(&config_dir)?;
+
+    let describe = |field: &str, empty: &str| {
+        let types: Vec<&str> = node[field]
+            .as_array()
+            .map(|types| types.iter().filter_map(|t| t.as_str()).collect())
+            .unwrap_or_default();
+        if types.is_empty() {
+            empty.to_string()
+        } else {
+            types.join(", ")
+        }
+    };
+    println!("Node {} task types:", config.node_id);
+    println!("  allowed: {}", describe("allowed_task_types", "all"));
+    println!("  blocked: {}", describe("blocked_task_types", "none"));
+    Ok(())
+}
@@ -0,0 +1,200 @@
+//! Circuit breaker for nodes that flap between online and offline
+//!
+//! Every return to online after being offline counts as one flap.  A node
+//! that flaps `threshold` times within `window_secs` of its first counted
+//! flap trips the breaker and is skipped for new assignments for
+//! `cooldown_secs`.  Work already assigned to it is left alone.  Tripping
+//! starts a fresh count, so a node that keeps flapping after the cool-down
+//! trips again.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const DEFAULT_FLAP_THRESHOLD: u32 = 3;
+pub const DEFAULT_FLAP_WINDOW_SECS: u64 = 900;
+pub const DEFAULT_FLAP_COOLDOWN_SECS: u64 = 600;
+
+/// Breaker thresholds.  A `None` threshold disables the breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlapBreakerConfig {
+    pub threshold: Option<u32>,
+    pub window_secs: u64,
+    pub cooldown_secs: u64,
+}
+
+impl Default for FlapBreakerConfig {
+    fn default() -> Self {
+        Self {
+            threshold: Some(DEFAULT_FLAP_THRESHOLD),
+            window_secs: DEFAULT_FLAP_WINDOW_SECS,
+            cooldown_secs: DEFAULT_FLAP_COOLDOWN_SECS,
+        }
+    }
+}
+
+impl FlapBreakerConfig {
+    /// Count a flap at `now` for a node with `count` flaps since
+    /// `window_started_at` (Unix-epoch seconds).  Returns `None` when the
+    /// breaker is disabled.
+    pub fn count_flap(&self, count: u32, window_started_at: u64, now: u64) -> Option<FlapCount> {
+        let threshold = self.threshold?;
+        let (count, window_started_at) =
+            if count == 0 || now.saturating_sub(window_started_at) > self.window_secs {
+                (1, now)
+            } else {
+                (count + 1, window_started_at)
+            };
+
+        Some(if count >= threshold {
+            FlapCount {
+                count: 0,
+                window_started_at: now,
+                open_until: Some(now + self.cooldown_secs),
+            }
+        } else {
+            FlapCount {
+                count,
+                window_started_at,
+                open_until: None,
+            }
+        })
+    }
+}
+
+/// Flap count after [`FlapBreakerConfig::count_flap`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlapCount {
+    /// Flaps in the current window; restarts at `0` when the breaker opens
+    pub count: u32,
+    pub window_started_at: u64,
+    /// Set when this flap opened the breaker
+    pub open_until: Option<u64>,
+}
+
+/// Breaker state of one node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FlapBreakerState {
+    /// Whether the node is excluded from new assignments
+    pub open: bool,
+    /// Flaps counted in the current window
+    pub recent_flaps: u32,
+    /// Unix-epoch seconds the cool-down ends, while open
+    pub open_until: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct NodeFlaps {
+    count: u32,
+    window_started_at: u64,
+    open_until: Option<u64>,
+}
+
+/// Per-node flap counts and cool-downs
+#[derive(Debug, Default)]
+pub struct FlapBreaker {
+    config: FlapBreakerConfig,
+    nodes: HashMap<String, NodeFlaps>,
+}
+
+impl FlapBreaker {
+    pub fn new(config: FlapBreakerConfig) -> Self {
+        Self {
+            config,
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> FlapBreakerConfig {
+        self.config
+    }
+
+    /// Count a flap of `node_id` at Unix-epoch second `now`.
+    ///
+    /// # Returns
+    /// `true` if this flap tripped the breaker.
+    pub fn record_flap(&mut self, node_id: &str, now: u64) -> bool {
+        let flaps = self.nodes.entry(node_id.to_string()).or_default();
+        let Some(counted) = self
+            .config
+            .count_flap(flaps.count, flaps.window_started_at, now)
+        else {
+            return false;
+        };
+
+        flaps.count = counted.count;
+        flaps.window_started_at = counted.window_started_at;
+        if counted.open_until.is_none() {
+            return false;
+        }
+        flaps.open_until = counted.open_until;
+        true
+    }
+
+    /// Whether `node_id` is cooling down at `now`.
+    pub fn is_open(&self, node_id: &str, now: u64) -> bool {
+        self.state(node_id, now).open
+    }
+
+    pub fn state(&self, node_id: &str, now: u64) -> FlapBreakerState {
+        let Some(flaps) = self.nodes.get(node_id) else {
+            return FlapBreakerState::default();
+        };
+        let open_until = flaps.open_until.filter(|until| *until > now);
+        let in_window = now.saturating_sub(flaps.window_started_at) <= self.config.window_secs;
+        FlapBreakerState {
+            open: open_until.is_some(),
+            recent_flaps: if in_window { flaps.count } else { 0 },
+            open_until,
+        }
+    }
+
+    /// Forget a node, e.g. on deregistration.
+    pub fn remove_node(&mut self, node_id: &str) {
+        self.nodes.remove(node_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_trips_within_window_and_cools_down() {
+        let mut breaker = FlapBreaker::new(FlapBreakerConfig::default());
+
+        assert!(!breaker.record_flap("node-1", 1_000));
+        assert!(!breaker.record_flap("node-1", 1_100));
+        assert_eq!(breaker.state("node-1", 1_100).recent_flaps, 2);
+        assert!(breaker.record_flap("node-1", 1_200));
+
+        let state = breaker.state("node-1", 1_200);
+        assert!(state.open);
+        assert_eq!(state.open_until, Some(1_800));
+        assert!(breaker.is_open("node-1", 1_799));
+        assert!(!breaker.is_open("node-1", 1_800));
+        assert!(!breaker.is_open("node-2", 1_200));
+    }
+
+    #[test]
+    fn test_flaps_outside_window_start_a_new_count() {
+        let mut breaker = FlapBreaker::new(FlapBreakerConfig::default());
+
+        breaker.record_flap("node-1", 0);
+        breaker.record_flap("node-1", 100);
+        assert!(!breaker.record_flap("node-1", 1_000));
+        assert_eq!(breaker.state("node-1", 1_000).recent_flaps, 1);
+        assert_eq!(breaker.state("node-1", 5_000).recent_flaps, 0);
+    }
+
+    #[test]
+    fn test_disabled_breaker_never_trips() {
+        let mut breaker = FlapBreaker::new(FlapBreakerConfig {
+            threshold: None,
+            ..FlapBreakerConfig::default()
+        });
+        for now in 0..10 {
+            assert!(!breaker.record_flap("node-1", now));
+        }
+        assert_eq!(breaker.state("node-1", 10), FlapBreakerState::default());
+    }
+}
@@ -6,11 +6,13 @@ use wasm_engine::WasmCall;
 use zk_prover::{ZKProof, ZKVerifier};
 
 pub mod assignment;
+pub mod flap_breaker;
 pub mod peer_routing;
 pub mod registry;
 pub mod settlement;
 
 pub use assignment::*;
+pub use flap_breaker::*;
 pub use peer_routing::*;
 pub use registry::*;
 pub use settlement::*;
@@ -70,6 +72,7 @@ pub struct MeshCoordinator {
     strategy: TaskAssignmentStrategy,
     verifier: ZKVerifier,
     peer_router: PeerRouter,
+    flap_breaker: FlapBreaker,
 }
 
 impl MeshCoordinator {
@@ -80,9 +83,16 @@ impl MeshCoordinator {
             strategy,
             verifier: ZKVerifier::default(),
             peer_router: PeerRouter::new(),
+            flap_breaker: FlapBreaker::new(FlapBreakerConfig::default()),
         }
     }
 
+    /// Replace the flap circuit breaker settings.
+    pub fn with_flap_breaker(mut self, config: FlapBreakerConfig) -> Self {
+        self.flap_breaker = FlapBreaker::new(config);
+        self
+    }
+
     /// Register a new node in the mesh
     pub fn register_node(&mut self, node: AmbientNode) {
         let node_id = node.id.id.clone();
@@ -90,12 +100,28 @@ impl MeshCoordinator {
         // New nodes start with Unknown connectivity until explicitly updated.
         self.peer_router
             .update_node(&node_id, &node_type, NodeConnectivityStatus::Unknown);
+        self.peer_router.set_network_location(
+            &node_id,
+            ambient_node::NetworkLocation::new(None, node.id.region.clone()),
+        );
         self.nodes.insert(node_id, node);
     }
 
+    /// Record the ASN a registered node's uplink belongs to, so standby
+    /// relays are spread across networks.
+    pub fn set_node_asn(&mut self, node_id: &str, asn: Option<u32>) {
+        if let Some(node) = self.nodes.get(node_id) {
+            self.peer_router.set_network_location(
+                node_id,
+                ambient_node::NetworkLocation::new(asn, node.id.region.clone()),
+            );
+        }
+    }
+
     /// Unregister a node
     pub fn unregister_node(&mut self, node_id: &str) {
         self.peer_router.remove_node(node_id);
+        self.flap_breaker.remove_node(node_id);
         self.nodes.remove(node_id);
     }
 
@@ -104,13 +130,42 @@ impl MeshCoordinator {
     /// Call this whenever the node's backhaul state changes so that
     /// [`MeshCoordinator::find_peer_route`] always reflects current
     /// network conditions.
+    ///
+    /// A return to online after being offline counts as a flap; see
+    /// [`FlapBreaker`].
     pub fn sync_connectivity(&mut self, node_id: &str, status: NodeConnectivityStatus) {
+        self.sync_connectivity_at(node_id, status, unix_now());
+    }
+
+    /// [`MeshCoordinator::sync_connectivity`] at Unix-epoch second `now`.
+    pub fn sync_connectivity_at(
+        &mut self,
+        node_id: &str,
+        status: NodeConnectivityStatus,
+        now: u64,
+    ) {
         if let Some(node) = self.nodes.get(node_id) {
             let node_type = node.id.node_type.clone();
+            let previous = self.peer_router.connectivity_status(node_id);
             self.peer_router.update_node(node_id, &node_type, status);
+            if previous == NodeConnectivityStatus::Offline
+                && status == NodeConnectivityStatus::Online
+                && self.flap_breaker.record_flap(node_id, now)
+            {
+                tracing::warn!(
+                    node_id,
+                    cooldown_secs = self.flap_breaker.config().cooldown_secs,
+                    "Node is flapping; excluding it from new assignments"
+                );
+            }
         }
     }
 
+    /// Flap circuit breaker state of a node.
+    pub fn flap_breaker_state(&self, node_id: &str) -> FlapBreakerState {
+        self.flap_breaker.state(node_id, unix_now())
+    }
+
     /// Find the best peer route for `node_id` to reach the internet.
     ///
     /// Returns `None` if the node is not registered or no internet path
@@ -119,14 +174,91 @@ impl MeshCoordinator {
         self.peer_router.find_route(node_id)
     }
 
+    /// Resolve `node_id`'s peer route and count it against the chosen relay,
+    /// so other offline nodes spread across relays; see
+    /// [`PeerRouter::assign_route`].
+    pub fn assign_peer_route(&mut self, node_id: &str) -> Option<PeerRoute> {
+        self.peer_router.assign_route(node_id, unix_now())
+    }
+
+    /// Forget `node_id`'s relay assignment, e.g. when its session ends.
+    pub fn release_peer_route(&mut self, node_id: &str) {
+        self.peer_router.release_route(node_id);
+    }
+
+    /// Move assigned nodes off relays that became clearly worse than another;
+    /// call periodically.  See [`PeerRouter::rebalance`].
+    pub fn rebalance_peer_routes(&mut self) -> Vec<RouteChange> {
+        let changes = self.peer_router.rebalance(unix_now());
+        for change in &changes {
+            tracing::info!(
+                node_id = %change.source_node_id,
+                from_relay = %change.from_relay,
+                to_relay = ?change.to_relay,
+                "Rebalanced peer route"
+            );
+        }
+        changes
+    }
+
+    /// A registered relay announced it shuts down in `grace_secs` (capped at
+    /// [`MAX_RELAY_DRAIN_GRACE_SECS`]): stop choosing it for new routes and
+    /// move the nodes routed through it now, while it still forwards.
+    /// Returns the nodes that moved.
+    pub fn announce_relay_drain(&mut self, relay_id: &str, grace_secs: u64) -> Vec<RouteChange> {
+        if !self.nodes.contains_key(relay_id) {
+            return Vec::new();
+        }
+        let grace_secs = grace_secs.min(MAX_RELAY_DRAIN_GRACE_SECS);
+        tracing::info!(relay_id, grace_secs, "Relay draining");
+        self.peer_router
+            .drain_relay(relay_id, unix_now().saturating_add(grace_secs));
+        self.rebalance_peer_routes()
+    }
+
+    /// Withdraw a relay's drain announcement.
+    pub fn cancel_relay_drain(&mut self, relay_id: &str) {
+        self.peer_router.cancel_drain(relay_id);
+    }
+
+    /// Stop routing `node_id` through `relay_id`, e.g. after the relay
+    /// dropped it; see [`PeerRouter::invalidate_route`].  Returns the route
+    /// to use instead, which becomes the node's assignment.
+    pub fn invalidate_peer_route(&mut self, node_id: &str, relay_id: &str) -> Option<PeerRoute> {
+        let now = unix_now();
+        self.peer_router.invalidate_route(node_id, relay_id, now);
+        self.peer_router.assign_route(node_id, now)
+    }
+
+    /// Record the load a registered relay reports for itself; saturated
+    /// relays are avoided by [`MeshCoordinator::find_peer_route`].
+    pub fn report_relay_load(&mut self, relay_id: &str, utilization: f64, saturated: bool) {
+        if self.nodes.contains_key(relay_id) {
+            self.peer_router
+                .report_relay_load(relay_id, utilization, saturated, unix_now());
+        }
+    }
+
+    /// Record the throughput a registered relay recently forwarded for its
+    /// peers, measured against the bandwidth in its telemetry.
+    pub fn record_relay_traffic(&mut self, relay_id: &str, mbps: f64) {
+        if let Some(node) = self.nodes.get(relay_id) {
+            let capacity_mbps = node.telemetry.bandwidth_mbps;
+            self.peer_router
+                .record_relay_traffic(relay_id, mbps, capacity_mbps, unix_now());
+        }
+    }
+
     /// Select best node for a task based on requirements and strategy
     pub fn select_node_for_task(&self, requirements: TaskRequirements) -> Option<&AmbientNode> {
-        // Filter nodes that meet requirements
+        // Filter nodes that meet requirements; flapping nodes are cooling down
+        let now = unix_now();
         let eligible_nodes: Vec<&AmbientNode> = self
             .nodes
             .values()
             .filter(|node| {
                 !node.is_safe_mode()
+                    && !self.flap_breaker.is_open(&node.id.id, now)
                     && node.health_score() >= requirements.min_health_score
                     && node.telemetry.bandwidth_mbps >= requirements.min_bandwidth_mbps
                     && node.telemetry.avg_latency_ms <= requirements.max_latency_ms
@@ -241,6 +373,13 @@ impl MeshCoordinator {
     }
 }
 
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
 /// Cluster statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClusterStats {
@@ -252,7 +391,7 @@ pub struct ClusterStats {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ambient_node::{NodeId, SafetyPolicy};
+    use ambient_node::{NodeId, SafetyPolicy, TelemetrySample};
     use zk_prover::{ExecutionTrace, ZKProver};
 
     #[test]
@@ -389,6 +528,98 @@ mod tests {
         assert_eq!(route.hops[0].kind, NodeKind::Universal);
     }
 
+    #[test]
+    fn test_relay_feedback_reroutes_peer_route() {
+        let mut coordinator =
+            MeshCoordinator::new("test-cluster".to_string(), TaskAssignmentStrategy::Weighted);
+        for (id, node_type, status) in [
+            ("relay-a", "universal", NodeConnectivityStatus::Online),
+            ("relay-b", "universal", NodeConnectivityStatus::Online),
+            ("worker-1", "worker", NodeConnectivityStatus::Offline),
+        ] {
+            let node_id = NodeId::new(id, "us-east", node_type).unwrap();
+            coordinator.register_node(AmbientNode::new(node_id, SafetyPolicy::default()));
+            coordinator.sync_connectivity(id, status);
+        }
+        assert_eq!(
+            coordinator.find_peer_route("worker-1").unwrap().hops[0].node_id,
+            "relay-a"
+        );
+
+        coordinator.report_relay_load("relay-a", 0.5, true);
+        assert_eq!(
+            coordinator.find_peer_route("worker-1").unwrap().hops[0].node_id,
+            "relay-b"
+        );
+
+        let rerouted = coordinator
+            .invalidate_peer_route("worker-1", "relay-b")
+            .expect("saturated relay is still a last resort");
+        assert_eq!(rerouted.hops[0].node_id, "relay-a");
+    }
+
+    #[test]
+    fn test_rebalance_peer_routes_uses_relay_traffic() {
+        let mut coordinator =
+            MeshCoordinator::new("test-cluster".to_string(), TaskAssignmentStrategy::Weighted);
+        for (id, node_type, status) in [
+            ("relay-a", "universal", NodeConnectivityStatus::Online),
+            ("worker-1", "worker", NodeConnectivityStatus::Offline),
+        ] {
+            let node_id = NodeId::new(id, "us-east", node_type).unwrap();
+            let mut node = AmbientNode::new(node_id, SafetyPolicy::default());
+            node.ingest_telemetry(TelemetrySample {
+                bandwidth_mbps: 100.0,
+                ..TelemetrySample::default()
+            });
+            coordinator.register_node(node);
+            coordinator.sync_connectivity(id, status);
+        }
+        assert_eq!(
+            coordinator.assign_peer_route("worker-1").unwrap().hops[0].node_id,
+            "relay-a"
+        );
+
+        let node_id = NodeId::new("relay-b", "us-east", "universal").unwrap();
+        coordinator.register_node(AmbientNode::new(node_id, SafetyPolicy::default()));
+        coordinator.sync_connectivity("relay-b", NodeConnectivityStatus::Online);
+        assert!(coordinator.rebalance_peer_routes().is_empty());
+
+        coordinator.record_relay_traffic("relay-a", 95.0);
+        let changes = coordinator.rebalance_peer_routes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].to_relay.as_deref(), Some("relay-b"));
+
+        coordinator.release_peer_route("worker-1");
+        assert!(coordinator.rebalance_peer_routes().is_empty());
+    }
+
+    #[test]
+    fn test_relay_drain_announcement_moves_peer_routes() {
+        let mut coordinator =
+            MeshCoordinator::new("test-cluster".to_string(), TaskAssignmentStrategy::Weighted);
+        for (id, node_type, status) in [
+            ("relay-a", "universal", NodeConnectivityStatus::Online),
+            ("relay-b", "universal", NodeConnectivityStatus::Online),
+            ("worker-1", "worker", NodeConnectivityStatus::Offline),
+        ] {
+            let node_id = NodeId::new(id, "us-east", node_type).unwrap();
+            coordinator.register_node(AmbientNode::new(node_id, SafetyPolicy::default()));
+            coordinator.sync_connectivity(id, status);
+        }
+        coordinator.assign_peer_route("worker-1").unwrap();
+
+        let changes = coordinator.announce_relay_drain("relay-a", DEFAULT_RELAY_DRAIN_GRACE_SECS);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].from_relay, "relay-a");
+        assert_eq!(changes[0].to_relay.as_deref(), Some("relay-b"));
+        assert!(coordinator.announce_relay_drain("unknown", 10).is_empty());
+
+        coordinator.cancel_relay_drain("relay-a");
+        let route = coordinator.find_peer_route("worker-1").unwrap();
+        assert_eq!(route.standby[0].node_id, "relay-a");
+    }
+
     #[test]
     fn test_unregister_removes_from_peer_router() {
         let mut coordinator =
@@ -414,4 +645,49 @@ mod tests {
         // After removal: no relay → no route.
         assert!(coordinator.find_peer_route("node-y").is_none());
     }
+
+    #[test]
+    fn test_flapping_node_is_skipped_until_cooldown_ends() {
+        let mut coordinator =
+            MeshCoordinator::new("test-cluster".to_string(), TaskAssignmentStrategy::Weighted)
+                .with_flap_breaker(FlapBreakerConfig {
+                    threshold: Some(2),
+                    window_secs: 60,
+                    cooldown_secs: 300,
+                });
+
+        let node_id = NodeId::new("node-flap", "us-west", "compute").unwrap();
+        coordinator.register_node(AmbientNode::new(node_id, SafetyPolicy::default()));
+        let requirements = TaskRequirements {
+            min_health_score: 0.0,
+            min_bandwidth_mbps: 0.0,
+            max_latency_ms: f64::MAX,
+            required_compute_mb: 0,
+        };
+        assert!(coordinator
+            .select_node_for_task(requirements.clone())
+            .is_some());
+
+        // Repeating a status is not a flap; only offline -> online counts.
+        let now = unix_now();
+        for status in [
+            NodeConnectivityStatus::Online,
+            NodeConnectivityStatus::Online,
+            NodeConnectivityStatus::Offline,
+            NodeConnectivityStatus::Online,
+        ] {
+            coordinator.sync_connectivity_at("node-flap", status, now);
+        }
+        assert_eq!(coordinator.flap_breaker_state("node-flap").recent_flaps, 1);
+        assert!(coordinator
+            .select_node_for_task(requirements.clone())
+            .is_some());
+
+        coordinator.sync_connectivity_at("node-flap", NodeConnectivityStatus::Offline, now);
+        coordinator.sync_connectivity_at("node-flap", NodeConnectivityStatus::Online, now);
+        let state = coordinator.flap_breaker_state("node-flap");
+        assert!(state.open);
+        assert_eq!(state.open_until, Some(now + 300));
+        assert!(coordinator.select_node_for_task(requirements).is_none());
+    }
 }
@@ -7,9 +7,37 @@
 //! Routing is *connection-only*: it resolves a forwarding path to the
 //! internet but does not schedule or execute application workloads.
 
+use ambient_node::{spread_by_network, NetworkLocation};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Standby relays returned with a relayed route.
+pub const MAX_STANDBY_RELAYS: usize = 2;
+
+/// Reported utilization (0.0–1.0) at which a relay counts as saturated.
+pub const RELAY_SATURATION_UTILIZATION: f64 = 0.9;
+
+/// How long a relay load report, traffic sample or route invalidation is
+/// honoured.
+pub const ROUTE_FEEDBACK_TTL_SECS: u64 = 300;
+
+/// Peers routed through one relay at which it counts as fully loaded.
+pub const ROUTES_PER_RELAY: usize = 8;
+
+/// Load score an `Open` relay carries against a `Universal` one: Universal
+/// relays win until they are this much more loaded.
+pub const OPEN_RELAY_PENALTY: f64 = 0.5;
+
+/// Grace window a draining relay gets when it does not name one.
+pub const DEFAULT_RELAY_DRAIN_GRACE_SECS: u64 = 30;
+
+/// Longest grace window a draining relay may ask for.
+pub const MAX_RELAY_DRAIN_GRACE_SECS: u64 = 600;
+
+/// Score by which another relay must beat a source's current relay before
+/// the source is moved, so routes don't flap between similarly loaded relays.
+pub const REBALANCE_MARGIN: f64 = 0.25;
+
 /// Internet connectivity status of a node
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NodeConnectivityStatus {
@@ -33,11 +61,12 @@ pub enum NodeKind {
 }
 
 impl NodeKind {
-    /// Derive the node kind from the string stored in `NodeId::node_type`.
+    /// Derive the node kind from the string stored in `NodeId::node_type`,
+    /// using the relay rules of the canonical [`ambient_node::NodeKind`].
     pub fn from_node_type(node_type: &str) -> Self {
-        match node_type.to_lowercase().as_str() {
-            "universal" => NodeKind::Universal,
-            "open" | "gateway" => NodeKind::Open,
+        match ambient_node::NodeKind::parse(&node_type.to_lowercase()) {
+            Some(ambient_node::NodeKind::Universal) => NodeKind::Universal,
+            Some(kind) if kind.relays_traffic() => NodeKind::Open,
             _ => NodeKind::Standard,
         }
     }
@@ -61,6 +90,10 @@ pub struct PeerRoute {
     pub source_node_id: String,
     /// Ordered list of relay hops; empty means direct internet access.
     pub hops: Vec<RoutingHop>,
+    /// Relays to fail over to, in order, chosen outside the primary relay's
+    /// ASN (or region) where possible.  Empty for direct routes.
+    #[serde(default)]
+    pub standby: Vec<RoutingHop>,
 }
 
 impl PeerRoute {
@@ -70,14 +103,90 @@ impl PeerRoute {
     }
 }
 
+/// Load a relay last reported for itself.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RelayLoad {
+    /// Share of the relay's capacity in use, 0.0–1.0
+    pub utilization: f64,
+    /// Set when the relay refuses further peers, whatever its utilization
+    pub saturated: bool,
+    /// Unix-epoch second of the report
+    pub reported_at: u64,
+}
+
+impl RelayLoad {
+    fn is_fresh(&self, now: u64) -> bool {
+        now < self.reported_at.saturating_add(ROUTE_FEEDBACK_TTL_SECS)
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.saturated || self.utilization >= RELAY_SATURATION_UTILIZATION
+    }
+}
+
+/// Recent traffic a relay forwarded for its peers.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RelayTraffic {
+    /// Average forwarded throughput over the sample window
+    pub mbps: f64,
+    /// Bandwidth the relay advertises; `0.0` when unknown
+    pub capacity_mbps: f64,
+    /// Unix-epoch second the sample was taken
+    pub measured_at: u64,
+}
+
+impl RelayTraffic {
+    fn utilization(&self, now: u64) -> f64 {
+        let fresh = now < self.measured_at.saturating_add(ROUTE_FEEDBACK_TTL_SECS);
+        if fresh && self.capacity_mbps > 0.0 {
+            (self.mbps / self.capacity_mbps).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A source moved to a different relay by [`PeerRouter::rebalance`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteChange {
+    pub source_node_id: String,
+    pub from_relay: String,
+    /// `None` when no relay is left for the source
+    pub to_relay: Option<String>,
+}
+
 /// Peer router: tracks node connectivity and resolves routing paths
 ///
 /// `PeerRouter` is maintained by the `MeshCoordinator` and updated whenever
 /// a node's backhaul state changes.  Call [`PeerRouter::find_route`] to
 /// resolve the best path to the internet for any registered node.
+///
+/// Nodes feed back into relay selection: relays report their load
+/// ([`PeerRouter::report_relay_load`]) and sources drop a relay that failed
+/// them ([`PeerRouter::invalidate_route`]).  Both expire after
+/// [`ROUTE_FEEDBACK_TTL_SECS`].
+///
+/// Relays are scored by load: the highest of the reported utilization, the
+/// share of [`ROUTES_PER_RELAY`] already assigned to them
+/// ([`PeerRouter::assign_route`]) and recent traffic against their capacity
+/// ([`PeerRouter::record_relay_traffic`]).  [`PeerRouter::rebalance`] moves
+/// assigned sources off relays that have become clearly worse than another.
+///
+/// A relay about to shut down announces it ([`PeerRouter::drain_relay`]):
+/// until its deadline it is only chosen when no other relay is left, so
+/// rebalancing moves its sources away while it still forwards traffic.
 pub struct PeerRouter {
     connectivity: HashMap<String, NodeConnectivityStatus>,
     kinds: HashMap<String, NodeKind>,
+    locations: HashMap<String, NetworkLocation>,
+    loads: HashMap<String, RelayLoad>,
+    /// `(source, relay)` pairs excluded from routing, with their expiry.
+    invalidated: HashMap<(String, String), u64>,
+    traffic: HashMap<String, RelayTraffic>,
+    /// Relay each source was last assigned.
+    assignments: HashMap<String, String>,
+    /// Relays shutting down, with the Unix-epoch second they go away.
+    draining: HashMap<String, u64>,
 }
 
 impl PeerRouter {
@@ -85,9 +194,20 @@ impl PeerRouter {
         Self {
             connectivity: HashMap::new(),
             kinds: HashMap::new(),
+            locations: HashMap::new(),
+            loads: HashMap::new(),
+            invalidated: HashMap::new(),
+            traffic: HashMap::new(),
+            assignments: HashMap::new(),
+            draining: HashMap::new(),
         }
     }
 
+    /// Record a node's ASN and region for standby relay selection.
+    pub fn set_network_location(&mut self, node_id: &str, location: NetworkLocation) {
+        self.locations.insert(node_id.to_string(), location);
+    }
+
     /// Register or update a node's connectivity status and role.
     pub fn update_node(&mut self, node_id: &str, node_type: &str, status: NodeConnectivityStatus) {
         self.connectivity.insert(node_id.to_string(), status);
@@ -99,6 +219,185 @@ impl PeerRouter {
     pub fn remove_node(&mut self, node_id: &str) {
         self.connectivity.remove(node_id);
         self.kinds.remove(node_id);
+        self.locations.remove(node_id);
+        self.loads.remove(node_id);
+        self.invalidated
+            .retain(|(source, relay), _| source != node_id && relay != node_id);
+        self.traffic.remove(node_id);
+        self.assignments
+            .retain(|source, relay| source != node_id && relay != node_id);
+        self.draining.remove(node_id);
+    }
+
+    /// Record the load `relay_id` reports for itself at Unix-epoch second
+    /// `reported_at`.  Saturated relays are only chosen when no other relay
+    /// is available; otherwise less loaded relays rank first within a kind.
+    pub fn report_relay_load(
+        &mut self,
+        relay_id: &str,
+        utilization: f64,
+        saturated: bool,
+        reported_at: u64,
+    ) {
+        self.loads.insert(
+            relay_id.to_string(),
+            RelayLoad {
+                utilization: utilization.clamp(0.0, 1.0),
+                saturated,
+                reported_at,
+            },
+        );
+    }
+
+    /// The load `relay_id` last reported, if any.
+    pub fn relay_load(&self, relay_id: &str) -> Option<RelayLoad> {
+        self.loads.get(relay_id).copied()
+    }
+
+    /// Mark `relay_id` as shutting down at Unix-epoch second `until`.  It
+    /// stays a last-resort relay until then; call [`PeerRouter::rebalance`]
+    /// to move the sources assigned to it.
+    pub fn drain_relay(&mut self, relay_id: &str, until: u64) {
+        self.draining.insert(relay_id.to_string(), until);
+    }
+
+    /// Withdraw a drain announcement, e.g. when the shutdown was cancelled.
+    pub fn cancel_drain(&mut self, relay_id: &str) {
+        self.draining.remove(relay_id);
+    }
+
+    /// Whether `relay_id` announced a shutdown that is still pending at `now`.
+    pub fn is_draining(&self, relay_id: &str, now: u64) -> bool {
+        self.draining
+            .get(relay_id)
+            .is_some_and(|until| now < *until)
+    }
+
+    /// Record the throughput `relay_id` forwarded for its peers, measured at
+    /// Unix-epoch second `measured_at`, against its advertised capacity.
+    pub fn record_relay_traffic(
+        &mut self,
+        relay_id: &str,
+        mbps: f64,
+        capacity_mbps: f64,
+        measured_at: u64,
+    ) {
+        self.traffic.insert(
+            relay_id.to_string(),
+            RelayTraffic {
+                mbps: mbps.max(0.0),
+                capacity_mbps: capacity_mbps.max(0.0),
+                measured_at,
+            },
+        );
+    }
+
+    /// The traffic last recorded for `relay_id`, if any.
+    pub fn relay_traffic(&self, relay_id: &str) -> Option<RelayTraffic> {
+        self.traffic.get(relay_id).copied()
+    }
+
+    /// Record that `source_node_id` routes through `relay_id`, without
+    /// re-resolving its route (e.g. when restoring known assignments).
+    pub fn set_assignment(&mut self, source_node_id: &str, relay_id: &str) {
+        self.assignments
+            .insert(source_node_id.to_string(), relay_id.to_string());
+    }
+
+    /// The relay `source_node_id` is assigned to, if any.
+    pub fn assigned_relay(&self, source_node_id: &str) -> Option<&str> {
+        self.assignments.get(source_node_id).map(String::as_str)
+    }
+
+    /// Number of sources currently assigned to `relay_id`.
+    pub fn active_routes(&self, relay_id: &str) -> usize {
+        self.assignments
+            .values()
+            .filter(|relay| relay.as_str() == relay_id)
+            .count()
+    }
+
+    /// Resolve `source_node_id`'s route at `now` and record its relay, so
+    /// later routes for other sources see the relay's added load.  Direct
+    /// or unroutable sources lose their assignment.
+    pub fn assign_route(&mut self, source_node_id: &str, now: u64) -> Option<PeerRoute> {
+        let route = self.find_route_at(source_node_id, now);
+        match route.as_ref().and_then(|route| route.hops.first()) {
+            Some(relay) => self.set_assignment(source_node_id, &relay.node_id.clone()),
+            None => self.release_route(source_node_id),
+        }
+        route
+    }
+
+    /// Forget `source_node_id`'s relay assignment.
+    pub fn release_route(&mut self, source_node_id: &str) {
+        self.assignments.remove(source_node_id);
+    }
+
+    /// Re-resolve every assigned source at `now` and return those that moved.
+    ///
+    /// A source stays on its relay unless that relay went away, saturated,
+    /// started draining, was invalidated, or another relay now scores better
+    /// by more than
+    /// [`REBALANCE_MARGIN`].  Sources that came online directly are released
+    /// without being reported.
+    pub fn rebalance(&mut self, now: u64) -> Vec<RouteChange> {
+        let mut sources: Vec<String> = self.assignments.keys().cloned().collect();
+        // Move sources in a fixed order so rebalancing is deterministic.
+        sources.sort();
+        let mut changes = Vec::new();
+        for source in sources {
+            let Some(from_relay) = self.assignments.get(&source).cloned() else {
+                continue;
+            };
+            let route = self.assign_route(&source, now);
+            if route.as_ref().is_some_and(PeerRoute::is_direct) {
+                continue;
+            }
+            let to_relay = route.and_then(|route| route.hops.into_iter().next().map(|h| h.node_id));
+            if to_relay.as_deref() != Some(from_relay.as_str()) {
+                changes.push(RouteChange {
+                    source_node_id: source,
+                    from_relay,
+                    to_relay,
+                });
+            }
+        }
+        changes
+    }
+
+    /// Load score of `relay_id` for `source_node_id` at `now`, 0.0–1.0.  The
+    /// source's own assignment doesn't count against its current relay.
+    pub fn relay_load_score(&self, relay_id: &str, source_node_id: &str, now: u64) -> f64 {
+        let reported = self
+            .loads
+            .get(relay_id)
+            .filter(|load| load.is_fresh(now))
+            .map_or(0.0, |load| load.utilization);
+        let routes = self
+            .assignments
+            .iter()
+            .filter(|(source, relay)| {
+                relay.as_str() == relay_id && source.as_str() != source_node_id
+            })
+            .count();
+        let route_share = (routes as f64 / ROUTES_PER_RELAY as f64).min(1.0);
+        let traffic = self
+            .traffic
+            .get(relay_id)
+            .map_or(0.0, |traffic| traffic.utilization(now));
+        reported.max(route_share).max(traffic)
+    }
+
+    /// Stop routing `source_node_id` through `relay_id` (e.g. after the
+    /// relay dropped or refused it) until [`ROUTE_FEEDBACK_TTL_SECS`] after
+    /// `now`.
+    pub fn invalidate_route(&mut self, source_node_id: &str, relay_id: &str, now: u64) {
+        self.invalidated.retain(|_, until| now < *until);
+        self.invalidated.insert(
+            (source_node_id.to_string(), relay_id.to_string()),
+            now.saturating_add(ROUTE_FEEDBACK_TTL_SECS),
+        );
     }
 
     /// Return the known connectivity status for a node.
@@ -123,10 +422,19 @@ impl PeerRouter {
     /// Returns:
     /// - `Some(PeerRoute { hops: [] })` – node is directly online.
     /// - `Some(PeerRoute { hops: [relay] })` – node must hop through a relay.
-    ///   Relay selection prefers `Universal` over `Open` nodes.
+    ///   Relay selection prefers the least loaded relay, `Universal` over
+    ///   `Open` at similar load, and keeps an assigned source on its relay
+    ///   within [`REBALANCE_MARGIN`]; up to [`MAX_STANDBY_RELAYS`] further
+    ///   relays are spread across networks.
     /// - `None` – no internet path is available (source is offline and no
     ///   suitable relay exists).
     pub fn find_route(&self, source_node_id: &str) -> Option<PeerRoute> {
+        self.find_route_at(source_node_id, crate::unix_now())
+    }
+
+    /// [`PeerRouter::find_route`] at Unix-epoch second `now`, which decides
+    /// whether load reports, traffic samples and invalidations still apply.
+    pub fn find_route_at(&self, source_node_id: &str, now: u64) -> Option<PeerRoute> {
         let source_status = self.connectivity_status(source_node_id);
 
         // Direct connection: no relay needed.
@@ -134,15 +442,18 @@ impl PeerRouter {
             return Some(PeerRoute {
                 source_node_id: source_node_id.to_string(),
                 hops: vec![],
+                standby: vec![],
             });
         }
 
         // Collect online relay candidates (Universal and Open nodes only).
-        let mut candidates: Vec<(String, NodeKind)> = self
+        let candidates: Vec<(String, NodeKind)> = self
             .connectivity
             .iter()
             .filter(|(id, status)| {
-                id.as_str() != source_node_id && **status == NodeConnectivityStatus::Online
+                id.as_str() != source_node_id
+                    && **status == NodeConnectivityStatus::Online
+                    && !self.is_invalidated(source_node_id, id, now)
             })
             .filter_map(|(id, _)| {
                 let kind = self
@@ -158,26 +469,96 @@ impl PeerRouter {
             return None;
         }
 
-        // Prefer Universal relays over Open relays; break ties by node ID for
-        // deterministic selection without requiring additional state.
-        candidates.sort_by(|(id_a, kind_a), (id_b, kind_b)| {
-            let rank = |k: &NodeKind| match k {
-                NodeKind::Universal => 0u8,
-                NodeKind::Open => 1,
-                NodeKind::Standard => 2,
+        // Available relays first, then saturated ones, then draining ones;
+        // within each, the lowest load score (Open relays carry a penalty
+        // against Universal ones).  Break ties by node ID for deterministic
+        // selection.
+        let score = |id: &str, kind: NodeKind| {
+            let load = self.relay_load_score(id, source_node_id, now);
+            let saturated = load >= RELAY_SATURATION_UTILIZATION
+                || self
+                    .loads
+                    .get(id)
+                    .is_some_and(|load| load.is_fresh(now) && load.is_saturated());
+            let availability = if self.is_draining(id, now) {
+                2u8
+            } else {
+                u8::from(saturated)
             };
-            rank(kind_a).cmp(&rank(kind_b)).then(id_a.cmp(id_b))
-        });
+            let penalty = if kind == NodeKind::Universal {
+                0.0
+            } else {
+                OPEN_RELAY_PENALTY
+            };
+            (availability, load + penalty)
+        };
+        let mut candidates: Vec<(String, NodeKind, (u8, f64))> = candidates
+            .into_iter()
+            .map(|(id, kind)| {
+                let score = score(&id, kind);
+                (id, kind, score)
+            })
+            .collect();
+        candidates.sort_by(
+            |(id_a, _, (availability_a, score_a)), (id_b, _, (availability_b, score_b))| {
+                availability_a
+                    .cmp(availability_b)
+                    .then(score_a.total_cmp(score_b))
+                    .then(id_a.cmp(id_b))
+            },
+        );
+
+        // Keep the source on its current relay unless another one is clearly
+        // better.
+        if let Some(current) = self.assignments.get(source_node_id) {
+            if let Some(position) = candidates.iter().position(|(id, _, _)| id == current) {
+                let (_, _, (availability, score)) = candidates[position];
+                let (_, _, (best_availability, best_score)) = candidates[0];
+                if availability == best_availability && score <= best_score + REBALANCE_MARGIN {
+                    let current = candidates.remove(position);
+                    candidates.insert(0, current);
+                }
+            }
+        }
+        let mut candidates: Vec<(String, NodeKind)> = candidates
+            .into_iter()
+            .map(|(id, kind, _)| (id, kind))
+            .collect();
 
         let (relay_id, relay_kind) = candidates.remove(0);
+        let location = |id: &str| {
+            self.locations
+                .get(id)
+                .cloned()
+                .unwrap_or_else(|| NetworkLocation::new(None, ""))
+        };
+        let standby = spread_by_network(
+            candidates
+                .into_iter()
+                .map(|(id, kind)| {
+                    let location = location(&id);
+                    (RoutingHop { node_id: id, kind }, location)
+                })
+                .collect(),
+            &[location(&relay_id)],
+            MAX_STANDBY_RELAYS,
+            false,
+        );
         Some(PeerRoute {
             source_node_id: source_node_id.to_string(),
             hops: vec![RoutingHop {
                 node_id: relay_id,
                 kind: relay_kind,
             }],
+            standby,
         })
     }
+
+    fn is_invalidated(&self, source_node_id: &str, relay_id: &str, now: u64) -> bool {
+        self.invalidated
+            .get(&(source_node_id.to_string(), relay_id.to_string()))
+            .is_some_and(|until| now < *until)
+    }
 }
 
 impl Default for PeerRouter {
@@ -212,6 +593,9 @@ mod tests {
         assert_eq!(NodeKind::from_node_type("UNIVERSAL"), NodeKind::Universal);
         assert_eq!(NodeKind::from_node_type("open"), NodeKind::Open);
         assert_eq!(NodeKind::from_node_type("gateway"), NodeKind::Open);
+        assert_eq!(NodeKind::from_node_type("any"), NodeKind::Universal);
+        assert_eq!(NodeKind::from_node_type("open_internet"), NodeKind::Open);
+        assert_eq!(NodeKind::from_node_type("compute"), NodeKind::Standard);
         assert_eq!(NodeKind::from_node_type("worker"), NodeKind::Standard);
         assert_eq!(NodeKind::from_node_type(""), NodeKind::Standard);
     }
@@ -230,6 +614,7 @@ mod tests {
         let direct = PeerRoute {
             source_node_id: "a".to_string(),
             hops: vec![],
+            standby: vec![],
         };
         assert!(direct.is_direct());
 
@@ -239,6 +624,7 @@ mod tests {
                 node_id: "b".to_string(),
                 kind: NodeKind::Open,
             }],
+            standby: vec![],
         };
         assert!(!relayed.is_direct());
     }
@@ -346,4 +732,214 @@ mod tests {
         r.update_node("solo", "universal", NodeConnectivityStatus::Offline);
         assert!(r.find_route("solo").is_none());
     }
+
+    #[test]
+    fn test_find_route_spreads_standby_relays_across_asns() {
+        let mut r = PeerRouter::new();
+        r.update_node("source", "compute", NodeConnectivityStatus::Offline);
+        for (id, asn, region) in [
+            ("relay-a", 7922, "us-east"),
+            ("relay-b", 7922, "us-east"),
+            ("relay-c", 3320, "eu-west"),
+            ("relay-d", 3320, "eu-west"),
+        ] {
+            r.update_node(id, "universal", NodeConnectivityStatus::Online);
+            r.set_network_location(id, NetworkLocation::new(Some(asn), region));
+        }
+
+        let route = r.find_route("source").unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-a");
+        let standby: Vec<&str> = route.standby.iter().map(|h| h.node_id.as_str()).collect();
+        assert_eq!(standby, vec!["relay-c", "relay-b"]);
+    }
+
+    #[test]
+    fn test_relay_load_reports_steer_relay_selection() {
+        let mut r = PeerRouter::new();
+        r.update_node("source", "compute", NodeConnectivityStatus::Offline);
+        r.update_node("relay-a", "universal", NodeConnectivityStatus::Online);
+        r.update_node("relay-b", "universal", NodeConnectivityStatus::Online);
+        r.update_node("relay-open", "open", NodeConnectivityStatus::Online);
+
+        r.report_relay_load("relay-a", 0.6, false, 1_000);
+        r.report_relay_load("relay-b", 0.2, false, 1_000);
+        let route = r.find_route_at("source", 1_010).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-b");
+
+        // Saturated relays drop behind every other relay, even Open ones.
+        r.report_relay_load("relay-b", 0.95, false, 1_020);
+        r.report_relay_load("relay-a", 0.1, true, 1_020);
+        let route = r.find_route_at("source", 1_030).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-open");
+
+        // Stale reports no longer count.
+        let later = 1_020 + ROUTE_FEEDBACK_TTL_SECS;
+        let route = r.find_route_at("source", later).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-a");
+    }
+
+    #[test]
+    fn test_invalidated_relay_is_skipped_for_that_source() {
+        let mut r = make_router();
+        r.update_node("other-offline", "compute", NodeConnectivityStatus::Offline);
+        r.invalidate_route("node-offline", "node-universal", 1_000);
+
+        let route = r.find_route_at("node-offline", 1_001).unwrap();
+        assert_eq!(route.hops[0].node_id, "node-open");
+        // Other sources still use the relay.
+        let route = r.find_route_at("other-offline", 1_001).unwrap();
+        assert_eq!(route.hops[0].node_id, "node-universal");
+
+        let expired = 1_000 + ROUTE_FEEDBACK_TTL_SECS;
+        let route = r.find_route_at("node-offline", expired).unwrap();
+        assert_eq!(route.hops[0].node_id, "node-universal");
+
+        r.invalidate_route("node-offline", "node-open", expired);
+        r.invalidate_route("node-offline", "node-universal", expired);
+        assert!(r.find_route_at("node-offline", expired + 1).is_none());
+    }
+
+    #[test]
+    fn test_assigned_routes_spread_offline_sources_across_relays() {
+        let mut r = PeerRouter::new();
+        r.update_node("relay-a", "universal", NodeConnectivityStatus::Online);
+        r.update_node("relay-b", "universal", NodeConnectivityStatus::Online);
+        r.update_node("relay-open", "open", NodeConnectivityStatus::Online);
+        for i in 0..11 {
+            r.update_node(
+                &format!("src-{i:02}"),
+                "compute",
+                NodeConnectivityStatus::Offline,
+            );
+        }
+
+        // Universal relays fill up until their load outweighs the Open
+        // relay's penalty.
+        for i in 0..11 {
+            r.assign_route(&format!("src-{i:02}"), 1_000).unwrap();
+        }
+        assert_eq!(r.active_routes("relay-a"), 5);
+        assert_eq!(r.active_routes("relay-b"), 5);
+        assert_eq!(r.active_routes("relay-open"), 1);
+
+        // Re-resolving an assigned source keeps its relay.
+        let relay = r.assigned_relay("src-00").unwrap().to_string();
+        let route = r.assign_route("src-00", 1_001).unwrap();
+        assert_eq!(route.hops[0].node_id, relay);
+
+        // A direct source gives its relay back.
+        r.update_node("src-00", "compute", NodeConnectivityStatus::Online);
+        assert!(r.assign_route("src-00", 1_002).unwrap().is_direct());
+        assert!(r.assigned_relay("src-00").is_none());
+    }
+
+    #[test]
+    fn test_rebalance_moves_sources_off_a_busy_relay() {
+        let mut r = PeerRouter::new();
+        r.update_node("relay-a", "universal", NodeConnectivityStatus::Online);
+        for i in 0..4 {
+            let source = format!("src-{i}");
+            r.update_node(&source, "compute", NodeConnectivityStatus::Offline);
+            r.assign_route(&source, 1_000).unwrap();
+        }
+        assert_eq!(r.active_routes("relay-a"), 4);
+
+        // A new relay only pulls sources over until the two are within the
+        // margin of each other.
+        r.update_node("relay-b", "universal", NodeConnectivityStatus::Online);
+        let changes = r.rebalance(1_010);
+        assert_eq!(
+            changes,
+            vec![RouteChange {
+                source_node_id: "src-0".to_string(),
+                from_relay: "relay-a".to_string(),
+                to_relay: Some("relay-b".to_string()),
+            }]
+        );
+        assert!(r.rebalance(1_011).is_empty());
+
+        // Traffic near the relay's capacity moves the rest.
+        r.record_relay_traffic("relay-a", 80.0, 100.0, 1_020);
+        let changes = r.rebalance(1_030);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.iter().all(|change| change.from_relay == "relay-a"
+            && change.to_relay.as_deref() == Some("relay-b")));
+        assert_eq!(r.active_routes("relay-b"), 4);
+
+        // Losing the relay moves its sources whatever the margin.
+        r.remove_node("relay-b");
+        assert_eq!(r.active_routes("relay-b"), 0);
+        for i in 0..4 {
+            r.assign_route(&format!("src-{i}"), 1_040).unwrap();
+        }
+        r.update_node("relay-a", "universal", NodeConnectivityStatus::Offline);
+        let changes = r.rebalance(1_050);
+        assert_eq!(changes.len(), 4);
+        assert!(changes.iter().all(|change| change.to_relay.is_none()));
+        assert_eq!(r.active_routes("relay-a"), 0);
+    }
+
+    #[test]
+    fn test_relay_load_score_combines_reports_routes_and_traffic() {
+        let mut r = PeerRouter::new();
+        r.update_node("relay", "universal", NodeConnectivityStatus::Online);
+        assert_eq!(r.relay_load_score("relay", "src", 1_000), 0.0);
+
+        r.set_assignment("src", "relay");
+        r.set_assignment("other", "relay");
+        // The source's own assignment doesn't count against its relay.
+        let one_route = 1.0 / ROUTES_PER_RELAY as f64;
+        assert_eq!(r.relay_load_score("relay", "src", 1_000), one_route);
+
+        r.record_relay_traffic("relay", 50.0, 100.0, 1_000);
+        assert_eq!(r.relay_load_score("relay", "src", 1_000), 0.5);
+        r.report_relay_load("relay", 0.7, false, 1_000);
+        assert_eq!(r.relay_load_score("relay", "src", 1_000), 0.7);
+
+        // Stale samples drop out; unknown capacity never counts.
+        let later = 1_000 + ROUTE_FEEDBACK_TTL_SECS;
+        assert_eq!(r.relay_load_score("relay", "src", later), one_route);
+        r.record_relay_traffic("relay", 500.0, 0.0, later);
+        assert_eq!(r.relay_load_score("relay", "src", later), one_route);
+    }
+
+    #[test]
+    fn test_draining_relay_sheds_its_sources() {
+        let mut r = PeerRouter::new();
+        r.update_node("relay-a", "universal", NodeConnectivityStatus::Online);
+        r.update_node("relay-b", "open", NodeConnectivityStatus::Online);
+        for i in 0..3 {
+            let source = format!("src-{i}");
+            r.update_node(&source, "compute", NodeConnectivityStatus::Offline);
+            r.set_assignment(&source, "relay-a");
+        }
+
+        r.drain_relay("relay-a", 1_060);
+        assert!(r.is_draining("relay-a", 1_000));
+        let changes = r.rebalance(1_000);
+        assert_eq!(changes.len(), 3);
+        assert!(changes
+            .iter()
+            .all(|change| change.to_relay.as_deref() == Some("relay-b")));
+        // New routes avoid it too, and it is only a standby.
+        r.update_node("src-new", "compute", NodeConnectivityStatus::Offline);
+        let route = r.find_route_at("src-new", 1_000).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-b");
+        assert_eq!(route.standby[0].node_id, "relay-a");
+
+        // The draining relay is still a last resort before its deadline.
+        r.update_node("relay-b", "open", NodeConnectivityStatus::Offline);
+        let route = r.find_route_at("src-new", 1_000).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-a");
+
+        // Back from its restart, it is an ordinary relay again.
+        r.update_node("relay-b", "open", NodeConnectivityStatus::Online);
+        assert!(!r.is_draining("relay-a", 1_060));
+        let route = r.find_route_at("src-new", 1_060).unwrap();
+        assert_eq!(route.hops[0].node_id, "relay-a");
+
+        r.drain_relay("relay-a", 2_000);
+        r.cancel_drain("relay-a");
+        assert!(!r.is_draining("relay-a", 1_100));
+    }
 }
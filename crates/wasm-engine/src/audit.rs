@@ -0,0 +1,154 @@
+use crate::{ExecutionTrace, SandboxCapabilities, SandboxLimits, WasmResult};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Machine-readable record of how the sandbox constrained one execution.
+///
+/// Nodes sign the report and attach it to the task's provenance bundle so
+/// security teams have evidence of sandbox enforcement.  Field order and
+/// sorted maps keep the serialized form, and so [`SandboxReport::digest`],
+/// stable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxReport {
+    pub module_hash: String,
+    pub function_name: String,
+    /// [`ExecutionTrace::hash`] of the execution this report covers.
+    pub trace_hash: String,
+    /// Unix seconds.
+    pub started_at: u64,
+    pub execution_time_ms: u64,
+    pub gas_used: u64,
+    pub success: bool,
+    pub limits: SandboxLimits,
+    pub capabilities: SandboxCapabilities,
+    /// Host function name → number of calls.
+    pub hostcalls: BTreeMap<String, u64>,
+    /// Checkpoint scratch-store entries the module read or wrote
+    /// (`checkpoint/resumed`, `checkpoint/latest`).
+    pub scratch_files: BTreeSet<String>,
+}
+
+impl SandboxReport {
+    /// Canonical bytes that nodes sign.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("sandbox report should serialize")
+    }
+
+    /// Hex SHA3-256 of [`SandboxReport::canonical_bytes`].
+    pub fn digest(&self) -> String {
+        format!("{:x}", Sha3_256::digest(self.canonical_bytes()))
+    }
+
+    /// [`environment_fingerprint`] of the sandbox this execution ran in.
+    pub fn environment_fingerprint(&self) -> String {
+        environment_fingerprint(&self.limits, &self.capabilities)
+    }
+}
+
+/// Hex SHA3-256 identifying a sandbox configuration: its limits and its
+/// capabilities, declared egress included.  A replay whose fingerprint
+/// matches a report's ran under the same constraints as the original.
+pub fn environment_fingerprint(
+    limits: &SandboxLimits,
+    capabilities: &SandboxCapabilities,
+) -> String {
+    let bytes =
+        serde_json::to_vec(&(limits, capabilities)).expect("sandbox settings should serialize");
+    format!("{:x}", Sha3_256::digest(bytes))
+}
+
+/// Collects sandbox activity while a module runs.
+///
+/// The engine hands the auditor to the execution's [`crate::HostContext`],
+/// whose host functions report each call here.  Modules get no socket or
+/// filesystem imports, so there are no network attempts to record.
+#[derive(Debug, Clone, Default)]
+pub struct SandboxAuditor {
+    hostcalls: BTreeMap<String, u64>,
+    scratch_files: BTreeSet<String>,
+}
+
+impl SandboxAuditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_hostcall(&mut self, name: &str) {
+        *self.hostcalls.entry(name.to_string()).or_default() += 1;
+    }
+
+    pub fn record_scratch_file(&mut self, path: impl Into<String>) {
+        self.scratch_files.insert(path.into());
+    }
+
+    /// Build the report for a finished execution.
+    pub fn finish(
+        self,
+        trace: &ExecutionTrace,
+        result: &WasmResult,
+        limits: &SandboxLimits,
+        capabilities: &SandboxCapabilities,
+    ) -> SandboxReport {
+        SandboxReport {
+            module_hash: trace.module_hash.clone(),
+            function_name: trace.function_name.clone(),
+            trace_hash: trace.hash(),
+            started_at: trace
+                .timestamp
+                .saturating_sub(result.execution_time_ms / 1000),
+            execution_time_ms: result.execution_time_ms,
+            gas_used: result.gas_used,
+            success: result.success,
+            limits: limits.clone(),
+            capabilities: capabilities.clone(),
+            hostcalls: self.hostcalls,
+            scratch_files: self.scratch_files,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EgressRule;
+
+    #[test]
+    fn test_auditor_records_activity() {
+        let caps = SandboxCapabilities::no_access()
+            .with_egress(vec![EgressRule::new("api.example.com", vec![443])]);
+        let mut auditor = SandboxAuditor::new();
+        auditor.record_hostcall("checkpoint_write");
+        auditor.record_hostcall("checkpoint_write");
+        auditor.record_hostcall("secret_read");
+        auditor.record_scratch_file("checkpoint/latest");
+        auditor.record_scratch_file("checkpoint/latest");
+
+        let trace = ExecutionTrace::new("m".into(), "run".into(), vec![], vec![1], 5, 7);
+        let result = WasmResult {
+            output: vec![1],
+            execution_time_ms: 5,
+            gas_used: 7,
+            success: true,
+            error: None,
+            usage: Default::default(),
+        };
+        let report = auditor.finish(&trace, &result, &SandboxLimits::strict(), &caps);
+
+        assert_eq!(report.hostcalls["checkpoint_write"], 2);
+        assert_eq!(report.hostcalls["secret_read"], 1);
+        assert_eq!(report.scratch_files.len(), 1);
+        assert_eq!(report.trace_hash, trace.hash());
+
+        let round_trip: SandboxReport = serde_json::from_slice(&report.canonical_bytes()).unwrap();
+        assert_eq!(round_trip.digest(), report.digest());
+        assert_eq!(
+            round_trip.environment_fingerprint(),
+            environment_fingerprint(&SandboxLimits::strict(), &caps)
+        );
+        assert_ne!(
+            report.environment_fingerprint(),
+            environment_fingerprint(&SandboxLimits::default(), &caps)
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use thiserror::Error;
+
+/// Import module under which checkpoint host functions are exposed.
+pub const CHECKPOINT_IMPORT_MODULE: &str = "ambient";
+/// `checkpoint_write(ptr: i32, len: i32) -> i32`: snapshot `len` bytes of
+/// guest memory at `ptr`.  Returns the new sequence number, or a negative
+/// [`CheckpointError::code`].
+pub const CHECKPOINT_WRITE_FN: &str = "checkpoint_write";
+/// `checkpoint_read(ptr: i32, cap: i32) -> i32`: copy the checkpoint the task
+/// resumed from into guest memory.  Returns the byte length (`0` when starting
+/// fresh), or a negative [`CheckpointError::code`].
+pub const CHECKPOINT_READ_FN: &str = "checkpoint_read";
+
+/// Default upper bound on a single checkpoint snapshot.
+pub const DEFAULT_MAX_CHECKPOINT_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CheckpointError {
+    #[error("checkpoint of {size} bytes exceeds the {max} byte limit")]
+    TooLarge { size: usize, max: usize },
+    #[error("checkpoint range lies outside guest memory")]
+    OutOfBounds,
+    #[error("guest buffer of {capacity} bytes cannot hold a {size} byte checkpoint")]
+    BufferTooSmall { size: usize, capacity: usize },
+}
+
+impl CheckpointError {
+    /// Negative status returned to the guest by the host functions.
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::TooLarge { .. } => -1,
+            Self::OutOfBounds => -2,
+            Self::BufferTooSmall { .. } => -3,
+        }
+    }
+}
+
+/// One state snapshot emitted by a checkpoint-aware module.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Increases by one with every snapshot of a task, across handoffs.
+    pub seq: u64,
+    pub data: Vec<u8>,
+}
+
+impl Checkpoint {
+    /// Hex SHA3-256 of the snapshot bytes.
+    pub fn sha3_256(&self) -> String {
+        format!("{:x}", Sha3_256::digest(&self.data))
+    }
+}
+
+/// Per-execution scratch store backing the checkpoint host functions.
+///
+/// Only the newest snapshot is kept; the node uploads it to the coordinator
+/// so that another node can resume the task if this one drains or fails.
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    max_bytes: usize,
+    resumed_from: Option<Checkpoint>,
+    latest: Option<Checkpoint>,
+}
+
+impl Default for CheckpointStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CHECKPOINT_BYTES)
+    }
+}
+
+impl CheckpointStore {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            resumed_from: None,
+            latest: None,
+        }
+    }
+
+    /// Start from a checkpoint taken on a previous node.
+    pub fn resume_from(mut self, checkpoint: Checkpoint) -> Self {
+        self.resumed_from = Some(checkpoint);
+        self
+    }
+
+    pub fn resumed_from(&self) -> Option<&Checkpoint> {
+        self.resumed_from.as_ref()
+    }
+
+    /// Newest snapshot written during this execution.
+    pub fn latest(&self) -> Option<&Checkpoint> {
+        self.latest.as_ref()
+    }
+
+    pub fn into_latest(self) -> Option<Checkpoint> {
+        self.latest
+    }
+
+    /// Record a snapshot, returning its sequence number.
+    pub fn write(&mut self, data: &[u8]) -> Result<u64, CheckpointError> {
+        if data.len() > self.max_bytes {
+            return Err(CheckpointError::TooLarge {
+                size: data.len(),
+                max: self.max_bytes,
+            });
+        }
+        let seq = self
+            .latest
+            .as_ref()
+            .or(self.resumed_from.as_ref())
+            .map_or(1, |previous| previous.seq + 1);
+        self.latest = Some(Checkpoint {
+            seq,
+            data: data.to_vec(),
+        });
+        Ok(seq)
+    }
+
+    /// Body of the `checkpoint_write` host function.
+    pub fn host_write(&mut self, memory: &[u8], ptr: u32, len: u32) -> i32 {
+        let result = guest_range(memory.len(), ptr, len)
+            .ok_or(CheckpointError::OutOfBounds)
+            .and_then(|range| self.write(&memory[range]));
+        match result {
+            Ok(seq) => seq.min(i32::MAX as u64) as i32,
+            Err(err) => err.code(),
+        }
+    }
+
+    /// Body of the `checkpoint_read` host function.
+    pub fn host_read(&self, memory: &mut [u8], ptr: u32, capacity: u32) -> i32 {
+        let Some(checkpoint) = &self.resumed_from else {
+            return 0;
+        };
+        let size = checkpoint.data.len();
+        if size > capacity as usize {
+            return CheckpointError::BufferTooSmall {
+                size,
+                capacity: capacity as usize,
+            }
+            .code();
+        }
+        let Some(range) = guest_range(memory.len(), ptr, size as u32) else {
+            return CheckpointError::OutOfBounds.code();
+        };
+        memory[range].copy_from_slice(&checkpoint.data);
+        size as i32
+    }
+}
+
+/// Byte range `ptr..ptr + len` of a guest memory of `memory_len` bytes, if
+/// it lies inside it.
+pub(crate) fn guest_range(memory_len: usize, ptr: u32, len: u32) -> Option<std::ops::Range<usize>> {
+    let start = ptr as usize;
+    let end = start.checked_add(len as usize)?;
+    (end <= memory_len).then_some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_write_then_resume_elsewhere() {
+        let mut memory = vec![0u8; 64];
+        memory[8..12].copy_from_slice(b"step");
+
+        let mut first = CheckpointStore::new(16);
+        assert_eq!(first.host_write(&memory, 8, 4), 1);
+        assert_eq!(first.host_write(&memory, 8, 4), 2);
+        assert_eq!(first.host_write(&memory, 60, 8), -2);
+        assert_eq!(first.host_write(&[0u8; 32], 0, 32), -1);
+        let taken = first.into_latest().unwrap();
+        assert_eq!(taken.seq, 2);
+
+        let mut second = CheckpointStore::new(16).resume_from(taken.clone());
+        let mut guest = vec![0u8; 16];
+        assert_eq!(second.host_read(&mut guest, 4, 2), -3);
+        assert_eq!(second.host_read(&mut guest, 4, 8), 4);
+        assert_eq!(&guest[4..8], b"step");
+        assert_eq!(second.write(b"next").unwrap(), 3);
+        assert_eq!(second.resumed_from().unwrap().sha3_256(), taken.sha3_256());
+    }
+
+    #[test]
+    fn test_fresh_store_reads_nothing() {
+        let store = CheckpointStore::default();
+        assert_eq!(store.host_read(&mut [0u8; 4], 0, 4), 0);
+        assert!(store.latest().is_none());
+    }
+}
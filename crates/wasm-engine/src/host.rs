@@ -0,0 +1,140 @@
+use crate::{
+    CheckpointStore, ReadOnlySecrets, SandboxAuditor, CHECKPOINT_READ_FN, CHECKPOINT_WRITE_FN,
+    SECRET_READ_FN,
+};
+
+/// Scratch entry recorded when a module reads the checkpoint it resumed from.
+pub const RESUMED_CHECKPOINT_ENTRY: &str = "checkpoint/resumed";
+/// Scratch entry recorded when a module writes a checkpoint.
+pub const LATEST_CHECKPOINT_ENTRY: &str = "checkpoint/latest";
+
+/// State behind the host functions a module imports from
+/// [`crate::CHECKPOINT_IMPORT_MODULE`] during one execution.
+///
+/// The engine owns the context while the module runs and hands it back
+/// afterwards.  Each method is the body of one host function, working on a
+/// view of the guest's linear memory, so the functions behave the same
+/// whichever runtime links them.  Every call is reported to `auditor`.
+#[derive(Debug, Default)]
+pub struct HostContext {
+    pub secrets: ReadOnlySecrets,
+    pub checkpoints: CheckpointStore,
+    pub auditor: SandboxAuditor,
+}
+
+impl HostContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_secrets(mut self, secrets: ReadOnlySecrets) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    pub fn with_checkpoints(mut self, checkpoints: CheckpointStore) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
+
+    pub fn with_auditor(mut self, auditor: SandboxAuditor) -> Self {
+        self.auditor = auditor;
+        self
+    }
+
+    /// [`crate::SECRET_READ_FN`]
+    pub fn secret_read(&mut self, memory: &mut [u8], args: [u32; 4]) -> i32 {
+        self.auditor.record_hostcall(SECRET_READ_FN);
+        let [name_ptr, name_len, ptr, capacity] = args;
+        self.secrets
+            .host_read(memory, name_ptr, name_len, ptr, capacity)
+    }
+
+    /// [`crate::CHECKPOINT_WRITE_FN`]
+    pub fn checkpoint_write(&mut self, memory: &[u8], args: [u32; 2]) -> i32 {
+        self.auditor.record_hostcall(CHECKPOINT_WRITE_FN);
+        let [ptr, len] = args;
+        let status = self.checkpoints.host_write(memory, ptr, len);
+        if status > 0 {
+            self.auditor.record_scratch_file(LATEST_CHECKPOINT_ENTRY);
+        }
+        status
+    }
+
+    /// [`crate::CHECKPOINT_READ_FN`]
+    pub fn checkpoint_read(&mut self, memory: &mut [u8], args: [u32; 2]) -> i32 {
+        self.auditor.record_hostcall(CHECKPOINT_READ_FN);
+        let [ptr, capacity] = args;
+        let status = self.checkpoints.host_read(memory, ptr, capacity);
+        if status > 0 {
+            self.auditor.record_scratch_file(RESUMED_CHECKPOINT_ENTRY);
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A counter that checkpoints after every step, as a module would.
+    fn run_counter(host: &mut HostContext, memory: &mut [u8], interrupt_at: Option<u32>) -> u32 {
+        host.checkpoint_read(memory, [0, 4]);
+        loop {
+            let count = u32::from_le_bytes(memory[..4].try_into().unwrap()) + 1;
+            memory[..4].copy_from_slice(&count.to_le_bytes());
+            assert!(host.checkpoint_write(memory, [0, 4]) > 0);
+            if Some(count) == interrupt_at || count == 5 {
+                return count;
+            }
+        }
+    }
+
+    #[test]
+    fn test_interrupted_run_resumes_from_its_checkpoint() {
+        let mut first = HostContext::new();
+        assert_eq!(run_counter(&mut first, &mut [0u8; 16], Some(3)), 3);
+        let saved = first.checkpoints.into_latest().unwrap();
+        assert_eq!(saved.seq, 3);
+
+        // A fresh node's memory starts zeroed; the checkpoint restores it.
+        let mut second =
+            HostContext::new().with_checkpoints(CheckpointStore::default().resume_from(saved));
+        assert_eq!(run_counter(&mut second, &mut [0u8; 16], None), 5);
+        assert_eq!(second.checkpoints.latest().unwrap().seq, 5);
+    }
+
+    #[test]
+    fn test_host_functions_feed_the_auditor() {
+        use crate::{ExecutionTrace, SandboxCapabilities, SandboxLimits, WasmResult};
+
+        let mut host = HostContext::new();
+        let mut memory = [0u8; 16];
+        run_counter(&mut host, &mut memory, Some(2));
+        host.secret_read(&mut memory, [0, 4, 8, 8]);
+
+        let trace = ExecutionTrace::new("m".into(), "run".into(), vec![], vec![], 1, 1);
+        let result = WasmResult {
+            output: vec![],
+            execution_time_ms: 1,
+            gas_used: 1,
+            success: true,
+            error: None,
+            usage: Default::default(),
+        };
+        let report = host.auditor.finish(
+            &trace,
+            &result,
+            &SandboxLimits::strict(),
+            &SandboxCapabilities::default(),
+        );
+        assert_eq!(report.hostcalls[CHECKPOINT_READ_FN], 1);
+        assert_eq!(report.hostcalls[CHECKPOINT_WRITE_FN], 2);
+        assert_eq!(report.hostcalls[SECRET_READ_FN], 1);
+        // A fresh run had nothing to resume from.
+        assert_eq!(
+            report.scratch_files.iter().collect::<Vec<_>>(),
+            vec![LATEST_CHECKPOINT_ENTRY]
+        );
+    }
+}
@@ -2,19 +2,35 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::time::Instant;
 
+#[cfg(feature = "wasm-runtime")]
+use std::sync::{Arc, Mutex};
 #[cfg(feature = "wasm-runtime")]
 use wasmedge_sdk::{
     config::{CommonConfigOptions, ConfigBuilder},
-    params, VmBuilder,
+    error::HostFuncError,
+    params, Caller, CallingFrame, ImportObject, ImportObjectBuilder, NeverType, VmBuilder,
+    WasmValue,
 };
 
+pub mod audit;
+pub mod checkpoint;
+pub mod host;
 pub mod limits;
 pub mod sandbox;
+pub mod secrets;
 pub mod trace;
+pub mod trace_store;
+pub mod usage;
 
+pub use audit::*;
+pub use checkpoint::*;
+pub use host::*;
 pub use limits::*;
 pub use sandbox::*;
+pub use secrets::*;
 pub use trace::*;
+pub use trace_store::*;
+pub use usage::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WasmRuntime {
@@ -30,6 +46,23 @@ pub struct WasmCall {
     pub inputs: Vec<u8>,
 }
 
+impl WasmCall {
+    /// Call for a task, passing the task's JSON `inputs` to the module as
+    /// their compact serialization.  Nodes and replays must build calls the
+    /// same way for their traces to match.
+    pub fn for_task(
+        module_path: impl Into<String>,
+        function_name: impl Into<String>,
+        inputs: &serde_json::Value,
+    ) -> Self {
+        Self {
+            module_path: module_path.into(),
+            function_name: function_name.into(),
+            inputs: serde_json::to_vec(inputs).expect("JSON value should serialize"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WasmResult {
     pub output: Vec<u8>,
@@ -37,11 +70,15 @@ pub struct WasmResult {
     pub gas_used: u64,
     pub success: bool,
     pub error: Option<String>,
+    /// Resources the execution consumed.
+    #[serde(default)]
+    pub usage: ResourceUsage,
 }
 
 pub struct WasmEngine {
     _runtime: WasmRuntime,
     limits: SandboxLimits,
+    capabilities: SandboxCapabilities,
 }
 
 impl WasmEngine {
@@ -49,22 +86,44 @@ impl WasmEngine {
         Self {
             _runtime: runtime,
             limits,
+            capabilities: SandboxCapabilities::default(),
         }
     }
 
+    /// Replace the sandbox capabilities, e.g. to grant a task's declared egress.
+    pub fn with_capabilities(mut self, capabilities: SandboxCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
     pub async fn execute(&self, call: WasmCall) -> Result<WasmResult> {
+        let (result, _) = self.execute_in(call, HostContext::default()).await?;
+        Ok(result)
+    }
+
+    /// Execute with `host` backing the module's host functions, returning
+    /// the context once the module has finished with it.
+    pub async fn execute_in(
+        &self,
+        call: WasmCall,
+        host: HostContext,
+    ) -> Result<(WasmResult, HostContext)> {
         let start = Instant::now();
 
         let canonical_module_path = match canonicalize_module_path(&call.module_path) {
             Ok(p) => p,
             Err(_) => {
-                return Ok(WasmResult {
-                    output: vec![],
-                    execution_time_ms: 0,
-                    gas_used: 0,
-                    success: false,
-                    error: Some(format!("Module not found: {}", call.module_path)),
-                })
+                return Ok((
+                    WasmResult {
+                        output: vec![],
+                        execution_time_ms: 0,
+                        gas_used: 0,
+                        success: false,
+                        error: Some(format!("Module not found: {}", call.module_path)),
+                        usage: ResourceUsage::default(),
+                    },
+                    host,
+                ))
             }
         };
 
@@ -72,19 +131,23 @@ impl WasmEngine {
         call.module_path = canonical_module_path.to_string_lossy().to_string();
 
         if !std::path::Path::new(&call.module_path).exists() {
-            return Ok(WasmResult {
-                output: vec![],
-                execution_time_ms: 0,
-                gas_used: 0,
-                success: false,
-                error: Some(format!("Module not found: {}", call.module_path)),
-            });
+            return Ok((
+                WasmResult {
+                    output: vec![],
+                    execution_time_ms: 0,
+                    gas_used: 0,
+                    success: false,
+                    error: Some(format!("Module not found: {}", call.module_path)),
+                    usage: ResourceUsage::default(),
+                },
+                host,
+            ));
         }
 
         #[cfg(feature = "wasm-runtime")]
         {
             match self._runtime {
-                WasmRuntime::WasmEdge => self.execute_wasmedge(&call, start).await,
+                WasmRuntime::WasmEdge => self.execute_wasmedge(&call, start, host).await,
                 _ => Err(anyhow::anyhow!(
                     "Runtime not implemented: {:?}",
                     self._runtime
@@ -95,52 +158,76 @@ impl WasmEngine {
         #[cfg(not(feature = "wasm-runtime"))]
         {
             let execution_time = start.elapsed().as_millis() as u64;
-            Ok(WasmResult {
-                output: vec![],
-                execution_time_ms: execution_time,
-                gas_used: 0,
-                success: false,
-                error: Some(
-                    "WASM runtime not enabled. Build with --features wasm-runtime".to_string(),
-                ),
-            })
+            Ok((
+                WasmResult {
+                    output: vec![],
+                    execution_time_ms: execution_time,
+                    gas_used: 0,
+                    success: false,
+                    error: Some(
+                        "WASM runtime not enabled. Build with --features wasm-runtime".to_string(),
+                    ),
+                    usage: ResourceUsage::default(),
+                },
+                host,
+            ))
         }
     }
 
     #[cfg(feature = "wasm-runtime")]
-    async fn execute_wasmedge(&self, call: &WasmCall, start: Instant) -> Result<WasmResult> {
+    async fn execute_wasmedge(
+        &self,
+        call: &WasmCall,
+        start: Instant,
+        host: HostContext,
+    ) -> Result<(WasmResult, HostContext)> {
         let config = ConfigBuilder::new(CommonConfigOptions::default())
             .with_bulk_memory_operations(true)
             .build()?;
 
         let mut vm = VmBuilder::new().with_config(config).build()?;
 
+        // Host functions are linked before the module, so a module importing
+        // anything else from the host module fails to instantiate.
+        let host = Arc::new(Mutex::new(host));
+        vm.register_import_module(&host_imports(&host)?)?;
+
         vm.load_wasm_from_file(&call.module_path)?;
         vm.validate()?;
 
         let max_duration = std::time::Duration::from_secs(self.limits.timeout_seconds as u64);
-        let result = tokio::time::timeout(max_duration, async move {
+        let cpu = CpuTimer::start();
+        let result = tokio::time::timeout(max_duration, async {
             vm.run_func(Some(&call.function_name), params!())
         })
         .await;
 
         let execution_time = start.elapsed().as_millis() as u64;
-
-        match result {
-            Err(_) => Ok(WasmResult {
+        let pages = vm
+            .active_module()
+            .ok()
+            .and_then(|module| module.memory("memory").ok())
+            .map_or(0, |memory| memory.page());
+        let usage = ResourceUsage::from_pages(pages, cpu.elapsed());
+        drop(vm);
+        let host = std::mem::take(&mut *host.lock().unwrap());
+
+        let result = match result {
+            Err(_) => WasmResult {
                 output: vec![],
                 execution_time_ms: execution_time,
                 gas_used: self.limits.max_instructions,
                 success: false,
                 error: Some("Timeout exceeded - execution cancelled".to_string()),
-            }),
+                usage,
+            },
             Ok(Ok(returns)) => {
                 let output = if returns.is_empty() {
                     vec![0u8]
                 } else {
                     returns[0].to_i32().to_le_bytes().to_vec()
                 };
-                Ok(WasmResult {
+                WasmResult {
                     output,
                     execution_time_ms: execution_time,
                     gas_used: self
@@ -149,9 +236,10 @@ impl WasmEngine {
                         .min(execution_time.saturating_mul(10_000)),
                     success: true,
                     error: None,
-                })
+                    usage,
+                }
             }
-            Ok(Err(e)) => Ok(WasmResult {
+            Ok(Err(e)) => WasmResult {
                 output: vec![],
                 execution_time_ms: execution_time,
                 gas_used: self
@@ -160,12 +248,23 @@ impl WasmEngine {
                     .min(execution_time.saturating_mul(10_000)),
                 success: false,
                 error: Some(e.to_string()),
-            }),
-        }
+                usage,
+            },
+        };
+        Ok((result, host))
     }
 
     pub async fn execute_with_trace(&self, call: WasmCall) -> Result<(WasmResult, ExecutionTrace)> {
-        let result = self.execute(call.clone()).await?;
+        let (result, trace, _) = self.execute_traced(call, HostContext::default()).await?;
+        Ok((result, trace))
+    }
+
+    async fn execute_traced(
+        &self,
+        call: WasmCall,
+        host: HostContext,
+    ) -> Result<(WasmResult, ExecutionTrace, HostContext)> {
+        let (result, host) = self.execute_in(call.clone(), host).await?;
 
         let trace = ExecutionTrace {
             module_hash: Self::hash_module(&call.module_path)?,
@@ -177,9 +276,64 @@ impl WasmEngine {
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)?
                 .as_secs(),
+            truncated: false,
+            full_hash: None,
+            egress: if self.capabilities.network_access {
+                self.capabilities.egress.clone()
+            } else {
+                Vec::new()
+            },
         };
 
-        Ok((result, trace))
+        Ok((result, trace, host))
+    }
+
+    /// Execute with tracing and produce a [`SandboxReport`] from the host
+    /// calls `auditor` collected, for the node to sign and attach to the
+    /// task's provenance bundle.
+    pub async fn execute_with_audit(
+        &self,
+        call: WasmCall,
+        auditor: SandboxAuditor,
+    ) -> Result<(WasmResult, ExecutionTrace, SandboxReport)> {
+        let (result, trace, host) = self
+            .execute_traced(call, HostContext::new().with_auditor(auditor))
+            .await?;
+        let report = host
+            .auditor
+            .finish(&trace, &result, &self.limits, &self.capabilities);
+        Ok((result, trace, report))
+    }
+
+    /// Execute with task secrets mounted read-only for the duration of the
+    /// call; the module reads them through [`SECRET_READ_FN`].  Secrets are
+    /// wiped once execution finishes, whether it succeeded, failed, or timed
+    /// out.
+    pub async fn execute_with_secrets(
+        &self,
+        call: WasmCall,
+        secrets: ReadOnlySecrets,
+    ) -> Result<WasmResult> {
+        let (result, mut host) = self
+            .execute_in(call, HostContext::new().with_secrets(secrets))
+            .await?;
+        host.secrets.wipe();
+        Ok(result)
+    }
+
+    /// Execute a checkpoint-aware module with `store` as its checkpoint
+    /// scratch store, returning the store so the newest snapshot can be
+    /// uploaded.  Modules reach the store through the
+    /// [`CHECKPOINT_IMPORT_MODULE`] host functions.
+    pub async fn execute_with_checkpoints(
+        &self,
+        call: WasmCall,
+        store: CheckpointStore,
+    ) -> Result<(WasmResult, CheckpointStore)> {
+        let (result, host) = self
+            .execute_in(call, HostContext::new().with_checkpoints(store))
+            .await?;
+        Ok((result, host.checkpoints))
     }
 
     pub async fn verify_determinism(&self, _module_hash: &str, _inputs: &[u8]) -> bool {
@@ -197,15 +351,112 @@ impl WasmEngine {
     pub fn limits(&self) -> &SandboxLimits {
         &self.limits
     }
+
+    pub fn capabilities(&self) -> &SandboxCapabilities {
+        &self.capabilities
+    }
+
+    /// [`environment_fingerprint`] of this engine's sandbox.
+    pub fn environment_fingerprint(&self) -> String {
+        environment_fingerprint(&self.limits, &self.capabilities)
+    }
+
+    /// Whether this build can execute modules (the `wasm-runtime` feature).
+    pub fn runtime_available() -> bool {
+        cfg!(feature = "wasm-runtime")
+    }
+}
+
+/// The [`CHECKPOINT_IMPORT_MODULE`] import object, each function locking
+/// `host` for the length of the call.
+#[cfg(feature = "wasm-runtime")]
+fn host_imports(host: &Arc<Mutex<HostContext>>) -> Result<ImportObject<NeverType>> {
+    let secrets = Arc::clone(host);
+    let writer = Arc::clone(host);
+    let reader = Arc::clone(host);
+    let import = ImportObjectBuilder::new()
+        .with_func::<(i32, i32, i32, i32), i32, NeverType>(
+            SECRET_READ_FN,
+            move |frame, args, _| {
+                let args = guest_args(&args)?;
+                with_guest_memory(frame, |memory| {
+                    secrets.lock().unwrap().secret_read(memory, args)
+                })
+            },
+            None,
+        )?
+        .with_func::<(i32, i32), i32, NeverType>(
+            CHECKPOINT_WRITE_FN,
+            move |frame, args, _| {
+                let args = guest_args(&args)?;
+                with_guest_memory(frame, |memory| {
+                    writer.lock().unwrap().checkpoint_write(memory, args)
+                })
+            },
+            None,
+        )?
+        .with_func::<(i32, i32), i32, NeverType>(
+            CHECKPOINT_READ_FN,
+            move |frame, args, _| {
+                let args = guest_args(&args)?;
+                with_guest_memory(frame, |memory| {
+                    reader.lock().unwrap().checkpoint_read(memory, args)
+                })
+            },
+            None,
+        )?
+        .build(CHECKPOINT_IMPORT_MODULE, None)?;
+    Ok(import)
+}
+
+/// Host function arguments as the unsigned offsets and lengths they are.
+#[cfg(feature = "wasm-runtime")]
+fn guest_args<const N: usize>(args: &[WasmValue]) -> Result<[u32; N], HostFuncError> {
+    let mut out = [0u32; N];
+    if args.len() != N {
+        return Err(HostFuncError::User(1));
+    }
+    for (slot, arg) in out.iter_mut().zip(args) {
+        *slot = arg.to_i32() as u32;
+    }
+    Ok(out)
+}
+
+/// Run a host function body over the caller's linear memory, returning its
+/// status to the guest.
+#[cfg(feature = "wasm-runtime")]
+fn with_guest_memory(
+    frame: CallingFrame,
+    body: impl FnOnce(&mut [u8]) -> i32,
+) -> Result<Vec<WasmValue>, HostFuncError> {
+    let caller = Caller::new(frame);
+    let mut memory = caller.memory(0).ok_or(HostFuncError::User(2))?;
+    let len = memory.size().min(u64::from(u32::MAX)) as u32;
+    let data = memory
+        .data_pointer_mut(0, len)
+        .map_err(|_| HostFuncError::User(2))?;
+    // SAFETY: the guest is suspended inside this host call, so nothing else
+    // touches its memory while the slice is alive, and `len` bytes from
+    // offset 0 are in bounds.
+    let memory = unsafe { std::slice::from_raw_parts_mut(data, len as usize) };
+    Ok(vec![WasmValue::from_i32(body(memory))])
+}
+
+/// Directories modules may be loaded from (`WASM_ALLOWED_ROOTS`, comma
+/// separated), as configured.
+pub fn module_roots() -> Vec<String> {
+    std::env::var("WASM_ALLOWED_ROOTS")
+        .unwrap_or_else(|_| "./wasm-modules,./tmp".to_string())
+        .split(',')
+        .map(|root| root.trim().to_string())
+        .collect()
 }
 
 fn canonicalize_module_path(path: &str) -> Result<std::path::PathBuf> {
     let canonical = std::fs::canonicalize(path)?;
-    let roots =
-        std::env::var("WASM_ALLOWED_ROOTS").unwrap_or_else(|_| "./wasm-modules,./tmp".to_string());
-    let allowed = roots
-        .split(',')
-        .filter_map(|r| std::fs::canonicalize(r.trim()).ok())
+    let allowed = module_roots()
+        .iter()
+        .filter_map(|r| std::fs::canonicalize(r).ok())
         .any(|root| canonical.starts_with(root));
     if !allowed {
         return Err(anyhow::anyhow!("Module path outside allowed roots"));
@@ -293,6 +544,123 @@ mod tests {
                 .contains("WASM runtime not enabled"));
         });
     }
+    /// Compile `wat` into a module under a fresh allowed root.
+    #[cfg(feature = "wasm-runtime")]
+    fn stage_wat(name: &str, wat: &str) -> (std::path::PathBuf, String) {
+        let root =
+            std::env::temp_dir().join(format!("wasm-engine-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join(format!("{name}.wasm"));
+        std::fs::write(&path, wasmedge_sdk::wat2wasm(wat.as_bytes()).unwrap()).unwrap();
+        (root, path.to_string_lossy().to_string())
+    }
+
+    #[cfg(feature = "wasm-runtime")]
+    #[test]
+    fn test_guest_reads_but_cannot_write_secrets() {
+        use zeroize::Zeroizing;
+
+        let secrets = || -> ReadOnlySecrets {
+            [("db_token".to_string(), Zeroizing::new(b"s3cr3t".to_vec()))]
+                .into_iter()
+                .collect()
+        };
+        let call = |module_path: &str| WasmCall {
+            module_path: module_path.to_string(),
+            function_name: "run".to_string(),
+            inputs: vec![],
+        };
+        let engine = WasmEngine::new(WasmRuntime::WasmEdge, SandboxLimits::default());
+        let rt = tokio::runtime::Runtime::new().unwrap();
+
+        // Reads the secret, overwrites its copy, reads again and returns the
+        // first byte.
+        let (root, reader) = stage_wat(
+            "secret-reader",
+            r#"(module
+                (import "ambient" "secret_read" (func $read (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "db_token")
+                (func (export "run") (result i32)
+                    (drop (call $read (i32.const 0) (i32.const 8) (i32.const 16) (i32.const 32)))
+                    (i32.store8 (i32.const 16) (i32.const 120))
+                    (drop (call $read (i32.const 0) (i32.const 8) (i32.const 16) (i32.const 32)))
+                    (i32.load8_u (i32.const 16))))"#,
+        );
+        let result = with_allowed_roots(root.to_str().unwrap(), || {
+            rt.block_on(engine.execute_with_secrets(call(&reader), secrets()))
+        })
+        .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.output, i32::from(b's').to_le_bytes().to_vec());
+
+        // The host offers no way to write a secret back.
+        let (root, writer) = stage_wat(
+            "secret-writer",
+            r#"(module
+                (import "ambient" "secret_write" (func $write (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "run") (result i32)
+                    (call $write (i32.const 0) (i32.const 8) (i32.const 16) (i32.const 6))))"#,
+        );
+        let outcome = with_allowed_roots(root.to_str().unwrap(), || {
+            rt.block_on(engine.execute_with_secrets(call(&writer), secrets()))
+        });
+        assert!(!outcome.is_ok_and(|result| result.success));
+    }
+
+    #[cfg(feature = "wasm-runtime")]
+    #[test]
+    fn test_interrupted_module_resumes_from_its_checkpoint() {
+        // Counts to five, checkpointing the counter after every step.  A
+        // fresh run traps at three, as if its node had died.
+        let (root, counter) = stage_wat(
+            "checkpoint-counter",
+            r#"(module
+                (import "ambient" "checkpoint_read" (func $read (param i32 i32) (result i32)))
+                (import "ambient" "checkpoint_write" (func $write (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "run") (result i32)
+                    (local $resumed i32)
+                    (local.set $resumed (call $read (i32.const 0) (i32.const 4)))
+                    (loop $step
+                        (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+                        (drop (call $write (i32.const 0) (i32.const 4)))
+                        (if (i32.and
+                                (i32.eqz (local.get $resumed))
+                                (i32.eq (i32.load (i32.const 0)) (i32.const 3)))
+                            (then unreachable))
+                        (br_if $step (i32.lt_u (i32.load (i32.const 0)) (i32.const 5))))
+                    (i32.load (i32.const 0))))"#,
+        );
+        let call = WasmCall {
+            module_path: counter,
+            function_name: "run".to_string(),
+            inputs: vec![],
+        };
+        let engine = WasmEngine::new(WasmRuntime::WasmEdge, SandboxLimits::default());
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let run = |store| {
+            with_allowed_roots(root.to_str().unwrap(), || {
+                rt.block_on(engine.execute_with_checkpoints(call.clone(), store))
+            })
+            .unwrap()
+        };
+
+        let (interrupted, store) = run(CheckpointStore::default());
+        assert!(!interrupted.success);
+        let saved = store.into_latest().unwrap();
+        assert_eq!(
+            (saved.seq, saved.data.clone()),
+            (3, 3u32.to_le_bytes().to_vec())
+        );
+
+        let (resumed, store) = run(CheckpointStore::default().resume_from(saved));
+        assert!(resumed.success, "{:?}", resumed.error);
+        assert_eq!(resumed.output, 5i32.to_le_bytes().to_vec());
+        assert_eq!(store.latest().unwrap().seq, 5);
+    }
+
     #[test]
     fn test_module_path_rejected_outside_roots() {
         with_allowed_roots("./wasm-modules", || {
@@ -6,6 +6,10 @@ pub struct SandboxCapabilities {
     pub filesystem_access: bool,
     pub network_access: bool,
     pub crypto_allowed: bool,
+    /// Destinations reachable when `network_access` is enabled.  An empty
+    /// list means no destination is reachable.
+    #[serde(default)]
+    pub egress: Vec<EgressRule>,
 }
 
 impl Default for SandboxCapabilities {
@@ -14,6 +18,7 @@ impl Default for SandboxCapabilities {
             filesystem_access: false,
             network_access: false,
             crypto_allowed: true,
+            egress: Vec::new(),
         }
     }
 }
@@ -24,6 +29,7 @@ impl SandboxCapabilities {
             filesystem_access: filesystem,
             network_access: network,
             crypto_allowed: crypto,
+            egress: Vec::new(),
         }
     }
 
@@ -32,6 +38,170 @@ impl SandboxCapabilities {
             filesystem_access: false,
             network_access: false,
             crypto_allowed: false,
+            egress: Vec::new(),
         }
     }
+
+    /// Enable networking restricted to the declared egress rules.
+    pub fn with_egress(mut self, egress: Vec<EgressRule>) -> Self {
+        self.network_access = !egress.is_empty();
+        self.egress = egress;
+        self
+    }
+
+    /// Whether the sandbox may open a connection to `host:port`.
+    ///
+    /// Socket host functions must call this before connecting.
+    pub fn allows_connect(&self, host: &str, port: u16) -> bool {
+        self.network_access && self.egress.iter().any(|rule| rule.matches(host, port))
+    }
+}
+
+/// One allowed network destination.
+///
+/// `host` is an exact hostname or IP, or `*.example.com` to match any
+/// subdomain (but not `example.com` itself).  An empty `ports` list matches
+/// any port.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EgressRule {
+    pub host: String,
+    #[serde(default)]
+    pub ports: Vec<u16>,
+}
+
+impl EgressRule {
+    pub fn new(host: impl Into<String>, ports: Vec<u16>) -> Self {
+        Self {
+            host: host.into().to_ascii_lowercase(),
+            ports,
+        }
+    }
+
+    /// Parse `host` or `host:port[|port...]`, as used in operator allowlists.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (host, ports) = match value.rsplit_once(':') {
+            Some((host, ports)) => {
+                let ports = ports
+                    .split('|')
+                    .map(|port| port.trim().parse::<u16>().ok().filter(|p| *p > 0))
+                    .collect::<Option<Vec<_>>>()?;
+                (host, ports)
+            }
+            None => (value, Vec::new()),
+        };
+
+        if host.is_empty() || host == "*" || host.contains('/') {
+            return None;
+        }
+        Some(Self::new(host, ports))
+    }
+
+    fn wildcard_suffix(&self) -> Option<&str> {
+        self.host.strip_prefix("*.")
+    }
+
+    fn host_matches(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        match self.wildcard_suffix() {
+            Some(suffix) => host
+                .strip_suffix(suffix)
+                .is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.')),
+            None => host == self.host,
+        }
+    }
+
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        self.host_matches(host) && (self.ports.is_empty() || self.ports.contains(&port))
+    }
+
+    /// Whether every destination `other` allows is also allowed by `self`.
+    pub fn covers(&self, other: &EgressRule) -> bool {
+        let host_covered = match other.wildcard_suffix() {
+            // A wildcard request is only covered by an equal or broader wildcard.
+            Some(_) => self.wildcard_suffix().is_some_and(|suffix| {
+                other.host == self.host || other.host.ends_with(&format!(".{suffix}"))
+            }),
+            None => self.host_matches(&other.host),
+        };
+        let ports_covered = self.ports.is_empty()
+            || (!other.ports.is_empty() && other.ports.iter().all(|p| self.ports.contains(p)));
+        host_covered && ports_covered
+    }
+}
+
+/// The first rule in `requested` not covered by any rule in `allowed`.
+pub fn uncovered_egress<'a>(
+    requested: &'a [EgressRule],
+    allowed: &[EgressRule],
+) -> Option<&'a EgressRule> {
+    requested
+        .iter()
+        .find(|rule| !allowed.iter().any(|allowed| allowed.covers(rule)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_egress_rule_matching() {
+        let exact = EgressRule::parse("api.example.com:443").unwrap();
+        assert!(exact.matches("API.example.com", 443));
+        assert!(!exact.matches("api.example.com", 80));
+        assert!(!exact.matches("evil-api.example.com", 443));
+
+        let wildcard = EgressRule::parse("*.data.org").unwrap();
+        assert!(wildcard.matches("eu.data.org", 8080));
+        assert!(!wildcard.matches("data.org", 8080));
+        assert!(!wildcard.matches("baddata.org", 8080));
+    }
+
+    #[test]
+    fn test_operator_policy_coverage() {
+        let allowed = vec![
+            EgressRule::parse("*.data.org:443|8443").unwrap(),
+            EgressRule::parse("10.0.0.5").unwrap(),
+        ];
+
+        let ok = vec![
+            EgressRule::new("eu.data.org", vec![443]),
+            EgressRule::new("*.eu.data.org", vec![8443]),
+            EgressRule::new("10.0.0.5", vec![5432]),
+        ];
+        assert!(uncovered_egress(&ok, &allowed).is_none());
+
+        let wrong_port = vec![EgressRule::new("eu.data.org", vec![80])];
+        assert!(uncovered_egress(&wrong_port, &allowed).is_some());
+
+        let any_port = vec![EgressRule::new("eu.data.org", vec![])];
+        assert!(uncovered_egress(&any_port, &allowed).is_some());
+
+        let other_host = vec![EgressRule::new("example.com", vec![443])];
+        assert!(uncovered_egress(&other_host, &allowed).is_some());
+    }
+
+    #[test]
+    fn test_capabilities_enforce_egress() {
+        assert!(!SandboxCapabilities::default().allows_connect("api.example.com", 443));
+
+        let caps = SandboxCapabilities::no_access()
+            .with_egress(vec![EgressRule::new("api.example.com", vec![443])]);
+        assert!(caps.network_access);
+        assert!(caps.allows_connect("api.example.com", 443));
+        assert!(!caps.allows_connect("api.example.com", 22));
+        assert!(!caps.allows_connect("other.example.com", 443));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_rules() {
+        assert!(EgressRule::parse("").is_none());
+        assert!(EgressRule::parse("*").is_none());
+        assert!(EgressRule::parse("host:notaport").is_none());
+        assert!(EgressRule::parse("host:0").is_none());
+        assert_eq!(
+            EgressRule::parse("host"),
+            Some(EgressRule::new("host", vec![]))
+        );
+    }
 }
@@ -0,0 +1,187 @@
+use crate::checkpoint::guest_range;
+use std::collections::BTreeMap;
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+/// `secret_read(name_ptr: i32, name_len: i32, ptr: i32, cap: i32) -> i32`,
+/// imported from [`crate::CHECKPOINT_IMPORT_MODULE`]: copy the secret named
+/// by the UTF-8 string at `name_ptr` into guest memory.  Returns the byte
+/// length, or a negative [`SecretReadError::code`].  There is no write
+/// counterpart, so a guest only ever holds a copy.
+pub const SECRET_READ_FN: &str = "secret_read";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SecretReadError {
+    #[error("no secret is mounted under that name")]
+    NotFound,
+    #[error("secret name or buffer lies outside guest memory")]
+    OutOfBounds,
+    #[error("guest buffer of {capacity} bytes cannot hold a {size} byte secret")]
+    BufferTooSmall { size: usize, capacity: usize },
+}
+
+impl SecretReadError {
+    /// Negative status returned to the guest by [`SECRET_READ_FN`].
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::NotFound => -1,
+            Self::OutOfBounds => -2,
+            Self::BufferTooSmall { .. } => -3,
+        }
+    }
+}
+
+/// Task secrets mounted into the sandbox for a single execution.
+///
+/// Values are exposed read-only by name through [`SECRET_READ_FN`] (the
+/// equivalent of a preopened, read-only directory of one file per secret)
+/// and are never written to disk.  Every value is zeroed on
+/// [`ReadOnlySecrets::wipe`] or drop.
+#[derive(Default)]
+pub struct ReadOnlySecrets {
+    values: BTreeMap<String, Zeroizing<Vec<u8>>>,
+}
+
+impl ReadOnlySecrets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mount a secret; a later mount with the same name replaces (and zeroes)
+    /// the earlier value.
+    pub fn mount(&mut self, name: impl Into<String>, value: Zeroizing<Vec<u8>>) {
+        self.values.insert(name.into(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.values.get(name).map(|value| value.as_slice())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Body of the `secret_read` host function.
+    pub fn host_read(
+        &self,
+        memory: &mut [u8],
+        name_ptr: u32,
+        name_len: u32,
+        ptr: u32,
+        capacity: u32,
+    ) -> i32 {
+        match self.copy_to_guest(memory, name_ptr, name_len, ptr, capacity) {
+            Ok(size) => size.min(i32::MAX as usize) as i32,
+            Err(err) => err.code(),
+        }
+    }
+
+    fn copy_to_guest(
+        &self,
+        memory: &mut [u8],
+        name_ptr: u32,
+        name_len: u32,
+        ptr: u32,
+        capacity: u32,
+    ) -> Result<usize, SecretReadError> {
+        let name =
+            guest_range(memory.len(), name_ptr, name_len).ok_or(SecretReadError::OutOfBounds)?;
+        let value = std::str::from_utf8(&memory[name])
+            .ok()
+            .and_then(|name| self.get(name))
+            .ok_or(SecretReadError::NotFound)?;
+        if value.len() > capacity as usize {
+            return Err(SecretReadError::BufferTooSmall {
+                size: value.len(),
+                capacity: capacity as usize,
+            });
+        }
+        let range = guest_range(memory.len(), ptr, value.len() as u32)
+            .ok_or(SecretReadError::OutOfBounds)?;
+        memory[range].copy_from_slice(value);
+        Ok(value.len())
+    }
+
+    /// Zero and unmount every value.
+    pub fn wipe(&mut self) {
+        for value in self.values.values_mut() {
+            value.zeroize();
+        }
+        self.values.clear();
+    }
+}
+
+impl FromIterator<(String, Zeroizing<Vec<u8>>)> for ReadOnlySecrets {
+    fn from_iter<I: IntoIterator<Item = (String, Zeroizing<Vec<u8>>)>>(iter: I) -> Self {
+        Self {
+            values: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl std::fmt::Debug for ReadOnlySecrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadOnlySecrets")
+            .field("names", &self.values.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ReadOnlySecrets {
+    fn drop(&mut self) {
+        self.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_read_and_wipe() {
+        let mut secrets: ReadOnlySecrets =
+            [("db_token".to_string(), Zeroizing::new(b"s3cr3t".to_vec()))]
+                .into_iter()
+                .collect();
+
+        assert_eq!(secrets.get("db_token"), Some(&b"s3cr3t"[..]));
+        assert_eq!(secrets.names().collect::<Vec<_>>(), vec!["db_token"]);
+        assert!(!format!("{secrets:?}").contains("s3cr3t"));
+
+        secrets.wipe();
+        assert!(secrets.is_empty());
+        assert_eq!(secrets.get("db_token"), None);
+    }
+
+    #[test]
+    fn test_guest_reads_a_copy() {
+        let secrets: ReadOnlySecrets =
+            [("db_token".to_string(), Zeroizing::new(b"s3cr3t".to_vec()))]
+                .into_iter()
+                .collect();
+        let mut memory = vec![0u8; 64];
+        memory[..8].copy_from_slice(b"db_token");
+
+        assert_eq!(secrets.host_read(&mut memory, 0, 8, 16, 32), 6);
+        assert_eq!(&memory[16..22], b"s3cr3t");
+
+        // Scribbling over the copy leaves the mounted value untouched.
+        memory[16..22].fill(b'x');
+        assert_eq!(secrets.get("db_token"), Some(&b"s3cr3t"[..]));
+        assert_eq!(secrets.host_read(&mut memory, 0, 8, 16, 32), 6);
+        assert_eq!(&memory[16..22], b"s3cr3t");
+
+        assert_eq!(secrets.host_read(&mut memory, 0, 2, 16, 32), -1);
+        assert_eq!(secrets.host_read(&mut memory, 60, 8, 16, 32), -2);
+        assert_eq!(secrets.host_read(&mut memory, 0, 8, 62, 32), -2);
+        assert_eq!(secrets.host_read(&mut memory, 0, 8, 16, 4), -3);
+    }
+}
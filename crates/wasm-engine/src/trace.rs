@@ -1,3 +1,4 @@
+use crate::sandbox::EgressRule;
 use serde::{Deserialize, Serialize};
 
 /// Execution trace for ZK proof generation
@@ -10,6 +11,16 @@ pub struct ExecutionTrace {
     pub execution_time_ms: u64,
     pub gas_used: u64,
     pub timestamp: u64,
+    /// Set when `inputs`/`outputs` were cut down to fit a storage size cap.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Hash of the trace before truncation, so proofs stay verifiable.
+    #[serde(default)]
+    pub full_hash: Option<String>,
+    /// Network destinations the sandbox could reach during this execution.
+    /// Empty means the execution had no network access.
+    #[serde(default)]
+    pub egress: Vec<EgressRule>,
 }
 
 impl ExecutionTrace {
@@ -33,17 +44,53 @@ impl ExecutionTrace {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            truncated: false,
+            full_hash: None,
+            egress: Vec::new(),
         }
     }
 
     /// Get trace hash for verification
+    ///
+    /// Truncated traces report the hash of the original, untruncated data.
     pub fn hash(&self) -> String {
+        if let Some(ref full_hash) = self.full_hash {
+            return full_hash.clone();
+        }
+
         use sha3::{Digest, Sha3_256};
         let mut hasher = Sha3_256::new();
         hasher.update(&self.module_hash);
         hasher.update(&self.function_name);
         hasher.update(&self.inputs);
         hasher.update(&self.outputs);
+        // Bind declared egress into the hash so it cannot be altered after
+        // the fact; traces without egress keep their original hash.
+        for rule in &self.egress {
+            hasher.update(rule.host.as_bytes());
+            for port in &rule.ports {
+                hasher.update(port.to_be_bytes());
+            }
+        }
         format!("{:x}", hasher.finalize())
     }
+
+    /// Approximate storage footprint in bytes.
+    pub fn size_bytes(&self) -> usize {
+        self.module_hash.len() + self.function_name.len() + self.inputs.len() + self.outputs.len()
+    }
+
+    /// Cap `inputs` and `outputs` at `max_payload_bytes` each, marking the
+    /// trace as truncated.  Returns `true` if anything was cut.
+    pub fn truncate_payloads(&mut self, max_payload_bytes: usize) -> bool {
+        if self.inputs.len() <= max_payload_bytes && self.outputs.len() <= max_payload_bytes {
+            return false;
+        }
+
+        self.full_hash = Some(self.hash());
+        self.inputs.truncate(max_payload_bytes);
+        self.outputs.truncate(max_payload_bytes);
+        self.truncated = true;
+        true
+    }
 }
@@ -0,0 +1,279 @@
+use crate::trace::ExecutionTrace;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Controls which execution traces a node keeps and for how long.
+///
+/// Traces are needed for proof generation and debugging, but storing one per
+/// execution grows without bound.  Successful executions are sampled per task
+/// type; failed executions are always kept when `keep_failures` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TracePolicy {
+    /// Fraction (0.0 – 1.0) of successful executions to keep for task types
+    /// without an entry in `sample_rates`.
+    pub default_sample_rate: f64,
+    /// Per-task-type overrides of `default_sample_rate`.
+    pub sample_rates: HashMap<String, f64>,
+    /// Traces older than this many seconds are pruned.
+    pub retention_secs: u64,
+    /// Inputs and outputs longer than this are truncated before storage.
+    pub max_payload_bytes: usize,
+    /// Total bytes the store may hold; oldest successful traces are evicted first.
+    pub max_total_bytes: usize,
+    /// Always keep traces of failed executions, regardless of sample rate.
+    pub keep_failures: bool,
+}
+
+impl Default for TracePolicy {
+    fn default() -> Self {
+        Self {
+            default_sample_rate: 0.1,
+            sample_rates: HashMap::new(),
+            retention_secs: 7 * 24 * 3600,
+            max_payload_bytes: 64 * 1024,
+            max_total_bytes: 256 * 1024 * 1024,
+            keep_failures: true,
+        }
+    }
+}
+
+impl TracePolicy {
+    /// Keep every trace in full; useful for development and tests.
+    pub fn keep_all() -> Self {
+        Self {
+            default_sample_rate: 1.0,
+            max_payload_bytes: usize::MAX,
+            max_total_bytes: usize::MAX,
+            ..Self::default()
+        }
+    }
+
+    pub fn sample_rate_for(&self, task_type: &str) -> f64 {
+        self.sample_rates
+            .get(task_type)
+            .copied()
+            .unwrap_or(self.default_sample_rate)
+            .clamp(0.0, 1.0)
+    }
+
+    /// Deterministic sampling keyed on the trace hash, so the same execution
+    /// replayed on another node gets the same decision.
+    pub fn should_sample(&self, task_type: &str, trace: &ExecutionTrace) -> bool {
+        let rate = self.sample_rate_for(task_type);
+        if rate >= 1.0 {
+            return true;
+        }
+        if rate <= 0.0 {
+            return false;
+        }
+
+        let hash = trace.hash();
+        let bucket = u64::from_str_radix(&hash[..16.min(hash.len())], 16).unwrap_or(0);
+        (bucket as f64 / u64::MAX as f64) < rate
+    }
+}
+
+/// Outcome of offering a trace to a `TraceStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceDecision {
+    /// Not selected by sampling.
+    Dropped,
+    Stored,
+    /// Stored with inputs/outputs cut to `max_payload_bytes`.
+    StoredTruncated,
+}
+
+#[derive(Debug, Clone)]
+struct StoredTrace {
+    trace: ExecutionTrace,
+    failed: bool,
+    size: usize,
+}
+
+/// In-memory trace store enforcing a `TracePolicy`.
+#[derive(Debug, Clone, Default)]
+pub struct TraceStore {
+    policy: TracePolicy,
+    traces: VecDeque<StoredTrace>,
+    total_bytes: usize,
+}
+
+impl TraceStore {
+    pub fn new(policy: TracePolicy) -> Self {
+        Self {
+            policy,
+            traces: VecDeque::new(),
+            total_bytes: 0,
+        }
+    }
+
+    pub fn policy(&self) -> &TracePolicy {
+        &self.policy
+    }
+
+    /// Offer a trace for storage, applying sampling, truncation, and size caps.
+    ///
+    /// `now` is Unix seconds and drives retention pruning.
+    pub fn record(
+        &mut self,
+        task_type: &str,
+        mut trace: ExecutionTrace,
+        success: bool,
+        now: u64,
+    ) -> TraceDecision {
+        self.prune(now);
+
+        let keep =
+            (!success && self.policy.keep_failures) || self.policy.should_sample(task_type, &trace);
+        if !keep {
+            return TraceDecision::Dropped;
+        }
+
+        let truncated = trace.truncate_payloads(self.policy.max_payload_bytes);
+        let size = trace.size_bytes();
+        self.total_bytes += size;
+        self.traces.push_back(StoredTrace {
+            trace,
+            failed: !success,
+            size,
+        });
+        self.evict_to_fit();
+
+        if truncated {
+            TraceDecision::StoredTruncated
+        } else {
+            TraceDecision::Stored
+        }
+    }
+
+    /// Drop traces older than the retention window.
+    pub fn prune(&mut self, now: u64) -> usize {
+        let cutoff = now.saturating_sub(self.policy.retention_secs);
+        let before = self.traces.len();
+        self.traces
+            .retain(|stored| stored.trace.timestamp >= cutoff);
+        self.total_bytes = self.traces.iter().map(|stored| stored.size).sum();
+        before - self.traces.len()
+    }
+
+    /// Look up a stored trace by its (pre-truncation) hash.
+    pub fn get(&self, trace_hash: &str) -> Option<&ExecutionTrace> {
+        self.traces
+            .iter()
+            .map(|stored| &stored.trace)
+            .find(|trace| trace.hash() == trace_hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.traces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    fn evict_to_fit(&mut self) {
+        while self.total_bytes > self.policy.max_total_bytes {
+            let victim = self
+                .traces
+                .iter()
+                .position(|stored| !stored.failed)
+                .unwrap_or(0);
+            match self.traces.remove(victim) {
+                Some(stored) => self.total_bytes -= stored.size,
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(function_name: &str, payload: usize, timestamp: u64) -> ExecutionTrace {
+        ExecutionTrace {
+            module_hash: "module".to_string(),
+            function_name: function_name.to_string(),
+            inputs: vec![1; payload],
+            outputs: vec![2; payload],
+            execution_time_ms: 1,
+            gas_used: 1,
+            timestamp,
+            truncated: false,
+            full_hash: None,
+            egress: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_failures_kept_when_sampling_disabled() {
+        let mut store = TraceStore::new(TracePolicy {
+            default_sample_rate: 0.0,
+            ..TracePolicy::default()
+        });
+
+        assert_eq!(
+            store.record("computation", trace("ok", 4, 100), true, 100),
+            TraceDecision::Dropped
+        );
+        assert_eq!(
+            store.record("computation", trace("err", 4, 100), false, 100),
+            TraceDecision::Stored
+        );
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_truncation_preserves_original_hash() {
+        let mut store = TraceStore::new(TracePolicy {
+            max_payload_bytes: 8,
+            ..TracePolicy::keep_all()
+        });
+        let original = trace("big", 32, 100);
+        let original_hash = original.hash();
+
+        assert_eq!(
+            store.record("computation", original, true, 100),
+            TraceDecision::StoredTruncated
+        );
+        let stored = store.get(&original_hash).unwrap();
+        assert!(stored.truncated);
+        assert_eq!(stored.inputs.len(), 8);
+    }
+
+    #[test]
+    fn test_retention_and_size_cap() {
+        let mut store = TraceStore::new(TracePolicy {
+            retention_secs: 60,
+            max_total_bytes: 60,
+            ..TracePolicy::keep_all()
+        });
+
+        store.record("computation", trace("failed", 10, 100), false, 100);
+        store.record("computation", trace("a", 10, 100), true, 100);
+        store.record("computation", trace("b", 10, 100), true, 100);
+        // Oldest successful trace is evicted before the failure.
+        assert_eq!(store.len(), 2);
+        assert!(store.traces.iter().any(|stored| stored.failed));
+        assert!(store.total_bytes() <= 60);
+
+        assert_eq!(store.prune(1_000), 2);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_per_task_type_sample_rate() {
+        let policy = TracePolicy {
+            default_sample_rate: 0.0,
+            sample_rates: HashMap::from([("zk_proof".to_string(), 1.0)]),
+            ..TracePolicy::default()
+        };
+        assert!(policy.should_sample("zk_proof", &trace("f", 1, 0)));
+        assert!(!policy.should_sample("computation", &trace("f", 1, 0)));
+    }
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Bytes in one WebAssembly linear-memory page.
+pub const WASM_PAGE_BYTES: u64 = 64 * 1024;
+
+/// Resources one sandboxed execution consumed.
+///
+/// Nodes report these with the task result so the control plane can bill
+/// attempts and estimate what a task type needs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// Largest linear memory the module reached, in bytes.  Linear memory
+    /// only grows, so this is its size when execution ended.
+    pub peak_memory_bytes: u64,
+    /// CPU time spent running the module; `None` where the platform has no
+    /// per-thread CPU clock.
+    pub cpu_time_ms: Option<u64>,
+    /// Energy attributed to the execution; `None` unless the node meters
+    /// power (see `ambient_node::EnergyMeter`).
+    pub energy_wh: Option<f64>,
+}
+
+impl ResourceUsage {
+    /// Usage of a module whose memory ended at `pages` pages.
+    pub fn from_pages(pages: u32, cpu_time: Option<Duration>) -> Self {
+        Self {
+            peak_memory_bytes: u64::from(pages) * WASM_PAGE_BYTES,
+            cpu_time_ms: cpu_time.map(|elapsed| elapsed.as_millis() as u64),
+            energy_wh: None,
+        }
+    }
+
+    /// Attach energy metered for the execution.
+    pub fn with_energy_wh(mut self, energy_wh: f64) -> Self {
+        self.energy_wh = Some(energy_wh);
+        self
+    }
+}
+
+/// Measures CPU time of the calling thread between [`CpuTimer::start`] and
+/// [`CpuTimer::elapsed`].  Modules run synchronously on the executing
+/// thread, so this is the module's CPU time.
+#[derive(Debug, Clone, Copy)]
+pub struct CpuTimer(Option<Duration>);
+
+impl CpuTimer {
+    pub fn start() -> Self {
+        Self(thread_cpu_time())
+    }
+
+    pub fn elapsed(&self) -> Option<Duration> {
+        let start = self.0?;
+        thread_cpu_time().map(|now| now.saturating_sub(start))
+    }
+}
+
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid, writable timespec for the duration of the call.
+    let rc = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    (rc == 0).then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_convert_to_bytes() {
+        let usage = ResourceUsage::from_pages(17, Some(Duration::from_micros(2_500)));
+        assert_eq!(usage.peak_memory_bytes, 17 * 65_536);
+        assert_eq!(usage.cpu_time_ms, Some(2));
+        assert_eq!(usage.with_energy_wh(0.25).energy_wh, Some(0.25));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cpu_timer_counts_busy_work() {
+        let timer = CpuTimer::start();
+        let mut acc = 0u64;
+        let started = std::time::Instant::now();
+        while started.elapsed() < Duration::from_millis(20) {
+            acc = std::hint::black_box(acc.wrapping_add(1));
+        }
+        assert!(timer.elapsed().unwrap() >= Duration::from_millis(10));
+    }
+}